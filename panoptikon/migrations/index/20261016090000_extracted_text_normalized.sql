@@ -0,0 +1,45 @@
+-- Search-text normalization ([text_normalization] in the system config).
+-- extracted_text.text keeps exactly what the model produced; when the
+-- configured normalization changes it, the normalized form is stored next
+-- to it and the FTS index covers that instead. NULL means "same as text",
+-- so every existing row is already consistent.
+ALTER TABLE extracted_text ADD COLUMN normalized_text TEXT;
+
+-- An external-content FTS5 table reads its content (for snippet() and
+-- 'rebuild') from a table or view with a column named like its own, so the
+-- index points at a view exposing whichever form was indexed. Pointing it at
+-- extracted_text would make snippet() highlight raw text using offsets from
+-- the normalized text.
+CREATE VIEW extracted_text_fts_content AS
+    SELECT id, coalesce(normalized_text, text) AS text FROM extracted_text;
+
+-- The content option can't be altered in place. The rebuild re-indexes the
+-- same text as before (no row has normalized_text yet), once.
+DROP TRIGGER IF EXISTS extracted_text_ad;
+DROP TRIGGER IF EXISTS extracted_text_ai;
+DROP TRIGGER IF EXISTS extracted_text_au;
+DROP TABLE extracted_text_fts;
+
+CREATE VIRTUAL TABLE extracted_text_fts
+    USING fts5(
+        text,
+        content="extracted_text_fts_content",
+        content_rowid="id",
+        tokenize="trigram case_sensitive 0"
+    );
+INSERT INTO extracted_text_fts(extracted_text_fts) VALUES('rebuild');
+
+CREATE TRIGGER extracted_text_ad AFTER DELETE ON extracted_text BEGIN
+    INSERT INTO extracted_text_fts(extracted_text_fts, rowid, text)
+    VALUES('delete', old.id, coalesce(old.normalized_text, old.text));
+END;
+CREATE TRIGGER extracted_text_ai AFTER INSERT ON extracted_text BEGIN
+    INSERT INTO extracted_text_fts(rowid, text)
+    VALUES (new.id, coalesce(new.normalized_text, new.text));
+END;
+CREATE TRIGGER extracted_text_au AFTER UPDATE ON extracted_text BEGIN
+    INSERT INTO extracted_text_fts(extracted_text_fts, rowid, text)
+    VALUES('delete', old.id, coalesce(old.normalized_text, old.text));
+    INSERT INTO extracted_text_fts(rowid, text)
+    VALUES (new.id, coalesce(new.normalized_text, new.text));
+END;
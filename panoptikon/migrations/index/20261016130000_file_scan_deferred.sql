@@ -0,0 +1,3 @@
+-- Files found still being written (size or mtime changing across the settle
+-- window) are deferred rather than hashed; counted apart from errors.
+ALTER TABLE file_scans ADD COLUMN deferred INTEGER NOT NULL DEFAULT 0;
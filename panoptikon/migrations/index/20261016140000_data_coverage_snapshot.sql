@@ -0,0 +1,7 @@
+-- Last computed data coverage report (GET /api/jobs/data/coverage), refreshed
+-- after every extraction job so `cached=true` needs no inference round trip.
+CREATE TABLE data_coverage_snapshot (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    computed_at TEXT NOT NULL,
+    report TEXT NOT NULL CHECK (json_valid(report))
+);
@@ -0,0 +1,3 @@
+-- Directories pruned from a scan because their name matched
+-- `ignored_dir_patterns`; their contents are never walked.
+ALTER TABLE file_scans ADD COLUMN ignored_dirs INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,33 @@
+-- Bit-rot verification runs (POST /api/jobs/maintenance/verify): one summary
+-- row per run, and one result row per file whose content no longer hashes to
+-- its stored sha256 or could not be read. Files are never modified by a run.
+CREATE TABLE file_verification_runs (
+    id INTEGER PRIMARY KEY,
+    start_time TEXT NOT NULL,
+    end_time TEXT,                    -- NULL while running, or if interrupted
+    path_prefix TEXT,
+    modified_since TEXT,
+    max_files INTEGER,
+    max_mb_per_sec REAL NOT NULL,
+    checked INTEGER NOT NULL DEFAULT 0,
+    verified INTEGER NOT NULL DEFAULT 0,
+    mismatched INTEGER NOT NULL DEFAULT 0,
+    unreadable INTEGER NOT NULL DEFAULT 0,
+    missing INTEGER NOT NULL DEFAULT 0,
+    changed INTEGER NOT NULL DEFAULT 0,
+    bytes_read INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE TABLE file_verification_results (
+    id INTEGER PRIMARY KEY,
+    run_id INTEGER NOT NULL REFERENCES file_verification_runs(id) ON DELETE CASCADE,
+    file_id INTEGER NOT NULL,         -- No FK: results outlive rescans
+    path TEXT NOT NULL,
+    status TEXT NOT NULL CHECK (status IN ('mismatch', 'unreadable')),
+    expected_sha256 TEXT NOT NULL,
+    actual_sha256 TEXT,
+    error TEXT,
+    checked_at TEXT NOT NULL
+);
+CREATE INDEX file_verification_results_run_id
+    ON file_verification_results(run_id);
@@ -0,0 +1,3 @@
+-- Number of concurrent file workers the scan ran with, after per-folder
+-- overrides and the io_profile default were applied; 0 for older scans.
+ALTER TABLE file_scans ADD COLUMN worker_count INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,8 @@
+-- Tag aliases: query-time synonyms across tagger vocabularies. Each alias
+-- belongs to exactly one canonical tag; match_tags expands a requested tag
+-- into its canonical tag plus every alias of it.
+CREATE TABLE tag_aliases (
+    alias TEXT PRIMARY KEY,
+    canonical TEXT NOT NULL
+);
+CREATE INDEX idx_tag_aliases_canonical ON tag_aliases(canonical);
@@ -0,0 +1,16 @@
+-- Database optimization runs (POST /api/jobs/maintenance/optimize and the
+-- `db_maintenance` schedule): one row per run, with the combined size of
+-- index.db and storage.db (WAL files included) before and after.
+CREATE TABLE db_maintenance_runs (
+    id INTEGER PRIMARY KEY,
+    start_time TEXT NOT NULL,
+    end_time TEXT NOT NULL,
+    trigger TEXT NOT NULL CHECK (trigger IN ('manual', 'scheduled')),
+    vacuum_mode TEXT NOT NULL CHECK (vacuum_mode IN ('none', 'full', 'incremental')),
+    vacuumed INTEGER NOT NULL DEFAULT 0,
+    skipped_reason TEXT,              -- Why a requested VACUUM did not run
+    error TEXT,                       -- NULL unless the run failed
+    size_before INTEGER NOT NULL,
+    size_after INTEGER NOT NULL,
+    duration_secs REAL NOT NULL
+);
@@ -0,0 +1,4 @@
+-- Files/items whose ffmpeg or ffprobe run was killed at its timeout. Also
+-- counted in `errors`; 0 for older scans and extraction jobs.
+ALTER TABLE file_scans ADD COLUMN timeouts INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE data_log ADD COLUMN timeouts INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,3 @@
+-- Files and directories skipped by `.panoptikonignore` rules during a scan;
+-- an ignored directory counts once. 0 for older scans and continuous scans.
+ALTER TABLE file_scans ADD COLUMN ignored_files INTEGER NOT NULL DEFAULT 0;
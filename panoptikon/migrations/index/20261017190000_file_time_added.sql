@@ -0,0 +1,9 @@
+-- When each file path was first indexed. items.time_added is when the
+-- content (sha256) was first seen; a second copy or a new version of a file
+-- gets its own time here. Modification rescans keep it. Existing rows can
+-- only be backfilled with their item's time_added, which is when the first
+-- file with that content was found.
+ALTER TABLE files ADD COLUMN time_added TEXT;
+UPDATE files
+SET time_added = (SELECT items.time_added FROM items WHERE items.id = files.item_id);
+CREATE INDEX idx_files_time_added ON files(time_added);
@@ -0,0 +1,3 @@
+-- Extraction jobs run in reprocess mode, which replaced each item's earlier
+-- data for the setter instead of skipping processed items.
+ALTER TABLE data_log ADD COLUMN reprocess INTEGER NOT NULL DEFAULT 0;
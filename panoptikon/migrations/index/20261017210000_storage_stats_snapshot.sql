@@ -0,0 +1,8 @@
+-- Last computed storage usage report (GET /api/search/stats/storage),
+-- refreshed by every database maintenance run so `cheap=true` needs no scan
+-- over the blob columns.
+CREATE TABLE storage_stats_snapshot (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    computed_at TEXT NOT NULL,
+    report TEXT NOT NULL CHECK (json_valid(report))
+);
@@ -0,0 +1,4 @@
+-- Effective parameters of an extraction run (resolved batch size and
+-- threshold, input handler options, model metadata) as JSON, recorded when
+-- the job starts. NULL for earlier runs and tag imports.
+ALTER TABLE data_log ADD COLUMN parameters TEXT CHECK (parameters IS NULL OR json_valid(parameters));
@@ -0,0 +1,3 @@
+-- Items whose thumbnails, frames and blurhash a fast scan left to be
+-- generated later. 0 for older scans and scans with fast_scan off.
+ALTER TABLE file_scans ADD COLUMN visuals_deferred INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,15 @@
+-- Embedding dimension each setter writes, fixed by its first stored
+-- embedding, and how many writes were rejected for not matching it.
+ALTER TABLE setters ADD COLUMN embedding_dim INTEGER;
+ALTER TABLE setters ADD COLUMN rejected_embeddings INTEGER NOT NULL DEFAULT 0;
+
+-- Embeddings are little-endian f32 arrays: 4 bytes per dimension.
+UPDATE setters
+SET embedding_dim = (
+    SELECT length(embeddings.embedding) / 4
+    FROM item_data
+    JOIN embeddings ON embeddings.id = item_data.id
+    WHERE item_data.setter_id = setters.id
+    ORDER BY item_data.id
+    LIMIT 1
+);
@@ -0,0 +1,4 @@
+-- How many items an extraction run matched and how many its mime type and
+-- job filters kept out, as JSON, recorded when the job starts. NULL for
+-- earlier runs and tag imports.
+ALTER TABLE data_log ADD COLUMN filter_counts TEXT CHECK (filter_counts IS NULL OR json_valid(filter_counts));
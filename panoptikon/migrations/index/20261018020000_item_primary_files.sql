@@ -0,0 +1,14 @@
+-- The file pinned as an item's primary file: shown and served for the item
+-- and picked first when searches group files by item. At most one per item.
+CREATE TABLE item_primary_files (
+    item_id INTEGER PRIMARY KEY REFERENCES items(id) ON DELETE CASCADE,
+    file_id INTEGER NOT NULL UNIQUE REFERENCES files(id) ON DELETE CASCADE,
+    time_pinned TEXT NOT NULL
+);
+
+-- Every path that removes a file row (rescans, folder removal, purges,
+-- content changes, which delete and re-insert the row) clears its pin,
+-- whether or not foreign keys are enforced on the connection.
+CREATE TRIGGER item_primary_files_file_ad AFTER DELETE ON files BEGIN
+    DELETE FROM item_primary_files WHERE file_id = old.id;
+END;
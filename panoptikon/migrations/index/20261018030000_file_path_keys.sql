@@ -0,0 +1,28 @@
+-- Lookup key for files.path: the path itself under case-sensitive included
+-- folders, its lowercase form under case-insensitive ones, so a watcher
+-- event spelling a path with different casing finds the same row. path
+-- keeps the casing shown to users. Existing rows are backfilled with their
+-- path; scans and the continuous scanner re-key the folders they find to be
+-- case-insensitive when they start.
+ALTER TABLE files ADD COLUMN path_key TEXT;
+
+-- The path FTS index only covers path and filename; re-indexing on every
+-- other column update would rewrite it for each backfilled or re-keyed row
+-- (and, inside the insert trigger below, delete an entry not yet added).
+DROP TRIGGER files_path_au;
+CREATE TRIGGER files_path_au AFTER UPDATE OF path, filename ON files BEGIN
+    INSERT INTO files_path_fts(files_path_fts, rowid, path, filename)
+    VALUES('delete', old.id, old.path, old.filename);
+    INSERT INTO files_path_fts(rowid, path, filename)
+    VALUES (new.id, new.path, new.filename);
+END;
+
+UPDATE files SET path_key = path;
+CREATE INDEX idx_files_path_key ON files(path_key);
+
+-- Rows inserted without a key (anything but the scan writers) are keyed
+-- case-sensitively, like the backfill.
+CREATE TRIGGER files_path_key_ai AFTER INSERT ON files
+WHEN new.path_key IS NULL BEGIN
+    UPDATE files SET path_key = new.path WHERE id = new.id;
+END;
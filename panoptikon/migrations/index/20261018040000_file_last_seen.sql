@@ -0,0 +1,12 @@
+-- When each file was last confirmed on disk by a scan. Scans stamp it on
+-- every file they find; unlike scan_id it is a time, so it can be compared
+-- across folders and used to purge items gone for a long time. A row's
+-- scan_id is the last scan that found it, so existing rows are backfilled
+-- with that scan's start time, or their own time_added if it is gone.
+ALTER TABLE files ADD COLUMN last_seen TEXT;
+UPDATE files
+SET last_seen = COALESCE(
+    (SELECT file_scans.start_time FROM file_scans WHERE file_scans.id = files.scan_id),
+    time_added
+);
+CREATE INDEX idx_files_last_seen ON files(last_seen);
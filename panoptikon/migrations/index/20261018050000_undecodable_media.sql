@@ -0,0 +1,6 @@
+-- Videos none of whose frames could be decoded, even by the fallback
+-- extraction steps. In scans such files are still indexed (without
+-- visuals) and not counted in `errors`; in extraction jobs the item fails
+-- and is counted in both. 0 for older scans and extraction jobs.
+ALTER TABLE file_scans ADD COLUMN undecodable INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE data_log ADD COLUMN undecodable INTEGER NOT NULL DEFAULT 0;
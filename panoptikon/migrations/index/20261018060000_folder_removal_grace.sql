@@ -0,0 +1,7 @@
+-- When a folder update took the file out of the indexed folders (its folder
+-- was removed or newly excluded). Such files are only marked unavailable, so
+-- re-adding the folder restores them; the folder update deletes them once
+-- the index's folder_removal_grace_days have passed. NULL for every other
+-- file.
+ALTER TABLE files ADD COLUMN removed_at TEXT;
+CREATE INDEX idx_files_removed_at ON files(removed_at) WHERE removed_at IS NOT NULL;
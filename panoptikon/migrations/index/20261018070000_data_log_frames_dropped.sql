@@ -0,0 +1,4 @@
+-- Stored video frames an extraction job deleted under the frame retention
+-- setting (`frame_retention.keep_frames_until_processed_by`). 0 for older
+-- jobs and when retention is off.
+ALTER TABLE data_log ADD COLUMN frames_dropped INTEGER NOT NULL DEFAULT 0;
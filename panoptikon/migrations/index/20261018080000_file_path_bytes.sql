@@ -0,0 +1,5 @@
+-- Exact bytes of a file path that is not valid UTF-8 (Unix filenames in a
+-- legacy encoding). files.path holds its text form with such bytes spelled
+-- \xNN, which does not name the file on disk; the file is opened by these
+-- bytes instead. NULL for every UTF-8 path.
+ALTER TABLE files ADD COLUMN path_bytes BLOB;
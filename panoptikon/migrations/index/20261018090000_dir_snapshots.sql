@@ -0,0 +1,16 @@
+-- Modification time and entry count of every directory the last full scan
+-- of a folder walked, with `skip_unchanged_dirs` on. The next scan skips the
+-- files directly inside a directory whose snapshot still matches. Rows are
+-- only valid for the scan settings digested into config_hash, and while the
+-- directory still holds file_count available indexed files.
+CREATE TABLE dir_snapshots (
+    path TEXT PRIMARY KEY,
+    mtime_ns INTEGER NOT NULL,
+    child_count INTEGER NOT NULL,
+    config_hash TEXT NOT NULL,
+    file_count INTEGER NOT NULL DEFAULT 0
+);
+
+-- Directories whose files a scan skipped because the directory was
+-- unchanged. 0 for older scans and scans with skip_unchanged_dirs off.
+ALTER TABLE file_scans ADD COLUMN skipped_dirs INTEGER NOT NULL DEFAULT 0;
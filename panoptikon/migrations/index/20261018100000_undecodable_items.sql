@@ -0,0 +1,6 @@
+-- Set on items none of whose video frames could be decoded, even by the
+-- fallback extraction steps: by the scan that indexed or backfilled the
+-- item, and by extraction jobs that hit the same failure. Cleared when a
+-- later scan decodes it. Listed by GET /api/items/undecodable.
+ALTER TABLE items ADD COLUMN undecodable INTEGER NOT NULL DEFAULT 0;
+CREATE INDEX idx_items_undecodable ON items(undecodable) WHERE undecodable = 1;
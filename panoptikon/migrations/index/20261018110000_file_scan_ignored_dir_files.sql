@@ -0,0 +1,3 @@
+-- Files skipped because they sit below a directory matching
+-- `ignored_dir_patterns`. Continuous scans count the change events they drop.
+ALTER TABLE file_scans ADD COLUMN ignored_dir_files INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,16 @@
+-- Saved queries: named PQL search bodies stored server-side so clients don't
+-- re-paste them. `query` is the PqlQuery exactly as submitted (minus any
+-- resolved embeddings, which are never serialized): vector filters re-embed
+-- their query text every time the saved query runs. Names are unique per
+-- user and double as the API identifier.
+CREATE TABLE saved_queries (
+    id INTEGER PRIMARY KEY,
+    user TEXT NOT NULL,
+    name TEXT NOT NULL,
+    description TEXT,
+    query JSON NOT NULL CHECK (json_valid(query)),
+    time_added TEXT NOT NULL,
+    time_updated TEXT NOT NULL,
+    UNIQUE (user, name)
+);
+CREATE INDEX idx_saved_queries_time_updated ON saved_queries(time_updated);
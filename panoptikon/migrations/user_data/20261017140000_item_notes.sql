@@ -0,0 +1,32 @@
+-- Item notes: free-form text a user attaches to an item, one note per
+-- (user, sha256). Like bookmarks, notes follow the content hash, so they
+-- survive moves and renames and show on every copy of the file.
+CREATE TABLE item_notes (
+    id INTEGER PRIMARY KEY,
+    user TEXT NOT NULL,
+    sha256 TEXT NOT NULL,
+    note TEXT NOT NULL,
+    time_updated TEXT NOT NULL,
+    UNIQUE (user, sha256)
+);
+CREATE INDEX idx_item_notes_sha256 ON item_notes(sha256);
+
+-- Full-text search for the match_note PQL filter. External-content FTS5
+-- over item_notes.note; the triggers keep it in sync.
+CREATE VIRTUAL TABLE item_notes_fts USING fts5(
+    note,
+    content='item_notes',
+    content_rowid='id'
+);
+CREATE TRIGGER item_notes_fts_insert AFTER INSERT ON item_notes BEGIN
+    INSERT INTO item_notes_fts(rowid, note) VALUES (new.id, new.note);
+END;
+CREATE TRIGGER item_notes_fts_delete AFTER DELETE ON item_notes BEGIN
+    INSERT INTO item_notes_fts(item_notes_fts, rowid, note)
+        VALUES ('delete', old.id, old.note);
+END;
+CREATE TRIGGER item_notes_fts_update AFTER UPDATE OF note ON item_notes BEGIN
+    INSERT INTO item_notes_fts(item_notes_fts, rowid, note)
+        VALUES ('delete', old.id, old.note);
+    INSERT INTO item_notes_fts(rowid, note) VALUES (new.id, new.note);
+END;
@@ -0,0 +1,13 @@
+-- Item metadata: user-defined key/value fields on items (e.g. project,
+-- status). Keyed by sha256 like bookmarks and notes, so fields follow the
+-- content across moves and renames. `value` holds a JSON scalar (string,
+-- number or boolean); match_meta reads it back with json_extract so numbers
+-- compare numerically.
+CREATE TABLE item_meta (
+    sha256 TEXT NOT NULL,
+    key TEXT NOT NULL,
+    value TEXT NOT NULL,
+    time_updated TEXT NOT NULL,
+    PRIMARY KEY (sha256, key)
+);
+CREATE INDEX idx_item_meta_key ON item_meta(key);
@@ -1,65 +1,74 @@
-use axum::{Json, extract::Path};
+use axum::{Extension, Json, extract::Path};
 use axum_extra::extract::Query;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::BTreeMap;
 use utoipa::{IntoParams, ToSchema};
 
 use crate::api::db_params::DbQueryParams;
 use crate::api_error::ApiError;
 use crate::db::bookmarks::{
     BookmarkSearchResult, add_bookmark, delete_bookmark, delete_bookmarks_exclude_last_n,
-    get_all_bookmark_namespaces, get_all_bookmark_users, get_bookmark_metadata, get_bookmarks,
-    get_bookmarks_item,
+    get_all_bookmark_namespaces, get_all_bookmark_users, get_bookmark_metadata,
+    get_bookmark_namespaces_for_items, get_bookmarks, get_bookmarks_item,
 };
 use crate::db::{DbConnection, ReadOnly, UserDataWrite};
+use crate::policy::{RequestIdentity, UserAccess, scoped_user};
 
 type ApiResult<T> = std::result::Result<T, ApiError>;
 
-const DEFAULT_USER: &str = "user";
 const LARGE_PAGE_SIZE: i64 = 1_000_000;
+const MAX_STATUS_ITEMS: usize = 10_000;
+const MAX_STATUS_NAMESPACES: usize = 100;
 
 #[derive(Deserialize, IntoParams)]
 #[into_params(parameter_in = Query)]
 pub(crate) struct ItemBookmarksQuery {
     /// The user to get the bookmark from. The wildcard '*' can be used to get `wildcard user` bookmarks that apply to all users.
-    #[serde(default = "default_user")]
+    /// Defaults to the authenticated user when the policy resolves one.
+    #[serde(default)]
     #[param(default = "user")]
-    user: String,
+    user: Option<String>,
 }
 
 #[derive(Deserialize, IntoParams)]
 #[into_params(parameter_in = Query)]
 pub(crate) struct BookmarkGetUserQuery {
     /// The user to get the bookmark from. The wildcard '*' can be used to get `wildcard user` bookmarks that apply to all users.
-    #[serde(default = "default_user")]
+    /// Defaults to the authenticated user when the policy resolves one.
+    #[serde(default)]
     #[param(default = "user")]
-    user: String,
+    user: Option<String>,
 }
 
 #[derive(Deserialize, IntoParams)]
 #[into_params(parameter_in = Query)]
 pub(crate) struct BookmarkSaveUserQuery {
     /// The user to save the bookmark under. The wildcard '*' can be used to set `wildcard user` bookmarks that apply to all users.
-    #[serde(default = "default_user")]
+    /// Defaults to the authenticated user when the policy resolves one.
+    #[serde(default)]
     #[param(default = "user")]
-    user: String,
+    user: Option<String>,
 }
 
 #[derive(Deserialize, IntoParams)]
 #[into_params(parameter_in = Query)]
 pub(crate) struct BookmarkDeleteUserQuery {
     /// The user to delete the bookmark from.
-    #[serde(default = "default_user")]
+    /// Defaults to the authenticated user when the policy resolves one.
+    #[serde(default)]
     #[param(default = "user")]
-    user: String,
+    user: Option<String>,
 }
 
 #[derive(Deserialize, IntoParams)]
 #[into_params(parameter_in = Query)]
 pub(crate) struct BookmarkListQuery {
-    #[serde(default = "default_user")]
+    /// The user to get the bookmarks of.
+    /// Defaults to the authenticated user when the policy resolves one.
+    #[serde(default)]
     #[param(default = "user")]
-    user: String,
+    user: Option<String>,
     #[serde(default = "default_page_size")]
     #[param(default = 1000)]
     page_size: i64,
@@ -79,10 +88,11 @@ pub(crate) struct BookmarkListQuery {
 #[derive(Deserialize, IntoParams)]
 #[into_params(parameter_in = Query)]
 pub(crate) struct DeleteNamespaceQuery {
-    #[serde(default = "default_user")]
+    #[serde(default)]
     #[param(default = "user")]
     /// The user to delete the bookmarks from.
-    user: String,
+    /// Defaults to the authenticated user when the policy resolves one.
+    user: Option<String>,
     #[serde(default)]
     exclude_last_n: i64,
 }
@@ -155,6 +165,27 @@ pub(crate) struct ItemBookmarks {
     bookmarks: Vec<ExistingBookmarkMetadata>,
 }
 
+#[derive(Deserialize, ToSchema)]
+pub(crate) struct BookmarkStatusRequest {
+    /// The items to check, up to 10000
+    sha256: Vec<String>,
+    /// The namespaces to check, up to 100. Empty or containing '*' checks
+    /// every namespace.
+    #[serde(default)]
+    namespaces: Vec<String>,
+    /// The user whose bookmarks to check. Defaults to the authenticated user
+    /// when the policy resolves one, otherwise `user`.
+    #[serde(default)]
+    user: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct BookmarkStatus {
+    /// Every requested sha256, mapped to the namespaces it is bookmarked in
+    /// (empty if none)
+    items: BTreeMap<String, Vec<String>>,
+}
+
 #[derive(Serialize, ToSchema)]
 pub(crate) struct BookmarkMetadata {
     exists: bool,
@@ -180,8 +211,10 @@ pub(crate) struct MessageResult {
 )]
 pub async fn bookmark_namespaces(
     mut db: DbConnection<ReadOnly>,
+    identity: Option<Extension<RequestIdentity>>,
 ) -> ApiResult<Json<BookmarkNamespaces>> {
-    let response = load_bookmark_namespaces(&mut db.conn, DEFAULT_USER).await?;
+    let user = scoped_user(identity.as_deref(), None, UserAccess::Read)?;
+    let response = load_bookmark_namespaces(&mut db.conn, &user).await?;
     Ok(Json(response))
 }
 
@@ -196,8 +229,18 @@ pub async fn bookmark_namespaces(
         (status = 200, description = "Bookmark users", body = BookmarkUsers)
     )
 )]
-pub async fn bookmark_users(mut db: DbConnection<ReadOnly>) -> ApiResult<Json<BookmarkUsers>> {
-    let response = load_bookmark_users(&mut db.conn).await?;
+pub async fn bookmark_users(
+    mut db: DbConnection<ReadOnly>,
+    identity: Option<Extension<RequestIdentity>>,
+) -> ApiResult<Json<BookmarkUsers>> {
+    let user = scoped_user(identity.as_deref(), None, UserAccess::Read)?;
+    let mut response = load_bookmark_users(&mut db.conn).await?;
+    // Everyone else's user names are cross-user data too.
+    if let Some(RequestIdentity::User { admin: false, .. }) = identity.as_deref() {
+        response
+            .users
+            .retain(|listed| *listed == user || listed == "*");
+    }
     Ok(Json(response))
 }
 
@@ -219,13 +262,15 @@ pub async fn bookmark_users(mut db: DbConnection<ReadOnly>) -> ApiResult<Json<Bo
 )]
 pub async fn bookmarks_by_namespace(
     mut db: DbConnection<ReadOnly>,
+    identity: Option<Extension<RequestIdentity>>,
     Path(namespace): Path<String>,
     Query(query): Query<BookmarkListQuery>,
 ) -> ApiResult<Json<Results>> {
+    let user = scoped_user(identity.as_deref(), query.user.as_deref(), UserAccess::Read)?;
     let response = load_bookmarks_by_namespace(
         &mut db.conn,
         &namespace,
-        &query.user,
+        &user,
         query.page_size,
         query.page,
         query.order_by,
@@ -257,14 +302,20 @@ pub async fn bookmarks_by_namespace(
 )]
 pub async fn delete_bookmarks_by_namespace(
     mut db: DbConnection<UserDataWrite>,
+    identity: Option<Extension<RequestIdentity>>,
     Path(namespace): Path<String>,
     Query(query): Query<DeleteNamespaceQuery>,
     body: Option<Json<Items>>,
 ) -> ApiResult<Json<MessageResult>> {
+    let user = scoped_user(
+        identity.as_deref(),
+        query.user.as_deref(),
+        UserAccess::Write,
+    )?;
     let response = delete_bookmarks_namespace(
         &mut db.conn,
         &namespace,
-        &query.user,
+        &user,
         query.exclude_last_n,
         body.as_ref().map(|items| &items.0),
     )
@@ -293,11 +344,17 @@ pub async fn delete_bookmarks_by_namespace(
 )]
 pub async fn add_bookmarks_by_namespace(
     mut db: DbConnection<UserDataWrite>,
+    identity: Option<Extension<RequestIdentity>>,
     Path(namespace): Path<String>,
     Query(query): Query<BookmarkSaveUserQuery>,
     Json(items): Json<ItemsMeta>,
 ) -> ApiResult<Json<MessageResult>> {
-    let response = add_bookmarks_bulk(&mut db.conn, &namespace, &query.user, &items).await?;
+    let user = scoped_user(
+        identity.as_deref(),
+        query.user.as_deref(),
+        UserAccess::Write,
+    )?;
+    let response = add_bookmarks_bulk(&mut db.conn, &namespace, &user, &items).await?;
     Ok(Json(response))
 }
 
@@ -320,10 +377,12 @@ pub async fn add_bookmarks_by_namespace(
 )]
 pub async fn get_bookmark(
     mut db: DbConnection<ReadOnly>,
+    identity: Option<Extension<RequestIdentity>>,
     Path((namespace, sha256)): Path<(String, String)>,
     Query(query): Query<BookmarkGetUserQuery>,
 ) -> ApiResult<Json<BookmarkMetadata>> {
-    let response = load_bookmark_metadata(&mut db.conn, &namespace, &sha256, &query.user).await?;
+    let user = scoped_user(identity.as_deref(), query.user.as_deref(), UserAccess::Read)?;
+    let response = load_bookmark_metadata(&mut db.conn, &namespace, &sha256, &user).await?;
     Ok(Json(response))
 }
 
@@ -349,15 +408,21 @@ pub async fn get_bookmark(
 )]
 pub async fn add_bookmark_by_sha256(
     mut db: DbConnection<UserDataWrite>,
+    identity: Option<Extension<RequestIdentity>>,
     Path((namespace, sha256)): Path<(String, String)>,
     Query(query): Query<BookmarkSaveUserQuery>,
     metadata: Option<Json<Value>>,
 ) -> ApiResult<Json<MessageResult>> {
+    let user = scoped_user(
+        identity.as_deref(),
+        query.user.as_deref(),
+        UserAccess::Write,
+    )?;
     let response = add_bookmark_entry(
         &mut db.conn,
         &namespace,
         &sha256,
-        &query.user,
+        &user,
         metadata.as_ref().map(|entry| &entry.0),
     )
     .await?;
@@ -382,10 +447,41 @@ pub async fn add_bookmark_by_sha256(
 )]
 pub async fn bookmarks_item(
     mut db: DbConnection<ReadOnly>,
+    identity: Option<Extension<RequestIdentity>>,
     Path(sha256): Path<String>,
     Query(query): Query<ItemBookmarksQuery>,
 ) -> ApiResult<Json<ItemBookmarks>> {
-    let response = load_item_bookmarks(&mut db.conn, &sha256, &query.user).await?;
+    let user = scoped_user(identity.as_deref(), query.user.as_deref(), UserAccess::Read)?;
+    let response = load_item_bookmarks(&mut db.conn, &sha256, &user).await?;
+    Ok(Json(response))
+}
+
+#[utoipa::path(
+    post,
+    operation_id = "bookmarks_items_status",
+    path = "/api/bookmarks/items/status",
+    tag = "bookmarks",
+    summary = "Get the bookmark namespaces of many items",
+    description = "Checks which of the given namespaces each item is bookmarked in, for one user, in a single request.\nMeant for rendering bookmark badges on a page of results. Only the user's own bookmarks count, not wildcard user ('*') bookmarks, unless '*' is the user asked for.",
+    params(DbQueryParams),
+    request_body = BookmarkStatusRequest,
+    responses(
+        (status = 200, description = "The namespaces of each item", body = BookmarkStatus),
+        (status = 400, description = "Too many items or namespaces")
+    )
+)]
+pub async fn bookmarks_items_status(
+    mut db: DbConnection<ReadOnly>,
+    identity: Option<Extension<RequestIdentity>>,
+    Json(request): Json<BookmarkStatusRequest>,
+) -> ApiResult<Json<BookmarkStatus>> {
+    let user = scoped_user(
+        identity.as_deref(),
+        request.user.as_deref(),
+        UserAccess::Read,
+    )?;
+    let response =
+        load_bookmark_status(&mut db.conn, request.sha256, &request.namespaces, &user).await?;
     Ok(Json(response))
 }
 
@@ -407,10 +503,16 @@ pub async fn bookmarks_item(
 )]
 pub async fn delete_bookmark_by_sha256(
     mut db: DbConnection<UserDataWrite>,
+    identity: Option<Extension<RequestIdentity>>,
     Path((namespace, sha256)): Path<(String, String)>,
     Query(query): Query<BookmarkDeleteUserQuery>,
 ) -> ApiResult<Json<MessageResult>> {
-    let response = delete_bookmark_entry(&mut db.conn, &sha256, &namespace, &query.user).await?;
+    let user = scoped_user(
+        identity.as_deref(),
+        query.user.as_deref(),
+        UserAccess::Write,
+    )?;
+    let response = delete_bookmark_entry(&mut db.conn, &sha256, &namespace, &user).await?;
     Ok(Json(response))
 }
 
@@ -599,6 +701,31 @@ async fn load_item_bookmarks(
     Ok(ItemBookmarks { bookmarks })
 }
 
+async fn load_bookmark_status(
+    conn: &mut sqlx::SqliteConnection,
+    mut sha256s: Vec<String>,
+    namespaces: &[String],
+    user: &str,
+) -> ApiResult<BookmarkStatus> {
+    if sha256s.len() > MAX_STATUS_ITEMS {
+        return Err(ApiError::bad_request(format!(
+            "At most {MAX_STATUS_ITEMS} items can be checked at once"
+        )));
+    }
+    if namespaces.len() > MAX_STATUS_NAMESPACES {
+        return Err(ApiError::bad_request(format!(
+            "At most {MAX_STATUS_NAMESPACES} namespaces can be checked at once"
+        )));
+    }
+    sha256s.sort_unstable();
+    sha256s.dedup();
+    let mut items = get_bookmark_namespaces_for_items(conn, &sha256s, namespaces, user).await?;
+    for sha256 in sha256s {
+        items.entry(sha256).or_default();
+    }
+    Ok(BookmarkStatus { items })
+}
+
 async fn delete_bookmark_entry(
     conn: &mut sqlx::SqliteConnection,
     sha256: &str,
@@ -665,10 +792,6 @@ fn is_truthy(value: &Value) -> bool {
     }
 }
 
-fn default_user() -> String {
-    DEFAULT_USER.to_string()
-}
-
 fn default_page_size() -> i64 {
     1000
 }
@@ -1273,4 +1396,93 @@ mod tests {
         let count: i64 = remaining.try_get("count").unwrap();
         assert_eq!(count, 1);
     }
+
+    // Ensures the batch status lookup maps every requested item to the
+    // requested namespaces it is bookmarked in, across several IN chunks,
+    // and stays fast for a grid-sized page over a few thousand bookmarks.
+    #[tokio::test]
+    async fn load_bookmark_status_maps_items_to_namespaces() {
+        let mut dbs = setup_user_data_db().await;
+        sqlx::query("BEGIN")
+            .execute(&mut dbs.index_conn)
+            .await
+            .unwrap();
+        for index in 0..6000 {
+            let sha256 = format!("sha_{index:05}");
+            for (user, namespace) in [
+                ("user", "default"),
+                ("user", "later"),
+                ("user", "archive"),
+                ("other", "default"),
+            ] {
+                if namespace == "later" && index % 2 == 1 {
+                    continue;
+                }
+                sqlx::query(
+                    r#"
+                    INSERT INTO user_data.bookmarks (user, namespace, sha256, time_added, metadata)
+                    VALUES (?, ?, ?, ?, NULL)
+                    "#,
+                )
+                .bind(user)
+                .bind(namespace)
+                .bind(&sha256)
+                .bind("2024-01-01T00:00:00")
+                .execute(&mut dbs.index_conn)
+                .await
+                .unwrap();
+            }
+        }
+        sqlx::query("COMMIT")
+            .execute(&mut dbs.index_conn)
+            .await
+            .unwrap();
+        let namespaces = vec!["default".to_string(), "later".to_string()];
+
+        let page: Vec<String> = (3000..3060)
+            .map(|index| format!("sha_{index:05}"))
+            .chain(["sha_missing".to_string()])
+            .collect();
+        let started = std::time::Instant::now();
+        let status = load_bookmark_status(&mut dbs.index_conn, page, &namespaces, "user")
+            .await
+            .unwrap();
+        let elapsed = started.elapsed();
+        assert!(
+            elapsed < std::time::Duration::from_secs(1),
+            "page lookup took {elapsed:?}"
+        );
+        assert_eq!(status.items.len(), 61);
+        assert_eq!(status.items["sha_03000"], vec!["default", "later"]);
+        assert_eq!(status.items["sha_03001"], vec!["default"]);
+        assert!(status.items["sha_missing"].is_empty());
+
+        let all: Vec<String> = (0..6000).map(|index| format!("sha_{index:05}")).collect();
+        let status = load_bookmark_status(&mut dbs.index_conn, all, &[], "user")
+            .await
+            .unwrap();
+        assert_eq!(status.items.len(), 6000);
+        assert_eq!(status.items["sha_05999"], vec!["archive", "default"]);
+        assert_eq!(
+            status.items["sha_05998"],
+            vec!["archive", "default", "later"]
+        );
+
+        let status = load_bookmark_status(
+            &mut dbs.index_conn,
+            vec!["sha_00001".to_string()],
+            &["*".to_string()],
+            "other",
+        )
+        .await
+        .unwrap();
+        assert_eq!(status.items["sha_00001"], vec!["default"]);
+
+        let too_many = vec![String::new(); MAX_STATUS_ITEMS + 1];
+        assert!(
+            load_bookmark_status(&mut dbs.index_conn, too_many, &[], "user")
+                .await
+                .is_err()
+        );
+    }
 }
@@ -13,6 +13,7 @@ use utoipa::ToSchema;
 use crate::api_error::ApiError;
 use crate::config::{PolicyConfig, Settings};
 use crate::policy::{PolicyContext, ruleset_allows};
+use crate::pql::model::PQL_FILTERS;
 use crate::proxy::ProxyState;
 
 /// Coarse feature switches derived from the matched policy's ruleset. Each
@@ -57,6 +58,9 @@ pub(crate) struct ClientConfigResponse {
     /// True only for a policy explicitly marked as the local Desktop client
     /// while the private parent-shell bridge is configured.
     pub desktop_shell_available: bool,
+    /// PQL filter keys searches accept: every filter except those in
+    /// `[search] disabled_filters`, whose controls the UI should hide.
+    pub pql_filters: Vec<&'static str>,
 }
 
 /// The probe table: (capability, method, representative real route). Paths
@@ -104,9 +108,18 @@ pub(crate) fn build_client_config(
             crate::desktop::is_managed(),
             crate::api::desktop::desktop_bridge_is_configured(),
         ),
+        pql_filters: enabled_pql_filters(&settings.search.disabled_filters),
     }
 }
 
+fn enabled_pql_filters(disabled: &[String]) -> Vec<&'static str> {
+    PQL_FILTERS
+        .iter()
+        .map(|(key, _)| *key)
+        .filter(|key| !disabled.iter().any(|name| name == key))
+        .collect()
+}
+
 #[utoipa::path(
     get,
     operation_id = "client_config",
@@ -168,6 +181,9 @@ base_url = "http://127.0.0.1:6339"
 [upstreams.api]
 base_url = "http://127.0.0.1:6342"
 
+[search]
+disabled_filters = ["SimilarTo", "text_embeddings", "similar_to"]
+
 [rulesets.allow_all]
 allow_all = true
 
@@ -275,6 +291,14 @@ disable_backend_open = true
             response.client,
             serde_json::json!({ "search_throttle_ms": 100 })
         );
+        // Variant names resolve to keys; disabled filters are left out.
+        assert_eq!(
+            settings.search.disabled_filters,
+            ["similar_to", "text_embeddings"]
+        );
+        assert_eq!(response.pql_filters.len(), PQL_FILTERS.len() - 2);
+        assert!(response.pql_filters.contains(&"image_embeddings"));
+        assert!(!response.pql_filters.contains(&"similar_to"));
     }
 
     /// The handler responds with Cache-Control: no-store (the body is
@@ -283,7 +307,8 @@ disable_backend_open = true
     #[tokio::test]
     async fn handler_sets_no_store_and_uses_matched_policy() {
         let settings = Arc::new(two_policy_settings());
-        let upstream = crate::proxy::Upstream::parse("api", "http://127.0.0.1:1").unwrap();
+        let upstream =
+            crate::proxy::Upstream::parse("api", "http://127.0.0.1:1", Default::default()).unwrap();
         let client = crate::inferio_client::InferenceApiClient::new_with_metadata_cache(
             "http://127.0.0.1:1".to_string(),
             false,
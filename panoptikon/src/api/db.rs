@@ -4,7 +4,7 @@ use tokio::runtime::Handle;
 use utoipa::{IntoParams, ToSchema};
 
 use crate::api_error::ApiError;
-use crate::db::info::load_db_info;
+use crate::db::info::load_db_info_with_status;
 use crate::db::migrations::migrate_databases_on_disk;
 
 #[utoipa::path(
@@ -13,13 +13,13 @@ use crate::db::migrations::migrate_databases_on_disk;
     path = "/api/db",
     tag = "database",
     summary = "Get information about all available databases",
-    description = "Get the name of the current default databases and a list of all available databases.\nMost API endpoints support specifying the databases to use for index and user data\nthrough the `index_db` and `user_data_db` query parameters.\nRegardless of which database is currently being defaulted to by panoptikon,\nthe API allows you to perform actions and query data from any of the available databases.\nThe current databases are simply the ones that are used by default.",
+    description = "Get the name of the current default databases and a list of all available databases.\nMost API endpoints support specifying the databases to use for index and user data\nthrough the `index_db` and `user_data_db` query parameters.\nRegardless of which database is currently being defaulted to by panoptikon,\nthe API allows you to perform actions and query data from any of the available databases.\nThe current databases are simply the ones that are used by default.\n\nEach database is also inspected on its own: `databases` holds one entry per database with its size\n(and item count for index databases), or an error code and message when it could not be read.\n`partial` is true when any entry is an error; the listing itself still succeeds.",
     responses(
         (status = 200, description = "Database information", body = crate::policy::DbInfo)
     )
 )]
 pub async fn db_info() -> impl IntoResponse {
-    let info = match load_db_info() {
+    let info = match load_db_info_with_status().await {
         Ok(info) => info,
         Err(err) => {
             tracing::error!(error = %err, "failed to load db info");
@@ -49,10 +49,11 @@ pub(crate) struct DbCreateResponse {
     path = "/api/db/create",
     tag = "database",
     summary = "Create new databases",
-    description = "Create new databases with the specified names.\nIt runs the migration scripts on the provided database names.\nIf the databases already exist, the effect is the same as running the migrations.",
+    description = "Create new databases with the specified names.\nIt runs the migration scripts on the provided database names.\nIf the databases already exist, the effect is the same as running the migrations.\nThe gateway then takes the new index database's instance lock; 409 if another Panoptikon instance holds it.",
     params(DbCreateQuery),
     responses(
-        (status = 200, description = "Created databases", body = DbCreateResponse)
+        (status = 200, description = "Created databases", body = DbCreateResponse),
+        (status = 409, description = "Another instance holds the index database's lock")
     )
 )]
 pub async fn db_create(
@@ -78,6 +79,10 @@ pub async fn db_create(
         tracing::error!(error = ?err, "failed to create databases");
         ApiError::internal("Failed to create databases")
     })?;
+    crate::db::instance_lock::lock_new_index_db(&result.index_db).map_err(|err| {
+        tracing::error!(error = %err, "failed to lock new index database");
+        ApiError::new(StatusCode::CONFLICT, err.to_string())
+    })?;
 
     let response = DbCreateResponse {
         index_db: result.index_db,
@@ -228,7 +228,7 @@ async fn desktop_bridge_request(
         .join(path.strip_prefix('/').unwrap_or(path))
         .map_err(|error| {
             tracing::error!(%error, "failed to construct Desktop shell bridge URL");
-            ApiError::internal("Desktop shell is unavailable")
+            ApiError::upstream_unavailable("Desktop shell is unavailable")
         })?;
     let client = reqwest::Client::builder()
         // The bridge is an authenticated process-local channel. Never send
@@ -242,7 +242,7 @@ async fn desktop_bridge_request(
         .build()
         .map_err(|error| {
             tracing::error!(%error, "failed to build Desktop shell bridge client");
-            ApiError::internal("Desktop shell is unavailable")
+            ApiError::upstream_unavailable("Desktop shell is unavailable")
         })?;
     let mut request = client.request(method, url).bearer_auth(&bridge.token);
     if let Some(body) = body {
@@ -250,7 +250,7 @@ async fn desktop_bridge_request(
     }
     request.send().await.map_err(|error| {
         tracing::warn!(%error, "Desktop shell bridge request failed");
-        ApiError::internal("Desktop shell is unavailable")
+        ApiError::upstream_unavailable("Desktop shell is unavailable")
     })
 }
 
@@ -0,0 +1,28 @@
+//! `GET /api/diagnostics`: setup checks for the common environment problems
+//! (see `crate::diagnostics`), so a user can see what is wrong with an
+//! installation, and how to fix it, before filing an issue.
+
+use axum::{Json, extract::State};
+use std::sync::Arc;
+
+use crate::diagnostics::{DiagnosticsReport, run_checks};
+use crate::proxy::ProxyState;
+
+#[utoipa::path(
+    get,
+    operation_id = "diagnostics",
+    path = "/api/diagnostics",
+    tag = "diagnostics",
+    summary = "Run the setup diagnostics",
+    description = "Checks SQLite and sqlite-vec, ffmpeg/ffprobe, the Python environment used by \
+local inference, the PDF and HTML renderers, the data folder's writability and free space, the \
+inference server's metadata, and the default databases' schema versions. Each check reports \
+`pass`, `warn` or `fail`, with a remediation hint for the last two; `status` is the worst of \
+them. The checks run concurrently and take up to about 15 seconds when a probe hangs.",
+    responses(
+        (status = 200, description = "Result of every check", body = DiagnosticsReport)
+    )
+)]
+pub(crate) async fn diagnostics(State(state): State<Arc<ProxyState>>) -> Json<DiagnosticsReport> {
+    Json(run_checks(&state.settings, true).await)
+}
@@ -0,0 +1,229 @@
+use axum::Json;
+use axum_extra::extract::Query;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::api::db_params::DbQueryParams;
+use crate::api_error::ApiError;
+use crate::db::item_meta::{self, ItemMetaRecord, MetaValue};
+use crate::db::{DbConnection, ReadOnly, UserDataWrite};
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+const MAX_KEY_CHARS: usize = 128;
+const MAX_VALUE_CHARS: usize = 4096;
+const DEFAULT_KEYS_LIMIT: i64 = 100;
+const MAX_KEYS_LIMIT: i64 = 1000;
+
+fn default_keys_limit() -> i64 {
+    DEFAULT_KEYS_LIMIT
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct ItemMetaQuery {
+    /// The sha256 of the item
+    sha256: String,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct ItemMetaKeyQuery {
+    /// The sha256 of the item
+    sha256: String,
+    /// The field to delete
+    key: String,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct MetaKeysQuery {
+    /// Only keys starting with this text (case-insensitive)
+    #[serde(default)]
+    prefix: Option<String>,
+    /// Maximum number of keys to return (1–1000)
+    #[serde(default = "default_keys_limit")]
+    #[param(default = 100)]
+    limit: i64,
+}
+
+/// Body for creating or replacing one field.
+#[derive(Deserialize, ToSchema)]
+pub(crate) struct ItemMetaRequest {
+    /// The field name. Leading and trailing whitespace is trimmed.
+    key: String,
+    /// A JSON string, number or boolean.
+    value: MetaValue,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct ItemMetaField {
+    key: String,
+    value: MetaValue,
+    time_updated: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct ItemMetaResponse {
+    sha256: String,
+    /// The item's fields, ordered by key
+    fields: Vec<ItemMetaField>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct ItemMetaDeleteResponse {
+    message: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct MetaKeyEntry {
+    key: String,
+    /// Items in the index that have this key
+    items: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct MetaKeysResponse {
+    keys: Vec<MetaKeyEntry>,
+}
+
+fn map_record(record: ItemMetaRecord) -> ItemMetaField {
+    ItemMetaField {
+        key: record.key,
+        value: record.value,
+        time_updated: record.time_updated,
+    }
+}
+
+fn validate_field(sha256: &str, key: &str, value: &MetaValue) -> Result<(), String> {
+    if sha256.trim().is_empty() {
+        return Err("sha256 must not be empty".to_string());
+    }
+    if key.is_empty() {
+        return Err("Key must not be blank".to_string());
+    }
+    if key.chars().count() > MAX_KEY_CHARS {
+        return Err(format!("Key must be at most {MAX_KEY_CHARS} characters"));
+    }
+    if let MetaValue::String(text) = value
+        && text.chars().count() > MAX_VALUE_CHARS
+    {
+        return Err(format!(
+            "String values must be at most {MAX_VALUE_CHARS} characters"
+        ));
+    }
+    Ok(())
+}
+
+async fn load_item_meta(
+    conn: &mut sqlx::SqliteConnection,
+    sha256: String,
+) -> ApiResult<ItemMetaResponse> {
+    let fields = item_meta::list_item_meta(conn, &sha256)
+        .await?
+        .into_iter()
+        .map(map_record)
+        .collect();
+    Ok(ItemMetaResponse { sha256, fields })
+}
+
+#[utoipa::path(
+    get,
+    operation_id = "get_item_meta_fields",
+    path = "/api/items/item/meta",
+    tag = "items",
+    summary = "List an item's metadata fields",
+    params(DbQueryParams, ItemMetaQuery),
+    responses(
+        (status = 200, description = "The item's fields; empty when it has none", body = ItemMetaResponse)
+    )
+)]
+pub async fn get_item_meta_fields(
+    mut db: DbConnection<ReadOnly>,
+    Query(query): Query<ItemMetaQuery>,
+) -> ApiResult<Json<ItemMetaResponse>> {
+    load_item_meta(&mut db.conn, query.sha256).await.map(Json)
+}
+
+#[utoipa::path(
+    put,
+    operation_id = "set_item_meta_field",
+    path = "/api/items/item/meta",
+    tag = "items",
+    summary = "Create or replace a metadata field on an item",
+    description = "Fields are key/value pairs whose values are JSON strings, numbers or booleans. They are stored in the user data DB keyed by sha256, like bookmarks, so they follow the content across moves and renames, and are shared by every user of that DB. Fields are searchable with the `match_meta` PQL filter.",
+    params(DbQueryParams, ItemMetaQuery),
+    request_body(content = ItemMetaRequest),
+    responses(
+        (status = 200, description = "The item's fields after the change", body = ItemMetaResponse),
+        (status = 400, description = "Blank or overlong key, overlong value, or blank sha256")
+    )
+)]
+pub async fn set_item_meta_field(
+    mut db: DbConnection<UserDataWrite>,
+    Query(query): Query<ItemMetaQuery>,
+    Json(request): Json<ItemMetaRequest>,
+) -> ApiResult<Json<ItemMetaResponse>> {
+    let key = request.key.trim();
+    validate_field(&query.sha256, key, &request.value).map_err(ApiError::bad_request)?;
+    item_meta::set_item_meta(&mut db.conn, &query.sha256, key, &request.value).await?;
+    load_item_meta(&mut db.conn, query.sha256).await.map(Json)
+}
+
+#[utoipa::path(
+    delete,
+    operation_id = "delete_item_meta_field",
+    path = "/api/items/item/meta",
+    tag = "items",
+    summary = "Delete a metadata field from an item",
+    params(DbQueryParams, ItemMetaKeyQuery),
+    responses(
+        (status = 200, description = "Deleted", body = ItemMetaDeleteResponse),
+        (status = 404, description = "The item has no such field")
+    )
+)]
+pub async fn delete_item_meta_field(
+    mut db: DbConnection<UserDataWrite>,
+    Query(query): Query<ItemMetaKeyQuery>,
+) -> ApiResult<Json<ItemMetaDeleteResponse>> {
+    if !item_meta::delete_item_meta(&mut db.conn, &query.sha256, query.key.trim()).await? {
+        return Err(ApiError::not_found("Field not found"));
+    }
+    Ok(Json(ItemMetaDeleteResponse {
+        message: "Field deleted".to_string(),
+    }))
+}
+
+#[utoipa::path(
+    get,
+    operation_id = "get_meta_keys",
+    path = "/api/search/meta/keys",
+    tag = "search",
+    summary = "List metadata keys",
+    description = "Lists the metadata keys used on items of the index, with the number of items that have each, most common first.\nMeant for autocompleting `match_meta` keys; `prefix` narrows the list as the user types.",
+    params(DbQueryParams, MetaKeysQuery),
+    responses(
+        (status = 200, description = "Keys with item counts", body = MetaKeysResponse),
+        (status = 400, description = "limit out of range")
+    )
+)]
+pub async fn get_meta_keys(
+    mut db: DbConnection<ReadOnly>,
+    Query(query): Query<MetaKeysQuery>,
+) -> ApiResult<Json<MetaKeysResponse>> {
+    if !(1..=MAX_KEYS_LIMIT).contains(&query.limit) {
+        return Err(ApiError::bad_request(format!(
+            "limit must be between 1 and {MAX_KEYS_LIMIT}"
+        )));
+    }
+    let prefix = query.prefix.as_deref().filter(|prefix| !prefix.is_empty());
+    let keys = item_meta::list_meta_keys(&mut db.conn, prefix, query.limit)
+        .await?
+        .into_iter()
+        .map(|entry| MetaKeyEntry {
+            key: entry.key,
+            items: entry.items,
+        })
+        .collect();
+    Ok(Json(MetaKeysResponse { keys }))
+}
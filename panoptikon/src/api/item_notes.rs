@@ -0,0 +1,432 @@
+use axum::{Extension, Json};
+use axum_extra::extract::Query;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::api::db_params::DbQueryParams;
+use crate::api_error::ApiError;
+use crate::db::item_notes::{self, ItemNoteRecord};
+use crate::db::{DbConnection, ReadOnly, UserDataWrite};
+use crate::policy::{RequestIdentity, UserAccess, scoped_user};
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+/// Identifies an export document, so an import can reject unrelated JSON.
+const EXPORT_FORMAT: &str = "panoptikon.item_notes";
+const EXPORT_VERSION: u32 = 1;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct ItemNoteQuery {
+    /// The sha256 of the item
+    sha256: String,
+    /// The user the note belongs to. Defaults to `user`, or to the
+    /// authenticated user when the request carries an identity; naming
+    /// another user then requires an admin token.
+    #[serde(default)]
+    #[param(default = "user")]
+    user: Option<String>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct ItemNotesUserQuery {
+    /// The user the notes belong to, scoped like `ItemNoteQuery::user`.
+    #[serde(default)]
+    #[param(default = "user")]
+    user: Option<String>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct ItemNotesImportQuery {
+    /// The user to import the notes for, scoped like `ItemNoteQuery::user`.
+    #[serde(default)]
+    #[param(default = "user")]
+    user: Option<String>,
+    /// Replace the user's existing notes on the same items instead of
+    /// skipping them.
+    #[serde(default)]
+    #[param(default = false)]
+    overwrite: bool,
+}
+
+/// Body for creating or replacing a note.
+#[derive(Deserialize, ToSchema)]
+pub(crate) struct ItemNoteRequest {
+    /// The note text. Must not be blank; delete the note instead.
+    note: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct ItemNoteResponse {
+    sha256: String,
+    note: String,
+    time_updated: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct ItemNoteDeleteResponse {
+    message: String,
+}
+
+/// One note in an export document. Timestamps are instance-local and not
+/// carried over.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub(crate) struct ItemNoteExportEntry {
+    sha256: String,
+    note: String,
+}
+
+/// Portable document for moving a user's notes between instances.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub(crate) struct ItemNotesExport {
+    /// Always `panoptikon.item_notes`.
+    format: String,
+    /// Format version; currently 1.
+    version: u32,
+    notes: Vec<ItemNoteExportEntry>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct ItemNotesImportResponse {
+    /// Items that had no note before.
+    created: Vec<String>,
+    /// Existing notes replaced because `overwrite` was set.
+    replaced: Vec<String>,
+    /// Existing notes left untouched because `overwrite` was not set.
+    skipped: Vec<String>,
+}
+
+fn map_record(record: ItemNoteRecord) -> ItemNoteResponse {
+    ItemNoteResponse {
+        sha256: record.sha256,
+        note: record.note,
+        time_updated: record.time_updated,
+    }
+}
+
+fn validate_entry(sha256: &str, note: &str) -> Result<(), String> {
+    if sha256.trim().is_empty() {
+        return Err("sha256 must not be empty".to_string());
+    }
+    if note.trim().is_empty() {
+        return Err("Note must not be blank".to_string());
+    }
+    Ok(())
+}
+
+async fn load_item_note(
+    conn: &mut sqlx::SqliteConnection,
+    user: &str,
+    sha256: &str,
+) -> ApiResult<ItemNoteResponse> {
+    match item_notes::get_item_note(conn, user, sha256).await? {
+        Some(record) => Ok(map_record(record)),
+        None => Err(ApiError::not_found("Note not found")),
+    }
+}
+
+#[utoipa::path(
+    get,
+    operation_id = "get_item_note",
+    path = "/api/items/item/notes",
+    tag = "items",
+    summary = "Get the user's note on an item",
+    params(DbQueryParams, ItemNoteQuery),
+    responses(
+        (status = 200, description = "Item note", body = ItemNoteResponse),
+        (status = 404, description = "The user has no note on this item")
+    )
+)]
+pub async fn get_item_note(
+    mut db: DbConnection<ReadOnly>,
+    Query(query): Query<ItemNoteQuery>,
+    identity: Option<Extension<RequestIdentity>>,
+) -> ApiResult<Json<ItemNoteResponse>> {
+    let user = scoped_user(identity.as_deref(), query.user.as_deref(), UserAccess::Read)?;
+    load_item_note(&mut db.conn, &user, &query.sha256)
+        .await
+        .map(Json)
+}
+
+#[utoipa::path(
+    put,
+    operation_id = "set_item_note",
+    path = "/api/items/item/notes",
+    tag = "items",
+    summary = "Create or replace the user's note on an item",
+    description = "Each user has at most one note per item. Notes are keyed by sha256, like bookmarks, so they follow the content across moves and renames. Notes are searchable with the `match_note` PQL filter.",
+    params(DbQueryParams, ItemNoteQuery),
+    request_body(content = ItemNoteRequest),
+    responses(
+        (status = 200, description = "Saved note", body = ItemNoteResponse),
+        (status = 400, description = "Blank note or sha256")
+    )
+)]
+pub async fn set_item_note(
+    mut db: DbConnection<UserDataWrite>,
+    Query(query): Query<ItemNoteQuery>,
+    identity: Option<Extension<RequestIdentity>>,
+    Json(request): Json<ItemNoteRequest>,
+) -> ApiResult<Json<ItemNoteResponse>> {
+    let user = scoped_user(
+        identity.as_deref(),
+        query.user.as_deref(),
+        UserAccess::Write,
+    )?;
+    validate_entry(&query.sha256, &request.note).map_err(ApiError::bad_request)?;
+    item_notes::set_item_note(&mut db.conn, &user, &query.sha256, &request.note).await?;
+    load_item_note(&mut db.conn, &user, &query.sha256)
+        .await
+        .map(Json)
+}
+
+#[utoipa::path(
+    delete,
+    operation_id = "delete_item_note",
+    path = "/api/items/item/notes",
+    tag = "items",
+    summary = "Delete the user's note on an item",
+    params(DbQueryParams, ItemNoteQuery),
+    responses(
+        (status = 200, description = "Deleted", body = ItemNoteDeleteResponse),
+        (status = 404, description = "The user has no note on this item")
+    )
+)]
+pub async fn delete_item_note(
+    mut db: DbConnection<UserDataWrite>,
+    Query(query): Query<ItemNoteQuery>,
+    identity: Option<Extension<RequestIdentity>>,
+) -> ApiResult<Json<ItemNoteDeleteResponse>> {
+    let user = scoped_user(
+        identity.as_deref(),
+        query.user.as_deref(),
+        UserAccess::Write,
+    )?;
+    if !item_notes::delete_item_note(&mut db.conn, &user, &query.sha256).await? {
+        return Err(ApiError::not_found("Note not found"));
+    }
+    Ok(Json(ItemNoteDeleteResponse {
+        message: "Note deleted".to_string(),
+    }))
+}
+
+#[utoipa::path(
+    get,
+    operation_id = "export_item_notes",
+    path = "/api/items/notes/export",
+    tag = "items",
+    summary = "Export item notes",
+    description = "Returns all of the user's notes, most recently updated first, as a portable document that `/api/items/notes/import` accepts on any instance.",
+    params(DbQueryParams, ItemNotesUserQuery),
+    responses(
+        (status = 200, description = "Export document", body = ItemNotesExport)
+    )
+)]
+pub async fn export_item_notes(
+    mut db: DbConnection<ReadOnly>,
+    Query(query): Query<ItemNotesUserQuery>,
+    identity: Option<Extension<RequestIdentity>>,
+) -> ApiResult<Json<ItemNotesExport>> {
+    let user = scoped_user(identity.as_deref(), query.user.as_deref(), UserAccess::Read)?;
+    let notes = item_notes::list_item_notes(&mut db.conn, &user)
+        .await?
+        .into_iter()
+        .map(|record| ItemNoteExportEntry {
+            sha256: record.sha256,
+            note: record.note,
+        })
+        .collect();
+    Ok(Json(ItemNotesExport {
+        format: EXPORT_FORMAT.to_string(),
+        version: EXPORT_VERSION,
+        notes,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    operation_id = "import_item_notes",
+    path = "/api/items/notes/import",
+    tag = "items",
+    summary = "Import item notes",
+    description = "Imports a document produced by `/api/items/notes/export`. Every entry is validated before anything is written; one invalid entry rejects the whole import.\nItems the user already has a note on are skipped unless `overwrite` is set. Notes are imported by sha256 whether or not the item is in this index, so they apply once it is scanned.",
+    params(DbQueryParams, ItemNotesImportQuery),
+    request_body(content = ItemNotesExport),
+    responses(
+        (status = 200, description = "Import summary", body = ItemNotesImportResponse),
+        (status = 400, description = "Unsupported document or invalid entry")
+    )
+)]
+pub async fn import_item_notes(
+    mut db: DbConnection<UserDataWrite>,
+    Query(query): Query<ItemNotesImportQuery>,
+    identity: Option<Extension<RequestIdentity>>,
+    Json(document): Json<ItemNotesExport>,
+) -> ApiResult<Json<ItemNotesImportResponse>> {
+    let user = scoped_user(
+        identity.as_deref(),
+        query.user.as_deref(),
+        UserAccess::Write,
+    )?;
+    validate_document(&document).map_err(ApiError::bad_request)?;
+
+    begin_transaction(&mut db.conn).await?;
+    let result: ApiResult<ItemNotesImportResponse> = async {
+        let mut response = ItemNotesImportResponse {
+            created: Vec::new(),
+            replaced: Vec::new(),
+            skipped: Vec::new(),
+        };
+        for entry in document.notes {
+            let created =
+                item_notes::insert_item_note(&mut db.conn, &user, &entry.sha256, &entry.note)
+                    .await?;
+            if created {
+                response.created.push(entry.sha256);
+            } else if query.overwrite {
+                item_notes::set_item_note(&mut db.conn, &user, &entry.sha256, &entry.note).await?;
+                response.replaced.push(entry.sha256);
+            } else {
+                response.skipped.push(entry.sha256);
+            }
+        }
+        Ok(response)
+    }
+    .await;
+
+    match result {
+        Ok(response) => {
+            commit_transaction(&mut db.conn).await?;
+            Ok(Json(response))
+        }
+        Err(err) => {
+            let _ = rollback_transaction(&mut db.conn).await;
+            Err(err)
+        }
+    }
+}
+
+fn validate_document(document: &ItemNotesExport) -> Result<(), String> {
+    if document.format != EXPORT_FORMAT || document.version != EXPORT_VERSION {
+        return Err(format!(
+            "Unsupported item notes export: expected format '{EXPORT_FORMAT}' version {EXPORT_VERSION}"
+        ));
+    }
+    for (index, entry) in document.notes.iter().enumerate() {
+        validate_entry(&entry.sha256, &entry.note)
+            .map_err(|err| format!("Note for '{}': {err}", entry.sha256))?;
+        if document.notes[..index]
+            .iter()
+            .any(|earlier| earlier.sha256 == entry.sha256)
+        {
+            return Err(format!(
+                "Note for '{}' appears more than once",
+                entry.sha256
+            ));
+        }
+    }
+    Ok(())
+}
+
+async fn begin_transaction(conn: &mut sqlx::SqliteConnection) -> ApiResult<()> {
+    sqlx::query("BEGIN TRANSACTION")
+        .execute(conn)
+        .await
+        .map_err(|err| {
+            tracing::error!(error = %err, "failed to start transaction");
+            ApiError::internal("Failed to start transaction")
+        })?;
+    Ok(())
+}
+
+async fn commit_transaction(conn: &mut sqlx::SqliteConnection) -> ApiResult<()> {
+    sqlx::query("COMMIT").execute(conn).await.map_err(|err| {
+        tracing::error!(error = %err, "failed to commit transaction");
+        ApiError::internal("Failed to commit transaction")
+    })?;
+    Ok(())
+}
+
+async fn rollback_transaction(conn: &mut sqlx::SqliteConnection) -> ApiResult<()> {
+    sqlx::query("ROLLBACK").execute(conn).await.map_err(|err| {
+        tracing::error!(error = %err, "failed to rollback transaction");
+        ApiError::internal("Failed to rollback transaction")
+    })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::migrations::setup_test_databases;
+
+    fn entry(sha256: &str, note: &str) -> ItemNoteExportEntry {
+        ItemNoteExportEntry {
+            sha256: sha256.to_string(),
+            note: note.to_string(),
+        }
+    }
+
+    // Ensures a note is unique per user and item, and saving again replaces it.
+    #[tokio::test]
+    async fn notes_are_unique_per_user_and_item() {
+        let mut dbs = setup_test_databases().await;
+        let conn = &mut dbs.index_conn;
+        item_notes::set_item_note(conn, "user", "abc", "draft")
+            .await
+            .unwrap();
+        item_notes::set_item_note(conn, "user", "abc", "final approved version")
+            .await
+            .unwrap();
+        item_notes::set_item_note(conn, "other", "abc", "theirs")
+            .await
+            .unwrap();
+        assert!(
+            !item_notes::insert_item_note(conn, "user", "abc", "ignored")
+                .await
+                .unwrap()
+        );
+
+        let note = load_item_note(conn, "user", "abc").await.unwrap();
+        assert_eq!(note.note, "final approved version");
+        assert_eq!(
+            item_notes::list_item_notes(conn, "user")
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+
+        assert!(
+            item_notes::delete_item_note(conn, "user", "abc")
+                .await
+                .unwrap()
+        );
+        assert!(load_item_note(conn, "user", "abc").await.is_err());
+        assert_eq!(
+            item_notes::delete_all_item_notes(conn, "abc")
+                .await
+                .unwrap(),
+            1
+        );
+    }
+
+    // Ensures imports reject foreign documents, blank notes and duplicate items.
+    #[test]
+    fn import_document_validation() {
+        let document = |notes| ItemNotesExport {
+            format: EXPORT_FORMAT.to_string(),
+            version: EXPORT_VERSION,
+            notes,
+        };
+        assert_eq!(validate_document(&document(vec![entry("a", "x")])), Ok(()));
+        assert!(validate_document(&document(vec![entry("a", "  ")])).is_err());
+        assert!(validate_document(&document(vec![entry("a", "x"), entry("a", "y")])).is_err());
+
+        let mut foreign = document(Vec::new());
+        foreign.format = "panoptikon.saved_queries".to_string();
+        assert!(validate_document(&foreign).is_err());
+    }
+}
@@ -4,6 +4,7 @@ use axum::{
     http::{HeaderMap, Response, StatusCode, header},
 };
 use axum_extra::extract::Query;
+use base64::{Engine as _, engine::general_purpose};
 
 use serde::{Deserialize, Serialize};
 use std::io::SeekFrom;
@@ -16,23 +17,47 @@ use utoipa::{IntoParams, ToSchema};
 use crate::api::db_params::DbQueryParams;
 use crate::api::utils::{content_disposition_value, iso_to_system_time, strip_non_latin1_chars};
 use crate::api_error::ApiError;
+use crate::db::bookmarks::delete_item_bookmarks;
+use crate::db::embeddings::get_item_embeddings;
+use crate::db::files::get_blurhash;
+use crate::db::index_writer::{IndexDbWriterMessage, call_index_db_writer};
+use crate::db::item_meta::delete_all_item_meta;
+use crate::db::item_notes::delete_all_item_notes;
+use crate::db::item_purge::{ItemPurgeCounts, stale_items};
 use crate::db::items::{
-    ExtractedTextRecord, FileRecord, ItemIdentifierType, ItemRecord, get_all_tags_for_item,
-    get_extracted_text_for_item, get_item_metadata, get_item_metadata_unchecked, get_text_by_ids,
-    get_thumbnail_bytes,
+    ExtractedTextRecord, FileRecord, ItemIdentifierType, ItemMetadata, ItemRecord, UndecodableFile,
+    get_all_tags_for_item, get_extracted_text_for_item, get_frame_tags_for_item, get_item_metadata,
+    get_item_metadata_unchecked, get_text_by_ids, get_undecodable_files,
 };
-use crate::db::{DbConnection, ReadOnlyNoUserData};
-use crate::jobs::files::format_system_time;
+use crate::db::manual_tags::ManualTag;
+use crate::db::storage::{
+    FrameInfo, OutdatedVisuals, StoredVisual, get_frame, get_frame_infos, get_thumbnail, has_frame,
+    visuals_dir,
+};
+use crate::db::{DbConnection, ReadOnlyNoUserData, UserDataWrite};
+use crate::jobs::files::{FRAME_PROCESS_VERSION, format_system_time};
+use crate::jobs::on_demand_visuals;
+use crate::jobs::queue::{JobType, get_queue_status};
+use crate::pql::embedding_utils::deserialize_f32;
 
 type ApiResult<T> = std::result::Result<T, ApiError>;
 
 const PLACEHOLDER_PNG: &[u8] = include_bytes!("assets/placeholder.png");
+/// Largest side, in pixels, of a decoded blurhash placeholder. Blurhashes
+/// hold a handful of color components, so bigger images add no detail.
+const MAX_PLACEHOLDER_SIZE: u32 = 128;
 
 /// Ceiling on opening/statting a file before giving up on it. Indexed files
 /// can live on network shares; a hung mount must not stall requests (or, with
 /// no timeout, tokio's blocking pool) indefinitely.
 const FILE_IO_TIMEOUT: Duration = Duration::from_secs(10);
 
+/// How long a thumbnail request waits for on-demand visuals before
+/// answering with a placeholder and `Retry-After`.
+const ON_DEMAND_VISUALS_WAIT: Duration = Duration::from_secs(3);
+/// `Retry-After` seconds sent with that placeholder.
+const ON_DEMAND_RETRY_AFTER_SECS: u64 = 5;
+
 /// Minimum sha256 prefix length (hex chars) still treated as
 /// content-addressed for caching. The pinboard stores 10-char prefixes as
 /// item identity, so it must be <= 10; at 40 bits the chance a given cached
@@ -67,6 +92,19 @@ fn if_none_match_matches(header_value: &str, etag: &str) -> bool {
             .any(|candidate| candidate == etag)
 }
 
+/// If-Modified-Since is satisfied when the resource is no newer than the
+/// given date. Dates that don't parse only match the exact Last-Modified.
+fn not_modified_since(if_modified_since: &str, last_modified: &str) -> bool {
+    let if_modified_since = if_modified_since.trim();
+    match (
+        httpdate::parse_http_date(if_modified_since),
+        httpdate::parse_http_date(last_modified),
+    ) {
+        (Ok(since), Ok(modified)) => modified <= since,
+        _ => if_modified_since == last_modified,
+    }
+}
+
 fn not_modified_response(
     etag: &str,
     cache_control: &str,
@@ -132,6 +170,9 @@ pub(crate) struct ItemTagsQuery {
     confidence_threshold: f64,
     /// Maximum number of tags to return for each *setter, namespace pair* (default: all). Higher confidence tags are given priority.
     limit_per_namespace: Option<usize>,
+    #[serde(default)]
+    /// Also return the tags of each video frame, for models that store them
+    per_frame: bool,
 }
 
 #[derive(Deserialize, IntoParams)]
@@ -173,6 +214,10 @@ pub(crate) struct ItemRecordResponse {
     #[schema(required)]
     blurhash: Option<String>,
     time_added: String,
+    /// The file pinned as the item's primary file; it is listed first in
+    /// `files` and picked for the item when searches group files by item.
+    #[schema(required)]
+    primary_file_id: Option<i64>,
 }
 
 #[derive(Serialize, ToSchema)]
@@ -182,6 +227,13 @@ pub(crate) struct FileRecordResponse {
     path: String,
     last_modified: String,
     filename: String,
+    /// When this path was first indexed, kept across modification rescans.
+    /// The item's `time_added` is when its content was first seen.
+    #[schema(required)]
+    time_added: Option<String>,
+    /// When a scan last found this file on disk
+    #[schema(required)]
+    last_seen: Option<String>,
 }
 
 #[derive(Serialize, ToSchema)]
@@ -192,6 +244,30 @@ pub(crate) struct TextResponse {
 #[derive(Serialize, ToSchema)]
 pub(crate) struct TagResponse {
     tags: Vec<(String, String, f64, String)>,
+    /// Per-frame tags, when requested with `per_frame`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frames: Option<Vec<FrameTags>>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct FrameTags {
+    /// Position of the frame among the frames sent to the model, from 0
+    frame_index: i64,
+    /// Same tuples as `tags`
+    tags: Vec<(String, String, f64, String)>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub(crate) struct ManualTagsRequest {
+    /// Tags to add or remove
+    tags: Vec<ManualTag>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct ManualTagsResponse {
+    /// Number of tags the item gained or lost. Tags it already had (or did
+    /// not have) are not counted.
+    changed: u64,
 }
 
 #[derive(Deserialize, IntoParams)]
@@ -206,6 +282,21 @@ pub(crate) struct ThumbnailQuery {
     big: bool,
 }
 
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct PlaceholderQuery {
+    /// The sha256 of the item
+    sha256: String,
+    /// Width of the placeholder in pixels, capped at 128
+    #[serde(default = "default_placeholder_size")]
+    #[param(default = 32, minimum = 1, maximum = 128)]
+    width: u32,
+    /// Height of the placeholder in pixels, capped at 128
+    #[serde(default = "default_placeholder_size")]
+    #[param(default = 32, minimum = 1, maximum = 128)]
+    height: u32,
+}
+
 #[utoipa::path(
     get,
     operation_id = "item_file",
@@ -245,6 +336,196 @@ pub async fn item_file(
     .await
 }
 
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct FrameQuery {
+    /// The item's full sha256 hash
+    sha256: String,
+    /// The frame index
+    #[serde(default)]
+    #[param(default = 0, minimum = 0)]
+    index: i64,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct FramesMetaQuery {
+    /// The item's full sha256 hash
+    sha256: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct FramesMetaResponse {
+    /// Stored frames in index order; empty when the item has none.
+    frames: Vec<FrameInfo>,
+}
+
+#[utoipa::path(
+    get,
+    operation_id = "item_frame",
+    path = "/api/items/item/frames",
+    tag = "items",
+    summary = "Get a stored frame of an item",
+    description = "Returns frame `index` of the frames extracted from a video when it was indexed, as a JPEG. Frames are read from the database, never from the original file. See /api/items/item/frames/meta for the available indexes.",
+    params(DbQueryParams, FrameQuery),
+    responses(
+        (status = 200, description = "Frame image"),
+        (status = 404, description = "No such frame")
+    )
+)]
+pub async fn item_frame(
+    mut db: DbConnection<ReadOnlyNoUserData>,
+    Query(query): Query<FrameQuery>,
+    request_headers: HeaderMap,
+) -> ApiResult<Response<Body>> {
+    let dir = visuals_dir(&db.index_db);
+    frame_response(
+        &mut db.conn,
+        &dir,
+        &query.sha256,
+        query.index,
+        &request_headers,
+    )
+    .await
+}
+
+/// Stored frames are keyed by full content hash, like stored thumbnails, so
+/// they are immutable under their URL.
+async fn frame_response(
+    conn: &mut sqlx::SqliteConnection,
+    dir: &Path,
+    sha256: &str,
+    index: i64,
+    request_headers: &HeaderMap,
+) -> ApiResult<Response<Body>> {
+    let not_found = || ApiError::not_found("Frame not found");
+    let (visual, version) = get_frame(conn, dir, sha256, index)
+        .await?
+        .ok_or_else(not_found)?;
+    let etag = format!("\"{sha256}-frame{index}-v{version}\"");
+    let filename = format!("{sha256}-frame{index}.jpg");
+    stored_visual_response(visual, &filename, &etag, CACHE_IMMUTABLE, request_headers)
+        .await?
+        .ok_or_else(not_found)
+}
+
+#[utoipa::path(
+    get,
+    operation_id = "item_frames_meta",
+    path = "/api/items/item/frames/meta",
+    tag = "items",
+    summary = "List the stored frames of an item",
+    description = "Returns the index and dimensions of each frame stored for an item, for fetching them from /api/items/item/frames.",
+    params(DbQueryParams, FramesMetaQuery),
+    responses(
+        (status = 200, description = "Stored frames", body = FramesMetaResponse)
+    )
+)]
+pub async fn item_frames_meta(
+    mut db: DbConnection<ReadOnlyNoUserData>,
+    Query(query): Query<FramesMetaQuery>,
+) -> ApiResult<Json<FramesMetaResponse>> {
+    let frames = get_frame_infos(&mut db.conn, &query.sha256).await?;
+    Ok(Json(FramesMetaResponse { frames }))
+}
+
+/// How embedding vectors are written in API responses.
+#[derive(Clone, Copy, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum EmbeddingFormat {
+    /// An array of floats
+    #[default]
+    Json,
+    /// Base64 of the stored blob (f32, little-endian)
+    Base64,
+}
+
+/// An embedding vector in the requested [`EmbeddingFormat`].
+#[derive(Serialize, ToSchema)]
+#[serde(untagged)]
+pub(crate) enum EmbeddingVector {
+    Floats(Vec<f32>),
+    Base64(String),
+}
+
+/// Encodes a stored embedding blob for a response.
+pub(crate) fn encode_embedding(blob: &[u8], format: EmbeddingFormat) -> ApiResult<EmbeddingVector> {
+    match format {
+        EmbeddingFormat::Json => {
+            deserialize_f32(blob)
+                .map(EmbeddingVector::Floats)
+                .map_err(|err| {
+                    tracing::error!(error = %err, "malformed stored embedding");
+                    ApiError::internal("Failed to read embeddings")
+                })
+        }
+        EmbeddingFormat::Base64 => Ok(EmbeddingVector::Base64(
+            general_purpose::STANDARD.encode(blob),
+        )),
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct ItemEmbeddingsQuery {
+    /// The item's full sha256 hash
+    sha256: String,
+    /// The setter (model) that produced the embeddings, e.g. "clip/ViT-H-14"
+    setter: String,
+    /// How to encode each vector
+    #[serde(default)]
+    #[param(inline)]
+    format: EmbeddingFormat,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct ItemEmbedding {
+    /// Index of the embedding within the item (frame, page, or text chunk
+    /// offset)
+    index: i64,
+    /// The item data the embedding was derived from (e.g. an extracted text
+    /// row); null for embeddings of the item itself
+    source_data_id: Option<i64>,
+    vector: EmbeddingVector,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct ItemEmbeddingsResponse {
+    /// Stored embeddings, origin embeddings first; empty when the setter
+    /// stored none for the item.
+    embeddings: Vec<ItemEmbedding>,
+}
+
+#[utoipa::path(
+    get,
+    operation_id = "item_embeddings",
+    path = "/api/items/item/embeddings",
+    tag = "items",
+    summary = "Get the stored embeddings of an item",
+    description = "Returns the embedding vectors a setter stored for an item, as float arrays or as base64 of the stored f32 little-endian blob.",
+    params(DbQueryParams, ItemEmbeddingsQuery),
+    responses(
+        (status = 200, description = "Stored embeddings", body = ItemEmbeddingsResponse)
+    )
+)]
+pub async fn item_embeddings(
+    mut db: DbConnection<ReadOnlyNoUserData>,
+    Query(query): Query<ItemEmbeddingsQuery>,
+) -> ApiResult<Json<ItemEmbeddingsResponse>> {
+    let stored = get_item_embeddings(&mut db.conn, &query.sha256, &query.setter).await?;
+    let embeddings = stored
+        .into_iter()
+        .map(|row| {
+            Ok(ItemEmbedding {
+                index: row.idx,
+                source_data_id: row.source_data_id,
+                vector: encode_embedding(&row.embedding, query.format)?,
+            })
+        })
+        .collect::<ApiResult<_>>()?;
+    Ok(Json(ItemEmbeddingsResponse { embeddings }))
+}
+
 #[utoipa::path(
     get,
     operation_id = "item_meta",
@@ -262,16 +543,18 @@ pub async fn item_meta(
     Query(query): Query<ItemQuery>,
 ) -> ApiResult<Json<ItemMetadataResponse>> {
     let item_data = get_item_metadata(&mut db.conn, &query.id, query.id_type).await?;
-    let Some(item) = item_data.item else {
-        return Err(ApiError::not_found("Item not found"));
-    };
+    let response =
+        item_metadata_response(item_data).ok_or_else(|| ApiError::not_found("Item not found"))?;
+    Ok(Json(response))
+}
 
-    let response = ItemMetadataResponse {
+/// The response body of `GET /api/items/item`; None when there is no item.
+pub(crate) fn item_metadata_response(metadata: ItemMetadata) -> Option<ItemMetadataResponse> {
+    let item = metadata.item?;
+    Some(ItemMetadataResponse {
         item: map_item_record(&item),
-        files: item_data.files.into_iter().map(map_file_record).collect(),
-    };
-
-    Ok(Json(response))
+        files: metadata.files.into_iter().map(map_file_record).collect(),
+    })
 }
 
 #[utoipa::path(
@@ -325,7 +608,7 @@ pub async fn item_text(
     path = "/api/items/item/tags",
     tag = "items",
     summary = "Get tags for an item",
-    description = "Returns the tags associated with a given item.\nThe response contains a list of tuples, where each tuple contains\nthe tag namespace, tag name, confidence, and setter name.\nThe `setters` parameter can be used to filter tags by the setter name.\nThe `confidence_threshold` parameter can be used to filter tags based on\nthe minimum confidence threshold",
+    description = "Returns the tags associated with a given item.\nThe response contains a list of tuples, where each tuple contains\nthe tag namespace, tag name, confidence, and setter name.\nThe `setters` parameter can be used to filter tags by the setter name.\nThe `confidence_threshold` parameter can be used to filter tags based on\nthe minimum confidence threshold.\nWith `per_frame=true`, models that store per-frame tags for videos also\nreturn each frame's tags under `frames`; `tags` is always the merged set.",
     params(DbQueryParams, ItemTagsQuery),
     responses(
         (status = 200, description = "Item tags", body = TagResponse)
@@ -350,8 +633,375 @@ pub async fn item_tags(
         query.limit_per_namespace,
     )
     .await?;
+    let frames = if query.per_frame {
+        let frames = get_frame_tags_for_item(
+            &mut db.conn,
+            item.id,
+            &query.setters,
+            query.confidence_threshold,
+            &query.namespaces,
+            query.limit_per_namespace,
+        )
+        .await?;
+        Some(
+            frames
+                .into_iter()
+                .map(|(frame_index, tags)| FrameTags { frame_index, tags })
+                .collect(),
+        )
+    } else {
+        None
+    };
+
+    Ok(Json(TagResponse { tags, frames }))
+}
+
+/// Resolves the item to its sha256 and rejects tags with an empty namespace
+/// or name.
+async fn manual_tags_target(
+    db: &mut DbConnection<ReadOnlyNoUserData>,
+    query: &ItemQuery,
+    request: &ManualTagsRequest,
+) -> ApiResult<String> {
+    if request
+        .tags
+        .iter()
+        .any(|tag| tag.namespace.trim().is_empty() || tag.name.trim().is_empty())
+    {
+        return Err(ApiError::bad_request(
+            "Tag namespace and name must not be empty",
+        ));
+    }
+    let item_data = get_item_metadata_unchecked(&mut db.conn, &query.id, query.id_type).await?;
+    let Some(item) = item_data.item else {
+        return Err(ApiError::not_found("Item not found"));
+    };
+    Ok(item.sha256)
+}
+
+#[utoipa::path(
+    post,
+    operation_id = "add_item_tags",
+    path = "/api/items/item/tags",
+    tag = "items",
+    summary = "Add tags to an item",
+    description = "Adds tags to an item by hand. They are stored under the reserved setter `manual:user` with confidence 1 and are returned, searched and filtered like any model's tags. Manual tags never cause a tagging model to skip the item.",
+    params(DbQueryParams, ItemQuery),
+    request_body(content = ManualTagsRequest, description = "The tags to add"),
+    responses(
+        (status = 200, description = "Number of tags added", body = ManualTagsResponse)
+    )
+)]
+pub async fn add_item_tags(
+    mut db: DbConnection<ReadOnlyNoUserData>,
+    Query(query): Query<ItemQuery>,
+    Json(request): Json<ManualTagsRequest>,
+) -> ApiResult<Json<ManualTagsResponse>> {
+    let item_sha256 = manual_tags_target(&mut db, &query, &request).await?;
+    let changed = call_index_db_writer(&db.index_db, |reply| IndexDbWriterMessage::AddManualTags {
+        item_sha256: item_sha256.clone(),
+        tags: request.tags.clone(),
+        reply,
+    })
+    .await?;
+    Ok(Json(ManualTagsResponse { changed }))
+}
+
+#[utoipa::path(
+    delete,
+    operation_id = "remove_item_tags",
+    path = "/api/items/item/tags",
+    tag = "items",
+    summary = "Remove manually added tags from an item",
+    description = "Removes tags added with the `manual:user` setter. Tags set by models are never removed. Tags no longer used by any item are deleted.",
+    params(DbQueryParams, ItemQuery),
+    request_body(content = ManualTagsRequest, description = "The tags to remove"),
+    responses(
+        (status = 200, description = "Number of tags removed", body = ManualTagsResponse)
+    )
+)]
+pub async fn remove_item_tags(
+    mut db: DbConnection<ReadOnlyNoUserData>,
+    Query(query): Query<ItemQuery>,
+    Json(request): Json<ManualTagsRequest>,
+) -> ApiResult<Json<ManualTagsResponse>> {
+    let item_sha256 = manual_tags_target(&mut db, &query, &request).await?;
+    let changed = call_index_db_writer(&db.index_db, |reply| {
+        IndexDbWriterMessage::RemoveManualTags {
+            item_sha256: item_sha256.clone(),
+            tags: request.tags.clone(),
+            reply,
+        }
+    })
+    .await?;
+    Ok(Json(ManualTagsResponse { changed }))
+}
 
-    Ok(Json(TagResponse { tags }))
+#[derive(Deserialize, ToSchema)]
+pub(crate) struct PrimaryFileRequest {
+    /// The file to pin, one of the item's files; null clears the pin
+    #[schema(required)]
+    file_id: Option<i64>,
+}
+
+#[utoipa::path(
+    put,
+    operation_id = "set_item_primary_file",
+    path = "/api/items/item/primary-file",
+    tag = "items",
+    summary = "Pin the primary file of an item",
+    description = "Pins one of an item's files as its primary file, replacing any earlier pin, or clears the pin when `file_id` is null. The primary file is listed first in the item's metadata, served for the item, and picked when a search groups files by item (`partition_by` `item_id`). Deleting the file, or a rescan finding new content at its path, clears the pin.",
+    params(DbQueryParams, ItemQuery),
+    request_body(content = PrimaryFileRequest, description = "The file to pin"),
+    responses(
+        (status = 200, description = "The item's metadata with the new pin", body = ItemMetadataResponse),
+        (status = 400, description = "The file does not belong to the item"),
+        (status = 404, description = "Item not found")
+    )
+)]
+pub async fn set_item_primary_file(
+    mut db: DbConnection<ReadOnlyNoUserData>,
+    Query(query): Query<ItemQuery>,
+    Json(request): Json<PrimaryFileRequest>,
+) -> ApiResult<Json<ItemMetadataResponse>> {
+    let item_data = get_item_metadata_unchecked(&mut db.conn, &query.id, query.id_type).await?;
+    let Some(item) = item_data.item else {
+        return Err(ApiError::not_found("Item not found"));
+    };
+    call_index_db_writer(&db.index_db, |reply| IndexDbWriterMessage::SetPrimaryFile {
+        item_id: item.id,
+        file_id: request.file_id,
+        reply,
+    })
+    .await?;
+    let item_id = item.id.to_string();
+    let item_data = get_item_metadata(&mut db.conn, &item_id, ItemIdentifierType::ItemId).await?;
+    let response =
+        item_metadata_response(item_data).ok_or_else(|| ApiError::not_found("Item not found"))?;
+    Ok(Json(response))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct ItemPurgeQuery {
+    /// The sha256 of the item to purge
+    sha256: String,
+    /// Must be true; guards against accidental deletes
+    #[serde(default)]
+    confirm: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct ItemPurgeResponse {
+    /// Rows removed from the index DB, per table
+    #[serde(flatten)]
+    pub index: ItemPurgeCounts,
+    /// Bookmarks removed from the user data DB, across all users
+    pub bookmarks: u64,
+    /// Notes removed from the user data DB, across all users
+    pub notes: u64,
+    /// Metadata fields removed from the user data DB
+    pub meta: u64,
+}
+
+#[utoipa::path(
+    delete,
+    operation_id = "purge_item",
+    path = "/api/items/item",
+    tag = "items",
+    summary = "Purge an item and all its data",
+    description = "Deletes an item from the index together with its file rows, extracted data, tags, embeddings, thumbnails and frames in one transaction, then removes its bookmarks, notes and metadata fields. The file on disk is not touched; if it is still under an included folder, the next scan adds it back as a new item. Requires `confirm=true`, and fails with 409 while a data extraction job is running on the index DB.",
+    params(DbQueryParams, ItemPurgeQuery),
+    responses(
+        (status = 200, description = "Rows removed per table", body = ItemPurgeResponse),
+        (status = 400, description = "confirm was not set"),
+        (status = 404, description = "Item not found"),
+        (status = 409, description = "A data extraction job is running")
+    )
+)]
+pub async fn purge_item(
+    mut db: DbConnection<UserDataWrite>,
+    Query(query): Query<ItemPurgeQuery>,
+) -> ApiResult<Json<ItemPurgeResponse>> {
+    if !query.confirm {
+        return Err(ApiError::bad_request(
+            "Purging an item cannot be undone; pass confirm=true",
+        ));
+    }
+    ensure_no_extraction_running(&db.index_db).await?;
+    let index = call_index_db_writer(&db.index_db, |reply| IndexDbWriterMessage::PurgeItem {
+        sha256: query.sha256.clone(),
+        reply,
+    })
+    .await?;
+    let bookmarks = delete_item_bookmarks(&mut db.conn, &query.sha256).await?;
+    let notes = delete_all_item_notes(&mut db.conn, &query.sha256).await?;
+    let meta = delete_all_item_meta(&mut db.conn, &query.sha256).await?;
+    tracing::info!(
+        index_db = %db.index_db,
+        sha256 = %query.sha256,
+        files = index.files,
+        item_data = index.item_data,
+        bookmarks,
+        notes,
+        meta,
+        "purged item"
+    );
+    Ok(Json(ItemPurgeResponse {
+        index,
+        bookmarks,
+        notes,
+        meta,
+    }))
+}
+
+/// Purges write through the index writer, which an extraction job also
+/// holds between batches; refuse rather than interleave with it.
+async fn ensure_no_extraction_running(index_db: &str) -> ApiResult<()> {
+    let status = get_queue_status().await?;
+    if status.queue.iter().any(|job| {
+        job.running && job.job_type == JobType::DataExtraction && job.index_db == index_db
+    }) {
+        return Err(ApiError::job_conflict(
+            "A data extraction job is running on this index DB; try again when it finishes",
+        ));
+    }
+    Ok(())
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct StaleItemPurgeQuery {
+    /// Purge items whose files were all last seen more than this many days ago
+    older_than_days: u32,
+    /// Only count the items that would be purged
+    #[serde(default)]
+    dry_run: bool,
+    /// Must be true unless dry_run is set; guards against accidental deletes
+    #[serde(default)]
+    confirm: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct StaleItemPurgeResponse {
+    dry_run: bool,
+    /// Items matched; on a dry run nothing was removed
+    candidates: u64,
+    /// Rows removed from the index DB, per table, summed over all items
+    #[serde(flatten)]
+    index: ItemPurgeCounts,
+    /// Bookmarks removed from the user data DB, across all users
+    bookmarks: u64,
+    /// Notes removed from the user data DB, across all users
+    notes: u64,
+    /// Metadata fields removed from the user data DB
+    meta: u64,
+}
+
+#[utoipa::path(
+    delete,
+    operation_id = "purge_stale_items",
+    path = "/api/items/stale",
+    tag = "items",
+    summary = "Purge items no longer seen on disk",
+    description = "Purges, like DELETE /api/items/item, every item none of whose files is available and all of whose files a scan last found (`last_seen`) more than `older_than_days` days ago. Only scans update `last_seen`, so folders that are no longer scanned age too. With `dry_run=true` nothing is removed and `candidates` is the number of items that would be; otherwise `confirm=true` is required. Fails with 409 while a data extraction job is running on the index DB.",
+    params(DbQueryParams, StaleItemPurgeQuery),
+    responses(
+        (status = 200, description = "Items matched and rows removed per table", body = StaleItemPurgeResponse),
+        (status = 400, description = "older_than_days was 0, or confirm was not set"),
+        (status = 409, description = "A data extraction job is running")
+    )
+)]
+pub async fn purge_stale_items(
+    mut db: DbConnection<UserDataWrite>,
+    Query(query): Query<StaleItemPurgeQuery>,
+) -> ApiResult<Json<StaleItemPurgeResponse>> {
+    if query.older_than_days == 0 {
+        return Err(ApiError::bad_request("older_than_days must be at least 1"));
+    }
+    if !query.dry_run && !query.confirm {
+        return Err(ApiError::bad_request(
+            "Purging items cannot be undone; pass confirm=true, or dry_run=true to count them",
+        ));
+    }
+    let candidates = stale_items(&mut db.conn, query.older_than_days).await?;
+    let mut response = StaleItemPurgeResponse {
+        dry_run: query.dry_run,
+        candidates: candidates.len() as u64,
+        index: ItemPurgeCounts::default(),
+        bookmarks: 0,
+        notes: 0,
+        meta: 0,
+    };
+    if query.dry_run || candidates.is_empty() {
+        return Ok(Json(response));
+    }
+    ensure_no_extraction_running(&db.index_db).await?;
+    for sha256 in candidates {
+        // One transaction per item, so a large purge never holds the writer
+        // for long and a failure keeps what was already purged.
+        let index = call_index_db_writer(&db.index_db, |reply| IndexDbWriterMessage::PurgeItem {
+            sha256: sha256.clone(),
+            reply,
+        })
+        .await?;
+        response.index.add(&index);
+        response.bookmarks += delete_item_bookmarks(&mut db.conn, &sha256).await?;
+        response.notes += delete_all_item_notes(&mut db.conn, &sha256).await?;
+        response.meta += delete_all_item_meta(&mut db.conn, &sha256).await?;
+    }
+    tracing::info!(
+        index_db = %db.index_db,
+        older_than_days = query.older_than_days,
+        items = response.index.items,
+        files = response.index.files,
+        bookmarks = response.bookmarks,
+        "purged stale items"
+    );
+    Ok(Json(response))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct UndecodableFilesQuery {
+    /// Page number
+    #[param(default = 1, minimum = 1)]
+    page: Option<i64>,
+    /// Page size
+    #[param(default = 1000, minimum = 1)]
+    page_size: Option<i64>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct UndecodableFilesResponse {
+    /// Available files of undecodable items, over all pages
+    count: i64,
+    files: Vec<UndecodableFile>,
+}
+
+#[utoipa::path(
+    get,
+    operation_id = "undecodable_files",
+    path = "/api/items/undecodable",
+    tag = "items",
+    summary = "List files of undecodable videos",
+    description = "Lists the available files of items none of whose video frames could be decoded, even by the fallback extraction steps, ordered by path. Items are marked by the scan that indexes them (or backfills their visuals) and by extraction jobs that fail the same way; a later scan that decodes the video clears the mark.",
+    params(DbQueryParams, UndecodableFilesQuery),
+    responses(
+        (status = 200, description = "Files of undecodable items", body = UndecodableFilesResponse)
+    )
+)]
+pub async fn undecodable_files(
+    mut db: DbConnection<ReadOnlyNoUserData>,
+    Query(query): Query<UndecodableFilesQuery>,
+) -> ApiResult<Json<UndecodableFilesResponse>> {
+    let (count, files) = get_undecodable_files(
+        &mut db.conn,
+        query.page.unwrap_or(1).max(1),
+        query.page_size.unwrap_or(1000).max(1),
+    )
+    .await?;
+    Ok(Json(UndecodableFilesResponse { count, files }))
 }
 
 #[utoipa::path(
@@ -380,7 +1030,7 @@ pub async fn texts_any(
     path = "/api/items/item/thumbnail",
     tag = "items",
     summary = "Get thumbnail for an item",
-    description = "Returns a thumbnail for a given item.\nThe thumbnail may be a thumbnail,\nthe unmodified original image (only for images),\nor a placeholder image generated on the fly.\nGIFs are always returned as the original file.\nFor video thumbnails, the `big` parameter can be used to\nselect between the 2x2 frame grid (big=True) or the first frame from the grid (big=False).",
+    description = "Returns a thumbnail for a given item.\nThe thumbnail may be a thumbnail,\nthe unmodified original image (only for images),\nor a placeholder image generated on the fly.\nGIFs are always returned as the original file.\nFor video thumbnails, the `big` parameter can be used to\nselect between the 2x2 frame grid (big=True) or the first frame from the grid (big=False).\nItems stored without visuals (e.g. by a fast scan) get them generated on the first request. If that takes longer than a few seconds, the response is the item's blurhash placeholder (or the generic one) with a `Retry-After` header; images are served from their original file meanwhile.",
     params(DbQueryParams, ThumbnailQuery),
     responses(
         (status = 200, description = "Item thumbnail image")
@@ -401,8 +1051,11 @@ pub async fn item_thumbnail(
     }
 
     let content_addressed = is_content_addressed(query.id_type, &query.id);
+    let dir = visuals_dir(&db.index_db);
     match thumbnail_response(
         &mut db.conn,
+        &db.index_db,
+        &dir,
         &item,
         &item_data.files,
         query.big,
@@ -419,6 +1072,89 @@ pub async fn item_thumbnail(
     }
 }
 
+#[utoipa::path(
+    get,
+    operation_id = "item_placeholder",
+    path = "/api/items/item/placeholder",
+    tag = "items",
+    summary = "Get a blurred placeholder image for an item",
+    description = "Decodes the item's stored BlurHash into a PNG of the requested size (at most 128x128), for clients that cannot decode BlurHash themselves.\nItems without a BlurHash, and unknown items, get a 1x1 transparent PNG instead of an error so image elements do not break.",
+    params(DbQueryParams, PlaceholderQuery),
+    responses(
+        (status = 200, description = "Placeholder PNG", content_type = "image/png")
+    )
+)]
+pub async fn item_placeholder(
+    mut db: DbConnection<ReadOnlyNoUserData>,
+    Query(query): Query<PlaceholderQuery>,
+    request_headers: HeaderMap,
+) -> ApiResult<Response<Body>> {
+    let blurhash = get_blurhash(&mut db.conn, &query.sha256).await?;
+    placeholder_response(
+        &query.sha256,
+        blurhash.as_deref(),
+        query.width,
+        query.height,
+        &request_headers,
+    )
+}
+
+fn placeholder_response(
+    sha256: &str,
+    blurhash: Option<&str>,
+    width: u32,
+    height: u32,
+    request_headers: &HeaderMap,
+) -> ApiResult<Response<Body>> {
+    let width = width.clamp(1, MAX_PLACEHOLDER_SIZE);
+    let height = height.clamp(1, MAX_PLACEHOLDER_SIZE);
+    match blurhash.and_then(|blurhash| blurhash_png(blurhash, width, height)) {
+        // The blurhash is computed from the content, so the sha256 pins it.
+        Some(png) => bytes_response(
+            png,
+            "image/png",
+            &format!("{sha256}-placeholder.png"),
+            &format!("\"{sha256}-placeholder-{width}x{height}\""),
+            CACHE_IMMUTABLE,
+            request_headers,
+        ),
+        // Revalidated: a later scan may still compute the blurhash.
+        None => bytes_response(
+            transparent_png(),
+            "image/png",
+            "placeholder.png",
+            "\"placeholder-empty\"",
+            CACHE_REVALIDATE,
+            request_headers,
+        ),
+    }
+}
+
+/// Decodes a blurhash into a PNG; None if the stored string is malformed.
+fn blurhash_png(blurhash: &str, width: u32, height: u32) -> Option<Vec<u8>> {
+    let pixels = match blurhash::decode(blurhash, width, height, 1.0) {
+        Ok(pixels) => pixels,
+        Err(err) => {
+            tracing::warn!(error = %err, blurhash, "failed to decode blurhash");
+            return None;
+        }
+    };
+    encode_png(image::RgbaImage::from_raw(width, height, pixels)?)
+}
+
+fn transparent_png() -> Vec<u8> {
+    encode_png(image::RgbaImage::new(1, 1)).unwrap_or_default()
+}
+
+fn encode_png(image: image::RgbaImage) -> Option<Vec<u8>> {
+    let mut buffer = std::io::Cursor::new(Vec::new());
+    image
+        .write_to(&mut buffer, image::ImageFormat::Png)
+        .inspect_err(|err| tracing::error!(error = %err, "failed to encode placeholder png"))
+        .ok()?;
+    Some(buffer.into_inner())
+}
+
 fn display_filename(file: &FileRecord) -> String {
     let filename = strip_non_latin1_chars(&file.filename);
     if filename.is_empty() {
@@ -428,8 +1164,11 @@ fn display_filename(file: &FileRecord) -> String {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn thumbnail_response(
     conn: &mut sqlx::SqliteConnection,
+    index_db: &str,
+    dir: &Path,
     item: &ItemRecord,
     files: &[FileRecord],
     big: bool,
@@ -460,22 +1199,42 @@ async fn thumbnail_response(
     // from exactly the content the URL names, however the disk file has
     // changed since, so content-addressed requests stay fully immutable.
     let sha256 = &item.sha256;
-    if let Some(buffer) = get_thumbnail_bytes(conn, sha256, index).await? {
-        let etag = format!("\"{sha256}-thumb{index}\"");
-        let filename = format!("{original_filename_no_ext}.jpg");
-        let cache_control = if content_addressed {
-            CACHE_IMMUTABLE
-        } else {
-            CACHE_REVALIDATE
-        };
-        return bytes_response(
-            buffer,
-            "image/jpeg",
-            &filename,
-            &etag,
-            cache_control,
-            request_headers,
-        );
+    let filename = format!("{original_filename_no_ext}.jpg");
+    if let Some(response) = stored_thumbnail_response(
+        conn,
+        dir,
+        sha256,
+        index,
+        &filename,
+        request_headers,
+        content_addressed,
+    )
+    .await?
+    {
+        return Ok(response);
+    }
+
+    if let Some(target) = on_demand_target(conn, item, files).await? {
+        let pending = on_demand_visuals::request_visuals(index_db, target);
+        // Images keep being served from their original file meanwhile.
+        if !mime.starts_with("image") {
+            if !on_demand_visuals::wait_for_visuals(pending, ON_DEMAND_VISUALS_WAIT).await {
+                return Ok(pending_placeholder_response(item, original_filename_no_ext));
+            }
+            if let Some(response) = stored_thumbnail_response(
+                conn,
+                dir,
+                sha256,
+                index,
+                &filename,
+                request_headers,
+                content_addressed,
+            )
+            .await?
+            {
+                return Ok(response);
+            }
+        }
     }
 
     if mime.starts_with("image") {
@@ -496,6 +1255,85 @@ async fn thumbnail_response(
     )
 }
 
+async fn stored_thumbnail_response(
+    conn: &mut sqlx::SqliteConnection,
+    dir: &Path,
+    sha256: &str,
+    index: i64,
+    filename: &str,
+    request_headers: &HeaderMap,
+    content_addressed: bool,
+) -> ApiResult<Option<Response<Body>>> {
+    let Some((visual, version)) = get_thumbnail(conn, dir, sha256, index).await? else {
+        return Ok(None);
+    };
+    let etag = format!("\"{sha256}-thumb{index}-v{version}\"");
+    let cache_control = if content_addressed {
+        CACHE_IMMUTABLE
+    } else {
+        CACHE_REVALIDATE
+    };
+    // A thumbnail file that has gone missing falls through to the original
+    // image or the placeholder, like a missing row.
+    stored_visual_response(visual, filename, &etag, cache_control, request_headers).await
+}
+
+/// The item to generate visuals for on demand, if it has none stored: any
+/// item of a type the scanner renders that lacks a thumbnail, except images
+/// that already have a blurhash (those are served from their file by
+/// design). None when no indexed file is on disk.
+async fn on_demand_target(
+    conn: &mut sqlx::SqliteConnection,
+    item: &ItemRecord,
+    files: &[FileRecord],
+) -> ApiResult<Option<OutdatedVisuals>> {
+    let mime = item.mime_type.as_str();
+    let renderable = ["image/", "video/", "audio/", "application/pdf", "text/html"]
+        .iter()
+        .any(|prefix| mime.starts_with(prefix));
+    if !renderable || (mime.starts_with("image") && item.blurhash.is_some()) {
+        return Ok(None);
+    }
+    let Some(file) = files.iter().find(|file| file.disk_path().is_file()) else {
+        return Ok(None);
+    };
+    let frames_outdated =
+        mime.starts_with("video") && !has_frame(conn, &item.sha256, FRAME_PROCESS_VERSION).await?;
+    Ok(Some(OutdatedVisuals {
+        sha256: item.sha256.clone(),
+        mime_type: item.mime_type.clone(),
+        thumbnails_outdated: true,
+        frames_outdated,
+        path: Some(file.disk_path()),
+        duration: item.duration,
+        video_tracks: item.video_tracks,
+    }))
+}
+
+/// Stand-in while on-demand visuals are still being generated: the item's
+/// blurhash if it has one, the generic placeholder otherwise, never cached.
+fn pending_placeholder_response(item: &ItemRecord, filename_stem: &str) -> Response<Body> {
+    let png = item
+        .blurhash
+        .as_deref()
+        .and_then(|blurhash| blurhash_png(blurhash, 32, 32))
+        .unwrap_or_else(|| PLACEHOLDER_PNG.to_vec());
+    let len = png.len() as u64;
+    let mut response = body_response(
+        Body::from(png),
+        len,
+        "image/png",
+        &format!("{filename_stem}.png"),
+        &format!("\"{}-pending\"", item.sha256),
+        "no-store",
+        None,
+    );
+    response
+        .headers_mut()
+        .insert(header::RETRY_AFTER, ON_DEMAND_RETRY_AFTER_SECS.into());
+    response
+}
+
 #[derive(Debug, PartialEq, Eq)]
 enum RangeOutcome {
     /// No usable Range header: serve the whole file with 200.
@@ -610,7 +1448,7 @@ async fn file_response(
     Err(last_error)
 }
 
-async fn open_file_with_timeout(path: &str) -> ApiResult<tokio::fs::File> {
+async fn open_file_with_timeout(path: &Path) -> ApiResult<tokio::fs::File> {
     match tokio::time::timeout(FILE_IO_TIMEOUT, tokio::fs::File::open(path)).await {
         Ok(Ok(file)) => Ok(file),
         Ok(Err(err)) => {
@@ -618,7 +1456,7 @@ async fn open_file_with_timeout(path: &str) -> ApiResult<tokio::fs::File> {
             Err(ApiError::not_found("No file found for item"))
         }
         Err(_) => {
-            tracing::error!(path = %path, "timed out opening file");
+            tracing::error!(path = %path.display(), "timed out opening file");
             Err(ApiError::internal("Timed out opening file"))
         }
     }
@@ -632,7 +1470,7 @@ async fn try_file_response(
     content_addressed: bool,
 ) -> ApiResult<Response<Body>> {
     let filename = display_filename(file);
-    let mut file_handle = open_file_with_timeout(&file.path).await?;
+    let mut file_handle = open_file_with_timeout(&file.disk_path()).await?;
     // The size on disk is authoritative for range math; the DB value can be
     // stale if the file changed since the last scan.
     let metadata = match tokio::time::timeout(FILE_IO_TIMEOUT, file_handle.metadata()).await {
@@ -696,7 +1534,10 @@ async fn try_file_response(
         .get(header::IF_MODIFIED_SINCE)
         .and_then(|value| value.to_str().ok())
     {
-        if last_modified.as_deref() == Some(if_modified_since.trim()) {
+        if last_modified
+            .as_deref()
+            .is_some_and(|last_modified| not_modified_since(if_modified_since, last_modified))
+        {
             return Ok(not_modified_response(
                 &etag,
                 cache_control,
@@ -808,17 +1649,113 @@ fn bytes_response(
     cache_control: &str,
     request_headers: &HeaderMap,
 ) -> ApiResult<Response<Body>> {
-    if let Some(if_none_match) = request_headers
-        .get(header::IF_NONE_MATCH)
-        .and_then(|value| value.to_str().ok())
-    {
-        if if_none_match_matches(if_none_match, etag) {
-            return Ok(not_modified_response(etag, cache_control, None));
+    if let Some(response) = conditional_response(etag, cache_control, None, request_headers) {
+        return Ok(response);
+    }
+    let len = bytes.len() as u64;
+    Ok(body_response(
+        Body::from(bytes),
+        len,
+        media_type,
+        filename,
+        etag,
+        cache_control,
+        None,
+    ))
+}
+
+/// Serves a stored thumbnail or frame as JPEG. Visuals kept as files are
+/// streamed from disk under the same validators and cache policy as blobs,
+/// plus their file's mtime as Last-Modified (blob rows record no time).
+/// `None` means the file is missing.
+async fn stored_visual_response(
+    visual: StoredVisual,
+    filename: &str,
+    etag: &str,
+    cache_control: &str,
+    request_headers: &HeaderMap,
+) -> ApiResult<Option<Response<Body>>> {
+    let path = match visual {
+        StoredVisual::Blob(bytes) => {
+            return bytes_response(
+                bytes,
+                "image/jpeg",
+                filename,
+                etag,
+                cache_control,
+                request_headers,
+            )
+            .map(Some);
         }
+        StoredVisual::File(path) => path,
+    };
+    let file = match tokio::fs::File::open(&path).await {
+        Ok(file) => file,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            tracing::warn!(path = %path.display(), "stored visual file is missing");
+            return Ok(None);
+        }
+        Err(err) => {
+            tracing::error!(error = %err, path = %path.display(), "failed to open visual file");
+            return Err(ApiError::internal("Failed to read stored visual"));
+        }
+    };
+    let metadata = file.metadata().await.map_err(|err| {
+        tracing::error!(error = %err, path = %path.display(), "failed to read visual file metadata");
+        ApiError::internal("Failed to read stored visual")
+    })?;
+    let last_modified = metadata.modified().ok().map(httpdate::fmt_http_date);
+    if let Some(response) = conditional_response(
+        etag,
+        cache_control,
+        last_modified.as_deref(),
+        request_headers,
+    ) {
+        return Ok(Some(response));
     }
+    Ok(Some(body_response(
+        Body::from_stream(ReaderStream::new(file)),
+        metadata.len(),
+        "image/jpeg",
+        filename,
+        etag,
+        cache_control,
+        last_modified.as_deref(),
+    )))
+}
+
+/// A 304 when the request's If-None-Match already names `etag`, or, without
+/// If-None-Match, when If-Modified-Since is no older than `last_modified`.
+fn conditional_response(
+    etag: &str,
+    cache_control: &str,
+    last_modified: Option<&str>,
+    request_headers: &HeaderMap,
+) -> Option<Response<Body>> {
+    let header_value = |name| {
+        request_headers
+            .get(name)
+            .and_then(|value: &header::HeaderValue| value.to_str().ok())
+    };
+    let not_modified = match header_value(header::IF_NONE_MATCH) {
+        Some(if_none_match) => if_none_match_matches(if_none_match, etag),
+        None => header_value(header::IF_MODIFIED_SINCE)
+            .zip(last_modified)
+            .is_some_and(|(since, last_modified)| not_modified_since(since, last_modified)),
+    };
+    not_modified.then(|| not_modified_response(etag, cache_control, last_modified))
+}
 
-    let len = bytes.len();
-    let mut response = Response::new(Body::from(bytes));
+fn body_response(
+    body: Body,
+    len: u64,
+    media_type: &str,
+    filename: &str,
+    etag: &str,
+    cache_control: &str,
+    last_modified: Option<&str>,
+) -> Response<Body> {
+    let mut response = Response::new(body);
     let headers = response.headers_mut();
 
     if let Ok(value) = header::HeaderValue::from_str(media_type) {
@@ -833,11 +1770,16 @@ fn bytes_response(
     if let Ok(value) = header::HeaderValue::from_str(cache_control) {
         headers.insert(header::CACHE_CONTROL, value);
     }
+    if let Some(last_modified) = last_modified
+        && let Ok(value) = header::HeaderValue::from_str(last_modified)
+    {
+        headers.insert(header::LAST_MODIFIED, value);
+    }
     if let Some(value) = content_disposition_value("inline", filename) {
         headers.insert(header::CONTENT_DISPOSITION, value);
     }
 
-    Ok(response)
+    response
 }
 
 fn map_item_record(item: &ItemRecord) -> ItemRecordResponse {
@@ -855,6 +1797,7 @@ fn map_item_record(item: &ItemRecord) -> ItemRecordResponse {
         subtitle_tracks: item.subtitle_tracks,
         blurhash: item.blurhash.clone(),
         time_added: item.time_added.clone(),
+        primary_file_id: item.primary_file_id,
     }
 }
 
@@ -865,6 +1808,8 @@ fn map_file_record(file: FileRecord) -> FileRecordResponse {
         path: file.path,
         last_modified: file.last_modified,
         filename: file.filename,
+        time_added: file.time_added,
+        last_seen: file.last_seen,
     }
 }
 
@@ -872,9 +1817,14 @@ fn default_true() -> bool {
     true
 }
 
+fn default_placeholder_size() -> u32 {
+    32
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::db::storage::VisualTable;
     use std::{
         path::PathBuf,
         time::{SystemTime, UNIX_EPOCH},
@@ -903,13 +1853,17 @@ mod tests {
             subtitle_tracks: None,
             blurhash: None,
             time_added: "2024-01-01T00:00:00".to_string(),
+            primary_file_id: None,
         };
         let file = FileRecord {
             id: 10,
             sha256: "sha256".to_string(),
             path: file_path.to_string_lossy().to_string(),
+            path_bytes: None,
             last_modified: "2024-01-01T00:00:00".to_string(),
             filename: "file.png".to_string(),
+            time_added: Some("2024-01-01T00:00:00".to_string()),
+            last_seen: Some("2024-01-01T00:00:00".to_string()),
         };
         (item, file)
     }
@@ -1116,6 +2070,204 @@ mod tests {
         assert_eq!(response.headers().get(header::ETAG).unwrap(), &etag);
         assert!(response.headers().get(header::CACHE_CONTROL).is_some());
         assert!(body_bytes(response).await.is_empty());
+
+        // A validator for other content gets the full body.
+        let other = etag.replacen("sha256", "othersha", 1);
+        request_headers.insert(header::IF_NONE_MATCH, other.parse().unwrap());
+        let response = file_response(
+            &item,
+            std::slice::from_ref(&file),
+            "inline",
+            &request_headers,
+            false,
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body_bytes(response).await, b"test");
+    }
+
+    // Stored frames are served by index with immutable caching, whether
+    // kept as blobs or as files, listed without their bytes, and a missing
+    // index or file is a 404.
+    #[tokio::test]
+    async fn frames_are_served_from_storage_by_index() {
+        let mut dbs = crate::db::migrations::setup_test_databases().await;
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        sqlx::query(
+            r#"
+INSERT INTO storage.frames (item_sha256, idx, item_mime_type, width, height, version, frame)
+VALUES
+    ('sha_video', 1, 'video/mp4', 320, 180, 1, x'0102'),
+    ('sha_video', 0, 'video/mp4', 640, 360, 1, x'0304'),
+    ('sha_video', 2, 'video/mp4', 320, 180, 1, x''),
+    ('sha_video', 3, 'video/mp4', 320, 180, 1, x'')
+            "#,
+        )
+        .execute(&mut dbs.index_conn)
+        .await
+        .unwrap();
+
+        let infos = get_frame_infos(&mut dbs.index_conn, "sha_video")
+            .await
+            .unwrap();
+        assert_eq!(
+            infos,
+            vec![
+                FrameInfo {
+                    index: 0,
+                    width: 640,
+                    height: 360,
+                },
+                FrameInfo {
+                    index: 1,
+                    width: 320,
+                    height: 180,
+                },
+                FrameInfo {
+                    index: 2,
+                    width: 320,
+                    height: 180,
+                },
+                FrameInfo {
+                    index: 3,
+                    width: 320,
+                    height: 180,
+                },
+            ]
+        );
+
+        let response = frame_response(&mut dbs.index_conn, dir, "sha_video", 1, &HeaderMap::new())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get(header::CONTENT_TYPE).unwrap(),
+            "image/jpeg"
+        );
+        assert_eq!(
+            response.headers().get(header::CACHE_CONTROL).unwrap(),
+            CACHE_IMMUTABLE
+        );
+        let etag = response.headers().get(header::ETAG).unwrap().clone();
+        assert_eq!(body_bytes(response).await, vec![1, 2]);
+
+        let mut request_headers = HeaderMap::new();
+        request_headers.insert(header::IF_NONE_MATCH, etag);
+        let response = frame_response(&mut dbs.index_conn, dir, "sha_video", 1, &request_headers)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+        let path = VisualTable::Frames.file_path(dir, "sha_video", 2);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, [5, 6, 7]).unwrap();
+        let response = frame_response(&mut dbs.index_conn, dir, "sha_video", 2, &HeaderMap::new())
+            .await
+            .unwrap();
+        assert_eq!(response.headers().get(header::CONTENT_LENGTH).unwrap(), "3");
+        assert_eq!(
+            response.headers().get(header::CACHE_CONTROL).unwrap(),
+            CACHE_IMMUTABLE
+        );
+        assert_eq!(body_bytes(response).await, vec![5, 6, 7]);
+
+        for index in [3, 4] {
+            let err = frame_response(
+                &mut dbs.index_conn,
+                dir,
+                "sha_video",
+                index,
+                &HeaderMap::new(),
+            )
+            .await
+            .unwrap_err();
+            assert_eq!(err.status(), StatusCode::NOT_FOUND);
+        }
+    }
+
+    // Stored visual ETags carry the hash, index and process version, so a
+    // regenerated visual or another item's validator gets a full 200. File-
+    // backed visuals also answer If-Modified-Since from the file's mtime.
+    #[tokio::test]
+    async fn stored_visual_validators_track_hash_and_process_version() {
+        let mut dbs = crate::db::migrations::setup_test_databases().await;
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        sqlx::query(
+            r#"
+INSERT INTO storage.frames (item_sha256, idx, item_mime_type, width, height, version, frame)
+VALUES
+    ('sha_video', 0, 'video/mp4', 320, 180, 1, x'0102'),
+    ('sha_video', 1, 'video/mp4', 320, 180, 1, x'')
+            "#,
+        )
+        .execute(&mut dbs.index_conn)
+        .await
+        .unwrap();
+        let with_header = |name, value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(name, value.parse().unwrap());
+            headers
+        };
+
+        let response = frame_response(&mut dbs.index_conn, dir, "sha_video", 0, &HeaderMap::new())
+            .await
+            .unwrap();
+        assert_eq!(
+            response.headers().get(header::ETAG).unwrap(),
+            "\"sha_video-frame0-v1\""
+        );
+        let matching = with_header(header::IF_NONE_MATCH, "\"sha_video-frame0-v1\"");
+        let response = frame_response(&mut dbs.index_conn, dir, "sha_video", 0, &matching)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        let other_hash = with_header(header::IF_NONE_MATCH, "\"sha_other-frame0-v1\"");
+        let response = frame_response(&mut dbs.index_conn, dir, "sha_video", 0, &other_hash)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body_bytes(response).await, vec![1, 2]);
+
+        sqlx::query("UPDATE storage.frames SET version = 2")
+            .execute(&mut dbs.index_conn)
+            .await
+            .unwrap();
+        let response = frame_response(&mut dbs.index_conn, dir, "sha_video", 0, &matching)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get(header::ETAG).unwrap(),
+            "\"sha_video-frame0-v2\""
+        );
+
+        let path = VisualTable::Frames.file_path(dir, "sha_video", 1);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, [5, 6, 7]).unwrap();
+        let response = frame_response(&mut dbs.index_conn, dir, "sha_video", 1, &HeaderMap::new())
+            .await
+            .unwrap();
+        let last_modified = response
+            .headers()
+            .get(header::LAST_MODIFIED)
+            .expect("Last-Modified header present")
+            .to_str()
+            .unwrap()
+            .to_string();
+        let since = with_header(header::IF_MODIFIED_SINCE, &last_modified);
+        let response = frame_response(&mut dbs.index_conn, dir, "sha_video", 1, &since)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        let before = with_header(header::IF_MODIFIED_SINCE, "Wed, 21 Oct 2015 07:28:00 GMT");
+        let response = frame_response(&mut dbs.index_conn, dir, "sha_video", 1, &before)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body_bytes(response).await, vec![5, 6, 7]);
     }
 
     // A missing first candidate falls through to the next instead of 404ing.
@@ -1130,8 +2282,11 @@ mod tests {
             path: temp_path("file_fallback_missing")
                 .to_string_lossy()
                 .to_string(),
+            path_bytes: None,
             last_modified: file.last_modified.clone(),
             filename: "gone.png".to_string(),
+            time_added: file.time_added.clone(),
+            last_seen: file.last_seen.clone(),
         };
 
         let response = file_response(&item, &[missing, file], "inline", &HeaderMap::new(), false)
@@ -1177,6 +2332,73 @@ mod tests {
         assert!(body_bytes(response).await.is_empty());
     }
 
+    fn png_dimensions(bytes: &[u8]) -> (u32, u32) {
+        let image = image::load_from_memory_with_format(bytes, image::ImageFormat::Png).unwrap();
+        (image.width(), image.height())
+    }
+
+    // Ensures decoded placeholders are capped at 128px and cached as immutable.
+    #[tokio::test]
+    async fn placeholder_decodes_blurhash_within_size_cap() {
+        let pixels = image::RgbaImage::from_pixel(8, 8, image::Rgba([200, 40, 40, 255]));
+        let blurhash = blurhash::encode(4, 4, 8, 8, pixels.as_raw()).unwrap();
+
+        let response =
+            placeholder_response("abc", Some(&blurhash), 500, 16, &HeaderMap::new()).unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get(header::CONTENT_TYPE).unwrap(),
+            "image/png"
+        );
+        assert_eq!(
+            response.headers().get(header::ETAG).unwrap(),
+            "\"abc-placeholder-128x16\""
+        );
+        assert_eq!(
+            response.headers().get(header::CACHE_CONTROL).unwrap(),
+            CACHE_IMMUTABLE
+        );
+        assert_eq!(png_dimensions(&body_bytes(response).await), (128, 16));
+
+        let response =
+            placeholder_response("abc", Some(&blurhash), 0, 32, &HeaderMap::new()).unwrap();
+        assert_eq!(png_dimensions(&body_bytes(response).await), (1, 32));
+    }
+
+    // Ensures a missing or malformed blurhash yields a 1x1 transparent PNG, not an error.
+    // Ensures the stand-in for pending visuals is never cached and tells
+    // the client when to retry.
+    #[test]
+    fn pending_placeholder_sets_retry_after() {
+        let file_path = temp_path("pending_placeholder");
+        let (item, _) = test_records(&file_path);
+        let response = pending_placeholder_response(&item, "file");
+        let headers = response.headers();
+        assert_eq!(headers.get(header::CACHE_CONTROL).unwrap(), "no-store");
+        assert_eq!(
+            headers.get(header::RETRY_AFTER).unwrap(),
+            &ON_DEMAND_RETRY_AFTER_SECS.to_string()
+        );
+        assert_eq!(headers.get(header::CONTENT_TYPE).unwrap(), "image/png");
+    }
+
+    #[tokio::test]
+    async fn placeholder_falls_back_to_transparent_pixel() {
+        for blurhash in [None, Some("not a blurhash")] {
+            let response =
+                placeholder_response("abc", blurhash, 32, 32, &HeaderMap::new()).unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(
+                response.headers().get(header::CACHE_CONTROL).unwrap(),
+                CACHE_REVALIDATE
+            );
+            let bytes = body_bytes(response).await;
+            let image = image::load_from_memory(&bytes).unwrap().to_rgba8();
+            assert_eq!(image.dimensions(), (1, 1));
+            assert_eq!(image.get_pixel(0, 0)[3], 0);
+        }
+    }
+
     #[test]
     fn content_addressing_requires_long_enough_sha256() {
         let full = "a".repeat(64);
@@ -1,29 +1,74 @@
-use axum::{Json, http::StatusCode};
+use axum::{Json, body::Body, http::StatusCode};
 // axum's own Query (serde_urlencoded) cannot deserialize repeated params
 // (?inference_ids=a&inference_ids=b) into a Vec; axum-extra's can, matching
 // FastAPI's List[str] query parameter behavior.
 use axum_extra::extract::Query;
+use futures_util::TryStreamExt;
 use serde::Deserialize;
 use serde_json::Value as JsonValue;
+use tokio_util::io::StreamReader;
 use utoipa::{IntoParams, ToSchema};
 
 use crate::api::db_params::DbQueryParams;
+use crate::api::items::{ItemMetadataResponse, item_metadata_response};
+use crate::api::saved_queries::load_saved_query;
+use crate::api::search::scope_bookmark_filters;
 use crate::api_error::ApiError;
-use crate::db::extraction_log::{LogRecord, get_all_data_logs, get_setters_total_data};
+use crate::db::data_coverage::get_coverage_snapshot;
+use crate::db::db_maintenance::{DbMaintenanceRun, get_db_maintenance_runs};
+use crate::db::extraction_log::{
+    LogRecord, SetterEmbeddingStats, SetterSummary, get_all_data_logs, get_setter_embedding_stats,
+    get_setter_summaries, get_setters_total_data,
+};
 use crate::db::file_scans::get_all_file_scans;
-use crate::db::folders::get_folders_from_database;
-use crate::db::system_config::{SystemConfig, SystemConfigStore};
+use crate::db::file_verification::{
+    VerificationResult, VerificationRunRecord, get_verification_results, get_verification_runs,
+};
+use crate::db::folders::{FolderRemovalPreview, get_folders_from_database, preview_folder_removal};
+use crate::db::items::{ItemIdentifierType, get_item_metadata};
+use crate::db::storage::{
+    OutdatedVisualsCount, VisualTable, count_missing_visuals, count_outdated_visuals,
+    count_visuals_to_migrate,
+};
+use crate::db::system_config::{
+    ExtractionLogRetention, SystemConfig, SystemConfigStore, VacuumMode,
+};
+use crate::db::lineage::{DanglingLineage, LineageRepairMode, get_dangling_lineage};
 use crate::db::{DbConnection, ReadOnly};
 use crate::jobs::continuous_scan;
 use crate::jobs::cron::{self, CronRunOutcome};
-use crate::jobs::files::is_resync_needed;
+use crate::jobs::data_coverage::{CoverageReport, compute_and_store_coverage};
+use crate::jobs::db_maintenance::OptimizeOptions;
+use crate::jobs::extraction::{
+    DataDeletionOptions, ExtractionDebugRequest, ExtractionDebugResponse, RENORMALIZE_JOB_TAG,
+    debug_extraction, fetch_inference_metadata_with_age, refresh_inference_metadata,
+    resolve_follow_up, resolve_model_metadata,
+};
+use crate::jobs::file_rescan::{self, FileRescanOutcome, RescanTarget};
+use crate::jobs::file_verification::{VerificationOptions, normalize_modified_since};
+use crate::jobs::fts_rebuild::{self, FtsRebuildOptions, FtsRebuildReport, FtsTarget};
+use crate::jobs::files::{
+    FRAME_PROCESS_VERSION, FolderRescanOptions, THUMBNAIL_PROCESS_VERSION, is_resync_needed,
+};
+use crate::jobs::filter_validation::{FilterValidation, describe_invalid, validate_filters};
+use crate::jobs::implicit_exclusions::{applied_implicit_exclusions, implicit_excluded_roots};
 use crate::jobs::inference_pool::job_inference_context;
+use crate::jobs::job_window::JobWindow;
+use crate::jobs::lineage_repair::{self, LineageRepairOptions, LineageRepairReport};
+use crate::jobs::log_retention::prune_extraction_logs;
+use crate::jobs::notifications::{NotificationTestResponse, send_test_notification};
+use crate::jobs::pql_report::{PqlReportOptions, validate_report_schedules};
 use crate::db::index_writer::{IndexDbWriterMessage, call_index_db_writer};
 use crate::db::vector_quants::{RECONCILE_JOB_TAG, VectorQuantStatus};
 use crate::jobs::queue::{
-    BatchDedup, JobModel, JobRequest, JobType, QueueStatusModel, cancel_queued_jobs,
-    cancel_running_job, enqueue_job, enqueue_jobs_unless_tagged, get_queue_status,
+    BatchDedup, JobChain, JobModel, JobRequest, JobType, QueueStatusModel, cancel_queued_jobs,
+    cancel_running_job, enqueue_job, enqueue_jobs_unless_tagged, extraction_dedup_key,
+    folder_rescan_dedup_key, get_queue_status, wake_job_queue,
 };
+use crate::jobs::tag_import::{TagImportReport, run_tag_import};
+use crate::jobs::tag_rethreshold::{TagRethresholdReport, run_tag_rethreshold};
+use crate::jobs::visuals_regeneration::{self, VisualsRegenerationProgress};
+use crate::policy::RequestIdentity;
 
 #[derive(Deserialize, IntoParams)]
 #[into_params(parameter_in = Query)]
@@ -38,6 +83,60 @@ pub(crate) struct InferenceQuery {
     threshold: Option<f64>,
 }
 
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct ForceQuery {
+    /// Enqueue even if an identical job is already queued
+    #[serde(default)]
+    force: bool,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct RunNowQuery {
+    /// Run even while the index DB's job window is closed
+    #[serde(default)]
+    run_now: bool,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct ForceFullQuery {
+    /// Visit every file, even in directories `skip_unchanged_dirs` would
+    /// skip as unchanged since the last scan
+    #[serde(default)]
+    force_full: bool,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct FollowUpQuery {
+    /// Inference IDs to enqueue once each job completes successfully,
+    /// replacing the models' `job_settings` follow-ups
+    #[serde(default)]
+    follow_up: Vec<String>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct CascadeQuery {
+    /// Also delete other models' data derived from this data (e.g. text
+    /// embeddings of deleted OCR text); when false, directly derived data
+    /// is kept as standalone data instead
+    #[serde(default = "default_true")]
+    #[param(default = true)]
+    cascade: bool,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct ReprocessQuery {
+    /// Also process items the model already processed, replacing their
+    /// existing data for this model instead of adding another set
+    #[serde(default)]
+    reprocess: bool,
+}
+
 #[derive(Deserialize, IntoParams)]
 #[into_params(parameter_in = Query)]
 pub(crate) struct LogIdQuery {
@@ -63,6 +162,22 @@ pub(crate) struct HistoryQuery {
     page_size: Option<i64>,
 }
 
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct HistoryPruneQuery {
+    /// Keep only the newest N logs of each setter (overrides the config)
+    #[param(minimum = 0)]
+    keep_last_per_setter: Option<u32>,
+    /// Keep only logs that started within the last D days (overrides the config)
+    #[param(minimum = 0)]
+    max_age_days: Option<u32>,
+}
+
+#[derive(serde::Serialize, ToSchema)]
+pub(crate) struct HistoryPruneResponse {
+    deleted: u64,
+}
+
 #[derive(serde::Serialize, ToSchema)]
 pub(crate) struct QueueCancelResponse {
     cancelled_jobs: Vec<i64>,
@@ -79,11 +194,121 @@ pub(crate) struct FoldersResponse {
     excluded_folders: Vec<String>,
 }
 
+#[derive(serde::Serialize, ToSchema)]
+pub(crate) struct FolderUpdateResponse {
+    #[serde(flatten)]
+    job: JobModel,
+    /// Panoptikon's own data directories (index and user-data DBs, scratch
+    /// files) that lie inside an included folder. Scans skip them regardless
+    /// of the excluded folders.
+    implicit_exclusions: Vec<String>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct FolderUpdateQuery {
+    /// Only report what the update would remove and restore; change nothing
+    #[serde(default)]
+    preview: bool,
+}
+
+#[derive(serde::Serialize, ToSchema)]
+pub(crate) struct FolderUpdatePreviewResponse {
+    /// Included folders in the config but not yet in the database
+    included_added: Vec<String>,
+    /// Included folders in the database that the config no longer lists
+    included_removed: Vec<String>,
+    /// Excluded folders in the config but not yet in the database
+    excluded_added: Vec<String>,
+    /// Excluded folders in the database that the config no longer lists
+    excluded_removed: Vec<String>,
+    /// Days removed files are kept, marked unavailable, before they are
+    /// deleted (`folder_removal_grace_days`); 0 deletes them right away
+    grace_days: u64,
+    #[serde(flatten)]
+    impact: FolderRemovalPreview,
+}
+
 #[derive(serde::Serialize, ToSchema)]
 pub(crate) struct SetterDataStats {
     total_counts: Vec<(String, i64)>,
 }
 
+#[derive(serde::Serialize, ToSchema)]
+pub(crate) struct SetterEmbeddingsResponse {
+    setters: Vec<SetterEmbeddingStats>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct SetterNamesQuery {
+    /// Setters to delete; each must be orphaned
+    setter_names: Vec<String>,
+}
+
+/// A setter merged with the inference server's current model list.
+#[derive(Debug, PartialEq, serde::Serialize, ToSchema)]
+pub(crate) struct SetterModelInfo {
+    setter_name: String,
+    /// Whether the inference server exposes a model with this inference ID.
+    model_available: bool,
+    /// The model's output type; null when no model matches.
+    #[schema(nullable)]
+    output_type: Option<String>,
+    /// Data types actually stored for this setter.
+    data_types: Vec<String>,
+    /// Non-placeholder item_data rows.
+    item_data_count: i64,
+    /// SystemConfig sections naming this setter (`cron_jobs`,
+    /// `job_settings`, `job_filters`).
+    config_references: Vec<String>,
+    /// No matching model and no item_data rows: safe to delete.
+    orphaned: bool,
+}
+
+#[derive(serde::Serialize, ToSchema)]
+pub(crate) struct SetterModelsResponse {
+    setters: Vec<SetterModelInfo>,
+    /// Age in seconds of the cached inference metadata the mapping used;
+    /// null when it was fetched without the cache.
+    #[schema(nullable)]
+    inference_metadata_age_secs: Option<u64>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct CoverageQuery {
+    /// Return the snapshot stored after the last extraction job (or live
+    /// computation) instead of recomputing; needs no inference server.
+    #[serde(default)]
+    cached: bool,
+}
+
+#[derive(serde::Serialize, ToSchema)]
+pub(crate) struct InferenceMetadataRefreshResponse {
+    /// Model groups in the refetched metadata.
+    groups: usize,
+    /// Inference IDs across those groups.
+    inference_ids: usize,
+}
+
+#[derive(serde::Serialize, ToSchema)]
+pub(crate) struct DeletedSettersResponse {
+    deleted: Vec<String>,
+}
+
+/// Key the GET response adds next to the config fields. A client that PUTs
+/// the GET payload back sends it too; it must not land in `extra`.
+const FILTER_VALIDATION_KEY: &str = "filter_validation";
+
+#[derive(serde::Serialize, ToSchema)]
+pub(crate) struct SystemConfigResponse {
+    #[serde(flatten)]
+    config: SystemConfig,
+    /// Per-filter status for `job_filters`, then `filescan_filter`.
+    filter_validation: Vec<FilterValidation>,
+}
+
 #[derive(serde::Serialize, ToSchema)]
 pub(crate) struct CronJobResponse {
     detail: String,
@@ -110,14 +335,20 @@ pub(crate) async fn queue_status() -> Result<Json<QueueStatusModel>, ApiError> {
     path = "/api/jobs/data/extraction",
     tag = "jobs",
     summary = "Run a data extraction job",
-    params(DbQueryParams, InferenceQuery),
+    params(DbQueryParams, InferenceQuery, ForceQuery, RunNowQuery, ReprocessQuery, FollowUpQuery),
     responses(
-        (status = 202, description = "Enqueued data extraction jobs", body = [JobModel])
+        (status = 202, description = "Enqueued data extraction jobs, each with `filter_counts`: the items it would process now and how many its mime type and job filters keep out", body = [JobModel]),
+        (status = 400, description = "An unknown model, or a follow-up naming a model the request enqueues"),
+        (status = 200, description = "Every job was already queued; the queued jobs, flagged `deduplicated`", body = [JobModel])
     )
 )]
 pub(crate) async fn enqueue_data_extraction(
     Query(query): Query<InferenceQuery>,
-    conn: DbConnection<ReadOnly>,
+    Query(force): Query<ForceQuery>,
+    Query(run_now): Query<RunNowQuery>,
+    Query(reprocess): Query<ReprocessQuery>,
+    Query(follow_up): Query<FollowUpQuery>,
+    mut conn: DbConnection<ReadOnly>,
 ) -> Result<(StatusCode, Json<Vec<JobModel>>), ApiError> {
     // Validate the models and resolve effective batch_size/threshold at
     // enqueue time (mirrors Python): a bad inference ID fails this request
@@ -126,8 +357,25 @@ pub(crate) async fn enqueue_data_extraction(
     let store = SystemConfigStore::from_env();
     let config = store.load(&conn.index_db)?;
     validate_external_inputs(&query.inference_ids).await?;
+    for follow_up in &follow_up.follow_up {
+        if query.inference_ids.contains(follow_up) {
+            return Err(ApiError::bad_request(format!(
+                "Follow-up {follow_up} is also enqueued directly by this request"
+            )));
+        }
+        crate::jobs::extraction::load_model_metadata(follow_up).await?;
+    }
     let mut jobs = Vec::new();
     for inference_id in query.inference_ids {
+        let chain = JobChain {
+            follow_up: if follow_up.follow_up.is_empty() {
+                resolve_follow_up(&config, &inference_id)
+            } else {
+                follow_up.follow_up.clone()
+            },
+            parent_queue_id: None,
+            ancestors: Vec::new(),
+        };
         let model = crate::jobs::extraction::load_model_metadata(&inference_id).await?;
         let defaults = crate::jobs::extraction::resolve_job_defaults(
             &config,
@@ -144,11 +392,59 @@ pub(crate) async fn enqueue_data_extraction(
             threshold: defaults.threshold,
             log_id: None,
             tag: None,
+            // A reprocess run does different work than a normal one, so the
+            // two never stand in for each other.
+            dedup_key: (!force.force).then(|| {
+                let key = extraction_dedup_key(&conn.index_db, &model.setter_name, &config);
+                if reprocess.reprocess {
+                    format!("{key}:reprocess")
+                } else {
+                    key
+                }
+            }),
+            run_now: run_now.run_now,
+            reprocess: reprocess.reprocess,
+            chain,
         })
         .await?;
-        jobs.push(job);
+        // Best effort: a projection that fails (say, an embedding filter
+        // with inference down) must not fail the enqueue.
+        let filter_counts = crate::jobs::extraction::count_filter_exclusions(
+            &mut conn.conn,
+            &config,
+            &model,
+            reprocess.reprocess,
+            &conn.index_db,
+            None,
+        )
+        .await
+        .inspect_err(|err| {
+            tracing::warn!(
+                error = ?err,
+                setter = model.setter_name,
+                "failed to project extraction counts"
+            )
+        })
+        .ok();
+        if let Some(counts) = &filter_counts {
+            crate::jobs::extraction::record_projection(job.queue_id, counts.total);
+        }
+        jobs.push(JobModel {
+            filter_counts,
+            ..job
+        });
+    }
+    Ok((enqueue_status(&jobs), Json(jobs)))
+}
+
+/// 200 when every job was already queued (nothing new was enqueued), else
+/// 202.
+fn enqueue_status(jobs: &[JobModel]) -> StatusCode {
+    if !jobs.is_empty() && jobs.iter().all(|job| job.deduplicated) {
+        StatusCode::OK
+    } else {
+        StatusCode::ACCEPTED
     }
-    Ok((StatusCode::ACCEPTED, Json(jobs)))
 }
 
 #[utoipa::path(
@@ -157,26 +453,38 @@ pub(crate) async fn enqueue_data_extraction(
     path = "/api/jobs/data/extraction",
     tag = "jobs",
     summary = "Delete extracted data",
-    params(DbQueryParams, InferenceQuery),
+    description = "Enqueues one job per inference ID that deletes the model's setter and all its data. With `cascade` (the default), data other models derived from it, through any number of steps, is deleted too; otherwise rows derived directly from it are kept as origin data. The job metadata holds the inference ID and the `cascade` flag as JSON.",
+    params(DbQueryParams, InferenceQuery, CascadeQuery),
     responses(
         (status = 202, description = "Enqueued data deletion jobs", body = [JobModel])
     )
 )]
 pub(crate) async fn enqueue_delete_extracted_data(
     Query(query): Query<InferenceQuery>,
+    Query(cascade): Query<CascadeQuery>,
     conn: DbConnection<ReadOnly>,
 ) -> Result<(StatusCode, Json<Vec<JobModel>>), ApiError> {
     let mut jobs = Vec::new();
     for inference_id in query.inference_ids {
+        let options = DataDeletionOptions {
+            inference_id,
+            cascade: cascade.cascade,
+        };
+        let metadata = serde_json::to_string(&options)
+            .map_err(|err| ApiError::internal(format!("Failed to encode options: {err}")))?;
         let job = enqueue_job(JobRequest {
             job_type: JobType::DataDeletion,
             index_db: conn.index_db.clone(),
             user_data_db: conn.user_data_db.clone(),
-            metadata: Some(inference_id),
+            metadata: Some(metadata),
             batch_size: None,
             threshold: None,
             log_id: None,
             tag: None,
+            dedup_key: None,
+            run_now: false,
+            reprocess: false,
+            chain: JobChain::default(),
         })
         .await?;
         jobs.push(job);
@@ -190,26 +498,145 @@ pub(crate) async fn enqueue_delete_extracted_data(
     path = "/api/jobs/folders/rescan",
     tag = "jobs",
     summary = "Run a folder rescan",
-    params(DbQueryParams),
+    description = "With `force_full`, the rescan visits every file even when `skip_unchanged_dirs` is on; the job metadata then holds the flag as JSON.",
+    params(DbQueryParams, ForceQuery, ForceFullQuery, RunNowQuery),
     responses(
-        (status = 202, description = "Enqueued folder rescan job", body = JobModel)
+        (status = 202, description = "Enqueued folder rescan job", body = JobModel),
+        (status = 200, description = "An identical rescan was already queued; that job, flagged `deduplicated`", body = JobModel)
     )
 )]
 pub(crate) async fn enqueue_folder_rescan(
+    Query(force): Query<ForceQuery>,
+    Query(force_full): Query<ForceFullQuery>,
+    Query(run_now): Query<RunNowQuery>,
     conn: DbConnection<ReadOnly>,
 ) -> Result<(StatusCode, Json<JobModel>), ApiError> {
+    let dedup_key = if force.force {
+        None
+    } else {
+        let config = SystemConfigStore::from_env().load(&conn.index_db)?;
+        let key = folder_rescan_dedup_key(&conn.index_db, &config);
+        // A queued differential rescan does not stand in for a full one.
+        Some(if force_full.force_full {
+            format!("{key}:full")
+        } else {
+            key
+        })
+    };
+    let metadata = if force_full.force_full {
+        let options = FolderRescanOptions { force_full: true };
+        Some(
+            serde_json::to_string(&options)
+                .map_err(|err| ApiError::internal(format!("Failed to encode options: {err}")))?,
+        )
+    } else {
+        None
+    };
     let job = enqueue_job(JobRequest {
         job_type: JobType::FolderRescan,
         index_db: conn.index_db.clone(),
         user_data_db: conn.user_data_db.clone(),
-        metadata: None,
+        metadata,
         batch_size: None,
         threshold: None,
         log_id: None,
         tag: None,
+        dedup_key,
+        run_now: run_now.run_now,
+        reprocess: false,
+        chain: JobChain::default(),
     })
     .await?;
-    Ok((StatusCode::ACCEPTED, Json(job)))
+    Ok((enqueue_status(std::slice::from_ref(&job)), Json(job)))
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub(crate) struct FileRescanRequest {
+    /// Path of the file to rescan; must be inside an included folder
+    #[serde(default)]
+    path: Option<String>,
+    /// Rescan the first of this item's files still on disk instead
+    #[serde(default)]
+    sha256: Option<String>,
+    /// Regenerate and replace the stored visuals and item metadata
+    #[serde(default)]
+    force: bool,
+}
+
+#[derive(serde::Serialize, ToSchema)]
+pub(crate) struct FileRescanResponse {
+    #[serde(flatten)]
+    outcome: FileRescanOutcome,
+    /// The item's metadata and files after the rescan; null if the item no
+    /// longer exists.
+    item: Option<ItemMetadataResponse>,
+}
+
+#[utoipa::path(
+    post,
+    operation_id = "rescan_file",
+    path = "/api/jobs/files/rescan",
+    tag = "jobs",
+    summary = "Rescan a single file now",
+    description = "Processes one file inline instead of through the job queue, under a scan record of its own, and returns the updated item and file metadata. Give either `path` or `sha256`. A file that is no longer on disk is marked unavailable. With `force`, the item's thumbnails, frames, blurhash and probed metadata are regenerated and replace the stored ones even when present.",
+    params(DbQueryParams),
+    request_body = FileRescanRequest,
+    responses(
+        (status = 200, description = "Rescan result", body = FileRescanResponse),
+        (status = 400, description = "Path outside the included folders, or a file type that is not scanned"),
+        (status = 404, description = "Unknown path or item"),
+        (status = 409, description = "File is still being written"),
+        (status = 504, description = "Processing the file timed out")
+    )
+)]
+pub(crate) async fn rescan_file(
+    mut conn: DbConnection<ReadOnly>,
+    Json(request): Json<FileRescanRequest>,
+) -> Result<Json<FileRescanResponse>, ApiError> {
+    let target = match (request.path, request.sha256) {
+        (Some(path), None) => RescanTarget::Path(path),
+        (None, Some(sha256)) => RescanTarget::Sha256(sha256),
+        _ => return Err(ApiError::bad_request("Give exactly one of path or sha256")),
+    };
+    let config = SystemConfigStore::from_env().load(&conn.index_db)?;
+    let outcome = file_rescan::rescan_file(
+        &mut conn.conn,
+        &conn.index_db,
+        &config,
+        target,
+        request.force,
+    )
+    .await?;
+    let metadata =
+        get_item_metadata(&mut conn.conn, &outcome.sha256, ItemIdentifierType::Sha256).await?;
+    Ok(Json(FileRescanResponse {
+        item: item_metadata_response(metadata),
+        outcome,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    operation_id = "debug_data_extraction",
+    path = "/api/jobs/data/debug",
+    tag = "jobs",
+    summary = "Run a model on a single item, without saving",
+    description = "Prepares the item's inputs the way an extraction job would, with the model's input handler options and the job's threshold, runs them through the batch inference endpoints and returns both, without writing anything. `threshold` and `input_handler_opts` override the job's values, so slicing and thresholds can be tuned before running a job. Items the job filters leave out, or the setter already processed, are run too. With `include_files`, input slices and binary outputs up to `max_file_bytes` are returned as base64. Text models run on up to 8 of the item's texts.",
+    params(DbQueryParams),
+    request_body = ExtractionDebugRequest,
+    responses(
+        (status = 200, description = "The prepared inputs and the model output for each input row", body = ExtractionDebugResponse),
+        (status = 400, description = "An unknown model, or an item type the model does not accept"),
+        (status = 404, description = "Unknown item, or no input for this model (e.g. no texts)"),
+        (status = 502, description = "No inference endpoint is available or the model failed to load")
+    )
+)]
+pub(crate) async fn debug_data_extraction(
+    conn: DbConnection<ReadOnly>,
+    Json(request): Json<ExtractionDebugRequest>,
+) -> Result<Json<ExtractionDebugResponse>, ApiError> {
+    let response = debug_extraction(&conn.index_db, &conn.user_data_db, request).await?;
+    Ok(Json(response))
 }
 
 #[utoipa::path(
@@ -218,15 +645,29 @@ pub(crate) async fn enqueue_folder_rescan(
     path = "/api/jobs/folders",
     tag = "jobs",
     summary = "Update the database with the current folder lists in the config",
-    description = "Must be run every time after the folder lists in the config are updated,\nto ensure that the database is in sync with the config.\nIf you update the config through the API, this will be done automatically if needed.\n\nThis will remove files and items from the database that are no longer in the included folders,\nand add files and items that are now in the included folders, as well as remove files and items\nfrom the database that are now in the excluded folders.",
-    params(DbQueryParams),
+    description = "Must be run every time after the folder lists in the config are updated,\nto ensure that the database is in sync with the config.\nIf you update the config through the API, this will be done automatically if needed.\n\nThis will remove files and items from the database that are no longer in the included folders,\nand add files and items that are now in the included folders, as well as remove files and items\nfrom the database that are now in the excluded folders.\n\nWith `preview=true` nothing is changed or enqueued: the response counts the files and items the\nupdate would remove, per folder, and the files it would restore. Removed files are kept, marked\nunavailable, for `folder_removal_grace_days` (default 7) so that re-adding their folder restores them.\n\nPanoptikon's own data directories are always skipped; the ones inside an included folder\nare listed in `implicit_exclusions`.",
+    params(DbQueryParams, FolderUpdateQuery),
     responses(
-        (status = 202, description = "Enqueued folder update job", body = JobModel)
+        (status = 202, description = "Enqueued folder update job", body = FolderUpdateResponse),
+        (status = 200, description = "What the update would remove and restore (preview=true)", body = FolderUpdatePreviewResponse)
     )
 )]
 pub(crate) async fn enqueue_update_folders(
-    conn: DbConnection<ReadOnly>,
-) -> Result<(StatusCode, Json<JobModel>), ApiError> {
+    mut conn: DbConnection<ReadOnly>,
+    Query(query): Query<FolderUpdateQuery>,
+) -> Result<axum::response::Response, ApiError> {
+    use axum::response::IntoResponse;
+
+    if query.preview {
+        let config = SystemConfigStore::from_env().read(&conn.index_db)?;
+        return Ok(Json(preview_folder_update(&mut conn.conn, &config).await?).into_response());
+    }
+    let store = SystemConfigStore::from_env();
+    let config = store.read(&conn.index_db)?;
+    let implicit_exclusions = applied_implicit_exclusions(
+        &implicit_excluded_roots(store.data_dir()),
+        &config.included_folders,
+    );
     let job = enqueue_job(JobRequest {
         job_type: JobType::FolderUpdate,
         index_db: conn.index_db.clone(),
@@ -236,9 +677,45 @@ pub(crate) async fn enqueue_update_folders(
         threshold: None,
         log_id: None,
         tag: None,
+        dedup_key: None,
+        run_now: false,
+        reprocess: false,
+        chain: JobChain::default(),
     })
     .await?;
-    Ok((StatusCode::ACCEPTED, Json(job)))
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(FolderUpdateResponse {
+            job,
+            implicit_exclusions,
+        }),
+    )
+        .into_response())
+}
+
+async fn preview_folder_update(
+    conn: &mut sqlx::SqliteConnection,
+    config: &SystemConfig,
+) -> Result<FolderUpdatePreviewResponse, ApiError> {
+    let current_included = get_folders_from_database(conn, true).await?;
+    let current_excluded = get_folders_from_database(conn, false).await?;
+    let missing_from = |folders: &[String], other: &[String]| {
+        folders
+            .iter()
+            .filter(|folder| !other.contains(folder))
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+    let impact =
+        preview_folder_removal(conn, &config.included_folders, &config.excluded_folders).await?;
+    Ok(FolderUpdatePreviewResponse {
+        included_added: missing_from(&config.included_folders, &current_included),
+        included_removed: missing_from(&current_included, &config.included_folders),
+        excluded_added: missing_from(&config.excluded_folders, &current_excluded),
+        excluded_removed: missing_from(&current_excluded, &config.excluded_folders),
+        grace_days: config.folder_removal_grace_days,
+        impact,
+    })
 }
 
 #[utoipa::path(
@@ -282,6 +759,28 @@ pub(crate) async fn cancel_current_job() -> Result<Json<CancelResponse>, ApiErro
     }))
 }
 
+#[utoipa::path(
+    get,
+    operation_id = "test_notifications",
+    path = "/api/jobs/notifications/test",
+    tag = "jobs",
+    summary = "Send a test notification",
+    description = "Send a sample `job_finished` event to every webhook in `[notifications]`, regardless of its event filter, and report each delivery.",
+    responses(
+        (status = 200, description = "Delivery outcome per webhook", body = NotificationTestResponse),
+        (status = 404, description = "No webhooks configured")
+    )
+)]
+pub(crate) async fn test_notifications() -> Result<Json<NotificationTestResponse>, ApiError> {
+    let config = &crate::config::runtime().notifications;
+    if config.webhooks.is_empty() {
+        return Err(ApiError::not_found(
+            "No notification webhooks are configured.",
+        ));
+    }
+    Ok(Json(send_test_notification(config).await))
+}
+
 #[utoipa::path(
     get,
     operation_id = "get_folders",
@@ -351,6 +850,10 @@ pub(crate) async fn delete_scan_data(
             threshold: None,
             log_id: Some(log_id),
             tag: None,
+            dedup_key: None,
+            run_now: false,
+            reprocess: false,
+            chain: JobChain::default(),
         })
         .await?;
         jobs.push(job);
@@ -378,6 +881,36 @@ pub(crate) async fn get_extraction_history(
     Ok(Json(logs))
 }
 
+#[utoipa::path(
+    post,
+    operation_id = "prune_extraction_history",
+    path = "/api/jobs/data/history/prune",
+    tag = "jobs",
+    summary = "Prune the extraction history",
+    description = "Deletes extraction logs outside the retention limits: the configured `extraction_log_retention`, or the limits given as query parameters instead. Logs of running jobs and logs whose job still owns extracted data are always kept. With no limit set, nothing is deleted.",
+    params(DbQueryParams, HistoryPruneQuery),
+    responses(
+        (status = 200, description = "Number of logs deleted", body = HistoryPruneResponse)
+    )
+)]
+pub(crate) async fn prune_extraction_history(
+    Query(query): Query<HistoryPruneQuery>,
+    conn: DbConnection<ReadOnly>,
+) -> Result<Json<HistoryPruneResponse>, ApiError> {
+    let retention = if query.keep_last_per_setter.is_some() || query.max_age_days.is_some() {
+        ExtractionLogRetention {
+            keep_last_per_setter: query.keep_last_per_setter,
+            max_age_days: query.max_age_days,
+        }
+    } else {
+        SystemConfigStore::from_env()
+            .read(&conn.index_db)?
+            .extraction_log_retention
+    };
+    let deleted = prune_extraction_logs(&conn.index_db, &retention).await?;
+    Ok(Json(HistoryPruneResponse { deleted }))
+}
+
 #[utoipa::path(
     put,
     operation_id = "update_config",
@@ -387,13 +920,15 @@ pub(crate) async fn get_extraction_history(
     params(DbQueryParams),
     request_body(content = SystemConfig, description = "The new system configuration"),
     responses(
-        (status = 200, description = "Updated system configuration", body = SystemConfig)
+        (status = 200, description = "Updated system configuration", body = SystemConfig),
+        (status = 400, description = "Invalid cron schedule, job window, PQL report, vector quants, or PQL filters")
     )
 )]
 pub(crate) async fn update_config(
     conn: DbConnection<ReadOnly>,
-    Json(config): Json<SystemConfig>,
+    Json(mut config): Json<SystemConfig>,
 ) -> Result<Json<SystemConfig>, ApiError> {
+    config.extra.remove(FILTER_VALIDATION_KEY);
     // Python accepts unparseable cron strings and fails invisibly inside the
     // scheduler forever; reject them here so typos surface at save time.
     if let Err(err) = cron::validate_cron_schedule(&config.cron_schedule) {
@@ -402,6 +937,16 @@ pub(crate) async fn update_config(
             config.cron_schedule
         )));
     }
+    if let Err(err) = cron::validate_cron_schedule(&config.db_maintenance.schedule) {
+        return Err(ApiError::bad_request(format!(
+            "Invalid db_maintenance.schedule {:?}: {err}",
+            config.db_maintenance.schedule
+        )));
+    }
+    validate_report_schedules(&config.pql_reports, &crate::config::runtime().reports)?;
+    if let Err(err) = JobWindow::from_config(&config.job_window) {
+        return Err(ApiError::bad_request(format!("Invalid job_window: {err}")));
+    }
     validate_external_inputs(
         &config
             .cron_jobs
@@ -418,10 +963,21 @@ pub(crate) async fn update_config(
     {
         return Err(ApiError::bad_request(message));
     }
+    // Filters otherwise only compile when a job runs; a typo would make jobs
+    // quietly process nothing.
+    reject_invalid_filters(&config)?;
     let store = SystemConfigStore::from_env();
+    let previous_normalization = store.load(&conn.index_db)?.text_normalization;
     store.save(&conn.index_db, &config)?;
     let config = store.load(&conn.index_db)?;
+    // Rows indexed under the old settings would stop matching queries
+    // normalized under the new ones; re-index them in the background.
+    if config.text_normalization != previous_normalization {
+        enqueue_renormalize_deduped(&conn.index_db, &conn.user_data_db).await?;
+    }
     let _ = continuous_scan::notify_config_change(&conn.index_db).await;
+    // Jobs may be waiting for a window that was just widened or disabled.
+    wake_job_queue();
     let _ = cron::notify_config_change(&conn.index_db).await;
     // Commit semantics: the TOML write, the discrepancy check, and its
     // consequence (synchronous metadata sync or a reconcile job) are one
@@ -439,12 +995,30 @@ pub(crate) async fn update_config(
             threshold: None,
             log_id: None,
             tag: None,
+            dedup_key: None,
+            run_now: false,
+            reprocess: false,
+            chain: JobChain::default(),
         })
         .await?;
     }
     Ok(Json(config))
 }
 
+/// `invalid_pql` with the failing entries of the filter validation report
+/// as `details.filters`, so the UI can point at each offending clause.
+fn reject_invalid_filters(config: &SystemConfig) -> Result<(), ApiError> {
+    let report = validate_filters(config);
+    let Some(detail) = describe_invalid(&report) else {
+        return Ok(());
+    };
+    let invalid = report
+        .into_iter()
+        .filter(|entry| !entry.valid)
+        .collect::<Vec<_>>();
+    Err(ApiError::invalid_pql(detail).with_details(serde_json::json!({ "filters": invalid })))
+}
+
 /// Validate declarations when the upstream supports the additive endpoint.
 /// Older remote Python Inferio servers do not have it, so a 404 preserves
 /// their previous behavior; every other discovery failure is surfaced.
@@ -502,15 +1076,19 @@ async fn validate_external_inputs(inference_ids: &[String]) -> Result<(), ApiErr
     summary = "Get the current system configuration",
     params(DbQueryParams),
     responses(
-        (status = 200, description = "Current system configuration", body = SystemConfig)
+        (status = 200, description = "Current system configuration", body = SystemConfigResponse)
     )
 )]
 pub(crate) async fn get_config(
     conn: DbConnection<ReadOnly>,
-) -> Result<Json<SystemConfig>, ApiError> {
+) -> Result<Json<SystemConfigResponse>, ApiError> {
     let store = SystemConfigStore::from_env();
     let config = store.load(&conn.index_db)?;
-    Ok(Json(config))
+    let filter_validation = validate_filters(&config);
+    Ok(Json(SystemConfigResponse {
+        config,
+        filter_validation,
+    }))
 }
 
 #[utoipa::path(
@@ -533,95 +1111,384 @@ pub(crate) async fn get_setter_data_count(
     }))
 }
 
-#[derive(Debug, serde::Serialize, ToSchema)]
-pub(crate) struct VectorQuantActionResponse {
-    pub detail: String,
-}
-
-#[derive(Debug, Deserialize, ToSchema)]
-pub(crate) struct VectorQuantRebuildRequest {
-    /// The quant profile name to rebuild.
-    pub profile: String,
-    /// A setter of the embedding space to rebuild; xmodal siblings rebuild
-    /// together.
-    pub setter_name: String,
-}
-
-#[derive(Deserialize, IntoParams)]
-#[into_params(parameter_in = Query)]
-pub(crate) struct VectorQuantStatusQuery {
-    /// Include per-setter vector/quantized counts (progress and size on
-    /// disk). These are full index scans over each setter's rows; pass
-    /// false from latency-sensitive surfaces that only need profile names
-    /// and states. Defaults to true.
-    #[serde(default = "default_true")]
-    counts: bool,
-}
-
-fn default_true() -> bool {
-    true
-}
-
 #[utoipa::path(
     get,
-    operation_id = "get_vector_quants",
-    path = "/api/jobs/quants",
+    operation_id = "get_setter_embeddings",
+    path = "/api/jobs/data/setters/embeddings",
     tag = "jobs",
-    summary = "Get vector quantization status",
-    description = "Desired (config.toml) merged with actual (DB) state of the vector quant profiles: per-profile setters coverage, build progress, size on disk and whether a reconcile is needed.",
-    params(DbQueryParams, VectorQuantStatusQuery),
+    summary = "Get the embedding dimension of each setter",
+    description = "Every setter that stored embeddings or had some rejected: the dimension recorded from its first stored embedding, how many embeddings it stores, and how many embedding writes were rejected for a different dimension. Rejected writes fail the item in the extraction job that produced them.",
+    params(DbQueryParams),
     responses(
-        (status = 200, description = "Vector quantization status", body = VectorQuantStatus)
+        (status = 200, description = "Per-setter embedding dimensions", body = SetterEmbeddingsResponse)
     )
 )]
-pub(crate) async fn get_vector_quants(
+pub(crate) async fn get_setter_embeddings(
     mut conn: DbConnection<ReadOnly>,
-    Query(params): Query<VectorQuantStatusQuery>,
-) -> Result<Json<VectorQuantStatus>, ApiError> {
-    // Invalid config is inert everywhere else (no reconcile action); here it
-    // is worth surfacing, since the card is exactly where the user would fix
-    // it.
-    let desired = crate::db::vector_quants::load_desired_state(&conn.index_db).ok_or_else(|| {
-        ApiError::bad_request(
-            "The [vector_quants] section of this database's config.toml is invalid; \
-             fix it to manage quant profiles.",
-        )
-    })?;
-    // Drift alone doesn't mean the user has to do anything: every action that
-    // creates drift also enqueues the reconcile that resolves it. Report the
-    // in-flight job so the card can say "converging" instead of "act now".
-    //
-    // The two reads can't be taken atomically, so bracket the DB read with
-    // them and take either. Sampling the queue only afterwards makes the
-    // one failure that matters: a job that finishes *during* the DB read
-    // leaves drift in the snapshot and nothing in the queue, which is
-    // exactly the "act now" banner flashing as the job completes. Bracketed,
-    // the worst case is the harmless direction — one extra poll reading
-    // "converging" after the work is already done.
-    let pending_before = reconcile_job_pending(&conn.index_db).await?;
-    let mut status =
-        crate::db::vector_quants::load_status(&mut conn.conn, desired, params.counts).await?;
-    status.reconcile_scheduled = pending_before || reconcile_job_pending(&conn.index_db).await?;
-    Ok(Json(status))
+) -> Result<Json<SetterEmbeddingsResponse>, ApiError> {
+    let setters = get_setter_embedding_stats(&mut conn.conn).await?;
+    Ok(Json(SetterEmbeddingsResponse { setters }))
+}
+
+#[derive(serde::Serialize, ToSchema)]
+pub(crate) struct LineageResponse {
+    /// Rows whose `source_id` references a deleted row, per setter and
+    /// data type.
+    dangling: Vec<DanglingLineage>,
+    /// Sum of `dangling` row counts.
+    total: i64,
+    /// The running or most recent repair job since the server started.
+    last_repair: Option<LineageRepairReport>,
 }
 
 #[utoipa::path(
-    post,
-    operation_id = "enqueue_vector_quant_reconcile",
-    path = "/api/jobs/quants/reconcile",
+    get,
+    operation_id = "get_data_lineage",
+    path = "/api/jobs/data/lineage",
     tag = "jobs",
-    summary = "Enqueue a vector quant reconcile job",
-    description = "Enqueues a reconcile job for the selected database (deduplicated: no-op when one is already queued or running). The job is stateless and converges the DB to the configured desired state.",
+    summary = "Report derived data whose source is gone",
+    description = "Counts item_data rows whose `source_id` references an item_data row that no longer exists (for example text embeddings of OCR text deleted without foreign key enforcement), grouped by setter and data type. Such rows count as processed for their model while matching no source. Also reports the running or most recent repair job; a `last_repair` with a null `finished_at` is still running, failed or was cancelled.",
     params(DbQueryParams),
     responses(
-        (status = 200, description = "Reconcile triggered", body = VectorQuantActionResponse)
+        (status = 200, description = "Dangling lineage per setter", body = LineageResponse)
     )
 )]
-pub(crate) async fn enqueue_vector_quant_reconcile(
-    conn: DbConnection<ReadOnly>,
-) -> Result<Json<VectorQuantActionResponse>, ApiError> {
-    let detail = enqueue_reconcile_deduped(&conn.index_db, &conn.user_data_db).await?;
-    Ok(Json(VectorQuantActionResponse { detail }))
+pub(crate) async fn get_data_lineage(
+    mut conn: DbConnection<ReadOnly>,
+) -> Result<Json<LineageResponse>, ApiError> {
+    let dangling = get_dangling_lineage(&mut conn.conn).await?;
+    let total = dangling.iter().map(|entry| entry.rows).sum();
+    Ok(Json(LineageResponse {
+        dangling,
+        total,
+        last_repair: lineage_repair::last_report(&conn.index_db),
+    }))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct LineageRepairQuery {
+    /// `delete` removes dangling rows and everything derived from them;
+    /// `null` keeps them as origin data by clearing `source_id`
+    #[serde(default)]
+    #[param(default = "delete")]
+    mode: LineageRepairMode,
+    /// Rows repaired per index writer transaction (default 500)
+    #[param(nullable)]
+    batch_size: Option<i64>,
+}
+
+#[utoipa::path(
+    post,
+    operation_id = "enqueue_lineage_repair",
+    path = "/api/jobs/data/lineage/repair",
+    tag = "jobs",
+    summary = "Enqueue a data lineage repair job",
+    description = "Repairs the rows reported by GET /api/jobs/data/lineage in batches through the index writer, so other writes interleave with it. `delete` removes each dangling row (and, through the foreign key, what was derived from it), then tags left without items; `null` clears `source_id` and marks the row as origin data, skipping rows whose setter already has origin data at the same index. Progress is reported by GET /api/jobs/data/lineage.",
+    params(DbQueryParams, LineageRepairQuery),
+    responses(
+        (status = 202, description = "Enqueued lineage repair job", body = JobModel),
+        (status = 400, description = "batch_size is not positive")
+    )
+)]
+pub(crate) async fn enqueue_lineage_repair(
+    Query(query): Query<LineageRepairQuery>,
+    conn: DbConnection<ReadOnly>,
+) -> Result<(StatusCode, Json<JobModel>), ApiError> {
+    if query.batch_size.is_some_and(|size| size <= 0) {
+        return Err(ApiError::bad_request("batch_size must be positive"));
+    }
+    let options = LineageRepairOptions { mode: query.mode };
+    let metadata = serde_json::to_string(&options)
+        .map_err(|err| ApiError::internal(format!("Failed to encode options: {err}")))?;
+    let job = enqueue_job(JobRequest {
+        job_type: JobType::LineageRepair,
+        index_db: conn.index_db.clone(),
+        user_data_db: conn.user_data_db.clone(),
+        metadata: Some(metadata),
+        batch_size: query.batch_size,
+        threshold: None,
+        log_id: None,
+        tag: None,
+        dedup_key: None,
+        run_now: false,
+        reprocess: false,
+        chain: JobChain::default(),
+    })
+    .await?;
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+/// Merges the setters table with the inference `/metadata` payload and the
+/// DB's SystemConfig. A setter matches a model when its name resolves as an
+/// inference ID (setters are named after the inference ID that wrote them).
+fn map_setters_to_models(
+    summaries: Vec<SetterSummary>,
+    metadata: &JsonValue,
+    config: &SystemConfig,
+) -> Vec<SetterModelInfo> {
+    summaries
+        .into_iter()
+        .map(|summary| {
+            let model = resolve_model_metadata(metadata, &summary.setter_name).ok();
+            let name = summary.setter_name.as_str();
+            let mut config_references = Vec::new();
+            if config.cron_jobs.iter().any(|job| job.inference_id == name) {
+                config_references.push("cron_jobs".to_string());
+            }
+            if config
+                .job_settings
+                .iter()
+                .any(|setting| setting.inference_id.as_deref() == Some(name))
+            {
+                config_references.push("job_settings".to_string());
+            }
+            if config
+                .job_filters
+                .iter()
+                .any(|filter| filter.setter_names.iter().any(|setter| setter == name))
+            {
+                config_references.push("job_filters".to_string());
+            }
+            let model_available = model.is_some();
+            SetterModelInfo {
+                model_available,
+                output_type: model.map(|model| model.output_type),
+                orphaned: !model_available && !summary.has_rows,
+                setter_name: summary.setter_name,
+                data_types: summary.data_types,
+                item_data_count: summary.item_data_count,
+                config_references,
+            }
+        })
+        .collect()
+}
+
+async fn load_setter_models(
+    conn: &mut DbConnection<ReadOnly>,
+) -> Result<(Vec<SetterModelInfo>, Option<std::time::Duration>), ApiError> {
+    let summaries = get_setter_summaries(&mut conn.conn).await?;
+    let (metadata, age) = fetch_inference_metadata_with_age().await?;
+    let config = SystemConfigStore::from_env().load(&conn.index_db)?;
+    Ok((map_setters_to_models(summaries, &metadata, &config), age))
+}
+
+#[utoipa::path(
+    get,
+    operation_id = "get_setter_models",
+    path = "/api/jobs/data/setters",
+    tag = "jobs",
+    summary = "Map setters to inference models",
+    description = "Every setter in the index DB merged with the inference server's current metadata: whether a model with that inference ID still exists, its output type, the stored data, and which SystemConfig sections reference it. Setters with no model and no data are flagged as orphaned.",
+    params(DbQueryParams),
+    responses(
+        (status = 200, description = "Setter to model mapping", body = SetterModelsResponse)
+    )
+)]
+pub(crate) async fn get_setter_models(
+    mut conn: DbConnection<ReadOnly>,
+) -> Result<Json<SetterModelsResponse>, ApiError> {
+    let (setters, age) = load_setter_models(&mut conn).await?;
+    Ok(Json(SetterModelsResponse {
+        setters,
+        inference_metadata_age_secs: age.map(|age| age.as_secs()),
+    }))
+}
+
+#[utoipa::path(
+    post,
+    operation_id = "refresh_inference_metadata",
+    path = "/api/inference/metadata/refresh",
+    tag = "inference",
+    summary = "Refetch the gateway's cached inference metadata",
+    description = "Drops the gateway's cached copy of the primary inference server's `/metadata` and fetches it again, so models added to the inference server are usable by jobs, the setter mapping and coverage without waiting for the cache TTL. Concurrent refreshes share one upstream request. A failed refetch leaves the cache empty, so the next read fetches again.",
+    responses(
+        (status = 200, description = "Metadata refetched", body = InferenceMetadataRefreshResponse),
+        (status = 502, description = "The inference server could not be reached")
+    )
+)]
+pub(crate) async fn refresh_inference_metadata_cache()
+-> Result<Json<InferenceMetadataRefreshResponse>, ApiError> {
+    let metadata = refresh_inference_metadata().await?;
+    let groups = metadata.as_object().cloned().unwrap_or_default();
+    let inference_ids = groups
+        .values()
+        .filter_map(|group| group.get("inference_ids")?.as_object())
+        .map(|ids| ids.len())
+        .sum();
+    Ok(Json(InferenceMetadataRefreshResponse {
+        groups: groups.len(),
+        inference_ids,
+    }))
+}
+
+#[utoipa::path(
+    delete,
+    operation_id = "delete_orphaned_setters",
+    path = "/api/jobs/data/setters",
+    tag = "jobs",
+    summary = "Delete orphaned setters",
+    description = "Deletes the named setters. Every name must be an orphaned setter (no matching model, no data); otherwise nothing is deleted.",
+    params(DbQueryParams, SetterNamesQuery),
+    responses(
+        (status = 200, description = "Deleted setters", body = DeletedSettersResponse),
+        (status = 400, description = "A named setter is unknown or not orphaned")
+    )
+)]
+pub(crate) async fn delete_orphaned_setters(
+    Query(query): Query<SetterNamesQuery>,
+    mut conn: DbConnection<ReadOnly>,
+) -> Result<Json<DeletedSettersResponse>, ApiError> {
+    let (setters, _) = load_setter_models(&mut conn).await?;
+    for name in &query.setter_names {
+        match setters.iter().find(|setter| &setter.setter_name == name) {
+            Some(setter) if setter.orphaned => {}
+            Some(_) => {
+                return Err(ApiError::bad_request(format!(
+                    "Setter {name} is not orphaned (it has a model or data)"
+                )));
+            }
+            None => return Err(ApiError::bad_request(format!("Setter {name} not found"))),
+        }
+    }
+    let mut deleted = Vec::new();
+    for name in query.setter_names {
+        let (rows, _) = call_index_db_writer(&conn.index_db, |reply| {
+            IndexDbWriterMessage::DeleteSetterData {
+                setter_name: name.clone(),
+                cascade: true,
+                include_orphan_tags: false,
+                reply,
+            }
+        })
+        .await?;
+        if rows.setters > 0 {
+            deleted.push(name);
+        }
+    }
+    Ok(Json(DeletedSettersResponse { deleted }))
+}
+
+#[utoipa::path(
+    get,
+    operation_id = "get_data_coverage",
+    path = "/api/jobs/data/coverage",
+    tag = "jobs",
+    summary = "Report how much of the library each model has processed",
+    description = "For every model in `job_settings` and every setter with data: the items (or text rows, for text-targeting models) whose MIME type the model accepts, how many of those it has data for, how many only have placeholders, and the coverage percentage. Live computation queries the inference server for model metadata and stores the result as the snapshot that `cached=true` returns; extraction jobs refresh that snapshot when they finish.",
+    params(DbQueryParams, CoverageQuery),
+    responses(
+        (status = 200, description = "Coverage per model", body = CoverageReport),
+        (status = 404, description = "cached=true and no snapshot has been computed yet")
+    )
+)]
+pub(crate) async fn get_data_coverage(
+    Query(query): Query<CoverageQuery>,
+    mut conn: DbConnection<ReadOnly>,
+) -> Result<Json<CoverageReport>, ApiError> {
+    if !query.cached {
+        let report = compute_and_store_coverage(&mut conn.conn, &conn.index_db).await?;
+        return Ok(Json(report));
+    }
+    let snapshot = get_coverage_snapshot(&mut conn.conn)
+        .await?
+        .ok_or_else(|| ApiError::not_found("No coverage snapshot has been computed yet"))?;
+    let report = serde_json::from_str(&snapshot).map_err(|err| {
+        tracing::error!(error = %err, "stored coverage snapshot is unreadable");
+        ApiError::internal("Failed to load coverage snapshot")
+    })?;
+    Ok(Json(report))
+}
+
+#[derive(Debug, serde::Serialize, ToSchema)]
+pub(crate) struct VectorQuantActionResponse {
+    pub detail: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub(crate) struct VectorQuantRebuildRequest {
+    /// The quant profile name to rebuild.
+    pub profile: String,
+    /// A setter of the embedding space to rebuild; xmodal siblings rebuild
+    /// together.
+    pub setter_name: String,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct VectorQuantStatusQuery {
+    /// Include per-setter vector/quantized counts (progress and size on
+    /// disk). These are full index scans over each setter's rows; pass
+    /// false from latency-sensitive surfaces that only need profile names
+    /// and states. Defaults to true.
+    #[serde(default = "default_true")]
+    counts: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[utoipa::path(
+    get,
+    operation_id = "get_vector_quants",
+    path = "/api/jobs/quants",
+    tag = "jobs",
+    summary = "Get vector quantization status",
+    description = "Desired (config.toml) merged with actual (DB) state of the vector quant profiles: per-profile setters coverage, build progress, size on disk and whether a reconcile is needed.",
+    params(DbQueryParams, VectorQuantStatusQuery),
+    responses(
+        (status = 200, description = "Vector quantization status", body = VectorQuantStatus)
+    )
+)]
+pub(crate) async fn get_vector_quants(
+    mut conn: DbConnection<ReadOnly>,
+    Query(params): Query<VectorQuantStatusQuery>,
+) -> Result<Json<VectorQuantStatus>, ApiError> {
+    // Invalid config is inert everywhere else (no reconcile action); here it
+    // is worth surfacing, since the card is exactly where the user would fix
+    // it.
+    let desired = crate::db::vector_quants::load_desired_state(&conn.index_db).ok_or_else(|| {
+        ApiError::bad_request(
+            "The [vector_quants] section of this database's config.toml is invalid; \
+             fix it to manage quant profiles.",
+        )
+    })?;
+    // Drift alone doesn't mean the user has to do anything: every action that
+    // creates drift also enqueues the reconcile that resolves it. Report the
+    // in-flight job so the card can say "converging" instead of "act now".
+    //
+    // The two reads can't be taken atomically, so bracket the DB read with
+    // them and take either. Sampling the queue only afterwards makes the
+    // one failure that matters: a job that finishes *during* the DB read
+    // leaves drift in the snapshot and nothing in the queue, which is
+    // exactly the "act now" banner flashing as the job completes. Bracketed,
+    // the worst case is the harmless direction — one extra poll reading
+    // "converging" after the work is already done.
+    let pending_before = reconcile_job_pending(&conn.index_db).await?;
+    let mut status =
+        crate::db::vector_quants::load_status(&mut conn.conn, desired, params.counts).await?;
+    status.reconcile_scheduled = pending_before || reconcile_job_pending(&conn.index_db).await?;
+    Ok(Json(status))
+}
+
+#[utoipa::path(
+    post,
+    operation_id = "enqueue_vector_quant_reconcile",
+    path = "/api/jobs/quants/reconcile",
+    tag = "jobs",
+    summary = "Enqueue a vector quant reconcile job",
+    description = "Enqueues a reconcile job for the selected database (deduplicated: no-op when one is already queued or running). The job is stateless and converges the DB to the configured desired state.",
+    params(DbQueryParams),
+    responses(
+        (status = 200, description = "Reconcile triggered", body = VectorQuantActionResponse)
+    )
+)]
+pub(crate) async fn enqueue_vector_quant_reconcile(
+    conn: DbConnection<ReadOnly>,
+) -> Result<Json<VectorQuantActionResponse>, ApiError> {
+    let detail = enqueue_reconcile_deduped(&conn.index_db, &conn.user_data_db).await?;
+    Ok(Json(VectorQuantActionResponse { detail }))
 }
 
 #[utoipa::path(
@@ -688,6 +1555,10 @@ async fn enqueue_reconcile_deduped(index_db: &str, user_data_db: &str) -> Result
         threshold: None,
         log_id: None,
         tag: Some(RECONCILE_JOB_TAG.to_string()),
+        dedup_key: None,
+        run_now: false,
+        reprocess: false,
+        chain: JobChain::default(),
     };
     let dedup = BatchDedup {
         tag: RECONCILE_JOB_TAG.to_string(),
@@ -701,22 +1572,650 @@ async fn enqueue_reconcile_deduped(index_db: &str, user_data_db: &str) -> Result
     }
 }
 
+#[derive(Debug, serde::Serialize, ToSchema)]
+pub(crate) struct TextRenormalizeResponse {
+    detail: String,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct TagImportQuery {
+    /// Setter the imported tags are stored under
+    setter_name: String,
+    /// Lines written per transaction (default 500, at most 10000)
+    #[param(nullable, minimum = 1)]
+    batch_size: Option<usize>,
+}
+
+#[utoipa::path(
+    post,
+    operation_id = "import_tags",
+    path = "/api/jobs/data/import/tags",
+    tag = "jobs",
+    summary = "Import tags from another tagging database",
+    description = "Reads an NDJSON body, one `{\"sha256\": ..., \"tags\": [{\"namespace\": ..., \"name\": ..., \"confidence\": ...}]}` object per line (confidence defaults to 1), and stores the tags of every item whose sha256 matches under `setter_name`, as if a tagging model with that name had run. The import is recorded as one entry in extraction history. Items that already have tags from the setter have them replaced, so repeating an import does not duplicate tags.\nLines are written in batches of `batch_size`, one transaction each. Hashes that match no item and lines that are not valid records are listed in the response instead of failing the import.",
+    params(DbQueryParams, TagImportQuery),
+    request_body(content = String, content_type = "application/x-ndjson"),
+    responses(
+        (status = 200, description = "Import summary", body = TagImportReport),
+        (status = 400, description = "Missing or reserved setter name, or unreadable body")
+    )
+)]
+pub(crate) async fn import_tags(
+    Query(query): Query<TagImportQuery>,
+    conn: DbConnection<ReadOnly>,
+    body: Body,
+) -> Result<Json<TagImportReport>, ApiError> {
+    let reader = StreamReader::new(body.into_data_stream().map_err(std::io::Error::other));
+    let report =
+        run_tag_import(&conn.index_db, &query.setter_name, query.batch_size, reader).await?;
+    Ok(Json(report))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub(crate) struct TagRethresholdRequest {
+    /// Setter whose tags are filtered
+    setter_name: String,
+    /// New minimum confidence; must not be below the setter's current one
+    threshold: f64,
+}
+
+#[utoipa::path(
+    post,
+    operation_id = "rethreshold_tags",
+    path = "/api/jobs/data/tags/rethreshold",
+    tag = "jobs",
+    summary = "Raise the confidence threshold of existing tags",
+    description = "Deletes the setter's tags with a confidence below `threshold` (per-frame video tags included) and rewrites the tags text entries they appeared in, the \"all tags\" list and its mcut subset, as if the model had run at that threshold. No inference is needed. Items left with no tags keep an empty tags row. The run is recorded as one entry in extraction history carrying the new threshold.\nA threshold can only be raised: tags below the setter's current threshold (the highest recorded by its runs that still own data, or by later rethresholds) were discarded at inference time, so a lower one is rejected and requires re-running the model.",
+    params(DbQueryParams),
+    request_body = TagRethresholdRequest,
+    responses(
+        (status = 200, description = "Rethreshold summary", body = TagRethresholdReport),
+        (status = 400, description = "Unknown or reserved setter, invalid threshold, or a threshold below the current one")
+    )
+)]
+pub(crate) async fn rethreshold_tags(
+    mut conn: DbConnection<ReadOnly>,
+    Json(request): Json<TagRethresholdRequest>,
+) -> Result<Json<TagRethresholdReport>, ApiError> {
+    let report = run_tag_rethreshold(
+        &mut conn.conn,
+        &conn.index_db,
+        &request.setter_name,
+        request.threshold,
+    )
+    .await?;
+    Ok(Json(report))
+}
+
+#[utoipa::path(
+    post,
+    operation_id = "enqueue_text_renormalize",
+    path = "/api/jobs/data/text/renormalize",
+    tag = "jobs",
+    summary = "Enqueue a text renormalization job",
+    description = "Recomputes the full-text search form of all extracted text under the database's current `text_normalization` settings (deduplicated: no-op when one is already queued or running). Saving changed settings through PUT /api/jobs/config enqueues this automatically.",
+    params(DbQueryParams),
+    responses(
+        (status = 200, description = "Renormalization triggered", body = TextRenormalizeResponse)
+    )
+)]
+pub(crate) async fn enqueue_text_renormalize(
+    conn: DbConnection<ReadOnly>,
+) -> Result<Json<TextRenormalizeResponse>, ApiError> {
+    let detail = enqueue_renormalize_deduped(&conn.index_db, &conn.user_data_db).await?;
+    Ok(Json(TextRenormalizeResponse { detail }))
+}
+
+async fn enqueue_renormalize_deduped(
+    index_db: &str,
+    user_data_db: &str,
+) -> Result<String, ApiError> {
+    let request = JobRequest {
+        job_type: JobType::TextRenormalize,
+        index_db: index_db.to_string(),
+        user_data_db: user_data_db.to_string(),
+        metadata: None,
+        batch_size: None,
+        threshold: None,
+        log_id: None,
+        tag: Some(RENORMALIZE_JOB_TAG.to_string()),
+        dedup_key: None,
+        run_now: false,
+        reprocess: false,
+        chain: JobChain::default(),
+    };
+    let dedup = BatchDedup {
+        tag: RENORMALIZE_JOB_TAG.to_string(),
+        index_db: index_db.to_string(),
+    };
+    match enqueue_jobs_unless_tagged(vec![request], Some(dedup)).await? {
+        Some(_) => Ok("Renormalization job enqueued.".to_string()),
+        None => Ok(
+            "A renormalization job for this database is already queued or running.".to_string(),
+        ),
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct VerifyQuery {
+    /// Only verify files whose path starts with this prefix
+    #[param(nullable)]
+    path_prefix: Option<String>,
+    /// Stop after this many files
+    #[param(nullable, minimum = 1)]
+    max_files: Option<i64>,
+    /// Only verify files last modified at or after this time
+    /// (`YYYY-MM-DD` or `YYYY-MM-DDTHH:MM:SS`, local time like the index)
+    #[param(nullable)]
+    modified_since: Option<String>,
+    /// Read rate cap in MB/s; 0 reads unthrottled. Defaults to the
+    /// database's `verify_max_mb_per_sec`
+    #[param(nullable, minimum = 0)]
+    max_mb_per_sec: Option<f64>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct VerifyResultsQuery {
+    /// Only return results of this run
+    #[param(nullable)]
+    run_id: Option<i64>,
+    /// Page number
+    #[param(default = 1, minimum = 1)]
+    page: Option<i64>,
+    /// Page size
+    #[param(default = 1000, minimum = 1)]
+    page_size: Option<i64>,
+}
+
+/// Most recent runs returned alongside verification results.
+const VERIFY_RUNS_LIMIT: i64 = 50;
+
+#[derive(serde::Serialize, ToSchema)]
+pub(crate) struct VerifyResultsResponse {
+    /// The most recent runs, newest first.
+    runs: Vec<VerificationRunRecord>,
+    /// Mismatched and unreadable files, newest first.
+    results: Vec<VerificationResult>,
+}
+
+#[utoipa::path(
+    post,
+    operation_id = "enqueue_file_verification",
+    path = "/api/jobs/maintenance/verify",
+    tag = "jobs",
+    summary = "Enqueue a bit-rot verification job",
+    description = "Re-reads available files and checks that they still hash to their stored sha256, at most `max_mb_per_sec`. Files modified since they were indexed are skipped as edits. Mismatched and unreadable files are recorded, never modified; see GET /api/jobs/maintenance/verify/results.",
+    params(DbQueryParams, VerifyQuery),
+    responses(
+        (status = 202, description = "Enqueued file verification job", body = JobModel)
+    )
+)]
+pub(crate) async fn enqueue_file_verification(
+    Query(query): Query<VerifyQuery>,
+    conn: DbConnection<ReadOnly>,
+) -> Result<(StatusCode, Json<JobModel>), ApiError> {
+    let options = verification_options(query)?;
+    let metadata = serde_json::to_string(&options)
+        .map_err(|err| ApiError::internal(format!("Failed to encode options: {err}")))?;
+    let job = enqueue_job(JobRequest {
+        job_type: JobType::FileVerification,
+        index_db: conn.index_db.clone(),
+        user_data_db: conn.user_data_db.clone(),
+        metadata: Some(metadata),
+        batch_size: None,
+        threshold: None,
+        log_id: None,
+        tag: None,
+        dedup_key: None,
+        run_now: false,
+        reprocess: false,
+        chain: JobChain::default(),
+    })
+    .await?;
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+fn verification_options(query: VerifyQuery) -> Result<VerificationOptions, ApiError> {
+    if query.max_files.is_some_and(|max| max < 1) {
+        return Err(ApiError::bad_request("max_files must be at least 1"));
+    }
+    if query
+        .max_mb_per_sec
+        .is_some_and(|rate| !rate.is_finite() || rate < 0.0)
+    {
+        return Err(ApiError::bad_request(
+            "max_mb_per_sec must be a non-negative number",
+        ));
+    }
+    let modified_since = query
+        .modified_since
+        .as_deref()
+        .map(|value| {
+            normalize_modified_since(value).ok_or_else(|| {
+                ApiError::bad_request("modified_since must be YYYY-MM-DD or YYYY-MM-DDTHH:MM:SS")
+            })
+        })
+        .transpose()?;
+    Ok(VerificationOptions {
+        path_prefix: query.path_prefix.filter(|prefix| !prefix.is_empty()),
+        modified_since,
+        max_files: query.max_files,
+        max_mb_per_sec: query.max_mb_per_sec,
+    })
+}
+
+#[utoipa::path(
+    get,
+    operation_id = "get_file_verification_results",
+    path = "/api/jobs/maintenance/verify/results",
+    tag = "jobs",
+    summary = "Get bit-rot verification results",
+    description = "The most recent verification runs with their counts, and the files they found mismatched or unreadable. A run with a null `end_time` is still running or was cancelled.",
+    params(DbQueryParams, VerifyResultsQuery),
+    responses(
+        (status = 200, description = "Verification runs and results", body = VerifyResultsResponse)
+    )
+)]
+pub(crate) async fn get_file_verification_results(
+    Query(query): Query<VerifyResultsQuery>,
+    mut conn: DbConnection<ReadOnly>,
+) -> Result<Json<VerifyResultsResponse>, ApiError> {
+    let runs = get_verification_runs(&mut conn.conn, VERIFY_RUNS_LIMIT).await?;
+    let results = get_verification_results(
+        &mut conn.conn,
+        query.run_id,
+        query.page.unwrap_or(1).max(1),
+        query.page_size.unwrap_or(1000).max(1),
+    )
+    .await?;
+    Ok(Json(VerifyResultsResponse { runs, results }))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct OptimizeQuery {
+    /// `none`, `full` or `incremental`. Defaults to the database's
+    /// `db_maintenance.vacuum`
+    #[param(nullable)]
+    vacuum: Option<VacuumMode>,
+}
+
+/// Most recent runs returned by the optimization history.
+const OPTIMIZE_RUNS_LIMIT: i64 = 50;
+
+#[utoipa::path(
+    post,
+    operation_id = "enqueue_db_optimize",
+    path = "/api/jobs/maintenance/optimize",
+    tag = "jobs",
+    summary = "Enqueue a database optimization job",
+    description = "Checkpoints and truncates the WAL, optionally vacuums, then runs `ANALYZE` and `PRAGMA optimize`, all through the index writer. As a queued job it never runs alongside an extraction job. A full VACUUM is skipped, with the reason recorded, unless free disk space exceeds the size of the databases. Duration and size before and after are recorded; see GET /api/jobs/maintenance/optimize/history.",
+    params(DbQueryParams, OptimizeQuery),
+    responses(
+        (status = 202, description = "Enqueued database optimization job", body = JobModel)
+    )
+)]
+pub(crate) async fn enqueue_db_optimize(
+    Query(query): Query<OptimizeQuery>,
+    conn: DbConnection<ReadOnly>,
+) -> Result<(StatusCode, Json<JobModel>), ApiError> {
+    let vacuum = match query.vacuum {
+        Some(vacuum) => vacuum,
+        None => {
+            SystemConfigStore::from_env()
+                .read(&conn.index_db)?
+                .db_maintenance
+                .vacuum
+        }
+    };
+    let options = OptimizeOptions {
+        vacuum,
+        scheduled: false,
+    };
+    let metadata = serde_json::to_string(&options)
+        .map_err(|err| ApiError::internal(format!("Failed to encode options: {err}")))?;
+    let job = enqueue_job(JobRequest {
+        job_type: JobType::DbOptimize,
+        index_db: conn.index_db.clone(),
+        user_data_db: conn.user_data_db.clone(),
+        metadata: Some(metadata),
+        batch_size: None,
+        threshold: None,
+        log_id: None,
+        tag: None,
+        dedup_key: None,
+        run_now: false,
+        reprocess: false,
+        chain: JobChain::default(),
+    })
+    .await?;
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+#[utoipa::path(
+    post,
+    operation_id = "enqueue_pql_report",
+    path = "/api/jobs/reports/pql",
+    tag = "jobs",
+    summary = "Enqueue a PQL report job",
+    description = "Runs a saved query (`saved_query`, owned by `user`) or an inline `query` as a queued job and delivers its first `max_results` results, limited to the `select` columns if given, to a `[[reports.destinations]]` entry of the server config: POSTed as JSON to its webhook with its configured headers, or written to its file. The payload carries the report `name`, `executed_at`, `duration_ms`, `total_count` and `results`. Failed deliveries are retried twice, after 2 and 4 seconds, before the job fails. A saved query is resolved when the job is enqueued. Reports can also be scheduled through the `pql_reports` system config.",
+    params(DbQueryParams),
+    request_body = PqlReportOptions,
+    responses(
+        (status = 202, description = "Enqueued PQL report job", body = JobModel),
+        (status = 400, description = "No query, or an unknown destination"),
+        (status = 404, description = "Saved query not found")
+    )
+)]
+pub(crate) async fn enqueue_pql_report(
+    mut conn: DbConnection<ReadOnly>,
+    identity: Option<axum::Extension<RequestIdentity>>,
+    Json(mut options): Json<PqlReportOptions>,
+) -> Result<(StatusCode, Json<JobModel>), ApiError> {
+    options.validate(&crate::config::runtime().reports)?;
+    if options.query.is_none()
+        && let Some(name) = &options.saved_query
+    {
+        options.query = Some(load_saved_query(&mut conn.conn, &options.user, name).await?.query);
+    }
+    if let Some(query) = options.query.as_mut() {
+        scope_bookmark_filters(query, identity.as_deref())?;
+    }
+    let metadata = serde_json::to_string(&options)
+        .map_err(|err| ApiError::internal(format!("Failed to encode options: {err}")))?;
+    let job = enqueue_job(JobRequest {
+        job_type: JobType::PqlReport,
+        index_db: conn.index_db.clone(),
+        user_data_db: conn.user_data_db.clone(),
+        metadata: Some(metadata),
+        batch_size: None,
+        threshold: None,
+        log_id: None,
+        tag: None,
+        dedup_key: None,
+        run_now: false,
+        reprocess: false,
+        chain: JobChain::default(),
+    })
+    .await?;
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+#[utoipa::path(
+    get,
+    operation_id = "get_db_optimize_history",
+    path = "/api/jobs/maintenance/optimize/history",
+    tag = "jobs",
+    summary = "Get the database optimization history",
+    description = "The most recent optimization runs, newest first, with their duration, whether the vacuum ran (or why it was skipped), and the size of index.db and storage.db before and after.",
+    params(DbQueryParams),
+    responses(
+        (status = 200, description = "Optimization runs", body = [DbMaintenanceRun])
+    )
+)]
+pub(crate) async fn get_db_optimize_history(
+    mut conn: DbConnection<ReadOnly>,
+) -> Result<Json<Vec<DbMaintenanceRun>>, ApiError> {
+    Ok(Json(
+        get_db_maintenance_runs(&mut conn.conn, OPTIMIZE_RUNS_LIMIT).await?,
+    ))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct VisualsRegenerateQuery {
+    /// Items regenerated per batch
+    #[param(nullable, minimum = 1)]
+    batch_size: Option<i64>,
+}
+
+#[utoipa::path(
+    post,
+    operation_id = "enqueue_visuals_regeneration",
+    path = "/api/jobs/maintenance/visuals",
+    tag = "jobs",
+    summary = "Enqueue a visuals regeneration job",
+    description = "Regenerates thumbnails and video frames stored by an older thumbnail or frame process version, from an available file of each item, in batches of `batch_size`, then generates the visuals a fast scan skipped. Items without an available file keep their old visuals. See GET /api/jobs/maintenance/visuals/status for progress.",
+    params(DbQueryParams, VisualsRegenerateQuery),
+    responses(
+        (status = 202, description = "Enqueued visuals regeneration job", body = JobModel)
+    )
+)]
+pub(crate) async fn enqueue_visuals_regeneration(
+    Query(query): Query<VisualsRegenerateQuery>,
+    conn: DbConnection<ReadOnly>,
+) -> Result<(StatusCode, Json<JobModel>), ApiError> {
+    if query.batch_size.is_some_and(|size| size < 1) {
+        return Err(ApiError::bad_request("batch_size must be at least 1"));
+    }
+    let job = enqueue_job(JobRequest {
+        job_type: JobType::VisualsRegeneration,
+        index_db: conn.index_db.clone(),
+        user_data_db: conn.user_data_db.clone(),
+        metadata: None,
+        batch_size: query.batch_size,
+        threshold: None,
+        log_id: None,
+        tag: None,
+        dedup_key: None,
+        run_now: false,
+        reprocess: false,
+        chain: JobChain::default(),
+    })
+    .await?;
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct VisualsMigrateQuery {
+    /// Thumbnails or frames moved per batch
+    #[param(nullable, minimum = 1)]
+    batch_size: Option<i64>,
+}
+
+#[utoipa::path(
+    post,
+    operation_id = "enqueue_visuals_storage_migration",
+    path = "/api/jobs/maintenance/visuals/storage",
+    tag = "jobs",
+    summary = "Enqueue a visuals storage migration job",
+    description = "Moves stored thumbnails and video frames to the server's configured `thumbnail_storage` backend (`sqlite` or `filesystem`), `batch_size` rows at a time. Until it has run, visuals stored under the other backend keep being served from there. GET /api/jobs/maintenance/visuals/status shows how many are left.",
+    params(DbQueryParams, VisualsMigrateQuery),
+    responses(
+        (status = 202, description = "Enqueued visuals storage migration job", body = JobModel)
+    )
+)]
+pub(crate) async fn enqueue_visuals_storage_migration(
+    Query(query): Query<VisualsMigrateQuery>,
+    conn: DbConnection<ReadOnly>,
+) -> Result<(StatusCode, Json<JobModel>), ApiError> {
+    if query.batch_size.is_some_and(|size| size < 1) {
+        return Err(ApiError::bad_request("batch_size must be at least 1"));
+    }
+    let job = enqueue_job(JobRequest {
+        job_type: JobType::VisualsStorageMigration,
+        index_db: conn.index_db.clone(),
+        user_data_db: conn.user_data_db.clone(),
+        metadata: None,
+        batch_size: query.batch_size,
+        threshold: None,
+        log_id: None,
+        tag: None,
+        dedup_key: None,
+        run_now: false,
+        reprocess: false,
+        chain: JobChain::default(),
+    })
+    .await?;
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+/// Stored visuals not yet in the configured `thumbnail_storage` backend.
+#[derive(serde::Serialize, ToSchema)]
+pub(crate) struct VisualsToMigrate {
+    thumbnails: i64,
+    frames: i64,
+}
+
+#[derive(serde::Serialize, ToSchema)]
+pub(crate) struct VisualsStatusResponse {
+    /// The process version new thumbnails are stored with.
+    thumbnail_version: i64,
+    /// The process version new video frames are stored with.
+    frame_version: i64,
+    /// Items whose stored visuals are older than these versions.
+    outdated: OutdatedVisualsCount,
+    /// Items with an available file that a fast scan left without visuals;
+    /// regeneration jobs generate them too.
+    missing: i64,
+    /// The running or most recent regeneration job since the server started.
+    last_run: Option<VisualsRegenerationProgress>,
+    /// Left for a visuals storage migration job to move.
+    to_migrate: VisualsToMigrate,
+}
+
+#[utoipa::path(
+    get,
+    operation_id = "get_visuals_status",
+    path = "/api/jobs/maintenance/visuals/status",
+    tag = "jobs",
+    summary = "Get outdated visuals and regeneration progress",
+    description = "The current thumbnail and frame process versions, how many items have visuals stored by an older version or none at all after a fast scan, and the progress of the running or most recent regeneration job. A `last_run` with a null `finished_at` is still running or was cancelled. `to_migrate` counts the thumbnails and frames not yet in the configured storage backend.",
+    params(DbQueryParams),
+    responses(
+        (status = 200, description = "Visuals status", body = VisualsStatusResponse)
+    )
+)]
+pub(crate) async fn get_visuals_status(
+    mut conn: DbConnection<ReadOnly>,
+) -> Result<Json<VisualsStatusResponse>, ApiError> {
+    let outdated = count_outdated_visuals(
+        &mut conn.conn,
+        THUMBNAIL_PROCESS_VERSION,
+        FRAME_PROCESS_VERSION,
+    )
+    .await?;
+    let missing = count_missing_visuals(&mut conn.conn).await?;
+    let backend = crate::config::runtime().thumbnail_storage;
+    let to_migrate = VisualsToMigrate {
+        thumbnails: count_visuals_to_migrate(&mut conn.conn, VisualTable::Thumbnails, backend)
+            .await?,
+        frames: count_visuals_to_migrate(&mut conn.conn, VisualTable::Frames, backend).await?,
+    };
+    Ok(Json(VisualsStatusResponse {
+        thumbnail_version: THUMBNAIL_PROCESS_VERSION,
+        frame_version: FRAME_PROCESS_VERSION,
+        outdated,
+        missing,
+        last_run: visuals_regeneration::last_progress(&conn.index_db),
+        to_migrate,
+    }))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct FtsRebuildQuery {
+    /// Which full-text indexes to repair
+    #[serde(default)]
+    #[param(default = "all")]
+    target: FtsTarget,
+    /// Run the FTS5 integrity check first and only rebuild indexes that fail it
+    #[serde(default)]
+    check_first: bool,
+    /// Rebuild even if the integrity check passes
+    #[serde(default)]
+    force: bool,
+}
+
+#[utoipa::path(
+    post,
+    operation_id = "enqueue_fts_rebuild",
+    path = "/api/jobs/maintenance/fts/rebuild",
+    tag = "jobs",
+    summary = "Enqueue a full-text index rebuild job",
+    description = "Rebuilds the path (`paths`), extracted text (`text`) or both (`all`) FTS5 indexes from their content tables, through the index writer so it serializes with other writes. Searches keep using the previous index until each rebuild commits. With `check_first`, an index is only rebuilt if the FTS5 integrity check fails, unless `force` is set. See GET /api/jobs/maintenance/fts/status for durations and row counts.",
+    params(DbQueryParams, FtsRebuildQuery),
+    responses(
+        (status = 202, description = "Enqueued full-text index rebuild job", body = JobModel)
+    )
+)]
+pub(crate) async fn enqueue_fts_rebuild(
+    Query(query): Query<FtsRebuildQuery>,
+    conn: DbConnection<ReadOnly>,
+) -> Result<(StatusCode, Json<JobModel>), ApiError> {
+    let options = FtsRebuildOptions {
+        target: query.target,
+        check_first: query.check_first,
+        force: query.force,
+    };
+    let metadata = serde_json::to_string(&options)
+        .map_err(|err| ApiError::internal(format!("Failed to encode options: {err}")))?;
+    let job = enqueue_job(JobRequest {
+        job_type: JobType::FtsRebuild,
+        index_db: conn.index_db.clone(),
+        user_data_db: conn.user_data_db.clone(),
+        metadata: Some(metadata),
+        batch_size: None,
+        threshold: None,
+        log_id: None,
+        tag: None,
+        dedup_key: None,
+        run_now: false,
+        reprocess: false,
+        chain: JobChain::default(),
+    })
+    .await?;
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+#[derive(serde::Serialize, ToSchema)]
+pub(crate) struct FtsStatusResponse {
+    /// The running or most recent rebuild job since the server started.
+    last_run: Option<FtsRebuildReport>,
+}
+
+#[utoipa::path(
+    get,
+    operation_id = "get_fts_status",
+    path = "/api/jobs/maintenance/fts/status",
+    tag = "jobs",
+    summary = "Get full-text index rebuild results",
+    description = "The running or most recent full-text index rebuild job for this index DB since the server started: per index, the integrity check result, whether it was rebuilt, how long it took and how many rows it holds. A `last_run` with a null `finished_at` is still running, failed or was cancelled.",
+    params(DbQueryParams),
+    responses(
+        (status = 200, description = "Full-text index rebuild status", body = FtsStatusResponse)
+    )
+)]
+pub(crate) async fn get_fts_status(
+    conn: DbConnection<ReadOnly>,
+) -> Result<Json<FtsStatusResponse>, ApiError> {
+    Ok(Json(FtsStatusResponse {
+        last_run: fts_rebuild::last_report(&conn.index_db),
+    }))
+}
+
 #[utoipa::path(
     post,
     operation_id = "manual_trigger_cronjob",
     path = "/api/jobs/cronjob/run",
     tag = "jobs",
     summary = "Manually trigger a cronjob run",
-    description = "Manually trigger the configured cronjob to run on the selected database.",
-    params(DbQueryParams),
+    description = "Manually trigger the configured cronjob to run on the selected database. Its folder rescan and extraction jobs wait for the job window unless `run_now` is set.",
+    params(DbQueryParams, RunNowQuery),
     responses(
         (status = 200, description = "Cronjob triggered", body = CronJobResponse)
     )
 )]
 pub(crate) async fn manual_trigger_cronjob(
+    Query(run_now): Query<RunNowQuery>,
     conn: DbConnection<ReadOnly>,
 ) -> Result<Json<CronJobResponse>, ApiError> {
-    let detail = match cron::run_cronjob(&conn.index_db, &conn.user_data_db).await? {
+    let outcome = cron::run_cronjob(&conn.index_db, &conn.user_data_db, run_now.run_now).await?;
+    let detail = match outcome {
         CronRunOutcome::Enqueued(_) => "Cronjob triggered.".to_string(),
         // Python also replies 200 here (the skip is silent); keep the status
         // code but say what happened.
@@ -919,4 +2418,126 @@ mod tests {
         let Query(q) = Query::<QueueCancelQuery>::try_from_uri(&uri).unwrap();
         assert_eq!(q.queue_ids, vec![3]);
     }
+
+    #[test]
+    fn verification_query_is_validated_and_normalized() {
+        let uri: Uri = "/x?path_prefix=/nas/photos&modified_since=2024-05-01&max_files=10"
+            .parse()
+            .unwrap();
+        let Query(query) = Query::<VerifyQuery>::try_from_uri(&uri).unwrap();
+        let options = verification_options(query).unwrap();
+        assert_eq!(options.path_prefix.as_deref(), Some("/nas/photos"));
+        assert_eq!(
+            options.modified_since.as_deref(),
+            Some("2024-05-01T00:00:00")
+        );
+        assert_eq!(options.max_files, Some(10));
+        assert_eq!(options.max_mb_per_sec, None);
+
+        for bad in ["max_files=0", "max_mb_per_sec=-1", "modified_since=May"] {
+            let uri: Uri = format!("/x?{bad}").parse().unwrap();
+            let Query(query) = Query::<VerifyQuery>::try_from_uri(&uri).unwrap();
+            let err = verification_options(query).unwrap_err();
+            assert_eq!(err.status(), StatusCode::BAD_REQUEST, "{bad}");
+        }
+    }
+
+    fn summary(name: &str, data_types: &[&str], count: i64, has_rows: bool) -> SetterSummary {
+        SetterSummary {
+            setter_name: name.to_string(),
+            data_types: data_types.iter().map(|value| value.to_string()).collect(),
+            item_data_count: count,
+            has_rows,
+        }
+    }
+
+    /// Setters resolve against the inference metadata by inference ID: a
+    /// known model reports its output type, config references are collected
+    /// from cron_jobs/job_settings/job_filters, and only a setter with
+    /// neither a model nor any item_data rows is orphaned (a model-less
+    /// setter that still has data is not).
+    #[test]
+    fn setters_map_to_models_and_flag_orphans() {
+        let metadata = serde_json::json!({
+            "tags": {
+                "group_metadata": {
+                    "input_spec": {"handler": "image_frames"},
+                    "output_type": "tags"
+                },
+                "inference_ids": {"wd": {}}
+            }
+        });
+        let config: SystemConfig = toml::from_str(
+            r#"
+cron_jobs = [{ inference_id = "tags/wd" }]
+job_settings = [{ group_name = "ocr", inference_id = "ocr/old" }]
+
+[[job_filters]]
+setter_names = ["ocr/old"]
+pql_query = { match = { eq = { type = "image/png" } } }
+"#,
+        )
+        .unwrap();
+        let mapped = map_setters_to_models(
+            vec![
+                summary("ocr/gone", &[], 0, false),
+                summary("ocr/old", &["text"], 3, true),
+                summary("tags/wd", &["tags"], 10, true),
+            ],
+            &metadata,
+            &config,
+        );
+
+        assert_eq!(mapped.len(), 3);
+        assert!(mapped[0].orphaned && !mapped[0].model_available);
+        assert!(mapped[0].config_references.is_empty());
+
+        assert!(!mapped[1].orphaned && !mapped[1].model_available);
+        assert_eq!(mapped[1].output_type, None);
+        assert_eq!(mapped[1].config_references, vec!["job_settings", "job_filters"]);
+
+        assert!(!mapped[2].orphaned && mapped[2].model_available);
+        assert_eq!(mapped[2].output_type.as_deref(), Some("tags"));
+        assert_eq!(mapped[2].config_references, vec!["cron_jobs"]);
+    }
+
+    /// The UI PUTs back what GET returned: the validation report must parse
+    /// as SystemConfig (it lands in `extra`, which update_config strips).
+    #[test]
+    fn config_response_round_trips_into_system_config() {
+        let config: SystemConfig = serde_json::from_value(serde_json::json!({
+            "job_filters": [{"setter_names": [], "pql_query": {"match": {"in_": {"size": 1}}}}],
+            "filescan_filter": {"match": {"gt": {"size": 1}}}
+        }))
+        .unwrap();
+        let filter_validation = validate_filters(&config);
+        assert!(!filter_validation[0].valid);
+        let payload = serde_json::to_value(SystemConfigResponse {
+            config,
+            filter_validation,
+        })
+        .unwrap();
+
+        let mut echoed: SystemConfig = serde_json::from_value(payload).unwrap();
+        assert!(echoed.extra.remove(FILTER_VALIDATION_KEY).is_some());
+        assert!(echoed.extra.is_empty());
+        assert_eq!(echoed.job_filters.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn invalid_filters_are_rejected_with_invalid_pql_details() {
+        let config: SystemConfig = serde_json::from_value(serde_json::json!({
+            "job_filters": [{"setter_names": [], "pql_query": {"match": {"in_": {"size": 1}}}}]
+        }))
+        .unwrap();
+        let error = reject_invalid_filters(&config).expect_err("invalid filter");
+        assert_eq!(error.code(), crate::api_error::ErrorCode::InvalidPql);
+        let response = axum::response::IntoResponse::into_response(error);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["details"]["filters"][0]["index"], 0);
+        assert!(body["details"]["filters"][0]["path"].is_string());
+    }
 }
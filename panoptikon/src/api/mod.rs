@@ -3,11 +3,20 @@ pub(crate) mod client_config;
 pub(crate) mod db;
 pub(crate) mod db_params;
 pub(crate) mod desktop;
+pub(crate) mod diagnostics;
+pub(crate) mod item_meta;
+pub(crate) mod item_notes;
 pub(crate) mod items;
 pub(crate) mod jobs;
 pub(crate) mod open;
 pub(crate) mod pinboards;
 pub(crate) mod relay;
+pub(crate) mod saved_queries;
 pub(crate) mod search;
 pub(crate) mod search_cache;
+pub(crate) mod search_export;
+pub(crate) mod search_slowlog;
+pub(crate) mod search_suggest;
+pub(crate) mod search_warmup;
+pub(crate) mod tag_index;
 pub(crate) mod utils;
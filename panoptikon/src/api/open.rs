@@ -1,24 +1,38 @@
-use axum::{Json, extract::Path};
+use axum::{
+    Json,
+    extract::{ConnectInfo, Path},
+    http::{HeaderMap, StatusCode},
+};
 use axum_extra::extract::Query;
 use serde::{Deserialize, Serialize};
-use std::path::Path as FsPath;
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path as FsPath, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
 use tokio::process::Command;
 use utoipa::{IntoParams, ToSchema};
 
 use crate::api::db_params::DbQueryParams;
 use crate::api_error::ApiError;
-use crate::db::items::get_existing_files_for_sha256;
+use crate::config::OpenConfig;
+use crate::db::items::{ItemIdentifierType, get_existing_files_for_sha256, get_item_metadata};
 use crate::db::{DbConnection, ReadOnly};
 
 type ApiResult<T> = std::result::Result<T, ApiError>;
 
+/// How long `/api/items/item/open` waits for the launcher to exit. Launchers
+/// such as `xdg-open` hand off and return at once; one still running after
+/// this is left to run detached.
+const LAUNCH_TIMEOUT: Duration = Duration::from_secs(5);
+
 #[derive(Deserialize, IntoParams)]
 #[into_params(parameter_in = Query)]
 pub(crate) struct OpenQuery {
     path: Option<String>,
 }
 
-#[derive(Serialize, ToSchema)]
+#[derive(Debug, Serialize, ToSchema)]
 pub(crate) struct OpenResponse {
     path: String,
     message: String,
@@ -98,7 +112,10 @@ async fn execute_custom_command(
     Ok(())
 }
 
-async fn execute_direct_command(program: &str, args: &[String], path: &FsPath) -> ApiResult<()> {
+/// Expands `{path}`, `{folder}` and `{filename}` in a direct (non-shell)
+/// program and its arguments. Nothing is quoted: each argument stays one
+/// argument whatever the path contains.
+fn expand_direct_command(program: &str, args: &[String], path: &FsPath) -> (String, Vec<String>) {
     let folder = path.parent().unwrap_or(path).to_string_lossy();
     let filename = path.file_name().unwrap_or_default().to_string_lossy();
     let expand = |value: &str| {
@@ -107,12 +124,17 @@ async fn execute_direct_command(program: &str, args: &[String], path: &FsPath) -
             .replace("{folder}", &folder)
             .replace("{filename}", &filename)
     };
-    Command::new(expand(program))
-        .args(args.iter().map(|arg| expand(arg)))
-        .spawn()
-        .map_err(|error| {
-            ApiError::internal(format!("Failed to start custom file action: {error}"))
-        })?;
+    (
+        expand(program),
+        args.iter().map(|arg| expand(arg)).collect(),
+    )
+}
+
+async fn execute_direct_command(program: &str, args: &[String], path: &FsPath) -> ApiResult<()> {
+    let (program, args) = expand_direct_command(program, args, path);
+    Command::new(program).args(args).spawn().map_err(|error| {
+        ApiError::internal(format!("Failed to start custom file action: {error}"))
+    })?;
     Ok(())
 }
 
@@ -285,18 +307,20 @@ async fn show_in_fm(path: &FsPath) -> ApiResult<()> {
     )))
 }
 
+/// Returns the stored path of the file to open along with the path to open
+/// it by, which differs when the name is not valid UTF-8.
 async fn get_correct_path(
     conn: &mut sqlx::SqliteConnection,
     sha256: &str,
     path: Option<String>,
-) -> ApiResult<String> {
+) -> ApiResult<(String, PathBuf)> {
     let trimmed_path = path
         .as_deref()
         .map(str::trim)
         .filter(|value| !value.is_empty());
     if let Some(path) = trimmed_path {
         let files = get_existing_files_for_sha256(conn, sha256).await?;
-        if files.is_empty() || !files.iter().any(|file| file.path == path) {
+        let Some(file) = files.iter().find(|file| file.path == path) else {
             let available = files
                 .iter()
                 .map(|file| file.path.clone())
@@ -311,13 +335,13 @@ async fn get_correct_path(
             return Err(ApiError::internal(format!(
                 "404: File {path} not found in {available}"
             )));
-        }
-        return Ok(path.to_string());
+        };
+        return Ok((file.path.clone(), file.disk_path()));
     }
 
     let files = get_existing_files_for_sha256(conn, sha256).await?;
     if let Some(file) = files.first() {
-        return Ok(file.path.clone());
+        return Ok((file.path.clone(), file.disk_path()));
     }
 
     Err(ApiError::internal("404: File not found"))
@@ -344,8 +368,8 @@ pub async fn open_file_on_host(
     Query(query): Query<OpenQuery>,
     mut db: DbConnection<ReadOnly>,
 ) -> ApiResult<Json<OpenResponse>> {
-    let path = get_correct_path(&mut db.conn, &sha256, query.path).await?;
-    open_file(FsPath::new(&path)).await?;
+    let (path, disk_path) = get_correct_path(&mut db.conn, &sha256, query.path).await?;
+    open_file(&disk_path).await?;
     Ok(Json(OpenResponse {
         path: path.clone(),
         message: format!("Attempting to open: {path}"),
@@ -373,10 +397,413 @@ pub async fn show_in_file_manager(
     Query(query): Query<OpenQuery>,
     mut db: DbConnection<ReadOnly>,
 ) -> ApiResult<Json<OpenResponse>> {
-    let path = get_correct_path(&mut db.conn, &sha256, query.path).await?;
-    show_in_fm(FsPath::new(&path)).await?;
+    let (path, disk_path) = get_correct_path(&mut db.conn, &sha256, query.path).await?;
+    show_in_fm(&disk_path).await?;
     Ok(Json(OpenResponse {
         path: path.clone(),
         message: format!("Attempting to open: {path}"),
     }))
 }
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum OpenMode {
+    /// Open the file with its default application
+    #[default]
+    Open,
+    /// Show the file in the file manager
+    Reveal,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct OpenItemQuery {
+    /// An item identifier (sha256 hash, file ID, path, item ID, or data ID for associated data)
+    id: String,
+    /// The type of the item identifier
+    id_type: ItemIdentifierType,
+    /// Which of the item's files to act on (default: its first existing file)
+    path: Option<String>,
+    /// Open the file, or reveal it in the file manager (default: open)
+    #[serde(default)]
+    mode: OpenMode,
+}
+
+/// A program and its arguments, run without a shell.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct LaunchCommand {
+    program: String,
+    args: Vec<String>,
+    /// Whether a non-zero exit means failure. Windows Explorer exits with 1
+    /// even when it opened the window.
+    checks_exit_status: bool,
+}
+
+/// Refuses local actions unless `[open] local_actions_enabled` is set and
+/// the request comes straight from this machine: the TCP peer must be
+/// loopback, and a request relayed by a proxy (any `Forwarded` or
+/// `X-Forwarded-For` header) must name only loopback clients.
+fn ensure_local_action(enabled: bool, peer: IpAddr, headers: &HeaderMap) -> ApiResult<()> {
+    fn forbidden(detail: &str) -> ApiError {
+        ApiError::new(StatusCode::FORBIDDEN, detail)
+    }
+    if !enabled {
+        return Err(forbidden(
+            "Local actions are disabled; set [open] local_actions_enabled to allow them",
+        ));
+    }
+    if !peer.to_canonical().is_loopback() {
+        return Err(forbidden(
+            "Local actions are only available from this machine",
+        ));
+    }
+    let mut relayed = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .flat_map(|value| value.to_str().unwrap_or("?").split(','))
+        .map(str::trim)
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    for value in headers.get_all("forwarded") {
+        let value = value.to_str().unwrap_or("for=?");
+        for part in value.split([',', ';']) {
+            if let Some((key, client)) = part.trim().split_once('=')
+                && key.trim().eq_ignore_ascii_case("for")
+            {
+                relayed.push(client.trim().trim_matches('"').to_string());
+            }
+        }
+    }
+    let is_loopback = |client: &str| {
+        let client = client
+            .strip_prefix('[')
+            .and_then(|value| value.split_once(']'))
+            .map_or(client, |(address, _)| address);
+        client
+            .parse::<IpAddr>()
+            .or_else(|_| client.parse::<SocketAddr>().map(|addr| addr.ip()))
+            .is_ok_and(|address| address.to_canonical().is_loopback())
+    };
+    if !relayed.iter().all(|client| is_loopback(client)) {
+        return Err(forbidden(
+            "Local actions are not available through a proxy for remote clients",
+        ));
+    }
+    Ok(())
+}
+
+/// The command that opens or reveals `path` on `os` (a
+/// `std::env::consts::OS` value). A direct `[open]` program for the action
+/// takes precedence; the shell-template forms are not used here.
+fn launch_command(
+    mode: OpenMode,
+    path: &FsPath,
+    open: &OpenConfig,
+    os: &str,
+) -> ApiResult<LaunchCommand> {
+    let (program, args) = match mode {
+        OpenMode::Open => (&open.file_program, &open.file_args),
+        OpenMode::Reveal => (&open.folder_program, &open.folder_args),
+    };
+    if let Some(program) = program.as_deref().filter(|value| !value.trim().is_empty()) {
+        let (program, args) = expand_direct_command(program, args, path);
+        return Ok(LaunchCommand {
+            program,
+            args,
+            checks_exit_status: true,
+        });
+    }
+
+    let path_arg = path.to_string_lossy().into_owned();
+    let (program, args) = match (os, mode) {
+        ("windows", OpenMode::Open) => ("explorer", vec![path_arg]),
+        ("windows", OpenMode::Reveal) => ("explorer", vec!["/select,".to_string(), path_arg]),
+        ("macos", OpenMode::Open) => ("open", vec![path_arg]),
+        ("macos", OpenMode::Reveal) => ("open", vec!["-R".to_string(), path_arg]),
+        // xdg-open has no way to select a file, so revealing opens its folder.
+        (_, OpenMode::Open) if cfg!(unix) => ("xdg-open", vec![path_arg]),
+        (_, OpenMode::Reveal) if cfg!(unix) => (
+            "xdg-open",
+            vec![path.parent().unwrap_or(path).to_string_lossy().into_owned()],
+        ),
+        _ => {
+            return Err(ApiError::internal(format!(
+                "Unsupported operating system: {os}"
+            )));
+        }
+    };
+    Ok(LaunchCommand {
+        program: program.to_string(),
+        args,
+        checks_exit_status: os != "windows",
+    })
+}
+
+/// Starts the command detached from the request: it waits up to
+/// `LAUNCH_TIMEOUT` for the launcher to exit, and a launcher still running
+/// then keeps running on its own.
+async fn spawn_detached(command: LaunchCommand) -> ApiResult<()> {
+    let mut child = Command::new(&command.program)
+        .args(&command.args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|err| ApiError::internal(format!("Failed to start {}: {err}", command.program)))?;
+    match tokio::time::timeout(LAUNCH_TIMEOUT, child.wait()).await {
+        Ok(Ok(status)) if command.checks_exit_status && !status.success() => Err(
+            ApiError::internal(format!("{} exited with {status}", command.program)),
+        ),
+        Ok(Ok(_)) | Err(_) => Ok(()),
+        Ok(Err(err)) => Err(ApiError::internal(format!(
+            "Failed to wait for {}: {err}",
+            command.program
+        ))),
+    }
+}
+
+/// Resolves the item's file to act on and hands the command to `launch`.
+/// Only paths of the item's `files` rows that exist on disk are accepted, so
+/// a request cannot name an arbitrary path.
+async fn open_item_with<F, Fut>(
+    conn: &mut sqlx::SqliteConnection,
+    query: OpenItemQuery,
+    open: &OpenConfig,
+    launch: F,
+) -> ApiResult<OpenResponse>
+where
+    F: FnOnce(LaunchCommand) -> Fut,
+    Fut: Future<Output = ApiResult<()>>,
+{
+    let metadata = get_item_metadata(conn, &query.id, query.id_type).await?;
+    if metadata.item.is_none() {
+        return Err(ApiError::not_found("Item not found"));
+    }
+    let requested = query
+        .path
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty());
+    let file = match requested {
+        Some(path) => metadata.files.iter().find(|file| file.path == path),
+        None => metadata.files.first(),
+    };
+    let Some(file) = file else {
+        return Err(ApiError::not_found(match requested {
+            Some(path) => format!("{path} is not an existing file of this item"),
+            None => "The item has no existing file".to_string(),
+        }));
+    };
+    let path = file.disk_path();
+    launch(launch_command(
+        query.mode,
+        &path,
+        open,
+        std::env::consts::OS,
+    )?)
+    .await?;
+    let action = match query.mode {
+        OpenMode::Open => "open",
+        OpenMode::Reveal => "reveal",
+    };
+    Ok(OpenResponse {
+        path: file.path.clone(),
+        message: format!("Attempting to {action}: {}", file.path),
+    })
+}
+
+#[utoipa::path(
+    post,
+    operation_id = "open_item_locally",
+    path = "/api/items/item/open",
+    tag = "open",
+    summary = "Open an item's file or reveal it in the file manager",
+    description = "Opens one of the item's files with the default application (`mode=open`) or shows it in the file manager (`mode=reveal`) on the machine running Panoptikon: `xdg-open` on Linux (revealing opens the containing folder), `open`/`open -R` on macOS, `explorer`/`explorer /select,` on Windows, or the direct `[open]` program for the action when configured. The command runs without a shell and is left running detached.\nOnly paths of the item's files that exist on disk are accepted. Requires `[open] local_actions_enabled` and a request from this machine (loopback peer, no proxy relaying a remote client); other requests get a 403.",
+    params(DbQueryParams, OpenItemQuery),
+    responses(
+        (status = 200, description = "Open request issued", body = OpenResponse),
+        (status = 403, description = "Local actions are disabled or the request is not local"),
+        (status = 404, description = "Unknown item, or the path is not an existing file of the item")
+    )
+)]
+pub(crate) async fn open_item_locally(
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Query(query): Query<OpenItemQuery>,
+    mut db: DbConnection<ReadOnly>,
+) -> ApiResult<Json<OpenResponse>> {
+    let open = crate::config::runtime().open.clone();
+    ensure_local_action(open.local_actions_enabled, peer.ip(), &headers)?;
+    let response = open_item_with(&mut db.conn, query, &open, spawn_detached).await?;
+    Ok(Json(response))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use axum::http::HeaderValue;
+
+    use super::*;
+    use crate::db::migrations::setup_test_databases;
+
+    fn loopback() -> IpAddr {
+        IpAddr::from([127, 0, 0, 1])
+    }
+
+    // Ensures local actions need the flag and a loopback peer, and that a
+    // proxy relaying a remote client is refused even from loopback.
+    #[test]
+    fn local_actions_require_flag_and_local_client() {
+        let status = |result: ApiResult<()>| result.map_err(|err| err.status());
+        let none = HeaderMap::new();
+        assert_eq!(
+            status(ensure_local_action(false, loopback(), &none)),
+            Err(StatusCode::FORBIDDEN)
+        );
+        assert_eq!(
+            status(ensure_local_action(
+                true,
+                IpAddr::from([192, 168, 1, 5]),
+                &none
+            )),
+            Err(StatusCode::FORBIDDEN)
+        );
+        assert_eq!(status(ensure_local_action(true, loopback(), &none)), Ok(()));
+        let mapped: IpAddr = "::ffff:127.0.0.1".parse().unwrap();
+        assert_eq!(status(ensure_local_action(true, mapped, &none)), Ok(()));
+
+        let mut relayed = HeaderMap::new();
+        relayed.insert(
+            "x-forwarded-for",
+            HeaderValue::from_static("127.0.0.1, ::1"),
+        );
+        relayed.insert(
+            "forwarded",
+            HeaderValue::from_static("for=\"[::1]:4711\";proto=http"),
+        );
+        assert_eq!(
+            status(ensure_local_action(true, loopback(), &relayed)),
+            Ok(())
+        );
+        relayed.insert("x-forwarded-for", HeaderValue::from_static("203.0.113.9"));
+        assert_eq!(
+            status(ensure_local_action(true, loopback(), &relayed)),
+            Err(StatusCode::FORBIDDEN)
+        );
+        let mut hidden = HeaderMap::new();
+        hidden.insert("forwarded", HeaderValue::from_static("for=unknown"));
+        assert_eq!(
+            status(ensure_local_action(true, loopback(), &hidden)),
+            Err(StatusCode::FORBIDDEN)
+        );
+    }
+
+    // Ensures each platform gets its launcher with the path as a single
+    // argument, and a direct [open] program overrides the default.
+    #[test]
+    fn launch_command_matches_platform_and_config() {
+        let path = FsPath::new("/media/a b/clip.mp4");
+        let open = OpenConfig::default();
+        let planned = |mode, os| {
+            let command = launch_command(mode, path, &open, os).unwrap();
+            (command.program, command.args)
+        };
+        let args = |values: &[&str]| values.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            planned(OpenMode::Reveal, "windows"),
+            (
+                "explorer".to_string(),
+                args(&["/select,", "/media/a b/clip.mp4"])
+            )
+        );
+        assert_eq!(
+            planned(OpenMode::Reveal, "macos"),
+            ("open".to_string(), args(&["-R", "/media/a b/clip.mp4"]))
+        );
+        if cfg!(unix) {
+            assert_eq!(
+                planned(OpenMode::Open, "linux"),
+                ("xdg-open".to_string(), args(&["/media/a b/clip.mp4"]))
+            );
+            assert_eq!(
+                planned(OpenMode::Reveal, "linux"),
+                ("xdg-open".to_string(), args(&["/media/a b"]))
+            );
+        }
+
+        let custom = OpenConfig {
+            folder_program: Some("dolphin".to_string()),
+            folder_args: vec!["--select".to_string(), "{path}".to_string()],
+            ..OpenConfig::default()
+        };
+        let command = launch_command(OpenMode::Reveal, path, &custom, "linux").unwrap();
+        assert_eq!(command.program, "dolphin");
+        assert_eq!(command.args, args(&["--select", "/media/a b/clip.mp4"]));
+    }
+
+    // Ensures only existing files of the item reach the launcher: a path
+    // from another item or a vanished file is a 404 and nothing runs.
+    #[tokio::test]
+    async fn open_item_accepts_only_existing_files_of_the_item() {
+        let mut dbs = setup_test_databases().await;
+        let conn = &mut dbs.index_conn;
+        let dir = tempfile::tempdir().unwrap();
+        let present = dir.path().join("present.png");
+        let other = dir.path().join("other.png");
+        std::fs::write(&present, b"x").unwrap();
+        std::fs::write(&other, b"x").unwrap();
+        let missing = dir.path().join("missing.png");
+        sqlx::query(
+            r#"
+INSERT INTO items (id, sha256, md5, type, time_added) VALUES
+    (1, 'sha1', 'md51', 'image/png', '2024-01-01T00:00:00'),
+    (2, 'sha2', 'md52', 'image/png', '2024-01-01T00:00:00');
+INSERT INTO file_scans (id, start_time, path) VALUES (1, '2024-01-01T00:00:00', '/');
+INSERT INTO files (id, sha256, item_id, path, filename, last_modified, scan_id, available)
+VALUES
+    (1, 'sha1', 1, ?1, 'missing.png', '2024-01-01T00:00:00', 1, 1),
+    (2, 'sha1', 1, ?2, 'present.png', '2024-01-01T00:00:00', 1, 1),
+    (3, 'sha2', 2, ?3, 'other.png', '2024-01-01T00:00:00', 1, 1);
+            "#,
+        )
+        .bind(missing.to_string_lossy())
+        .bind(present.to_string_lossy())
+        .bind(other.to_string_lossy())
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+
+        let launched = Mutex::new(Vec::new());
+        let stub = |command: LaunchCommand| {
+            launched.lock().unwrap().push(command.args);
+            async { Ok(()) }
+        };
+        let query = |path: Option<&std::path::PathBuf>, mode| OpenItemQuery {
+            id: "sha1".to_string(),
+            id_type: ItemIdentifierType::Sha256,
+            path: path.map(|path| path.to_string_lossy().into_owned()),
+            mode,
+        };
+        let open = OpenConfig {
+            file_program: Some("viewer".to_string()),
+            file_args: vec!["{path}".to_string()],
+            ..OpenConfig::default()
+        };
+
+        let response = open_item_with(conn, query(None, OpenMode::Open), &open, stub)
+            .await
+            .unwrap();
+        assert_eq!(response.path, present.to_string_lossy());
+        for path in [&missing, &other] {
+            let err = open_item_with(conn, query(Some(path), OpenMode::Open), &open, stub)
+                .await
+                .unwrap_err();
+            assert_eq!(err.status(), StatusCode::NOT_FOUND);
+        }
+        assert_eq!(
+            *launched.lock().unwrap(),
+            vec![vec![present.to_string_lossy().into_owned()]]
+        );
+    }
+}
@@ -16,6 +16,7 @@ use std::{
     time::{SystemTime, UNIX_EPOCH},
 };
 use tokio::sync::Mutex;
+use utoipa::ToSchema;
 use uuid::Uuid;
 
 use crate::{api_error::ApiError, policy::PolicyContext, proxy::ProxyState};
@@ -55,24 +56,32 @@ struct PairingStore {
     operations: Vec<PairingOperation>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct CommitPairing {
+    #[schema(value_type = String, format = "uuid")]
     relay_id: Uuid,
+    #[schema(value_type = String, format = "uuid")]
     instance_id: Uuid,
+    /// 32 to 512 characters.
     credential: String,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct PairingResponse {
+    #[schema(value_type = String, format = "uuid")]
     relay_id: Uuid,
+    #[schema(value_type = String, format = "uuid")]
     instance_id: Uuid,
     credential: String,
+    #[schema(value_type = Option<String>, format = "uuid")]
     operation_id: Option<Uuid>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct PairingOperationResponse {
+    #[schema(value_type = String, format = "uuid")]
     relay_id: Uuid,
+    #[schema(value_type = String, format = "uuid")]
     operation_id: Uuid,
     expires_unix: i64,
 }
@@ -227,6 +236,19 @@ fn no_store<T: IntoResponse>(body: T) -> impl IntoResponse {
     ([(header::CACHE_CONTROL, "no-store")], body)
 }
 
+#[utoipa::path(
+    get,
+    operation_id = "get_relay_pairing",
+    path = "/api/relay/pairings/{relay_id}",
+    tag = "relay",
+    summary = "Get the stored Relay pairing for this policy",
+    params(("relay_id" = String, Path, format = "uuid", description = "The Relay's id")),
+    responses(
+        (status = 200, description = "Stored pairing", body = PairingResponse),
+        (status = 403, description = "Relay is disabled for this endpoint"),
+        (status = 404, description = "No pairing stored for this Relay")
+    )
+)]
 pub async fn get_pairing(
     State(state): State<Arc<ProxyState>>,
     Extension(context): Extension<PolicyContext>,
@@ -257,6 +279,19 @@ pub async fn get_pairing(
     ))
 }
 
+#[utoipa::path(
+    get,
+    operation_id = "get_relay_pairing_operation",
+    path = "/api/relay/pairing-operations/{relay_id}",
+    tag = "relay",
+    summary = "Get the pending pairing operation for a Relay",
+    params(("relay_id" = String, Path, format = "uuid", description = "The Relay's id")),
+    responses(
+        (status = 200, description = "Pending operation", body = PairingOperationResponse),
+        (status = 403, description = "Relay is disabled for this endpoint"),
+        (status = 404, description = "No pending operation for this Relay")
+    )
+)]
 pub async fn get_pairing_operation(
     State(state): State<Arc<ProxyState>>,
     Extension(context): Extension<PolicyContext>,
@@ -288,6 +323,21 @@ pub async fn get_pairing_operation(
     ))
 }
 
+#[utoipa::path(
+    post,
+    operation_id = "begin_relay_pairing_operation",
+    path = "/api/relay/pairing-operations/{relay_id}",
+    tag = "relay",
+    summary = "Begin a pairing operation for a Relay",
+    description = "Starts a pairing operation that expires after ten minutes. Idempotent: if one is already pending for the Relay, it is returned instead.",
+    params(("relay_id" = String, Path, format = "uuid", description = "The Relay's id")),
+    responses(
+        (status = 200, description = "Already pending operation", body = PairingOperationResponse),
+        (status = 202, description = "New operation", body = PairingOperationResponse),
+        (status = 403, description = "Relay is disabled for this endpoint"),
+        (status = 429, description = "Too many pending operations")
+    )
+)]
 pub async fn begin_pairing_operation(
     State(state): State<Arc<ProxyState>>,
     Extension(context): Extension<PolicyContext>,
@@ -342,6 +392,24 @@ pub async fn begin_pairing_operation(
     ))
 }
 
+#[utoipa::path(
+    put,
+    operation_id = "commit_relay_pairing_operation",
+    path = "/api/relay/pairing-operations/{operation_id}/commit",
+    tag = "relay",
+    summary = "Store the credential of a pending pairing operation",
+    description = "Idempotent: committing the same credential again succeeds.",
+    params(("operation_id" = String, Path, format = "uuid", description = "The pairing operation's id")),
+    request_body(content = CommitPairing),
+    responses(
+        (status = 204, description = "Pairing stored"),
+        (status = 400, description = "Invalid credential"),
+        (status = 403, description = "Relay is disabled for this endpoint"),
+        (status = 409, description = "The Relay was paired by another operation"),
+        (status = 410, description = "The operation expired"),
+        (status = 429, description = "Too many stored pairings")
+    )
+)]
 pub async fn commit_pairing_operation(
     State(state): State<Arc<ProxyState>>,
     Extension(context): Extension<PolicyContext>,
@@ -410,6 +478,18 @@ pub async fn commit_pairing_operation(
     Ok(no_store(StatusCode::NO_CONTENT))
 }
 
+#[utoipa::path(
+    delete,
+    operation_id = "cancel_relay_pairing_operation",
+    path = "/api/relay/pairing-operations/{operation_id}/cancel",
+    tag = "relay",
+    summary = "Cancel a pending pairing operation",
+    params(("operation_id" = String, Path, format = "uuid", description = "The pairing operation's id")),
+    responses(
+        (status = 204, description = "Operation cancelled or already gone"),
+        (status = 403, description = "Relay is disabled for this endpoint")
+    )
+)]
 pub async fn cancel_pairing_operation(
     State(state): State<Arc<ProxyState>>,
     Extension(context): Extension<PolicyContext>,
@@ -427,6 +507,18 @@ pub async fn cancel_pairing_operation(
     Ok(no_store(StatusCode::NO_CONTENT))
 }
 
+#[utoipa::path(
+    delete,
+    operation_id = "delete_relay_pairing",
+    path = "/api/relay/pairings/{relay_id}",
+    tag = "relay",
+    summary = "Forget the stored Relay pairing for this policy",
+    params(("relay_id" = String, Path, format = "uuid", description = "The Relay's id")),
+    responses(
+        (status = 204, description = "Pairing removed or already gone"),
+        (status = 403, description = "Relay is disabled for this endpoint")
+    )
+)]
 pub async fn delete_pairing(
     State(state): State<Arc<ProxyState>>,
     Extension(context): Extension<PolicyContext>,
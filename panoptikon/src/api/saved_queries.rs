@@ -0,0 +1,788 @@
+use std::sync::Arc;
+
+use axum::{
+    Extension, Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use axum_extra::extract::Query;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::api::db_params::DbQueryParams;
+use crate::api::search::{
+    BookmarkStatusParams, FileSearchResponse, execute_pql, scope_bookmark_filters,
+};
+use crate::api_error::ApiError;
+use crate::db::saved_queries::{self, SavedQueryRecord, WriteOutcome};
+use crate::db::{DbConnection, ReadOnly, UserDataWrite};
+use crate::policy::{PolicyContext, RequestIdentity, UserAccess, scoped_user};
+use crate::pql::build_query;
+use crate::pql::model::{AndOperator, NotOperator, OrOperator, PqlQuery, QueryElement};
+use crate::proxy::ProxyState;
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+/// Identifies an export document, so an import can reject unrelated JSON.
+const EXPORT_FORMAT: &str = "panoptikon.saved_queries";
+const EXPORT_VERSION: u32 = 1;
+const MAX_NAME_CHARS: usize = 128;
+/// Path segments under /api/search/saved that are routes, not names.
+const RESERVED_NAMES: [&str; 2] = ["export", "import"];
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct SavedQueryUserQuery {
+    /// The user the saved queries belong to.
+    /// Defaults to the authenticated user when the policy resolves one.
+    #[serde(default)]
+    #[param(default = "user")]
+    user: Option<String>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct SavedQueryRunQuery {
+    /// The user the saved query belongs to.
+    /// Defaults to the authenticated user when the policy resolves one.
+    #[serde(default)]
+    #[param(default = "user")]
+    user: Option<String>,
+    /// Overrides the stored `page`.
+    page: Option<i64>,
+    /// Overrides the stored `page_size`.
+    page_size: Option<i64>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct SavedQueryImportQuery {
+    /// The user to import the saved queries for.
+    /// Defaults to the authenticated user when the policy resolves one.
+    #[serde(default)]
+    #[param(default = "user")]
+    user: Option<String>,
+    /// Replace existing saved queries with the same name instead of
+    /// skipping them.
+    #[serde(default)]
+    #[param(default = false)]
+    overwrite: bool,
+}
+
+/// Body for creating or replacing a saved query.
+#[derive(Deserialize, ToSchema)]
+pub(crate) struct SavedQueryRequest {
+    /// Unique per user; also the identifier in the URL.
+    name: String,
+    description: Option<String>,
+    /// The PQL search body, as sent to `/api/search/pql`.
+    query: PqlQuery,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct SavedQueryResponse {
+    id: i64,
+    name: String,
+    description: Option<String>,
+    pub(crate) query: PqlQuery,
+    time_added: String,
+    time_updated: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct SavedQueryListResponse {
+    /// Ordered by name.
+    queries: Vec<SavedQueryResponse>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct SavedQueryDeleteResponse {
+    message: String,
+}
+
+/// One saved query in an export document. Ids and timestamps are
+/// instance-local and not carried over.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub(crate) struct SavedQueryExportEntry {
+    name: String,
+    #[serde(default)]
+    description: Option<String>,
+    query: PqlQuery,
+}
+
+/// Portable document for moving saved queries between instances.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub(crate) struct SavedQueryExport {
+    /// Always `panoptikon.saved_queries`.
+    format: String,
+    /// Format version; currently 1.
+    version: u32,
+    queries: Vec<SavedQueryExportEntry>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct SavedQueryImportResponse {
+    /// Names that did not exist before.
+    created: Vec<String>,
+    /// Existing names replaced because `overwrite` was set.
+    replaced: Vec<String>,
+    /// Existing names left untouched because `overwrite` was not set.
+    skipped: Vec<String>,
+}
+
+/// Checks a name and query as on save, returning the reason for a 400.
+fn validate_entry(name: &str, query: &PqlQuery) -> Result<(), String> {
+    if name.trim().is_empty() || name.trim() != name {
+        return Err("Name must be non-empty without surrounding whitespace".to_string());
+    }
+    if name.chars().count() > MAX_NAME_CHARS {
+        return Err("Name too long".to_string());
+    }
+    if name.contains('/') {
+        return Err("Name must not contain '/'".to_string());
+    }
+    if RESERVED_NAMES.contains(&name) {
+        return Err(format!("Name '{name}' is reserved"));
+    }
+    validate_query(query)
+}
+
+/// Save-time validation: a dry-run `build_query`, which preprocesses the
+/// tree exactly like a search. Vector-search filters embed their query text
+/// through the inference server, which saving must not depend on, so they
+/// are checked for inline embeddings and then pruned from the dry run; they
+/// are fully validated (and re-embedded) each time the query runs.
+fn validate_query(query: &PqlQuery) -> Result<(), String> {
+    let mut pruned = query.clone();
+    pruned.query = match &query.query {
+        Some(root) => without_vector_leaves(root)?,
+        None => None,
+    };
+    build_query(pruned, false).map_err(|err| err.message)?;
+    Ok(())
+}
+
+fn without_vector_leaves(element: &QueryElement) -> Result<Option<QueryElement>, String> {
+    let prune_all = |children: &[QueryElement]| -> Result<Vec<QueryElement>, String> {
+        let mut kept = Vec::new();
+        for child in children {
+            kept.extend(without_vector_leaves(child)?);
+        }
+        Ok(kept)
+    };
+    match element {
+        QueryElement::And(op) => {
+            let and_ = prune_all(&op.and_)?;
+            Ok((!and_.is_empty()).then_some(QueryElement::And(AndOperator { and_ })))
+        }
+        QueryElement::Or(op) => {
+            let or_ = prune_all(&op.or_)?;
+            Ok((!or_.is_empty()).then_some(QueryElement::Or(OrOperator { or_ })))
+        }
+        QueryElement::Not(op) => Ok(without_vector_leaves(&op.not_)?.map(|child| {
+            QueryElement::Not(NotOperator {
+                not_: Box::new(child),
+            })
+        })),
+        QueryElement::SemanticTextSearch(filter) => {
+            if filter.text_embeddings.embed.is_none() {
+                return Err(inline_embedding_error("text_embeddings"));
+            }
+            Ok(None)
+        }
+        QueryElement::SemanticImageSearch(filter) => {
+            if filter.image_embeddings.embed.is_none() {
+                return Err(inline_embedding_error("image_embeddings"));
+            }
+            Ok(None)
+        }
+        QueryElement::SimilarTo(_) => Ok(None),
+        leaf => Ok(Some(leaf.clone())),
+    }
+}
+
+fn inline_embedding_error(filter: &str) -> String {
+    format!(
+        "Saved queries cannot store precomputed embeddings: set `embed` on {filter} so the query is embedded when it runs"
+    )
+}
+
+fn serialize_query(query: &PqlQuery) -> ApiResult<String> {
+    serde_json::to_string(query).map_err(|err| {
+        tracing::error!(error = %err, "failed to serialize saved query");
+        ApiError::internal("Failed to serialize saved query")
+    })
+}
+
+fn parse_query(raw: &str) -> ApiResult<PqlQuery> {
+    serde_json::from_str(raw).map_err(|err| {
+        tracing::error!(error = %err, "failed to parse stored saved query");
+        ApiError::internal("Failed to parse stored saved query")
+    })
+}
+
+fn map_record(record: SavedQueryRecord) -> ApiResult<SavedQueryResponse> {
+    Ok(SavedQueryResponse {
+        query: parse_query(&record.query)?,
+        id: record.id,
+        name: record.name,
+        description: record.description,
+        time_added: record.time_added,
+        time_updated: record.time_updated,
+    })
+}
+
+fn name_taken(name: &str) -> ApiError {
+    ApiError::new(
+        StatusCode::CONFLICT,
+        format!("A saved query named '{name}' already exists"),
+    )
+}
+
+pub(crate) async fn load_saved_query(
+    conn: &mut sqlx::SqliteConnection,
+    user: &str,
+    name: &str,
+) -> ApiResult<SavedQueryResponse> {
+    match saved_queries::get_saved_query(conn, user, name).await? {
+        Some(record) => map_record(record),
+        None => Err(ApiError::not_found("Saved query not found")),
+    }
+}
+
+#[utoipa::path(
+    get,
+    operation_id = "list_saved_queries",
+    path = "/api/search/saved",
+    tag = "saved_queries",
+    summary = "List saved queries",
+    params(DbQueryParams, SavedQueryUserQuery),
+    responses(
+        (status = 200, description = "Saved queries", body = SavedQueryListResponse)
+    )
+)]
+pub async fn list_saved_queries(
+    mut db: DbConnection<ReadOnly>,
+    Query(query): Query<SavedQueryUserQuery>,
+    identity: Option<Extension<RequestIdentity>>,
+) -> ApiResult<Json<SavedQueryListResponse>> {
+    let user = scoped_user(identity.as_deref(), query.user.as_deref(), UserAccess::Read)?;
+    let records = saved_queries::list_saved_queries(&mut db.conn, &user).await?;
+    let queries = records
+        .into_iter()
+        .map(map_record)
+        .collect::<ApiResult<Vec<_>>>()?;
+    Ok(Json(SavedQueryListResponse { queries }))
+}
+
+#[utoipa::path(
+    post,
+    operation_id = "create_saved_query",
+    path = "/api/search/saved",
+    tag = "saved_queries",
+    summary = "Save a PQL query under a name",
+    description = "Validates the query with the same preprocessing a search applies, then stores it.\nVector-search filters (`text_embeddings`, `image_embeddings`, `similar_to`) are validated when the query runs, since embedding needs the inference server; they must use `embed` rather than an inline base64 embedding, because resolved embeddings are never stored.",
+    params(DbQueryParams, SavedQueryUserQuery),
+    request_body(content = SavedQueryRequest),
+    responses(
+        (status = 200, description = "Saved query", body = SavedQueryResponse),
+        (status = 400, description = "Invalid name or query"),
+        (status = 409, description = "Name already in use")
+    )
+)]
+pub async fn create_saved_query(
+    mut db: DbConnection<UserDataWrite>,
+    Query(query): Query<SavedQueryUserQuery>,
+    identity: Option<Extension<RequestIdentity>>,
+    Json(request): Json<SavedQueryRequest>,
+) -> ApiResult<Json<SavedQueryResponse>> {
+    let user = scoped_user(
+        identity.as_deref(),
+        query.user.as_deref(),
+        UserAccess::Write,
+    )?;
+    validate_entry(&request.name, &request.query).map_err(ApiError::bad_request)?;
+    let serialized = serialize_query(&request.query)?;
+    match saved_queries::create_saved_query(
+        &mut db.conn,
+        &user,
+        &request.name,
+        request.description.as_deref(),
+        &serialized,
+    )
+    .await?
+    {
+        WriteOutcome::Written => {}
+        WriteOutcome::NameTaken | WriteOutcome::NotFound => {
+            return Err(name_taken(&request.name));
+        }
+    }
+    load_saved_query(&mut db.conn, &user, &request.name)
+        .await
+        .map(Json)
+}
+
+#[utoipa::path(
+    get,
+    operation_id = "get_saved_query",
+    path = "/api/search/saved/{name}",
+    tag = "saved_queries",
+    summary = "Get a saved query",
+    params(
+        DbQueryParams,
+        ("name" = String, Path, description = "The saved query's name"),
+        SavedQueryUserQuery
+    ),
+    responses(
+        (status = 200, description = "Saved query", body = SavedQueryResponse),
+        (status = 404, description = "Saved query not found")
+    )
+)]
+pub async fn get_saved_query(
+    mut db: DbConnection<ReadOnly>,
+    Path(name): Path<String>,
+    Query(query): Query<SavedQueryUserQuery>,
+    identity: Option<Extension<RequestIdentity>>,
+) -> ApiResult<Json<SavedQueryResponse>> {
+    let user = scoped_user(identity.as_deref(), query.user.as_deref(), UserAccess::Read)?;
+    load_saved_query(&mut db.conn, &user, &name).await.map(Json)
+}
+
+#[utoipa::path(
+    put,
+    operation_id = "update_saved_query",
+    path = "/api/search/saved/{name}",
+    tag = "saved_queries",
+    summary = "Replace a saved query",
+    description = "Replaces the saved query's name, description and query. A different `name` in the body renames it. Validation is the same as on create.",
+    params(
+        DbQueryParams,
+        ("name" = String, Path, description = "The saved query's current name"),
+        SavedQueryUserQuery
+    ),
+    request_body(content = SavedQueryRequest),
+    responses(
+        (status = 200, description = "Updated saved query", body = SavedQueryResponse),
+        (status = 400, description = "Invalid name or query"),
+        (status = 404, description = "Saved query not found"),
+        (status = 409, description = "New name already in use")
+    )
+)]
+pub async fn update_saved_query(
+    mut db: DbConnection<UserDataWrite>,
+    Path(name): Path<String>,
+    Query(query): Query<SavedQueryUserQuery>,
+    identity: Option<Extension<RequestIdentity>>,
+    Json(request): Json<SavedQueryRequest>,
+) -> ApiResult<Json<SavedQueryResponse>> {
+    let user = scoped_user(
+        identity.as_deref(),
+        query.user.as_deref(),
+        UserAccess::Write,
+    )?;
+    validate_entry(&request.name, &request.query).map_err(ApiError::bad_request)?;
+    let serialized = serialize_query(&request.query)?;
+    match saved_queries::update_saved_query(
+        &mut db.conn,
+        &user,
+        &name,
+        &request.name,
+        request.description.as_deref(),
+        &serialized,
+    )
+    .await?
+    {
+        WriteOutcome::Written => {}
+        WriteOutcome::NotFound => return Err(ApiError::not_found("Saved query not found")),
+        WriteOutcome::NameTaken => return Err(name_taken(&request.name)),
+    }
+    load_saved_query(&mut db.conn, &user, &request.name)
+        .await
+        .map(Json)
+}
+
+#[utoipa::path(
+    delete,
+    operation_id = "delete_saved_query",
+    path = "/api/search/saved/{name}",
+    tag = "saved_queries",
+    summary = "Delete a saved query",
+    params(
+        DbQueryParams,
+        ("name" = String, Path, description = "The saved query's name"),
+        SavedQueryUserQuery
+    ),
+    responses(
+        (status = 200, description = "Deleted", body = SavedQueryDeleteResponse),
+        (status = 404, description = "Saved query not found")
+    )
+)]
+pub async fn delete_saved_query(
+    mut db: DbConnection<UserDataWrite>,
+    Path(name): Path<String>,
+    Query(query): Query<SavedQueryUserQuery>,
+    identity: Option<Extension<RequestIdentity>>,
+) -> ApiResult<Json<SavedQueryDeleteResponse>> {
+    let user = scoped_user(
+        identity.as_deref(),
+        query.user.as_deref(),
+        UserAccess::Write,
+    )?;
+    if !saved_queries::delete_saved_query(&mut db.conn, &user, &name).await? {
+        return Err(ApiError::not_found("Saved query not found"));
+    }
+    Ok(Json(SavedQueryDeleteResponse {
+        message: "Saved query deleted".to_string(),
+    }))
+}
+
+#[utoipa::path(
+    get,
+    operation_id = "run_saved_query",
+    path = "/api/search/saved/{name}/run",
+    tag = "saved_queries",
+    summary = "Run a saved query",
+    description = "Executes the stored query exactly like `/api/search/pql`, with `page` and `page_size` optionally overridden. Vector-search filters are embedded afresh on every run.",
+    params(
+        DbQueryParams,
+        ("name" = String, Path, description = "The saved query's name"),
+        SavedQueryRunQuery,
+        BookmarkStatusParams
+    ),
+    responses(
+        (status = 200, description = "Search results", body = FileSearchResponse),
+        (status = 400, description = "The stored query no longer compiles"),
+        (status = 404, description = "Saved query not found")
+    )
+)]
+pub async fn run_saved_query(
+    State(state): State<Arc<ProxyState>>,
+    mut db: DbConnection<ReadOnly>,
+    Path(name): Path<String>,
+    Query(run): Query<SavedQueryRunQuery>,
+    Query(mut bookmark_params): Query<BookmarkStatusParams>,
+    policy: Option<Extension<PolicyContext>>,
+    identity: Option<Extension<RequestIdentity>>,
+) -> ApiResult<Json<FileSearchResponse>> {
+    let user = scoped_user(identity.as_deref(), run.user.as_deref(), UserAccess::Read)?;
+    let mut query = load_saved_query(&mut db.conn, &user, &name).await?.query;
+    scope_bookmark_filters(&mut query, identity.as_deref())?;
+    bookmark_params.scope(identity.as_deref())?;
+    if let Some(page) = run.page {
+        query.page = page;
+    }
+    if let Some(page_size) = run.page_size {
+        query.page_size = page_size;
+    }
+    let policy = policy.as_ref().map(|Extension(context)| context);
+    // Slow query log context: which saved query ran, and what it asked.
+    let payload = serde_json::json!({ "saved_query": name, "query": query });
+    execute_pql(&state, &mut db, &bookmark_params, policy, query, Some(&payload))
+        .await
+        .map(Json)
+}
+
+#[utoipa::path(
+    get,
+    operation_id = "export_saved_queries",
+    path = "/api/search/saved/export",
+    tag = "saved_queries",
+    summary = "Export saved queries",
+    description = "Returns all of the user's saved queries as a portable document that `/api/search/saved/import` accepts on any instance.",
+    params(DbQueryParams, SavedQueryUserQuery),
+    responses(
+        (status = 200, description = "Export document", body = SavedQueryExport)
+    )
+)]
+pub async fn export_saved_queries(
+    mut db: DbConnection<ReadOnly>,
+    Query(query): Query<SavedQueryUserQuery>,
+    identity: Option<Extension<RequestIdentity>>,
+) -> ApiResult<Json<SavedQueryExport>> {
+    let user = scoped_user(identity.as_deref(), query.user.as_deref(), UserAccess::Read)?;
+    let records = saved_queries::list_saved_queries(&mut db.conn, &user).await?;
+    let mut queries = Vec::with_capacity(records.len());
+    for record in records {
+        queries.push(SavedQueryExportEntry {
+            query: parse_query(&record.query)?,
+            name: record.name,
+            description: record.description,
+        });
+    }
+    Ok(Json(SavedQueryExport {
+        format: EXPORT_FORMAT.to_string(),
+        version: EXPORT_VERSION,
+        queries,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    operation_id = "import_saved_queries",
+    path = "/api/search/saved/import",
+    tag = "saved_queries",
+    summary = "Import saved queries",
+    description = "Imports a document produced by `/api/search/saved/export`. Every entry is validated as on create before anything is written; one invalid entry rejects the whole import.\nExisting names are skipped unless `overwrite` is set.",
+    params(DbQueryParams, SavedQueryImportQuery),
+    request_body(content = SavedQueryExport),
+    responses(
+        (status = 200, description = "Import summary", body = SavedQueryImportResponse),
+        (status = 400, description = "Unsupported document or invalid entry")
+    )
+)]
+pub async fn import_saved_queries(
+    mut db: DbConnection<UserDataWrite>,
+    Query(query): Query<SavedQueryImportQuery>,
+    identity: Option<Extension<RequestIdentity>>,
+    Json(document): Json<SavedQueryExport>,
+) -> ApiResult<Json<SavedQueryImportResponse>> {
+    let user = scoped_user(
+        identity.as_deref(),
+        query.user.as_deref(),
+        UserAccess::Write,
+    )?;
+    if document.format != EXPORT_FORMAT || document.version != EXPORT_VERSION {
+        return Err(ApiError::bad_request(format!(
+            "Unsupported saved query export: expected format '{EXPORT_FORMAT}' version {EXPORT_VERSION}"
+        )));
+    }
+    let mut entries = Vec::with_capacity(document.queries.len());
+    for entry in &document.queries {
+        validate_entry(&entry.name, &entry.query)
+            .map_err(|err| ApiError::bad_request(format!("Saved query '{}': {err}", entry.name)))?;
+        if entries.iter().any(|(name, _, _)| name == &entry.name) {
+            return Err(ApiError::bad_request(format!(
+                "Saved query '{}' appears more than once",
+                entry.name
+            )));
+        }
+        entries.push((
+            entry.name.clone(),
+            entry.description.clone(),
+            serialize_query(&entry.query)?,
+        ));
+    }
+
+    begin_transaction(&mut db.conn).await?;
+    let result: ApiResult<SavedQueryImportResponse> = async {
+        let mut response = SavedQueryImportResponse {
+            created: Vec::new(),
+            replaced: Vec::new(),
+            skipped: Vec::new(),
+        };
+        for (name, description, serialized) in entries {
+            let created = saved_queries::create_saved_query(
+                &mut db.conn,
+                &user,
+                &name,
+                description.as_deref(),
+                &serialized,
+            )
+            .await?;
+            match created {
+                WriteOutcome::Written => response.created.push(name),
+                WriteOutcome::NameTaken | WriteOutcome::NotFound if !query.overwrite => {
+                    response.skipped.push(name)
+                }
+                WriteOutcome::NameTaken | WriteOutcome::NotFound => {
+                    saved_queries::update_saved_query(
+                        &mut db.conn,
+                        &user,
+                        &name,
+                        &name,
+                        description.as_deref(),
+                        &serialized,
+                    )
+                    .await?;
+                    response.replaced.push(name);
+                }
+            }
+        }
+        Ok(response)
+    }
+    .await;
+
+    match result {
+        Ok(response) => {
+            commit_transaction(&mut db.conn).await?;
+            Ok(Json(response))
+        }
+        Err(err) => {
+            let _ = rollback_transaction(&mut db.conn).await;
+            Err(err)
+        }
+    }
+}
+
+async fn begin_transaction(conn: &mut sqlx::SqliteConnection) -> ApiResult<()> {
+    sqlx::query("BEGIN TRANSACTION")
+        .execute(conn)
+        .await
+        .map_err(|err| {
+            tracing::error!(error = %err, "failed to start transaction");
+            ApiError::internal("Failed to start transaction")
+        })?;
+    Ok(())
+}
+
+async fn commit_transaction(conn: &mut sqlx::SqliteConnection) -> ApiResult<()> {
+    sqlx::query("COMMIT").execute(conn).await.map_err(|err| {
+        tracing::error!(error = %err, "failed to commit transaction");
+        ApiError::internal("Failed to commit transaction")
+    })?;
+    Ok(())
+}
+
+async fn rollback_transaction(conn: &mut sqlx::SqliteConnection) -> ApiResult<()> {
+    sqlx::query("ROLLBACK").execute(conn).await.map_err(|err| {
+        tracing::error!(error = %err, "failed to rollback transaction");
+        ApiError::internal("Failed to rollback transaction")
+    })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::migrations::setup_test_databases;
+    use serde_json::json;
+
+    fn pql(value: serde_json::Value) -> PqlQuery {
+        serde_json::from_value(value).expect("pql query")
+    }
+
+    // Embedded vector filters are deferred to run time; the rest is compiled.
+    #[test]
+    fn validation_defers_vector_filters_and_compiles_the_rest() {
+        let query = pql(json!({
+            "query": { "and_": [
+                { "match": { "gt": { "size": 10 } } },
+                { "text_embeddings": { "query": "cat", "model": "clip" } }
+            ] }
+        }));
+        assert_eq!(validate_entry("cats", &query), Ok(()));
+
+        let invalid = pql(json!({ "query": { "match": { "in_": { "size": 5 } } } }));
+        assert!(validate_entry("bad", &invalid).is_err());
+    }
+
+    // Inline base64 embeddings would be persisted, so they are rejected.
+    #[test]
+    fn validation_rejects_inline_embeddings_and_bad_names() {
+        let inline = pql(json!({
+            "query": { "image_embeddings": { "query": "AAAA", "model": "clip", "embed": null } }
+        }));
+        let err = validate_entry("inline", &inline).unwrap_err();
+        assert!(err.contains("image_embeddings"), "{err}");
+
+        let empty = PqlQuery::default();
+        assert!(validate_entry(" padded", &empty).is_err());
+        assert!(validate_entry("a/b", &empty).is_err());
+        assert!(validate_entry("export", &empty).is_err());
+    }
+
+    // Names are unique per user; renames onto a taken name are refused.
+    #[tokio::test]
+    async fn saved_query_names_are_unique_per_user() {
+        let mut dbs = setup_test_databases().await;
+        let conn = &mut dbs.index_conn;
+        for (user, name) in [("user", "a"), ("user", "b"), ("other", "a")] {
+            let outcome = saved_queries::create_saved_query(conn, user, name, None, "{}")
+                .await
+                .unwrap();
+            assert!(matches!(outcome, WriteOutcome::Written));
+        }
+        let duplicate = saved_queries::create_saved_query(conn, "user", "a", None, "{}")
+            .await
+            .unwrap();
+        assert!(matches!(duplicate, WriteOutcome::NameTaken));
+
+        let rename = saved_queries::update_saved_query(conn, "user", "b", "a", None, "{}")
+            .await
+            .unwrap();
+        assert!(matches!(rename, WriteOutcome::NameTaken));
+        let missing = saved_queries::update_saved_query(conn, "user", "zzz", "zzz", None, "{}")
+            .await
+            .unwrap();
+        assert!(matches!(missing, WriteOutcome::NotFound));
+
+        let rename = saved_queries::update_saved_query(conn, "user", "b", "c", Some("d"), "{}")
+            .await
+            .unwrap();
+        assert!(matches!(rename, WriteOutcome::Written));
+        let names = saved_queries::list_saved_queries(conn, "user")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|record| record.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["a".to_string(), "c".to_string()]);
+
+        assert!(
+            saved_queries::delete_saved_query(conn, "user", "a")
+                .await
+                .unwrap()
+        );
+        assert!(
+            !saved_queries::delete_saved_query(conn, "user", "a")
+                .await
+                .unwrap()
+        );
+        assert!(
+            saved_queries::get_saved_query(conn, "other", "a")
+                .await
+                .unwrap()
+                .is_some()
+        );
+    }
+
+    // A stored query round-trips and never carries a resolved embedding.
+    #[tokio::test]
+    async fn stored_query_round_trips_without_embeddings() {
+        let mut dbs = setup_test_databases().await;
+        let mut query = pql(json!({
+            "query": { "text_embeddings": { "query": "cat", "model": "clip" } },
+            "page_size": 25
+        }));
+        if let Some(QueryElement::SemanticTextSearch(filter)) = query.query.as_mut() {
+            filter.text_embeddings._embedding = Some(vec![1, 2, 3, 4]);
+        }
+        let serialized = serialize_query(&query).unwrap();
+        saved_queries::create_saved_query(&mut dbs.index_conn, "user", "cats", None, &serialized)
+            .await
+            .unwrap();
+
+        let loaded = load_saved_query(&mut dbs.index_conn, "user", "cats")
+            .await
+            .unwrap();
+        assert_eq!(loaded.query.page_size, 25);
+        let Some(QueryElement::SemanticTextSearch(filter)) = loaded.query.query else {
+            panic!("expected text_embeddings");
+        };
+        assert_eq!(filter.text_embeddings.query, "cat");
+        assert!(filter.text_embeddings._embedding.is_none());
+        assert!(filter.text_embeddings.embed.is_some());
+    }
+
+    // The export document parses back into what import accepts.
+    #[test]
+    fn export_document_round_trips() {
+        let document = SavedQueryExport {
+            format: EXPORT_FORMAT.to_string(),
+            version: EXPORT_VERSION,
+            queries: vec![SavedQueryExportEntry {
+                name: "large".to_string(),
+                description: Some("big files".to_string()),
+                query: pql(json!({ "query": { "match": { "gt": { "size": 10 } } } })),
+            }],
+        };
+        let value = serde_json::to_value(&document).unwrap();
+        assert_eq!(value["format"], "panoptikon.saved_queries");
+        let parsed: SavedQueryExport = serde_json::from_value(value).unwrap();
+        assert_eq!(parsed.queries.len(), 1);
+        assert_eq!(
+            validate_entry(&parsed.queries[0].name, &parsed.queries[0].query),
+            Ok(())
+        );
+    }
+}
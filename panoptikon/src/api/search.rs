@@ -1,27 +1,45 @@
 use crate::api::db_params::DbQueryParams;
+use crate::api::items::{EmbeddingFormat, EmbeddingVector, encode_embedding};
 use crate::api::search_cache::{self, CacheLookup, EpochSnapshot, QueryKey};
+use crate::api::search_slowlog::{self, SlowQuery};
+use crate::api::tag_index;
 use crate::api_error::ApiError;
 use crate::db::bookmarks::get_all_bookmark_namespaces;
+use crate::db::embeddings::{EXPORT_CHUNK_SIZE, get_embeddings_by_ids, select_export_page};
 use crate::db::extraction_log::get_existing_setters;
 use crate::db::folders::get_folders_from_database;
+use crate::db::index_writer::{IndexDbWriterMessage, call_index_db_writer};
 use crate::db::items::{
     TextStats, get_all_mime_types, get_existing_file_for_item_id, get_file_stats, get_text_stats,
 };
 use crate::db::pql::{run_compiled_count, run_compiled_query};
+use crate::db::storage_stats::{
+    StorageStats, compute_and_store_storage_stats, compute_storage_stats,
+    get_storage_stats_snapshot,
+};
+use crate::db::tag_aliases::{TagAliasGroup, collapse_tag_counts, list_tag_alias_groups};
 use crate::db::tags::{
     find_tags, get_all_tag_namespaces, get_min_tag_confidence, get_most_common_tags_frequency,
 };
-use crate::db::{DbConnection, ReadOnly};
-use crate::policy::PolicyContext;
-use crate::pql::model::{EntityType, PqlQuery};
+use crate::db::{DbConnection, ReadOnly, ReadOnlyNoUserData};
+use crate::inferio_client::InferenceApiClient;
+use crate::msgpack;
+use crate::policy::{PolicyContext, RequestIdentity, UserAccess, scoped_user};
+use crate::pql::model::{Column as PqlColumn, EntityType, PqlQuery, QueryElement};
 use crate::pql::{
-    EmbeddingCacheStats, PqlError, build_query_preprocessed, clear_embedding_cache,
-    embedding_cache_stats, preprocess_query_async,
+    EmbeddingCacheStats, OrderKey, apply_refine, build_query_preprocessed, clear_embedding_cache,
+    embedding_cache_stats, preprocess_query_async, reject_disabled_filters,
 };
 use crate::proxy::ProxyState;
-use axum::{Extension, Json, extract::State};
+use axum::{
+    Extension, Json,
+    body::{Body, Bytes},
+    extract::State,
+    http::{HeaderMap, Response, StatusCode, header},
+};
 use axum_extra::extract::Query;
 use base64::{Engine as _, engine::general_purpose};
+use futures_util::StreamExt;
 use sea_query::{SqliteQueryBuilder, Value as SeaValue, Values};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
@@ -29,20 +47,26 @@ use sqlx::Column;
 use sqlx::Row;
 use std::{
     collections::{HashMap, HashSet},
-    path::Path,
     sync::Arc,
-    time::Instant,
+    time::{Duration, Instant},
 };
 use utoipa::{IntoParams, ToSchema};
 
 type ApiResult<T> = std::result::Result<T, ApiError>;
 
+mod multi_db;
+mod stacks;
+
 const DEFAULT_LIMIT: i64 = 10;
 const DEFAULT_USER: &str = "user";
 /// Server-side clamp on the request's `prefetch_rows`. A row budget rather
 /// than a page count, so a large page size can no longer multiply into an
 /// enormous execution.
 const MAX_PREFETCH_ROWS: u32 = 4096;
+/// Default and server-side clamp for the row cap of one embedding export
+/// response; larger exports page through `x-next-cursor`.
+const DEFAULT_EXPORT_ROWS: usize = 10_000;
+const MAX_EXPORT_ROWS: usize = 100_000;
 
 /// Search result cache outcome for one request side (count or results).
 #[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
@@ -132,6 +156,12 @@ pub(crate) struct PqlBuildResponse {
     /// Mapping of SQL column labels to their user-facing aliases.
     extra_columns: HashMap<String, String>,
     #[serde(default)]
+    /// Attribution Column Names
+    ///
+    /// Mapping of SQL column labels to the names of the filters they
+    /// attribute. Empty unless the query set `attribute_filters`.
+    attribution_columns: HashMap<String, String>,
+    #[serde(default)]
     /// Check Paths Exist
     ///
     /// Whether to validate paths after executing search queries.
@@ -148,6 +178,10 @@ pub(crate) struct PqlBuildResponse {
     /// Same, for the count query.
     #[serde(skip)]
     count_uses_user_data: bool,
+    /// Selected ORDER BY value columns, when the query set
+    /// `select_order_keys`. Internal, for multi-database merging.
+    #[serde(skip)]
+    order_keys: Vec<OrderKey>,
     /// Random Order Seed
     ///
     /// The seed bound into the returned results SQL, present only when the
@@ -185,6 +219,10 @@ pub(crate) struct SearchResult {
     #[serde(skip_serializing_if = "Option::is_none")]
     time_added: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    file_time_added: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_seen: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     md5: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     audio_tracks: Option<i64>,
@@ -217,17 +255,40 @@ pub(crate) struct SearchResult {
     #[serde(skip_serializing_if = "Option::is_none")]
     source_id: Option<i64>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    directory: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    type_prefix: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     /// Extra Fields
     ///
     /// Extra fields retrieved from filters that are not part of the main result object.
     extra: Option<HashMap<String, Value>>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    /// Filter Attribution
+    ///
+    /// Whether each named filter matched this result. Only present when the
+    /// query set `attribute_filters` and names at least one filter.
+    attribution: Option<HashMap<String, bool>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     /// Bookmarked
     ///
     /// Whether this item is bookmarked. Only present when the search was
     /// requested with `include_bookmarks` and the result has a sha256.
     /// Computed after the query runs; never part of the compiled search SQL.
     bookmarked: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// Available
+    ///
+    /// Whether the file at `path` exists. Only present when the query set
+    /// `check_path` and the path was checked within the time budget; false
+    /// when neither this file nor any other file of the item was found.
+    available: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    /// Index Database
+    ///
+    /// The index database this result came from. Only present in searches
+    /// across several databases (`index_dbs`); ids are local to it.
+    index_db: Option<String>,
 }
 
 #[cfg(test)]
@@ -260,6 +321,56 @@ pub(crate) struct FileSearchResponse {
     /// Echoed whether the caller supplied it or the server minted it.
     #[serde(skip_serializing_if = "Option::is_none")]
     seed: Option<i64>,
+    /// Unchecked Paths
+    ///
+    /// With `check_path`, the number of results whose path was not checked
+    /// because `[search] check_path_budget_ms` ran out; those rows carry no
+    /// `available` field. Absent when every path was checked.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    unchecked_paths: Option<usize>,
+    /// Skipped Index Databases
+    ///
+    /// In searches across several databases (`index_dbs`), the databases
+    /// left out because the query cannot run against them, with the reason.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    skipped_index_dbs: Option<Vec<SkippedIndexDb>>,
+    /// Result Stacks
+    ///
+    /// With `group_similar`, the page's results grouped into stacks of
+    /// near-identical items, by position in `results`. Every result belongs
+    /// to exactly one stack; stacks are ordered by their representative.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stacks: Option<Vec<stacks::ResultStack>>,
+    /// Stacking Timed Out
+    ///
+    /// With `group_similar`, true when `[search] group_similar_budget_ms`
+    /// ran out before the page was grouped; `stacks` is then absent.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stacks_timed_out: Option<bool>,
+}
+
+/// An index database a multi-database search could not include.
+#[derive(Serialize, ToSchema)]
+pub(crate) struct SkippedIndexDb {
+    index_db: String,
+    reason: String,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct MultiDbParams {
+    /// Index Databases
+    ///
+    /// Search these index databases together (repeat the parameter for
+    /// each). The query runs against every database and the results are
+    /// merged in the query's order, keeping only the best-ranked result per
+    /// sha256; `page` and `page_size` apply to the merged results and
+    /// `count` is the sum of the per-database counts. Bookmarks come from
+    /// the request's `user_data_db`. A database lacking a setter the query's
+    /// embedding filters use is skipped and listed in `skipped_index_dbs`.
+    /// Multi-database searches do not use the result cache.
+    #[serde(default)]
+    index_dbs: Vec<String>,
 }
 
 #[derive(Deserialize, IntoParams)]
@@ -282,10 +393,34 @@ pub(crate) struct BookmarkStatusParams {
     bookmarks_namespace: String,
     /// Bookmarks User
     ///
-    /// The bookmarks user to check against.
-    #[serde(default = "default_user")]
+    /// The bookmarks user to check against. Defaults to `user`, or to the
+    /// authenticated user when the request carries an identity.
+    #[serde(default)]
     #[param(default = "user")]
-    bookmarks_user: String,
+    bookmarks_user: Option<String>,
+}
+
+impl Default for BookmarkStatusParams {
+    fn default() -> Self {
+        Self {
+            include_bookmarks: false,
+            bookmarks_namespace: default_wildcard_namespace(),
+            bookmarks_user: None,
+        }
+    }
+}
+
+impl BookmarkStatusParams {
+    /// Resolves `bookmarks_user` to the user the request acts as (see
+    /// [`scoped_user`]).
+    pub(crate) fn scope(&mut self, identity: Option<&RequestIdentity>) -> ApiResult<()> {
+        self.bookmarks_user = Some(scoped_user(
+            identity,
+            self.bookmarks_user.as_deref(),
+            UserAccess::Read,
+        )?);
+        Ok(())
+    }
 }
 
 fn default_wildcard_namespace() -> String {
@@ -301,11 +436,51 @@ pub(crate) struct TagSearchQuery {
     #[param(default = 10)]
     /// The `limit` parameter can be used to control the number of tags to return.
     limit: i64,
+    #[serde(default)]
+    #[param(default = false)]
+    /// Report aliased tags once, under their canonical name, counting the
+    /// items tagged with any tag of the alias group
+    collapse_aliases: bool,
+    #[serde(default)]
+    #[param(default = false)]
+    /// Match tag names starting with `name` instead of containing it.
+    /// Served from the in-memory tag index when it is ready, which leaves out
+    /// tags on fewer than `[search] tag_index_min_count` items
+    prefix: bool,
+}
+
+/// Where tag search results came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum TagSearchSource {
+    /// The in-memory tag index (prefix searches only).
+    Index,
+    /// A query against the index database.
+    Db,
 }
 
 #[derive(Serialize, ToSchema)]
 pub(crate) struct TagSearchResults {
     tags: Vec<(String, String, i64)>,
+    source: TagSearchSource,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct TagAliasesResponse {
+    aliases: Vec<TagAliasGroup>,
+}
+
+#[derive(Deserialize, ToSchema, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct TagAliasDeleteQuery {
+    /// The canonical tag whose aliases are removed
+    canonical: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct TagAliasDeleteResponse {
+    /// Number of aliases removed
+    deleted: u64,
 }
 
 #[derive(Deserialize, ToSchema, IntoParams)]
@@ -343,6 +518,15 @@ pub(crate) struct SearchStatsQuery {
     include_wildcard: bool,
 }
 
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct StorageStatsQuery {
+    /// Scan every blob now instead of returning the stored snapshot. The
+    /// result is not stored; POST to refresh the snapshot.
+    #[serde(default)]
+    live: bool,
+}
+
 #[derive(Serialize, ToSchema)]
 pub(crate) struct ExtractedTextStats {
     languages: Vec<String>,
@@ -379,18 +563,126 @@ pub(crate) struct SearchStats {
     path = "/api/search/tags",
     tag = "search",
     summary = "Search tag names for autocompletion",
-    description = "Given a string, finds tags whose names contain the string.\nMeant to be used for autocompletion in the search bar.\nThe `limit` parameter can be used to control the number of tags to return.\nReturns a list of tuples, where each tuple contains the namespace, name, \nand the number of unique items tagged with the tag.\nThe tags are returned in descending order of the number of items tagged.",
+    description = "Given a string, finds tags whose names contain the string (or, with `prefix`, start with it).\nMeant to be used for autocompletion in the search bar.\nThe `limit` parameter can be used to control the number of tags to return.\nReturns a list of tuples, where each tuple contains the namespace, name, \nand the number of unique items tagged with the tag.\nThe tags are returned in descending order of the number of items tagged.\nPrefix searches are answered from an in-memory index of the tags on at least `[search] tag_index_min_count` items once it is built; until then, and while it is rebuilt after tags change, they query the database. `source` says which one answered.",
     params(DbQueryParams, TagSearchQuery),
     responses(
         (status = 200, description = "Tag autocomplete results", body = TagSearchResults)
     )
 )]
 pub async fn get_tags(
+    State(state): State<Arc<ProxyState>>,
     mut db: DbConnection<ReadOnly>,
     Query(query): Query<TagSearchQuery>,
 ) -> ApiResult<Json<TagSearchResults>> {
-    let tags = load_tags(&mut db.conn, &query.name, query.limit).await?;
-    Ok(Json(TagSearchResults { tags }))
+    let indexed = query
+        .prefix
+        .then(|| {
+            tag_index::lookup(
+                &db.index_db,
+                state.settings.search.tag_index_min_count,
+                &query.name,
+                query.limit.max(0) as usize,
+            )
+        })
+        .flatten();
+    let (mut tags, source) = match indexed {
+        Some(tags) => (tags, TagSearchSource::Index),
+        None => (
+            load_tags(&mut db.conn, &query.name, query.prefix, query.limit).await?,
+            TagSearchSource::Db,
+        ),
+    };
+    if query.collapse_aliases {
+        tags = collapse_tag_counts(&mut db.conn, tags).await?;
+        tags.sort_by_key(|tag| std::cmp::Reverse(tag.2));
+    }
+    Ok(Json(TagSearchResults { tags, source }))
+}
+
+#[utoipa::path(
+    get,
+    operation_id = "get_tag_aliases",
+    path = "/api/search/tags/aliases",
+    tag = "search",
+    summary = "List tag aliases",
+    description = "Lists every canonical tag with its aliases. `match_tags` matches a tag's whole alias group unless `expand_aliases` is false.",
+    params(DbQueryParams),
+    responses(
+        (status = 200, description = "Alias groups", body = TagAliasesResponse)
+    )
+)]
+pub async fn get_tag_aliases(
+    mut db: DbConnection<ReadOnly>,
+) -> ApiResult<Json<TagAliasesResponse>> {
+    let aliases = list_tag_alias_groups(&mut db.conn).await?;
+    Ok(Json(TagAliasesResponse { aliases }))
+}
+
+#[utoipa::path(
+    put,
+    operation_id = "set_tag_aliases",
+    path = "/api/search/tags/aliases",
+    tag = "search",
+    summary = "Set the aliases of a tag",
+    description = "Replaces the aliases of the canonical tag; an empty list removes them all. A tag can have at most 50 aliases. An alias belongs to one canonical tag only, and a canonical tag cannot be an alias itself.",
+    params(DbQueryParams),
+    request_body = TagAliasGroup,
+    responses(
+        (status = 200, description = "The stored alias group", body = TagAliasGroup),
+        (status = 400, description = "Empty canonical tag, a tag aliased to itself, or too many aliases"),
+        (status = 409, description = "An alias already belongs to another tag, or the canonical tag is an alias")
+    )
+)]
+pub async fn set_tag_aliases(
+    mut db: DbConnection<ReadOnlyNoUserData>,
+    Json(group): Json<TagAliasGroup>,
+) -> ApiResult<Json<TagAliasGroup>> {
+    call_index_db_writer(&db.index_db, |reply| {
+        IndexDbWriterMessage::SetTagAliasGroup {
+            group: group.clone(),
+            reply,
+        }
+    })
+    .await?;
+    let canonical = group.canonical.trim();
+    let stored = list_tag_alias_groups(&mut db.conn)
+        .await?
+        .into_iter()
+        .find(|stored| stored.canonical == canonical)
+        .unwrap_or_else(|| TagAliasGroup {
+            canonical: canonical.to_string(),
+            aliases: Vec::new(),
+        });
+    Ok(Json(stored))
+}
+
+#[utoipa::path(
+    delete,
+    operation_id = "delete_tag_aliases",
+    path = "/api/search/tags/aliases",
+    tag = "search",
+    summary = "Remove the aliases of a tag",
+    params(DbQueryParams, TagAliasDeleteQuery),
+    responses(
+        (status = 200, description = "Aliases removed", body = TagAliasDeleteResponse),
+        (status = 404, description = "The tag has no aliases")
+    )
+)]
+pub async fn delete_tag_aliases(
+    db: DbConnection<ReadOnlyNoUserData>,
+    Query(query): Query<TagAliasDeleteQuery>,
+) -> ApiResult<Json<TagAliasDeleteResponse>> {
+    let deleted = call_index_db_writer(&db.index_db, |reply| {
+        IndexDbWriterMessage::DeleteTagAliasGroup {
+            canonical: query.canonical.clone(),
+            reply,
+        }
+    })
+    .await?;
+    if deleted == 0 {
+        return Err(ApiError::not_found("Tag has no aliases"));
+    }
+    Ok(Json(TagAliasDeleteResponse { deleted }))
 }
 
 #[utoipa::path(
@@ -448,6 +740,52 @@ pub async fn get_stats(
     Ok(Json(stats))
 }
 
+#[utoipa::path(
+    get,
+    operation_id = "get_storage_stats",
+    path = "/api/search/stats/storage",
+    tag = "search",
+    summary = "Get disk usage of the stored data",
+    description = "Byte estimates of the derived data in the index: embeddings and extracted text per setter, thumbnails and frames (blobs in storage.db and files on disk), and the sizes of the database files and their WAL files.\nReturns the stored snapshot with current database file sizes. Every database maintenance run and every POST to this path refreshes the snapshot; `live=true` instead scans every blob now, which is slow on large databases, without storing the result.",
+    params(DbQueryParams, StorageStatsQuery),
+    responses(
+        (status = 200, description = "Storage usage", body = StorageStats),
+        (status = 404, description = "No snapshot has been computed yet")
+    )
+)]
+pub async fn get_storage_stats(
+    mut db: DbConnection<ReadOnlyNoUserData>,
+    Query(query): Query<StorageStatsQuery>,
+) -> ApiResult<Json<StorageStats>> {
+    if query.live {
+        let stats = compute_storage_stats(&mut db.conn, &db.index_db).await?;
+        return Ok(Json(stats));
+    }
+    let stats = get_storage_stats_snapshot(&mut db.conn, &db.index_db)
+        .await?
+        .ok_or_else(|| ApiError::not_found("No storage stats snapshot has been computed yet"))?;
+    Ok(Json(stats))
+}
+
+#[utoipa::path(
+    post,
+    operation_id = "refresh_storage_stats",
+    path = "/api/search/stats/storage",
+    tag = "search",
+    summary = "Recompute the disk usage of the stored data",
+    description = "Scans every blob, like `live=true` on GET, and stores the result as the snapshot that GET returns. Slow on large databases.",
+    params(DbQueryParams),
+    responses(
+        (status = 200, description = "Storage usage", body = StorageStats)
+    )
+)]
+pub async fn refresh_storage_stats(
+    mut db: DbConnection<ReadOnlyNoUserData>,
+) -> ApiResult<Json<StorageStats>> {
+    let stats = compute_and_store_storage_stats(&mut db.conn, &db.index_db).await?;
+    Ok(Json(stats))
+}
+
 #[utoipa::path(
     post,
     operation_id = "search_pql",
@@ -455,42 +793,223 @@ pub async fn get_stats(
     tag = "search",
     summary = "Search for files and items in the database",
     description = "Search for files in the database based on the provided query parameters.\nThis endpoint is meant to be used with the Panoptikon Query Language.\nWith `include_bookmarks`, each result additionally carries a `bookmarked` field\nresolved after the query runs (see the parameter description).",
-    params(DbQueryParams, BookmarkStatusParams),
+    params(DbQueryParams, BookmarkStatusParams, MultiDbParams),
     request_body(
-        content = Option<PqlQuery>,
-        description = "The PQL Search query to execute"
+        description = "The PQL Search query to execute",
+        content(
+            (Option<PqlQuery> = "application/json"),
+            (Option<PqlQuery> = "application/msgpack")
+        )
     ),
     responses(
-        (status = 200, description = "Search results", body = FileSearchResponse)
+        (status = 200, description = "Search results", content(
+            (FileSearchResponse = "application/json"),
+            (FileSearchResponse = "application/msgpack")
+        )),
+        (status = 415, description = "The request body is neither JSON nor msgpack")
     )
 )]
+#[allow(clippy::too_many_arguments)]
 pub async fn search_pql(
     State(state): State<Arc<ProxyState>>,
     mut db: DbConnection<ReadOnly>,
-    Query(bookmark_params): Query<BookmarkStatusParams>,
+    Query(mut bookmark_params): Query<BookmarkStatusParams>,
+    Query(multi_db): Query<MultiDbParams>,
     policy: Option<Extension<PolicyContext>>,
-    body: Option<Json<Value>>,
-) -> ApiResult<Json<FileSearchResponse>> {
-    let payload = body
-        .map(|Json(value)| value)
-        .unwrap_or_else(|| Value::Object(serde_json::Map::new()));
+    identity: Option<Extension<RequestIdentity>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> ApiResult<Response<Body>> {
+    let payload = decode_search_body(&headers, &body)?;
     let mut query = decode_pql_payload(&payload)?;
+    scope_bookmark_filters(&mut query, identity.as_deref())?;
+    bookmark_params.scope(identity.as_deref())?;
+    if !multi_db.index_dbs.is_empty() {
+        let response = multi_db::execute_multi_db_pql(
+            &state,
+            &mut db,
+            &bookmark_params,
+            query,
+            &multi_db.index_dbs,
+        )
+        .await?;
+        return encode_search_response(&response, SearchEncoding::negotiate(&headers));
+    }
+    let policy = policy.as_ref().map(|Extension(context)| context);
+    let response = execute_pql(
+        &state,
+        &mut db,
+        &bookmark_params,
+        policy,
+        query,
+        Some(&payload),
+    )
+    .await?;
+    encode_search_response(&response, SearchEncoding::negotiate(&headers))
+}
+
+/// Wire format of a PQL search response, picked from the request's Accept
+/// header. Anything other than an explicit msgpack entry gets JSON.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SearchEncoding {
+    Json,
+    Msgpack,
+}
+
+impl SearchEncoding {
+    fn negotiate(headers: &HeaderMap) -> Self {
+        let wants_msgpack = headers
+            .get_all(header::ACCEPT)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .any(msgpack::is_msgpack_media_type);
+        if wants_msgpack {
+            Self::Msgpack
+        } else {
+            Self::Json
+        }
+    }
+}
+
+/// Reads the search body by Content-Type: msgpack or JSON. A request
+/// without a Content-Type (or with an empty body) is an empty query.
+fn decode_search_body(headers: &HeaderMap, body: &[u8]) -> ApiResult<Value> {
+    let Some(content_type) = headers.get(header::CONTENT_TYPE) else {
+        return Ok(Value::Object(serde_json::Map::new()));
+    };
+    if body.is_empty() {
+        return Ok(Value::Object(serde_json::Map::new()));
+    }
+    let content_type = content_type.to_str().unwrap_or_default();
+    if msgpack::is_msgpack_media_type(content_type) {
+        return msgpack::decode_json(body).map_err(|err| {
+            tracing::error!(error = %err, "failed to decode msgpack pql payload");
+            ApiError::bad_request(format!("Invalid msgpack body: {err:#}"))
+        });
+    }
+    let kind = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    if kind == "application/json" || (kind.starts_with("application/") && kind.ends_with("+json")) {
+        return serde_json::from_slice(body)
+            .map_err(|err| ApiError::bad_request(format!("Invalid JSON body: {err}")));
+    }
+    Err(ApiError::new(
+        StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        format!(
+            "Unsupported Content-Type '{content_type}'; expected application/json or {}",
+            msgpack::CONTENT_TYPE
+        ),
+    ))
+}
+
+/// Encodes a search response in the negotiated format. Both formats are
+/// written from the same serde value, so they always carry the same fields.
+fn encode_search_response(
+    response: &FileSearchResponse,
+    encoding: SearchEncoding,
+) -> ApiResult<Response<Body>> {
+    let value = serde_json::to_value(response)
+        .map_err(|err| ApiError::internal(format!("Failed to serialize search response: {err}")))?;
+    let (bytes, content_type) = match encoding {
+        SearchEncoding::Json => (
+            serde_json::to_vec(&value).map_err(|err| {
+                ApiError::internal(format!("Failed to serialize search response: {err}"))
+            })?,
+            "application/json",
+        ),
+        SearchEncoding::Msgpack => (
+            msgpack::encode_json(&value).map_err(|err| {
+                ApiError::internal(format!("Failed to serialize search response: {err:#}"))
+            })?,
+            msgpack::CONTENT_TYPE,
+        ),
+    };
+    let mut response = Response::new(Body::from(bytes));
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_static(content_type),
+    );
+    response
+        .headers_mut()
+        .insert(header::VARY, header::HeaderValue::from_static("accept"));
+    Ok(response)
+}
+
+/// Points every `in_bookmarks` and `match_note` filter at the user the
+/// request acts as (see [`scoped_user`]). Without an identity the
+/// query is untouched.
+pub(crate) fn scope_bookmark_filters(
+    query: &mut PqlQuery,
+    identity: Option<&RequestIdentity>,
+) -> ApiResult<()> {
+    fn scope(element: &mut QueryElement, identity: &RequestIdentity) -> ApiResult<()> {
+        match element {
+            QueryElement::And(op) => op.and_.iter_mut().try_for_each(|el| scope(el, identity)),
+            QueryElement::Or(op) => op.or_.iter_mut().try_for_each(|el| scope(el, identity)),
+            QueryElement::Not(op) => scope(&mut op.not_, identity),
+            QueryElement::InBookmarks(filter) => {
+                let args = &mut filter.in_bookmarks;
+                args.user = Some(scoped_user(
+                    Some(identity),
+                    args.user.as_deref(),
+                    UserAccess::Read,
+                )?);
+                Ok(())
+            }
+            QueryElement::MatchNote(filter) => {
+                let args = &mut filter.match_note;
+                args.user = Some(scoped_user(
+                    Some(identity),
+                    args.user.as_deref(),
+                    UserAccess::Read,
+                )?);
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+    match (query.query.as_mut(), identity) {
+        (Some(root), Some(identity)) => scope(root, identity),
+        _ => Ok(()),
+    }
+}
+
+/// Runs a decoded PQL query end to end: async preprocessing (embedding any
+/// vector-search text), the cached count and page, then enrichment. Shared by
+/// `search_pql` and saved-query runs. `payload` is the request JSON, kept
+/// only if the search ends up in the slow query log.
+pub(crate) async fn execute_pql(
+    state: &ProxyState,
+    db: &mut DbConnection<ReadOnly>,
+    bookmark_params: &BookmarkStatusParams,
+    policy: Option<&PolicyContext>,
+    mut query: PqlQuery,
+    payload: Option<&Value>,
+) -> ApiResult<FileSearchResponse> {
+    let search_start = Instant::now();
     let skip_missing_file =
         query.check_path && matches!(query.entity, EntityType::File) && is_empty_partition(&query);
     let cache_requested = query.cache;
     let prefetch_rows = query.prefetch_rows.min(MAX_PREFETCH_ROWS);
     // Must happen before compiling: the seed is bound into the results SQL.
     let seed = query.resolve_seed();
-    let builder = compile_pql(&state, query, &db.index_db).await?;
+    let group_similar = query.group_similar.take();
+    if let Some(args) = group_similar.as_deref() {
+        stacks::validate(args)?;
+    }
+    let builder = compile_pql(state, query, &db.index_db).await?;
 
     let mut count_metrics = builder.count_metrics.clone();
     let mut result_metrics = builder.result_metrics.clone();
 
     // Requests outside the policy layer (no PolicyContext extension, e.g.
     // local mode) default to cache-enabled, same as the policy default.
-    let policy_allows = policy
-        .as_ref()
-        .is_none_or(|Extension(context)| context.search_cache);
+    let policy_allows = policy.is_none_or(|context| context.search_cache);
     let cache_available = search_cache::is_enabled() && policy_allows;
     let use_cache = cache_available && cache_requested;
     // A synthesized seed differs on every request, so its rows are keyed
@@ -576,6 +1095,7 @@ pub async fn search_pql(
                         prefetch_rows,
                         Some((key, snapshot)),
                         &builder.extra_columns,
+                        &builder.attribution_columns,
                     )
                     .await?;
                     (
@@ -597,6 +1117,7 @@ pub async fn search_pql(
                 0,
                 None,
                 &builder.extra_columns,
+                &builder.attribution_columns,
             )
             .await?;
             (page, inactive_outcome, 0)
@@ -610,27 +1131,61 @@ pub async fn search_pql(
     };
 
     let enrich_start = Instant::now();
+    let mut unchecked_paths = None;
     if builder.check_path {
-        let mut kept = Vec::with_capacity(results.len());
-        for mut result in results {
-            if apply_check_path(&mut db.conn, &mut result, skip_missing_file).await? {
-                kept.push(result);
-            }
-        }
-        results = kept;
+        let budget = Duration::from_millis(state.settings.search.check_path_budget_ms);
+        let (checked, unchecked) =
+            apply_check_path(&mut db.conn, results, skip_missing_file, budget).await?;
+        results = checked;
+        unchecked_paths = (unchecked > 0).then_some(unchecked);
     }
     if bookmark_params.include_bookmarks {
-        annotate_bookmark_status(&mut db.conn, &mut results, &bookmark_params).await?;
+        annotate_bookmark_status(&mut db.conn, &mut results, bookmark_params).await?;
+    }
+    let mut result_stacks = None;
+    let mut stacks_timed_out = None;
+    if let Some(args) = group_similar.as_deref() {
+        let budget = Duration::from_millis(state.settings.search.group_similar_budget_ms);
+        result_stacks = stacks::stack_results(&mut db.conn, &results, args, budget).await?;
+        stacks_timed_out = Some(result_stacks.is_none());
     }
     result_metrics.enrich = elapsed_seconds(enrich_start);
 
-    Ok(Json(FileSearchResponse {
+    let total_ms = elapsed_seconds(search_start) * 1000.0;
+    if search_slowlog::is_slow(total_ms) {
+        let build = |m: &SearchMetrics| m.build + m.compile;
+        search_slowlog::record(
+            &db.index_db,
+            SlowQuery {
+                query: payload,
+                sql: builder.compiled_query.as_ref().map(|c| c.sql.as_str()),
+                count_sql: builder
+                    .compiled_count_query
+                    .as_ref()
+                    .map(|c| c.sql.as_str()),
+                build_ms: (result_metrics.preprocess
+                    + build(&count_metrics)
+                    + build(&result_metrics))
+                    * 1000.0,
+                execute_ms: (count_metrics.execute + result_metrics.execute) * 1000.0,
+                total_ms,
+                rows: results.len(),
+                count,
+            },
+        );
+    }
+
+    Ok(FileSearchResponse {
         count,
         results,
         count_metrics,
         result_metrics,
         seed: seed.effective,
-    }))
+        unchecked_paths,
+        skipped_index_dbs: None,
+        stacks: result_stacks,
+        stacks_timed_out,
+    })
 }
 
 /// Serialize bound params into the canonical cache-key string.
@@ -662,6 +1217,7 @@ async fn execute_results(
     prefetch_rows: u32,
     cache_target: Option<(Arc<QueryKey>, EpochSnapshot)>,
     extra_columns: &HashMap<String, String>,
+    attribution_columns: &HashMap<String, String>,
 ) -> ApiResult<(Vec<SearchResult>, u64)> {
     // Prefetching only pays for itself if there is somewhere to put the extra
     // rows.
@@ -690,7 +1246,7 @@ async fn execute_results(
     let rows = run_compiled_query(conn, sql, params).await?;
     let mut mapped = Vec::with_capacity(rows.len());
     for row in rows {
-        mapped.push(map_search_result(&row, extra_columns)?);
+        mapped.push(map_search_result(&row, extra_columns, attribution_columns)?);
     }
 
     let page_len = pagination.map_or(mapped.len(), |p| (p.limit as usize).min(mapped.len()));
@@ -729,28 +1285,77 @@ pub async fn search_pql_build(
     let payload = body
         .map(|Json(value)| value)
         .unwrap_or_else(|| Value::Object(serde_json::Map::new()));
-    let mut query = decode_pql_payload(&payload)?;
+    let builder = build_pql(&PqlCompiler::of(&state), &payload, &db.index_db).await?;
+    Ok(Json(builder))
+}
+
+/// Compiles a PQL payload into the SQL a search would execute, pagination
+/// included: the `/pql/build` response, also printed by the `pql` subcommand.
+pub(crate) async fn build_pql(
+    compiler: &PqlCompiler<'_>,
+    payload: &Value,
+    index_db: &str,
+) -> ApiResult<PqlBuildResponse> {
+    let mut query = decode_pql_payload(payload)?;
     // Mirrors the search handler so the returned SQL is what a search would
     // actually execute, seed included. The seed lands in the response, so a
     // caller who omitted one can still reproduce this exact build.
     query.resolve_seed();
-    let mut builder = compile_pql(&state, query, &db.index_db).await?;
+    let mut builder = compile_pql_with(compiler, query, index_db).await?;
     // The builder keeps pagination out of the compiled SQL (cache keying);
-    // this endpoint contracts to return the executable query, so re-apply it.
+    // this contracts to return the executable query, so re-apply it.
     if let (Some(compiled), Some(pagination)) =
         (builder.compiled_query.as_mut(), builder.pagination)
     {
         *compiled = compiled.with_pagination(pagination.limit, pagination.offset);
     }
-    Ok(Json(builder))
+    Ok(builder)
+}
+
+/// Runs the results query of a [`build_pql`] build, bypassing the result
+/// cache and per-response enrichment. Empty when the query asked for no
+/// results.
+pub(crate) async fn run_pql_build(
+    conn: &mut sqlx::SqliteConnection,
+    build: &PqlBuildResponse,
+) -> ApiResult<Vec<SearchResult>> {
+    let Some(compiled) = build.compiled_query.as_ref() else {
+        return Ok(Vec::new());
+    };
+    let (results, _) = execute_results(
+        conn,
+        compiled,
+        None,
+        0,
+        None,
+        &build.extra_columns,
+        &build.attribution_columns,
+    )
+    .await?;
+    Ok(results)
+}
+
+/// Runs the count query of a [`build_pql`] build. `None` when the query did
+/// not ask for a count.
+pub(crate) async fn run_pql_build_count(
+    conn: &mut sqlx::SqliteConnection,
+    build: &PqlBuildResponse,
+) -> ApiResult<Option<i64>> {
+    let Some(compiled) = build.compiled_count_query.as_ref() else {
+        return Ok(None);
+    };
+    run_compiled_count(conn, &compiled.sql, &compiled.params)
+        .await
+        .map(Some)
 }
 
 async fn load_tags(
     conn: &mut sqlx::SqliteConnection,
     name: &str,
+    prefix: bool,
     limit: i64,
 ) -> ApiResult<Vec<(String, String, i64)>> {
-    let mut tags = find_tags(conn, name, limit).await?;
+    let mut tags = find_tags(conn, name, prefix, limit).await?;
     tags.sort_by(|a, b| b.2.cmp(&a.2));
     Ok(tags)
 }
@@ -804,29 +1409,63 @@ fn map_text_stats(stats: TextStats) -> ExtractedTextStats {
     }
 }
 
+/// What compiling a PQL query needs besides the query: the filters disabled
+/// by `[search] disabled_filters` and the client that embeds vector-search
+/// text. Borrowed from the server state, or built by the `pql` subcommand.
+pub(crate) struct PqlCompiler<'a> {
+    pub disabled_filters: &'a [String],
+    pub inference_client: &'a InferenceApiClient,
+    pub embedding_cache_bytes: usize,
+}
+
+impl<'a> PqlCompiler<'a> {
+    fn of(state: &'a ProxyState) -> Self {
+        Self {
+            disabled_filters: &state.settings.search.disabled_filters,
+            inference_client: &state.inference_client,
+            embedding_cache_bytes: state.search_embedding_cache_bytes,
+        }
+    }
+}
+
 async fn compile_pql(
     state: &ProxyState,
+    query: PqlQuery,
+    index_db: &str,
+) -> ApiResult<PqlBuildResponse> {
+    compile_pql_with(&PqlCompiler::of(state), query, index_db).await
+}
+
+async fn compile_pql_with(
+    compiler: &PqlCompiler<'_>,
     mut query: PqlQuery,
     index_db: &str,
 ) -> ApiResult<PqlBuildResponse> {
     let mut count_metrics = SearchMetrics::default();
     let mut result_metrics = SearchMetrics::default();
     let check_path = query.check_path;
+    reject_disabled_filters(&query, compiler.disabled_filters)?;
 
     let mut used_preprocess = false;
     let mut preprocess_time = 0.0;
+    if query.refine.is_some() {
+        let start = Instant::now();
+        // Boxed: its connection and row buffers would otherwise inflate
+        // every search future, including the ones that never refine.
+        Box::pin(apply_refine(&mut query, index_db)).await?;
+        preprocess_time += elapsed_seconds(start);
+    }
     if let Some(root) = query.query.take() {
         used_preprocess = true;
         let start = Instant::now();
         let preprocessed = preprocess_query_async(
             root,
-            &state.inference_client,
-            state.search_embedding_cache_size,
+            compiler.inference_client,
+            compiler.embedding_cache_bytes,
             Some(index_db),
         )
-        .await
-        .map_err(map_pql_error)?;
-        preprocess_time = elapsed_seconds(start);
+        .await?;
+        preprocess_time += elapsed_seconds(start);
         query.query = preprocessed;
     }
 
@@ -847,10 +1486,12 @@ async fn compile_pql(
             result_metrics,
             count_metrics,
             extra_columns: HashMap::new(),
+            attribution_columns: HashMap::new(),
             check_path,
             pagination: None,
             uses_user_data: false,
             count_uses_user_data: false,
+            order_keys: Vec::new(),
             seed,
         });
     }
@@ -859,7 +1500,7 @@ async fn compile_pql(
     let mut count_uses_user_data = false;
     if query.count {
         let start = Instant::now();
-        let built = build_query_preprocessed(query.clone(), true).map_err(map_pql_error)?;
+        let built = build_query_preprocessed(query.clone(), true)?;
         count_metrics.build = elapsed_seconds(start);
         count_uses_user_data = built.uses_user_data;
         let start = Instant::now();
@@ -873,21 +1514,25 @@ async fn compile_pql(
                 result_metrics,
                 count_metrics,
                 extra_columns: HashMap::new(),
+                attribution_columns: HashMap::new(),
                 check_path,
                 pagination: None,
                 uses_user_data: false,
                 count_uses_user_data,
+                order_keys: Vec::new(),
                 seed,
             });
         }
     }
 
     let start = Instant::now();
-    let built = build_query_preprocessed(query, false).map_err(map_pql_error)?;
+    let built = build_query_preprocessed(query, false)?;
     result_metrics.build = elapsed_seconds(start);
     let extra_columns = built.extra_columns.clone();
+    let attribution_columns = built.attribution_columns.clone();
     let pagination = built.pagination;
     let uses_user_data = built.uses_user_data;
+    let order_keys = built.order_keys.clone();
     let start = Instant::now();
     let compiled_query = compile_select(built)?;
     result_metrics.compile = elapsed_seconds(start);
@@ -898,10 +1543,12 @@ async fn compile_pql(
         result_metrics,
         count_metrics,
         extra_columns,
+        attribution_columns,
         check_path,
         pagination,
         uses_user_data,
         count_uses_user_data,
+        order_keys,
         seed,
     })
 }
@@ -937,7 +1584,7 @@ pub async fn get_search_cache(
 ) -> ApiResult<Json<EmbeddingCacheStats>> {
     let page = query.page.max(1);
     let page_size = query.page_size.max(1);
-    let stats = embedding_cache_stats(state.search_embedding_cache_size, page, page_size).await;
+    let stats = embedding_cache_stats(state.search_embedding_cache_bytes, page, page_size).await;
     Ok(Json(stats))
 }
 
@@ -957,13 +1604,185 @@ pub async fn clear_search_cache(
     State(state): State<Arc<ProxyState>>,
     Query(query): Query<CacheQuery>,
 ) -> ApiResult<Json<EmbeddingCacheStats>> {
-    clear_embedding_cache(state.search_embedding_cache_size).await;
+    clear_embedding_cache(state.search_embedding_cache_bytes).await;
     let page = query.page.max(1);
     let page_size = query.page_size.max(1);
-    let stats = embedding_cache_stats(state.search_embedding_cache_size, page, page_size).await;
+    let stats = embedding_cache_stats(state.search_embedding_cache_bytes, page, page_size).await;
     Ok(Json(stats))
 }
 
+#[derive(Deserialize, ToSchema)]
+pub(crate) struct EmbeddingExportRequest {
+    /// The setter (model) whose embeddings to export, e.g. "clip/ViT-H-14"
+    setter: String,
+    /// Optional PQL filter: only embeddings of items matching it are
+    /// exported
+    #[serde(default)]
+    #[schema(no_recursion)]
+    query: Option<QueryElement>,
+    /// Maximum number of embeddings in this response (default 10000,
+    /// clamped to 100000)
+    #[serde(default = "default_export_limit")]
+    limit: usize,
+    /// Resume an export: the `x-next-cursor` header of the previous
+    /// response
+    #[serde(default)]
+    cursor: Option<i64>,
+    /// How to encode each vector
+    #[serde(default)]
+    format: EmbeddingFormat,
+}
+
+fn default_export_limit() -> usize {
+    DEFAULT_EXPORT_ROWS
+}
+
+/// One line of an embedding export.
+#[derive(Serialize, ToSchema)]
+pub(crate) struct EmbeddingExportRow {
+    sha256: String,
+    /// Index of the embedding within the item
+    index: i64,
+    vector: EmbeddingVector,
+}
+
+#[utoipa::path(
+    post,
+    operation_id = "export_embeddings",
+    path = "/api/search/embeddings/export",
+    tag = "search",
+    summary = "Export stored embeddings as NDJSON",
+    description = "Streams one `EmbeddingExportRow` JSON object per line for the embeddings a setter stored, in a stable order, optionally restricted to the items matching a PQL filter.\nAt most `limit` rows are returned per response. When more remain, the response carries an `x-next-cursor` header; pass it back as `cursor` to continue.",
+    params(DbQueryParams),
+    request_body = EmbeddingExportRequest,
+    responses(
+        (status = 200, description = "Newline-delimited EmbeddingExportRow objects", content_type = "application/x-ndjson", body = String)
+    )
+)]
+pub async fn export_embeddings(
+    State(state): State<Arc<ProxyState>>,
+    mut db: DbConnection<ReadOnly>,
+    identity: Option<Extension<RequestIdentity>>,
+    Json(request): Json<EmbeddingExportRequest>,
+) -> ApiResult<Response<Body>> {
+    let items = match request.query {
+        Some(filter) => {
+            Some(matching_item_ids(&state, &mut db, filter, identity.as_deref()).await?)
+        }
+        None => None,
+    };
+    let page = select_export_page(
+        &mut db.conn,
+        &request.setter,
+        items.as_ref(),
+        request.cursor.unwrap_or(0),
+        request.limit.clamp(1, MAX_EXPORT_ROWS),
+    )
+    .await?;
+
+    // Blobs are read a chunk at a time as the body is consumed, so memory
+    // stays bounded by the chunk size rather than the row cap.
+    let format = request.format;
+    let chunks: Vec<Vec<i64>> = page
+        .data_ids
+        .chunks(EXPORT_CHUNK_SIZE)
+        .map(<[i64]>::to_vec)
+        .collect();
+    let stream = futures_util::stream::try_unfold(
+        (db, chunks.into_iter()),
+        move |(mut db, mut chunks)| async move {
+            let Some(data_ids) = chunks.next() else {
+                return Ok(None);
+            };
+            let lines = export_lines(&mut db, &data_ids, format)
+                .await
+                .map_err(|_| std::io::Error::other("Failed to read embeddings"))?;
+            Ok::<_, std::io::Error>(Some((Bytes::from(lines), (db, chunks))))
+        },
+    );
+
+    let mut response = Response::new(Body::from_stream(stream));
+    let headers = response.headers_mut();
+    headers.insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_static("application/x-ndjson"),
+    );
+    if let Some(cursor) = page.next_cursor {
+        headers.insert("x-next-cursor", header::HeaderValue::from(cursor));
+    }
+    Ok(response)
+}
+
+/// Items matching an export's PQL filter, run as a file query partitioned by
+/// item without pagination.
+async fn matching_item_ids(
+    state: &ProxyState,
+    db: &mut DbConnection<ReadOnly>,
+    filter: QueryElement,
+    identity: Option<&RequestIdentity>,
+) -> ApiResult<HashSet<i64>> {
+    let rows =
+        matching_file_rows(state, db, Some(filter), identity, vec![PqlColumn::Sha256]).await?;
+    rows.iter()
+        .map(|row| {
+            row.try_get::<i64, _>("item_id").map_err(|err| {
+                tracing::error!(error = %err, "failed to read item_id from export filter");
+                ApiError::internal("Failed to execute search query")
+            })
+        })
+        .collect()
+}
+
+/// One file per item matching `filter` (every item when `None`), preferring
+/// the item's pinned primary file, with the `select` columns and without
+/// pagination. Bookmark filters are scoped to `identity` as in a search.
+pub(crate) async fn matching_file_rows(
+    state: &ProxyState,
+    db: &mut DbConnection<ReadOnly>,
+    filter: Option<QueryElement>,
+    identity: Option<&RequestIdentity>,
+    select: Vec<PqlColumn>,
+) -> ApiResult<Vec<sqlx::sqlite::SqliteRow>> {
+    let mut query = PqlQuery {
+        query: filter,
+        entity: EntityType::File,
+        partition_by: Some(vec![PqlColumn::ItemId]),
+        select,
+        page_size: 0,
+        count: false,
+        check_path: false,
+        cache: false,
+        ..PqlQuery::default()
+    };
+    scope_bookmark_filters(&mut query, identity)?;
+    let built = compile_pql(state, query, &db.index_db).await?;
+    let Some(compiled) = built.compiled_query else {
+        return Ok(Vec::new());
+    };
+    run_compiled_query(&mut db.conn, &compiled.sql, &compiled.params).await
+}
+
+async fn export_lines(
+    db: &mut DbConnection<ReadOnly>,
+    data_ids: &[i64],
+    format: EmbeddingFormat,
+) -> ApiResult<Vec<u8>> {
+    let mut out = Vec::new();
+    for row in get_embeddings_by_ids(&mut db.conn, data_ids).await? {
+        let line = EmbeddingExportRow {
+            sha256: row.sha256,
+            index: row.idx,
+            vector: encode_embedding(&row.embedding, format)?,
+        };
+        serde_json::to_writer(&mut out, &line).map_err(|err| {
+            tracing::error!(error = %err, "failed to serialize embedding export row");
+            ApiError::internal("Failed to read embeddings")
+        })?;
+        out.push(b'\n');
+    }
+    Ok(out)
+}
+
 fn decode_pql_payload(payload: &Value) -> ApiResult<PqlQuery> {
     serde_json::from_value(payload.clone()).map_err(|err| {
         tracing::error!(error = %err, "failed to decode pql payload");
@@ -1048,10 +1867,6 @@ fn json_f64(value: f64) -> ApiResult<Value> {
         .ok_or_else(|| ApiError::bad_request("Invalid floating point parameter"))
 }
 
-fn map_pql_error(err: PqlError) -> ApiError {
-    ApiError::bad_request(err.message)
-}
-
 /// SQLite's default variable limit is 32766; stay well under it.
 const BOOKMARK_LOOKUP_CHUNK: usize = 5000;
 
@@ -1093,7 +1908,7 @@ async fn annotate_bookmark_status(
             )
         };
         let mut query = sqlx::query(sqlx::AssertSqlSafe(sql.as_str()));
-        query = query.bind(&params.bookmarks_user);
+        query = query.bind(params.bookmarks_user.as_deref().unwrap_or(DEFAULT_USER));
         if !any_namespace {
             query = query.bind(&params.bookmarks_namespace);
         }
@@ -1124,48 +1939,95 @@ async fn annotate_bookmark_status(
     Ok(())
 }
 
+/// Paths `check_path` stats at once.
+const CHECK_PATH_CONCURRENCY: usize = 32;
+
+/// Whether each path exists; `None` for paths still unchecked when
+/// `deadline` passed. A row without a path counts as existing.
+async fn check_paths_exist(paths: Vec<Option<String>>, deadline: Instant) -> Vec<Option<bool>> {
+    let mut exists = vec![None; paths.len()];
+    let mut checks = futures_util::stream::iter(paths.into_iter().enumerate())
+        .map(|(idx, path)| async move {
+            let found = match path {
+                Some(path) => tokio::fs::metadata(path).await.is_ok(),
+                None => true,
+            };
+            (idx, found)
+        })
+        .buffer_unordered(CHECK_PATH_CONCURRENCY);
+    while let Ok(Some((idx, found))) = tokio::time::timeout_at(deadline.into(), checks.next()).await
+    {
+        exists[idx] = Some(found);
+    }
+    exists
+}
+
+/// Verifies result paths within `budget`. A missing path is replaced by
+/// another existing file of the item, or the row is marked unavailable;
+/// unpartitioned file queries drop the row instead, since the item's other
+/// files are rows of their own. Rows left unchecked when the budget runs
+/// out are returned as they are; the second value counts them.
 async fn apply_check_path(
     conn: &mut sqlx::SqliteConnection,
-    result: &mut SearchResult,
+    results: Vec<SearchResult>,
     skip_missing_file: bool,
-) -> ApiResult<bool> {
-    let Some(path) = result.path.as_deref() else {
-        return Ok(true);
-    };
-    if Path::new(path).exists() {
-        return Ok(true);
-    }
-
-    if skip_missing_file {
-        tracing::warn!(
-            path = %path,
-            item_id = %result.item_id,
-            "pql file path missing"
-        );
-        return Ok(false);
-    }
-
-    tracing::warn!(
-        path = %path,
-        item_id = %result.item_id,
-        "pql result path missing"
-    );
-    let Some(file) = get_existing_file_for_item_id(conn, result.item_id).await? else {
-        return Ok(false);
-    };
-    result.path = Some(file.path);
-    if result.last_modified.is_none() {
-        result.last_modified = Some(file.last_modified);
-    }
-    if result.filename.is_none() {
-        result.filename = Some(file.filename);
+    budget: Duration,
+) -> ApiResult<(Vec<SearchResult>, usize)> {
+    let deadline = Instant::now() + budget;
+    let paths = results.iter().map(|result| result.path.clone()).collect();
+    let exists = check_paths_exist(paths, deadline).await;
+
+    let mut kept = Vec::with_capacity(results.len());
+    let mut unchecked = 0;
+    for (mut result, exists) in results.into_iter().zip(exists) {
+        let Some(path) = result.path.as_deref() else {
+            kept.push(result);
+            continue;
+        };
+        match exists {
+            None => unchecked += 1,
+            Some(true) => result.available = Some(true),
+            Some(false) if skip_missing_file => {
+                tracing::warn!(
+                    path = %path,
+                    item_id = %result.item_id,
+                    "pql file path missing"
+                );
+                continue;
+            }
+            Some(false) => {
+                tracing::warn!(
+                    path = %path,
+                    item_id = %result.item_id,
+                    "pql result path missing"
+                );
+                // Past the deadline the lookup is skipped, not the row.
+                let replacement = if Instant::now() < deadline {
+                    get_existing_file_for_item_id(conn, result.item_id).await?
+                } else {
+                    None
+                };
+                result.available = Some(replacement.is_some());
+                if let Some(file) = replacement {
+                    result.path = Some(file.path);
+                    if result.last_modified.is_none() {
+                        result.last_modified = Some(file.last_modified);
+                    }
+                    if result.filename.is_none() {
+                        result.filename = Some(file.filename);
+                    }
+                }
+            }
+        }
+        kept.push(result);
     }
-    Ok(true)
+    Ok((kept, unchecked))
 }
 
 fn map_search_result(
     row: &sqlx::sqlite::SqliteRow,
     extra_columns: &HashMap<String, String>,
+    attribution_columns: &HashMap<String, String>,
 ) -> ApiResult<SearchResult> {
     let columns: HashSet<&str> = row.columns().iter().map(|column| column.name()).collect();
     let file_id = read_required_i64(row, "file_id")?;
@@ -1186,6 +2048,8 @@ fn map_search_result(
     result.height = read_optional(row, &columns, "height")?;
     result.duration = read_optional(row, &columns, "duration")?;
     result.time_added = read_optional(row, &columns, "time_added")?;
+    result.file_time_added = read_optional(row, &columns, "file_time_added")?;
+    result.last_seen = read_optional(row, &columns, "last_seen")?;
     result.md5 = read_optional(row, &columns, "md5")?;
     result.audio_tracks = read_optional(row, &columns, "audio_tracks")?;
     result.video_tracks = read_optional(row, &columns, "video_tracks")?;
@@ -1202,6 +2066,19 @@ fn map_search_result(
     result.setter_name = read_optional(row, &columns, "setter_name")?;
     result.data_index = read_optional(row, &columns, "data_index")?;
     result.source_id = read_optional(row, &columns, "source_id")?;
+    result.directory = read_optional(row, &columns, "directory")?;
+    result.type_prefix = read_optional(row, &columns, "type_prefix")?;
+
+    // Filters sharing a name (say, both branches of an `or_` named "ocr")
+    // report whether any of them matched.
+    let mut attribution: HashMap<String, bool> = HashMap::new();
+    for (column, name) in attribution_columns {
+        let matched = read_optional::<bool>(row, &columns, column)?.unwrap_or(false);
+        *attribution.entry(name.clone()).or_default() |= matched;
+    }
+    if !attribution.is_empty() {
+        result.attribution = Some(attribution);
+    }
 
     let mut extras = HashMap::new();
     for column in columns {
@@ -1282,6 +2159,8 @@ fn is_known_column(name: &str) -> bool {
             | "height"
             | "duration"
             | "time_added"
+            | "file_time_added"
+            | "last_seen"
             | "md5"
             | "audio_tracks"
             | "video_tracks"
@@ -1298,6 +2177,8 @@ fn is_known_column(name: &str) -> bool {
             | "setter_name"
             | "data_index"
             | "source_id"
+            | "directory"
+            | "type_prefix"
     )
 }
 
@@ -1341,6 +2222,86 @@ mod tests {
         }
     }
 
+    // Ensures check_path keeps existing paths, swaps a missing one for
+    // another existing file of the item, marks items with no existing file
+    // unavailable, and drops missing rows of unpartitioned file queries.
+    #[tokio::test]
+    async fn check_path_substitutes_or_marks_missing_files() {
+        let mut dbs = setup_test_databases().await;
+        let dir = tempfile::tempdir().unwrap();
+        let present = dir.path().join("present.png");
+        let copy = dir.path().join("copy.png");
+        std::fs::write(&present, b"png").unwrap();
+        std::fs::write(&copy, b"png").unwrap();
+        let gone = |name: &str| dir.path().join(name).to_string_lossy().to_string();
+        sqlx::query(sqlx::AssertSqlSafe(format!(
+            r#"
+            INSERT INTO file_scans (id, start_time, path)
+            VALUES (1, '2024-01-01T00:00:00', '/data');
+            INSERT INTO items (id, sha256, md5, type, time_added)
+            VALUES
+                (1, 'sha_1', 'md5_1', 'image/png', '2024-01-01T00:00:00'),
+                (2, 'sha_2', 'md5_2', 'image/png', '2024-01-01T00:00:00'),
+                (3, 'sha_3', 'md5_3', 'image/png', '2024-01-01T00:00:00');
+            INSERT INTO files (id, sha256, item_id, path, filename, last_modified, scan_id, available)
+            VALUES
+                (10, 'sha_1', 1, '{present}', 'present.png', '2024-01-01T00:00:00', 1, 1),
+                (20, 'sha_2', 2, '{moved}', 'moved.png', '2024-01-01T00:00:00', 1, 1),
+                (21, 'sha_2', 2, '{copy}', 'copy.png', '2024-01-01T00:00:00', 1, 1),
+                (30, 'sha_3', 3, '{deleted}', 'deleted.png', '2024-01-01T00:00:00', 1, 1);
+            "#,
+            present = present.display(),
+            copy = copy.display(),
+            moved = gone("moved.png"),
+            deleted = gone("deleted.png"),
+        )))
+        .execute(&mut dbs.index_conn)
+        .await
+        .unwrap();
+        let results = || {
+            [
+                (10, 1, present.to_string_lossy().to_string()),
+                (20, 2, gone("moved.png")),
+                (30, 3, gone("deleted.png")),
+            ]
+            .map(|(file_id, item_id, path)| SearchResult {
+                file_id,
+                item_id,
+                path: Some(path),
+                ..SearchResult::default()
+            })
+            .to_vec()
+        };
+        let budget = Duration::from_secs(10);
+
+        let (checked, unchecked) = apply_check_path(&mut dbs.index_conn, results(), false, budget)
+            .await
+            .unwrap();
+        assert_eq!(unchecked, 0);
+        assert_eq!(
+            checked
+                .iter()
+                .map(|result| (result.path.clone().unwrap(), result.available))
+                .collect::<Vec<_>>(),
+            vec![
+                (present.to_string_lossy().to_string(), Some(true)),
+                (copy.to_string_lossy().to_string(), Some(true)),
+                (gone("deleted.png"), Some(false)),
+            ]
+        );
+
+        let (checked, _) = apply_check_path(&mut dbs.index_conn, results(), true, budget)
+            .await
+            .unwrap();
+        assert_eq!(
+            checked
+                .iter()
+                .map(|result| result.file_id)
+                .collect::<Vec<_>>(),
+            vec![10]
+        );
+    }
+
     // Enrichment stamps Some(true/false) per sha256 with namespace/user
     // scoping mirroring get_bookmark_metadata; results without a sha256 stay
     // None instead of claiming "not bookmarked".
@@ -1370,7 +2331,7 @@ mod tests {
         let params = BookmarkStatusParams {
             include_bookmarks: true,
             bookmarks_namespace: "default".to_string(),
-            bookmarks_user: "user".to_string(),
+            bookmarks_user: Some("user".to_string()),
         };
         annotate_bookmark_status(&mut dbs.index_conn, &mut results, &params)
             .await
@@ -1384,7 +2345,7 @@ mod tests {
         let params = BookmarkStatusParams {
             include_bookmarks: true,
             bookmarks_namespace: "*".to_string(),
-            bookmarks_user: "user".to_string(),
+            bookmarks_user: Some("user".to_string()),
         };
         annotate_bookmark_status(&mut dbs.index_conn, &mut results, &params)
             .await
@@ -1422,7 +2383,7 @@ mod tests {
         let params = BookmarkStatusParams {
             include_bookmarks: true,
             bookmarks_namespace: "default".to_string(),
-            bookmarks_user: "user".to_string(),
+            bookmarks_user: Some("user".to_string()),
         };
         annotate_bookmark_status(&mut dbs.index_conn, &mut results, &params)
             .await
@@ -1501,7 +2462,9 @@ mod tests {
     #[tokio::test]
     async fn load_tags_sorts_by_frequency_desc() {
         let mut dbs = setup_tag_db().await;
-        let tags = load_tags(&mut dbs.index_conn, "cat", 10).await.unwrap();
+        let tags = load_tags(&mut dbs.index_conn, "cat", false, 10)
+            .await
+            .unwrap();
 
         assert_eq!(
             tags,
@@ -1924,6 +2887,7 @@ mod tests {
             4,
             Some((Arc::clone(&key), snapshot)),
             &extra_columns,
+            &HashMap::new(),
         )
         .await
         .expect("execute");
@@ -1977,6 +2941,7 @@ mod tests {
             6,
             Some((Arc::clone(&key), snapshot)),
             &extra_columns,
+            &HashMap::new(),
         )
         .await
         .expect("execute");
@@ -2019,6 +2984,7 @@ mod tests {
             3,
             None,
             &extra_columns,
+            &HashMap::new(),
         )
         .await
         .expect("execute");
@@ -2030,4 +2996,245 @@ mod tests {
             CacheLookup::Miss
         ));
     }
+
+    async fn post_search(
+        state: &Arc<ProxyState>,
+        index_db: &str,
+        user_data_db: &str,
+        headers: &[(header::HeaderName, &'static str)],
+        body: Vec<u8>,
+    ) -> (String, Bytes) {
+        let db = DbConnection::<ReadOnly>::open_named(
+            Some(index_db.to_string()),
+            Some(user_data_db.to_string()),
+        )
+        .await
+        .unwrap();
+        let mut header_map = HeaderMap::new();
+        for (name, value) in headers {
+            header_map.insert(name.clone(), header::HeaderValue::from_static(value));
+        }
+        let params = BookmarkStatusParams {
+            include_bookmarks: true,
+            bookmarks_namespace: "*".to_string(),
+            bookmarks_user: Some("user".to_string()),
+        };
+        let response = search_pql(
+            State(state.clone()),
+            db,
+            Query(params),
+            Query(MultiDbParams {
+                index_dbs: Vec::new(),
+            }),
+            None,
+            None,
+            header_map,
+            Bytes::from(body),
+        )
+        .await
+        .unwrap();
+        let content_type = response.headers()[header::CONTENT_TYPE]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (content_type, bytes)
+    }
+
+    // Ensures a msgpack request answered in msgpack decodes to exactly the
+    // JSON result of the same query, including extra text columns and the
+    // snippet carried in `extra`.
+    #[tokio::test]
+    async fn search_pql_msgpack_round_trips_json_result() {
+        let _test_env = crate::test_utils::test_data_dir();
+        let index_db = "search_msgpack_index";
+        let user_data_db = "search_msgpack_user";
+        crate::db::migrations::migrate_databases_on_disk(Some(index_db), Some(user_data_db))
+            .await
+            .unwrap();
+        let mut index = crate::db::open_index_db_write_no_user_data(index_db)
+            .await
+            .unwrap();
+        sqlx::query(
+            r#"
+            INSERT INTO file_scans (id, start_time, path)
+            VALUES (1, '2024-01-01T00:00:00', '/data');
+            INSERT INTO items (id, sha256, md5, type, time_added, width, height)
+            VALUES
+                (1, 'sha_1', 'md5_1', 'image/png', '2024-01-01T00:00:00', 640, 480),
+                (2, 'sha_2', 'md5_2', 'image/png', '2024-01-02T00:00:00', NULL, NULL);
+            INSERT INTO files (id, sha256, item_id, path, filename, last_modified, scan_id, available)
+            VALUES
+                (10, 'sha_1', 1, '/data/one.png', 'one.png', '2024-01-01T00:00:00', 1, 1),
+                (11, 'sha_2', 2, '/data/two.png', 'two.png', '2024-01-02T00:00:00', 1, 1);
+            INSERT INTO setters (id, name) VALUES (1, 'ocr');
+            INSERT INTO item_data (id, item_id, setter_id, data_type, idx, is_origin)
+            VALUES (100, 1, 1, 'text', 0, 1), (101, 2, 1, 'text', 0, 1);
+            INSERT INTO extracted_text (id, language, language_confidence, confidence, text, text_length)
+            VALUES
+                (100, 'en', 0.5, 0.75, 'hello wörld, a greeting ✓', 25),
+                (101, 'ja', NULL, NULL, 'こんにちは hello again', 18);
+            "#,
+        )
+        .execute(&mut index)
+        .await
+        .unwrap();
+        drop(index);
+
+        let state = Arc::new(crate::test_utils::offline_proxy_state());
+        let query = serde_json::json!({
+            "entity": "text",
+            "select": ["sha256", "path", "width", "language", "language_confidence",
+                       "confidence", "text", "setter_name", "data_id"],
+            "query": { "match_text": { "match": "hello", "select_snippet_as": "snip" } },
+            "order_by": [{ "order_by": "data_id" }],
+            "page_size": 10,
+            "count": true
+        });
+
+        let (json_type, json_body) = post_search(
+            &state,
+            index_db,
+            user_data_db,
+            &[(header::CONTENT_TYPE, "application/json")],
+            serde_json::to_vec(&query).unwrap(),
+        )
+        .await;
+        let (msgpack_type, msgpack_body) = post_search(
+            &state,
+            index_db,
+            user_data_db,
+            &[
+                (header::CONTENT_TYPE, "application/msgpack"),
+                (
+                    header::ACCEPT,
+                    "application/msgpack, application/json;q=0.5",
+                ),
+            ],
+            msgpack::encode_json(&query).unwrap(),
+        )
+        .await;
+        assert_eq!(json_type, "application/json");
+        assert_eq!(msgpack_type, msgpack::CONTENT_TYPE);
+
+        let mut from_json: Value = serde_json::from_slice(&json_body).unwrap();
+        let mut from_msgpack = msgpack::decode_json(&msgpack_body).unwrap();
+        // Timings differ between two runs; everything else must match.
+        for body in [&mut from_json, &mut from_msgpack] {
+            let body = body.as_object_mut().unwrap();
+            body.remove("count_metrics");
+            body.remove("result_metrics");
+        }
+        assert_eq!(from_msgpack, from_json);
+
+        let results = from_json["results"].as_array().unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(from_json["count"], 2);
+        assert_eq!(results[0]["width"], 640);
+        assert_eq!(results[0]["language_confidence"], 0.5);
+        assert_eq!(results[1]["text"], "こんにちは hello again");
+        assert_eq!(results[0]["bookmarked"], false);
+        for result in results {
+            assert!(result["extra"]["snip"].as_str().unwrap().contains("hello"));
+        }
+    }
+
+    // Ensures bodies are read by Content-Type: a missing type is an empty
+    // query, truncated msgpack is a 400 and other media types are a 415.
+    #[test]
+    fn decode_search_body_dispatches_on_content_type() {
+        let headers = |value: &'static str| {
+            let mut map = HeaderMap::new();
+            map.insert(
+                header::CONTENT_TYPE,
+                header::HeaderValue::from_static(value),
+            );
+            map
+        };
+        assert_eq!(
+            decode_search_body(&HeaderMap::new(), b"ignored").unwrap(),
+            serde_json::json!({})
+        );
+        let packed = msgpack::encode_json(&serde_json::json!({"page": 2})).unwrap();
+        assert_eq!(
+            decode_search_body(&headers("application/x-msgpack"), &packed).unwrap()["page"],
+            2
+        );
+        assert_eq!(
+            decode_search_body(
+                &headers("application/json; charset=utf-8"),
+                b"{\"page\": 3}"
+            )
+            .unwrap()["page"],
+            3
+        );
+        let err = decode_search_body(&headers("application/msgpack"), &[0x92, 0x01]).unwrap_err();
+        assert_eq!(err.status(), StatusCode::BAD_REQUEST);
+        let err = decode_search_body(&headers("text/plain"), b"page=2").unwrap_err();
+        assert_eq!(err.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    // Ensures only an explicit msgpack Accept entry switches the encoding.
+    #[test]
+    fn search_encoding_negotiates_on_accept() {
+        let accept = |value: &'static str| {
+            let mut map = HeaderMap::new();
+            map.insert(header::ACCEPT, header::HeaderValue::from_static(value));
+            SearchEncoding::negotiate(&map)
+        };
+        assert_eq!(
+            SearchEncoding::negotiate(&HeaderMap::new()),
+            SearchEncoding::Json
+        );
+        assert_eq!(accept("*/*"), SearchEncoding::Json);
+        assert_eq!(accept("application/json"), SearchEncoding::Json);
+        assert_eq!(
+            accept("text/html, Application/MsgPack"),
+            SearchEncoding::Msgpack
+        );
+    }
+
+    // Ensures an identified caller cannot read another user's bookmarks or
+    // notes through filters or bookmark status, and that unnamed users are
+    // pointed at the caller.
+    #[test]
+    fn bookmark_filters_and_status_are_scoped_to_the_identity() {
+        let alice = RequestIdentity::User {
+            user: "alice".to_string(),
+            admin: false,
+        };
+        for filter in [
+            serde_json::json!({ "in_bookmarks": { "user": "bob" } }),
+            serde_json::json!({ "match_note": { "match": "approved", "user": "bob" } }),
+        ] {
+            let mut query: PqlQuery =
+                serde_json::from_value(serde_json::json!({ "query": filter })).unwrap();
+            let err = scope_bookmark_filters(&mut query, Some(&alice)).unwrap_err();
+            assert_eq!(err.status(), StatusCode::FORBIDDEN);
+        }
+
+        let mut query: PqlQuery = serde_json::from_value(serde_json::json!({
+            "query": { "and_": [
+                { "in_bookmarks": {} },
+                { "not_": { "match_note": { "match": "approved" } } }
+            ] }
+        }))
+        .unwrap();
+        scope_bookmark_filters(&mut query, Some(&alice)).unwrap();
+        let json = serde_json::to_value(&query.query).unwrap();
+        assert_eq!(json["and_"][0]["in_bookmarks"]["user"], "alice");
+        assert_eq!(json["and_"][1]["not_"]["match_note"]["user"], "alice");
+
+        let mut params = BookmarkStatusParams {
+            bookmarks_user: Some("bob".to_string()),
+            ..BookmarkStatusParams::default()
+        };
+        let err = params.scope(Some(&alice)).unwrap_err();
+        assert_eq!(err.status(), StatusCode::FORBIDDEN);
+        let mut params = BookmarkStatusParams::default();
+        params.scope(Some(&alice)).unwrap();
+        assert_eq!(params.bookmarks_user.as_deref(), Some("alice"));
+    }
 }
@@ -0,0 +1,689 @@
+//! PQL search across several index databases (`index_dbs` on
+//! `/api/search/pql`). The query is compiled and run against each database
+//! on its own read connection, so everything that resolves against a
+//! database (setters, quant profiles, tag aliases, folders) resolves there;
+//! the per-database streams are then merged on the selected ORDER BY values.
+
+use std::cmp::Ordering;
+use std::collections::{HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+use futures_util::future::{join_all, try_join_all};
+use serde_json::Value;
+
+use super::{
+    ApiResult, BookmarkStatusParams, FileSearchResponse, PqlBuildResponse, SearchMetrics,
+    SearchResult, SkippedIndexDb, annotate_bookmark_status, apply_check_path, compile_pql,
+    elapsed_seconds, is_empty_partition, map_search_result, read_extra_value,
+};
+use crate::api_error::ApiError;
+use crate::db::pql::{run_compiled_count, run_compiled_query};
+use crate::db::{DbConnection, ReadOnly};
+use crate::pql::OrderKey;
+use crate::pql::model::{EntityType, PqlQuery, QueryElement};
+use crate::proxy::ProxyState;
+
+/// Most index databases one search may span.
+const MAX_INDEX_DBS: usize = 16;
+
+struct KeyedResult {
+    result: SearchResult,
+    /// The row's `order_key_{n}` values, in `OrderKey` order.
+    keys: Vec<Value>,
+}
+
+/// One database's side of the search: its compiled query and a buffer of
+/// rows read from it in its own order, refilled as the merge drains it.
+struct DbSearch {
+    index_db: String,
+    conn: DbConnection<ReadOnly>,
+    built: PqlBuildResponse,
+    count: i64,
+    count_execute: f64,
+    result_execute: f64,
+    rows: VecDeque<KeyedResult>,
+    fetched: u64,
+    exhausted: bool,
+}
+
+enum DbOutcome {
+    Ready(Box<DbSearch>),
+    Skipped(SkippedIndexDb),
+}
+
+impl DbSearch {
+    /// Reads the next `batch` rows, or all remaining rows for `None`.
+    async fn fetch(&mut self, batch: Option<u64>) -> ApiResult<()> {
+        let Some(compiled) = self.built.compiled_query.as_ref() else {
+            self.exhausted = true;
+            return Ok(());
+        };
+        let start = Instant::now();
+        let paged;
+        let (sql, params) = match batch {
+            Some(limit) => {
+                paged = compiled.with_pagination(limit, self.fetched);
+                (paged.sql.as_str(), paged.params.as_slice())
+            }
+            None => (compiled.sql.as_str(), compiled.params.as_slice()),
+        };
+        let rows = run_compiled_query(&mut self.conn.conn, sql, params).await?;
+        self.exhausted = batch.is_none_or(|limit| (rows.len() as u64) < limit);
+        self.fetched += rows.len() as u64;
+        for row in rows {
+            let result = map_search_result(
+                &row,
+                &self.built.extra_columns,
+                &self.built.attribution_columns,
+            )?;
+            let mut keys = Vec::with_capacity(self.built.order_keys.len());
+            for key in &self.built.order_keys {
+                keys.push(read_extra_value(&row, &key.label)?.unwrap_or(Value::Null));
+            }
+            self.rows.push_back(KeyedResult { result, keys });
+        }
+        self.result_execute += elapsed_seconds(start);
+        Ok(())
+    }
+}
+
+/// Runs `query` against every database in `index_dbs` and merges the
+/// results. `db` is the request's own connection; only its user data
+/// database is used, for bookmark status.
+pub(super) async fn execute_multi_db_pql(
+    state: &ProxyState,
+    db: &mut DbConnection<ReadOnly>,
+    bookmark_params: &BookmarkStatusParams,
+    mut query: PqlQuery,
+    index_dbs: &[String],
+) -> ApiResult<FileSearchResponse> {
+    let mut names: Vec<&str> = Vec::with_capacity(index_dbs.len());
+    for name in index_dbs {
+        if !names.contains(&name.as_str()) {
+            names.push(name);
+        }
+    }
+    if names.len() > MAX_INDEX_DBS {
+        return Err(ApiError::bad_request(format!(
+            "A search can span at most {MAX_INDEX_DBS} index databases"
+        )));
+    }
+    if query.refine.is_some() {
+        return Err(ApiError::bad_request(
+            "refine cannot be combined with index_dbs: its files belong to a single database",
+        ));
+    }
+    if query.group_similar.is_some() {
+        return Err(ApiError::bad_request(
+            "group_similar cannot be combined with index_dbs: it groups a single database's page",
+        ));
+    }
+
+    let skip_missing_file =
+        query.check_path && matches!(query.entity, EntityType::File) && is_empty_partition(&query);
+    // One seed for every database, so a random order is one shuffle.
+    let seed = query.resolve_seed();
+    // Pagination applies to the merged stream: each database is read from
+    // its start, in batches of the rows the page needs at most.
+    let page = query.page.max(1);
+    let window = (query.page_size >= 1).then(|| {
+        let limit = query.page_size as u64;
+        ((page - 1) as u64 * limit, limit)
+    });
+    let batch = window.map(|(offset, limit)| offset + limit);
+    query.page = 1;
+    query.page_size = 0;
+    query.select_order_keys = true;
+
+    let mut models = Vec::new();
+    if let Some(root) = query.query.as_ref() {
+        collect_embedding_models(root, &mut models);
+    }
+
+    let outcomes = join_all(names.iter().map(|index_db| {
+        open_db_search(
+            state,
+            index_db,
+            &db.user_data_db,
+            query.clone(),
+            &models,
+            batch,
+        )
+    }))
+    .await;
+    let mut searches = Vec::new();
+    let mut skipped = Vec::new();
+    for outcome in outcomes {
+        match outcome? {
+            DbOutcome::Ready(search) => searches.push(*search),
+            DbOutcome::Skipped(entry) => skipped.push(entry),
+        }
+    }
+
+    let mut count_metrics = SearchMetrics::default();
+    let mut result_metrics = SearchMetrics::default();
+    let mut count = 0;
+    for search in &searches {
+        count += search.count;
+        merge_phase_metrics(&mut count_metrics, &search.built.count_metrics);
+        merge_phase_metrics(&mut result_metrics, &search.built.result_metrics);
+        count_metrics.execute = count_metrics.execute.max(search.count_execute);
+    }
+
+    let order_keys = searches
+        .first()
+        .map(|search| search.built.order_keys.clone())
+        .unwrap_or_default();
+    let mut seen = HashSet::new();
+    let mut merged: Vec<(usize, SearchResult)> = Vec::new();
+    while batch.is_none_or(|wanted| (merged.len() as u64) < wanted) {
+        for search in searches.iter_mut() {
+            if search.rows.is_empty() && !search.exhausted {
+                search.fetch(batch).await?;
+            }
+        }
+        let Some(best) = next_source(&searches, &order_keys) else {
+            break;
+        };
+        let search = &mut searches[best];
+        let Some(KeyedResult { mut result, .. }) = search.rows.pop_front() else {
+            break;
+        };
+        // The first occurrence of a sha256 is its best-ranked one.
+        if let Some(sha256) = result.sha256.as_ref()
+            && !seen.insert(sha256.clone())
+        {
+            continue;
+        }
+        result.index_db = Some(search.index_db.clone());
+        merged.push((best, result));
+    }
+    for search in &searches {
+        result_metrics.execute = result_metrics.execute.max(search.result_execute);
+    }
+    let offset = window.map_or(0, |(offset, _)| offset as usize);
+    let page_rows: Vec<(usize, SearchResult)> = merged.into_iter().skip(offset).collect();
+
+    let enrich_start = Instant::now();
+    let check_path = searches.iter().any(|search| search.built.check_path);
+    let (mut results, unchecked) = if check_path {
+        let budget = Duration::from_millis(state.settings.search.check_path_budget_ms);
+        check_paths_per_db(&mut searches, page_rows, skip_missing_file, budget).await?
+    } else {
+        (page_rows.into_iter().map(|(_, result)| result).collect(), 0)
+    };
+    if bookmark_params.include_bookmarks {
+        annotate_bookmark_status(&mut db.conn, &mut results, bookmark_params).await?;
+    }
+    result_metrics.enrich = elapsed_seconds(enrich_start);
+
+    Ok(FileSearchResponse {
+        count,
+        results,
+        count_metrics,
+        result_metrics,
+        seed: seed.effective,
+        unchecked_paths: (unchecked > 0).then_some(unchecked),
+        skipped_index_dbs: (!skipped.is_empty()).then_some(skipped),
+        stacks: None,
+        stacks_timed_out: None,
+    })
+}
+
+/// Opens, compiles and runs the query against one database: the count, and
+/// the first batch of rows. A database lacking a setter the query's
+/// embedding filters name is skipped rather than failing the search.
+async fn open_db_search(
+    state: &ProxyState,
+    index_db: &str,
+    user_data_db: &str,
+    query: PqlQuery,
+    models: &[(String, bool)],
+    batch: Option<u64>,
+) -> ApiResult<DbOutcome> {
+    let mut conn = DbConnection::<ReadOnly>::open_named(
+        Some(index_db.to_string()),
+        Some(user_data_db.to_string()),
+    )
+    .await?;
+    if let Some(model) = missing_setter(&mut conn.conn, models).await? {
+        tracing::warn!(
+            index_db,
+            model,
+            "skipping index db without the queried setter"
+        );
+        return Ok(DbOutcome::Skipped(SkippedIndexDb {
+            index_db: index_db.to_string(),
+            reason: format!("setter '{model}' does not exist in this database"),
+        }));
+    }
+    let built = compile_pql(state, query, index_db).await?;
+
+    let start = Instant::now();
+    let count = match built.compiled_count_query.as_ref() {
+        Some(compiled) => {
+            run_compiled_count(&mut conn.conn, &compiled.sql, &compiled.params).await?
+        }
+        None => 0,
+    };
+    let count_execute = elapsed_seconds(start);
+
+    let mut search = DbSearch {
+        index_db: index_db.to_string(),
+        conn,
+        built,
+        count,
+        count_execute,
+        result_execute: 0.0,
+        rows: VecDeque::new(),
+        fetched: 0,
+        exhausted: false,
+    };
+    search.fetch(batch).await?;
+    Ok(DbOutcome::Ready(Box::new(search)))
+}
+
+/// The embedding models (setter names) the query's vector filters search,
+/// with whether the filter also accepts the model's `t`-prefixed
+/// cross-modal text setter.
+fn collect_embedding_models(element: &QueryElement, models: &mut Vec<(String, bool)>) {
+    let model = match element {
+        QueryElement::And(op) => {
+            op.and_
+                .iter()
+                .for_each(|el| collect_embedding_models(el, models));
+            return;
+        }
+        QueryElement::Or(op) => {
+            op.or_
+                .iter()
+                .for_each(|el| collect_embedding_models(el, models));
+            return;
+        }
+        QueryElement::Not(op) => {
+            collect_embedding_models(&op.not_, models);
+            return;
+        }
+        QueryElement::SemanticTextSearch(filter) => (filter.text_embeddings.model.clone(), false),
+        QueryElement::SemanticImageSearch(filter) => (
+            filter.image_embeddings.model.clone(),
+            filter.image_embeddings.clip_xmodal,
+        ),
+        QueryElement::SimilarTo(filter) => (
+            filter.similar_to.model.clone(),
+            filter.similar_to.clip_xmodal,
+        ),
+        _ => return,
+    };
+    if !models.contains(&model) {
+        models.push(model);
+    }
+}
+
+/// The first model in `models` with no setter in the database.
+async fn missing_setter(
+    conn: &mut sqlx::SqliteConnection,
+    models: &[(String, bool)],
+) -> ApiResult<Option<String>> {
+    for (model, clip_xmodal) in models {
+        let exists: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM setters WHERE name = ? OR (? AND name = ?))",
+        )
+        .bind(model)
+        .bind(clip_xmodal)
+        .bind(format!("t{model}"))
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|err| {
+            tracing::error!(error = %err, "failed to look up search setters");
+            ApiError::internal("Failed to execute search query")
+        })?;
+        if !exists {
+            return Ok(Some(model.clone()));
+        }
+    }
+    Ok(None)
+}
+
+/// Per-database phases run concurrently, so the slowest one is the time.
+fn merge_phase_metrics(total: &mut SearchMetrics, metrics: &SearchMetrics) {
+    total.preprocess = total.preprocess.max(metrics.preprocess);
+    total.build = total.build.max(metrics.build);
+    total.compile = total.compile.max(metrics.compile);
+}
+
+/// The search whose next buffered row comes first in the query's order;
+/// ties go to the database listed first.
+fn next_source(searches: &[DbSearch], order_keys: &[OrderKey]) -> Option<usize> {
+    let mut best: Option<(usize, &KeyedResult)> = None;
+    for (index, search) in searches.iter().enumerate() {
+        let Some(head) = search.rows.front() else {
+            continue;
+        };
+        let better = best.is_none_or(|(_, current)| {
+            compare_keys(&head.keys, &current.keys, order_keys) == Ordering::Less
+        });
+        if better {
+            best = Some((index, head));
+        }
+    }
+    best.map(|(index, _)| index)
+}
+
+/// Compares two rows' ORDER BY values the way the SQL orders them: term by
+/// term, NULLs last in either direction.
+fn compare_keys(left: &[Value], right: &[Value], order_keys: &[OrderKey]) -> Ordering {
+    for ((left, right), key) in left.iter().zip(right).zip(order_keys) {
+        let ordering = match (left.is_null(), right.is_null()) {
+            (true, true) => Ordering::Equal,
+            (true, false) => Ordering::Greater,
+            (false, true) => Ordering::Less,
+            (false, false) if key.descending => compare_values(left, right).reverse(),
+            (false, false) => compare_values(left, right),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
+
+/// SQLite's value order: numbers before text, text by bytes.
+fn compare_values(left: &Value, right: &Value) -> Ordering {
+    match (left, right) {
+        (Value::Number(left), Value::Number(right)) => match (left.as_i64(), right.as_i64()) {
+            (Some(left), Some(right)) => left.cmp(&right),
+            _ => {
+                let left = left.as_f64().unwrap_or(f64::NAN);
+                let right = right.as_f64().unwrap_or(f64::NAN);
+                left.partial_cmp(&right).unwrap_or(Ordering::Equal)
+            }
+        },
+        (Value::Number(_), _) => Ordering::Less,
+        (_, Value::Number(_)) => Ordering::Greater,
+        (Value::String(left), Value::String(right)) => left.cmp(right),
+        _ => Ordering::Equal,
+    }
+}
+
+/// Runs `check_path` on each database's rows of the page, concurrently and
+/// against that database (a replacement file is looked up by its item id),
+/// and puts the surviving rows back in merged order.
+async fn check_paths_per_db(
+    searches: &mut [DbSearch],
+    page_rows: Vec<(usize, SearchResult)>,
+    skip_missing_file: bool,
+    budget: Duration,
+) -> ApiResult<(Vec<SearchResult>, usize)> {
+    let mut groups: Vec<Vec<(usize, SearchResult)>> =
+        (0..searches.len()).map(|_| Vec::new()).collect();
+    for (position, (source, result)) in page_rows.into_iter().enumerate() {
+        groups[source].push((position, result));
+    }
+    let total = groups.iter().map(Vec::len).sum();
+    let checked = try_join_all(
+        searches
+            .iter_mut()
+            .zip(groups)
+            .map(|(search, group)| async move {
+                let (positions, rows): (Vec<usize>, Vec<SearchResult>) = group.into_iter().unzip();
+                let ids: Vec<(i64, Option<i64>)> =
+                    rows.iter().map(|row| (row.file_id, row.data_id)).collect();
+                let (kept, unchecked) =
+                    apply_check_path(&mut search.conn.conn, rows, skip_missing_file, budget)
+                        .await?;
+                // Kept rows are an in-order subsequence of the input.
+                let mut placed = Vec::with_capacity(kept.len());
+                let mut next = 0;
+                for row in kept {
+                    while ids[next] != (row.file_id, row.data_id) {
+                        next += 1;
+                    }
+                    placed.push((positions[next], row));
+                    next += 1;
+                }
+                ApiResult::Ok((placed, unchecked))
+            }),
+    )
+    .await?;
+
+    let mut slots: Vec<Option<SearchResult>> = (0..total).map(|_| None).collect();
+    let mut unchecked = 0;
+    for (placed, group_unchecked) in checked {
+        unchecked += group_unchecked;
+        for (position, row) in placed {
+            slots[position] = Some(row);
+        }
+    }
+    Ok((slots.into_iter().flatten().collect(), unchecked))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::search::compile_select;
+    use crate::db::migrations::setup_test_databases;
+    use crate::pql::build_query_preprocessed;
+    use crate::pql::model::Column;
+
+    fn keys(descending: &[bool]) -> Vec<OrderKey> {
+        descending
+            .iter()
+            .enumerate()
+            .map(|(index, &descending)| OrderKey {
+                label: format!("order_key_{index}"),
+                descending,
+            })
+            .collect()
+    }
+
+    // Ensures merged rows follow the SQL order: direction per term, NULLs
+    // last either way, numbers before text, and later terms break ties.
+    #[test]
+    fn compare_keys_matches_sql_ordering() {
+        let desc = keys(&[true]);
+        assert_eq!(
+            compare_keys(&[Value::from(2)], &[Value::from(1)], &desc),
+            Ordering::Less
+        );
+        assert_eq!(
+            compare_keys(&[Value::Null], &[Value::from(1)], &desc),
+            Ordering::Greater
+        );
+        let asc = keys(&[false]);
+        assert_eq!(
+            compare_keys(&[Value::Null], &[Value::from(1)], &asc),
+            Ordering::Greater
+        );
+        assert_eq!(
+            compare_keys(&[Value::from(0.5)], &[Value::from("a")], &asc),
+            Ordering::Less
+        );
+        let two = keys(&[false, true]);
+        assert_eq!(
+            compare_keys(
+                &[Value::from("a"), Value::from(1)],
+                &[Value::from("a"), Value::from(3)],
+                &two
+            ),
+            Ordering::Greater
+        );
+    }
+
+    // Ensures the selected order keys carry the ORDER BY values in the
+    // query's direction, with and without partitioning.
+    #[tokio::test]
+    async fn order_keys_follow_the_sql_order() {
+        let mut dbs = setup_test_databases().await;
+        sqlx::query(
+            r#"
+            INSERT INTO file_scans (id, start_time, path)
+            VALUES (1, '2024-01-01T00:00:00', '/data');
+            INSERT INTO items (id, sha256, md5, type, time_added)
+            VALUES
+                (1, 'sha_1', 'md5_1', 'image/png', '2024-01-01T00:00:00'),
+                (2, 'sha_2', 'md5_2', 'image/png', '2024-01-01T00:00:00');
+            INSERT INTO files (id, sha256, item_id, path, filename, last_modified, scan_id, available)
+            VALUES
+                (10, 'sha_1', 1, '/data/a.png', 'a.png', '2024-01-02T00:00:00', 1, 1),
+                (11, 'sha_1', 1, '/data/b.png', 'b.png', '2024-01-04T00:00:00', 1, 1),
+                (20, 'sha_2', 2, '/data/c.png', 'c.png', '2024-01-03T00:00:00', 1, 1);
+            "#,
+        )
+        .execute(&mut dbs.index_conn)
+        .await
+        .unwrap();
+
+        for partition_by in [None, Some(vec![Column::ItemId])] {
+            let expected = if partition_by.is_some() {
+                vec!["2024-01-04T00:00:00", "2024-01-03T00:00:00"]
+            } else {
+                vec![
+                    "2024-01-04T00:00:00",
+                    "2024-01-03T00:00:00",
+                    "2024-01-02T00:00:00",
+                ]
+            };
+            let query = PqlQuery {
+                partition_by,
+                page_size: 0,
+                count: false,
+                select_order_keys: true,
+                ..PqlQuery::default()
+            };
+            let built = build_query_preprocessed(query, false).unwrap();
+            assert_eq!(
+                built.order_keys,
+                vec![OrderKey {
+                    label: "order_key_0".to_string(),
+                    descending: true,
+                }]
+            );
+            let compiled = compile_select(built).unwrap();
+            let rows = run_compiled_query(&mut dbs.index_conn, &compiled.sql, &compiled.params)
+                .await
+                .unwrap();
+            let keys: Vec<Value> = rows
+                .iter()
+                .map(|row| read_extra_value(row, "order_key_0").unwrap().unwrap())
+                .collect();
+            assert_eq!(keys, expected);
+        }
+    }
+
+    async fn seed_index_db(index_db: &str, files: &str) {
+        crate::db::migrations::migrate_databases_on_disk(Some(index_db), Some("multi_db_user"))
+            .await
+            .unwrap();
+        let mut conn = crate::db::open_index_db_write_no_user_data(index_db)
+            .await
+            .unwrap();
+        sqlx::query(sqlx::AssertSqlSafe(format!(
+            r#"
+            INSERT INTO file_scans (id, start_time, path)
+            VALUES (1, '2024-01-01T00:00:00', '/data');
+            {files}
+            "#
+        )))
+        .execute(&mut conn)
+        .await
+        .unwrap();
+    }
+
+    // Ensures results from several databases are merged in the query's
+    // order, a sha256 found in both is kept once at its best rank, pages
+    // cut the merged stream, and a database missing a queried embedding
+    // setter is skipped with a reason instead of failing the search.
+    #[tokio::test]
+    async fn merges_pages_across_index_dbs() {
+        let _test_env = crate::test_utils::test_data_dir();
+        seed_index_db(
+            "multi_db_a",
+            r#"
+            INSERT INTO items (id, sha256, md5, type, time_added) VALUES
+                (1, 'shared', 'm1', 'image/png', '2024-01-01T00:00:00'),
+                (2, 'only_a', 'm2', 'image/png', '2024-01-01T00:00:00');
+            INSERT INTO files (id, sha256, item_id, path, filename, last_modified, scan_id, available)
+            VALUES
+                (1, 'shared', 1, '/a/shared.png', 'shared.png', '2024-01-05T00:00:00', 1, 1),
+                (2, 'only_a', 2, '/a/only_a.png', 'only_a.png', '2024-01-03T00:00:00', 1, 1);
+            "#,
+        )
+        .await;
+        seed_index_db(
+            "multi_db_b",
+            r#"
+            INSERT INTO items (id, sha256, md5, type, time_added) VALUES
+                (1, 'only_b', 'm3', 'image/png', '2024-01-01T00:00:00'),
+                (2, 'shared', 'm1', 'image/png', '2024-01-01T00:00:00');
+            INSERT INTO files (id, sha256, item_id, path, filename, last_modified, scan_id, available)
+            VALUES
+                (1, 'only_b', 1, '/b/only_b.png', 'only_b.png', '2024-01-04T00:00:00', 1, 1),
+                (2, 'shared', 2, '/b/shared.png', 'shared.png', '2024-01-02T00:00:00', 1, 1);
+            "#,
+        )
+        .await;
+        let state = crate::test_utils::offline_proxy_state();
+        let mut db = DbConnection::<ReadOnly>::open_named(
+            Some("multi_db_a".to_string()),
+            Some("multi_db_user".to_string()),
+        )
+        .await
+        .unwrap();
+        let index_dbs = ["multi_db_a".to_string(), "multi_db_b".to_string()];
+        let search = |page: i64, query: serde_json::Value| {
+            let mut query: PqlQuery = serde_json::from_value(query).unwrap();
+            query.page = page;
+            query.page_size = 2;
+            query
+        };
+
+        let mut pages = Vec::new();
+        for page in [1, 2] {
+            let query = search(page, serde_json::json!({}));
+            let response = execute_multi_db_pql(
+                &state,
+                &mut db,
+                &BookmarkStatusParams::default(),
+                query,
+                &index_dbs,
+            )
+            .await
+            .unwrap();
+            assert_eq!(response.count, 4);
+            pages.extend(
+                response
+                    .results
+                    .into_iter()
+                    .map(|result| (result.path.unwrap(), result.index_db.unwrap())),
+            );
+        }
+        let expected = [
+            ("/a/shared.png", "multi_db_a"),
+            ("/b/only_b.png", "multi_db_b"),
+            ("/a/only_a.png", "multi_db_a"),
+        ]
+        .map(|(path, index_db)| (path.to_string(), index_db.to_string()));
+        assert_eq!(pages, expected);
+
+        let query = search(
+            1,
+            serde_json::json!({
+                "query": { "image_embeddings": { "query": "cat", "model": "clip" } }
+            }),
+        );
+        let response = execute_multi_db_pql(
+            &state,
+            &mut db,
+            &BookmarkStatusParams::default(),
+            query,
+            &index_dbs,
+        )
+        .await
+        .unwrap();
+        assert!(response.results.is_empty());
+        let skipped = response.skipped_index_dbs.unwrap();
+        assert_eq!(skipped.len(), 2);
+        assert!(skipped[0].reason.contains("'clip'"));
+    }
+}
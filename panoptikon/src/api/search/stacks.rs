@@ -0,0 +1,225 @@
+//! Query-time stacking of near-identical results (`group_similar` on
+//! `/api/search/pql`). Only the returned page is grouped: its items'
+//! embeddings are read in one query, compared pairwise, and results within
+//! the threshold of each other are merged with a union-find, so a chain of
+//! close neighbours forms a single stack.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::time::Instant;
+use utoipa::ToSchema;
+
+use super::{ApiResult, SearchResult};
+use crate::api_error::ApiError;
+use crate::db::embeddings::get_embeddings_for_items;
+use crate::pql::embedding_utils::{average_embeddings, deserialize_f32};
+use crate::pql::model::{DistanceFunction, GroupSimilarArgs};
+
+/// A group of near-identical results, by position in the page's `results`.
+#[derive(Debug, PartialEq, Serialize, ToSchema)]
+pub(crate) struct ResultStack {
+    /// The best-ranked result of the stack.
+    representative: usize,
+    /// The other results of the stack, best-ranked first. Empty for a result
+    /// with no near-identical neighbour on the page.
+    members: Vec<usize>,
+}
+
+/// Rejects arguments that could never group anything meaningfully.
+pub(super) fn validate(args: &GroupSimilarArgs) -> ApiResult<()> {
+    if args.model.trim().is_empty() {
+        return Err(ApiError::bad_request("group_similar requires a model"));
+    }
+    if !args.threshold.is_finite() || args.threshold < 0.0 {
+        return Err(ApiError::bad_request(
+            "group_similar threshold must be a finite, non-negative distance",
+        ));
+    }
+    Ok(())
+}
+
+/// Stacks the page's results by the distance between their items'
+/// embeddings. Items with several embeddings (e.g. video frames) are compared
+/// by their mean; results whose item has none stay on their own. Returns
+/// `None` when `budget` runs out before every pair was compared.
+pub(super) async fn stack_results(
+    conn: &mut sqlx::SqliteConnection,
+    results: &[SearchResult],
+    args: &GroupSimilarArgs,
+    budget: Duration,
+) -> ApiResult<Option<Vec<ResultStack>>> {
+    let deadline = Instant::now() + budget;
+    let mut item_ids = results.iter().map(|r| r.item_id).collect::<Vec<_>>();
+    item_ids.sort_unstable();
+    item_ids.dedup();
+    let Ok(rows) = tokio::time::timeout_at(
+        deadline,
+        get_embeddings_for_items(conn, &item_ids, &args.model),
+    )
+    .await
+    else {
+        return Ok(None);
+    };
+    let rows = rows?;
+
+    let mut vectors = HashMap::with_capacity(item_ids.len());
+    for group in rows.chunk_by(|a, b| a.0 == b.0) {
+        let blobs = group.iter().map(|(_, blob)| blob).collect::<Vec<_>>();
+        // An item whose embeddings cannot be averaged is left unstacked
+        // rather than failing the search.
+        if let Ok(vector) = average_embeddings(&blobs).and_then(|mean| deserialize_f32(&mean)) {
+            vectors.insert(group[0].0, vector);
+        }
+    }
+    let page = results
+        .iter()
+        .map(|r| vectors.get(&r.item_id).map(Vec::as_slice))
+        .collect::<Vec<_>>();
+    Ok(cluster(
+        &page,
+        args.threshold,
+        args.distance_function,
+        deadline,
+    ))
+}
+
+/// Union-find over the page's vectors: rows closer than `threshold` are
+/// joined. Stacks come out in rank order of their representative, which is
+/// their lowest position.
+fn cluster(
+    vectors: &[Option<&[f32]>],
+    threshold: f64,
+    distance_function: DistanceFunction,
+    deadline: Instant,
+) -> Option<Vec<ResultStack>> {
+    let mut parent = (0..vectors.len()).collect::<Vec<_>>();
+    for (i, a) in vectors.iter().enumerate() {
+        if Instant::now() >= deadline {
+            return None;
+        }
+        let Some(a) = a else { continue };
+        for (j, b) in vectors.iter().enumerate().skip(i + 1) {
+            let Some(b) = b else { continue };
+            if distance(a, b, distance_function).is_some_and(|d| d < threshold) {
+                union(&mut parent, i, j);
+            }
+        }
+    }
+
+    let mut stacks: Vec<ResultStack> = Vec::new();
+    let mut stack_of_root: HashMap<usize, usize> = HashMap::new();
+    for position in 0..vectors.len() {
+        let root = find(&mut parent, position);
+        match stack_of_root.get(&root) {
+            Some(&stack) => stacks[stack].members.push(position),
+            None => {
+                stack_of_root.insert(root, stacks.len());
+                stacks.push(ResultStack {
+                    representative: position,
+                    members: Vec::new(),
+                });
+            }
+        }
+    }
+    Some(stacks)
+}
+
+fn find(parent: &mut [usize], mut node: usize) -> usize {
+    while parent[node] != node {
+        parent[node] = parent[parent[node]];
+        node = parent[node];
+    }
+    node
+}
+
+fn union(parent: &mut [usize], a: usize, b: usize) {
+    let (a, b) = (find(parent, a), find(parent, b));
+    // The lower position becomes the root, so a stack's root is its
+    // best-ranked row.
+    if a < b {
+        parent[b] = a;
+    } else if b < a {
+        parent[a] = b;
+    }
+}
+
+/// Distance between two vectors as the vector filters measure it; `None`
+/// when they cannot be compared (different dimensions, or a zero vector
+/// under cosine).
+fn distance(a: &[f32], b: &[f32], distance_function: DistanceFunction) -> Option<f64> {
+    if a.len() != b.len() {
+        return None;
+    }
+    let pairs = a.iter().zip(b).map(|(&x, &y)| (f64::from(x), f64::from(y)));
+    match distance_function {
+        DistanceFunction::L2 => Some(pairs.map(|(x, y)| (x - y) * (x - y)).sum::<f64>().sqrt()),
+        DistanceFunction::Cosine => {
+            let (mut dot, mut norm_a, mut norm_b) = (0.0, 0.0, 0.0);
+            for (x, y) in pairs {
+                dot += x * y;
+                norm_a += x * x;
+                norm_b += y * y;
+            }
+            if norm_a == 0.0 || norm_b == 0.0 {
+                return None;
+            }
+            Some(1.0 - dot / (norm_a.sqrt() * norm_b.sqrt()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stack(representative: usize, members: &[usize]) -> ResultStack {
+        ResultStack {
+            representative,
+            members: members.to_vec(),
+        }
+    }
+
+    fn later() -> Instant {
+        Instant::now() + Duration::from_secs(60)
+    }
+
+    // Ensures close rows chain into one stack led by the best-ranked row,
+    // while rows without a comparable vector stay on their own.
+    #[test]
+    fn cluster_chains_neighbours_and_keeps_rank_order() {
+        let vectors: [Option<&[f32]>; 6] = [
+            Some(&[0.0, 0.0]),
+            Some(&[10.0, 0.0]),
+            None,
+            Some(&[0.0, 0.9]),
+            Some(&[0.0, 1.8]),
+            Some(&[0.0, 0.0, 0.0]),
+        ];
+        let stacks = cluster(&vectors, 1.0, DistanceFunction::L2, later()).unwrap();
+        assert_eq!(
+            stacks,
+            vec![
+                stack(0, &[3, 4]),
+                stack(1, &[]),
+                stack(2, &[]),
+                stack(5, &[]),
+            ]
+        );
+    }
+
+    // Ensures cosine grouping ignores magnitude, and that running out of
+    // budget yields no stacks rather than a partial grouping.
+    #[test]
+    fn cluster_uses_cosine_and_honours_the_deadline() {
+        let vectors: [Option<&[f32]>; 3] =
+            [Some(&[1.0, 0.0]), Some(&[0.0, 1.0]), Some(&[5.0, 0.1])];
+        let stacks = cluster(&vectors, 0.01, DistanceFunction::Cosine, later()).unwrap();
+        assert_eq!(stacks, vec![stack(0, &[2]), stack(1, &[])]);
+        assert_eq!(
+            cluster(&vectors, 0.01, DistanceFunction::Cosine, Instant::now()),
+            None
+        );
+    }
+}
@@ -0,0 +1,278 @@
+//! `POST /api/search/export`: the files matching a PQL filter, one per item,
+//! as a zip archive in the response or as hardlinks/copies written by a
+//! queued job. Naming, destination checks and the job itself live in
+//! `jobs::search_export`.
+
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    body::Body,
+    extract::{Extension, State},
+    http::{Method, Response, StatusCode, header},
+    response::IntoResponse,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use tokio_util::io::ReaderStream;
+use utoipa::ToSchema;
+
+use crate::api::db_params::DbQueryParams;
+use crate::api::search::matching_file_rows;
+use crate::api_error::ApiError;
+use crate::db::files::get_raw_path_bytes;
+use crate::db::folders::get_folders_from_database;
+use crate::db::{DbConnection, ReadOnly};
+use crate::jobs::queue::{JobChain, JobModel, JobRequest, JobType, enqueue_job};
+use crate::jobs::search_export::{
+    self, ExportFile, ExportMode, SearchExportOptions, SearchExportProgress,
+};
+use crate::policy::{PolicyContext, RequestIdentity, ruleset_allows};
+use crate::pql::model::{Column as PqlColumn, QueryElement};
+use crate::proxy::ProxyState;
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+const BYTES_PER_MB: u64 = 1024 * 1024;
+/// The route a policy must allow for hardlink/copy exports, which write to
+/// the server's filesystem: the same bar as starting a folder rescan.
+const WRITE_EXPORT_PROBE: &str = "/api/jobs/folders/rescan";
+
+#[derive(Deserialize, ToSchema)]
+pub(crate) struct SearchExportRequest {
+    /// PQL filter selecting the items to export; null exports every item.
+    /// Each item contributes one file, its pinned primary file if it has one
+    #[serde(default)]
+    #[schema(no_recursion)]
+    query: Option<QueryElement>,
+    #[serde(default)]
+    mode: ExportMode,
+    /// Directory hardlink and copy exports write into (created if missing).
+    /// Must be absolute and outside included folders unless listed in
+    /// `[search] export_allowed_destinations`; ignored for zip exports
+    #[serde(default)]
+    destination: Option<String>,
+}
+
+#[utoipa::path(
+    post,
+    operation_id = "export_search_results",
+    path = "/api/search/export",
+    tag = "search",
+    summary = "Export the files matching a search",
+    description = "Exports one file per item matching the PQL filter, preferring each item's pinned primary file, under flat names: the first file in path order keeps its filename and later ones with the same name (case-insensitively) get the first eight hex digits of their sha256 appended.\n`zip` streams an uncompressed archive, bounded by `[search] export_max_files` and `export_max_mb`; files that cannot be read are listed in an `export_errors.txt` entry.\n`hardlink` and `copy` enqueue a job writing into `destination`, which requires a policy allowed to start folder rescans. Existing targets are never overwritten. See GET /api/search/export/status for progress and per-file failures.",
+    params(DbQueryParams),
+    request_body = SearchExportRequest,
+    responses(
+        (status = 200, description = "Zip archive of the matched files", content_type = "application/zip", body = Vec<u8>),
+        (status = 202, description = "Enqueued hardlink or copy export job", body = JobModel),
+        (status = 400, description = "Too many files, too large, or an invalid destination"),
+        (status = 403, description = "The policy may not write exports to the server's filesystem")
+    )
+)]
+pub(crate) async fn export_search_results(
+    State(state): State<Arc<ProxyState>>,
+    mut db: DbConnection<ReadOnly>,
+    policy: Option<Extension<PolicyContext>>,
+    identity: Option<Extension<RequestIdentity>>,
+    Json(request): Json<SearchExportRequest>,
+) -> ApiResult<Response<Body>> {
+    let settings = &state.settings.search;
+    if request.mode != ExportMode::Zip {
+        ensure_write_exports_allowed(&state, policy.as_deref())?;
+    }
+    let rows = matching_file_rows(
+        &state,
+        &mut db,
+        request.query,
+        identity.as_deref(),
+        vec![PqlColumn::Path, PqlColumn::Sha256, PqlColumn::Size],
+    )
+    .await?;
+    let rows = rows
+        .iter()
+        .map(|row| {
+            Ok((
+                row.try_get::<String, _>("path")?,
+                row.try_get::<String, _>("sha256")?,
+                row.try_get::<Option<i64>, _>("size")?.unwrap_or(0),
+            ))
+        })
+        .collect::<Result<Vec<_>, sqlx::Error>>()
+        .map_err(|err| {
+            tracing::error!(error = %err, "failed to read search export rows");
+            ApiError::internal("Failed to execute search query")
+        })?;
+    let mut files = search_export::name_export_files(rows);
+    let paths = files
+        .iter()
+        .map(|file| file.path.clone())
+        .collect::<Vec<_>>();
+    let mut raw_paths = get_raw_path_bytes(&mut db.conn, &paths).await?;
+    for file in &mut files {
+        file.path_bytes = raw_paths.remove(&file.path);
+    }
+
+    match request.mode {
+        ExportMode::Zip => {
+            let max_bytes = settings.export_max_mb.saturating_mul(BYTES_PER_MB);
+            if files.len() > settings.export_max_files {
+                return Err(ApiError::bad_request(format!(
+                    "{} files match; zip exports are limited to {}",
+                    files.len(),
+                    settings.export_max_files
+                )));
+            }
+            let total: u64 = files.iter().map(|file| file.size.max(0) as u64).sum();
+            if total > max_bytes {
+                return Err(ApiError::bad_request(format!(
+                    "Matched files total {} MB; zip exports are limited to {} MB",
+                    total.div_ceil(BYTES_PER_MB),
+                    settings.export_max_mb
+                )));
+            }
+            stream_zip(files, max_bytes).await
+        }
+        ExportMode::Hardlink | ExportMode::Copy => {
+            if files.len() > settings.export_job_max_files {
+                return Err(ApiError::bad_request(format!(
+                    "{} files match; export jobs are limited to {}",
+                    files.len(),
+                    settings.export_job_max_files
+                )));
+            }
+            let destination = request
+                .destination
+                .as_deref()
+                .ok_or_else(|| ApiError::bad_request("destination is required for this mode"))?;
+            let included = get_folders_from_database(&mut db.conn, true).await?;
+            let destination = search_export::validate_destination(
+                destination,
+                &included,
+                &settings.export_allowed_destinations,
+            )?;
+            let job = enqueue_export_job(&db, files, request.mode, destination).await?;
+            Ok((StatusCode::ACCEPTED, Json(job)).into_response())
+        }
+    }
+}
+
+fn ensure_write_exports_allowed(
+    state: &ProxyState,
+    context: Option<&PolicyContext>,
+) -> ApiResult<()> {
+    let Some(context) = context else {
+        return Ok(());
+    };
+    let settings = &state.settings;
+    let allowed = settings
+        .policies
+        .iter()
+        .find(|policy| policy.name == context.policy_name)
+        .is_some_and(|policy| ruleset_allows(settings, policy, &Method::POST, WRITE_EXPORT_PROBE));
+    if allowed {
+        Ok(())
+    } else {
+        Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            "This policy may only export search results as zip archives",
+        ))
+    }
+}
+
+async fn stream_zip(files: Vec<ExportFile>, max_bytes: u64) -> ApiResult<Response<Body>> {
+    // zip needs a seekable writer, so the archive is staged in an unlinked
+    // temp file and streamed from there once complete.
+    let archive = tokio::task::spawn_blocking(move || {
+        let out = tempfile::tempfile()?;
+        search_export::write_zip_archive(&files, out, max_bytes)
+    })
+    .await
+    .map_err(|err| std::io::Error::other(err.to_string()))
+    .and_then(|result| result)
+    .map_err(|err| {
+        tracing::error!(error = %err, "failed to write search export archive");
+        ApiError::internal("Failed to write the export archive")
+    })?;
+    let length = archive.metadata().map(|meta| meta.len()).ok();
+    let body = Body::from_stream(ReaderStream::new(tokio::fs::File::from_std(archive)));
+
+    let mut response = Response::new(body);
+    let headers = response.headers_mut();
+    headers.insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_static("application/zip"),
+    );
+    headers.insert(
+        header::CONTENT_DISPOSITION,
+        header::HeaderValue::from_static("attachment; filename=\"panoptikon-export.zip\""),
+    );
+    if let Some(length) = length {
+        headers.insert(header::CONTENT_LENGTH, header::HeaderValue::from(length));
+    }
+    Ok(response)
+}
+
+async fn enqueue_export_job(
+    db: &DbConnection<ReadOnly>,
+    files: Vec<ExportFile>,
+    mode: ExportMode,
+    destination: std::path::PathBuf,
+) -> ApiResult<JobModel> {
+    let export_id = search_export::stage_export(files);
+    let options = SearchExportOptions {
+        export_id: export_id.clone(),
+        mode,
+        destination,
+    };
+    let metadata = serde_json::to_string(&options).map_err(|err| {
+        tracing::error!(error = %err, "failed to encode search export options");
+        ApiError::internal("Failed to enqueue search export")
+    })?;
+    let job = enqueue_job(JobRequest {
+        job_type: JobType::SearchExport,
+        index_db: db.index_db.clone(),
+        user_data_db: db.user_data_db.clone(),
+        metadata: Some(metadata),
+        batch_size: None,
+        threshold: None,
+        log_id: None,
+        tag: None,
+        dedup_key: None,
+        run_now: false,
+        reprocess: false,
+        chain: JobChain::default(),
+    })
+    .await;
+    if job.is_err() {
+        search_export::discard_export(&export_id);
+    }
+    job
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct SearchExportStatusResponse {
+    /// The running or most recent hardlink/copy export job since the
+    /// server started.
+    last_run: Option<SearchExportProgress>,
+}
+
+#[utoipa::path(
+    get,
+    operation_id = "get_search_export_status",
+    path = "/api/search/export/status",
+    tag = "search",
+    summary = "Get search export job progress",
+    description = "Progress of the running or most recent hardlink/copy export job for the index DB: files exported, skipped because the target existed, and failed, with the first 100 failures by path. A `last_run` with a null `finished_at` is still running or was cancelled.",
+    params(DbQueryParams),
+    responses(
+        (status = 200, description = "Search export status", body = SearchExportStatusResponse)
+    )
+)]
+pub(crate) async fn get_search_export_status(
+    db: DbConnection<ReadOnly>,
+) -> ApiResult<Json<SearchExportStatusResponse>> {
+    Ok(Json(SearchExportStatusResponse {
+        last_run: search_export::last_progress(&db.index_db),
+    }))
+}
@@ -0,0 +1,344 @@
+//! Slow query log for PQL searches.
+//!
+//! A search whose build plus execution time reaches `[search]
+//! slow_query_ms` is recorded in a fixed-size, in-memory ring buffer: the
+//! request JSON (capped at `slow_query_max_bytes`), hashes of the rendered
+//! SQL, per-phase durations and row counts. The log is process-local and
+//! starts empty at every startup. Searches under the threshold pay for one
+//! atomic load and a comparison; nothing is serialized or locked unless the
+//! search is being recorded.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+use axum::{Json, extract::Query};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use utoipa::{IntoParams, ToSchema};
+
+/// Threshold in milliseconds; `0` disables the log.
+static THRESHOLD_MS: AtomicU64 = AtomicU64::new(0);
+
+struct SlowLogState {
+    entries: VecDeque<SlowQueryEntry>,
+    capacity: usize,
+    max_query_bytes: usize,
+    next_id: u64,
+}
+
+static SLOWLOG: OnceLock<Mutex<SlowLogState>> = OnceLock::new();
+
+fn slowlog() -> &'static Mutex<SlowLogState> {
+    SLOWLOG.get_or_init(|| {
+        Mutex::new(SlowLogState {
+            entries: VecDeque::new(),
+            capacity: 0,
+            max_query_bytes: 0,
+            next_id: 1,
+        })
+    })
+}
+
+/// Apply `[search] slow_query_*` (startup). Shrinking the capacity drops the
+/// oldest entries; a capacity of `0` disables the log like a `0` threshold.
+pub(crate) fn configure(threshold_ms: u64, capacity: usize, max_query_bytes: usize) {
+    let mut state = slowlog().lock().unwrap_or_else(|err| err.into_inner());
+    state.capacity = capacity;
+    state.max_query_bytes = max_query_bytes;
+    while state.entries.len() > capacity {
+        state.entries.pop_front();
+    }
+    let threshold = if capacity == 0 { 0 } else { threshold_ms };
+    THRESHOLD_MS.store(threshold, Ordering::Relaxed);
+}
+
+/// Whether a search that took `total_ms` should be recorded.
+pub(crate) fn is_slow(total_ms: f64) -> bool {
+    let threshold = THRESHOLD_MS.load(Ordering::Relaxed);
+    threshold > 0 && total_ms >= threshold as f64
+}
+
+/// What a search reports about itself once it is known to be slow.
+pub(crate) struct SlowQuery<'a> {
+    /// The request body; for saved-query runs, the saved query's name and
+    /// query. Absent for warmup searches.
+    pub query: Option<&'a serde_json::Value>,
+    pub sql: Option<&'a str>,
+    pub count_sql: Option<&'a str>,
+    pub build_ms: f64,
+    pub execute_ms: f64,
+    pub total_ms: f64,
+    pub rows: usize,
+    pub count: i64,
+}
+
+/// Append a search to the log, evicting the oldest entry when full. Callers
+/// check [`is_slow`] first so fast searches never get this far.
+pub(crate) fn record(index_db: &str, slow: SlowQuery<'_>) {
+    let max_query_bytes = slowlog()
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .max_query_bytes;
+    let (query, query_truncated) = match slow.query {
+        Some(value) => {
+            let (text, truncated) = cap_query(value.to_string(), max_query_bytes);
+            (Some(text), truncated)
+        }
+        None => (None, false),
+    };
+    let entry = SlowQueryEntry {
+        id: 0,
+        timestamp: chrono::Local::now().to_rfc3339(),
+        index_db: index_db.to_string(),
+        query,
+        query_truncated,
+        sql_hash: slow.sql.map(sql_hash),
+        count_sql_hash: slow.count_sql.map(sql_hash),
+        build_ms: slow.build_ms,
+        execute_ms: slow.execute_ms,
+        total_ms: slow.total_ms,
+        rows: slow.rows,
+        count: slow.count,
+    };
+    tracing::warn!(
+        index_db,
+        total_ms = slow.total_ms,
+        sql_hash = entry.sql_hash.as_deref(),
+        "slow pql search"
+    );
+
+    let mut state = slowlog().lock().unwrap_or_else(|err| err.into_inner());
+    if state.capacity == 0 {
+        return;
+    }
+    while state.entries.len() >= state.capacity {
+        state.entries.pop_front();
+    }
+    let id = state.next_id;
+    state.next_id += 1;
+    state.entries.push_back(SlowQueryEntry { id, ..entry });
+}
+
+/// Truncate the serialized query to `max_bytes` on a char boundary.
+fn cap_query(mut text: String, max_bytes: usize) -> (String, bool) {
+    if text.len() <= max_bytes {
+        return (text, false);
+    }
+    let mut end = max_bytes;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    text.truncate(end);
+    (text, true)
+}
+
+fn sql_hash(sql: &str) -> String {
+    format!("{:x}", Sha256::digest(sql.as_bytes()))
+}
+
+#[derive(Clone, Serialize, ToSchema)]
+pub(crate) struct SlowQueryEntry {
+    /// Increases with every recorded search; never reused while the process
+    /// runs, so clients can tell which entries they have already seen.
+    pub id: u64,
+    /// When the search finished (RFC 3339, local time).
+    pub timestamp: String,
+    pub index_db: String,
+    /// The request JSON, cut off at `[search] slow_query_max_bytes`; for
+    /// saved-query runs, `{"saved_query": name, "query": ...}`. Absent for
+    /// warmup searches.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>,
+    /// Whether `query` was cut off (and is therefore not valid JSON).
+    pub query_truncated: bool,
+    /// SHA-256 of the rendered results SQL, without pagination. Searches
+    /// with the same hash ran the same statement.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sql_hash: Option<String>,
+    /// SHA-256 of the rendered count SQL.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub count_sql_hash: Option<String>,
+    /// Preprocessing, building and compiling, count and results together.
+    pub build_ms: f64,
+    /// Count and results execution together.
+    pub execute_ms: f64,
+    /// The whole search, enrichment included; this is what the threshold
+    /// is compared against.
+    pub total_ms: f64,
+    /// Rows returned on the requested page.
+    pub rows: usize,
+    /// Total count, or `0` when the search did not ask for one.
+    pub count: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct SlowQueryLog {
+    /// Threshold in milliseconds; `0` means the log is disabled.
+    pub threshold_ms: u64,
+    /// How many entries the ring buffer holds before dropping the oldest.
+    pub capacity: usize,
+    /// Entries currently held.
+    pub total: usize,
+    pub page: usize,
+    pub page_size: usize,
+    /// Paginated entries, most recent first.
+    pub entries: Vec<SlowQueryEntry>,
+}
+
+#[derive(Deserialize, ToSchema, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct SlowLogPageParams {
+    #[serde(default = "default_page")]
+    #[param(default = 1)]
+    /// Page number
+    page: usize,
+    #[serde(default = "default_page_size")]
+    #[param(default = 50)]
+    /// Page size
+    page_size: usize,
+}
+
+fn default_page() -> usize {
+    1
+}
+
+fn default_page_size() -> usize {
+    50
+}
+
+pub(crate) fn snapshot(page: usize, page_size: usize) -> SlowQueryLog {
+    let page = page.max(1);
+    let page_size = page_size.max(1);
+    let state = slowlog().lock().unwrap_or_else(|err| err.into_inner());
+    let entries = state
+        .entries
+        .iter()
+        .rev()
+        .skip((page - 1).saturating_mul(page_size))
+        .take(page_size)
+        .cloned()
+        .collect();
+    SlowQueryLog {
+        threshold_ms: THRESHOLD_MS.load(Ordering::Relaxed),
+        capacity: state.capacity,
+        total: state.entries.len(),
+        page,
+        page_size,
+        entries,
+    }
+}
+
+pub(crate) fn clear() {
+    slowlog()
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .entries
+        .clear();
+}
+
+#[utoipa::path(
+    get,
+    operation_id = "get_search_slowlog",
+    path = "/api/search/slowlog",
+    tag = "search",
+    summary = "Get the slow query log",
+    description = "Returns the PQL searches that took at least `[search] slow_query_ms`, most \
+        recent first. The log is an in-memory ring buffer of `[search] slow_query_log_size` \
+        entries and starts empty at every startup.",
+    params(SlowLogPageParams),
+    responses(
+        (status = 200, description = "Slow query log", body = SlowQueryLog)
+    )
+)]
+pub async fn get_slowlog(Query(query): Query<SlowLogPageParams>) -> Json<SlowQueryLog> {
+    Json(snapshot(query.page, query.page_size))
+}
+
+#[utoipa::path(
+    delete,
+    operation_id = "clear_search_slowlog",
+    path = "/api/search/slowlog",
+    tag = "search",
+    summary = "Clear the slow query log",
+    description = "Drops every slow query log entry and returns the emptied log. Entry IDs \
+        keep increasing after a clear.",
+    params(SlowLogPageParams),
+    responses(
+        (status = 200, description = "Slow query log after clearing", body = SlowQueryLog)
+    )
+)]
+pub async fn clear_slowlog(Query(query): Query<SlowLogPageParams>) -> Json<SlowQueryLog> {
+    clear();
+    Json(snapshot(query.page, query.page_size))
+}
+
+/// Serializes tests that touch the process-global log.
+#[cfg(test)]
+pub(crate) fn test_lock() -> std::sync::MutexGuard<'static, ()> {
+    static LOCK: OnceLock<Mutex<()>> = OnceLock::new();
+    LOCK.get_or_init(|| Mutex::new(()))
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slow(total_ms: f64, query: &serde_json::Value) -> SlowQuery<'_> {
+        SlowQuery {
+            query: Some(query),
+            sql: Some("SELECT 1"),
+            count_sql: None,
+            build_ms: 1.0,
+            execute_ms: total_ms - 1.0,
+            total_ms,
+            rows: 3,
+            count: 0,
+        }
+    }
+
+    // The threshold gates recording, the ring keeps only the newest
+    // entries, long queries are cut off on a char boundary, and pages run
+    // newest first.
+    #[test]
+    fn slowlog_keeps_the_newest_entries_within_capacity() {
+        let _guard = test_lock();
+        configure(100, 2, 12);
+        clear();
+
+        assert!(!is_slow(99.0));
+        assert!(is_slow(100.0));
+
+        let short = serde_json::json!({"page": 1});
+        let long = serde_json::json!({"query": "ééééééééé"});
+        record("index", slow(150.0, &short));
+        record("index", slow(200.0, &long));
+        record("index", slow(300.0, &short));
+
+        let log = snapshot(1, 10);
+        assert_eq!(log.total, 2);
+        let totals: Vec<f64> = log.entries.iter().map(|entry| entry.total_ms).collect();
+        assert_eq!(totals, vec![300.0, 200.0]);
+        assert!(log.entries[0].id > log.entries[1].id);
+        assert_eq!(log.entries[0].query.as_deref(), Some(r#"{"page":1}"#));
+        assert!(!log.entries[0].query_truncated);
+        let truncated = &log.entries[1];
+        assert!(truncated.query_truncated);
+        assert!(truncated.query.as_ref().unwrap().len() <= 12);
+        assert_eq!(
+            truncated.sql_hash.as_deref(),
+            Some(format!("{:x}", Sha256::digest(b"SELECT 1")).as_str())
+        );
+
+        let second_page = snapshot(2, 1);
+        assert_eq!(second_page.entries.len(), 1);
+        assert_eq!(second_page.entries[0].total_ms, 200.0);
+
+        clear();
+        assert_eq!(snapshot(1, 10).total, 0);
+        configure(0, 2, 12);
+        assert!(!is_slow(1_000_000.0));
+    }
+}
@@ -0,0 +1,371 @@
+//! Typeahead suggestions for the search box.
+//!
+//! Sources are asked in a fixed order (saved queries, bookmark namespaces,
+//! tags, file name words), each for only the slots still open, and the
+//! lookup stops as soon as `limit` suggestions are collected. Every source
+//! gets its own time budget; one that runs over is skipped and named in the
+//! response, so a slow path lookup never holds up tag suggestions. Complete
+//! responses are kept in a small in-memory cache for a few seconds, since
+//! the search box asks again for every keystroke and backspace.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use axum::{Extension, Json};
+use axum_extra::extract::Query;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::api::db_params::DbQueryParams;
+use crate::api_error::ApiError;
+use crate::db::suggestions::{
+    bookmark_namespace_suggestions, filename_token_suggestions, saved_query_suggestions,
+    tag_suggestions,
+};
+use crate::db::tags::get_most_common_tags_frequency;
+use crate::db::{DbConnection, ReadOnly};
+use crate::policy::{RequestIdentity, UserAccess, scoped_user};
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+const MAX_LIMIT: i64 = 50;
+/// Time each source may take before it is skipped.
+const SOURCE_BUDGET: Duration = Duration::from_millis(100);
+const CACHE_ENTRIES: usize = 256;
+const CACHE_TTL: Duration = Duration::from_secs(30);
+
+fn default_limit() -> i64 {
+    10
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct SuggestQuery {
+    /// What has been typed so far
+    #[serde(default)]
+    q: String,
+    /// Maximum number of suggestions (at most 50)
+    #[serde(default = "default_limit")]
+    #[param(default = 10)]
+    limit: i64,
+    /// The user whose saved queries and bookmark namespaces are suggested.
+    /// Defaults to the authenticated user when the policy resolves one.
+    #[param(nullable)]
+    user: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum SuggestionKind {
+    SavedQuery,
+    BookmarkNamespace,
+    Tag,
+    Path,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub(crate) struct SearchSuggestion {
+    pub kind: SuggestionKind,
+    /// The suggested text: a saved query or bookmark namespace name, a tag
+    /// name, or a word from file names.
+    pub text: String,
+    /// Tag namespace, for tags.
+    pub namespace: Option<String>,
+    /// Times a tag is applied, or files whose name contains the word.
+    pub count: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub(crate) struct SearchSuggestions {
+    pub suggestions: Vec<SearchSuggestion>,
+    /// Sources skipped because they ran over their time budget.
+    pub timed_out: Vec<SuggestionKind>,
+    /// Served from the suggestion cache.
+    pub cached: bool,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct CacheKey {
+    index_db: String,
+    user_data_db: String,
+    user: Option<String>,
+    q: String,
+    limit: i64,
+}
+
+struct SuggestCache {
+    entries: HashMap<CacheKey, (Instant, Vec<SearchSuggestion>)>,
+    order: VecDeque<CacheKey>,
+}
+
+fn cache() -> &'static Mutex<SuggestCache> {
+    static CACHE: OnceLock<Mutex<SuggestCache>> = OnceLock::new();
+    CACHE.get_or_init(|| {
+        Mutex::new(SuggestCache {
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+        })
+    })
+}
+
+fn cache_get(key: &CacheKey) -> Option<Vec<SearchSuggestion>> {
+    let mut cache = cache().lock().unwrap_or_else(|err| err.into_inner());
+    match cache.entries.get(key) {
+        Some((stored, suggestions)) if stored.elapsed() < CACHE_TTL => Some(suggestions.clone()),
+        Some(_) => {
+            cache.entries.remove(key);
+            cache.order.retain(|entry| entry != key);
+            None
+        }
+        None => None,
+    }
+}
+
+fn cache_put(key: CacheKey, suggestions: Vec<SearchSuggestion>) {
+    let mut cache = cache().lock().unwrap_or_else(|err| err.into_inner());
+    if cache
+        .entries
+        .insert(key.clone(), (Instant::now(), suggestions))
+        .is_none()
+    {
+        cache.order.push_back(key);
+    }
+    while cache.order.len() > CACHE_ENTRIES {
+        if let Some(oldest) = cache.order.pop_front() {
+            cache.entries.remove(&oldest);
+        }
+    }
+}
+
+/// Runs one source within [`SOURCE_BUDGET`]; `None` when it ran over.
+async fn within_budget<T>(
+    future: impl std::future::Future<Output = ApiResult<T>>,
+) -> ApiResult<Option<T>> {
+    match tokio::time::timeout(SOURCE_BUDGET, future).await {
+        Ok(result) => result.map(Some),
+        Err(_) => Ok(None),
+    }
+}
+
+#[utoipa::path(
+    get,
+    operation_id = "get_search_suggestions",
+    path = "/api/search/suggest",
+    tag = "search",
+    summary = "Suggest completions for the search box",
+    description = "Returns up to `limit` suggestions for what has been typed, each tagged with its kind: saved query names, bookmark namespaces, tag names and words from file names, in that order and all matched by prefix, case-insensitively. Tags come most used first and file name words most common first. Later sources are not consulted once `limit` is reached.\nA query shorter than two characters returns the most common tags instead.\nEach source has a short time budget; sources that run over are skipped and listed in `timed_out`. Complete results are cached for 30 seconds.",
+    params(DbQueryParams, SuggestQuery),
+    responses(
+        (status = 200, description = "Search suggestions", body = SearchSuggestions),
+        (status = 400, description = "limit is not between 1 and 50")
+    )
+)]
+pub async fn get_search_suggestions(
+    mut db: DbConnection<ReadOnly>,
+    Query(query): Query<SuggestQuery>,
+    identity: Option<Extension<RequestIdentity>>,
+) -> ApiResult<Json<SearchSuggestions>> {
+    if !(1..=MAX_LIMIT).contains(&query.limit) {
+        return Err(ApiError::bad_request(format!(
+            "limit must be between 1 and {MAX_LIMIT}"
+        )));
+    }
+    let prefix = query.q.trim().to_lowercase();
+    // Saved queries and bookmark namespaces follow the user scoping rules; a
+    // request not allowed to see the user's data just gets no suggestions
+    // from them. The cache is keyed by this resolved user, never the raw
+    // parameter, so callers cannot see each other's cached entries.
+    let user = scoped_user(identity.as_deref(), query.user.as_deref(), UserAccess::Read).ok();
+    let key = CacheKey {
+        index_db: db.index_db.clone(),
+        user_data_db: db.user_data_db.clone(),
+        user: user.clone(),
+        q: prefix.clone(),
+        limit: query.limit,
+    };
+    if let Some(suggestions) = cache_get(&key) {
+        return Ok(Json(SearchSuggestions {
+            suggestions,
+            timed_out: Vec::new(),
+            cached: true,
+        }));
+    }
+
+    let conn = &mut db.conn;
+    let mut suggestions = Vec::new();
+    let mut timed_out = Vec::new();
+    if prefix.chars().count() < 2 {
+        match within_budget(get_most_common_tags_frequency(
+            conn,
+            None,
+            &[],
+            None,
+            query.limit,
+        ))
+        .await?
+        {
+            Some(tags) => {
+                suggestions.extend(tags.into_iter().map(|(namespace, name, count, _)| {
+                    SearchSuggestion {
+                        kind: SuggestionKind::Tag,
+                        text: name,
+                        namespace: Some(namespace),
+                        count: Some(count),
+                    }
+                }))
+            }
+            None => timed_out.push(SuggestionKind::Tag),
+        }
+    } else {
+        for kind in [
+            SuggestionKind::SavedQuery,
+            SuggestionKind::BookmarkNamespace,
+            SuggestionKind::Tag,
+            SuggestionKind::Path,
+        ] {
+            let remaining = query.limit - suggestions.len() as i64;
+            if remaining <= 0 {
+                break;
+            }
+            let found = match kind {
+                SuggestionKind::SavedQuery => match user.as_deref() {
+                    Some(user) => {
+                        within_budget(saved_query_suggestions(conn, user, &prefix, remaining))
+                            .await?
+                            .map(|names| names.into_iter().map(|name| named(kind, name)).collect())
+                    }
+                    None => Some(Vec::new()),
+                },
+                SuggestionKind::BookmarkNamespace => match user.as_deref() {
+                    Some(user) => within_budget(bookmark_namespace_suggestions(
+                        conn, user, &prefix, remaining,
+                    ))
+                    .await?
+                    .map(|names| names.into_iter().map(|name| named(kind, name)).collect()),
+                    None => Some(Vec::new()),
+                },
+                SuggestionKind::Tag => within_budget(tag_suggestions(conn, &prefix, remaining))
+                    .await?
+                    .map(|tags| {
+                        tags.into_iter()
+                            .map(|(namespace, name, count)| SearchSuggestion {
+                                kind,
+                                text: name,
+                                namespace: Some(namespace),
+                                count: Some(count),
+                            })
+                            .collect()
+                    }),
+                SuggestionKind::Path => {
+                    within_budget(filename_token_suggestions(conn, &prefix, remaining))
+                        .await?
+                        .map(|tokens| {
+                            tokens
+                                .into_iter()
+                                .map(|(token, count)| SearchSuggestion {
+                                    count: Some(count),
+                                    ..named(kind, token)
+                                })
+                                .collect()
+                        })
+                }
+            };
+            match found {
+                Some(found) => suggestions.extend::<Vec<_>>(found),
+                None => timed_out.push(kind),
+            }
+        }
+    }
+
+    if timed_out.is_empty() {
+        cache_put(key, suggestions.clone());
+    }
+    Ok(Json(SearchSuggestions {
+        suggestions,
+        timed_out,
+        cached: false,
+    }))
+}
+
+fn named(kind: SuggestionKind, text: String) -> SearchSuggestion {
+    SearchSuggestion {
+        kind,
+        text,
+        namespace: None,
+        count: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::UserDataWrite;
+    use crate::db::migrations::migrate_databases_on_disk;
+    use crate::test_utils::test_data_dir;
+
+    // Ensures two identities sending the same `?user=` resolve to different
+    // cache entries: the owner gets their saved queries and bookmark
+    // namespaces, and another caller gets neither, cached or not.
+    #[tokio::test]
+    async fn suggestions_are_cached_per_resolved_user() {
+        let _test_env = test_data_dir();
+        let index_db = "search_suggest_index".to_string();
+        let user_data_db = "search_suggest_user".to_string();
+        migrate_databases_on_disk(Some(&index_db), Some(&user_data_db))
+            .await
+            .unwrap();
+        let mut user_data = DbConnection::<UserDataWrite>::open_named(
+            Some(index_db.clone()),
+            Some(user_data_db.clone()),
+        )
+        .await
+        .unwrap();
+        crate::db::saved_queries::create_saved_query(
+            &mut user_data.conn,
+            "bob",
+            "beach walks",
+            None,
+            r#"{"page_size": 10}"#,
+        )
+        .await
+        .unwrap();
+        crate::db::bookmarks::add_bookmark(&mut user_data.conn, "sha_1", "beaches", "bob", None)
+            .await
+            .unwrap();
+        drop(user_data);
+
+        let suggest = |user: &str| {
+            let identity = RequestIdentity::User {
+                user: user.to_string(),
+                admin: false,
+            };
+            let (index_db, user_data_db) = (index_db.clone(), user_data_db.clone());
+            async move {
+                let db = DbConnection::<ReadOnly>::open_named(Some(index_db), Some(user_data_db))
+                    .await
+                    .unwrap();
+                let query = SuggestQuery {
+                    q: "bea".to_string(),
+                    limit: 10,
+                    user: Some("bob".to_string()),
+                };
+                get_search_suggestions(db, Query(query), Some(Extension(identity)))
+                    .await
+                    .unwrap()
+                    .0
+            }
+        };
+
+        let owner = suggest("bob").await;
+        let texts: Vec<_> = owner.suggestions.iter().map(|s| s.text.as_str()).collect();
+        assert_eq!(texts, ["beach walks", "beaches"]);
+        assert!(!owner.cached);
+
+        for _ in 0..2 {
+            let other = suggest("alice").await;
+            assert!(other.suggestions.is_empty());
+        }
+        assert!(suggest("bob").await.cached);
+    }
+}
@@ -0,0 +1,330 @@
+//! Startup search warmup (`[search.warmup]`).
+//!
+//! The first semantic search after a restart pays for a cold embedding cache
+//! (a model load plus an embed round trip) and for vector index pages that
+//! are not in SQLite's page cache yet. Warmup runs the configured searches
+//! once, in a background task started after the listeners bind: each prompt
+//! as a semantic search per search-usable embedding model, and each saved
+//! query, all with `page_size = 1` through the normal `execute_pql` path.
+//! Every step is best-effort — failures are recorded in the report and
+//! logged, never propagated — and the last report is exposed as
+//! `search_warmup` on the inference health endpoint.
+
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use utoipa::ToSchema;
+
+use super::saved_queries::load_saved_query;
+use super::search::{BookmarkStatusParams, execute_pql};
+use crate::api_error::ApiError;
+use crate::config::SearchWarmupConfig;
+use crate::db::extraction_log::{filter_search_embedding_setters, get_existing_setters};
+use crate::db::extraction_write::current_iso_timestamp;
+use crate::db::{DbConnection, ReadOnly};
+use crate::pql::model::PqlQuery;
+use crate::proxy::ProxyState;
+
+/// Saved queries are looked up for the default user, like the UI does.
+const WARMUP_USER: &str = "user";
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub(crate) struct SearchWarmupReport {
+    pub index_db: String,
+    pub started_at: String,
+    pub finished_at: String,
+    pub duration_seconds: f64,
+    pub steps: Vec<SearchWarmupStep>,
+    /// Set when warmup could not run at all (e.g. the default index DB
+    /// failed to open); `steps` is then empty.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub(crate) struct SearchWarmupStep {
+    /// `prompt` or `saved_query`.
+    pub kind: String,
+    /// The prompt text or saved query name.
+    pub name: String,
+    /// The embedding model a prompt was searched with.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    pub duration_seconds: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+fn last_report_slot() -> &'static Mutex<Option<SearchWarmupReport>> {
+    static LAST: OnceLock<Mutex<Option<SearchWarmupReport>>> = OnceLock::new();
+    LAST.get_or_init(|| Mutex::new(None))
+}
+
+/// The report of the most recent warmup, if one has finished.
+pub(crate) fn last_report() -> Option<SearchWarmupReport> {
+    last_report_slot()
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .clone()
+}
+
+/// Runs every configured warmup search against the given databases (`None`
+/// = configured default), logs a summary, and stores the report for the
+/// health endpoint. Never fails; a no-op when nothing is configured.
+pub(crate) async fn run_search_warmup(
+    state: &ProxyState,
+    config: &SearchWarmupConfig,
+    index_db: Option<String>,
+    user_data_db: Option<String>,
+) -> Option<SearchWarmupReport> {
+    if config.prompts.is_empty() && config.saved_queries.is_empty() {
+        return None;
+    }
+    let started_at = current_iso_timestamp();
+    let start = Instant::now();
+    let (index_db, steps, error) =
+        match DbConnection::<ReadOnly>::open_named(index_db.clone(), user_data_db).await {
+            Ok(mut db) => {
+                let steps = run_steps(state, &mut db, config).await;
+                (db.index_db.clone(), steps, None)
+            }
+            Err(err) => (
+                index_db.unwrap_or_else(|| crate::db::info::db_defaults().0),
+                Vec::new(),
+                Some(err.detail().to_string()),
+            ),
+        };
+    let report = SearchWarmupReport {
+        index_db,
+        started_at,
+        finished_at: current_iso_timestamp(),
+        duration_seconds: elapsed_seconds(start),
+        steps,
+        error,
+    };
+
+    let failed = report
+        .steps
+        .iter()
+        .filter(|step| step.error.is_some())
+        .count();
+    match &report.error {
+        Some(error) => tracing::warn!(index_db = report.index_db, error, "search warmup failed"),
+        None => tracing::info!(
+            index_db = report.index_db,
+            steps = report.steps.len(),
+            failed,
+            duration_seconds = report.duration_seconds,
+            "search warmup finished"
+        ),
+    }
+    *last_report_slot()
+        .lock()
+        .unwrap_or_else(|err| err.into_inner()) = Some(report.clone());
+    Some(report)
+}
+
+async fn run_steps(
+    state: &ProxyState,
+    db: &mut DbConnection<ReadOnly>,
+    config: &SearchWarmupConfig,
+) -> Vec<SearchWarmupStep> {
+    let mut steps = Vec::new();
+    if !config.prompts.is_empty() {
+        let models = match embedding_models(db).await {
+            Ok(models) => models,
+            Err(err) => {
+                tracing::warn!(error = err.detail(), "search warmup: failed to list models");
+                Vec::new()
+            }
+        };
+        for prompt in &config.prompts {
+            for (data_type, model) in &models {
+                let query = prompt_query(prompt, model, data_type);
+                let mut step = run_step(state, db, "prompt", prompt, Ok(query)).await;
+                step.model = Some(model.clone());
+                steps.push(step);
+            }
+        }
+    }
+    for name in &config.saved_queries {
+        let query = load_saved_query(&mut db.conn, WARMUP_USER, name)
+            .await
+            .map(|saved| PqlQuery {
+                page: 1,
+                page_size: 1,
+                ..saved.query
+            });
+        steps.push(run_step(state, db, "saved_query", name, query).await);
+    }
+    steps
+}
+
+async fn run_step(
+    state: &ProxyState,
+    db: &mut DbConnection<ReadOnly>,
+    kind: &str,
+    name: &str,
+    query: Result<PqlQuery, ApiError>,
+) -> SearchWarmupStep {
+    let start = Instant::now();
+    let result = match query {
+        // Boxed: a search future is large, and the warmup loop would
+        // otherwise carry one inline for every step it awaits.
+        Ok(query) => Box::pin(execute_pql(
+            state,
+            db,
+            &BookmarkStatusParams::default(),
+            None,
+            query,
+            None,
+        ))
+        .await
+        .map(drop),
+        Err(err) => Err(err),
+    };
+    let error = result.err().map(|err| err.detail().to_string());
+    let duration_seconds = elapsed_seconds(start);
+    tracing::debug!(kind, name, duration_seconds, error, "search warmup step");
+    SearchWarmupStep {
+        kind: kind.to_string(),
+        name: name.to_string(),
+        model: None,
+        duration_seconds,
+        error,
+    }
+}
+
+/// `(data_type, setter)` for each search-usable embedding model with data.
+async fn embedding_models(
+    db: &mut DbConnection<ReadOnly>,
+) -> Result<Vec<(String, String)>, ApiError> {
+    let setters = get_existing_setters(&mut db.conn).await?;
+    let usable = filter_search_embedding_setters(setters.iter().cloned());
+    Ok(setters
+        .into_iter()
+        .filter(|(_, setter)| usable.contains(setter))
+        .collect())
+}
+
+/// A one-row semantic search for `prompt`: CLIP setters search image
+/// embeddings, text-embedding setters search text embeddings.
+fn prompt_query(prompt: &str, model: &str, data_type: &str) -> PqlQuery {
+    let filter = if data_type == "clip" {
+        "image_embeddings"
+    } else {
+        "text_embeddings"
+    };
+    serde_json::from_value(json!({
+        "query": { filter: { "query": prompt, "model": model } },
+        "page_size": 1
+    }))
+    .expect("warmup query is valid PQL")
+}
+
+fn elapsed_seconds(start: Instant) -> f64 {
+    let seconds = start.elapsed().as_secs_f64();
+    (seconds * 1000.0).round() / 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::UserDataWrite;
+    use crate::db::migrations::migrate_databases_on_disk;
+    use crate::test_utils::{offline_proxy_state, test_data_dir};
+
+    #[test]
+    fn prompts_pick_the_filter_by_data_type() {
+        let query = prompt_query("a cat", "clip/model", "clip");
+        let value = serde_json::to_value(query.query.unwrap()).unwrap();
+        assert_eq!(value["image_embeddings"]["model"], "clip/model");
+        let query = prompt_query("a cat", "embed/model", "text-embedding");
+        let value = serde_json::to_value(query.query.unwrap()).unwrap();
+        assert_eq!(value["text_embeddings"]["query"], "a cat");
+        assert_eq!(query.page_size, 1);
+    }
+
+    /// An unreachable inference server fails the prompt step without
+    /// stopping the saved query after it, and the report is kept.
+    #[tokio::test]
+    async fn warmup_records_failures_and_keeps_going() {
+        let _test_env = test_data_dir();
+        let index_db = "search_warmup_index".to_string();
+        let user_data_db = "search_warmup_user".to_string();
+        migrate_databases_on_disk(Some(&index_db), Some(&user_data_db))
+            .await
+            .unwrap();
+        let mut index = crate::db::open_index_db_write_no_user_data(&index_db)
+            .await
+            .unwrap();
+        sqlx::query(
+            r#"
+            INSERT INTO items (id, sha256, md5, type, time_added)
+            VALUES (1, 'sha_1', 'md5_1', 'image/png', '2024-01-01T00:00:00');
+            INSERT INTO setters (id, name) VALUES (1, 'clip/model-a');
+            INSERT INTO item_data (id, item_id, setter_id, data_type, idx, is_origin)
+            VALUES (1, 1, 1, 'clip', 0, 1);
+            "#,
+        )
+        .execute(&mut index)
+        .await
+        .unwrap();
+        drop(index);
+        let mut user_data = DbConnection::<UserDataWrite>::open_named(
+            Some(index_db.clone()),
+            Some(user_data_db.clone()),
+        )
+        .await
+        .unwrap();
+        crate::db::saved_queries::create_saved_query(
+            &mut user_data.conn,
+            WARMUP_USER,
+            "everything",
+            None,
+            r#"{"page_size": 50}"#,
+        )
+        .await
+        .unwrap();
+        drop(user_data);
+
+        let config = SearchWarmupConfig {
+            prompts: vec!["a cat".to_string()],
+            saved_queries: vec!["everything".to_string(), "missing".to_string()],
+        };
+        let report = run_search_warmup(
+            &offline_proxy_state(),
+            &config,
+            Some(index_db.clone()),
+            Some(user_data_db),
+        )
+        .await
+        .expect("report");
+
+        assert_eq!(report.index_db, index_db);
+        assert!(report.error.is_none());
+        let steps = report
+            .steps
+            .iter()
+            .map(|step| {
+                (
+                    step.kind.as_str(),
+                    step.name.as_str(),
+                    step.model.as_deref(),
+                    step.error.is_some(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            steps,
+            vec![
+                ("prompt", "a cat", Some("clip/model-a"), true),
+                ("saved_query", "everything", None, false),
+                ("saved_query", "missing", None, true),
+            ]
+        );
+        assert_eq!(last_report().unwrap().started_at, report.started_at);
+    }
+}
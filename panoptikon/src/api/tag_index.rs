@@ -0,0 +1,267 @@
+//! In-memory prefix index over tag names for `GET /api/search/tags`.
+//!
+//! A `LIKE` over the `tags` table costs 100-300 ms per keystroke on indexes
+//! with over a million distinct tags. Prefix lookups are served instead from
+//! a name-sorted `Vec` of the tags applied to at least
+//! `[search] tag_index_min_count` items, found by binary search.
+//!
+//! One index is kept per index DB, built lazily on the first prefix lookup
+//! and stamped with the tag epoch (`db::epochs::tag_epoch`) sampled before
+//! its rows were read. A lookup against an index whose epoch is no longer
+//! current returns None, so the caller answers from SQLite, and starts a
+//! rebuild in the background; at most one build runs per index DB.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::sync::Arc;
+
+use crate::db::epochs;
+use crate::db::open_index_db_read_no_user_data;
+use crate::db::tags::get_tags_with_min_count;
+use crate::jobs::registry::Registry;
+
+/// Tag names sorted by lowercased name; ties keep no particular order.
+pub(crate) struct TagIndex {
+    /// `tag_epoch` when the rows were read.
+    epoch: u64,
+    min_count: i64,
+    entries: Vec<TagEntry>,
+}
+
+struct TagEntry {
+    key: Box<str>,
+    namespace: Box<str>,
+    name: Box<str>,
+    count: i64,
+}
+
+impl TagIndex {
+    pub(crate) fn new(epoch: u64, min_count: i64, rows: Vec<(String, String, i64)>) -> Self {
+        let mut entries: Vec<TagEntry> = rows
+            .into_iter()
+            .map(|(namespace, name, count)| TagEntry {
+                key: name.to_lowercase().into(),
+                namespace: namespace.into(),
+                name: name.into(),
+                count,
+            })
+            .collect();
+        entries.sort_unstable_by(|a, b| a.key.cmp(&b.key));
+        Self {
+            epoch,
+            min_count,
+            entries,
+        }
+    }
+
+    /// The `limit` most used tags whose names start with `prefix`, ignoring
+    /// case, most used first.
+    pub(crate) fn lookup(&self, prefix: &str, limit: usize) -> Vec<(String, String, i64)> {
+        if limit == 0 {
+            return Vec::new();
+        }
+        let prefix = prefix.to_lowercase();
+        let start = self
+            .entries
+            .partition_point(|entry| *entry.key < *prefix.as_str());
+        let mut top = BinaryHeap::with_capacity(limit + 1);
+        for (offset, entry) in self.entries[start..].iter().enumerate() {
+            if !entry.key.starts_with(prefix.as_str()) {
+                break;
+            }
+            // Min-heap on (count, position): keeps the `limit` largest counts,
+            // earlier names winning ties.
+            top.push(Reverse((entry.count, Reverse(offset))));
+            if top.len() > limit {
+                top.pop();
+            }
+        }
+        top.into_sorted_vec()
+            .into_iter()
+            .map(|Reverse((_, Reverse(offset)))| {
+                let entry = &self.entries[start + offset];
+                (
+                    entry.namespace.to_string(),
+                    entry.name.to_string(),
+                    entry.count,
+                )
+            })
+            .collect()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+#[derive(Default)]
+struct Slot {
+    index: Option<Arc<TagIndex>>,
+    building: bool,
+}
+
+static SLOTS: Registry<String, Slot> = Registry::new();
+
+/// Answers a prefix lookup from the index of `index_db`, or returns None
+/// when the index is missing, stale or was built for another `min_count`,
+/// after starting a rebuild. `min_count` 0 disables the index.
+pub(crate) fn lookup(
+    index_db: &str,
+    min_count: i64,
+    prefix: &str,
+    limit: usize,
+) -> Option<Vec<(String, String, i64)>> {
+    if min_count <= 0 {
+        return None;
+    }
+    let epoch = epochs::tag_epoch(index_db);
+    let index = {
+        let mut slots = SLOTS.lock();
+        let slot = slots.entry(index_db.to_string()).or_default();
+        match &slot.index {
+            Some(index) if index.epoch == epoch && index.min_count == min_count => {
+                Some(Arc::clone(index))
+            }
+            _ => {
+                if !slot.building {
+                    slot.building = true;
+                    tokio::spawn(rebuild(index_db.to_string(), min_count));
+                }
+                None
+            }
+        }
+    };
+    index.map(|index| index.lookup(prefix, limit))
+}
+
+async fn rebuild(index_db: String, min_count: i64) {
+    let epoch = epochs::tag_epoch(&index_db);
+    let started = std::time::Instant::now();
+    let result = async {
+        let mut conn = open_index_db_read_no_user_data(&index_db).await?;
+        get_tags_with_min_count(&mut conn, min_count).await
+    }
+    .await;
+    let mut slots = SLOTS.lock();
+    let slot = slots.entry(index_db.clone()).or_default();
+    slot.building = false;
+    match result {
+        Ok(rows) => {
+            let index = TagIndex::new(epoch, min_count, rows);
+            tracing::info!(
+                index_db = %index_db,
+                tags = index.len(),
+                min_count,
+                elapsed_ms = started.elapsed().as_millis() as u64,
+                "built tag autocomplete index"
+            );
+            slot.index = Some(Arc::new(index));
+        }
+        Err(err) => {
+            tracing::warn!(index_db = %index_db, error = ?err, "failed to build tag autocomplete index");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::migrations::migrate_databases_on_disk;
+    use crate::db::open_index_db_write_no_user_data;
+    use crate::test_utils::test_data_dir;
+
+    fn row(namespace: &str, name: &str, count: i64) -> (String, String, i64) {
+        (namespace.to_string(), name.to_string(), count)
+    }
+
+    // Prefix lookups ignore case, stop at the first non-matching name and
+    // return the most used matches first, cut to the limit.
+    #[test]
+    fn lookup_returns_most_used_prefix_matches() {
+        let index = TagIndex::new(
+            0,
+            1,
+            vec![
+                row("general", "cat", 5),
+                row("general", "Caterpillar", 9),
+                row("general", "dog", 50),
+                row("character", "catgirl", 2),
+                row("general", "bat", 40),
+            ],
+        );
+        assert_eq!(
+            index.lookup("CAT", 10),
+            vec![
+                row("general", "Caterpillar", 9),
+                row("general", "cat", 5),
+                row("character", "catgirl", 2)
+            ]
+        );
+        assert_eq!(
+            index.lookup("cat", 2),
+            vec![row("general", "Caterpillar", 9), row("general", "cat", 5)]
+        );
+        assert!(index.lookup("cow", 10).is_empty());
+        assert!(index.lookup("cat", 0).is_empty());
+    }
+
+    // The first lookup and every one after the tag epoch moves fall back to
+    // the database until the background build for the current epoch lands.
+    #[tokio::test]
+    async fn lookup_serves_only_an_index_of_the_current_epoch() {
+        let _test_env = test_data_dir();
+        let index_db = "tag_index_epochs".to_string();
+        migrate_databases_on_disk(Some(&index_db), None)
+            .await
+            .unwrap();
+        let mut conn = open_index_db_write_no_user_data(&index_db).await.unwrap();
+        sqlx::query(
+            r#"
+INSERT INTO items (id, sha256, md5, type, time_added)
+VALUES (1, 'sha1', 'md51', 'image/png', '2024-01-01T00:00:00');
+INSERT INTO setters (id, name) VALUES (1, 'tagger');
+INSERT INTO item_data (id, item_id, setter_id, data_type, idx, is_origin, is_placeholder)
+VALUES (1, 1, 1, 'tags', 0, 1, 0);
+INSERT INTO tags (id, namespace, name) VALUES (1, 'general', 'cat');
+INSERT INTO tags_items (item_data_id, tag_id, confidence) VALUES (1, 1, 0.9);
+            "#,
+        )
+        .execute(&mut conn)
+        .await
+        .unwrap();
+
+        assert_eq!(lookup(&index_db, 0, "ca", 10), None);
+        assert_eq!(lookup(&index_db, 1, "ca", 10), None);
+        let served = wait_for_index(&index_db, 1).await;
+        assert_eq!(served, vec![row("general", "cat", 1)]);
+
+        sqlx::query("INSERT INTO tags (id, namespace, name) VALUES (2, 'general', 'car')")
+            .execute(&mut conn)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO tags_items (item_data_id, tag_id, confidence) VALUES (1, 2, 0.8)")
+            .execute(&mut conn)
+            .await
+            .unwrap();
+        epochs::bump_tag_epoch(&index_db);
+        assert_eq!(lookup(&index_db, 1, "ca", 10), None);
+        let mut served = wait_for_index(&index_db, 1).await;
+        served.sort();
+        assert_eq!(
+            served,
+            vec![row("general", "car", 1), row("general", "cat", 1)]
+        );
+        // Another minimum needs its own build.
+        assert_eq!(lookup(&index_db, 2, "ca", 10), None);
+    }
+
+    async fn wait_for_index(index_db: &str, min_count: i64) -> Vec<(String, String, i64)> {
+        for _ in 0..200 {
+            if let Some(tags) = lookup(index_db, min_count, "ca", 10) {
+                return tags;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        panic!("tag index was never built");
+    }
+}
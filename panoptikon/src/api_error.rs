@@ -4,18 +4,83 @@ use axum::{
     response::{IntoResponse, Response},
 };
 use serde::Serialize;
+use serde_json::Value;
+
+use crate::pql::{PqlError, PqlErrorKind};
+
+/// Machine-readable error category, stable across releases (the `detail`
+/// text is not). Codes refine the HTTP status rather than replace it:
+///
+/// | code | status | meaning |
+/// |---|---|---|
+/// | `bad_request` | 400, 422 | Malformed or invalid request parameters |
+/// | `invalid_pql` | 400 | A PQL query (or saved job filter) failed to compile |
+/// | `unauthorized` | 401 | Missing or invalid credentials |
+/// | `forbidden` | 403 | The matched policy does not allow this action |
+/// | `not_found` | 404 | The requested item, file, job or record does not exist |
+/// | `db_not_found` | 404 | The selected index or user-data database does not exist |
+/// | `conflict` | 409 | The resource changed or already exists |
+/// | `job_conflict` | 409 | The job queue cannot accept the job in its current state |
+/// | `gone` | 410 | The resource existed but has expired |
+/// | `rate_limited` | 429 | Too many pending operations; retry later |
+/// | `upstream_unavailable` | 502 | An inference server or other upstream failed |
+/// | `media_timeout` | 500 | ffmpeg/ffprobe ran past its configured timeout on a file |
+/// | `media_undecodable` | 422 | No frame of a video could be decoded, even by the fallbacks |
+/// | `internal` | 500 | Unexpected server-side failure |
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    BadRequest,
+    InvalidPql,
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    DbNotFound,
+    Conflict,
+    JobConflict,
+    Gone,
+    RateLimited,
+    UpstreamUnavailable,
+    MediaTimeout,
+    MediaUndecodable,
+    Internal,
+}
+
+impl ErrorCode {
+    /// The generic code for a status, used when a construction site does not
+    /// pick a more specific one.
+    fn for_status(status: StatusCode) -> Self {
+        match status {
+            StatusCode::UNAUTHORIZED => Self::Unauthorized,
+            StatusCode::FORBIDDEN => Self::Forbidden,
+            StatusCode::NOT_FOUND => Self::NotFound,
+            StatusCode::CONFLICT => Self::Conflict,
+            StatusCode::GONE => Self::Gone,
+            StatusCode::TOO_MANY_REQUESTS => Self::RateLimited,
+            StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT => Self::UpstreamUnavailable,
+            status if status.is_client_error() => Self::BadRequest,
+            _ => Self::Internal,
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct ApiError {
     status: StatusCode,
+    code: ErrorCode,
     detail: String,
+    details: Option<Value>,
 }
 
 impl ApiError {
     pub fn new(status: StatusCode, detail: impl Into<String>) -> Self {
         Self {
             status,
+            code: ErrorCode::for_status(status),
             detail: detail.into(),
+            details: None,
         }
     }
 
@@ -30,21 +95,147 @@ impl ApiError {
     pub fn internal(detail: impl Into<String>) -> Self {
         Self::new(StatusCode::INTERNAL_SERVER_ERROR, detail)
     }
+
+    pub fn invalid_pql(detail: impl Into<String>) -> Self {
+        Self::bad_request(detail).with_code(ErrorCode::InvalidPql)
+    }
+
+    pub fn db_not_found(detail: impl Into<String>) -> Self {
+        Self::not_found(detail).with_code(ErrorCode::DbNotFound)
+    }
+
+    pub fn job_conflict(detail: impl Into<String>) -> Self {
+        Self::new(StatusCode::CONFLICT, detail).with_code(ErrorCode::JobConflict)
+    }
+
+    pub fn upstream_unavailable(detail: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_GATEWAY, detail)
+    }
+
+    pub fn media_timeout(detail: impl Into<String>) -> Self {
+        Self::internal(detail).with_code(ErrorCode::MediaTimeout)
+    }
+
+    pub fn media_undecodable(detail: impl Into<String>) -> Self {
+        Self::new(StatusCode::UNPROCESSABLE_ENTITY, detail).with_code(ErrorCode::MediaUndecodable)
+    }
+
+    pub fn with_code(mut self, code: ErrorCode) -> Self {
+        self.code = code;
+        self
+    }
+
+    /// Attaches a structured `details` object, e.g. the config path of an
+    /// invalid filter, for clients that point at the offending input.
+    pub fn with_details(mut self, details: Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    pub fn detail(&self) -> &str {
+        &self.detail
+    }
+
+    #[cfg(test)]
+    pub(crate) fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    pub(crate) fn code(&self) -> ErrorCode {
+        self.code
+    }
+}
+
+impl From<PqlError> for ApiError {
+    fn from(err: PqlError) -> Self {
+        match err.kind {
+            PqlErrorKind::Invalid => Self::invalid_pql(err.message),
+            PqlErrorKind::Upstream => Self::upstream_unavailable(err.message),
+            PqlErrorKind::Disabled => Self::new(StatusCode::FORBIDDEN, err.message),
+            PqlErrorKind::Internal => Self::internal(err.message),
+        }
+    }
 }
 
 /// The single error body shape every gateway error path serializes.
 /// (Unlike FastAPI there is no structured 422 validation body: axum
-/// extractor rejections and all `ApiError`s use this `detail` string.)
+/// extractor rejections and all `ApiError`s use this shape.)
 #[derive(Serialize, utoipa::ToSchema)]
 pub(crate) struct ErrorBody {
+    /// Human-readable message; wording may change between releases.
     detail: String,
+    code: ErrorCode,
+    /// Structured context for some codes, e.g. the failing filter paths of
+    /// an `invalid_pql` config save.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    details: Option<Value>,
 }
 
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
         let body = Json(ErrorBody {
             detail: self.detail,
+            code: self.code,
+            details: self.details,
         });
         (self.status, body).into_response()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn body_json(error: ApiError) -> (StatusCode, Value) {
+        let response = error.into_response();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("body");
+        (status, serde_json::from_slice(&bytes).expect("json body"))
+    }
+
+    #[tokio::test]
+    async fn error_body_carries_code_and_optional_details() {
+        let (status, body) = body_json(ApiError::not_found("Item not found")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(
+            body,
+            serde_json::json!({"detail": "Item not found", "code": "not_found"})
+        );
+
+        let error = ApiError::invalid_pql("bad filter")
+            .with_details(serde_json::json!({"path": "and_[1]"}));
+        let (status, body) = body_json(error).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "invalid_pql");
+        assert_eq!(body["details"]["path"], "and_[1]");
+    }
+
+    #[test]
+    fn generic_codes_follow_the_status() {
+        let code = |status| ApiError::new(status, "").code();
+        assert_eq!(
+            code(StatusCode::UNPROCESSABLE_ENTITY),
+            ErrorCode::BadRequest
+        );
+        assert_eq!(code(StatusCode::FORBIDDEN), ErrorCode::Forbidden);
+        assert_eq!(code(StatusCode::CONFLICT), ErrorCode::Conflict);
+        assert_eq!(code(StatusCode::TOO_MANY_REQUESTS), ErrorCode::RateLimited);
+        assert_eq!(
+            code(StatusCode::BAD_GATEWAY),
+            ErrorCode::UpstreamUnavailable
+        );
+        assert_eq!(code(StatusCode::INTERNAL_SERVER_ERROR), ErrorCode::Internal);
+    }
+
+    #[test]
+    fn pql_errors_map_to_invalid_pql() {
+        let error = ApiError::from(PqlError::invalid("Unknown field"));
+        assert_eq!(error.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(error.code(), ErrorCode::InvalidPql);
+        let error = ApiError::from(PqlError::upstream("inference embed error"));
+        assert_eq!(error.status(), StatusCode::BAD_GATEWAY);
+        assert_eq!(error.code(), ErrorCode::UpstreamUnavailable);
+    }
+}
@@ -0,0 +1,340 @@
+//! Offline administration subcommands: `migrate`, `scan`, `pql` and
+//! `verify` work on the databases directly, without starting the HTTP
+//! server; `doctor` runs the setup diagnostics. Each runs the same service
+//! the server uses for that job, logs to stderr, prints its results on
+//! stdout and fails (exit status 1) when the work does, so the commands
+//! can be scripted.
+
+use std::io::Write as _;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::{Context as _, Result, anyhow, bail};
+use serde_json::Value;
+
+use crate::api::search::{PqlCompiler, build_pql, run_pql_build};
+use crate::api_error::ApiError;
+use crate::config::Settings;
+use crate::db::file_scans::{FileScanRecord, get_all_file_scans};
+use crate::db::file_verification::{get_verification_results, get_verification_runs};
+use crate::db::index_writer::flush_all_writers;
+use crate::db::migrations::{migrate_all_databases_on_disk, migrate_databases_on_disk};
+use crate::db::{open_index_db_read, open_index_db_read_no_user_data, readonly_mode};
+use crate::diagnostics::{CheckStatus, run_checks};
+use crate::inferio_client::InferenceApiClient;
+use crate::jobs::file_verification::{
+    VerificationOptions, normalize_modified_since, run_verification_job,
+};
+use crate::jobs::files::FileScanService;
+
+/// How often `scan` prints the running counters of the open scan.
+const SCAN_PROGRESS_INTERVAL: Duration = Duration::from_secs(2);
+/// Failed files listed by `verify`; the rest are in the run's results.
+const MAX_LISTED_FAILURES: i64 = 100;
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct DbArgs {
+    /// Index database name. Default: the configured `index_db`.
+    #[arg(long, value_name = "NAME")]
+    index_db: Option<String>,
+    /// User data database name. Default: the configured `user_data_db`.
+    #[arg(long, value_name = "NAME")]
+    user_data_db: Option<String>,
+}
+
+impl DbArgs {
+    fn names(&self) -> (String, String) {
+        let runtime = crate::config::runtime();
+        (
+            self.index_db
+                .clone()
+                .unwrap_or_else(|| runtime.index_db.clone()),
+            self.user_data_db
+                .clone()
+                .unwrap_or_else(|| runtime.user_data_db.clone()),
+        )
+    }
+}
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct ScanArgs {
+    /// Folder to scan: an included folder or a folder inside one. Default:
+    /// every included folder, like a rescan job.
+    folder: Option<String>,
+    #[command(flatten)]
+    db: DbArgs,
+}
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct PqlArgs {
+    /// JSON file holding the PQL query (the `/api/search/pql` body); `-`
+    /// reads it from stdin.
+    file: PathBuf,
+    /// Run the query and print its result rows as NDJSON instead of the
+    /// compiled SQL.
+    #[arg(long)]
+    execute: bool,
+    #[command(flatten)]
+    db: DbArgs,
+}
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct VerifyArgs {
+    /// Only check files under this path.
+    #[arg(long, value_name = "PATH")]
+    path_prefix: Option<String>,
+    /// Only check files modified on or after this date
+    /// (`YYYY-MM-DD` or `YYYY-MM-DDTHH:MM:SS`).
+    #[arg(long, value_name = "DATE")]
+    modified_since: Option<String>,
+    /// Stop after checking this many files.
+    #[arg(long, value_name = "N")]
+    max_files: Option<i64>,
+    /// Read throughput cap; 0 = unthrottled. Default: the DB's
+    /// `verify_max_mb_per_sec`.
+    #[arg(long, value_name = "MB")]
+    max_mb_per_sec: Option<f64>,
+    #[command(flatten)]
+    db: DbArgs,
+}
+
+fn api_error(err: ApiError) -> anyhow::Error {
+    anyhow!("{}", err.detail())
+}
+
+fn ensure_writable(command: &str) -> Result<()> {
+    if readonly_mode() {
+        bail!("`{command}` writes to the databases and is unavailable in readonly mode");
+    }
+    Ok(())
+}
+
+/// Without DB names, migrates every database under the data folder and
+/// reports each; with names, migrates (creating if missing) that index and
+/// user data DB pair.
+pub(crate) async fn migrate(db: DbArgs) -> Result<()> {
+    ensure_writable("migrate")?;
+    if db.index_db.is_none() && db.user_data_db.is_none() {
+        let results = migrate_all_databases_on_disk().await?;
+        let mut failed = 0;
+        for result in &results {
+            match &result.error {
+                None => println!("ok\t{}", result.path.display()),
+                Some(err) => {
+                    failed += 1;
+                    println!("failed\t{}\t{err:#}", result.path.display());
+                }
+            }
+        }
+        if failed > 0 {
+            bail!(
+                "{failed} of {} databases could not be migrated",
+                results.len()
+            );
+        }
+        return Ok(());
+    }
+    let (index_db, user_data_db) = db.names();
+    let paths = migrate_databases_on_disk(Some(&index_db), Some(&user_data_db)).await?;
+    for path in [
+        &paths.index_db_file,
+        &paths.storage_db_file,
+        &paths.user_db_file,
+    ] {
+        println!("ok\t{}", path.display());
+    }
+    Ok(())
+}
+
+/// Scans one folder (or all included folders) of the DB, printing the open
+/// scan's counters to stderr while it runs and a summary per folder.
+pub(crate) async fn scan(args: ScanArgs) -> Result<()> {
+    ensure_writable("scan")?;
+    let (index_db, user_data_db) = args.db.names();
+    migrate_databases_on_disk(Some(&index_db), Some(&user_data_db)).await?;
+
+    let progress = tokio::spawn(report_scan_progress(index_db.clone()));
+    let service = FileScanService::from_env(index_db.clone(), user_data_db);
+    let result = match &args.folder {
+        Some(folder) => service.scan_folder(folder).await,
+        None => service.rescan_folders().await,
+    };
+    progress.abort();
+    flush_all_writers().await;
+    let scan_ids = result.map_err(api_error)?.scan_ids;
+
+    let mut conn = open_index_db_read_no_user_data(&index_db)
+        .await
+        .map_err(api_error)?;
+    let page_size = i64::try_from(scan_ids.len()).unwrap_or(i64::MAX).max(1);
+    let scans = get_all_file_scans(&mut conn, 1, Some(page_size))
+        .await
+        .map_err(api_error)?;
+    let mut errors = 0;
+    for scan in scans
+        .iter()
+        .rev()
+        .filter(|scan| scan_ids.contains(&scan.id))
+    {
+        errors += scan.errors;
+        println!("{}", scan_summary(scan));
+    }
+    if errors > 0 {
+        bail!("{errors} files could not be scanned");
+    }
+    Ok(())
+}
+
+fn scan_summary(scan: &FileScanRecord) -> String {
+    format!(
+        "{}: {} new, {} modified, {} unchanged, {} marked unavailable, {} errors",
+        scan.path,
+        scan.new_files,
+        scan.modified_files,
+        scan.unchanged_files,
+        scan.marked_unavailable,
+        scan.errors
+    )
+}
+
+/// Prints the latest scan's counters while it is open. Runs until aborted.
+async fn report_scan_progress(index_db: String) {
+    let mut interval = tokio::time::interval(SCAN_PROGRESS_INTERVAL);
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        let Ok(mut conn) = open_index_db_read_no_user_data(&index_db).await else {
+            continue;
+        };
+        if let Ok(scans) = get_all_file_scans(&mut conn, 1, Some(1)).await
+            && let Some(scan) = scans.first().filter(|scan| scan.end_time.is_none())
+        {
+            eprintln!("scanning {}", scan_summary(scan));
+        }
+    }
+}
+
+/// Compiles a PQL query and prints the build (`/api/search/pql/build`
+/// output) as JSON, or with `--execute` runs it and prints one JSON object
+/// per result row.
+pub(crate) async fn pql(args: PqlArgs, settings: &Settings) -> Result<()> {
+    let source = if args.file.as_os_str() == "-" {
+        std::io::read_to_string(std::io::stdin()).context("failed to read PQL from stdin")?
+    } else {
+        std::fs::read_to_string(&args.file)
+            .with_context(|| format!("failed to read {}", args.file.display()))?
+    };
+    let payload: Value = serde_json::from_str(&source).context("PQL file is not valid JSON")?;
+    let (index_db, user_data_db) = args.db.names();
+
+    // Only used to embed vector-search text; queries without it never call
+    // the inference service.
+    let inference_client = InferenceApiClient::from_settings_with_metadata_cache(settings, true)?;
+    let compiler = PqlCompiler {
+        disabled_filters: &settings.search.disabled_filters,
+        inference_client: &inference_client,
+        embedding_cache_bytes: settings.search.embedding_cache_bytes(),
+    };
+    let build = build_pql(&compiler, &payload, &index_db)
+        .await
+        .map_err(api_error)?;
+
+    let mut stdout = std::io::stdout().lock();
+    if !args.execute {
+        serde_json::to_writer_pretty(&mut stdout, &build)?;
+        writeln!(stdout)?;
+        return Ok(());
+    }
+    let mut conn = open_index_db_read(&index_db, &user_data_db)
+        .await
+        .map_err(api_error)?;
+    for row in run_pql_build(&mut conn, &build).await.map_err(api_error)? {
+        serde_json::to_writer(&mut stdout, &row)?;
+        writeln!(stdout)?;
+    }
+    Ok(())
+}
+
+/// Runs a file verification pass and prints its totals and failed files.
+/// Fails when any file mismatched its stored hash or could not be read.
+pub(crate) async fn verify(args: VerifyArgs) -> Result<()> {
+    ensure_writable("verify")?;
+    let (index_db, user_data_db) = args.db.names();
+    migrate_databases_on_disk(Some(&index_db), Some(&user_data_db)).await?;
+    let modified_since = args
+        .modified_since
+        .as_deref()
+        .map(|value| {
+            normalize_modified_since(value)
+                .context("--modified-since must be YYYY-MM-DD or YYYY-MM-DDTHH:MM:SS")
+        })
+        .transpose()?;
+    let options = VerificationOptions {
+        path_prefix: args.path_prefix.filter(|prefix| !prefix.is_empty()),
+        modified_since,
+        max_files: args.max_files,
+        max_mb_per_sec: args.max_mb_per_sec,
+    };
+    let run_id = run_verification_job(&index_db, options).await;
+    flush_all_writers().await;
+    let run_id = run_id.map_err(api_error)?;
+
+    let mut conn = open_index_db_read_no_user_data(&index_db)
+        .await
+        .map_err(api_error)?;
+    let run = get_verification_runs(&mut conn, 1)
+        .await
+        .map_err(api_error)?
+        .into_iter()
+        .find(|run| run.id == run_id)
+        .context("verification run not found after it finished")?;
+    println!(
+        "checked {}, verified {}, mismatched {}, unreadable {}, missing {}, changed {}",
+        run.checked, run.verified, run.mismatched, run.unreadable, run.missing, run.changed
+    );
+    let failed = run.mismatched + run.unreadable;
+    if failed == 0 {
+        return Ok(());
+    }
+    let results = get_verification_results(&mut conn, Some(run_id), 1, MAX_LISTED_FAILURES)
+        .await
+        .map_err(api_error)?;
+    for result in results {
+        match result.error {
+            Some(error) => println!("{}\t{}\t{error}", result.status, result.path),
+            None => println!("{}\t{}", result.status, result.path),
+        }
+    }
+    bail!("{failed} files failed verification")
+}
+
+/// Runs the setup diagnostics and prints `status, name, detail` per check,
+/// plus the remediation for problems. Fails when any check fails.
+pub(crate) async fn doctor(settings: &Settings) -> Result<()> {
+    let report = run_checks(settings, false).await;
+    for check in &report.checks {
+        match &check.remediation {
+            Some(remediation) => println!(
+                "{}\t{}\t{}\t{remediation}",
+                check.status.as_str(),
+                check.name,
+                check.detail
+            ),
+            None => println!(
+                "{}\t{}\t{}",
+                check.status.as_str(),
+                check.name,
+                check.detail
+            ),
+        }
+    }
+    let failed = report
+        .checks
+        .iter()
+        .filter(|check| check.status == CheckStatus::Fail)
+        .count();
+    if failed > 0 {
+        bail!("{failed} of {} checks failed", report.checks.len());
+    }
+    Ok(())
+}
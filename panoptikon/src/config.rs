@@ -34,6 +34,11 @@ pub struct Settings {
     /// env default, deliberately NOT derived from `data_folder`.
     #[serde(default = "default_temp_dir")]
     pub temp_dir: PathBuf,
+    /// Where generated thumbnails and video frames are written. Reads look
+    /// in both places, so switching only affects new writes until a
+    /// `visuals_storage_migration` job moves the rest. Default: `sqlite`.
+    #[serde(default)]
+    pub thumbnail_storage: ThumbnailStorage,
     #[serde(default)]
     pub logging: LoggingConfig,
     #[serde(default)]
@@ -43,6 +48,10 @@ pub struct Settings {
     #[serde(default)]
     pub jobs: JobsConfig,
     #[serde(default)]
+    pub notifications: NotificationsConfig,
+    #[serde(default)]
+    pub reports: ReportsConfig,
+    #[serde(default)]
     pub rulesets: BTreeMap<String, RuleSetConfig>,
     #[serde(default)]
     pub policies: Vec<PolicyConfig>,
@@ -62,6 +71,19 @@ fn default_temp_dir() -> PathBuf {
     PathBuf::from("data/tmp")
 }
 
+/// Storage backend for thumbnails and video frames.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThumbnailStorage {
+    /// Blobs in the index DB's `storage.db`.
+    #[default]
+    Sqlite,
+    /// JPEG files under the index DB's folder
+    /// (`thumbnails/ab/cd/<sha256>_<idx>.jpg`, likewise `frames/`), keeping
+    /// `storage.db` small for very large libraries.
+    Filesystem,
+}
+
 /// `[logging]`: console + file logging.
 #[derive(Debug, Clone, Deserialize)]
 pub struct LoggingConfig {
@@ -75,6 +97,19 @@ pub struct LoggingConfig {
     /// tracing debug tool and supports per-module directives.
     #[serde(default = "default_log_level")]
     pub level: String,
+    /// Line format for both console and file output.
+    #[serde(default)]
+    pub format: LogFormat,
+}
+
+/// `[logging].format`: human-readable lines, or one JSON object per event
+/// carrying the enclosing spans' fields (`request_id`, `job_id`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
 }
 
 fn default_log_level() -> String {
@@ -86,6 +121,7 @@ impl Default for LoggingConfig {
         Self {
             file: None,
             level: default_log_level(),
+            format: LogFormat::default(),
         }
     }
 }
@@ -115,6 +151,10 @@ pub struct OpenConfig {
     /// Arguments passed directly to `folder_program`.
     #[serde(default)]
     pub folder_args: Vec<String>,
+    /// Allows `POST /api/items/item/open` for requests from this machine.
+    /// Default: false.
+    #[serde(default)]
+    pub local_actions_enabled: bool,
 }
 
 /// `[inference_local]`: the in-process inferio orchestrator (design doc §3).
@@ -373,6 +413,19 @@ pub struct JobsConfig {
     /// Explicit ffprobe executable; same default chain as `ffmpeg`.
     #[serde(default)]
     pub ffprobe: Option<PathBuf>,
+    /// Seconds one ffmpeg run (video frames, audio decoding) may take
+    /// before it is killed, so a corrupt file cannot hang a worker.
+    /// 0 = no limit. Default: 1800.
+    #[serde(default = "default_ffmpeg_timeout_secs")]
+    pub ffmpeg_timeout_secs: u64,
+    /// Same for ffprobe, which only reads container metadata. Default: 60.
+    #[serde(default = "default_ffprobe_timeout_secs")]
+    pub ffprobe_timeout_secs: u64,
+    /// Cap in MiB on the stdout of one ffmpeg/ffprobe run; only decoded
+    /// audio gets large (about 115 MiB per hour). 0 = unlimited.
+    /// Default: 1024.
+    #[serde(default = "default_media_max_output_mb")]
+    pub media_max_output_mb: u64,
     /// Explicit pdfium dynamic library path (file or containing directory)
     /// for PDF thumbnails/extraction. Default: the executable's directory,
     /// the working directory, then the system library. Empty string = unset
@@ -391,6 +444,22 @@ pub struct JobsConfig {
     /// used.
     #[serde(default)]
     pub html_renderer_args: Vec<String>,
+    /// Seconds one PDF/HTML render may take. A headless browser past it is
+    /// killed with its process tree; a PDF stops between pages. Extraction
+    /// counts such items as timeouts. 0 = no limit. Default: 30.
+    #[serde(default = "default_render_timeout_secs")]
+    pub render_timeout_secs: u64,
+    /// Pages of one PDF rendered for data extraction; later pages are
+    /// ignored. 0 = all pages. Default: 100.
+    #[serde(default = "default_render_max_pages")]
+    pub render_max_pages: usize,
+    /// Unix only: cap in MiB on the data segment (`RLIMIT_DATA`) of each
+    /// headless browser process, so an adversarial page fails to allocate
+    /// instead of exhausting host memory. Chromium processes commit several
+    /// hundred MiB before rendering anything; keep this generous. 0 = no
+    /// limit. Default: 0.
+    #[serde(default)]
+    pub render_memory_limit_mb: u64,
     /// Explicit TTF font file for thumbnail text labels. Default:
     /// well-known system fonts (Segoe UI/Arial/DejaVu). Empty string =
     /// unset (templated as `${PANOPTIKON_FONT:-}`).
@@ -410,6 +479,26 @@ fn default_image_decode_memory_limit_mb() -> u64 {
     8192
 }
 
+fn default_ffmpeg_timeout_secs() -> u64 {
+    1800
+}
+
+fn default_ffprobe_timeout_secs() -> u64 {
+    60
+}
+
+fn default_media_max_output_mb() -> u64 {
+    1024
+}
+
+fn default_render_timeout_secs() -> u64 {
+    30
+}
+
+fn default_render_max_pages() -> usize {
+    100
+}
+
 impl Default for JobsConfig {
     fn default() -> Self {
         Self {
@@ -419,14 +508,112 @@ impl Default for JobsConfig {
             image_decode_memory_limit_mb: default_image_decode_memory_limit_mb(),
             ffmpeg: None,
             ffprobe: None,
+            ffmpeg_timeout_secs: default_ffmpeg_timeout_secs(),
+            ffprobe_timeout_secs: default_ffprobe_timeout_secs(),
+            media_max_output_mb: default_media_max_output_mb(),
             pdfium: None,
             html_renderer: None,
             html_renderer_args: Vec::new(),
+            render_timeout_secs: default_render_timeout_secs(),
+            render_max_pages: default_render_max_pages(),
+            render_memory_limit_mb: 0,
             thumbnail_font: None,
         }
     }
 }
 
+/// `[notifications]`: webhooks POSTed a small JSON event when jobs and
+/// folder scans end (see `jobs::notifications`). Delivery is best-effort and
+/// never holds up the job.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct NotificationsConfig {
+    #[serde(default)]
+    pub webhooks: Vec<WebhookConfig>,
+    /// `errors_above_threshold` fires for a folder scan with more errors than
+    /// this. Default: 0 (any error).
+    #[serde(default)]
+    pub error_threshold: i64,
+}
+
+/// One `[[notifications.webhooks]]` entry.
+#[derive(Debug, Clone, Deserialize)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// Events sent to this webhook. Empty (default): all of them.
+    #[serde(default)]
+    pub events: Vec<NotificationEvent>,
+}
+
+impl WebhookConfig {
+    pub fn wants(&self, event: NotificationEvent) -> bool {
+        self.events.is_empty() || self.events.contains(&event)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, serde::Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationEvent {
+    /// A queued job completed successfully.
+    JobFinished,
+    /// A queued job returned an error or panicked (cancellations are not
+    /// reported).
+    JobFailed,
+    /// A folder scan finished (one event per included folder).
+    ScanFinished,
+    /// A folder scan finished with more errors than `error_threshold`.
+    ErrorsAboveThreshold,
+}
+
+/// `[reports]`: where `pql_report` jobs deliver their results (see
+/// `jobs::pql_report`). Jobs name a destination rather than carrying a URL
+/// or path, so webhook credentials stay in this file (env-templatable like
+/// any string) and API callers can't write anywhere else.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReportsConfig {
+    #[serde(default)]
+    pub destinations: Vec<ReportDestinationConfig>,
+    /// Upper bound on the results one report collects. Default: 10000.
+    #[serde(default = "default_report_max_results")]
+    pub max_results: i64,
+}
+
+impl Default for ReportsConfig {
+    fn default() -> Self {
+        Self {
+            destinations: Vec::new(),
+            max_results: default_report_max_results(),
+        }
+    }
+}
+
+impl ReportsConfig {
+    pub fn destination(&self, name: &str) -> Option<&ReportDestinationConfig> {
+        self.destinations
+            .iter()
+            .find(|destination| destination.name == name)
+    }
+}
+
+fn default_report_max_results() -> i64 {
+    10_000
+}
+
+/// One `[[reports.destinations]]` entry: a webhook `url` or a file `path`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReportDestinationConfig {
+    pub name: String,
+    /// POSTed the report as JSON.
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Extra request headers for `url`, e.g.
+    /// `Authorization = "Bearer ${REPORT_TOKEN}"`.
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// Overwritten with the report as JSON.
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+}
+
 /// Process-global copy of the config values needed deep inside code that has
 /// no `Settings` handle (DB path resolution, job helpers, the open API).
 /// Installed exactly once by `main` right after config load; the defaults
@@ -438,15 +625,26 @@ pub struct RuntimeConfig {
     pub user_data_db: String,
     pub readonly: bool,
     pub temp_dir: PathBuf,
+    pub thumbnail_storage: ThumbnailStorage,
     pub atomic_extraction_jobs: bool,
     pub image_decode_memory_limit_mb: u64,
     pub open: OpenConfig,
     pub ffmpeg: Option<PathBuf>,
     pub ffprobe: Option<PathBuf>,
+    pub ffmpeg_timeout_secs: u64,
+    pub ffprobe_timeout_secs: u64,
+    pub media_max_output_mb: u64,
     pub pdfium: Option<PathBuf>,
     pub html_renderer: Option<PathBuf>,
     pub html_renderer_args: Vec<String>,
+    pub render_timeout_secs: u64,
+    pub render_max_pages: usize,
+    pub render_memory_limit_mb: u64,
     pub thumbnail_font: Option<PathBuf>,
+    pub notifications: NotificationsConfig,
+    pub reports: ReportsConfig,
+    /// `[search] disabled_filters`, for PQL compiled by jobs.
+    pub disabled_filters: Vec<String>,
     /// The venv interpreter `media_tools` probes for static-ffmpeg —
     /// the same one that runs inference workers.
     pub venv_python: PathBuf,
@@ -460,15 +658,25 @@ impl Default for RuntimeConfig {
             user_data_db: default_db_name(),
             readonly: false,
             temp_dir: default_temp_dir(),
+            thumbnail_storage: ThumbnailStorage::default(),
             atomic_extraction_jobs: false,
             image_decode_memory_limit_mb: default_image_decode_memory_limit_mb(),
             open: OpenConfig::default(),
             ffmpeg: None,
             ffprobe: None,
+            ffmpeg_timeout_secs: default_ffmpeg_timeout_secs(),
+            ffprobe_timeout_secs: default_ffprobe_timeout_secs(),
+            media_max_output_mb: default_media_max_output_mb(),
             pdfium: None,
             html_renderer: None,
             html_renderer_args: Vec::new(),
+            render_timeout_secs: default_render_timeout_secs(),
+            render_max_pages: default_render_max_pages(),
+            render_memory_limit_mb: 0,
             thumbnail_font: None,
+            notifications: NotificationsConfig::default(),
+            reports: ReportsConfig::default(),
+            disabled_filters: Vec::new(),
             venv_python: crate::resources::default_worker_python(crate::resources::py_source_mode()),
         }
     }
@@ -526,15 +734,25 @@ impl Settings {
             user_data_db: self.user_data_db.clone(),
             readonly: self.readonly,
             temp_dir: self.temp_dir.clone(),
+            thumbnail_storage: self.thumbnail_storage,
             atomic_extraction_jobs: self.jobs.atomic_extraction_jobs,
             image_decode_memory_limit_mb: self.jobs.image_decode_memory_limit_mb,
             open: self.open.clone(),
             ffmpeg: self.jobs.ffmpeg.clone(),
             ffprobe: self.jobs.ffprobe.clone(),
+            ffmpeg_timeout_secs: self.jobs.ffmpeg_timeout_secs,
+            ffprobe_timeout_secs: self.jobs.ffprobe_timeout_secs,
+            media_max_output_mb: self.jobs.media_max_output_mb,
             pdfium: self.jobs.pdfium.clone(),
             html_renderer: self.jobs.html_renderer.clone(),
             html_renderer_args: self.jobs.html_renderer_args.clone(),
+            render_timeout_secs: self.jobs.render_timeout_secs,
+            render_max_pages: self.jobs.render_max_pages,
+            render_memory_limit_mb: self.jobs.render_memory_limit_mb,
             thumbnail_font: self.jobs.thumbnail_font.clone(),
+            notifications: self.notifications.clone(),
+            reports: self.reports.clone(),
+            disabled_filters: self.search.disabled_filters.clone(),
             venv_python: self.inference_local.resolved_python(),
         }
     }
@@ -572,6 +790,32 @@ pub struct ServerConfig {
     /// GET of the public release manifest). Default: true.
     #[serde(default = "default_true")]
     pub check_for_updates: bool,
+    /// What to do when another gateway already holds an index DB's instance
+    /// lock at startup (see `db/instance_lock.rs`): `refuse` to start, or
+    /// start `readonly`. Default: `refuse`.
+    #[serde(default)]
+    pub db_lock_conflict: DbLockConflict,
+    /// Seconds without a heartbeat after which another instance's index DB
+    /// lock is considered abandoned and reclaimed. Default: 30.
+    #[serde(default = "default_db_lock_stale_secs")]
+    pub db_lock_stale_secs: u64,
+    /// Run the setup diagnostics (`GET /api/diagnostics`) once the server
+    /// is listening and log a warning per problem found. Default: false.
+    #[serde(default)]
+    pub startup_diagnostics: bool,
+}
+
+/// `[server].db_lock_conflict`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DbLockConflict {
+    #[default]
+    Refuse,
+    Readonly,
+}
+
+fn default_db_lock_stale_secs() -> u64 {
+    30
 }
 
 /// `[[server.endpoints]]`: an extra named listener. Unlike host matching,
@@ -599,6 +843,89 @@ pub struct UpstreamConfig {
     pub base_url: String,
     #[serde(default)]
     pub local: bool,
+    #[serde(default)]
+    pub timeouts: UpstreamTimeouts,
+    #[serde(default)]
+    pub websocket: UpstreamWebSocket,
+}
+
+/// `[upstreams.<name>.timeouts]`: proxy deadlines for one upstream. Every
+/// key is optional; an unset deadline means none (the proxy waits as long
+/// as the upstream does).
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct UpstreamTimeouts {
+    /// TCP connect deadline in seconds.
+    #[serde(default)]
+    pub connect_secs: Option<u64>,
+    /// Deadline in seconds for the upstream's response head. Streaming a
+    /// body that has already started is never cut off, and upgrade
+    /// (WebSocket) and `text/event-stream` requests are exempt.
+    #[serde(default)]
+    pub request_secs: Option<u64>,
+    /// Per-path overrides of `request_secs`: request-path prefix → seconds
+    /// (e.g. `"/api/inference/predict" = 600`). The longest matching prefix
+    /// wins.
+    #[serde(default)]
+    pub paths: BTreeMap<String, u64>,
+}
+
+impl UpstreamTimeouts {
+    /// The response-head deadline for a request path: the longest matching
+    /// `paths` prefix, else `request_secs`.
+    pub fn request_timeout(&self, path: &str) -> Option<std::time::Duration> {
+        self.paths
+            .iter()
+            .filter(|(prefix, _)| path.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, secs)| *secs)
+            .or(self.request_secs)
+            .map(std::time::Duration::from_secs)
+    }
+
+    fn validate(&self, label: &str) -> Result<()> {
+        if self.connect_secs == Some(0) {
+            anyhow::bail!("{label}.timeouts.connect_secs must be > 0 (omit it for no deadline)");
+        }
+        if self.request_secs == Some(0) {
+            anyhow::bail!("{label}.timeouts.request_secs must be > 0 (omit it for no deadline)");
+        }
+        for (prefix, secs) in &self.paths {
+            if !prefix.starts_with('/') {
+                anyhow::bail!("{label}.timeouts.paths key '{prefix}' must start with '/'");
+            }
+            if *secs == 0 {
+                anyhow::bail!("{label}.timeouts.paths '{prefix}' must be > 0");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `[upstreams.<name>.websocket]`: limits on the upgraded (WebSocket)
+/// connections proxied to one upstream. Every key is optional; unset means
+/// no limit.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct UpstreamWebSocket {
+    /// Upgraded connections open at once. Further upgrade requests get a
+    /// 503 without reaching the upstream.
+    #[serde(default)]
+    pub max_connections: Option<usize>,
+    /// Close an upgraded connection after this many seconds in which
+    /// neither side sent anything.
+    #[serde(default)]
+    pub idle_secs: Option<u64>,
+}
+
+impl UpstreamWebSocket {
+    fn validate(&self, label: &str) -> Result<()> {
+        if self.max_connections == Some(0) {
+            anyhow::bail!("{label}.websocket.max_connections must be > 0 (omit it for no limit)");
+        }
+        if self.idle_secs == Some(0) {
+            anyhow::bail!("{label}.websocket.idle_secs must be > 0 (omit it for no limit)");
+        }
+        Ok(())
+    }
 }
 
 /// `[upstreams.ui]`: where the Next.js frontend lives. With `local = true`
@@ -622,6 +949,10 @@ pub struct UiUpstreamConfig {
     /// When to run `next build`. Default: auto (build-staleness check).
     #[serde(default)]
     pub build: UiBuildPolicy,
+    #[serde(default)]
+    pub timeouts: UpstreamTimeouts,
+    #[serde(default)]
+    pub websocket: UpstreamWebSocket,
 }
 
 /// `[upstreams.ui].build`: `next build` policy for local UI mode.
@@ -696,12 +1027,26 @@ pub struct InferenceEndpointConfig {
     pub weight: f64,
     #[serde(default = "default_inference_use_for_jobs")]
     pub use_for_jobs: bool,
+    /// Proxy deadlines; only the first entry (the `/api/inference/*` proxy
+    /// target) is proxied, so only its timeouts take effect.
+    #[serde(default)]
+    pub timeouts: UpstreamTimeouts,
+    /// Upgrade limits; like `timeouts`, only the first entry's take effect.
+    #[serde(default)]
+    pub websocket: UpstreamWebSocket,
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct SearchConfig {
-    #[serde(default = "default_embedding_cache_size")]
-    pub embedding_cache_size: usize,
+    /// Byte budget for the search embedding cache, in megabytes. `0`
+    /// disables it.
+    #[serde(default = "default_embedding_cache_mb")]
+    pub embedding_cache_mb: usize,
+    /// Deprecated entry-count size of the embedding cache. When set it
+    /// overrides `embedding_cache_mb`, converted at
+    /// `ESTIMATED_EMBEDDING_BYTES` per entry.
+    #[serde(default)]
+    pub embedding_cache_size: Option<usize>,
     /// Byte budget for the search result cache, in megabytes. `0` disables
     /// it. Runtime-adjustable via `PUT /api/search/cache`; this value is
     /// what the cache starts with (and returns to) at process startup.
@@ -712,10 +1057,89 @@ pub struct SearchConfig {
     /// by any client on any listener). Not exposed in the Desktop app.
     #[serde(default = "default_search_cache_size_max_mb")]
     pub cache_size_max_mb: usize,
+    /// PQL searches taking at least this many milliseconds (build plus
+    /// execution) are recorded in the slow query log. `0` disables the log.
+    #[serde(default = "default_slow_query_ms")]
+    pub slow_query_ms: u64,
+    /// How many slow searches the log keeps before dropping the oldest.
+    #[serde(default = "default_slow_query_log_size")]
+    pub slow_query_log_size: usize,
+    /// Cap on the request JSON stored per slow search, in bytes.
+    #[serde(default = "default_slow_query_max_bytes")]
+    pub slow_query_max_bytes: usize,
+    #[serde(default)]
+    pub warmup: SearchWarmupConfig,
+    /// PQL filters searches may not use, by JSON key (`similar_to`) or
+    /// `QueryElement` variant name (`SimilarTo`); normalized to keys at load.
+    /// A query using one anywhere fails with 403. Internal queries
+    /// (extraction jobs, job filters) are not affected. Default: empty.
+    #[serde(default)]
+    pub disabled_filters: Vec<String>,
+    /// Time a `check_path` search may spend verifying result paths, in
+    /// milliseconds; rows not checked by then are returned unchecked.
+    #[serde(default = "default_check_path_budget_ms")]
+    pub check_path_budget_ms: u64,
+    /// Time a `group_similar` search may spend reading and comparing the
+    /// page's embeddings, in milliseconds; past it the page is returned
+    /// without stacks.
+    #[serde(default = "default_group_similar_budget_ms")]
+    pub group_similar_budget_ms: u64,
+    /// Most files a zip search export may contain; larger result sets fail
+    /// with 400 rather than being truncated.
+    #[serde(default = "default_export_max_files")]
+    pub export_max_files: usize,
+    /// Most bytes, in megabytes, the files of a zip search export may add
+    /// up to. The archive is staged in the temp directory before streaming.
+    #[serde(default = "default_export_max_mb")]
+    pub export_max_mb: u64,
+    /// Most files a hardlink or copy search export job may take; the file
+    /// list is held in memory until the job runs.
+    #[serde(default = "default_export_job_max_files")]
+    pub export_job_max_files: usize,
+    /// Destinations hardlink and copy exports may write under even when
+    /// they lie inside an included folder. Default: empty.
+    #[serde(default)]
+    pub export_allowed_destinations: Vec<PathBuf>,
+    /// Tags applied to fewer items are left out of the in-memory index that
+    /// answers prefix tag autocompletion, bounding its memory; prefix
+    /// lookups from the index never return them. `0` disables the index.
+    #[serde(default = "default_tag_index_min_count")]
+    pub tag_index_min_count: i64,
+}
+
+/// `[search.warmup]`: searches run once in the background after the
+/// gateway binds, so the first real semantic search after a restart does
+/// not pay for a cold embedding cache and cold vector index pages. Both
+/// lists target the default index and user-data databases; empty (the
+/// default) skips warmup.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SearchWarmupConfig {
+    /// Text prompts, each embedded with every search-usable embedding model
+    /// that has data (the `preload_embedding_models` selection).
+    #[serde(default)]
+    pub prompts: Vec<String>,
+    /// Names of saved queries (of the default `user`) to run.
+    #[serde(default)]
+    pub saved_queries: Vec<String>,
 }
 
-fn default_embedding_cache_size() -> usize {
-    1024
+fn default_embedding_cache_mb() -> usize {
+    64
+}
+
+/// What one legacy `embedding_cache_size` entry is assumed to cost: a
+/// 1024-dimension float32 embedding.
+const ESTIMATED_EMBEDDING_BYTES: usize = 4096;
+
+impl SearchConfig {
+    /// The embedding cache byte budget, from the legacy entry count if it
+    /// is set.
+    pub fn embedding_cache_bytes(&self) -> usize {
+        match self.embedding_cache_size {
+            Some(entries) => entries.saturating_mul(ESTIMATED_EMBEDDING_BYTES),
+            None => self.embedding_cache_mb.saturating_mul(1024 * 1024),
+        }
+    }
 }
 
 fn default_search_cache_size_mb() -> usize {
@@ -726,6 +1150,42 @@ fn default_search_cache_size_max_mb() -> usize {
     65_536
 }
 
+fn default_slow_query_ms() -> u64 {
+    1000
+}
+
+fn default_slow_query_log_size() -> usize {
+    200
+}
+
+fn default_slow_query_max_bytes() -> usize {
+    16 * 1024
+}
+
+fn default_check_path_budget_ms() -> u64 {
+    150
+}
+
+fn default_group_similar_budget_ms() -> u64 {
+    200
+}
+
+fn default_export_max_files() -> usize {
+    1000
+}
+
+fn default_export_max_mb() -> u64 {
+    4096
+}
+
+fn default_export_job_max_files() -> usize {
+    100_000
+}
+
+fn default_tag_index_min_count() -> i64 {
+    2
+}
+
 fn default_inference_weight() -> f64 {
     1.0
 }
@@ -737,9 +1197,22 @@ fn default_inference_use_for_jobs() -> bool {
 impl Default for SearchConfig {
     fn default() -> Self {
         Self {
-            embedding_cache_size: default_embedding_cache_size(),
+            embedding_cache_mb: default_embedding_cache_mb(),
+            embedding_cache_size: None,
             cache_size_mb: default_search_cache_size_mb(),
             cache_size_max_mb: default_search_cache_size_max_mb(),
+            slow_query_ms: default_slow_query_ms(),
+            slow_query_log_size: default_slow_query_log_size(),
+            slow_query_max_bytes: default_slow_query_max_bytes(),
+            warmup: SearchWarmupConfig::default(),
+            disabled_filters: Vec::new(),
+            check_path_budget_ms: default_check_path_budget_ms(),
+            group_similar_budget_ms: default_group_similar_budget_ms(),
+            export_max_files: default_export_max_files(),
+            export_max_mb: default_export_max_mb(),
+            export_job_max_files: default_export_job_max_files(),
+            export_allowed_destinations: Vec::new(),
+            tag_index_min_count: default_tag_index_min_count(),
         }
     }
 }
@@ -808,9 +1281,28 @@ pub struct PolicyMatch {
     pub endpoints: Vec<String>,
 }
 
+/// `[policies.identity]`: who a request acts as. The user id comes from an
+/// API token (`Authorization: Bearer <token>`) or, failing that, from
+/// `user_header`, which a trusted reverse proxy is expected to set. It names
+/// the tenant for `tenant_prefix_template` and scopes the bookmark
+/// endpoints to that user.
 #[derive(Debug, Clone, Deserialize)]
 pub struct IdentityConfig {
-    pub user_header: String,
+    #[serde(default)]
+    pub user_header: Option<String>,
+    /// `[[policies.identity.tokens]]`. Once any are configured, bookmark
+    /// access under this policy requires an identity.
+    #[serde(default)]
+    pub tokens: Vec<IdentityToken>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct IdentityToken {
+    pub token: String,
+    pub user: String,
+    /// Admin tokens may act on other users' bookmarks.
+    #[serde(default)]
+    pub admin: bool,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -870,8 +1362,8 @@ impl Settings {
             .set_default("upstreams.ui.base_url", "http://127.0.0.1:6340")?
             .set_default("upstreams.api.base_url", "http://127.0.0.1:6342")?
             .set_default(
-                "search.embedding_cache_size",
-                default_embedding_cache_size() as i64,
+                "search.embedding_cache_mb",
+                default_embedding_cache_mb() as i64,
             )?
             .set_default(
                 "search.cache_size_mb",
@@ -894,6 +1386,7 @@ impl Settings {
 
         let mut settings: Settings = builder.build()?.try_deserialize()?;
         settings.normalize_empty_tool_paths();
+        settings.normalize_disabled_filters()?;
         let loopback_synthesized = settings.apply_inference_default();
         settings.validate(loopback_synthesized)?;
         Ok(settings)
@@ -940,12 +1433,36 @@ impl Settings {
     /// synthesized a loopback self-call inference upstream, which must be
     /// checked against the policies (see
     /// [`Settings::validate_loopback_inference_policy`]).
+    /// Resolves `search.disabled_filters` to filter keys, rejecting names
+    /// that are not filters (the `and_`/`or_`/`not_` operators included).
+    fn normalize_disabled_filters(&mut self) -> Result<()> {
+        let mut keys: Vec<String> = Vec::new();
+        for name in &self.search.disabled_filters {
+            let key = crate::pql::model::filter_key(name).with_context(|| {
+                let known = crate::pql::model::PQL_FILTERS
+                    .map(|(key, _)| key)
+                    .join(", ");
+                format!(
+                    "search.disabled_filters: unknown filter {name:?} (expected one of {known})"
+                )
+            })?;
+            if !keys.iter().any(|existing| existing == key) {
+                keys.push(key.to_string());
+            }
+        }
+        self.search.disabled_filters = keys;
+        Ok(())
+    }
+
     fn validate(&self, loopback_synthesized: bool) -> Result<()> {
         self.validate_endpoints()?;
         self.validate_rulesets()?;
         self.validate_policies()?;
         self.validate_inference_endpoints()?;
         self.validate_ui()?;
+        self.validate_upstream_timeouts()?;
+        self.validate_notifications()?;
+        self.validate_reports()?;
         if loopback_synthesized {
             self.validate_loopback_inference_policy()?;
         }
@@ -957,6 +1474,18 @@ impl Settings {
     /// during `Settings::load` itself would be dropped).
     pub fn log_warnings(&self) {
         self.warn_inference_local();
+        self.warn_legacy_embedding_cache_size();
+    }
+
+    fn warn_legacy_embedding_cache_size(&self) {
+        if let Some(entries) = self.search.embedding_cache_size {
+            tracing::warn!(
+                entries,
+                budget_bytes = self.search.embedding_cache_bytes(),
+                "search.embedding_cache_size is deprecated and was converted to \
+                 an estimated byte budget; set search.embedding_cache_mb instead"
+            );
+        }
     }
 
     /// Local inference spawns workers lazily, so a missing interpreter is a
@@ -1088,10 +1617,10 @@ impl Settings {
             // would never be seen — every request would silently fall back
             // to the un-tenanted defaults, defeating tenant isolation.
             if let Some(identity) = &policy.identity {
-                if identity
-                    .user_header
-                    .to_ascii_lowercase()
-                    .starts_with("x-panoptikon-")
+                if let Some(user_header) = &identity.user_header
+                    && user_header
+                        .to_ascii_lowercase()
+                        .starts_with("x-panoptikon-")
                 {
                     anyhow::bail!(
                         "policy '{}' identity.user_header '{}' is invalid: the \
@@ -1100,9 +1629,34 @@ impl Settings {
                          would never reach identity extraction; use a different \
                          header name",
                         policy.name,
-                        identity.user_header
+                        user_header
+                    );
+                }
+                if identity.user_header.is_none() && identity.tokens.is_empty() {
+                    anyhow::bail!(
+                        "policy '{}' identity must set user_header or at least one token",
+                        policy.name
                     );
                 }
+                for (index, token) in identity.tokens.iter().enumerate() {
+                    if token.token.trim().is_empty() || token.user.trim().is_empty() {
+                        anyhow::bail!(
+                            "policy '{}' identity token {} must have a non-empty token and user",
+                            policy.name,
+                            index + 1
+                        );
+                    }
+                    if identity.tokens[..index]
+                        .iter()
+                        .any(|other| other.token == token.token)
+                    {
+                        anyhow::bail!(
+                            "policy '{}' identity token {} duplicates an earlier token",
+                            policy.name,
+                            index + 1
+                        );
+                    }
+                }
             }
             if policy.match_rule.hosts.is_empty() && policy.match_rule.endpoints.is_empty() {
                 anyhow::bail!(
@@ -1218,6 +1772,70 @@ impl Settings {
         Ok(())
     }
 
+    fn validate_upstream_timeouts(&self) -> Result<()> {
+        self.upstreams.ui.timeouts.validate("upstreams.ui")?;
+        self.upstreams.ui.websocket.validate("upstreams.ui")?;
+        self.upstreams.api.timeouts.validate("upstreams.api")?;
+        self.upstreams.api.websocket.validate("upstreams.api")?;
+        for (idx, endpoint) in self.upstreams.inference.iter().enumerate() {
+            let label = format!("upstreams.inference[{idx}]");
+            endpoint.timeouts.validate(&label)?;
+            endpoint.websocket.validate(&label)?;
+        }
+        Ok(())
+    }
+
+    fn validate_notifications(&self) -> Result<()> {
+        for (idx, webhook) in self.notifications.webhooks.iter().enumerate() {
+            let parsed = url::Url::parse(&webhook.url)
+                .with_context(|| format!("notifications.webhooks[{idx}] url is not a valid URL"))?;
+            if !matches!(parsed.scheme(), "http" | "https") {
+                anyhow::bail!("notifications.webhooks[{}] url must be http(s)", idx);
+            }
+        }
+        if self.notifications.error_threshold < 0 {
+            anyhow::bail!("notifications.error_threshold must be >= 0");
+        }
+        Ok(())
+    }
+
+    fn validate_reports(&self) -> Result<()> {
+        let mut seen_names = std::collections::HashSet::new();
+        for (idx, destination) in self.reports.destinations.iter().enumerate() {
+            if !is_safe_identifier(&destination.name, MAX_DB_NAME_LEN) {
+                anyhow::bail!(
+                    "reports.destinations[{idx}] name '{}' is invalid",
+                    destination.name
+                );
+            }
+            if !seen_names.insert(destination.name.as_str()) {
+                anyhow::bail!(
+                    "reports.destinations name '{}' is duplicated",
+                    destination.name
+                );
+            }
+            match (&destination.url, &destination.path) {
+                (Some(url), None) => {
+                    let parsed = url::Url::parse(url).with_context(|| {
+                        format!("reports.destinations[{idx}] url is not a valid URL")
+                    })?;
+                    if !matches!(parsed.scheme(), "http" | "https") {
+                        anyhow::bail!("reports.destinations[{idx}] url must be http(s)");
+                    }
+                }
+                (None, Some(path)) if path.is_absolute() => {}
+                (None, Some(_)) => {
+                    anyhow::bail!("reports.destinations[{idx}] path must be absolute")
+                }
+                _ => anyhow::bail!("reports.destinations[{idx}] needs exactly one of url or path"),
+            }
+        }
+        if self.reports.max_results < 1 {
+            anyhow::bail!("reports.max_results must be >= 1");
+        }
+        Ok(())
+    }
+
     fn validate_inference_endpoints(&self) -> Result<()> {
         if self.upstreams.inference.is_empty() {
             anyhow::bail!("upstreams.inference must include at least one endpoint");
@@ -1250,15 +1868,27 @@ impl Settings {
     fn apply_inference_default(&mut self) -> bool {
         if self.upstreams.inference.is_empty() {
             let local = self.inference_local.enabled;
-            let base_url = if local {
-                loopback_base_url(&self.server.host, self.server.port)
+            // The API-upstream fallback is the API server itself, so it
+            // keeps that upstream's proxy deadlines and upgrade limits.
+            let (base_url, timeouts, websocket) = if local {
+                (
+                    loopback_base_url(&self.server.host, self.server.port),
+                    UpstreamTimeouts::default(),
+                    UpstreamWebSocket::default(),
+                )
             } else {
-                self.upstreams.api.base_url.clone()
+                (
+                    self.upstreams.api.base_url.clone(),
+                    self.upstreams.api.timeouts.clone(),
+                    self.upstreams.api.websocket.clone(),
+                )
             };
             self.upstreams.inference.push(InferenceEndpointConfig {
                 base_url,
                 weight: default_inference_weight(),
                 use_for_jobs: default_inference_use_for_jobs(),
+                timeouts,
+                websocket,
             });
             return local;
         }
@@ -1504,6 +2134,134 @@ mod tests {
         assert_eq!(loopback_base_url("[::1]", 8080), "http://[::1]:8080");
     }
 
+    /// `[upstreams.<name>.timeouts]` parses on every upstream block (path
+    /// keys containing slashes included) and resolves the per-path
+    /// deadline by longest prefix; a zero deadline is rejected at load.
+    #[test]
+    fn upstream_timeouts_parse_and_resolve_longest_prefix() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("gw.toml");
+        std::fs::write(
+            &path,
+            r#"
+[server]
+host = "127.0.0.1"
+port = 9155
+
+[upstreams.ui]
+base_url = "http://127.0.0.1:6339"
+timeouts = { connect_secs = 2, request_secs = 30 }
+
+[upstreams.api]
+base_url = "http://127.0.0.1:6342"
+
+[[upstreams.inference]]
+base_url = "http://127.0.0.1:7000"
+
+[upstreams.inference.timeouts]
+request_secs = 60
+
+[upstreams.inference.timeouts.paths]
+"/api/inference" = 90
+"/api/inference/predict" = 600
+"#,
+        )
+        .unwrap();
+        let settings = Settings::load(Some(path.clone())).unwrap();
+        assert_eq!(settings.upstreams.ui.timeouts.connect_secs, Some(2));
+        assert_eq!(
+            settings.upstreams.ui.timeouts.request_timeout("/anything"),
+            Some(std::time::Duration::from_secs(30))
+        );
+        assert_eq!(
+            settings.upstreams.api.timeouts.request_timeout("/api/db"),
+            None
+        );
+        let inference = &settings.upstreams.inference[0].timeouts;
+        assert_eq!(
+            inference.request_timeout("/api/inference/predict/g/id"),
+            Some(std::time::Duration::from_secs(600))
+        );
+        assert_eq!(
+            inference.request_timeout("/api/inference/metadata"),
+            Some(std::time::Duration::from_secs(90))
+        );
+        assert_eq!(
+            inference.request_timeout("/health"),
+            Some(std::time::Duration::from_secs(60))
+        );
+
+        std::fs::write(
+            &path,
+            r#"
+[server]
+host = "127.0.0.1"
+port = 9155
+
+[upstreams.ui]
+base_url = "http://127.0.0.1:6339"
+
+[upstreams.api]
+base_url = "http://127.0.0.1:6342"
+timeouts = { request_secs = 0 }
+"#,
+        )
+        .unwrap();
+        let err = Settings::load(Some(path)).unwrap_err().to_string();
+        assert!(err.contains("upstreams.api.timeouts.request_secs"), "{err}");
+    }
+
+    /// `[upstreams.<name>.websocket]` parses per upstream, defaults to no
+    /// limits, and rejects a zero limit at load.
+    #[test]
+    fn upstream_websocket_limits_parse_and_validate() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("gw.toml");
+        std::fs::write(
+            &path,
+            r#"
+[server]
+host = "127.0.0.1"
+port = 9155
+
+[upstreams.ui]
+base_url = "http://127.0.0.1:6339"
+websocket = { max_connections = 16, idle_secs = 300 }
+
+[upstreams.api]
+base_url = "http://127.0.0.1:6342"
+"#,
+        )
+        .unwrap();
+        let settings = Settings::load(Some(path.clone())).unwrap();
+        assert_eq!(settings.upstreams.ui.websocket.max_connections, Some(16));
+        assert_eq!(settings.upstreams.ui.websocket.idle_secs, Some(300));
+        assert_eq!(settings.upstreams.api.websocket.max_connections, None);
+        assert_eq!(settings.upstreams.inference[0].websocket.idle_secs, None);
+
+        std::fs::write(
+            &path,
+            r#"
+[server]
+host = "127.0.0.1"
+port = 9155
+
+[upstreams.ui]
+base_url = "http://127.0.0.1:6339"
+websocket = { max_connections = 0 }
+
+[upstreams.api]
+base_url = "http://127.0.0.1:6342"
+"#,
+        )
+        .unwrap();
+        let err = Settings::load(Some(path)).unwrap_err().to_string();
+        assert!(
+            err.contains("upstreams.ui.websocket.max_connections"),
+            "{err}"
+        );
+    }
+
     /// A minimal policy block matching the given hosts with no ruleset
     /// restriction, for tests that need config load to pass the loopback
     /// self-call policy validation.
@@ -2186,6 +2944,7 @@ temp_dir = "D:/scratch"
 [logging]
 file = ""
 level = "DEBUG"
+format = "json"
 
 [open]
 file_command = "mpv {{path}}"
@@ -2204,6 +2963,7 @@ image_decode_memory_limit_mb = 2048
         assert_eq!(settings.temp_dir, PathBuf::from("D:/scratch"));
         assert_eq!(settings.logging.file.as_deref(), Some(""));
         assert_eq!(settings.logging.level, "DEBUG");
+        assert_eq!(settings.logging.format, LogFormat::Json);
         assert_eq!(settings.open.file_command.as_deref(), Some("mpv {path}"));
         assert_eq!(
             settings.open.folder_command.as_deref(),
@@ -2220,6 +2980,111 @@ image_decode_memory_limit_mb = 2048
         assert_eq!(runtime.open.file_command.as_deref(), Some("mpv {path}"));
     }
 
+    /// `[notifications]` webhooks parse with their event filters and reach
+    /// the runtime config; a non-http(s) URL fails load.
+    #[test]
+    fn notification_webhooks_parse_and_validate() {
+        let _guard = env_lock();
+        let settings = load_from(&format!(
+            r#"{MINIMAL}
+[notifications]
+error_threshold = 5
+
+[[notifications.webhooks]]
+url = "https://hooks.example.com/a"
+events = ["job_failed", "errors_above_threshold"]
+
+[[notifications.webhooks]]
+url = "http://127.0.0.1:9000/b"
+"#
+        ))
+        .unwrap();
+        let notifications = settings.runtime_config().notifications;
+        assert_eq!(notifications.error_threshold, 5);
+        let [filtered, all] = notifications.webhooks.as_slice() else {
+            panic!("expected two webhooks");
+        };
+        assert!(filtered.wants(NotificationEvent::JobFailed));
+        assert!(!filtered.wants(NotificationEvent::JobFinished));
+        assert!(all.wants(NotificationEvent::ScanFinished));
+
+        let err = load_from(&format!(
+            r#"{MINIMAL}
+[[notifications.webhooks]]
+url = "ftp://hooks.example.com/a"
+"#
+        ))
+        .unwrap_err();
+        assert!(
+            format!("{err:#}").contains("notifications.webhooks[0]"),
+            "{err:#}"
+        );
+    }
+
+    /// `[reports]` destinations parse with their templated headers and
+    /// reach the runtime config; one with both a url and a path fails load.
+    #[test]
+    fn report_destinations_parse_and_validate() {
+        let _guard = env_lock();
+        unsafe {
+            env::set_var("GW_TPL_REPORT_TOKEN", "s3cret");
+        }
+        let settings = load_from(&format!(
+            r#"{MINIMAL}
+[reports]
+max_results = 500
+
+[[reports.destinations]]
+name = "automation"
+url = "https://hooks.example.com/raw"
+headers = {{ Authorization = "Bearer ${{GW_TPL_REPORT_TOKEN}}" }}
+
+[[reports.destinations]]
+name = "archive"
+path = "/srv/reports/raw.json"
+"#
+        ))
+        .unwrap();
+        unsafe {
+            env::remove_var("GW_TPL_REPORT_TOKEN");
+        }
+        let reports = settings.runtime_config().reports;
+        assert_eq!(reports.max_results, 500);
+        let automation = reports.destination("automation").unwrap();
+        assert_eq!(automation.headers["Authorization"], "Bearer s3cret");
+        assert!(reports.destination("archive").unwrap().path.is_some());
+        assert!(reports.destination("missing").is_none());
+
+        let err = load_from(&format!(
+            r#"{MINIMAL}
+[[reports.destinations]]
+name = "both"
+url = "https://hooks.example.com/raw"
+path = "/srv/reports/raw.json"
+"#
+        ))
+        .unwrap_err();
+        assert!(
+            format!("{err:#}").contains("exactly one of url or path"),
+            "{err:#}"
+        );
+    }
+
+    /// `search.disabled_filters` only accepts filter names; the boolean
+    /// operators and unknown names fail load with the valid keys listed.
+    #[test]
+    fn disabled_filters_reject_unknown_names() {
+        let _guard = env_lock();
+        for name in ["and_", "SimilarItems"] {
+            let err = load_from(&format!(
+                "[search]\ndisabled_filters = [\"{name}\"]\n{MINIMAL}"
+            ))
+            .unwrap_err();
+            let text = format!("{err:#}");
+            assert!(text.contains(name) && text.contains("similar_to"), "{text}");
+        }
+    }
+
     /// There is no env override layer: PANOPTIKON__* variables (the removed
     /// mechanism) have no effect on config load whatsoever. Env vars reach
     /// the config exclusively through `${VAR}` templating in the file.
@@ -2274,7 +3139,7 @@ base_url = "http://127.0.0.1:6342"
 weight = "${GW_TPL_WEIGHT:-1.5}"
 
 [search]
-embedding_cache_size = "${GW_TPL_CACHE:-16}"
+embedding_cache_mb = "${GW_TPL_CACHE:-16}"
 "#;
 
         // Variables unset: the template defaults land as the typed values.
@@ -2283,7 +3148,7 @@ embedding_cache_size = "${GW_TPL_CACHE:-16}"
         assert!(settings.server.trust_forwarded_headers);
         assert!(!settings.readonly);
         assert_eq!(settings.upstreams.inference[0].weight, 1.5f64);
-        assert_eq!(settings.search.embedding_cache_size, 16usize);
+        assert_eq!(settings.search.embedding_cache_mb, 16usize);
         // Numeric-looking value on a string key: stays a string verbatim
         // (never round-tripped through a number — the leading zero lives).
         assert_eq!(settings.index_db, "0123");
@@ -2315,7 +3180,7 @@ embedding_cache_size = "${GW_TPL_CACHE:-16}"
         assert!(!settings.server.trust_forwarded_headers);
         assert!(settings.readonly);
         assert_eq!(settings.upstreams.inference[0].weight, 2.25f64);
-        assert_eq!(settings.search.embedding_cache_size, 32usize);
+        assert_eq!(settings.search.embedding_cache_mb, 32usize);
         assert_eq!(settings.index_db, "007");
 
         // A non-numeric value on a numeric key fails the load loudly
@@ -2326,6 +3191,25 @@ embedding_cache_size = "${GW_TPL_CACHE:-16}"
         assert!(result.is_err(), "garbage in a numeric key must fail");
     }
 
+    /// The deprecated entry count still sizes the embedding cache, converted
+    /// to an estimated byte budget; without it the megabyte budget applies.
+    #[test]
+    fn legacy_embedding_cache_size_becomes_a_byte_budget() {
+        let _guard = env_lock();
+        let settings =
+            load_from(&format!("{MINIMAL}\n[search]\nembedding_cache_mb = 8\n")).unwrap();
+        assert_eq!(settings.search.embedding_cache_bytes(), 8 * 1024 * 1024);
+
+        let settings = load_from(&format!(
+            "{MINIMAL}\n[search]\nembedding_cache_mb = 8\nembedding_cache_size = 100\n"
+        ))
+        .unwrap();
+        assert_eq!(
+            settings.search.embedding_cache_bytes(),
+            100 * ESTIMATED_EMBEDDING_BYTES
+        );
+    }
+
     /// Env templating applies to the gateway settings file: `${VAR}` and
     /// `${VAR:-default}` expand inside string values (nested tables and
     /// arrays included), Windows backslash values survive, and `$${` stays a
@@ -2561,6 +3445,59 @@ allow = "*"
         }
     }
 
+    /// Identity tokens load without a user header, and an identity with
+    /// neither, an empty token, or a repeated token fails config load.
+    #[test]
+    fn identity_tokens_are_validated() {
+        let policy_with_identity = |identity: &str| {
+            format!(
+                r#"{MINIMAL}
+[[policies]]
+name = "tokens"
+
+[policies.match]
+hosts = ["localhost"]
+
+[policies.identity]
+{identity}
+
+[policies.index_db]
+default = "default"
+allow = "*"
+
+[policies.user_data_db]
+default = "default"
+allow = "*"
+"#
+            )
+        };
+
+        let settings = load_from(&policy_with_identity(
+            r#"tokens = [{ token = "secret-a", user = "alice" }, { token = "secret-b", user = "bob", admin = true }]"#,
+        ))
+        .expect("tokens alone are a valid identity");
+        let identity = settings.policies[0].identity.as_ref().unwrap();
+        assert!(identity.user_header.is_none());
+        assert!(!identity.tokens[0].admin);
+        assert!(identity.tokens[1].admin);
+
+        for (bad, expected) in [
+            ("tokens = []", "user_header or at least one token"),
+            (
+                r#"tokens = [{ token = " ", user = "alice" }]"#,
+                "non-empty token and user",
+            ),
+            (
+                r#"tokens = [{ token = "same", user = "alice" }, { token = "same", user = "bob" }]"#,
+                "duplicates an earlier token",
+            ),
+        ] {
+            let err = load_from(&policy_with_identity(bad)).expect_err(bad);
+            let text = format!("{err:#}");
+            assert!(text.contains(expected), "'{bad}': {text}");
+        }
+    }
+
     /// `${VAR}` without a default and with the variable unset fails config
     /// load with an error naming both the file and the variable.
     #[test]
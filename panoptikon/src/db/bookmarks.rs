@@ -1,11 +1,17 @@
 use serde_json::Value;
 use sqlx::Row;
+use std::collections::BTreeMap;
 use std::path::Path;
 
 use crate::api_error::ApiError;
+use crate::db::raw_paths::disk_path;
 
 type ApiResult<T> = std::result::Result<T, ApiError>;
 
+/// Hashes bound per `IN` query in `get_bookmark_namespaces_for_items`; with
+/// the user and namespaces, stays well under SQLite's 32766 variables.
+const STATUS_LOOKUP_CHUNK: usize = 5000;
+
 pub(crate) struct BookmarkEntry {
     pub namespace: String,
     pub metadata: Option<Value>,
@@ -94,6 +100,28 @@ pub(crate) async fn delete_bookmark(
     Ok(result.rows_affected())
 }
 
+/// Removes the item's bookmarks for every user and namespace.
+pub(crate) async fn delete_item_bookmarks(
+    conn: &mut sqlx::SqliteConnection,
+    sha256: &str,
+) -> ApiResult<u64> {
+    let result = sqlx::query(
+        r#"
+        DELETE FROM user_data.bookmarks
+        WHERE sha256 = ?
+        "#,
+    )
+    .bind(sha256)
+    .execute(conn)
+    .await
+    .map_err(|err| {
+        tracing::error!(error = %err, "failed to delete item bookmarks");
+        ApiError::internal("Failed to delete item bookmarks")
+    })?;
+
+    Ok(result.rows_affected())
+}
+
 pub(crate) async fn delete_bookmarks_exclude_last_n(
     conn: &mut sqlx::SqliteConnection,
     n: i64,
@@ -447,6 +475,66 @@ pub(crate) async fn get_bookmarks_item(
     Ok(bookmarks)
 }
 
+/// The namespaces each of `sha256s` is bookmarked in by `user`, restricted
+/// to `namespaces` unless that is empty or contains `*`. Items without a
+/// matching bookmark are absent from the map; namespaces come sorted.
+pub(crate) async fn get_bookmark_namespaces_for_items(
+    conn: &mut sqlx::SqliteConnection,
+    sha256s: &[String],
+    namespaces: &[String],
+    user: &str,
+) -> ApiResult<BTreeMap<String, Vec<String>>> {
+    let any_namespace = namespaces.is_empty() || namespaces.iter().any(|ns| ns == "*");
+    let namespace_condition = if any_namespace {
+        String::new()
+    } else {
+        format!(
+            "AND namespace IN ({})",
+            vec!["?"; namespaces.len()].join(", ")
+        )
+    };
+
+    let mut found: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for chunk in sha256s.chunks(STATUS_LOOKUP_CHUNK) {
+        let sql = format!(
+            r#"
+            SELECT sha256, namespace
+            FROM user_data.bookmarks
+            WHERE user = ? {namespace_condition}
+            AND sha256 IN ({})
+            ORDER BY sha256, namespace
+            "#,
+            vec!["?"; chunk.len()].join(", ")
+        );
+        let mut query = sqlx::query(sqlx::AssertSqlSafe(sql.as_str())).bind(user);
+        if !any_namespace {
+            for namespace in namespaces {
+                query = query.bind(namespace);
+            }
+        }
+        for sha256 in chunk {
+            query = query.bind(sha256);
+        }
+        let rows = query.fetch_all(&mut *conn).await.map_err(|err| {
+            tracing::error!(error = %err, "failed to read bookmark status for items");
+            ApiError::internal("Failed to get bookmark status")
+        })?;
+        for row in rows {
+            let sha256: String = row.try_get("sha256").map_err(|err| {
+                tracing::error!(error = %err, "failed to read bookmark sha256");
+                ApiError::internal("Failed to get bookmark status")
+            })?;
+            let namespace: String = row.try_get("namespace").map_err(|err| {
+                tracing::error!(error = %err, "failed to read bookmark namespace");
+                ApiError::internal("Failed to get bookmark status")
+            })?;
+            found.entry(sha256).or_default().push(namespace);
+        }
+    }
+
+    Ok(found)
+}
+
 fn parse_metadata(raw: Option<String>, detail: &'static str) -> ApiResult<Option<Value>> {
     match raw {
         Some(raw) => {
@@ -466,7 +554,7 @@ async fn get_existing_file_for_sha256(
 ) -> ApiResult<Option<ExistingFile>> {
     let rows = sqlx::query(
         r#"
-        SELECT path
+        SELECT path, path_bytes
         FROM files
         WHERE sha256 = ?
         ORDER BY available DESC
@@ -485,7 +573,11 @@ async fn get_existing_file_for_sha256(
             tracing::error!(error = %err, "failed to read file path");
             ApiError::internal("Failed to get bookmarks")
         })?;
-        if Path::new(&path).exists() {
+        let path_bytes: Option<Vec<u8>> = row.try_get("path_bytes").map_err(|err| {
+            tracing::error!(error = %err, "failed to read file path bytes");
+            ApiError::internal("Failed to get bookmarks")
+        })?;
+        if disk_path(&path, path_bytes.as_deref()).exists() {
             return Ok(Some(ExistingFile { path }));
         }
     }
@@ -170,12 +170,31 @@ where
     async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
         let query = Query::<DbQuery>::try_from_uri(&parts.uri)
             .map_err(|_| ApiError::bad_request("Invalid query parameters"))?;
+        Self::open(query.0).await
+    }
+}
+
+impl<M: DbMode> DbConnection<M> {
+    /// The connection a request naming these databases gets (`None` = the
+    /// configured default), for callers outside a request such as startup
+    /// tasks.
+    pub(crate) async fn open_named(
+        index_db: Option<String>,
+        user_data_db: Option<String>,
+    ) -> Result<Self, ApiError> {
+        Self::open(DbQuery {
+            index_db,
+            user_data_db,
+        })
+        .await
+    }
 
+    async fn open(query: DbQuery) -> Result<Self, ApiError> {
         // Read-only requests share pooled connections; only writes still open
         // (and tear down) a dedicated connection per request.
         if !M::WRITE_LOCK && !M::USER_DATA_WL {
-            let names = resolve_db_names_unchecked(&query.0);
-            let conn = acquire_read_conn(&query.0, &names, M::ATTACH_USER_DATA).await?;
+            let names = resolve_db_names_unchecked(&query);
+            let conn = acquire_read_conn(&query, &names, M::ATTACH_USER_DATA).await?;
             return Ok(Self {
                 conn: DbConn::Pooled(conn),
                 index_db: names.index_db,
@@ -184,7 +203,7 @@ where
             });
         }
 
-        let names = resolve_db_names(query.0)?;
+        let names = resolve_db_names(query)?;
         let paths = db_paths(&names.index_db, &names.user_data_db)?;
         let conn = connect_db(&paths, M::WRITE_LOCK, M::USER_DATA_WL, M::ATTACH_USER_DATA).await?;
 
@@ -454,7 +473,7 @@ fn check_dbs(index_db: Option<&str>, user_data_db: Option<&str>) -> Result<(), A
     let (index_dbs, user_data_dbs) = db_lists()?;
     if let Some(index_db) = index_db {
         if !index_dbs.iter().any(|entry| entry == index_db) {
-            return Err(ApiError::not_found(format!(
+            return Err(ApiError::db_not_found(format!(
                 "Index database {index_db} not found"
             )));
         }
@@ -465,7 +484,7 @@ fn check_dbs(index_db: Option<&str>, user_data_db: Option<&str>) -> Result<(), A
     // people hunting for a missing index DB when it is the user-data DB.
     if let Some(user_data_db) = user_data_db {
         if !user_data_dbs.iter().any(|entry| entry == user_data_db) {
-            return Err(ApiError::not_found(format!(
+            return Err(ApiError::db_not_found(format!(
                 "User data database {user_data_db} not found"
             )));
         }
@@ -596,3 +615,18 @@ fn user_data_attach_path(path: &Path, read_only: bool) -> String {
     let path = path.to_string_lossy().replace('\\', "/");
     format!("file:{path}?mode=ro")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_error::ErrorCode;
+
+    #[test]
+    fn unknown_databases_report_db_not_found() {
+        let _test_env = crate::test_utils::test_data_dir();
+        let error = check_dbs(Some("no-such-index"), None).expect_err("missing index db");
+        assert_eq!(error.code(), ErrorCode::DbNotFound);
+        let error = check_dbs(None, Some("no-such-user-data")).expect_err("missing user db");
+        assert_eq!(error.code(), ErrorCode::DbNotFound);
+    }
+}
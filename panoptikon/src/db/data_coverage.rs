@@ -0,0 +1,194 @@
+use sqlx::Row;
+
+use crate::api_error::ApiError;
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+/// Units (items, or text rows) of one MIME type that a setter has data for.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ProcessedCount {
+    pub setter_name: String,
+    pub mime_type: String,
+    pub processed: i64,
+    /// Units that only have placeholder data, i.e. nothing was extracted.
+    pub placeholders: i64,
+}
+
+fn internal(context: &'static str) -> impl Fn(sqlx::Error) -> ApiError {
+    move |err| {
+        tracing::error!(error = %err, context, "data coverage query failed");
+        ApiError::internal(context)
+    }
+}
+
+async fn fetch_type_counts(
+    conn: &mut sqlx::SqliteConnection,
+    sql: &str,
+) -> ApiResult<Vec<(String, i64)>> {
+    let rows = sqlx::query(sqlx::AssertSqlSafe(sql))
+        .fetch_all(conn)
+        .await
+        .map_err(internal("Failed to count eligible data"))?;
+    rows.iter()
+        .map(|row| Ok((row.try_get("mime_type")?, row.try_get("total")?)))
+        .collect::<Result<Vec<_>, sqlx::Error>>()
+        .map_err(internal("Failed to count eligible data"))
+}
+
+async fn fetch_processed_counts(
+    conn: &mut sqlx::SqliteConnection,
+    sql: &str,
+) -> ApiResult<Vec<ProcessedCount>> {
+    let rows = sqlx::query(sqlx::AssertSqlSafe(sql))
+        .fetch_all(conn)
+        .await
+        .map_err(internal("Failed to count processed data"))?;
+    rows.iter()
+        .map(|row| {
+            Ok(ProcessedCount {
+                setter_name: row.try_get("setter_name")?,
+                mime_type: row.try_get("mime_type")?,
+                processed: row.try_get("processed")?,
+                placeholders: row.try_get("placeholders")?,
+            })
+        })
+        .collect::<Result<Vec<_>, sqlx::Error>>()
+        .map_err(internal("Failed to count processed data"))
+}
+
+/// Item count per MIME type.
+pub(crate) async fn get_item_type_counts(
+    conn: &mut sqlx::SqliteConnection,
+) -> ApiResult<Vec<(String, i64)>> {
+    fetch_type_counts(
+        conn,
+        "SELECT type AS mime_type, COUNT(*) AS total FROM items GROUP BY type",
+    )
+    .await
+}
+
+/// Extracted text row count per MIME type of the item it belongs to.
+pub(crate) async fn get_text_type_counts(
+    conn: &mut sqlx::SqliteConnection,
+) -> ApiResult<Vec<(String, i64)>> {
+    fetch_type_counts(
+        conn,
+        r#"
+        SELECT items.type AS mime_type, COUNT(*) AS total
+        FROM item_data
+        JOIN items ON items.id = item_data.item_id
+        WHERE item_data.data_type = 'text'
+        GROUP BY items.type
+        "#,
+    )
+    .await
+}
+
+/// Items each setter has any data for, per setter and MIME type.
+pub(crate) async fn get_item_processed_counts(
+    conn: &mut sqlx::SqliteConnection,
+) -> ApiResult<Vec<ProcessedCount>> {
+    fetch_processed_counts(
+        conn,
+        r#"
+        SELECT
+            setters.name AS setter_name,
+            items.type AS mime_type,
+            COUNT(DISTINCT item_data.item_id) AS processed,
+            COUNT(DISTINCT item_data.item_id)
+                - COUNT(DISTINCT CASE WHEN item_data.is_placeholder = 0 THEN item_data.item_id END)
+                AS placeholders
+        FROM item_data
+        JOIN setters ON setters.id = item_data.setter_id
+        JOIN items ON items.id = item_data.item_id
+        GROUP BY setters.name, items.type
+        "#,
+    )
+    .await
+}
+
+/// Text rows each setter has derived data from, per setter and MIME type.
+pub(crate) async fn get_text_processed_counts(
+    conn: &mut sqlx::SqliteConnection,
+) -> ApiResult<Vec<ProcessedCount>> {
+    fetch_processed_counts(
+        conn,
+        r#"
+        SELECT
+            setters.name AS setter_name,
+            items.type AS mime_type,
+            COUNT(DISTINCT derived.source_id) AS processed,
+            COUNT(DISTINCT derived.source_id)
+                - COUNT(DISTINCT CASE WHEN derived.is_placeholder = 0 THEN derived.source_id END)
+                AS placeholders
+        FROM item_data AS derived
+        JOIN item_data AS source
+            ON source.id = derived.source_id AND source.data_type = 'text'
+        JOIN setters ON setters.id = derived.setter_id
+        JOIN items ON items.id = source.item_id
+        GROUP BY setters.name, items.type
+        "#,
+    )
+    .await
+}
+
+/// The stored report JSON, if a report was ever computed.
+pub(crate) async fn get_coverage_snapshot(
+    conn: &mut sqlx::SqliteConnection,
+) -> ApiResult<Option<String>> {
+    let row = sqlx::query("SELECT report FROM data_coverage_snapshot WHERE id = 1")
+        .fetch_optional(conn)
+        .await
+        .map_err(internal("Failed to load coverage snapshot"))?;
+    row.map(|row| row.try_get("report"))
+        .transpose()
+        .map_err(internal("Failed to load coverage snapshot"))
+}
+
+pub(crate) async fn store_coverage_snapshot(
+    conn: &mut sqlx::SqliteConnection,
+    computed_at: &str,
+    report: &str,
+) -> ApiResult<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO data_coverage_snapshot (id, computed_at, report)
+        VALUES (1, ?, ?)
+        ON CONFLICT(id) DO UPDATE SET
+            computed_at = excluded.computed_at,
+            report = excluded.report
+        "#,
+    )
+    .bind(computed_at)
+    .bind(report)
+    .execute(conn)
+    .await
+    .map_err(internal("Failed to store coverage snapshot"))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::migrations::setup_test_databases;
+
+    // Storing twice keeps a single snapshot: the latest one.
+    #[tokio::test]
+    async fn coverage_snapshot_is_replaced() {
+        let mut dbs = setup_test_databases().await;
+        assert_eq!(
+            get_coverage_snapshot(&mut dbs.index_conn).await.unwrap(),
+            None
+        );
+        store_coverage_snapshot(&mut dbs.index_conn, "2024-01-01T00:00:00", r#"{"a":1}"#)
+            .await
+            .unwrap();
+        store_coverage_snapshot(&mut dbs.index_conn, "2024-01-02T00:00:00", r#"{"a":2}"#)
+            .await
+            .unwrap();
+        assert_eq!(
+            get_coverage_snapshot(&mut dbs.index_conn).await.unwrap(),
+            Some(r#"{"a":2}"#.to_string())
+        );
+    }
+}
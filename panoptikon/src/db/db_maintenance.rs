@@ -0,0 +1,188 @@
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use sqlx::Row;
+use utoipa::ToSchema;
+
+use crate::api_error::ApiError;
+use crate::db::connection::index_storage_paths_unchecked;
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+fn internal(context: &'static str) -> impl Fn(sqlx::Error) -> ApiError {
+    move |err| {
+        tracing::error!(error = %err, context, "db maintenance query failed");
+        ApiError::internal(context)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub(crate) struct DbMaintenanceRun {
+    pub id: i64,
+    pub start_time: String,
+    pub end_time: String,
+    /// `manual` or `scheduled`.
+    pub trigger: String,
+    /// `none`, `full` or `incremental`.
+    pub vacuum_mode: String,
+    /// Whether the requested vacuum actually ran.
+    pub vacuumed: bool,
+    /// Why a requested full VACUUM was skipped (e.g. not enough free space).
+    pub skipped_reason: Option<String>,
+    /// Null unless the run failed partway.
+    pub error: Option<String>,
+    /// Bytes of index.db and storage.db, WAL files included.
+    pub size_before: i64,
+    pub size_after: i64,
+    pub duration_secs: f64,
+}
+
+/// A finished run, before it has an ID.
+#[derive(Debug, Clone)]
+pub(crate) struct NewDbMaintenanceRun {
+    pub start_time: String,
+    pub end_time: String,
+    pub trigger: &'static str,
+    pub vacuum_mode: &'static str,
+    pub vacuumed: bool,
+    pub skipped_reason: Option<String>,
+    pub error: Option<String>,
+    pub size_before: i64,
+    pub size_after: i64,
+    pub duration_secs: f64,
+}
+
+/// The folder holding `index_db`'s files, for free-space checks.
+pub(crate) fn index_db_dir(index_db: &str) -> PathBuf {
+    index_storage_paths_unchecked(index_db)
+        .index_db_file
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default()
+}
+
+/// Bytes on disk of `index_db`'s index.db and storage.db with their WAL
+/// files; missing files count as empty.
+pub(crate) fn index_db_size(index_db: &str) -> u64 {
+    let paths = index_storage_paths_unchecked(index_db);
+    [paths.index_db_file, paths.storage_db_file]
+        .iter()
+        .flat_map(|file| {
+            let wal = PathBuf::from(format!("{}-wal", file.to_string_lossy()));
+            [file.clone(), wal]
+        })
+        .filter_map(|file| std::fs::metadata(file).ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+pub(crate) async fn add_db_maintenance_run(
+    conn: &mut sqlx::SqliteConnection,
+    run: &NewDbMaintenanceRun,
+) -> ApiResult<i64> {
+    let result = sqlx::query(
+        r#"
+INSERT INTO db_maintenance_runs
+    (start_time, end_time, trigger, vacuum_mode, vacuumed, skipped_reason,
+     error, size_before, size_after, duration_secs)
+VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+        "#,
+    )
+    .bind(&run.start_time)
+    .bind(&run.end_time)
+    .bind(run.trigger)
+    .bind(run.vacuum_mode)
+    .bind(run.vacuumed)
+    .bind(&run.skipped_reason)
+    .bind(&run.error)
+    .bind(run.size_before)
+    .bind(run.size_after)
+    .bind(run.duration_secs)
+    .execute(&mut *conn)
+    .await
+    .map_err(internal("Failed to record maintenance run"))?;
+    Ok(result.last_insert_rowid())
+}
+
+/// Maintenance runs, newest first.
+pub(crate) async fn get_db_maintenance_runs(
+    conn: &mut sqlx::SqliteConnection,
+    limit: i64,
+) -> ApiResult<Vec<DbMaintenanceRun>> {
+    let rows = sqlx::query(
+        r#"
+SELECT
+    id, start_time, end_time, trigger, vacuum_mode, vacuumed, skipped_reason,
+    error, size_before, size_after, duration_secs
+FROM db_maintenance_runs
+ORDER BY id DESC
+LIMIT ?1
+        "#,
+    )
+    .bind(limit)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(internal("Failed to get maintenance runs"))?;
+    rows.iter()
+        .map(|row| {
+            Ok(DbMaintenanceRun {
+                id: row.try_get("id")?,
+                start_time: row.try_get("start_time")?,
+                end_time: row.try_get("end_time")?,
+                trigger: row.try_get("trigger")?,
+                vacuum_mode: row.try_get("vacuum_mode")?,
+                vacuumed: row.try_get("vacuumed")?,
+                skipped_reason: row.try_get("skipped_reason")?,
+                error: row.try_get("error")?,
+                size_before: row.try_get("size_before")?,
+                size_after: row.try_get("size_after")?,
+                duration_secs: row.try_get("duration_secs")?,
+            })
+        })
+        .collect::<Result<Vec<_>, sqlx::Error>>()
+        .map_err(internal("Failed to get maintenance runs"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::migrations::setup_test_databases;
+
+    // Ensures recorded runs round-trip and list newest first.
+    #[tokio::test]
+    async fn maintenance_runs_round_trip() {
+        let mut dbs = setup_test_databases().await;
+        let conn = &mut dbs.index_conn;
+        let mut run = NewDbMaintenanceRun {
+            start_time: "2026-10-17T03:00:00".to_string(),
+            end_time: "2026-10-17T03:00:05".to_string(),
+            trigger: "scheduled",
+            vacuum_mode: "full",
+            vacuumed: false,
+            skipped_reason: Some("not enough free disk space".to_string()),
+            error: None,
+            size_before: 1000,
+            size_after: 900,
+            duration_secs: 5.0,
+        };
+        let first = add_db_maintenance_run(conn, &run).await.unwrap();
+        run.trigger = "manual";
+        run.vacuumed = true;
+        run.skipped_reason = None;
+        let second = add_db_maintenance_run(conn, &run).await.unwrap();
+
+        let runs = get_db_maintenance_runs(conn, 10).await.unwrap();
+        assert_eq!(
+            runs.iter().map(|run| run.id).collect::<Vec<_>>(),
+            vec![second, first]
+        );
+        assert_eq!(runs[0].trigger, "manual");
+        assert!(runs[0].vacuumed);
+        assert_eq!(
+            runs[1].skipped_reason.as_deref(),
+            Some("not enough free disk space")
+        );
+        assert_eq!(runs[1].size_before, 1000);
+        assert_eq!(runs[1].size_after, 900);
+    }
+}
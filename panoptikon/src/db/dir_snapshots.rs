@@ -0,0 +1,276 @@
+//! Per-directory snapshots behind `skip_unchanged_dirs` (see
+//! `jobs::dir_snapshots`). A snapshot also keeps how many available indexed
+//! files sat directly in the directory when it was taken; one whose count no
+//! longer matches the `files` table (a file row was deleted or marked
+//! unavailable since) is not loaded, so the directory is scanned in full.
+
+use std::collections::HashMap;
+
+use sqlx::Row;
+
+use crate::api_error::ApiError;
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+/// A directory as the last scan found it. `path` ends with a separator.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct DirSnapshot {
+    pub path: String,
+    pub mtime_ns: i64,
+    pub child_count: i64,
+}
+
+/// Directory of every `files` row, with its trailing separator.
+const FILE_DIR: &str = "substr(path, 1, length(path) - length(filename))";
+
+/// Snapshots under `root` taken with `config_hash` whose indexed file count
+/// still holds, keyed by path, as `(mtime_ns, child_count)`.
+pub(crate) async fn get_dir_snapshots(
+    conn: &mut sqlx::SqliteConnection,
+    root: &str,
+    config_hash: &str,
+) -> ApiResult<HashMap<String, (i64, i64)>> {
+    let sql = format!(
+        r#"
+WITH counts AS (
+    SELECT {FILE_DIR} AS dir, COUNT(*) AS files
+    FROM files
+    WHERE available = TRUE
+    AND path LIKE ?1 || '%'
+    GROUP BY dir
+)
+SELECT snapshots.path, snapshots.mtime_ns, snapshots.child_count
+FROM dir_snapshots AS snapshots
+LEFT JOIN counts ON counts.dir = snapshots.path
+WHERE snapshots.path LIKE ?1 || '%'
+AND snapshots.config_hash = ?2
+AND snapshots.file_count = COALESCE(counts.files, 0)
+        "#
+    );
+    let rows = sqlx::query(sqlx::AssertSqlSafe(sql.as_str()))
+        .bind(root)
+        .bind(config_hash)
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|err| {
+            tracing::error!(error = %err, root, "failed to load directory snapshots");
+            ApiError::internal("Failed to load directory snapshots")
+        })?;
+    rows.into_iter()
+        .map(|row| {
+            let path: String = row.try_get("path")?;
+            let mtime_ns: i64 = row.try_get("mtime_ns")?;
+            let child_count: i64 = row.try_get("child_count")?;
+            Ok((path, (mtime_ns, child_count)))
+        })
+        .collect::<Result<_, sqlx::Error>>()
+        .map_err(|err| {
+            tracing::error!(error = %err, "failed to read directory snapshot");
+            ApiError::internal("Failed to load directory snapshots")
+        })
+}
+
+/// Moves the available files directly inside `dirs` (all under `root`) to
+/// `scan_id`, as if the scan had found them unchanged. Returns how many.
+pub(crate) async fn adopt_unchanged_dir_files(
+    conn: &mut sqlx::SqliteConnection,
+    scan_id: i64,
+    root: &str,
+    dirs: &[String],
+) -> ApiResult<i64> {
+    if dirs.is_empty() {
+        return Ok(0);
+    }
+    let dirs_json = serde_json::to_string(dirs)
+        .map_err(|err| ApiError::internal(format!("Failed to encode directories: {err}")))?;
+    let sql = format!(
+        r#"
+UPDATE files
+SET scan_id = ?1
+WHERE available = TRUE
+AND scan_id != ?1
+AND path LIKE ?2 || '%'
+AND {FILE_DIR} IN (SELECT value FROM json_each(?3))
+        "#
+    );
+    let result = sqlx::query(sqlx::AssertSqlSafe(sql.as_str()))
+        .bind(scan_id)
+        .bind(root)
+        .bind(dirs_json)
+        .execute(&mut *conn)
+        .await
+        .map_err(|err| {
+            tracing::error!(error = %err, scan_id, "failed to adopt files of unchanged directories");
+            ApiError::internal("Failed to adopt files of unchanged directories")
+        })?;
+    Ok(result.rows_affected() as i64)
+}
+
+/// Replaces every snapshot under `root` with `snapshots`, recording each
+/// one's current count of available indexed files. An empty list just
+/// clears them.
+pub(crate) async fn replace_dir_snapshots(
+    conn: &mut sqlx::SqliteConnection,
+    root: &str,
+    config_hash: &str,
+    snapshots: &[DirSnapshot],
+) -> ApiResult<()> {
+    let map_err = |err: sqlx::Error| {
+        tracing::error!(error = %err, root, "failed to store directory snapshots");
+        ApiError::internal("Failed to store directory snapshots")
+    };
+    sqlx::query("DELETE FROM dir_snapshots WHERE path LIKE ?1 || '%'")
+        .bind(root)
+        .execute(&mut *conn)
+        .await
+        .map_err(map_err)?;
+    if snapshots.is_empty() {
+        return Ok(());
+    }
+    for snapshot in snapshots {
+        sqlx::query(
+            r#"
+INSERT OR REPLACE INTO dir_snapshots (path, mtime_ns, child_count, config_hash, file_count)
+VALUES (?1, ?2, ?3, ?4, 0)
+            "#,
+        )
+        .bind(&snapshot.path)
+        .bind(snapshot.mtime_ns)
+        .bind(snapshot.child_count)
+        .bind(config_hash)
+        .execute(&mut *conn)
+        .await
+        .map_err(map_err)?;
+    }
+    let sql = format!(
+        r#"
+UPDATE dir_snapshots
+SET file_count = counts.files
+FROM (
+    SELECT {FILE_DIR} AS dir, COUNT(*) AS files
+    FROM files
+    WHERE available = TRUE
+    AND path LIKE ?1 || '%'
+    GROUP BY dir
+) AS counts
+WHERE counts.dir = dir_snapshots.path
+        "#
+    );
+    sqlx::query(sqlx::AssertSqlSafe(sql.as_str()))
+        .bind(root)
+        .execute(&mut *conn)
+        .await
+        .map_err(map_err)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::file_scans::add_file_scan;
+    use crate::db::migrations::setup_test_databases;
+
+    fn snapshot(path: &str, mtime_ns: i64, child_count: i64) -> DirSnapshot {
+        DirSnapshot {
+            path: path.to_string(),
+            mtime_ns,
+            child_count,
+        }
+    }
+
+    // Snapshots come back only for their settings digest and while the
+    // directory's indexed file count is unchanged; adopting a directory
+    // moves only its own available files to the new scan.
+    #[tokio::test]
+    async fn snapshots_round_trip_and_adopt_direct_files() {
+        let mut dbs = setup_test_databases().await;
+        let old_scan = add_file_scan(&mut dbs.index_conn, "2024-01-01T00:00:00", "/data/")
+            .await
+            .unwrap();
+        let new_scan = add_file_scan(&mut dbs.index_conn, "2024-02-01T00:00:00", "/data/")
+            .await
+            .unwrap();
+        sqlx::query(
+            r#"
+INSERT INTO items (id, sha256, md5, type, time_added)
+VALUES (1, 'sha_one', 'md5_one', 'image/png', '2024-01-01T00:00:00');
+INSERT INTO files (sha256, item_id, path, filename, last_modified, scan_id, available)
+VALUES
+    ('sha_one', 1, '/data/a/one.png', 'one.png', '2024-01-01T00:00:00', ?1, 1),
+    ('sha_one', 1, '/data/a/two.png', 'two.png', '2024-01-01T00:00:00', ?1, 1),
+    ('sha_one', 1, '/data/a/b/three.png', 'three.png', '2024-01-01T00:00:00', ?1, 1)
+            "#,
+        )
+        .bind(old_scan)
+        .execute(&mut dbs.index_conn)
+        .await
+        .unwrap();
+
+        replace_dir_snapshots(
+            &mut dbs.index_conn,
+            "/data/",
+            "hash",
+            &[
+                snapshot("/data/", 1, 1),
+                snapshot("/data/a/", 2, 3),
+                snapshot("/data/a/b/", 3, 1),
+            ],
+        )
+        .await
+        .unwrap();
+        let loaded = get_dir_snapshots(&mut dbs.index_conn, "/data/", "hash")
+            .await
+            .unwrap();
+        assert_eq!(loaded.len(), 3);
+        assert_eq!(loaded["/data/a/"], (2, 3));
+        assert!(
+            get_dir_snapshots(&mut dbs.index_conn, "/data/", "other")
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        let adopted = adopt_unchanged_dir_files(
+            &mut dbs.index_conn,
+            new_scan,
+            "/data/",
+            &["/data/a/".into()],
+        )
+        .await
+        .unwrap();
+        assert_eq!(adopted, 2);
+        let rows: Vec<(String, i64)> =
+            sqlx::query_as("SELECT filename, scan_id FROM files ORDER BY filename")
+                .fetch_all(&mut dbs.index_conn)
+                .await
+                .unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                ("one.png".to_string(), new_scan),
+                ("three.png".to_string(), old_scan),
+                ("two.png".to_string(), new_scan),
+            ]
+        );
+
+        sqlx::query("UPDATE files SET available = FALSE WHERE filename = 'two.png'")
+            .execute(&mut dbs.index_conn)
+            .await
+            .unwrap();
+        let loaded = get_dir_snapshots(&mut dbs.index_conn, "/data/", "hash")
+            .await
+            .unwrap();
+        assert!(!loaded.contains_key("/data/a/"));
+        assert!(loaded.contains_key("/data/a/b/"));
+
+        replace_dir_snapshots(&mut dbs.index_conn, "/data/", "hash", &[])
+            .await
+            .unwrap();
+        assert!(
+            get_dir_snapshots(&mut dbs.index_conn, "/data/", "hash")
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,384 @@
+use std::collections::HashSet;
+
+use sqlx::Row;
+
+use crate::api_error::ApiError;
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+/// Rows read per query by the export id scan and blob reads. Bounds both the
+/// bind-parameter count of the blob reads and the memory held per chunk.
+pub(crate) const EXPORT_CHUNK_SIZE: usize = 500;
+
+/// One stored embedding, as the raw f32 little-endian blob.
+pub(crate) struct StoredEmbedding {
+    pub sha256: String,
+    pub idx: i64,
+    /// The item_data row this embedding was derived from (e.g. the extracted
+    /// text row for text embeddings); None for embeddings of the item itself.
+    pub source_data_id: Option<i64>,
+    pub embedding: Vec<u8>,
+}
+
+fn stored_embedding(row: &sqlx::sqlite::SqliteRow) -> Result<StoredEmbedding, sqlx::Error> {
+    Ok(StoredEmbedding {
+        sha256: row.try_get("sha256")?,
+        idx: row.try_get("idx")?,
+        source_data_id: row.try_get("source_id")?,
+        embedding: row.try_get("embedding")?,
+    })
+}
+
+/// Every embedding `setter_name` stored for the item, origin embeddings
+/// first, then by index.
+pub(crate) async fn get_item_embeddings(
+    conn: &mut sqlx::SqliteConnection,
+    sha256: &str,
+    setter_name: &str,
+) -> ApiResult<Vec<StoredEmbedding>> {
+    let rows = sqlx::query(
+        r#"
+SELECT items.sha256, item_data.idx, item_data.source_id, embeddings.embedding
+FROM items
+JOIN item_data ON item_data.item_id = items.id
+JOIN setters ON setters.id = item_data.setter_id
+JOIN embeddings ON embeddings.id = item_data.id
+WHERE items.sha256 = ?1 AND setters.name = ?2
+ORDER BY item_data.source_id IS NOT NULL, item_data.source_id, item_data.idx
+        "#,
+    )
+    .bind(sha256)
+    .bind(setter_name)
+    .fetch_all(&mut *conn)
+    .await
+    .and_then(|rows| rows.iter().map(stored_embedding).collect());
+    rows.map_err(|err| {
+        tracing::error!(error = %err, "failed to read item embeddings");
+        ApiError::internal("Failed to read embeddings")
+    })
+}
+
+/// The embeddings `setter_name` stored for each of `item_ids`, read in one
+/// query as `(item_id, blob)` pairs ordered by item, then index. An item's
+/// own embeddings are returned when it has any, and its derived ones (e.g.
+/// embeddings of its extracted text) only otherwise.
+pub(crate) async fn get_embeddings_for_items(
+    conn: &mut sqlx::SqliteConnection,
+    item_ids: &[i64],
+    setter_name: &str,
+) -> ApiResult<Vec<(i64, Vec<u8>)>> {
+    if item_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let placeholders = vec!["?"; item_ids.len()].join(", ");
+    let sql = format!(
+        r#"
+SELECT item_data.item_id, embeddings.embedding
+FROM item_data
+JOIN setters ON setters.id = item_data.setter_id
+JOIN embeddings ON embeddings.id = item_data.id
+WHERE setters.name = ?
+  AND item_data.item_id IN ({placeholders})
+  AND (
+    item_data.source_id IS NULL
+    OR NOT EXISTS (
+      SELECT 1
+      FROM item_data AS origin
+      JOIN embeddings AS origin_embedding ON origin_embedding.id = origin.id
+      WHERE origin.item_id = item_data.item_id
+        AND origin.setter_id = item_data.setter_id
+        AND origin.source_id IS NULL
+    )
+  )
+ORDER BY item_data.item_id, item_data.source_id, item_data.idx
+        "#
+    );
+    let mut query = sqlx::query_as(sqlx::AssertSqlSafe(sql)).bind(setter_name);
+    for item_id in item_ids {
+        query = query.bind(item_id);
+    }
+    query.fetch_all(&mut *conn).await.map_err(|err| {
+        tracing::error!(error = %err, "failed to read item embeddings");
+        ApiError::internal("Failed to read embeddings")
+    })
+}
+
+/// One page of an embedding export: the data ids to stream, in id order, and
+/// the cursor for the next page when the row cap cut the page short.
+#[derive(Debug, PartialEq)]
+pub(crate) struct ExportPage {
+    pub data_ids: Vec<i64>,
+    pub next_cursor: Option<i64>,
+}
+
+/// Selects up to `limit` embeddings of `setter_name` with a data id above
+/// `after`, restricted to `items` when given. Reads ids only, in
+/// `EXPORT_CHUNK_SIZE` chunks, so a selective item filter never loads the
+/// blobs it skips.
+pub(crate) async fn select_export_page(
+    conn: &mut sqlx::SqliteConnection,
+    setter_name: &str,
+    items: Option<&HashSet<i64>>,
+    after: i64,
+    limit: usize,
+) -> ApiResult<ExportPage> {
+    let mut data_ids = Vec::new();
+    let mut cursor = after;
+    // One extra id tells whether anything is left past the cap.
+    while data_ids.len() <= limit {
+        let rows: Vec<(i64, i64)> = sqlx::query_as(
+            r#"
+SELECT item_data.id, item_data.item_id
+FROM item_data
+JOIN setters ON setters.id = item_data.setter_id
+JOIN embeddings ON embeddings.id = item_data.id
+WHERE setters.name = ?1 AND item_data.id > ?2
+ORDER BY item_data.id
+LIMIT ?3
+            "#,
+        )
+        .bind(setter_name)
+        .bind(cursor)
+        .bind(EXPORT_CHUNK_SIZE as i64)
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|err| {
+            tracing::error!(error = %err, "failed to select embeddings to export");
+            ApiError::internal("Failed to read embeddings")
+        })?;
+        let Some(&(last, _)) = rows.last() else {
+            break;
+        };
+        cursor = last;
+        data_ids.extend(
+            rows.into_iter()
+                .filter(|(_, item_id)| items.is_none_or(|items| items.contains(item_id)))
+                .map(|(data_id, _)| data_id),
+        );
+    }
+    let next_cursor = if data_ids.len() > limit {
+        data_ids.truncate(limit);
+        data_ids.last().copied()
+    } else {
+        None
+    };
+    Ok(ExportPage {
+        data_ids,
+        next_cursor,
+    })
+}
+
+/// The embeddings behind `data_ids` (at most `EXPORT_CHUNK_SIZE`), in data
+/// id order.
+pub(crate) async fn get_embeddings_by_ids(
+    conn: &mut sqlx::SqliteConnection,
+    data_ids: &[i64],
+) -> ApiResult<Vec<StoredEmbedding>> {
+    if data_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let placeholders = vec!["?"; data_ids.len()].join(", ");
+    let sql = format!(
+        r#"
+SELECT items.sha256, item_data.idx, item_data.source_id, embeddings.embedding
+FROM item_data
+JOIN items ON items.id = item_data.item_id
+JOIN embeddings ON embeddings.id = item_data.id
+WHERE item_data.id IN ({placeholders})
+ORDER BY item_data.id
+        "#
+    );
+    let mut query = sqlx::query(sqlx::AssertSqlSafe(sql));
+    for data_id in data_ids {
+        query = query.bind(data_id);
+    }
+    query
+        .fetch_all(&mut *conn)
+        .await
+        .and_then(|rows| rows.iter().map(stored_embedding).collect())
+        .map_err(|err| {
+            tracing::error!(error = %err, "failed to read embeddings to export");
+            ApiError::internal("Failed to read embeddings")
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::SqliteConnection;
+
+    use super::*;
+    use crate::db::migrations::setup_test_databases;
+    use crate::db::sql_functions::ensure_sqlite_extensions;
+    use crate::pql::embedding_utils::{deserialize_f32, serialize_f32};
+
+    async fn seed_item(conn: &mut SqliteConnection, sha: &str) -> i64 {
+        sqlx::query(
+            "INSERT INTO items (sha256, md5, type, time_added) VALUES (?, ?, 'image/png', '2026-01-01') RETURNING id",
+        )
+        .bind(sha)
+        .bind(sha)
+        .fetch_one(&mut *conn)
+        .await
+        .expect("insert item")
+        .try_get::<i64, _>("id")
+        .expect("item id")
+    }
+
+    async fn seed_embedding(
+        conn: &mut SqliteConnection,
+        item_id: i64,
+        setter_id: i64,
+        idx: i64,
+        vector: &[f32],
+    ) -> i64 {
+        let data_id = sqlx::query(
+            "INSERT INTO item_data (item_id, setter_id, data_type, idx, is_origin, is_placeholder) \
+             VALUES (?, ?, 'clip', ?, 1, 0) RETURNING id",
+        )
+        .bind(item_id)
+        .bind(setter_id)
+        .bind(idx)
+        .fetch_one(&mut *conn)
+        .await
+        .expect("insert item_data")
+        .try_get::<i64, _>("id")
+        .expect("data id");
+        sqlx::query("INSERT INTO embeddings (id, embedding) VALUES (?, ?)")
+            .bind(data_id)
+            .bind(serialize_f32(vector))
+            .execute(&mut *conn)
+            .await
+            .expect("insert embedding");
+        data_id
+    }
+
+    // Ensures item reads return the stored vectors in index order and only
+    // for the requested setter.
+    #[tokio::test]
+    async fn item_embeddings_are_scoped_to_the_setter() {
+        ensure_sqlite_extensions().expect("sqlite extensions");
+        let mut dbs = setup_test_databases().await;
+        let conn = &mut dbs.index_conn;
+        let item = seed_item(conn, "aa").await;
+        sqlx::query("INSERT INTO setters (id, name) VALUES (1, 'clip/a'), (2, 'clip/b')")
+            .execute(&mut *conn)
+            .await
+            .expect("insert setters");
+        seed_embedding(conn, item, 1, 1, &[3.0, 4.0]).await;
+        seed_embedding(conn, item, 1, 0, &[1.0, 2.0]).await;
+        seed_embedding(conn, item, 2, 0, &[9.0, 9.0]).await;
+
+        let stored = get_item_embeddings(conn, "aa", "clip/a").await.unwrap();
+        let vectors: Vec<_> = stored
+            .iter()
+            .map(|row| (row.idx, deserialize_f32(&row.embedding).unwrap()))
+            .collect();
+        assert_eq!(vectors, vec![(0, vec![1.0, 2.0]), (1, vec![3.0, 4.0])]);
+        assert!(stored.iter().all(|row| row.source_data_id.is_none()));
+        assert!(
+            get_item_embeddings(conn, "bb", "clip/a")
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    // Ensures the batch read returns every requested item's embeddings in
+    // one pass, scoped to the setter, and prefers an item's own embeddings
+    // over ones derived from its text.
+    #[tokio::test]
+    async fn item_batch_prefers_origin_embeddings() {
+        ensure_sqlite_extensions().expect("sqlite extensions");
+        let mut dbs = setup_test_databases().await;
+        let conn = &mut dbs.index_conn;
+        let image = seed_item(conn, "aa").await;
+        let document = seed_item(conn, "bb").await;
+        let unrelated = seed_item(conn, "cc").await;
+        sqlx::query("INSERT INTO setters (id, name) VALUES (1, 'clip/a'), (2, 'clip/b')")
+            .execute(&mut *conn)
+            .await
+            .expect("insert setters");
+        seed_embedding(conn, image, 1, 1, &[2.0]).await;
+        seed_embedding(conn, image, 1, 0, &[1.0]).await;
+        seed_embedding(conn, image, 2, 0, &[9.0]).await;
+        seed_embedding(conn, unrelated, 1, 0, &[7.0]).await;
+        let text = seed_embedding(conn, document, 1, 0, &[0.0]).await;
+        let derived = seed_embedding(conn, image, 1, 2, &[5.0]).await;
+        let document_text = seed_embedding(conn, document, 1, 1, &[3.0]).await;
+        sqlx::query("UPDATE item_data SET source_id = ?1, is_origin = NULL WHERE id IN (?2, ?3)")
+            .bind(text)
+            .bind(derived)
+            .bind(document_text)
+            .execute(&mut *conn)
+            .await
+            .expect("mark derived");
+
+        let rows = get_embeddings_for_items(conn, &[image, document], "clip/a")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(item_id, blob)| (item_id, deserialize_f32(&blob).unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rows,
+            vec![
+                (image, vec![1.0]),
+                (image, vec![2.0]),
+                (document, vec![0.0]),
+            ]
+        );
+    }
+
+    // Ensures export pages honor the item filter, stop at the row cap with a
+    // cursor that resumes exactly where the page ended, and span chunks.
+    #[tokio::test]
+    async fn export_pages_resume_from_the_cursor() {
+        ensure_sqlite_extensions().expect("sqlite extensions");
+        let mut dbs = setup_test_databases().await;
+        let conn = &mut dbs.index_conn;
+        let kept = seed_item(conn, "aa").await;
+        let skipped = seed_item(conn, "bb").await;
+        sqlx::query("INSERT INTO setters (id, name) VALUES (1, 'clip/a')")
+            .execute(&mut *conn)
+            .await
+            .expect("insert setter");
+        let mut expected = Vec::new();
+        for idx in 0..EXPORT_CHUNK_SIZE as i64 + 10 {
+            expected.push(seed_embedding(conn, kept, 1, idx, &[idx as f32]).await);
+            seed_embedding(conn, skipped, 1, idx, &[0.0]).await;
+        }
+        let items = HashSet::from([kept]);
+
+        let first = select_export_page(conn, "clip/a", Some(&items), 0, EXPORT_CHUNK_SIZE)
+            .await
+            .unwrap();
+        assert_eq!(first.data_ids, expected[..EXPORT_CHUNK_SIZE]);
+        assert_eq!(first.next_cursor, first.data_ids.last().copied());
+
+        let rest = select_export_page(
+            conn,
+            "clip/a",
+            Some(&items),
+            first.next_cursor.unwrap(),
+            EXPORT_CHUNK_SIZE,
+        )
+        .await
+        .unwrap();
+        assert_eq!(rest.data_ids, expected[EXPORT_CHUNK_SIZE..]);
+        assert_eq!(rest.next_cursor, None);
+
+        let rows = get_embeddings_by_ids(conn, &rest.data_ids[..2])
+            .await
+            .unwrap();
+        assert_eq!(rows[0].sha256, "aa");
+        assert_eq!(
+            deserialize_f32(&rows[1].embedding).unwrap(),
+            vec![EXPORT_CHUNK_SIZE as f32 + 1.0]
+        );
+
+        let unfiltered = select_export_page(conn, "clip/a", None, 0, 3)
+            .await
+            .unwrap();
+        assert_eq!(unfiltered.data_ids.len(), 3);
+    }
+}
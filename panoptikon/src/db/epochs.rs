@@ -6,6 +6,11 @@
 //! they were built under and are re-validated on read; a mismatch means the
 //! underlying data may have changed since the entry was stored.
 //!
+//! A third, coarser counter per index DB guards the tag autocomplete index
+//! (`api::tag_index`): the writer bumps it only for messages that change
+//! tags or their counts, and extraction jobs once when they finish rather
+//! than per written batch.
+//!
 //! The counters live here in global maps — not inside the writer actor,
 //! which spins down when idle — and reset with the process, together with
 //! the (also in-memory) cache they guard.
@@ -24,6 +29,7 @@ type EpochMap = Mutex<HashMap<String, Arc<AtomicU64>>>;
 
 static INDEX_EPOCHS: OnceLock<EpochMap> = OnceLock::new();
 static USER_DATA_EPOCHS: OnceLock<EpochMap> = OnceLock::new();
+static TAG_EPOCHS: OnceLock<EpochMap> = OnceLock::new();
 
 fn counter(map: &'static OnceLock<EpochMap>, db: &str) -> Arc<AtomicU64> {
     let map = map.get_or_init(|| Mutex::new(HashMap::new()));
@@ -51,6 +57,14 @@ pub(crate) fn bump_user_data_epoch(user_data_db: &str) {
     counter(&USER_DATA_EPOCHS, user_data_db).fetch_add(1, Ordering::AcqRel);
 }
 
+pub(crate) fn tag_epoch(index_db: &str) -> u64 {
+    counter(&TAG_EPOCHS, index_db).load(Ordering::Acquire)
+}
+
+pub(crate) fn bump_tag_epoch(index_db: &str) {
+    counter(&TAG_EPOCHS, index_db).fetch_add(1, Ordering::AcqRel);
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -65,5 +79,8 @@ mod tests {
         assert_eq!(index_epoch("epoch-test-a"), 2);
         assert_eq!(user_data_epoch("epoch-test-a"), 1);
         assert_eq!(index_epoch("epoch-test-b"), 0);
+        bump_tag_epoch("epoch-test-a");
+        assert_eq!(tag_epoch("epoch-test-a"), 1);
+        assert_eq!(index_epoch("epoch-test-a"), 2);
     }
 }
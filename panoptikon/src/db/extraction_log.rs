@@ -1,4 +1,6 @@
-use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use sqlx::Row;
 use utoipa::ToSchema;
 
@@ -73,6 +75,55 @@ pub(crate) async fn get_search_embedding_setters(
     ))
 }
 
+/// What an extraction run actually ran with, after merging the model's
+/// metadata defaults, `job_settings` group and model overrides, and the
+/// request's own values.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub(crate) struct ExtractionParameters {
+    pub batch_size: i64,
+    /// None lets the inference side apply its own fallback.
+    pub threshold: Option<f64>,
+    pub input_handler: String,
+    #[schema(value_type = Object)]
+    pub input_handler_opts: serde_json::Map<String, Value>,
+    pub model: ExtractionModelSnapshot,
+}
+
+/// How many items an extraction run matches, and how many each of its
+/// filters keeps out: the items that would also match without that one
+/// filter, every other filter still applied. A filter that excludes the
+/// whole library shows up as a zero `total` with a large exclusion.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub(crate) struct ExtractionFilterCounts {
+    /// Items matched with every filter applied (`total_remaining`).
+    pub total: i64,
+    /// Kept out by the model's `input_mime_types`.
+    pub excluded_by_mime_type: i64,
+    /// One entry per system config `job_filters` entry that applies to the
+    /// model, in config order.
+    pub excluded_by_job_filters: Vec<JobFilterExclusion>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub(crate) struct JobFilterExclusion {
+    /// Position of the filter in the system config's `job_filters`.
+    pub index: usize,
+    pub setter_names: Vec<String>,
+    pub excluded: i64,
+}
+
+/// The model's metadata as the inference server reported it for the run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub(crate) struct ExtractionModelSnapshot {
+    pub group: String,
+    pub name: Option<String>,
+    pub output_type: String,
+    pub target_entities: Vec<String>,
+    pub input_mime_types: Vec<String>,
+    pub default_batch_size: i64,
+    pub default_threshold: Option<f64>,
+}
+
 #[derive(Debug, Serialize, ToSchema)]
 pub(crate) struct LogRecord {
     pub id: i64,
@@ -84,17 +135,51 @@ pub(crate) struct LogRecord {
     pub setter: String,
     pub threshold: Option<f64>,
     pub batch_size: i64,
+    /// Run in reprocess mode: processed items were included and their earlier
+    /// data for the setter replaced.
+    pub reprocess: bool,
     pub image_files: i64,
     pub video_files: i64,
     pub other_files: i64,
     pub total_segments: i64,
     pub errors: i64,
+    /// Errors where ffmpeg/ffprobe was killed at its timeout.
+    pub timeouts: i64,
+    /// Errors where no frame of a video could be decoded, even by the
+    /// fallbacks.
+    pub undecodable: i64,
+    /// Stored video frames deleted afterwards by frame retention.
+    pub frames_dropped: i64,
     pub total_remaining: i64,
     pub data_load_time: f64,
     pub inference_time: f64,
     pub failed: i64,
     pub completed: i64,
     pub status: Option<i64>,
+    /// Effective run parameters; None for tag imports, tag rethresholds and
+    /// for runs logged before parameters were recorded.
+    pub parameters: Option<ExtractionParameters>,
+    /// Items the run matched and how many its filters kept out; None for
+    /// tag imports, tag rethresholds and for runs logged before this was
+    /// recorded.
+    pub filter_counts: Option<ExtractionFilterCounts>,
+}
+
+/// A stored JSON blob that no longer parses is reported as absent rather
+/// than failing the whole history.
+fn parse_json_column<T: DeserializeOwned>(
+    row: &sqlx::sqlite::SqliteRow,
+    column: &str,
+) -> ApiResult<Option<T>> {
+    let raw: Option<String> = row.try_get(column).map_err(|err| {
+        tracing::error!(error = %err, column, "failed to read data log column");
+        ApiError::internal("Failed to get data logs")
+    })?;
+    Ok(raw.and_then(|raw| {
+        serde_json::from_str(&raw)
+            .inspect_err(|err| tracing::warn!(error = %err, column, "unreadable data log column"))
+            .ok()
+    }))
 }
 
 pub(crate) async fn get_all_data_logs(
@@ -119,11 +204,15 @@ pub(crate) async fn get_all_data_logs(
             setter,
             threshold,
             batch_size,
+            reprocess,
             image_files,
             video_files,
             other_files,
             total_segments,
             errors,
+            timeouts,
+            undecodable,
+            frames_dropped,
             total_remaining,
             data_load_time,
             inference_time,
@@ -133,7 +222,9 @@ pub(crate) async fn get_all_data_logs(
                 ELSE 0
             END AS failed,
             data_log.completed,
-            data_jobs.completed AS status
+            data_jobs.completed AS status,
+            data_log.parameters,
+            data_log.filter_counts
         FROM data_log
         LEFT JOIN item_data 
             ON item_data.job_id = data_log.job_id
@@ -142,7 +233,8 @@ pub(crate) async fn get_all_data_logs(
         LEFT JOIN data_jobs
             ON data_log.job_id = data_jobs.id
         GROUP BY data_log.id
-        ORDER BY start_time DESC
+        -- The id tie-break keeps pages stable when logs share a start time.
+        ORDER BY start_time DESC, data_log.id DESC
         "#,
     );
     if page_size.is_some() {
@@ -200,6 +292,10 @@ pub(crate) async fn get_all_data_logs(
                 tracing::error!(error = %err, "failed to read data log batch size");
                 ApiError::internal("Failed to get data logs")
             })?,
+            reprocess: row.try_get("reprocess").map_err(|err| {
+                tracing::error!(error = %err, "failed to read data log reprocess");
+                ApiError::internal("Failed to get data logs")
+            })?,
             image_files: row.try_get("image_files").map_err(|err| {
                 tracing::error!(error = %err, "failed to read data log image files");
                 ApiError::internal("Failed to get data logs")
@@ -220,6 +316,18 @@ pub(crate) async fn get_all_data_logs(
                 tracing::error!(error = %err, "failed to read data log errors");
                 ApiError::internal("Failed to get data logs")
             })?,
+            timeouts: row.try_get("timeouts").map_err(|err| {
+                tracing::error!(error = %err, "failed to read data log timeouts");
+                ApiError::internal("Failed to get data logs")
+            })?,
+            undecodable: row.try_get("undecodable").map_err(|err| {
+                tracing::error!(error = %err, "failed to read data log undecodable");
+                ApiError::internal("Failed to get data logs")
+            })?,
+            frames_dropped: row.try_get("frames_dropped").map_err(|err| {
+                tracing::error!(error = %err, "failed to read data log frames dropped");
+                ApiError::internal("Failed to get data logs")
+            })?,
             total_remaining: row.try_get("total_remaining").map_err(|err| {
                 tracing::error!(error = %err, "failed to read data log remaining");
                 ApiError::internal("Failed to get data logs")
@@ -244,6 +352,8 @@ pub(crate) async fn get_all_data_logs(
                 tracing::error!(error = %err, "failed to read data log status");
                 ApiError::internal("Failed to get data logs")
             })?,
+            parameters: parse_json_column(&row, "parameters")?,
+            filter_counts: parse_json_column(&row, "filter_counts")?,
         });
     }
 
@@ -282,6 +392,163 @@ pub(crate) async fn delete_data_job_by_log_id(
     Ok(deleted)
 }
 
+/// Deletes up to `batch_size` extraction logs outside the retention limits
+/// and returns how many went. A log is out once it is past the newest
+/// `keep_last_per_setter` for its setter or started more than `max_age_days`
+/// ago. Logs of running jobs and logs whose job still owns item_data are
+/// never selected, so pruning cannot take extracted data with it; the
+/// data_jobs row of each pruned log is deleted along with it.
+pub(crate) async fn prune_data_logs(
+    conn: &mut sqlx::SqliteConnection,
+    keep_last_per_setter: Option<u32>,
+    max_age_days: Option<u32>,
+    batch_size: i64,
+) -> ApiResult<u64> {
+    let rows = sqlx::query(
+        r#"
+        SELECT id, job_id FROM (
+            SELECT
+                id,
+                job_id,
+                start_time,
+                completed,
+                ROW_NUMBER() OVER (
+                    PARTITION BY setter ORDER BY start_time DESC, id DESC
+                ) AS position
+            FROM data_log
+        ) ranked
+        WHERE (
+            (?1 IS NOT NULL AND position > ?1)
+            OR start_time < strftime('%Y-%m-%dT%H:%M:%S', 'now', 'localtime', ?2)
+        )
+        AND NOT (completed = 0 AND job_id IS NOT NULL)
+        AND NOT EXISTS (
+            SELECT 1 FROM item_data WHERE item_data.job_id = ranked.job_id
+        )
+        ORDER BY id
+        LIMIT ?3
+        "#,
+    )
+    .bind(keep_last_per_setter.map(i64::from))
+    .bind(max_age_days.map(|days| format!("-{days} days")))
+    .bind(batch_size)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|err| {
+        tracing::error!(error = %err, "failed to select data logs to prune");
+        ApiError::internal("Failed to prune data logs")
+    })?;
+
+    let mut deleted = 0;
+    for row in rows {
+        let read_err = |err: sqlx::Error| {
+            tracing::error!(error = %err, "failed to read data log to prune");
+            ApiError::internal("Failed to prune data logs")
+        };
+        let log_id: i64 = row.try_get("id").map_err(read_err)?;
+        let job_id: Option<i64> = row.try_get("job_id").map_err(read_err)?;
+        deleted += sqlx::query("DELETE FROM data_log WHERE id = ?")
+            .bind(log_id)
+            .execute(&mut *conn)
+            .await
+            .map_err(|err| {
+                tracing::error!(error = %err, "failed to delete data log");
+                ApiError::internal("Failed to prune data logs")
+            })?
+            .rows_affected();
+        if let Some(job_id) = job_id {
+            sqlx::query("DELETE FROM data_jobs WHERE id = ?")
+                .bind(job_id)
+                .execute(&mut *conn)
+                .await
+                .map_err(|err| {
+                    tracing::error!(error = %err, "failed to delete data job");
+                    ApiError::internal("Failed to prune data logs")
+                })?;
+        }
+    }
+
+    Ok(deleted)
+}
+
+/// Recent finished extraction runs of one setter against one entity type,
+/// summed so items / seconds weighs long runs more than short ones.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ExtractionThroughput {
+    pub setter: String,
+    /// First of the run's `target_entities` (`items` or `text`).
+    pub entity: Option<String>,
+    pub runs: i64,
+    /// Items attempted, errors included.
+    pub items: i64,
+    /// Wall-clock seconds from start to end of the runs.
+    pub seconds: f64,
+}
+
+/// Throughput of the newest `runs_per_setter` completed extraction runs of
+/// every (setter, entity type). Only runs that recorded their parameters
+/// count: tag imports and rethresholds do no inference, and older rows have
+/// no entity type.
+pub(crate) async fn get_extraction_throughput(
+    conn: &mut sqlx::SqliteConnection,
+    runs_per_setter: i64,
+) -> ApiResult<Vec<ExtractionThroughput>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT setter, entity, COUNT(*) AS runs, SUM(items) AS items, SUM(seconds) AS seconds
+        FROM (
+            SELECT
+                setter,
+                entity,
+                items,
+                seconds,
+                ROW_NUMBER() OVER (
+                    PARTITION BY setter, entity ORDER BY start_time DESC, id DESC
+                ) AS position
+            FROM (
+                SELECT
+                    id,
+                    setter,
+                    start_time,
+                    json_extract(parameters, '$.model.target_entities[0]') AS entity,
+                    image_files + video_files + other_files + errors AS items,
+                    (julianday(end_time) - julianday(start_time)) * 86400.0 AS seconds
+                FROM data_log
+                WHERE completed = 1 AND json_valid(parameters)
+            )
+            WHERE items > 0 AND seconds > 0
+        )
+        WHERE position <= ?
+        GROUP BY setter, entity
+        ORDER BY setter, entity
+        "#,
+    )
+    .bind(runs_per_setter)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|err| {
+        tracing::error!(error = %err, "failed to read extraction throughput");
+        ApiError::internal("Failed to get extraction throughput")
+    })?;
+
+    let mut results = Vec::with_capacity(rows.len());
+    for row in rows {
+        let read_err = |err: sqlx::Error| {
+            tracing::error!(error = %err, "failed to read extraction throughput");
+            ApiError::internal("Failed to get extraction throughput")
+        };
+        results.push(ExtractionThroughput {
+            setter: row.try_get("setter").map_err(read_err)?,
+            entity: row.try_get("entity").map_err(read_err)?,
+            runs: row.try_get("runs").map_err(read_err)?,
+            items: row.try_get("items").map_err(read_err)?,
+            seconds: row.try_get("seconds").map_err(read_err)?,
+        });
+    }
+
+    Ok(results)
+}
+
 /// Served to the scan page, which polls it. `idx_item_data_placeholder_setter_type`
 /// is what keeps it off the item_data heap: grouping on the bare `setter_id`
 /// column (rather than on the joined `setters.id`) is what lets the index
@@ -324,9 +591,137 @@ pub(crate) async fn get_setters_total_data(
     Ok(results)
 }
 
+/// One row of the `setters` table with the shape of its data.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct SetterSummary {
+    pub setter_name: String,
+    /// Distinct non-placeholder data types, sorted.
+    pub data_types: Vec<String>,
+    /// Non-placeholder item_data rows (the `/setters/total` count).
+    pub item_data_count: i64,
+    /// Whether any item_data row exists at all, placeholders included.
+    pub has_rows: bool,
+}
+
+/// Every setter, including ones with no item_data left. Each subquery is a
+/// per-setter index lookup (`idx_item_data_placeholder_setter_type`,
+/// `idx_item_data_setter_id`), so this never scans all of item_data.
+pub(crate) async fn get_setter_summaries(
+    conn: &mut sqlx::SqliteConnection,
+) -> ApiResult<Vec<SetterSummary>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT
+            s.name AS setter_name,
+            (
+                SELECT COUNT(*) FROM item_data
+                WHERE is_placeholder = 0 AND setter_id = s.id
+            ) AS item_data_count,
+            EXISTS (
+                SELECT 1 FROM item_data WHERE setter_id = s.id
+            ) AS has_rows,
+            (
+                SELECT GROUP_CONCAT(data_type, ',') FROM (
+                    SELECT DISTINCT data_type FROM item_data
+                    WHERE is_placeholder = 0 AND setter_id = s.id
+                    ORDER BY data_type
+                )
+            ) AS data_types
+        FROM setters s
+        ORDER BY s.name
+        "#,
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|err| {
+        tracing::error!(error = %err, "failed to read setter summaries");
+        ApiError::internal("Failed to get setters")
+    })?;
+
+    let mut results = Vec::with_capacity(rows.len());
+    for row in rows {
+        let read_err = |err: sqlx::Error| {
+            tracing::error!(error = %err, "failed to read setter summary");
+            ApiError::internal("Failed to get setters")
+        };
+        let data_types: Option<String> = row.try_get("data_types").map_err(read_err)?;
+        results.push(SetterSummary {
+            setter_name: row.try_get("setter_name").map_err(read_err)?,
+            data_types: data_types
+                .map(|joined| joined.split(',').map(str::to_string).collect())
+                .unwrap_or_default(),
+            item_data_count: row.try_get("item_data_count").map_err(read_err)?,
+            has_rows: row.try_get("has_rows").map_err(read_err)?,
+        });
+    }
+
+    Ok(results)
+}
+
+/// Embedding dimension bookkeeping of a setter (see
+/// `check_embedding_dimensions`).
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub(crate) struct SetterEmbeddingStats {
+    pub setter_name: String,
+    /// Dimension recorded from the setter's first stored embedding; null
+    /// until it stores one.
+    pub embedding_dim: Option<i64>,
+    /// Stored (non-placeholder) embeddings.
+    pub embedding_count: i64,
+    /// Embedding writes rejected for not matching `embedding_dim`.
+    pub rejected_embeddings: i64,
+}
+
+/// Setters that stored or tried to store embeddings. The count is a
+/// per-setter lookup on `idx_item_data_setter_data_type`.
+pub(crate) async fn get_setter_embedding_stats(
+    conn: &mut sqlx::SqliteConnection,
+) -> ApiResult<Vec<SetterEmbeddingStats>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT
+            s.name AS setter_name,
+            s.embedding_dim AS embedding_dim,
+            s.rejected_embeddings AS rejected_embeddings,
+            (
+                SELECT COUNT(*) FROM item_data
+                WHERE setter_id = s.id
+                  AND data_type IN ('clip', 'text-embedding')
+                  AND is_placeholder = 0
+            ) AS embedding_count
+        FROM setters s
+        WHERE s.embedding_dim IS NOT NULL OR s.rejected_embeddings > 0
+        ORDER BY s.name
+        "#,
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|err| {
+        tracing::error!(error = %err, "failed to read setter embedding stats");
+        ApiError::internal("Failed to get setters")
+    })?;
+
+    let mut results = Vec::with_capacity(rows.len());
+    for row in rows {
+        let read_err = |err: sqlx::Error| {
+            tracing::error!(error = %err, "failed to read setter embedding stats");
+            ApiError::internal("Failed to get setters")
+        };
+        results.push(SetterEmbeddingStats {
+            setter_name: row.try_get("setter_name").map_err(read_err)?,
+            embedding_dim: row.try_get("embedding_dim").map_err(read_err)?,
+            embedding_count: row.try_get("embedding_count").map_err(read_err)?,
+            rejected_embeddings: row.try_get("rejected_embeddings").map_err(read_err)?,
+        });
+    }
+
+    Ok(results)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::db::extraction_write::add_data_log;
     use crate::db::migrations::setup_test_databases;
 
     // Ensures distinct data_type/setter pairs are returned from the extraction log tables.
@@ -464,6 +859,106 @@ mod tests {
         assert_eq!(logs[0].status, Some(1));
     }
 
+    // Ensures the mode a job ran in is recorded and read back with its log.
+    #[tokio::test]
+    async fn data_logs_report_reprocess_mode() {
+        let mut dbs = setup_test_databases().await;
+        let conn = &mut dbs.index_conn;
+        let scan_time = "2024-01-01T00:00:00";
+        let types = ["tags".to_string()];
+        add_data_log(conn, scan_time, None, &types, "alpha", 8, false, None, None)
+            .await
+            .unwrap();
+        add_data_log(conn, scan_time, None, &types, "alpha", 8, true, None, None)
+            .await
+            .unwrap();
+        let logs = get_all_data_logs(conn, 1, None).await.unwrap();
+        let modes: Vec<_> = logs.iter().map(|log| log.reprocess).collect();
+        assert_eq!(modes, [true, false]);
+    }
+
+    // Ensures run parameters round-trip and older rows without them read as None.
+    #[tokio::test]
+    async fn data_logs_report_run_parameters() {
+        let mut dbs = setup_test_databases().await;
+        let conn = &mut dbs.index_conn;
+        let scan_time = "2024-01-01T00:00:00";
+        let types = ["tags".to_string()];
+        let parameters = ExtractionParameters {
+            batch_size: 16,
+            threshold: Some(0.1),
+            input_handler: "image_frames".to_string(),
+            input_handler_opts: serde_json::from_str(r#"{"max_frames": 4}"#).unwrap(),
+            model: ExtractionModelSnapshot {
+                group: "wd".to_string(),
+                name: Some("WD Tagger".to_string()),
+                output_type: "tags".to_string(),
+                target_entities: vec!["items".to_string()],
+                input_mime_types: vec!["image/".to_string()],
+                default_batch_size: 64,
+                default_threshold: Some(0.35),
+            },
+        };
+        let json = serde_json::to_string(&parameters).unwrap();
+        add_data_log(conn, scan_time, None, &types, "alpha", 8, false, None, None)
+            .await
+            .unwrap();
+        add_data_log(
+            conn,
+            scan_time,
+            Some(0.1),
+            &types,
+            "alpha",
+            16,
+            false,
+            Some(&json),
+            None,
+        )
+        .await
+        .unwrap();
+        let logs = get_all_data_logs(conn, 1, None).await.unwrap();
+        assert_eq!(logs[0].parameters.as_ref(), Some(&parameters));
+        assert!(logs[1].parameters.is_none());
+    }
+
+    // Ensures the filter breakdown round-trips and older rows read as None.
+    #[tokio::test]
+    async fn data_logs_report_filter_counts() {
+        let mut dbs = setup_test_databases().await;
+        let conn = &mut dbs.index_conn;
+        let scan_time = "2024-01-01T00:00:00";
+        let types = ["tags".to_string()];
+        let counts = ExtractionFilterCounts {
+            total: 3,
+            excluded_by_mime_type: 40,
+            excluded_by_job_filters: vec![JobFilterExclusion {
+                index: 1,
+                setter_names: vec!["*".to_string()],
+                excluded: 7,
+            }],
+        };
+        let json = serde_json::to_string(&counts).unwrap();
+        add_data_log(conn, scan_time, None, &types, "alpha", 8, false, None, None)
+            .await
+            .unwrap();
+        add_data_log(
+            conn,
+            scan_time,
+            None,
+            &types,
+            "alpha",
+            8,
+            false,
+            None,
+            Some(&json),
+        )
+        .await
+        .unwrap();
+        let logs = get_all_data_logs(conn, 1, None).await.unwrap();
+        assert_eq!(logs[0].filter_counts.as_ref(), Some(&counts));
+        assert!(logs[1].filter_counts.is_none());
+    }
+
     // Ensures setter totals return counts per setter.
     #[tokio::test]
     async fn get_setters_total_data_returns_counts() {
@@ -553,4 +1048,246 @@ mod tests {
             .unwrap();
         assert_eq!(remaining.0, 0);
     }
+
+    // Ensures pruning keeps the newest logs per setter, logs of running jobs
+    // and logs whose job still owns item_data, and removes the rest with
+    // their data_jobs rows.
+    #[tokio::test]
+    async fn prune_data_logs_respects_retention() {
+        let mut dbs = setup_test_databases().await;
+        sqlx::query(
+            r#"
+            INSERT INTO items (id, sha256, md5, type, time_added)
+            VALUES (1, 'sha_1', 'md5_1', 'image/png', '2024-01-01T00:00:00')
+            "#,
+        )
+        .execute(&mut dbs.index_conn)
+        .await
+        .unwrap();
+        sqlx::query("INSERT INTO setters (id, name) VALUES (1, 'alpha')")
+            .execute(&mut dbs.index_conn)
+            .await
+            .unwrap();
+        sqlx::query(
+            "INSERT INTO data_jobs (id, completed) VALUES (1, 1), (2, 1), (3, 0), (4, 1), (5, 1)",
+        )
+        .execute(&mut dbs.index_conn)
+        .await
+        .unwrap();
+        sqlx::query(
+            r#"
+            INSERT INTO item_data (id, item_id, setter_id, data_type, idx, is_origin, job_id)
+            VALUES (10, 1, 1, 'tags', 0, 1, 2)
+            "#,
+        )
+        .execute(&mut dbs.index_conn)
+        .await
+        .unwrap();
+        // 1: old, no data left. 2: old, owns item_data. 3: still running.
+        // 4 and 5: the newest two.
+        sqlx::query(
+            r#"
+            INSERT INTO data_log
+                (id, job_id, start_time, end_time, type, setter, threshold, batch_size,
+                 image_files, video_files, other_files, total_segments, errors, total_remaining,
+                 data_load_time, inference_time, completed)
+            VALUES
+                (1, 1, '2024-01-01T00:00:00', '2024-01-01T00:10:00', 'tags', 'alpha', 0.5, 32,
+                 0, 0, 0, 0, 0, 0, 0, 0, 1),
+                (2, 2, '2024-01-02T00:00:00', '2024-01-02T00:10:00', 'tags', 'alpha', 0.5, 32,
+                 0, 0, 0, 0, 0, 0, 0, 0, 1),
+                (3, 3, '2024-01-03T00:00:00', '2024-01-03T00:10:00', 'tags', 'alpha', 0.5, 32,
+                 0, 0, 0, 0, 0, 0, 0, 0, 0),
+                (4, 4, '2024-01-04T00:00:00', '2024-01-04T00:10:00', 'tags', 'alpha', 0.5, 32,
+                 0, 0, 0, 0, 0, 0, 0, 0, 1),
+                (5, 5, '2024-01-05T00:00:00', '2024-01-05T00:10:00', 'tags', 'alpha', 0.5, 32,
+                 0, 0, 0, 0, 0, 0, 0, 0, 1)
+            "#,
+        )
+        .execute(&mut dbs.index_conn)
+        .await
+        .unwrap();
+
+        let deleted = prune_data_logs(&mut dbs.index_conn, Some(2), None, 100)
+            .await
+            .unwrap();
+        assert_eq!(deleted, 1);
+        let remaining: Vec<(i64,)> = sqlx::query_as("SELECT id FROM data_log ORDER BY id")
+            .fetch_all(&mut dbs.index_conn)
+            .await
+            .unwrap();
+        assert_eq!(remaining, vec![(2,), (3,), (4,), (5,)]);
+        let job: Option<(i64,)> = sqlx::query_as("SELECT id FROM data_jobs WHERE id = 1")
+            .fetch_optional(&mut dbs.index_conn)
+            .await
+            .unwrap();
+        assert!(job.is_none());
+
+        // Every log is older than a day; only the protected ones survive.
+        let deleted = prune_data_logs(&mut dbs.index_conn, None, Some(1), 100)
+            .await
+            .unwrap();
+        assert_eq!(deleted, 2);
+        let remaining: Vec<(i64,)> = sqlx::query_as("SELECT id FROM data_log ORDER BY id")
+            .fetch_all(&mut dbs.index_conn)
+            .await
+            .unwrap();
+        assert_eq!(remaining, vec![(2,), (3,)]);
+    }
+
+    // Ensures throughput sums only the newest completed runs per setter and
+    // entity type, skipping unfinished runs and runs without parameters.
+    #[tokio::test]
+    async fn extraction_throughput_uses_recent_completed_runs() {
+        let mut dbs = setup_test_databases().await;
+        let items = r#"{"model": {"target_entities": ["items"]}}"#;
+        let text = r#"{"model": {"target_entities": ["text"]}}"#;
+        // alpha/items: 10 items in 10s, 30 in 10s, then 100 in 10s (oldest,
+        // past the limit). alpha/text: 5 in 10s. beta: unfinished, or no
+        // parameters (a tag import).
+        for (day, files, errors, completed, setter, parameters) in [
+            (1, 100, 0, 1, "alpha", Some(items)),
+            (2, 28, 2, 1, "alpha", Some(items)),
+            (3, 10, 0, 1, "alpha", Some(items)),
+            (3, 5, 0, 1, "alpha", Some(text)),
+            (3, 50, 0, 0, "beta", Some(items)),
+            (3, 50, 0, 1, "beta", None),
+        ] {
+            let start = format!("2024-01-0{day}T00:00:00");
+            let end = format!("2024-01-0{day}T00:00:10");
+            sqlx::query(
+                r#"
+                INSERT INTO data_log
+                    (start_time, end_time, type, setter, batch_size, image_files, errors,
+                     completed, parameters)
+                VALUES (?, ?, 'tags', ?, 8, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(&start)
+            .bind(&end)
+            .bind(setter)
+            .bind(files)
+            .bind(errors)
+            .bind(completed)
+            .bind(parameters)
+            .execute(&mut dbs.index_conn)
+            .await
+            .unwrap();
+        }
+
+        let stats = get_extraction_throughput(&mut dbs.index_conn, 2)
+            .await
+            .unwrap();
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0].setter, "alpha");
+        assert_eq!(stats[0].entity.as_deref(), Some("items"));
+        assert_eq!((stats[0].runs, stats[0].items), (2, 40));
+        assert!((stats[0].seconds - 20.0).abs() < 1e-3);
+        assert_eq!(stats[1].entity.as_deref(), Some("text"));
+        assert_eq!((stats[1].runs, stats[1].items), (1, 5));
+    }
+
+    // Setter summaries list every setter row (data-less ones included) with
+    // non-placeholder counts and data types; a placeholder-only setter has
+    // rows but no counted data.
+    #[tokio::test]
+    async fn get_setter_summaries_covers_data_less_setters() {
+        let mut dbs = setup_test_databases().await;
+        sqlx::query(
+            r#"
+            INSERT INTO items (id, sha256, md5, type, time_added)
+            VALUES (1, 'sha_1', 'md5_1', 'image/png', '2024-01-01T00:00:00')
+            "#,
+        )
+        .execute(&mut dbs.index_conn)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO setters (id, name) VALUES (1, 'alpha'), (2, 'beta'), (3, 'gamma')",
+        )
+        .execute(&mut dbs.index_conn)
+        .await
+        .unwrap();
+        sqlx::query(
+            r#"
+            INSERT INTO item_data (id, item_id, setter_id, data_type, idx, is_origin, is_placeholder)
+            VALUES
+                (10, 1, 1, 'tags', 0, 1, 0),
+                (11, 1, 1, 'text', 0, 1, 0),
+                (12, 1, 2, 'text', 0, 1, 1)
+            "#,
+        )
+        .execute(&mut dbs.index_conn)
+        .await
+        .unwrap();
+
+        let summaries = get_setter_summaries(&mut dbs.index_conn).await.unwrap();
+        assert_eq!(
+            summaries,
+            vec![
+                SetterSummary {
+                    setter_name: "alpha".to_string(),
+                    data_types: vec!["tags".to_string(), "text".to_string()],
+                    item_data_count: 2,
+                    has_rows: true,
+                },
+                SetterSummary {
+                    setter_name: "beta".to_string(),
+                    data_types: Vec::new(),
+                    item_data_count: 0,
+                    has_rows: true,
+                },
+                SetterSummary {
+                    setter_name: "gamma".to_string(),
+                    data_types: Vec::new(),
+                    item_data_count: 0,
+                    has_rows: false,
+                },
+            ]
+        );
+    }
+
+    // Embedding stats list only setters with a recorded dimension or
+    // rejections, counting stored non-placeholder embeddings.
+    #[tokio::test]
+    async fn get_setter_embedding_stats_lists_embedding_setters() {
+        let mut dbs = setup_test_databases().await;
+        sqlx::query(
+            r#"
+            INSERT INTO items (id, sha256, md5, type, time_added)
+            VALUES (1, 'sha_1', 'md5_1', 'image/png', '2024-01-01T00:00:00');
+            INSERT INTO setters (id, name, embedding_dim, rejected_embeddings)
+            VALUES (1, 'clip', 2, 3), (2, 'ocr', NULL, 0), (3, 'broken', NULL, 1);
+            INSERT INTO item_data (id, item_id, setter_id, data_type, idx, is_origin, is_placeholder)
+            VALUES
+                (10, 1, 1, 'clip', 0, 1, 0),
+                (11, 1, 1, 'clip', 1, 1, 0),
+                (12, 1, 2, 'text', 0, 1, 0);
+            "#,
+        )
+        .execute(&mut dbs.index_conn)
+        .await
+        .unwrap();
+
+        let stats = get_setter_embedding_stats(&mut dbs.index_conn)
+            .await
+            .unwrap();
+        assert_eq!(
+            stats,
+            vec![
+                SetterEmbeddingStats {
+                    setter_name: "broken".to_string(),
+                    embedding_dim: None,
+                    embedding_count: 0,
+                    rejected_embeddings: 1,
+                },
+                SetterEmbeddingStats {
+                    setter_name: "clip".to_string(),
+                    embedding_dim: Some(2),
+                    embedding_count: 2,
+                    rejected_embeddings: 3,
+                },
+            ]
+        );
+    }
 }
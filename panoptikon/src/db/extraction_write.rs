@@ -1,3 +1,4 @@
+use axum::http::StatusCode;
 use sqlx::Row;
 use time::{OffsetDateTime, format_description::FormatItem};
 
@@ -12,6 +13,12 @@ pub(crate) struct DataLogUpdate {
     pub other_files: i64,
     pub total_segments: i64,
     pub errors: i64,
+    /// Errors where ffmpeg/ffprobe was killed at its timeout.
+    pub timeouts: i64,
+    /// Errors where no frame of a video could be decoded.
+    pub undecodable: i64,
+    /// Stored frames deleted by frame retention after the job.
+    pub frames_dropped: i64,
     pub total_remaining: i64,
     pub data_load_time: f64,
     pub inference_time: f64,
@@ -29,15 +36,42 @@ pub(crate) struct TagEntry {
 pub(crate) struct TagTextEntry {
     pub index: i64,
     pub text: String,
+    /// Indexed for FTS instead of `text` when set (see `normalized_for_index`).
+    pub normalized_text: Option<String>,
     pub language: String,
     pub language_confidence: f64,
     pub confidence: f64,
 }
 
+/// The confidence at the largest gap between consecutive scores (sorted
+/// descending): the cut for the `-mcut` tags text entry.
+pub(crate) fn mcut_threshold(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal));
+    let mut max_diff = 0.0;
+    let mut idx = 0usize;
+    for i in 0..sorted.len().saturating_sub(1) {
+        let diff = sorted[i] - sorted[i + 1];
+        if diff > max_diff {
+            max_diff = diff;
+            idx = i;
+        }
+    }
+    if idx + 1 >= sorted.len() {
+        return sorted[0];
+    }
+    (sorted[idx] + sorted[idx + 1]) / 2.0
+}
+
 #[derive(Debug, Clone)]
 pub(crate) struct TextEntry {
     pub index: i64,
     pub text: String,
+    /// Indexed for FTS instead of `text` when set (see `normalized_for_index`).
+    pub normalized_text: Option<String>,
     pub language: Option<String>,
     pub language_confidence: Option<f64>,
     pub confidence: Option<f64>,
@@ -84,6 +118,9 @@ pub(crate) async fn remove_incomplete_jobs(conn: &mut sqlx::SqliteConnection) ->
     Ok(())
 }
 
+/// `parameters` is the run's `ExtractionParameters` and `filter_counts` its
+/// `ExtractionFilterCounts`, both as JSON.
+#[allow(clippy::too_many_arguments)]
 pub(crate) async fn add_data_log(
     conn: &mut sqlx::SqliteConnection,
     scan_time: &str,
@@ -91,6 +128,9 @@ pub(crate) async fn add_data_log(
     types: &[String],
     setter: &str,
     batch_size: i64,
+    reprocess: bool,
+    parameters: Option<&str>,
+    filter_counts: Option<&str>,
 ) -> ApiResult<i64> {
     sqlx::query("INSERT INTO data_jobs (completed) VALUES (0)")
         .execute(&mut *conn)
@@ -122,9 +162,12 @@ pub(crate) async fn add_data_log(
             setter,
             threshold,
             batch_size,
-            job_id
+            job_id,
+            reprocess,
+            parameters,
+            filter_counts
         )
-        VALUES (?, ?, ?, ?, ?, ?, ?)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
         "#,
     )
     .bind(scan_time)
@@ -134,6 +177,9 @@ pub(crate) async fn add_data_log(
     .bind(threshold)
     .bind(batch_size)
     .bind(job_id)
+    .bind(reprocess)
+    .bind(parameters)
+    .bind(filter_counts)
     .execute(&mut *conn)
     .await
     .map_err(|err| {
@@ -159,6 +205,9 @@ pub(crate) async fn update_data_log(
             other_files = ?,
             total_segments = ?,
             errors = ?,
+            timeouts = ?,
+            undecodable = ?,
+            frames_dropped = ?,
             total_remaining = ?,
             data_load_time = ?,
             inference_time = ?,
@@ -172,6 +221,9 @@ pub(crate) async fn update_data_log(
     .bind(update.other_files)
     .bind(update.total_segments)
     .bind(update.errors)
+    .bind(update.timeouts)
+    .bind(update.undecodable)
+    .bind(update.frames_dropped)
     .bind(update.total_remaining)
     .bind(update.data_load_time)
     .bind(update.inference_time)
@@ -230,7 +282,11 @@ pub(crate) async fn write_tags_output(
     item_sha256: &str,
     tags: &[TagEntry],
     text_entries: &[TagTextEntry],
+    replace: bool,
 ) -> ApiResult<()> {
+    if replace {
+        delete_previous_item_data(conn, job_id, setter_name, item_sha256, None).await?;
+    }
     let tags_data_id = add_item_data(
         conn,
         item_sha256,
@@ -274,6 +330,7 @@ pub(crate) async fn write_tags_output(
             conn,
             text_data_id,
             &entry.text,
+            entry.normalized_text.as_deref(),
             Some(&entry.language),
             Some(entry.language_confidence),
             Some(entry.confidence),
@@ -283,13 +340,80 @@ pub(crate) async fn write_tags_output(
     Ok(())
 }
 
+/// Per-frame tag sets of a video, stored after [`write_tags_output`] in the
+/// same transaction. Frame `n` becomes a `tags` row at idx `n + 1`, derived
+/// from the job's merged row at idx 0, so it is deleted along with it.
+/// Frames without tags get no row.
+pub(crate) async fn write_frame_tags(
+    conn: &mut sqlx::SqliteConnection,
+    job_id: i64,
+    setter_name: &str,
+    item_sha256: &str,
+    frames: &[Vec<TagEntry>],
+) -> ApiResult<()> {
+    if frames.iter().all(Vec::is_empty) {
+        return Ok(());
+    }
+    let merged_id: Option<i64> = sqlx::query_scalar(
+        r#"
+        SELECT item_data.id
+        FROM item_data
+        JOIN items ON items.id = item_data.item_id
+        JOIN setters ON setters.id = item_data.setter_id
+        WHERE item_data.job_id = ?
+          AND items.sha256 = ?
+          AND setters.name = ?
+          AND item_data.data_type = 'tags'
+          AND item_data.idx = 0
+          AND item_data.is_placeholder = 0
+        "#,
+    )
+    .bind(job_id)
+    .bind(item_sha256)
+    .bind(setter_name)
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(|err| {
+        tracing::error!(error = %err, "failed to find merged tags row");
+        ApiError::internal("Failed to write extraction data")
+    })?;
+    let Some(merged_id) = merged_id else {
+        return Err(ApiError::internal("Per-frame tags written without merged tags"));
+    };
+    for (frame, tags) in frames.iter().enumerate() {
+        if tags.is_empty() {
+            continue;
+        }
+        let frame_data_id = add_item_data(
+            conn,
+            item_sha256,
+            setter_name,
+            job_id,
+            "tags",
+            frame as i64 + 1,
+            Some(merged_id),
+            false,
+        )
+        .await?;
+        for tag in tags {
+            add_tag_to_item(conn, frame_data_id, &tag.namespace, &tag.name, tag.confidence)
+                .await?;
+        }
+    }
+    Ok(())
+}
+
 pub(crate) async fn write_text_output(
     conn: &mut sqlx::SqliteConnection,
     job_id: i64,
     setter_name: &str,
     item_sha256: &str,
     entries: &[TextEntry],
+    replace: bool,
 ) -> ApiResult<()> {
+    if replace {
+        delete_previous_item_data(conn, job_id, setter_name, item_sha256, None).await?;
+    }
     if entries.is_empty() {
         let _ = add_item_data(
             conn,
@@ -321,6 +445,7 @@ pub(crate) async fn write_text_output(
             conn,
             data_id,
             &entry.text,
+            entry.normalized_text.as_deref(),
             entry.language.as_deref(),
             entry.language_confidence,
             entry.confidence,
@@ -336,7 +461,11 @@ pub(crate) async fn write_clip_output(
     setter_name: &str,
     item_sha256: &str,
     entries: &[EmbeddingEntry],
+    replace: bool,
 ) -> ApiResult<()> {
+    if replace {
+        delete_previous_item_data(conn, job_id, setter_name, item_sha256, None).await?;
+    }
     if entries.is_empty() {
         let _ = add_item_data(
             conn,
@@ -376,7 +505,11 @@ pub(crate) async fn write_text_embedding_output(
     item_sha256: &str,
     source_data_id: Option<i64>,
     entries: &[EmbeddingEntry],
+    replace: bool,
 ) -> ApiResult<()> {
+    if replace {
+        delete_previous_item_data(conn, job_id, setter_name, item_sha256, source_data_id).await?;
+    }
     if entries.is_empty() {
         let _ = add_item_data(
             conn,
@@ -413,10 +546,211 @@ pub(crate) async fn write_text_embedding_output(
     Ok(())
 }
 
-pub(crate) async fn delete_setter_by_name(
+/// Checks `entries` against the embedding dimension recorded for
+/// `setter_name`, recording it from the first embeddings a setter writes
+/// (or the first after all of its embeddings are gone). A mismatch comes
+/// back as the inner error and counts as a rejected write on the setter;
+/// the caller should commit that count and store nothing.
+pub(crate) async fn check_embedding_dimensions(
     conn: &mut sqlx::SqliteConnection,
     setter_name: &str,
+    item_sha256: &str,
+    entries: &[EmbeddingEntry],
+) -> ApiResult<Result<(), ApiError>> {
+    let Some(first) = entries.first() else {
+        return Ok(Ok(()));
+    };
+    let setter_id = upsert_setter(conn, setter_name).await?;
+    let recorded: Option<i64> =
+        sqlx::query_scalar("SELECT embedding_dim FROM setters WHERE id = ?")
+            .bind(setter_id)
+            .fetch_one(&mut *conn)
+            .await
+            .map_err(|err| {
+                tracing::error!(error = %err, "failed to read setter embedding dimension");
+                ApiError::internal("Failed to check embedding dimensions")
+            })?;
+
+    let find_mismatch = |expected: i64| {
+        entries.iter().find(|entry| {
+            !is_f32_array(&entry.embedding) || embedding_dim(&entry.embedding) != expected
+        })
+    };
+    let mut expected = recorded.unwrap_or(embedding_dim(&first.embedding));
+    let mut mismatch = find_mismatch(expected);
+    if recorded.is_some() && mismatch.is_some() && !setter_has_embeddings(conn, setter_id).await? {
+        // Nothing stored under the old dimension any more: start over.
+        expected = embedding_dim(&first.embedding);
+        mismatch = find_mismatch(expected);
+    }
+
+    if let Some(entry) = mismatch {
+        sqlx::query(
+            "UPDATE setters SET rejected_embeddings = rejected_embeddings + 1 WHERE id = ?",
+        )
+        .bind(setter_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|err| {
+            tracing::error!(error = %err, "failed to count rejected embeddings");
+            ApiError::internal("Failed to check embedding dimensions")
+        })?;
+        let detail = if is_f32_array(&entry.embedding) {
+            format!(
+                "Embedding {} of item {item_sha256} has {} dimensions, but setter '{setter_name}' stores {expected}-dimensional embeddings",
+                entry.index,
+                embedding_dim(&entry.embedding)
+            )
+        } else {
+            format!(
+                "Embedding {} of item {item_sha256} is {} bytes, not a whole number of float32 values",
+                entry.index,
+                entry.embedding.len()
+            )
+        };
+        return Ok(Err(ApiError::new(StatusCode::CONFLICT, detail)));
+    }
+
+    if recorded != Some(expected) {
+        sqlx::query("UPDATE setters SET embedding_dim = ? WHERE id = ?")
+            .bind(expected)
+            .bind(setter_id)
+            .execute(&mut *conn)
+            .await
+            .map_err(|err| {
+                tracing::error!(error = %err, "failed to record setter embedding dimension");
+                ApiError::internal("Failed to check embedding dimensions")
+            })?;
+    }
+    Ok(Ok(()))
+}
+
+/// Dimension of a serialized little-endian f32 embedding.
+fn embedding_dim(embedding: &[u8]) -> i64 {
+    (embedding.len() / 4) as i64
+}
+
+fn is_f32_array(embedding: &[u8]) -> bool {
+    !embedding.is_empty() && embedding.len().is_multiple_of(4)
+}
+
+async fn setter_has_embeddings(
+    conn: &mut sqlx::SqliteConnection,
+    setter_id: i64,
+) -> ApiResult<bool> {
+    sqlx::query_scalar(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM item_data
+            WHERE setter_id = ?
+              AND data_type IN ('clip', 'text-embedding')
+              AND is_placeholder = 0
+        )
+        "#,
+    )
+    .bind(setter_id)
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(|err| {
+        tracing::error!(error = %err, "failed to check setter embeddings");
+        ApiError::internal("Failed to check embedding dimensions")
+    })
+}
+
+/// Reprocess mode: deletes what earlier jobs stored for `item_sha256` under
+/// `setter_name`, in the transaction that writes the replacement. Rows of
+/// `job_id` itself stay, since one job can write an item several times (once
+/// per text for text inputs). With `source_data_id`, only data derived from
+/// that source goes. Derived rows, text, embeddings and tag links cascade.
+async fn delete_previous_item_data(
+    conn: &mut sqlx::SqliteConnection,
+    job_id: i64,
+    setter_name: &str,
+    item_sha256: &str,
+    source_data_id: Option<i64>,
 ) -> ApiResult<u64> {
+    let result = sqlx::query(
+        r#"
+        DELETE FROM item_data
+        WHERE item_id = (SELECT id FROM items WHERE sha256 = ?1)
+          AND setter_id = (SELECT id FROM setters WHERE name = ?2)
+          AND job_id IS NOT ?3
+          AND (?4 IS NULL OR source_id = ?4)
+        "#,
+    )
+    .bind(item_sha256)
+    .bind(setter_name)
+    .bind(job_id)
+    .bind(source_data_id)
+    .execute(&mut *conn)
+    .await
+    .map_err(|err| {
+        tracing::error!(error = %err, "failed to delete previous item data");
+        ApiError::internal("Failed to replace previous extraction data")
+    })?;
+    Ok(result.rows_affected())
+}
+
+/// Rows removed by `delete_setter_by_name`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct SetterDeletion {
+    /// Setter rows deleted (0 or 1); their own data goes with them.
+    pub setters: u64,
+    /// Rows of other setters derived from the setter's data: deleted with
+    /// `cascade`, otherwise detached into origin data.
+    pub derived: u64,
+}
+
+/// Deletes a setter and its data. With `cascade`, every row of another
+/// setter whose `source_id` chain passes through that data is deleted
+/// explicitly first, so nothing is left dangling even on connections that
+/// don't enforce foreign keys. Without it, rows derived directly from the
+/// setter's data are kept as origin data instead; those that would collide
+/// with existing origin data still go through the foreign key cascade.
+pub(crate) async fn delete_setter_by_name(
+    conn: &mut sqlx::SqliteConnection,
+    setter_name: &str,
+    cascade: bool,
+) -> ApiResult<SetterDeletion> {
+    let derived_sql = if cascade {
+        r#"
+        WITH RECURSIVE derived(id) AS (
+            SELECT item_data.id
+            FROM item_data
+            JOIN setters ON setters.id = item_data.setter_id
+            WHERE setters.name = ?1
+            UNION
+            SELECT item_data.id
+            FROM item_data
+            JOIN derived ON item_data.source_id = derived.id
+        )
+        DELETE FROM item_data
+        WHERE id IN (SELECT id FROM derived)
+          AND setter_id IS NOT (SELECT id FROM setters WHERE name = ?1)
+        "#
+    } else {
+        r#"
+        UPDATE OR IGNORE item_data
+        SET source_id = NULL, is_origin = 1
+        WHERE source_id IN (
+                SELECT item_data.id
+                FROM item_data
+                JOIN setters ON setters.id = item_data.setter_id
+                WHERE setters.name = ?1
+            )
+          AND setter_id IS NOT (SELECT id FROM setters WHERE name = ?1)
+        "#
+    };
+    let derived = sqlx::query(derived_sql)
+        .bind(setter_name)
+        .execute(&mut *conn)
+        .await
+        .map_err(|err| {
+            tracing::error!(error = %err, "failed to handle data derived from setter");
+            ApiError::internal("Failed to delete setter")
+        })?
+        .rows_affected();
+
     let result = sqlx::query("DELETE FROM setters WHERE name = ?")
         .bind(setter_name)
         .execute(&mut *conn)
@@ -425,7 +759,10 @@ pub(crate) async fn delete_setter_by_name(
             tracing::error!(error = %err, "failed to delete setter");
             ApiError::internal("Failed to delete setter")
         })?;
-    Ok(result.rows_affected())
+    Ok(SetterDeletion {
+        setters: result.rows_affected(),
+        derived,
+    })
 }
 
 pub(crate) async fn delete_orphan_tags(conn: &mut sqlx::SqliteConnection) -> ApiResult<u64> {
@@ -527,6 +864,7 @@ async fn add_extracted_text(
     conn: &mut sqlx::SqliteConnection,
     data_id: i64,
     text: &str,
+    normalized_text: Option<&str>,
     language: Option<&str>,
     language_confidence: Option<f64>,
     confidence: Option<f64>,
@@ -538,8 +876,9 @@ async fn add_extracted_text(
     let result = sqlx::query(
         r#"
         INSERT INTO extracted_text
-            (id, language, language_confidence, confidence, text, text_length)
-        SELECT item_data.id, ?, ?, ?, ?, ?
+            (id, language, language_confidence, confidence, text, text_length,
+             normalized_text)
+        SELECT item_data.id, ?, ?, ?, ?, ?, ?
         FROM item_data
         WHERE item_data.id = ?
         AND item_data.data_type = 'text'
@@ -550,6 +889,7 @@ async fn add_extracted_text(
     .bind(confidence)
     .bind(text)
     .bind(text_length)
+    .bind(normalized_text)
     .bind(data_id)
     .execute(&mut *conn)
     .await
@@ -560,6 +900,107 @@ async fn add_extracted_text(
     Ok(result.last_insert_rowid())
 }
 
+/// Outcome of one `renormalize_text_chunk` transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct RenormalizeChunk {
+    /// Rows examined; zero means the table is exhausted.
+    pub scanned: u64,
+    /// Rows whose indexed form changed (and were re-indexed).
+    pub updated: u64,
+    /// Resume after this id.
+    pub cursor: i64,
+}
+
+/// Recomputes `normalized_text` for up to `limit` rows after `after_id`
+/// under the given settings. Only rows whose value changes are updated, so
+/// the FTS update trigger re-indexes exactly those.
+pub(crate) async fn renormalize_text_chunk(
+    conn: &mut sqlx::SqliteConnection,
+    config: &crate::db::system_config::TextNormalizationConfig,
+    after_id: i64,
+    limit: i64,
+) -> ApiResult<RenormalizeChunk> {
+    let rows = sqlx::query(
+        r#"
+        SELECT id, text, normalized_text
+        FROM extracted_text
+        WHERE id > ?
+        ORDER BY id
+        LIMIT ?
+        "#,
+    )
+    .bind(after_id)
+    .bind(limit)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|err| {
+        tracing::error!(error = %err, "failed to read extracted text for renormalization");
+        ApiError::internal("Failed to renormalize extracted text")
+    })?;
+
+    let mut chunk = RenormalizeChunk {
+        scanned: rows.len() as u64,
+        updated: 0,
+        cursor: after_id,
+    };
+    let read_err = |err: sqlx::Error| {
+        tracing::error!(error = %err, "failed to decode extracted text row");
+        ApiError::internal("Failed to renormalize extracted text")
+    };
+    for row in rows {
+        let id: i64 = row.try_get("id").map_err(read_err)?;
+        let text: String = row.try_get("text").map_err(read_err)?;
+        let current: Option<String> = row.try_get("normalized_text").map_err(read_err)?;
+        chunk.cursor = id;
+        let wanted = crate::pql::utils::normalized_for_index(config, &text);
+        if wanted == current {
+            continue;
+        }
+        sqlx::query("UPDATE extracted_text SET normalized_text = ? WHERE id = ?")
+            .bind(wanted)
+            .bind(id)
+            .execute(&mut *conn)
+            .await
+            .map_err(|err| {
+                tracing::error!(error = %err, "failed to update normalized text");
+                ApiError::internal("Failed to renormalize extracted text")
+            })?;
+        chunk.updated += 1;
+    }
+    Ok(chunk)
+}
+
+/// Replaces the text of an existing `extracted_text` row, e.g. a tags text
+/// entry after some of its tags were removed. The FTS update trigger
+/// re-indexes it.
+pub(crate) async fn rewrite_extracted_text(
+    conn: &mut sqlx::SqliteConnection,
+    data_id: i64,
+    text: &str,
+    normalized_text: Option<&str>,
+    confidence: f64,
+) -> ApiResult<()> {
+    sqlx::query(
+        r#"
+        UPDATE extracted_text
+        SET text = ?, text_length = ?, normalized_text = ?, confidence = ?
+        WHERE id = ?
+        "#,
+    )
+    .bind(text)
+    .bind(text.chars().count() as i64)
+    .bind(normalized_text)
+    .bind(round_value(confidence))
+    .bind(data_id)
+    .execute(&mut *conn)
+    .await
+    .map_err(|err| {
+        tracing::error!(error = %err, "failed to rewrite extracted text");
+        ApiError::internal("Failed to write extraction data")
+    })?;
+    Ok(())
+}
+
 async fn add_embedding(
     conn: &mut sqlx::SqliteConnection,
     data_id: i64,
@@ -615,7 +1056,7 @@ async fn add_tag_to_item(
     Ok(())
 }
 
-async fn upsert_tag(
+pub(crate) async fn upsert_tag(
     conn: &mut sqlx::SqliteConnection,
     namespace: &str,
     name: &str,
@@ -704,3 +1145,507 @@ fn iso_format() -> &'static [FormatItem<'static>] {
         .expect("invalid time format")
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::migrations::setup_test_databases;
+    use crate::db::system_config::TextNormalizationConfig;
+
+    async fn fts_matches(conn: &mut sqlx::SqliteConnection, query: &str) -> Vec<i64> {
+        sqlx::query_scalar(
+            "SELECT rowid FROM extracted_text_fts WHERE extracted_text_fts MATCH ? ORDER BY rowid",
+        )
+        .bind(query)
+        .fetch_all(conn)
+        .await
+        .unwrap()
+    }
+
+    // The FTS index covers normalized_text when it is set and text otherwise,
+    // snippets come from the indexed form, and renormalizing under new
+    // settings (chunked, only touching changed rows) re-indexes through the
+    // update trigger.
+    #[tokio::test]
+    async fn fts_indexes_normalized_text_and_renormalizes() {
+        let mut dbs = setup_test_databases().await;
+        let conn = &mut dbs.index_conn;
+        sqlx::query(
+            r#"
+            INSERT INTO items (id, sha256, md5, type, time_added)
+            VALUES (100, 'sha_100', 'md5_100', 'image/png', '2024-01-01T00:00:00');
+            INSERT INTO setters (id, name) VALUES (1, 'ocr');
+            INSERT INTO item_data (id, item_id, setter_id, data_type, idx, is_origin)
+            VALUES (10, 100, 1, 'text', 0, 1), (11, 100, 1, 'text', 1, 1);
+            "#,
+        )
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+        add_extracted_text(
+            conn,
+            10,
+            "ＰＡＮＯＰＴＩＫＯＮ scan",
+            Some("PANOPTIKON scan"),
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        add_extracted_text(conn, 11, "plain words", None, None, None, None)
+            .await
+            .unwrap();
+
+        assert_eq!(fts_matches(conn, "\"PANOPTIKON\"").await, vec![10]);
+        assert_eq!(fts_matches(conn, "\"plain\"").await, vec![11]);
+        let snippet: String = sqlx::query_scalar(
+            "SELECT snippet(extracted_text_fts, -1, '[', ']', '...', 64) \
+             FROM extracted_text_fts WHERE extracted_text_fts MATCH '\"PANOPTIKON\"'",
+        )
+        .fetch_one(&mut *conn)
+        .await
+        .unwrap();
+        assert_eq!(snippet, "[PANOPTIKON] scan");
+
+        // Normalization off: the stored form is dropped, one row per chunk.
+        let disabled = TextNormalizationConfig::default();
+        let first = renormalize_text_chunk(conn, &disabled, 0, 1).await.unwrap();
+        assert_eq!(
+            first,
+            RenormalizeChunk {
+                scanned: 1,
+                updated: 1,
+                cursor: 10
+            }
+        );
+        let second = renormalize_text_chunk(conn, &disabled, 10, 1)
+            .await
+            .unwrap();
+        assert_eq!((second.scanned, second.updated), (1, 0));
+        let done = renormalize_text_chunk(conn, &disabled, 11, 1)
+            .await
+            .unwrap();
+        assert_eq!(done.scanned, 0);
+        assert!(fts_matches(conn, "\"PANOPTIKON\"").await.is_empty());
+        assert_eq!(
+            fts_matches(conn, "\"ＰＡＮＯＰＴＩＫＯＮ\"").await,
+            vec![10]
+        );
+
+        let nfkc = TextNormalizationConfig {
+            nfkc: true,
+            ..TextNormalizationConfig::default()
+        };
+        let chunk = renormalize_text_chunk(conn, &nfkc, 0, 100).await.unwrap();
+        assert_eq!((chunk.scanned, chunk.updated), (2, 1));
+        assert_eq!(fts_matches(conn, "\"PANOPTIKON\"").await, vec![10]);
+    }
+
+    /// `(job_id, data_type, source_id)` of every row `setter` holds for
+    /// `item_id`, in insertion order.
+    async fn setter_rows(
+        conn: &mut sqlx::SqliteConnection,
+        item_id: i64,
+        setter: &str,
+    ) -> Vec<(i64, String, Option<i64>)> {
+        sqlx::query_as(
+            r#"
+            SELECT item_data.job_id, item_data.data_type, item_data.source_id
+            FROM item_data
+            JOIN setters ON setters.id = item_data.setter_id
+            WHERE item_data.item_id = ? AND setters.name = ?
+            ORDER BY item_data.id
+            "#,
+        )
+        .bind(item_id)
+        .bind(setter)
+        .fetch_all(conn)
+        .await
+        .unwrap()
+    }
+
+    fn tag(name: &str) -> TagEntry {
+        TagEntry {
+            namespace: "danbooru:general".to_string(),
+            name: name.to_string(),
+            confidence: 0.9,
+        }
+    }
+
+    fn tag_text(text: &str) -> TagTextEntry {
+        TagTextEntry {
+            index: 0,
+            text: text.to_string(),
+            normalized_text: None,
+            language: "danbooru".to_string(),
+            language_confidence: 1.0,
+            confidence: 0.9,
+        }
+    }
+
+    // Ensures per-frame tags are stored beside the merged set without
+    // showing up in it or in tag counts, come back grouped by frame, and go
+    // away with the merged row.
+    #[tokio::test]
+    async fn frame_tags_are_kept_apart_from_merged_tags() {
+        let mut dbs = setup_test_databases().await;
+        let conn = &mut dbs.index_conn;
+        sqlx::query(
+            r#"
+            INSERT INTO items (id, sha256, md5, type, time_added)
+            VALUES (1, 'sha_1', 'md5_1', 'video/mp4', '2024-01-01T00:00:00');
+            INSERT INTO setters (id, name) VALUES (1, 'tagger');
+            INSERT INTO data_jobs (id, completed) VALUES (1, 1);
+            "#,
+        )
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+        write_tags_output(
+            conn,
+            1,
+            "tagger",
+            "sha_1",
+            &[tag("cat"), tag("dog")],
+            &[tag_text("cat, dog")],
+            false,
+        )
+        .await
+        .unwrap();
+        write_frame_tags(
+            conn,
+            1,
+            "tagger",
+            "sha_1",
+            &[vec![tag("cat")], Vec::new(), vec![tag("cat"), tag("dog")]],
+        )
+        .await
+        .unwrap();
+
+        let merged = crate::db::items::get_all_tags_for_item(conn, 1, &[], 0.0, &[], None)
+            .await
+            .unwrap();
+        assert_eq!(merged.len(), 2);
+        let frames = crate::db::items::get_frame_tags_for_item(conn, 1, &[], 0.0, &[], None)
+            .await
+            .unwrap();
+        let names = frames
+            .iter()
+            .map(|(frame, tags)| {
+                let names = tags.iter().map(|tag| tag.1.as_str()).collect::<Vec<_>>();
+                (*frame, names)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec![(0, vec!["cat"]), (2, vec!["cat", "dog"])]);
+        let counts =
+            crate::db::tags::get_most_common_tags_frequency(conn, None, &[], None, 10)
+                .await
+                .unwrap();
+        assert!(counts.iter().all(|(_, _, count, _)| *count == 1), "{counts:?}");
+
+        sqlx::query("DELETE FROM item_data WHERE data_type = 'tags' AND idx = 0")
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        assert!(setter_rows(conn, 1, "tagger").await.is_empty());
+    }
+
+    // Ensures a replacing write drops the item's rows from earlier jobs of the
+    // setter (derived text and tag links included) and leaves other items and
+    // other setters alone.
+    #[tokio::test]
+    async fn replacing_write_keeps_one_data_set_per_item() {
+        let mut dbs = setup_test_databases().await;
+        let conn = &mut dbs.index_conn;
+        sqlx::query(
+            r#"
+            INSERT INTO items (id, sha256, md5, type, time_added)
+            VALUES
+                (1, 'sha_1', 'md5_1', 'image/png', '2024-01-01T00:00:00'),
+                (2, 'sha_2', 'md5_2', 'image/png', '2024-01-01T00:00:00');
+            INSERT INTO setters (id, name) VALUES (1, 'tagger'), (2, 'ocr');
+            INSERT INTO data_jobs (id, completed) VALUES (1, 1), (2, 0);
+            "#,
+        )
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+        for sha256 in ["sha_1", "sha_2"] {
+            write_tags_output(
+                conn,
+                1,
+                "tagger",
+                sha256,
+                &[tag("cat")],
+                &[tag_text("cat")],
+                false,
+            )
+            .await
+            .unwrap();
+        }
+        let ocr = TextEntry {
+            index: 0,
+            text: "hello".to_string(),
+            normalized_text: None,
+            language: None,
+            language_confidence: None,
+            confidence: None,
+        };
+        write_text_output(conn, 1, "ocr", "sha_1", &[ocr], false)
+            .await
+            .unwrap();
+
+        write_tags_output(
+            conn,
+            2,
+            "tagger",
+            "sha_1",
+            &[tag("dog")],
+            &[tag_text("dog")],
+            true,
+        )
+        .await
+        .unwrap();
+
+        let rows = setter_rows(conn, 1, "tagger").await;
+        assert!(rows.iter().all(|(job_id, _, _)| *job_id == 2), "{rows:?}");
+        assert_eq!(rows.len(), 2);
+        let tags: Vec<String> = sqlx::query_scalar(
+            r#"
+            SELECT tags.name FROM tags_items
+            JOIN tags ON tags.id = tags_items.tag_id
+            JOIN item_data ON item_data.id = tags_items.item_data_id
+            WHERE item_data.item_id = 1
+            ORDER BY tags.name
+            "#,
+        )
+        .fetch_all(&mut *conn)
+        .await
+        .unwrap();
+        assert_eq!(tags, ["dog"]);
+        let texts: Vec<String> = sqlx::query_scalar(
+            r#"
+            SELECT extracted_text.text FROM extracted_text
+            JOIN item_data ON item_data.id = extracted_text.id
+            WHERE item_data.item_id = 1
+            ORDER BY extracted_text.text
+            "#,
+        )
+        .fetch_all(&mut *conn)
+        .await
+        .unwrap();
+        assert_eq!(texts, ["dog", "hello"]);
+        assert_eq!(setter_rows(conn, 2, "tagger").await.len(), 2);
+        assert_eq!(setter_rows(conn, 1, "ocr").await.len(), 1);
+
+        // Without replace, the rerun collides with the earlier origin rows.
+        assert!(
+            write_tags_output(conn, 2, "tagger", "sha_2", &[tag("cat")], &[], false)
+                .await
+                .is_err()
+        );
+        assert_eq!(setter_rows(conn, 2, "tagger").await.len(), 2);
+    }
+
+    // Ensures a replacing text-embedding write only drops the embeddings of
+    // the text it was computed from, leaving the item's other texts alone.
+    #[tokio::test]
+    async fn replacing_text_embedding_is_scoped_to_its_source() {
+        crate::db::sql_functions::ensure_sqlite_extensions().unwrap();
+        let mut dbs = setup_test_databases().await;
+        let conn = &mut dbs.index_conn;
+        sqlx::query(
+            r#"
+            INSERT INTO items (id, sha256, md5, type, time_added)
+            VALUES (1, 'sha_1', 'md5_1', 'image/png', '2024-01-01T00:00:00');
+            INSERT INTO setters (id, name) VALUES (1, 'ocr'), (2, 'embed');
+            INSERT INTO data_jobs (id, completed) VALUES (1, 1), (2, 1), (3, 0);
+            INSERT INTO item_data (id, job_id, item_id, setter_id, data_type, idx, is_origin)
+            VALUES (10, 1, 1, 1, 'text', 0, 1), (11, 1, 1, 1, 'text', 1, 1);
+            "#,
+        )
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+        let entry = EmbeddingEntry {
+            index: 0,
+            embedding: vec![0; 8],
+        };
+        for source in [10, 11] {
+            write_text_embedding_output(
+                conn,
+                2,
+                "embed",
+                "sha_1",
+                Some(source),
+                std::slice::from_ref(&entry),
+                false,
+            )
+            .await
+            .unwrap();
+        }
+        write_text_embedding_output(
+            conn,
+            3,
+            "embed",
+            "sha_1",
+            Some(10),
+            std::slice::from_ref(&entry),
+            true,
+        )
+        .await
+        .unwrap();
+
+        let rows = setter_rows(conn, 1, "embed").await;
+        assert_eq!(
+            rows,
+            [
+                (2, "text-embedding".to_string(), Some(11)),
+                (3, "text-embedding".to_string(), Some(10)),
+            ]
+        );
+        let embeddings: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM embeddings")
+            .fetch_one(&mut *conn)
+            .await
+            .unwrap();
+        assert_eq!(embeddings, 2);
+    }
+
+    async fn clip_dims(conn: &mut sqlx::SqliteConnection) -> (Option<i64>, i64) {
+        sqlx::query_as("SELECT embedding_dim, rejected_embeddings FROM setters WHERE name = 'clip'")
+            .fetch_one(conn)
+            .await
+            .unwrap()
+    }
+
+    // Ensures the first embeddings fix a setter's dimension, later writes of
+    // another size are rejected and counted, and the dimension resets once
+    // the setter stores no embeddings.
+    #[tokio::test]
+    async fn embedding_dimensions_are_recorded_and_enforced() {
+        let mut dbs = setup_test_databases().await;
+        let conn = &mut dbs.index_conn;
+        sqlx::query(
+            r#"
+            INSERT INTO items (id, sha256, md5, type, time_added)
+            VALUES (1, 'sha_1', 'md5_1', 'image/png', '2024-01-01T00:00:00');
+            INSERT INTO data_jobs (id, completed) VALUES (1, 1);
+            "#,
+        )
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+        let entry = |index: i64, dim: usize| EmbeddingEntry {
+            index,
+            embedding: vec![0; dim * 4],
+        };
+
+        let first = [entry(0, 4), entry(1, 4)];
+        check_embedding_dimensions(conn, "clip", "sha_1", &first)
+            .await
+            .unwrap()
+            .unwrap();
+        write_clip_output(conn, 1, "clip", "sha_1", &first, false)
+            .await
+            .unwrap();
+        assert_eq!(clip_dims(conn).await, (Some(4), 0));
+
+        let err = check_embedding_dimensions(conn, "clip", "sha_1", &[entry(2, 8)])
+            .await
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::CONFLICT);
+        assert_eq!(
+            err.detail(),
+            "Embedding 2 of item sha_1 has 8 dimensions, but setter 'clip' stores 4-dimensional embeddings"
+        );
+        let torn = EmbeddingEntry {
+            index: 2,
+            embedding: vec![0; 6],
+        };
+        assert!(
+            check_embedding_dimensions(conn, "clip", "sha_1", &[entry(2, 4), torn])
+                .await
+                .unwrap()
+                .is_err()
+        );
+        assert_eq!(clip_dims(conn).await, (Some(4), 2));
+
+        sqlx::query("DELETE FROM item_data")
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        check_embedding_dimensions(conn, "clip", "sha_1", &[entry(0, 8)])
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(clip_dims(conn).await, (Some(8), 2));
+    }
+
+    async fn seed_lineage_chain(conn: &mut sqlx::SqliteConnection) {
+        sqlx::query(
+            r#"
+            INSERT INTO items (id, sha256, md5, type, time_added)
+            VALUES (1, 'sha_1', 'md5_1', 'image/png', '2024-01-01T00:00:00');
+            INSERT INTO setters (id, name) VALUES (1, 'ocr'), (2, 'embed'), (3, 'summary');
+            INSERT INTO item_data (id, item_id, setter_id, data_type, idx, is_origin, source_id)
+            VALUES (10, 1, 1, 'text', 0, 1, NULL),
+                   (11, 1, 2, 'text-embedding', 0, NULL, 10),
+                   (12, 1, 3, 'text', 0, NULL, 11);
+            "#,
+        )
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+    }
+
+    // Ensures deleting a setter with `cascade` removes every row whose
+    // source chain passes through its data, even without foreign keys, and
+    // that without it directly derived rows are kept as origin data.
+    #[tokio::test]
+    async fn setter_deletion_cascades_or_detaches_derived_data() {
+        let mut dbs = setup_test_databases().await;
+        let conn = &mut dbs.index_conn;
+        sqlx::query("PRAGMA foreign_keys = OFF")
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        seed_lineage_chain(conn).await;
+        let deleted = delete_setter_by_name(conn, "ocr", true).await.unwrap();
+        assert_eq!(
+            deleted,
+            SetterDeletion {
+                setters: 1,
+                derived: 2
+            }
+        );
+        let ids: Vec<i64> = sqlx::query_scalar("SELECT id FROM item_data WHERE setter_id != 1")
+            .fetch_all(&mut *conn)
+            .await
+            .unwrap();
+        assert!(ids.is_empty());
+
+        sqlx::query(
+            "DELETE FROM item_data; DELETE FROM setters; DELETE FROM items; PRAGMA foreign_keys = ON",
+        )
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+        seed_lineage_chain(conn).await;
+        let deleted = delete_setter_by_name(conn, "ocr", false).await.unwrap();
+        assert_eq!(
+            deleted,
+            SetterDeletion {
+                setters: 1,
+                derived: 1
+            }
+        );
+        let rows: Vec<(i64, Option<i64>, Option<i64>)> =
+            sqlx::query_as("SELECT id, source_id, is_origin FROM item_data ORDER BY id")
+                .fetch_all(&mut *conn)
+                .await
+                .unwrap();
+        assert_eq!(rows, [(11, None, Some(1)), (12, Some(11), None)]);
+    }
+}
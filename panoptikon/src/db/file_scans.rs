@@ -19,6 +19,28 @@ pub(crate) struct FileScanRecord {
     pub modified_files: i64,
     pub marked_unavailable: i64,
     pub errors: i64,
+    /// Errors where ffmpeg/ffprobe was killed at its timeout.
+    pub timeouts: i64,
+    /// Videos indexed without visuals because no frame could be decoded,
+    /// even by the fallbacks. Not counted in `errors`.
+    pub undecodable: i64,
+    /// Files skipped because they were still being written.
+    pub deferred: i64,
+    /// Directories skipped because their name matched an ignore pattern.
+    pub ignored_dirs: i64,
+    /// Files skipped because they sit in such a directory. Continuous scans
+    /// count the change events they dropped.
+    pub ignored_dir_files: i64,
+    /// Files and directories skipped by `.panoptikonignore` rules.
+    pub ignored_files: i64,
+    /// Items a fast scan indexed without generating their visuals.
+    pub visuals_deferred: i64,
+    /// Directories whose files were skipped because the directory was
+    /// unchanged since the last scan (`skip_unchanged_dirs`).
+    pub skipped_dirs: i64,
+    /// Concurrent file workers the scan ran with; 0 for scans recorded
+    /// before this was tracked.
+    pub worker_count: i64,
     pub false_changes: i64,
     pub metadata_time: f64,
     pub hashing_time: f64,
@@ -37,6 +59,25 @@ pub(crate) struct FileScanUpdate {
     pub modified_files: i64,
     pub marked_unavailable: i64,
     pub errors: i64,
+    /// Errors where ffmpeg/ffprobe was killed at its timeout.
+    pub timeouts: i64,
+    /// Videos whose frames no extraction step could decode.
+    pub undecodable: i64,
+    /// Times a file was found still being written and postponed; not errors.
+    pub deferred: i64,
+    /// Directories pruned by `ignored_dir_patterns`.
+    pub ignored_dirs: i64,
+    /// Files below those directories that the scan would have indexed.
+    pub ignored_dir_files: i64,
+    /// Files and directories skipped by `.panoptikonignore` rules; an ignored
+    /// directory counts once.
+    pub ignored_files: i64,
+    /// Items whose visuals `fast_scan` left for later generation.
+    pub visuals_deferred: i64,
+    /// Directories matching their snapshot, whose files were not visited.
+    pub skipped_dirs: i64,
+    /// Effective worker count after per-folder settings were applied.
+    pub worker_count: i64,
     pub total_available: i64,
     pub false_changes: i64,
     pub metadata_time: f64,
@@ -89,6 +130,15 @@ pub(crate) async fn update_file_scan(
         modified_files,
         marked_unavailable,
         errors,
+        timeouts,
+        undecodable,
+        deferred,
+        ignored_dirs,
+        ignored_dir_files,
+        ignored_files,
+        visuals_deferred,
+        skipped_dirs,
+        worker_count,
         total_available,
         false_changes,
         metadata_time,
@@ -113,8 +163,17 @@ SET
     metadata_time = ?10,
     hashing_time = ?11,
     thumbgen_time = ?12,
-    blurhash_time = ?13
-WHERE id = ?14
+    blurhash_time = ?13,
+    deferred = ?14,
+    ignored_dirs = ?15,
+    worker_count = ?16,
+    timeouts = ?17,
+    ignored_files = ?18,
+    visuals_deferred = ?19,
+    undecodable = ?20,
+    skipped_dirs = ?21,
+    ignored_dir_files = ?22
+WHERE id = ?23
         "#,
     )
     .bind(end_time)
@@ -130,6 +189,15 @@ WHERE id = ?14
     .bind(round_time(hashing_time))
     .bind(round_time(thumbgen_time))
     .bind(round_time(blurhash_time))
+    .bind(deferred)
+    .bind(ignored_dirs)
+    .bind(worker_count)
+    .bind(timeouts)
+    .bind(ignored_files)
+    .bind(visuals_deferred)
+    .bind(undecodable)
+    .bind(skipped_dirs)
+    .bind(ignored_dir_files)
     .bind(scan_id)
     .execute(&mut *conn)
     .await
@@ -228,6 +296,15 @@ SELECT
     modified_files,
     marked_unavailable,
     errors,
+    timeouts,
+    undecodable,
+    deferred,
+    ignored_dirs,
+    ignored_dir_files,
+    ignored_files,
+    visuals_deferred,
+    skipped_dirs,
+    worker_count,
     false_changes,
     metadata_time,
     hashing_time,
@@ -300,6 +377,42 @@ ORDER BY start_time DESC
             tracing::error!(error = %err, "failed to read file scan errors");
             ApiError::internal("Failed to get scan history")
         })?;
+        let timeouts: i64 = row.try_get("timeouts").map_err(|err| {
+            tracing::error!(error = %err, "failed to read file scan timeouts");
+            ApiError::internal("Failed to get scan history")
+        })?;
+        let undecodable: i64 = row.try_get("undecodable").map_err(|err| {
+            tracing::error!(error = %err, "failed to read file scan undecodable");
+            ApiError::internal("Failed to get scan history")
+        })?;
+        let deferred: i64 = row.try_get("deferred").map_err(|err| {
+            tracing::error!(error = %err, "failed to read file scan deferred");
+            ApiError::internal("Failed to get scan history")
+        })?;
+        let ignored_dirs: i64 = row.try_get("ignored_dirs").map_err(|err| {
+            tracing::error!(error = %err, "failed to read file scan ignored_dirs");
+            ApiError::internal("Failed to get scan history")
+        })?;
+        let ignored_dir_files: i64 = row.try_get("ignored_dir_files").map_err(|err| {
+            tracing::error!(error = %err, "failed to read file scan ignored_dir_files");
+            ApiError::internal("Failed to get scan history")
+        })?;
+        let ignored_files: i64 = row.try_get("ignored_files").map_err(|err| {
+            tracing::error!(error = %err, "failed to read file scan ignored_files");
+            ApiError::internal("Failed to get scan history")
+        })?;
+        let visuals_deferred: i64 = row.try_get("visuals_deferred").map_err(|err| {
+            tracing::error!(error = %err, "failed to read file scan visuals_deferred");
+            ApiError::internal("Failed to get scan history")
+        })?;
+        let skipped_dirs: i64 = row.try_get("skipped_dirs").map_err(|err| {
+            tracing::error!(error = %err, "failed to read file scan skipped_dirs");
+            ApiError::internal("Failed to get scan history")
+        })?;
+        let worker_count: i64 = row.try_get("worker_count").map_err(|err| {
+            tracing::error!(error = %err, "failed to read file scan worker_count");
+            ApiError::internal("Failed to get scan history")
+        })?;
         let false_changes: i64 = row.try_get("false_changes").map_err(|err| {
             tracing::error!(error = %err, "failed to read file scan false_changes");
             ApiError::internal("Failed to get scan history")
@@ -333,6 +446,15 @@ ORDER BY start_time DESC
             modified_files,
             marked_unavailable,
             errors,
+            timeouts,
+            undecodable,
+            deferred,
+            ignored_dirs,
+            ignored_dir_files,
+            ignored_files,
+            visuals_deferred,
+            skipped_dirs,
+            worker_count,
             false_changes,
             metadata_time,
             hashing_time,
@@ -344,12 +466,35 @@ ORDER BY start_time DESC
     Ok(scans)
 }
 
+/// Marks files under `path_prefix` that scan `scan_id` did not find as
+/// unavailable, and stamps `last_seen = seen_at` on the ones it did. Returns
+/// the number marked unavailable and the number still available.
 pub(crate) async fn mark_unavailable_files(
     conn: &mut sqlx::SqliteConnection,
     scan_id: i64,
+    seen_at: &str,
     path_prefix: &str,
     excluded_paths: &[String],
 ) -> ApiResult<(i64, i64)> {
+    sqlx::query(
+        r#"
+UPDATE files
+SET last_seen = ?1
+WHERE scan_id = ?2
+AND available = TRUE
+AND path LIKE ?3 || '%'
+        "#,
+    )
+    .bind(seen_at)
+    .bind(scan_id)
+    .bind(path_prefix)
+    .execute(&mut *conn)
+    .await
+    .map_err(|err| {
+        tracing::error!(error = %err, scan_id, "failed to stamp last seen files");
+        ApiError::internal("Failed to mark unavailable files")
+    })?;
+
     // SQLite caps the number of bind variables; a scan with this many
     // failures should not be trusted to mark availability at all.
     if excluded_paths.len() > 30_000 {
@@ -453,11 +598,14 @@ AND path LIKE ?1 || '%'
     })
 }
 
+/// Deletes unavailable files, except the ones a folder update removed: those
+/// wait out `folder_removal_grace_days` instead.
 pub(crate) async fn delete_unavailable_files(conn: &mut sqlx::SqliteConnection) -> ApiResult<u64> {
     let result = sqlx::query(
         r#"
 DELETE FROM files
 WHERE available = 0
+AND removed_at IS NULL
         "#,
     )
     .execute(&mut *conn)
@@ -494,6 +642,15 @@ mod tests {
                 modified_files: 4,
                 marked_unavailable: 5,
                 errors: 6,
+                timeouts: 2,
+                undecodable: 13,
+                deferred: 9,
+                ignored_dirs: 10,
+                ignored_dir_files: 15,
+                ignored_files: 11,
+                visuals_deferred: 12,
+                skipped_dirs: 14,
+                worker_count: 3,
                 total_available: 7,
                 false_changes: 8,
                 metadata_time: 1.1,
@@ -513,6 +670,15 @@ mod tests {
         assert_eq!(scan.path, r"C:\data");
         assert_eq!(scan.end_time.as_deref(), Some("2024-01-01T00:01:00"));
         assert_eq!(scan.new_files, 3);
+        assert_eq!(scan.timeouts, 2);
+        assert_eq!(scan.undecodable, 13);
+        assert_eq!(scan.deferred, 9);
+        assert_eq!(scan.ignored_dirs, 10);
+        assert_eq!(scan.ignored_dir_files, 15);
+        assert_eq!(scan.ignored_files, 11);
+        assert_eq!(scan.visuals_deferred, 12);
+        assert_eq!(scan.skipped_dirs, 14);
+        assert_eq!(scan.worker_count, 3);
         assert_eq!(scan.blurhash_time, 4.4);
     }
 
@@ -551,10 +717,15 @@ VALUES
         .await
         .unwrap();
 
-        let (marked, available) =
-            mark_unavailable_files(&mut dbs.index_conn, scan_id, r"C:\data\", &[])
-                .await
-                .unwrap();
+        let (marked, available) = mark_unavailable_files(
+            &mut dbs.index_conn,
+            scan_id,
+            "2024-01-01T00:01:00",
+            r"C:\data\",
+            &[],
+        )
+        .await
+        .unwrap();
 
         assert_eq!(marked, 1);
         assert_eq!(available, 0);
@@ -606,6 +777,7 @@ VALUES
         let (marked, available) = mark_unavailable_files(
             &mut dbs.index_conn,
             scan_id,
+            "2024-01-01T00:01:00",
             r"C:\data\",
             &[r"C:\data\one.png".to_string()],
         )
@@ -668,6 +840,7 @@ VALUES ('sha_one', 1, 'C:\data\one.png', 'one.png', '2024-01-01T00:00:00', ?1, 1
         let (marked, _) = mark_unavailable_files(
             &mut dbs.index_conn,
             scan_id,
+            "2024-01-01T00:01:00",
             r"C:\data\",
             &[r"c:\data\ONE.png".to_string()],
         )
@@ -683,6 +856,72 @@ VALUES ('sha_one', 1, 'C:\data\one.png', 'one.png', '2024-01-01T00:00:00', ?1, 1
         assert_eq!(row.0, 1);
     }
 
+    // Ensures files the scan found get its last_seen while files it missed
+    // keep the time they were last found.
+    #[tokio::test]
+    async fn mark_unavailable_files_stamps_last_seen_on_found_files() {
+        let mut dbs = setup_test_databases().await;
+        let previous_scan_id = add_file_scan(&mut dbs.index_conn, "2024-01-01T00:00:00", "/data/")
+            .await
+            .unwrap();
+        let scan_id = add_file_scan(&mut dbs.index_conn, "2024-02-01T00:00:00", "/data/")
+            .await
+            .unwrap();
+        sqlx::query(
+            r#"
+INSERT INTO items (id, sha256, md5, type, time_added)
+VALUES (1, 'sha_one', 'md5_one', 'image/png', '2024-01-01T00:00:00')
+            "#,
+        )
+        .execute(&mut dbs.index_conn)
+        .await
+        .unwrap();
+        sqlx::query(
+            r#"
+INSERT INTO files (sha256, item_id, path, filename, last_modified, scan_id, available, last_seen)
+VALUES
+    ('sha_one', 1, '/data/found.png', 'found.png', '2024-01-01T00:00:00', ?2, 1, '2024-01-01T00:00:00'),
+    ('sha_one', 1, '/data/gone.png', 'gone.png', '2024-01-01T00:00:00', ?1, 1, '2024-01-01T00:00:00')
+            "#,
+        )
+        .bind(previous_scan_id)
+        .bind(scan_id)
+        .execute(&mut dbs.index_conn)
+        .await
+        .unwrap();
+
+        mark_unavailable_files(
+            &mut dbs.index_conn,
+            scan_id,
+            "2024-02-01T00:00:00",
+            "/data/",
+            &[],
+        )
+        .await
+        .unwrap();
+
+        let rows: Vec<(String, bool, String)> =
+            sqlx::query_as("SELECT filename, available, last_seen FROM files ORDER BY filename")
+                .fetch_all(&mut dbs.index_conn)
+                .await
+                .unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                (
+                    "found.png".to_string(),
+                    true,
+                    "2024-02-01T00:00:00".to_string()
+                ),
+                (
+                    "gone.png".to_string(),
+                    false,
+                    "2024-01-01T00:00:00".to_string()
+                ),
+            ]
+        );
+    }
+
     // Ensures unavailable deletion removes only files flagged unavailable.
     #[tokio::test]
     async fn delete_unavailable_files_removes_only_unavailable() {
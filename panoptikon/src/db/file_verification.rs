@@ -0,0 +1,306 @@
+use serde::Serialize;
+use sqlx::Row;
+use utoipa::ToSchema;
+
+use crate::api_error::ApiError;
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+fn internal(context: &'static str) -> impl Fn(sqlx::Error) -> ApiError {
+    move |err| {
+        tracing::error!(error = %err, context, "file verification query failed");
+        ApiError::internal(context)
+    }
+}
+
+/// An available file to re-hash, with what the index recorded for it.
+#[derive(Debug, Clone)]
+pub(crate) struct VerificationCandidate {
+    pub file_id: i64,
+    pub path: String,
+    pub path_bytes: Option<Vec<u8>>,
+    pub sha256: String,
+    pub last_modified: String,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub(crate) struct VerificationRunRecord {
+    pub id: i64,
+    pub start_time: String,
+    /// Null while the run is in progress, or if it was cancelled.
+    pub end_time: Option<String>,
+    pub path_prefix: Option<String>,
+    pub modified_since: Option<String>,
+    pub max_files: Option<i64>,
+    pub max_mb_per_sec: f64,
+    /// Files looked at, whatever their outcome.
+    pub checked: i64,
+    /// Files whose content still hashes to the stored sha256.
+    pub verified: i64,
+    pub mismatched: i64,
+    /// Files that exist but failed to read (I/O errors).
+    pub unreadable: i64,
+    /// Files no longer on disk; the next scan marks them unavailable.
+    pub missing: i64,
+    /// Files modified since they were indexed, skipped as edits rather than
+    /// bit rot; the next scan re-hashes them.
+    pub changed: i64,
+    pub bytes_read: i64,
+}
+
+/// Running totals of a verification run, written as progress and at the end.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct VerificationRunUpdate {
+    /// None for progress updates; set once the run finishes.
+    pub end_time: Option<String>,
+    pub checked: i64,
+    pub verified: i64,
+    pub mismatched: i64,
+    pub unreadable: i64,
+    pub missing: i64,
+    pub changed: i64,
+    pub bytes_read: i64,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub(crate) struct VerificationResult {
+    pub id: i64,
+    pub run_id: i64,
+    pub file_id: i64,
+    pub path: String,
+    /// `mismatch` or `unreadable`.
+    pub status: String,
+    pub expected_sha256: String,
+    /// The hash of the content read this run; null when unreadable.
+    pub actual_sha256: Option<String>,
+    pub error: Option<String>,
+    pub checked_at: String,
+}
+
+/// A mismatched or unreadable file found by a run.
+#[derive(Debug, Clone)]
+pub(crate) struct NewVerificationResult {
+    pub file_id: i64,
+    pub path: String,
+    pub status: &'static str,
+    pub expected_sha256: String,
+    pub actual_sha256: Option<String>,
+    pub error: Option<String>,
+    pub checked_at: String,
+}
+
+/// One page of available files to verify, in file ID order after `after_id`.
+/// `modified_since` is an ISO-8601 string compared against
+/// `files.last_modified`.
+pub(crate) async fn get_verification_candidates(
+    conn: &mut sqlx::SqliteConnection,
+    path_prefix: Option<&str>,
+    modified_since: Option<&str>,
+    after_id: i64,
+    limit: i64,
+) -> ApiResult<Vec<VerificationCandidate>> {
+    let rows = sqlx::query(
+        r#"
+SELECT id, path, path_bytes, sha256, last_modified
+FROM files
+WHERE available = 1
+  AND id > ?1
+  AND (?2 IS NULL OR path LIKE ?2 || '%')
+  AND (?3 IS NULL OR last_modified >= ?3)
+ORDER BY id
+LIMIT ?4
+        "#,
+    )
+    .bind(after_id)
+    .bind(path_prefix)
+    .bind(modified_since)
+    .bind(limit)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(internal("Failed to list files to verify"))?;
+    rows.iter()
+        .map(|row| {
+            Ok(VerificationCandidate {
+                file_id: row.try_get("id")?,
+                path: row.try_get("path")?,
+                path_bytes: row.try_get("path_bytes")?,
+                sha256: row.try_get("sha256")?,
+                last_modified: row.try_get("last_modified")?,
+            })
+        })
+        .collect::<Result<Vec<_>, sqlx::Error>>()
+        .map_err(internal("Failed to list files to verify"))
+}
+
+pub(crate) async fn add_verification_run(
+    conn: &mut sqlx::SqliteConnection,
+    start_time: &str,
+    path_prefix: Option<&str>,
+    modified_since: Option<&str>,
+    max_files: Option<i64>,
+    max_mb_per_sec: f64,
+) -> ApiResult<i64> {
+    let result = sqlx::query(
+        r#"
+INSERT INTO file_verification_runs
+    (start_time, path_prefix, modified_since, max_files, max_mb_per_sec)
+VALUES (?1, ?2, ?3, ?4, ?5)
+        "#,
+    )
+    .bind(start_time)
+    .bind(path_prefix)
+    .bind(modified_since)
+    .bind(max_files)
+    .bind(max_mb_per_sec)
+    .execute(&mut *conn)
+    .await
+    .map_err(internal("Failed to create verification run"))?;
+    Ok(result.last_insert_rowid())
+}
+
+pub(crate) async fn update_verification_run(
+    conn: &mut sqlx::SqliteConnection,
+    run_id: i64,
+    update: &VerificationRunUpdate,
+) -> ApiResult<()> {
+    sqlx::query(
+        r#"
+UPDATE file_verification_runs
+SET
+    end_time = ?1,
+    checked = ?2,
+    verified = ?3,
+    mismatched = ?4,
+    unreadable = ?5,
+    missing = ?6,
+    changed = ?7,
+    bytes_read = ?8
+WHERE id = ?9
+        "#,
+    )
+    .bind(&update.end_time)
+    .bind(update.checked)
+    .bind(update.verified)
+    .bind(update.mismatched)
+    .bind(update.unreadable)
+    .bind(update.missing)
+    .bind(update.changed)
+    .bind(update.bytes_read)
+    .bind(run_id)
+    .execute(&mut *conn)
+    .await
+    .map_err(internal("Failed to update verification run"))?;
+    Ok(())
+}
+
+pub(crate) async fn add_verification_result(
+    conn: &mut sqlx::SqliteConnection,
+    run_id: i64,
+    result: &NewVerificationResult,
+) -> ApiResult<()> {
+    sqlx::query(
+        r#"
+INSERT INTO file_verification_results
+    (run_id, file_id, path, status, expected_sha256, actual_sha256, error, checked_at)
+VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+        "#,
+    )
+    .bind(run_id)
+    .bind(result.file_id)
+    .bind(&result.path)
+    .bind(result.status)
+    .bind(&result.expected_sha256)
+    .bind(&result.actual_sha256)
+    .bind(&result.error)
+    .bind(&result.checked_at)
+    .execute(&mut *conn)
+    .await
+    .map_err(internal("Failed to record verification result"))?;
+    Ok(())
+}
+
+/// Verification runs, newest first.
+pub(crate) async fn get_verification_runs(
+    conn: &mut sqlx::SqliteConnection,
+    limit: i64,
+) -> ApiResult<Vec<VerificationRunRecord>> {
+    let rows = sqlx::query(
+        r#"
+SELECT
+    id, start_time, end_time, path_prefix, modified_since, max_files,
+    max_mb_per_sec, checked, verified, mismatched, unreadable, missing,
+    changed, bytes_read
+FROM file_verification_runs
+ORDER BY id DESC
+LIMIT ?1
+        "#,
+    )
+    .bind(limit)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(internal("Failed to get verification runs"))?;
+    rows.iter()
+        .map(|row| {
+            Ok(VerificationRunRecord {
+                id: row.try_get("id")?,
+                start_time: row.try_get("start_time")?,
+                end_time: row.try_get("end_time")?,
+                path_prefix: row.try_get("path_prefix")?,
+                modified_since: row.try_get("modified_since")?,
+                max_files: row.try_get("max_files")?,
+                max_mb_per_sec: row.try_get("max_mb_per_sec")?,
+                checked: row.try_get("checked")?,
+                verified: row.try_get("verified")?,
+                mismatched: row.try_get("mismatched")?,
+                unreadable: row.try_get("unreadable")?,
+                missing: row.try_get("missing")?,
+                changed: row.try_get("changed")?,
+                bytes_read: row.try_get("bytes_read")?,
+            })
+        })
+        .collect::<Result<Vec<_>, sqlx::Error>>()
+        .map_err(internal("Failed to get verification runs"))
+}
+
+/// Recorded results, newest first, optionally for one run only.
+pub(crate) async fn get_verification_results(
+    conn: &mut sqlx::SqliteConnection,
+    run_id: Option<i64>,
+    page: i64,
+    page_size: i64,
+) -> ApiResult<Vec<VerificationResult>> {
+    let offset = page.saturating_sub(1).saturating_mul(page_size);
+    let rows = sqlx::query(
+        r#"
+SELECT
+    id, run_id, file_id, path, status, expected_sha256, actual_sha256, error,
+    checked_at
+FROM file_verification_results
+WHERE ?1 IS NULL OR run_id = ?1
+ORDER BY id DESC
+LIMIT ?2 OFFSET ?3
+        "#,
+    )
+    .bind(run_id)
+    .bind(page_size)
+    .bind(offset)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(internal("Failed to get verification results"))?;
+    rows.iter()
+        .map(|row| {
+            Ok(VerificationResult {
+                id: row.try_get("id")?,
+                run_id: row.try_get("run_id")?,
+                file_id: row.try_get("file_id")?,
+                path: row.try_get("path")?,
+                status: row.try_get("status")?,
+                expected_sha256: row.try_get("expected_sha256")?,
+                actual_sha256: row.try_get("actual_sha256")?,
+                error: row.try_get("error")?,
+                checked_at: row.try_get("checked_at")?,
+            })
+        })
+        .collect::<Result<Vec<_>, sqlx::Error>>()
+        .map_err(internal("Failed to get verification results"))
+}
@@ -1,3 +1,6 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
 use sea_query::SqliteQueryBuilder;
 use sea_query_sqlx::SqlxBinder;
 use sqlx::Row;
@@ -6,6 +9,7 @@ use crate::pql::build_query;
 use crate::pql::model::{AndOperator, JobFilter, NotOperator, PqlQuery, QueryElement};
 
 use crate::api_error::ApiError;
+use crate::db::raw_paths::disk_path;
 
 type ApiResult<T> = std::result::Result<T, ApiError>;
 
@@ -19,6 +23,8 @@ pub(crate) struct ItemScanMeta {
     pub audio_tracks: Option<i64>,
     pub video_tracks: Option<i64>,
     pub subtitle_tracks: Option<i64>,
+    /// No frame of the video could be decoded, even by the fallbacks.
+    pub undecodable: bool,
 }
 
 #[derive(Clone)]
@@ -26,6 +32,11 @@ pub(crate) struct FileScanData {
     pub sha256: String,
     pub last_modified: String,
     pub path: String,
+    /// `raw_paths::path_bytes` of the path: set only when it is not UTF-8.
+    pub path_bytes: Option<Vec<u8>>,
+    /// `PathKeys::key(path)`; the row is matched on it, so a path seen with
+    /// other casing updates the same row.
+    pub path_key: String,
     pub new_file_hash: bool,
     pub file_size: Option<i64>,
     pub item_metadata: Option<ItemScanMeta>,
@@ -35,6 +46,8 @@ pub(crate) struct FileScanData {
 pub(crate) struct FilePathRecord {
     pub sha256: String,
     pub last_modified: String,
+    /// Size of the item the path was last indexed as.
+    pub size: Option<i64>,
 }
 
 pub(crate) struct FileDeleteInfo {
@@ -49,20 +62,21 @@ pub(crate) struct FileUpsertResult {
     pub file_inserted: bool,
 }
 
+/// The file stored under `path_key` (see `db::path_keys`).
 pub(crate) async fn get_file_by_path(
     conn: &mut sqlx::SqliteConnection,
-    path: &str,
+    path_key: &str,
 ) -> ApiResult<Option<FilePathRecord>> {
     let row = sqlx::query(
         r#"
-SELECT files.sha256 AS sha256, files.last_modified AS last_modified
+SELECT files.sha256 AS sha256, files.last_modified AS last_modified, items.size AS size
 FROM files
 JOIN items ON files.sha256 = items.sha256
-WHERE files.path = ?1
+WHERE files.path_key = ?1
 LIMIT 1
         "#,
     )
-    .bind(path)
+    .bind(path_key)
     .fetch_optional(&mut *conn)
     .await
     .map_err(|err| {
@@ -83,20 +97,73 @@ LIMIT 1
         ApiError::internal("Failed to query file")
     })?;
 
+    let size: Option<i64> = row.try_get("size").map_err(|err| {
+        tracing::error!(error = %err, "failed to read item size");
+        ApiError::internal("Failed to query file")
+    })?;
+
     Ok(Some(FilePathRecord {
         sha256,
         last_modified,
+        size,
     }))
 }
 
-/// Bulk-loads every known file path with its stored mtime, used to seed the
-/// continuous-scan directory poller so unchanged files are never re-dispatched.
+/// Stored state of every indexed path key in `path_keys`, keyed by path
+/// key; keys that are not indexed are absent. Issues one query, so callers
+/// chunk `path_keys` well under SQLite's variable limit.
+pub(crate) async fn get_files_by_paths(
+    conn: &mut sqlx::SqliteConnection,
+    path_keys: &[String],
+) -> ApiResult<HashMap<String, FilePathRecord>> {
+    if path_keys.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let placeholders = vec!["?"; path_keys.len()].join(", ");
+    let sql = format!(
+        "SELECT files.path_key AS path_key, files.sha256 AS sha256,
+                files.last_modified AS last_modified, items.size AS size
+         FROM files
+         JOIN items ON files.sha256 = items.sha256
+         WHERE files.path_key IN ({placeholders})"
+    );
+    let mut query = sqlx::query(sqlx::AssertSqlSafe(sql.as_str()));
+    for key in path_keys {
+        query = query.bind(key);
+    }
+    let rows = query.fetch_all(&mut *conn).await.map_err(|err| {
+        tracing::error!(error = %err, "failed to query files by path");
+        ApiError::internal("Failed to query files")
+    })?;
+
+    let read_error = |err: sqlx::Error| {
+        tracing::error!(error = %err, "failed to read file by path");
+        ApiError::internal("Failed to query files")
+    };
+    let mut records = HashMap::with_capacity(rows.len());
+    for row in rows {
+        let key: String = row.try_get("path_key").map_err(read_error)?;
+        records.insert(
+            key,
+            FilePathRecord {
+                sha256: row.try_get("sha256").map_err(read_error)?,
+                last_modified: row.try_get("last_modified").map_err(read_error)?,
+                size: row.try_get("size").map_err(read_error)?,
+            },
+        );
+    }
+    Ok(records)
+}
+
+/// Bulk-loads every known file's on-disk path with its stored mtime, used to
+/// seed the continuous-scan directory poller so unchanged files are never
+/// re-dispatched.
 pub(crate) async fn get_all_file_paths_with_mtime(
     conn: &mut sqlx::SqliteConnection,
-) -> ApiResult<Vec<(String, String)>> {
-    sqlx::query_as::<_, (String, String)>(
+) -> ApiResult<Vec<(PathBuf, String)>> {
+    let rows = sqlx::query_as::<_, (String, Option<Vec<u8>>, String)>(
         r#"
-SELECT path, last_modified
+SELECT path, path_bytes, last_modified
 FROM files
         "#,
     )
@@ -105,22 +172,54 @@ FROM files
     .map_err(|err| {
         tracing::error!(error = %err, "failed to load file mtimes");
         ApiError::internal("Failed to load file mtimes")
-    })
+    })?;
+    Ok(rows
+        .into_iter()
+        .map(|(path, path_bytes, last_modified)| {
+            (disk_path(&path, path_bytes.as_deref()), last_modified)
+        })
+        .collect())
+}
+
+/// Raw bytes of those of `paths` that are stored escaped (not UTF-8), keyed
+/// by their text path.
+pub(crate) async fn get_raw_path_bytes(
+    conn: &mut sqlx::SqliteConnection,
+    paths: &[String],
+) -> ApiResult<HashMap<String, Vec<u8>>> {
+    let paths_json = serde_json::to_string(paths)
+        .map_err(|err| ApiError::internal(format!("Failed to encode paths: {err}")))?;
+    let rows = sqlx::query_as::<_, (String, Vec<u8>)>(
+        r#"
+SELECT path, path_bytes
+FROM files
+WHERE path_bytes IS NOT NULL
+AND path IN (SELECT value FROM json_each(?1))
+        "#,
+    )
+    .bind(paths_json)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|err| {
+        tracing::error!(error = %err, "failed to load raw file paths");
+        ApiError::internal("Failed to load raw file paths")
+    })?;
+    Ok(rows.into_iter().collect())
 }
 
 pub(crate) async fn get_file_delete_info(
     conn: &mut sqlx::SqliteConnection,
-    path: &str,
+    path_key: &str,
 ) -> ApiResult<Option<FileDeleteInfo>> {
     let row = sqlx::query(
         r#"
 SELECT files.item_id AS item_id, files.scan_id AS scan_id
 FROM files
-WHERE files.path = ?1
+WHERE files.path_key = ?1
 LIMIT 1
         "#,
     )
-    .bind(path)
+    .bind(path_key)
     .fetch_optional(&mut *conn)
     .await
     .map_err(|err| {
@@ -161,19 +260,36 @@ pub(crate) async fn count_files_for_item(
 
 pub(crate) async fn delete_file_by_path(
     conn: &mut sqlx::SqliteConnection,
-    path: &str,
+    path_key: &str,
 ) -> ApiResult<u64> {
-    let result = sqlx::query("DELETE FROM files WHERE path = ?1")
-        .bind(path)
+    let result = sqlx::query("DELETE FROM files WHERE path_key = ?1")
+        .bind(path_key)
         .execute(&mut *conn)
         .await
         .map_err(|err| {
-            tracing::error!(error = %err, path = %path, "failed to delete file path");
+            tracing::error!(error = %err, path_key = %path_key, "failed to delete file path");
             ApiError::internal("Failed to delete file")
         })?;
     Ok(result.rows_affected())
 }
 
+/// Marks the file stored under `path_key` unavailable, as a scan does for
+/// files it no longer finds. False when no file is recorded there.
+pub(crate) async fn mark_file_unavailable(
+    conn: &mut sqlx::SqliteConnection,
+    path_key: &str,
+) -> ApiResult<bool> {
+    let result = sqlx::query("UPDATE files SET available = FALSE WHERE path_key = ?1")
+        .bind(path_key)
+        .execute(&mut *conn)
+        .await
+        .map_err(|err| {
+            tracing::error!(error = %err, path_key = %path_key, "failed to mark file unavailable");
+            ApiError::internal("Failed to update file")
+        })?;
+    Ok(result.rows_affected() > 0)
+}
+
 pub(crate) async fn delete_item_if_orphan(
     conn: &mut sqlx::SqliteConnection,
     item_id: i64,
@@ -195,10 +311,15 @@ WHERE id = ?1
     Ok(result.rows_affected() > 0)
 }
 
+/// Moves the file stored under `old_key` to `new_path`. A case-only rename
+/// under a case-insensitive folder keeps its key and only updates the
+/// displayed path.
 pub(crate) async fn rename_file_path(
     conn: &mut sqlx::SqliteConnection,
-    old_path: &str,
+    old_key: &str,
     new_path: &str,
+    new_path_bytes: Option<&[u8]>,
+    new_key: &str,
     scan_id: i64,
     last_modified: &str,
 ) -> ApiResult<bool> {
@@ -211,18 +332,23 @@ pub(crate) async fn rename_file_path(
         r#"
 UPDATE files
 SET path = ?1,
-    filename = ?2,
-    scan_id = ?3,
+    path_key = ?2,
+    filename = ?3,
+    scan_id = ?4,
     available = TRUE,
-    last_modified = ?4
-WHERE path = ?5
+    last_modified = ?5,
+    last_seen = strftime('%Y-%m-%dT%H:%M:%S', 'now', 'localtime'),
+    path_bytes = ?7
+WHERE path_key = ?6
         "#,
     )
     .bind(new_path)
+    .bind(new_key)
     .bind(filename)
     .bind(scan_id)
     .bind(last_modified)
-    .bind(old_path)
+    .bind(old_key)
+    .bind(new_path_bytes)
     .execute(&mut *conn)
     .await
     .map_err(|err| {
@@ -256,6 +382,41 @@ WHERE id = ?2
     Ok(result.rows_affected() > 0)
 }
 
+/// Overwrites an existing item's probed metadata (type and media
+/// properties) with `meta`. Scans only write metadata when inserting an
+/// item; this is for an explicit re-probe of a file that is already indexed.
+pub(crate) async fn update_item_metadata(
+    conn: &mut sqlx::SqliteConnection,
+    sha256: &str,
+    meta: &ItemScanMeta,
+) -> ApiResult<bool> {
+    let result = sqlx::query(
+        r#"
+UPDATE items
+SET type = ?1, width = ?2, height = ?3, duration = ?4,
+    audio_tracks = ?5, video_tracks = ?6, subtitle_tracks = ?7, undecodable = ?8
+WHERE sha256 = ?9
+        "#,
+    )
+    .bind(&meta.mime_type)
+    .bind(meta.width)
+    .bind(meta.height)
+    .bind(meta.duration)
+    .bind(meta.audio_tracks)
+    .bind(meta.video_tracks)
+    .bind(meta.subtitle_tracks)
+    .bind(meta.undecodable)
+    .bind(sha256)
+    .execute(&mut *conn)
+    .await
+    .map_err(|err| {
+        tracing::error!(error = %err, sha256, "failed to update item metadata");
+        ApiError::internal("Failed to update item")
+    })?;
+
+    Ok(result.rows_affected() > 0)
+}
+
 pub(crate) async fn get_item_id(
     conn: &mut sqlx::SqliteConnection,
     sha256: &str,
@@ -271,6 +432,59 @@ pub(crate) async fn get_item_id(
     Ok(row.map(|(id,)| id))
 }
 
+/// Pins `file_id` as the primary file of `item_id`, replacing any earlier
+/// pin, or clears the item's pin when `file_id` is None. Fails with 400
+/// when the file does not belong to the item.
+pub(crate) async fn set_primary_file(
+    conn: &mut sqlx::SqliteConnection,
+    item_id: i64,
+    file_id: Option<i64>,
+) -> ApiResult<()> {
+    let Some(file_id) = file_id else {
+        sqlx::query("DELETE FROM item_primary_files WHERE item_id = ?1")
+            .bind(item_id)
+            .execute(&mut *conn)
+            .await
+            .map_err(|err| {
+                tracing::error!(error = %err, item_id, "failed to clear primary file");
+                ApiError::internal("Failed to set primary file")
+            })?;
+        return Ok(());
+    };
+    let owner: Option<i64> = sqlx::query_scalar("SELECT item_id FROM files WHERE id = ?1")
+        .bind(file_id)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|err| {
+            tracing::error!(error = %err, file_id, "failed to read file item");
+            ApiError::internal("Failed to set primary file")
+        })?;
+    if owner != Some(item_id) {
+        return Err(ApiError::bad_request(format!(
+            "File {file_id} does not belong to this item"
+        )));
+    }
+    sqlx::query(
+        r#"
+INSERT INTO item_primary_files (item_id, file_id, time_pinned)
+VALUES (?1, ?2, ?3)
+ON CONFLICT(item_id) DO UPDATE SET
+    file_id = excluded.file_id,
+    time_pinned = excluded.time_pinned
+        "#,
+    )
+    .bind(item_id)
+    .bind(file_id)
+    .bind(crate::db::extraction_write::current_iso_timestamp())
+    .execute(&mut *conn)
+    .await
+    .map_err(|err| {
+        tracing::error!(error = %err, item_id, file_id, "failed to set primary file");
+        ApiError::internal("Failed to set primary file")
+    })?;
+    Ok(())
+}
+
 /// Returns `(duration, video_tracks)` for the item, used to decide whether
 /// video thumbnail generation is possible without probing the file.
 pub(crate) async fn get_item_visual_meta(
@@ -311,6 +525,14 @@ pub(crate) async fn has_blurhash(
     conn: &mut sqlx::SqliteConnection,
     sha256: &str,
 ) -> ApiResult<bool> {
+    Ok(get_blurhash(conn, sha256).await?.is_some())
+}
+
+/// The item's stored blurhash; None when the item is unknown or has none.
+pub(crate) async fn get_blurhash(
+    conn: &mut sqlx::SqliteConnection,
+    sha256: &str,
+) -> ApiResult<Option<String>> {
     let row: Option<(Option<String>,)> =
         sqlx::query_as("SELECT blurhash FROM items WHERE sha256 = ?1")
             .bind(sha256)
@@ -321,7 +543,25 @@ pub(crate) async fn has_blurhash(
                 ApiError::internal("Failed to load blurhash")
             })?;
 
-    Ok(row.and_then(|(value,)| value).is_some())
+    Ok(row.and_then(|(value,)| value))
+}
+
+/// Sets or clears an item's `undecodable` marker.
+pub(crate) async fn set_item_undecodable(
+    conn: &mut sqlx::SqliteConnection,
+    sha256: &str,
+    undecodable: bool,
+) -> ApiResult<()> {
+    sqlx::query("UPDATE items SET undecodable = ?1 WHERE sha256 = ?2")
+        .bind(undecodable)
+        .bind(sha256)
+        .execute(&mut *conn)
+        .await
+        .map_err(|err| {
+            tracing::error!(error = %err, "failed to update undecodable marker");
+            ApiError::internal("Failed to update item")
+        })?;
+    Ok(())
 }
 
 pub(crate) async fn set_blurhash(
@@ -366,8 +606,9 @@ INSERT INTO items (
     audio_tracks,
     video_tracks,
     subtitle_tracks,
-    blurhash
-) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
+    blurhash,
+    undecodable
+) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
                 "#,
             )
             .bind(&data.sha256)
@@ -382,6 +623,7 @@ INSERT INTO items (
             .bind(meta.video_tracks)
             .bind(meta.subtitle_tracks)
             .bind(&data.blurhash)
+            .bind(meta.undecodable)
             .execute(&mut *conn)
             .await
             .map_err(|err| {
@@ -408,13 +650,15 @@ INSERT INTO items (
         let result = sqlx::query(
             r#"
 UPDATE files
-SET scan_id = ?1, available = TRUE, last_modified = ?2
-WHERE path = ?3
+SET scan_id = ?1, available = TRUE, last_modified = ?2,
+    time_added = COALESCE(time_added, ?4), last_seen = ?4
+WHERE path_key = ?3
             "#,
         )
         .bind(scan_id)
         .bind(&data.last_modified)
-        .bind(&data.path)
+        .bind(&data.path_key)
+        .bind(time_added)
         .execute(&mut *conn)
         .await
         .map_err(|err| {
@@ -430,7 +674,29 @@ WHERE path = ?3
         });
     }
 
-    let delete_result = sqlx::query("DELETE FROM files WHERE path = ?1")
+    // The path keeps the time it was first seen, and the casing it was first
+    // stored with, even when its content (and so its item) changed. The
+    // exact path also matches, in case the row was keyed before its folder
+    // was found to be case-insensitive.
+    let first_seen: Option<(Option<String>, String, Option<Vec<u8>>)> = sqlx::query_as(
+        "SELECT time_added, path, path_bytes FROM files WHERE path_key = ?1 OR path = ?2 LIMIT 1",
+    )
+    .bind(&data.path_key)
+    .bind(&data.path)
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(|err| {
+        tracing::error!(error = %err, path = %data.path, "failed to read file time_added");
+        ApiError::internal("Failed to update file")
+    })?;
+    let (file_time_added, path, path_bytes) = match first_seen {
+        Some((file_time_added, path, path_bytes)) => (file_time_added, path, path_bytes),
+        None => (None, data.path.clone(), data.path_bytes.clone()),
+    };
+    let file_time_added = file_time_added.as_deref().unwrap_or(time_added);
+
+    let delete_result = sqlx::query("DELETE FROM files WHERE path_key = ?1 OR path = ?2")
+        .bind(&data.path_key)
         .bind(&data.path)
         .execute(&mut *conn)
         .await
@@ -440,7 +706,7 @@ WHERE path = ?3
         })?;
     let file_deleted = delete_result.rows_affected() > 0;
 
-    let filename = std::path::Path::new(&data.path)
+    let filename = std::path::Path::new(&path)
         .file_name()
         .and_then(|name| name.to_str())
         .unwrap_or("")
@@ -448,16 +714,23 @@ WHERE path = ?3
 
     let insert_result = sqlx::query(
         r#"
-INSERT INTO files (sha256, item_id, path, filename, last_modified, scan_id, available)
-VALUES (?1, ?2, ?3, ?4, ?5, ?6, TRUE)
+INSERT INTO files (
+    sha256, item_id, path, path_key, filename, last_modified, scan_id, available, time_added,
+    last_seen, path_bytes
+)
+VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, TRUE, ?8, ?9, ?10)
         "#,
     )
     .bind(&data.sha256)
     .bind(item_id)
-    .bind(&data.path)
+    .bind(&path)
+    .bind(&data.path_key)
     .bind(&filename)
     .bind(&data.last_modified)
     .bind(scan_id)
+    .bind(file_time_added)
+    .bind(time_added)
+    .bind(path_bytes)
     .execute(&mut *conn)
     .await
     .map_err(|err| {
@@ -666,6 +939,8 @@ VALUES ('sha_one', 1, 'C:\data\one.png', 'one.png', '2024-01-01T00:00:00', ?1, 1
                 sha256: "sha_one".to_string(),
                 last_modified: "2024-01-01T00:00:00".to_string(),
                 path: r"C:\data\one.png".to_string(),
+                path_bytes: None,
+                path_key: r"C:\data\one.png".to_string(),
                 new_file_hash: true,
                 file_size: Some(12),
                 item_metadata: Some(ItemScanMeta {
@@ -677,6 +952,7 @@ VALUES ('sha_one', 1, 'C:\data\one.png', 'one.png', '2024-01-01T00:00:00', ?1, 1
                     audio_tracks: None,
                     video_tracks: None,
                     subtitle_tracks: None,
+                    undecodable: false,
                 }),
                 blurhash: Some("bh".to_string()),
             },
@@ -736,6 +1012,8 @@ VALUES ('sha_one', 1, 'C:\data\one.png', 'one.png', '2024-01-01T00:00:00', 1, 0)
                 sha256: "sha_one".to_string(),
                 last_modified: "2024-01-01T00:02:00".to_string(),
                 path: r"C:\data\one.png".to_string(),
+                path_bytes: None,
+                path_key: r"C:\data\one.png".to_string(),
                 new_file_hash: false,
                 file_size: None,
                 item_metadata: None,
@@ -799,4 +1077,97 @@ VALUES ('sha_one', 1, 'C:\data\one.png', 'one.png', '2024-01-01T00:00:00', ?1, 1
             .unwrap();
         assert_eq!(remaining.0, 1);
     }
+
+    // Ensures a file can only be pinned as the primary file of its own item,
+    // a new pin replaces the old one, and None clears it.
+    #[tokio::test]
+    async fn set_primary_file_validates_and_replaces_pin() {
+        let mut dbs = setup_test_databases().await;
+        let scan_id = add_file_scan(&mut dbs.index_conn, "2024-01-01T00:00:00", "/data")
+            .await
+            .unwrap();
+        sqlx::query(
+            r#"
+INSERT INTO items (id, sha256, md5, type, time_added) VALUES
+    (1, 'sha_one', 'md5_one', 'image/png', '2024-01-01T00:00:00'),
+    (2, 'sha_two', 'md5_two', 'image/png', '2024-01-01T00:00:00')
+            "#,
+        )
+        .execute(&mut dbs.index_conn)
+        .await
+        .unwrap();
+        sqlx::query(
+            r#"
+INSERT INTO files (id, sha256, item_id, path, filename, last_modified, scan_id, available) VALUES
+    (10, 'sha_one', 1, '/data/a.png', 'a.png', '2024-01-01T00:00:00', ?1, 1),
+    (11, 'sha_one', 1, '/data/b.png', 'b.png', '2024-01-01T00:00:00', ?1, 1),
+    (12, 'sha_two', 2, '/data/c.png', 'c.png', '2024-01-01T00:00:00', ?1, 1)
+            "#,
+        )
+        .bind(scan_id)
+        .execute(&mut dbs.index_conn)
+        .await
+        .unwrap();
+        async fn pinned(conn: &mut sqlx::SqliteConnection) -> sqlx::Result<Option<i64>> {
+            sqlx::query_scalar("SELECT file_id FROM item_primary_files WHERE item_id = 1")
+                .fetch_optional(conn)
+                .await
+        }
+
+        let err = set_primary_file(&mut dbs.index_conn, 1, Some(12))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), axum::http::StatusCode::BAD_REQUEST);
+        set_primary_file(&mut dbs.index_conn, 1, Some(10)).await.unwrap();
+        set_primary_file(&mut dbs.index_conn, 1, Some(11)).await.unwrap();
+        assert_eq!(pinned(&mut dbs.index_conn).await.unwrap(), Some(11));
+        set_primary_file(&mut dbs.index_conn, 1, None).await.unwrap();
+        assert_eq!(pinned(&mut dbs.index_conn).await.unwrap(), None);
+    }
+
+    // Ensures rows stored with raw bytes come back as their on-disk path,
+    // not the escaped text.
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn raw_path_bytes_resolve_to_the_disk_path() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let mut dbs = setup_test_databases().await;
+        let scan_id = add_file_scan(&mut dbs.index_conn, "2024-01-01T00:00:00", "/data/")
+            .await
+            .unwrap();
+        let raw = b"/data/caf\xe9.png".to_vec();
+        sqlx::query(
+            r#"
+INSERT INTO items (id, sha256, md5, type, time_added)
+VALUES (1, 'sha_one', 'md5_one', 'image/png', '2024-01-01T00:00:00');
+INSERT INTO files (sha256, item_id, path, path_bytes, filename, last_modified, scan_id, available)
+VALUES
+    ('sha_one', 1, '/data/caf\xE9.png', ?1, 'caf\xE9.png', '2024-01-01T00:00:00', ?2, 1),
+    ('sha_one', 1, '/data/plain.png', NULL, 'plain.png', '2024-01-01T00:00:00', ?2, 1)
+            "#,
+        )
+        .bind(&raw)
+        .bind(scan_id)
+        .execute(&mut dbs.index_conn)
+        .await
+        .unwrap();
+
+        let mut paths = get_all_file_paths_with_mtime(&mut dbs.index_conn)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(path, _)| path.as_os_str().as_bytes().to_vec())
+            .collect::<Vec<_>>();
+        paths.sort();
+        assert_eq!(paths, vec![raw.clone(), b"/data/plain.png".to_vec()]);
+
+        let bytes = get_raw_path_bytes(
+            &mut dbs.index_conn,
+            &[r"/data/caf\xE9.png".to_string(), "/data/plain.png".to_string()],
+        )
+        .await
+        .unwrap();
+        assert_eq!(bytes, HashMap::from([(r"/data/caf\xE9.png".to_string(), raw)]));
+    }
 }
@@ -1,4 +1,6 @@
+use serde::Serialize;
 use sqlx::Row;
+use utoipa::ToSchema;
 
 use crate::api_error::ApiError;
 
@@ -67,52 +69,277 @@ pub(crate) async fn delete_folders_not_in_list(
     Ok(result.rows_affected())
 }
 
+/// Files under an excluded folder.
+const UNDER_EXCLUDED_FOLDER: &str = r#"EXISTS (
+    SELECT 1
+    FROM folders
+    WHERE folders.included = 0
+    AND files.path LIKE folders.path || '%'
+)"#;
+
+/// Files under no included folder.
+const OUTSIDE_INCLUDED_FOLDERS: &str = r#"NOT EXISTS (
+    SELECT 1
+    FROM folders
+    WHERE folders.included = 1
+    AND files.path LIKE folders.path || '%'
+)"#;
+
+/// Deletes the files matching `predicate`, or with `soft` marks them
+/// unavailable and stamps `removed_at`, keeping an earlier stamp so the grace
+/// period runs from the first removal.
+async fn remove_files_matching(
+    conn: &mut sqlx::SqliteConnection,
+    predicate: &str,
+    soft: bool,
+) -> Result<u64, sqlx::Error> {
+    let sql = if soft {
+        format!(
+            r#"
+UPDATE files
+SET available = FALSE,
+    removed_at = strftime('%Y-%m-%dT%H:%M:%S', 'now', 'localtime')
+WHERE removed_at IS NULL
+AND {predicate}
+        "#
+        )
+    } else {
+        format!("DELETE FROM files WHERE {predicate}")
+    };
+    let result = sqlx::query(sqlx::AssertSqlSafe(sql))
+        .execute(&mut *conn)
+        .await?;
+    Ok(result.rows_affected())
+}
+
 pub(crate) async fn delete_files_under_excluded_folders(
     conn: &mut sqlx::SqliteConnection,
+    soft: bool,
+) -> ApiResult<u64> {
+    remove_files_matching(conn, UNDER_EXCLUDED_FOLDER, soft)
+        .await
+        .map_err(|err| {
+            tracing::error!(error = %err, "failed to delete files under excluded folders");
+            ApiError::internal("Failed to delete excluded files")
+        })
+}
+
+pub(crate) async fn delete_files_not_under_included_folders(
+    conn: &mut sqlx::SqliteConnection,
+    soft: bool,
+) -> ApiResult<u64> {
+    remove_files_matching(conn, OUTSIDE_INCLUDED_FOLDERS, soft)
+        .await
+        .map_err(|err| {
+            tracing::error!(error = %err, "failed to delete files outside included folders");
+            ApiError::internal("Failed to delete orphan files")
+        })
+}
+
+/// Undoes a soft removal for files back inside the indexed folders (their
+/// folder was re-added or is no longer excluded). They count as available
+/// again until the next scan of their folder checks them.
+pub(crate) async fn restore_removed_files(conn: &mut sqlx::SqliteConnection) -> ApiResult<u64> {
+    let sql = format!(
+        r#"
+UPDATE files
+SET available = TRUE, removed_at = NULL
+WHERE removed_at IS NOT NULL
+AND NOT {UNDER_EXCLUDED_FOLDER}
+AND NOT {OUTSIDE_INCLUDED_FOLDERS}
+        "#
+    );
+    let result = sqlx::query(sqlx::AssertSqlSafe(sql))
+        .execute(&mut *conn)
+        .await
+        .map_err(|err| {
+            tracing::error!(error = %err, "failed to restore removed files");
+            ApiError::internal("Failed to restore removed files")
+        })?;
+    Ok(result.rows_affected())
+}
+
+/// Deletes files a folder update removed more than `grace_days` days ago;
+/// with 0, every removed file.
+pub(crate) async fn purge_removed_files(
+    conn: &mut sqlx::SqliteConnection,
+    grace_days: u64,
 ) -> ApiResult<u64> {
     let result = sqlx::query(
         r#"
 DELETE FROM files
-WHERE EXISTS (
-    SELECT 1
-    FROM folders
-    WHERE folders.included = 0
-    AND files.path LIKE folders.path || '%'
-)
+WHERE removed_at IS NOT NULL
+AND removed_at <= strftime('%Y-%m-%dT%H:%M:%S', 'now', 'localtime', ?1)
         "#,
     )
+    .bind(format!("-{grace_days} days"))
     .execute(&mut *conn)
     .await
     .map_err(|err| {
-        tracing::error!(error = %err, "failed to delete files under excluded folders");
-        ApiError::internal("Failed to delete excluded files")
+        tracing::error!(error = %err, "failed to purge removed files");
+        ApiError::internal("Failed to purge removed files")
     })?;
-
     Ok(result.rows_affected())
 }
 
-pub(crate) async fn delete_files_not_under_included_folders(
+/// Why a folder update would take files out of the index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum FolderRemovalReason {
+    /// No longer under any included folder.
+    NotIncluded,
+    /// Under an excluded folder.
+    Excluded,
+}
+
+/// The files one folder would lose.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub(crate) struct FolderRemovalImpact {
+    /// The currently included folder the files are under for `not_included`
+    /// (null for files under none), or the excluded folder for `excluded`.
+    /// The longest matching folder wins.
+    pub folder: Option<String>,
+    pub reason: FolderRemovalReason,
+    pub files: i64,
+    /// Items left with no file, counted under every folder holding one of
+    /// their files.
+    pub items: i64,
+}
+
+/// What applying a pair of folder lists would take out of the index.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, ToSchema)]
+pub(crate) struct FolderRemovalPreview {
+    /// Files that would be removed
+    pub files: i64,
+    /// Items that would be left with no file and deleted
+    pub items: i64,
+    /// Files removed by an earlier folder update that would come back
+    pub restored_files: i64,
+    pub folders: Vec<FolderRemovalImpact>,
+}
+
+/// The files `included`/`excluded` would remove, as the `removed` CTE, with
+/// the predicates of [`OUTSIDE_INCLUDED_FOLDERS`] and
+/// [`UNDER_EXCLUDED_FOLDER`] evaluated against the given lists instead of
+/// the `folders` table. Binds both lists as JSON arrays (?1, ?2).
+const PREVIEW_CTES: &str = r#"
+WITH included(path) AS (SELECT value FROM json_each(?1)),
+excluded(path) AS (SELECT value FROM json_each(?2)),
+candidates AS (
+    SELECT files.id, files.item_id, files.path,
+        NOT EXISTS (
+            SELECT 1 FROM included WHERE files.path LIKE included.path || '%'
+        ) AS not_included
+    FROM files
+    WHERE files.removed_at IS NULL
+),
+removed AS (
+    SELECT candidates.id, candidates.item_id,
+        CASE WHEN candidates.not_included THEN 'not_included' ELSE 'excluded' END AS reason,
+        CASE WHEN candidates.not_included THEN (
+            SELECT folders.path FROM folders
+            WHERE folders.included = 1 AND candidates.path LIKE folders.path || '%'
+            ORDER BY length(folders.path) DESC LIMIT 1
+        ) ELSE (
+            SELECT excluded.path FROM excluded
+            WHERE candidates.path LIKE excluded.path || '%'
+            ORDER BY length(excluded.path) DESC LIMIT 1
+        ) END AS folder
+    FROM candidates
+    WHERE candidates.not_included
+    OR EXISTS (SELECT 1 FROM excluded WHERE candidates.path LIKE excluded.path || '%')
+),
+orphaned AS (
+    SELECT DISTINCT removed.item_id
+    FROM removed
+    WHERE NOT EXISTS (
+        SELECT 1 FROM files
+        WHERE files.item_id = removed.item_id
+        AND files.removed_at IS NULL
+        AND files.id NOT IN (SELECT id FROM removed)
+    )
+)
+"#;
+
+/// Counts, with read-only queries, what a folder update to `included` and
+/// `excluded` would remove (files outside the included folders or under an
+/// excluded one, and the items left without files) and restore.
+pub(crate) async fn preview_folder_removal(
     conn: &mut sqlx::SqliteConnection,
-) -> ApiResult<u64> {
-    let result = sqlx::query(
+    included: &[String],
+    excluded: &[String],
+) -> ApiResult<FolderRemovalPreview> {
+    let map_err = |err: sqlx::Error| {
+        tracing::error!(error = %err, "failed to preview folder update");
+        ApiError::internal("Failed to preview folder update")
+    };
+    let included = serde_json::to_string(included).unwrap_or_else(|_| "[]".to_string());
+    let excluded = serde_json::to_string(excluded).unwrap_or_else(|_| "[]".to_string());
+
+    let sql = format!(
+        r#"{PREVIEW_CTES}
+SELECT removed.folder, removed.reason, COUNT(*) AS files,
+    COUNT(DISTINCT orphaned.item_id) AS items
+FROM removed
+LEFT JOIN orphaned ON orphaned.item_id = removed.item_id
+GROUP BY removed.reason, removed.folder
+ORDER BY removed.reason DESC, removed.folder
+        "#
+    );
+    let rows = sqlx::query(sqlx::AssertSqlSafe(sql))
+        .bind(&included)
+        .bind(&excluded)
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(map_err)?;
+    let mut preview = FolderRemovalPreview::default();
+    for row in rows {
+        let reason = match row
+            .try_get::<String, _>("reason")
+            .map_err(map_err)?
+            .as_str()
+        {
+            "excluded" => FolderRemovalReason::Excluded,
+            _ => FolderRemovalReason::NotIncluded,
+        };
+        let impact = FolderRemovalImpact {
+            folder: row.try_get("folder").map_err(map_err)?,
+            reason,
+            files: row.try_get("files").map_err(map_err)?,
+            items: row.try_get("items").map_err(map_err)?,
+        };
+        preview.files += impact.files;
+        preview.folders.push(impact);
+    }
+
+    let sql = format!("{PREVIEW_CTES} SELECT COUNT(*) FROM orphaned");
+    preview.items = sqlx::query_scalar(sqlx::AssertSqlSafe(sql))
+        .bind(&included)
+        .bind(&excluded)
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(map_err)?;
+
+    preview.restored_files = sqlx::query_scalar(
         r#"
-DELETE FROM files
-WHERE NOT EXISTS (
-    SELECT 1
-    FROM folders
-    WHERE folders.included = 1
-    AND files.path LIKE folders.path || '%'
+SELECT COUNT(*)
+FROM files
+WHERE removed_at IS NOT NULL
+AND EXISTS (
+    SELECT 1 FROM json_each(?1) AS included WHERE files.path LIKE included.value || '%'
+)
+AND NOT EXISTS (
+    SELECT 1 FROM json_each(?2) AS excluded WHERE files.path LIKE excluded.value || '%'
 )
         "#,
     )
-    .execute(&mut *conn)
+    .bind(&included)
+    .bind(&excluded)
+    .fetch_one(&mut *conn)
     .await
-    .map_err(|err| {
-        tracing::error!(error = %err, "failed to delete files outside included folders");
-        ApiError::internal("Failed to delete orphan files")
-    })?;
+    .map_err(map_err)?;
 
-    Ok(result.rows_affected())
+    Ok(preview)
 }
 
 pub(crate) async fn get_folders_from_database(
@@ -243,7 +470,7 @@ VALUES
         .await
         .unwrap();
 
-        let deleted = delete_files_under_excluded_folders(&mut dbs.index_conn)
+        let deleted = delete_files_under_excluded_folders(&mut dbs.index_conn, false)
             .await
             .unwrap();
         assert_eq!(deleted, 1);
@@ -254,4 +481,157 @@ VALUES
             .unwrap();
         assert_eq!(remaining.0, 1);
     }
+
+    async fn seed_removal_fixture(conn: &mut sqlx::SqliteConnection, folders: &[(&str, bool)]) {
+        for (path, included) in folders {
+            add_folder_to_database(conn, "2024-01-01T00:00:00", path, *included)
+                .await
+                .unwrap();
+        }
+        for statement in [
+            r#"
+INSERT INTO items (id, sha256, md5, type, time_added) VALUES
+    (1, 'sha_one', 'md5_one', 'image/png', '2024-01-01T00:00:00'),
+    (2, 'sha_two', 'md5_two', 'image/png', '2024-01-01T00:00:00'),
+    (3, 'sha_three', 'md5_three', 'image/png', '2024-01-01T00:00:00')
+            "#,
+            "INSERT INTO file_scans (id, start_time, path) VALUES (1, '2024-01-01T00:00:00', '/data/')",
+            r#"
+INSERT INTO files (sha256, item_id, path, filename, last_modified, scan_id, available) VALUES
+    ('sha_one', 1, '/data/a.png', 'a.png', '2024-01-01T00:00:00', 1, 1),
+    ('sha_one', 1, '/old/b.png', 'b.png', '2024-01-01T00:00:00', 1, 1),
+    ('sha_two', 2, '/old/c.png', 'c.png', '2024-01-01T00:00:00', 1, 1),
+    ('sha_three', 3, '/data/skip/d.png', 'd.png', '2024-01-01T00:00:00', 1, 1),
+    ('sha_three', 3, '/stray/e.png', 'e.png', '2024-01-01T00:00:00', 1, 1)
+            "#,
+        ] {
+            sqlx::query(statement).execute(&mut *conn).await.unwrap();
+        }
+    }
+
+    // Ensures the preview counts removed files per folder and the items left
+    // without files, without changing anything, and counts files a re-added
+    // folder would restore.
+    #[tokio::test]
+    async fn preview_folder_removal_counts_without_writing() {
+        let mut dbs = setup_test_databases().await;
+        seed_removal_fixture(&mut dbs.index_conn, &[("/data/", true), ("/old/", true)]).await;
+
+        let preview = preview_folder_removal(
+            &mut dbs.index_conn,
+            &["/data/".to_string()],
+            &["/data/skip/".to_string()],
+        )
+        .await
+        .unwrap();
+
+        let impact = |folder: Option<&str>, reason, files, items| FolderRemovalImpact {
+            folder: folder.map(str::to_string),
+            reason,
+            files,
+            items,
+        };
+        assert_eq!(
+            preview,
+            FolderRemovalPreview {
+                files: 4,
+                items: 2,
+                restored_files: 0,
+                folders: vec![
+                    impact(None, FolderRemovalReason::NotIncluded, 1, 1),
+                    impact(Some("/old/"), FolderRemovalReason::NotIncluded, 2, 1),
+                    impact(Some("/data/skip/"), FolderRemovalReason::Excluded, 1, 1),
+                ],
+            }
+        );
+        let available: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM files WHERE available = 1")
+            .fetch_one(&mut dbs.index_conn)
+            .await
+            .unwrap();
+        assert_eq!(available, 5);
+
+        sqlx::query("UPDATE files SET available = 0, removed_at = '2024-01-02T00:00:00' WHERE path LIKE '/old/%'")
+            .execute(&mut dbs.index_conn)
+            .await
+            .unwrap();
+        let preview = preview_folder_removal(
+            &mut dbs.index_conn,
+            &["/data/".to_string(), "/old/".to_string()],
+            &[],
+        )
+        .await
+        .unwrap();
+        assert_eq!(preview.restored_files, 2);
+        assert_eq!(preview.files, 1);
+    }
+
+    // Ensures soft removal only marks files, keeps the first stamp, survives
+    // unavailable-file cleanup, is undone by re-adding the folder, and is
+    // purged once the grace period is over.
+    #[tokio::test]
+    async fn soft_removed_files_are_restored_or_purged() {
+        let mut dbs = setup_test_databases().await;
+        seed_removal_fixture(
+            &mut dbs.index_conn,
+            &[("/data/", true), ("/data/skip/", false)],
+        )
+        .await;
+
+        let outside = delete_files_not_under_included_folders(&mut dbs.index_conn, true)
+            .await
+            .unwrap();
+        let excluded = delete_files_under_excluded_folders(&mut dbs.index_conn, true)
+            .await
+            .unwrap();
+        assert_eq!((outside, excluded), (3, 1));
+        let removed: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM files WHERE available = 0 AND removed_at IS NOT NULL",
+        )
+        .fetch_one(&mut dbs.index_conn)
+        .await
+        .unwrap();
+        assert_eq!(removed, 4);
+        let deleted = crate::db::file_scans::delete_unavailable_files(&mut dbs.index_conn)
+            .await
+            .unwrap();
+        assert_eq!(deleted, 0);
+
+        sqlx::query(
+            "UPDATE files SET removed_at = '2000-01-01T00:00:00' WHERE path = '/stray/e.png'",
+        )
+        .execute(&mut dbs.index_conn)
+        .await
+        .unwrap();
+        let again = delete_files_not_under_included_folders(&mut dbs.index_conn, true)
+            .await
+            .unwrap();
+        assert_eq!(again, 0);
+        assert_eq!(
+            purge_removed_files(&mut dbs.index_conn, 7).await.unwrap(),
+            1
+        );
+
+        add_folder_to_database(&mut dbs.index_conn, "2024-01-02T00:00:00", "/old/", true)
+            .await
+            .unwrap();
+        assert_eq!(restore_removed_files(&mut dbs.index_conn).await.unwrap(), 2);
+        assert_eq!(
+            purge_removed_files(&mut dbs.index_conn, 0).await.unwrap(),
+            1
+        );
+
+        let files: Vec<(String, bool)> =
+            sqlx::query_as("SELECT path, available FROM files ORDER BY path")
+                .fetch_all(&mut dbs.index_conn)
+                .await
+                .unwrap();
+        assert_eq!(
+            files,
+            vec![
+                ("/data/a.png".to_string(), true),
+                ("/old/b.png".to_string(), true),
+                ("/old/c.png".to_string(), true),
+            ]
+        );
+    }
 }
@@ -0,0 +1,215 @@
+//! FTS5 index maintenance. Both full-text indexes are external-content
+//! tables kept in sync by triggers; if one drifts from its content table
+//! (searches miss rows that exist), the FTS5 `rebuild` command regenerates
+//! it from the content table. `integrity-check` with a non-zero rank also
+//! compares the index against the content table, so it detects that drift.
+
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::api_error::ApiError;
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+/// SQLITE_CORRUPT, the primary code of every corruption result code
+/// (including SQLITE_CORRUPT_VTAB, which FTS5 reports).
+const SQLITE_CORRUPT: i32 = 11;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum FtsTable {
+    /// `files_path_fts`, behind path and filename search.
+    FilesPath,
+    /// `extracted_text_fts`, behind extracted text search.
+    ExtractedText,
+}
+
+impl FtsTable {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            FtsTable::FilesPath => "files_path_fts",
+            FtsTable::ExtractedText => "extracted_text_fts",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub(crate) struct FtsRepair {
+    pub table: FtsTable,
+    /// Result of the integrity check; null when none was run.
+    pub integrity_ok: Option<bool>,
+    pub rebuilt: bool,
+    /// Seconds spent checking and rebuilding.
+    pub duration: f64,
+    /// Rows in the index afterwards.
+    pub rows: i64,
+}
+
+fn internal(table: FtsTable, context: &'static str) -> impl Fn(sqlx::Error) -> ApiError {
+    move |err| {
+        tracing::error!(error = %err, table = table.name(), context, "fts maintenance failed");
+        ApiError::internal(context)
+    }
+}
+
+/// Whether the index is consistent with itself and its content table.
+/// Corruption is a `false`; any other failure is an error.
+pub(crate) async fn check_fts_integrity(
+    conn: &mut sqlx::SqliteConnection,
+    table: FtsTable,
+) -> ApiResult<bool> {
+    let name = table.name();
+    let sql = format!("INSERT INTO {name}({name}, rank) VALUES('integrity-check', 1)");
+    match sqlx::query(sqlx::AssertSqlSafe(sql))
+        .execute(&mut *conn)
+        .await
+    {
+        Ok(_) => Ok(true),
+        Err(err) if is_corruption(&err) => {
+            tracing::warn!(error = %err, table = name, "fts integrity check failed");
+            Ok(false)
+        }
+        Err(err) => Err(internal(table, "Failed to check the full-text index")(err)),
+    }
+}
+
+fn is_corruption(err: &sqlx::Error) -> bool {
+    err.as_database_error()
+        .and_then(|db_err| db_err.code())
+        .and_then(|code| code.parse::<i32>().ok())
+        .is_some_and(|code| code & 0xff == SQLITE_CORRUPT)
+}
+
+/// Regenerates the index from its content table.
+pub(crate) async fn rebuild_fts(
+    conn: &mut sqlx::SqliteConnection,
+    table: FtsTable,
+) -> ApiResult<()> {
+    let name = table.name();
+    let sql = format!("INSERT INTO {name}({name}) VALUES('rebuild')");
+    sqlx::query(sqlx::AssertSqlSafe(sql))
+        .execute(&mut *conn)
+        .await
+        .map_err(internal(table, "Failed to rebuild the full-text index"))?;
+    Ok(())
+}
+
+/// Rows in the index, counted from its docsize shadow table so the content
+/// table is not scanned.
+pub(crate) async fn count_fts_rows(
+    conn: &mut sqlx::SqliteConnection,
+    table: FtsTable,
+) -> ApiResult<i64> {
+    let sql = format!("SELECT COUNT(*) FROM {}_docsize", table.name());
+    sqlx::query_scalar(sqlx::AssertSqlSafe(sql))
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(internal(table, "Failed to count full-text index rows"))
+}
+
+/// Rebuilds `table`. With `check_first`, the integrity check runs first and
+/// the rebuild only happens if it fails, unless `force` is set.
+pub(crate) async fn repair_fts(
+    conn: &mut sqlx::SqliteConnection,
+    table: FtsTable,
+    check_first: bool,
+    force: bool,
+) -> ApiResult<FtsRepair> {
+    let start = Instant::now();
+    let integrity_ok = if check_first {
+        Some(check_fts_integrity(conn, table).await?)
+    } else {
+        None
+    };
+    let rebuild = force || integrity_ok != Some(true);
+    if rebuild {
+        rebuild_fts(conn, table).await?;
+    }
+    let duration = start.elapsed().as_secs_f64();
+    let rows = count_fts_rows(conn, table).await?;
+    Ok(FtsRepair {
+        table,
+        integrity_ok,
+        rebuilt: rebuild,
+        duration,
+        rows,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::migrations::setup_test_databases;
+
+    async fn path_matches(conn: &mut sqlx::SqliteConnection, term: &str) -> i64 {
+        sqlx::query_scalar("SELECT COUNT(*) FROM files_path_fts WHERE files_path_fts MATCH ?1")
+            .bind(term)
+            .fetch_one(conn)
+            .await
+            .unwrap()
+    }
+
+    // Drops a file's entry from the path index behind the triggers' back:
+    // the check catches it, the rebuild restores it, and a healthy index is
+    // left alone unless forced.
+    #[tokio::test]
+    async fn repair_rebuilds_a_drifted_index_only_when_needed() {
+        let mut dbs = setup_test_databases().await;
+        let conn = &mut dbs.index_conn;
+        sqlx::query(
+            r#"
+INSERT INTO file_scans (id, start_time, path) VALUES (1, '2024-01-01T00:00:00', '/');
+INSERT INTO items (id, sha256, md5, type, time_added)
+VALUES (1, 'sha1', 'md51', 'image/png', '2024-01-01T00:00:00');
+INSERT INTO files (id, sha256, item_id, path, filename, last_modified, scan_id, available)
+VALUES
+    (1, 'sha1', 1, '/photos/renamed.png', 'renamed.png', '2024-01-01T00:00:00', 1, 1),
+    (2, 'sha1', 1, '/photos/other.png', 'other.png', '2024-01-01T00:00:00', 1, 1);
+            "#,
+        )
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+
+        let healthy = repair_fts(conn, FtsTable::FilesPath, true, false)
+            .await
+            .unwrap();
+        assert_eq!(healthy.integrity_ok, Some(true));
+        assert!(!healthy.rebuilt);
+        assert_eq!(healthy.rows, 2);
+
+        sqlx::query(
+            r#"
+INSERT INTO files_path_fts(files_path_fts, rowid, path, filename)
+VALUES('delete', 1, '/photos/renamed.png', 'renamed.png')
+            "#,
+        )
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+        assert_eq!(path_matches(conn, "renamed").await, 0);
+
+        let repaired = repair_fts(conn, FtsTable::FilesPath, true, false)
+            .await
+            .unwrap();
+        assert_eq!(repaired.integrity_ok, Some(false));
+        assert!(repaired.rebuilt);
+        assert_eq!(repaired.rows, 2);
+        assert_eq!(path_matches(conn, "renamed").await, 1);
+
+        let forced = repair_fts(conn, FtsTable::FilesPath, true, true)
+            .await
+            .unwrap();
+        assert_eq!(forced.integrity_ok, Some(true));
+        assert!(forced.rebuilt);
+
+        let text = repair_fts(conn, FtsTable::ExtractedText, false, false)
+            .await
+            .unwrap();
+        assert_eq!(text.integrity_ok, None);
+        assert!(text.rebuilt);
+        assert_eq!(text.rows, 0);
+    }
+}
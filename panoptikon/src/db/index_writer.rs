@@ -1,44 +1,71 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     future::Future,
     pin::Pin,
-    sync::OnceLock,
+    sync::{
+        Arc, OnceLock,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
     time::{Duration, Instant},
 };
 
 use ractor::concurrency::Duration as RactorDuration;
 use ractor::{Actor, ActorProcessingErr, ActorRef};
+use serde::{Deserialize, Serialize};
 use sqlx::SqliteConnection;
-use tokio::sync::{Mutex, oneshot};
+use tokio::sync::{Mutex, OwnedSemaphorePermit, Semaphore, oneshot};
+use tracing::Instrument;
+use utoipa::ToSchema;
 
 use crate::api_error::ApiError;
+use crate::config::ThumbnailStorage;
 use crate::db::connection::index_storage_paths_unchecked;
 use crate::db::{
-    extraction_log::delete_data_job_by_log_id,
+    data_coverage::store_coverage_snapshot,
+    db_maintenance::{NewDbMaintenanceRun, add_db_maintenance_run},
+    dir_snapshots::{DirSnapshot, adopt_unchanged_dir_files, replace_dir_snapshots},
+    extraction_log::{delete_data_job_by_log_id, prune_data_logs},
     extraction_write::{
-        DataLogUpdate, EmbeddingEntry, TagEntry, TagTextEntry, TextEntry, add_data_log,
-        delete_orphan_tags, delete_setter_by_name, remove_incomplete_jobs, update_data_log,
-        upsert_setter, write_clip_output, write_tags_output, write_text_embedding_output,
-        write_text_output,
+        DataLogUpdate, EmbeddingEntry, RenormalizeChunk, SetterDeletion, TagEntry, TagTextEntry,
+        TextEntry, add_data_log, check_embedding_dimensions, delete_orphan_tags,
+        delete_setter_by_name, remove_incomplete_jobs, renormalize_text_chunk, update_data_log, upsert_setter,
+        write_clip_output, write_frame_tags, write_tags_output, write_text_embedding_output, write_text_output,
     },
     file_scans::{
         FileScanUpdate, add_file_scan, close_file_scan, delete_unavailable_files,
         mark_unavailable_files, update_file_scan,
     },
+    file_verification::{
+        NewVerificationResult, VerificationRunUpdate, add_verification_result,
+        add_verification_run, update_verification_run,
+    },
     files::{
-        FileScanData, FileUpsertResult, delete_file_by_path, delete_files_not_allowed,
-        delete_item_if_orphan, delete_items_without_files, rename_file_path, set_blurhash,
-        update_file_data,
+        FileScanData, FileUpsertResult, ItemScanMeta, delete_file_by_path,
+        delete_files_not_allowed, delete_item_if_orphan, delete_items_without_files,
+        mark_file_unavailable, rename_file_path, set_blurhash, set_item_undecodable,
+        set_primary_file, update_file_data, update_item_metadata,
     },
     folders::{
         add_folder_to_database, delete_files_not_under_included_folders,
-        delete_files_under_excluded_folders, delete_folders_not_in_list,
+        delete_files_under_excluded_folders, delete_folders_not_in_list, purge_removed_files,
+        restore_removed_files,
     },
+    fts::{FtsRepair, FtsTable, repair_fts},
+    item_purge::{ItemPurgeCounts, purge_item},
+    lineage::{LineageRepairChunk, LineageRepairMode, repair_dangling_lineage_chunk},
+    manual_tags::{ManualTag, add_manual_tags, remove_manual_tags},
     open_index_db_read_no_user_data, open_index_db_write_no_user_data,
+    path_keys::{PathKeys, rekey_file_paths},
     storage::{
-        StoredImage, delete_orphaned_frames, delete_orphaned_thumbnails, store_frames,
-        store_thumbnails,
+        StoredImage, VisualTable, VisualsWrite, delete_orphaned_frames, delete_orphaned_thumbnails,
+        delete_processed_frames, migrate_visuals_batch, remove_visual_files, store_frames,
+        store_thumbnails, visuals_dir,
     },
+    storage_stats::store_storage_stats_snapshot,
+    system_config::TextNormalizationConfig,
+    tag_aliases::{TagAliasGroup, delete_tag_alias_group, set_tag_alias_group},
+    tag_import::{TagImportBatch, TagImportEntry, import_tags_batch},
+    tag_rethreshold::{TagRethresholdChunk, rethreshold_tags_chunk},
 };
 
 type ApiResult<T> = std::result::Result<T, ApiError>;
@@ -47,6 +74,12 @@ type DbFuture<'a, T> = Pin<Box<dyn Future<Output = ApiResult<T>> + Send + 'a>>;
 const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(300);
 const IDLE_TIMEOUT: Duration = Duration::from_secs(300);
 const CALL_RETRY_ATTEMPTS: usize = 2;
+/// Messages a writer may have outstanding (queued or executing) before
+/// `call_index_db_writer` holds further callers back. Every caller awaits its
+/// reply, so the backlog is one message per concurrent producer; the cap
+/// keeps scan workers and extraction batches from piling payloads (frames,
+/// embeddings) into the mailbox faster than SQLite commits them.
+const WRITER_QUEUE_LIMIT: usize = 64;
 
 /// Statistics refresh, run after every job by `run_post_job_maintenance`.
 ///
@@ -94,10 +127,27 @@ pub(crate) enum IndexDbWriterMessage {
     },
     MarkUnavailableFiles {
         scan_id: i64,
+        seen_at: String,
         path: String,
         excluded_paths: Vec<String>,
         reply: Reply<(i64, i64)>,
     },
+    /// Moves the files directly inside unchanged directories to the scan;
+    /// replies with how many.
+    AdoptUnchangedDirFiles {
+        scan_id: i64,
+        path: String,
+        dirs: Vec<String>,
+        reply: Reply<i64>,
+    },
+    /// Replaces the directory snapshots under `path`; an empty list clears
+    /// them.
+    ReplaceDirSnapshots {
+        path: String,
+        config_hash: String,
+        snapshots: Vec<DirSnapshot>,
+        reply: Reply<()>,
+    },
     UpdateFileData {
         time_added: String,
         scan_id: i64,
@@ -119,25 +169,45 @@ pub(crate) enum IndexDbWriterMessage {
         reply: Reply<()>,
     },
     RenameFilePath {
-        old_path: String,
+        old_key: String,
         new_path: String,
+        new_path_bytes: Option<Vec<u8>>,
+        new_key: String,
         scan_id: i64,
         last_modified: String,
         reply: Reply<bool>,
     },
     DeleteFileByPath {
-        path: String,
+        path_key: String,
         reply: Reply<u64>,
     },
     DeleteItemIfOrphan {
         item_id: i64,
         reply: Reply<bool>,
     },
+    MarkFileUnavailable {
+        path_key: String,
+        reply: Reply<bool>,
+    },
+    RekeyFilePaths {
+        keys: PathKeys,
+        reply: Reply<u64>,
+    },
+    UpdateItemMetadata {
+        sha256: String,
+        metadata: ItemScanMeta,
+        reply: Reply<bool>,
+    },
     SetBlurhash {
         sha256: String,
         blurhash: String,
         reply: Reply<()>,
     },
+    SetItemUndecodable {
+        sha256: String,
+        undecodable: bool,
+        reply: Reply<()>,
+    },
     DeleteUnavailableFiles {
         reply: Reply<u64>,
     },
@@ -155,10 +225,45 @@ pub(crate) enum IndexDbWriterMessage {
     DeleteOrphanedThumbnails {
         reply: Reply<u64>,
     },
+    /// Frame retention: deletes the frames of up to `limit` items every
+    /// listed setter has processed; replies with the frame rows deleted.
+    DeleteProcessedFrames {
+        setters: Vec<String>,
+        limit: i64,
+        reply: Reply<u64>,
+    },
+    /// One batch of a visuals storage migration; replies with the number of
+    /// rows handled.
+    MigrateVisuals {
+        table: VisualTable,
+        target: ThumbnailStorage,
+        limit: i64,
+        reply: Reply<u64>,
+    },
+    /// Integrity-checks and/or rebuilds one FTS5 index in a single
+    /// transaction; readers keep the previous index until it commits.
+    RepairFts {
+        table: FtsTable,
+        check_first: bool,
+        force: bool,
+        reply: Reply<FtsRepair>,
+    },
+    /// Deletes one item with its files and all derived data, replying with
+    /// the rows removed per table.
+    PurgeItem {
+        sha256: String,
+        reply: Reply<ItemPurgeCounts>,
+    },
     DeleteJobData {
         log_id: i64,
         reply: Reply<u64>,
     },
+    PruneDataLogs {
+        keep_last_per_setter: Option<u32>,
+        max_age_days: Option<u32>,
+        batch_size: i64,
+        reply: Reply<u64>,
+    },
     RemoveIncompleteJobs {
         reply: Reply<()>,
     },
@@ -168,6 +273,11 @@ pub(crate) enum IndexDbWriterMessage {
         types: Vec<String>,
         setter: String,
         batch_size: i64,
+        reprocess: bool,
+        /// `ExtractionParameters` JSON; None for tag imports.
+        parameters: Option<String>,
+        /// `ExtractionFilterCounts` JSON; None for tag imports.
+        filter_counts: Option<String>,
         reply: Reply<i64>,
     },
     UpdateDataLog {
@@ -185,13 +295,37 @@ pub(crate) enum IndexDbWriterMessage {
         item_sha256: String,
         tags: Vec<TagEntry>,
         text_entries: Vec<TagTextEntry>,
+        /// Each video frame's own tags, when the model stores them; empty
+        /// otherwise.
+        frame_tags: Vec<Vec<TagEntry>>,
+        /// Replace the item's data from earlier jobs of this setter.
+        replace: bool,
         reply: Reply<()>,
     },
+    /// One batch of imported tags, replacing what the setter had for those
+    /// items.
+    ImportTags {
+        job_id: i64,
+        setter_name: String,
+        entries: Vec<TagImportEntry>,
+        reply: Reply<TagImportBatch>,
+    },
+    /// One chunk of a setter's tags raised to `threshold`, resuming after
+    /// `after_id`.
+    RethresholdTagsChunk {
+        setter_name: String,
+        threshold: f64,
+        normalization: TextNormalizationConfig,
+        after_id: i64,
+        limit: i64,
+        reply: Reply<TagRethresholdChunk>,
+    },
     WriteTextOutput {
         job_id: i64,
         setter_name: String,
         item_sha256: String,
         entries: Vec<TextEntry>,
+        replace: bool,
         reply: Reply<()>,
     },
     WriteClipOutput {
@@ -199,6 +333,7 @@ pub(crate) enum IndexDbWriterMessage {
         setter_name: String,
         item_sha256: String,
         entries: Vec<EmbeddingEntry>,
+        replace: bool,
         reply: Reply<()>,
     },
     WriteTextEmbeddingOutput {
@@ -207,16 +342,24 @@ pub(crate) enum IndexDbWriterMessage {
         item_sha256: String,
         source_data_id: Option<i64>,
         entries: Vec<EmbeddingEntry>,
+        replace: bool,
         reply: Reply<()>,
     },
-    /// Deletes a setter and (for tag setters) the orphaned tags it leaves
-    /// behind in one transaction, so a crash can't leave dangling tag rows
-    /// visible in tag lists.
+    /// Deletes a setter, the data derived from it by other setters (or
+    /// detaches that data, without `cascade`) and, for tag setters, the
+    /// orphaned tags it leaves behind in one transaction, so a crash can't
+    /// leave dangling tag rows visible in tag lists.
     DeleteSetterData {
         setter_name: String,
+        cascade: bool,
         include_orphan_tags: bool,
-        /// (setter rows deleted, orphan tags deleted)
-        reply: Reply<(u64, u64)>,
+        /// (setter deletion, orphan tags deleted)
+        reply: Reply<(SetterDeletion, u64)>,
+    },
+    /// Deletes tags no item links to any more, e.g. after a reprocess job
+    /// replaced a tagger's output.
+    DeleteOrphanTags {
+        reply: Reply<u64>,
     },
     AddFolderToDatabase {
         time_added: String,
@@ -229,10 +372,21 @@ pub(crate) enum IndexDbWriterMessage {
         included: bool,
         reply: Reply<u64>,
     },
+    /// With `soft`, marks the files unavailable and stamps `removed_at`
+    /// instead of deleting them (see `folder_removal_grace_days`).
     DeleteFilesUnderExcludedFolders {
+        soft: bool,
         reply: Reply<u64>,
     },
     DeleteFilesNotUnderIncludedFolders {
+        soft: bool,
+        reply: Reply<u64>,
+    },
+    RestoreRemovedFiles {
+        reply: Reply<u64>,
+    },
+    PurgeRemovedFiles {
+        grace_days: u64,
         reply: Reply<u64>,
     },
     /// Applies the vector-quant metadata diff (profiles/coverage rows) for
@@ -283,12 +437,94 @@ pub(crate) enum IndexDbWriterMessage {
         setter_ids: Vec<i64>,
         reply: Reply<()>,
     },
+    /// One chunk of dangling `source_id` repairs, resuming after `after_id`.
+    RepairLineageChunk {
+        mode: LineageRepairMode,
+        after_id: i64,
+        limit: i64,
+        reply: Reply<LineageRepairChunk>,
+    },
+    /// One chunked `normalized_text` recompute, resuming after `after_id`.
+    RenormalizeTextChunk {
+        config: TextNormalizationConfig,
+        after_id: i64,
+        limit: i64,
+        reply: Reply<RenormalizeChunk>,
+    },
+    /// Replaces the cached data coverage report.
+    StoreCoverageSnapshot {
+        computed_at: String,
+        report: String,
+        reply: Reply<()>,
+    },
+    StoreStorageStatsSnapshot {
+        computed_at: String,
+        report: String,
+        reply: Reply<()>,
+    },
+    /// Opens a bit-rot verification run; replies with its ID.
+    AddVerificationRun {
+        start_time: String,
+        path_prefix: Option<String>,
+        modified_since: Option<String>,
+        max_files: Option<i64>,
+        max_mb_per_sec: f64,
+        reply: Reply<i64>,
+    },
+    UpdateVerificationRun {
+        run_id: i64,
+        update: VerificationRunUpdate,
+        reply: Reply<()>,
+    },
+    AddVerificationResult {
+        run_id: i64,
+        result: NewVerificationResult,
+        reply: Reply<()>,
+    },
+    AddManualTags {
+        item_sha256: String,
+        tags: Vec<ManualTag>,
+        reply: Reply<u64>,
+    },
+    RemoveManualTags {
+        item_sha256: String,
+        tags: Vec<ManualTag>,
+        reply: Reply<u64>,
+    },
+    SetPrimaryFile {
+        item_id: i64,
+        file_id: Option<i64>,
+        reply: Reply<()>,
+    },
+    SetTagAliasGroup {
+        group: TagAliasGroup,
+        reply: Reply<()>,
+    },
+    DeleteTagAliasGroup {
+        canonical: String,
+        reply: Reply<u64>,
+    },
     Vacuum {
         reply: Reply<()>,
     },
     Analyze {
         reply: Reply<()>,
     },
+    /// `PRAGMA incremental_vacuum` on both databases. Only reclaims space
+    /// when they were created with `auto_vacuum = INCREMENTAL`; otherwise a
+    /// no-op.
+    IncrementalVacuum {
+        reply: Reply<()>,
+    },
+    /// Checkpoints the WAL of both databases and truncates the -wal files.
+    WalCheckpoint {
+        reply: Reply<()>,
+    },
+    /// Records a finished database optimization run; replies with its ID.
+    AddDbMaintenanceRun {
+        run: NewDbMaintenanceRun,
+        reply: Reply<i64>,
+    },
     /// No-op barrier: the writer handles messages in order, so a reply proves
     /// every previously queued write has committed. Used at process shutdown.
     Flush {
@@ -399,8 +635,48 @@ impl IndexDbWriterState {
     }
 }
 
+impl IndexDbWriterMessage {
+    /// Whether handling the message can add or remove tags or change how
+    /// many items carry one, which outdates the tag autocomplete index.
+    /// Extraction output (`WriteTagsOutput`) is left out: the job bumps the
+    /// tag epoch once when it finishes instead of once per batch.
+    fn changes_tags(&self) -> bool {
+        matches!(
+            self,
+            Self::ImportTags { .. }
+                | Self::RethresholdTagsChunk { .. }
+                | Self::DeleteSetterData { .. }
+                | Self::DeleteOrphanTags { .. }
+                | Self::AddManualTags { .. }
+                | Self::RemoveManualTags { .. }
+                | Self::PurgeItem { .. }
+                | Self::DeleteJobData { .. }
+                | Self::DeleteItemsWithoutFiles { .. }
+                | Self::DeleteItemIfOrphan { .. }
+                | Self::PurgeRemovedFiles { .. }
+                | Self::RemoveIncompleteJobs { .. }
+        )
+    }
+}
+
+/// A writer message plus the span it was sent from. The writer handles it
+/// inside that span, so its logs carry the sender's `request_id` / `job_id`.
+pub(crate) struct WriterEnvelope {
+    message: IndexDbWriterMessage,
+    span: tracing::Span,
+}
+
+impl From<IndexDbWriterMessage> for WriterEnvelope {
+    fn from(message: IndexDbWriterMessage) -> Self {
+        Self {
+            message,
+            span: tracing::Span::current(),
+        }
+    }
+}
+
 impl Actor for IndexDbWriter {
-    type Msg = IndexDbWriterMessage;
+    type Msg = WriterEnvelope;
     type State = IndexDbWriterState;
     type Arguments = IndexDbWriterArgs;
 
@@ -411,7 +687,7 @@ impl Actor for IndexDbWriter {
     ) -> Result<Self::State, ActorProcessingErr> {
         let _ = myself.send_interval(
             RactorDuration::from_secs(args.idle_timeout.as_secs()),
-            || IndexDbWriterMessage::IdleCheck,
+            || IndexDbWriterMessage::IdleCheck.into(),
         );
         Ok(IndexDbWriterState {
             index_db: args.index_db,
@@ -424,8 +700,24 @@ impl Actor for IndexDbWriter {
     async fn handle(
         &self,
         _myself: ActorRef<Self::Msg>,
-        message: Self::Msg,
+        envelope: Self::Msg,
         state: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        let WriterEnvelope { message, span } = envelope;
+        let changes_tags = message.changes_tags();
+        let result = self.handle_message(message, state).instrument(span).await;
+        if changes_tags {
+            crate::db::epochs::bump_tag_epoch(&state.index_db);
+        }
+        result
+    }
+}
+
+impl IndexDbWriter {
+    async fn handle_message(
+        &self,
+        message: IndexDbWriterMessage,
+        state: &mut IndexDbWriterState,
     ) -> Result<(), ActorProcessingErr> {
         match message {
             IndexDbWriterMessage::IdleCheck => {
@@ -478,6 +770,7 @@ impl Actor for IndexDbWriter {
             }
             IndexDbWriterMessage::MarkUnavailableFiles {
                 scan_id,
+                seen_at,
                 path,
                 excluded_paths,
                 reply,
@@ -485,7 +778,38 @@ impl Actor for IndexDbWriter {
                 let result = state
                     .with_transaction(move |conn| {
                         Box::pin(async move {
-                            mark_unavailable_files(conn, scan_id, &path, &excluded_paths).await
+                            mark_unavailable_files(conn, scan_id, &seen_at, &path, &excluded_paths)
+                                .await
+                        })
+                    })
+                    .await;
+                let _ = reply.send(result);
+            }
+            IndexDbWriterMessage::AdoptUnchangedDirFiles {
+                scan_id,
+                path,
+                dirs,
+                reply,
+            } => {
+                let result = state
+                    .with_transaction(move |conn| {
+                        Box::pin(async move {
+                            adopt_unchanged_dir_files(conn, scan_id, &path, &dirs).await
+                        })
+                    })
+                    .await;
+                let _ = reply.send(result);
+            }
+            IndexDbWriterMessage::ReplaceDirSnapshots {
+                path,
+                config_hash,
+                snapshots,
+                reply,
+            } => {
+                let result = state
+                    .with_transaction(move |conn| {
+                        Box::pin(async move {
+                            replace_dir_snapshots(conn, &path, &config_hash, &snapshots).await
                         })
                     })
                     .await;
@@ -513,11 +837,15 @@ impl Actor for IndexDbWriter {
                 thumbnails,
                 reply,
             } => {
+                let dir = visuals_dir(&state.index_db);
+                let backend = crate::config::runtime().thumbnail_storage;
                 let result = state
                     .with_transaction(move |conn| {
                         Box::pin(async move {
                             store_thumbnails(
                                 conn,
+                                &dir,
+                                backend,
                                 &sha256,
                                 &mime_type,
                                 process_version,
@@ -527,7 +855,7 @@ impl Actor for IndexDbWriter {
                         })
                     })
                     .await;
-                let _ = reply.send(result);
+                let _ = reply.send(finish_visuals_write(result).await.map(|_| ()));
             }
             IndexDbWriterMessage::StoreFrames {
                 sha256,
@@ -536,18 +864,31 @@ impl Actor for IndexDbWriter {
                 frames,
                 reply,
             } => {
+                let dir = visuals_dir(&state.index_db);
+                let backend = crate::config::runtime().thumbnail_storage;
                 let result = state
                     .with_transaction(move |conn| {
                         Box::pin(async move {
-                            store_frames(conn, &sha256, &mime_type, process_version, &frames).await
+                            store_frames(
+                                conn,
+                                &dir,
+                                backend,
+                                &sha256,
+                                &mime_type,
+                                process_version,
+                                &frames,
+                            )
+                            .await
                         })
                     })
                     .await;
-                let _ = reply.send(result);
+                let _ = reply.send(finish_visuals_write(result).await.map(|_| ()));
             }
             IndexDbWriterMessage::RenameFilePath {
-                old_path,
+                old_key,
                 new_path,
+                new_path_bytes,
+                new_key,
                 scan_id,
                 last_modified,
                 reply,
@@ -555,17 +896,25 @@ impl Actor for IndexDbWriter {
                 let result = state
                     .with_transaction(move |conn| {
                         Box::pin(async move {
-                            rename_file_path(conn, &old_path, &new_path, scan_id, &last_modified)
-                                .await
+                            rename_file_path(
+                                conn,
+                                &old_key,
+                                &new_path,
+                                new_path_bytes.as_deref(),
+                                &new_key,
+                                scan_id,
+                                &last_modified,
+                            )
+                            .await
                         })
                     })
                     .await;
                 let _ = reply.send(result);
             }
-            IndexDbWriterMessage::DeleteFileByPath { path, reply } => {
+            IndexDbWriterMessage::DeleteFileByPath { path_key, reply } => {
                 let result = state
                     .with_transaction(move |conn| {
-                        Box::pin(async move { delete_file_by_path(conn, &path).await })
+                        Box::pin(async move { delete_file_by_path(conn, &path_key).await })
                     })
                     .await;
                 let _ = reply.send(result);
@@ -578,6 +927,36 @@ impl Actor for IndexDbWriter {
                     .await;
                 let _ = reply.send(result);
             }
+            IndexDbWriterMessage::MarkFileUnavailable { path_key, reply } => {
+                let result = state
+                    .with_transaction(move |conn| {
+                        Box::pin(async move { mark_file_unavailable(conn, &path_key).await })
+                    })
+                    .await;
+                let _ = reply.send(result);
+            }
+            IndexDbWriterMessage::RekeyFilePaths { keys, reply } => {
+                let result = state
+                    .with_transaction(move |conn| {
+                        Box::pin(async move { rekey_file_paths(conn, &keys).await })
+                    })
+                    .await;
+                let _ = reply.send(result);
+            }
+            IndexDbWriterMessage::UpdateItemMetadata {
+                sha256,
+                metadata,
+                reply,
+            } => {
+                let result = state
+                    .with_transaction(move |conn| {
+                        Box::pin(
+                            async move { update_item_metadata(conn, &sha256, &metadata).await },
+                        )
+                    })
+                    .await;
+                let _ = reply.send(result);
+            }
             IndexDbWriterMessage::SetBlurhash {
                 sha256,
                 blurhash,
@@ -590,6 +969,20 @@ impl Actor for IndexDbWriter {
                     .await;
                 let _ = reply.send(result);
             }
+            IndexDbWriterMessage::SetItemUndecodable {
+                sha256,
+                undecodable,
+                reply,
+            } => {
+                let result = state
+                    .with_transaction(move |conn| {
+                        Box::pin(
+                            async move { set_item_undecodable(conn, &sha256, undecodable).await },
+                        )
+                    })
+                    .await;
+                let _ = reply.send(result);
+            }
             IndexDbWriterMessage::DeleteUnavailableFiles { reply } => {
                 let result = state
                     .with_transaction(move |conn| {
@@ -615,21 +1008,88 @@ impl Actor for IndexDbWriter {
                 let _ = reply.send(result);
             }
             IndexDbWriterMessage::DeleteOrphanedFrames { reply } => {
+                let dir = visuals_dir(&state.index_db);
                 let result = state
                     .with_transaction(move |conn| {
-                        Box::pin(async move { delete_orphaned_frames(conn).await })
+                        Box::pin(async move { delete_orphaned_frames(conn, &dir).await })
                     })
                     .await;
-                let _ = reply.send(result);
+                let _ = reply.send(finish_visuals_write(result).await);
             }
             IndexDbWriterMessage::DeleteOrphanedThumbnails { reply } => {
+                let dir = visuals_dir(&state.index_db);
+                let result = state
+                    .with_transaction(move |conn| {
+                        Box::pin(async move { delete_orphaned_thumbnails(conn, &dir).await })
+                    })
+                    .await;
+                let _ = reply.send(finish_visuals_write(result).await);
+            }
+            IndexDbWriterMessage::DeleteProcessedFrames {
+                setters,
+                limit,
+                reply,
+            } => {
+                let dir = visuals_dir(&state.index_db);
+                let result = state
+                    .with_transaction(move |conn| {
+                        Box::pin(async move {
+                            delete_processed_frames(conn, &dir, &setters, limit).await
+                        })
+                    })
+                    .await;
+                let _ = reply.send(finish_visuals_write(result).await);
+            }
+            IndexDbWriterMessage::MigrateVisuals {
+                table,
+                target,
+                limit,
+                reply,
+            } => {
+                let dir = visuals_dir(&state.index_db);
+                let result = state
+                    .with_transaction(move |conn| {
+                        Box::pin(async move {
+                            migrate_visuals_batch(conn, &dir, table, target, limit).await
+                        })
+                    })
+                    .await;
+                let _ = reply.send(finish_visuals_write(result).await);
+            }
+            IndexDbWriterMessage::RepairFts {
+                table,
+                check_first,
+                force,
+                reply,
+            } => {
+                tracing::info!(
+                    index_db = %state.index_db,
+                    table = table.name(),
+                    "repairing full-text index; this may take a while"
+                );
                 let result = state
                     .with_transaction(move |conn| {
-                        Box::pin(async move { delete_orphaned_thumbnails(conn).await })
+                        Box::pin(async move { repair_fts(conn, table, check_first, force).await })
                     })
                     .await;
                 let _ = reply.send(result);
             }
+            IndexDbWriterMessage::PurgeItem { sha256, reply } => {
+                let dir = visuals_dir(&state.index_db);
+                let result = state
+                    .with_transaction(move |conn| {
+                        Box::pin(async move { purge_item(conn, &dir, &sha256).await })
+                    })
+                    .await;
+                let result = match result {
+                    Ok(purge) => {
+                        remove_visual_files(&purge.stale_files).await;
+                        Ok(purge.counts)
+                    }
+                    Err(err) => Err(err),
+                };
+                let _ = reply.send(result);
+            }
             IndexDbWriterMessage::DeleteJobData { log_id, reply } => {
                 let result = state
                     .with_transaction(move |conn| {
@@ -638,6 +1098,22 @@ impl Actor for IndexDbWriter {
                     .await;
                 let _ = reply.send(result);
             }
+            IndexDbWriterMessage::PruneDataLogs {
+                keep_last_per_setter,
+                max_age_days,
+                batch_size,
+                reply,
+            } => {
+                let result = state
+                    .with_transaction(move |conn| {
+                        Box::pin(async move {
+                            prune_data_logs(conn, keep_last_per_setter, max_age_days, batch_size)
+                                .await
+                        })
+                    })
+                    .await;
+                let _ = reply.send(result);
+            }
             IndexDbWriterMessage::RemoveIncompleteJobs { reply } => {
                 let result = state
                     .with_transaction(move |conn| {
@@ -652,13 +1128,26 @@ impl Actor for IndexDbWriter {
                 types,
                 setter,
                 batch_size,
+                reprocess,
+                parameters,
+                filter_counts,
                 reply,
             } => {
                 let result = state
                     .with_transaction(move |conn| {
                         Box::pin(async move {
-                            add_data_log(conn, &scan_time, threshold, &types, &setter, batch_size)
-                                .await
+                            add_data_log(
+                                conn,
+                                &scan_time,
+                                threshold,
+                                &types,
+                                &setter,
+                                batch_size,
+                                reprocess,
+                                parameters.as_deref(),
+                                filter_counts.as_deref(),
+                            )
+                            .await
                         })
                     })
                     .await;
@@ -690,6 +1179,8 @@ impl Actor for IndexDbWriter {
                 item_sha256,
                 tags,
                 text_entries,
+                frame_tags,
+                replace,
                 reply,
             } => {
                 let result = state
@@ -702,6 +1193,49 @@ impl Actor for IndexDbWriter {
                                 &item_sha256,
                                 &tags,
                                 &text_entries,
+                                replace,
+                            )
+                            .await?;
+                            write_frame_tags(conn, job_id, &setter_name, &item_sha256, &frame_tags)
+                                .await
+                        })
+                    })
+                    .await;
+                let _ = reply.send(result);
+            }
+            IndexDbWriterMessage::ImportTags {
+                job_id,
+                setter_name,
+                entries,
+                reply,
+            } => {
+                let result = state
+                    .with_transaction(move |conn| {
+                        Box::pin(async move {
+                            import_tags_batch(conn, job_id, &setter_name, &entries).await
+                        })
+                    })
+                    .await;
+                let _ = reply.send(result);
+            }
+            IndexDbWriterMessage::RethresholdTagsChunk {
+                setter_name,
+                threshold,
+                normalization,
+                after_id,
+                limit,
+                reply,
+            } => {
+                let result = state
+                    .with_transaction(move |conn| {
+                        Box::pin(async move {
+                            rethreshold_tags_chunk(
+                                conn,
+                                &setter_name,
+                                threshold,
+                                &normalization,
+                                after_id,
+                                limit,
                             )
                             .await
                         })
@@ -714,13 +1248,21 @@ impl Actor for IndexDbWriter {
                 setter_name,
                 item_sha256,
                 entries,
+                replace,
                 reply,
             } => {
                 let result = state
                     .with_transaction(move |conn| {
                         Box::pin(async move {
-                            write_text_output(conn, job_id, &setter_name, &item_sha256, &entries)
-                                .await
+                            write_text_output(
+                                conn,
+                                job_id,
+                                &setter_name,
+                                &item_sha256,
+                                &entries,
+                                replace,
+                            )
+                            .await
                         })
                     })
                     .await;
@@ -731,16 +1273,37 @@ impl Actor for IndexDbWriter {
                 setter_name,
                 item_sha256,
                 entries,
+                replace,
                 reply,
             } => {
+                // A dimension mismatch commits only the rejection count.
                 let result = state
                     .with_transaction(move |conn| {
                         Box::pin(async move {
-                            write_clip_output(conn, job_id, &setter_name, &item_sha256, &entries)
-                                .await
+                            if let Err(rejected) = check_embedding_dimensions(
+                                conn,
+                                &setter_name,
+                                &item_sha256,
+                                &entries,
+                            )
+                            .await?
+                            {
+                                return Ok(Err(rejected));
+                            }
+                            write_clip_output(
+                                conn,
+                                job_id,
+                                &setter_name,
+                                &item_sha256,
+                                &entries,
+                                replace,
+                            )
+                            .await
+                            .map(Ok)
                         })
                     })
-                    .await;
+                    .await
+                    .and_then(|written| written);
                 let _ = reply.send(result);
             }
             IndexDbWriterMessage::WriteTextEmbeddingOutput {
@@ -749,11 +1312,23 @@ impl Actor for IndexDbWriter {
                 item_sha256,
                 source_data_id,
                 entries,
+                replace,
                 reply,
             } => {
+                // A dimension mismatch commits only the rejection count.
                 let result = state
                     .with_transaction(move |conn| {
                         Box::pin(async move {
+                            if let Err(rejected) = check_embedding_dimensions(
+                                conn,
+                                &setter_name,
+                                &item_sha256,
+                                &entries,
+                            )
+                            .await?
+                            {
+                                return Ok(Err(rejected));
+                            }
                             write_text_embedding_output(
                                 conn,
                                 job_id,
@@ -761,22 +1336,27 @@ impl Actor for IndexDbWriter {
                                 &item_sha256,
                                 source_data_id,
                                 &entries,
+                                replace,
                             )
                             .await
+                            .map(Ok)
                         })
                     })
-                    .await;
+                    .await
+                    .and_then(|written| written);
                 let _ = reply.send(result);
             }
             IndexDbWriterMessage::DeleteSetterData {
                 setter_name,
+                cascade,
                 include_orphan_tags,
                 reply,
             } => {
                 let result = state
                     .with_transaction(move |conn| {
                         Box::pin(async move {
-                            let deleted = delete_setter_by_name(conn, &setter_name).await?;
+                            let deleted =
+                                delete_setter_by_name(conn, &setter_name, cascade).await?;
                             let orphan_tags = if include_orphan_tags {
                                 delete_orphan_tags(conn).await?
                             } else {
@@ -788,6 +1368,14 @@ impl Actor for IndexDbWriter {
                     .await;
                 let _ = reply.send(result);
             }
+            IndexDbWriterMessage::DeleteOrphanTags { reply } => {
+                let result = state
+                    .with_transaction(move |conn| {
+                        Box::pin(async move { delete_orphan_tags(conn).await })
+                    })
+                    .await;
+                let _ = reply.send(result);
+            }
             IndexDbWriterMessage::AddFolderToDatabase {
                 time_added,
                 path,
@@ -817,18 +1405,38 @@ impl Actor for IndexDbWriter {
                     .await;
                 let _ = reply.send(result);
             }
-            IndexDbWriterMessage::DeleteFilesUnderExcludedFolders { reply } => {
+            IndexDbWriterMessage::DeleteFilesUnderExcludedFolders { soft, reply } => {
+                let result = state
+                    .with_transaction(move |conn| {
+                        Box::pin(
+                            async move { delete_files_under_excluded_folders(conn, soft).await },
+                        )
+                    })
+                    .await;
+                let _ = reply.send(result);
+            }
+            IndexDbWriterMessage::DeleteFilesNotUnderIncludedFolders { soft, reply } => {
+                let result = state
+                    .with_transaction(move |conn| {
+                        Box::pin(async move {
+                            delete_files_not_under_included_folders(conn, soft).await
+                        })
+                    })
+                    .await;
+                let _ = reply.send(result);
+            }
+            IndexDbWriterMessage::RestoreRemovedFiles { reply } => {
                 let result = state
                     .with_transaction(move |conn| {
-                        Box::pin(async move { delete_files_under_excluded_folders(conn).await })
+                        Box::pin(async move { restore_removed_files(conn).await })
                     })
                     .await;
                 let _ = reply.send(result);
             }
-            IndexDbWriterMessage::DeleteFilesNotUnderIncludedFolders { reply } => {
+            IndexDbWriterMessage::PurgeRemovedFiles { grace_days, reply } => {
                 let result = state
                     .with_transaction(move |conn| {
-                        Box::pin(async move { delete_files_not_under_included_folders(conn).await })
+                        Box::pin(async move { purge_removed_files(conn, grace_days).await })
                     })
                     .await;
                 let _ = reply.send(result);
@@ -948,6 +1556,169 @@ impl Actor for IndexDbWriter {
                     .await;
                 let _ = reply.send(result);
             }
+            IndexDbWriterMessage::RepairLineageChunk {
+                mode,
+                after_id,
+                limit,
+                reply,
+            } => {
+                let result = state
+                    .with_transaction(move |conn| {
+                        Box::pin(async move {
+                            repair_dangling_lineage_chunk(conn, mode, after_id, limit).await
+                        })
+                    })
+                    .await;
+                let _ = reply.send(result);
+            }
+            IndexDbWriterMessage::RenormalizeTextChunk {
+                config,
+                after_id,
+                limit,
+                reply,
+            } => {
+                let result = state
+                    .with_transaction(move |conn| {
+                        Box::pin(async move {
+                            renormalize_text_chunk(conn, &config, after_id, limit).await
+                        })
+                    })
+                    .await;
+                let _ = reply.send(result);
+            }
+            IndexDbWriterMessage::StoreCoverageSnapshot {
+                computed_at,
+                report,
+                reply,
+            } => {
+                let result = state
+                    .with_transaction(move |conn| {
+                        Box::pin(async move {
+                            store_coverage_snapshot(conn, &computed_at, &report).await
+                        })
+                    })
+                    .await;
+                let _ = reply.send(result);
+            }
+            IndexDbWriterMessage::StoreStorageStatsSnapshot {
+                computed_at,
+                report,
+                reply,
+            } => {
+                let result = state
+                    .with_transaction(move |conn| {
+                        Box::pin(async move {
+                            store_storage_stats_snapshot(conn, &computed_at, &report).await
+                        })
+                    })
+                    .await;
+                let _ = reply.send(result);
+            }
+            IndexDbWriterMessage::AddVerificationRun {
+                start_time,
+                path_prefix,
+                modified_since,
+                max_files,
+                max_mb_per_sec,
+                reply,
+            } => {
+                let result = state
+                    .with_transaction(move |conn| {
+                        Box::pin(async move {
+                            add_verification_run(
+                                conn,
+                                &start_time,
+                                path_prefix.as_deref(),
+                                modified_since.as_deref(),
+                                max_files,
+                                max_mb_per_sec,
+                            )
+                            .await
+                        })
+                    })
+                    .await;
+                let _ = reply.send(result);
+            }
+            IndexDbWriterMessage::UpdateVerificationRun {
+                run_id,
+                update,
+                reply,
+            } => {
+                let result = state
+                    .with_transaction(move |conn| {
+                        Box::pin(
+                            async move { update_verification_run(conn, run_id, &update).await },
+                        )
+                    })
+                    .await;
+                let _ = reply.send(result);
+            }
+            IndexDbWriterMessage::AddVerificationResult {
+                run_id,
+                result,
+                reply,
+            } => {
+                let result = state
+                    .with_transaction(move |conn| {
+                        Box::pin(
+                            async move { add_verification_result(conn, run_id, &result).await },
+                        )
+                    })
+                    .await;
+                let _ = reply.send(result);
+            }
+            IndexDbWriterMessage::AddManualTags {
+                item_sha256,
+                tags,
+                reply,
+            } => {
+                let result = state
+                    .with_transaction(move |conn| {
+                        Box::pin(async move { add_manual_tags(conn, &item_sha256, &tags).await })
+                    })
+                    .await;
+                let _ = reply.send(result);
+            }
+            IndexDbWriterMessage::RemoveManualTags {
+                item_sha256,
+                tags,
+                reply,
+            } => {
+                let result = state
+                    .with_transaction(move |conn| {
+                        Box::pin(async move { remove_manual_tags(conn, &item_sha256, &tags).await })
+                    })
+                    .await;
+                let _ = reply.send(result);
+            }
+            IndexDbWriterMessage::SetPrimaryFile {
+                item_id,
+                file_id,
+                reply,
+            } => {
+                let result = state
+                    .with_transaction(move |conn| {
+                        Box::pin(async move { set_primary_file(conn, item_id, file_id).await })
+                    })
+                    .await;
+                let _ = reply.send(result);
+            }
+            IndexDbWriterMessage::SetTagAliasGroup { group, reply } => {
+                let result = state
+                    .with_transaction(move |conn| {
+                        Box::pin(async move { set_tag_alias_group(conn, &group).await })
+                    })
+                    .await;
+                let _ = reply.send(result);
+            }
+            IndexDbWriterMessage::DeleteTagAliasGroup { canonical, reply } => {
+                let result = state
+                    .with_transaction(move |conn| {
+                        Box::pin(async move { delete_tag_alias_group(conn, &canonical).await })
+                    })
+                    .await;
+                let _ = reply.send(result);
+            }
             IndexDbWriterMessage::Vacuum { reply } => {
                 tracing::info!(
                     index_db = %state.index_db,
@@ -963,6 +1734,35 @@ impl Actor for IndexDbWriter {
                 let result = state.run_maintenance(ANALYZE_STATEMENTS).await;
                 let _ = reply.send(result);
             }
+            IndexDbWriterMessage::IncrementalVacuum { reply } => {
+                let result = state
+                    .run_maintenance(&[
+                        "PRAGMA main.incremental_vacuum",
+                        "PRAGMA storage.incremental_vacuum",
+                    ])
+                    .await;
+                let _ = reply.send(result);
+            }
+            IndexDbWriterMessage::WalCheckpoint { reply } => {
+                // Readers holding an old snapshot can keep TRUNCATE from
+                // resetting the log; SQLite then reports busy instead of
+                // failing, and the next checkpoint picks up where it left.
+                let result = state
+                    .run_maintenance(&[
+                        "PRAGMA main.wal_checkpoint(TRUNCATE)",
+                        "PRAGMA storage.wal_checkpoint(TRUNCATE)",
+                    ])
+                    .await;
+                let _ = reply.send(result);
+            }
+            IndexDbWriterMessage::AddDbMaintenanceRun { run, reply } => {
+                let result = state
+                    .with_transaction(move |conn| {
+                        Box::pin(async move { add_db_maintenance_run(conn, &run).await })
+                    })
+                    .await;
+                let _ = reply.send(result);
+            }
             IndexDbWriterMessage::Flush { reply } => {
                 let _ = reply.send(Ok(()));
             }
@@ -975,9 +1775,13 @@ pub(crate) enum IndexDbSupervisorMessage {
     GetWriter {
         index_db: String,
         force_new: bool,
-        reply: Reply<ActorRef<IndexDbWriterMessage>>,
+        reply: Reply<WriterHandle>,
     },
     HealthCheck,
+    /// Queue depth snapshot for every index DB a writer has been started for.
+    Status {
+        reply: oneshot::Sender<Vec<IndexWriterStatus>>,
+    },
     /// Barrier across every live writer; replies with the number of writers
     /// that acknowledged once each has processed all previously queued
     /// messages. Used at process shutdown.
@@ -994,10 +1798,114 @@ pub(crate) struct IndexDbSupervisorArgs {
 }
 
 pub(crate) struct IndexDbSupervisorState {
-    writers: HashMap<IndexDbKey, ActorRef<IndexDbWriterMessage>>,
+    writers: HashMap<IndexDbKey, ActorRef<WriterEnvelope>>,
+    /// Kept across writer respawns: callers of a dead writer still hold
+    /// permits until their retry resolves.
+    loads: HashMap<IndexDbKey, Arc<WriterLoad>>,
     idle_timeout: Duration,
 }
 
+#[derive(Clone)]
+pub(crate) struct WriterHandle {
+    actor: ActorRef<WriterEnvelope>,
+    load: Arc<WriterLoad>,
+}
+
+/// Outstanding-message accounting for one index DB's writer. Only messages
+/// sent through `call_index_db_writer` are counted; the writer's own timers
+/// and supervisor barriers are not.
+pub(crate) struct WriterLoad {
+    permits: Arc<Semaphore>,
+    waiting: AtomicUsize,
+    next_seq: AtomicU64,
+    /// Send time per outstanding message; the first entry is the oldest.
+    outstanding: std::sync::Mutex<BTreeMap<u64, Instant>>,
+}
+
+/// Counts a message as outstanding until dropped, which also covers callers
+/// cancelled while awaiting the reply.
+struct OutstandingMessage {
+    load: Arc<WriterLoad>,
+    seq: u64,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl WriterLoad {
+    fn new(limit: usize) -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(limit)),
+            waiting: AtomicUsize::new(0),
+            next_seq: AtomicU64::new(0),
+            outstanding: std::sync::Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Waits until the writer has room for another message.
+    async fn begin(self: &Arc<Self>) -> OutstandingMessage {
+        let permit = match self.permits.clone().try_acquire_owned() {
+            Ok(permit) => permit,
+            Err(_) => {
+                self.waiting.fetch_add(1, Ordering::Relaxed);
+                let permit = self.permits.clone().acquire_owned().await;
+                self.waiting.fetch_sub(1, Ordering::Relaxed);
+                permit.expect("writer load semaphore is never closed")
+            }
+        };
+        let seq = self.next_seq.fetch_add(1, Ordering::Relaxed);
+        self.outstanding
+            .lock()
+            .expect("writer load lock poisoned")
+            .insert(seq, Instant::now());
+        OutstandingMessage {
+            load: self.clone(),
+            seq,
+            _permit: permit,
+        }
+    }
+
+    fn status(&self, index_db: &str, running: bool) -> IndexWriterStatus {
+        let outstanding = self.outstanding.lock().expect("writer load lock poisoned");
+        IndexWriterStatus {
+            index_db: index_db.to_string(),
+            running,
+            queue_depth: outstanding.len(),
+            waiting_callers: self.waiting.load(Ordering::Relaxed),
+            oldest_message_age_ms: outstanding
+                .values()
+                .next()
+                .map(|sent| sent.elapsed().as_millis() as u64),
+            queue_limit: WRITER_QUEUE_LIMIT,
+        }
+    }
+}
+
+impl Drop for OutstandingMessage {
+    fn drop(&mut self) {
+        self.load
+            .outstanding
+            .lock()
+            .expect("writer load lock poisoned")
+            .remove(&self.seq);
+    }
+}
+
+/// Backlog of one index DB's writer, as reported by the health endpoint and
+/// the job queue status.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub(crate) struct IndexWriterStatus {
+    pub index_db: String,
+    /// False once the supervisor has dropped the writer, e.g. after a
+    /// failed health check; the next call starts a new one.
+    pub running: bool,
+    /// Messages sent and not yet answered, including the one executing.
+    pub queue_depth: usize,
+    /// Callers held back because `queue_depth` reached `queue_limit`.
+    pub waiting_callers: usize,
+    /// Time since the oldest outstanding message was sent; None when idle.
+    pub oldest_message_age_ms: Option<u64>,
+    pub queue_limit: usize,
+}
+
 impl Actor for IndexDbSupervisor {
     type Msg = IndexDbSupervisorMessage;
     type State = IndexDbSupervisorState;
@@ -1014,6 +1922,7 @@ impl Actor for IndexDbSupervisor {
         );
         Ok(IndexDbSupervisorState {
             writers: HashMap::new(),
+            loads: HashMap::new(),
             idle_timeout: args.idle_timeout,
         })
     }
@@ -1033,22 +1942,33 @@ impl Actor for IndexDbSupervisor {
                 let key = IndexDbKey {
                     index_db: index_db.clone(),
                 };
+                let load = state
+                    .loads
+                    .entry(key.clone())
+                    .or_insert_with(|| Arc::new(WriterLoad::new(WRITER_QUEUE_LIMIT)))
+                    .clone();
                 if let Some(existing) = state.writers.get(&key) {
                     if force_new {
                         // Only force a respawn if the existing writer is actually dead.
                         if existing
-                            .send_message(IndexDbWriterMessage::IdleCheck)
+                            .send_message(IndexDbWriterMessage::IdleCheck.into())
                             .is_err()
                         {
                             if let Some(existing) = state.writers.remove(&key) {
                                 existing.stop(None);
                             }
                         } else {
-                            let _ = reply.send(Ok(existing.clone()));
+                            let _ = reply.send(Ok(WriterHandle {
+                                actor: existing.clone(),
+                                load,
+                            }));
                             return Ok(());
                         }
                     } else {
-                        let _ = reply.send(Ok(existing.clone()));
+                        let _ = reply.send(Ok(WriterHandle {
+                            actor: existing.clone(),
+                            load,
+                        }));
                         return Ok(());
                     }
                 }
@@ -1057,7 +1977,10 @@ impl Actor for IndexDbSupervisor {
                 match writer {
                     Ok(writer) => {
                         state.writers.insert(key, writer.clone());
-                        let _ = reply.send(Ok(writer));
+                        let _ = reply.send(Ok(WriterHandle {
+                            actor: writer,
+                            load,
+                        }));
                     }
                     Err(err) => {
                         let _ = reply.send(Err(err));
@@ -1091,7 +2014,7 @@ impl Actor for IndexDbSupervisor {
 
                     if !to_remove.contains(key) {
                         if writer
-                            .send_message(IndexDbWriterMessage::IdleCheck)
+                            .send_message(IndexDbWriterMessage::IdleCheck.into())
                             .is_err()
                         {
                             to_remove.push(key.clone());
@@ -1105,12 +2028,21 @@ impl Actor for IndexDbSupervisor {
                     }
                 }
             }
+            IndexDbSupervisorMessage::Status { reply } => {
+                let mut statuses: Vec<IndexWriterStatus> = state
+                    .loads
+                    .iter()
+                    .map(|(key, load)| load.status(&key.index_db, state.writers.contains_key(key)))
+                    .collect();
+                statuses.sort_by(|a, b| a.index_db.cmp(&b.index_db));
+                let _ = reply.send(statuses);
+            }
             IndexDbSupervisorMessage::FlushAll { reply } => {
                 let mut receivers = Vec::new();
                 for writer in state.writers.values() {
                     let (tx, rx) = oneshot::channel();
                     if writer
-                        .send_message(IndexDbWriterMessage::Flush { reply: tx })
+                        .send_message(IndexDbWriterMessage::Flush { reply: tx }.into())
                         .is_ok()
                     {
                         receivers.push(rx);
@@ -1131,20 +2063,15 @@ impl Actor for IndexDbSupervisor {
 
 static SUPERVISOR: OnceLock<Mutex<Option<ActorRef<IndexDbSupervisorMessage>>>> = OnceLock::new();
 
-pub(crate) async fn get_index_db_writer(
-    index_db: &str,
-) -> ApiResult<ActorRef<IndexDbWriterMessage>> {
+pub(crate) async fn get_index_db_writer(index_db: &str) -> ApiResult<WriterHandle> {
     get_index_db_writer_inner(index_db, false).await
 }
 
-async fn get_index_db_writer_fresh(index_db: &str) -> ApiResult<ActorRef<IndexDbWriterMessage>> {
+async fn get_index_db_writer_fresh(index_db: &str) -> ApiResult<WriterHandle> {
     get_index_db_writer_inner(index_db, true).await
 }
 
-async fn get_index_db_writer_inner(
-    index_db: &str,
-    force_new: bool,
-) -> ApiResult<ActorRef<IndexDbWriterMessage>> {
+async fn get_index_db_writer_inner(index_db: &str, force_new: bool) -> ApiResult<WriterHandle> {
     for attempt in 0..CALL_RETRY_ATTEMPTS {
         let supervisor = if attempt == 0 {
             ensure_supervisor().await?
@@ -1171,6 +2098,8 @@ async fn get_index_db_writer_inner(
 
 /// Sends a request to the writer with a single retry on writer death.
 /// The builder may be called more than once; use Arc/cloneable payloads if needed.
+/// Waits first while the writer already has `WRITER_QUEUE_LIMIT` messages
+/// outstanding, so producers slow to the writer's pace instead of queueing.
 pub(crate) async fn call_index_db_writer<T, F>(index_db: &str, mut build: F) -> ApiResult<T>
 where
     F: FnMut(Reply<T>) -> IndexDbWriterMessage,
@@ -1182,9 +2111,10 @@ where
         } else {
             get_index_db_writer_fresh(index_db).await?
         };
+        let _outstanding = writer.load.begin().await;
         let (reply, rx) = oneshot::channel();
         let msg = build(reply);
-        if writer.send_message(msg).is_err() {
+        if writer.actor.send_message(msg.into()).is_err() {
             last_err = Some(ApiError::internal("Index DB writer unavailable"));
             continue;
         }
@@ -1200,6 +2130,26 @@ where
     Err(last_err.unwrap_or_else(|| ApiError::internal("Index DB writer unavailable")))
 }
 
+/// Queue depth of every index DB writer started in this process; empty when
+/// the supervisor was never started (e.g. inference-only mode).
+pub(crate) async fn index_writer_status() -> Vec<IndexWriterStatus> {
+    let Some(cell) = SUPERVISOR.get() else {
+        return Vec::new();
+    };
+    let supervisor = cell.lock().await.clone();
+    let Some(supervisor) = supervisor else {
+        return Vec::new();
+    };
+    let (reply, rx) = oneshot::channel();
+    if supervisor
+        .send_message(IndexDbSupervisorMessage::Status { reply })
+        .is_err()
+    {
+        return Vec::new();
+    }
+    rx.await.unwrap_or_default()
+}
+
 /// Drains every live index DB writer (a message-ordering barrier, not an
 /// fsync). Returns the number of writers that acknowledged; 0 when the
 /// supervisor was never started. Used at process shutdown.
@@ -1257,7 +2207,7 @@ async fn spawn_supervisor() -> ApiResult<ActorRef<IndexDbSupervisorMessage>> {
 async fn spawn_writer(
     index_db: &str,
     idle_timeout: Duration,
-) -> ApiResult<ActorRef<IndexDbWriterMessage>> {
+) -> ApiResult<ActorRef<WriterEnvelope>> {
     let name = format!("index-db-writer-{}", sanitize_name(index_db));
     let args = IndexDbWriterArgs {
         index_db: index_db.to_string(),
@@ -1313,6 +2263,13 @@ async fn commit_tx(conn: &mut SqliteConnection) -> ApiResult<()> {
     Ok(())
 }
 
+/// Removes the files a committed visuals write left without a row.
+async fn finish_visuals_write(result: ApiResult<VisualsWrite>) -> ApiResult<u64> {
+    let write = result?;
+    remove_visual_files(&write.stale_files).await;
+    Ok(write.rows)
+}
+
 async fn rollback_tx(conn: &mut SqliteConnection) -> ApiResult<()> {
     sqlx::query("ROLLBACK")
         .execute(&mut *conn)
@@ -1331,6 +2288,73 @@ mod tests {
     use super::*;
     use crate::db::migrations::setup_test_databases;
 
+    // Ensures a full writer queue holds new callers back until a slot frees.
+    #[tokio::test]
+    async fn writer_load_holds_callers_at_limit() {
+        let load = Arc::new(WriterLoad::new(2));
+        let first = load.begin().await;
+        let _second = load.begin().await;
+        assert_eq!(load.status("db", true).queue_depth, 2);
+
+        let waiter = tokio::spawn({
+            let load = load.clone();
+            async move { load.begin().await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiter.is_finished());
+        let status = load.status("db", true);
+        assert_eq!(status.queue_depth, 2);
+        assert_eq!(status.waiting_callers, 1);
+        assert!(status.oldest_message_age_ms.is_some());
+
+        drop(first);
+        let _third = tokio::time::timeout(Duration::from_secs(5), waiter)
+            .await
+            .expect("waiter should get the freed slot")
+            .unwrap();
+        let status = load.status("db", true);
+        assert_eq!(status.queue_depth, 2);
+        assert_eq!(status.waiting_callers, 0);
+    }
+
+    // Floods a real writer with cheap messages: every call completes, but the
+    // writer never has more than WRITER_QUEUE_LIMIT of them outstanding.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn flooded_writer_queue_stays_bounded() {
+        let index_db = "backpressure_flood_test";
+        let producers = WRITER_QUEUE_LIMIT * 8;
+        let mut calls = tokio::task::JoinSet::new();
+        for _ in 0..producers {
+            calls.spawn(async move {
+                call_index_db_writer(index_db, |reply| IndexDbWriterMessage::Flush { reply }).await
+            });
+        }
+
+        let mut peak_depth = 0;
+        while !calls.is_empty() {
+            if let Some(status) = index_writer_status()
+                .await
+                .into_iter()
+                .find(|status| status.index_db == index_db)
+            {
+                peak_depth = peak_depth.max(status.queue_depth);
+            }
+            while let Some(joined) = calls.try_join_next() {
+                joined.unwrap().unwrap();
+            }
+            tokio::task::yield_now().await;
+        }
+
+        assert!(peak_depth <= WRITER_QUEUE_LIMIT, "peak depth {peak_depth}");
+        let status = index_writer_status()
+            .await
+            .into_iter()
+            .find(|status| status.index_db == index_db)
+            .unwrap();
+        assert_eq!(status.queue_depth, 0);
+        assert_eq!(status.oldest_message_age_ms, None);
+    }
+
 
     // Guards the property the constant's comment argues for: post-job
     // maintenance must leave real statistics behind, on a connection with no
@@ -1,7 +1,25 @@
 use anyhow::{Context, Result};
-use std::fs;
+use sqlx::{Connection, SqliteConnection, sqlite::SqliteConnectOptions};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
 
-use crate::policy::{DbInfo, SingleDbInfo};
+use crate::jobs::registry::Registry;
+use crate::policy::{DbErrorCode, DbInfo, DbStatus, DbStatusError, SingleDbInfo};
+
+/// How long an inspection waits on another connection's lock before the
+/// database is reported as `locked`.
+const INSPECT_BUSY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Size and mtime of a database file or its WAL; None if it doesn't exist.
+type FileStamp = Option<(u64, SystemTime)>;
+
+/// Healthy statuses by database files, reused while none of the files or
+/// their WALs changed, so listing databases doesn't open each one and count
+/// its items on every request. Errors are inspected again next time.
+static INSPECTED: Registry<Vec<PathBuf>, (Vec<FileStamp>, DbStatus)> = Registry::new();
 
 pub(crate) fn load_db_info() -> Result<DbInfo> {
     let (index_default, user_default) = db_defaults();
@@ -10,14 +28,165 @@ pub(crate) fn load_db_info() -> Result<DbInfo> {
         index: SingleDbInfo {
             current: index_default,
             all: index_dbs,
+            databases: Vec::new(),
         },
         user_data: SingleDbInfo {
             current: user_default,
             all: user_data_dbs,
+            databases: Vec::new(),
         },
+        partial: false,
     })
 }
 
+/// [`load_db_info`] plus a status entry per database. Each database is
+/// opened read-only on its own, so a corrupt or locked file shows up as an
+/// error entry (and sets `partial`) instead of failing the listing.
+pub(crate) async fn load_db_info_with_status() -> Result<DbInfo> {
+    let mut info = load_db_info()?;
+    let data_dir = crate::config::runtime().data_folder.clone();
+    for name in &info.index.all {
+        let dir = data_dir.join("index").join(name);
+        let status = inspect_database(
+            name,
+            &[dir.join("index.db"), dir.join("storage.db")],
+            Some("SELECT COUNT(*) FROM items"),
+        )
+        .await;
+        info.index.databases.push(status);
+    }
+    for name in &info.user_data.all {
+        let file = data_dir.join("user_data").join(format!("{name}.db"));
+        let status = inspect_database(name, &[file], None).await;
+        info.user_data.databases.push(status);
+    }
+    info.partial = info
+        .index
+        .databases
+        .iter()
+        .chain(&info.user_data.databases)
+        .any(|status| !status.ok);
+    Ok(info)
+}
+
+/// The cached status of `files` if they are unchanged since it was taken,
+/// otherwise a fresh [`read_database_status`].
+async fn inspect_database(
+    name: &str,
+    files: &[PathBuf],
+    count_query: Option<&'static str>,
+) -> DbStatus {
+    let stamps = file_stamps(files);
+    if let Some((cached_stamps, status)) = INSPECTED.get(files)
+        && cached_stamps == stamps
+    {
+        return status;
+    }
+    let status = read_database_status(name, files, count_query).await;
+    if status.ok {
+        INSPECTED.insert(files.to_vec(), (stamps, status.clone()));
+    }
+    status
+}
+
+/// Opens each of `files` that exists and reads its schema; `count_query` runs
+/// against the first one.
+async fn read_database_status(
+    name: &str,
+    files: &[PathBuf],
+    count_query: Option<&'static str>,
+) -> DbStatus {
+    let mut item_count = None;
+    for (idx, file) in files.iter().enumerate() {
+        // Storage DBs are created on first migration; their absence is not
+        // an error in itself.
+        if idx > 0 && !file.exists() {
+            continue;
+        }
+        let query = match count_query {
+            Some(query) if idx == 0 => query,
+            _ => "SELECT COUNT(*) FROM sqlite_master",
+        };
+        match count_rows(file, query).await {
+            Ok(count) if idx == 0 && count_query.is_some() => item_count = Some(count),
+            Ok(_) => {}
+            Err(err) => {
+                tracing::warn!(database = name, path = %file.display(), error = %err, "failed to inspect database");
+                return DbStatus {
+                    name: name.to_string(),
+                    ok: false,
+                    size_bytes: None,
+                    item_count: None,
+                    error: Some(DbStatusError {
+                        code: classify_error(&err),
+                        message: err.to_string(),
+                    }),
+                };
+            }
+        }
+    }
+    DbStatus {
+        name: name.to_string(),
+        ok: true,
+        size_bytes: Some(files.iter().map(|file| size_with_wal(file)).sum()),
+        item_count,
+        error: None,
+    }
+}
+
+async fn count_rows(file: &Path, query: &'static str) -> std::result::Result<i64, sqlx::Error> {
+    let options = SqliteConnectOptions::new()
+        .filename(file)
+        .read_only(true)
+        .busy_timeout(INSPECT_BUSY_TIMEOUT);
+    let mut conn = SqliteConnection::connect_with(&options).await?;
+    let count: std::result::Result<(i64,), sqlx::Error> =
+        sqlx::query_as(query).fetch_one(&mut conn).await;
+    let _ = conn.close().await;
+    Ok(count?.0)
+}
+
+fn classify_error(err: &sqlx::Error) -> DbErrorCode {
+    let primary_code = err
+        .as_database_error()
+        .and_then(|db_err| db_err.code())
+        .and_then(|code| code.parse::<i32>().ok())
+        .map(|code| code & 0xff);
+    match primary_code {
+        // SQLITE_CORRUPT, SQLITE_NOTADB
+        Some(11 | 26) => DbErrorCode::Corrupt,
+        // SQLITE_BUSY, SQLITE_LOCKED
+        Some(5 | 6) => DbErrorCode::Locked,
+        _ => DbErrorCode::Unreadable,
+    }
+}
+
+fn size_with_wal(file: &Path) -> u64 {
+    [file.to_path_buf(), wal_path(file)]
+        .iter()
+        .filter_map(|path| fs::metadata(path).ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+fn file_stamps(files: &[PathBuf]) -> Vec<FileStamp> {
+    files
+        .iter()
+        .flat_map(|file| [file.clone(), wal_path(file)])
+        .map(|path| {
+            let metadata = fs::metadata(path).ok()?;
+            Some((metadata.len(), metadata.modified().ok()?))
+        })
+        .collect()
+}
+
+/// The SQLite write-ahead log beside `file`.
+pub(crate) fn wal_path(file: &Path) -> PathBuf {
+    let mut wal = file.as_os_str().to_owned();
+    wal.push("-wal");
+    PathBuf::from(wal)
+}
+
 pub(crate) fn db_defaults() -> (String, String) {
     let runtime = crate::config::runtime();
     (runtime.index_db.clone(), runtime.user_data_db.clone())
@@ -61,3 +230,86 @@ pub(crate) fn db_lists() -> Result<(Vec<String>, Vec<String>)> {
 
     Ok((index_dbs, user_data_dbs))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::migrations::migrate_databases_on_disk;
+    use crate::test_utils::test_data_dir;
+
+    fn find(statuses: &[DbStatus], name: &str) -> DbStatus {
+        statuses
+            .iter()
+            .find(|status| status.name == name)
+            .cloned()
+            .unwrap_or_else(|| panic!("no status for {name}"))
+    }
+
+    // Ensures a corrupt database becomes an error entry instead of failing
+    // the listing, while healthy ones still report their stats.
+    #[tokio::test]
+    async fn corrupt_database_is_reported_not_fatal() {
+        let test_env = test_data_dir();
+        migrate_databases_on_disk(Some("info_healthy"), Some("info_healthy"))
+            .await
+            .unwrap();
+        let broken_dir = test_env.path().join("index").join("info_corrupt");
+        fs::create_dir_all(&broken_dir).unwrap();
+        fs::write(broken_dir.join("index.db"), vec![0x5a; 4096]).unwrap();
+        let broken_user = test_env.path().join("user_data").join("info_corrupt.db");
+        fs::write(&broken_user, vec![0x5a; 4096]).unwrap();
+
+        let info = load_db_info_with_status().await;
+        // Other tests share the data folder; don't leave the broken files.
+        fs::remove_dir_all(&broken_dir).unwrap();
+        fs::remove_file(&broken_user).unwrap();
+        let info = info.unwrap();
+
+        assert!(info.partial);
+        let healthy = find(&info.index.databases, "info_healthy");
+        assert!(healthy.ok, "{healthy:?}");
+        assert_eq!(healthy.item_count, Some(0));
+        assert!(healthy.size_bytes.unwrap() > 0);
+        assert!(find(&info.user_data.databases, "info_healthy").ok);
+
+        for broken in [
+            find(&info.index.databases, "info_corrupt"),
+            find(&info.user_data.databases, "info_corrupt"),
+        ] {
+            assert!(!broken.ok);
+            assert_eq!(broken.error.unwrap().code, DbErrorCode::Corrupt);
+        }
+    }
+
+    // Ensures a cached status is reused only until the database is written.
+    #[tokio::test]
+    async fn cached_status_follows_database_writes() {
+        let test_env = test_data_dir();
+        migrate_databases_on_disk(Some("info_cached"), None)
+            .await
+            .unwrap();
+        let dir = test_env.path().join("index").join("info_cached");
+        let files = [dir.join("index.db"), dir.join("storage.db")];
+        let count = Some("SELECT COUNT(*) FROM items");
+
+        let first = inspect_database("info_cached", &files, count).await;
+        assert_eq!(first.item_count, Some(0));
+        assert!(INSPECTED.get(&files[..]).is_some());
+        let again = inspect_database("info_cached", &files, count).await;
+        assert_eq!(again.item_count, Some(0));
+
+        let mut conn = crate::db::open_index_db_write_no_user_data("info_cached")
+            .await
+            .unwrap();
+        sqlx::query(
+            "INSERT INTO items (sha256, md5, type, time_added) \
+             VALUES ('sha1', 'md51', 'image/png', '2024-01-01T00:00:00')",
+        )
+        .execute(&mut conn)
+        .await
+        .unwrap();
+        conn.close().await.unwrap();
+        let after = inspect_database("info_cached", &files, count).await;
+        assert_eq!(after.item_count, Some(1));
+    }
+}
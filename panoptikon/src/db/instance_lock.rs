@@ -0,0 +1,403 @@
+//! One gateway per index DB.
+//!
+//! Every serving process (and the offline commands that write) holds an
+//! exclusive advisory lock on `index/<db>/instance.lock` for each index DB
+//! it finds at startup, so a second gateway started against the same data
+//! folder (say by systemd and by hand, from different roots) can't run a
+//! second set of continuous scans and index writers on it. The OS drops the
+//! lock when its process dies. The file also records the owner and a
+//! heartbeat, refreshed every third of `[server] db_lock_stale_secs`; a
+//! heartbeat fresher than that from another instance counts as held even
+//! when the lock itself could be taken, which covers filesystems that don't
+//! share locks between hosts. A crashed owner's lock is therefore reclaimed
+//! once its heartbeat goes stale, and a clean shutdown clears the record so
+//! a restart isn't held up.
+
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use fs2::FileExt;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+pub(crate) const LOCK_FILE_NAME: &str = "instance.lock";
+
+/// Who holds an index DB's lock, as recorded in its lock file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub(crate) struct DbLockOwner {
+    /// Random per process, so a restarted gateway is a different owner.
+    pub instance_id: String,
+    pub pid: u32,
+    /// Unix seconds.
+    pub started_at: i64,
+    /// Unix seconds of the owner's last heartbeat.
+    pub heartbeat_at: i64,
+}
+
+/// Lock ownership of one index DB, as reported by `/health`.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub(crate) struct DbLockStatus {
+    pub index_db: String,
+    pub path: String,
+    /// Whether this process holds the lock. False for DBs another instance
+    /// held at startup; this process then serves them read-only.
+    pub owned: bool,
+    /// The recorded owner: this process when `owned`, otherwise the other
+    /// instance as of its last heartbeat (null if it left no record).
+    pub owner: Option<DbLockOwner>,
+}
+
+/// An index DB locked by another live instance.
+#[derive(Debug, Clone)]
+pub(crate) struct DbLockHeld {
+    pub index_db: String,
+    pub path: PathBuf,
+    pub owner: Option<DbLockOwner>,
+}
+
+impl std::fmt::Display for DbLockHeld {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "index DB '{}' is in use by another Panoptikon instance (lock '{}'",
+            self.index_db,
+            self.path.display()
+        )?;
+        if let Some(owner) = &self.owner {
+            write!(
+                f,
+                ", pid {}, instance {}, last heartbeat {}s ago",
+                owner.pid,
+                owner.instance_id,
+                (unix_now() - owner.heartbeat_at).max(0)
+            )?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// A held lock on one index DB.
+pub(crate) struct DbLock {
+    file: File,
+    index_db: String,
+    path: PathBuf,
+    owner: DbLockOwner,
+}
+
+impl DbLock {
+    /// Takes the lock in `index_dir` (created if missing) for this process.
+    pub(crate) fn acquire(
+        index_dir: &Path,
+        index_db: &str,
+        stale_after: Duration,
+    ) -> Result<Self, DbLockError> {
+        std::fs::create_dir_all(index_dir).map_err(DbLockError::Io)?;
+        let path = index_dir.join(LOCK_FILE_NAME);
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .write(true)
+            .truncate(false)
+            .open(&path)
+            .map_err(DbLockError::Io)?;
+        let held = |owner| {
+            DbLockError::Held(DbLockHeld {
+                index_db: index_db.to_string(),
+                path: path.clone(),
+                owner,
+            })
+        };
+        if file.try_lock_exclusive().is_err() {
+            return Err(held(read_owner(&mut file)));
+        }
+        let previous = read_owner(&mut file);
+        let instance_id = instance_id();
+        if let Some(previous) = previous.filter(|owner| {
+            owner.instance_id != instance_id
+                && unix_now() - owner.heartbeat_at < stale_after.as_secs() as i64
+        }) {
+            let _ = FileExt::unlock(&file);
+            return Err(held(Some(previous)));
+        }
+        let now = unix_now();
+        let mut lock = Self {
+            file,
+            index_db: index_db.to_string(),
+            path,
+            owner: DbLockOwner {
+                instance_id: instance_id.to_string(),
+                pid: std::process::id(),
+                started_at: now,
+                heartbeat_at: now,
+            },
+        };
+        lock.write_owner().map_err(DbLockError::Io)?;
+        Ok(lock)
+    }
+
+    pub(crate) fn heartbeat(&mut self) -> std::io::Result<()> {
+        self.owner.heartbeat_at = unix_now();
+        self.write_owner()
+    }
+
+    fn write_owner(&mut self) -> std::io::Result<()> {
+        let record = serde_json::to_vec(&self.owner).map_err(std::io::Error::other)?;
+        self.file.set_len(0)?;
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.write_all(&record)?;
+        self.file.sync_data()
+    }
+
+    fn status(&self) -> DbLockStatus {
+        DbLockStatus {
+            index_db: self.index_db.clone(),
+            path: self.path.display().to_string(),
+            owned: true,
+            owner: Some(self.owner.clone()),
+        }
+    }
+}
+
+impl Drop for DbLock {
+    /// Clears the record so the next instance doesn't wait for the
+    /// heartbeat to go stale; the OS releases the lock with the file.
+    fn drop(&mut self) {
+        let _ = self.file.set_len(0);
+    }
+}
+
+#[derive(Debug)]
+pub(crate) enum DbLockError {
+    Held(DbLockHeld),
+    Io(std::io::Error),
+}
+
+impl std::fmt::Display for DbLockError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Held(held) => held.fmt(f),
+            Self::Io(err) => write!(f, "failed to open index DB lock: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for DbLockError {}
+
+fn read_owner(file: &mut File) -> Option<DbLockOwner> {
+    let mut record = String::new();
+    file.seek(SeekFrom::Start(0)).ok()?;
+    file.read_to_string(&mut record).ok()?;
+    serde_json::from_str(&record).ok()
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+fn instance_id() -> &'static str {
+    static ID: OnceLock<String> = OnceLock::new();
+    ID.get_or_init(|| uuid::Uuid::new_v4().to_string())
+}
+
+struct Registry {
+    stale_after: Duration,
+    locks: Vec<DbLock>,
+    /// DBs another instance held at startup.
+    held: Vec<DbLockHeld>,
+}
+
+fn registry() -> &'static Mutex<Option<Registry>> {
+    static REGISTRY: OnceLock<Mutex<Option<Registry>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| Mutex::new(None))
+}
+
+/// Locks every index DB under `data_folder` plus `default_index_db` (which
+/// may not exist yet) and remembers them for the process. Returns the DBs
+/// another instance holds; I/O failures are returned as errors.
+pub(crate) fn acquire_startup_locks(
+    data_folder: &Path,
+    default_index_db: &str,
+    stale_after: Duration,
+) -> std::io::Result<Vec<DbLockHeld>> {
+    let index_root = data_folder.join("index");
+    let mut names = vec![default_index_db.to_string()];
+    if let Ok(entries) = std::fs::read_dir(&index_root) {
+        for entry in entries.flatten() {
+            if entry.path().join("index.db").is_file() {
+                names.push(entry.file_name().to_string_lossy().to_string());
+            }
+        }
+    }
+    names.sort();
+    names.dedup();
+
+    let mut locks = Vec::new();
+    let mut held = Vec::new();
+    for name in names {
+        match DbLock::acquire(&index_root.join(&name), &name, stale_after) {
+            Ok(lock) => locks.push(lock),
+            Err(DbLockError::Held(other)) => held.push(other),
+            Err(DbLockError::Io(err)) => return Err(err),
+        }
+    }
+    *registry().lock().unwrap_or_else(|err| err.into_inner()) = Some(Registry {
+        stale_after,
+        locks,
+        held: held.clone(),
+    });
+    Ok(held)
+}
+
+/// Locks an index DB created after startup. A no-op when startup locks were
+/// never taken (tests, commands that don't own the data folder).
+pub(crate) fn lock_new_index_db(index_db: &str) -> Result<(), DbLockError> {
+    let mut guard = registry().lock().unwrap_or_else(|err| err.into_inner());
+    let Some(registry) = guard.as_mut() else {
+        return Ok(());
+    };
+    if registry.locks.iter().any(|lock| lock.index_db == index_db) {
+        return Ok(());
+    }
+    let index_dir = crate::config::runtime()
+        .data_folder
+        .join("index")
+        .join(index_db);
+    let lock = DbLock::acquire(&index_dir, index_db, registry.stale_after)?;
+    tracing::info!(index_db, path = %lock.path.display(), "holding index DB instance lock");
+    registry.locks.push(lock);
+    Ok(())
+}
+
+/// Refreshes the heartbeat of every held lock, a third of the stale timeout
+/// apart, for the life of the process.
+pub(crate) fn spawn_heartbeat() {
+    let Some(stale_after) = registry()
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .as_ref()
+        .map(|registry| registry.stale_after)
+    else {
+        return;
+    };
+    let interval = (stale_after / 3).max(Duration::from_secs(1));
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            let mut guard = registry().lock().unwrap_or_else(|err| err.into_inner());
+            let Some(registry) = guard.as_mut() else {
+                return;
+            };
+            for lock in &mut registry.locks {
+                if let Err(err) = lock.heartbeat() {
+                    tracing::warn!(
+                        index_db = %lock.index_db,
+                        error = %err,
+                        "failed to refresh index DB lock heartbeat"
+                    );
+                }
+            }
+        }
+    });
+}
+
+/// Lock ownership of every index DB seen at startup or created since.
+pub(crate) fn lock_status() -> Vec<DbLockStatus> {
+    let guard = registry().lock().unwrap_or_else(|err| err.into_inner());
+    let Some(registry) = guard.as_ref() else {
+        return Vec::new();
+    };
+    let mut status = registry
+        .locks
+        .iter()
+        .map(DbLock::status)
+        .chain(registry.held.iter().map(|held| {
+            // The other instance keeps heartbeating; report its latest.
+            let owner = File::open(&held.path)
+                .ok()
+                .and_then(|mut file| read_owner(&mut file))
+                .or_else(|| held.owner.clone());
+            DbLockStatus {
+                index_db: held.index_db.clone(),
+                path: held.path.display().to_string(),
+                owned: false,
+                owner,
+            }
+        }))
+        .collect::<Vec<_>>();
+    status.sort_by(|a, b| a.index_db.cmp(&b.index_db));
+    status
+}
+
+/// Releases every lock; called at the end of a clean shutdown.
+pub(crate) fn release_all() {
+    registry()
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .take();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STALE: Duration = Duration::from_secs(30);
+
+    // Ensures a second instance can't take a lock while the first holds it,
+    // and can once the first releases it.
+    #[test]
+    fn lock_is_exclusive_until_released() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = DbLock::acquire(dir.path(), "default", STALE).unwrap();
+
+        let Err(DbLockError::Held(held)) = DbLock::acquire(dir.path(), "default", STALE) else {
+            panic!("second acquire should see the lock held");
+        };
+        let owner = held.owner.as_ref().expect("owner record");
+        assert_eq!(owner.pid, std::process::id());
+        assert!(
+            held.to_string()
+                .contains("in use by another Panoptikon instance")
+        );
+
+        drop(first);
+        DbLock::acquire(dir.path(), "default", STALE).unwrap();
+    }
+
+    // Ensures a fresh heartbeat from another instance blocks the lock even
+    // when the file lock is free, and a stale one is reclaimed.
+    #[test]
+    fn heartbeat_record_blocks_until_stale() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(LOCK_FILE_NAME);
+        let mut crashed = DbLockOwner {
+            instance_id: "other-instance".to_string(),
+            pid: 1,
+            started_at: unix_now() - 100,
+            heartbeat_at: unix_now() - 5,
+        };
+        std::fs::write(&path, serde_json::to_vec(&crashed).unwrap()).unwrap();
+
+        let Err(DbLockError::Held(held)) = DbLock::acquire(dir.path(), "default", STALE) else {
+            panic!("a fresh heartbeat should hold the lock");
+        };
+        assert_eq!(held.owner.as_ref(), Some(&crashed));
+
+        crashed.heartbeat_at = unix_now() - 60;
+        std::fs::write(&path, serde_json::to_vec(&crashed).unwrap()).unwrap();
+        let mut lock = DbLock::acquire(dir.path(), "default", STALE).unwrap();
+        lock.heartbeat().unwrap();
+        let record: DbLockOwner = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(record.instance_id, instance_id());
+
+        drop(lock);
+        assert!(std::fs::read(&path).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,243 @@
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use utoipa::ToSchema;
+
+use crate::api_error::ApiError;
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+/// A metadata field value: a JSON string, number or boolean. Stored as its
+/// JSON encoding so `match_meta` can tell numbers from numeric-looking text.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(untagged)]
+pub(crate) enum MetaValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+}
+
+impl MetaValue {
+    fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("scalar serializes")
+    }
+}
+
+/// One metadata field on an item.
+pub(crate) struct ItemMetaRecord {
+    pub key: String,
+    pub value: MetaValue,
+    pub time_updated: String,
+}
+
+/// A metadata key and the number of items in the index that have it.
+pub(crate) struct MetaKeyCount {
+    pub key: String,
+    pub items: i64,
+}
+
+fn internal(context: &'static str) -> impl FnOnce(sqlx::Error) -> ApiError {
+    move |err| {
+        tracing::error!(error = %err, context, "item meta query failed");
+        ApiError::internal(context)
+    }
+}
+
+fn map_record(row: &sqlx::sqlite::SqliteRow) -> Result<ItemMetaRecord, sqlx::Error> {
+    let value: String = row.try_get("value")?;
+    Ok(ItemMetaRecord {
+        key: row.try_get("key")?,
+        value: serde_json::from_str(&value).map_err(|err| sqlx::Error::ColumnDecode {
+            index: "value".to_string(),
+            source: Box::new(err),
+        })?,
+        time_updated: row.try_get("time_updated")?,
+    })
+}
+
+/// The item's fields, by key.
+pub(crate) async fn list_item_meta(
+    conn: &mut sqlx::SqliteConnection,
+    sha256: &str,
+) -> ApiResult<Vec<ItemMetaRecord>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT key, value, time_updated
+        FROM user_data.item_meta
+        WHERE sha256 = ?
+        ORDER BY key
+        "#,
+    )
+    .bind(sha256)
+    .fetch_all(conn)
+    .await
+    .map_err(internal("Failed to load item metadata"))?;
+
+    rows.iter()
+        .map(map_record)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(internal("Failed to load item metadata"))
+}
+
+/// Creates or replaces one field on the item.
+pub(crate) async fn set_item_meta(
+    conn: &mut sqlx::SqliteConnection,
+    sha256: &str,
+    key: &str,
+    value: &MetaValue,
+) -> ApiResult<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO user_data.item_meta (sha256, key, value, time_updated)
+        VALUES (?, ?, ?, strftime('%Y-%m-%dT%H:%M:%f','now','localtime'))
+        ON CONFLICT (sha256, key) DO UPDATE SET
+            value = excluded.value,
+            time_updated = excluded.time_updated
+        "#,
+    )
+    .bind(sha256)
+    .bind(key)
+    .bind(value.to_json())
+    .execute(conn)
+    .await
+    .map_err(internal("Failed to save item metadata"))?;
+    Ok(())
+}
+
+pub(crate) async fn delete_item_meta(
+    conn: &mut sqlx::SqliteConnection,
+    sha256: &str,
+    key: &str,
+) -> ApiResult<bool> {
+    let result = sqlx::query("DELETE FROM user_data.item_meta WHERE sha256 = ? AND key = ?")
+        .bind(sha256)
+        .bind(key)
+        .execute(conn)
+        .await
+        .map_err(internal("Failed to delete item metadata"))?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Removes every field on the item.
+pub(crate) async fn delete_all_item_meta(
+    conn: &mut sqlx::SqliteConnection,
+    sha256: &str,
+) -> ApiResult<u64> {
+    let result = sqlx::query("DELETE FROM user_data.item_meta WHERE sha256 = ?")
+        .bind(sha256)
+        .execute(conn)
+        .await
+        .map_err(internal("Failed to delete item metadata"))?;
+    Ok(result.rows_affected())
+}
+
+/// Keys in use on items of the current index, most common first. `prefix`
+/// matches case-insensitively.
+pub(crate) async fn list_meta_keys(
+    conn: &mut sqlx::SqliteConnection,
+    prefix: Option<&str>,
+    limit: i64,
+) -> ApiResult<Vec<MetaKeyCount>> {
+    let pattern = prefix.map(|prefix| {
+        let escaped = prefix
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_");
+        format!("{}%", escaped.to_lowercase())
+    });
+    let rows = sqlx::query(
+        r#"
+        SELECT meta.key AS key, COUNT(*) AS items
+        FROM user_data.item_meta AS meta
+        WHERE (?1 IS NULL OR lower(meta.key) LIKE ?1 ESCAPE '\')
+            AND EXISTS (SELECT 1 FROM items WHERE items.sha256 = meta.sha256)
+        GROUP BY meta.key
+        ORDER BY items DESC, meta.key
+        LIMIT ?2
+        "#,
+    )
+    .bind(pattern)
+    .bind(limit)
+    .fetch_all(conn)
+    .await
+    .map_err(internal("Failed to list metadata keys"))?;
+
+    rows.iter()
+        .map(|row| {
+            Ok(MetaKeyCount {
+                key: row.try_get("key")?,
+                items: row.try_get("items")?,
+            })
+        })
+        .collect::<Result<Vec<_>, sqlx::Error>>()
+        .map_err(internal("Failed to list metadata keys"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::migrations::setup_test_databases;
+
+    // Ensures values round-trip with their JSON type, and key counts only
+    // cover items present in the index.
+    #[tokio::test]
+    async fn meta_round_trips_and_counts_keys() {
+        let mut dbs = setup_test_databases().await;
+        sqlx::query(
+            r#"
+INSERT INTO items (id, sha256, md5, type, time_added)
+VALUES (1, 'sha_one', 'md5_one', 'image/png', '2024-01-01T00:00:00'),
+       (2, 'sha_two', 'md5_two', 'image/png', '2024-01-01T00:00:00')
+            "#,
+        )
+        .execute(&mut dbs.index_conn)
+        .await
+        .unwrap();
+
+        let conn = &mut dbs.index_conn;
+        set_item_meta(conn, "sha_one", "status", &MetaValue::String("5".into()))
+            .await
+            .unwrap();
+        set_item_meta(conn, "sha_one", "rating", &MetaValue::Int(5))
+            .await
+            .unwrap();
+        set_item_meta(conn, "sha_one", "rating", &MetaValue::Float(4.5))
+            .await
+            .unwrap();
+        set_item_meta(conn, "sha_two", "status", &MetaValue::Bool(true))
+            .await
+            .unwrap();
+        set_item_meta(conn, "sha_gone", "status", &MetaValue::Int(1))
+            .await
+            .unwrap();
+
+        let fields = list_item_meta(conn, "sha_one").await.unwrap();
+        let fields = fields
+            .into_iter()
+            .map(|record| (record.key, record.value))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fields,
+            vec![
+                ("rating".to_string(), MetaValue::Float(4.5)),
+                ("status".to_string(), MetaValue::String("5".to_string())),
+            ]
+        );
+
+        let keys = list_meta_keys(conn, None, 10).await.unwrap();
+        let keys = keys
+            .into_iter()
+            .map(|key| (key.key, key.items))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            keys,
+            vec![("status".to_string(), 2), ("rating".to_string(), 1)]
+        );
+        let keys = list_meta_keys(conn, Some("RAT"), 10).await.unwrap();
+        assert_eq!(keys.len(), 1);
+
+        assert!(delete_item_meta(conn, "sha_one", "rating").await.unwrap());
+        assert!(!delete_item_meta(conn, "sha_one", "rating").await.unwrap());
+        assert_eq!(delete_all_item_meta(conn, "sha_one").await.unwrap(), 1);
+    }
+}
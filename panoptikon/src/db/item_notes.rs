@@ -0,0 +1,151 @@
+use sqlx::Row;
+
+use crate::api_error::ApiError;
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+/// A user's note on one item.
+pub(crate) struct ItemNoteRecord {
+    pub sha256: String,
+    pub note: String,
+    pub time_updated: String,
+}
+
+fn internal(context: &'static str) -> impl FnOnce(sqlx::Error) -> ApiError {
+    move |err| {
+        tracing::error!(error = %err, context, "item notes query failed");
+        ApiError::internal(context)
+    }
+}
+
+fn map_record(row: &sqlx::sqlite::SqliteRow) -> Result<ItemNoteRecord, sqlx::Error> {
+    Ok(ItemNoteRecord {
+        sha256: row.try_get("sha256")?,
+        note: row.try_get("note")?,
+        time_updated: row.try_get("time_updated")?,
+    })
+}
+
+pub(crate) async fn get_item_note(
+    conn: &mut sqlx::SqliteConnection,
+    user: &str,
+    sha256: &str,
+) -> ApiResult<Option<ItemNoteRecord>> {
+    let row = sqlx::query(
+        r#"
+        SELECT sha256, note, time_updated
+        FROM user_data.item_notes
+        WHERE user = ? AND sha256 = ?
+        "#,
+    )
+    .bind(user)
+    .bind(sha256)
+    .fetch_optional(conn)
+    .await
+    .map_err(internal("Failed to load item note"))?;
+
+    row.as_ref()
+        .map(map_record)
+        .transpose()
+        .map_err(internal("Failed to load item note"))
+}
+
+/// All of the user's notes, most recently updated first.
+pub(crate) async fn list_item_notes(
+    conn: &mut sqlx::SqliteConnection,
+    user: &str,
+) -> ApiResult<Vec<ItemNoteRecord>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT sha256, note, time_updated
+        FROM user_data.item_notes
+        WHERE user = ?
+        ORDER BY time_updated DESC, sha256
+        "#,
+    )
+    .bind(user)
+    .fetch_all(conn)
+    .await
+    .map_err(internal("Failed to list item notes"))?;
+
+    rows.iter()
+        .map(map_record)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(internal("Failed to list item notes"))
+}
+
+/// Creates or replaces the user's note on the item.
+pub(crate) async fn set_item_note(
+    conn: &mut sqlx::SqliteConnection,
+    user: &str,
+    sha256: &str,
+    note: &str,
+) -> ApiResult<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO user_data.item_notes (user, sha256, note, time_updated)
+        VALUES (?, ?, ?, strftime('%Y-%m-%dT%H:%M:%f','now','localtime'))
+        ON CONFLICT (user, sha256) DO UPDATE SET
+            note = excluded.note,
+            time_updated = excluded.time_updated
+        "#,
+    )
+    .bind(user)
+    .bind(sha256)
+    .bind(note)
+    .execute(conn)
+    .await
+    .map_err(internal("Failed to save item note"))?;
+    Ok(())
+}
+
+/// Inserts the note only if the user has none on the item yet; false when
+/// one already exists.
+pub(crate) async fn insert_item_note(
+    conn: &mut sqlx::SqliteConnection,
+    user: &str,
+    sha256: &str,
+    note: &str,
+) -> ApiResult<bool> {
+    let result = sqlx::query(
+        r#"
+        INSERT INTO user_data.item_notes (user, sha256, note, time_updated)
+        VALUES (?, ?, ?, strftime('%Y-%m-%dT%H:%M:%f','now','localtime'))
+        ON CONFLICT (user, sha256) DO NOTHING
+        "#,
+    )
+    .bind(user)
+    .bind(sha256)
+    .bind(note)
+    .execute(conn)
+    .await
+    .map_err(internal("Failed to save item note"))?;
+    Ok(result.rows_affected() > 0)
+}
+
+pub(crate) async fn delete_item_note(
+    conn: &mut sqlx::SqliteConnection,
+    user: &str,
+    sha256: &str,
+) -> ApiResult<bool> {
+    let result = sqlx::query("DELETE FROM user_data.item_notes WHERE user = ? AND sha256 = ?")
+        .bind(user)
+        .bind(sha256)
+        .execute(conn)
+        .await
+        .map_err(internal("Failed to delete item note"))?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Removes the item's notes for every user.
+pub(crate) async fn delete_all_item_notes(
+    conn: &mut sqlx::SqliteConnection,
+    sha256: &str,
+) -> ApiResult<u64> {
+    let result = sqlx::query("DELETE FROM user_data.item_notes WHERE sha256 = ?")
+        .bind(sha256)
+        .execute(conn)
+        .await
+        .map_err(internal("Failed to delete item notes"))?;
+    Ok(result.rows_affected())
+}
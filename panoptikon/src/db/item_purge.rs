@@ -0,0 +1,295 @@
+//! Purging a single item from the index: the item row, its file rows and
+//! everything derived from it, in one transaction. The file on disk is never
+//! touched; if it is still under an included folder, the next scan indexes
+//! it again as a new item. Bookmarks live in the user data DB and are purged
+//! separately by the caller. Bit-rot verification results are kept, like
+//! they are across rescans. `stale_items` selects the items whose files
+//! have all been missing from disk for a given number of days.
+
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::api_error::ApiError;
+use crate::db::extraction_write::delete_orphan_tags;
+use crate::db::storage::{VisualTable, delete_item_visuals};
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+/// Rows removed per table.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, ToSchema)]
+pub(crate) struct ItemPurgeCounts {
+    pub items: u64,
+    pub files: u64,
+    pub item_data: u64,
+    pub extracted_text: u64,
+    pub embeddings: u64,
+    pub tags_items: u64,
+    /// Tags left without any item by this purge.
+    pub orphan_tags: u64,
+    pub thumbnails: u64,
+    pub frames: u64,
+}
+
+impl ItemPurgeCounts {
+    pub fn add(&mut self, other: &ItemPurgeCounts) {
+        self.items += other.items;
+        self.files += other.files;
+        self.item_data += other.item_data;
+        self.extracted_text += other.extracted_text;
+        self.embeddings += other.embeddings;
+        self.tags_items += other.tags_items;
+        self.orphan_tags += other.orphan_tags;
+        self.thumbnails += other.thumbnails;
+        self.frames += other.frames;
+    }
+}
+
+pub(crate) struct ItemPurge {
+    pub counts: ItemPurgeCounts,
+    /// File-backed visuals to remove once the transaction commits.
+    pub stale_files: Vec<PathBuf>,
+}
+
+fn internal(context: &'static str) -> impl Fn(sqlx::Error) -> ApiError {
+    move |err| {
+        tracing::error!(error = %err, context, "item purge failed");
+        ApiError::internal(context)
+    }
+}
+
+/// Rows of `table` (keyed by item_data id) that belong to the item, counted
+/// before the cascade removes them.
+async fn count_derived(
+    conn: &mut sqlx::SqliteConnection,
+    item_id: i64,
+    table: &str,
+    id_column: &str,
+) -> ApiResult<u64> {
+    let sql = format!(
+        "SELECT COUNT(*) FROM {table} \
+         JOIN item_data ON item_data.id = {table}.{id_column} \
+         WHERE item_data.item_id = ?1"
+    );
+    let count: i64 = sqlx::query_scalar(sqlx::AssertSqlSafe(sql))
+        .bind(item_id)
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(internal("Failed to count item data"))?;
+    Ok(count as u64)
+}
+
+/// Deletes the item with `sha256` and all its derived data. Fails with 404
+/// if no such item exists.
+pub(crate) async fn purge_item(
+    conn: &mut sqlx::SqliteConnection,
+    dir: &Path,
+    sha256: &str,
+) -> ApiResult<ItemPurge> {
+    let item_id: Option<i64> = sqlx::query_scalar("SELECT id FROM items WHERE sha256 = ?1")
+        .bind(sha256)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(internal("Failed to look up item"))?;
+    let Some(item_id) = item_id else {
+        return Err(ApiError::not_found("Item not found"));
+    };
+
+    let mut counts = ItemPurgeCounts {
+        extracted_text: count_derived(conn, item_id, "extracted_text", "id").await?,
+        embeddings: count_derived(conn, item_id, "embeddings", "id").await?,
+        tags_items: count_derived(conn, item_id, "tags_items", "item_data_id").await?,
+        ..Default::default()
+    };
+
+    // files.item_id has no cascade, so the files go first; their delete
+    // trigger keeps the path index in sync.
+    counts.files = sqlx::query("DELETE FROM files WHERE item_id = ?1")
+        .bind(item_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(internal("Failed to delete item files"))?
+        .rows_affected();
+    counts.item_data = sqlx::query("DELETE FROM item_data WHERE item_id = ?1")
+        .bind(item_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(internal("Failed to delete item data"))?
+        .rows_affected();
+    counts.items = sqlx::query("DELETE FROM items WHERE id = ?1")
+        .bind(item_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(internal("Failed to delete item"))?
+        .rows_affected();
+    counts.orphan_tags = delete_orphan_tags(conn).await?;
+
+    let thumbnails = delete_item_visuals(conn, dir, VisualTable::Thumbnails, sha256).await?;
+    let frames = delete_item_visuals(conn, dir, VisualTable::Frames, sha256).await?;
+    counts.thumbnails = thumbnails.rows;
+    counts.frames = frames.rows;
+    let mut stale_files = thumbnails.stale_files;
+    stale_files.extend(frames.stale_files);
+
+    Ok(ItemPurge {
+        counts,
+        stale_files,
+    })
+}
+
+/// The sha256 of each item none of whose files is available and all of
+/// whose files a scan last found more than `older_than_days` days ago, the
+/// longest gone first. A file without `last_seen` (written outside the
+/// scanner) keeps its item.
+pub(crate) async fn stale_items(
+    conn: &mut sqlx::SqliteConnection,
+    older_than_days: u32,
+) -> ApiResult<Vec<String>> {
+    sqlx::query_scalar(
+        r#"
+SELECT items.sha256
+FROM items
+JOIN files ON files.item_id = items.id
+GROUP BY items.id
+HAVING MAX(files.available) = 0
+AND COUNT(files.last_seen) = COUNT(*)
+AND MAX(files.last_seen) < strftime('%Y-%m-%dT%H:%M:%S', 'now', 'localtime', ?1)
+ORDER BY MAX(files.last_seen), items.id
+        "#,
+    )
+    .bind(format!("-{older_than_days} days"))
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(internal("Failed to select stale items"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::migrations::setup_test_databases;
+
+    async fn count(conn: &mut sqlx::SqliteConnection, sql: &str) -> i64 {
+        sqlx::query_scalar(sqlx::AssertSqlSafe(sql))
+            .fetch_one(conn)
+            .await
+            .unwrap()
+    }
+
+    // Item 1 has two files, text, an embedding, a tag shared with item 2, a
+    // tag of its own and a file-backed thumbnail. Purging it removes all of
+    // that and leaves item 2 and the shared tag.
+    #[tokio::test]
+    async fn purge_removes_the_item_and_everything_derived_from_it() {
+        let mut dbs = setup_test_databases().await;
+        let conn = &mut dbs.index_conn;
+        sqlx::query(
+            r#"
+INSERT INTO file_scans (id, start_time, path) VALUES (1, '2024-01-01T00:00:00', '/');
+INSERT INTO items (id, sha256, md5, type, time_added) VALUES
+    (1, 'sha1', 'md51', 'image/png', '2024-01-01T00:00:00'),
+    (2, 'sha2', 'md52', 'image/png', '2024-01-01T00:00:00');
+INSERT INTO files (id, sha256, item_id, path, filename, last_modified, scan_id, available) VALUES
+    (1, 'sha1', 1, '/photos/one.png', 'one.png', '2024-01-01T00:00:00', 1, 1),
+    (2, 'sha1', 1, '/backup/one.png', 'one.png', '2024-01-01T00:00:00', 1, 1),
+    (3, 'sha2', 2, '/photos/two.png', 'two.png', '2024-01-01T00:00:00', 1, 1);
+INSERT INTO setters (id, name) VALUES (1, 'model');
+INSERT INTO item_data (id, item_id, setter_id, data_type, idx, is_origin) VALUES
+    (1, 1, 1, 'text', 0, 1),
+    (2, 1, 1, 'clip', 0, 1),
+    (3, 1, 1, 'tags', 0, 1),
+    (4, 2, 1, 'tags', 0, 1);
+INSERT INTO extracted_text (id, text, text_length) VALUES (1, 'hello purge', 11);
+INSERT INTO embeddings (id, embedding) VALUES (2, x'00000000');
+INSERT INTO tags (id, namespace, name) VALUES (1, 'general', 'shared'), (2, 'general', 'own');
+INSERT INTO tags_items (item_data_id, tag_id) VALUES (3, 1), (3, 2), (4, 1);
+INSERT INTO storage.thumbnails (item_sha256, idx, item_mime_type, width, height, version, thumbnail)
+VALUES ('sha1', 0, 'image/png', 1, 1, 1, x''), ('sha2', 0, 'image/png', 1, 1, 1, x'01');
+            "#,
+        )
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+
+        let dir = Path::new("/visuals");
+        let purge = purge_item(conn, dir, "sha1").await.unwrap();
+        assert_eq!(
+            purge.counts,
+            ItemPurgeCounts {
+                items: 1,
+                files: 2,
+                item_data: 3,
+                extracted_text: 1,
+                embeddings: 1,
+                tags_items: 2,
+                orphan_tags: 1,
+                thumbnails: 1,
+                frames: 0,
+            }
+        );
+        assert_eq!(
+            purge.stale_files,
+            vec![VisualTable::Thumbnails.file_path(dir, "sha1", 0)]
+        );
+
+        assert_eq!(count(conn, "SELECT COUNT(*) FROM items").await, 1);
+        assert_eq!(count(conn, "SELECT COUNT(*) FROM files").await, 1);
+        assert_eq!(count(conn, "SELECT COUNT(*) FROM tags").await, 1);
+        assert_eq!(
+            count(conn, "SELECT COUNT(*) FROM storage.thumbnails").await,
+            1
+        );
+        assert_eq!(
+            count(
+                conn,
+                "SELECT COUNT(*) FROM files_path_fts WHERE files_path_fts MATCH 'backup'"
+            )
+            .await,
+            0
+        );
+        assert_eq!(
+            count(
+                conn,
+                "SELECT COUNT(*) FROM extracted_text_fts WHERE extracted_text_fts MATCH 'purge'"
+            )
+            .await,
+            0
+        );
+
+        let missing = purge_item(conn, dir, "sha1").await;
+        assert!(missing.is_err());
+    }
+
+    // Only items whose every file is unavailable and was last seen before the
+    // cutoff are stale; one recent or available file keeps the item.
+    #[tokio::test]
+    async fn stale_items_require_every_file_gone_past_the_cutoff() {
+        let mut dbs = setup_test_databases().await;
+        let conn = &mut dbs.index_conn;
+        sqlx::query(
+            r#"
+INSERT INTO file_scans (id, start_time, path) VALUES (1, '2024-01-01T00:00:00', '/');
+INSERT INTO items (id, sha256, md5, type, time_added) VALUES
+    (1, 'gone', 'md51', 'image/png', '2024-01-01T00:00:00'),
+    (2, 'recent_copy', 'md52', 'image/png', '2024-01-01T00:00:00'),
+    (3, 'available', 'md53', 'image/png', '2024-01-01T00:00:00'),
+    (4, 'older', 'md54', 'image/png', '2024-01-01T00:00:00'),
+    (5, 'unknown', 'md55', 'image/png', '2024-01-01T00:00:00');
+INSERT INTO files (id, sha256, item_id, path, filename, last_modified, scan_id, available, last_seen) VALUES
+    (1, 'gone', 1, '/a/one.png', 'one.png', '2024-01-01T00:00:00', 1, 0, '2024-03-01T00:00:00'),
+    (2, 'gone', 1, '/b/one.png', 'one.png', '2024-01-01T00:00:00', 1, 0, '2024-02-01T00:00:00'),
+    (3, 'recent_copy', 2, '/a/two.png', 'two.png', '2024-01-01T00:00:00', 1, 0, '2024-01-01T00:00:00'),
+    (4, 'recent_copy', 2, '/b/two.png', 'two.png', '2024-01-01T00:00:00', 1, 0, strftime('%Y-%m-%dT%H:%M:%S', 'now', 'localtime')),
+    (5, 'available', 3, '/a/three.png', 'three.png', '2024-01-01T00:00:00', 1, 1, '2024-01-01T00:00:00'),
+    (6, 'older', 4, '/a/four.png', 'four.png', '2024-01-01T00:00:00', 1, 0, '2023-01-01T00:00:00'),
+    (7, 'unknown', 5, '/a/five.png', 'five.png', '2024-01-01T00:00:00', 1, 0, NULL);
+            "#,
+        )
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+
+        let stale = stale_items(conn, 30).await.unwrap();
+        assert_eq!(stale, vec!["older".to_string(), "gone".to_string()]);
+    }
+}
@@ -1,9 +1,14 @@
 use serde::{Deserialize, Serialize};
 use sqlx::Row;
-use std::{collections::HashMap, path::PathBuf, time::Duration};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+    time::Duration,
+};
 use utoipa::ToSchema;
 
 use crate::api_error::ApiError;
+use crate::db::raw_paths::disk_path;
 
 type ApiResult<T> = std::result::Result<T, ApiError>;
 
@@ -32,14 +37,31 @@ pub(crate) struct ItemRecord {
     pub subtitle_tracks: Option<i64>,
     pub blurhash: Option<String>,
     pub time_added: String,
+    /// The file pinned as the item's primary file, if any.
+    pub primary_file_id: Option<i64>,
 }
 
 pub(crate) struct FileRecord {
     pub id: i64,
     pub sha256: String,
     pub path: String,
+    /// Raw bytes of a path that is not UTF-8 (see `db::raw_paths`).
+    pub path_bytes: Option<Vec<u8>>,
     pub last_modified: String,
     pub filename: String,
+    /// When this path was first indexed; None only for rows written outside
+    /// the scanner.
+    pub time_added: Option<String>,
+    /// When a scan last found this file on disk; None only for rows written
+    /// outside the scanner.
+    pub last_seen: Option<String>,
+}
+
+impl FileRecord {
+    /// The path to open the file by.
+    pub(crate) fn disk_path(&self) -> PathBuf {
+        disk_path(&self.path, self.path_bytes.as_deref())
+    }
 }
 
 pub(crate) struct ItemMetadata {
@@ -88,7 +110,7 @@ pub(crate) async fn get_item_metadata(
     let check = tokio::task::spawn_blocking(move || {
         files
             .into_iter()
-            .filter(|file| PathBuf::from(&file.path).exists())
+            .filter(|file| file.disk_path().exists())
             .collect()
     });
     metadata.files = match tokio::time::timeout(EXISTENCE_CHECK_TIMEOUT, check).await {
@@ -106,7 +128,8 @@ pub(crate) async fn get_item_metadata(
 }
 
 /// Item metadata without touching the filesystem: the file list is returned
-/// as recorded in the database (ordered by `available` DESC). File-serving
+/// as recorded in the database (the pinned primary file first, then ordered
+/// by `available` DESC). File-serving
 /// endpoints use this and discover missing files at open() instead of paying
 /// a per-request stat.
 pub(crate) async fn get_item_metadata_unchecked(
@@ -132,9 +155,14 @@ pub(crate) async fn get_item_metadata_unchecked(
         files.id AS file_id,
         files.path AS path,
         files.filename AS filename,
-        files.last_modified AS last_modified
+        files.last_modified AS last_modified,
+        files.time_added AS file_time_added,
+        files.last_seen AS last_seen,
+        files.path_bytes AS path_bytes,
+        primary_file.file_id AS primary_file_id
     FROM items
         JOIN files ON items.id = files.item_id
+        LEFT JOIN item_primary_files AS primary_file ON primary_file.item_id = items.id
     "#;
 
     let (query, value) = match identifier_type {
@@ -142,7 +170,7 @@ pub(crate) async fn get_item_metadata_unchecked(
             format!(
                 "{select}
         WHERE items.sha256 LIKE ? || '%'
-        ORDER BY files.available DESC
+        ORDER BY files.id IS primary_file.file_id DESC, files.available DESC
         "
             ),
             identifier,
@@ -152,7 +180,7 @@ pub(crate) async fn get_item_metadata_unchecked(
                 "{select}
         JOIN item_data ON items.id = item_data.item_id
         WHERE item_data.id = ?
-        ORDER BY files.available DESC
+        ORDER BY files.id IS primary_file.file_id DESC, files.available DESC
         "
             ),
             identifier,
@@ -160,17 +188,17 @@ pub(crate) async fn get_item_metadata_unchecked(
         _ => {
             let column = match identifier_type {
                 ItemIdentifierType::Sha256 => "items.sha256",
-                ItemIdentifierType::ItemId => "item_id",
-                ItemIdentifierType::FileId => "file_id",
+                ItemIdentifierType::ItemId => "items.id",
+                ItemIdentifierType::FileId => "files.id",
                 ItemIdentifierType::DataId => "data_id",
-                ItemIdentifierType::Path => "path",
-                ItemIdentifierType::Md5 => "md5",
+                ItemIdentifierType::Path => "files.path",
+                ItemIdentifierType::Md5 => "items.md5",
             };
             (
                 format!(
                     "{select}
         WHERE {column} = ?
-        ORDER BY files.available DESC
+        ORDER BY files.id IS primary_file.file_id DESC, files.available DESC
         "
                 ),
                 identifier,
@@ -251,6 +279,10 @@ pub(crate) async fn get_item_metadata_unchecked(
             tracing::error!(error = %err, "failed to read path");
             ApiError::internal("Failed to get item")
         })?;
+        let path_bytes: Option<Vec<u8>> = row.try_get("path_bytes").map_err(|err| {
+            tracing::error!(error = %err, "failed to read path bytes");
+            ApiError::internal("Failed to get item")
+        })?;
         let filename: String = row.try_get("filename").map_err(|err| {
             tracing::error!(error = %err, "failed to read filename");
             ApiError::internal("Failed to get item")
@@ -259,6 +291,18 @@ pub(crate) async fn get_item_metadata_unchecked(
             tracing::error!(error = %err, "failed to read last modified");
             ApiError::internal("Failed to get item")
         })?;
+        let file_time_added: Option<String> = row.try_get("file_time_added").map_err(|err| {
+            tracing::error!(error = %err, "failed to read file time_added");
+            ApiError::internal("Failed to get item")
+        })?;
+        let last_seen: Option<String> = row.try_get("last_seen").map_err(|err| {
+            tracing::error!(error = %err, "failed to read file last_seen");
+            ApiError::internal("Failed to get item")
+        })?;
+        let primary_file_id: Option<i64> = row.try_get("primary_file_id").map_err(|err| {
+            tracing::error!(error = %err, "failed to read primary file id");
+            ApiError::internal("Failed to get item")
+        })?;
 
         if item_record.is_none() {
             item_record = Some(ItemRecord {
@@ -275,6 +319,7 @@ pub(crate) async fn get_item_metadata_unchecked(
                 subtitle_tracks,
                 blurhash,
                 time_added,
+                primary_file_id,
             });
         }
 
@@ -282,8 +327,11 @@ pub(crate) async fn get_item_metadata_unchecked(
             id: file_id,
             sha256,
             path,
+            path_bytes,
             last_modified,
             filename,
+            time_added: file_time_added,
+            last_seen,
         });
     }
 
@@ -299,10 +347,11 @@ pub(crate) async fn get_existing_file_for_item_id(
 ) -> ApiResult<Option<FileRecord>> {
     let rows = sqlx::query(
         r#"
-        SELECT id, sha256, path, last_modified, filename
+        SELECT files.id, sha256, path, path_bytes, last_modified, filename, time_added, last_seen
         FROM files
-        WHERE item_id = ?
-        ORDER BY available DESC
+            LEFT JOIN item_primary_files AS primary_file ON primary_file.file_id = files.id
+        WHERE files.item_id = ?
+        ORDER BY primary_file.file_id IS NOT NULL DESC, available DESC
         "#,
     )
     .bind(item_id)
@@ -318,7 +367,11 @@ pub(crate) async fn get_existing_file_for_item_id(
             tracing::error!(error = %err, "failed to read file path");
             ApiError::internal("Failed to read file metadata")
         })?;
-        if PathBuf::from(&path).exists() {
+        let path_bytes: Option<Vec<u8>> = row.try_get("path_bytes").map_err(|err| {
+            tracing::error!(error = %err, "failed to read file path bytes");
+            ApiError::internal("Failed to read file metadata")
+        })?;
+        if disk_path(&path, path_bytes.as_deref()).exists() {
             let id: i64 = row.try_get("id").map_err(|err| {
                 tracing::error!(error = %err, "failed to read file id");
                 ApiError::internal("Failed to read file metadata")
@@ -335,12 +388,23 @@ pub(crate) async fn get_existing_file_for_item_id(
                 tracing::error!(error = %err, "failed to read file filename");
                 ApiError::internal("Failed to read file metadata")
             })?;
+            let time_added: Option<String> = row.try_get("time_added").map_err(|err| {
+                tracing::error!(error = %err, "failed to read file time_added");
+                ApiError::internal("Failed to read file metadata")
+            })?;
+            let last_seen: Option<String> = row.try_get("last_seen").map_err(|err| {
+                tracing::error!(error = %err, "failed to read file last_seen");
+                ApiError::internal("Failed to read file metadata")
+            })?;
             return Ok(Some(FileRecord {
                 id,
                 sha256,
                 path,
+                path_bytes,
                 last_modified,
                 filename,
+                time_added,
+                last_seen,
             }));
         }
     }
@@ -362,7 +426,7 @@ pub(crate) async fn get_existing_files_for_sha256(
     };
     let rows = sqlx::query(sqlx::AssertSqlSafe(format!(
         r#"
-        SELECT id, sha256, path, last_modified, filename
+        SELECT id, sha256, path, path_bytes, last_modified, filename, time_added, last_seen
         FROM files
         {where_clause}
         ORDER BY available DESC
@@ -382,7 +446,11 @@ pub(crate) async fn get_existing_files_for_sha256(
             tracing::error!(error = %err, "failed to read file path");
             ApiError::internal("Failed to read file metadata")
         })?;
-        if PathBuf::from(&path).exists() {
+        let path_bytes: Option<Vec<u8>> = row.try_get("path_bytes").map_err(|err| {
+            tracing::error!(error = %err, "failed to read file path bytes");
+            ApiError::internal("Failed to read file metadata")
+        })?;
+        if disk_path(&path, path_bytes.as_deref()).exists() {
             let id: i64 = row.try_get("id").map_err(|err| {
                 tracing::error!(error = %err, "failed to read file id");
                 ApiError::internal("Failed to read file metadata")
@@ -399,12 +467,23 @@ pub(crate) async fn get_existing_files_for_sha256(
                 tracing::error!(error = %err, "failed to read file filename");
                 ApiError::internal("Failed to read file metadata")
             })?;
+            let time_added: Option<String> = row.try_get("time_added").map_err(|err| {
+                tracing::error!(error = %err, "failed to read file time_added");
+                ApiError::internal("Failed to read file metadata")
+            })?;
+            let last_seen: Option<String> = row.try_get("last_seen").map_err(|err| {
+                tracing::error!(error = %err, "failed to read file last_seen");
+                ApiError::internal("Failed to read file metadata")
+            })?;
             files.push(FileRecord {
                 id,
                 sha256,
                 path,
+                path_bytes,
                 last_modified,
                 filename,
+                time_added,
+                last_seen,
             });
         }
     }
@@ -643,9 +722,58 @@ pub(crate) async fn get_all_tags_for_item(
     namespaces: &[String],
     limit_per_namespace: Option<usize>,
 ) -> ApiResult<Vec<(String, String, f64, String)>> {
+    let tags = query_item_tags(conn, item_id, setters, confidence_threshold, namespaces, false)
+        .await?
+        .into_iter()
+        .map(|(_, tag)| tag)
+        .collect();
+    if let Some(limit) = limit_per_namespace {
+        Ok(limit_tags_by_namespace(tags, limit))
+    } else {
+        Ok(tags)
+    }
+}
+
+/// Tags of individual video frames, for models that store them, as
+/// (frame index, tags) in frame order. Filters and the per-namespace limit
+/// apply to each frame on its own.
+pub(crate) async fn get_frame_tags_for_item(
+    conn: &mut sqlx::SqliteConnection,
+    item_id: i64,
+    setters: &[String],
+    confidence_threshold: f64,
+    namespaces: &[String],
+    limit_per_namespace: Option<usize>,
+) -> ApiResult<Vec<(i64, Vec<(String, String, f64, String)>)>> {
+    let rows =
+        query_item_tags(conn, item_id, setters, confidence_threshold, namespaces, true).await?;
+    let mut frames: BTreeMap<i64, Vec<_>> = BTreeMap::new();
+    for (idx, tag) in rows {
+        frames.entry(idx - 1).or_default().push(tag);
+    }
+    Ok(frames
+        .into_iter()
+        .map(|(frame, tags)| match limit_per_namespace {
+            Some(limit) => (frame, limit_tags_by_namespace(tags, limit)),
+            None => (frame, tags),
+        })
+        .collect())
+}
+
+/// An item's tags with the idx of the `item_data` row holding them: the
+/// merged rows (idx 0), or with `frames` the per-frame rows (idx >= 1).
+async fn query_item_tags(
+    conn: &mut sqlx::SqliteConnection,
+    item_id: i64,
+    setters: &[String],
+    confidence_threshold: f64,
+    namespaces: &[String],
+    frames: bool,
+) -> ApiResult<Vec<(i64, (String, String, f64, String))>> {
     let mut sql = String::from(
         r#"
-        SELECT tags.namespace, tags.name, tags_items.confidence, setters.name AS setter_name
+        SELECT item_data.idx, tags.namespace, tags.name, tags_items.confidence,
+            setters.name AS setter_name
         FROM item_data
         JOIN tags_items
             ON tags_items.item_data_id = item_data.id
@@ -656,6 +784,11 @@ pub(crate) async fn get_all_tags_for_item(
         WHERE item_data.item_id = ?
         "#,
     );
+    sql.push_str(if frames {
+        " AND item_data.idx > 0"
+    } else {
+        " AND item_data.idx = 0"
+    });
 
     if !setters.is_empty() {
         let placeholders = std::iter::repeat("?")
@@ -697,6 +830,10 @@ pub(crate) async fn get_all_tags_for_item(
 
     let mut tags = Vec::with_capacity(rows.len());
     for row in rows {
+        let idx: i64 = row.try_get("idx").map_err(|err| {
+            tracing::error!(error = %err, "failed to read tag data index");
+            ApiError::internal("Failed to get tags")
+        })?;
         let namespace: String = row.try_get("namespace").map_err(|err| {
             tracing::error!(error = %err, "failed to read tag namespace");
             ApiError::internal("Failed to get tags")
@@ -713,14 +850,9 @@ pub(crate) async fn get_all_tags_for_item(
             tracing::error!(error = %err, "failed to read setter name");
             ApiError::internal("Failed to get tags")
         })?;
-        tags.push((namespace, name, confidence, setter_name));
-    }
-
-    if let Some(limit) = limit_per_namespace {
-        Ok(limit_tags_by_namespace(tags, limit))
-    } else {
-        Ok(tags)
+        tags.push((idx, (namespace, name, confidence, setter_name)));
     }
+    Ok(tags)
 }
 
 fn limit_tags_by_namespace(
@@ -829,35 +961,73 @@ pub(crate) async fn get_file_stats(conn: &mut sqlx::SqliteConnection) -> ApiResu
     Ok((total_files, total_items))
 }
 
-pub(crate) async fn get_thumbnail_bytes(
+/// An available file of an item whose video frames could not be decoded.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub(crate) struct UndecodableFile {
+    pub item_id: i64,
+    pub file_id: i64,
+    pub sha256: String,
+    pub mime_type: String,
+    pub path: String,
+    pub last_modified: String,
+}
+
+/// A page of the available files of items marked `undecodable`, ordered by
+/// path, and the total number of such files.
+pub(crate) async fn get_undecodable_files(
     conn: &mut sqlx::SqliteConnection,
-    sha256: &str,
-    idx: i64,
-) -> ApiResult<Option<Vec<u8>>> {
-    let row = sqlx::query(
+    page: i64,
+    page_size: i64,
+) -> ApiResult<(i64, Vec<UndecodableFile>)> {
+    let internal = |err: sqlx::Error| {
+        tracing::error!(error = %err, "failed to list undecodable files");
+        ApiError::internal("Failed to list undecodable files")
+    };
+    let (total,): (i64,) = sqlx::query_as(
         r#"
-        SELECT thumbnail
-        FROM thumbnails
-        WHERE item_sha256 = ? AND idx = ?
+        SELECT COUNT(*)
+        FROM files
+        JOIN items ON items.id = files.item_id
+        WHERE items.undecodable = 1 AND files.available = 1
         "#,
     )
-    .bind(sha256)
-    .bind(idx)
-    .fetch_optional(conn)
+    .fetch_one(&mut *conn)
     .await
-    .map_err(|err| {
-        tracing::error!(error = %err, "failed to read thumbnail bytes");
-        ApiError::internal("Failed to load thumbnail")
-    })?;
-
-    let Some(row) = row else {
-        return Ok(None);
-    };
-    let bytes: Vec<u8> = row.try_get("thumbnail").map_err(|err| {
-        tracing::error!(error = %err, "failed to parse thumbnail bytes");
-        ApiError::internal("Failed to load thumbnail")
-    })?;
-    Ok(Some(bytes))
+    .map_err(internal)?;
+    let offset = page.saturating_sub(1).saturating_mul(page_size);
+    let rows = sqlx::query(
+        r#"
+        SELECT
+            items.id AS item_id, files.id AS file_id, items.sha256 AS sha256,
+            items.type AS mime_type, files.path AS path,
+            files.last_modified AS last_modified
+        FROM files
+        JOIN items ON items.id = files.item_id
+        WHERE items.undecodable = 1 AND files.available = 1
+        ORDER BY files.path
+        LIMIT ?1 OFFSET ?2
+        "#,
+    )
+    .bind(page_size)
+    .bind(offset)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(internal)?;
+    let files = rows
+        .iter()
+        .map(|row| {
+            Ok(UndecodableFile {
+                item_id: row.try_get("item_id")?,
+                file_id: row.try_get("file_id")?,
+                sha256: row.try_get("sha256")?,
+                mime_type: row.try_get("mime_type")?,
+                path: row.try_get("path")?,
+                last_modified: row.try_get("last_modified")?,
+            })
+        })
+        .collect::<Result<Vec<_>, sqlx::Error>>()
+        .map_err(internal)?;
+    Ok((total, files))
 }
 
 #[cfg(test)]
@@ -949,6 +1119,57 @@ mod tests {
         assert_eq!(result.files[0].path, file_path.to_string_lossy());
     }
 
+    // Ensures the pinned primary file is listed first and reported on the
+    // item, also when the item is looked up by another of its files.
+    #[tokio::test]
+    async fn item_metadata_lists_pinned_file_first() {
+        let mut dbs = setup_test_databases().await;
+        insert_scan(&mut dbs.index_conn, 1, "/data").await;
+        sqlx::query(
+            "INSERT INTO items (id, sha256, md5, type, time_added) \
+             VALUES (1, 'sha256', 'md5', 'image/png', '2024-01-01T00:00:00')",
+        )
+        .execute(&mut dbs.index_conn)
+        .await
+        .unwrap();
+        sqlx::query(
+            r#"
+            INSERT INTO files (
+                id, sha256, item_id, path, filename, last_modified, scan_id, available
+            ) VALUES
+                (10, 'sha256', 1, '/data/a.png', 'a.png', '2024-01-01T00:00:00', 1, 1),
+                (11, 'sha256', 1, '/data/b.png', 'b.png', '2024-01-01T00:00:00', 1, 0)
+            "#,
+        )
+        .execute(&mut dbs.index_conn)
+        .await
+        .unwrap();
+
+        let result = get_item_metadata_unchecked(&mut dbs.index_conn, "1", ItemIdentifierType::ItemId)
+            .await
+            .unwrap();
+        assert_eq!(result.item.unwrap().primary_file_id, None);
+        assert_eq!(result.files[0].id, 10);
+
+        crate::db::files::set_primary_file(&mut dbs.index_conn, 1, Some(11))
+            .await
+            .unwrap();
+        let result = get_item_metadata_unchecked(&mut dbs.index_conn, "1", ItemIdentifierType::ItemId)
+            .await
+            .unwrap();
+        assert_eq!(result.item.unwrap().primary_file_id, Some(11));
+        let ids: Vec<i64> = result.files.iter().map(|file| file.id).collect();
+        assert_eq!(ids, [11, 10]);
+
+        let result =
+            get_item_metadata_unchecked(&mut dbs.index_conn, "10", ItemIdentifierType::FileId)
+                .await
+                .unwrap();
+        assert_eq!(result.item.unwrap().primary_file_id, Some(11));
+        assert_eq!(result.files.len(), 1);
+        assert_eq!(result.files[0].id, 10);
+    }
+
     // Ensures mime type stats include general type prefixes.
     #[tokio::test]
     async fn get_all_mime_types_includes_general_types() {
@@ -1015,6 +1236,56 @@ mod tests {
         assert_eq!(items, 2);
     }
 
+    // Ensures only available files of marked items are listed, by path, and
+    // that clearing the mark drops them.
+    #[tokio::test]
+    async fn undecodable_files_list_available_files_of_marked_items() {
+        let mut dbs = setup_test_databases().await;
+        insert_scan(&mut dbs.index_conn, 1, "/data").await;
+        sqlx::query(
+            r#"
+            INSERT INTO items (id, sha256, md5, type, time_added)
+            VALUES
+                (1, 'sha_1', 'md5_1', 'video/mpeg', '2024-01-01T00:00:00'),
+                (2, 'sha_2', 'md5_2', 'video/mp4', '2024-01-01T00:00:00');
+            INSERT INTO files (
+                id, sha256, item_id, path, filename, last_modified, scan_id, available
+            )
+            VALUES
+                (10, 'sha_1', 1, '/data/b.vob', 'b.vob', '2024-01-01T00:00:00', 1, 1),
+                (11, 'sha_1', 1, '/data/a.vob', 'a.vob', '2024-01-01T00:00:00', 1, 1),
+                (12, 'sha_1', 1, '/data/gone.vob', 'gone.vob', '2024-01-01T00:00:00', 1, 0),
+                (13, 'sha_2', 2, '/data/fine.mp4', 'fine.mp4', '2024-01-01T00:00:00', 1, 1)
+            "#,
+        )
+        .execute(&mut dbs.index_conn)
+        .await
+        .unwrap();
+        crate::db::files::set_item_undecodable(&mut dbs.index_conn, "sha_1", true)
+            .await
+            .unwrap();
+
+        let (count, files) = get_undecodable_files(&mut dbs.index_conn, 1, 1)
+            .await
+            .unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path, "/data/a.vob");
+        let (_, files) = get_undecodable_files(&mut dbs.index_conn, 2, 1)
+            .await
+            .unwrap();
+        assert_eq!(files[0].file_id, 10);
+
+        crate::db::files::set_item_undecodable(&mut dbs.index_conn, "sha_1", false)
+            .await
+            .unwrap();
+        let (count, files) = get_undecodable_files(&mut dbs.index_conn, 1, 10)
+            .await
+            .unwrap();
+        assert_eq!(count, 0);
+        assert!(files.is_empty());
+    }
+
     // Ensures text stats return available languages and minimum confidences.
     #[tokio::test]
     async fn get_text_stats_returns_languages_and_mins() {
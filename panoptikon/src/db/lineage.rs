@@ -0,0 +1,260 @@
+//! Derived data lineage: `item_data.source_id` chains whose source row is
+//! gone. The writer enforces the foreign key (deleting a source cascades to
+//! what was derived from it), but databases written without that pragma,
+//! or before setter deletion cascaded explicitly, can hold rows pointing at
+//! deleted data. Such rows count as processed for their setter while no
+//! longer matching any source, so they are reported here and repaired in
+//! batches by the lineage repair job.
+
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use utoipa::ToSchema;
+
+use crate::api_error::ApiError;
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+/// Dangling rows of one setter and data type.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub(crate) struct DanglingLineage {
+    pub setter_name: String,
+    pub data_type: String,
+    /// Rows whose `source_id` references a missing `item_data` row.
+    pub rows: i64,
+}
+
+/// What the repair does with a dangling row.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum LineageRepairMode {
+    /// Delete the row, and through the foreign key everything derived from
+    /// it.
+    #[default]
+    Delete,
+    /// Keep the row as origin data: clear `source_id` and set `is_origin`.
+    Null,
+}
+
+/// Outcome of one `repair_dangling_lineage_chunk` transaction.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct LineageRepairChunk {
+    /// Dangling rows examined; zero means none are left after the cursor.
+    pub scanned: u64,
+    /// Rows deleted or detached.
+    pub repaired: u64,
+    /// Rows `null` mode could not detach because the setter already has
+    /// origin data at the same index.
+    pub skipped: u64,
+    /// Resume after this id.
+    pub cursor: i64,
+}
+
+fn internal(context: &'static str) -> impl Fn(sqlx::Error) -> ApiError {
+    move |err| {
+        tracing::error!(error = %err, "{context}");
+        ApiError::internal(context)
+    }
+}
+
+/// Dangling rows grouped by setter and data type, largest groups first.
+pub(crate) async fn get_dangling_lineage(
+    conn: &mut sqlx::SqliteConnection,
+) -> ApiResult<Vec<DanglingLineage>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT setters.name AS setter_name, item_data.data_type AS data_type, COUNT(*) AS rows
+        FROM item_data
+        JOIN setters ON setters.id = item_data.setter_id
+        WHERE item_data.source_id IS NOT NULL
+          AND NOT EXISTS (SELECT 1 FROM item_data AS src WHERE src.id = item_data.source_id)
+        GROUP BY setters.name, item_data.data_type
+        ORDER BY rows DESC, setters.name, item_data.data_type
+        "#,
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(internal("Failed to read data lineage"))?;
+
+    rows.iter()
+        .map(|row| {
+            Ok(DanglingLineage {
+                setter_name: row.try_get("setter_name")?,
+                data_type: row.try_get("data_type")?,
+                rows: row.try_get("rows")?,
+            })
+        })
+        .collect::<Result<Vec<_>, sqlx::Error>>()
+        .map_err(internal("Failed to read data lineage"))
+}
+
+/// Repairs up to `limit` dangling rows with an id above `after_id`.
+pub(crate) async fn repair_dangling_lineage_chunk(
+    conn: &mut sqlx::SqliteConnection,
+    mode: LineageRepairMode,
+    after_id: i64,
+    limit: i64,
+) -> ApiResult<LineageRepairChunk> {
+    let ids: Vec<i64> = sqlx::query_scalar(
+        r#"
+        SELECT item_data.id
+        FROM item_data
+        WHERE item_data.id > ?
+          AND item_data.source_id IS NOT NULL
+          AND NOT EXISTS (SELECT 1 FROM item_data AS src WHERE src.id = item_data.source_id)
+        ORDER BY item_data.id
+        LIMIT ?
+        "#,
+    )
+    .bind(after_id)
+    .bind(limit)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(internal("Failed to read data lineage"))?;
+
+    let mut chunk = LineageRepairChunk {
+        scanned: ids.len() as u64,
+        cursor: ids.last().copied().unwrap_or(after_id),
+        ..Default::default()
+    };
+    // Row by row, so in `null` mode a unique conflict only skips that row.
+    for id in ids {
+        let sql = match mode {
+            LineageRepairMode::Delete => "DELETE FROM item_data WHERE id = ?",
+            LineageRepairMode::Null => {
+                "UPDATE OR IGNORE item_data SET source_id = NULL, is_origin = 1 WHERE id = ?"
+            }
+        };
+        let result = sqlx::query(sql)
+            .bind(id)
+            .execute(&mut *conn)
+            .await
+            .map_err(internal("Failed to repair data lineage"))?;
+        if result.rows_affected() > 0 {
+            chunk.repaired += 1;
+        } else {
+            chunk.skipped += 1;
+        }
+    }
+    Ok(chunk)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::migrations::setup_test_databases;
+
+    async fn count(conn: &mut sqlx::SqliteConnection, sql: &str) -> i64 {
+        sqlx::query_scalar(sqlx::AssertSqlSafe(sql))
+            .fetch_one(conn)
+            .await
+            .unwrap()
+    }
+
+    // Item 1 has OCR text (10) with an embedding (11) and a second text
+    // embedding (12) whose source is gone, plus a derived row (13) of that
+    // one. Item 2 has origin `text-embedding` data at idx 0 already, so its
+    // dangling row (21) can't become origin data.
+    async fn seed_dangling(conn: &mut sqlx::SqliteConnection) {
+        sqlx::query("PRAGMA foreign_keys = OFF")
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        sqlx::query(
+            r#"
+INSERT INTO items (id, sha256, md5, type, time_added) VALUES
+    (1, 'sha1', 'md51', 'image/png', '2024-01-01T00:00:00'),
+    (2, 'sha2', 'md52', 'image/png', '2024-01-01T00:00:00');
+INSERT INTO setters (id, name) VALUES (1, 'ocr'), (2, 'embedder'), (3, 'summary');
+INSERT INTO item_data (id, item_id, setter_id, data_type, idx, is_origin, source_id) VALUES
+    (10, 1, 1, 'text', 0, 1, NULL),
+    (11, 1, 2, 'text-embedding', 0, NULL, 10),
+    (12, 1, 2, 'text-embedding', 1, NULL, 99),
+    (13, 1, 3, 'text', 0, NULL, 12),
+    (20, 2, 2, 'text-embedding', 0, 1, NULL),
+    (21, 2, 2, 'text-embedding', 0, NULL, 98);
+            "#,
+        )
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+        sqlx::query("PRAGMA foreign_keys = ON")
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+    }
+
+    // Ensures only rows whose direct source is missing are reported.
+    #[tokio::test]
+    async fn dangling_rows_are_grouped_by_setter_and_type() {
+        let mut dbs = setup_test_databases().await;
+        let conn = &mut dbs.index_conn;
+        seed_dangling(conn).await;
+
+        let report = get_dangling_lineage(conn).await.unwrap();
+        assert_eq!(
+            report,
+            vec![DanglingLineage {
+                setter_name: "embedder".to_string(),
+                data_type: "text-embedding".to_string(),
+                rows: 2,
+            }]
+        );
+    }
+
+    // Ensures delete mode removes dangling rows with what was derived from
+    // them, across chunks.
+    #[tokio::test]
+    async fn delete_mode_removes_rows_and_their_derived_data() {
+        let mut dbs = setup_test_databases().await;
+        let conn = &mut dbs.index_conn;
+        seed_dangling(conn).await;
+
+        let first = repair_dangling_lineage_chunk(conn, LineageRepairMode::Delete, 0, 1)
+            .await
+            .unwrap();
+        assert_eq!((first.scanned, first.repaired, first.cursor), (1, 1, 12));
+        let second =
+            repair_dangling_lineage_chunk(conn, LineageRepairMode::Delete, first.cursor, 1)
+                .await
+                .unwrap();
+        assert_eq!((second.scanned, second.repaired, second.cursor), (1, 1, 21));
+        let last =
+            repair_dangling_lineage_chunk(conn, LineageRepairMode::Delete, second.cursor, 1)
+                .await
+                .unwrap();
+        assert_eq!(last.scanned, 0);
+
+        let ids: Vec<i64> = sqlx::query_scalar("SELECT id FROM item_data ORDER BY id")
+            .fetch_all(&mut *conn)
+            .await
+            .unwrap();
+        assert_eq!(ids, vec![10, 11, 20]);
+    }
+
+    // Ensures null mode turns dangling rows into origin data and skips rows
+    // that would collide with existing origin data.
+    #[tokio::test]
+    async fn null_mode_detaches_rows_and_skips_conflicts() {
+        let mut dbs = setup_test_databases().await;
+        let conn = &mut dbs.index_conn;
+        seed_dangling(conn).await;
+
+        let chunk = repair_dangling_lineage_chunk(conn, LineageRepairMode::Null, 0, 10)
+            .await
+            .unwrap();
+        assert_eq!((chunk.scanned, chunk.repaired, chunk.skipped), (2, 1, 1));
+        assert_eq!(
+            count(
+                conn,
+                "SELECT COUNT(*) FROM item_data WHERE id = 12 AND source_id IS NULL AND is_origin = 1"
+            )
+            .await,
+            1
+        );
+        assert_eq!(
+            count(conn, "SELECT COUNT(*) FROM item_data WHERE id = 13").await,
+            1
+        );
+        assert_eq!(get_dangling_lineage(conn).await.unwrap()[0].rows, 1);
+    }
+}
@@ -0,0 +1,274 @@
+//! Tags added and removed by hand. They are stored exactly like model
+//! output, as one `tags` item_data row per item under the reserved
+//! [`MANUAL_TAGS_SETTER`], so tag filters, tag search and the item tag list
+//! include them without special cases. A model's `skip_processed_items`
+//! only looks at data from its own setter, so manual tags never make a model
+//! skip an item.
+
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use utoipa::ToSchema;
+
+use crate::api_error::ApiError;
+use crate::db::extraction_write::{upsert_setter, upsert_tag};
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+/// The setter manual tags are stored under. No inference ID contains a
+/// colon before a slash, so it cannot collide with a model's setter name.
+pub(crate) const MANUAL_TAGS_SETTER: &str = "manual:user";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub(crate) struct ManualTag {
+    pub namespace: String,
+    pub name: String,
+}
+
+fn internal(context: &'static str) -> impl Fn(sqlx::Error) -> ApiError {
+    move |err| {
+        tracing::error!(error = %err, context, "manual tag query failed");
+        ApiError::internal(context)
+    }
+}
+
+/// The item's manual tags row, if it has one.
+async fn get_manual_data_id(
+    conn: &mut sqlx::SqliteConnection,
+    item_sha256: &str,
+) -> ApiResult<Option<i64>> {
+    let row = sqlx::query(
+        r#"
+SELECT item_data.id
+FROM item_data
+JOIN items ON items.id = item_data.item_id
+JOIN setters ON setters.id = item_data.setter_id
+WHERE items.sha256 = ?1
+  AND setters.name = ?2
+  AND item_data.data_type = 'tags'
+  AND item_data.idx = 0
+  AND item_data.is_origin = 1
+        "#,
+    )
+    .bind(item_sha256)
+    .bind(MANUAL_TAGS_SETTER)
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(internal("Failed to read manual tags"))?;
+    row.map(|row| row.try_get("id"))
+        .transpose()
+        .map_err(internal("Failed to read manual tags"))
+}
+
+/// Adds tags to an item under [`MANUAL_TAGS_SETTER`] with confidence 1.
+/// Tags the item already has manually are left alone; tags from models are
+/// unaffected. Returns how many tags were added.
+pub(crate) async fn add_manual_tags(
+    conn: &mut sqlx::SqliteConnection,
+    item_sha256: &str,
+    tags: &[ManualTag],
+) -> ApiResult<u64> {
+    let data_id = match get_manual_data_id(conn, item_sha256).await? {
+        Some(data_id) => data_id,
+        None => {
+            upsert_setter(conn, MANUAL_TAGS_SETTER).await?;
+            let result = sqlx::query(
+                r#"
+INSERT INTO item_data (item_id, setter_id, data_type, idx, is_origin, is_placeholder)
+SELECT items.id, setters.id, 'tags', 0, 1, 0
+FROM items
+JOIN setters ON setters.name = ?1
+WHERE items.sha256 = ?2
+                "#,
+            )
+            .bind(MANUAL_TAGS_SETTER)
+            .bind(item_sha256)
+            .execute(&mut *conn)
+            .await
+            .map_err(internal("Failed to write manual tags"))?;
+            if result.rows_affected() == 0 {
+                return Err(ApiError::not_found("Item not found"));
+            }
+            result.last_insert_rowid()
+        }
+    };
+
+    let mut added = 0;
+    for tag in tags {
+        let tag_id = upsert_tag(conn, &tag.namespace, &tag.name).await?;
+        let result = sqlx::query(
+            r#"
+INSERT INTO tags_items (item_data_id, tag_id, confidence)
+VALUES (?1, ?2, 1.0)
+ON CONFLICT(item_data_id, tag_id) DO NOTHING
+            "#,
+        )
+        .bind(data_id)
+        .bind(tag_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(internal("Failed to write manual tags"))?;
+        added += result.rows_affected();
+    }
+    Ok(added)
+}
+
+/// Removes manual tags from an item. Model tags are never touched. Tags no
+/// item has any more are deleted, and so is the item's manual tags row once
+/// it is empty. Returns how many tags were removed.
+pub(crate) async fn remove_manual_tags(
+    conn: &mut sqlx::SqliteConnection,
+    item_sha256: &str,
+    tags: &[ManualTag],
+) -> ApiResult<u64> {
+    let Some(data_id) = get_manual_data_id(conn, item_sha256).await? else {
+        return Ok(0);
+    };
+
+    let mut removed = 0;
+    for tag in tags {
+        let result = sqlx::query(
+            r#"
+DELETE FROM tags_items
+WHERE item_data_id = ?1
+  AND tag_id = (SELECT id FROM tags WHERE namespace = ?2 AND name = ?3)
+            "#,
+        )
+        .bind(data_id)
+        .bind(&tag.namespace)
+        .bind(&tag.name)
+        .execute(&mut *conn)
+        .await
+        .map_err(internal("Failed to remove manual tags"))?;
+        removed += result.rows_affected();
+
+        sqlx::query(
+            r#"
+DELETE FROM tags
+WHERE namespace = ?1
+  AND name = ?2
+  AND NOT EXISTS (SELECT 1 FROM tags_items WHERE tags_items.tag_id = tags.id)
+            "#,
+        )
+        .bind(&tag.namespace)
+        .bind(&tag.name)
+        .execute(&mut *conn)
+        .await
+        .map_err(internal("Failed to remove manual tags"))?;
+    }
+
+    sqlx::query(
+        r#"
+DELETE FROM item_data
+WHERE id = ?1
+  AND NOT EXISTS (SELECT 1 FROM tags_items WHERE tags_items.item_data_id = ?1)
+        "#,
+    )
+    .bind(data_id)
+    .execute(&mut *conn)
+    .await
+    .map_err(internal("Failed to remove manual tags"))?;
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::items::get_all_tags_for_item;
+    use crate::db::migrations::setup_test_databases;
+
+    fn tag(namespace: &str, name: &str) -> ManualTag {
+        ManualTag {
+            namespace: namespace.to_string(),
+            name: name.to_string(),
+        }
+    }
+
+    async fn count(conn: &mut sqlx::SqliteConnection, sql: &str) -> i64 {
+        sqlx::query_scalar(sqlx::AssertSqlSafe(sql))
+            .fetch_one(conn)
+            .await
+            .unwrap()
+    }
+
+    // Manual tags sit next to a model's tags for the same item: adding is
+    // idempotent, removal only touches manual tags, and tags and the manual
+    // row disappear once nothing uses them.
+    #[tokio::test]
+    async fn manual_tags_are_added_and_removed_beside_model_tags() {
+        let mut dbs = setup_test_databases().await;
+        let conn = &mut dbs.index_conn;
+        sqlx::query(
+            r#"
+INSERT INTO items (id, sha256, md5, type, time_added)
+VALUES (1, 'sha_one', 'md5_one', 'image/png', '2024-01-01T00:00:00');
+INSERT INTO setters (id, name) VALUES (1, 'tagger/model');
+INSERT INTO item_data (id, item_id, setter_id, data_type, idx, is_origin, is_placeholder)
+VALUES (1, 1, 1, 'tags', 0, 1, 0);
+INSERT INTO tags (id, namespace, name) VALUES (1, 'general', 'cat');
+INSERT INTO tags_items (item_data_id, tag_id, confidence) VALUES (1, 1, 0.5);
+            "#,
+        )
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+
+        let added = add_manual_tags(
+            conn,
+            "sha_one",
+            &[tag("general", "cat"), tag("general", "dog")],
+        )
+        .await
+        .unwrap();
+        assert_eq!(added, 2);
+        let added = add_manual_tags(conn, "sha_one", &[tag("general", "dog")])
+            .await
+            .unwrap();
+        assert_eq!(added, 0);
+        let tags = get_all_tags_for_item(conn, 1, &[], 0.0, &[], None)
+            .await
+            .unwrap();
+        assert_eq!(
+            tags,
+            vec![
+                ("general".into(), "cat".into(), 0.5, "tagger/model".into()),
+                (
+                    "general".into(),
+                    "cat".into(),
+                    1.0,
+                    MANUAL_TAGS_SETTER.into()
+                ),
+                (
+                    "general".into(),
+                    "dog".into(),
+                    1.0,
+                    MANUAL_TAGS_SETTER.into()
+                ),
+            ]
+        );
+
+        let removed = remove_manual_tags(
+            conn,
+            "sha_one",
+            &[tag("general", "cat"), tag("general", "dog")],
+        )
+        .await
+        .unwrap();
+        assert_eq!(removed, 2);
+        // The model's tag and its tag row stay; the manual-only tag and the
+        // now empty manual row are gone.
+        let tags = get_all_tags_for_item(conn, 1, &[], 0.0, &[], None)
+            .await
+            .unwrap();
+        assert_eq!(
+            tags,
+            vec![("general".into(), "cat".into(), 0.5, "tagger/model".into())]
+        );
+        assert_eq!(count(conn, "SELECT COUNT(*) FROM tags").await, 1);
+        assert_eq!(count(conn, "SELECT COUNT(*) FROM item_data").await, 1);
+
+        let err = add_manual_tags(conn, "sha_missing", &[tag("general", "cat")])
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), axum::http::StatusCode::NOT_FOUND);
+    }
+}
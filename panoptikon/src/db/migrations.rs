@@ -105,10 +105,26 @@ pub(crate) async fn migrate_databases_on_disk(
     Ok(paths)
 }
 
-pub(crate) async fn migrate_all_databases_on_disk() -> Result<()> {
+/// Outcome of migrating one database file in [`migrate_all_databases_on_disk`].
+#[derive(Debug)]
+pub(crate) struct DbMigrationResult {
+    pub path: PathBuf,
+    pub error: Option<anyhow::Error>,
+}
+
+/// Brings every database under the data folder up to date. Databases are
+/// migrated independently: one that fails (corrupt, locked, refused) is
+/// logged and reported in the results while the rest still migrate. Only an
+/// unreadable data folder fails the call.
+pub(crate) async fn migrate_all_databases_on_disk() -> Result<Vec<DbMigrationResult>> {
     let data_dir = crate::config::runtime().data_folder.clone();
+    migrate_all_databases_in(&data_dir).await
+}
+
+async fn migrate_all_databases_in(data_dir: &Path) -> Result<Vec<DbMigrationResult>> {
     let index_db_dir = data_dir.join("index");
     let user_data_db_dir = data_dir.join("user_data");
+    let mut targets: Vec<(PathBuf, &Migrator, &str)> = Vec::new();
 
     if index_db_dir.is_dir() {
         for entry in fs::read_dir(&index_db_dir)
@@ -124,11 +140,11 @@ pub(crate) async fn migrate_all_databases_on_disk() -> Result<()> {
             let db_dir = entry.path();
             let index_db_file = db_dir.join("index.db");
             if index_db_file.is_file() {
-                migrate_path(&index_db_file, &INDEX_MIGRATOR, INDEX_ALEMBIC_HEAD).await?;
+                targets.push((index_db_file, &INDEX_MIGRATOR, INDEX_ALEMBIC_HEAD));
             }
             let storage_db_file = db_dir.join("storage.db");
             if storage_db_file.is_file() {
-                migrate_path(&storage_db_file, &STORAGE_MIGRATOR, STORAGE_ALEMBIC_HEAD).await?;
+                targets.push((storage_db_file, &STORAGE_MIGRATOR, STORAGE_ALEMBIC_HEAD));
             }
         }
     }
@@ -152,11 +168,91 @@ pub(crate) async fn migrate_all_databases_on_disk() -> Result<()> {
             if !is_db {
                 continue;
             }
-            migrate_path(&path, &USER_DATA_MIGRATOR, USER_DATA_ALEMBIC_HEAD).await?;
+            targets.push((path, &USER_DATA_MIGRATOR, USER_DATA_ALEMBIC_HEAD));
         }
     }
 
-    Ok(())
+    let mut results = Vec::with_capacity(targets.len());
+    for (path, migrator, alembic_head) in targets {
+        let error = migrate_path(&path, migrator, alembic_head).await.err();
+        if let Some(err) = &error {
+            tracing::error!(path = %path.display(), error = ?err, "failed to migrate database");
+        }
+        results.push(DbMigrationResult { path, error });
+    }
+    Ok(results)
+}
+
+/// One database file's schema compared with what this build ships, read
+/// without creating or migrating anything.
+#[derive(Debug, PartialEq)]
+pub(crate) struct SchemaStatus {
+    pub path: PathBuf,
+    /// Newest migration recorded as applied; `None` when the file does not
+    /// exist yet or has no migration history.
+    pub applied: Option<i64>,
+    /// Newest migration this build ships.
+    pub latest: i64,
+}
+
+/// Schema status of the default index, storage and user data databases.
+/// Only reads: a missing file is reported, not created.
+pub(crate) async fn default_schema_status() -> Result<Vec<SchemaStatus>> {
+    let (index_db, user_data_db) = db_default_names();
+    let data_dir = crate::config::runtime().data_folder.clone();
+    schema_status_in(&data_dir, &index_db, &user_data_db).await
+}
+
+async fn schema_status_in(
+    data_dir: &Path,
+    index_db: &str,
+    user_data_db: &str,
+) -> Result<Vec<SchemaStatus>> {
+    let index_dir = data_dir.join("index").join(index_db);
+    let targets: [(PathBuf, &Migrator); 3] = [
+        (index_dir.join("index.db"), &INDEX_MIGRATOR),
+        (index_dir.join("storage.db"), &STORAGE_MIGRATOR),
+        (
+            data_dir.join("user_data").join(format!("{user_data_db}.db")),
+            &USER_DATA_MIGRATOR,
+        ),
+    ];
+    let mut statuses = Vec::with_capacity(targets.len());
+    for (path, migrator) in targets {
+        let latest = migrator
+            .iter()
+            .filter(|migration| !migration.migration_type.is_down_migration())
+            .map(|migration| migration.version)
+            .max()
+            .unwrap_or(0);
+        let applied = if path.is_file() {
+            applied_version(&path).await?
+        } else {
+            None
+        };
+        statuses.push(SchemaStatus {
+            path,
+            applied,
+            latest,
+        });
+    }
+    Ok(statuses)
+}
+
+async fn applied_version(path: &Path) -> Result<Option<i64>> {
+    let options = SqliteConnectOptions::new().filename(path).read_only(true);
+    let mut conn = SqliteConnection::connect_with(&options)
+        .await
+        .with_context(|| format!("failed to open database {}", path.display()))?;
+    if !table_exists(&mut conn, "_sqlx_migrations").await? {
+        return Ok(None);
+    }
+    let row: (Option<i64>,) =
+        sqlx::query_as("SELECT MAX(version) FROM _sqlx_migrations WHERE success")
+            .fetch_one(&mut conn)
+            .await
+            .with_context(|| format!("failed to read applied migrations of {}", path.display()))?;
+    Ok(row.0)
 }
 
 fn db_default_names() -> (String, String) {
@@ -798,4 +894,65 @@ mod tests {
         assert_eq!(version.0, USER_DATA_ALEMBIC_HEAD);
         conn.close().await.unwrap();
     }
+
+    // Ensures the schema status reads applied versions without creating
+    // missing databases, and reports a never-migrated file as unapplied.
+    #[tokio::test]
+    async fn schema_status_reports_applied_and_missing_databases() {
+        let dir = tempfile::tempdir().unwrap();
+        let user_data = dir.path().join("user_data").join("default.db");
+        fs::create_dir_all(user_data.parent().unwrap()).unwrap();
+        migrate_path(&user_data, &USER_DATA_MIGRATOR, USER_DATA_ALEMBIC_HEAD)
+            .await
+            .unwrap();
+        let index = dir.path().join("index").join("default").join("index.db");
+        fs::create_dir_all(index.parent().unwrap()).unwrap();
+        connect(&index).await.close().await.unwrap();
+
+        let statuses = schema_status_in(dir.path(), "default", "default")
+            .await
+            .unwrap();
+
+        let applied = statuses
+            .iter()
+            .map(|status| (status.path.clone(), status.applied))
+            .collect::<Vec<_>>();
+        let latest_user_data = statuses[2].latest;
+        assert_eq!(
+            applied,
+            vec![
+                (index, None),
+                (dir.path().join("index").join("default").join("storage.db"), None),
+                (user_data, Some(latest_user_data)),
+            ]
+        );
+        assert!(!statuses[1].path.exists());
+    }
+
+    // One corrupt database must not stop the others from migrating; every
+    // file gets its own result.
+    #[tokio::test]
+    async fn corrupt_database_does_not_abort_the_others() {
+        let dir = tempfile::tempdir().unwrap();
+        let broken = dir.path().join("index").join("broken").join("index.db");
+        fs::create_dir_all(broken.parent().unwrap()).unwrap();
+        fs::write(&broken, vec![0x5a; 4096]).unwrap();
+        let good = dir.path().join("user_data").join("good.db");
+        fs::create_dir_all(good.parent().unwrap()).unwrap();
+        fs::write(&good, b"").unwrap();
+
+        let results = migrate_all_databases_in(dir.path()).await.unwrap();
+
+        assert_eq!(results.len(), 2);
+        let failed = results
+            .iter()
+            .filter(|result| result.error.is_some())
+            .map(|result| result.path.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(failed, vec![broken]);
+        assert_eq!(
+            sqlx_migration_count(&good).await,
+            user_data_migration_total()
+        );
+    }
 }
@@ -1,21 +1,41 @@
 pub(crate) mod bookmarks;
 mod connection;
+pub(crate) mod data_coverage;
+pub(crate) mod db_maintenance;
+pub(crate) mod dir_snapshots;
+pub(crate) mod embeddings;
 pub(crate) mod epochs;
 pub(crate) mod extraction_log;
 pub(crate) mod extraction_write;
 pub(crate) mod file_scans;
+pub(crate) mod file_verification;
 pub(crate) mod files;
 pub(crate) mod folders;
+pub(crate) mod fts;
 pub(crate) mod index_writer;
 pub(crate) mod info;
+pub(crate) mod instance_lock;
+pub(crate) mod item_meta;
+pub(crate) mod item_notes;
+pub(crate) mod item_purge;
 pub(crate) mod items;
+pub(crate) mod lineage;
+pub(crate) mod manual_tags;
 pub(crate) mod migrations;
+pub(crate) mod path_keys;
 pub(crate) mod pinboards;
 pub(crate) mod pql;
+pub(crate) mod raw_paths;
+pub(crate) mod saved_queries;
 pub(crate) mod setup;
 pub(crate) mod sql_functions;
 pub(crate) mod storage;
+pub(crate) mod storage_stats;
+pub(crate) mod suggestions;
 pub(crate) mod system_config;
+pub(crate) mod tag_aliases;
+pub(crate) mod tag_import;
+pub(crate) mod tag_rethreshold;
 pub(crate) mod tags;
 pub(crate) mod vector_quants;
 
@@ -0,0 +1,366 @@
+//! Lookup keys for file paths on case-insensitive filesystems.
+//!
+//! `files.path` keeps the casing a file was first indexed with, for display.
+//! Lookups go through `files.path_key` instead: the path itself in
+//! case-sensitive folders, and its lowercase form in case-insensitive ones,
+//! so a watcher event or rescan reporting `/Photos/a.JPG` for an indexed
+//! `/photos/A.jpg` updates the existing row instead of adding a duplicate.
+
+use std::path::{Path, PathBuf};
+
+use sqlx::Row;
+
+use crate::api_error::ApiError;
+use crate::db::system_config::{PathCaseSensitivity, SystemConfig, normalize_folder_list};
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+/// The lookup key of `path` in a folder with the given case sensitivity.
+pub(crate) fn path_key(path: &str, case_insensitive: bool) -> String {
+    if case_insensitive {
+        path.to_lowercase()
+    } else {
+        path.to_string()
+    }
+}
+
+/// Whether `folder` lives on a case-insensitive filesystem: the deepest
+/// component of it with letters is looked up again with its case swapped,
+/// and the result compared by file identity. `None` when no component can
+/// be probed (missing folder, no letters in the path).
+pub(crate) fn probe_case_insensitive(folder: &Path) -> Option<bool> {
+    let identity = file_identity(folder)?;
+    let mut rest = PathBuf::new();
+    for ancestor in folder.ancestors() {
+        let (Some(parent), Some(name)) = (ancestor.parent(), ancestor.file_name()) else {
+            break;
+        };
+        let name = name.to_string_lossy();
+        let swapped: String = name
+            .chars()
+            .map(|ch| {
+                if ch.is_lowercase() {
+                    ch.to_uppercase().next().unwrap_or(ch)
+                } else {
+                    ch.to_lowercase().next().unwrap_or(ch)
+                }
+            })
+            .collect();
+        if swapped != name {
+            let probe = parent.join(swapped).join(&rest);
+            return Some(file_identity(&probe).is_some_and(|other| other == identity));
+        }
+        rest = Path::new(ancestor.file_name()?).join(rest);
+    }
+    None
+}
+
+#[cfg(unix)]
+fn file_identity(path: &Path) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_identity(path: &Path) -> Option<PathBuf> {
+    std::fs::canonicalize(path).ok()
+}
+
+/// Case sensitivity of each included folder, resolved once per scan or
+/// watcher restart.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct PathKeys {
+    /// Included folders with their lowercase form and whether they are
+    /// case-insensitive, longest first.
+    folders: Vec<(String, String, bool)>,
+    /// Set by an explicit `path_case_sensitivity`, which applies to every
+    /// path, including ones outside the included folders.
+    forced: Option<bool>,
+}
+
+impl PathKeys {
+    pub(crate) fn new(config: &SystemConfig) -> Self {
+        let forced = match config.path_case_sensitivity {
+            PathCaseSensitivity::Auto => None,
+            PathCaseSensitivity::Sensitive => Some(false),
+            PathCaseSensitivity::Insensitive => Some(true),
+        };
+        let platform_default = cfg!(any(windows, target_os = "macos"));
+        let mut folders: Vec<(String, String, bool)> =
+            normalize_folder_list(&config.included_folders)
+                .into_iter()
+                .map(|root| {
+                    let insensitive = forced.unwrap_or_else(|| {
+                        probe_case_insensitive(Path::new(&root)).unwrap_or(platform_default)
+                    });
+                    let lowered = root.to_lowercase();
+                    (root, lowered, insensitive)
+                })
+                .collect();
+        folders.sort_by_key(|(root, _, _)| std::cmp::Reverse(root.len()));
+        Self { folders, forced }
+    }
+
+    /// The lookup key of `path`: lowercased when the included folder it is
+    /// in (matched case-insensitively) is case-insensitive.
+    pub(crate) fn key(&self, path: &str) -> String {
+        if let Some(insensitive) = self.forced {
+            return path_key(path, insensitive);
+        }
+        let lowered = path.to_lowercase();
+        let insensitive = self
+            .folders
+            .iter()
+            .find(|(_, root, _)| lowered.starts_with(root.as_str()))
+            .is_some_and(|(_, _, insensitive)| *insensitive);
+        if insensitive {
+            lowered
+        } else {
+            path.to_string()
+        }
+    }
+
+    /// Path prefixes whose keys `rekey_file_paths` brings up to date, with
+    /// their case sensitivity.
+    fn scopes(&self) -> Vec<(&str, bool)> {
+        match self.forced {
+            Some(insensitive) => vec![("", insensitive)],
+            None => self
+                .folders
+                .iter()
+                .map(|(root, _, insensitive)| (root.as_str(), *insensitive))
+                .collect(),
+        }
+    }
+}
+
+/// Rewrites the stored keys of indexed files whose folder changed case
+/// sensitivity (or that predate the key), so lookups with `keys` find them.
+/// Returns the number of rows rekeyed.
+pub(crate) async fn rekey_file_paths(
+    conn: &mut sqlx::SqliteConnection,
+    keys: &PathKeys,
+) -> ApiResult<u64> {
+    let mut rekeyed = 0;
+    // Nested folders are selected by each root containing them; the new key
+    // always comes from `PathKeys::key`, so the innermost one wins.
+    for (root, insensitive) in keys.scopes() {
+        // SQLite's lower() only folds ASCII, so this over-selects paths with
+        // other letters; the update below skips keys that are already right.
+        let rows = sqlx::query(
+            r#"
+SELECT id, path FROM files
+WHERE lower(substr(path, 1, length(?1))) = lower(?1)
+  AND path_key IS NOT (CASE WHEN ?2 THEN lower(path) ELSE path END)
+            "#,
+        )
+        .bind(root)
+        .bind(insensitive)
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|err| {
+            tracing::error!(error = %err, root, "failed to read file path keys");
+            ApiError::internal("Failed to update file path keys")
+        })?;
+        for row in rows {
+            let id: i64 = row.get("id");
+            let path: String = row.get("path");
+            let result =
+                sqlx::query("UPDATE files SET path_key = ?1 WHERE id = ?2 AND path_key IS NOT ?1")
+                    .bind(keys.key(&path))
+                    .bind(id)
+                    .execute(&mut *conn)
+                    .await
+                    .map_err(|err| {
+                        tracing::error!(error = %err, path, "failed to update file path key");
+                        ApiError::internal("Failed to update file path keys")
+                    })?;
+            rekeyed += result.rows_affected();
+        }
+    }
+    Ok(rekeyed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::file_scans::add_file_scan;
+    use crate::db::files::{FileScanData, get_file_by_path, rename_file_path, update_file_data};
+    use crate::db::migrations::setup_test_databases;
+
+    fn keys(sensitivity: PathCaseSensitivity) -> PathKeys {
+        PathKeys::new(&SystemConfig {
+            included_folders: vec!["/Data/".to_string()],
+            path_case_sensitivity: sensitivity,
+            ..SystemConfig::default()
+        })
+    }
+
+    fn scan_data(keys: &PathKeys, path: &str, last_modified: &str, new: bool) -> FileScanData {
+        FileScanData {
+            sha256: "sha_one".to_string(),
+            last_modified: last_modified.to_string(),
+            path: path.to_string(),
+            path_bytes: None,
+            path_key: keys.key(path),
+            new_file_hash: new,
+            file_size: Some(3),
+            item_metadata: None,
+            blurhash: None,
+        }
+    }
+
+    async fn insert_item(conn: &mut sqlx::SqliteConnection) {
+        sqlx::query(
+            r#"
+INSERT INTO items (id, sha256, md5, type, time_added)
+VALUES (1, 'sha_one', 'md5_one', 'image/jpeg', '2024-01-01T00:00:00')
+            "#,
+        )
+        .execute(conn)
+        .await
+        .unwrap();
+    }
+
+    // Ensures a mixed-case event for an indexed file in a case-insensitive
+    // folder updates its row, keeping the casing it was indexed with.
+    #[tokio::test]
+    async fn mixed_case_event_updates_existing_row_when_insensitive() {
+        let mut dbs = setup_test_databases().await;
+        insert_item(&mut dbs.index_conn).await;
+        let scan_id = add_file_scan(&mut dbs.index_conn, "2024-01-01T00:00:00", "/Data/")
+            .await
+            .unwrap();
+        let keys = keys(PathCaseSensitivity::Insensitive);
+
+        // Indexed, then touched and reported with other casing: the scanner
+        // finds the row by key, sees the same hash and updates it in place.
+        for (path, last_modified, new) in [
+            ("/Data/Photo.JPG", "2024-01-01T00:00:00", true),
+            ("/data/photo.jpg", "2024-01-02T00:00:00", false),
+        ] {
+            update_file_data(
+                &mut dbs.index_conn,
+                "2024-01-01T00:00:00",
+                scan_id,
+                &scan_data(&keys, path, last_modified, new),
+            )
+            .await
+            .unwrap();
+        }
+
+        let rows: Vec<(String, String)> = sqlx::query_as("SELECT path, last_modified FROM files")
+            .fetch_all(&mut dbs.index_conn)
+            .await
+            .unwrap();
+        assert_eq!(
+            rows,
+            vec![(
+                "/Data/Photo.JPG".to_string(),
+                "2024-01-02T00:00:00".to_string()
+            )]
+        );
+        let found = get_file_by_path(&mut dbs.index_conn, &keys.key("/DATA/photo.Jpg"))
+            .await
+            .unwrap();
+        assert!(found.is_some());
+
+        let renamed = rename_file_path(
+            &mut dbs.index_conn,
+            &keys.key("/data/PHOTO.jpg"),
+            "/Data/Renamed.jpg",
+            None,
+            &keys.key("/Data/Renamed.jpg"),
+            scan_id,
+            "2024-01-03T00:00:00",
+        )
+        .await
+        .unwrap();
+        assert!(renamed);
+        let path: String = sqlx::query_scalar("SELECT path FROM files")
+            .fetch_one(&mut dbs.index_conn)
+            .await
+            .unwrap();
+        assert_eq!(path, "/Data/Renamed.jpg");
+    }
+
+    // Ensures case-sensitive folders keep paths differing in case apart.
+    #[tokio::test]
+    async fn mixed_case_paths_stay_separate_when_sensitive() {
+        let mut dbs = setup_test_databases().await;
+        insert_item(&mut dbs.index_conn).await;
+        let scan_id = add_file_scan(&mut dbs.index_conn, "2024-01-01T00:00:00", "/Data/")
+            .await
+            .unwrap();
+        let keys = keys(PathCaseSensitivity::Sensitive);
+
+        for path in ["/Data/Photo.JPG", "/Data/photo.jpg"] {
+            update_file_data(
+                &mut dbs.index_conn,
+                "2024-01-01T00:00:00",
+                scan_id,
+                &scan_data(&keys, path, "2024-01-01T00:00:00", true),
+            )
+            .await
+            .unwrap();
+        }
+
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM files")
+            .fetch_one(&mut dbs.index_conn)
+            .await
+            .unwrap();
+        assert_eq!(count, 2);
+    }
+
+    // Ensures rekeying folds the keys of rows indexed before their folder
+    // was treated as case-insensitive, and leaves other folders alone.
+    #[tokio::test]
+    async fn rekey_folds_keys_under_insensitive_folders() {
+        let mut dbs = setup_test_databases().await;
+        insert_item(&mut dbs.index_conn).await;
+        let scan_id = add_file_scan(&mut dbs.index_conn, "2024-01-01T00:00:00", "/Data/")
+            .await
+            .unwrap();
+        for path in ["/Data/One.JPG", "/data/Two.jpg", "/Other/Three.JPG"] {
+            sqlx::query(
+                r#"
+INSERT INTO files (sha256, item_id, path, filename, last_modified, scan_id, available)
+VALUES ('sha_one', 1, ?1, 'f', '2024-01-01T00:00:00', ?2, 1)
+                "#,
+            )
+            .bind(path)
+            .bind(scan_id)
+            .execute(&mut dbs.index_conn)
+            .await
+            .unwrap();
+        }
+        let keys = PathKeys {
+            folders: vec![("/Data/".to_string(), "/data/".to_string(), true)],
+            forced: None,
+        };
+
+        let rekeyed = rekey_file_paths(&mut dbs.index_conn, &keys).await.unwrap();
+        assert_eq!(rekeyed, 2);
+        let stored: Vec<String> = sqlx::query_scalar("SELECT path_key FROM files ORDER BY id")
+            .fetch_all(&mut dbs.index_conn)
+            .await
+            .unwrap();
+        assert_eq!(
+            stored,
+            vec!["/data/one.jpg", "/data/two.jpg", "/Other/Three.JPG"]
+        );
+        assert_eq!(
+            rekey_file_paths(&mut dbs.index_conn, &keys).await.unwrap(),
+            0
+        );
+    }
+
+    // Ensures the probe reports a case-sensitive filesystem as such.
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn probe_detects_case_sensitive_folder() {
+        let dir = tempfile::Builder::new().prefix("Probe").tempdir().unwrap();
+        assert_eq!(probe_case_insensitive(dir.path()), Some(false));
+    }
+}
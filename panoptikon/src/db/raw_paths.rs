@@ -0,0 +1,96 @@
+//! File paths that are not valid UTF-8.
+//!
+//! `files.path` is text: it is what search, PQL and the UI see. A Unix
+//! filename in a legacy encoding (say latin-1 on an old NAS) has bytes that
+//! are not UTF-8, so its text form from [`display_path`] spells each such
+//! byte as `\xNN`. That keeps two such files apart and the form stable from
+//! scan to scan, but the text no longer names the file on disk: the exact
+//! bytes are kept in `files.path_bytes` (NULL for every UTF-8 path), and
+//! [`disk_path`] turns a stored row back into the path to open. Windows
+//! paths are UTF-16, so there the text is `to_string_lossy` as before.
+
+use std::path::{Path, PathBuf};
+
+/// The `files.path` text of `path`: the path itself when it is valid UTF-8,
+/// otherwise with every byte outside a valid sequence written as `\xNN`.
+#[cfg(unix)]
+pub(crate) fn display_path(path: &Path) -> String {
+    use std::fmt::Write;
+    use std::os::unix::ffi::OsStrExt;
+
+    if let Some(path) = path.to_str() {
+        return path.to_string();
+    }
+    let bytes = path.as_os_str().as_bytes();
+    let mut display = String::with_capacity(bytes.len() + 8);
+    for chunk in bytes.utf8_chunks() {
+        display.push_str(chunk.valid());
+        for byte in chunk.invalid() {
+            let _ = write!(display, "\\x{byte:02X}");
+        }
+    }
+    display
+}
+
+#[cfg(not(unix))]
+pub(crate) fn display_path(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+/// The `files.path_bytes` of `path`: its raw bytes when it is not valid
+/// UTF-8, else None.
+#[cfg(unix)]
+pub(crate) fn path_bytes(path: &Path) -> Option<Vec<u8>> {
+    use std::os::unix::ffi::OsStrExt;
+
+    path.to_str()
+        .is_none()
+        .then(|| path.as_os_str().as_bytes().to_vec())
+}
+
+#[cfg(not(unix))]
+pub(crate) fn path_bytes(_path: &Path) -> Option<Vec<u8>> {
+    None
+}
+
+/// The on-disk path of a `files` row.
+#[cfg(unix)]
+pub(crate) fn disk_path(path: &str, path_bytes: Option<&[u8]>) -> PathBuf {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    match path_bytes {
+        Some(bytes) => PathBuf::from(OsStr::from_bytes(bytes)),
+        None => PathBuf::from(path),
+    }
+}
+
+#[cfg(not(unix))]
+pub(crate) fn disk_path(path: &str, _path_bytes: Option<&[u8]>) -> PathBuf {
+    PathBuf::from(path)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    use super::*;
+
+    // Ensures UTF-8 paths pass through untouched, while latin-1 names get a
+    // distinct, stable text form and round-trip through their raw bytes.
+    #[test]
+    fn non_utf8_paths_round_trip_through_their_bytes() {
+        let plain = Path::new("/media/café.jpg");
+        assert_eq!(display_path(plain), "/media/café.jpg");
+        assert_eq!(path_bytes(plain), None);
+        assert_eq!(disk_path("/media/café.jpg", None), plain);
+
+        let latin1 = Path::new(OsStr::from_bytes(b"/media/caf\xe9.jpg"));
+        let other = Path::new(OsStr::from_bytes(b"/media/caf\xe8.jpg"));
+        assert_eq!(display_path(latin1), "/media/caf\\xE9.jpg");
+        assert_ne!(display_path(latin1), display_path(other));
+        let bytes = path_bytes(latin1).unwrap();
+        assert_eq!(disk_path(&display_path(latin1), Some(&bytes)), latin1);
+    }
+}
@@ -0,0 +1,171 @@
+use sqlx::Row;
+
+use crate::api_error::ApiError;
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+/// One saved query. `query` is the stored PqlQuery JSON, verbatim.
+pub(crate) struct SavedQueryRecord {
+    pub id: i64,
+    pub name: String,
+    pub description: Option<String>,
+    pub query: String,
+    pub time_added: String,
+    pub time_updated: String,
+}
+
+pub(crate) enum WriteOutcome {
+    Written,
+    NotFound,
+    /// Another saved query of the same user already has the target name.
+    NameTaken,
+}
+
+fn internal(context: &'static str) -> impl FnOnce(sqlx::Error) -> ApiError {
+    move |err| {
+        tracing::error!(error = %err, context, "saved queries query failed");
+        ApiError::internal(context)
+    }
+}
+
+fn is_unique_violation(err: &sqlx::Error) -> bool {
+    err.as_database_error()
+        .is_some_and(|db_err| db_err.is_unique_violation())
+}
+
+fn map_record(row: &sqlx::sqlite::SqliteRow) -> Result<SavedQueryRecord, sqlx::Error> {
+    Ok(SavedQueryRecord {
+        id: row.try_get("id")?,
+        name: row.try_get("name")?,
+        description: row.try_get("description")?,
+        query: row.try_get("query")?,
+        time_added: row.try_get("time_added")?,
+        time_updated: row.try_get("time_updated")?,
+    })
+}
+
+/// The user's saved queries, ordered by name.
+pub(crate) async fn list_saved_queries(
+    conn: &mut sqlx::SqliteConnection,
+    user: &str,
+) -> ApiResult<Vec<SavedQueryRecord>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT id, name, description, query, time_added, time_updated
+        FROM user_data.saved_queries
+        WHERE user = ?
+        ORDER BY name
+        "#,
+    )
+    .bind(user)
+    .fetch_all(conn)
+    .await
+    .map_err(internal("Failed to list saved queries"))?;
+
+    rows.iter()
+        .map(map_record)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(internal("Failed to list saved queries"))
+}
+
+pub(crate) async fn get_saved_query(
+    conn: &mut sqlx::SqliteConnection,
+    user: &str,
+    name: &str,
+) -> ApiResult<Option<SavedQueryRecord>> {
+    let row = sqlx::query(
+        r#"
+        SELECT id, name, description, query, time_added, time_updated
+        FROM user_data.saved_queries
+        WHERE user = ? AND name = ?
+        "#,
+    )
+    .bind(user)
+    .bind(name)
+    .fetch_optional(conn)
+    .await
+    .map_err(internal("Failed to load saved query"))?;
+
+    row.as_ref()
+        .map(map_record)
+        .transpose()
+        .map_err(internal("Failed to load saved query"))
+}
+
+/// Inserts a new saved query; `NameTaken` if the user already has `name`.
+pub(crate) async fn create_saved_query(
+    conn: &mut sqlx::SqliteConnection,
+    user: &str,
+    name: &str,
+    description: Option<&str>,
+    query: &str,
+) -> ApiResult<WriteOutcome> {
+    let result = sqlx::query(
+        r#"
+        INSERT INTO user_data.saved_queries (user, name, description, query, time_added, time_updated)
+        VALUES (
+            ?, ?, ?, ?,
+            strftime('%Y-%m-%dT%H:%M:%f','now','localtime'),
+            strftime('%Y-%m-%dT%H:%M:%f','now','localtime')
+        )
+        "#,
+    )
+    .bind(user)
+    .bind(name)
+    .bind(description)
+    .bind(query)
+    .execute(conn)
+    .await;
+    match result {
+        Ok(_) => Ok(WriteOutcome::Written),
+        Err(err) if is_unique_violation(&err) => Ok(WriteOutcome::NameTaken),
+        Err(err) => Err(internal("Failed to create saved query")(err)),
+    }
+}
+
+/// Replaces the saved query `name`, renaming it to `new_name` (which may be
+/// the same). `time_added` is preserved.
+pub(crate) async fn update_saved_query(
+    conn: &mut sqlx::SqliteConnection,
+    user: &str,
+    name: &str,
+    new_name: &str,
+    description: Option<&str>,
+    query: &str,
+) -> ApiResult<WriteOutcome> {
+    let result = sqlx::query(
+        r#"
+        UPDATE user_data.saved_queries
+        SET name = ?, description = ?, query = ?,
+            time_updated = strftime('%Y-%m-%dT%H:%M:%f','now','localtime')
+        WHERE user = ? AND name = ?
+        "#,
+    )
+    .bind(new_name)
+    .bind(description)
+    .bind(query)
+    .bind(user)
+    .bind(name)
+    .execute(conn)
+    .await;
+    match result {
+        Ok(done) if done.rows_affected() == 0 => Ok(WriteOutcome::NotFound),
+        Ok(_) => Ok(WriteOutcome::Written),
+        Err(err) if is_unique_violation(&err) => Ok(WriteOutcome::NameTaken),
+        Err(err) => Err(internal("Failed to update saved query")(err)),
+    }
+}
+
+pub(crate) async fn delete_saved_query(
+    conn: &mut sqlx::SqliteConnection,
+    user: &str,
+    name: &str,
+) -> ApiResult<bool> {
+    let result = sqlx::query("DELETE FROM user_data.saved_queries WHERE user = ? AND name = ?")
+        .bind(user)
+        .bind(name)
+        .execute(conn)
+        .await
+        .map_err(internal("Failed to delete saved query"))?;
+    Ok(result.rows_affected() > 0)
+}
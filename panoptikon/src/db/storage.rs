@@ -1,5 +1,12 @@
+use std::path::{Path, PathBuf};
+
 use crate::api_error::ApiError;
+use crate::config::ThumbnailStorage;
+use crate::db::connection::index_storage_paths_unchecked;
+use crate::db::raw_paths::disk_path;
+use serde::Serialize;
 use sqlx::Row;
+use utoipa::ToSchema;
 
 type ApiResult<T> = std::result::Result<T, ApiError>;
 
@@ -65,148 +72,322 @@ SELECT EXISTS(
     Ok(row.0 == 1)
 }
 
+/// The two visuals tables. A row whose blob column is empty keeps its bytes
+/// in a file instead (see [`ThumbnailStorage`]); the row still holds the
+/// metadata, so version checks and frame listings never touch the disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum VisualTable {
+    Thumbnails,
+    Frames,
+}
+
+impl VisualTable {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Self::Thumbnails => "thumbnails",
+            Self::Frames => "frames",
+        }
+    }
+
+    pub(crate) fn blob_column(self) -> &'static str {
+        match self {
+            Self::Thumbnails => "thumbnail",
+            Self::Frames => "frame",
+        }
+    }
+
+    /// `<dir>/<table>/ab/cd/<sha256>_<idx>.jpg`. Two levels of two hex
+    /// characters keep every directory small even for millions of items.
+    pub(crate) fn file_path(self, dir: &Path, sha256: &str, idx: i64) -> PathBuf {
+        let shard = |range: std::ops::Range<usize>| sha256.get(range).unwrap_or("_");
+        dir.join(self.name())
+            .join(shard(0..2))
+            .join(shard(2..4))
+            .join(format!("{sha256}_{idx}.jpg"))
+    }
+}
+
+/// The folder an index DB's file-backed visuals live under: the index DB's
+/// own folder, next to `storage.db`.
+pub(crate) fn visuals_dir(index_db: &str) -> PathBuf {
+    let paths = index_storage_paths_unchecked(index_db);
+    paths
+        .storage_db_file
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default()
+}
+
+/// A stored thumbnail or frame.
+#[derive(Debug)]
+pub(crate) enum StoredVisual {
+    Blob(Vec<u8>),
+    File(PathBuf),
+}
+
+impl StoredVisual {
+    /// The image bytes, or `None` if the file is gone.
+    pub(crate) async fn into_bytes(self) -> ApiResult<Option<Vec<u8>>> {
+        match self {
+            Self::Blob(bytes) => Ok(Some(bytes)),
+            Self::File(path) => read_visual_file(&path).await,
+        }
+    }
+}
+
+async fn read_visual_file(path: &Path) -> ApiResult<Option<Vec<u8>>> {
+    match tokio::fs::read(path).await {
+        Ok(bytes) => Ok(Some(bytes)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            tracing::warn!(path = %path.display(), "stored visual file is missing");
+            Ok(None)
+        }
+        Err(err) => {
+            tracing::error!(error = %err, path = %path.display(), "failed to read visual file");
+            Err(ApiError::internal("Failed to read stored visual"))
+        }
+    }
+}
+
+/// Writes through a temporary file so a reader never sees a partial image.
+async fn write_visual_file(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let tmp_path = path.with_extension("jpg.tmp");
+    tokio::fs::write(&tmp_path, bytes).await?;
+    tokio::fs::rename(&tmp_path, path).await
+}
+
+/// Deletes visual files whose rows are gone. Only called once the
+/// transaction that removed the rows has committed, so a rollback can never
+/// leave a row pointing at a deleted file; a failure here leaves a stray
+/// file behind, nothing worse.
+pub(crate) async fn remove_visual_files(paths: &[PathBuf]) {
+    for path in paths {
+        match tokio::fs::remove_file(path).await {
+            Ok(()) => {}
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => {
+                tracing::warn!(error = %err, path = %path.display(), "failed to remove visual file");
+            }
+        }
+    }
+}
+
+/// Rows changed by a visuals write, and the files that no longer back any
+/// row; see [`remove_visual_files`].
+#[derive(Debug, Default)]
+pub(crate) struct VisualsWrite {
+    pub rows: u64,
+    pub stale_files: Vec<PathBuf>,
+}
+
 pub(crate) async fn store_thumbnails(
     conn: &mut sqlx::SqliteConnection,
+    dir: &Path,
+    backend: ThumbnailStorage,
     sha256: &str,
     mime_type: &str,
     process_version: i64,
     thumbnails: &[StoredImage],
-) -> ApiResult<()> {
-    // <= makes a same-version re-store replace instead of violating the
-    // (item_sha256, idx) uniqueness when two sources race to store visuals
-    // for identical content.
-    sqlx::query(
-        r#"
-DELETE FROM storage.thumbnails
-WHERE item_sha256 = ?1 AND version <= ?2
-        "#,
+) -> ApiResult<VisualsWrite> {
+    store_visuals(
+        conn,
+        dir,
+        backend,
+        VisualTable::Thumbnails,
+        sha256,
+        mime_type,
+        process_version,
+        thumbnails,
     )
-    .bind(sha256)
-    .bind(process_version)
-    .execute(&mut *conn)
     .await
-    .map_err(|err| {
-        tracing::error!(error = %err, "failed to prune thumbnails");
-        ApiError::internal("Failed to store thumbnails")
-    })?;
-
-    for thumb in thumbnails {
-        sqlx::query(
-            r#"
-INSERT INTO storage.thumbnails (
-    item_sha256, idx, item_mime_type, width, height, version, thumbnail
-)
-VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
-            "#,
-        )
-        .bind(sha256)
-        .bind(thumb.idx)
-        .bind(mime_type)
-        .bind(thumb.width)
-        .bind(thumb.height)
-        .bind(process_version)
-        .bind(&thumb.bytes)
-        .execute(&mut *conn)
-        .await
-        .map_err(|err| {
-            tracing::error!(error = %err, "failed to store thumbnail");
-            ApiError::internal("Failed to store thumbnails")
-        })?;
-    }
-
-    Ok(())
 }
 
 pub(crate) async fn store_frames(
     conn: &mut sqlx::SqliteConnection,
+    dir: &Path,
+    backend: ThumbnailStorage,
     sha256: &str,
     mime_type: &str,
     process_version: i64,
     frames: &[StoredImage],
-) -> ApiResult<()> {
-    // <= for the same reason as store_thumbnails: same-version re-stores
-    // replace rather than conflict.
-    sqlx::query(
-        r#"
-DELETE FROM storage.frames
-WHERE item_sha256 = ?1 AND version <= ?2
-        "#,
+) -> ApiResult<VisualsWrite> {
+    store_visuals(
+        conn,
+        dir,
+        backend,
+        VisualTable::Frames,
+        sha256,
+        mime_type,
+        process_version,
+        frames,
     )
+    .await
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn store_visuals(
+    conn: &mut sqlx::SqliteConnection,
+    dir: &Path,
+    backend: ThumbnailStorage,
+    table: VisualTable,
+    sha256: &str,
+    mime_type: &str,
+    process_version: i64,
+    images: &[StoredImage],
+) -> ApiResult<VisualsWrite> {
+    let name = table.name();
+    let column = table.blob_column();
+    let store_error = || ApiError::internal(format!("Failed to store {name}"));
+
+    let replaced_files: Vec<i64> = sqlx::query_scalar(sqlx::AssertSqlSafe(format!(
+        "SELECT idx FROM storage.{name} \
+         WHERE item_sha256 = ?1 AND version <= ?2 AND length({column}) = 0"
+    )))
+    .bind(sha256)
+    .bind(process_version)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|err| {
+        tracing::error!(error = %err, table = name, "failed to read stored visuals");
+        store_error()
+    })?;
+
+    // <= makes a same-version re-store replace instead of violating the
+    // (item_sha256, idx) uniqueness when two sources race to store visuals
+    // for identical content.
+    sqlx::query(sqlx::AssertSqlSafe(format!(
+        "DELETE FROM storage.{name} WHERE item_sha256 = ?1 AND version <= ?2"
+    )))
     .bind(sha256)
     .bind(process_version)
     .execute(&mut *conn)
     .await
     .map_err(|err| {
-        tracing::error!(error = %err, "failed to prune frames");
-        ApiError::internal("Failed to store frames")
+        tracing::error!(error = %err, table = name, "failed to prune stored visuals");
+        store_error()
     })?;
 
-    for frame in frames {
-        sqlx::query(
-            r#"
-INSERT INTO storage.frames (
-    item_sha256, idx, item_mime_type, width, height, version, frame
-)
-VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
-            "#,
-        )
+    let to_files = backend == ThumbnailStorage::Filesystem;
+    for image in images {
+        if to_files {
+            let path = table.file_path(dir, sha256, image.idx);
+            write_visual_file(&path, &image.bytes)
+                .await
+                .map_err(|err| {
+                    tracing::error!(error = %err, path = %path.display(), "failed to write visual file");
+                    store_error()
+                })?;
+        }
+        let blob: &[u8] = if to_files { &[] } else { &image.bytes };
+        sqlx::query(sqlx::AssertSqlSafe(format!(
+            "INSERT INTO storage.{name} \
+             (item_sha256, idx, item_mime_type, width, height, version, {column}) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)"
+        )))
         .bind(sha256)
-        .bind(frame.idx)
+        .bind(image.idx)
         .bind(mime_type)
-        .bind(frame.width)
-        .bind(frame.height)
+        .bind(image.width)
+        .bind(image.height)
         .bind(process_version)
-        .bind(&frame.bytes)
+        .bind(blob)
         .execute(&mut *conn)
         .await
         .map_err(|err| {
-            tracing::error!(error = %err, "failed to store frame");
-            ApiError::internal("Failed to store frames")
+            tracing::error!(error = %err, table = name, "failed to store visual");
+            store_error()
         })?;
     }
 
-    Ok(())
+    // A replaced file stays only if the new row at its index is file-backed
+    // too, in which case it was just overwritten.
+    let stale_files = replaced_files
+        .into_iter()
+        .filter(|idx| !(to_files && images.iter().any(|image| image.idx == *idx)))
+        .map(|idx| table.file_path(dir, sha256, idx))
+        .collect();
+    Ok(VisualsWrite {
+        rows: images.len() as u64,
+        stale_files,
+    })
 }
 
-pub(crate) async fn get_thumbnail_bytes(
+/// A stored visual and the process version it was generated with.
+async fn get_visual(
     conn: &mut sqlx::SqliteConnection,
+    dir: &Path,
+    table: VisualTable,
     sha256: &str,
     idx: i64,
-) -> ApiResult<Option<Vec<u8>>> {
-    let row = sqlx::query(
-        r#"
-SELECT thumbnail
-FROM storage.thumbnails
-WHERE item_sha256 = ?1 AND idx = ?2
-LIMIT 1
-        "#,
-    )
+) -> ApiResult<Option<(StoredVisual, i64)>> {
+    let name = table.name();
+    let row: Option<(Vec<u8>, i64)> = sqlx::query_as(sqlx::AssertSqlSafe(format!(
+        "SELECT {}, version FROM storage.{name} WHERE item_sha256 = ?1 AND idx = ?2 LIMIT 1",
+        table.blob_column()
+    )))
     .bind(sha256)
     .bind(idx)
     .fetch_optional(&mut *conn)
     .await
     .map_err(|err| {
-        tracing::error!(error = %err, "failed to read thumbnail");
-        ApiError::internal("Failed to read thumbnail")
+        tracing::error!(error = %err, table = name, "failed to read stored visual");
+        ApiError::internal(format!("Failed to read {name}"))
     })?;
 
-    let Some(row) = row else {
-        return Ok(None);
-    };
-    let bytes: Vec<u8> = row.try_get("thumbnail").map_err(|err| {
-        tracing::error!(error = %err, "failed to parse thumbnail");
-        ApiError::internal("Failed to read thumbnail")
-    })?;
-    Ok(Some(bytes))
+    Ok(row.map(|(bytes, version)| {
+        let visual = if bytes.is_empty() {
+            StoredVisual::File(table.file_path(dir, sha256, idx))
+        } else {
+            StoredVisual::Blob(bytes)
+        };
+        (visual, version)
+    }))
+}
+
+pub(crate) async fn get_thumbnail(
+    conn: &mut sqlx::SqliteConnection,
+    dir: &Path,
+    sha256: &str,
+    idx: i64,
+) -> ApiResult<Option<(StoredVisual, i64)>> {
+    get_visual(conn, dir, VisualTable::Thumbnails, sha256, idx).await
+}
+
+pub(crate) async fn get_frame(
+    conn: &mut sqlx::SqliteConnection,
+    dir: &Path,
+    sha256: &str,
+    idx: i64,
+) -> ApiResult<Option<(StoredVisual, i64)>> {
+    get_visual(conn, dir, VisualTable::Frames, sha256, idx).await
+}
+
+pub(crate) async fn get_thumbnail_bytes(
+    conn: &mut sqlx::SqliteConnection,
+    dir: &Path,
+    sha256: &str,
+    idx: i64,
+) -> ApiResult<Option<Vec<u8>>> {
+    match get_thumbnail(conn, dir, sha256, idx).await? {
+        Some((visual, _)) => visual.into_bytes().await,
+        None => Ok(None),
+    }
 }
 
+/// All stored frames of an item, in index order. If any frame's file is
+/// missing the item is treated as having no stored frames, so callers
+/// extract a fresh, complete set.
 pub(crate) async fn get_frames_bytes(
     conn: &mut sqlx::SqliteConnection,
+    dir: &Path,
     sha256: &str,
 ) -> ApiResult<Vec<Vec<u8>>> {
-    let rows = sqlx::query(
+    let rows: Vec<(i64, Vec<u8>)> = sqlx::query_as(
         r#"
-SELECT frame
+SELECT idx, frame
 FROM storage.frames
 WHERE item_sha256 = ?1
 ORDER BY idx
@@ -221,60 +402,559 @@ ORDER BY idx
     })?;
 
     let mut frames = Vec::with_capacity(rows.len());
-    for row in rows {
-        let frame: Vec<u8> = row.try_get("frame").map_err(|err| {
-            tracing::error!(error = %err, "failed to parse frame");
-            ApiError::internal("Failed to read frames")
-        })?;
-        frames.push(frame);
+    for (idx, frame) in rows {
+        if !frame.is_empty() {
+            frames.push(frame);
+            continue;
+        }
+        let path = VisualTable::Frames.file_path(dir, sha256, idx);
+        match read_visual_file(&path).await? {
+            Some(frame) => frames.push(frame),
+            None => return Ok(Vec::new()),
+        }
     }
     Ok(frames)
 }
 
-pub(crate) async fn delete_orphaned_thumbnails(
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub(crate) struct FrameInfo {
+    pub index: i64,
+    pub width: i64,
+    pub height: i64,
+}
+
+/// The stored frames of an item, in index order, without their bytes.
+pub(crate) async fn get_frame_infos(
     conn: &mut sqlx::SqliteConnection,
-) -> ApiResult<u64> {
-    let result = sqlx::query(
+    sha256: &str,
+) -> ApiResult<Vec<FrameInfo>> {
+    let rows: Vec<(i64, i64, i64)> = sqlx::query_as(
         r#"
-DELETE FROM storage.thumbnails
-WHERE item_sha256 IN (
-    SELECT storage.thumbnails.item_sha256
+SELECT idx, width, height
+FROM storage.frames
+WHERE item_sha256 = ?1
+ORDER BY idx
+        "#,
+    )
+    .bind(sha256)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|err| {
+        tracing::error!(error = %err, "failed to read frame metadata");
+        ApiError::internal("Failed to read frames")
+    })?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(index, width, height)| FrameInfo {
+            index,
+            width,
+            height,
+        })
+        .collect())
+}
+
+/// An item with thumbnails or frames stored by an older process version.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct OutdatedVisuals {
+    pub sha256: String,
+    pub mime_type: String,
+    pub thumbnails_outdated: bool,
+    pub frames_outdated: bool,
+    /// An available file with the item's content, if there is one.
+    pub path: Option<PathBuf>,
+    pub duration: Option<f64>,
+    pub video_tracks: Option<i64>,
+}
+
+/// The on-disk path from a row's `path` and `path_bytes` columns.
+fn row_disk_path(row: &sqlx::sqlite::SqliteRow) -> Result<Option<PathBuf>, sqlx::Error> {
+    let path: Option<String> = row.try_get("path")?;
+    let path_bytes: Option<Vec<u8>> = row.try_get("path_bytes")?;
+    Ok(path.map(|path| disk_path(&path, path_bytes.as_deref())))
+}
+
+/// One page of items whose stored visuals are older than the given process
+/// versions, in sha256 order after `after_sha256`. Orphaned visuals (no
+/// item) are left to the post-scan cleanup.
+pub(crate) async fn get_outdated_visuals(
+    conn: &mut sqlx::SqliteConnection,
+    thumbnail_version: i64,
+    frame_version: i64,
+    after_sha256: &str,
+    limit: i64,
+) -> ApiResult<Vec<OutdatedVisuals>> {
+    let rows = sqlx::query(
+        r#"
+WITH outdated AS (
+    SELECT item_sha256, 1 AS thumbnails_outdated, 0 AS frames_outdated
     FROM storage.thumbnails
-    LEFT JOIN items ON storage.thumbnails.item_sha256 = items.sha256
-    WHERE items.sha256 IS NULL
+    WHERE version < ?1 AND item_sha256 > ?3
+    UNION ALL
+    SELECT item_sha256, 0, 1
+    FROM storage.frames
+    WHERE version < ?2 AND item_sha256 > ?3
 )
+SELECT
+    items.sha256 AS sha256,
+    items.type AS mime_type,
+    MAX(outdated.thumbnails_outdated) AS thumbnails_outdated,
+    MAX(outdated.frames_outdated) AS frames_outdated,
+    (
+        SELECT files.path
+        FROM files
+        WHERE files.item_id = items.id AND files.available = 1
+        ORDER BY files.id
+        LIMIT 1
+    ) AS path,
+    (
+        SELECT files.path_bytes
+        FROM files
+        WHERE files.item_id = items.id AND files.available = 1
+        ORDER BY files.id
+        LIMIT 1
+    ) AS path_bytes,
+    items.duration AS duration,
+    items.video_tracks AS video_tracks
+FROM outdated
+JOIN items ON items.sha256 = outdated.item_sha256
+GROUP BY items.sha256
+ORDER BY items.sha256
+LIMIT ?4
+        "#,
+    )
+    .bind(thumbnail_version)
+    .bind(frame_version)
+    .bind(after_sha256)
+    .bind(limit)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|err| {
+        tracing::error!(error = %err, "failed to list outdated visuals");
+        ApiError::internal("Failed to list outdated visuals")
+    })?;
+
+    rows.iter()
+        .map(|row| {
+            Ok(OutdatedVisuals {
+                sha256: row.try_get("sha256")?,
+                mime_type: row.try_get("mime_type")?,
+                thumbnails_outdated: row.try_get("thumbnails_outdated")?,
+                frames_outdated: row.try_get("frames_outdated")?,
+                path: row_disk_path(row)?,
+                duration: row.try_get("duration")?,
+                video_tracks: row.try_get("video_tracks")?,
+            })
+        })
+        .collect::<Result<Vec<_>, sqlx::Error>>()
+        .map_err(|err| {
+            tracing::error!(error = %err, "failed to parse outdated visuals");
+            ApiError::internal("Failed to list outdated visuals")
+        })
+}
+
+/// Conditions for an item left without visuals by a fast scan: no
+/// thumbnail and no blurhash, an available file, and a type the scanner
+/// always renders (images, audio, and videos with a usable video track).
+/// PDFs and HTML pages are left out since they depend on optional
+/// renderers and would be retried on every run.
+const MISSING_VISUALS_CONDITIONS: &str = r#"
+    items.blurhash IS NULL
+    AND (
+        items.type LIKE 'image/%'
+        OR items.type LIKE 'audio/%'
+        OR (items.type LIKE 'video/%' AND items.video_tracks > 0 AND items.duration > 0)
+    )
+    AND NOT EXISTS (
+        SELECT 1 FROM storage.thumbnails WHERE storage.thumbnails.item_sha256 = items.sha256
+    )
+    AND EXISTS (
+        SELECT 1 FROM files WHERE files.item_id = items.id AND files.available = 1
+    )
+"#;
+
+/// One page of items a fast scan indexed without visuals, in sha256 order
+/// after `after_sha256`, shaped like [`get_outdated_visuals`] rows: their
+/// thumbnails count as outdated, and so do the frames of videos without
+/// any.
+pub(crate) async fn get_missing_visuals(
+    conn: &mut sqlx::SqliteConnection,
+    after_sha256: &str,
+    limit: i64,
+) -> ApiResult<Vec<OutdatedVisuals>> {
+    let rows = sqlx::query(sqlx::AssertSqlSafe(format!(
+        r#"
+SELECT
+    items.sha256 AS sha256,
+    items.type AS mime_type,
+    items.type LIKE 'video/%' AND NOT EXISTS (
+        SELECT 1 FROM storage.frames WHERE storage.frames.item_sha256 = items.sha256
+    ) AS frames_outdated,
+    (
+        SELECT files.path
+        FROM files
+        WHERE files.item_id = items.id AND files.available = 1
+        ORDER BY files.id
+        LIMIT 1
+    ) AS path,
+    (
+        SELECT files.path_bytes
+        FROM files
+        WHERE files.item_id = items.id AND files.available = 1
+        ORDER BY files.id
+        LIMIT 1
+    ) AS path_bytes,
+    items.duration AS duration,
+    items.video_tracks AS video_tracks
+FROM items
+WHERE items.sha256 > ?1 AND {MISSING_VISUALS_CONDITIONS}
+ORDER BY items.sha256
+LIMIT ?2
+        "#
+    )))
+    .bind(after_sha256)
+    .bind(limit)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|err| {
+        tracing::error!(error = %err, "failed to list items missing visuals");
+        ApiError::internal("Failed to list items missing visuals")
+    })?;
+
+    rows.iter()
+        .map(|row| {
+            Ok(OutdatedVisuals {
+                sha256: row.try_get("sha256")?,
+                mime_type: row.try_get("mime_type")?,
+                thumbnails_outdated: true,
+                frames_outdated: row.try_get("frames_outdated")?,
+                path: row_disk_path(row)?,
+                duration: row.try_get("duration")?,
+                video_tracks: row.try_get("video_tracks")?,
+            })
+        })
+        .collect::<Result<Vec<_>, sqlx::Error>>()
+        .map_err(|err| {
+            tracing::error!(error = %err, "failed to parse items missing visuals");
+            ApiError::internal("Failed to list items missing visuals")
+        })
+}
+
+/// How many items [`get_missing_visuals`] would page through.
+pub(crate) async fn count_missing_visuals(conn: &mut sqlx::SqliteConnection) -> ApiResult<i64> {
+    sqlx::query_scalar(sqlx::AssertSqlSafe(format!(
+        "SELECT COUNT(*) FROM items WHERE {MISSING_VISUALS_CONDITIONS}"
+    )))
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(|err| {
+        tracing::error!(error = %err, "failed to count items missing visuals");
+        ApiError::internal("Failed to count items missing visuals")
+    })
+}
+
+/// How many items have visuals older than the given process versions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, ToSchema)]
+pub(crate) struct OutdatedVisualsCount {
+    /// Items with outdated thumbnails or frames.
+    pub items: i64,
+    pub thumbnails: i64,
+    pub frames: i64,
+}
+
+pub(crate) async fn count_outdated_visuals(
+    conn: &mut sqlx::SqliteConnection,
+    thumbnail_version: i64,
+    frame_version: i64,
+) -> ApiResult<OutdatedVisualsCount> {
+    let (items, thumbnails, frames): (i64, i64, i64) = sqlx::query_as(
+        r#"
+WITH
+    thumbnails AS (
+        SELECT DISTINCT item_sha256
+        FROM storage.thumbnails
+        JOIN items ON items.sha256 = item_sha256
+        WHERE version < ?1
+    ),
+    frames AS (
+        SELECT DISTINCT item_sha256
+        FROM storage.frames
+        JOIN items ON items.sha256 = item_sha256
+        WHERE version < ?2
+    )
+SELECT
+    (SELECT COUNT(*) FROM (SELECT item_sha256 FROM thumbnails UNION SELECT item_sha256 FROM frames)),
+    (SELECT COUNT(*) FROM thumbnails),
+    (SELECT COUNT(*) FROM frames)
         "#,
     )
+    .bind(thumbnail_version)
+    .bind(frame_version)
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(|err| {
+        tracing::error!(error = %err, "failed to count outdated visuals");
+        ApiError::internal("Failed to count outdated visuals")
+    })?;
+
+    Ok(OutdatedVisualsCount {
+        items,
+        thumbnails,
+        frames,
+    })
+}
+
+pub(crate) async fn delete_orphaned_thumbnails(
+    conn: &mut sqlx::SqliteConnection,
+    dir: &Path,
+) -> ApiResult<VisualsWrite> {
+    delete_orphaned_visuals(conn, dir, VisualTable::Thumbnails).await
+}
+
+pub(crate) async fn delete_orphaned_frames(
+    conn: &mut sqlx::SqliteConnection,
+    dir: &Path,
+) -> ApiResult<VisualsWrite> {
+    delete_orphaned_visuals(conn, dir, VisualTable::Frames).await
+}
+
+async fn delete_orphaned_visuals(
+    conn: &mut sqlx::SqliteConnection,
+    dir: &Path,
+    table: VisualTable,
+) -> ApiResult<VisualsWrite> {
+    let name = table.name();
+    let delete_error = || ApiError::internal(format!("Failed to delete orphaned {name}"));
+    let orphaned_files: Vec<(String, i64)> = sqlx::query_as(sqlx::AssertSqlSafe(format!(
+        "SELECT storage.{name}.item_sha256, storage.{name}.idx \
+         FROM storage.{name} \
+         LEFT JOIN items ON storage.{name}.item_sha256 = items.sha256 \
+         WHERE items.sha256 IS NULL AND length(storage.{name}.{}) = 0",
+        table.blob_column()
+    )))
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|err| {
+        tracing::error!(error = %err, table = name, "failed to read orphaned visuals");
+        delete_error()
+    })?;
+
+    let result = sqlx::query(sqlx::AssertSqlSafe(format!(
+        "DELETE FROM storage.{name} \
+         WHERE item_sha256 IN ( \
+             SELECT storage.{name}.item_sha256 \
+             FROM storage.{name} \
+             LEFT JOIN items ON storage.{name}.item_sha256 = items.sha256 \
+             WHERE items.sha256 IS NULL \
+         )"
+    )))
     .execute(&mut *conn)
     .await
     .map_err(|err| {
-        tracing::error!(error = %err, "failed to delete orphaned thumbnails");
-        ApiError::internal("Failed to delete orphaned thumbnails")
+        tracing::error!(error = %err, table = name, "failed to delete orphaned visuals");
+        delete_error()
     })?;
 
-    Ok(result.rows_affected())
+    Ok(VisualsWrite {
+        rows: result.rows_affected(),
+        stale_files: orphaned_files
+            .into_iter()
+            .map(|(sha256, idx)| table.file_path(dir, &sha256, idx))
+            .collect(),
+    })
+}
+
+/// Deletes every stored visual of one item. File-backed rows are returned as
+/// stale files for the caller to remove once the transaction commits.
+pub(crate) async fn delete_item_visuals(
+    conn: &mut sqlx::SqliteConnection,
+    dir: &Path,
+    table: VisualTable,
+    sha256: &str,
+) -> ApiResult<VisualsWrite> {
+    let name = table.name();
+    let delete_error = |err: sqlx::Error| {
+        tracing::error!(error = %err, table = name, "failed to delete item visuals");
+        ApiError::internal(format!("Failed to delete {name}"))
+    };
+    let file_backed: Vec<i64> = sqlx::query_scalar(sqlx::AssertSqlSafe(format!(
+        "SELECT idx FROM storage.{name} WHERE item_sha256 = ?1 AND length({}) = 0",
+        table.blob_column()
+    )))
+    .bind(sha256)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(delete_error)?;
+    let result = sqlx::query(sqlx::AssertSqlSafe(format!(
+        "DELETE FROM storage.{name} WHERE item_sha256 = ?1"
+    )))
+    .bind(sha256)
+    .execute(&mut *conn)
+    .await
+    .map_err(delete_error)?;
+    Ok(VisualsWrite {
+        rows: result.rows_affected(),
+        stale_files: file_backed
+            .into_iter()
+            .map(|idx| table.file_path(dir, sha256, idx))
+            .collect(),
+    })
 }
 
-pub(crate) async fn delete_orphaned_frames(conn: &mut sqlx::SqliteConnection) -> ApiResult<u64> {
-    let result = sqlx::query(
+/// Deletes the stored frames of up to `limit` items that every setter in
+/// `setters` has processed, i.e. holds an item_data row for (placeholders
+/// included). Deleted items stop matching, so calling this until it deletes
+/// nothing covers every such item. File-backed rows are returned as stale
+/// files for the caller to remove once the transaction commits.
+pub(crate) async fn delete_processed_frames(
+    conn: &mut sqlx::SqliteConnection,
+    dir: &Path,
+    setters: &[String],
+    limit: i64,
+) -> ApiResult<VisualsWrite> {
+    let setters = serde_json::to_string(setters)
+        .map_err(|err| ApiError::internal(format!("Failed to encode setters: {err}")))?;
+    let items: Vec<String> = sqlx::query_scalar(
         r#"
-DELETE FROM storage.frames
-WHERE item_sha256 IN (
-    SELECT storage.frames.item_sha256
-    FROM storage.frames
-    LEFT JOIN items ON storage.frames.item_sha256 = items.sha256
-    WHERE items.sha256 IS NULL
+SELECT DISTINCT frames.item_sha256
+FROM storage.frames AS frames
+JOIN items ON items.sha256 = frames.item_sha256
+WHERE NOT EXISTS (
+    SELECT 1
+    FROM json_each(?1) AS wanted
+    WHERE NOT EXISTS (
+        SELECT 1
+        FROM item_data
+        JOIN setters ON setters.id = item_data.setter_id
+        WHERE item_data.item_id = items.id AND setters.name = wanted.value
+    )
 )
+LIMIT ?2
         "#,
     )
-    .execute(&mut *conn)
+    .bind(setters)
+    .bind(limit)
+    .fetch_all(&mut *conn)
     .await
     .map_err(|err| {
-        tracing::error!(error = %err, "failed to delete orphaned frames");
-        ApiError::internal("Failed to delete orphaned frames")
+        tracing::error!(error = %err, "failed to find processed frames");
+        ApiError::internal("Failed to delete frames")
     })?;
 
-    Ok(result.rows_affected())
+    let mut write = VisualsWrite {
+        rows: 0,
+        stale_files: Vec::new(),
+    };
+    for sha256 in &items {
+        let deleted = delete_item_visuals(conn, dir, VisualTable::Frames, sha256).await?;
+        write.rows += deleted.rows;
+        write.stale_files.extend(deleted.stale_files);
+    }
+    Ok(write)
+}
+
+/// Moves up to `limit` rows of `table` that are not yet in `target` there.
+/// Moving to files writes each blob out and empties it; moving to SQLite
+/// reads each file back into its row, and the files are removed once the
+/// transaction commits. A row whose file is already gone is deleted, so the
+/// next scan generates that item's visuals again. Every handled row stops
+/// matching, so calling this until it returns fewer than `limit` rows
+/// finishes the table.
+pub(crate) async fn migrate_visuals_batch(
+    conn: &mut sqlx::SqliteConnection,
+    dir: &Path,
+    table: VisualTable,
+    target: ThumbnailStorage,
+    limit: i64,
+) -> ApiResult<VisualsWrite> {
+    let name = table.name();
+    let column = table.blob_column();
+    let migrate_error = || ApiError::internal(format!("Failed to migrate {name}"));
+    let db_error = |err: sqlx::Error| {
+        tracing::error!(error = %err, table = name, "failed to migrate stored visuals");
+        migrate_error()
+    };
+
+    let mut write = VisualsWrite::default();
+    match target {
+        ThumbnailStorage::Filesystem => {
+            let rows: Vec<(i64, String, i64, Vec<u8>)> =
+                sqlx::query_as(sqlx::AssertSqlSafe(format!(
+                    "SELECT id, item_sha256, idx, {column} FROM storage.{name} \
+                     WHERE length({column}) > 0 ORDER BY id LIMIT ?1"
+                )))
+                .bind(limit)
+                .fetch_all(&mut *conn)
+                .await
+                .map_err(db_error)?;
+            for (id, sha256, idx, bytes) in rows {
+                let path = table.file_path(dir, &sha256, idx);
+                write_visual_file(&path, &bytes).await.map_err(|err| {
+                    tracing::error!(error = %err, path = %path.display(), "failed to write visual file");
+                    migrate_error()
+                })?;
+                sqlx::query(sqlx::AssertSqlSafe(format!(
+                    "UPDATE storage.{name} SET {column} = x'' WHERE id = ?1"
+                )))
+                .bind(id)
+                .execute(&mut *conn)
+                .await
+                .map_err(db_error)?;
+                write.rows += 1;
+            }
+        }
+        ThumbnailStorage::Sqlite => {
+            let rows: Vec<(i64, String, i64)> = sqlx::query_as(sqlx::AssertSqlSafe(format!(
+                "SELECT id, item_sha256, idx FROM storage.{name} \
+                 WHERE length({column}) = 0 ORDER BY id LIMIT ?1"
+            )))
+            .bind(limit)
+            .fetch_all(&mut *conn)
+            .await
+            .map_err(db_error)?;
+            for (id, sha256, idx) in rows {
+                let path = table.file_path(dir, &sha256, idx);
+                let query = match read_visual_file(&path).await? {
+                    Some(bytes) => sqlx::query(sqlx::AssertSqlSafe(format!(
+                        "UPDATE storage.{name} SET {column} = ?2 WHERE id = ?1"
+                    )))
+                    .bind(id)
+                    .bind(bytes),
+                    None => sqlx::query(sqlx::AssertSqlSafe(format!(
+                        "DELETE FROM storage.{name} WHERE id = ?1"
+                    )))
+                    .bind(id),
+                };
+                query.execute(&mut *conn).await.map_err(db_error)?;
+                write.rows += 1;
+                write.stale_files.push(path);
+            }
+        }
+    }
+    Ok(write)
+}
+
+/// Rows of `table` not yet stored in `target`.
+pub(crate) async fn count_visuals_to_migrate(
+    conn: &mut sqlx::SqliteConnection,
+    table: VisualTable,
+    target: ThumbnailStorage,
+) -> ApiResult<i64> {
+    let name = table.name();
+    let condition = match target {
+        ThumbnailStorage::Filesystem => "> 0",
+        ThumbnailStorage::Sqlite => "= 0",
+    };
+    sqlx::query_scalar(sqlx::AssertSqlSafe(format!(
+        "SELECT COUNT(*) FROM storage.{name} WHERE length({}) {condition}",
+        table.blob_column()
+    )))
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(|err| {
+        tracing::error!(error = %err, table = name, "failed to count visuals to migrate");
+        ApiError::internal(format!("Failed to read {name}"))
+    })
 }
 
 #[cfg(test)]
@@ -307,10 +987,12 @@ VALUES
         .await
         .unwrap();
 
-        let deleted = delete_orphaned_thumbnails(&mut dbs.index_conn)
+        let dir = tempfile::tempdir().unwrap();
+        let deleted = delete_orphaned_thumbnails(&mut dbs.index_conn, dir.path())
             .await
             .unwrap();
-        assert_eq!(deleted, 1);
+        assert_eq!(deleted.rows, 1);
+        assert!(deleted.stale_files.is_empty());
     }
 
     // Ensures storage cleanup removes frames that no longer have corresponding items.
@@ -338,7 +1020,107 @@ VALUES
         .await
         .unwrap();
 
-        let deleted = delete_orphaned_frames(&mut dbs.index_conn).await.unwrap();
-        assert_eq!(deleted, 1);
+        let dir = tempfile::tempdir().unwrap();
+        let deleted = delete_orphaned_frames(&mut dbs.index_conn, dir.path())
+            .await
+            .unwrap();
+        assert_eq!(deleted.rows, 1);
+    }
+
+    fn image(idx: i64, bytes: &[u8]) -> StoredImage {
+        StoredImage {
+            idx,
+            width: 10,
+            height: 10,
+            bytes: bytes.to_vec(),
+        }
+    }
+
+    async fn blob_lengths(conn: &mut sqlx::SqliteConnection) -> Vec<(i64, i64)> {
+        sqlx::query_as("SELECT idx, length(thumbnail) FROM storage.thumbnails ORDER BY idx")
+            .fetch_all(conn)
+            .await
+            .unwrap()
+    }
+
+    // Thumbnails stored as files keep their metadata row with an empty
+    // blob; reads find the file, and a re-store with fewer thumbnails, an
+    // orphan cleanup and a migration in either direction all leave exactly
+    // the files the rows point at.
+    #[tokio::test]
+    async fn filesystem_visuals_round_trip_through_rows_and_files() {
+        let mut dbs = setup_test_databases().await;
+        let conn = &mut dbs.index_conn;
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        let files = ThumbnailStorage::Filesystem;
+        let sha = "abcdef";
+
+        let written = store_thumbnails(
+            conn,
+            dir,
+            files,
+            sha,
+            "video/mp4",
+            1,
+            &[image(0, b"big"), image(1, b"small")],
+        )
+        .await
+        .unwrap();
+        assert!(written.stale_files.is_empty());
+        let path = VisualTable::Thumbnails.file_path(dir, sha, 1);
+        assert_eq!(path, dir.join("thumbnails/ab/cd/abcdef_1.jpg"));
+        assert_eq!(std::fs::read(&path).unwrap(), b"small");
+        assert_eq!(blob_lengths(conn).await, vec![(0, 0), (1, 0)]);
+        assert_eq!(
+            get_thumbnail_bytes(conn, dir, sha, 1).await.unwrap(),
+            Some(b"small".to_vec())
+        );
+
+        // Index 1 is dropped by the re-store; its file is reported stale.
+        let written = store_thumbnails(conn, dir, files, sha, "video/mp4", 1, &[image(0, b"new")])
+            .await
+            .unwrap();
+        assert_eq!(written.stale_files, vec![path]);
+        remove_visual_files(&written.stale_files).await;
+        assert_eq!(
+            get_thumbnail_bytes(conn, dir, sha, 0).await.unwrap(),
+            Some(b"new".to_vec())
+        );
+        assert_eq!(get_thumbnail_bytes(conn, dir, sha, 1).await.unwrap(), None);
+
+        // Back into SQLite, then out to files again.
+        let moved = migrate_visuals_batch(
+            conn,
+            dir,
+            VisualTable::Thumbnails,
+            ThumbnailStorage::Sqlite,
+            10,
+        )
+        .await
+        .unwrap();
+        assert_eq!(moved.rows, 1);
+        remove_visual_files(&moved.stale_files).await;
+        assert_eq!(blob_lengths(conn).await, vec![(0, 3)]);
+        assert!(!VisualTable::Thumbnails.file_path(dir, sha, 0).exists());
+        let moved = migrate_visuals_batch(conn, dir, VisualTable::Thumbnails, files, 10)
+            .await
+            .unwrap();
+        assert_eq!(moved.rows, 1);
+        assert_eq!(blob_lengths(conn).await, vec![(0, 0)]);
+        assert_eq!(
+            count_visuals_to_migrate(conn, VisualTable::Thumbnails, files)
+                .await
+                .unwrap(),
+            0
+        );
+
+        // The item never existed, so the row is an orphan.
+        let deleted = delete_orphaned_thumbnails(conn, dir).await.unwrap();
+        assert_eq!(deleted.rows, 1);
+        assert_eq!(
+            deleted.stale_files,
+            vec![VisualTable::Thumbnails.file_path(dir, sha, 0)]
+        );
     }
 }
@@ -0,0 +1,375 @@
+//! Disk usage of an index DB's derived data: embedding and extracted text
+//! bytes per setter, thumbnail and frame bytes (blobs in `storage.db` plus
+//! file-backed visuals), and the database files themselves. The byte counts
+//! scan every blob, so `GET /api/search/stats/storage` serves a snapshot that
+//! maintenance runs and `POST` to the same path refresh.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use utoipa::ToSchema;
+
+use crate::api_error::ApiError;
+use crate::db::connection::index_storage_paths_unchecked;
+use crate::db::extraction_write::current_iso_timestamp;
+use crate::db::index_writer::{IndexDbWriterMessage, call_index_db_writer};
+use crate::db::info::wal_path;
+use crate::db::open_index_db_read_no_user_data;
+use crate::db::storage::{VisualTable, visuals_dir};
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+fn internal(context: &'static str) -> impl Fn(sqlx::Error) -> ApiError {
+    move |err| {
+        tracing::error!(error = %err, context, "storage stats query failed");
+        ApiError::internal(context)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub(crate) struct DataSize {
+    pub count: i64,
+    pub bytes: i64,
+}
+
+/// Stored data of one setter. Text bytes are the UTF-8 size of the text
+/// itself; the full-text index over it is not included.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub(crate) struct SetterStorage {
+    pub setter_name: String,
+    pub embeddings: DataSize,
+    pub extracted_text: DataSize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub(crate) struct VisualStorage {
+    /// Rows, whether the image is a blob or a file.
+    pub count: i64,
+    /// Bytes of images stored as blobs in `storage.db`.
+    pub blob_bytes: i64,
+    /// Bytes of image files under the index DB's folder.
+    pub file_bytes: i64,
+}
+
+/// Bytes on disk of the index DB's files; a missing file counts as 0.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub(crate) struct DbFileSizes {
+    pub index_db: i64,
+    pub index_db_wal: i64,
+    pub storage_db: i64,
+    pub storage_db_wal: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub(crate) struct StorageStats {
+    /// When the data sizes were computed.
+    pub computed_at: String,
+    /// By setter name.
+    pub setters: Vec<SetterStorage>,
+    pub thumbnails: VisualStorage,
+    pub frames: VisualStorage,
+    /// Always current, also when the rest comes from the snapshot.
+    pub files: DbFileSizes,
+}
+
+fn file_len(path: &Path) -> i64 {
+    std::fs::metadata(path)
+        .map(|metadata| metadata.len() as i64)
+        .unwrap_or(0)
+}
+
+pub(crate) fn db_file_sizes(index_db: &str) -> DbFileSizes {
+    let paths = index_storage_paths_unchecked(index_db);
+    DbFileSizes {
+        index_db: file_len(&paths.index_db_file),
+        index_db_wal: file_len(&wal_path(&paths.index_db_file)),
+        storage_db: file_len(&paths.storage_db_file),
+        storage_db_wal: file_len(&wal_path(&paths.storage_db_file)),
+    }
+}
+
+/// Count and byte size per setter name of one derived data table.
+async fn setter_sizes(
+    conn: &mut sqlx::SqliteConnection,
+    sql: &str,
+) -> ApiResult<Vec<(String, DataSize)>> {
+    let rows = sqlx::query(sqlx::AssertSqlSafe(sql))
+        .fetch_all(conn)
+        .await
+        .map_err(internal("Failed to measure stored data"))?;
+    rows.iter()
+        .map(|row| {
+            Ok((
+                row.try_get("setter_name")?,
+                DataSize {
+                    count: row.try_get("count")?,
+                    bytes: row.try_get("bytes")?,
+                },
+            ))
+        })
+        .collect::<Result<Vec<_>, sqlx::Error>>()
+        .map_err(internal("Failed to measure stored data"))
+}
+
+fn setter_entry(setters: &mut BTreeMap<String, SetterStorage>, name: String) -> &mut SetterStorage {
+    setters
+        .entry(name.clone())
+        .or_insert_with(|| SetterStorage {
+            setter_name: name,
+            embeddings: DataSize::default(),
+            extracted_text: DataSize::default(),
+        })
+}
+
+pub(crate) async fn get_setter_storage(
+    conn: &mut sqlx::SqliteConnection,
+) -> ApiResult<Vec<SetterStorage>> {
+    let embeddings = setter_sizes(
+        conn,
+        r#"
+        SELECT
+            setters.name AS setter_name,
+            COUNT(*) AS count,
+            COALESCE(SUM(length(embeddings.embedding)), 0) AS bytes
+        FROM embeddings
+        JOIN item_data ON item_data.id = embeddings.id
+        JOIN setters ON setters.id = item_data.setter_id
+        GROUP BY setters.name
+        "#,
+    )
+    .await?;
+    let text = setter_sizes(
+        conn,
+        r#"
+        SELECT
+            setters.name AS setter_name,
+            COUNT(*) AS count,
+            COALESCE(SUM(length(CAST(extracted_text.text AS BLOB))), 0) AS bytes
+        FROM extracted_text
+        JOIN item_data ON item_data.id = extracted_text.id
+        JOIN setters ON setters.id = item_data.setter_id
+        GROUP BY setters.name
+        "#,
+    )
+    .await?;
+
+    let mut setters: BTreeMap<String, SetterStorage> = BTreeMap::new();
+    for (name, size) in embeddings {
+        setter_entry(&mut setters, name).embeddings = size;
+    }
+    for (name, size) in text {
+        setter_entry(&mut setters, name).extracted_text = size;
+    }
+    Ok(setters.into_values().collect())
+}
+
+/// Row count and blob bytes of one visuals table; `file_bytes` is left 0.
+pub(crate) async fn get_visual_storage(
+    conn: &mut sqlx::SqliteConnection,
+    table: VisualTable,
+) -> ApiResult<VisualStorage> {
+    let sql = format!(
+        "SELECT COUNT(*) AS count, COALESCE(SUM(length({column})), 0) AS bytes FROM storage.{name}",
+        column = table.blob_column(),
+        name = table.name(),
+    );
+    let row = sqlx::query(sqlx::AssertSqlSafe(sql))
+        .fetch_one(conn)
+        .await
+        .map_err(internal("Failed to measure stored visuals"))?;
+    Ok(VisualStorage {
+        count: row
+            .try_get("count")
+            .map_err(internal("Failed to measure stored visuals"))?,
+        blob_bytes: row
+            .try_get("bytes")
+            .map_err(internal("Failed to measure stored visuals"))?,
+        file_bytes: 0,
+    })
+}
+
+/// Total size of the files under `dir`, 0 when it does not exist.
+fn dir_size(dir: &Path) -> i64 {
+    walkdir::WalkDir::new(dir)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| entry.metadata().ok())
+        .map(|metadata| metadata.len() as i64)
+        .sum()
+}
+
+/// Scans every blob (and walks the visuals folders) of `index_db`: slow on
+/// large DBs.
+pub(crate) async fn compute_storage_stats(
+    conn: &mut sqlx::SqliteConnection,
+    index_db: &str,
+) -> ApiResult<StorageStats> {
+    let setters = get_setter_storage(conn).await?;
+    let mut thumbnails = get_visual_storage(conn, VisualTable::Thumbnails).await?;
+    let mut frames = get_visual_storage(conn, VisualTable::Frames).await?;
+    let dir = visuals_dir(index_db);
+    let (thumbnail_files, frame_files) = tokio::task::spawn_blocking(move || {
+        (
+            dir_size(&dir.join(VisualTable::Thumbnails.name())),
+            dir_size(&dir.join(VisualTable::Frames.name())),
+        )
+    })
+    .await
+    .map_err(|err| ApiError::internal(format!("Failed to measure visual files: {err}")))?;
+    thumbnails.file_bytes = thumbnail_files;
+    frames.file_bytes = frame_files;
+    Ok(StorageStats {
+        computed_at: current_iso_timestamp(),
+        setters,
+        thumbnails,
+        frames,
+        files: db_file_sizes(index_db),
+    })
+}
+
+/// Computes the report live and stores it as the DB's snapshot. Storing is
+/// best-effort (the DB may be read-only); the report is returned either way.
+pub(crate) async fn compute_and_store_storage_stats(
+    conn: &mut sqlx::SqliteConnection,
+    index_db: &str,
+) -> ApiResult<StorageStats> {
+    let stats = compute_storage_stats(conn, index_db).await?;
+    let result = match serde_json::to_string(&stats) {
+        Ok(json) => {
+            call_index_db_writer(index_db, |reply| {
+                IndexDbWriterMessage::StoreStorageStatsSnapshot {
+                    computed_at: stats.computed_at.clone(),
+                    report: json.clone(),
+                    reply,
+                }
+            })
+            .await
+        }
+        Err(err) => Err(ApiError::internal(format!(
+            "Failed to serialize storage stats: {err}"
+        ))),
+    };
+    if let Err(err) = result {
+        tracing::warn!(error = ?err, index_db, "failed to store storage stats snapshot");
+    }
+    Ok(stats)
+}
+
+/// Refreshes the snapshot after a maintenance run. Best-effort: a failure is
+/// logged and leaves the previous snapshot in place.
+pub(crate) async fn refresh_storage_stats_snapshot(index_db: &str) {
+    let result = async {
+        let mut conn = open_index_db_read_no_user_data(index_db).await?;
+        compute_and_store_storage_stats(&mut conn, index_db).await
+    }
+    .await;
+    if let Err(err) = result {
+        tracing::warn!(error = ?err, index_db, "failed to refresh storage stats snapshot");
+    }
+}
+
+/// The stored snapshot with current `files` sizes, if one was ever computed.
+pub(crate) async fn get_storage_stats_snapshot(
+    conn: &mut sqlx::SqliteConnection,
+    index_db: &str,
+) -> ApiResult<Option<StorageStats>> {
+    let row = sqlx::query("SELECT report FROM storage_stats_snapshot WHERE id = 1")
+        .fetch_optional(conn)
+        .await
+        .map_err(internal("Failed to load storage stats snapshot"))?;
+    let Some(row) = row else {
+        return Ok(None);
+    };
+    let report: String = row
+        .try_get("report")
+        .map_err(internal("Failed to load storage stats snapshot"))?;
+    let mut stats: StorageStats = serde_json::from_str(&report).map_err(|err| {
+        tracing::error!(error = %err, "stored storage stats snapshot is unreadable");
+        ApiError::internal("Failed to load storage stats snapshot")
+    })?;
+    stats.files = db_file_sizes(index_db);
+    Ok(Some(stats))
+}
+
+pub(crate) async fn store_storage_stats_snapshot(
+    conn: &mut sqlx::SqliteConnection,
+    computed_at: &str,
+    report: &str,
+) -> ApiResult<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO storage_stats_snapshot (id, computed_at, report)
+        VALUES (1, ?, ?)
+        ON CONFLICT(id) DO UPDATE SET
+            computed_at = excluded.computed_at,
+            report = excluded.report
+        "#,
+    )
+    .bind(computed_at)
+    .bind(report)
+    .execute(conn)
+    .await
+    .map_err(internal("Failed to store storage stats snapshot"))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::migrations::setup_test_databases;
+
+    // Ensures embedding and text bytes are summed per setter and visuals per
+    // table, with setters sorted by name.
+    #[tokio::test]
+    async fn sizes_are_summed_per_setter_and_table() {
+        let mut dbs = setup_test_databases().await;
+        for statement in [
+            r#"INSERT INTO items (id, sha256, md5, type, time_added) VALUES
+                (1, 'sha_1', 'md5_1', 'image/png', '2024-01-01T00:00:00')"#,
+            "INSERT INTO setters (id, name) VALUES (1, 'ocr'), (2, 'clip')",
+            r#"INSERT INTO item_data (id, item_id, setter_id, data_type, idx, is_origin) VALUES
+                (1, 1, 1, 'text', 0, 1),
+                (2, 1, 1, 'text', 1, 1),
+                (3, 1, 2, 'clip', 0, 1)"#,
+            "INSERT INTO extracted_text (id, text) VALUES (1, 'abc'), (2, 'héllo')",
+            "INSERT INTO embeddings (id, embedding) VALUES (3, x'0000803f00000040')",
+            r#"INSERT INTO storage.thumbnails
+                (item_sha256, idx, item_mime_type, width, height, version, thumbnail)
+                VALUES ('sha_1', 0, 'image/png', 1, 1, 1, x'010203'),
+                       ('sha_1', 1, 'image/png', 1, 1, 1, x'')"#,
+        ] {
+            sqlx::query(statement)
+                .execute(&mut dbs.index_conn)
+                .await
+                .unwrap();
+        }
+
+        let setters = get_setter_storage(&mut dbs.index_conn).await.unwrap();
+        assert_eq!(
+            setters,
+            vec![
+                SetterStorage {
+                    setter_name: "clip".to_string(),
+                    embeddings: DataSize { count: 1, bytes: 8 },
+                    extracted_text: DataSize::default(),
+                },
+                SetterStorage {
+                    setter_name: "ocr".to_string(),
+                    embeddings: DataSize::default(),
+                    extracted_text: DataSize { count: 2, bytes: 9 },
+                },
+            ]
+        );
+        let thumbnails = get_visual_storage(&mut dbs.index_conn, VisualTable::Thumbnails)
+            .await
+            .unwrap();
+        assert_eq!((thumbnails.count, thumbnails.blob_bytes), (2, 3));
+        let frames = get_visual_storage(&mut dbs.index_conn, VisualTable::Frames)
+            .await
+            .unwrap();
+        assert_eq!(frames, VisualStorage::default());
+    }
+}
@@ -0,0 +1,307 @@
+//! Prefix lookups behind search box suggestions. Each source returns at most
+//! `limit` matches, best first; matching is case-insensitive.
+
+use std::collections::HashMap;
+
+use sqlx::Row;
+
+use crate::api_error::ApiError;
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+/// Filenames read per path token lookup; tokens are ranked by how many of
+/// them contain the token.
+const FILENAME_SAMPLE: i64 = 500;
+
+fn internal(context: &'static str) -> impl Fn(sqlx::Error) -> ApiError {
+    move |err| {
+        tracing::error!(error = %err, "{context}");
+        ApiError::internal(context)
+    }
+}
+
+/// `LIKE` pattern matching lowercased values that start with `prefix`
+/// (connections enable `case_sensitive_like`).
+fn like_prefix(prefix: &str) -> String {
+    let mut pattern = String::with_capacity(prefix.len() + 1);
+    for ch in prefix.to_lowercase().chars() {
+        if matches!(ch, '\\' | '%' | '_') {
+            pattern.push('\\');
+        }
+        pattern.push(ch);
+    }
+    pattern.push('%');
+    pattern
+}
+
+/// Tags whose name starts with `prefix` as (namespace, name, times applied),
+/// most applied first. Per-frame video tags don't add to the count.
+pub(crate) async fn tag_suggestions(
+    conn: &mut sqlx::SqliteConnection,
+    prefix: &str,
+    limit: i64,
+) -> ApiResult<Vec<(String, String, i64)>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT tags.namespace AS namespace, tags.name AS name, COUNT(item_data.id) AS count
+        FROM tags
+        LEFT JOIN tags_items ON tags_items.tag_id = tags.id
+        LEFT JOIN item_data ON item_data.id = tags_items.item_data_id AND item_data.idx = 0
+        WHERE lower(tags.name) LIKE ? ESCAPE '\'
+        GROUP BY tags.id
+        ORDER BY count DESC, tags.name, tags.namespace
+        LIMIT ?
+        "#,
+    )
+    .bind(like_prefix(prefix))
+    .bind(limit)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(internal("Failed to get tag suggestions"))?;
+
+    rows.iter()
+        .map(|row| {
+            Ok((
+                row.try_get("namespace")?,
+                row.try_get("name")?,
+                row.try_get("count")?,
+            ))
+        })
+        .collect::<Result<Vec<_>, sqlx::Error>>()
+        .map_err(internal("Failed to get tag suggestions"))
+}
+
+/// Words of file names starting with `prefix` as (word, files), most common
+/// first. Words are split on anything that isn't a letter or digit, so
+/// `IMG_beach-2023.jpg` offers `beach`. Queries of three or more characters
+/// use the trigram path index; shorter ones only look at file names that
+/// start with `prefix`.
+pub(crate) async fn filename_token_suggestions(
+    conn: &mut sqlx::SqliteConnection,
+    prefix: &str,
+    limit: i64,
+) -> ApiResult<Vec<(String, i64)>> {
+    let query = if prefix.chars().count() >= 3 {
+        sqlx::query(
+            r#"
+            SELECT files.filename AS filename
+            FROM files_path_fts
+            JOIN files ON files.id = files_path_fts.rowid
+            WHERE files_path_fts.filename MATCH ?
+            LIMIT ?
+            "#,
+        )
+        .bind(format!("\"{}\"", prefix.replace('"', "\"\"")))
+    } else {
+        sqlx::query(
+            r#"
+            SELECT filename
+            FROM files
+            WHERE lower(filename) LIKE ? ESCAPE '\'
+            LIMIT ?
+            "#,
+        )
+        .bind(like_prefix(prefix))
+    };
+    let filenames: Vec<String> = query
+        .bind(FILENAME_SAMPLE)
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(internal("Failed to get path suggestions"))?
+        .iter()
+        .map(|row| row.try_get("filename"))
+        .collect::<Result<_, _>>()
+        .map_err(internal("Failed to get path suggestions"))?;
+
+    let prefix = prefix.to_lowercase();
+    let mut counts: HashMap<String, i64> = HashMap::new();
+    for filename in &filenames {
+        let mut seen = Vec::new();
+        for token in filename.split(|ch: char| !ch.is_alphanumeric()) {
+            let token = token.to_lowercase();
+            if token.starts_with(&prefix) && !seen.contains(&token) {
+                seen.push(token);
+            }
+        }
+        for token in seen {
+            *counts.entry(token).or_default() += 1;
+        }
+    }
+    let mut tokens: Vec<(String, i64)> = counts.into_iter().collect();
+    tokens.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    tokens.truncate(usize::try_from(limit).unwrap_or(0));
+    Ok(tokens)
+}
+
+/// The user's (and shared `*`) bookmark namespaces starting with `prefix`.
+pub(crate) async fn bookmark_namespace_suggestions(
+    conn: &mut sqlx::SqliteConnection,
+    user: &str,
+    prefix: &str,
+    limit: i64,
+) -> ApiResult<Vec<String>> {
+    sqlx::query_scalar(
+        r#"
+        SELECT DISTINCT namespace
+        FROM user_data.bookmarks
+        WHERE (user = ? OR user = '*')
+          AND lower(namespace) LIKE ? ESCAPE '\'
+        ORDER BY namespace
+        LIMIT ?
+        "#,
+    )
+    .bind(user)
+    .bind(like_prefix(prefix))
+    .bind(limit)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(internal("Failed to get bookmark namespace suggestions"))
+}
+
+/// Names of the user's saved queries starting with `prefix`.
+pub(crate) async fn saved_query_suggestions(
+    conn: &mut sqlx::SqliteConnection,
+    user: &str,
+    prefix: &str,
+    limit: i64,
+) -> ApiResult<Vec<String>> {
+    sqlx::query_scalar(
+        r#"
+        SELECT name
+        FROM user_data.saved_queries
+        WHERE user = ?
+          AND lower(name) LIKE ? ESCAPE '\'
+        ORDER BY name
+        LIMIT ?
+        "#,
+    )
+    .bind(user)
+    .bind(like_prefix(prefix))
+    .bind(limit)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(internal("Failed to get saved query suggestions"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::file_scans::add_file_scan;
+    use crate::db::migrations::setup_test_databases;
+
+    async fn seed(conn: &mut sqlx::SqliteConnection) {
+        let scan_id = add_file_scan(conn, "2024-01-01T00:00:00", "/data")
+            .await
+            .unwrap();
+        sqlx::query(
+            r#"
+INSERT INTO items (id, sha256, md5, type, time_added) VALUES
+    (1, 'sha1', 'md51', 'image/png', '2024-01-01T00:00:00'),
+    (2, 'sha2', 'md52', 'image/png', '2024-01-01T00:00:00'),
+    (3, 'sha3', 'md53', 'image/png', '2024-01-01T00:00:00');
+INSERT INTO setters (id, name) VALUES (1, 'tagger');
+INSERT INTO item_data (id, item_id, setter_id, data_type, idx, is_origin) VALUES
+    (1, 1, 1, 'tags', 0, 1), (2, 2, 1, 'tags', 0, 1);
+INSERT INTO tags (id, namespace, name) VALUES
+    (1, 'danbooru', 'cat'), (2, 'danbooru', 'catgirl'), (3, 'danbooru', 'dog'),
+    (4, 'danbooru', 'c_at');
+INSERT INTO tags_items (item_data_id, tag_id, confidence) VALUES
+    (1, 2, 1.0), (2, 2, 1.0), (1, 1, 1.0);
+            "#,
+        )
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+        for (id, filename) in [
+            (1, "IMG_beach-2023.jpg"),
+            (2, "Beach_Day.png"),
+            (3, "beacon_notes.txt"),
+        ] {
+            sqlx::query(
+                r#"
+INSERT INTO files (sha256, item_id, path, filename, last_modified, scan_id, available)
+VALUES (?1, ?2, '/data/' || ?3, ?3, '2024-01-01T00:00:00', ?4, 1)
+                "#,
+            )
+            .bind(format!("sha{id}"))
+            .bind(id)
+            .bind(filename)
+            .bind(scan_id)
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        }
+    }
+
+    // Ensures tag suggestions are prefix matches ranked by use, with LIKE
+    // wildcards in the query taken literally.
+    #[tokio::test]
+    async fn tags_match_by_prefix_most_used_first() {
+        let mut dbs = setup_test_databases().await;
+        let conn = &mut dbs.index_conn;
+        seed(conn).await;
+
+        let tags = tag_suggestions(conn, "CA", 10).await.unwrap();
+        assert_eq!(
+            tags,
+            vec![
+                ("danbooru".to_string(), "catgirl".to_string(), 2),
+                ("danbooru".to_string(), "cat".to_string(), 1),
+            ]
+        );
+        let literal = tag_suggestions(conn, "c_", 10).await.unwrap();
+        assert_eq!(literal.len(), 1);
+        assert_eq!(literal[0].1, "c_at");
+    }
+
+    // Ensures file name words are offered for both the trigram index and the
+    // short-query fallback, counted once per file.
+    #[tokio::test]
+    async fn filename_words_are_ranked_by_file_count() {
+        let mut dbs = setup_test_databases().await;
+        let conn = &mut dbs.index_conn;
+        seed(conn).await;
+
+        let tokens = filename_token_suggestions(conn, "bea", 10).await.unwrap();
+        assert_eq!(
+            tokens,
+            vec![("beach".to_string(), 2), ("beacon".to_string(), 1)]
+        );
+        let short = filename_token_suggestions(conn, "be", 10).await.unwrap();
+        assert_eq!(
+            short,
+            vec![("beach".to_string(), 1), ("beacon".to_string(), 1)]
+        );
+    }
+
+    // Ensures bookmark namespaces include shared ones and saved queries stay
+    // per user.
+    #[tokio::test]
+    async fn user_data_sources_match_by_prefix() {
+        let mut dbs = setup_test_databases().await;
+        let conn = &mut dbs.index_conn;
+        sqlx::query(
+            r#"
+INSERT INTO user_data.bookmarks (user, namespace, sha256, time_added) VALUES
+    ('user', 'favorites', 'sha1', '2024-01-01T00:00:00'),
+    ('*', 'family', 'sha1', '2024-01-01T00:00:00'),
+    ('other', 'fanart', 'sha1', '2024-01-01T00:00:00');
+INSERT INTO user_data.saved_queries (user, name, query, time_added, time_updated) VALUES
+    ('user', 'Favorite cats', '{}', '2024-01-01T00:00:00', '2024-01-01T00:00:00'),
+    ('other', 'Fast cars', '{}', '2024-01-01T00:00:00', '2024-01-01T00:00:00');
+            "#,
+        )
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+
+        let namespaces = bookmark_namespace_suggestions(conn, "user", "fa", 10)
+            .await
+            .unwrap();
+        assert_eq!(namespaces, vec!["family", "favorites"]);
+        let saved = saved_query_suggestions(conn, "user", "fa", 10)
+            .await
+            .unwrap();
+        assert_eq!(saved, vec!["Favorite cats"]);
+    }
+}
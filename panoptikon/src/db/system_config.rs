@@ -8,7 +8,7 @@ use std::{
 use utoipa::ToSchema;
 
 use crate::api_error::ApiError;
-use crate::pql::model::{JobFilter, Match};
+use crate::pql::model::{Column, JobFilter, Match};
 
 type ApiResult<T> = std::result::Result<T, ApiError>;
 
@@ -30,6 +30,16 @@ pub(crate) struct JobSettings {
     pub default_batch_size: Option<i64>,
     #[serde(default)]
     pub default_threshold: Option<f64>,
+    /// Inference IDs to enqueue after a successful extraction job of this
+    /// group or model. A model's own entry wins over its group's; a
+    /// `follow_up` on the enqueue request wins over both.
+    #[serde(default)]
+    pub follow_up: Vec<String>,
+    /// Tagging models: also store each video frame's tags, next to the
+    /// merged set. Unset keeps the model's `per_frame_tags` metadata; a
+    /// model's own entry wins over its group's.
+    #[serde(default)]
+    pub per_frame_tags: Option<bool>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
@@ -75,10 +85,189 @@ pub(crate) fn effective_vector_quants(config: &SystemConfig) -> VectorQuantsConf
         .unwrap_or_else(VectorQuantsConfig::builtin_default)
 }
 
+/// Normalization applied to extracted text before it is indexed for
+/// full-text search, and identically to `match_text` queries. The raw text
+/// stays in `extracted_text.text`; only the FTS index sees the normalized
+/// form. Every option defaults to off. Case folding is not an option: the
+/// trigram tokenizer already matches case-insensitively.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, ToSchema)]
+pub(crate) struct TextNormalizationConfig {
+    /// Unicode NFKC (fullwidth forms, ligatures, compatibility characters).
+    #[serde(default)]
+    pub nfkc: bool,
+    /// Drop control and invisible format characters (soft hyphens,
+    /// zero-width spaces, bidi marks); tabs and newlines are kept.
+    #[serde(default)]
+    pub strip_control: bool,
+    /// Collapse whitespace runs to a single space and trim the ends.
+    #[serde(default)]
+    pub collapse_whitespace: bool,
+    /// Map typographic dashes and quotes to their ASCII forms.
+    #[serde(default)]
+    pub ascii_punctuation: bool,
+}
+
+impl TextNormalizationConfig {
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.nfkc || self.strip_control || self.collapse_whitespace || self.ascii_punctuation
+    }
+}
+
+/// Automatic pruning of the extraction history (`data_log`), applied after
+/// every job and by `POST /api/jobs/data/history/prune`. A log is pruned
+/// once it falls outside either limit; logs whose job still owns item_data
+/// are always kept. Both limits default to off.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, ToSchema)]
+pub(crate) struct ExtractionLogRetention {
+    /// Keep only the newest N logs of each setter.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep_last_per_setter: Option<u32>,
+    /// Keep only logs that started within the last D days.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_age_days: Option<u32>,
+}
+
+impl ExtractionLogRetention {
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.keep_last_per_setter.is_some() || self.max_age_days.is_some()
+    }
+}
+
+/// Deletion of stored video frames once the models that read them are done,
+/// applied at the end of every extraction job. Off while the list is empty.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, ToSchema)]
+pub(crate) struct FrameRetention {
+    /// Setter names (e.g. CLIP models and taggers). An item's frames are
+    /// deleted once every listed setter has processed it; they are
+    /// extracted from the video again if a model needs them later.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub keep_frames_until_processed_by: Vec<String>,
+}
+
+impl FrameRetention {
+    pub(crate) fn is_enabled(&self) -> bool {
+        !self.keep_frames_until_processed_by.is_empty()
+    }
+}
+
+/// How a database optimization run reclaims free pages.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum VacuumMode {
+    /// Statistics and WAL checkpoint only.
+    None,
+    /// Full `VACUUM` of index.db and storage.db. Rewrites both files, so it
+    /// is skipped when free disk space does not exceed their size.
+    #[default]
+    Full,
+    /// `PRAGMA incremental_vacuum`; only reclaims space on databases created
+    /// with `auto_vacuum = INCREMENTAL`, a no-op otherwise.
+    Incremental,
+}
+
+impl VacuumMode {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Full => "full",
+            Self::Incremental => "incremental",
+        }
+    }
+}
+
+/// Scheduled database optimization (`PRAGMA optimize`, `ANALYZE`, WAL
+/// checkpoint and optionally a vacuum), the same run as
+/// `POST /api/jobs/maintenance/optimize`. Off by default.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
+pub(crate) struct DbMaintenanceConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Cron expression, like `cron_schedule`; weekly by default.
+    #[serde(default = "default_db_maintenance_schedule")]
+    pub schedule: String,
+    #[serde(default)]
+    pub vacuum: VacuumMode,
+}
+
+impl Default for DbMaintenanceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            schedule: default_db_maintenance_schedule(),
+            vacuum: VacuumMode::default(),
+        }
+    }
+}
+
+/// A saved query run on a schedule as a `pql_report` job, its results
+/// delivered to one of the server config's `[[reports.destinations]]`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
+pub(crate) struct PqlReportSchedule {
+    /// Names the report in its payload; unique per index DB.
+    pub name: String,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Cron expression, like `cron_schedule`.
+    pub schedule: String,
+    pub saved_query: String,
+    /// The user the saved query belongs to. Default: `user`.
+    #[serde(default = "default_report_user")]
+    pub user: String,
+    /// Columns to return instead of the saved query's `select`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub select: Vec<Column>,
+    /// Most results to deliver, within `[reports] max_results`. Default: 1000.
+    #[serde(default = "default_report_max_results")]
+    pub max_results: i64,
+    /// Name of a `[[reports.destinations]]` entry.
+    pub destination: String,
+}
+
+pub(crate) fn default_report_user() -> String {
+    "user".to_string()
+}
+
+pub(crate) fn default_report_max_results() -> i64 {
+    1000
+}
+
+/// Time window in which heavy jobs (data extraction and full folder
+/// rescans) may start. Such jobs enqueued outside it stay queued until it
+/// opens, unless enqueued with `run_now`. Off by default.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
+pub(crate) struct JobWindowConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Local start and end time, `HH:MM-HH:MM`. An end at or before the
+    /// start runs past midnight into the next day.
+    #[serde(default = "default_job_window_hours")]
+    pub allowed_hours: String,
+    /// Days on which the window opens: comma-separated day names or ranges,
+    /// e.g. `mon-fri,sun`. A window past midnight belongs to the day it
+    /// opens on.
+    #[serde(default = "default_job_window_days")]
+    pub days: String,
+}
+
+impl Default for JobWindowConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            allowed_hours: default_job_window_hours(),
+            days: default_job_window_days(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub(crate) struct SystemConfig {
     #[serde(default = "default_true")]
     pub remove_unavailable_files: bool,
+    /// Days files taken out of the index by a folder update (their folder
+    /// removed or excluded) are kept, marked unavailable, so re-adding the
+    /// folder restores them with their data. 0 deletes them right away.
+    #[serde(default = "default_folder_removal_grace_days")]
+    pub folder_removal_grace_days: u64,
     #[serde(default = "default_true")]
     pub scan_images: bool,
     #[serde(default = "default_true")]
@@ -114,11 +303,87 @@ pub(crate) struct SystemConfig {
     pub prewarm_embedding_models: bool,
     #[serde(default)]
     pub continuous_filescan: ContinuousFilescanConfig,
+    /// Seconds a file's size and mtime must hold still before a scan hashes
+    /// it. Files modified more recently are re-checked once the window has
+    /// passed and deferred if still changing; 0 disables the check.
+    #[serde(default = "default_scan_settle_secs")]
+    pub scan_settle_secs: u64,
+    /// Skip directories whose name matches one of `ignored_dir_patterns`
+    /// (version control, caches, recycle bins) during full and continuous
+    /// scans. Their contents are never walked.
+    #[serde(default = "default_true")]
+    pub skip_ignored_dirs: bool,
+    /// Glob-style (`*`, `?`), case-insensitive directory name patterns,
+    /// matched against every directory below an included folder.
+    #[serde(default = "default_ignored_dir_patterns")]
+    pub ignored_dir_patterns: Vec<String>,
+    /// Honor `.panoptikonignore` files: gitignore-style patterns that skip
+    /// matching files and directories below the folder holding the file.
+    #[serde(default = "default_true")]
+    pub respect_ignore_files: bool,
+    /// Walk into symlinked files and directories during scans. Each
+    /// directory is walked once per scan, so link cycles and bind mounts of
+    /// an already scanned tree are skipped.
+    #[serde(default = "default_true")]
+    pub follow_symlinks: bool,
+    /// What to do with a file reachable through a symlink as well as its
+    /// real path inside a scanned folder.
+    #[serde(default)]
+    pub symlink_duplicates: SymlinkDuplicates,
+    /// Whether paths differing only in letter case name the same file, for
+    /// matching watcher events and rescans against indexed paths.
+    #[serde(default)]
+    pub path_case_sensitivity: PathCaseSensitivity,
+    /// Read rate cap (MB/s) for bit-rot verification jobs that don't pass
+    /// their own; 0 reads unthrottled.
+    #[serde(default = "default_verify_max_mb_per_sec")]
+    pub verify_max_mb_per_sec: f64,
+    /// Full scans skip thumbnail, frame and blurhash generation for the
+    /// files they index. The visuals are generated the first time an item's
+    /// thumbnail is requested, by the visuals regeneration job, or by the
+    /// next scan with this off.
+    #[serde(default)]
+    pub fast_scan: bool,
+    /// Full scans record each directory's modification time and entry
+    /// count, and on the next scan skip the files directly inside a
+    /// directory where both are unchanged (subdirectories are still
+    /// visited). Off by default: files edited in place, and directories on
+    /// filesystems that do not update directory times, go unnoticed.
+    #[serde(default)]
+    pub skip_unchanged_dirs: bool,
+    /// Per-folder scan concurrency. A scanned folder uses the entry with the
+    /// longest `path` containing it; folders without one scan as `ssd`.
+    #[serde(default)]
+    pub folder_scan_settings: Vec<FolderScanSettings>,
 
     /// Vector quantization desired state; absent = built-in default profile.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub vector_quants: Option<VectorQuantsConfig>,
 
+    /// Search-text normalization; changing it schedules a renormalize job.
+    #[serde(default)]
+    pub text_normalization: TextNormalizationConfig,
+
+    /// Extraction history retention; off unless a limit is set.
+    #[serde(default)]
+    pub extraction_log_retention: ExtractionLogRetention,
+
+    /// Stored frame retention; off unless setters are listed.
+    #[serde(default)]
+    pub frame_retention: FrameRetention,
+
+    /// Periodic database optimization; off unless enabled.
+    #[serde(default)]
+    pub db_maintenance: DbMaintenanceConfig,
+
+    /// Hours and days heavy jobs may start in; off unless enabled.
+    #[serde(default)]
+    pub job_window: JobWindowConfig,
+
+    /// Scheduled saved-query reports.
+    #[serde(default)]
+    pub pql_reports: Vec<PqlReportSchedule>,
+
     /// PQL job filters (parsed).
     #[serde(default)]
     pub job_filters: Vec<JobFilter>,
@@ -142,18 +407,112 @@ pub(crate) struct ContinuousFilescanConfig {
     pub included_folders: Vec<String>,
 }
 
+/// Storage hint for a scanned folder, deciding how many files a scan reads
+/// concurrently when no explicit `worker_count` is set.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum IoProfile {
+    /// Spinning disk: 2 workers, since parallel reads mostly add seeks.
+    Hdd,
+    /// Solid-state storage: one worker per CPU.
+    #[default]
+    Ssd,
+    /// Network share: 4 workers, enough to overlap round trips.
+    Network,
+    /// Probe random against sequential read latency on the folder before
+    /// each scan and use the `hdd` or `ssd` default accordingly.
+    Auto,
+}
+
+/// Handling of a file a scan reaches both through a symlink and directly.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum SymlinkDuplicates {
+    /// Index it under its real path only; links to files outside the
+    /// scanned folders are still indexed under the link path.
+    #[default]
+    Canonical,
+    /// Index it under every path that reaches it.
+    Keep,
+}
+
+/// How indexed paths are matched against the paths scans and watcher
+/// events report.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum PathCaseSensitivity {
+    /// Probe each included folder when a scan or the continuous scanner
+    /// starts; folders that can't be probed follow the platform default
+    /// (insensitive on Windows and macOS).
+    #[default]
+    Auto,
+    Sensitive,
+    Insensitive,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
+pub(crate) struct FolderScanSettings {
+    /// Folder the settings apply to, including everything below it.
+    pub path: String,
+    #[serde(default)]
+    pub io_profile: IoProfile,
+    /// Fixed number of concurrent file workers; overrides the `io_profile`
+    /// default.
+    #[serde(default)]
+    pub worker_count: Option<usize>,
+}
+
 fn default_true() -> bool {
     true
 }
 
+fn default_folder_removal_grace_days() -> u64 {
+    7
+}
+
+fn default_scan_settle_secs() -> u64 {
+    2
+}
+
+fn default_verify_max_mb_per_sec() -> f64 {
+    20.0
+}
+
+fn default_ignored_dir_patterns() -> Vec<String> {
+    [
+        ".git",
+        ".svn",
+        "node_modules",
+        "__pycache__",
+        "$RECYCLE.BIN",
+        ".Trash*",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect()
+}
+
 fn default_cron_schedule() -> String {
     "0 3 * * *".to_string()
 }
 
+fn default_db_maintenance_schedule() -> String {
+    "0 4 * * 0".to_string()
+}
+
+fn default_job_window_hours() -> String {
+    "22:00-07:00".to_string()
+}
+
+fn default_job_window_days() -> String {
+    "mon-sun".to_string()
+}
+
 impl Default for SystemConfig {
     fn default() -> Self {
         Self {
             remove_unavailable_files: true,
+            folder_removal_grace_days: default_folder_removal_grace_days(),
             scan_images: true,
             scan_video: true,
             scan_audio: false,
@@ -172,7 +531,24 @@ impl Default for SystemConfig {
                 poll_interval_secs: None,
                 included_folders: Vec::new(),
             },
+            scan_settle_secs: default_scan_settle_secs(),
+            skip_ignored_dirs: true,
+            ignored_dir_patterns: default_ignored_dir_patterns(),
+            respect_ignore_files: true,
+            follow_symlinks: true,
+            symlink_duplicates: SymlinkDuplicates::default(),
+            path_case_sensitivity: PathCaseSensitivity::default(),
+            verify_max_mb_per_sec: default_verify_max_mb_per_sec(),
+            fast_scan: false,
+            skip_unchanged_dirs: false,
+            folder_scan_settings: Vec::new(),
             vector_quants: None,
+            text_normalization: TextNormalizationConfig::default(),
+            extraction_log_retention: ExtractionLogRetention::default(),
+            frame_retention: FrameRetention::default(),
+            db_maintenance: DbMaintenanceConfig::default(),
+            job_window: JobWindowConfig::default(),
+            pql_reports: Vec::new(),
             job_filters: Vec::new(),
             filescan_filter: None,
             extra: BTreeMap::new(),
@@ -196,6 +572,10 @@ impl SystemConfigStore {
         Self { data_dir }
     }
 
+    pub(crate) fn data_dir(&self) -> &Path {
+        &self.data_dir
+    }
+
     pub(crate) fn config_path(&self, index_db: &str) -> PathBuf {
         self.data_dir
             .join("index")
@@ -204,12 +584,21 @@ impl SystemConfigStore {
     }
 
     pub(crate) fn load(&self, index_db: &str) -> ApiResult<SystemConfig> {
-        let config_path = self.config_path(index_db);
-        if !config_path.exists() {
+        if !self.config_path(index_db).exists() {
             let config = SystemConfig::default();
             self.save(index_db, &config)?;
             return Ok(config);
         }
+        self.read(index_db)
+    }
+
+    /// Like `load`, but a missing file reads as the default instead of
+    /// being created, for read-only callers such as search.
+    pub(crate) fn read(&self, index_db: &str) -> ApiResult<SystemConfig> {
+        let config_path = self.config_path(index_db);
+        if !config_path.exists() {
+            return Ok(SystemConfig::default());
+        }
 
         let raw = fs::read_to_string(&config_path).map_err(|err| {
             tracing::error!(error = %err, path = %config_path.display(), "failed to read system config");
@@ -280,6 +669,11 @@ fn normalize_folder_lists(config: &mut SystemConfig) {
         config.continuous_filescan.included_folders =
             normalize_folder_list(&config.continuous_filescan.included_folders);
     }
+    for settings in &mut config.folder_scan_settings {
+        if !settings.path.trim().is_empty() {
+            settings.path = normalize_path(&settings.path);
+        }
+    }
 }
 
 /// Applies the same path cleanup used when a scan configuration is saved.
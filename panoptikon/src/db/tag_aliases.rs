@@ -0,0 +1,349 @@
+//! Tag aliases bridge tagger vocabularies ("grayscale" from one model,
+//! "monochrome" from another). Each alias belongs to one canonical tag;
+//! `match_tags` expands a requested tag into its whole group at preprocess
+//! time, and tag autocompletion can report a group as its canonical tag.
+
+use std::collections::{HashMap, HashSet};
+
+use axum::http::StatusCode;
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use utoipa::ToSchema;
+
+use crate::api_error::ApiError;
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+/// Aliases allowed per canonical tag, which bounds how far one requested
+/// tag can expand.
+pub(crate) const MAX_TAG_ALIASES: usize = 50;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub(crate) struct TagAliasGroup {
+    /// The tag the aliases stand for
+    pub canonical: String,
+    /// Tag names treated as the same tag as `canonical`
+    pub aliases: Vec<String>,
+}
+
+impl TagAliasGroup {
+    /// The canonical tag followed by its aliases.
+    pub(crate) fn members(&self) -> Vec<String> {
+        std::iter::once(self.canonical.clone())
+            .chain(self.aliases.iter().cloned())
+            .collect()
+    }
+}
+
+fn internal(context: &'static str) -> impl Fn(sqlx::Error) -> ApiError {
+    move |err| {
+        tracing::error!(error = %err, context, "tag alias query failed");
+        ApiError::internal(context)
+    }
+}
+
+pub(crate) async fn list_tag_alias_groups(
+    conn: &mut sqlx::SqliteConnection,
+) -> ApiResult<Vec<TagAliasGroup>> {
+    let rows = sqlx::query("SELECT canonical, alias FROM tag_aliases ORDER BY canonical, alias")
+        .fetch_all(conn)
+        .await
+        .map_err(internal("Failed to list tag aliases"))?;
+
+    let mut groups: Vec<TagAliasGroup> = Vec::new();
+    for row in rows {
+        let canonical: String = row
+            .try_get("canonical")
+            .map_err(internal("Failed to list tag aliases"))?;
+        let alias: String = row
+            .try_get("alias")
+            .map_err(internal("Failed to list tag aliases"))?;
+        match groups.last_mut() {
+            Some(group) if group.canonical == canonical => group.aliases.push(alias),
+            _ => groups.push(TagAliasGroup {
+                canonical,
+                aliases: vec![alias],
+            }),
+        }
+    }
+    Ok(groups)
+}
+
+async fn canonical_of(conn: &mut sqlx::SqliteConnection, tag: &str) -> ApiResult<Option<String>> {
+    sqlx::query_scalar("SELECT canonical FROM tag_aliases WHERE alias = ?")
+        .bind(tag)
+        .fetch_optional(conn)
+        .await
+        .map_err(internal("Failed to read tag aliases"))
+}
+
+async fn aliases_of(conn: &mut sqlx::SqliteConnection, canonical: &str) -> ApiResult<Vec<String>> {
+    sqlx::query_scalar("SELECT alias FROM tag_aliases WHERE canonical = ? ORDER BY alias LIMIT ?")
+        .bind(canonical)
+        .bind(MAX_TAG_ALIASES as i64)
+        .fetch_all(conn)
+        .await
+        .map_err(internal("Failed to read tag aliases"))
+}
+
+/// Replaces the aliases of `group.canonical`; an empty alias list removes
+/// the group. An alias may belong to one group only, and a canonical tag
+/// cannot itself be an alias, so groups never chain.
+pub(crate) async fn set_tag_alias_group(
+    conn: &mut sqlx::SqliteConnection,
+    group: &TagAliasGroup,
+) -> ApiResult<()> {
+    let canonical = group.canonical.trim();
+    if canonical.is_empty() {
+        return Err(ApiError::bad_request("canonical must not be empty"));
+    }
+    let mut aliases = Vec::new();
+    for alias in &group.aliases {
+        let alias = alias.trim();
+        if alias.is_empty() || aliases.contains(&alias) {
+            continue;
+        }
+        if alias == canonical {
+            return Err(ApiError::bad_request("A tag cannot be an alias of itself"));
+        }
+        aliases.push(alias);
+    }
+    if aliases.len() > MAX_TAG_ALIASES {
+        return Err(ApiError::bad_request(format!(
+            "A tag can have at most {MAX_TAG_ALIASES} aliases"
+        )));
+    }
+
+    if let Some(owner) = canonical_of(conn, canonical).await? {
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            format!("{canonical} is already an alias of {owner}"),
+        ));
+    }
+    for alias in &aliases {
+        if let Some(owner) = canonical_of(conn, alias).await?
+            && owner != canonical
+        {
+            return Err(ApiError::new(
+                StatusCode::CONFLICT,
+                format!("{alias} is already an alias of {owner}"),
+            ));
+        }
+        if !aliases_of(conn, alias).await?.is_empty() {
+            return Err(ApiError::new(
+                StatusCode::CONFLICT,
+                format!("{alias} has aliases of its own"),
+            ));
+        }
+    }
+
+    sqlx::query("DELETE FROM tag_aliases WHERE canonical = ?")
+        .bind(canonical)
+        .execute(&mut *conn)
+        .await
+        .map_err(internal("Failed to save tag aliases"))?;
+    for alias in aliases {
+        sqlx::query("INSERT INTO tag_aliases (alias, canonical) VALUES (?, ?)")
+            .bind(alias)
+            .bind(canonical)
+            .execute(&mut *conn)
+            .await
+            .map_err(internal("Failed to save tag aliases"))?;
+    }
+    Ok(())
+}
+
+/// Removes every alias of `canonical`; returns how many were removed.
+pub(crate) async fn delete_tag_alias_group(
+    conn: &mut sqlx::SqliteConnection,
+    canonical: &str,
+) -> ApiResult<u64> {
+    let result = sqlx::query("DELETE FROM tag_aliases WHERE canonical = ?")
+        .bind(canonical)
+        .execute(conn)
+        .await
+        .map_err(internal("Failed to delete tag aliases"))?;
+    Ok(result.rows_affected())
+}
+
+/// The alias group of each tag: its canonical tag plus the canonical tag's
+/// aliases, capped at [`MAX_TAG_ALIASES`]. A tag without aliases is a group
+/// of one.
+pub(crate) async fn resolve_tag_alias_groups(
+    conn: &mut sqlx::SqliteConnection,
+    tags: &[String],
+) -> ApiResult<Vec<Vec<String>>> {
+    let mut groups = Vec::with_capacity(tags.len());
+    for tag in tags {
+        let canonical = canonical_of(conn, tag)
+            .await?
+            .unwrap_or_else(|| tag.clone());
+        let mut group = TagAliasGroup {
+            aliases: aliases_of(conn, &canonical).await?,
+            canonical,
+        }
+        .members();
+        if !group.contains(tag) {
+            group.push(tag.clone());
+        }
+        groups.push(group);
+    }
+    Ok(groups)
+}
+
+/// Merges `(namespace, name, item count)` rows whose names share an alias
+/// group into one row under the canonical name, recounting the distinct
+/// items tagged with any member of the group in that namespace.
+pub(crate) async fn collapse_tag_counts(
+    conn: &mut sqlx::SqliteConnection,
+    tags: Vec<(String, String, i64)>,
+) -> ApiResult<Vec<(String, String, i64)>> {
+    let mut collapsed = Vec::with_capacity(tags.len());
+    let mut seen = HashSet::new();
+    let mut groups: HashMap<String, Vec<String>> = HashMap::new();
+    for (namespace, name, count) in tags {
+        let canonical = canonical_of(conn, &name)
+            .await?
+            .unwrap_or_else(|| name.clone());
+        if !groups.contains_key(&canonical) {
+            let members = TagAliasGroup {
+                aliases: aliases_of(conn, &canonical).await?,
+                canonical: canonical.clone(),
+            }
+            .members();
+            groups.insert(canonical.clone(), members);
+        }
+        if !seen.insert((namespace.clone(), canonical.clone())) {
+            continue;
+        }
+        let members = &groups[&canonical];
+        if members.len() == 1 {
+            collapsed.push((namespace, name, count));
+            continue;
+        }
+        let count = count_items_tagged_with_any(conn, &namespace, members).await?;
+        collapsed.push((namespace, canonical, count));
+    }
+    Ok(collapsed)
+}
+
+async fn count_items_tagged_with_any(
+    conn: &mut sqlx::SqliteConnection,
+    namespace: &str,
+    names: &[String],
+) -> ApiResult<i64> {
+    let placeholders = vec!["?"; names.len()].join(", ");
+    let sql = format!(
+        r#"
+        SELECT COUNT(DISTINCT item_data.item_id)
+        FROM tags_items
+        JOIN item_data ON tags_items.item_data_id = item_data.id
+        JOIN tags ON tags.id = tags_items.tag_id
+        WHERE tags.namespace = ? AND tags.name IN ({placeholders})
+        "#
+    );
+    let mut query = sqlx::query_scalar(sqlx::AssertSqlSafe(sql.as_str())).bind(namespace);
+    for name in names {
+        query = query.bind(name);
+    }
+    query
+        .fetch_one(conn)
+        .await
+        .map_err(internal("Failed to count aliased tags"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::migrations::setup_test_databases;
+
+    fn group(canonical: &str, aliases: &[&str]) -> TagAliasGroup {
+        TagAliasGroup {
+            canonical: canonical.to_string(),
+            aliases: aliases.iter().map(|alias| alias.to_string()).collect(),
+        }
+    }
+
+    // Ensures groups resolve from either side and cannot overlap or chain.
+    #[tokio::test]
+    async fn alias_groups_resolve_and_reject_overlaps() {
+        let mut dbs = setup_test_databases().await;
+        let conn = &mut dbs.index_conn;
+        set_tag_alias_group(conn, &group("monochrome", &["grayscale", "greyscale"]))
+            .await
+            .unwrap();
+
+        let groups = resolve_tag_alias_groups(conn, &["greyscale".to_string(), "cat".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(
+            groups,
+            vec![vec!["monochrome", "grayscale", "greyscale"], vec!["cat"],]
+        );
+
+        let err = set_tag_alias_group(conn, &group("bw", &["grayscale"]))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::CONFLICT);
+        let err = set_tag_alias_group(conn, &group("grayscale", &["gray"]))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::CONFLICT);
+        let err = set_tag_alias_group(conn, &group("bw", &["monochrome"]))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), StatusCode::CONFLICT);
+
+        set_tag_alias_group(conn, &group("monochrome", &["grayscale"]))
+            .await
+            .unwrap();
+        assert_eq!(
+            list_tag_alias_groups(conn).await.unwrap(),
+            vec![group("monochrome", &["grayscale"])]
+        );
+        assert_eq!(delete_tag_alias_group(conn, "monochrome").await.unwrap(), 1);
+        assert!(list_tag_alias_groups(conn).await.unwrap().is_empty());
+    }
+
+    // Ensures collapsed counts cover items carrying any member of a group once.
+    #[tokio::test]
+    async fn collapse_counts_distinct_items_per_group() {
+        let mut dbs = setup_test_databases().await;
+        let conn = &mut dbs.index_conn;
+        sqlx::query(
+            r#"
+INSERT INTO items (id, sha256, md5, type, time_added)
+VALUES
+    (1, 'sha_a', 'md5_a', 'image/png', '2024-01-01T00:00:00'),
+    (2, 'sha_b', 'md5_b', 'image/png', '2024-01-01T00:00:00');
+INSERT INTO setters (id, name) VALUES (1, 'tagger');
+INSERT INTO item_data (id, item_id, setter_id, data_type, idx, is_origin, is_placeholder)
+VALUES (1, 1, 1, 'tags', 0, 1, 0), (2, 2, 1, 'tags', 0, 1, 0);
+INSERT INTO tags (id, namespace, name)
+VALUES (1, 'ns', 'monochrome'), (2, 'ns', 'grayscale');
+INSERT INTO tags_items (item_data_id, tag_id, confidence)
+VALUES (1, 1, 1.0), (1, 2, 1.0), (2, 2, 1.0);
+            "#,
+        )
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+        set_tag_alias_group(conn, &group("monochrome", &["grayscale"]))
+            .await
+            .unwrap();
+
+        let collapsed = collapse_tag_counts(
+            conn,
+            vec![
+                ("ns".to_string(), "grayscale".to_string(), 2),
+                ("ns".to_string(), "monochrome".to_string(), 1),
+            ],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            collapsed,
+            vec![("ns".to_string(), "monochrome".to_string(), 2)]
+        );
+    }
+}
@@ -0,0 +1,236 @@
+//! Writing imported tags (e.g. a Hydrus sidecar export) as if a model had
+//! produced them. Each batch is one index writer transaction; items that
+//! already have tags from the setter have them replaced, like a model
+//! re-run after its data was deleted, so importing the same file twice
+//! leaves one copy.
+
+use serde::Serialize;
+use sqlx::Row;
+use utoipa::ToSchema;
+
+use crate::api_error::ApiError;
+use crate::db::extraction_write::{TagEntry, delete_orphan_tags, upsert_setter, write_tags_output};
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+#[derive(Debug, Clone)]
+pub(crate) struct TagImportEntry {
+    pub sha256: String,
+    pub tags: Vec<TagEntry>,
+}
+
+/// What one batch wrote. Counts add up across batches.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, ToSchema)]
+pub(crate) struct TagImportCounts {
+    /// Items whose tags were written.
+    pub items: u64,
+    /// Items whose previous tags from the setter were replaced.
+    pub replaced: u64,
+    /// Tag rows written.
+    pub tags: u64,
+    pub image_files: u64,
+    pub video_files: u64,
+    pub other_files: u64,
+}
+
+impl TagImportCounts {
+    pub(crate) fn add(&mut self, other: &TagImportCounts) {
+        self.items += other.items;
+        self.replaced += other.replaced;
+        self.tags += other.tags;
+        self.image_files += other.image_files;
+        self.video_files += other.video_files;
+        self.other_files += other.other_files;
+    }
+}
+
+pub(crate) struct TagImportBatch {
+    pub counts: TagImportCounts,
+    /// Hashes with no matching item, in input order.
+    pub unmatched: Vec<String>,
+}
+
+fn internal(context: &'static str) -> impl Fn(sqlx::Error) -> ApiError {
+    move |err| {
+        tracing::error!(error = %err, context, "tag import failed");
+        ApiError::internal(context)
+    }
+}
+
+/// Writes one batch of imported tags under `setter_name`, attributed to the
+/// import's data log `job_id`.
+pub(crate) async fn import_tags_batch(
+    conn: &mut sqlx::SqliteConnection,
+    job_id: i64,
+    setter_name: &str,
+    entries: &[TagImportEntry],
+) -> ApiResult<TagImportBatch> {
+    let setter_id = upsert_setter(conn, setter_name).await?;
+    let mut counts = TagImportCounts::default();
+    let mut unmatched = Vec::new();
+    for entry in entries {
+        let row = sqlx::query("SELECT id, type FROM items WHERE sha256 = ?1")
+            .bind(&entry.sha256)
+            .fetch_optional(&mut *conn)
+            .await
+            .map_err(internal("Failed to look up item"))?;
+        let Some(row) = row else {
+            unmatched.push(entry.sha256.clone());
+            continue;
+        };
+        let item_id: i64 = row
+            .try_get("id")
+            .map_err(internal("Failed to look up item"))?;
+        let mime_type: String = row
+            .try_get("type")
+            .map_err(internal("Failed to look up item"))?;
+
+        // Derived rows (text generated from the tags) cascade through
+        // source_id.
+        let replaced = sqlx::query(
+            r#"
+DELETE FROM item_data
+WHERE item_id = ?1
+  AND setter_id = ?2
+  AND data_type = 'tags'
+  AND is_origin = 1
+            "#,
+        )
+        .bind(item_id)
+        .bind(setter_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(internal("Failed to replace previous tags"))?
+        .rows_affected();
+
+        write_tags_output(
+            conn,
+            job_id,
+            setter_name,
+            &entry.sha256,
+            &entry.tags,
+            &[],
+            false,
+        )
+        .await?;
+
+        counts.items += 1;
+        counts.replaced += replaced.min(1);
+        counts.tags += entry.tags.len() as u64;
+        if mime_type.starts_with("image/") {
+            counts.image_files += 1;
+        } else if mime_type.starts_with("video/") {
+            counts.video_files += 1;
+        } else {
+            counts.other_files += 1;
+        }
+    }
+    if counts.replaced > 0 {
+        delete_orphan_tags(conn).await?;
+    }
+    Ok(TagImportBatch { counts, unmatched })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::migrations::setup_test_databases;
+
+    fn entry(sha256: &str, tags: &[(&str, &str)]) -> TagImportEntry {
+        TagImportEntry {
+            sha256: sha256.to_string(),
+            tags: tags
+                .iter()
+                .map(|(namespace, name)| TagEntry {
+                    namespace: namespace.to_string(),
+                    name: name.to_string(),
+                    confidence: 1.0,
+                })
+                .collect(),
+        }
+    }
+
+    async fn tag_names(conn: &mut sqlx::SqliteConnection) -> Vec<String> {
+        sqlx::query_scalar(
+            r#"
+SELECT tags.namespace || ':' || tags.name
+FROM tags_items
+JOIN tags ON tags.id = tags_items.tag_id
+ORDER BY tags.namespace, tags.name
+            "#,
+        )
+        .fetch_all(conn)
+        .await
+        .unwrap()
+    }
+
+    // Unknown hashes are reported, and importing again replaces the item's
+    // tags instead of adding a second tags row.
+    #[tokio::test]
+    async fn import_reports_unmatched_hashes_and_replaces_on_rerun() {
+        let mut dbs = setup_test_databases().await;
+        let conn = &mut dbs.index_conn;
+        sqlx::query(
+            r#"
+INSERT INTO items (id, sha256, md5, type, time_added) VALUES
+    (1, 'sha1', 'md51', 'image/png', '2024-01-01T00:00:00'),
+    (2, 'sha2', 'md52', 'video/mp4', '2024-01-01T00:00:00');
+INSERT INTO data_jobs (id, completed) VALUES (1, 0), (2, 0);
+            "#,
+        )
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+
+        let first = import_tags_batch(
+            conn,
+            1,
+            "hydrus",
+            &[
+                entry("sha1", &[("character", "alice"), ("general", "old")]),
+                entry("missing", &[("general", "cat")]),
+                entry("sha2", &[("general", "cat")]),
+            ],
+        )
+        .await
+        .unwrap();
+        assert_eq!(first.unmatched, vec!["missing"]);
+        assert_eq!(
+            first.counts,
+            TagImportCounts {
+                items: 2,
+                replaced: 0,
+                tags: 3,
+                image_files: 1,
+                video_files: 1,
+                other_files: 0,
+            }
+        );
+
+        let second = import_tags_batch(
+            conn,
+            2,
+            "hydrus",
+            &[entry("sha1", &[("character", "alice"), ("general", "new")])],
+        )
+        .await
+        .unwrap();
+        assert_eq!(second.counts.replaced, 1);
+        assert_eq!(
+            tag_names(conn).await,
+            vec!["character:alice", "general:cat", "general:new"]
+        );
+        let tag_rows: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM item_data WHERE item_id = 1 AND data_type = 'tags'",
+        )
+        .fetch_one(&mut *conn)
+        .await
+        .unwrap();
+        assert_eq!(tag_rows, 1);
+        let orphan: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM tags WHERE name = 'old'")
+            .fetch_one(&mut *conn)
+            .await
+            .unwrap();
+        assert_eq!(orphan, 0);
+    }
+}
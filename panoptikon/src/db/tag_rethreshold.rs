@@ -0,0 +1,466 @@
+//! Raising the threshold of tags a setter already wrote: tags below the new
+//! threshold are deleted and the text entries generated from each item's
+//! tags (the "all tags" list and its mcut subset) are rewritten to match, as
+//! if the model had run at that threshold. Tags under the original threshold
+//! were never stored, so a threshold can only go up.
+
+use serde::Serialize;
+use sqlx::Row;
+use utoipa::ToSchema;
+
+use crate::api_error::ApiError;
+use crate::db::extraction_write::{delete_orphan_tags, mcut_threshold, rewrite_extracted_text};
+use crate::db::system_config::TextNormalizationConfig;
+use crate::pql::utils::normalized_for_index;
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+/// What one chunk changed. Counts add up across chunks.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, ToSchema)]
+pub(crate) struct TagRethresholdCounts {
+    /// Items that lost at least one tag.
+    pub items: u64,
+    /// Tag rows removed, per-frame tags of videos included.
+    pub tags_removed: u64,
+    /// Items left with no tags at all. They keep an empty tags row, like a
+    /// model run that found none, so they are not picked up as unprocessed.
+    pub emptied: u64,
+    pub image_files: u64,
+    pub video_files: u64,
+    pub other_files: u64,
+}
+
+impl TagRethresholdCounts {
+    pub(crate) fn add(&mut self, other: &TagRethresholdCounts) {
+        self.items += other.items;
+        self.tags_removed += other.tags_removed;
+        self.emptied += other.emptied;
+        self.image_files += other.image_files;
+        self.video_files += other.video_files;
+        self.other_files += other.other_files;
+    }
+}
+
+/// Outcome of one `rethreshold_tags_chunk` transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct TagRethresholdChunk {
+    /// Tags rows examined; zero means the setter is exhausted.
+    pub scanned: u64,
+    pub counts: TagRethresholdCounts,
+    /// Resume after this item_data id.
+    pub cursor: i64,
+}
+
+fn internal(context: &'static str) -> impl Fn(sqlx::Error) -> ApiError {
+    move |err| {
+        tracing::error!(error = %err, context, "tag rethreshold failed");
+        ApiError::internal(context)
+    }
+}
+
+/// Whether the setter has any tags rows, empty ones included.
+pub(crate) async fn setter_has_tags(
+    conn: &mut sqlx::SqliteConnection,
+    setter_name: &str,
+) -> ApiResult<bool> {
+    sqlx::query_scalar(
+        r#"
+SELECT EXISTS (
+    SELECT 1
+    FROM item_data
+    JOIN setters ON setters.id = item_data.setter_id
+    WHERE setters.name = ?1
+      AND item_data.data_type = 'tags'
+      AND item_data.is_origin = 1
+)
+        "#,
+    )
+    .bind(setter_name)
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(internal("Failed to read setter tags"))
+}
+
+/// The threshold the setter's stored tags were cut at: the highest one
+/// recorded in extraction history by a tags run that still owns data for
+/// the setter, or by a later run that owns none (a rethreshold, or a run
+/// whose data was replaced). Runs older than all of the setter's data, e.g.
+/// from before its data was deleted, no longer count. `None` when no run
+/// recorded a threshold, as for imported tags.
+pub(crate) async fn current_tags_threshold(
+    conn: &mut sqlx::SqliteConnection,
+    setter_name: &str,
+) -> ApiResult<Option<f64>> {
+    sqlx::query_scalar(
+        r#"
+WITH owners AS (
+    SELECT DISTINCT item_data.job_id
+    FROM item_data
+    JOIN setters ON setters.id = item_data.setter_id
+    WHERE setters.name = ?1
+      AND item_data.data_type = 'tags'
+      AND item_data.is_origin = 1
+)
+SELECT MAX(threshold)
+FROM data_log
+WHERE setter = ?1
+  AND type = 'tags'
+  AND threshold IS NOT NULL
+  AND (
+      job_id IN (SELECT job_id FROM owners)
+      OR job_id > (SELECT MIN(job_id) FROM owners)
+  )
+        "#,
+    )
+    .bind(setter_name)
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(internal("Failed to read setter threshold"))
+}
+
+/// Applies `threshold` to up to `limit` of the setter's tags rows after
+/// `after_id`. Items whose tags all pass are left untouched.
+pub(crate) async fn rethreshold_tags_chunk(
+    conn: &mut sqlx::SqliteConnection,
+    setter_name: &str,
+    threshold: f64,
+    normalization: &TextNormalizationConfig,
+    after_id: i64,
+    limit: i64,
+) -> ApiResult<TagRethresholdChunk> {
+    let rows = sqlx::query(
+        r#"
+SELECT item_data.id, items.type
+FROM item_data
+JOIN items ON items.id = item_data.item_id
+JOIN setters ON setters.id = item_data.setter_id
+WHERE setters.name = ?1
+  AND item_data.data_type = 'tags'
+  AND item_data.is_origin = 1
+  AND item_data.is_placeholder = 0
+  AND item_data.id > ?2
+ORDER BY item_data.id
+LIMIT ?3
+        "#,
+    )
+    .bind(setter_name)
+    .bind(after_id)
+    .bind(limit)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(internal("Failed to read setter tags"))?;
+
+    let mut chunk = TagRethresholdChunk {
+        scanned: rows.len() as u64,
+        counts: TagRethresholdCounts::default(),
+        cursor: after_id,
+    };
+    for row in rows {
+        let tags_data_id: i64 = row
+            .try_get("id")
+            .map_err(internal("Failed to read setter tags"))?;
+        let mime_type: String = row
+            .try_get("type")
+            .map_err(internal("Failed to read setter tags"))?;
+        chunk.cursor = tags_data_id;
+
+        // Per-frame tags of a video hang off the merged row through
+        // source_id; frames left without tags lose their row, as frames
+        // without tags never get one.
+        let frame_removed = sqlx::query(
+            r#"
+DELETE FROM tags_items
+WHERE confidence < ?1
+  AND item_data_id IN (
+      SELECT id FROM item_data WHERE source_id = ?2 AND data_type = 'tags'
+  )
+            "#,
+        )
+        .bind(threshold)
+        .bind(tags_data_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(internal("Failed to remove tags"))?
+        .rows_affected();
+        if frame_removed > 0 {
+            sqlx::query(
+                r#"
+DELETE FROM item_data
+WHERE source_id = ?1
+  AND data_type = 'tags'
+  AND NOT EXISTS (
+      SELECT 1 FROM tags_items WHERE tags_items.item_data_id = item_data.id
+  )
+                "#,
+            )
+            .bind(tags_data_id)
+            .execute(&mut *conn)
+            .await
+            .map_err(internal("Failed to remove tags"))?;
+        }
+
+        let removed =
+            sqlx::query("DELETE FROM tags_items WHERE item_data_id = ?1 AND confidence < ?2")
+                .bind(tags_data_id)
+                .bind(threshold)
+                .execute(&mut *conn)
+                .await
+                .map_err(internal("Failed to remove tags"))?
+                .rows_affected();
+        if removed + frame_removed == 0 {
+            continue;
+        }
+        if removed > 0 && !rewrite_tags_text(conn, tags_data_id, normalization).await? {
+            chunk.counts.emptied += 1;
+        }
+
+        chunk.counts.items += 1;
+        chunk.counts.tags_removed += removed + frame_removed;
+        if mime_type.starts_with("image/") {
+            chunk.counts.image_files += 1;
+        } else if mime_type.starts_with("video/") {
+            chunk.counts.video_files += 1;
+        } else {
+            chunk.counts.other_files += 1;
+        }
+    }
+    if chunk.counts.items > 0 {
+        delete_orphan_tags(conn).await?;
+    }
+    Ok(chunk)
+}
+
+/// Rebuilds the text entries of a tags row from its remaining tags, in the
+/// order the model wrote them. Returns false when no tags remain; the row
+/// then becomes a placeholder and its text entries are deleted.
+async fn rewrite_tags_text(
+    conn: &mut sqlx::SqliteConnection,
+    tags_data_id: i64,
+    normalization: &TextNormalizationConfig,
+) -> ApiResult<bool> {
+    let tags: Vec<(String, String, f64)> = sqlx::query_as(
+        r#"
+SELECT tags.namespace, tags.name, tags_items.confidence
+FROM tags_items
+JOIN tags ON tags.id = tags_items.tag_id
+WHERE tags_items.item_data_id = ?1
+ORDER BY tags_items.rowid
+        "#,
+    )
+    .bind(tags_data_id)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(internal("Failed to read remaining tags"))?;
+
+    if tags.is_empty() {
+        sqlx::query("DELETE FROM item_data WHERE source_id = ?1 AND data_type = 'text'")
+            .bind(tags_data_id)
+            .execute(&mut *conn)
+            .await
+            .map_err(internal("Failed to remove tags text"))?;
+        sqlx::query("UPDATE item_data SET is_placeholder = 1 WHERE id = ?1")
+            .bind(tags_data_id)
+            .execute(&mut *conn)
+            .await
+            .map_err(internal("Failed to remove tags text"))?;
+        return Ok(false);
+    }
+
+    let text_rows: Vec<(i64, i64)> = sqlx::query_as(
+        r#"
+SELECT id, idx
+FROM item_data
+WHERE source_id = ?1
+  AND data_type = 'text'
+  AND idx IN (0, 1)
+        "#,
+    )
+    .bind(tags_data_id)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(internal("Failed to read tags text"))?;
+
+    for (text_data_id, idx) in text_rows {
+        let (text, confidence) = if idx == 0 {
+            let min_confidence = tags
+                .iter()
+                .map(|(_, _, confidence)| *confidence)
+                .fold(f64::INFINITY, f64::min);
+            (join_names(tags.iter()), min_confidence)
+        } else {
+            let is_general = |namespace: &str| namespace.ends_with(":general");
+            let general_scores = tags
+                .iter()
+                .filter(|(namespace, _, _)| is_general(namespace))
+                .map(|(_, _, confidence)| *confidence)
+                .collect::<Vec<_>>();
+            // Without general tags the model would not have written an
+            // mcut entry at all.
+            if general_scores.is_empty() {
+                sqlx::query("DELETE FROM item_data WHERE id = ?1")
+                    .bind(text_data_id)
+                    .execute(&mut *conn)
+                    .await
+                    .map_err(internal("Failed to remove tags text"))?;
+                continue;
+            }
+            let m_thresh = mcut_threshold(&general_scores);
+            let kept = tags.iter().filter(|(namespace, _, confidence)| {
+                !is_general(namespace) || *confidence >= m_thresh
+            });
+            (join_names(kept), m_thresh)
+        };
+        let normalized = normalized_for_index(normalization, &text);
+        rewrite_extracted_text(conn, text_data_id, &text, normalized.as_deref(), confidence)
+            .await?;
+    }
+    Ok(true)
+}
+
+fn join_names<'a>(tags: impl Iterator<Item = &'a (String, String, f64)>) -> String {
+    tags.map(|(_, name, _)| name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::extraction_write::{TagEntry, TagTextEntry, write_tags_output};
+    use crate::db::migrations::setup_test_databases;
+
+    fn tag(namespace: &str, name: &str, confidence: f64) -> TagEntry {
+        TagEntry {
+            namespace: namespace.to_string(),
+            name: name.to_string(),
+            confidence,
+        }
+    }
+
+    fn text(index: i64, text: &str, language: &str) -> TagTextEntry {
+        TagTextEntry {
+            index,
+            text: text.to_string(),
+            normalized_text: None,
+            language: language.to_string(),
+            language_confidence: 1.0,
+            confidence: 0.0,
+        }
+    }
+
+    async fn tags_text(conn: &mut sqlx::SqliteConnection, sha256: &str) -> Vec<(String, String)> {
+        sqlx::query_as(
+            r#"
+SELECT extracted_text.language, extracted_text.text
+FROM extracted_text
+JOIN item_data ON item_data.id = extracted_text.id
+JOIN items ON items.id = item_data.item_id
+WHERE items.sha256 = ?1
+ORDER BY item_data.idx
+            "#,
+        )
+        .bind(sha256)
+        .fetch_all(conn)
+        .await
+        .unwrap()
+    }
+
+    // Ensures tags below the threshold go, the all-tags and mcut text rows
+    // are rebuilt from what remains, and an item losing every tag keeps an
+    // empty tags row without text.
+    #[tokio::test]
+    async fn rethreshold_removes_tags_and_rewrites_text() {
+        let mut dbs = setup_test_databases().await;
+        let conn = &mut dbs.index_conn;
+        sqlx::query(
+            r#"
+INSERT INTO items (id, sha256, md5, type, time_added) VALUES
+    (1, 'sha1', 'md51', 'image/png', '2024-01-01T00:00:00'),
+    (2, 'sha2', 'md52', 'image/png', '2024-01-01T00:00:00');
+INSERT INTO setters (id, name) VALUES (1, 'wd');
+INSERT INTO data_jobs (id, completed) VALUES (1, 1);
+INSERT INTO data_log (job_id, start_time, end_time, type, setter, threshold, batch_size)
+VALUES (1, '2024-01-01T00:00:00', '2024-01-01T00:00:00', 'tags', 'wd', 0.2, 64);
+            "#,
+        )
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+        write_tags_output(
+            conn,
+            1,
+            "wd",
+            "sha1",
+            &[
+                tag("wd:character", "alice", 0.3),
+                tag("wd:general", "cat", 0.95),
+                tag("wd:general", "dog", 0.9),
+                tag("wd:general", "tail", 0.45),
+                tag("wd:general", "sky", 0.25),
+            ],
+            &[
+                text(0, "alice, cat, dog, tail, sky", "wd"),
+                text(1, "alice, cat, dog", "wd-mcut"),
+            ],
+            false,
+        )
+        .await
+        .unwrap();
+        write_tags_output(
+            conn,
+            1,
+            "wd",
+            "sha2",
+            &[tag("wd:general", "grass", 0.3)],
+            &[text(0, "grass", "wd")],
+            false,
+        )
+        .await
+        .unwrap();
+        assert_eq!(current_tags_threshold(conn, "wd").await.unwrap(), Some(0.2));
+
+        let chunk =
+            rethreshold_tags_chunk(conn, "wd", 0.4, &TextNormalizationConfig::default(), 0, 100)
+                .await
+                .unwrap();
+        assert_eq!(chunk.scanned, 2);
+        assert_eq!(
+            chunk.counts,
+            TagRethresholdCounts {
+                items: 2,
+                tags_removed: 3,
+                emptied: 1,
+                image_files: 2,
+                video_files: 0,
+                other_files: 0,
+            }
+        );
+        assert_eq!(
+            tags_text(conn, "sha1").await,
+            vec![
+                ("wd".to_string(), "cat, dog, tail".to_string()),
+                ("wd-mcut".to_string(), "cat, dog".to_string()),
+            ]
+        );
+        let (min_confidence,): (f64,) = sqlx::query_as(
+            "SELECT confidence FROM extracted_text WHERE language = 'wd' AND text LIKE 'cat%'",
+        )
+        .fetch_one(&mut *conn)
+        .await
+        .unwrap();
+        assert_eq!(min_confidence, 0.45);
+        assert!(tags_text(conn, "sha2").await.is_empty());
+        let placeholder: i64 = sqlx::query_scalar(
+            "SELECT is_placeholder FROM item_data WHERE item_id = 2 AND data_type = 'tags'",
+        )
+        .fetch_one(&mut *conn)
+        .await
+        .unwrap();
+        assert_eq!(placeholder, 1);
+        let orphan: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM tags WHERE name = 'sky'")
+            .fetch_one(&mut *conn)
+            .await
+            .unwrap();
+        assert_eq!(orphan, 0);
+    }
+}
@@ -5,9 +5,12 @@ use crate::api_error::ApiError;
 
 type ApiResult<T> = std::result::Result<T, ApiError>;
 
+/// Tags whose names contain `name` (or start with it, with `prefix`), with
+/// the number of distinct items tagged with each.
 pub(crate) async fn find_tags(
     conn: &mut sqlx::SqliteConnection,
     name: &str,
+    prefix: bool,
     limit: i64,
 ) -> ApiResult<Vec<(String, String, i64)>> {
     let rows = sqlx::query(
@@ -18,7 +21,11 @@ pub(crate) async fn find_tags(
         LIMIT ?
         "#,
     )
-    .bind(format!("%{name}%"))
+    .bind(if prefix {
+        format!("{name}%")
+    } else {
+        format!("%{name}%")
+    })
     .bind(limit)
     .fetch_all(&mut *conn)
     .await
@@ -93,6 +100,47 @@ pub(crate) async fn find_tags(
     Ok(results)
 }
 
+/// Every tag applied to at least `min_count` distinct items, with that
+/// count: the rows of the tag autocomplete index.
+pub(crate) async fn get_tags_with_min_count(
+    conn: &mut sqlx::SqliteConnection,
+    min_count: i64,
+) -> ApiResult<Vec<(String, String, i64)>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT tags.namespace AS namespace, tags.name AS name,
+            COUNT(DISTINCT item_data.item_id) AS count
+        FROM tags_items
+        JOIN item_data
+            ON tags_items.item_data_id = item_data.id
+        JOIN tags
+            ON tags.id = tags_items.tag_id
+        GROUP BY tags_items.tag_id
+        HAVING count >= ?
+        "#,
+    )
+    .bind(min_count)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|err| {
+        tracing::error!(error = %err, "failed to load tag counts");
+        ApiError::internal("Failed to load tag counts")
+    })?;
+    rows.iter()
+        .map(|row| {
+            Ok((
+                row.try_get("namespace")?,
+                row.try_get("name")?,
+                row.try_get("count")?,
+            ))
+        })
+        .collect::<Result<Vec<_>, sqlx::Error>>()
+        .map_err(|err| {
+            tracing::error!(error = %err, "failed to read tag counts");
+            ApiError::internal("Failed to load tag counts")
+        })
+}
+
 pub(crate) async fn get_most_common_tags_frequency(
     conn: &mut sqlx::SqliteConnection,
     namespace: Option<&str>,
@@ -232,7 +280,8 @@ async fn get_most_common_tags(
         "#,
     );
 
-    let mut conditions: Vec<String> = Vec::new();
+    // Per-frame rows repeat their video's merged tags.
+    let mut conditions: Vec<String> = vec!["item_data.idx = 0".to_string()];
     if namespace.is_some() {
         conditions.push("tags.namespace LIKE ? || '%'".to_string());
     }
@@ -247,10 +296,8 @@ async fn get_most_common_tags(
         conditions.push(format!("setters.name IN ({placeholders})"));
     }
 
-    if !conditions.is_empty() {
-        sql.push_str(" WHERE ");
-        sql.push_str(&conditions.join(" AND "));
-    }
+    sql.push_str(" WHERE ");
+    sql.push_str(&conditions.join(" AND "));
 
     sql.push_str(" GROUP BY tags.namespace, tags.name");
     sql.push_str(" ORDER BY count DESC");
@@ -372,7 +419,9 @@ mod tests {
     #[tokio::test]
     async fn find_tags_returns_distinct_item_counts() {
         let mut dbs = setup_tag_db().await;
-        let mut tags = find_tags(&mut dbs.index_conn, "cat", 10).await.unwrap();
+        let mut tags = find_tags(&mut dbs.index_conn, "cat", false, 10)
+            .await
+            .unwrap();
         tags.sort_by(|a, b| a.1.cmp(&b.1));
 
         assert_eq!(
@@ -384,6 +433,38 @@ mod tests {
         );
     }
 
+    // Prefix matching only finds names starting with the text, and the index
+    // rows leave out tags applied to fewer items than the minimum.
+    #[tokio::test]
+    async fn prefix_search_and_min_count_rows() {
+        let mut dbs = setup_tag_db().await;
+        let tags = find_tags(&mut dbs.index_conn, "ater", true, 10)
+            .await
+            .unwrap();
+        assert!(tags.is_empty());
+        let tags = find_tags(&mut dbs.index_conn, "cater", true, 10)
+            .await
+            .unwrap();
+        assert_eq!(tags, vec![("ns".to_string(), "caterpillar".to_string(), 1)]);
+
+        let mut rows = get_tags_with_min_count(&mut dbs.index_conn, 1)
+            .await
+            .unwrap();
+        rows.sort();
+        assert_eq!(
+            rows,
+            vec![
+                ("ns".to_string(), "cat".to_string(), 2),
+                ("ns".to_string(), "caterpillar".to_string(), 1),
+                ("ns".to_string(), "dog".to_string(), 1)
+            ]
+        );
+        let rows = get_tags_with_min_count(&mut dbs.index_conn, 2)
+            .await
+            .unwrap();
+        assert_eq!(rows, vec![("ns".to_string(), "cat".to_string(), 2)]);
+    }
+
     // Ensures tag namespaces include colon prefixes for search stats.
     #[tokio::test]
     async fn get_all_tag_namespaces_includes_prefixes() {
@@ -0,0 +1,580 @@
+//! Setup diagnostics: a battery of checks for the environment problems
+//! behind most "it doesn't work" reports — SQLite without sqlite-vec,
+//! missing ffmpeg/ffprobe, no Python environment or document renderers, an
+//! unwritable or full data folder, an unreachable inference server, and
+//! databases from a newer build. Served as `GET /api/diagnostics`, run by
+//! `panoptikon doctor`, and optionally logged at startup
+//! (`[server] startup_diagnostics`).
+//!
+//! Checks never fail the caller: every problem becomes a `warn` or `fail`
+//! entry with a remediation hint, and nothing is created or migrated except
+//! a probe file in the data folder.
+
+use std::path::Path;
+use std::time::Duration;
+
+use serde::Serialize;
+use sqlx::{Connection, SqliteConnection};
+use utoipa::ToSchema;
+
+use crate::config::Settings;
+use crate::db::migrations::{SchemaStatus, default_schema_status};
+use crate::inferio_client::InferenceApiClient;
+use crate::media_tools::{self, MediaTool};
+
+/// Below this much free space in the data folder the check warns.
+const MIN_FREE_BYTES: u64 = 1024 * 1024 * 1024;
+/// How long the inference metadata fetch and the Python probe may take.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(15);
+/// Written and removed again to prove the data folder is writable.
+const PROBE_FILE: &str = ".panoptikon-diagnostics-probe";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl CheckStatus {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Pass => "pass",
+            Self::Warn => "warn",
+            Self::Fail => "fail",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub(crate) struct DiagnosticCheck {
+    /// Stable check name, e.g. `sqlite` or `ffmpeg`.
+    pub name: &'static str,
+    pub status: CheckStatus,
+    /// What was found: versions, paths, or the error.
+    pub detail: String,
+    /// How to fix a `warn` or `fail`; absent when the check passed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remediation: Option<String>,
+}
+
+impl DiagnosticCheck {
+    fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Pass,
+            detail: detail.into(),
+            remediation: None,
+        }
+    }
+
+    fn problem(
+        name: &'static str,
+        status: CheckStatus,
+        detail: impl Into<String>,
+        remediation: impl Into<String>,
+    ) -> Self {
+        Self {
+            name,
+            status,
+            detail: detail.into(),
+            remediation: Some(remediation.into()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub(crate) struct DiagnosticsReport {
+    /// The worst status of any check.
+    pub status: CheckStatus,
+    pub checks: Vec<DiagnosticCheck>,
+}
+
+/// Runs every check concurrently. `serving` is whether this process is the
+/// running server: offline runs (`doctor`) cannot reach in-process local
+/// inference and skip that probe.
+pub(crate) async fn run_checks(settings: &Settings, serving: bool) -> DiagnosticsReport {
+    let (sqlite, ffmpeg, ffprobe, python, renderers, data_folder, inference, schema) = tokio::join!(
+        check_sqlite(),
+        check_media_tool(MediaTool::Ffmpeg),
+        check_media_tool(MediaTool::Ffprobe),
+        check_python(settings),
+        check_renderers(),
+        check_data_folder(settings),
+        check_inference(settings, serving),
+        check_schema(),
+    );
+    let [pdfium, html_renderer] = renderers;
+    let checks = vec![
+        sqlite,
+        ffmpeg,
+        ffprobe,
+        python,
+        pdfium,
+        html_renderer,
+        data_folder,
+        inference,
+        schema,
+    ];
+    DiagnosticsReport {
+        status: checks
+            .iter()
+            .map(|check| check.status)
+            .max()
+            .unwrap_or(CheckStatus::Pass),
+        checks,
+    }
+}
+
+/// Runs the checks and logs a warning per problem; for
+/// `[server] startup_diagnostics`.
+pub(crate) async fn log_startup_diagnostics(settings: &Settings) {
+    let report = run_checks(settings, true).await;
+    for check in &report.checks {
+        if check.status == CheckStatus::Pass {
+            continue;
+        }
+        tracing::warn!(
+            check = check.name,
+            status = check.status.as_str(),
+            detail = %check.detail,
+            remediation = check.remediation.as_deref().unwrap_or_default(),
+            "setup diagnostics found a problem"
+        );
+    }
+    if report.status == CheckStatus::Pass {
+        tracing::info!("setup diagnostics passed");
+    }
+}
+
+/// SQLite and sqlite-vec are compiled in, so this only fails for a broken
+/// build; it still proves the extension registers on a fresh connection.
+async fn check_sqlite() -> DiagnosticCheck {
+    const NAME: &str = "sqlite";
+    let probe = async {
+        crate::db::sql_functions::ensure_sqlite_extensions()
+            .map_err(|err| err.detail().to_string())?;
+        let mut conn = SqliteConnection::connect("sqlite::memory:")
+            .await
+            .map_err(|err| err.to_string())?;
+        let versions: (String, String) = sqlx::query_as("SELECT sqlite_version(), vec_version()")
+            .fetch_one(&mut conn)
+            .await
+            .map_err(|err| err.to_string())?;
+        let _ = conn.close().await;
+        Ok::<_, String>(versions)
+    };
+    match probe.await {
+        Ok((sqlite, vec)) => {
+            DiagnosticCheck::pass(NAME, format!("SQLite {sqlite}, sqlite-vec {vec}"))
+        }
+        Err(err) => DiagnosticCheck::problem(
+            NAME,
+            CheckStatus::Fail,
+            format!("sqlite-vec is not available: {err}"),
+            "SQLite and sqlite-vec are built into Panoptikon; reinstall it, or report the \
+             issue if a fresh install fails the same way.",
+        ),
+    }
+}
+
+async fn check_media_tool(tool: MediaTool) -> DiagnosticCheck {
+    let name = tool.name();
+    // Resolution may probe the venv, and the run is a blocking child wait.
+    let result = tokio::task::spawn_blocking(move || {
+        let mut command = tool.command();
+        command.arg("-version");
+        let executable = Path::new(command.get_program()).display().to_string();
+        (executable, media_tools::run(tool, &mut command))
+    })
+    .await;
+    match result {
+        Ok((executable, Ok(stdout))) => {
+            DiagnosticCheck::pass(name, format!("{} ({executable})", version_line(&stdout)))
+        }
+        Ok((executable, Err(err))) => DiagnosticCheck::problem(
+            name,
+            CheckStatus::Fail,
+            format!("{executable}: {err}"),
+            format!(
+                "Install ffmpeg (it includes ffprobe) on PATH, set `[jobs] {name}` to the \
+                 executable, or run `panoptikon setup` to install the managed copy. Videos \
+                 and audio cannot be processed without it."
+            ),
+        ),
+        Err(err) => DiagnosticCheck::problem(
+            name,
+            CheckStatus::Fail,
+            format!("check did not complete: {err}"),
+            "Retry the diagnostics; see the server log for details.",
+        ),
+    }
+}
+
+/// First line of `-version` output, without the copyright notice.
+fn version_line(stdout: &[u8]) -> String {
+    let text = String::from_utf8_lossy(stdout);
+    let line = text.lines().next().unwrap_or_default();
+    line.split(" Copyright")
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_string()
+}
+
+async fn check_python(settings: &Settings) -> DiagnosticCheck {
+    const NAME: &str = "python";
+    if !settings.inference_local.enabled {
+        return DiagnosticCheck::pass(NAME, "not needed: local inference is disabled");
+    }
+    let remediation = "Run `panoptikon setup` to create the managed Python environment, or \
+                       set `[inference_local] python` to a working interpreter.";
+    let python = settings.inference_local.resolved_python();
+    if !python.is_file() {
+        return DiagnosticCheck::problem(
+            NAME,
+            CheckStatus::Fail,
+            format!("no interpreter at {}", python.display()),
+            remediation,
+        );
+    }
+    let mut command = tokio::process::Command::new(&python);
+    command
+        .args(["-c", "import sys; print(sys.version.split()[0])"])
+        .kill_on_drop(true);
+    match tokio::time::timeout(PROBE_TIMEOUT, command.output()).await {
+        Ok(Ok(output)) if output.status.success() => DiagnosticCheck::pass(
+            NAME,
+            format!(
+                "Python {} ({})",
+                String::from_utf8_lossy(&output.stdout).trim(),
+                python.display()
+            ),
+        ),
+        Ok(Ok(output)) => DiagnosticCheck::problem(
+            NAME,
+            CheckStatus::Fail,
+            format!(
+                "{} failed: {}",
+                python.display(),
+                crate::jobs::files::stderr_tail(&output.stderr)
+            ),
+            remediation,
+        ),
+        Ok(Err(err)) => DiagnosticCheck::problem(
+            NAME,
+            CheckStatus::Fail,
+            format!("{} could not be run: {err}", python.display()),
+            remediation,
+        ),
+        Err(_) => DiagnosticCheck::problem(
+            NAME,
+            CheckStatus::Fail,
+            format!(
+                "{} did not answer within {}s",
+                python.display(),
+                PROBE_TIMEOUT.as_secs()
+            ),
+            remediation,
+        ),
+    }
+}
+
+/// pdfium and the headless browser are optional: without them PDF and HTML
+/// files are indexed without thumbnails or extracted pages.
+async fn check_renderers() -> [DiagnosticCheck; 2] {
+    let available = tokio::task::spawn_blocking(|| {
+        (
+            crate::jobs::files::pdf_renderer_available(),
+            crate::jobs::files::html_renderer_available(),
+        )
+    })
+    .await
+    .unwrap_or((false, false));
+    let pdfium = if available.0 {
+        DiagnosticCheck::pass("pdfium", "pdfium library loaded")
+    } else {
+        DiagnosticCheck::problem(
+            "pdfium",
+            CheckStatus::Warn,
+            "pdfium library not found; PDFs get no thumbnails or page extraction",
+            "Place the pdfium library next to the executable or set `[jobs] pdfium`.",
+        )
+    };
+    let html_renderer = if available.1 {
+        DiagnosticCheck::pass("html_renderer", "headless browser found")
+    } else {
+        DiagnosticCheck::problem(
+            "html_renderer",
+            CheckStatus::Warn,
+            "no Chromium-family browser found; HTML files get no thumbnails",
+            "Install Chrome, Chromium or Edge, or set `[jobs] html_renderer`.",
+        )
+    };
+    [pdfium, html_renderer]
+}
+
+async fn check_data_folder(settings: &Settings) -> DiagnosticCheck {
+    let folder = settings.data_folder.clone();
+    let readonly = settings.readonly;
+    tokio::task::spawn_blocking(move || data_folder_check(&folder, readonly, MIN_FREE_BYTES))
+        .await
+        .unwrap_or_else(|err| {
+            DiagnosticCheck::problem(
+                "data_folder",
+                CheckStatus::Fail,
+                format!("check did not complete: {err}"),
+                "Retry the diagnostics; see the server log for details.",
+            )
+        })
+}
+
+fn data_folder_check(folder: &Path, readonly: bool, min_free_bytes: u64) -> DiagnosticCheck {
+    const NAME: &str = "data_folder";
+    let shown = folder.display();
+    if readonly {
+        if !folder.is_dir() {
+            return DiagnosticCheck::problem(
+                NAME,
+                CheckStatus::Fail,
+                format!("{shown} does not exist"),
+                "Point `data_folder` at the folder holding your databases.",
+            );
+        }
+        return DiagnosticCheck::pass(NAME, format!("{shown} (readonly: not written)"));
+    }
+    let probe = std::fs::create_dir_all(folder)
+        .and_then(|()| std::fs::write(folder.join(PROBE_FILE), b"ok"))
+        .and_then(|()| std::fs::remove_file(folder.join(PROBE_FILE)));
+    if let Err(err) = probe {
+        return DiagnosticCheck::problem(
+            NAME,
+            CheckStatus::Fail,
+            format!("{shown} is not writable: {err}"),
+            "Give the user running Panoptikon write access to the folder, or point \
+             `data_folder` somewhere it can write.",
+        );
+    }
+    match fs2::available_space(folder) {
+        Ok(free) if free < min_free_bytes => DiagnosticCheck::problem(
+            NAME,
+            CheckStatus::Warn,
+            format!("{shown}: only {} MiB free", free / (1024 * 1024)),
+            "Free up space: thumbnails, extracted data and database maintenance need room \
+             to grow.",
+        ),
+        Ok(free) => {
+            DiagnosticCheck::pass(NAME, format!("{shown}: {} MiB free", free / (1024 * 1024)))
+        }
+        Err(err) => DiagnosticCheck::problem(
+            NAME,
+            CheckStatus::Warn,
+            format!("{shown}: free space unknown: {err}"),
+            "Make sure the disk holding the data folder has room to grow.",
+        ),
+    }
+}
+
+async fn check_inference(settings: &Settings, serving: bool) -> DiagnosticCheck {
+    const NAME: &str = "inference";
+    if settings.inference_local.enabled && !serving {
+        return DiagnosticCheck::pass(
+            NAME,
+            "skipped: local inference runs inside the server (see the python check)",
+        );
+    }
+    if !serving && inference_through_own_listener(settings) {
+        return DiagnosticCheck::pass(
+            NAME,
+            "skipped: inference is reached through this server, which is not running",
+        );
+    }
+    let remediation = if settings.inference_local.enabled {
+        "Check the server log for inference worker errors, and run `panoptikon setup` if the \
+         Python environment is incomplete."
+    } else {
+        "Start the inference server, or fix `base_url` in `[[upstreams.inference]]`."
+    };
+    // Uncached: a cached copy would pass without contacting the server.
+    let client = match InferenceApiClient::from_settings_with_metadata_cache(settings, false) {
+        Ok(client) => client,
+        Err(err) => {
+            return DiagnosticCheck::problem(
+                NAME,
+                CheckStatus::Fail,
+                format!("{err:#}"),
+                remediation,
+            );
+        }
+    };
+    match tokio::time::timeout(PROBE_TIMEOUT, client.get_metadata()).await {
+        Ok(Ok(metadata)) => {
+            let groups = metadata.as_object().map_or(0, |groups| groups.len());
+            DiagnosticCheck::pass(NAME, format!("metadata fetched: {groups} model groups"))
+        }
+        Ok(Err(err)) => DiagnosticCheck::problem(
+            NAME,
+            CheckStatus::Fail,
+            format!("metadata fetch failed: {err:#}"),
+            remediation,
+        ),
+        Err(_) => DiagnosticCheck::problem(
+            NAME,
+            CheckStatus::Fail,
+            format!(
+                "metadata fetch did not finish within {}s",
+                PROBE_TIMEOUT.as_secs()
+            ),
+            remediation,
+        ),
+    }
+}
+
+/// Whether the inference base URL points at one of this server's own
+/// listeners (the default, proxying to the configured inference server).
+fn inference_through_own_listener(settings: &Settings) -> bool {
+    let Some(url) = settings
+        .upstreams
+        .inference
+        .first()
+        .and_then(|inference| url::Url::parse(&inference.base_url).ok())
+    else {
+        return false;
+    };
+    let (Some(host), Some(port)) = (url.host_str(), url.port_or_known_default()) else {
+        return false;
+    };
+    let loopback = matches!(host, "127.0.0.1" | "localhost" | "[::1]");
+    settings.listener_addrs().iter().any(|(_, addr)| {
+        addr.rsplit_once(':')
+            .is_some_and(|(listen_host, listen_port)| {
+                listen_port == port.to_string() && (loopback || listen_host == host)
+            })
+    })
+}
+
+async fn check_schema() -> DiagnosticCheck {
+    match default_schema_status().await {
+        Ok(statuses) => schema_check(&statuses),
+        Err(err) => DiagnosticCheck::problem(
+            "schema",
+            CheckStatus::Fail,
+            format!("{err:#}"),
+            "The database may be corrupt or locked; see the server log, and restore it from \
+             a backup if it cannot be opened.",
+        ),
+    }
+}
+
+/// A database from a newer build fails (this build would misread it);
+/// missing or outdated ones only warn, since startup creates and migrates
+/// them.
+fn schema_check(statuses: &[SchemaStatus]) -> DiagnosticCheck {
+    const NAME: &str = "schema";
+    let mut status = CheckStatus::Pass;
+    let mut parts = Vec::with_capacity(statuses.len());
+    for entry in statuses {
+        let shown = entry.path.display();
+        match entry.applied {
+            _ if !entry.path.is_file() => {
+                status = status.max(CheckStatus::Warn);
+                parts.push(format!("{shown}: not created yet"));
+            }
+            Some(applied) if applied > entry.latest => {
+                status = CheckStatus::Fail;
+                parts.push(format!(
+                    "{shown}: version {applied} is newer than this build's {}",
+                    entry.latest
+                ));
+            }
+            Some(applied) if applied == entry.latest => {
+                parts.push(format!("{shown}: version {applied}"));
+            }
+            applied => {
+                status = status.max(CheckStatus::Warn);
+                parts.push(format!(
+                    "{shown}: version {} of {}",
+                    applied.unwrap_or(0),
+                    entry.latest
+                ));
+            }
+        }
+    }
+    let detail = parts.join("; ");
+    match status {
+        CheckStatus::Pass => DiagnosticCheck::pass(NAME, detail),
+        CheckStatus::Warn => DiagnosticCheck::problem(
+            NAME,
+            status,
+            detail,
+            "Start the server or run `panoptikon migrate` to create and update the databases.",
+        ),
+        CheckStatus::Fail => DiagnosticCheck::problem(
+            NAME,
+            status,
+            detail,
+            "A newer Panoptikon wrote these databases; update Panoptikon before using them.",
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Ensures a writable folder passes, low free space warns, and a
+    // readonly run never writes but fails on a missing folder.
+    #[test]
+    fn data_folder_check_probes_writability_and_space() {
+        let dir = tempfile::tempdir().unwrap();
+        let folder = dir.path().join("data");
+
+        let check = data_folder_check(&folder, false, 0);
+        assert_eq!(check.status, CheckStatus::Pass, "{}", check.detail);
+        assert!(folder.is_dir());
+        assert!(!folder.join(PROBE_FILE).exists());
+
+        let check = data_folder_check(&folder, false, u64::MAX);
+        assert_eq!(check.status, CheckStatus::Warn);
+        assert!(check.remediation.is_some());
+
+        let missing = dir.path().join("missing");
+        let check = data_folder_check(&missing, true, 0);
+        assert_eq!(check.status, CheckStatus::Fail);
+        assert!(!missing.exists());
+    }
+
+    // Ensures the schema check fails only for databases from a newer build
+    // and warns for missing or outdated ones.
+    #[test]
+    fn schema_check_grades_versions() {
+        let dir = tempfile::tempdir().unwrap();
+        let current = dir.path().join("index.db");
+        std::fs::write(&current, b"").unwrap();
+        let status = |path: &Path, applied, latest| SchemaStatus {
+            path: path.to_path_buf(),
+            applied,
+            latest,
+        };
+
+        let check = schema_check(&[status(&current, Some(5), 5)]);
+        assert_eq!(check.status, CheckStatus::Pass);
+
+        let missing = dir.path().join("storage.db");
+        let check = schema_check(&[status(&current, Some(4), 5), status(&missing, None, 5)]);
+        assert_eq!(check.status, CheckStatus::Warn);
+        assert!(check.detail.contains("version 4 of 5"), "{}", check.detail);
+        assert!(check.detail.contains("not created yet"), "{}", check.detail);
+
+        let check = schema_check(&[status(&current, Some(6), 5), status(&missing, None, 5)]);
+        assert_eq!(check.status, CheckStatus::Fail);
+    }
+
+    #[test]
+    fn version_line_drops_the_copyright_notice() {
+        let stdout =
+            b"ffmpeg version 6.1.1 Copyright (c) 2000-2023 the FFmpeg developers\nbuilt with gcc\n";
+        assert_eq!(version_line(stdout), "ffmpeg version 6.1.1");
+    }
+}
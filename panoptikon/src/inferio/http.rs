@@ -590,14 +590,20 @@ async fn get_external_inputs(
     tag = "inference",
     summary = "Inference service health",
     description = "Orchestrator + per-model liveness, queue depths, and batch \
-        caps. Gateway addition — the Python inference server has no such \
+        caps, plus the last startup search warmup report and index DB \
+        writer backlogs in gateway mode. \
+        Gateway addition — the Python inference server has no such \
         endpoint (a proxied upstream 404s it).",
     responses(
         (status = 200, description = "Health report", body = HealthReport)
     )
 )]
 async fn health(State(state): State<Arc<InferioState>>) -> Json<HealthReport> {
-    Json(state.manager.health())
+    let mut report = state.manager.health();
+    report.search_warmup = crate::api::search_warmup::last_report();
+    report.index_writers = crate::db::index_writer::index_writer_status().await;
+    report.instance_locks = crate::db::instance_lock::lock_status();
+    Json(report)
 }
 
 /// Port of `utils.parse_input_request`: the `data` form field is a JSON
@@ -731,11 +737,17 @@ fn encode_output_response(outputs: Vec<WorkerOutput>) -> Response {
         CacheKeyResponse,
         CacheListResponse,
         crate::api_error::ErrorBody,
+        crate::api_error::ErrorCode,
         HealthReport,
         super::manager::ModelHealth,
         super::manager::ReplicaHealth,
         super::prewarm::PrewarmHealth,
-        super::prewarm::PrewarmWorkerHealth
+        super::prewarm::PrewarmWorkerHealth,
+        crate::api::search_warmup::SearchWarmupReport,
+        crate::api::search_warmup::SearchWarmupStep,
+        crate::db::index_writer::IndexWriterStatus,
+        crate::db::instance_lock::DbLockStatus,
+        crate::db::instance_lock::DbLockOwner
     ))
 )]
 pub struct InferioApiDoc;
@@ -1209,6 +1221,8 @@ metadata.description = "batch size reporter"
             base_url,
             weight: 1.0,
             use_for_jobs: true,
+            timeouts: Default::default(),
+            websocket: Default::default(),
         }])
         .expect("pool builds");
 
@@ -1488,6 +1502,9 @@ config.impl_class = "echo_test"
                 policy_token_key: None,
                 endpoints: Vec::new(),
                 check_for_updates: false,
+                db_lock_conflict: Default::default(),
+                db_lock_stale_secs: 30,
+                startup_diagnostics: false,
             },
             upstreams: UpstreamsConfig {
                 ui: crate::config::UiUpstreamConfig {
@@ -1496,10 +1513,14 @@ config.impl_class = "echo_test"
                     dir: None,
                     node: None,
                     build: Default::default(),
+                    timeouts: Default::default(),
+                    websocket: Default::default(),
                 },
                 api: UpstreamConfig {
                     base_url: "http://127.0.0.1:6342".to_string(),
                     local: false,
+                    timeouts: Default::default(),
+                    websocket: Default::default(),
                 },
                 inference: Vec::new(),
             },
@@ -1508,10 +1529,13 @@ config.impl_class = "echo_test"
             user_data_db: "default".to_string(),
             readonly: false,
             temp_dir: std::path::PathBuf::from("data/tmp"),
+            thumbnail_storage: Default::default(),
             logging: Default::default(),
             open: Default::default(),
             search: Default::default(),
             jobs: Default::default(),
+            notifications: Default::default(),
+            reports: Default::default(),
             rulesets: Default::default(),
             policies: Vec::new(),
             inference_local: InferenceLocalConfig {
@@ -104,6 +104,18 @@ pub struct HealthReport {
     /// entry per impl class held (state "warm" | "spawning" |
     /// "failed_prepare").
     pub prewarm: PrewarmHealth,
+    /// Last startup search warmup (`[search.warmup]`), gateway mode only;
+    /// attached by the HTTP handler, absent until a warmup has finished.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub search_warmup: Option<crate::api::search_warmup::SearchWarmupReport>,
+    /// Index DB writer backlogs, gateway mode only; attached by the HTTP
+    /// handler, empty until a writer has been started.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub index_writers: Vec<crate::db::index_writer::IndexWriterStatus>,
+    /// Index DB instance lock ownership, gateway mode only; attached by the
+    /// HTTP handler, empty when this process took no locks.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub instance_locks: Vec<crate::db::instance_lock::DbLockStatus>,
 }
 
 /// One loaded model in the [`HealthReport`].
@@ -734,6 +746,9 @@ impl ModelManager {
             model_count: models.len(),
             models,
             prewarm,
+            search_warmup: None,
+            index_writers: Vec::new(),
+            instance_locks: Vec::new(),
         }
     }
 
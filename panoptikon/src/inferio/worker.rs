@@ -47,6 +47,7 @@ use tokio::process::{Child, ChildStderr, ChildStdin, ChildStdout, Command};
 use tokio::time::timeout;
 
 use super::registry::SpawnSpec;
+use crate::msgpack::{json_to_rmpv, rmpv_to_json};
 use crate::process_tree::{JobGuard, detach_from_console, die_with_parent, kill_process_group};
 
 /// Protocol version this orchestrator speaks; workers answering anything
@@ -890,81 +891,6 @@ fn take_field(map: &mut Vec<(Value, Value)>, key: &str) -> Option<Value> {
     Some(map.swap_remove(index).1)
 }
 
-/// JSON → msgpack value. Straightforward except numbers: serde_json numbers
-/// are exactly one of i64/u64/f64, and each maps to the corresponding
-/// msgpack representation so ints stay ints end-to-end.
-fn json_to_rmpv(value: &JsonValue) -> Value {
-    match value {
-        JsonValue::Null => Value::Nil,
-        JsonValue::Bool(b) => Value::Boolean(*b),
-        JsonValue::Number(n) => {
-            if let Some(i) = n.as_i64() {
-                Value::from(i)
-            } else if let Some(u) = n.as_u64() {
-                Value::from(u)
-            } else {
-                // as_f64 is total for serde_json numbers that are not ints.
-                Value::F64(n.as_f64().unwrap_or(f64::NAN))
-            }
-        }
-        JsonValue::String(s) => Value::from(s.as_str()),
-        JsonValue::Array(items) => Value::Array(items.iter().map(json_to_rmpv).collect()),
-        JsonValue::Object(map) => Value::Map(
-            map.iter()
-                .map(|(key, value)| (Value::from(key.as_str()), json_to_rmpv(value)))
-                .collect(),
-        ),
-    }
-}
-
-/// msgpack → JSON value for JSON-like predict outputs. Non-string map keys
-/// are coerced via their msgpack display form (should not occur — Python
-/// dict keys from impls are strings). Binary/ext nested *inside* a JSON-like
-/// value has no JSON form and fails the conversion (top-level bin is handled
-/// as [`WorkerOutput::Bytes`] before this is called).
-fn rmpv_to_json(value: &Value) -> Result<JsonValue> {
-    Ok(match value {
-        Value::Nil => JsonValue::Null,
-        Value::Boolean(b) => JsonValue::Bool(*b),
-        Value::Integer(i) => {
-            if let Some(v) = i.as_i64() {
-                JsonValue::from(v)
-            } else if let Some(v) = i.as_u64() {
-                JsonValue::from(v)
-            } else {
-                bail!("msgpack integer {i} fits neither i64 nor u64")
-            }
-        }
-        Value::F32(f) => serde_json::Number::from_f64(f64::from(*f))
-            .map(JsonValue::Number)
-            .with_context(|| format!("non-finite float {f} has no JSON form"))?,
-        Value::F64(f) => serde_json::Number::from_f64(*f)
-            .map(JsonValue::Number)
-            .with_context(|| format!("non-finite float {f} has no JSON form"))?,
-        Value::String(s) => JsonValue::String(
-            s.as_str()
-                .context("worker sent a non-UTF-8 msgpack string")?
-                .to_owned(),
-        ),
-        Value::Binary(_) => bail!("binary data nested inside a JSON-like output has no JSON form"),
-        Value::Array(items) => {
-            JsonValue::Array(items.iter().map(rmpv_to_json).collect::<Result<_>>()?)
-        }
-        Value::Map(entries) => {
-            let mut map = serde_json::Map::with_capacity(entries.len());
-            for (key, value) in entries {
-                let key = match key.as_str() {
-                    Some(s) => s.to_owned(),
-                    None => key.to_string(),
-                };
-                map.insert(key, rmpv_to_json(value)?);
-            }
-            JsonValue::Object(map)
-        }
-        Value::Ext(tag, _) => bail!("msgpack ext type {tag} has no JSON form"),
-    })
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1273,20 +1199,6 @@ mod tests {
         assert_eq!(status.code(), Some(0));
     }
 
-    /// Non-finite floats and binary/ext nested inside a JSON-like output
-    /// have no JSON form: rmpv_to_json must report an error (never silently
-    /// coerce — the Python side would equally fail to JSON-encode them),
-    /// while ordinary finite floats convert cleanly.
-    #[test]
-    fn rmpv_to_json_rejects_nonfinite_and_nested_binary() {
-        assert!(rmpv_to_json(&Value::F64(f64::NAN)).is_err());
-        assert!(rmpv_to_json(&Value::F64(f64::INFINITY)).is_err());
-        assert!(rmpv_to_json(&Value::F32(f32::NEG_INFINITY)).is_err());
-        assert!(rmpv_to_json(&Value::Array(vec![Value::Binary(vec![1, 2])])).is_err());
-        assert!(rmpv_to_json(&Value::Ext(7, vec![0])).is_err());
-        assert_eq!(rmpv_to_json(&Value::F64(1.5)).unwrap(), json!(1.5));
-    }
-
     /// Data fidelity: a JSON value exercising nested unicode strings,
     /// positive/negative/large integers, floats, booleans, null, lists, and
     /// maps survives the JSON → msgpack → Python → msgpack → JSON round trip
@@ -7,9 +7,9 @@ use reqwest_retry::policies::ExponentialBackoff;
 use serde_json::{Value, json};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
-use std::sync::OnceLock;
+use std::sync::{Arc, Mutex as StdMutex, OnceLock};
 use std::time::{Duration, Instant};
-use tokio::sync::RwLock;
+use tokio::sync::{Mutex, RwLock};
 use tracing::warn;
 
 use crate::config::Settings;
@@ -52,7 +52,16 @@ struct CachedMetadata {
     fetched_at: Instant,
 }
 
-static METADATA_CACHE: OnceLock<RwLock<HashMap<String, CachedMetadata>>> = OnceLock::new();
+/// One upstream's cached `/metadata`. `refresh` serializes refetches so a
+/// burst of callers that all find the entry stale (or all ask for a forced
+/// refresh) share a single upstream request instead of stampeding it.
+#[derive(Debug, Default)]
+struct MetadataSlot {
+    entry: RwLock<Option<CachedMetadata>>,
+    refresh: Mutex<()>,
+}
+
+static METADATA_CACHE: OnceLock<StdMutex<HashMap<String, Arc<MetadataSlot>>>> = OnceLock::new();
 const METADATA_CACHE_TTL: Duration = Duration::from_secs(300);
 const PREDICT_MAX_RETRIES: u32 = 3;
 const PREDICT_MIN_DELAY: Duration = Duration::from_secs(1);
@@ -237,29 +246,66 @@ impl InferenceApiClient {
     }
 
     pub async fn get_metadata(&self) -> Result<Value> {
+        self.get_metadata_with_age().await.map(|(value, _)| value)
+    }
+
+    /// `/metadata` plus the age of the cached copy it came from; the age is
+    /// `None` when the client does not cache metadata.
+    pub async fn get_metadata_with_age(&self) -> Result<(Value, Option<Duration>)> {
+        if !self.cache_metadata {
+            return Ok((self.fetch_metadata().await?, None));
+        }
+        let slot = self.metadata_slot();
+        if let Some(cached) = fresh_entry(&slot).await {
+            return Ok(cached);
+        }
+        let _refresh = slot.refresh.lock().await;
+        // Whoever held the lock before us may have refetched already.
+        if let Some(cached) = fresh_entry(&slot).await {
+            return Ok(cached);
+        }
+        self.refetch_into(&slot).await
+    }
+
+    /// Refetches `/metadata` regardless of the TTL. Callers that queue up
+    /// behind a refetch that started after they asked reuse its result. A
+    /// failed refetch drops the cached copy so the next read retries.
+    pub async fn refresh_metadata(&self) -> Result<Value> {
         if !self.cache_metadata {
             return self.fetch_metadata().await;
         }
-        let cache = METADATA_CACHE.get_or_init(|| RwLock::new(HashMap::new()));
+        let requested_at = Instant::now();
+        let slot = self.metadata_slot();
+        let _refresh = slot.refresh.lock().await;
+        if let Some(entry) = slot.entry.read().await.as_ref()
+            && entry.fetched_at >= requested_at
         {
-            let guard = cache.read().await;
-            if let Some(entry) = guard.get(&self.base_url) {
-                if entry.fetched_at.elapsed() < METADATA_CACHE_TTL {
-                    return Ok(entry.value.clone());
-                }
-            }
+            return Ok(entry.value.clone());
         }
+        let result = self.refetch_into(&slot).await;
+        if result.is_err() {
+            *slot.entry.write().await = None;
+        }
+        result.map(|(value, _)| value)
+    }
 
+    fn metadata_slot(&self) -> Arc<MetadataSlot> {
+        let cache = METADATA_CACHE.get_or_init(|| StdMutex::new(HashMap::new()));
+        let mut guard = cache.lock().unwrap_or_else(|err| err.into_inner());
+        Arc::clone(guard.entry(self.base_url.clone()).or_default())
+    }
+
+    /// Callers hold `slot.refresh`. The timestamp is taken before the request
+    /// so a forced refresh never mistakes a response that was already in
+    /// flight for one issued after it asked.
+    async fn refetch_into(&self, slot: &MetadataSlot) -> Result<(Value, Option<Duration>)> {
+        let fetched_at = Instant::now();
         let value = self.fetch_metadata().await?;
-        let mut guard = cache.write().await;
-        guard.insert(
-            self.base_url.clone(),
-            CachedMetadata {
-                value: value.clone(),
-                fetched_at: Instant::now(),
-            },
-        );
-        Ok(value)
+        *slot.entry.write().await = Some(CachedMetadata {
+            value: value.clone(),
+            fetched_at,
+        });
+        Ok((value, Some(fetched_at.elapsed())))
     }
 
     async fn fetch_metadata(&self) -> Result<Value> {
@@ -302,6 +348,13 @@ impl InferenceApiClient {
     }
 }
 
+async fn fresh_entry(slot: &MetadataSlot) -> Option<(Value, Option<Duration>)> {
+    let guard = slot.entry.read().await;
+    let entry = guard.as_ref()?;
+    let age = entry.fetched_at.elapsed();
+    (age < METADATA_CACHE_TTL).then(|| (entry.value.clone(), Some(age)))
+}
+
 async fn file_to_part(idx: usize, file: &InferenceFile) -> Result<Part> {
     let name = idx.to_string();
     let part = match file {
@@ -624,4 +677,48 @@ mod tests {
             queries[3]
         );
     }
+
+    /// Cached metadata is single-flight: a burst of readers that all miss
+    /// the cache shares one `/metadata` request, later readers report the
+    /// cached copy's age, and an explicit refresh refetches despite the TTL.
+    #[tokio::test]
+    async fn metadata_cache_shares_one_fetch_and_refreshes_on_demand() {
+        let hits = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = Arc::clone(&hits);
+        let app = Router::new().route(
+            "/api/inference/metadata",
+            get(move || {
+                let counter = Arc::clone(&counter);
+                async move {
+                    let n = counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    Json(json!({"group": {"inference_ids": {"fetch": n}}}))
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let client =
+            InferenceApiClient::new_with_metadata_cache(format!("http://{addr}"), true).unwrap();
+        let readers = (0..8).map(|_| {
+            let client = client.clone();
+            tokio::spawn(async move { client.get_metadata().await.unwrap() })
+        });
+        for reader in readers.collect::<Vec<_>>() {
+            assert_eq!(reader.await.unwrap()["group"]["inference_ids"]["fetch"], 1);
+        }
+        assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+        let (_, age) = client.get_metadata_with_age().await.unwrap();
+        assert!(age.is_some(), "cached reads report their age");
+
+        let refreshed = client.refresh_metadata().await.unwrap();
+        assert_eq!(refreshed["group"]["inference_ids"]["fetch"], 2);
+        assert_eq!(client.get_metadata().await.unwrap(), refreshed);
+        assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 2);
+    }
 }
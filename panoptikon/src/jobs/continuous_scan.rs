@@ -20,10 +20,12 @@ use crate::db::{
     file_scans::{FileScanUpdate, get_open_file_scan_id},
     files::{
         FileDeleteInfo, FileUpsertResult, count_files_for_item, get_all_file_paths_with_mtime,
-        get_file_by_path, get_file_delete_info,
+        get_file_by_path, get_file_delete_info, get_files_by_paths,
     },
     index_writer::{IndexDbWriterMessage, call_index_db_writer},
     open_index_db_read,
+    path_keys::PathKeys,
+    raw_paths::{display_path, path_bytes},
     storage::{has_frame, has_thumbnail},
     system_config::{SystemConfig, SystemConfigStore},
 };
@@ -31,12 +33,17 @@ use crate::jobs::dir_poller::{
     FileMeta, PollFilters, PollOutcome, PollerSnapshot, run_poll_pass, seed_snapshot,
 };
 use crate::jobs::files::{
-    FRAME_PROCESS_VERSION, FileProcessError, PreparedFile, SCAN_PROGRESS_INTERVAL, ScanOptions,
-    ScanTimers, THUMBNAIL_PROCESS_VERSION, build_extension_set, build_file_scan_data,
+    FRAME_PROCESS_VERSION, FileProcessError, IgnoredDirs, PreparedFile, SCAN_PROGRESS_INTERVAL,
+    ScanOptions, ScanTimers, THUMBNAIL_PROCESS_VERSION, build_extension_set, build_file_scan_data,
     check_folder_validity, current_iso_timestamp, deduplicate_paths, folder_is_empty,
-    get_last_modified_time_and_size, has_allowed_extension, is_excluded, is_hidden_or_temp,
-    normalize_path, parse_filescan_filter, process_file, run_post_job_maintenance,
+    format_system_time, get_last_modified_time_and_size, has_allowed_extension, is_excluded,
+    is_hidden_or_temp, normalize_path, parse_filescan_filter, predict_stored_visuals, process_file,
+    run_post_job_maintenance,
 };
+use crate::jobs::ignore_files::{IGNORE_FILE_NAME, IgnoreFiles};
+use crate::jobs::implicit_exclusions::{applied_implicit_exclusions, implicit_excluded_roots};
+use crate::jobs::scan_io::folder_worker_count;
+use crate::jobs::symlinks::SymlinkPolicy;
 use crate::pql::model::Match;
 
 type ApiResult<T> = Result<T, ApiError>;
@@ -55,16 +62,30 @@ const MAINTENANCE_DELETION_THRESHOLD: u64 = 1000;
 const POLL_SETTLE_DELAY: Duration = Duration::from_secs(2);
 // Backoff ceiling for files that keep changing (e.g. a long copy in progress).
 const SETTLE_MAX_DELAY: Duration = Duration::from_secs(60);
+// Re-queues of a file a worker found still being written. Past this the file
+// is left until its next change event (or the next full scan).
+const DEFERRED_MAX_RETRIES: u32 = 8;
 // Poll interval used when the native watcher was requested but failed to start
 // (commonly the OS watch-descriptor limit on a large tree). Polling is heavier
 // than the watcher, so this only ever applies as a degraded fallback, and the
 // status endpoint reports it so the choice is visible rather than silent.
 const WATCHER_FALLBACK_POLL_INTERVAL: Duration = Duration::from_secs(60);
+// Create/modify events are collected for this long and checked against the
+// index in one pass, so an event storm on unchanged files (a backup touching
+// mtimes) costs a few IN queries instead of a connection and a point query
+// per event.
+const EVENT_LOOKUP_WINDOW: Duration = Duration::from_millis(250);
+// Paths per IN query; well under SQLite's variable limit.
+const EVENT_LOOKUP_CHUNK: usize = 500;
 
 #[derive(Clone)]
 struct FileWork {
     path: PathBuf,
+    path_key: String,
+    /// How many times this path was already deferred as still being written.
+    attempts: u32,
     filescan_filter: Option<Arc<Match>>,
+    settle: Duration,
     epoch: u64,
     scan_time: String,
     index_db: String,
@@ -98,7 +119,10 @@ impl Worker for ContinuousWorker {
     ) -> Result<Self::Key, ActorProcessingErr> {
         let FileWork {
             path,
+            path_key,
+            attempts,
             filescan_filter,
+            settle,
             epoch,
             scan_time,
             index_db,
@@ -111,38 +135,53 @@ impl Worker for ContinuousWorker {
         // the full-scan dedup: spurious watcher events and re-dispatched paths
         // cost one stat and one point query instead of a full re-process.
         let stat_path = path.clone();
-        let disk_mtime = tokio::task::spawn_blocking(move || {
-            get_last_modified_time_and_size(&stat_path).map(|(mtime, _)| mtime)
-        })
-        .await
-        .ok()
-        .and_then(|res| res.ok());
-        if let Some(disk_mtime) = &disk_mtime {
-            if let Ok(mut conn) = open_index_db_read(&index_db, &user_data_db).await {
-                if let Ok(Some(existing)) =
-                    get_file_by_path(&mut conn, path.to_string_lossy().as_ref()).await
-                {
-                    if &existing.last_modified == disk_mtime {
-                        let _ = reply_to.cast(ContinuousScanMessage::WorkerResult {
-                            epoch,
-                            scan_time,
-                            result: Err(FileProcessError::Unchanged),
-                        });
-                        return Ok(job.key);
-                    }
-                }
+        let disk_meta =
+            tokio::task::spawn_blocking(move || get_last_modified_time_and_size(&stat_path))
+                .await
+                .ok()
+                .and_then(|res| res.ok());
+        let mut stored_visuals = None;
+        if let Some((disk_mtime, disk_size)) = &disk_meta
+            && let Ok(mut conn) = open_index_db_read(&index_db, &user_data_db).await
+            && let Ok(Some(existing)) = get_file_by_path(&mut conn, &path_key).await
+        {
+            if &existing.last_modified == disk_mtime {
+                let _ = reply_to.cast(ContinuousScanMessage::WorkerResult {
+                    epoch,
+                    scan_time,
+                    path,
+                    attempts,
+                    result: Err(FileProcessError::Unchanged),
+                });
+                return Ok(());
             }
+            // A touched file usually keeps its content; don't re-render
+            // visuals the index already has unless hashing disproves it.
+            stored_visuals = predict_stored_visuals(&mut conn, &path, &existing, *disk_size)
+                .await
+                .ok()
+                .flatten();
         }
 
-        let result =
-            tokio::task::spawn_blocking(move || process_file(path, filescan_filter, &timers))
-                .await
-                .map_err(|err| FileProcessError::Worker(err.to_string()))
-                .and_then(|res| res);
+        let work_path = path.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            process_file(
+                work_path,
+                filescan_filter,
+                stored_visuals.as_deref(),
+                settle,
+                &timers,
+            )
+        })
+        .await
+        .map_err(|err| FileProcessError::Worker(err.to_string()))
+        .and_then(|res| res);
 
         let _ = reply_to.cast(ContinuousScanMessage::WorkerResult {
             epoch,
             scan_time,
+            path,
+            attempts,
             result,
         });
         Ok(job.key)
@@ -196,15 +235,45 @@ pub(crate) enum ContinuousScanMessage {
         epoch: u64,
         path: PathBuf,
     },
+    /// The event lookup window closed: check the pending paths against the
+    /// index off the actor.
+    FlushLookups,
+    /// Pending paths were checked; dispatch the ones that changed.
+    LookupsResolved {
+        epoch: u64,
+        outcome: LookupOutcome,
+    },
+    /// Re-dispatch a file a worker found still being written.
+    RetryDeferred {
+        epoch: u64,
+        path: PathBuf,
+        attempts: u32,
+    },
     WorkerResult {
         epoch: u64,
         scan_time: String,
+        path: PathBuf,
+        attempts: u32,
         result: Result<PreparedFile, FileProcessError>,
     },
     /// Point-in-time state for the status endpoint.
     GetStatus {
         reply: oneshot::Sender<ContinuousScanSnapshot>,
     },
+    #[cfg(test)]
+    GetStats {
+        reply: oneshot::Sender<ScanStats>,
+    },
+}
+
+/// Result of checking a batch of event paths against the index.
+pub(crate) struct LookupOutcome {
+    /// Files that are new or whose mtime or size differ from the index.
+    changed: Vec<PathBuf>,
+    /// Files that still match their indexed mtime and size.
+    unchanged: i64,
+    /// IN queries issued.
+    queries: usize,
 }
 
 /// Live scanner state reported to the status endpoint. Paths are stringified
@@ -242,13 +311,20 @@ pub(crate) struct WatchRootsOutcome {
     pub invalid_includes: Vec<String>,
 }
 
-struct ScanStats {
+#[cfg_attr(test, derive(Debug, Clone))]
+pub(crate) struct ScanStats {
     new_items: i64,
     unchanged_files: i64,
     new_files: i64,
     modified_files: i64,
     marked_unavailable: i64,
     errors: i64,
+    timeouts: i64,
+    undecodable: i64,
+    deferred: i64,
+    /// Created, modified, or renamed-to files dropped because they sit in an
+    /// ignored directory; one per event.
+    ignored_dir_files: i64,
     total_available: i64,
     false_changes: i64,
 }
@@ -262,6 +338,10 @@ impl ScanStats {
             modified_files: 0,
             marked_unavailable: 0,
             errors: 0,
+            timeouts: 0,
+            undecodable: 0,
+            deferred: 0,
+            ignored_dir_files: 0,
             total_available: 0,
             false_changes: 0,
         }
@@ -356,6 +436,12 @@ pub(crate) struct ContinuousScanState {
     invalid_includes: Vec<String>,
     roots_valid: bool,
     allowed_extensions: HashSet<String>,
+    ignored_dirs: IgnoredDirs,
+    symlinks: SymlinkPolicy,
+    path_keys: PathKeys,
+    /// Cached `.panoptikonignore` rules; kept across config reloads so the
+    /// cache survives unless the feature is toggled.
+    ignore_files: IgnoreFiles,
     filescan_filter: Option<Arc<Match>>,
     scan_id: Option<i64>,
     scan_time: Option<String>,
@@ -371,6 +457,8 @@ pub(crate) struct ContinuousScanState {
     actor_ref: ActorRef<ContinuousScanMessage>,
     factory: ActorRef<FactoryMessage<(), FileWork>>,
     factory_handle: Option<ractor::concurrency::JoinHandle<()>>,
+    /// Current size of the factory's worker pool.
+    worker_count: usize,
     watcher: Option<RecommendedWatcher>,
     poller: Option<PollerRuntime>,
     /// True when the native watcher was configured but failed to start and the
@@ -380,6 +468,9 @@ pub(crate) struct ContinuousScanState {
     watcher_fallback: bool,
     enable_watcher: bool,
     deletions_since_maintenance: u64,
+    /// Create/modify paths waiting for the batched index lookup; a
+    /// `FlushLookups` is scheduled whenever this goes from empty to non-empty.
+    pending_lookups: HashSet<PathBuf>,
 }
 impl ContinuousScanState {
     fn reset_stats(&mut self) {
@@ -409,6 +500,16 @@ impl ContinuousScanState {
             modified_files: self.stats.modified_files,
             marked_unavailable: self.stats.marked_unavailable,
             errors: self.stats.errors,
+            timeouts: self.stats.timeouts,
+            undecodable: self.stats.undecodable,
+            deferred: self.stats.deferred,
+            // Ignored directories are filtered per event, not pruned once.
+            ignored_dirs: 0,
+            ignored_dir_files: self.stats.ignored_dir_files,
+            ignored_files: 0,
+            visuals_deferred: 0,
+            skipped_dirs: 0,
+            worker_count: self.worker_count as i64,
             total_available: self.stats.total_available,
             false_changes: self.stats.false_changes,
             metadata_time: self.timers.metadata.busy_secs(),
@@ -447,10 +548,48 @@ impl ContinuousScanState {
         let outcome = compute_watch_roots_with_safe_empty(&self.config, &safe_empty);
         self.watch_roots = outcome.watch_roots;
         self.excluded_roots = outcome.excluded_roots;
+        // Our own DB and scratch writes must never trigger a scan.
+        let implicit_roots = implicit_excluded_roots(self.config_store.data_dir());
+        applied_implicit_exclusions(&implicit_roots, &self.config.included_folders);
+        for root in implicit_roots {
+            if !self.excluded_roots.contains(&root) {
+                self.excluded_roots.push(root);
+            }
+        }
         self.invalid_includes = outcome.invalid_includes;
         self.roots_valid = outcome.valid;
         self.allowed_extensions = build_extension_set(&self.config);
+        self.ignored_dirs = IgnoredDirs::from_config(&self.config);
+        // Links are judged against everything a full scan covers, so a file
+        // already indexed under its real path isn't re-added through a link
+        // inside a watched folder.
+        let scanned_roots = self
+            .config
+            .included_folders
+            .iter()
+            .map(|folder| normalize_path(folder, true))
+            .chain(self.watch_roots.iter().cloned())
+            .collect::<Vec<_>>();
+        self.symlinks = SymlinkPolicy::new(&self.config, &scanned_roots, &self.excluded_roots);
+        let path_keys = PathKeys::new(&self.config);
+        if path_keys != self.path_keys {
+            if let Err(err) = call_index_db_writer(&self.index_db, |reply| {
+                IndexDbWriterMessage::RekeyFilePaths {
+                    keys: path_keys.clone(),
+                    reply,
+                }
+            })
+            .await
+            {
+                tracing::error!(index_db = %self.index_db, error = ?err, "failed to rekey file paths");
+            }
+            self.path_keys = path_keys;
+        }
+        if self.ignore_files.enabled() != self.config.respect_ignore_files {
+            self.ignore_files = IgnoreFiles::from_config(&self.config);
+        }
         self.filescan_filter = parse_filescan_filter(&self.config).map(Arc::new);
+        self.resize_worker_pool().await;
         if !outcome.valid {
             tracing::warn!(
                 index_db = %self.index_db,
@@ -461,6 +600,30 @@ impl ContinuousScanState {
         outcome.valid
     }
 
+    /// The pool is shared by every watched folder, so it is sized for the
+    /// slowest one: the smallest worker count any root's
+    /// `folder_scan_settings` resolves to.
+    async fn resize_worker_pool(&mut self) {
+        let ssd_workers = ScanOptions::default().worker_count;
+        let mut worker_count: Option<usize> = None;
+        for root in &self.watch_roots {
+            let root_workers = folder_worker_count(&self.config, root, ssd_workers).await;
+            worker_count = Some(worker_count.map_or(root_workers, |count| count.min(root_workers)));
+        }
+        let worker_count = worker_count.unwrap_or(ssd_workers);
+        if worker_count != self.worker_count {
+            tracing::info!(
+                index_db = %self.index_db,
+                worker_count,
+                "resizing continuous scan worker pool"
+            );
+            let _ = self
+                .factory
+                .cast(FactoryMessage::AdjustWorkerPool(worker_count));
+            self.worker_count = worker_count;
+        }
+    }
+
     async fn start_scan(&mut self) -> ApiResult<()> {
         let scan_time = current_iso_timestamp();
         let scan_id =
@@ -491,6 +654,15 @@ impl ContinuousScanState {
             modified_files: self.stats.modified_files,
             marked_unavailable: self.stats.marked_unavailable,
             errors: self.stats.errors,
+            timeouts: self.stats.timeouts,
+            undecodable: self.stats.undecodable,
+            deferred: self.stats.deferred,
+            ignored_dirs: 0,
+            ignored_dir_files: self.stats.ignored_dir_files,
+            ignored_files: 0,
+            visuals_deferred: 0,
+            skipped_dirs: 0,
+            worker_count: self.worker_count as i64,
             total_available: self.stats.total_available,
             false_changes: self.stats.false_changes,
             metadata_time: self.timers.metadata.busy_secs(),
@@ -527,6 +699,34 @@ impl ContinuousScanState {
         Ok(())
     }
 
+    /// Drops cached `.panoptikonignore` rules the event may have changed: the
+    /// directory of an ignore file that was written or removed, and both
+    /// sides of a rename (a moved directory takes its ignore files along).
+    fn invalidate_ignore_files(&self, event: &FsEvent) {
+        let is_ignore_file = |path: &Path| {
+            path.file_name()
+                .is_some_and(|name| name == IGNORE_FILE_NAME)
+        };
+        match event {
+            FsEvent::Create(path) | FsEvent::Modify(path) | FsEvent::Remove(path) => {
+                if is_ignore_file(path)
+                    && let Some(dir) = path.parent()
+                {
+                    self.ignore_files.invalidate(dir);
+                }
+            }
+            FsEvent::Rename { from, to } => {
+                for path in [from, to] {
+                    match path.parent() {
+                        Some(dir) if is_ignore_file(path) => self.ignore_files.invalidate(dir),
+                        _ => self.ignore_files.invalidate(path),
+                    }
+                }
+            }
+            FsEvent::Overflow => self.ignore_files.clear(),
+        }
+    }
+
     fn should_process_path(&self, path: &Path) -> bool {
         if self.watch_roots.is_empty() {
             return false;
@@ -537,14 +737,35 @@ impl ContinuousScanState {
         if !has_allowed_extension(path, &self.allowed_extensions) {
             return false;
         }
-        let is_included = self.watch_roots.iter().any(|root| path.starts_with(root));
-        if !is_included {
+        let Some(root) = self.watch_roots.iter().find(|root| path.starts_with(root)) else {
+            return false;
+        };
+        if self.ignored_dirs.contains_file(root, path) {
+            return false;
+        }
+        if self.ignore_files.contains_file(root, path) {
             return false;
         }
         if is_excluded(path, &self.excluded_roots) {
             return false;
         }
-        true
+        self.symlinks.admits(path)
+    }
+
+    /// Counts a file event dropped only because the file sits in an ignored
+    /// directory, for the scan's `ignored_dir_files`.
+    fn count_if_in_ignored_dir(&mut self, path: &Path) {
+        if is_hidden_or_temp(path) || !has_allowed_extension(path, &self.allowed_extensions) {
+            return;
+        }
+        let ignored = self
+            .watch_roots
+            .iter()
+            .find(|root| path.starts_with(root))
+            .is_some_and(|root| self.ignored_dirs.contains_file(root, path));
+        if ignored {
+            self.stats.ignored_dir_files += 1;
+        }
     }
 
     async fn handle_remove(&mut self, path: PathBuf) -> ApiResult<()> {
@@ -560,10 +781,11 @@ impl ContinuousScanState {
         if !self.should_process_path(&path) {
             return Ok(());
         }
+        let path_key = self.path_keys.key(&display_path(&path));
         let mut conn = open_index_db_read(&self.index_db, &self.user_data_db).await?;
         let Some(FileDeleteInfo {
             item_id, scan_id, ..
-        }) = get_file_delete_info(&mut conn, path.to_string_lossy().as_ref()).await?
+        }) = get_file_delete_info(&mut conn, &path_key).await?
         else {
             return Ok(());
         };
@@ -577,7 +799,7 @@ impl ContinuousScanState {
 
         let files_deleted = call_index_db_writer(&self.index_db, |reply| {
             IndexDbWriterMessage::DeleteFileByPath {
-                path: path.to_string_lossy().to_string(),
+                path_key: path_key.clone(),
                 reply,
             }
         })
@@ -599,6 +821,7 @@ impl ContinuousScanState {
             return Ok(());
         }
         if !self.should_process_path(&to) {
+            self.count_if_in_ignored_dir(&to);
             return Ok(());
         }
         if !to.exists() {
@@ -612,10 +835,13 @@ impl ContinuousScanState {
             Ok((time, _)) => time,
             Err(_) => return Ok(()),
         };
+        let new_path = display_path(&to);
         let renamed = call_index_db_writer(&self.index_db, |reply| {
             IndexDbWriterMessage::RenameFilePath {
-                old_path: from.to_string_lossy().to_string(),
-                new_path: to.to_string_lossy().to_string(),
+                old_key: self.path_keys.key(&display_path(&from)),
+                new_key: self.path_keys.key(&new_path),
+                new_path: new_path.clone(),
+                new_path_bytes: path_bytes(&to),
                 scan_id,
                 last_modified: last_modified.clone(),
                 reply,
@@ -633,6 +859,46 @@ impl ContinuousScanState {
     }
 
     fn dispatch_path(&self, path: PathBuf) {
+        self.dispatch_attempt(path, 0);
+    }
+
+    /// Queues a created or modified path for the next batched index lookup.
+    fn queue_lookup(&mut self, path: PathBuf) {
+        if !self.should_process_path(&path) {
+            self.count_if_in_ignored_dir(&path);
+            return;
+        }
+        if self.pending_lookups.is_empty() {
+            self.actor_ref
+                .send_after(EVENT_LOOKUP_WINDOW, || ContinuousScanMessage::FlushLookups);
+        }
+        self.pending_lookups.insert(path);
+    }
+
+    /// Re-queues a file a worker found still being written, with the settle
+    /// backoff, until `DEFERRED_MAX_RETRIES` is reached.
+    fn requeue_deferred(&self, path: PathBuf, attempts: u32) {
+        if attempts >= DEFERRED_MAX_RETRIES {
+            tracing::warn!(
+                path = %path.display(),
+                attempts,
+                "file is still being written; waiting for its next change"
+            );
+            return;
+        }
+        let epoch = self.epoch;
+        // Fire-and-forget: a stale epoch drops the retry on arrival.
+        self.actor_ref
+            .send_after(settle_backoff(attempts), move || {
+                ContinuousScanMessage::RetryDeferred {
+                    epoch,
+                    path,
+                    attempts: attempts + 1,
+                }
+            });
+    }
+
+    fn dispatch_attempt(&self, path: PathBuf, attempts: u32) {
         if self.paused {
             return;
         }
@@ -651,8 +917,11 @@ impl ContinuousScanState {
             None => current_iso_timestamp(),
         };
         let msg = FileWork {
+            path_key: self.path_keys.key(&display_path(&path)),
             path,
+            attempts,
             filescan_filter: self.filescan_filter.clone(),
+            settle: Duration::from_secs(self.config.scan_settle_secs),
             epoch: self.epoch,
             scan_time,
             index_db: self.index_db.clone(),
@@ -745,6 +1014,8 @@ impl ContinuousScanState {
             roots: self.watch_roots.clone(),
             excluded_roots: self.excluded_roots.clone(),
             allowed_extensions: self.allowed_extensions.clone(),
+            ignored_dirs: self.ignored_dirs.clone(),
+            symlinks: self.symlinks.clone(),
         });
         let mut conn = open_index_db_read(&self.index_db, &self.user_data_db).await?;
         let rows = get_all_file_paths_with_mtime(&mut conn).await?;
@@ -847,6 +1118,10 @@ impl Actor for ContinuousScanActor {
             invalid_includes: Vec::new(),
             roots_valid: true,
             allowed_extensions: HashSet::new(),
+            ignored_dirs: IgnoredDirs::default(),
+            symlinks: SymlinkPolicy::default(),
+            path_keys: PathKeys::default(),
+            ignore_files: IgnoreFiles::default(),
             filescan_filter: None,
             scan_id: None,
             scan_time: None,
@@ -859,11 +1134,13 @@ impl Actor for ContinuousScanActor {
             actor_ref: myself.clone(),
             factory,
             factory_handle: Some(handle),
+            worker_count: options.worker_count,
             watcher: None,
             poller: None,
             watcher_fallback: false,
             enable_watcher: args.enable_watcher,
             deletions_since_maintenance: 0,
+            pending_lookups: HashSet::new(),
         };
 
         let roots_ok = state.refresh_roots().await;
@@ -929,6 +1206,9 @@ impl Actor for ContinuousScanActor {
                 let prev_roots = state.watch_roots.clone();
                 let prev_excluded = state.excluded_roots.clone();
                 let prev_extensions = state.allowed_extensions.clone();
+                let prev_ignored = state.ignored_dirs.clone();
+                let prev_symlinks = state.symlinks.clone();
+                let prev_ignore_files = state.ignore_files.enabled();
                 let prev_interval = state.config.continuous_filescan.poll_interval_secs;
 
                 state.config = config;
@@ -965,6 +1245,9 @@ impl Actor for ContinuousScanActor {
                         || state.watch_roots != prev_roots
                         || state.excluded_roots != prev_excluded
                         || state.allowed_extensions != prev_extensions
+                        || state.ignored_dirs != prev_ignored
+                        || state.symlinks != prev_symlinks
+                        || state.ignore_files.enabled() != prev_ignore_files
                         || state.config.continuous_filescan.poll_interval_secs != prev_interval;
                     let needs_restart = scan_relevant_changed
                         || state.paused
@@ -983,9 +1266,10 @@ impl Actor for ContinuousScanActor {
                 if state.paused {
                     return Ok(());
                 }
+                state.invalidate_ignore_files(&event);
                 match event {
-                    FsEvent::Create(path) => state.dispatch_path(path),
-                    FsEvent::Modify(path) => state.dispatch_path(path),
+                    FsEvent::Create(path) => state.queue_lookup(path),
+                    FsEvent::Modify(path) => state.queue_lookup(path),
                     FsEvent::Remove(path) => {
                         let _ = state.handle_remove(path).await;
                     }
@@ -1052,6 +1336,9 @@ impl Actor for ContinuousScanActor {
                 };
                 poller.snapshot = Some(outcome.snapshot);
                 let interval = poller.interval;
+                // Poll passes skip hidden files, ignore files included, so
+                // edits to them are only noticed by re-reading after a pass.
+                state.ignore_files.clear();
                 if outcome.degraded {
                     tracing::warn!(
                         index_db = %state.index_db,
@@ -1115,9 +1402,7 @@ impl Actor for ContinuousScanActor {
                         return;
                     }
                     // Still being written: retry with backoff until it settles.
-                    let delay = POLL_SETTLE_DELAY
-                        .saturating_mul(2u32.saturating_pow(attempts.min(5)))
-                        .min(SETTLE_MAX_DELAY);
+                    let delay = settle_backoff(attempts);
                     let _ = reply.send_after(delay, move || ContinuousScanMessage::SettleCheck {
                         epoch,
                         path,
@@ -1135,9 +1420,54 @@ impl Actor for ContinuousScanActor {
                 }
                 state.dispatch_path(path);
             }
+            ContinuousScanMessage::FlushLookups => {
+                let paths: Vec<PathBuf> = state.pending_lookups.drain().collect();
+                if state.paused || paths.is_empty() {
+                    return Ok(());
+                }
+                let epoch = state.epoch;
+                let index_db = state.index_db.clone();
+                let user_data_db = state.user_data_db.clone();
+                let path_keys = state.path_keys.clone();
+                let reply = state.actor_ref.clone();
+                tokio::spawn(async move {
+                    let outcome =
+                        resolve_lookups(&index_db, &user_data_db, &path_keys, paths).await;
+                    let _ = reply.cast(ContinuousScanMessage::LookupsResolved { epoch, outcome });
+                });
+            }
+            ContinuousScanMessage::LookupsResolved { epoch, outcome } => {
+                if state.paused || epoch != state.epoch {
+                    return Ok(());
+                }
+                tracing::debug!(
+                    index_db = %state.index_db,
+                    changed = outcome.changed.len(),
+                    unchanged = outcome.unchanged,
+                    queries = outcome.queries,
+                    "resolved batched continuous scan events"
+                );
+                state.stats.false_changes += outcome.unchanged;
+                for path in outcome.changed {
+                    state.dispatch_path(path);
+                }
+                state.maybe_report_progress().await;
+            }
+            ContinuousScanMessage::RetryDeferred {
+                epoch,
+                path,
+                attempts,
+            } => {
+                if state.paused || epoch != state.epoch {
+                    return Ok(());
+                }
+                state.dispatch_attempt(path, attempts);
+            }
             ContinuousScanMessage::WorkerResult {
                 epoch,
                 scan_time,
+                path,
+                attempts,
                 result,
             } => {
                 if state.paused || epoch != state.epoch {
@@ -1150,12 +1480,24 @@ impl Actor for ContinuousScanActor {
                         state.maybe_report_progress().await;
                         return Ok(());
                     }
-                    Err(_) => {
+                    Err(FileProcessError::Busy) => {
+                        state.stats.deferred += 1;
+                        state.requeue_deferred(path, attempts);
+                        state.maybe_report_progress().await;
+                        return Ok(());
+                    }
+                    Err(err) => {
                         state.stats.errors += 1;
+                        if matches!(err, FileProcessError::TimedOut(_)) {
+                            state.stats.timeouts += 1;
+                        }
                         state.maybe_report_progress().await;
                         return Ok(());
                     }
                 };
+                if processed.undecodable {
+                    state.stats.undecodable += 1;
+                }
 
                 let mut conn = match open_index_db_read(&state.index_db, &state.user_data_db).await
                 {
@@ -1166,7 +1508,8 @@ impl Actor for ContinuousScanActor {
                         return Ok(());
                     }
                 };
-                let file_data = match build_file_scan_data(&mut conn, processed, &scan_time).await {
+                let file_data = match build_file_scan_data(&mut conn, &state.path_keys, processed, &scan_time).await
+                {
                     Ok(data) => data,
                     Err(err) => {
                         tracing::error!(error = ?err, "failed to build file scan data");
@@ -1307,6 +1650,10 @@ impl Actor for ContinuousScanActor {
                         .map(|interval| interval.as_secs()),
                 });
             }
+            #[cfg(test)]
+            ContinuousScanMessage::GetStats { reply } => {
+                let _ = reply.send(state.stats.clone());
+            }
         }
         Ok(())
     }
@@ -1325,6 +1672,71 @@ impl Actor for ContinuousScanActor {
         Ok(())
     }
 }
+/// Checks a batch of event paths against the index: one stat per path on the
+/// blocking pool, then one IN query per `EVENT_LOOKUP_CHUNK` paths. Paths
+/// that are gone or not regular files are dropped. If the index can't be
+/// read, every file counts as changed and the workers' own checks decide.
+async fn resolve_lookups(
+    index_db: &str,
+    user_data_db: &str,
+    path_keys: &PathKeys,
+    paths: Vec<PathBuf>,
+) -> LookupOutcome {
+    let on_disk = tokio::task::spawn_blocking(move || {
+        paths
+            .into_iter()
+            .filter_map(|path| {
+                let metadata = std::fs::metadata(&path)
+                    .ok()
+                    .filter(|meta| meta.is_file())?;
+                let modified = format_system_time(metadata.modified().ok()?)?;
+                Some((path, modified, metadata.len() as i64))
+            })
+            .collect::<Vec<_>>()
+    })
+    .await
+    .unwrap_or_default();
+
+    let mut outcome = LookupOutcome {
+        changed: Vec::new(),
+        unchanged: 0,
+        queries: 0,
+    };
+    let mut conn = open_index_db_read(index_db, user_data_db).await.ok();
+    for chunk in on_disk.chunks(EVENT_LOOKUP_CHUNK) {
+        let keys: Vec<String> = chunk
+            .iter()
+            .map(|(path, _, _)| path_keys.key(&display_path(path)))
+            .collect();
+        let stored = match conn.as_mut() {
+            Some(conn) => {
+                outcome.queries += 1;
+                get_files_by_paths(conn, &keys).await.unwrap_or_default()
+            }
+            None => Default::default(),
+        };
+        for ((path, modified, size), key) in chunk.iter().zip(&keys) {
+            let unchanged = stored.get(key).is_some_and(|record| {
+                &record.last_modified == modified && record.size.is_none_or(|s| s == *size)
+            });
+            if unchanged {
+                outcome.unchanged += 1;
+            } else {
+                outcome.changed.push(path.clone());
+            }
+        }
+    }
+    outcome
+}
+
+/// Delay before re-checking a file that is still changing: doubles per
+/// attempt from `POLL_SETTLE_DELAY`, capped at `SETTLE_MAX_DELAY`.
+fn settle_backoff(attempts: u32) -> Duration {
+    POLL_SETTLE_DELAY
+        .saturating_mul(2u32.saturating_pow(attempts.min(5)))
+        .min(SETTLE_MAX_DELAY)
+}
+
 fn start_watcher(
     actor: ActorRef<ContinuousScanMessage>,
     roots: &[PathBuf],
@@ -1892,6 +2304,8 @@ mod tests {
         let prepared = process_file(
             file_path.clone(),
             parse_filescan_filter(&config).map(Arc::new),
+            None,
+            Duration::ZERO,
             &ScanTimers::default(),
         )
         .unwrap();
@@ -1899,6 +2313,8 @@ mod tests {
             .cast(ContinuousScanMessage::WorkerResult {
                 epoch: 0,
                 scan_time: current_iso_timestamp(),
+                path: file_path.clone(),
+                attempts: 0,
                 result: Ok(prepared),
             })
             .unwrap();
@@ -1980,6 +2396,8 @@ mod tests {
         let prepared = process_file(
             file_path.clone(),
             parse_filescan_filter(&config).map(Arc::new),
+            None,
+            Duration::ZERO,
             &ScanTimers::default(),
         )
         .unwrap();
@@ -1987,6 +2405,8 @@ mod tests {
             .cast(ContinuousScanMessage::WorkerResult {
                 epoch: 0,
                 scan_time: current_iso_timestamp(),
+                path: file_path.clone(),
+                attempts: 0,
                 result: Ok(prepared),
             })
             .unwrap();
@@ -2126,6 +2546,184 @@ mod tests {
         assert!(found, "poll mode did not index the new file in time");
     }
 
+    // A storm of modify events on files whose mtime and size match the index
+    // is resolved by a handful of IN queries: nothing reaches a worker and
+    // every event counts as a false change.
+    #[tokio::test]
+    async fn modify_storm_on_unchanged_files_dispatches_nothing() {
+        const FILES: usize = 5000;
+        let test_env = test_data_dir();
+        let root = test_env.path().to_path_buf();
+        let index_db = unique_db_name("storm");
+        let _ = migrate_databases_on_disk(Some(&index_db), Some(&index_db))
+            .await
+            .unwrap();
+
+        let watch_dir = root.join("stormwatch");
+        std::fs::create_dir_all(&watch_dir).unwrap();
+        let paths: Vec<PathBuf> = (0..FILES)
+            .map(|i| watch_dir.join(format!("file{i}.png")))
+            .collect();
+        let mut conn = crate::db::open_index_db_write_no_user_data(&index_db)
+            .await
+            .unwrap();
+        sqlx::query("BEGIN").execute(&mut conn).await.unwrap();
+        sqlx::query(
+            "INSERT INTO file_scans (id, start_time, path) VALUES (1, '2024-01-01T00:00:00', '/')",
+        )
+        .execute(&mut conn)
+        .await
+        .unwrap();
+        for (i, path) in paths.iter().enumerate() {
+            fs::write(path, i.to_string()).unwrap();
+            let (last_modified, size) = get_last_modified_time_and_size(path).unwrap();
+            let sha256 = format!("sha{i}");
+            sqlx::query(
+                "INSERT INTO items (id, sha256, md5, type, size, time_added)
+                 VALUES (?1, ?2, 'md5', 'image/png', ?3, '2024-01-01T00:00:00')",
+            )
+            .bind(i as i64 + 1)
+            .bind(&sha256)
+            .bind(size)
+            .execute(&mut conn)
+            .await
+            .unwrap();
+            sqlx::query(
+                "INSERT INTO files (sha256, item_id, path, filename, last_modified, scan_id, available)
+                 VALUES (?1, ?2, ?3, 'file.png', ?4, 1, 1)",
+            )
+            .bind(&sha256)
+            .bind(i as i64 + 1)
+            .bind(path.to_string_lossy().as_ref())
+            .bind(&last_modified)
+            .execute(&mut conn)
+            .await
+            .unwrap();
+        }
+        sqlx::query("COMMIT").execute(&mut conn).await.unwrap();
+        drop(conn);
+
+        let outcome = resolve_lookups(&index_db, &index_db, &PathKeys::default(), paths.clone()).await;
+        assert!(outcome.changed.is_empty());
+        assert_eq!(outcome.unchanged, FILES as i64);
+        assert_eq!(outcome.queries, FILES.div_ceil(EVENT_LOOKUP_CHUNK));
+
+        let new_file = watch_dir.join("new.png");
+        fs::write(&new_file, "new").unwrap();
+        let outcome = resolve_lookups(
+            &index_db,
+            &index_db,
+            &PathKeys::default(),
+            vec![new_file.clone()],
+        ).await;
+        assert_eq!(outcome.changed, vec![new_file.clone()]);
+        fs::remove_file(&new_file).unwrap();
+
+        let store = SystemConfigStore::new(root.clone());
+        let mut config = store.load(&index_db).unwrap();
+        config.continuous_filescan.enabled = true;
+        config.included_folders = vec![watch_dir.to_string_lossy().to_string()];
+        store.save(&index_db, &config).unwrap();
+
+        let (actor, _handle) = Actor::spawn(
+            None,
+            ContinuousScanActor,
+            ContinuousScanActorArgs {
+                index_db: index_db.clone(),
+                user_data_db: index_db.clone(),
+                data_dir: root.clone(),
+                enable_watcher: false,
+            },
+        )
+        .await
+        .unwrap();
+        for path in &paths {
+            actor
+                .cast(ContinuousScanMessage::FsEvent(FsEvent::Modify(
+                    path.clone(),
+                )))
+                .unwrap();
+        }
+
+        let mut stats = None;
+        for _ in 0..100 {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            let (tx, rx) = oneshot::channel();
+            actor
+                .cast(ContinuousScanMessage::GetStats { reply: tx })
+                .unwrap();
+            let current = rx.await.unwrap();
+            if current.false_changes >= FILES as i64 {
+                stats = Some(current);
+                break;
+            }
+        }
+        // Give any stray dispatch time to come back from a worker.
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let (tx, rx) = oneshot::channel();
+        actor
+            .cast(ContinuousScanMessage::GetStats { reply: tx })
+            .unwrap();
+        let after = rx.await.unwrap();
+        actor.stop(None);
+
+        let stats = stats.expect("batched lookups did not resolve in time");
+        assert_eq!(stats.false_changes, FILES as i64);
+        assert_eq!(after.false_changes, FILES as i64);
+        assert_eq!(after.unchanged_files, 0);
+        assert_eq!(after.new_files, 0);
+        assert_eq!(after.modified_files, 0);
+        assert_eq!(after.errors, 0);
+    }
+
+    // Change events for media files in ignored directories are dropped and
+    // counted; other dropped files are not.
+    #[tokio::test]
+    async fn events_in_ignored_dirs_are_counted() {
+        let test_env = test_data_dir();
+        let root = test_env.path().to_path_buf();
+        let index_db = unique_db_name("ignored");
+        let _ = migrate_databases_on_disk(Some(&index_db), Some(&index_db))
+            .await
+            .unwrap();
+
+        let watch_dir = root.join("ignoredwatch");
+        std::fs::create_dir_all(&watch_dir).unwrap();
+        let store = SystemConfigStore::new(root.clone());
+        let mut config = store.load(&index_db).unwrap();
+        config.continuous_filescan.enabled = true;
+        config.included_folders = vec![watch_dir.to_string_lossy().to_string()];
+        store.save(&index_db, &config).unwrap();
+
+        let (actor, _handle) = Actor::spawn(
+            None,
+            ContinuousScanActor,
+            ContinuousScanActorArgs {
+                index_db: index_db.clone(),
+                user_data_db: index_db.clone(),
+                data_dir: root.clone(),
+                enable_watcher: false,
+            },
+        )
+        .await
+        .unwrap();
+        for event in [
+            FsEvent::Create(watch_dir.join(".git/objects/a.png")),
+            FsEvent::Modify(watch_dir.join("node_modules/pkg/b.jpg")),
+            FsEvent::Create(watch_dir.join("node_modules/notes.txt")),
+        ] {
+            actor.cast(ContinuousScanMessage::FsEvent(event)).unwrap();
+        }
+
+        let (tx, rx) = oneshot::channel();
+        actor
+            .cast(ContinuousScanMessage::GetStats { reply: tx })
+            .unwrap();
+        let stats = rx.await.unwrap();
+        actor.stop(None);
+        assert_eq!(stats.ignored_dir_files, 2);
+    }
+
     #[test]
     fn continuous_includes_subset_of_global() {
         let tmp = TempDir::new().unwrap();
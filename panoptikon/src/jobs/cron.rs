@@ -12,6 +12,11 @@
 //! endpoint's implementation: the cron_jobs list is the user's standing set of
 //! models to run on new data, so the endpoint runs it even when automatic
 //! scheduling is disabled.
+//!
+//! The same tick also fires the `db_maintenance` schedule, which enqueues a
+//! database optimization job (`jobs::db_maintenance`), and each enabled
+//! `pql_reports` schedule, which enqueues a `pql_report` job
+//! (`jobs::pql_report`).
 
 use std::collections::{HashMap, HashSet};
 use std::str::FromStr;
@@ -26,14 +31,24 @@ use crate::api_error::ApiError;
 use crate::db::extraction_log::get_search_embedding_setters;
 use crate::db::info::{db_defaults, db_lists};
 use crate::db::open_index_db_read;
-use crate::db::system_config::{CronJob, SystemConfig, SystemConfigStore};
-use crate::jobs::extraction::resolve_model_metadata;
+use crate::db::system_config::{
+    CronJob, PqlReportSchedule, SystemConfig, SystemConfigStore, VacuumMode,
+};
+use crate::jobs::db_maintenance::{DB_OPTIMIZE_JOB_TAG, OptimizeOptions};
+use crate::jobs::extraction::{resolve_follow_up, resolve_model_metadata};
 use crate::jobs::inference_pool::job_inference_context;
-use crate::jobs::queue::{BatchDedup, JobModel, JobRequest, JobType, enqueue_jobs_unless_tagged};
+use crate::jobs::pql_report::PqlReportOptions;
+use crate::jobs::queue::{
+    BatchDedup, JobChain, JobModel, JobRequest, JobType, enqueue_jobs_unless_tagged,
+    extraction_dedup_key, folder_rescan_dedup_key,
+};
 
 type ApiResult<T> = std::result::Result<T, ApiError>;
 
 pub(crate) const CRON_TAG: &str = "cronjob";
+/// Scheduled reports are tagged with this prefix and their name, so a slow
+/// report is skipped rather than stacked up.
+const PQL_REPORT_TAG_PREFIX: &str = "pql_report:";
 
 const TICK_INTERVAL_SECS: u64 = 60;
 const PRELOAD_TTL_SECS: i64 = 3600;
@@ -134,9 +149,14 @@ fn order_cron_jobs(jobs: Vec<(CronJob, Option<Vec<String>>)>) -> Vec<CronJob> {
 /// data-extraction job per configured model. The whole batch is enqueued
 /// atomically and skipped when a previous cronjob for this DB is still queued
 /// or running. Runs regardless of `enable_cron_job` — the manual trigger uses
-/// the cron set as "the jobs to run now".
-pub(crate) async fn run_cronjob(index_db: &str, user_data_db: &str) -> ApiResult<CronRunOutcome> {
-    run_cronjob_with_scan(index_db, user_data_db, JobType::FolderRescan).await
+/// the cron set as "the jobs to run now"; `run_now` lets those jobs bypass
+/// the `job_window`.
+pub(crate) async fn run_cronjob(
+    index_db: &str,
+    user_data_db: &str,
+    run_now: bool,
+) -> ApiResult<CronRunOutcome> {
+    run_cronjob_with_scan(index_db, user_data_db, JobType::FolderRescan, run_now).await
 }
 
 /// Enqueues the wizard's first processing run. FolderUpdate both registers and
@@ -146,19 +166,26 @@ pub(crate) async fn run_initial_cronjob(
     index_db: &str,
     user_data_db: &str,
 ) -> ApiResult<CronRunOutcome> {
-    run_cronjob_with_scan(index_db, user_data_db, JobType::FolderUpdate).await
+    run_cronjob_with_scan(index_db, user_data_db, JobType::FolderUpdate, false).await
 }
 
 async fn run_cronjob_with_scan(
     index_db: &str,
     user_data_db: &str,
     scan_job_type: JobType,
+    run_now: bool,
 ) -> ApiResult<CronRunOutcome> {
     tracing::info!(index_db, "running cronjob");
     let store = SystemConfigStore::from_env();
     let config = store.load(index_db)?;
 
-    let mut requests = vec![cron_request(scan_job_type, index_db, user_data_db, None)];
+    // Dedup keys let a manual enqueue of the same work while the cron batch
+    // is queued return the cron job instead of adding a duplicate.
+    let mut scan_request = cron_request(scan_job_type.clone(), index_db, user_data_db, None);
+    if scan_job_type == JobType::FolderRescan {
+        scan_request.dedup_key = Some(folder_rescan_dedup_key(index_db, &config));
+    }
+    let mut requests = vec![scan_request];
 
     let ordered = match job_inference_context().primary.get_metadata().await {
         Ok(metadata) => {
@@ -196,7 +223,16 @@ async fn run_cronjob_with_scan(
         }
     };
 
+    // Follow-ups chain like API-enqueued jobs, except those the schedule
+    // already runs directly.
+    let scheduled = ordered
+        .iter()
+        .map(|job| job.inference_id.clone())
+        .collect::<HashSet<_>>();
     for job in ordered {
+        let dedup_key = extraction_dedup_key(index_db, &job.inference_id, &config);
+        let mut follow_up = resolve_follow_up(&config, &job.inference_id);
+        follow_up.retain(|inference_id| !scheduled.contains(inference_id));
         let mut request = cron_request(
             JobType::DataExtraction,
             index_db,
@@ -205,8 +241,13 @@ async fn run_cronjob_with_scan(
         );
         request.batch_size = job.batch_size;
         request.threshold = job.threshold;
+        request.dedup_key = Some(dedup_key);
+        request.chain.follow_up = follow_up;
         requests.push(request);
     }
+    for request in &mut requests {
+        request.run_now = run_now;
+    }
 
     let dedup = BatchDedup {
         tag: CRON_TAG.to_string(),
@@ -239,6 +280,10 @@ fn cron_request(
         threshold: None,
         log_id: None,
         tag: Some(CRON_TAG.to_string()),
+        dedup_key: None,
+        run_now: false,
+        reprocess: false,
+        chain: JobChain::default(),
     }
 }
 
@@ -273,6 +318,10 @@ pub(crate) struct CronSchedulerState {
     /// minute.
     invalid_logged: HashMap<String, String>,
     last_run: HashMap<String, DateTime<Local>>,
+    /// Per DB, the `db_maintenance` schedule; same rules as `schedules`.
+    maintenance_schedules: HashMap<String, DbCronState>,
+    /// Per DB and report name, the `pql_reports` schedules; same rules.
+    report_schedules: HashMap<String, HashMap<String, DbCronState>>,
     preload: PreloadState,
 }
 
@@ -297,6 +346,8 @@ impl Actor for CronSchedulerActor {
             schedules: HashMap::new(),
             invalid_logged: HashMap::new(),
             last_run: HashMap::new(),
+            maintenance_schedules: HashMap::new(),
+            report_schedules: HashMap::new(),
             preload: PreloadState::default(),
         })
     }
@@ -338,6 +389,10 @@ async fn tick_all(state: &mut CronSchedulerState) {
     state.schedules.retain(|db, _| known.contains(db));
     state.invalid_logged.retain(|db, _| known.contains(db));
     state.last_run.retain(|db, _| known.contains(db));
+    state
+        .maintenance_schedules
+        .retain(|db, _| known.contains(db));
+    state.report_schedules.retain(|db, _| known.contains(db));
     state.preload.retain(&known);
 
     for index_db in &index_dbs {
@@ -394,7 +449,7 @@ async fn tick_db(state: &mut CronSchedulerState, index_db: &str) {
     if fire {
         // The schedule slot is consumed regardless of the run's outcome,
         // matching Python (run_cronjob swallows its own errors there).
-        match run_cronjob(index_db, &db_defaults().1).await {
+        match run_cronjob(index_db, &db_defaults().1, false).await {
             Ok(CronRunOutcome::Enqueued(jobs)) => {
                 tracing::info!(index_db, jobs = jobs.len(), "cronjob enqueued");
             }
@@ -409,9 +464,126 @@ async fn tick_db(state: &mut CronSchedulerState, index_db: &str) {
         }
     }
 
+    maintenance_tick(state, index_db, &config).await;
+    report_tick(state, index_db, &config).await;
     preload_tick(state, index_db, &config).await;
 }
 
+/// Fires the `db_maintenance` schedule. An invalid schedule is rejected at
+/// save time and otherwise behaves as disabled, like `cron_schedule`.
+async fn maintenance_tick(state: &mut CronSchedulerState, index_db: &str, config: &SystemConfig) {
+    let maintenance = &config.db_maintenance;
+    let schedule = maintenance.enabled.then_some(maintenance.schedule.as_str());
+    let prev = state.maintenance_schedules.remove(index_db);
+    let (next, fire) = plan_tick(prev, schedule, Local::now());
+    if let Some(next) = next {
+        state
+            .maintenance_schedules
+            .insert(index_db.to_string(), next);
+    }
+    if !fire {
+        return;
+    }
+    if let Err(err) = enqueue_scheduled_optimize(index_db, maintenance.vacuum).await {
+        tracing::error!(error = ?err, index_db, "failed to enqueue database optimization");
+    }
+}
+
+async fn enqueue_scheduled_optimize(index_db: &str, vacuum: VacuumMode) -> ApiResult<()> {
+    let options = OptimizeOptions {
+        vacuum,
+        scheduled: true,
+    };
+    let metadata = serde_json::to_string(&options)
+        .map_err(|err| ApiError::internal(format!("Failed to encode options: {err}")))?;
+    let request = JobRequest {
+        job_type: JobType::DbOptimize,
+        index_db: index_db.to_string(),
+        user_data_db: db_defaults().1,
+        metadata: Some(metadata),
+        batch_size: None,
+        threshold: None,
+        log_id: None,
+        tag: Some(DB_OPTIMIZE_JOB_TAG.to_string()),
+        dedup_key: None,
+        run_now: false,
+        reprocess: false,
+        chain: JobChain::default(),
+    };
+    let dedup = BatchDedup {
+        tag: DB_OPTIMIZE_JOB_TAG.to_string(),
+        index_db: index_db.to_string(),
+    };
+    match enqueue_jobs_unless_tagged(vec![request], Some(dedup)).await? {
+        Some(_) => tracing::info!(index_db, "scheduled database optimization enqueued"),
+        None => tracing::info!(
+            index_db,
+            "a database optimization for this index DB is still queued or running, skipping"
+        ),
+    }
+    Ok(())
+}
+
+/// Fires the `pql_reports` schedules. Disabled or removed reports drop
+/// their state; invalid schedules are rejected at save time.
+async fn report_tick(state: &mut CronSchedulerState, index_db: &str, config: &SystemConfig) {
+    let previous = state.report_schedules.remove(index_db).unwrap_or_default();
+    let mut schedules = HashMap::new();
+    let mut due = Vec::new();
+    let now = Local::now();
+    for report in config.pql_reports.iter().filter(|report| report.enabled) {
+        let prev = previous.get(&report.name).cloned();
+        let (next, fire) = plan_tick(prev, Some(report.schedule.as_str()), now);
+        if let Some(next) = next {
+            schedules.insert(report.name.clone(), next);
+        }
+        if fire {
+            due.push(report);
+        }
+    }
+    state
+        .report_schedules
+        .insert(index_db.to_string(), schedules);
+    for report in due {
+        if let Err(err) = enqueue_scheduled_report(index_db, report).await {
+            tracing::error!(error = ?err, index_db, report = %report.name, "failed to enqueue PQL report");
+        }
+    }
+}
+
+async fn enqueue_scheduled_report(index_db: &str, report: &PqlReportSchedule) -> ApiResult<()> {
+    let metadata = serde_json::to_string(&PqlReportOptions::from(report))
+        .map_err(|err| ApiError::internal(format!("Failed to encode options: {err}")))?;
+    let tag = format!("{PQL_REPORT_TAG_PREFIX}{}", report.name);
+    let request = JobRequest {
+        job_type: JobType::PqlReport,
+        index_db: index_db.to_string(),
+        user_data_db: db_defaults().1,
+        metadata: Some(metadata),
+        batch_size: None,
+        threshold: None,
+        log_id: None,
+        tag: Some(tag.clone()),
+        dedup_key: None,
+        run_now: false,
+        reprocess: false,
+        chain: JobChain::default(),
+    };
+    let dedup = BatchDedup {
+        tag,
+        index_db: index_db.to_string(),
+    };
+    match enqueue_jobs_unless_tagged(vec![request], Some(dedup)).await? {
+        Some(_) => tracing::info!(index_db, report = %report.name, "scheduled PQL report enqueued"),
+        None => tracing::info!(
+            index_db,
+            report = %report.name,
+            "this PQL report is still queued or running, skipping"
+        ),
+    }
+    Ok(())
+}
+
 // ---------------------------------------------------------------------------
 // Embedding model preload (port of preload.py)
 // ---------------------------------------------------------------------------
@@ -0,0 +1,385 @@
+//! Library coverage per extraction model: how many of the items (or text
+//! rows) a model accepts already have its data.
+//!
+//! Eligibility uses the same MIME prefix filter extraction jobs apply
+//! (`model_mime_filter`); `job_filters` and `skip_processed_items` are
+//! ignored, so coverage describes the library rather than the next job.
+//! The counts come from one grouped query per target entity, bucketed by
+//! MIME type, and each model sums the buckets its filter accepts.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::ToSchema;
+
+use crate::api_error::ApiError;
+use crate::db::data_coverage::{
+    ProcessedCount, get_item_processed_counts, get_item_type_counts, get_text_processed_counts,
+    get_text_type_counts,
+};
+use crate::db::extraction_write::current_iso_timestamp;
+use crate::db::index_writer::{IndexDbWriterMessage, call_index_db_writer};
+use crate::db::open_index_db_read;
+use crate::db::system_config::{SystemConfig, SystemConfigStore};
+use crate::jobs::extraction::{
+    ModelMetadata, fetch_inference_metadata_with_age, model_mime_filter, resolve_model_metadata,
+};
+use crate::pql::builder::filters::{MatchValue, evaluate_match};
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub(crate) struct ModelCoverage {
+    /// Setter the model's data is stored under (its inference ID).
+    pub setter_name: String,
+    /// The `job_settings` group naming this model, else its inference group.
+    pub group: String,
+    /// Whether the inference server currently exposes this model. Without
+    /// it the accepted MIME types are unknown, so `eligible` is null.
+    pub model_available: bool,
+    /// What coverage is counted in: `items` (also for models targeting
+    /// files, whose data is stored per item) or `text` (extracted text rows).
+    pub target_entity: String,
+    /// MIME type prefixes the model accepts; empty means all.
+    pub input_mime_types: Vec<String>,
+    #[schema(nullable)]
+    pub eligible: Option<i64>,
+    /// Eligible units with data from this setter, placeholders included.
+    pub processed: i64,
+    /// Processed units for which the model extracted nothing.
+    pub placeholders: i64,
+    /// `processed / eligible` in percent; null when nothing is eligible.
+    #[schema(nullable)]
+    pub coverage_percent: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub(crate) struct CoverageReport {
+    pub computed_at: String,
+    pub models: Vec<ModelCoverage>,
+    /// Age in seconds, at `computed_at`, of the cached inference metadata
+    /// the report used; null when it was fetched without the cache.
+    #[serde(default)]
+    #[schema(nullable)]
+    pub inference_metadata_age_secs: Option<u64>,
+}
+
+/// Counts for one target entity: units per MIME type, and processed units
+/// per setter and MIME type.
+#[derive(Default)]
+struct EntityCounts {
+    eligible: Vec<(String, i64)>,
+    processed: HashMap<String, Vec<ProcessedCount>>,
+}
+
+impl EntityCounts {
+    fn new(eligible: Vec<(String, i64)>, processed: Vec<ProcessedCount>) -> Self {
+        let mut by_setter: HashMap<String, Vec<ProcessedCount>> = HashMap::new();
+        for count in processed {
+            by_setter
+                .entry(count.setter_name.clone())
+                .or_default()
+                .push(count);
+        }
+        Self {
+            eligible,
+            processed: by_setter,
+        }
+    }
+}
+
+fn targets_text(model: &ModelMetadata) -> bool {
+    model.target_entities.first().map(String::as_str) == Some("text")
+}
+
+/// Computes the report from the index DB and the inference `/metadata`
+/// payload. Models are every `job_settings` inference ID plus every setter
+/// with data.
+pub(crate) async fn compute_coverage(
+    conn: &mut sqlx::SqliteConnection,
+    config: &SystemConfig,
+    metadata: &Value,
+) -> ApiResult<CoverageReport> {
+    let items = EntityCounts::new(
+        get_item_type_counts(conn).await?,
+        get_item_processed_counts(conn).await?,
+    );
+
+    let mut groups = BTreeMap::new();
+    for setting in &config.job_settings {
+        if let Some(inference_id) = &setting.inference_id {
+            groups.insert(inference_id.clone(), setting.group_name.clone());
+        }
+    }
+    let names = groups
+        .keys()
+        .cloned()
+        .chain(items.processed.keys().cloned())
+        .collect::<BTreeSet<_>>();
+    let models = names
+        .into_iter()
+        .map(|name| (resolve_model_metadata(metadata, &name).ok(), name))
+        .collect::<Vec<_>>();
+
+    let text = if models
+        .iter()
+        .any(|(model, _)| model.as_ref().is_some_and(targets_text))
+    {
+        EntityCounts::new(
+            get_text_type_counts(conn).await?,
+            get_text_processed_counts(conn).await?,
+        )
+    } else {
+        EntityCounts::default()
+    };
+
+    let models = models
+        .into_iter()
+        .map(|(model, setter_name)| {
+            let counts = match &model {
+                Some(model) if targets_text(model) => &text,
+                _ => &items,
+            };
+            let group = groups
+                .get(&setter_name)
+                .cloned()
+                .unwrap_or_else(|| match &model {
+                    Some(model) => model.group.clone(),
+                    None => setter_name
+                        .split_once('/')
+                        .map_or(setter_name.as_str(), |(group, _)| group)
+                        .to_string(),
+                });
+            summarize_model(setter_name, group, model.as_ref(), counts)
+        })
+        .collect();
+
+    Ok(CoverageReport {
+        computed_at: current_iso_timestamp(),
+        models,
+        inference_metadata_age_secs: None,
+    })
+}
+
+fn summarize_model(
+    setter_name: String,
+    group: String,
+    model: Option<&ModelMetadata>,
+    counts: &EntityCounts,
+) -> ModelCoverage {
+    let filter = model.and_then(model_mime_filter);
+    // Unknown models accept everything: their processed counts are still
+    // worth reporting, but there is no eligible total to compare against.
+    let accepts = |mime_type: &str| {
+        filter.as_ref().is_none_or(|filter| {
+            evaluate_match(
+                filter,
+                &MatchValue {
+                    r#type: Some(mime_type.to_string()),
+                    ..Default::default()
+                },
+            )
+        })
+    };
+
+    let eligible = model.map(|_| {
+        counts
+            .eligible
+            .iter()
+            .filter(|(mime_type, _)| accepts(mime_type))
+            .map(|(_, total)| total)
+            .sum::<i64>()
+    });
+    let (processed, placeholders) = counts
+        .processed
+        .get(&setter_name)
+        .into_iter()
+        .flatten()
+        .filter(|count| accepts(&count.mime_type))
+        .fold((0, 0), |(processed, placeholders), count| {
+            (
+                processed + count.processed,
+                placeholders + count.placeholders,
+            )
+        });
+    let coverage_percent = eligible
+        .filter(|&eligible| eligible > 0)
+        .map(|eligible| processed as f64 * 100.0 / eligible as f64);
+
+    ModelCoverage {
+        setter_name,
+        group,
+        model_available: model.is_some(),
+        target_entity: if model.is_some_and(targets_text) {
+            "text"
+        } else {
+            "items"
+        }
+        .to_string(),
+        input_mime_types: model
+            .map(|model| model.input_mime_types.clone())
+            .unwrap_or_default(),
+        eligible,
+        processed,
+        placeholders,
+        coverage_percent,
+    }
+}
+
+/// Computes the report live and stores it as the DB's cached snapshot.
+pub(crate) async fn compute_and_store_coverage(
+    conn: &mut sqlx::SqliteConnection,
+    index_db: &str,
+) -> ApiResult<CoverageReport> {
+    let config = SystemConfigStore::from_env().load(index_db)?;
+    let (metadata, age) = fetch_inference_metadata_with_age().await?;
+    let mut report = compute_coverage(conn, &config, &metadata).await?;
+    report.inference_metadata_age_secs = age.map(|age| age.as_secs());
+    let json = serde_json::to_string(&report).map_err(|err| {
+        tracing::error!(error = %err, "failed to serialize coverage report");
+        ApiError::internal("Failed to store coverage snapshot")
+    })?;
+    call_index_db_writer(index_db, |reply| {
+        IndexDbWriterMessage::StoreCoverageSnapshot {
+            computed_at: report.computed_at.clone(),
+            report: json.clone(),
+            reply,
+        }
+    })
+    .await?;
+    Ok(report)
+}
+
+/// Refreshes the cached snapshot after an extraction job. Best-effort: a
+/// failure is logged and leaves the previous snapshot in place.
+pub(crate) async fn refresh_coverage_snapshot(index_db: &str, user_data_db: &str) {
+    let result = async {
+        let mut conn = open_index_db_read(index_db, user_data_db).await?;
+        compute_and_store_coverage(&mut conn, index_db).await
+    }
+    .await;
+    if let Err(err) = result {
+        tracing::warn!(error = ?err, index_db, "failed to refresh data coverage snapshot");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::migrations::setup_test_databases;
+    use crate::db::system_config::JobSettings;
+    use serde_json::json;
+
+    async fn seed(conn: &mut sqlx::SqliteConnection) {
+        for statement in [
+            r#"INSERT INTO items (id, sha256, md5, type, time_added) VALUES
+                (1, 'sha_1', 'md5_1', 'image/png', '2024-01-01T00:00:00'),
+                (2, 'sha_2', 'md5_2', 'image/jpeg', '2024-01-01T00:00:00'),
+                (3, 'sha_3', 'md5_3', 'video/mp4', '2024-01-01T00:00:00')"#,
+            r#"INSERT INTO setters (id, name) VALUES
+                (1, 'wd/tagger'), (2, 'ocr/doctr'), (3, 'te/minilm'), (4, 'old/gone')"#,
+            r#"INSERT INTO item_data
+                (id, item_id, setter_id, data_type, idx, is_origin, source_id, is_placeholder)
+            VALUES
+                (10, 1, 1, 'tags', 0, 1, NULL, 0),
+                (11, 2, 1, 'tags', 0, 1, NULL, 1),
+                (30, 1, 2, 'text', 0, 1, NULL, 0),
+                (32, 3, 2, 'text', 0, 1, NULL, 0),
+                (40, 1, 3, 'text-embedding', 0, NULL, 30, 0),
+                (42, 3, 3, 'text-embedding', 0, NULL, 32, 1),
+                (50, 3, 4, 'tags', 0, 1, NULL, 0)"#,
+        ] {
+            sqlx::query(statement).execute(&mut *conn).await.unwrap();
+        }
+    }
+
+    // Eligibility follows each model's MIME filter and target entity;
+    // unknown models keep their processed counts but have no eligible total.
+    #[tokio::test]
+    async fn coverage_counts_eligible_processed_and_placeholders() {
+        let mut dbs = setup_test_databases().await;
+        seed(&mut dbs.index_conn).await;
+        let metadata = json!({
+            "wd": {
+                "group_metadata": {
+                    "input_spec": { "handler": "image_frames" },
+                    "input_mime_types": ["image/", "video/"]
+                },
+                "inference_ids": { "tagger": {} }
+            },
+            "ocr": {
+                "group_metadata": {
+                    "input_spec": { "handler": "image_frames" },
+                    "input_mime_types": ["image/"]
+                },
+                "inference_ids": { "doctr": {} }
+            },
+            "te": {
+                "group_metadata": {
+                    "input_spec": { "handler": "extracted_text" },
+                    "target_entities": ["text"]
+                },
+                "inference_ids": { "minilm": {} }
+            }
+        });
+        let config = SystemConfig {
+            job_settings: vec![
+                JobSettings {
+                    group_name: "tags".to_string(),
+                    inference_id: Some("wd/tagger".to_string()),
+                    default_batch_size: None,
+                    default_threshold: None,
+                    follow_up: Vec::new(),
+                    per_frame_tags: None,
+                },
+                JobSettings {
+                    group_name: "future".to_string(),
+                    inference_id: Some("new/model".to_string()),
+                    default_batch_size: None,
+                    default_threshold: None,
+                    follow_up: Vec::new(),
+                    per_frame_tags: None,
+                },
+            ],
+            ..Default::default()
+        };
+
+        let report = compute_coverage(&mut dbs.index_conn, &config, &metadata)
+            .await
+            .unwrap();
+        let by_name = report
+            .models
+            .iter()
+            .map(|model| (model.setter_name.as_str(), model))
+            .collect::<HashMap<_, _>>();
+        assert_eq!(by_name.len(), 5);
+
+        let tagger = by_name["wd/tagger"];
+        assert_eq!(tagger.group, "tags");
+        assert_eq!(
+            (tagger.eligible, tagger.processed, tagger.placeholders),
+            (Some(3), 2, 1)
+        );
+
+        let ocr = by_name["ocr/doctr"];
+        assert_eq!((ocr.eligible, ocr.processed), (Some(2), 1));
+        assert_eq!(ocr.coverage_percent, Some(50.0));
+
+        let minilm = by_name["te/minilm"];
+        assert_eq!(minilm.target_entity, "text");
+        assert_eq!(
+            (minilm.eligible, minilm.processed, minilm.placeholders),
+            (Some(2), 2, 1)
+        );
+
+        let gone = by_name["old/gone"];
+        assert!(!gone.model_available);
+        assert_eq!((gone.eligible, gone.processed), (None, 1));
+        assert_eq!(gone.group, "old");
+
+        let future = by_name["new/model"];
+        assert_eq!((future.eligible, future.processed), (None, 0));
+        assert_eq!(future.group, "future");
+    }
+}
@@ -0,0 +1,162 @@
+//! Database optimization: WAL checkpoint, optional vacuum, `ANALYZE` and
+//! `PRAGMA optimize`, all through the index writer so they serialize with
+//! every other write. Runs as a queued job, so it never overlaps an
+//! extraction job; triggered by `POST /api/jobs/maintenance/optimize` or the
+//! `db_maintenance` schedule.
+//!
+//! A full VACUUM rewrites both database files, so it only runs when the
+//! free space on their volume exceeds their combined size; otherwise it is
+//! skipped and the reason recorded. Every run is recorded in
+//! `db_maintenance_runs` with its duration and size before and after, and
+//! refreshes the storage usage snapshot (see `db::storage_stats`).
+
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+use crate::api_error::ApiError;
+use crate::db::db_maintenance::{NewDbMaintenanceRun, index_db_dir, index_db_size};
+use crate::db::extraction_write::current_iso_timestamp;
+use crate::db::index_writer::{IndexDbWriterMessage, call_index_db_writer};
+use crate::db::storage_stats::refresh_storage_stats_snapshot;
+use crate::db::system_config::VacuumMode;
+use crate::jobs::continuous_scan;
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+/// Job tag of scheduled runs, so a slow run is not queued twice.
+pub(crate) const DB_OPTIMIZE_JOB_TAG: &str = "db-optimize";
+
+/// Options of one optimization run, stored as the job's metadata.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct OptimizeOptions {
+    #[serde(default)]
+    pub vacuum: VacuumMode,
+    /// True for runs enqueued by the `db_maintenance` schedule.
+    #[serde(default)]
+    pub scheduled: bool,
+}
+
+/// Why a full VACUUM must be skipped, if it must: it needs room for a
+/// complete copy of the databases.
+fn vacuum_space_shortfall(db_size: u64, available: u64) -> Option<String> {
+    (available <= db_size).then(|| {
+        format!(
+            "not enough free disk space for VACUUM: {available} bytes available, more than {db_size} needed"
+        )
+    })
+}
+
+/// Runs one optimization pass over `index_db` and returns its run ID. The
+/// run is recorded even when a step fails; the error is then returned too.
+pub(crate) async fn run_optimize_job(index_db: &str, options: OptimizeOptions) -> ApiResult<i64> {
+    let start_time = current_iso_timestamp();
+    let started = Instant::now();
+    let size_before = index_db_size(index_db);
+
+    // A VACUUM holds the writer for its whole run; pause continuous scans
+    // like the other write-heavy jobs rather than queueing their writes.
+    let guard = continuous_scan::pause_for_job_guarded(index_db).await?;
+    let mut vacuumed = false;
+    let mut skipped_reason = None;
+    let result = async {
+        call_index_db_writer(index_db, |reply| IndexDbWriterMessage::WalCheckpoint {
+            reply,
+        })
+        .await?;
+        match options.vacuum {
+            VacuumMode::None => {}
+            VacuumMode::Full => {
+                let db_size = index_db_size(index_db);
+                let available = fs2::available_space(index_db_dir(index_db)).map_err(|err| {
+                    ApiError::internal(format!("Failed to read free disk space: {err}"))
+                })?;
+                skipped_reason = vacuum_space_shortfall(db_size, available);
+                if let Some(reason) = &skipped_reason {
+                    tracing::warn!(index_db, reason, "skipping VACUUM");
+                } else {
+                    call_index_db_writer(index_db, |reply| IndexDbWriterMessage::Vacuum { reply })
+                        .await?;
+                    vacuumed = true;
+                }
+            }
+            VacuumMode::Incremental => {
+                call_index_db_writer(index_db, |reply| IndexDbWriterMessage::IncrementalVacuum {
+                    reply,
+                })
+                .await?;
+                vacuumed = true;
+            }
+        }
+        call_index_db_writer(index_db, |reply| IndexDbWriterMessage::Analyze { reply }).await?;
+        // In WAL mode VACUUM writes the whole database through the log.
+        call_index_db_writer(index_db, |reply| IndexDbWriterMessage::WalCheckpoint {
+            reply,
+        })
+        .await
+    }
+    .await;
+    guard.resume().await;
+
+    let run = NewDbMaintenanceRun {
+        start_time,
+        end_time: current_iso_timestamp(),
+        trigger: if options.scheduled {
+            "scheduled"
+        } else {
+            "manual"
+        },
+        vacuum_mode: options.vacuum.as_str(),
+        vacuumed,
+        skipped_reason,
+        error: result.as_ref().err().map(|err| format!("{err:?}")),
+        size_before: size_before as i64,
+        size_after: index_db_size(index_db) as i64,
+        duration_secs: started.elapsed().as_secs_f64(),
+    };
+    tracing::info!(
+        index_db,
+        vacuum = run.vacuum_mode,
+        vacuumed = run.vacuumed,
+        size_before = run.size_before,
+        size_after = run.size_after,
+        duration_secs = run.duration_secs,
+        "database optimization finished"
+    );
+    let run_id = call_index_db_writer(index_db, |reply| {
+        IndexDbWriterMessage::AddDbMaintenanceRun {
+            run: run.clone(),
+            reply,
+        }
+    })
+    .await?;
+    // Measured last, so the snapshot reflects the vacuumed files.
+    refresh_storage_stats_snapshot(index_db).await;
+    result.map(|()| run_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jobs::cron::validate_cron_schedule;
+
+    // Ensures VACUUM is only allowed with more free space than the DB size.
+    #[test]
+    fn vacuum_requires_free_space_beyond_db_size() {
+        assert!(vacuum_space_shortfall(1000, 1001).is_none());
+        assert!(vacuum_space_shortfall(1000, 1000).is_some());
+        assert!(vacuum_space_shortfall(1000, 10).is_some());
+    }
+
+    // Ensures the default weekly schedule parses and options default to a
+    // manual full vacuum.
+    #[test]
+    fn defaults_are_valid() {
+        let config = crate::db::system_config::DbMaintenanceConfig::default();
+        assert!(!config.enabled);
+        assert!(validate_cron_schedule(&config.schedule).is_ok());
+        let options: OptimizeOptions = serde_json::from_str("{}").unwrap();
+        assert_eq!(options.vacuum, VacuumMode::Full);
+        assert!(!options.scheduled);
+    }
+}
@@ -30,14 +30,19 @@ use std::path::{Path, PathBuf};
 use std::time::{Duration, SystemTime};
 
 use crate::jobs::files::{
-    format_system_time, has_allowed_extension, is_excluded, is_hidden_or_temp,
+    IgnoredDirs, format_system_time, has_allowed_extension, is_excluded, is_hidden_or_temp,
 };
+use crate::jobs::symlinks::SymlinkPolicy;
 
 /// Path filters mirroring `should_process_path` in the continuous scan actor.
 pub(crate) struct PollFilters {
     pub roots: Vec<PathBuf>,
     pub excluded_roots: Vec<PathBuf>,
     pub allowed_extensions: HashSet<String>,
+    /// Directories with these names are never enumerated.
+    pub ignored_dirs: IgnoredDirs,
+    /// Which symlinks are followed.
+    pub symlinks: SymlinkPolicy,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -86,21 +91,24 @@ pub(crate) struct PollOutcome {
     pub degraded: bool,
 }
 
-/// Builds the initial snapshot from DB rows of `(path, last_modified)`, so the
-/// first pass diffs the disk against the index instead of treating every file
-/// as new. Directories get `dir_modified: None` and are enumerated once; only
+/// Builds the initial snapshot from DB rows of `(path, last_modified)`, with
+/// paths as they are on disk (`raw_paths::disk_path`), so the first pass
+/// diffs the disk against the index instead of treating every file as new.
+/// Directories get `dir_modified: None` and are enumerated once; only
 /// files genuinely absent from or newer than the index surface as changes.
-pub(crate) fn seed_snapshot(rows: &[(String, String)], filters: &PollFilters) -> PollerSnapshot {
+pub(crate) fn seed_snapshot(rows: &[(PathBuf, String)], filters: &PollFilters) -> PollerSnapshot {
     let mut snapshot = PollerSnapshot::default();
-    for (path_str, last_modified) in rows {
-        let path = PathBuf::from(path_str);
-        if !filters.roots.iter().any(|root| path.starts_with(root)) {
+    for (path, last_modified) in rows {
+        let Some(root) = filters.roots.iter().find(|root| path.starts_with(root)) else {
+            continue;
+        };
+        if filters.ignored_dirs.contains_file(root, path) {
             continue;
         }
-        if is_excluded(&path, &filters.excluded_roots) || is_hidden_or_temp(&path) {
+        if is_excluded(path, &filters.excluded_roots) || is_hidden_or_temp(path) {
             continue;
         }
-        if !has_allowed_extension(&path, &filters.allowed_extensions) {
+        if !has_allowed_extension(path, &filters.allowed_extensions) {
             continue;
         }
         let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
@@ -138,10 +146,15 @@ fn enumerate_dir(dir: &Path, filters: &PollFilters) -> std::io::Result<DirListin
         // itself), so this stays at O(1) network round-trips per directory
         // batch. Symlinks need a real stat to resolve the target.
         let metadata = match entry.file_type() {
-            Ok(file_type) if file_type.is_symlink() => match std::fs::metadata(&path) {
-                Ok(metadata) => metadata,
-                Err(_) => continue,
-            },
+            Ok(file_type) if file_type.is_symlink() => {
+                if !filters.symlinks.follows_link(&path) {
+                    continue;
+                }
+                match std::fs::metadata(&path) {
+                    Ok(metadata) => metadata,
+                    Err(_) => continue,
+                }
+            }
             Ok(_) => match entry.metadata() {
                 Ok(metadata) => metadata,
                 Err(_) => continue,
@@ -149,6 +162,9 @@ fn enumerate_dir(dir: &Path, filters: &PollFilters) -> std::io::Result<DirListin
             Err(_) => continue,
         };
         if metadata.is_dir() {
+            if filters.ignored_dirs.matches_name(&name) {
+                continue;
+            }
             subdirs.insert(name);
         } else if metadata.is_file() {
             if is_hidden_or_temp(&path)
@@ -334,6 +350,8 @@ mod tests {
             roots: vec![root.to_path_buf()],
             excluded_roots: Vec::new(),
             allowed_extensions: HashSet::from([".png".to_string()]),
+            ignored_dirs: IgnoredDirs::default(),
+            symlinks: SymlinkPolicy::default(),
         }
     }
 
@@ -389,11 +407,8 @@ mod tests {
 
         let (fresh_mtime, _) = get_last_modified_time_and_size(&fresh).unwrap();
         let rows = vec![
-            (fresh.to_string_lossy().to_string(), fresh_mtime),
-            (
-                stale.to_string_lossy().to_string(),
-                "2000-01-01T00:00:00".to_string(),
-            ),
+            (fresh.clone(), fresh_mtime),
+            (stale.clone(), "2000-01-01T00:00:00".to_string()),
         ];
 
         let filters = png_filters(root);
@@ -496,6 +511,8 @@ mod tests {
             roots: vec![missing],
             excluded_roots: Vec::new(),
             allowed_extensions: HashSet::from([".png".to_string()]),
+            ignored_dirs: IgnoredDirs::default(),
+            symlinks: SymlinkPolicy::default(),
         };
         let second = run_poll_pass(first.snapshot, &filters);
 
@@ -512,25 +529,15 @@ mod tests {
             roots: vec![root.clone()],
             excluded_roots: vec![root.join("excluded")],
             allowed_extensions: HashSet::from([".png".to_string()]),
+            ignored_dirs: IgnoredDirs::default(),
+            symlinks: SymlinkPolicy::default(),
         };
         let mtime = "2024-01-01T00:00:00".to_string();
         let rows = vec![
-            (
-                root.join("keep.png").to_string_lossy().to_string(),
-                mtime.clone(),
-            ),
-            (
-                root.join("skip.txt").to_string_lossy().to_string(),
-                mtime.clone(),
-            ),
-            (
-                root.join("excluded")
-                    .join("no.png")
-                    .to_string_lossy()
-                    .to_string(),
-                mtime.clone(),
-            ),
-            ("C:\\outside\\no.png".to_string(), mtime.clone()),
+            (root.join("keep.png"), mtime.clone()),
+            (root.join("skip.txt"), mtime.clone()),
+            (root.join("excluded").join("no.png"), mtime.clone()),
+            (PathBuf::from("C:\\outside\\no.png"), mtime.clone()),
         ];
 
         let snapshot = seed_snapshot(&rows, &filters);
@@ -0,0 +1,173 @@
+//! Differential full scans (`skip_unchanged_dirs`).
+//!
+//! Every directory a scan walks is recorded with its modification time and
+//! entry count. On the next scan of the folder, a directory where both still
+//! match has had no file added, removed or renamed directly inside it, so
+//! its files are not stat'ed, looked up or hashed; their rows are moved to
+//! the new scan instead, which keeps them from being marked unavailable. A
+//! directory's time only reflects its own entries, so the walk still
+//! descends into every subdirectory and checks each one on its own.
+//!
+//! Snapshots are only trusted under the scan settings they were taken with
+//! (file types, filters, ignore rules, symlink handling), and are left out
+//! for directories holding a file that failed or was still being written.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{MAIN_SEPARATOR, Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use sha2::{Digest, Sha256};
+
+use crate::api_error::ApiError;
+use crate::db::dir_snapshots::{DirSnapshot, get_dir_snapshots};
+use crate::db::raw_paths::display_path;
+use crate::db::system_config::SystemConfig;
+use crate::jobs::files::build_extension_set;
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+/// Snapshot bookkeeping for one folder scan.
+pub(crate) struct DirSnapshotWalk {
+    config_hash: String,
+    /// Snapshots of the previous scan; empty when the scan must not skip.
+    previous: HashMap<String, (i64, i64)>,
+    recorded: Vec<DirSnapshot>,
+    unchanged: HashSet<PathBuf>,
+}
+
+impl DirSnapshotWalk {
+    /// Loads the previous snapshots under `root`. With `force_full`, nothing
+    /// is skipped but the directories are still recorded for the next scan.
+    pub(crate) async fn load(
+        conn: &mut sqlx::SqliteConnection,
+        config: &SystemConfig,
+        root: &str,
+        force_full: bool,
+    ) -> ApiResult<Self> {
+        let config_hash = scan_settings_hash(config);
+        let previous = if force_full {
+            HashMap::new()
+        } else {
+            get_dir_snapshots(conn, root, &config_hash).await?
+        };
+        Ok(Self {
+            config_hash,
+            previous,
+            recorded: Vec::new(),
+            unchanged: HashSet::new(),
+        })
+    }
+
+    /// Records a directory as the walk enters it, before its entries are
+    /// read, so anything added while it is scanned moves its time past the
+    /// snapshot. Directories that cannot be read are not recorded.
+    pub(crate) fn visit_dir(&mut self, path: &Path) {
+        let Some((mtime_ns, child_count)) = dir_state(path) else {
+            return;
+        };
+        let key = dir_key(path);
+        if self.previous.get(&key) == Some(&(mtime_ns, child_count)) {
+            self.unchanged.insert(path.to_path_buf());
+        }
+        self.recorded.push(DirSnapshot {
+            path: key,
+            mtime_ns,
+            child_count,
+        });
+    }
+
+    /// Whether `path` lies directly inside an unchanged directory.
+    pub(crate) fn skips(&self, path: &Path) -> bool {
+        path.parent()
+            .is_some_and(|parent| self.unchanged.contains(parent))
+    }
+
+    pub(crate) fn skipped_dirs(&self) -> i64 {
+        self.unchanged.len() as i64
+    }
+
+    pub(crate) fn config_hash(&self) -> &str {
+        &self.config_hash
+    }
+
+    /// Unchanged directories in the form `files.path` starts with.
+    pub(crate) fn unchanged_dirs(&self) -> Vec<String> {
+        self.unchanged.iter().map(|dir| dir_key(dir)).collect()
+    }
+
+    /// The snapshots to store, without the directories of `failed_paths`,
+    /// whose files have to be looked at again next time.
+    pub(crate) fn into_snapshots(self, failed_paths: &[String]) -> Vec<DirSnapshot> {
+        let failed_dirs = failed_paths
+            .iter()
+            .filter_map(|path| Path::new(path).parent().map(dir_key))
+            .collect::<HashSet<_>>();
+        self.recorded
+            .into_iter()
+            .filter(|snapshot| !failed_dirs.contains(&snapshot.path))
+            .collect()
+    }
+}
+
+/// A directory's display path with exactly one trailing separator.
+fn dir_key(path: &Path) -> String {
+    let mut key = display_path(path);
+    if !key.ends_with(MAIN_SEPARATOR) {
+        key.push(MAIN_SEPARATOR);
+    }
+    key
+}
+
+/// Modification time in nanoseconds and number of entries.
+fn dir_state(path: &Path) -> Option<(i64, i64)> {
+    let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+    let mtime_ns = i64::try_from(modified.duration_since(UNIX_EPOCH).ok()?.as_nanos()).ok()?;
+    let child_count = std::fs::read_dir(path).ok()?.count() as i64;
+    Some((mtime_ns, child_count))
+}
+
+/// Digest of the settings that decide which files in a directory get
+/// indexed.
+fn scan_settings_hash(config: &SystemConfig) -> String {
+    let mut extensions = build_extension_set(config).into_iter().collect::<Vec<_>>();
+    extensions.sort();
+    let settings = serde_json::json!({
+        "extensions": extensions,
+        "filescan_filter": config.filescan_filter,
+        "skip_ignored_dirs": config.skip_ignored_dirs,
+        "ignored_dir_patterns": config.ignored_dir_patterns,
+        "respect_ignore_files": config.respect_ignore_files,
+        "follow_symlinks": config.follow_symlinks,
+        "symlink_duplicates": config.symlink_duplicates,
+        "path_case_sensitivity": config.path_case_sensitivity,
+    });
+    let json = serde_json::to_vec(&settings).unwrap_or_default();
+    format!("{:x}", Sha256::digest(json))[..16].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Directory keys end in exactly one separator, whether or not the
+    // walked path had one.
+    #[test]
+    fn dir_keys_end_with_one_separator() {
+        let with = format!("{0}data{0}", MAIN_SEPARATOR);
+        assert_eq!(dir_key(Path::new(&with)), with);
+        assert_eq!(
+            dir_key(&Path::new(&with).join("sub")),
+            format!("{with}sub{MAIN_SEPARATOR}")
+        );
+    }
+
+    // Any change to which files get indexed invalidates the snapshots.
+    #[test]
+    fn settings_hash_tracks_indexed_file_types() {
+        let config = SystemConfig::default();
+        let mut changed = config.clone();
+        changed.scan_pdf = !config.scan_pdf;
+        assert_eq!(scan_settings_hash(&config), scan_settings_hash(&config));
+        assert_ne!(scan_settings_hash(&config), scan_settings_hash(&changed));
+    }
+}
@@ -1,27 +1,37 @@
-use std::path::Path;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
 use base64::{Engine as _, engine::general_purpose};
 use futures_util::TryStreamExt;
 use sea_query::{SqliteQueryBuilder, Value as SeaValue, Values};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use sqlx::{
     Row,
     sqlite::{SqliteArguments, SqliteRow},
 };
 use tokio::sync::{Mutex, Semaphore};
+use tracing::Instrument;
 
-use crate::api_error::ApiError;
+use crate::api_error::{ApiError, ErrorCode};
+use crate::db::epochs;
+use crate::db::extraction_log::{
+    ExtractionFilterCounts, ExtractionModelSnapshot, ExtractionParameters, JobFilterExclusion,
+};
 use crate::db::extraction_write::{DataLogUpdate, get_setter_data_types};
 use crate::db::index_writer::{IndexDbWriterMessage, call_index_db_writer};
 use crate::db::items::get_existing_file_for_item_id;
 use crate::db::open_index_db_read;
 use crate::db::pql::run_compiled_count;
-use crate::db::system_config::{SystemConfig, SystemConfigStore};
-use crate::inferio_client::{InferenceFile, InferenceInput, PredictOutput};
+use crate::db::raw_paths::disk_path;
+use crate::db::system_config::{SystemConfig, SystemConfigStore, TextNormalizationConfig};
+use crate::inferio_client::{InferenceInput, PredictOutput};
 use crate::jobs::continuous_scan;
+use crate::jobs::data_coverage;
 use crate::jobs::files::{FileScanService, is_resync_needed, run_post_job_maintenance};
-use crate::jobs::inference_pool::{InferencePool, job_inference_context};
+use crate::jobs::frame_retention;
+use crate::jobs::inference_pool::{InferencePool, JobInferenceContext, job_inference_context};
 use crate::jobs::timing::PhaseTimer;
 use crate::pql::builder::filters::OneOrMany;
 use crate::pql::model::{
@@ -32,13 +42,32 @@ use crate::pql::{build_query_preprocessed, preprocess_query_async};
 
 type ApiResult<T> = std::result::Result<T, ApiError>;
 
+mod debug;
 mod input_handlers;
+mod memory_budget;
 mod output_handlers;
+mod throughput;
+
+pub(crate) use debug::{
+    ExtractionDebugFile, ExtractionDebugInput, ExtractionDebugOutput, ExtractionDebugRequest,
+    ExtractionDebugResponse, ExtractionDebugRow, debug_extraction,
+};
+pub(crate) use memory_budget::{ExtractionMemory, job_memory};
+use memory_budget::{BudgetRegistration, MemoryBudget, input_memory_bytes};
+
+pub(crate) use throughput::{EtaConfidence, ExtractionEta, annotate_queue_eta, record_projection};
+use throughput::{JobThroughput, ThroughputRegistration};
 
 const CACHE_KEY: &str = "batch";
 const CACHE_LRU_SIZE: i64 = 1;
 const CACHE_TTL_SECS: i64 = 60;
 
+/// Tags renormalize jobs so config saves and the manual trigger dedup
+/// against one that is already queued or running.
+pub(crate) const RENORMALIZE_JOB_TAG: &str = "text_renormalize";
+/// Rows per renormalize writer transaction.
+const RENORMALIZE_CHUNK_ROWS: i64 = 2000;
+
 #[derive(Debug, Clone)]
 pub(crate) struct ModelMetadata {
     pub group: String,
@@ -55,8 +84,14 @@ pub(crate) struct ModelMetadata {
     pub default_threshold: Option<f64>,
     pub input_mime_types: Vec<String>,
     pub skip_processed_items: bool,
+    /// Tags models: store each frame's tags besides the merged set. Starts
+    /// from the `per_frame_tags` metadata flag; `job_settings` can override
+    /// it for a run (see [`resolve_per_frame_tags`]).
+    pub per_frame_tags: bool,
+    /// MIME type prefixes left out of this run because their renderer is
+    /// not installed; set at job start (see [`unavailable_renderer_types`]).
+    pub unrenderable_mime_types: Vec<String>,
     // Informational metadata mirrored from the inference server's config.
-    #[allow(dead_code)]
     pub name: Option<String>,
     #[allow(dead_code)]
     pub description: Option<String>,
@@ -69,6 +104,8 @@ struct JobInputData {
     file_id: i64,
     item_id: i64,
     path: String,
+    /// Raw bytes of a path that is not UTF-8 (see `db::raw_paths`).
+    path_bytes: Option<Vec<u8>>,
     sha256: String,
     md5: String,
     last_modified: String,
@@ -87,12 +124,38 @@ struct JobInputData {
     text: Option<String>,
 }
 
+impl JobInputData {
+    /// The path to open the file by.
+    fn disk_path(&self) -> PathBuf {
+        disk_path(&self.path, self.path_bytes.as_deref())
+    }
+}
+
 #[derive(Debug, Clone)]
 pub(crate) struct JobDefaults {
     pub batch_size: i64,
     pub threshold: Option<f64>,
 }
 
+/// Snapshot of the resolved settings a run starts with, stored on its log.
+fn extraction_parameters(defaults: &JobDefaults, model: &ModelMetadata) -> ExtractionParameters {
+    ExtractionParameters {
+        batch_size: defaults.batch_size,
+        threshold: defaults.threshold,
+        input_handler: model.input_handler.clone(),
+        input_handler_opts: model.input_handler_opts.clone(),
+        model: ExtractionModelSnapshot {
+            group: model.group.clone(),
+            name: model.name.clone(),
+            output_type: model.output_type.clone(),
+            target_entities: model.target_entities.clone(),
+            input_mime_types: model.input_mime_types.clone(),
+            default_batch_size: model.default_batch_size,
+            default_threshold: model.default_threshold,
+        },
+    }
+}
+
 #[derive(Debug, Clone)]
 struct PreparedItem {
     item: JobInputData,
@@ -107,8 +170,11 @@ struct JobCounters {
     other_files: i64,
     total_segments: i64,
     errors: i64,
+    timeouts: i64,
+    undecodable: i64,
     data_load_time: PhaseTimer,
     inference_time: PhaseTimer,
+    throughput: Arc<JobThroughput>,
 }
 
 pub(crate) async fn run_extraction_job(job: crate::jobs::queue::Job) -> Result<(), String> {
@@ -124,10 +190,14 @@ pub(crate) async fn run_extraction_job(job: crate::jobs::queue::Job) -> Result<(
 
     let result = run_extraction_job_inner(&job, &inference_id).await;
     guard.resume().await;
+    // Tag output is written batch by batch without touching the tag epoch;
+    // the autocomplete index picks it all up once the job is over.
+    epochs::bump_tag_epoch(&job.index_db);
     match result {
         Ok(()) => {
             cleanup.disarm();
             run_post_job_maintenance(&job.index_db, false).await;
+            data_coverage::refresh_coverage_snapshot(&job.index_db, &job.user_data_db).await;
             Ok(())
         }
         Err(err) => {
@@ -195,31 +265,41 @@ async fn run_extraction_job_inner(
         service.run_folder_update().await?;
     }
 
-    let model = load_model_metadata(inference_id).await?;
+    let mut model = load_model_metadata(inference_id).await?;
+    model.per_frame_tags = resolve_per_frame_tags(&config, &model);
+    model.unrenderable_mime_types = unavailable_renderer_types(&model);
+    if !model.unrenderable_mime_types.is_empty() {
+        tracing::error!(
+            inference_id,
+            skipped = ?model.unrenderable_mime_types,
+            "renderer not available; these items are left for a later run"
+        );
+    }
     let defaults = resolve_job_defaults(&config, &model, job.batch_size, job.threshold);
 
     let context = job_inference_context();
     if context.pool.is_empty().await {
-        return Err(ApiError::internal(
+        return Err(ApiError::upstream_unavailable(
             "No inference endpoints enabled for batch jobs",
         ));
     }
 
-    let mut query = build_job_pql(&config, &model)?;
-    if let Some(root) = query.query.take() {
-        let preprocessed = preprocess_query_async(
-            root,
-            &context.primary,
-            context.embedding_cache_size,
-            Some(&job.index_db),
-        )
-        .await
-        .map_err(|err| ApiError::bad_request(err.message))?;
-        query.query = preprocessed;
-    }
-
+    let query = preprocess_job_pql(
+        build_job_pql(&config, &model, job.reprocess)?,
+        context,
+        &job.index_db,
+    )
+    .await?;
     let compiled = compile_pql_select(query.clone())?;
-    let compiled_count = compile_pql_count(query.clone())?;
+    let compiled_count = compile_pql_count(query)?;
+    // A reprocess job matches processed items too, so what is left to do
+    // afterwards is counted with the normal query.
+    let compiled_remaining = if job.reprocess {
+        let query = build_job_pql(&config, &model, false)?;
+        compile_pql_count(preprocess_job_pql(query, context, &job.index_db).await?)?
+    } else {
+        compiled_count.clone()
+    };
 
     // Clean up incomplete jobs before counting: with ATOMIC_EXTRACTION_JOBS
     // the cleanup deletes their item_data, which frees items for
@@ -232,10 +312,19 @@ async fn run_extraction_job_inner(
     let mut count_conn = open_index_db_read(&job.index_db, &job.user_data_db).await?;
     let total_remaining =
         run_compiled_count(&mut count_conn, &compiled_count.sql, &compiled_count.params).await?;
+    let filter_counts = count_filter_exclusions(
+        &mut count_conn,
+        &config,
+        &model,
+        job.reprocess,
+        &job.index_db,
+        Some(total_remaining),
+    )
+    .await?;
     drop(count_conn);
 
     if total_remaining < 1 {
-        tracing::info!(inference_id, "no items to process");
+        tracing::info!(inference_id, ?filter_counts, "no items to process");
         return Ok(());
     }
 
@@ -243,12 +332,19 @@ async fn run_extraction_job_inner(
     // start_time/end_time are directly comparable (and match Python's local
     // isoformat convention).
     let scan_time = crate::db::extraction_write::current_iso_timestamp();
+    let parameters = serde_json::to_string(&extraction_parameters(&defaults, &model))
+        .map_err(|err| ApiError::internal(format!("Failed to encode run parameters: {err}")))?;
+    let filter_counts = serde_json::to_string(&filter_counts)
+        .map_err(|err| ApiError::internal(format!("Failed to encode filter counts: {err}")))?;
     let job_id = call_index_db_writer(&job.index_db, |reply| IndexDbWriterMessage::AddDataLog {
         scan_time: scan_time.clone(),
         threshold: defaults.threshold,
         types: vec![model.output_type.clone()],
         setter: model.setter_name.clone(),
         batch_size: defaults.batch_size,
+        reprocess: job.reprocess,
+        parameters: Some(parameters.clone()),
+        filter_counts: Some(filter_counts.clone()),
         reply,
     })
     .await?;
@@ -272,20 +368,27 @@ async fn run_extraction_job_inner(
         )
         .await;
     if let Err(err) = load_result {
-        return Err(ApiError::internal(format!("Failed to load model: {err}")));
+        return Err(ApiError::upstream_unavailable(format!(
+            "Failed to load model: {err}"
+        )));
     }
 
-    let counters = Arc::new(Mutex::new(JobCounters::default()));
+    let counters = JobCounters::default();
+    let _throughput = ThroughputRegistration::register(
+        job.queue_id,
+        &counters.throughput,
+        model.target_entities.first().cloned(),
+        total_remaining,
+    );
+    let counters = Arc::new(Mutex::new(counters));
     // Bounds concurrent input loading (decode processes, file reads). Loaded
     // items park on the byte budget below, so loading pipelines ahead of
     // inference instead of running in lockstep with it.
     let loader_slots = Arc::new(Semaphore::new(context.loader_concurrency.max(1)));
-    // Bounds loaded-but-unfinished intermediate data across in-flight items
-    // (KiB permits). An item larger than the whole budget clamps to capacity
-    // and runs alone; worst-case memory is roughly
-    // budget + loader_concurrency × item size.
-    let budget_capacity = context.intermediate_budget_kib.max(1);
-    let budget_slots = Arc::new(Semaphore::new(budget_capacity as usize));
+    // Bounds loaded-but-unfinished intermediate data across in-flight items.
+    // Worst-case memory is roughly budget + loader_concurrency × item size.
+    let budget = MemoryBudget::new(context.intermediate_budget_kib);
+    let _registration = BudgetRegistration::register(job.queue_id, &budget);
     // Bounds the total number of work units inside in-flight inference
     // requests across all items (the actual meaning of job batch_size).
     let unit_slots = Arc::new(Semaphore::new(defaults.batch_size as usize));
@@ -316,19 +419,22 @@ async fn run_extraction_job_inner(
         let counters = Arc::clone(&counters);
         let index_db = job.index_db.clone();
         let threshold = defaults.threshold;
+        let reprocess = job.reprocess;
+        let normalization = config.text_normalization.clone();
         let unit_slots = Arc::clone(&unit_slots);
-        let budget_slots = Arc::clone(&budget_slots);
+        let budget = Arc::clone(&budget);
         tasks.spawn(async move {
             let result = process_item(
                 &index_db,
                 &model,
                 job_id,
+                reprocess,
                 item,
                 threshold,
+                &normalization,
                 &pool,
                 loader_permit,
-                &budget_slots,
-                budget_capacity,
+                &budget,
                 &unit_slots,
                 unit_capacity,
                 counters,
@@ -338,22 +444,37 @@ async fn run_extraction_job_inner(
             if let Err(err) = result {
                 tracing::error!(error = ?err, "extraction item failed");
             }
-        });
+        }
+        .in_current_span());
     }
     drop(rows);
     drop(conn);
 
     while tasks.join_next().await.is_some() {}
 
+    // Replaced tag sets can leave tags that no item carries any more.
+    if job.reprocess
+        && model.output_type == "tags"
+        && let Err(err) = call_index_db_writer(&job.index_db, |reply| {
+            IndexDbWriterMessage::DeleteOrphanTags { reply }
+        })
+        .await
+    {
+        tracing::error!(error = ?err, "failed to delete orphan tags after reprocessing");
+    }
+
     let remaining_after = {
         let mut count_conn = open_index_db_read(&job.index_db, &job.user_data_db).await?;
-        let remaining =
-            run_compiled_count(&mut count_conn, &compiled_count.sql, &compiled_count.params)
-                .await?;
+        let remaining = run_compiled_count(
+            &mut count_conn,
+            &compiled_remaining.sql,
+            &compiled_remaining.params,
+        )
+        .await?;
         remaining
     };
 
-    let (final_update, total_failure) = {
+    let (mut final_update, total_failure) = {
         let guard = counters.lock().await;
         // Every attempted item failing means a systemic cause (inference
         // server down, model broken), not per-item bad data: surface it as a
@@ -366,6 +487,9 @@ async fn run_extraction_job_inner(
             other_files: guard.other_files,
             total_segments: guard.total_segments,
             errors: guard.errors,
+            timeouts: guard.timeouts,
+            undecodable: guard.undecodable,
+            frames_dropped: 0,
             total_remaining: remaining_after,
             data_load_time: guard.data_load_time.busy_secs(),
             inference_time: guard.inference_time.busy_secs(),
@@ -382,6 +506,11 @@ async fn run_extraction_job_inner(
         );
         (update, total_failure)
     };
+    if !total_failure {
+        final_update.frames_dropped =
+            frame_retention::drop_frames_after_job(&job.index_db, &config.frame_retention).await
+                as i64;
+    }
     let _ = call_index_db_writer(&job.index_db, |reply| IndexDbWriterMessage::UpdateDataLog {
         job_id,
         update: final_update.clone(),
@@ -395,7 +524,7 @@ async fn run_extraction_job_inner(
         .await;
 
     if total_failure {
-        return Err(ApiError::internal(format!(
+        return Err(ApiError::upstream_unavailable(format!(
             "All {} attempted items failed; check the inference server",
             final_update.errors
         )));
@@ -403,23 +532,37 @@ async fn run_extraction_job_inner(
     Ok(())
 }
 
+/// Data deletion job options, stored as the queued job's metadata.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct DataDeletionOptions {
+    pub inference_id: String,
+    /// Also delete other setters' data derived from this setter's; without
+    /// it, directly derived rows are kept as origin data.
+    pub cascade: bool,
+}
+
 pub(crate) async fn run_data_deletion_job(job: crate::jobs::queue::Job) -> Result<(), String> {
-    let inference_id = job
+    let options: DataDeletionOptions = job
         .metadata
-        .clone()
-        .ok_or_else(|| "Inference ID required".to_string())?;
+        .as_deref()
+        .ok_or_else(|| "Inference ID required".to_string())
+        .and_then(|metadata| {
+            serde_json::from_str(metadata)
+                .map_err(|err| format!("Invalid data deletion options: {err}"))
+        })?;
     let guard = continuous_scan::pause_for_job_guarded(&job.index_db)
         .await
         .map_err(|err| format!("{err:?}"))?;
-    let result = run_data_deletion_job_inner(&job, &inference_id).await;
+    let result = run_data_deletion_job_inner(&job, &options).await;
     guard.resume().await;
     result.map_err(|err| format!("{err:?}"))
 }
 
 async fn run_data_deletion_job_inner(
     job: &crate::jobs::queue::Job,
-    inference_id: &str,
+    options: &DataDeletionOptions,
 ) -> ApiResult<()> {
+    let inference_id = options.inference_id.as_str();
     let mut conn = open_index_db_read(&job.index_db, &job.user_data_db).await?;
     let data_types = get_setter_data_types(&mut conn, inference_id).await?;
     drop(conn);
@@ -428,41 +571,108 @@ async fn run_data_deletion_job_inner(
     let (deleted, orphan_tags_deleted) = call_index_db_writer(&job.index_db, |reply| {
         IndexDbWriterMessage::DeleteSetterData {
             setter_name: inference_id.to_string(),
+            cascade: options.cascade,
             include_orphan_tags,
             reply,
         }
     })
     .await?;
+    tracing::info!(
+        index_db = job.index_db,
+        inference_id,
+        cascade = options.cascade,
+        derived = deleted.derived,
+        "setter data deleted"
+    );
 
     // VACUUM blocks the writer for the whole run; skip it when the deletion
     // turned out to be a no-op.
-    run_post_job_maintenance(&job.index_db, deleted > 0 || orphan_tags_deleted > 0).await;
+    run_post_job_maintenance(
+        &job.index_db,
+        deleted.setters > 0 || deleted.derived > 0 || orphan_tags_deleted > 0,
+    )
+    .await;
     Ok(())
 }
 
+/// Brings every row's `normalized_text` in line with the DB's current
+/// `[text_normalization]` settings, one writer transaction per chunk.
+/// Settings saved while it runs are applied by another full pass instead of
+/// a second job (the enqueue is deduplicated against this one).
+pub(crate) async fn run_text_renormalize_job(index_db: &str) -> ApiResult<()> {
+    let store = SystemConfigStore::from_env();
+    let mut config = store.load(index_db)?.text_normalization;
+    loop {
+        let mut after_id = 0;
+        let mut updated = 0;
+        loop {
+            let chunk = call_index_db_writer(index_db, |reply| {
+                IndexDbWriterMessage::RenormalizeTextChunk {
+                    config: config.clone(),
+                    after_id,
+                    limit: RENORMALIZE_CHUNK_ROWS,
+                    reply,
+                }
+            })
+            .await?;
+            if chunk.scanned == 0 {
+                break;
+            }
+            updated += chunk.updated;
+            after_id = chunk.cursor;
+        }
+        tracing::info!(index_db, updated, "extracted text renormalized");
+        let latest = store.load(index_db)?.text_normalization;
+        if latest == config {
+            return Ok(());
+        }
+        config = latest;
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
 async fn process_item(
     index_db: &str,
     model: &ModelMetadata,
     job_id: i64,
+    replace: bool,
     item: JobInputData,
     threshold: Option<f64>,
+    normalization: &TextNormalizationConfig,
     pool: &InferencePool,
     loader_permit: tokio::sync::OwnedSemaphorePermit,
-    budget_slots: &Arc<Semaphore>,
-    budget_capacity: u32,
+    budget: &Arc<MemoryBudget>,
     unit_slots: &Arc<Semaphore>,
     unit_capacity: usize,
     counters: Arc<Mutex<JobCounters>>,
     total_remaining: i64,
 ) -> ApiResult<()> {
     let item_type = item.item_type.clone();
+    let sha256 = item.sha256.clone();
     let load_span = counters.lock().await.data_load_time.start();
     let prepare_result = input_handlers::prepare_item(index_db, model, item).await;
     drop(load_span);
     let prepared = match prepare_result {
         Ok(prepared) => prepared,
         Err(err) => {
+            match err.code() {
+                ErrorCode::MediaTimeout => counters.lock().await.timeouts += 1,
+                ErrorCode::MediaUndecodable => {
+                    counters.lock().await.undecodable += 1;
+                    if let Err(err) = call_index_db_writer(index_db, |reply| {
+                        IndexDbWriterMessage::SetItemUndecodable {
+                            sha256: sha256.clone(),
+                            undecodable: true,
+                            reply,
+                        }
+                    })
+                    .await
+                    {
+                        tracing::error!(error = ?err, "failed to mark item undecodable");
+                    }
+                }
+                _ => {}
+            }
             finalize_item(
                 index_db,
                 job_id,
@@ -480,7 +690,8 @@ async fn process_item(
 
     if prepared.inputs.is_empty() {
         let result =
-            output_handlers::write_placeholder(index_db, model, job_id, &prepared.item).await;
+            output_handlers::write_placeholder(index_db, model, job_id, replace, &prepared.item)
+                .await;
         finalize_item(
             index_db,
             job_id,
@@ -499,22 +710,11 @@ async fn process_item(
     // Reserve budget for the loaded data *before* releasing the loader slot:
     // when the budget is exhausted this parks with the slot still held, so
     // once every loader slot is parked no new loads start — that is the
-    // backpressure that bounds memory. The clamp to capacity means an item
-    // bigger than the entire budget acquires all of it and runs alone rather
-    // than deadlocking.
-    let kib = input_memory_kib(&inference_inputs);
-    let _budget_permits = if kib > 0 {
-        let want = kib.min(budget_capacity);
-        Some(
-            budget_slots
-                .clone()
-                .acquire_many_owned(want)
-                .await
-                .map_err(|_| ApiError::internal("Extraction budget semaphore closed"))?,
-        )
-    } else {
-        None
-    };
+    // backpressure that bounds memory.
+    let _reservation = budget
+        .reserve(input_memory_bytes(&inference_inputs))
+        .await
+        .ok_or_else(|| ApiError::internal("Extraction budget semaphore closed"))?;
     drop(loader_permit);
 
     let segments = inference_inputs.len() as i64;
@@ -530,7 +730,7 @@ async fn process_item(
     {
         Ok(outputs) => outputs,
         Err(err) => {
-            let api_err = ApiError::internal(format!("Inference failed: {err}"));
+            let api_err = ApiError::upstream_unavailable(format!("Inference failed: {err}"));
             finalize_item(
                 index_db,
                 job_id,
@@ -546,9 +746,16 @@ async fn process_item(
         }
     };
 
-    let result =
-        output_handlers::handle_outputs(index_db, model, job_id, prepared.item.clone(), outputs)
-            .await;
+    let result = output_handlers::handle_outputs(
+        index_db,
+        model,
+        job_id,
+        replace,
+        prepared.item.clone(),
+        outputs,
+        normalization,
+    )
+    .await;
     finalize_item(
         index_db,
         job_id,
@@ -563,20 +770,6 @@ async fn process_item(
     result.map(|_| ())
 }
 
-/// In-memory footprint of an item's prepared inputs, in KiB (rounded up).
-/// Only counts buffers actually held in memory: path-based inputs are read
-/// transiently at request time, which the work-unit cap already bounds.
-fn input_memory_kib(inputs: &[InferenceInput]) -> u32 {
-    let bytes: usize = inputs
-        .iter()
-        .map(|input| match &input.file {
-            Some(InferenceFile::Bytes(buffer)) => buffer.len(),
-            _ => 0,
-        })
-        .sum();
-    u32::try_from(bytes.div_ceil(1024)).unwrap_or(u32::MAX)
-}
-
 /// Runs inference over one item's work units in chunks of at most
 /// `unit_capacity`, holding one unit permit per work unit for the duration of
 /// each request. Together with the shared semaphore this caps the total
@@ -656,6 +849,7 @@ async fn finalize_item(
         let mut guard = counters.lock().await;
         guard.processed += 1;
         guard.total_segments += segments;
+        guard.throughput.record(guard.processed);
 
         if count_file {
             if item_type.starts_with("video") {
@@ -676,6 +870,9 @@ async fn finalize_item(
             other_files: guard.other_files,
             total_segments: guard.total_segments,
             errors: guard.errors,
+            timeouts: guard.timeouts,
+            undecodable: guard.undecodable,
+            frames_dropped: 0,
             total_remaining: remaining,
             data_load_time: guard.data_load_time.busy_secs(),
             inference_time: guard.inference_time.busy_secs(),
@@ -715,6 +912,10 @@ async fn map_job_input(
         file_id,
         item_id,
         path,
+        // The job query selects only the text path. A file whose name is not
+        // UTF-8 does not exist under it, so it takes the fallback below,
+        // which reads the raw bytes too.
+        path_bytes: None,
         sha256,
         md5,
         last_modified,
@@ -729,10 +930,11 @@ async fn map_job_input(
         text,
     };
 
-    if !Path::new(&input.path).exists() {
+    if !input.disk_path().exists() {
         let mut conn = open_index_db_read(index_db, user_data_db).await?;
         if let Some(file) = get_existing_file_for_item_id(&mut conn, input.item_id).await? {
             input.path = file.path;
+            input.path_bytes = file.path_bytes;
             input.file_id = file.id;
             input.last_modified = file.last_modified;
         } else {
@@ -747,21 +949,111 @@ fn map_row_err(err: sqlx::Error) -> ApiError {
     tracing::error!(error = %err, "failed to read query row");
     ApiError::internal("Failed to read job input")
 }
-fn build_job_pql(config: &SystemConfig, model: &ModelMetadata) -> ApiResult<PqlQuery> {
-    let mut filters = Vec::new();
-    if !model.input_mime_types.is_empty() {
-        filters.push(QueryElement::Match(Match {
-            match_: Matches::Ops(MatchOps {
-                startswith: Some(MatchValues {
-                    r#type: Some(OneOrMany::Many(model.input_mime_types.clone())),
-                    ..Default::default()
-                }),
+/// Restricts a job to the MIME type prefixes the model accepts; `None` when
+/// it accepts everything.
+pub(crate) fn model_mime_filter(model: &ModelMetadata) -> Option<Match> {
+    if model.input_mime_types.is_empty() {
+        return None;
+    }
+    Some(mime_prefix_match(model.input_mime_types.clone()))
+}
+
+fn mime_prefix_match(prefixes: Vec<String>) -> Match {
+    Match {
+        match_: Matches::Ops(MatchOps {
+            startswith: Some(MatchValues {
+                r#type: Some(OneOrMany::Many(prefixes)),
                 ..Default::default()
             }),
+            ..Default::default()
+        }),
+    }
+}
+
+/// MIME type prefixes the `image_frames` handler renders with an external
+/// renderer, which the model accepts but whose renderer is not installed.
+/// The renderers are probed once per process and cached, so a missing one
+/// costs one job-level error instead of a failed item per document; the
+/// items stay unprocessed and are picked up once the renderer is there.
+fn unavailable_renderer_types(model: &ModelMetadata) -> Vec<String> {
+    if model.input_handler != "image_frames" {
+        return Vec::new();
+    }
+    let accepts = |prefix: &str| {
+        model.input_mime_types.is_empty()
+            || model
+                .input_mime_types
+                .iter()
+                .any(|accepted| prefix.starts_with(accepted) || accepted.starts_with(prefix))
+    };
+    [
+        ("application/pdf", crate::jobs::files::pdf_renderer_available as fn() -> bool),
+        ("text/html", crate::jobs::files::html_renderer_available),
+    ]
+    .into_iter()
+    .filter(|(prefix, available)| accepts(prefix) && !available())
+    .map(|(prefix, _)| prefix.to_string())
+    .collect()
+}
+
+/// Runs async preprocessing (embedding any vector-search text in the job
+/// filters) on a job query.
+async fn preprocess_job_pql(
+    mut query: PqlQuery,
+    context: &JobInferenceContext,
+    index_db: &str,
+) -> ApiResult<PqlQuery> {
+    if let Some(root) = query.query.take() {
+        query.query = preprocess_query_async(
+            root,
+            &context.primary,
+            context.embedding_cache_bytes,
+            Some(index_db),
+        )
+        .await?;
+    }
+    Ok(query)
+}
+
+/// A filter left out of the job query, to count the items it excludes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum OmittedFilter {
+    MimeType,
+    /// Index into the system config's `job_filters`.
+    JobFilter(usize),
+}
+
+/// The job's item query. `reprocess` keeps items the setter already
+/// processed, whose data the job then replaces.
+fn build_job_pql(
+    config: &SystemConfig,
+    model: &ModelMetadata,
+    reprocess: bool,
+) -> ApiResult<PqlQuery> {
+    build_job_pql_omitting(config, model, reprocess, None)
+}
+
+fn build_job_pql_omitting(
+    config: &SystemConfig,
+    model: &ModelMetadata,
+    reprocess: bool,
+    omitted: Option<OmittedFilter>,
+) -> ApiResult<PqlQuery> {
+    let mut filters = Vec::new();
+    if omitted != Some(OmittedFilter::MimeType)
+        && let Some(filter) = model_mime_filter(model)
+    {
+        filters.push(QueryElement::Match(filter));
+    }
+    if !model.unrenderable_mime_types.is_empty() {
+        filters.push(QueryElement::Not(NotOperator {
+            not_: Box::new(QueryElement::Match(mime_prefix_match(
+                model.unrenderable_mime_types.clone(),
+            ))),
         }));
     }
 
-    if model.skip_processed_items {
+    if model.skip_processed_items && !reprocess {
         filters.push(QueryElement::Not(NotOperator {
             not_: Box::new(QueryElement::ProcessedBy(ProcessedBy {
                 processed_by: model.setter_name.clone(),
@@ -770,11 +1062,9 @@ fn build_job_pql(config: &SystemConfig, model: &ModelMetadata) -> ApiResult<PqlQ
     }
 
     let mut user_filters = Vec::new();
-    for filter in &config.job_filters {
-        if filter
-            .setter_names
-            .iter()
-            .any(|name| name == "*" || name == &model.setter_name)
+    for (index, filter) in config.job_filters.iter().enumerate() {
+        if filter.applies_to(&model.setter_name)
+            && omitted != Some(OmittedFilter::JobFilter(index))
         {
             match &filter.pql_query {
                 QueryElement::And(and) => user_filters.extend(and.and_.clone()),
@@ -857,6 +1147,112 @@ fn build_job_pql(config: &SystemConfig, model: &ModelMetadata) -> ApiResult<PqlQ
     Ok(pql)
 }
 
+/// Counts the job query's items, and the items each of its filters keeps
+/// out (see [`ExtractionFilterCounts`]): one count query per mime type or
+/// job filter that applies. `total` skips the full count when the caller
+/// already ran it.
+pub(crate) async fn count_filter_exclusions(
+    conn: &mut sqlx::SqliteConnection,
+    config: &SystemConfig,
+    model: &ModelMetadata,
+    reprocess: bool,
+    index_db: &str,
+    total: Option<i64>,
+) -> ApiResult<ExtractionFilterCounts> {
+    let total = match total {
+        Some(total) => total,
+        None => {
+            let query = build_job_pql(config, model, reprocess)?;
+            count_job_pql(conn, query, index_db).await?
+        }
+    };
+    let excluded_by_mime_type = if model_mime_filter(model).is_some() {
+        let omitted = Some(OmittedFilter::MimeType);
+        let query = build_job_pql_omitting(config, model, reprocess, omitted)?;
+        count_job_pql(conn, query, index_db).await? - total
+    } else {
+        0
+    };
+    let mut excluded_by_job_filters = Vec::new();
+    for (index, filter) in config.job_filters.iter().enumerate() {
+        if !filter.applies_to(&model.setter_name) {
+            continue;
+        }
+        let omitted = Some(OmittedFilter::JobFilter(index));
+        let query = build_job_pql_omitting(config, model, reprocess, omitted)?;
+        excluded_by_job_filters.push(JobFilterExclusion {
+            index,
+            setter_names: filter.setter_names.clone(),
+            excluded: count_job_pql(conn, query, index_db).await? - total,
+        });
+    }
+    Ok(ExtractionFilterCounts {
+        total,
+        excluded_by_mime_type,
+        excluded_by_job_filters,
+    })
+}
+
+async fn count_job_pql(
+    conn: &mut sqlx::SqliteConnection,
+    query: PqlQuery,
+    index_db: &str,
+) -> ApiResult<i64> {
+    let query = preprocess_job_pql(query, job_inference_context(), index_db).await?;
+    let compiled = compile_pql_count(query)?;
+    run_compiled_count(conn, &compiled.sql, &compiled.params).await
+}
+
+/// The follow-up jobs `job_settings` configure for `inference_id`: its own
+/// entry's if that names any, else its group's.
+pub(crate) fn resolve_follow_up(config: &SystemConfig, inference_id: &str) -> Vec<String> {
+    let group = inference_id.split_once('/').map(|(group, _)| group);
+    let configured = |model_entry: bool| {
+        config
+            .job_settings
+            .iter()
+            .filter(|setting| Some(setting.group_name.as_str()) == group)
+            .filter(|setting| {
+                if model_entry {
+                    setting.inference_id.as_deref() == Some(inference_id)
+                } else {
+                    setting.inference_id.is_none()
+                }
+            })
+            .flat_map(|setting| setting.follow_up.iter().cloned())
+            .collect::<Vec<_>>()
+    };
+    let own = configured(true);
+    if own.is_empty() {
+        configured(false)
+    } else {
+        own
+    }
+}
+
+/// Whether a run of `model` stores per-frame tags: its own `job_settings`
+/// entry decides if it sets `per_frame_tags`, then its group's, then the
+/// model metadata.
+pub(crate) fn resolve_per_frame_tags(config: &SystemConfig, model: &ModelMetadata) -> bool {
+    let configured = |model_entry: bool| {
+        config
+            .job_settings
+            .iter()
+            .filter(|setting| setting.group_name == model.group)
+            .filter(|setting| {
+                if model_entry {
+                    setting.inference_id.as_deref() == Some(model.setter_name.as_str())
+                } else {
+                    setting.inference_id.is_none()
+                }
+            })
+            .find_map(|setting| setting.per_frame_tags)
+    };
+    configured(true)
+        .or_else(|| configured(false))
+        .unwrap_or(model.per_frame_tags)
+}
+
 pub(crate) fn resolve_job_defaults(
     config: &SystemConfig,
     model: &ModelMetadata,
@@ -918,15 +1314,65 @@ pub(crate) fn resolve_job_defaults(
     }
 }
 
+/// Resolves one model against the primary upstream's metadata. A model the
+/// cached copy doesn't list may have been added to the inference server
+/// since it was fetched, so that case retries once against a forced refetch
+/// before reporting the model as unknown.
 pub(crate) async fn load_model_metadata(inference_id: &str) -> ApiResult<ModelMetadata> {
-    let context = job_inference_context();
-    let metadata = context.primary.get_metadata().await.map_err(|err| {
-        tracing::error!(error = %err, "failed to load inference metadata");
-        ApiError::internal("Failed to load inference metadata")
-    })?;
+    let metadata = fetch_inference_metadata().await?;
+    if !inference_id.contains('/') || lists_inference_id(&metadata, inference_id) {
+        return resolve_model_metadata(&metadata, inference_id);
+    }
+    let metadata = refresh_inference_metadata().await?;
     resolve_model_metadata(&metadata, inference_id)
 }
 
+/// The primary inference upstream's full `/metadata` payload, for callers
+/// that resolve many models against one fetch.
+pub(crate) async fn fetch_inference_metadata() -> ApiResult<Value> {
+    fetch_inference_metadata_with_age()
+        .await
+        .map(|(metadata, _)| metadata)
+}
+
+/// Like [`fetch_inference_metadata`], plus the age of the cached copy the
+/// payload came from (`None` when it was fetched without the cache).
+pub(crate) async fn fetch_inference_metadata_with_age() -> ApiResult<(Value, Option<Duration>)> {
+    job_inference_context()
+        .primary
+        .get_metadata_with_age()
+        .await
+        .map_err(|err| {
+            tracing::error!(error = %err, "failed to load inference metadata");
+            ApiError::upstream_unavailable("Failed to load inference metadata")
+        })
+}
+
+/// Drops the primary upstream's cached `/metadata` and fetches it again.
+pub(crate) async fn refresh_inference_metadata() -> ApiResult<Value> {
+    job_inference_context()
+        .primary
+        .refresh_metadata()
+        .await
+        .map_err(|err| {
+            tracing::error!(error = %err, "failed to refresh inference metadata");
+            ApiError::upstream_unavailable("Failed to refresh inference metadata")
+        })
+}
+
+fn lists_inference_id(metadata: &Value, inference_id: &str) -> bool {
+    inference_id
+        .split_once('/')
+        .and_then(|(group, short_id)| {
+            metadata
+                .get(group)?
+                .get("inference_ids")?
+                .as_object()
+                .map(|ids| ids.contains_key(short_id))
+        })
+        .unwrap_or(false)
+}
+
 /// Resolves a single model's metadata from an already-fetched `/metadata`
 /// payload. Errors mean the model is unknown to the inference server (or its
 /// entry is malformed) — the payload itself being unavailable is the caller's
@@ -1009,6 +1455,11 @@ pub(crate) fn resolve_model_metadata(
         .and_then(Value::as_bool)
         .unwrap_or(true);
 
+    let per_frame_tags = merged
+        .get("per_frame_tags")
+        .and_then(Value::as_bool)
+        .unwrap_or(false);
+
     let name = merged
         .get("name")
         .and_then(Value::as_str)
@@ -1034,6 +1485,8 @@ pub(crate) fn resolve_model_metadata(
         default_threshold,
         input_mime_types,
         skip_processed_items,
+        per_frame_tags,
+        unrenderable_mime_types: Vec::new(),
         name,
         description,
         link,
@@ -1090,14 +1543,12 @@ struct CompiledQuery {
 }
 
 fn compile_pql_select(query: PqlQuery) -> ApiResult<CompiledQuery> {
-    let built =
-        build_query_preprocessed(query, false).map_err(|err| ApiError::bad_request(err.message))?;
+    let built = build_query_preprocessed(query, false)?;
     compile_select(built)
 }
 
 fn compile_pql_count(query: PqlQuery) -> ApiResult<CompiledQuery> {
-    let built =
-        build_query_preprocessed(query, true).map_err(|err| ApiError::bad_request(err.message))?;
+    let built = build_query_preprocessed(query, true)?;
     compile_select(built)
 }
 
@@ -1228,3 +1679,140 @@ fn bind_param<'q>(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::migrations::setup_test_databases;
+    use crate::inferio_client::InferenceApiClient;
+    use crate::jobs::inference_pool::set_job_inference_context;
+
+    async fn seed(conn: &mut sqlx::SqliteConnection) {
+        sqlx::query(
+            r#"
+            INSERT INTO file_scans (id, start_time, path)
+            VALUES (1, '2024-01-01T00:00:00', '/data');
+            INSERT INTO items (id, sha256, md5, type, size, time_added)
+            VALUES
+                (1, 'sha_1', 'md5_1', 'image/png', 1000, '2024-01-01T00:00:00'),
+                (2, 'sha_2', 'md5_2', 'image/jpeg', 90000000, '2024-01-01T00:00:00'),
+                (3, 'sha_3', 'md5_3', 'video/mp4', 5000, '2024-01-01T00:00:00'),
+                (4, 'sha_4', 'md5_4', 'video/mp4', 10, '2024-01-01T00:00:00'),
+                (5, 'sha_5', 'md5_5', 'image/png', 2000, '2024-01-01T00:00:00'),
+                (6, 'sha_6', 'md5_6', 'image/png', 10, '2024-01-01T00:00:00');
+            INSERT INTO files (id, sha256, item_id, path, filename, last_modified, scan_id, available)
+            VALUES
+                (10, 'sha_1', 1, '/data/a.png', 'a.png', '2024-01-01T00:00:00', 1, 1),
+                (20, 'sha_2', 2, '/data/b.jpg', 'b.jpg', '2024-01-01T00:00:00', 1, 1),
+                (30, 'sha_3', 3, '/data/c.mp4', 'c.mp4', '2024-01-01T00:00:00', 1, 1),
+                (40, 'sha_4', 4, '/data/d.mp4', 'd.mp4', '2024-01-01T00:00:00', 1, 1),
+                (50, 'sha_5', 5, '/data/skip/e.png', 'e.png', '2024-01-01T00:00:00', 1, 1),
+                (60, 'sha_6', 6, '/data/skip/f.png', 'f.png', '2024-01-01T00:00:00', 1, 1);
+            "#,
+        )
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+    }
+
+    fn model() -> ModelMetadata {
+        ModelMetadata {
+            group: "tags".to_string(),
+            inference_id: "tags/model".to_string(),
+            setter_name: "tagger".to_string(),
+            input_handler: "image_frames".to_string(),
+            input_handler_opts: serde_json::Map::new(),
+            target_entities: vec!["items".to_string()],
+            output_type: "tags".to_string(),
+            default_batch_size: 1,
+            default_threshold: None,
+            input_mime_types: vec!["image/".to_string()],
+            skip_processed_items: false,
+            per_frame_tags: false,
+            unrenderable_mime_types: Vec::new(),
+            name: None,
+            description: None,
+            link: None,
+        }
+    }
+
+    fn job_filter(setter_names: &[&str], pql_query: Value) -> crate::pql::model::JobFilter {
+        serde_json::from_value(serde_json::json!({
+            "setter_names": setter_names,
+            "pql_query": pql_query,
+        }))
+        .unwrap()
+    }
+
+    // Ensures each mime type and job filter reports the items it alone
+    // keeps out of the job, and filters for other setters are not counted.
+    #[test]
+    fn filter_exclusions_count_the_items_each_filter_removes() {
+        // Debug-build PQL compilation needs more than the default 2 MiB test
+        // thread stack; the server's runtime workers get 8 MiB.
+        let counts = std::thread::Builder::new()
+            .stack_size(8 * 1024 * 1024)
+            .spawn(|| {
+                tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .unwrap()
+                    .block_on(count_seeded_exclusions())
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(counts.total, 1);
+        assert_eq!(counts.excluded_by_mime_type, 2);
+        let excluded = counts
+            .excluded_by_job_filters
+            .iter()
+            .map(|filter| (filter.index, filter.excluded))
+            .collect::<Vec<_>>();
+        assert_eq!(excluded, vec![(0, 1), (2, 2)]);
+    }
+
+    async fn count_seeded_exclusions() -> ExtractionFilterCounts {
+        // Never called: the job filters have no vector filters.
+        let client =
+            InferenceApiClient::new_with_metadata_cache("http://127.0.0.1:9".to_string(), false)
+                .unwrap();
+        let _ = set_job_inference_context(JobInferenceContext {
+            primary: client,
+            pool: InferencePool::new(Vec::new()).unwrap(),
+            embedding_cache_bytes: 0,
+            loader_concurrency: 1,
+            intermediate_budget_kib: 0,
+        });
+        let mut dbs = setup_test_databases().await;
+        seed(&mut dbs.index_conn).await;
+
+        let config = SystemConfig {
+            job_filters: vec![
+                job_filter(
+                    &["tagger"],
+                    serde_json::json!({ "match": { "lt": { "size": 50000000 } } }),
+                ),
+                job_filter(
+                    &["other"],
+                    serde_json::json!({ "match": { "gt": { "size": 100 } } }),
+                ),
+                job_filter(
+                    &["*"],
+                    serde_json::json!({ "match": { "not_contains": { "path": "skip" } } }),
+                ),
+            ],
+            ..SystemConfig::default()
+        };
+        count_filter_exclusions(
+            &mut dbs.index_conn,
+            &config,
+            &model(),
+            false,
+            "default",
+            None,
+        )
+        .await
+        .unwrap()
+    }
+}
@@ -0,0 +1,437 @@
+//! Runs one model on one item inline, for debugging what an extraction job
+//! would send and get back. Goes through the same input handlers, threshold
+//! resolution and inference pool as a job, but writes nothing: no data log,
+//! no setter, no extracted data.
+
+use std::time::Instant;
+
+use base64::{Engine as _, engine::general_purpose};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::ToSchema;
+
+use crate::api_error::ApiError;
+use crate::db::open_index_db_read;
+use crate::db::system_config::SystemConfigStore;
+use crate::inferio_client::{InferenceFile, InferenceInput, PredictOutput};
+use crate::jobs::inference_pool::job_inference_context;
+use crate::pql::builder::filters::OneOrMany;
+use crate::pql::model::{Match, MatchOps, MatchValues, Matches, QueryElement};
+
+use super::{
+    ApiResult, bind_params, build_job_pql, compile_pql_select, deep_merge, input_handlers,
+    load_model_metadata, map_job_input, resolve_job_defaults,
+};
+
+const DEBUG_CACHE_TTL_SECS: i64 = 60;
+/// Input rows run per request. Text models get one row per extracted text
+/// and file models one per file, so an item can have several.
+const MAX_DEBUG_ROWS: usize = 8;
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub(crate) struct ExtractionDebugRequest {
+    /// The item to run the model on
+    pub sha256: String,
+    /// The model, as accepted by POST /api/jobs/data/extraction
+    pub inference_id: String,
+    /// Overrides the threshold a job would use (`job_settings`, then the
+    /// model default)
+    #[serde(default)]
+    pub threshold: Option<f64>,
+    /// Merged over the model's input handler options, e.g.
+    /// `{"max_frames": 8, "slice_settings": {"mode": "grid"}}`
+    #[serde(default)]
+    #[schema(value_type = Option<Object>)]
+    pub input_handler_opts: Option<serde_json::Map<String, Value>>,
+    /// Return input files and binary outputs as base64, each up to
+    /// `max_file_bytes`
+    #[serde(default)]
+    pub include_files: bool,
+    #[serde(default = "default_max_file_bytes")]
+    pub max_file_bytes: usize,
+}
+
+fn default_max_file_bytes() -> usize {
+    2 * 1024 * 1024
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct ExtractionDebugResponse {
+    pub setter_name: String,
+    pub input_handler: String,
+    /// The handler options used, after merging the overrides
+    #[schema(value_type = Object)]
+    pub input_handler_opts: serde_json::Map<String, Value>,
+    /// The threshold sent with each input; null when none applies
+    pub threshold: Option<f64>,
+    /// One per input row the job would process for this item
+    pub rows: Vec<ExtractionDebugRow>,
+    /// More rows matched than were run
+    pub truncated: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct ExtractionDebugRow {
+    pub path: String,
+    /// The extracted text used as input, for text models
+    pub data_id: Option<i64>,
+    pub prepare_ms: u64,
+    pub inputs: Vec<ExtractionDebugInput>,
+    /// Null when the handler produced no inputs (a job would store a
+    /// placeholder) or inference failed
+    pub output: Option<ExtractionDebugOutput>,
+    pub inference_ms: Option<u64>,
+    /// Why preparing or inference failed
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct ExtractionDebugInput {
+    /// The JSON part of the input, threshold included
+    #[schema(value_type = Object)]
+    pub data: Value,
+    pub file: Option<ExtractionDebugFile>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct ExtractionDebugFile {
+    pub size_bytes: u64,
+    /// Set for inputs sent as a path rather than bytes
+    pub path: Option<String>,
+    /// Image dimensions, when the bytes are an image
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// Base64 of the bytes, when `include_files` is set and they fit
+    /// `max_file_bytes`
+    pub base64: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub(crate) enum ExtractionDebugOutput {
+    Json {
+        #[schema(value_type = Vec<Object>)]
+        outputs: Vec<Value>,
+    },
+    Binary {
+        outputs: Vec<ExtractionDebugFile>,
+    },
+}
+
+pub(crate) async fn debug_extraction(
+    index_db: &str,
+    user_data_db: &str,
+    request: ExtractionDebugRequest,
+) -> ApiResult<ExtractionDebugResponse> {
+    let config = SystemConfigStore::from_env().load(index_db)?;
+    let mut model = load_model_metadata(&request.inference_id).await?;
+    if let Some(overrides) = &request.input_handler_opts {
+        let mut opts = Value::Object(std::mem::take(&mut model.input_handler_opts));
+        deep_merge(&mut opts, &Value::Object(overrides.clone()));
+        if let Value::Object(opts) = opts {
+            model.input_handler_opts = opts;
+        }
+    }
+    let defaults = resolve_job_defaults(&config, &model, None, request.threshold);
+
+    // The job's query and columns for the model's target entity, with every
+    // filter replaced by the item: a debug run also works on items the
+    // job filters leave out or the setter already processed.
+    let mut query = build_job_pql(&config, &model, true)?;
+    query.query = Some(QueryElement::Match(Match {
+        match_: Matches::Ops(MatchOps {
+            in_: Some(MatchValues {
+                sha256: Some(OneOrMany::One(request.sha256.clone())),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }),
+    }));
+    let compiled = compile_pql_select(query)?;
+    let mut conn = open_index_db_read(index_db, user_data_db).await?;
+    let rows = bind_params(
+        sqlx::query(sqlx::AssertSqlSafe(compiled.sql.as_str())),
+        &compiled.params,
+    )?
+    .fetch_all(&mut conn)
+    .await
+    .map_err(|err| {
+        tracing::error!(error = %err, "failed to fetch debug extraction rows");
+        ApiError::internal("Failed to execute extraction query")
+    })?;
+    drop(conn);
+    if rows.is_empty() {
+        return Err(ApiError::not_found(
+            "Item not found, or it has no input for this model",
+        ));
+    }
+    if let Some(item_type) = rows
+        .first()
+        .and_then(|row| sqlx::Row::try_get::<String, _>(row, "type").ok())
+        && !model.input_mime_types.is_empty()
+        && !model
+            .input_mime_types
+            .iter()
+            .any(|prefix| item_type.starts_with(prefix.as_str()))
+    {
+        return Err(ApiError::bad_request(format!(
+            "{} does not accept {item_type} items",
+            request.inference_id
+        )));
+    }
+
+    let context = job_inference_context();
+    if context.pool.is_empty().await {
+        return Err(ApiError::upstream_unavailable(
+            "No inference endpoints enabled for batch jobs",
+        ));
+    }
+    // Its own cache key per request, so a debug run neither evicts nor
+    // unloads the model a running job or a concurrent debug run holds.
+    let cache_key = format!("debug[{}]", uuid::Uuid::new_v4());
+    context
+        .pool
+        .load_model_all(
+            &model.setter_name,
+            &cache_key,
+            1,
+            DEBUG_CACHE_TTL_SECS,
+            Some(false),
+        )
+        .await
+        .map_err(|err| ApiError::upstream_unavailable(format!("Failed to load model: {err}")))?;
+    let loaded = DebugModelGuard {
+        setter_name: Some(model.setter_name.clone()),
+        cache_key,
+    };
+
+    let truncated = rows.len() > MAX_DEBUG_ROWS;
+    let mut debug_rows = Vec::new();
+    for row in rows.iter().take(MAX_DEBUG_ROWS) {
+        let Some(item) = map_job_input(index_db, user_data_db, row).await? else {
+            continue;
+        };
+        let path = item.path.clone();
+        let data_id = item.data_id;
+        let started = Instant::now();
+        let prepared = input_handlers::prepare_item(index_db, &model, item).await;
+        let prepare_ms = started.elapsed().as_millis() as u64;
+        let prepared = match prepared {
+            Ok(prepared) => prepared,
+            Err(err) => {
+                debug_rows.push(ExtractionDebugRow {
+                    path,
+                    data_id,
+                    prepare_ms,
+                    inputs: Vec::new(),
+                    output: None,
+                    inference_ms: None,
+                    error: Some(err.detail().to_string()),
+                });
+                continue;
+            }
+        };
+        let inputs = input_handlers::apply_threshold(prepared.inputs, defaults.threshold);
+        let described = describe_inputs(&inputs, &request).await;
+        if inputs.is_empty() {
+            debug_rows.push(ExtractionDebugRow {
+                path,
+                data_id,
+                prepare_ms,
+                inputs: described,
+                output: None,
+                inference_ms: None,
+                error: None,
+            });
+            continue;
+        }
+        let started = Instant::now();
+        let result = context
+            .pool
+            .predict(
+                &model.setter_name,
+                &loaded.cache_key,
+                1,
+                DEBUG_CACHE_TTL_SECS,
+                Some(u32::try_from(defaults.batch_size).unwrap_or(u32::MAX)),
+                Some(false),
+                &inputs,
+            )
+            .await;
+        let inference_ms = Some(started.elapsed().as_millis() as u64);
+        let (output, error) = match result {
+            Ok(output) => (Some(describe_output(output, &request)), None),
+            Err(err) => (None, Some(format!("Inference failed: {err}"))),
+        };
+        debug_rows.push(ExtractionDebugRow {
+            path,
+            data_id,
+            prepare_ms,
+            inputs: described,
+            output,
+            inference_ms,
+            error,
+        });
+    }
+
+    loaded.unload().await;
+
+    Ok(ExtractionDebugResponse {
+        setter_name: model.setter_name,
+        input_handler: model.input_handler,
+        input_handler_opts: model.input_handler_opts,
+        threshold: defaults.threshold,
+        rows: debug_rows,
+        truncated,
+    })
+}
+
+/// Unloads the debug run's model from every endpoint, also when the request
+/// fails part-way or is dropped: `Drop` spawns the unload, like
+/// `JobPauseGuard` does for the scan resume.
+struct DebugModelGuard {
+    setter_name: Option<String>,
+    cache_key: String,
+}
+
+impl DebugModelGuard {
+    /// Unloads inline; use on the normal completion path.
+    async fn unload(mut self) {
+        if let Some(setter_name) = self.setter_name.take() {
+            unload_debug_model(&setter_name, &self.cache_key).await;
+        }
+    }
+}
+
+impl Drop for DebugModelGuard {
+    fn drop(&mut self) {
+        if let Some(setter_name) = self.setter_name.take() {
+            let cache_key = std::mem::take(&mut self.cache_key);
+            tokio::spawn(async move {
+                unload_debug_model(&setter_name, &cache_key).await;
+            });
+        }
+    }
+}
+
+async fn unload_debug_model(setter_name: &str, cache_key: &str) {
+    let pool = &job_inference_context().pool;
+    if let Err(err) = pool.unload_model_all(setter_name, cache_key).await {
+        tracing::warn!(setter_name, cache_key, error = %err, "failed to unload debug model");
+    }
+}
+
+async fn describe_inputs(
+    inputs: &[InferenceInput],
+    request: &ExtractionDebugRequest,
+) -> Vec<ExtractionDebugInput> {
+    let mut described = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        let file = match &input.file {
+            Some(InferenceFile::Bytes(bytes)) => Some(describe_bytes(bytes, request)),
+            Some(InferenceFile::Path(path)) => Some(ExtractionDebugFile {
+                size_bytes: tokio::fs::metadata(path)
+                    .await
+                    .map(|meta| meta.len())
+                    .unwrap_or(0),
+                path: Some(path.to_string_lossy().to_string()),
+                width: None,
+                height: None,
+                base64: None,
+            }),
+            None => None,
+        };
+        described.push(ExtractionDebugInput {
+            data: input.data.clone(),
+            file,
+        });
+    }
+    described
+}
+
+fn describe_output(
+    output: PredictOutput,
+    request: &ExtractionDebugRequest,
+) -> ExtractionDebugOutput {
+    match output {
+        PredictOutput::Json(outputs) => ExtractionDebugOutput::Json { outputs },
+        PredictOutput::Binary(outputs) => ExtractionDebugOutput::Binary {
+            outputs: outputs
+                .iter()
+                .map(|bytes| describe_bytes(bytes, request))
+                .collect(),
+        },
+    }
+}
+
+fn describe_bytes(bytes: &[u8], request: &ExtractionDebugRequest) -> ExtractionDebugFile {
+    let dimensions = image::ImageReader::new(std::io::Cursor::new(bytes))
+        .with_guessed_format()
+        .ok()
+        .and_then(|reader| reader.into_dimensions().ok());
+    ExtractionDebugFile {
+        size_bytes: bytes.len() as u64,
+        path: None,
+        width: dimensions.map(|(width, _)| width),
+        height: dimensions.map(|(_, height)| height),
+        base64: (request.include_files && bytes.len() <= request.max_file_bytes)
+            .then(|| general_purpose::STANDARD.encode(bytes)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(include_files: bool, max_file_bytes: usize) -> ExtractionDebugRequest {
+        ExtractionDebugRequest {
+            sha256: "sha".to_string(),
+            inference_id: "group/model".to_string(),
+            threshold: None,
+            input_handler_opts: None,
+            include_files,
+            max_file_bytes,
+        }
+    }
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        image::DynamicImage::new_rgb8(width, height)
+            .write_to(
+                &mut std::io::Cursor::new(&mut bytes),
+                image::ImageFormat::Png,
+            )
+            .unwrap();
+        bytes
+    }
+
+    // Ensures input files report their size and image dimensions, and are
+    // only inlined when asked for and within the size cap.
+    #[tokio::test]
+    async fn inputs_report_dimensions_and_inline_within_the_cap() {
+        let image = png(3, 2);
+        let inputs = vec![
+            InferenceInput::new(
+                serde_json::json!({"threshold": 0.3}),
+                Some(InferenceFile::Bytes(image.clone())),
+            ),
+            InferenceInput::new(serde_json::json!({"text": "hi"}), None),
+        ];
+
+        let described = describe_inputs(&inputs, &request(true, image.len())).await;
+        let file = described[0].file.as_ref().unwrap();
+        assert_eq!(file.size_bytes, image.len() as u64);
+        assert_eq!((file.width, file.height), (Some(3), Some(2)));
+        assert_eq!(
+            file.base64.as_deref(),
+            Some(general_purpose::STANDARD.encode(&image).as_str())
+        );
+        assert_eq!(described[0].data, serde_json::json!({"threshold": 0.3}));
+        assert!(described[1].file.is_none());
+
+        let described = describe_inputs(&inputs, &request(true, image.len() - 1)).await;
+        assert!(described[0].file.as_ref().unwrap().base64.is_none());
+        let described = describe_inputs(&inputs, &request(false, usize::MAX)).await;
+        assert!(described[0].file.as_ref().unwrap().base64.is_none());
+    }
+}
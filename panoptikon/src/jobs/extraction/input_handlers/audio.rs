@@ -1,8 +1,11 @@
+use std::path::Path;
+
 use serde_json::{Value, json};
 
 use crate::api_error::ApiError;
 use crate::inferio_client::{InferenceFile, InferenceInput};
 use crate::jobs::extraction::{ApiResult, JobInputData, ModelMetadata};
+use crate::media_tools::{self, MediaTool, MediaToolError};
 
 pub(super) async fn build_audio_tracks_inputs(
     item: &JobInputData,
@@ -18,7 +21,7 @@ pub(super) async fn build_audio_tracks_inputs(
         .unwrap_or(16000) as u32;
     let max_tracks = opts.get("max_tracks").and_then(Value::as_i64).unwrap_or(4) as usize;
 
-    let audio = load_audio_single(&item.path, sample_rate)?;
+    let audio = load_audio_single(&item.disk_path(), sample_rate)?;
     let mut outputs = Vec::new();
     for track in audio.into_iter().take(max_tracks) {
         let bytes = serialize_npy_f32(&track);
@@ -44,7 +47,7 @@ pub(super) async fn build_audio_files_inputs(
         .unwrap_or(48000) as u32;
     let max_tracks = opts.get("max_tracks").and_then(Value::as_i64).unwrap_or(4) as usize;
 
-    let audio = load_audio_single(&item.path, sample_rate)?;
+    let audio = load_audio_single(&item.disk_path(), sample_rate)?;
     let mut outputs = Vec::new();
     for track in audio.into_iter().take(max_tracks) {
         let wav_bytes = audio_to_wav_bytes(&track, sample_rate);
@@ -75,59 +78,56 @@ fn serialize_npy_f32(values: &[f32]) -> Vec<u8> {
     out
 }
 
-fn load_audio_single(path: &str, sample_rate: u32) -> ApiResult<Vec<Vec<f32>>> {
-    let output = std::process::Command::new(crate::media_tools::ffmpeg())
-        .arg("-nostdin")
-        .arg("-threads")
-        .arg("0")
-        .arg("-i")
-        .arg(path)
-        .arg("-f")
-        .arg("s16le")
-        .arg("-ac")
-        .arg("1")
-        .arg("-acodec")
-        .arg("pcm_s16le")
-        .arg("-ar")
-        .arg(sample_rate.to_string())
-        .arg("-")
-        .output();
+fn load_audio_single(path: &Path, sample_rate: u32) -> ApiResult<Vec<Vec<f32>>> {
+    let output = media_tools::run(
+        MediaTool::Ffmpeg,
+        MediaTool::Ffmpeg
+            .command()
+            .arg("-nostdin")
+            .arg("-threads")
+            .arg("0")
+            .arg("-i")
+            .arg(path)
+            .arg("-f")
+            .arg("s16le")
+            .arg("-ac")
+            .arg("1")
+            .arg("-acodec")
+            .arg("pcm_s16le")
+            .arg("-ar")
+            .arg(sample_rate.to_string())
+            .arg("-"),
+    );
 
     match output {
-        Ok(output) => {
-            if output.status.success() {
-                let audio = s16le_to_f32(&output.stdout);
-                return Ok(vec![audio]);
-            }
+        Ok(stdout) => Ok(vec![s16le_to_f32(&stdout)]),
+        Err(err @ MediaToolError::Failed { .. }) => {
             if !has_audio_stream(path)? {
                 return Ok(Vec::new());
             }
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            Err(ApiError::internal(format!("ffmpeg failed: {stderr}")))
+            Err(err.into())
         }
-        Err(err) => Err(ApiError::internal(format!("ffmpeg failed: {err}"))),
+        Err(err) => Err(err.into()),
     }
 }
 
-fn has_audio_stream(path: &str) -> ApiResult<bool> {
-    let output = std::process::Command::new(crate::media_tools::ffprobe())
-        .arg("-v")
-        .arg("error")
-        .arg("-show_entries")
-        .arg("stream=codec_type")
-        .arg("-of")
-        .arg("json")
-        .arg(path)
-        .output()
-        .map_err(|err| ApiError::internal(format!("ffprobe failed: {err}")))?;
+fn has_audio_stream(path: &Path) -> ApiResult<bool> {
     // ffprobe failing is not the same as "no audio stream": a corrupt file or
     // a transient read error (e.g. an SMB hiccup) must fail the item so it is
     // retried, not permanently marked processed with a placeholder.
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(ApiError::internal(format!("ffprobe failed: {stderr}")));
-    }
-    let value: Value = serde_json::from_slice(&output.stdout)
+    let stdout = media_tools::run(
+        MediaTool::Ffprobe,
+        MediaTool::Ffprobe
+            .command()
+            .arg("-v")
+            .arg("error")
+            .arg("-show_entries")
+            .arg("stream=codec_type")
+            .arg("-of")
+            .arg("json")
+            .arg(path),
+    )?;
+    let value: Value = serde_json::from_slice(&stdout)
         .map_err(|err| ApiError::internal(format!("ffprobe output unparseable: {err}")))?;
     let streams = value
         .get("streams")
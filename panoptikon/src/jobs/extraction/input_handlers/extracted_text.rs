@@ -1,12 +1,194 @@
-use serde_json::json;
+use serde_json::{Map, Value, json};
 
 use crate::api_error::ApiError;
 use crate::inferio_client::InferenceInput;
-use crate::jobs::extraction::{ApiResult, JobInputData};
+use crate::jobs::extraction::{ApiResult, JobInputData, ModelMetadata};
 
-pub(super) fn build_extracted_text_inputs(item: &JobInputData) -> ApiResult<Vec<InferenceInput>> {
-    let Some(text) = item.text.clone() else {
+pub(super) fn build_extracted_text_inputs(
+    item: &JobInputData,
+    model: &ModelMetadata,
+) -> ApiResult<Vec<InferenceInput>> {
+    let Some(text) = item.text.as_deref() else {
         return Err(ApiError::bad_request("Text input missing text field"));
     };
-    Ok(vec![InferenceInput::new(json!({"text": text}), None)])
+    let options = ChunkOptions::from_opts(&model.input_handler_opts);
+    let inputs = chunk_text(text, options.as_ref())
+        .into_iter()
+        .map(|chunk| InferenceInput::new(json!({"text": chunk.text}), None))
+        .collect();
+    Ok(inputs)
+}
+
+/// Text chunking for the `extracted_text` handler, from `input_spec.opts`:
+/// `max_chunk_chars` (unset or 0 = one input per text row, as before),
+/// `chunk_overlap` (characters repeated at the start of the next chunk) and
+/// `split_on_sentences` (default true: end chunks at the last sentence
+/// boundary that fits, falling back to a hard cut).
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ChunkOptions {
+    pub max_chars: usize,
+    pub overlap: usize,
+    pub split_on_sentences: bool,
+}
+
+impl ChunkOptions {
+    pub(crate) fn from_opts(opts: &Map<String, Value>) -> Option<Self> {
+        let max_chars = opts
+            .get("max_chunk_chars")
+            .and_then(Value::as_u64)
+            .filter(|&max_chars| max_chars > 0)? as usize;
+        // The overlap must leave room to advance, or chunking never ends.
+        let overlap = opts
+            .get("chunk_overlap")
+            .and_then(Value::as_u64)
+            .unwrap_or(0)
+            .min(max_chars as u64 - 1) as usize;
+        let split_on_sentences = opts
+            .get("split_on_sentences")
+            .and_then(Value::as_bool)
+            .unwrap_or(true);
+        Some(Self {
+            max_chars,
+            overlap,
+            split_on_sentences,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct TextChunk {
+    /// Character (not byte) offset of the chunk in the source text; stored as
+    /// the embedding's `idx`.
+    pub offset: usize,
+    pub text: String,
+}
+
+/// Splits `text` into the chunks that get embedded. Without options, or
+/// when the text fits, this is the whole text at offset 0. Deterministic, so
+/// the output handler can recompute the offsets of the inputs it was given.
+pub(crate) fn chunk_text(text: &str, options: Option<&ChunkOptions>) -> Vec<TextChunk> {
+    let chars = text.chars().collect::<Vec<_>>();
+    let Some(options) = options.filter(|options| chars.len() > options.max_chars) else {
+        return vec![TextChunk {
+            offset: 0,
+            text: text.to_string(),
+        }];
+    };
+
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    loop {
+        if chars.len() - start <= options.max_chars {
+            chunks.push(TextChunk {
+                offset: start,
+                text: chars[start..].iter().collect(),
+            });
+            break;
+        }
+        let hard_end = start + options.max_chars;
+        // A sentence boundary only counts if the next chunk still starts
+        // past this one's start once the overlap is taken back.
+        let end = options
+            .split_on_sentences
+            .then(|| {
+                (start + options.overlap + 1..=hard_end)
+                    .rev()
+                    .find(|&end| is_sentence_end(&chars, end))
+            })
+            .flatten()
+            .unwrap_or(hard_end);
+        chunks.push(TextChunk {
+            offset: start,
+            text: chars[start..end].iter().collect(),
+        });
+        start = end - options.overlap;
+        // Without overlap, don't open a chunk with the whitespace that
+        // separated it from the previous sentence.
+        if options.overlap == 0 {
+            while start < chars.len() && chars[start].is_whitespace() {
+                start += 1;
+            }
+            if start == chars.len() {
+                break;
+            }
+        }
+    }
+    chunks
+}
+
+/// Whether a sentence ends right before `chars[end]`: terminal punctuation
+/// followed by whitespace (or the end of the text), or a line break.
+fn is_sentence_end(chars: &[char], end: usize) -> bool {
+    let Some(&last) = chars.get(end.wrapping_sub(1)) else {
+        return false;
+    };
+    if last == '\n' {
+        return true;
+    }
+    matches!(last, '.' | '!' | '?' | '。' | '！' | '？')
+        && chars.get(end).is_none_or(|next| next.is_whitespace())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(max_chars: usize, overlap: usize, split_on_sentences: bool) -> ChunkOptions {
+        ChunkOptions {
+            max_chars,
+            overlap,
+            split_on_sentences,
+        }
+    }
+
+    fn offsets_and_texts(chunks: &[TextChunk]) -> Vec<(usize, &str)> {
+        chunks
+            .iter()
+            .map(|chunk| (chunk.offset, chunk.text.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn chunking_is_off_without_max_chunk_chars() {
+        assert_eq!(ChunkOptions::from_opts(&Map::new()), None);
+        let chunks = chunk_text("anything at all", None);
+        assert_eq!(offsets_and_texts(&chunks), vec![(0, "anything at all")]);
+    }
+
+    #[test]
+    fn chunks_end_at_sentence_boundaries() {
+        let text = "One two. Three four five. Six.";
+        let chunks = chunk_text(text, Some(&options(20, 0, true)));
+        assert_eq!(
+            offsets_and_texts(&chunks),
+            vec![(0, "One two."), (9, "Three four five."), (26, "Six.")]
+        );
+    }
+
+    #[test]
+    fn chunks_fall_back_to_hard_cuts_with_overlap() {
+        let chunks = chunk_text("abcdefghij", Some(&options(4, 1, true)));
+        assert_eq!(
+            offsets_and_texts(&chunks),
+            vec![(0, "abcd"), (3, "defg"), (6, "ghij")]
+        );
+    }
+
+    #[test]
+    fn chunk_offsets_count_characters() {
+        let chunks = chunk_text("ééé. ààà.", Some(&options(5, 0, true)));
+        assert_eq!(offsets_and_texts(&chunks), vec![(0, "ééé."), (5, "ààà.")]);
+    }
+
+    #[test]
+    fn overlap_is_clamped_below_max_chars() {
+        let opts = json!({"max_chunk_chars": 3, "chunk_overlap": 10});
+        let options = ChunkOptions::from_opts(opts.as_object().unwrap()).unwrap();
+        assert_eq!(options.overlap, 2);
+        let chunks = chunk_text("abcde", Some(&options));
+        assert_eq!(
+            offsets_and_texts(&chunks),
+            vec![(0, "abc"), (1, "bcd"), (2, "cde")]
+        );
+    }
 }
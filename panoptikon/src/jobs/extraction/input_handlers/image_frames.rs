@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::path::Path;
 
 use image::AnimationDecoder;
 use image::codecs::gif::GifDecoder;
@@ -8,10 +8,12 @@ use serde_json::{Value, json};
 use crate::api_error::ApiError;
 use crate::db::index_writer::{IndexDbWriterMessage, call_index_db_writer};
 use crate::db::open_index_db_read_no_user_data;
-use crate::db::storage::{StoredImage, get_frames_bytes};
+use crate::db::storage::{StoredImage, get_frames_bytes, visuals_dir};
 use crate::inferio_client::{InferenceFile, InferenceInput};
 use crate::jobs::extraction::{ApiResult, JobInputData, ModelMetadata};
-use crate::jobs::files::{FRAME_PROCESS_VERSION, stderr_tail};
+use crate::jobs::files::{FRAME_PROCESS_VERSION, RenderError};
+use crate::media_tools::{self, MediaTool};
+use crate::video_frames::{self, FrameExtractionError};
 
 /// A frame ready to be sent to inference. PDF pages and HTML screenshots
 /// carry their own pixel dimensions (each page differs from the item's stored
@@ -103,11 +105,12 @@ pub(super) async fn load_base_frames(
             return Ok(Vec::new());
         }
     }
+    let path = item.disk_path();
     if item.item_type.starts_with("image/gif") {
-        return gif_to_frames(&item.path);
+        return gif_to_frames(&path);
     }
     if item.item_type.starts_with("image") {
-        let buffer = tokio::fs::read(&item.path).await.map_err(|err| {
+        let buffer = tokio::fs::read(&path).await.map_err(|err| {
             tracing::error!(error = %err, path = %item.path, "failed to read image");
             ApiError::internal("Failed to read image")
         })?;
@@ -116,19 +119,16 @@ pub(super) async fn load_base_frames(
     }
     if item.item_type.starts_with("video") {
         let mut conn = open_index_db_read_no_user_data(index_db).await?;
-        let cached = get_frames_bytes(&mut conn, &item.sha256)
+        let cached = get_frames_bytes(&mut conn, &visuals_dir(index_db), &item.sha256)
             .await
             .unwrap_or_default();
         if !cached.is_empty() {
             return Ok(cached.into_iter().map(BaseFrame::sized_by_item).collect());
         }
         if item.duration.unwrap_or(0.0) > 0.0 && item.video_tracks.unwrap_or(0) > 0 {
-            let extracted = tokio::task::spawn_blocking({
-                let path = item.path.clone();
-                move || extract_video_frames(&path, 4)
-            })
-            .await
-            .map_err(|_| ApiError::internal("Failed to extract frames"))??;
+            let extracted = tokio::task::spawn_blocking(move || extract_video_frames(&path, 4))
+                .await
+                .map_err(|_| ApiError::internal("Failed to extract frames"))??;
             let frames = extracted
                 .iter()
                 .map(encode_jpeg)
@@ -156,10 +156,10 @@ pub(super) async fn load_base_frames(
         return Ok(Vec::new());
     }
     if item.item_type.starts_with("application/pdf") {
-        return render_pdf_frames(&item.path).await;
+        return render_pdf_frames(&path).await;
     }
     if item.item_type.starts_with("text/html") {
-        return render_html_frames(&item.path).await;
+        return render_html_frames(&path).await;
     }
     Ok(Vec::new())
 }
@@ -417,7 +417,7 @@ fn encode_jpeg(image: &DynamicImage) -> ApiResult<Vec<u8>> {
     Ok(buffer)
 }
 
-fn gif_to_frames(path: &str) -> ApiResult<Vec<BaseFrame>> {
+fn gif_to_frames(path: &Path) -> ApiResult<Vec<BaseFrame>> {
     let buffer = std::fs::read(path).map_err(|err| {
         tracing::error!(error = %err, "failed to open gif");
         ApiError::internal("Failed to open gif")
@@ -427,7 +427,7 @@ fn gif_to_frames(path: &str) -> ApiResult<Vec<BaseFrame>> {
     // single still frame instead of failing the item.
     if !matches!(image::guess_format(&buffer), Ok(image::ImageFormat::Gif)) {
         let image = crate::jobs::files::decode_image_bytes(&buffer).map_err(|err| {
-            tracing::error!(error = %err, path, "failed to decode mis-named gif");
+            tracing::error!(error = %err, path = %path.display(), "failed to decode mis-named gif");
             ApiError::internal("Failed to decode gif")
         })?;
         return Ok(vec![BaseFrame::sized_by_item(encode_jpeg(&image)?)]);
@@ -459,7 +459,7 @@ fn gif_to_frames(path: &str) -> ApiResult<Vec<BaseFrame>> {
     Ok(output)
 }
 
-fn extract_video_frames(path: &str, num_frames: usize) -> ApiResult<Vec<DynamicImage>> {
+fn extract_video_frames(path: &Path, num_frames: usize) -> ApiResult<Vec<DynamicImage>> {
     let duration = probe_duration(path)?;
     // The caller already checked the DB-recorded duration; a zero here means
     // the file on disk disagrees (truncated or corrupt). Fail the item so it
@@ -467,127 +467,73 @@ fn extract_video_frames(path: &str, num_frames: usize) -> ApiResult<Vec<DynamicI
     if duration <= 0.0 {
         return Err(ApiError::internal("Video has no probeable duration"));
     }
-    let interval = duration / num_frames as f64;
-    let temp_dir = temp_dir_path();
-    std::fs::create_dir_all(&temp_dir).map_err(|err| {
-        tracing::error!(error = %err, "failed to create temp dir");
-        ApiError::internal("Failed to extract frames")
-    })?;
-    let result = extract_video_frames_into(path, num_frames, interval, &temp_dir);
-    if let Err(err) = std::fs::remove_dir_all(&temp_dir) {
-        tracing::debug!(error = %err, path = %temp_dir.display(), "failed to remove temp frame dir");
-    }
-    result
-}
-
-fn extract_video_frames_into(
-    path: &str,
-    num_frames: usize,
-    interval: f64,
-    temp_dir: &std::path::Path,
-) -> ApiResult<Vec<DynamicImage>> {
-    let output_pattern = temp_dir.join("frame_%04d.png");
-    // stdout is silenced, but stderr is captured so a failure can say why
-    // (corrupt file, missing codec, disk full); it is only surfaced on a
-    // non-zero exit.
-    let output = std::process::Command::new(crate::media_tools::ffmpeg())
-        .arg("-i")
-        .arg(path)
-        .arg("-vf")
-        .arg(format!("fps=1/{interval}"))
-        .arg("-vsync")
-        .arg("vfr")
-        .arg(&output_pattern)
-        .stdout(std::process::Stdio::null())
-        .output()
-        .map_err(|err| {
-            tracing::error!(error = %err, "ffmpeg failed");
-            ApiError::internal("Failed to extract frames")
-        })?;
-    if !output.status.success() {
-        tracing::error!(
-            path,
-            stderr = %stderr_tail(&output.stderr),
-            "ffmpeg failed to extract frames"
-        );
-        return Err(ApiError::internal("ffmpeg failed to extract frames"));
-    }
-    let mut paths = std::fs::read_dir(temp_dir)
-        .map_err(|err| ApiError::internal(format!("Failed to read frames: {err}")))?
-        .filter_map(|entry| entry.ok())
-        .map(|entry| entry.path())
-        .filter(|path| path.extension().and_then(|ext| ext.to_str()) == Some("png"))
-        .collect::<Vec<_>>();
-    paths.sort();
-    let mut frames = Vec::new();
-    for frame_path in paths.into_iter().take(num_frames) {
-        // These PNGs were just written by our own ffmpeg run; one being
-        // unreadable means something is broken (disk full, races), so fail
-        // the item rather than silently tagging it from fewer frames.
-        let image = crate::jobs::files::open_image(&frame_path)
-            .map_err(|err| ApiError::internal(format!("Failed to read extracted frame: {err}")))?;
-        frames.push(image);
-    }
-    Ok(frames)
+    video_frames::extract_video_frames(path, num_frames, duration).map_err(|err| {
+        tracing::error!(path = %path.display(), error = %err, "failed to extract frames");
+        match err {
+            FrameExtractionError::Tool(err) if err.is_timeout() => {
+                ApiError::media_timeout("ffmpeg timed out extracting frames")
+            }
+            FrameExtractionError::Undecodable(_) => {
+                ApiError::media_undecodable("No frame of the video could be decoded")
+            }
+            _ => ApiError::internal("ffmpeg failed to extract frames"),
+        }
+    })
 }
 
-fn probe_duration(path: &str) -> ApiResult<f64> {
-    let output = std::process::Command::new(crate::media_tools::ffprobe())
-        .arg("-v")
-        .arg("error")
-        .arg("-show_entries")
-        .arg("format=duration")
-        .arg("-of")
-        .arg("default=noprint_wrappers=1:nokey=1")
-        .arg(path)
-        .output()
-        .map_err(|err| {
-            tracing::error!(error = %err, "ffprobe failed");
+fn probe_duration(path: &Path) -> ApiResult<f64> {
+    let stdout = media_tools::run(
+        MediaTool::Ffprobe,
+        MediaTool::Ffprobe
+            .command()
+            .arg("-v")
+            .arg("error")
+            .arg("-show_entries")
+            .arg("format=duration")
+            .arg("-of")
+            .arg("default=noprint_wrappers=1:nokey=1")
+            .arg(path),
+    )
+    .map_err(|err| {
+        tracing::error!(path = %path.display(), error = %err, "ffprobe failed");
+        if err.is_timeout() {
+            ApiError::media_timeout("ffprobe timed out probing video")
+        } else {
             ApiError::internal("Failed to probe video")
-        })?;
-    if !output.status.success() {
-        return Err(ApiError::internal("ffprobe failed"));
-    }
-    let stdout = String::from_utf8_lossy(&output.stdout);
+        }
+    })?;
+    let stdout = String::from_utf8_lossy(&stdout);
     stdout.trim().parse::<f64>().map_err(|err| {
         tracing::error!(error = %err, "failed to parse duration");
         ApiError::internal("Failed to probe video")
     })
 }
 
-fn temp_dir_path() -> PathBuf {
-    // PID plus a process-local counter rules out collisions between
-    // concurrent extractions and between gateway instances; the timestamp
-    // alone could repeat across calls or processes, and a collision means
-    // one call's cleanup deletes the other's frames mid-extraction.
-    static COUNTER: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
-    let base = std::env::temp_dir();
-    let nanos = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .map(|d| d.as_nanos())
-        .unwrap_or(0);
-    let unique = COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-    base.join(format!(
-        "panoptikon-extract-{}-{nanos:x}-{unique}",
-        std::process::id()
-    ))
+/// Maps a failed PDF/HTML render to the item's error. A timeout counts in
+/// the run's `timeouts`; a missing renderer was already reported once at
+/// job start (see `unavailable_renderer_types`), so it is not logged again.
+fn render_error(err: RenderError, kind: &str, path: &Path) -> ApiError {
+    match err {
+        RenderError::TimedOut => ApiError::media_timeout(format!("Rendering {kind} timed out")),
+        RenderError::Unavailable => ApiError::internal(format!("No {kind} renderer available")),
+        RenderError::Failed(detail) => {
+            tracing::error!(error = %detail, path = %path.display(), "failed to render {kind}");
+            ApiError::internal(format!("Failed to render {kind}"))
+        }
+    }
 }
 
-/// Renders every PDF page natively via the shared pdfium binding (same
-/// library the scan pipeline uses for thumbnails). Any failure — including
-/// pdfium not being installed — is an error so the item is recorded as
-/// failed and retried on the next run, never silently marked processed.
-async fn render_pdf_frames(path: &str) -> ApiResult<Vec<BaseFrame>> {
-    let owned = path.to_string();
-    let pages = tokio::task::spawn_blocking(move || {
-        crate::jobs::files::render_pdf_pages(std::path::Path::new(&owned))
-    })
-    .await
-    .map_err(|_| ApiError::internal("PDF render task failed"))?
-    .map_err(|err| {
-        tracing::error!(error = %err, path, "failed to render PDF");
-        ApiError::internal("Failed to render PDF")
-    })?;
+/// Renders the PDF's pages (up to `[jobs] render_max_pages`) natively via
+/// the shared pdfium binding (same library the scan pipeline uses for
+/// thumbnails). Any failure — including pdfium not being installed — is an
+/// error so the item is recorded as failed and retried on the next run,
+/// never silently marked processed.
+async fn render_pdf_frames(path: &Path) -> ApiResult<Vec<BaseFrame>> {
+    let owned = path.to_path_buf();
+    let pages = tokio::task::spawn_blocking(move || crate::jobs::files::render_pdf_pages(&owned))
+        .await
+        .map_err(|_| ApiError::internal("PDF render task failed"))?
+        .map_err(|err| render_error(err, "PDF", path))?;
     let mut frames = Vec::with_capacity(pages.len());
     for page in pages {
         frames.push(BaseFrame {
@@ -603,20 +549,94 @@ async fn render_pdf_frames(path: &str) -> ApiResult<Vec<BaseFrame>> {
 /// by the scan pipeline (replacing the Python weasyprint HTML->PDF chain).
 /// Failure — including no browser being installed — is an error so the item
 /// is recorded as failed and retried, never silently marked processed.
-async fn render_html_frames(path: &str) -> ApiResult<Vec<BaseFrame>> {
-    let owned = path.to_string();
-    let shot = tokio::task::spawn_blocking(move || {
-        crate::jobs::files::render_html_screenshot(std::path::Path::new(&owned))
-    })
-    .await
-    .map_err(|_| ApiError::internal("HTML render task failed"))?
-    .ok_or_else(|| {
-        tracing::error!(path, "failed to render HTML page");
-        ApiError::internal("Failed to render HTML page")
-    })?;
+async fn render_html_frames(path: &Path) -> ApiResult<Vec<BaseFrame>> {
+    let owned = path.to_path_buf();
+    let shot =
+        tokio::task::spawn_blocking(move || crate::jobs::files::render_html_screenshot(&owned))
+            .await
+            .map_err(|_| ApiError::internal("HTML render task failed"))?
+            .map_err(|err| render_error(err, "HTML page", path))?;
     Ok(vec![BaseFrame {
         width: Some(shot.width() as i64),
         height: Some(shot.height() as i64),
         bytes: encode_jpeg(&shot)?,
     }])
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::migrations::migrate_databases_on_disk;
+    use crate::db::system_config::FrameRetention;
+    use crate::jobs::frame_retention::drop_processed_frames;
+    use crate::test_utils::test_data_dir;
+
+    /// A video's stored frames are used as they are; once frame retention
+    /// has dropped them, loading goes back to the video file to extract
+    /// them again instead of treating the item as having no frames. The
+    /// file is absent here, so that fails at its first step, probing it.
+    #[tokio::test]
+    async fn dropped_frames_are_extracted_from_the_video_again() {
+        let _test_env = test_data_dir();
+        let index_db = "image_frames_retention_index".to_string();
+        migrate_databases_on_disk(Some(&index_db), None)
+            .await
+            .unwrap();
+        let mut conn = crate::db::open_index_db_write_no_user_data(&index_db)
+            .await
+            .unwrap();
+        sqlx::query(
+            r#"
+INSERT INTO items (id, sha256, md5, type, time_added) VALUES
+    (1, 'aaaa11', 'md51', 'video/mp4', '2024-01-01T00:00:00');
+INSERT INTO setters (id, name) VALUES (1, 'clip');
+INSERT INTO data_jobs (id, completed) VALUES (1, 1);
+INSERT INTO item_data (item_id, job_id, setter_id, data_type, idx, is_origin, is_placeholder)
+VALUES (1, 1, 1, 'clip', 0, 1, 0);
+INSERT INTO storage.frames (item_sha256, idx, item_mime_type, width, height, version, frame)
+VALUES
+    ('aaaa11', 0, 'video/mp4', 10, 10, 1, x'0a'),
+    ('aaaa11', 1, 'video/mp4', 10, 10, 1, x'0b');
+            "#,
+        )
+        .execute(&mut conn)
+        .await
+        .unwrap();
+        drop(conn);
+        let missing_video = std::env::temp_dir().join("panoptikon-missing-video.mp4");
+        let item = JobInputData {
+            file_id: 1,
+            item_id: 1,
+            path: missing_video.to_string_lossy().into_owned(),
+            path_bytes: None,
+            sha256: "aaaa11".to_string(),
+            md5: "md51".to_string(),
+            last_modified: "2024-01-01T00:00:00".to_string(),
+            item_type: "video/mp4".to_string(),
+            duration: Some(2.0),
+            audio_tracks: Some(0),
+            video_tracks: Some(1),
+            subtitle_tracks: Some(0),
+            width: Some(10),
+            height: Some(10),
+            data_id: None,
+            text: None,
+        };
+
+        let cached = load_base_frames(&index_db, &item).await.unwrap();
+        let bytes: Vec<Vec<u8>> = cached.into_iter().map(|frame| frame.bytes).collect();
+        assert_eq!(bytes, vec![vec![0x0a], vec![0x0b]]);
+
+        let retention = FrameRetention {
+            keep_frames_until_processed_by: vec!["clip".to_string()],
+        };
+        assert_eq!(
+            drop_processed_frames(&index_db, &retention).await.unwrap(),
+            2
+        );
+        let Err(err) = load_base_frames(&index_db, &item).await else {
+            panic!("frames were not extracted again");
+        };
+        assert_eq!(err.detail(), "Failed to probe video");
+    }
+}
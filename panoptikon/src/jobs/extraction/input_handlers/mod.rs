@@ -5,7 +5,7 @@ use crate::inferio_client::InferenceInput;
 use crate::jobs::extraction::{ApiResult, JobInputData, ModelMetadata, PreparedItem};
 
 mod audio;
-mod extracted_text;
+pub(super) mod extracted_text;
 mod image_frames;
 mod md5;
 mod md5_image;
@@ -20,7 +20,7 @@ pub(super) async fn prepare_item(
         "image_frames" => image_frames::build_image_frames_inputs(index_db, &item, model).await?,
         "audio_tracks" => audio::build_audio_tracks_inputs(&item, model).await?,
         "audio_files" => audio::build_audio_files_inputs(&item, model).await?,
-        "extracted_text" => extracted_text::build_extracted_text_inputs(&item)?,
+        "extracted_text" => extracted_text::build_extracted_text_inputs(&item, model)?,
         "md5" => md5::build_md5_inputs(&item)?,
         "md5_image" => md5_image::build_md5_image_inputs(index_db, &item).await?,
         "sha256_md5_path" => sha256_md5_path::build_sha256_md5_path_inputs(&item)?,
@@ -0,0 +1,220 @@
+//! Byte budget for the loaded inputs (decoded frames, audio, rendered pages)
+//! of an extraction job's in-flight items.
+//!
+//! Items reserve their input size in KiB permits after loading and hold them
+//! until their outputs are written, so a few large items (4K video frames)
+//! lower the job's effective concurrency instead of piling up in memory. The
+//! running job's usage is registered under its queue ID and reported in the
+//! queue status.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+use serde::Serialize;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use utoipa::ToSchema;
+
+use crate::inferio_client::{InferenceFile, InferenceInput};
+use crate::jobs::registry::Registry;
+
+/// Memory held by a running extraction job's loaded inputs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+pub(crate) struct ExtractionMemory {
+    /// Approximate bytes of loaded inputs held by in-flight items.
+    pub in_flight_bytes: u64,
+    /// Items past loading whose inputs are held until their outputs are
+    /// written.
+    pub in_flight_items: u32,
+    /// Configured budget (`jobs.intermediate_data_budget_mb`).
+    pub budget_bytes: u64,
+}
+
+pub(super) struct MemoryBudget {
+    slots: Arc<Semaphore>,
+    capacity_kib: u32,
+    in_flight_bytes: AtomicU64,
+    in_flight_items: AtomicU32,
+}
+
+/// An item's share of the budget, released on drop.
+pub(super) struct MemoryReservation {
+    budget: Arc<MemoryBudget>,
+    bytes: u64,
+    _permits: Option<OwnedSemaphorePermit>,
+}
+
+impl MemoryBudget {
+    pub(super) fn new(capacity_kib: u32) -> Arc<Self> {
+        let capacity_kib = capacity_kib.max(1);
+        Arc::new(Self {
+            slots: Arc::new(Semaphore::new(capacity_kib as usize)),
+            capacity_kib,
+            in_flight_bytes: AtomicU64::new(0),
+            in_flight_items: AtomicU32::new(0),
+        })
+    }
+
+    /// Waits until `bytes` fit in the budget. An item larger than the whole
+    /// budget clamps to capacity and runs alone rather than deadlocking.
+    /// `None` once the budget is closed.
+    pub(super) async fn reserve(self: &Arc<Self>, bytes: u64) -> Option<MemoryReservation> {
+        let kib = u32::try_from(bytes.div_ceil(1024)).unwrap_or(u32::MAX);
+        let permits = if kib > 0 {
+            Some(
+                self.slots
+                    .clone()
+                    .acquire_many_owned(kib.min(self.capacity_kib))
+                    .await
+                    .ok()?,
+            )
+        } else {
+            None
+        };
+        self.in_flight_bytes.fetch_add(bytes, Ordering::Relaxed);
+        self.in_flight_items.fetch_add(1, Ordering::Relaxed);
+        Some(MemoryReservation {
+            budget: Arc::clone(self),
+            bytes,
+            _permits: permits,
+        })
+    }
+
+    pub(super) fn usage(&self) -> ExtractionMemory {
+        ExtractionMemory {
+            in_flight_bytes: self.in_flight_bytes.load(Ordering::Relaxed),
+            in_flight_items: self.in_flight_items.load(Ordering::Relaxed),
+            budget_bytes: u64::from(self.capacity_kib) * 1024,
+        }
+    }
+}
+
+impl Drop for MemoryReservation {
+    fn drop(&mut self) {
+        self.budget
+            .in_flight_bytes
+            .fetch_sub(self.bytes, Ordering::Relaxed);
+        self.budget.in_flight_items.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// In-memory footprint of an item's prepared inputs. Only counts buffers
+/// actually held in memory: path-based inputs are read transiently at
+/// request time, which the work-unit cap already bounds.
+pub(super) fn input_memory_bytes(inputs: &[InferenceInput]) -> u64 {
+    inputs
+        .iter()
+        .map(|input| match &input.file {
+            Some(InferenceFile::Bytes(buffer)) => buffer.len() as u64,
+            _ => 0,
+        })
+        .sum()
+}
+
+static BUDGETS: Registry<i64, Arc<MemoryBudget>> = Registry::new();
+
+/// Publishes a job's budget under its queue ID until dropped.
+pub(super) struct BudgetRegistration {
+    queue_id: i64,
+}
+
+impl BudgetRegistration {
+    pub(super) fn register(queue_id: i64, budget: &Arc<MemoryBudget>) -> Self {
+        BUDGETS.insert(queue_id, Arc::clone(budget));
+        Self { queue_id }
+    }
+}
+
+impl Drop for BudgetRegistration {
+    fn drop(&mut self) {
+        BUDGETS.remove(&self.queue_id);
+    }
+}
+
+/// Current input memory of the extraction job with `queue_id`, while it is
+/// processing items.
+pub(crate) fn job_memory(queue_id: i64) -> Option<ExtractionMemory> {
+    BUDGETS.get(&queue_id).map(|budget| budget.usage())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn frames(count: usize, bytes: usize) -> Vec<InferenceInput> {
+        (0..count)
+            .map(|_| {
+                InferenceInput::new(
+                    serde_json::json!({}),
+                    Some(InferenceFile::Bytes(vec![0; bytes])),
+                )
+            })
+            .collect()
+    }
+
+    // Ensures large items admit fewer concurrent items than small ones: two
+    // 4 MiB items saturate a 10 MiB budget so a third waits until one
+    // finishes, while five 1 MiB items all run at once. Usage is visible
+    // through the queue ID registry meanwhile.
+    #[tokio::test]
+    async fn large_items_reduce_concurrency_while_budget_is_saturated() {
+        let budget = MemoryBudget::new(10 * 1024);
+        let _registration = BudgetRegistration::register(-172, &budget);
+        let large = input_memory_bytes(&frames(4, 1024 * 1024));
+        assert_eq!(large, 4 * 1024 * 1024);
+
+        let first = budget.reserve(large).await.unwrap();
+        let second = budget.reserve(large).await.unwrap();
+        let waiting = tokio::spawn({
+            let budget = Arc::clone(&budget);
+            async move { budget.reserve(large).await.map(drop) }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiting.is_finished());
+        assert_eq!(
+            job_memory(-172),
+            Some(ExtractionMemory {
+                in_flight_bytes: 2 * large,
+                in_flight_items: 2,
+                budget_bytes: 10 * 1024 * 1024,
+            })
+        );
+
+        drop(first);
+        tokio::time::timeout(Duration::from_secs(1), waiting)
+            .await
+            .expect("waiting item admitted once budget frees up")
+            .unwrap()
+            .unwrap();
+        drop(second);
+
+        let small = input_memory_bytes(&frames(1, 1024 * 1024));
+        let mut reservations = Vec::new();
+        for _ in 0..5 {
+            let reservation = tokio::time::timeout(Duration::from_secs(1), budget.reserve(small))
+                .await
+                .expect("small items fit together")
+                .unwrap();
+            reservations.push(reservation);
+        }
+        assert_eq!(budget.usage().in_flight_items, 5);
+        drop(reservations);
+        assert_eq!(budget.usage().in_flight_bytes, 0);
+    }
+
+    // Ensures an item bigger than the whole budget runs alone instead of
+    // deadlocking, and is reported with its real size.
+    #[tokio::test]
+    async fn oversized_item_takes_the_whole_budget() {
+        let budget = MemoryBudget::new(1024);
+        let bytes = input_memory_bytes(&frames(1, 3 * 1024 * 1024));
+        let reservation = budget.reserve(bytes).await.unwrap();
+        assert_eq!(budget.usage().in_flight_bytes, bytes);
+        let blocked = tokio::time::timeout(Duration::from_millis(50), budget.reserve(1)).await;
+        assert!(blocked.is_err());
+        drop(reservation);
+        assert_eq!(budget.usage().in_flight_bytes, 0);
+        assert!(budget.reserve(1).await.is_some());
+    }
+}
@@ -10,6 +10,7 @@ pub(super) async fn handle_clip_output(
     index_db: &str,
     model: &ModelMetadata,
     job_id: i64,
+    replace: bool,
     item: &JobInputData,
     outputs: PredictOutput,
 ) -> ApiResult<OutputDisposition> {
@@ -37,6 +38,7 @@ pub(super) async fn handle_clip_output(
 
     call_index_db_writer(index_db, |reply| IndexDbWriterMessage::WriteClipOutput {
         job_id,
+        replace,
         setter_name: model.setter_name.clone(),
         item_sha256: item.sha256.clone(),
         entries: entries.clone(),
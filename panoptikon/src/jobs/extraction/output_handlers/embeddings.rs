@@ -2,6 +2,9 @@ use serde_json::Value;
 
 use crate::api_error::ApiError;
 use crate::jobs::extraction::ApiResult;
+// Shared with search and the embedding export so every path agrees on the
+// stored blob layout (f32 little-endian).
+pub(super) use crate::pql::embedding_utils::serialize_f32;
 
 pub(super) fn parse_embedding_json(value: &Value) -> ApiResult<Vec<f32>> {
     let arr = value
@@ -16,14 +19,6 @@ pub(super) fn parse_embedding_json(value: &Value) -> ApiResult<Vec<f32>> {
     Ok(embedding)
 }
 
-pub(super) fn serialize_f32(values: &[f32]) -> Vec<u8> {
-    let mut out = Vec::with_capacity(values.len() * 4);
-    for value in values {
-        out.extend_from_slice(&value.to_le_bytes());
-    }
-    out
-}
-
 pub(super) fn parse_npy_to_f32(buffer: &[u8]) -> ApiResult<Vec<f32>> {
     let (shape, data) = parse_npy(buffer)?;
     if shape.len() != 1 {
@@ -1,5 +1,6 @@
 use crate::api_error::ApiError;
 use crate::db::index_writer::{IndexDbWriterMessage, call_index_db_writer};
+use crate::db::system_config::TextNormalizationConfig;
 use crate::inferio_client::PredictOutput;
 use crate::jobs::extraction::{ApiResult, JobInputData, ModelMetadata};
 
@@ -23,6 +24,7 @@ pub(super) async fn write_placeholder(
     index_db: &str,
     model: &ModelMetadata,
     job_id: i64,
+    replace: bool,
     item: &JobInputData,
 ) -> ApiResult<OutputDisposition> {
     let setter_name = &model.setter_name;
@@ -31,10 +33,12 @@ pub(super) async fn write_placeholder(
         "tags" => {
             call_index_db_writer(index_db, |reply| IndexDbWriterMessage::WriteTagsOutput {
                 job_id,
+                replace,
                 setter_name: setter_name.clone(),
                 item_sha256: item_sha256.clone(),
                 tags: Vec::new(),
                 text_entries: Vec::new(),
+                frame_tags: Vec::new(),
                 reply,
             })
             .await?;
@@ -42,6 +46,7 @@ pub(super) async fn write_placeholder(
         "text" => {
             call_index_db_writer(index_db, |reply| IndexDbWriterMessage::WriteTextOutput {
                 job_id,
+                replace,
                 setter_name: setter_name.clone(),
                 item_sha256: item_sha256.clone(),
                 entries: Vec::new(),
@@ -52,6 +57,7 @@ pub(super) async fn write_placeholder(
         "clip" => {
             call_index_db_writer(index_db, |reply| IndexDbWriterMessage::WriteClipOutput {
                 job_id,
+                replace,
                 setter_name: setter_name.clone(),
                 item_sha256: item_sha256.clone(),
                 entries: Vec::new(),
@@ -63,6 +69,7 @@ pub(super) async fn write_placeholder(
             call_index_db_writer(index_db, |reply| {
                 IndexDbWriterMessage::WriteTextEmbeddingOutput {
                     job_id,
+                    replace,
                     setter_name: setter_name.clone(),
                     item_sha256: item_sha256.clone(),
                     source_data_id: item.data_id,
@@ -105,15 +112,21 @@ pub(super) async fn handle_outputs(
     index_db: &str,
     model: &ModelMetadata,
     job_id: i64,
+    replace: bool,
     item: JobInputData,
     outputs: PredictOutput,
+    normalization: &TextNormalizationConfig,
 ) -> ApiResult<OutputDisposition> {
     match model.output_type.as_str() {
-        "tags" => tags::handle_tags_output(index_db, model, job_id, &item, outputs).await,
-        "text" => text::handle_text_output(index_db, model, job_id, &item, outputs).await,
-        "clip" => clip::handle_clip_output(index_db, model, job_id, &item, outputs).await,
+        "tags" => {
+            tags::handle_tags_output(index_db, model, job_id, replace, &item, outputs, normalization).await
+        }
+        "text" => {
+            text::handle_text_output(index_db, model, job_id, replace, &item, outputs, normalization).await
+        }
+        "clip" => clip::handle_clip_output(index_db, model, job_id, replace, &item, outputs).await,
         "text-embedding" => {
-            text_embedding::handle_text_embedding_output(index_db, model, job_id, &item, outputs)
+            text_embedding::handle_text_embedding_output(index_db, model, job_id, replace, &item, outputs)
                 .await
         }
         other => Err(ApiError::bad_request(format!(
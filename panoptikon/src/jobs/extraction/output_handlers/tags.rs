@@ -3,10 +3,12 @@ use std::collections::HashMap;
 use serde_json::Value;
 
 use crate::api_error::ApiError;
-use crate::db::extraction_write::{TagEntry, TagTextEntry};
+use crate::db::extraction_write::{TagEntry, TagTextEntry, mcut_threshold};
 use crate::db::index_writer::{IndexDbWriterMessage, call_index_db_writer};
+use crate::db::system_config::TextNormalizationConfig;
 use crate::inferio_client::PredictOutput;
 use crate::jobs::extraction::{ApiResult, JobInputData, ModelMetadata};
+use crate::pql::utils::normalized_for_index;
 
 use super::OutputDisposition;
 
@@ -14,17 +16,21 @@ pub(super) async fn handle_tags_output(
     index_db: &str,
     model: &ModelMetadata,
     job_id: i64,
+    replace: bool,
     item: &JobInputData,
     outputs: PredictOutput,
+    normalization: &TextNormalizationConfig,
 ) -> ApiResult<OutputDisposition> {
     let values = outputs.into_json("tags")?;
     if values.is_empty() {
         let _ = call_index_db_writer(index_db, |reply| IndexDbWriterMessage::WriteTagsOutput {
             job_id,
+            replace,
             setter_name: model.setter_name.clone(),
             item_sha256: item.sha256.clone(),
             tags: Vec::new(),
             text_entries: Vec::new(),
+            frame_tags: Vec::new(),
             reply,
         })
         .await?;
@@ -53,10 +59,12 @@ pub(super) async fn handle_tags_output(
     if total_tag_groups == 0 {
         let _ = call_index_db_writer(index_db, |reply| IndexDbWriterMessage::WriteTagsOutput {
             job_id,
+            replace,
             setter_name: model.setter_name.clone(),
             item_sha256: item.sha256.clone(),
             tags: Vec::new(),
             text_entries: Vec::new(),
+            frame_tags: Vec::new(),
             reply,
         })
         .await?;
@@ -79,22 +87,17 @@ pub(super) async fn handle_tags_output(
         tag_results.iter().map(|r| r.tags.clone()).collect(),
         &rating_severity,
     );
-    let mut tags = Vec::new();
-    for (namespace, name, confidence) in aggregated {
-        tags.push(TagEntry {
-            namespace: format!("{main_namespace}:{namespace}"),
-            name,
-            confidence,
-        });
-    }
+    let tags = tag_entries(&main_namespace, aggregated);
 
     if tags.is_empty() {
         let _ = call_index_db_writer(index_db, |reply| IndexDbWriterMessage::WriteTagsOutput {
             job_id,
+            replace,
             setter_name: model.setter_name.clone(),
             item_sha256: item.sha256.clone(),
             tags: Vec::new(),
             text_entries: Vec::new(),
+            frame_tags: Vec::new(),
             reply,
         })
         .await?;
@@ -114,6 +117,7 @@ pub(super) async fn handle_tags_output(
     text_entries.push(TagTextEntry {
         index: 0,
         text: all_tags_string,
+        normalized_text: None,
         language: main_namespace.clone(),
         language_confidence: 1.0,
         confidence: min_confidence,
@@ -138,6 +142,7 @@ pub(super) async fn handle_tags_output(
             text_entries.push(TagTextEntry {
                 index: 1,
                 text: mcut_tags,
+                normalized_text: None,
                 language: format!("{main_namespace}-mcut"),
                 language_confidence: 1.0,
                 confidence: m_thresh,
@@ -150,18 +155,44 @@ pub(super) async fn handle_tags_output(
         text_entries.push(TagTextEntry {
             index: 2,
             text: metadata_text,
+            normalized_text: None,
             language: "metadata".to_string(),
             language_confidence: 1.0,
             confidence: tag_results[0].metadata_score,
         });
     }
 
+    for entry in &mut text_entries {
+        entry.normalized_text = normalized_for_index(normalization, &entry.text);
+    }
+
+    // One output per frame for videos; other items (and sliced images) have
+    // nothing to attribute.
+    let frame_tags = if model.per_frame_tags
+        && item.item_type.starts_with("video")
+        && tag_results.len() > 1
+    {
+        tag_results
+            .iter()
+            .map(|result| {
+                tag_entries(
+                    &main_namespace,
+                    aggregate_tags(vec![result.tags.clone()], &rating_severity),
+                )
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+
     call_index_db_writer(index_db, |reply| IndexDbWriterMessage::WriteTagsOutput {
         job_id,
+        replace,
         setter_name: model.setter_name.clone(),
         item_sha256: item.sha256.clone(),
         tags: tags.clone(),
         text_entries: text_entries.clone(),
+        frame_tags: frame_tags.clone(),
         reply,
     })
     .await?;
@@ -233,6 +264,16 @@ impl TagResult {
     }
 }
 
+fn tag_entries(main_namespace: &str, tags: Vec<(String, String, f64)>) -> Vec<TagEntry> {
+    tags.into_iter()
+        .map(|(namespace, name, confidence)| TagEntry {
+            namespace: format!("{main_namespace}:{namespace}"),
+            name,
+            confidence,
+        })
+        .collect()
+}
+
 fn aggregate_tags(
     namespaces_tags: Vec<Vec<(String, HashMap<String, f64>)>>,
     severity_order: &[String],
@@ -316,24 +357,3 @@ fn get_rating(tags: &[HashMap<String, f64>], severity_order: &[String]) -> Optio
     }
     final_rating.map(|rating| (rating, final_score))
 }
-
-fn mcut_threshold(values: &[f64]) -> f64 {
-    if values.is_empty() {
-        return 0.0;
-    }
-    let mut sorted = values.to_vec();
-    sorted.sort_by(|a, b| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal));
-    let mut max_diff = 0.0;
-    let mut idx = 0usize;
-    for i in 0..sorted.len().saturating_sub(1) {
-        let diff = sorted[i] - sorted[i + 1];
-        if diff > max_diff {
-            max_diff = diff;
-            idx = i;
-        }
-    }
-    if idx + 1 >= sorted.len() {
-        return sorted[0];
-    }
-    (sorted[idx] + sorted[idx + 1]) / 2.0
-}
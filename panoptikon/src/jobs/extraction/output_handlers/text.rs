@@ -4,8 +4,10 @@ use serde_json::Value;
 
 use crate::db::extraction_write::TextEntry;
 use crate::db::index_writer::{IndexDbWriterMessage, call_index_db_writer};
+use crate::db::system_config::TextNormalizationConfig;
 use crate::inferio_client::PredictOutput;
 use crate::jobs::extraction::{ApiResult, JobInputData, ModelMetadata};
+use crate::pql::utils::normalized_for_index;
 
 use super::OutputDisposition;
 
@@ -13,8 +15,10 @@ pub(super) async fn handle_text_output(
     index_db: &str,
     model: &ModelMetadata,
     job_id: i64,
+    replace: bool,
     item: &JobInputData,
     outputs: PredictOutput,
+    normalization: &TextNormalizationConfig,
 ) -> ApiResult<OutputDisposition> {
     let values = outputs.into_json("text")?;
     let mut entries = Vec::new();
@@ -44,6 +48,7 @@ pub(super) async fn handle_text_output(
         let language_confidence = value.get("language_confidence").and_then(Value::as_f64);
         entries.push(TextEntry {
             index: idx as i64,
+            normalized_text: normalized_for_index(normalization, &transcription),
             text: transcription,
             language,
             language_confidence,
@@ -53,6 +58,7 @@ pub(super) async fn handle_text_output(
 
     call_index_db_writer(index_db, |reply| IndexDbWriterMessage::WriteTextOutput {
         job_id,
+        replace,
         setter_name: model.setter_name.clone(),
         item_sha256: item.sha256.clone(),
         entries: entries.clone(),
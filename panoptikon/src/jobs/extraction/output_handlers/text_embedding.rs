@@ -4,6 +4,7 @@ use crate::db::index_writer::{IndexDbWriterMessage, call_index_db_writer};
 use crate::inferio_client::PredictOutput;
 use crate::jobs::extraction::{ApiResult, JobInputData, ModelMetadata};
 
+use super::super::input_handlers::extracted_text::{ChunkOptions, chunk_text};
 use super::OutputDisposition;
 use super::embeddings::{parse_npy_to_f32_rows, serialize_f32};
 
@@ -11,33 +12,51 @@ pub(super) async fn handle_text_embedding_output(
     index_db: &str,
     model: &ModelMetadata,
     job_id: i64,
+    replace: bool,
     item: &JobInputData,
     outputs: PredictOutput,
 ) -> ApiResult<OutputDisposition> {
     let source_data_id = item.data_id;
     let mut entries = Vec::new();
     let buffers = outputs.into_binary("text-embedding")?;
+    // The input handler sent one input per chunk (one for the whole text
+    // unless chunking is configured); recomputing the chunks recovers the
+    // offsets each embedding is stored under.
+    let options = ChunkOptions::from_opts(&model.input_handler_opts);
+    let chunks = chunk_text(item.text.as_deref().unwrap_or_default(), options.as_ref());
     // The zero-input placeholder never reaches this handler, so anything
-    // other than exactly one npy for the single text input is an inference
-    // anomaly: fail the item so it stays retryable instead of writing a
-    // placeholder that would permanently mark it processed.
-    if buffers.len() != 1 {
+    // other than exactly one npy per text input is an inference anomaly:
+    // fail the item so it stays retryable instead of writing a placeholder
+    // that would permanently mark it processed.
+    if buffers.len() != chunks.len() {
         return Err(ApiError::internal(format!(
-            "Text embedding output mismatch: expected 1 buffer, got {}",
+            "Text embedding output mismatch: expected {} buffers, got {}",
+            chunks.len(),
             buffers.len()
         )));
     }
-    let embedding_rows = parse_npy_to_f32_rows(&buffers[0])?;
-    for (idx, embedding) in embedding_rows.into_iter().enumerate() {
-        entries.push(EmbeddingEntry {
-            index: idx as i64,
-            embedding: serialize_f32(&embedding),
-        });
+    for (chunk, buffer) in chunks.iter().zip(&buffers) {
+        let embedding_rows = parse_npy_to_f32_rows(buffer)?;
+        // Several rows for one chunk would collide with the next chunk's
+        // offsets; only an unchunked text may yield more than one.
+        if chunks.len() > 1 && embedding_rows.len() != 1 {
+            return Err(ApiError::internal(format!(
+                "Text embedding output mismatch: expected 1 embedding per chunk, got {}",
+                embedding_rows.len()
+            )));
+        }
+        for (idx, embedding) in embedding_rows.into_iter().enumerate() {
+            entries.push(EmbeddingEntry {
+                index: (chunk.offset + idx) as i64,
+                embedding: serialize_f32(&embedding),
+            });
+        }
     }
 
     call_index_db_writer(index_db, |reply| {
         IndexDbWriterMessage::WriteTextEmbeddingOutput {
             job_id,
+            replace,
             setter_name: model.setter_name.clone(),
             item_sha256: item.sha256.clone(),
             source_data_id,
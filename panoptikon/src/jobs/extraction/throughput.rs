@@ -0,0 +1,353 @@
+//! Time estimates for the data extraction jobs in the queue status.
+//!
+//! The running job records its processed count after every item, and its
+//! rate is measured over roughly the last [`RATE_WINDOW`], so a slowdown
+//! shows up within minutes. Until it has processed [`MIN_MEASURED_ITEMS`],
+//! and for queued jobs, the rate comes from the setter's recent finished
+//! runs in the data log. A queued job's item count is the projection its
+//! enqueue request computed.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use chrono::Local;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::db::extraction_log::{ExtractionThroughput, get_extraction_throughput};
+use crate::db::open_index_db_read_no_user_data;
+use crate::jobs::queue::{JobModel, JobType};
+use crate::jobs::registry::Registry;
+
+/// Span of recent items the running job's rate is measured over.
+const RATE_WINDOW: Duration = Duration::from_secs(120);
+/// Items the running job processes before its own rate replaces history.
+const MIN_MEASURED_ITEMS: i64 = 5;
+/// Newest finished runs per setter and entity type the history rate sums.
+const HISTORY_RUNS: i64 = 5;
+
+/// Where an estimate's rate comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum EtaConfidence {
+    /// Measured on the running job over its last few minutes.
+    Measured,
+    /// The setter's recent finished runs.
+    History,
+    /// The setter has no finished runs: pooled over every setter's recent
+    /// runs, or no rate at all in a fresh index DB.
+    Low,
+}
+
+/// Estimated time left of a data extraction job.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub(crate) struct ExtractionEta {
+    /// The running job's matched items less those it processed, or the
+    /// count projected when a queued job was enqueued. Null for jobs
+    /// enqueued without a projection (follow-ups).
+    pub remaining_items: Option<i64>,
+    pub items_per_second: Option<f64>,
+    /// Seconds the job needs once running.
+    pub remaining_secs: Option<f64>,
+    /// When the job should finish (local time, RFC 3339), after the jobs
+    /// ahead of it. Null when it or a job ahead has no estimate or waits for
+    /// its `job_window`.
+    pub estimated_completion: Option<String>,
+    pub confidence: EtaConfidence,
+}
+
+/// Processed counts of the running job over the last [`RATE_WINDOW`].
+#[derive(Default)]
+pub(super) struct JobThroughput {
+    samples: Mutex<VecDeque<(Instant, i64)>>,
+}
+
+impl JobThroughput {
+    /// Records the job's processed count after an item finished.
+    pub(super) fn record(&self, processed: i64) {
+        self.record_at(Instant::now(), processed);
+    }
+
+    fn record_at(&self, now: Instant, processed: i64) {
+        let mut samples = self.lock();
+        samples.push_back((now, processed));
+        // The newest sample before the window stays as its baseline.
+        while samples.len() > 2 && samples[1].0 + RATE_WINDOW <= now {
+            samples.pop_front();
+        }
+    }
+
+    fn processed(&self) -> i64 {
+        self.lock().back().map_or(0, |(_, processed)| *processed)
+    }
+
+    /// Items per second from the window's first sample until `now`, so a
+    /// stalled job's rate keeps falling instead of freezing.
+    fn rate_at(&self, now: Instant) -> Option<f64> {
+        let samples = self.lock();
+        let (start, first) = *samples.front()?;
+        let (_, last) = *samples.back()?;
+        let elapsed = now.saturating_duration_since(start).as_secs_f64();
+        (last >= MIN_MEASURED_ITEMS && last > first && elapsed > 0.0)
+            .then(|| (last - first) as f64 / elapsed)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, VecDeque<(Instant, i64)>> {
+        self.samples.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+struct RunningThroughput {
+    throughput: Arc<JobThroughput>,
+    entity: Option<String>,
+    total_remaining: i64,
+}
+
+/// What the estimate of the running job starts from.
+struct RunningSnapshot {
+    entity: Option<String>,
+    remaining: i64,
+    rate: Option<f64>,
+}
+
+static RUNNING: Registry<i64, RunningThroughput> = Registry::new();
+static PROJECTIONS: Registry<i64, i64> = Registry::new();
+
+/// Publishes a job's throughput under its queue ID until dropped.
+pub(super) struct ThroughputRegistration {
+    queue_id: i64,
+}
+
+impl ThroughputRegistration {
+    pub(super) fn register(
+        queue_id: i64,
+        throughput: &Arc<JobThroughput>,
+        entity: Option<String>,
+        total_remaining: i64,
+    ) -> Self {
+        throughput.record(0);
+        RUNNING.insert(
+            queue_id,
+            RunningThroughput {
+                throughput: Arc::clone(throughput),
+                entity,
+                total_remaining,
+            },
+        );
+        Self { queue_id }
+    }
+}
+
+impl Drop for ThroughputRegistration {
+    fn drop(&mut self) {
+        RUNNING.remove(&self.queue_id);
+    }
+}
+
+/// Remembers the item count an enqueue request projected for the job with
+/// `queue_id`, until the job leaves the queue.
+pub(crate) fn record_projection(queue_id: i64, items: i64) {
+    PROJECTIONS.insert(queue_id, items);
+}
+
+fn running_snapshot(queue_id: i64, now: Instant) -> Option<RunningSnapshot> {
+    RUNNING
+        .lock()
+        .get(&queue_id)
+        .map(|running| RunningSnapshot {
+            entity: running.entity.clone(),
+            remaining: running
+                .total_remaining
+                .saturating_sub(running.throughput.processed())
+                .max(0),
+            rate: running.throughput.rate_at(now),
+        })
+}
+
+/// Summed items over summed seconds of `stats`.
+fn pooled_rate<'a>(stats: impl Iterator<Item = &'a ExtractionThroughput>) -> Option<f64> {
+    let (items, seconds) = stats.fold((0, 0.0), |(items, seconds), stat| {
+        (items + stat.items, seconds + stat.seconds)
+    });
+    (items > 0 && seconds > 0.0).then(|| items as f64 / seconds)
+}
+
+fn estimate(
+    setter: &str,
+    running: Option<RunningSnapshot>,
+    projected: Option<i64>,
+    history: &[ExtractionThroughput],
+) -> ExtractionEta {
+    let entity = running.as_ref().and_then(|running| running.entity.clone());
+    let remaining_items = running
+        .as_ref()
+        .map(|running| running.remaining)
+        .or(projected);
+    let (items_per_second, confidence) = match running.and_then(|running| running.rate) {
+        Some(rate) => (Some(rate), EtaConfidence::Measured),
+        None => {
+            let own = history.iter().filter(|stat| {
+                stat.setter == setter
+                    && (entity.is_none() || stat.entity.as_deref() == entity.as_deref())
+            });
+            match pooled_rate(own) {
+                Some(rate) => (Some(rate), EtaConfidence::History),
+                None => (pooled_rate(history.iter()), EtaConfidence::Low),
+            }
+        }
+    };
+    ExtractionEta {
+        remaining_items,
+        items_per_second,
+        remaining_secs: remaining_items
+            .zip(items_per_second)
+            .map(|(items, rate)| items as f64 / rate),
+        estimated_completion: None,
+        confidence,
+    }
+}
+
+async fn load_history(index_db: &str) -> Vec<ExtractionThroughput> {
+    let result = match open_index_db_read_no_user_data(index_db).await {
+        Ok(mut conn) => get_extraction_throughput(&mut conn, HISTORY_RUNS).await,
+        Err(err) => Err(err),
+    };
+    result
+        .inspect_err(
+            |err| tracing::warn!(error = ?err, index_db, "failed to read extraction throughput"),
+        )
+        .unwrap_or_default()
+}
+
+/// Sets `eta` on the data extraction jobs of `queue`, the running job first
+/// and the rest in queue order. Completion times add up along the queue
+/// until a job without an estimate (any other job type included).
+pub(crate) async fn annotate_queue_eta(queue: &mut [JobModel]) {
+    let queued = queue.iter().map(|job| job.queue_id).collect::<HashSet<_>>();
+    let projected = {
+        let mut projections = PROJECTIONS.lock();
+        projections.retain(|queue_id, _| queued.contains(queue_id));
+        projections.clone()
+    };
+
+    let now = Instant::now();
+    let started = Local::now();
+    let mut history: HashMap<String, Vec<ExtractionThroughput>> = HashMap::new();
+    let mut offset = Some(0.0);
+    for job in queue.iter_mut() {
+        if job.job_type != JobType::DataExtraction {
+            offset = None;
+            continue;
+        }
+        if !history.contains_key(&job.index_db) {
+            let stats = load_history(&job.index_db).await;
+            history.insert(job.index_db.clone(), stats);
+        }
+        let running = job
+            .running
+            .then(|| running_snapshot(job.queue_id, now))
+            .flatten();
+        let mut eta = estimate(
+            job.metadata.as_deref().unwrap_or_default(),
+            running,
+            projected.get(&job.queue_id).copied(),
+            &history[&job.index_db],
+        );
+        offset = offset
+            .filter(|_| !job.waiting_for_window)
+            .zip(eta.remaining_secs)
+            .map(|(offset, secs)| offset + secs);
+        eta.estimated_completion = offset.map(|secs| {
+            (started + chrono::Duration::milliseconds((secs * 1000.0) as i64)).to_rfc3339()
+        });
+        job.eta = Some(eta);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stat(setter: &str, entity: &str, items: i64, seconds: f64) -> ExtractionThroughput {
+        ExtractionThroughput {
+            setter: setter.to_string(),
+            entity: Some(entity.to_string()),
+            runs: 1,
+            items,
+            seconds,
+        }
+    }
+
+    // Ensures the measured rate covers the recent window only, needs a few
+    // items first, and keeps falling while the job stalls.
+    #[test]
+    fn measured_rate_tracks_the_recent_window() {
+        let throughput = JobThroughput::default();
+        let start = Instant::now();
+        throughput.record_at(start, 0);
+        throughput.record_at(start + Duration::from_secs(1), 1);
+        assert_eq!(throughput.rate_at(start + Duration::from_secs(1)), None);
+
+        // 10 items/s for the first 10 minutes, then 1 item/s.
+        for second in 1..=600 {
+            throughput.record_at(start + Duration::from_secs(second), second as i64 * 10);
+        }
+        for second in 601..=900 {
+            throughput.record_at(
+                start + Duration::from_secs(second),
+                6000 + second as i64 - 600,
+            );
+        }
+        let now = start + Duration::from_secs(900);
+        let rate = throughput.rate_at(now).unwrap();
+        assert!((rate - 1.0).abs() < 0.05, "rate {rate}");
+
+        let stalled = throughput.rate_at(now + Duration::from_secs(120)).unwrap();
+        assert!(stalled < rate / 1.5, "stalled rate {stalled}");
+    }
+
+    // Ensures the running job's own rate wins once measured, queued jobs use
+    // their setter's history, and a setter without history falls back to
+    // every setter's runs marked low confidence.
+    #[test]
+    fn estimates_prefer_measured_then_own_history() {
+        let history = vec![
+            stat("alpha", "items", 20, 10.0),
+            stat("alpha", "text", 100, 10.0),
+            stat("beta", "items", 40, 10.0),
+        ];
+
+        let running = RunningSnapshot {
+            entity: Some("items".to_string()),
+            remaining: 30,
+            rate: Some(3.0),
+        };
+        let eta = estimate("alpha", Some(running), Some(1000), &history);
+        assert_eq!(eta.confidence, EtaConfidence::Measured);
+        assert_eq!(eta.remaining_items, Some(30));
+        assert_eq!(eta.remaining_secs, Some(10.0));
+
+        let warming_up = RunningSnapshot {
+            entity: Some("items".to_string()),
+            remaining: 30,
+            rate: None,
+        };
+        let eta = estimate("alpha", Some(warming_up), None, &history);
+        assert_eq!(eta.confidence, EtaConfidence::History);
+        assert_eq!(eta.items_per_second, Some(2.0));
+
+        let eta = estimate("beta", None, Some(80), &history);
+        assert_eq!(eta.confidence, EtaConfidence::History);
+        assert_eq!(eta.remaining_secs, Some(20.0));
+
+        let eta = estimate("gamma", None, Some(160), &history);
+        assert_eq!(eta.confidence, EtaConfidence::Low);
+        assert_eq!(eta.items_per_second, Some(160.0 / 30.0));
+        assert!((eta.remaining_secs.unwrap() - 30.0).abs() < 1e-9);
+
+        let eta = estimate("gamma", None, None, &[]);
+        assert_eq!(eta.confidence, EtaConfidence::Low);
+        assert_eq!((eta.items_per_second, eta.remaining_secs), (None, None));
+    }
+}
@@ -0,0 +1,635 @@
+//! On-demand rescan of one file, run inline by the API instead of through the
+//! job queue.
+//!
+//! The file goes through the same [`process_file`] and
+//! [`build_file_scan_data`] path as a folder scan, under a synthetic scan
+//! record of its own, so history shows the rescan like any other scan. A
+//! file that is no longer on disk is marked unavailable instead. `force`
+//! regenerates the item's visuals and replaces the stored ones, and rewrites
+//! the item's probed metadata from the fresh extraction.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::http::StatusCode;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::api_error::{ApiError, ErrorCode};
+use crate::db::file_scans::FileScanUpdate;
+use crate::db::files::{FileUpsertResult, get_file_by_path, has_blurhash};
+use crate::db::index_writer::{IndexDbWriterMessage, call_index_db_writer};
+use crate::db::items::get_existing_files_for_sha256;
+use crate::db::storage::{has_frame, has_thumbnail};
+use crate::db::path_keys::PathKeys;
+use crate::db::raw_paths::display_path;
+use crate::db::system_config::SystemConfig;
+use crate::jobs::files::{
+    FRAME_PROCESS_VERSION, FileProcessError, FileWriteData, ScanTimers, THUMBNAIL_PROCESS_VERSION,
+    build_extension_set, build_file_scan_data, current_iso_timestamp, has_allowed_extension,
+    is_excluded, normalize_path, parse_filescan_filter, predict_stored_visuals, process_file,
+};
+use crate::jobs::implicit_exclusions::implicit_excluded_roots;
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+/// Upper bound on hashing and processing one file; the request fails with a
+/// gateway timeout past it rather than holding the connection open.
+pub(crate) const FILE_RESCAN_TIMEOUT: Duration = Duration::from_secs(120);
+
+pub(crate) enum RescanTarget {
+    Path(String),
+    Sha256(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum FileRescanStatus {
+    /// The file was processed and its index rows are up to date.
+    Indexed,
+    /// The file is gone from disk and was marked unavailable.
+    Unavailable,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub(crate) struct FileRescanOutcome {
+    pub status: FileRescanStatus,
+    pub path: String,
+    /// The synthetic scan record the rescan was logged under.
+    pub scan_id: i64,
+    /// The item the file holds; for an unavailable file, the one it held
+    /// when last indexed.
+    pub sha256: String,
+    /// The file's content was not indexed before, so a new item was created.
+    pub item_inserted: bool,
+    /// The path was not indexed before.
+    pub file_inserted: bool,
+    /// The file's content changed since it was last indexed.
+    pub content_changed: bool,
+    /// Stored visuals were replaced because the rescan was forced.
+    pub visuals_replaced: bool,
+    /// Visuals were due but no frame of the video could be decoded, even by
+    /// the fallbacks; the file was indexed without them.
+    pub undecodable: bool,
+}
+
+/// Rescans the file named by `target`: a path must lie inside the included
+/// folders; a sha256 resolves to the first of its files still on disk.
+pub(crate) async fn rescan_file(
+    conn: &mut sqlx::SqliteConnection,
+    index_db: &str,
+    config: &SystemConfig,
+    target: RescanTarget,
+    force: bool,
+) -> ApiResult<FileRescanOutcome> {
+    let path = match target {
+        RescanTarget::Path(path) => {
+            let path = normalize_path(&path, false);
+            check_scannable(config, &path)?;
+            path
+        }
+        RescanTarget::Sha256(sha256) => {
+            let files = get_existing_files_for_sha256(conn, &sha256).await?;
+            match files.into_iter().next() {
+                Some(file) => file.disk_path(),
+                None => return mark_item_unavailable(conn, index_db, &sha256).await,
+            }
+        }
+    };
+    let path_str = display_path(&path);
+    let path_keys = PathKeys::new(config);
+    let path_key = path_keys.key(&path_str);
+
+    let record = get_file_by_path(conn, &path_key).await?;
+    if !path.is_file() {
+        let Some(record) = record else {
+            return Err(ApiError::not_found("File not found"));
+        };
+        return mark_paths_unavailable(index_db, record.sha256, &path_str, vec![path_key]).await;
+    }
+
+    let stored_visuals = match (&record, force) {
+        (Some(record), false) => {
+            let file_size = std::fs::metadata(&path)
+                .map(|meta| meta.len() as i64)
+                .unwrap_or_default();
+            predict_stored_visuals(conn, &path, record, file_size).await?
+        }
+        _ => None,
+    };
+
+    let scan_time = current_iso_timestamp();
+    let scan_id = call_index_db_writer(index_db, |reply| IndexDbWriterMessage::AddFileScan {
+        scan_time: scan_time.clone(),
+        path: path_str.clone(),
+        reply,
+    })
+    .await?;
+
+    let timers = ScanTimers::default();
+    let mut update = FileScanUpdate {
+        end_time: None,
+        new_items: 0,
+        unchanged_files: 0,
+        new_files: 0,
+        modified_files: 0,
+        marked_unavailable: 0,
+        errors: 0,
+        timeouts: 0,
+        undecodable: 0,
+        deferred: 0,
+        ignored_dirs: 0,
+        ignored_dir_files: 0,
+        ignored_files: 0,
+        visuals_deferred: 0,
+        skipped_dirs: 0,
+        worker_count: 1,
+        total_available: 0,
+        false_changes: 0,
+        metadata_time: 0.0,
+        hashing_time: 0.0,
+        thumbgen_time: 0.0,
+        blurhash_time: 0.0,
+    };
+    let result = index_file(
+        conn,
+        index_db,
+        config,
+        &path_keys,
+        path,
+        stored_visuals,
+        force,
+        scan_id,
+        &scan_time,
+        &timers,
+    )
+    .await;
+    match &result {
+        Ok((outcome, upsert, false_change)) => {
+            update.new_items = i64::from(upsert.item_inserted);
+            if upsert.file_updated {
+                update.unchanged_files = 1;
+            } else if upsert.file_deleted {
+                update.modified_files = 1;
+            } else if upsert.file_inserted {
+                update.new_files = 1;
+            }
+            update.total_available = 1;
+            update.false_changes = i64::from(*false_change);
+            update.undecodable = i64::from(outcome.undecodable);
+        }
+        Err(err) => {
+            update.errors = 1;
+            update.timeouts = i64::from(err.code() == ErrorCode::MediaTimeout);
+        }
+    }
+    update.end_time = Some(current_iso_timestamp());
+    update.metadata_time = timers.metadata.busy_secs();
+    update.hashing_time = timers.hashing.busy_secs();
+    update.thumbgen_time = timers.thumbgen.busy_secs();
+    update.blurhash_time = timers.blurhash.busy_secs();
+    call_index_db_writer(index_db, |reply| IndexDbWriterMessage::UpdateFileScan {
+        scan_id,
+        update: update.clone(),
+        reply,
+    })
+    .await?;
+
+    result.map(|(outcome, _, _)| outcome)
+}
+
+/// A path outside the included folders, inside an excluded one (explicitly
+/// or as Panoptikon's own data), or with an extension the config does not
+/// scan would never be indexed by a folder scan, so it is rejected here too.
+fn check_scannable(config: &SystemConfig, path: &std::path::Path) -> ApiResult<()> {
+    let included = config
+        .included_folders
+        .iter()
+        .any(|folder| path.starts_with(normalize_path(folder, true)));
+    if !included {
+        return Err(ApiError::bad_request(
+            "Path is not inside an included folder",
+        ));
+    }
+    let excluded: Vec<PathBuf> = config
+        .excluded_folders
+        .iter()
+        .map(|folder| normalize_path(folder, true))
+        .chain(implicit_excluded_roots(
+            &crate::config::runtime().data_folder,
+        ))
+        .collect();
+    if is_excluded(path, &excluded) {
+        return Err(ApiError::bad_request("Path is inside an excluded folder"));
+    }
+    if !has_allowed_extension(path, &build_extension_set(config)) {
+        return Err(ApiError::bad_request(
+            "File type is not enabled for scanning",
+        ));
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn index_file(
+    conn: &mut sqlx::SqliteConnection,
+    index_db: &str,
+    config: &SystemConfig,
+    path_keys: &PathKeys,
+    path: PathBuf,
+    stored_visuals: Option<String>,
+    force: bool,
+    scan_id: i64,
+    scan_time: &str,
+    timers: &ScanTimers,
+) -> ApiResult<(FileRescanOutcome, FileUpsertResult, bool)> {
+    let filter = parse_filescan_filter(config).map(Arc::new);
+    let worker_timers = timers.clone();
+    let task = tokio::task::spawn_blocking(move || {
+        process_file(
+            path,
+            filter,
+            stored_visuals.as_deref(),
+            Duration::ZERO,
+            &worker_timers,
+        )
+    });
+    let prepared = match tokio::time::timeout(FILE_RESCAN_TIMEOUT, task).await {
+        Err(_) => {
+            return Err(ApiError::new(
+                StatusCode::GATEWAY_TIMEOUT,
+                "Timed out processing the file",
+            ));
+        }
+        Ok(Err(err)) => {
+            tracing::error!(error = %err, "file rescan task failed");
+            return Err(ApiError::internal("Failed to process the file"));
+        }
+        Ok(Ok(Err(err))) => return Err(map_process_error(err)),
+        Ok(Ok(Ok(prepared))) => prepared,
+    };
+
+    let fresh_metadata = force.then(|| prepared.metadata.clone());
+    let undecodable = prepared.undecodable;
+    let is_image = prepared.mime_type.starts_with("image");
+    let file_data = build_file_scan_data(conn, path_keys, prepared, scan_time).await?;
+    let false_change = !file_data.new_file_hash && file_data.new_file_timestamp;
+    let visuals_replaced = if force {
+        replace_visuals(index_db, &file_data, is_image).await?
+    } else {
+        store_missing_visuals(conn, index_db, &file_data).await?;
+        false
+    };
+
+    let upsert = call_index_db_writer(index_db, |reply| IndexDbWriterMessage::UpdateFileData {
+        time_added: file_data.time_added.clone(),
+        scan_id,
+        data: file_data.data.clone(),
+        reply,
+    })
+    .await?;
+    // A new item was written with the fresh metadata already.
+    if let Some(metadata) = fresh_metadata.filter(|_| !upsert.item_inserted) {
+        call_index_db_writer(index_db, |reply| IndexDbWriterMessage::UpdateItemMetadata {
+            sha256: file_data.sha256.clone(),
+            metadata: metadata.clone(),
+            reply,
+        })
+        .await?;
+    }
+
+    let outcome = FileRescanOutcome {
+        status: FileRescanStatus::Indexed,
+        path: file_data.data.path.clone(),
+        scan_id,
+        sha256: file_data.sha256.clone(),
+        item_inserted: upsert.item_inserted,
+        file_inserted: upsert.file_inserted,
+        content_changed: file_data.new_file_hash && !upsert.file_inserted,
+        visuals_replaced,
+        undecodable,
+    };
+    Ok((outcome, upsert, false_change))
+}
+
+fn map_process_error(err: FileProcessError) -> ApiError {
+    match err {
+        FileProcessError::Filtered => {
+            ApiError::bad_request("File is rejected by the filescan filter")
+        }
+        FileProcessError::Unsupported(reason) => {
+            ApiError::bad_request(format!("File could not be processed: {reason}"))
+        }
+        FileProcessError::TimedOut(reason) => {
+            ApiError::media_timeout(format!("File could not be processed: {reason}"))
+        }
+        FileProcessError::Undecodable(reason) => {
+            ApiError::media_undecodable(format!("File could not be processed: {reason}"))
+        }
+        FileProcessError::Busy => ApiError::new(
+            StatusCode::CONFLICT,
+            "File is still being written; try again once it settles",
+        ),
+        // Settle is zero and nothing compares the mtime, so this cannot
+        // happen; treat it as the internal error it would be.
+        err @ (FileProcessError::Io(_)
+        | FileProcessError::Worker(_)
+        | FileProcessError::Unchanged) => {
+            tracing::error!(error = ?err, "file rescan failed");
+            ApiError::internal("Failed to process the file")
+        }
+    }
+}
+
+/// Like a scan, stores only the visuals the item does not have yet.
+async fn store_missing_visuals(
+    conn: &mut sqlx::SqliteConnection,
+    index_db: &str,
+    file_data: &FileWriteData,
+) -> ApiResult<()> {
+    if !file_data.thumbnails.is_empty()
+        && !has_thumbnail(conn, &file_data.sha256, THUMBNAIL_PROCESS_VERSION).await?
+    {
+        store_thumbnails(index_db, file_data).await?;
+    }
+    if !file_data.frames.is_empty()
+        && !has_frame(conn, &file_data.sha256, FRAME_PROCESS_VERSION).await?
+    {
+        store_frames(index_db, file_data).await?;
+    }
+    if file_data.blurhash.is_some() && !has_blurhash(conn, &file_data.sha256).await? {
+        store_blurhash(index_db, file_data).await?;
+    }
+    Ok(())
+}
+
+/// Stores freshly generated visuals over the existing ones. An image with no
+/// thumbnails is small enough to be served from its file, so storing the
+/// empty set drops stale ones; for other types an empty set means
+/// generation failed, and the old visuals are kept.
+async fn replace_visuals(
+    index_db: &str,
+    file_data: &FileWriteData,
+    is_image: bool,
+) -> ApiResult<bool> {
+    let mut replaced = false;
+    if is_image || !file_data.thumbnails.is_empty() {
+        store_thumbnails(index_db, file_data).await?;
+        replaced = true;
+    }
+    if !file_data.frames.is_empty() {
+        store_frames(index_db, file_data).await?;
+        replaced = true;
+    }
+    if file_data.blurhash.is_some() {
+        store_blurhash(index_db, file_data).await?;
+        replaced = true;
+    }
+    Ok(replaced)
+}
+
+async fn store_thumbnails(index_db: &str, file_data: &FileWriteData) -> ApiResult<()> {
+    call_index_db_writer(index_db, |reply| IndexDbWriterMessage::StoreThumbnails {
+        sha256: file_data.sha256.clone(),
+        mime_type: file_data.mime_type.clone(),
+        process_version: THUMBNAIL_PROCESS_VERSION,
+        thumbnails: file_data.thumbnails.clone(),
+        reply,
+    })
+    .await
+}
+
+async fn store_frames(index_db: &str, file_data: &FileWriteData) -> ApiResult<()> {
+    call_index_db_writer(index_db, |reply| IndexDbWriterMessage::StoreFrames {
+        sha256: file_data.sha256.clone(),
+        mime_type: file_data.mime_type.clone(),
+        process_version: FRAME_PROCESS_VERSION,
+        frames: file_data.frames.clone(),
+        reply,
+    })
+    .await
+}
+
+async fn store_blurhash(index_db: &str, file_data: &FileWriteData) -> ApiResult<()> {
+    let Some(blurhash) = &file_data.blurhash else {
+        return Ok(());
+    };
+    call_index_db_writer(index_db, |reply| IndexDbWriterMessage::SetBlurhash {
+        sha256: file_data.sha256.clone(),
+        blurhash: blurhash.clone(),
+        reply,
+    })
+    .await
+}
+
+/// None of the item's files is on disk any more: marks every recorded one
+/// unavailable.
+async fn mark_item_unavailable(
+    conn: &mut sqlx::SqliteConnection,
+    index_db: &str,
+    sha256: &str,
+) -> ApiResult<FileRescanOutcome> {
+    let files: Vec<(String, String)> = sqlx::query_as(
+        "SELECT path, path_key FROM files WHERE sha256 = ? AND available = TRUE ORDER BY path",
+    )
+    .bind(sha256)
+    .fetch_all(conn)
+    .await
+    .map_err(|err| {
+        tracing::error!(error = %err, "failed to read files for sha256");
+        ApiError::internal("Failed to read file metadata")
+    })?;
+    let Some((first, _)) = files.first().cloned() else {
+        return Err(ApiError::not_found("No available file for this item"));
+    };
+    let path_keys = files.into_iter().map(|(_, path_key)| path_key).collect();
+    mark_paths_unavailable(index_db, sha256.to_string(), &first, path_keys).await
+}
+
+/// Records a synthetic scan of `scan_path` that marks the files with
+/// `path_keys` unavailable.
+async fn mark_paths_unavailable(
+    index_db: &str,
+    sha256: String,
+    scan_path: &str,
+    path_keys: Vec<String>,
+) -> ApiResult<FileRescanOutcome> {
+    let scan_time = current_iso_timestamp();
+    let scan_id = call_index_db_writer(index_db, |reply| IndexDbWriterMessage::AddFileScan {
+        scan_time: scan_time.clone(),
+        path: scan_path.to_string(),
+        reply,
+    })
+    .await?;
+    let mut marked = 0;
+    for path_key in path_keys {
+        let updated = call_index_db_writer(index_db, |reply| {
+            IndexDbWriterMessage::MarkFileUnavailable {
+                path_key: path_key.clone(),
+                reply,
+            }
+        })
+        .await?;
+        marked += i64::from(updated);
+    }
+    call_index_db_writer(index_db, |reply| IndexDbWriterMessage::UpdateFileScan {
+        scan_id,
+        update: FileScanUpdate {
+            end_time: Some(current_iso_timestamp()),
+            new_items: 0,
+            unchanged_files: 0,
+            new_files: 0,
+            modified_files: 0,
+            marked_unavailable: marked,
+            errors: 0,
+            timeouts: 0,
+            undecodable: 0,
+            deferred: 0,
+            ignored_dirs: 0,
+            ignored_dir_files: 0,
+            ignored_files: 0,
+            visuals_deferred: 0,
+            skipped_dirs: 0,
+            worker_count: 1,
+            total_available: 0,
+            false_changes: 0,
+            metadata_time: 0.0,
+            hashing_time: 0.0,
+            thumbgen_time: 0.0,
+            blurhash_time: 0.0,
+        },
+        reply,
+    })
+    .await?;
+    Ok(FileRescanOutcome {
+        status: FileRescanStatus::Unavailable,
+        path: scan_path.to_string(),
+        scan_id,
+        sha256,
+        item_inserted: false,
+        file_inserted: false,
+        content_changed: false,
+        visuals_replaced: false,
+        undecodable: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::migrations::migrate_databases_on_disk;
+    use crate::db::open_index_db_read_no_user_data;
+    use crate::test_utils::test_data_dir;
+
+    // Ensures a rescan indexes a new file, a forced one replaces its visuals
+    // in place, and a file gone from disk is marked unavailable.
+    #[tokio::test]
+    async fn rescan_indexes_refreshes_and_marks_missing_files() {
+        let test_env = test_data_dir();
+        let index_db = "file_rescan_index".to_string();
+        migrate_databases_on_disk(Some(&index_db), None)
+            .await
+            .unwrap();
+        let folder = test_env.path().join("photos");
+        std::fs::create_dir_all(&folder).unwrap();
+        let image_path = folder.join("rescan.png");
+        image::RgbImage::from_pixel(32, 32, image::Rgb([40, 200, 40]))
+            .save(&image_path)
+            .unwrap();
+        let config = SystemConfig {
+            included_folders: vec![folder.to_string_lossy().to_string()],
+            ..SystemConfig::default()
+        };
+        let path = image_path.to_string_lossy().to_string();
+
+        let mut conn = open_index_db_read_no_user_data(&index_db).await.unwrap();
+        let first = rescan_file(
+            &mut conn,
+            &index_db,
+            &config,
+            RescanTarget::Path(path.clone()),
+            false,
+        )
+        .await
+        .unwrap();
+        assert_eq!(first.status, FileRescanStatus::Indexed);
+        assert!(first.item_inserted && first.file_inserted);
+        assert!(!first.visuals_replaced);
+
+        let forced = rescan_file(
+            &mut conn,
+            &index_db,
+            &config,
+            RescanTarget::Sha256(first.sha256.clone()),
+            true,
+        )
+        .await
+        .unwrap();
+        assert_eq!(forced.sha256, first.sha256);
+        assert!(!forced.item_inserted && !forced.file_inserted && !forced.content_changed);
+        assert!(forced.visuals_replaced);
+        assert_ne!(forced.scan_id, first.scan_id);
+
+        std::fs::remove_file(&image_path).unwrap();
+        let missing = rescan_file(
+            &mut conn,
+            &index_db,
+            &config,
+            RescanTarget::Sha256(first.sha256.clone()),
+            false,
+        )
+        .await
+        .unwrap();
+        assert_eq!(missing.status, FileRescanStatus::Unavailable);
+        assert_eq!(missing.path, path);
+        let (available, marked): (bool, i64) = sqlx::query_as(
+            r#"
+            SELECT files.available, file_scans.marked_unavailable
+            FROM files, file_scans
+            WHERE files.path = ?1 AND file_scans.id = ?2
+            "#,
+        )
+        .bind(&path)
+        .bind(missing.scan_id)
+        .fetch_one(&mut conn)
+        .await
+        .unwrap();
+        assert!(!available);
+        assert_eq!(marked, 1);
+    }
+
+    // Ensures paths a folder scan would never index are rejected up front.
+    #[tokio::test]
+    async fn rescan_rejects_paths_outside_included_folders() {
+        let test_env = test_data_dir();
+        let index_db = "file_rescan_reject_index".to_string();
+        migrate_databases_on_disk(Some(&index_db), None)
+            .await
+            .unwrap();
+        let folder = test_env.path().join("included");
+        let config = SystemConfig {
+            included_folders: vec![folder.to_string_lossy().to_string()],
+            excluded_folders: vec![folder.join("skip").to_string_lossy().to_string()],
+            ..SystemConfig::default()
+        };
+
+        let mut conn = open_index_db_read_no_user_data(&index_db).await.unwrap();
+        for path in [
+            test_env.path().join("elsewhere.png"),
+            folder.join("skip/a.png"),
+            folder.join("notes.txt"),
+        ] {
+            let err = rescan_file(
+                &mut conn,
+                &index_db,
+                &config,
+                RescanTarget::Path(path.to_string_lossy().to_string()),
+                false,
+            )
+            .await
+            .unwrap_err();
+            assert_eq!(err.status(), StatusCode::BAD_REQUEST);
+        }
+    }
+}
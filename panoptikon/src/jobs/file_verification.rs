@@ -0,0 +1,474 @@
+//! Bit-rot verification: re-reads indexed files and checks that they still
+//! hash to the sha256 stored when they were scanned.
+//!
+//! Only available files are checked, optionally limited to a path prefix,
+//! to files modified on or after a cutoff, and to a maximum count per run,
+//! so large libraries can be verified incrementally. Reads are throttled to
+//! a MB/s cap to keep slow network storage usable while a run is going.
+//!
+//! A file whose mtime no longer matches the index was edited, not corrupted:
+//! it is counted as `changed` and left to the next scan. Mismatched and
+//! unreadable files are recorded in `file_verification_results`; nothing is
+//! ever modified, re-hashed into the index or marked unavailable.
+
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::api_error::ApiError;
+use crate::db::extraction_write::current_iso_timestamp;
+use crate::db::file_verification::{
+    NewVerificationResult, VerificationCandidate, VerificationRunUpdate,
+    get_verification_candidates,
+};
+use crate::db::index_writer::{IndexDbWriterMessage, call_index_db_writer};
+use crate::db::open_index_db_read_no_user_data;
+use crate::db::raw_paths::disk_path;
+use crate::db::system_config::SystemConfigStore;
+use crate::jobs::files::get_last_modified_time_and_size;
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+/// Candidates fetched per read; the connection is not held while hashing.
+const CANDIDATE_PAGE_SIZE: i64 = 500;
+/// Files between progress writes of the run's counters.
+const PROGRESS_INTERVAL: i64 = 100;
+const READ_BUFFER_BYTES: usize = 256 * 1024;
+const BYTES_PER_MB: f64 = 1_000_000.0;
+
+/// Options of one verification run, stored as the job's metadata.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct VerificationOptions {
+    #[serde(default)]
+    pub path_prefix: Option<String>,
+    /// ISO-8601 (`YYYY-MM-DDTHH:MM:SS`); only files last modified at or
+    /// after it are checked.
+    #[serde(default)]
+    pub modified_since: Option<String>,
+    #[serde(default)]
+    pub max_files: Option<i64>,
+    /// None = the DB's `verify_max_mb_per_sec`; 0 = unthrottled.
+    #[serde(default)]
+    pub max_mb_per_sec: Option<f64>,
+}
+
+/// Accepts `YYYY-MM-DD` or `YYYY-MM-DDTHH:MM:SS` and returns the latter, the
+/// format of `files.last_modified`, so the cutoff compares as a string.
+pub(crate) fn normalize_modified_since(value: &str) -> Option<String> {
+    let value = value.trim();
+    let full = match value.len() {
+        10 => format!("{value}T00:00:00"),
+        19 => value.replacen(' ', "T", 1),
+        _ => return None,
+    };
+    let valid = full.char_indices().all(|(index, ch)| match index {
+        4 | 7 => ch == '-',
+        10 => ch == 'T',
+        13 | 16 => ch == ':',
+        _ => ch.is_ascii_digit(),
+    });
+    valid.then_some(full)
+}
+
+/// Paces reads to an average rate over the whole run.
+struct Throttle {
+    bytes_per_sec: Option<f64>,
+    start: Instant,
+    bytes: u64,
+}
+
+impl Throttle {
+    fn new(max_mb_per_sec: f64) -> Self {
+        Self {
+            bytes_per_sec: (max_mb_per_sec > 0.0).then_some(max_mb_per_sec * BYTES_PER_MB),
+            start: Instant::now(),
+            bytes: 0,
+        }
+    }
+
+    /// Records `read` more bytes and returns how long to wait so the total
+    /// stays under the cap, given `elapsed` time since the run started.
+    fn delay_after(&mut self, read: usize, elapsed: Duration) -> Duration {
+        self.bytes += read as u64;
+        let Some(bytes_per_sec) = self.bytes_per_sec else {
+            return Duration::ZERO;
+        };
+        let due = Duration::from_secs_f64(self.bytes as f64 / bytes_per_sec);
+        due.saturating_sub(elapsed)
+    }
+
+    fn wait_after(&mut self, read: usize) {
+        let delay = self.delay_after(read, self.start.elapsed());
+        if !delay.is_zero() {
+            std::thread::sleep(delay);
+        }
+    }
+}
+
+/// Streams a file through sha256 under the throttle; returns the hex digest
+/// and the bytes read.
+fn hash_file_throttled(path: &Path, throttle: &mut Throttle) -> io::Result<(String, u64)> {
+    let mut file = fs::File::open(path)?;
+    let mut sha = Sha256::new();
+    let mut buffer = vec![0u8; READ_BUFFER_BYTES];
+    let mut total = 0_u64;
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        total += read as u64;
+        sha.update(&buffer[..read]);
+        throttle.wait_after(read);
+    }
+    Ok((format!("{:x}", sha.finalize()), total))
+}
+
+enum Outcome {
+    Verified,
+    Mismatched(String),
+    Unreadable(String),
+    Missing,
+    Changed,
+}
+
+/// Checks one file; blocking. The mtime is compared before and after
+/// reading so that an edit racing the read is not reported as corruption.
+fn verify_file(candidate: &VerificationCandidate, throttle: &mut Throttle) -> (Outcome, u64) {
+    let path = disk_path(&candidate.path, candidate.path_bytes.as_deref());
+    let path = path.as_path();
+    let unchanged = |path: &Path| -> io::Result<bool> {
+        let (last_modified, _) = get_last_modified_time_and_size(path)?;
+        Ok(last_modified == candidate.last_modified)
+    };
+    let io_outcome = |err: io::Error| match err.kind() {
+        io::ErrorKind::NotFound => Outcome::Missing,
+        _ => Outcome::Unreadable(err.to_string()),
+    };
+
+    match unchanged(path) {
+        Ok(true) => {}
+        Ok(false) => return (Outcome::Changed, 0),
+        Err(err) => return (io_outcome(err), 0),
+    }
+    let (sha256, bytes) = match hash_file_throttled(path, throttle) {
+        Ok(hashed) => hashed,
+        Err(err) => return (io_outcome(err), 0),
+    };
+    let outcome = match unchanged(path) {
+        Ok(true) if sha256 == candidate.sha256 => Outcome::Verified,
+        Ok(true) => Outcome::Mismatched(sha256),
+        Ok(false) => Outcome::Changed,
+        Err(err) => io_outcome(err),
+    };
+    (outcome, bytes)
+}
+
+/// Runs one verification pass over `index_db` and returns its run ID.
+pub(crate) async fn run_verification_job(
+    index_db: &str,
+    options: VerificationOptions,
+) -> ApiResult<i64> {
+    let max_mb_per_sec = match options.max_mb_per_sec {
+        Some(value) => value,
+        None => {
+            SystemConfigStore::from_env()
+                .read(index_db)?
+                .verify_max_mb_per_sec
+        }
+    };
+    let start_time = current_iso_timestamp();
+    let run_id = call_index_db_writer(index_db, |reply| IndexDbWriterMessage::AddVerificationRun {
+        start_time: start_time.clone(),
+        path_prefix: options.path_prefix.clone(),
+        modified_since: options.modified_since.clone(),
+        max_files: options.max_files,
+        max_mb_per_sec,
+        reply,
+    })
+    .await?;
+
+    let mut totals = VerificationRunUpdate::default();
+    let mut throttle = Throttle::new(max_mb_per_sec);
+    let mut after_id = 0;
+    loop {
+        let remaining = options
+            .max_files
+            .map_or(CANDIDATE_PAGE_SIZE, |max| max - totals.checked);
+        if remaining <= 0 {
+            break;
+        }
+        let candidates = {
+            let mut conn = open_index_db_read_no_user_data(index_db).await?;
+            get_verification_candidates(
+                &mut conn,
+                options.path_prefix.as_deref(),
+                options.modified_since.as_deref(),
+                after_id,
+                remaining.min(CANDIDATE_PAGE_SIZE),
+            )
+            .await?
+        };
+        let Some(last) = candidates.last() else {
+            break;
+        };
+        after_id = last.file_id;
+
+        for candidate in candidates {
+            let (outcome, bytes, candidate, returned) = tokio::task::spawn_blocking(move || {
+                let (outcome, bytes) = verify_file(&candidate, &mut throttle);
+                (outcome, bytes, candidate, throttle)
+            })
+            .await
+            .map_err(|err| ApiError::internal(format!("Verification task failed: {err}")))?;
+            throttle = returned;
+
+            totals.checked += 1;
+            totals.bytes_read += bytes as i64;
+            let failure = match outcome {
+                Outcome::Verified => {
+                    totals.verified += 1;
+                    None
+                }
+                Outcome::Missing => {
+                    totals.missing += 1;
+                    None
+                }
+                Outcome::Changed => {
+                    totals.changed += 1;
+                    None
+                }
+                Outcome::Mismatched(actual) => {
+                    totals.mismatched += 1;
+                    Some(("mismatch", Some(actual), None))
+                }
+                Outcome::Unreadable(error) => {
+                    totals.unreadable += 1;
+                    Some(("unreadable", None, Some(error)))
+                }
+            };
+            if let Some((status, actual_sha256, error)) = failure {
+                tracing::warn!(
+                    index_db,
+                    path = candidate.path,
+                    status,
+                    error,
+                    "file failed verification"
+                );
+                let result = NewVerificationResult {
+                    file_id: candidate.file_id,
+                    path: candidate.path,
+                    status,
+                    expected_sha256: candidate.sha256,
+                    actual_sha256,
+                    error,
+                    checked_at: current_iso_timestamp(),
+                };
+                call_index_db_writer(index_db, |reply| {
+                    IndexDbWriterMessage::AddVerificationResult {
+                        run_id,
+                        result: result.clone(),
+                        reply,
+                    }
+                })
+                .await?;
+            }
+            // Progress, so a running (or cancelled) run shows how far it got.
+            if totals.checked % PROGRESS_INTERVAL == 0 {
+                write_totals(index_db, run_id, &totals).await?;
+            }
+        }
+    }
+
+    totals.end_time = Some(current_iso_timestamp());
+    write_totals(index_db, run_id, &totals).await?;
+    tracing::info!(
+        index_db,
+        run_id,
+        checked = totals.checked,
+        verified = totals.verified,
+        mismatched = totals.mismatched,
+        unreadable = totals.unreadable,
+        missing = totals.missing,
+        changed = totals.changed,
+        bytes_read = totals.bytes_read,
+        "file verification finished"
+    );
+    Ok(run_id)
+}
+
+async fn write_totals(
+    index_db: &str,
+    run_id: i64,
+    totals: &VerificationRunUpdate,
+) -> ApiResult<()> {
+    call_index_db_writer(index_db, |reply| {
+        IndexDbWriterMessage::UpdateVerificationRun {
+            run_id,
+            update: totals.clone(),
+            reply,
+        }
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::file_verification::{get_verification_results, get_verification_runs};
+    use crate::db::migrations::migrate_databases_on_disk;
+    use crate::test_utils::test_data_dir;
+
+    #[test]
+    fn throttle_delays_reads_to_the_cap() {
+        let mut throttle = Throttle::new(1.0);
+        // 500 KB at 1 MB/s is due at 0.5s; 0.2s have passed.
+        let delay = throttle.delay_after(500_000, Duration::from_millis(200));
+        assert_eq!(delay, Duration::from_millis(300));
+        // Ahead of schedule: no wait.
+        let delay = throttle.delay_after(100_000, Duration::from_secs(2));
+        assert_eq!(delay, Duration::ZERO);
+
+        let mut unthrottled = Throttle::new(0.0);
+        assert_eq!(
+            unthrottled.delay_after(usize::MAX, Duration::ZERO),
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn modified_since_accepts_dates_and_datetimes() {
+        assert_eq!(
+            normalize_modified_since("2024-05-01").as_deref(),
+            Some("2024-05-01T00:00:00")
+        );
+        assert_eq!(
+            normalize_modified_since("2024-05-01 12:30:00").as_deref(),
+            Some("2024-05-01T12:30:00")
+        );
+        assert_eq!(normalize_modified_since("2024-5-1"), None);
+        assert_eq!(normalize_modified_since("yesterday"), None);
+    }
+
+    /// One file per outcome, plus one outside the path prefix. Nothing in
+    /// `files` changes, and only the mismatch is recorded as a result.
+    #[tokio::test]
+    async fn verification_reports_mismatches_without_touching_files() {
+        let test_env = test_data_dir();
+        let index_db = "file_verification_index".to_string();
+        migrate_databases_on_disk(Some(&index_db), None)
+            .await
+            .unwrap();
+
+        let media = test_env.path().join("verify_media");
+        let other = test_env.path().join("verify_other");
+        fs::create_dir_all(&media).unwrap();
+        fs::create_dir_all(&other).unwrap();
+        let sha = |content: &[u8]| format!("{:x}", Sha256::digest(content));
+        let write = |path: &Path, content: &[u8]| {
+            fs::write(path, content).unwrap();
+            get_last_modified_time_and_size(path).unwrap().0
+        };
+        let intact = media.join("intact.bin");
+        let rotten = media.join("rotten.bin");
+        let edited = media.join("edited.bin");
+        let gone = media.join("gone.bin");
+        let elsewhere = other.join("elsewhere.bin");
+        let files = [
+            (&intact, write(&intact, b"intact"), sha(b"intact")),
+            (&rotten, write(&rotten, b"r0tten"), sha(b"rotten")),
+            (&edited, "2000-01-01T00:00:00".to_string(), sha(b"edited")),
+            (&gone, "2000-01-01T00:00:00".to_string(), sha(b"gone")),
+            (&elsewhere, write(&elsewhere, b"x"), sha(b"y")),
+        ];
+        write(&edited, b"edited later");
+
+        let mut conn = crate::db::open_index_db_write_no_user_data(&index_db)
+            .await
+            .unwrap();
+        sqlx::query(
+            "INSERT INTO file_scans (id, start_time, path) VALUES (1, '2024-01-01T00:00:00', '/')",
+        )
+        .execute(&mut conn)
+        .await
+        .unwrap();
+        for (id, (path, last_modified, sha256)) in files.iter().enumerate() {
+            let path = path.to_string_lossy();
+            sqlx::query(
+                "INSERT INTO items (id, sha256, md5, type, time_added) VALUES (?1, ?2, 'md5', 'application/octet-stream', '2024-01-01T00:00:00')",
+            )
+            .bind(id as i64 + 1)
+            .bind(sha256)
+            .execute(&mut conn)
+            .await
+            .unwrap();
+            sqlx::query(
+                "INSERT INTO files (id, sha256, item_id, path, filename, last_modified, scan_id, available) VALUES (?1, ?2, ?1, ?3, 'f', ?4, 1, 1)",
+            )
+            .bind(id as i64 + 1)
+            .bind(sha256)
+            .bind(path.as_ref())
+            .bind(last_modified)
+            .execute(&mut conn)
+            .await
+            .unwrap();
+        }
+
+        let options = VerificationOptions {
+            path_prefix: Some(media.to_string_lossy().to_string()),
+            max_mb_per_sec: Some(0.0),
+            ..Default::default()
+        };
+        let run_id = run_verification_job(&index_db, options).await.unwrap();
+
+        let runs = get_verification_runs(&mut conn, 10).await.unwrap();
+        let run = runs.iter().find(|run| run.id == run_id).unwrap();
+        assert!(run.end_time.is_some());
+        assert_eq!(
+            (
+                run.checked,
+                run.verified,
+                run.mismatched,
+                run.missing,
+                run.changed
+            ),
+            (4, 1, 1, 1, 1)
+        );
+        assert_eq!(run.unreadable, 0);
+        assert_eq!(run.bytes_read, 12);
+
+        let results = get_verification_results(&mut conn, Some(run_id), 1, 100)
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].path, rotten.to_string_lossy());
+        assert_eq!(results[0].status, "mismatch");
+        assert_eq!(results[0].expected_sha256, sha(b"rotten"));
+        assert_eq!(
+            results[0].actual_sha256.as_deref(),
+            Some(sha(b"r0tten").as_str())
+        );
+
+        let (available, stored): (i64, String) =
+            sqlx::query_as("SELECT available, sha256 FROM files WHERE id = 2")
+                .fetch_one(&mut conn)
+                .await
+                .unwrap();
+        assert_eq!((available, stored), (1, sha(b"rotten")));
+
+        // max_files caps the run.
+        let options = VerificationOptions {
+            max_files: Some(2),
+            max_mb_per_sec: Some(0.0),
+            ..Default::default()
+        };
+        let run_id = run_verification_job(&index_db, options).await.unwrap();
+        let runs = get_verification_runs(&mut conn, 10).await.unwrap();
+        assert_eq!(runs[0].id, run_id);
+        assert_eq!(runs[0].checked, 2);
+    }
+}
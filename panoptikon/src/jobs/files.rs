@@ -1,12 +1,14 @@
 use std::{
     collections::{HashMap, HashSet},
-    env, fs,
+    env,
+    ffi::OsStr,
+    fs,
     io::{self, Read},
     path::{Component, Path, PathBuf},
     process::Command,
     sync::{
         Arc, Condvar, Mutex, OnceLock,
-        atomic::{AtomicU64, Ordering},
+        atomic::{AtomicI64, AtomicU64, Ordering},
     },
     time::{Duration, Instant},
 };
@@ -21,7 +23,7 @@ use lofty::prelude::{Accessor, TaggedFileExt};
 use md5::{Digest, Md5};
 use mime_guess::MimeGuess;
 use pdfium_render::prelude::{PdfRenderConfig, Pdfium};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use sha2::Sha256;
 use time::{OffsetDateTime, format_description::FormatItem};
 use tokio::sync::Semaphore;
@@ -33,24 +35,44 @@ use crate::{
     db::{
         file_scans::{FileScanUpdate, get_completed_scan_paths, get_open_file_scan_id},
         files::{
-            FileScanData, FileUpsertResult, ItemScanMeta, get_file_by_path, get_item_id,
-            get_item_dimensions, get_item_visual_meta, has_blurhash,
+            FilePathRecord, FileScanData, FileUpsertResult, ItemScanMeta, get_file_by_path,
+            get_item_id, get_item_dimensions, get_item_visual_meta, has_blurhash,
         },
         folders::get_folders_from_database,
         index_writer::{IndexDbWriterMessage, call_index_db_writer},
         open_index_db_read,
-        storage::{StoredImage, get_frames_bytes, get_thumbnail_bytes, has_frame, has_thumbnail},
+        path_keys::PathKeys,
+        raw_paths::{display_path, path_bytes},
+        storage::{
+            StoredImage, get_frames_bytes, get_thumbnail_bytes, has_frame, has_thumbnail,
+            visuals_dir,
+        },
         system_config::{SystemConfig, SystemConfigStore},
     },
-    jobs::timing::PhaseTimer,
-    pql::builder::filters::evaluate_match,
-    pql::model::{Match, MatchValue},
+    jobs::{
+        dir_snapshots::DirSnapshotWalk,
+        ignore_files::IgnoreFiles,
+        implicit_exclusions::{applied_implicit_exclusions, implicit_excluded_roots},
+        notifications::{self, ScanCounts},
+        scan_io::folder_worker_count,
+        symlinks::{SymlinkPolicy, SymlinkWalk},
+        timing::PhaseTimer,
+    },
+    media_tools::{self, MediaTool, MediaToolError},
+    pql::builder::{
+        filters::{evaluate_match, match_columns},
+        is_derived_column,
+    },
+    pql::model::{Column, Match, MatchValue},
+    video_frames::{self, FrameExtractionError},
 };
 
 type ApiResult<T> = std::result::Result<T, ApiError>;
 
 #[derive(Clone, Copy)]
 pub(crate) struct ScanOptions {
+    /// Workers for `ssd` and unconfigured folders; see
+    /// `SystemConfig::folder_scan_settings`.
     pub worker_count: usize,
 }
 
@@ -63,6 +85,14 @@ impl Default for ScanOptions {
     }
 }
 
+/// Folder rescan job options, stored as the queued job's metadata.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub(crate) struct FolderRescanOptions {
+    /// Do not skip unchanged directories this time.
+    #[serde(default)]
+    pub force_full: bool,
+}
+
 pub(crate) struct RescanResult {
     // Only read by tests; production callers ignore the result.
     #[allow(dead_code)]
@@ -82,6 +112,8 @@ pub(crate) struct FileScanService {
     user_data_db: String,
     config_store: SystemConfigStore,
     options: ScanOptions,
+    /// Visit every file even with `SystemConfig::skip_unchanged_dirs` on.
+    force_full: bool,
 }
 
 impl FileScanService {
@@ -91,9 +123,15 @@ impl FileScanService {
             user_data_db: user_data_db.into(),
             config_store: SystemConfigStore::from_env(),
             options: ScanOptions::default(),
+            force_full: false,
         }
     }
 
+    pub(crate) fn with_force_full(mut self, force_full: bool) -> Self {
+        self.force_full = force_full;
+        self
+    }
+
     // Only used by tests, which need an explicit data_dir and worker count.
     #[allow(dead_code)]
     pub(crate) fn new(
@@ -107,6 +145,7 @@ impl FileScanService {
             user_data_db: user_data_db.into(),
             config_store: SystemConfigStore::new(data_dir),
             options,
+            force_full: false,
         }
     }
 
@@ -121,16 +160,71 @@ impl FileScanService {
         let excluded_folders = get_folders_from_database(&mut conn, false).await?;
         drop(conn);
 
+        let implicit_roots = implicit_excluded_roots(self.config_store.data_dir());
+        applied_implicit_exclusions(&implicit_roots, &included_folders);
         let scan_ids = execute_folder_scan(
             &self.index_db,
             &self.user_data_db,
             &config,
             &included_folders,
             &excluded_folders,
+            &implicit_roots,
+            self.options,
+            self.force_full,
+        )
+        .await?;
+        self.clean_up_after_scan(&config).await?;
+        Ok(RescanResult { scan_ids })
+    }
+
+    /// Scans one folder, which must be an included folder or lie inside
+    /// one, then runs the same cleanup as a full rescan. Files outside
+    /// `folder` are left as they are, unless the folder lists changed since
+    /// the last scan: the folder update then rescans them all first. Used by
+    /// the `scan` CLI subcommand.
+    pub(crate) async fn scan_folder(&self, folder: &str) -> ApiResult<RescanResult> {
+        let config = self.config_store.load(&self.index_db)?;
+        if is_resync_needed(&self.index_db, &self.user_data_db, &config).await? {
+            let _ = self.run_folder_update().await?;
+        }
+
+        let mut conn = open_index_db_read(&self.index_db, &self.user_data_db).await?;
+        let included_folders = get_folders_from_database(&mut conn, true).await?;
+        let excluded_folders = get_folders_from_database(&mut conn, false).await?;
+        drop(conn);
+
+        let target = normalize_path(folder, true);
+        let included = included_folders
+            .iter()
+            .any(|included| target.starts_with(normalize_path(included, true)));
+        let excluded_paths = excluded_folders
+            .iter()
+            .map(|excluded| normalize_path(excluded, true))
+            .collect::<Vec<_>>();
+        if !included || is_excluded(&target, &excluded_paths) {
+            return Err(ApiError::bad_request(format!(
+                "{folder} is not inside an included folder; add it to included_folders first"
+            )));
+        }
+
+        let implicit_roots = implicit_excluded_roots(self.config_store.data_dir());
+        let scan_ids = execute_folder_scan(
+            &self.index_db,
+            &self.user_data_db,
+            &config,
+            &[target.to_string_lossy().to_string()],
+            &excluded_folders,
+            &implicit_roots,
             self.options,
+            self.force_full,
         )
         .await?;
+        self.clean_up_after_scan(&config).await?;
+        Ok(RescanResult { scan_ids })
+    }
 
+    /// Deletes what a scan left unreferenced, then vacuums if anything went.
+    async fn clean_up_after_scan(&self, config: &SystemConfig) -> ApiResult<()> {
         let unavailable_files_deleted = if config.remove_unavailable_files {
             call_index_db_writer(&self.index_db, |reply| {
                 IndexDbWriterMessage::DeleteUnavailableFiles { reply }
@@ -139,6 +233,13 @@ impl FileScanService {
         } else {
             0
         };
+        let removed_files_purged = call_index_db_writer(&self.index_db, |reply| {
+            IndexDbWriterMessage::PurgeRemovedFiles {
+                grace_days: config.folder_removal_grace_days,
+                reply,
+            }
+        })
+        .await?;
         let rule_files_deleted = call_index_db_writer(&self.index_db, |reply| {
             IndexDbWriterMessage::DeleteFilesNotAllowed {
                 job_filters: config.job_filters.clone(),
@@ -163,13 +264,13 @@ impl FileScanService {
         .await?;
 
         let vacuum = unavailable_files_deleted > 0
+            || removed_files_purged > 0
             || rule_files_deleted > 0
             || orphan_items_deleted > 0
             || orphan_frames_deleted > 0
             || orphan_thumbnails_deleted > 0;
         run_post_job_maintenance(&self.index_db, vacuum).await;
-
-        Ok(RescanResult { scan_ids })
+        Ok(())
     }
 
     pub(crate) async fn run_folder_update(&self) -> ApiResult<FolderUpdateResult> {
@@ -223,6 +324,19 @@ impl FileScanService {
             .await?;
         }
 
+        // Files of a re-added (or no longer excluded) folder that are still
+        // within the grace period come back before the scan revisits them.
+        let restored_files = call_index_db_writer(&self.index_db, |reply| {
+            IndexDbWriterMessage::RestoreRemovedFiles { reply }
+        })
+        .await?;
+        if restored_files > 0 {
+            tracing::info!(
+                restored_files,
+                "restored files removed by an earlier folder update"
+            );
+        }
+
         // Folder registration and scanning are separate committed writes, so
         // a folder update that failed mid-scan leaves folders registered but
         // never scanned — and re-running the update would skip them, since
@@ -253,13 +367,19 @@ impl FileScanService {
             }
         }
 
+        // The enqueue handler reports the applied exclusions; here they are
+        // only logged.
+        let implicit_roots = implicit_excluded_roots(self.config_store.data_dir());
+        applied_implicit_exclusions(&implicit_roots, &config.included_folders);
         let scan_ids = execute_folder_scan(
             &self.index_db,
             &self.user_data_db,
             &config,
             &included_added,
             &config.excluded_folders,
+            &implicit_roots,
             self.options,
+            self.force_full,
         )
         .await?;
 
@@ -271,12 +391,29 @@ impl FileScanService {
         } else {
             0
         };
+        // Within the grace period files leaving the indexed folders are only
+        // marked removed, so an accidental folder removal can be undone.
+        let soft = config.folder_removal_grace_days > 0;
         let excluded_folder_files_deleted = call_index_db_writer(&self.index_db, |reply| {
-            IndexDbWriterMessage::DeleteFilesUnderExcludedFolders { reply }
+            IndexDbWriterMessage::DeleteFilesUnderExcludedFolders { soft, reply }
         })
         .await?;
         let orphan_files_deleted = call_index_db_writer(&self.index_db, |reply| {
-            IndexDbWriterMessage::DeleteFilesNotUnderIncludedFolders { reply }
+            IndexDbWriterMessage::DeleteFilesNotUnderIncludedFolders { soft, reply }
+        })
+        .await?;
+        if soft && excluded_folder_files_deleted + orphan_files_deleted > 0 {
+            tracing::info!(
+                files = excluded_folder_files_deleted + orphan_files_deleted,
+                grace_days = config.folder_removal_grace_days,
+                "marked files outside the indexed folders as removed"
+            );
+        }
+        let removed_files_purged = call_index_db_writer(&self.index_db, |reply| {
+            IndexDbWriterMessage::PurgeRemovedFiles {
+                grace_days: config.folder_removal_grace_days,
+                reply,
+            }
         })
         .await?;
         let rule_files_deleted = call_index_db_writer(&self.index_db, |reply| {
@@ -303,8 +440,8 @@ impl FileScanService {
         .await?;
 
         let vacuum = unavailable_files_deleted > 0
-            || excluded_folder_files_deleted > 0
-            || orphan_files_deleted > 0
+            || (!soft && (excluded_folder_files_deleted > 0 || orphan_files_deleted > 0))
+            || removed_files_purged > 0
             || rule_files_deleted > 0
             || orphan_items_deleted > 0
             || orphan_frames_deleted > 0
@@ -336,7 +473,7 @@ pub(crate) async fn is_resync_needed(
     Ok(current_included != new_included || current_excluded != new_excluded)
 }
 
-/// Post-job VACUUM/ANALYZE. Failures are logged but never fail the job: the
+/// Post-job VACUUM/ANALYZE and WAL checkpoint. Failures are logged but never fail the job: the
 /// job's own work has already been committed at this point.
 pub(crate) async fn run_post_job_maintenance(index_db: &str, vacuum: bool) {
     if vacuum {
@@ -351,15 +488,27 @@ pub(crate) async fn run_post_job_maintenance(index_db: &str, vacuum: bool) {
     {
         tracing::error!(error = ?err, index_db, "failed to analyze index database");
     }
+    // Auto-checkpoints never shrink the -wal file, which otherwise stays at
+    // the size of the largest job's writes (or of the VACUUM copy).
+    if let Err(err) = call_index_db_writer(index_db, |reply| IndexDbWriterMessage::WalCheckpoint {
+        reply,
+    })
+    .await
+    {
+        tracing::error!(error = ?err, index_db, "failed to checkpoint index database WAL");
+    }
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn execute_folder_scan(
     index_db: &str,
     user_data_db: &str,
     config: &SystemConfig,
     included_folders: &[String],
     excluded_folders: &[String],
+    implicit_roots: &[PathBuf],
     options: ScanOptions,
+    force_full: bool,
 ) -> ApiResult<Vec<i64>> {
     let mut conn = open_index_db_read(index_db, user_data_db).await?;
     let mut all_included = Vec::new();
@@ -395,6 +544,28 @@ async fn execute_folder_scan(
     let scan_time = current_iso_timestamp();
     let mut scan_ids = Vec::new();
 
+    let excluded_paths = excluded_folders
+        .iter()
+        .map(|folder| normalize_path(folder, true))
+        .chain(implicit_roots.iter().cloned())
+        .collect::<Vec<_>>();
+    let roots = starting_points
+        .iter()
+        .map(|folder| normalize_path(folder, true))
+        .collect::<Vec<_>>();
+    let symlink_policy = SymlinkPolicy::new(config, &roots, &excluded_paths);
+    // Shared by all folders, so a tree bind-mounted into two of them is only
+    // walked once.
+    let symlink_walk = symlink_policy.walk();
+    // Rows indexed before a folder was found to be case-insensitive (or
+    // under another setting) get keys matching the current one first.
+    let path_keys = PathKeys::new(config);
+    call_index_db_writer(index_db, |reply| IndexDbWriterMessage::RekeyFilePaths {
+        keys: path_keys.clone(),
+        reply,
+    })
+    .await?;
+
     for folder in starting_points {
         let scan_id = call_index_db_writer(index_db, |reply| IndexDbWriterMessage::AddFileScan {
             scan_time: scan_time.clone(),
@@ -404,20 +575,19 @@ async fn execute_folder_scan(
         .await?;
         scan_ids.push(scan_id);
 
-        let excluded_paths = excluded_folders
-            .iter()
-            .map(|folder| normalize_path(folder, true))
-            .collect::<Vec<_>>();
-
+        let started = Instant::now();
         let stats = scan_single_folder(
             index_db,
             user_data_db,
             config,
             &folder,
             &excluded_paths,
+            &symlink_walk,
+            &path_keys,
             scan_id,
             &scan_time,
             options,
+            force_full,
         )
         .await?;
 
@@ -431,6 +601,15 @@ async fn execute_folder_scan(
                 modified_files: stats.modified_files,
                 marked_unavailable: stats.marked_unavailable,
                 errors: stats.errors,
+                timeouts: stats.timeouts,
+                undecodable: stats.undecodable,
+                deferred: stats.deferred,
+                ignored_dirs: stats.ignored_dirs,
+                ignored_dir_files: stats.ignored_dir_files,
+                ignored_files: stats.ignored_files,
+                visuals_deferred: stats.visuals_deferred,
+                skipped_dirs: stats.skipped_dirs,
+                worker_count: stats.worker_count,
                 total_available: stats.total_available,
                 false_changes: stats.false_changes,
                 metadata_time: stats.metadata_time,
@@ -441,12 +620,40 @@ async fn execute_folder_scan(
             reply,
         })
         .await?;
+        notifications::notify_scan_finished(
+            index_db,
+            &folder,
+            started.elapsed(),
+            ScanCounts {
+                new_items: stats.new_items,
+                new_files: stats.new_files,
+                modified_files: stats.modified_files,
+                unchanged_files: stats.unchanged_files,
+                marked_unavailable: stats.marked_unavailable,
+                errors: stats.errors,
+                timeouts: stats.timeouts,
+                undecodable: stats.undecodable,
+            },
+            &stats.error_samples,
+        );
+    }
+    if symlink_walk.skipped() > 0 {
+        tracing::info!(
+            index_db,
+            skipped = symlink_walk.skipped(),
+            "skipped symlinks and directories reachable through another path"
+        );
     }
 
     Ok(scan_ids)
 }
 
+/// Version stored with every thumbnail batch. Bump it whenever a change to
+/// thumbnail generation should reach items that are already indexed: scans
+/// only skip items whose thumbnails are at least this version, and the
+/// visuals regeneration job rebuilds the older ones.
 pub(crate) const THUMBNAIL_PROCESS_VERSION: i64 = 1;
+/// Like [`THUMBNAIL_PROCESS_VERSION`], for extracted video frames.
 pub(crate) const FRAME_PROCESS_VERSION: i64 = 1;
 /// Minimum interval between mid-scan writes of the running counters to the
 /// file_scans row (progress display only; the final update is unconditional).
@@ -466,12 +673,23 @@ struct FolderStats {
     modified_files: i64,
     marked_unavailable: i64,
     errors: i64,
+    timeouts: i64,
+    undecodable: i64,
+    deferred: i64,
+    ignored_dirs: i64,
+    ignored_dir_files: i64,
+    ignored_files: i64,
+    visuals_deferred: i64,
+    skipped_dirs: i64,
+    worker_count: i64,
     total_available: i64,
     false_changes: i64,
     metadata_time: f64,
     hashing_time: f64,
     thumbgen_time: f64,
     blurhash_time: f64,
+    /// The first failed or skipped paths, sent with scan notifications.
+    error_samples: Vec<String>,
 }
 
 /// One [`PhaseTimer`] per timed scan phase. The stored per-scan times are the
@@ -495,12 +713,22 @@ impl FolderStats {
             modified_files: 0,
             marked_unavailable: 0,
             errors: 0,
+            timeouts: 0,
+            undecodable: 0,
+            deferred: 0,
+            ignored_dirs: 0,
+            ignored_dir_files: 0,
+            ignored_files: 0,
+            visuals_deferred: 0,
+            skipped_dirs: 0,
+            worker_count: 0,
             total_available: 0,
             false_changes: 0,
             metadata_time: 0.0,
             hashing_time: 0.0,
             thumbgen_time: 0.0,
             blurhash_time: 0.0,
+            error_samples: Vec::new(),
         }
     }
 }
@@ -526,6 +754,7 @@ struct NewItemData {
     thumbnails: Vec<StoredImage>,
     frames: Vec<StoredImage>,
     blurhash: Option<String>,
+    undecodable: bool,
 }
 
 struct BackfillResult {
@@ -534,6 +763,7 @@ struct BackfillResult {
     thumbnails: Vec<StoredImage>,
     extracted_frames: Vec<StoredImage>,
     blurhash: Option<String>,
+    undecodable: bool,
 }
 
 struct FailedFile {
@@ -553,6 +783,11 @@ struct ScanContext {
     scan_id: i64,
     scan_time: String,
     filescan_filter: Option<Arc<Match>>,
+    /// See `SystemConfig::scan_settle_secs`.
+    settle: Duration,
+    /// See `SystemConfig::fast_scan`.
+    fast_scan: bool,
+    path_keys: PathKeys,
     semaphore: Arc<Semaphore>,
     tasks: JoinSet<TaskOutcome>,
     // Path (and whether the task is a visuals backfill) per in-flight task, so
@@ -567,6 +802,8 @@ struct ScanContext {
     timers: ScanTimers,
     last_progress: Instant,
     error_paths: Vec<String>,
+    /// Files found still being written, retried after the walk.
+    deferred_paths: Vec<PathBuf>,
     conn: sqlx::SqliteConnection,
 }
 
@@ -583,33 +820,82 @@ async fn scan_single_folder(
     config: &SystemConfig,
     folder: &str,
     excluded_paths: &[PathBuf],
+    symlink_walk: &SymlinkWalk<'_>,
+    path_keys: &PathKeys,
     scan_id: i64,
     scan_time: &str,
     options: ScanOptions,
+    force_full: bool,
 ) -> ApiResult<FolderStats> {
     let allowed_extensions = build_extension_set(config);
-    let conn = open_index_db_read(index_db, user_data_db).await?;
+    let worker_count = folder_worker_count(config, Path::new(folder), options.worker_count).await;
+    tracing::info!(folder, worker_count, "scanning folder");
+    let mut conn = open_index_db_read(index_db, user_data_db).await?;
+    let mut dir_snapshots = if config.skip_unchanged_dirs {
+        Some(DirSnapshotWalk::load(&mut conn, config, folder, force_full).await?)
+    } else {
+        None
+    };
     let mut ctx = ScanContext {
         index_db: index_db.to_string(),
         scan_id,
         scan_time: scan_time.to_string(),
         filescan_filter: parse_filescan_filter(config).map(Arc::new),
-        semaphore: Arc::new(Semaphore::new(options.worker_count)),
+        settle: Duration::from_secs(config.scan_settle_secs),
+        fast_scan: config.fast_scan,
+        path_keys: path_keys.clone(),
+        semaphore: Arc::new(Semaphore::new(worker_count)),
         tasks: JoinSet::new(),
         task_paths: HashMap::new(),
         in_flight_visuals: HashSet::new(),
-        stats: FolderStats::new(),
+        stats: FolderStats {
+            worker_count: worker_count as i64,
+            ..FolderStats::new()
+        },
         timers: ScanTimers::default(),
         last_progress: Instant::now(),
         error_paths: Vec::new(),
+        deferred_paths: Vec::new(),
         conn,
     };
 
+    let ignored_dirs = IgnoredDirs::from_config(config);
+    let ignored_count = AtomicI64::new(0);
+    let ignored_file_count = AtomicI64::new(0);
+    let ignore_files = IgnoreFiles::from_config(config);
+    let ignore_file_count = AtomicI64::new(0);
     for entry in WalkDir::new(folder)
-        .follow_links(true)
+        .follow_links(config.follow_symlinks)
         .into_iter()
-        .filter_entry(|entry| !is_excluded(entry.path(), excluded_paths))
+        .filter_entry(|entry| {
+            if is_excluded(entry.path(), excluded_paths) {
+                return false;
+            }
+            let ignored = entry.depth() > 0
+                && entry.file_type().is_dir()
+                && ignored_dirs.matches_name(entry.file_name());
+            if ignored {
+                tracing::debug!(path = %entry.path().display(), "skipping ignored directory");
+                ignored_count.fetch_add(1, Ordering::Relaxed);
+                ignored_file_count.fetch_add(
+                    count_scannable_files(entry.path(), &allowed_extensions),
+                    Ordering::Relaxed,
+                );
+                return false;
+            }
+            let ignored_by_file = entry.depth() > 0
+                && entry.file_type().is_dir()
+                && ignore_files.is_ignored(Path::new(folder), entry.path(), true);
+            if ignored_by_file {
+                tracing::debug!(path = %entry.path().display(), "skipping directory listed in ignore file");
+                ignore_file_count.fetch_add(1, Ordering::Relaxed);
+            }
+            !ignored_by_file && symlink_walk.admits(entry)
+        })
     {
+        ctx.stats.ignored_dirs = ignored_count.load(Ordering::Relaxed);
+        ctx.stats.ignored_dir_files = ignored_file_count.load(Ordering::Relaxed);
+        ctx.stats.ignored_files = ignore_file_count.load(Ordering::Relaxed);
         // Drain finished work before taking on more, so completed results
         // are persisted as the walk progresses instead of piling up in memory.
         while let Some(joined) = ctx.tasks.try_join_next_with_id() {
@@ -618,16 +904,26 @@ async fn scan_single_folder(
 
         let entry = match entry {
             Ok(entry) => entry,
+            Err(err) if symlink_walk.is_cycle(&err) => continue,
             Err(err) => {
                 tracing::error!(error = %err, "error walking directory");
                 continue;
             }
         };
         if entry.file_type().is_dir() {
+            if let Some(dir_snapshots) = &mut dir_snapshots {
+                dir_snapshots.visit_dir(entry.path());
+            }
             continue;
         }
 
         let path = entry.path().to_path_buf();
+        if dir_snapshots
+            .as_ref()
+            .is_some_and(|dir_snapshots| dir_snapshots.skips(&path))
+        {
+            continue;
+        }
         if is_hidden_or_temp(&path) {
             continue;
         }
@@ -636,6 +932,12 @@ async fn scan_single_folder(
             continue;
         }
 
+        // Parent directories were already checked as the walk descended.
+        if ignore_files.is_ignored(Path::new(folder), &path, false) {
+            ignore_file_count.fetch_add(1, Ordering::Relaxed);
+            continue;
+        }
+
         ctx.scan_path(path).await?;
         ctx.maybe_report_progress().await;
     }
@@ -645,16 +947,62 @@ async fn scan_single_folder(
         ctx.maybe_report_progress().await;
     }
 
+    // Files that were still being written get a second chance once the walk
+    // is done; most copies have finished by then.
+    let deferred = std::mem::take(&mut ctx.deferred_paths);
+    if !deferred.is_empty() {
+        tracing::info!(folder, files = deferred.len(), "retrying deferred files");
+        for path in deferred {
+            ctx.scan_path(path).await?;
+        }
+        while let Some(joined) = ctx.tasks.join_next_with_id().await {
+            ctx.handle_joined(joined).await?;
+            ctx.maybe_report_progress().await;
+        }
+    }
+
+    ctx.stats.ignored_dirs = ignored_count.load(Ordering::Relaxed);
+    ctx.stats.ignored_dir_files = ignored_file_count.load(Ordering::Relaxed);
+    ctx.stats.ignored_files = ignore_file_count.load(Ordering::Relaxed);
+
     let ScanContext {
         mut stats,
         timers,
-        error_paths,
+        mut error_paths,
+        deferred_paths,
         ..
     } = ctx;
+    // Still busy after the retry: leave them to the next scan, and keep an
+    // already-indexed file that is being rewritten from being marked missing.
+    for path in deferred_paths {
+        tracing::warn!(path = %path.display(), "file is still being written, skipped");
+        error_paths.push(path.to_string_lossy().to_string());
+    }
+    stats.error_samples = error_paths
+        .iter()
+        .take(notifications::MAX_ERROR_SAMPLES)
+        .cloned()
+        .collect();
+
+    // Files of unchanged directories join this scan before the rest are
+    // marked unavailable.
+    if let Some(dir_snapshots) = &dir_snapshots {
+        stats.skipped_dirs = dir_snapshots.skipped_dirs();
+        stats.unchanged_files += call_index_db_writer(index_db, |reply| {
+            IndexDbWriterMessage::AdoptUnchangedDirFiles {
+                scan_id,
+                path: folder.to_string(),
+                dirs: dir_snapshots.unchanged_dirs(),
+                reply,
+            }
+        })
+        .await?;
+    }
 
     let (marked_unavailable, total_available) = call_index_db_writer(index_db, |reply| {
         IndexDbWriterMessage::MarkUnavailableFiles {
             scan_id,
+            seen_at: scan_time.to_string(),
             path: folder.to_string(),
             excluded_paths: error_paths.clone(),
             reply,
@@ -664,6 +1012,23 @@ async fn scan_single_folder(
     stats.marked_unavailable = marked_unavailable;
     stats.total_available = total_available;
 
+    // With the setting off, snapshots from an earlier scan are dropped so
+    // turning it back on starts from a full scan.
+    let (config_hash, snapshots) = match dir_snapshots {
+        Some(dir_snapshots) => (
+            dir_snapshots.config_hash().to_string(),
+            dir_snapshots.into_snapshots(&error_paths),
+        ),
+        None => (String::new(), Vec::new()),
+    };
+    call_index_db_writer(index_db, |reply| IndexDbWriterMessage::ReplaceDirSnapshots {
+        path: folder.to_string(),
+        config_hash: config_hash.clone(),
+        snapshots: snapshots.clone(),
+        reply,
+    })
+    .await?;
+
     // Stored times are phase wall-clock (busy); aggregate worker time only
     // goes to the log, where work / busy reads as average parallelism.
     stats.metadata_time = timers.metadata.busy_secs();
@@ -704,6 +1069,15 @@ impl ScanContext {
             modified_files: self.stats.modified_files,
             marked_unavailable: self.stats.marked_unavailable,
             errors: self.stats.errors,
+            timeouts: self.stats.timeouts,
+            undecodable: self.stats.undecodable,
+            deferred: self.stats.deferred,
+            ignored_dirs: self.stats.ignored_dirs,
+            ignored_dir_files: self.stats.ignored_dir_files,
+            ignored_files: self.stats.ignored_files,
+            visuals_deferred: self.stats.visuals_deferred,
+            skipped_dirs: self.stats.skipped_dirs,
+            worker_count: self.stats.worker_count,
             total_available: self.stats.total_available,
             false_changes: self.stats.false_changes,
             metadata_time: self.timers.metadata.busy_secs(),
@@ -759,8 +1133,9 @@ impl ScanContext {
             return Ok(());
         }
 
-        let path_str = path.to_string_lossy().to_string();
-        let existing = get_file_by_path(&mut self.conn, &path_str).await?;
+        let path_str = display_path(&path);
+        let path_key = self.path_keys.key(&path_str);
+        let existing = get_file_by_path(&mut self.conn, &path_key).await?;
 
         if let Some(existing) = &existing {
             if existing.last_modified == last_modified {
@@ -769,6 +1144,8 @@ impl ScanContext {
                     sha256: sha256.clone(),
                     last_modified: existing.last_modified.clone(),
                     path: path_str,
+                    path_bytes: path_bytes(&path),
+                    path_key,
                     new_file_hash: false,
                     file_size: None,
                     item_metadata: None,
@@ -842,15 +1219,27 @@ impl ScanContext {
                 Ok(())
             }
             TaskOutcome::Failed(failed) => {
-                self.stats.errors += 1;
-                match &failed.error {
+                match failed.error {
+                    FileProcessError::Busy => {
+                        tracing::debug!(
+                            path = %failed.path.display(),
+                            "file is still being written, deferring"
+                        );
+                        self.stats.deferred += 1;
+                        self.deferred_paths.push(failed.path);
+                    }
                     FileProcessError::Filtered => {
+                        self.stats.errors += 1;
                         tracing::debug!(
                             path = %failed.path.display(),
                             "file does not match the filescan filter (stage 2), skipping"
                         );
                     }
                     error => {
+                        self.stats.errors += 1;
+                        if matches!(error, FileProcessError::TimedOut(_)) {
+                            self.stats.timeouts += 1;
+                        }
                         tracing::error!(
                             error = ?error,
                             path = %failed.path.display(),
@@ -879,7 +1268,8 @@ impl ScanContext {
         if real_size != reported_size {
             tracing::warn!(path = %path.display(), real_size, reported_size, "file size mismatch");
         }
-        let path_str = path.to_string_lossy().to_string();
+        let path_str = display_path(&path);
+        let path_key = self.path_keys.key(&path_str);
 
         if existing_sha256.as_deref() == Some(sha256.as_str()) {
             // The timestamp changed but the contents did not.
@@ -888,6 +1278,8 @@ impl ScanContext {
                 sha256: sha256.clone(),
                 last_modified,
                 path: path_str,
+                path_bytes: path_bytes(&path),
+                path_key,
                 new_file_hash: false,
                 file_size: Some(real_size),
                 item_metadata: None,
@@ -905,6 +1297,8 @@ impl ScanContext {
                 sha256: sha256.clone(),
                 last_modified,
                 path: path_str,
+                path_bytes: path_bytes(&path),
+                path_key,
                 new_file_hash: true,
                 file_size: Some(real_size),
                 item_metadata: None,
@@ -920,6 +1314,12 @@ impl ScanContext {
     }
 
     async fn handle_new_item(&mut self, item: NewItemData) -> ApiResult<()> {
+        if self.fast_scan {
+            self.stats.visuals_deferred += 1;
+        }
+        if item.undecodable {
+            self.stats.undecodable += 1;
+        }
         if !item.thumbnails.is_empty()
             && !has_thumbnail(&mut self.conn, &item.sha256, THUMBNAIL_PROCESS_VERSION).await?
         {
@@ -955,10 +1355,13 @@ impl ScanContext {
             }
         }
 
+        let path = display_path(&item.path);
         let data = FileScanData {
             sha256: item.sha256.clone(),
             last_modified: item.last_modified.clone(),
-            path: item.path.to_string_lossy().to_string(),
+            path_key: self.path_keys.key(&path),
+            path_bytes: path_bytes(&item.path),
+            path,
             new_file_hash: true,
             file_size: Some(item.file_size),
             item_metadata: Some(item.metadata.clone()),
@@ -971,6 +1374,9 @@ impl ScanContext {
 
     async fn handle_backfill(&mut self, backfill: BackfillResult) {
         self.in_flight_visuals.remove(&backfill.sha256);
+        if backfill.undecodable {
+            self.stats.undecodable += 1;
+        }
 
         // Another task may have stored visuals for the same content while
         // this one was running; re-check before writing so a duplicate store
@@ -1029,6 +1435,21 @@ impl ScanContext {
                 tracing::error!(error = ?err, "failed to set blurhash");
             }
         }
+
+        // Frames that decoded now clear a mark left by an earlier failure.
+        if backfill.undecodable || !backfill.extracted_frames.is_empty() {
+            let marked = call_index_db_writer(&self.index_db, |reply| {
+                IndexDbWriterMessage::SetItemUndecodable {
+                    sha256: backfill.sha256.clone(),
+                    undecodable: backfill.undecodable,
+                    reply,
+                }
+            })
+            .await;
+            if let Err(err) = marked {
+                tracing::error!(error = ?err, "failed to update undecodable marker");
+            }
+        }
     }
 
     /// Regenerates missing thumbnails or blurhashes for files whose contents
@@ -1067,6 +1488,10 @@ impl ScanContext {
         if !needs_thumb && !needs_blurhash {
             return Ok(());
         }
+        if self.fast_scan {
+            self.stats.visuals_deferred += 1;
+            return Ok(());
+        }
         // Identical content elsewhere in this scan already has a visuals task
         // in flight; its results apply to this sha256 as well.
         if self.in_flight_visuals.contains(&sha256) {
@@ -1080,7 +1505,8 @@ impl ScanContext {
             // even when the item's duration metadata is missing; only a fresh
             // ffmpeg extraction needs a usable duration (matching Python,
             // which consults metadata only when no frames exist).
-            existing_frames = get_frames_bytes(&mut self.conn, &sha256).await?;
+            existing_frames =
+                get_frames_bytes(&mut self.conn, &visuals_dir(&self.index_db), &sha256).await?;
             if existing_frames.is_empty() {
                 if let Some((duration, video_tracks)) =
                     get_item_visual_meta(&mut self.conn, &sha256).await?
@@ -1098,7 +1524,7 @@ impl ScanContext {
             }
         }
         let existing_thumb = if !needs_thumb && needs_blurhash {
-            get_thumbnail_bytes(&mut self.conn, &sha256, 0).await?
+            get_thumbnail_bytes(&mut self.conn, &visuals_dir(&self.index_db), &sha256, 0).await?
         } else {
             None
         };
@@ -1161,6 +1587,7 @@ impl ScanContext {
                         thumbnails: Vec::new(),
                         extracted_frames: Vec::new(),
                         blurhash: None,
+                        undecodable: false,
                     })
                 }
             }
@@ -1188,12 +1615,19 @@ impl ScanContext {
             backfill_sha256: None,
         };
         let hash_timer = self.timers.hashing.clone();
+        let settle = self.settle;
         let handle = self.tasks.spawn(async move {
             let _permit = permit;
             let hash_path = path.clone();
             let joined = tokio::task::spawn_blocking(move || {
-                let _span = hash_timer.start();
-                calculate_hashes(&hash_path)
+                let fingerprint = wait_until_settled(&hash_path, settle)?;
+                let hashes = {
+                    let _span = hash_timer.start();
+                    calculate_hashes(&hash_path)
+                }
+                .map_err(|err| FileProcessError::Io(err.to_string()))?;
+                fingerprint.ensure_unchanged(&hash_path)?;
+                Ok(hashes)
             })
             .await;
             match joined {
@@ -1207,10 +1641,7 @@ impl ScanContext {
                     sha256,
                     real_size,
                 }),
-                Ok(Err(err)) => TaskOutcome::Failed(FailedFile {
-                    path,
-                    error: FileProcessError::Io(err.to_string()),
-                }),
+                Ok(Err(error)) => TaskOutcome::Failed(FailedFile { path, error }),
                 Err(err) => TaskOutcome::Failed(FailedFile {
                     path,
                     error: FileProcessError::Worker(err.to_string()),
@@ -1239,6 +1670,7 @@ impl ScanContext {
             .await
             .map_err(|_| ApiError::internal("Failed to schedule scan work"))?;
         let filter = self.filescan_filter.clone();
+        let generate_visuals = !self.fast_scan;
         let tracked = TrackedTask {
             path: path.to_string_lossy().to_string(),
             backfill_sha256: None,
@@ -1256,6 +1688,7 @@ impl ScanContext {
                     md5,
                     sha256,
                     filter,
+                    generate_visuals,
                     &timers,
                 )
             })
@@ -1307,6 +1740,7 @@ fn prepare_new_item(
     md5: String,
     sha256: String,
     filter: Option<Arc<Match>>,
+    generate_visuals: bool,
     timers: &ScanTimers,
 ) -> TaskOutcome {
     let metadata_span = timers.metadata.start();
@@ -1323,7 +1757,7 @@ fn prepare_new_item(
     } else {
         None
     };
-    let metadata =
+    let mut metadata =
         match extract_item_metadata_inner(&path, &mime_type, md5, preloaded_image.as_ref()) {
             Ok(metadata) => metadata,
             Err(error) => {
@@ -1348,14 +1782,12 @@ fn prepare_new_item(
         });
     }
 
-    let (thumbnails, frames, blurhash) =
-        match generate_new_item_visuals(&path, &mime_type, &metadata, preloaded_image, timers) {
-            Ok(result) => result,
-            Err(err) => {
-                tracing::error!(error = ?err, path = %path.display(), "failed to generate visuals");
-                (Vec::new(), Vec::new(), None)
-            }
-        };
+    let visuals = if generate_visuals {
+        new_item_visuals_or_empty(&path, &mime_type, &metadata, preloaded_image, timers)
+    } else {
+        NewItemVisuals::default()
+    };
+    metadata.undecodable = visuals.undecodable;
 
     TaskOutcome::NewItem(NewItemData {
         path,
@@ -1364,9 +1796,10 @@ fn prepare_new_item(
         sha256,
         mime_type,
         metadata,
-        thumbnails,
-        frames,
-        blurhash,
+        thumbnails: visuals.thumbnails,
+        frames: visuals.frames,
+        blurhash: visuals.blurhash,
+        undecodable: visuals.undecodable,
     })
 }
 
@@ -1380,6 +1813,10 @@ pub(crate) struct PreparedFile {
     pub(crate) thumbnails: Vec<StoredImage>,
     pub(crate) frames: Vec<StoredImage>,
     pub(crate) blurhash: Option<String>,
+    /// No frame of the video could be decoded, even by the fallbacks; the
+    /// file is indexed without visuals and counted in the scan's
+    /// `undecodable`.
+    pub(crate) undecodable: bool,
 }
 
 pub(crate) struct FileWriteData {
@@ -1420,10 +1857,14 @@ impl FileWriteData {
 
 pub(crate) async fn build_file_scan_data(
     conn: &mut sqlx::SqliteConnection,
+    path_keys: &PathKeys,
     prepared: PreparedFile,
     scan_time: &str,
 ) -> ApiResult<FileWriteData> {
-    let existing = get_file_by_path(conn, prepared.path.to_string_lossy().as_ref()).await?;
+    let path = display_path(&prepared.path);
+    let raw_path = path_bytes(&prepared.path);
+    let path_key = path_keys.key(&path);
+    let existing = get_file_by_path(conn, &path_key).await?;
     let time_added = scan_time.to_string();
 
     if let Some(existing) = existing {
@@ -1432,7 +1873,9 @@ pub(crate) async fn build_file_scan_data(
             let data = FileScanData {
                 sha256: existing.sha256.clone(),
                 last_modified: existing.last_modified,
-                path: prepared.path.to_string_lossy().to_string(),
+                path: path.clone(),
+                path_bytes: raw_path.clone(),
+                path_key: path_key.clone(),
                 new_file_hash: false,
                 file_size: None,
                 item_metadata: None,
@@ -1455,7 +1898,9 @@ pub(crate) async fn build_file_scan_data(
             let data = FileScanData {
                 sha256: sha256.clone(),
                 last_modified: prepared.last_modified.clone(),
-                path: prepared.path.to_string_lossy().to_string(),
+                path: path.clone(),
+                path_bytes: raw_path.clone(),
+                path_key: path_key.clone(),
                 new_file_hash: false,
                 file_size: Some(prepared.file_size),
                 item_metadata: None,
@@ -1478,7 +1923,9 @@ pub(crate) async fn build_file_scan_data(
     let data = FileScanData {
         sha256: sha256.clone(),
         last_modified: prepared.last_modified.clone(),
-        path: prepared.path.to_string_lossy().to_string(),
+        path,
+        path_bytes: raw_path,
+        path_key,
         new_file_hash: true,
         file_size: Some(prepared.file_size),
         item_metadata,
@@ -1497,17 +1944,59 @@ pub(crate) enum FileProcessError {
     Worker(#[allow(dead_code)] String),
     Io(#[allow(dead_code)] String),
     Unsupported(#[allow(dead_code)] String),
+    /// ffmpeg or ffprobe ran past its timeout on this file and was killed;
+    /// counted in the scan's `timeouts` as well as its `errors`.
+    TimedOut(#[allow(dead_code)] String),
+    /// No frame of a video could be decoded, even by the fallback steps of
+    /// [`video_frames::extract_video_frames`].
+    Undecodable(#[allow(dead_code)] String),
     /// The file was rejected by the user's filescan filter.
     Filtered,
     /// The file's mtime matches the DB record, so hashing was skipped.
     Unchanged,
+    /// The file's size or mtime changed across the settle window or while it
+    /// was being hashed: it is still being written. Deferred, not an error.
+    Busy,
+}
+
+impl From<MediaToolError> for FileProcessError {
+    fn from(err: MediaToolError) -> Self {
+        if err.is_timeout() {
+            Self::TimedOut(err.to_string())
+        } else {
+            Self::Unsupported(err.to_string())
+        }
+    }
+}
+
+impl From<FrameExtractionError> for FileProcessError {
+    fn from(err: FrameExtractionError) -> Self {
+        match err {
+            FrameExtractionError::Io(reason) => Self::Io(reason),
+            FrameExtractionError::Tool(err) => err.into(),
+            FrameExtractionError::Undecodable(reason) => Self::Undecodable(reason),
+        }
+    }
 }
 
+/// Hashes, probes and renders one file for the continuous scanner.
+///
+/// Hashing runs on a scoped thread while this thread extracts metadata and,
+/// when the outcome cannot depend on the hashes, generates visuals: on large
+/// videos both take seconds and read independent data. `stored_visuals` is
+/// the sha256 predicted by [`predict_stored_visuals`]; visual generation is
+/// skipped for it and only runs after hashing if the prediction was wrong.
+///
+/// Files still being written (see [`wait_until_settled`]) fail with `Busy`,
+/// both before hashing and if they change while being hashed.
 pub(crate) fn process_file(
     path: PathBuf,
     filescan_filter: Option<Arc<Match>>,
+    stored_visuals: Option<&str>,
+    settle: Duration,
     timers: &ScanTimers,
 ) -> Result<PreparedFile, FileProcessError> {
+    let fingerprint = wait_until_settled(&path, settle)?;
     let (last_modified, file_size) = get_last_modified_time_and_size(&path)
         .map_err(|err| FileProcessError::Io(err.to_string()))?;
 
@@ -1522,22 +2011,75 @@ pub(crate) fn process_file(
         return Err(FileProcessError::Filtered);
     }
 
-    let hash_span = timers.hashing.start();
-    let (md5, sha256, real_size) =
-        calculate_hashes(&path).map_err(|err| FileProcessError::Io(err.to_string()))?;
-    drop(hash_span);
+    let filter = filescan_filter.as_deref();
+    // Stage 2 can only be decided before hashing if it ignores the hashes.
+    let filter_needs_hashes = filter.is_some_and(|filter| {
+        match_columns(filter)
+            .iter()
+            .any(|column| matches!(column, Column::Md5 | Column::Sha256))
+    });
+
+    let (hashes, metadata, speculative_visuals) = std::thread::scope(|scope| {
+        let hasher = scope.spawn(|| {
+            let _span = timers.hashing.start();
+            calculate_hashes(&path)
+        });
+
+        // Decode images once for both metadata and visuals.
+        let metadata_span = timers.metadata.start();
+        let preloaded_image = if mime_type.starts_with("image") {
+            open_image(&path).ok()
+        } else {
+            None
+        };
+        let metadata =
+            extract_item_metadata_inner(&path, &mime_type, String::new(), preloaded_image.as_ref());
+        drop(metadata_span);
+
+        let speculative_visuals = match &metadata {
+            Ok(metadata)
+                if stored_visuals.is_none()
+                    && !filter_needs_hashes
+                    && passes_filescan_filter_stage2(
+                        filter,
+                        &path,
+                        &last_modified,
+                        file_size,
+                        &mime_type,
+                        "",
+                        "",
+                        metadata,
+                    ) =>
+            {
+                Some(new_item_visuals_or_empty(
+                    &path,
+                    &mime_type,
+                    metadata,
+                    preloaded_image,
+                    timers,
+                ))
+            }
+            _ => None,
+        };
+        (hasher.join(), metadata, speculative_visuals)
+    });
+
+    let (md5, sha256, real_size) = hashes
+        .map_err(|_| FileProcessError::Worker("hashing thread panicked".to_string()))?
+        .map_err(|err| FileProcessError::Io(err.to_string()))?;
+    // Hashes of a file that changed mid-read describe no version of it.
+    fingerprint.ensure_unchanged(&path)?;
 
     if real_size != file_size {
         tracing::warn!(path = %path.display(), real_size, file_size, "file size mismatch");
     }
     let file_size = real_size;
 
-    let metadata_span = timers.metadata.start();
-    let metadata = extract_item_metadata(&path, &mime_type, md5.clone())?;
-    drop(metadata_span);
+    let mut metadata = metadata?;
+    metadata.md5 = md5.clone();
 
     if !passes_filescan_filter_stage2(
-        filescan_filter.as_deref(),
+        filter,
         &path,
         &last_modified,
         file_size,
@@ -1549,14 +2091,13 @@ pub(crate) fn process_file(
         return Err(FileProcessError::Filtered);
     }
 
-    let (thumbnails, frames, blurhash) =
-        match generate_new_item_visuals(&path, &mime_type, &metadata, None, timers) {
-            Ok(result) => result,
-            Err(err) => {
-                tracing::error!(error = ?err, path = %path.display(), "failed to generate visuals");
-                (Vec::new(), Vec::new(), None)
-            }
-        };
+    let visuals = match speculative_visuals {
+        Some(visuals) => visuals,
+        // The prediction held: the index already has this item's visuals.
+        None if stored_visuals == Some(sha256.as_str()) => NewItemVisuals::default(),
+        None => new_item_visuals_or_empty(&path, &mime_type, &metadata, None, timers),
+    };
+    metadata.undecodable = visuals.undecodable;
 
     Ok(PreparedFile {
         path,
@@ -1565,12 +2106,54 @@ pub(crate) fn process_file(
         sha256,
         mime_type,
         metadata,
-        thumbnails,
-        frames,
-        blurhash,
+        thumbnails: visuals.thumbnails,
+        frames: visuals.frames,
+        blurhash: visuals.blurhash,
+        undecodable: visuals.undecodable,
     })
 }
 
+/// Predicts the content of a file whose mtime changed: if the item its path
+/// was indexed as has the same size and every visual it can have is already
+/// stored, returns that item's sha256 for [`process_file`]'s
+/// `stored_visuals`. Hashing still verifies the prediction.
+pub(crate) async fn predict_stored_visuals(
+    conn: &mut sqlx::SqliteConnection,
+    path: &Path,
+    record: &FilePathRecord,
+    file_size: i64,
+) -> ApiResult<Option<String>> {
+    if record.size != Some(file_size) {
+        return Ok(None);
+    }
+    let Ok(mime_type) = infer_mime_type(path) else {
+        return Ok(None);
+    };
+    let sha256 = &record.sha256;
+    if !has_blurhash(conn, sha256).await? {
+        return Ok(None);
+    }
+    let thumbnail_stored = if has_thumbnail(conn, sha256, THUMBNAIL_PROCESS_VERSION).await? {
+        true
+    } else if mime_type.starts_with("image") {
+        // Images served from the original file never get a stored thumbnail.
+        matches!(
+            get_item_dimensions(conn, sha256).await?,
+            Some((Some(width), Some(height)))
+                if image_is_served_directly(file_size as u64, width, height)
+        )
+    } else {
+        false
+    };
+    if !thumbnail_stored {
+        return Ok(None);
+    }
+    if mime_type.starts_with("video") && !has_frame(conn, sha256, FRAME_PROCESS_VERSION).await? {
+        return Ok(None);
+    }
+    Ok(Some(sha256.clone()))
+}
+
 fn passes_filescan_filter_stage1(
     filter: Option<&Match>,
     path: &Path,
@@ -1586,17 +2169,40 @@ fn passes_filescan_filter_stage1(
         .and_then(|name| name.to_str())
         .unwrap_or("")
         .to_string();
+    let dimensions = stage1_dimensions(filter, path, mime_type);
     let value = MatchValue {
         last_modified: Some(last_modified.to_string()),
         size: Some(file_size),
-        path: Some(path.to_string_lossy().to_string()),
+        path: Some(display_path(path)),
         filename: Some(filename),
         r#type: Some(mime_type.to_string()),
+        width: dimensions.map(|(width, _)| width as i64),
+        height: dimensions.map(|(_, height)| height as i64),
         ..Default::default()
     };
     evaluate_match(filter, &value)
 }
 
+/// An image's width and height read from its header, when the filter has a
+/// condition on them or on the columns derived from them. Stage 1 then skips
+/// small images before they are hashed; other media, and images whose header
+/// can't be read, leave the condition to stage 2.
+fn stage1_dimensions(filter: &Match, path: &Path, mime_type: &str) -> Option<(u32, u32)> {
+    let needs_dimensions = mime_type.starts_with("image")
+        && match_columns(filter).iter().any(|column| {
+            matches!(column, Column::Width | Column::Height) || is_derived_column(*column)
+        });
+    if !needs_dimensions {
+        return None;
+    }
+    image::ImageReader::open(path)
+        .ok()?
+        .with_guessed_format()
+        .ok()?
+        .into_dimensions()
+        .ok()
+}
+
 fn passes_filescan_filter_stage2(
     filter: Option<&Match>,
     path: &Path,
@@ -1618,7 +2224,7 @@ fn passes_filescan_filter_stage2(
     let value = MatchValue {
         last_modified: Some(last_modified.to_string()),
         size: Some(file_size),
-        path: Some(path.to_string_lossy().to_string()),
+        path: Some(display_path(path)),
         filename: Some(filename),
         r#type: Some(mime_type.to_string()),
         md5: Some(md5.to_string()),
@@ -1674,14 +2280,6 @@ fn decode_limits() -> image::Limits {
     limits
 }
 
-fn extract_item_metadata(
-    path: &Path,
-    mime_type: &str,
-    md5: String,
-) -> Result<ItemScanMeta, FileProcessError> {
-    extract_item_metadata_inner(path, mime_type, md5, None)
-}
-
 fn extract_item_metadata_inner(
     path: &Path,
     mime_type: &str,
@@ -1697,6 +2295,7 @@ fn extract_item_metadata_inner(
         audio_tracks: None,
         video_tracks: None,
         subtitle_tracks: None,
+        undecodable: false,
     };
 
     if mime_type.starts_with("image") {
@@ -1733,6 +2332,42 @@ fn extract_item_metadata_inner(
     Ok(metadata)
 }
 
+/// Visuals generated for a new item; all empty if generation failed.
+#[derive(Default)]
+struct NewItemVisuals {
+    thumbnails: Vec<StoredImage>,
+    frames: Vec<StoredImage>,
+    blurhash: Option<String>,
+    /// No frame of the video could be decoded, even by the fallbacks.
+    undecodable: bool,
+}
+
+/// [`generate_new_item_visuals`], logging failures and degrading to no
+/// visuals so the file itself is still indexed.
+fn new_item_visuals_or_empty(
+    path: &Path,
+    mime_type: &str,
+    metadata: &ItemScanMeta,
+    preloaded_image: Option<DynamicImage>,
+    timers: &ScanTimers,
+) -> NewItemVisuals {
+    match generate_new_item_visuals(path, mime_type, metadata, preloaded_image, timers) {
+        Ok((thumbnails, frames, blurhash)) => NewItemVisuals {
+            thumbnails,
+            frames,
+            blurhash,
+            undecodable: false,
+        },
+        Err(err) => {
+            tracing::error!(error = ?err, path = %path.display(), "failed to generate visuals");
+            NewItemVisuals {
+                undecodable: matches!(err, FileProcessError::Undecodable(_)),
+                ..NewItemVisuals::default()
+            }
+        }
+    }
+}
+
 fn generate_new_item_visuals(
     path: &Path,
     mime_type: &str,
@@ -1794,7 +2429,7 @@ fn generate_new_item_visuals(
     } else if mime_type.starts_with("text/html") {
         // Renders nothing when no headless browser is installed or the page
         // fails to render; the item is then indexed without visuals.
-        if let Some(shot) = render_html_screenshot(path) {
+        if let Ok(shot) = render_html_screenshot(path) {
             thumbnails.push(encode_image(0, &shot)?);
             blurhash_source = Some(shot);
         }
@@ -1832,6 +2467,7 @@ fn generate_backfill_visuals(
     let mut thumbnails = Vec::new();
     let mut extracted_frames = Vec::new();
     let mut blurhash_source: Option<DynamicImage> = None;
+    let mut undecodable = false;
 
     if needs_thumb {
         match build_backfill_thumbnails(path, mime_type, &existing_frames, video_duration) {
@@ -1841,6 +2477,7 @@ fn generate_backfill_visuals(
                 blurhash_source = source;
             }
             Err(err) => {
+                undecodable = matches!(err, FileProcessError::Undecodable(_));
                 tracing::error!(error = ?err, path = %path.display(), "failed to generate thumbnails");
             }
         }
@@ -1872,9 +2509,45 @@ fn generate_backfill_visuals(
         thumbnails,
         extracted_frames,
         blurhash,
+        undecodable,
     }
 }
 
+/// Visuals rebuilt for an item whose stored ones are outdated.
+pub(crate) struct RegeneratedVisuals {
+    pub thumbnails: Vec<StoredImage>,
+    /// Freshly extracted video frames; empty when existing frames were reused.
+    pub frames: Vec<StoredImage>,
+    pub blurhash: Option<String>,
+}
+
+/// Rebuilds an item's thumbnails (and, for videos without `existing_frames`,
+/// its frames) with the current generation logic. Unlike the scan backfill
+/// this does not look at what is already stored, and fails instead of
+/// degrading so that the caller keeps the old visuals.
+pub(crate) fn regenerate_visuals(
+    path: &Path,
+    mime_type: &str,
+    existing_frames: &[Vec<u8>],
+    video_duration: f64,
+) -> Result<RegeneratedVisuals, FileProcessError> {
+    let (thumbnails, frames, source) =
+        build_backfill_thumbnails(path, mime_type, existing_frames, video_duration)?;
+    let source = match source {
+        Some(source) => Some(source),
+        None if mime_type.starts_with("image") => open_image(path).ok(),
+        None => None,
+    };
+    let blurhash = source
+        .as_ref()
+        .and_then(|image| compute_blurhash(image).ok());
+    Ok(RegeneratedVisuals {
+        thumbnails,
+        frames,
+        blurhash,
+    })
+}
+
 fn build_backfill_thumbnails(
     path: &Path,
     mime_type: &str,
@@ -1936,7 +2609,7 @@ fn build_backfill_thumbnails(
             source = Some(page);
         }
     } else if mime_type.starts_with("text/html") {
-        if let Some(shot) = render_html_screenshot(path) {
+        if let Ok(shot) = render_html_screenshot(path) {
             thumbnails.push(encode_image(0, &shot)?);
             source = Some(shot);
         }
@@ -2096,24 +2769,76 @@ fn render_pdf_first_page(path: &Path) -> Option<DynamicImage> {
     }
 }
 
-/// Renders every page of a PDF at 2x its point size (144 dpi), matching the
-/// Python pypdfium2 loader used by data extraction (`scale=2`). Unlike
-/// thumbnail generation this fails hard: extraction must record the item as
-/// failed (and retry it next run) rather than mark it processed.
-pub(crate) fn render_pdf_pages(path: &Path) -> Result<Vec<DynamicImage>, String> {
-    let pdfium = pdfium().ok_or_else(|| "pdfium library not available".to_string())?;
-    let _serialized = PDFIUM_CALL_LOCK
-        .lock()
-        .unwrap_or_else(|poisoned| poisoned.into_inner());
-    let document = pdfium
-        .load_pdf_from_file(path, None)
-        .map_err(|err| format!("failed to load PDF: {err}"))?;
-    let mut pages = Vec::new();
-    for page in document.pages().iter() {
-        let bitmap = page
-            .render_with_config(&PdfRenderConfig::new().scale_page_by_factor(2.0))
-            .map_err(|err| format!("failed to render PDF page: {err}"))?;
-        pages.push(bitmap.as_image());
+/// Why a PDF/HTML render produced no image. Thumbnail generation only needs
+/// "no image"; extraction records each variant as its own item outcome.
+#[derive(Debug)]
+pub(crate) enum RenderError {
+    /// The renderer (pdfium or a headless browser) is not installed.
+    Unavailable,
+    /// The render ran past `[jobs] render_timeout_secs`.
+    TimedOut,
+    /// The document could not be loaded or rendered.
+    Failed(String),
+}
+
+impl std::fmt::Display for RenderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unavailable => f.write_str("renderer not available"),
+            Self::TimedOut => f.write_str("render timed out"),
+            Self::Failed(detail) => f.write_str(detail),
+        }
+    }
+}
+
+/// Whether PDFs can be rendered. Binds pdfium on first use and caches the
+/// result, so extraction can probe once at job start.
+pub(crate) fn pdf_renderer_available() -> bool {
+    pdfium().is_some()
+}
+
+/// Whether HTML can be rendered; cached like [`pdf_renderer_available`].
+pub(crate) fn html_renderer_available() -> bool {
+    html_renderer().is_some()
+}
+
+/// The render deadline from `[jobs] render_timeout_secs`; `None` = no limit.
+fn render_deadline() -> Option<Instant> {
+    match crate::config::runtime().render_timeout_secs {
+        0 => None,
+        secs => Some(Instant::now() + std::time::Duration::from_secs(secs)),
+    }
+}
+
+/// Renders the pages of a PDF at 2x their point size (144 dpi), matching the
+/// Python pypdfium2 loader used by data extraction (`scale=2`), up to
+/// `[jobs] render_max_pages`. pdfium runs in-process and cannot be
+/// interrupted mid-page, so the render timeout is checked between pages.
+/// Unlike thumbnail generation this fails hard: extraction must record the
+/// item as failed (and retry it next run) rather than mark it processed.
+pub(crate) fn render_pdf_pages(path: &Path) -> Result<Vec<DynamicImage>, RenderError> {
+    let pdfium = pdfium().ok_or(RenderError::Unavailable)?;
+    let runtime = crate::config::runtime();
+    let max_pages = match runtime.render_max_pages {
+        0 => usize::MAX,
+        limit => limit,
+    };
+    let deadline = render_deadline();
+    let _serialized = PDFIUM_CALL_LOCK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let document = pdfium
+        .load_pdf_from_file(path, None)
+        .map_err(|err| RenderError::Failed(format!("failed to load PDF: {err}")))?;
+    let mut pages = Vec::new();
+    for page in document.pages().iter().take(max_pages) {
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return Err(RenderError::TimedOut);
+        }
+        let bitmap = page
+            .render_with_config(&PdfRenderConfig::new().scale_page_by_factor(2.0))
+            .map_err(|err| RenderError::Failed(format!("failed to render PDF page: {err}")))?;
+        pages.push(bitmap.as_image());
     }
     Ok(pages)
 }
@@ -2186,6 +2911,10 @@ fn html_file_url(path: &Path) -> Option<String> {
 /// many renders run at once, independent of the scan semaphore.
 static BROWSER_SLOTS: BlockingSemaphore = BlockingSemaphore::new(2);
 
+/// How long a finished browser gets to produce its screenshot file when no
+/// render timeout is configured.
+const SCREENSHOT_FILE_WAIT: std::time::Duration = std::time::Duration::from_secs(30);
+
 /// A minimal counting semaphore usable from spawn_blocking threads, where the
 /// tokio async semaphore cannot be awaited.
 struct BlockingSemaphore {
@@ -2235,11 +2964,13 @@ impl Drop for BlockingSemaphoreGuard<'_> {
 
 /// Screenshots an HTML file with a locally installed headless browser. This
 /// intentionally replaces the Python weasyprint HTML->PDF pipeline with a
-/// browser viewport capture. Never fails hard: any error degrades to None.
-/// (Extraction callers treat None as an item failure so the item is retried.)
-pub(crate) fn render_html_screenshot(path: &Path) -> Option<DynamicImage> {
-    let browser = html_renderer()?;
-    let url = html_file_url(path)?;
+/// browser viewport capture. Failures are logged here (except a missing
+/// browser, warned once); thumbnail generation treats any error as "no
+/// image", extraction records it against the item so the item is retried.
+pub(crate) fn render_html_screenshot(path: &Path) -> Result<DynamicImage, RenderError> {
+    let browser = html_renderer().ok_or(RenderError::Unavailable)?;
+    let url = html_file_url(path)
+        .ok_or_else(|| RenderError::Failed("failed to build file URL".to_string()))?;
     let _slot = BROWSER_SLOTS.acquire();
     let temp_dir = temp_dir_path();
     // The browser resolves its path arguments itself, so they must not
@@ -2247,14 +2978,18 @@ pub(crate) fn render_html_screenshot(path: &Path) -> Option<DynamicImage> {
     let temp_dir = if temp_dir.is_absolute() {
         temp_dir
     } else {
-        env::current_dir().ok()?.join(temp_dir)
+        env::current_dir()
+            .map_err(|err| RenderError::Failed(format!("failed to resolve temp dir: {err}")))?
+            .join(temp_dir)
     };
     // Headless browsers refuse to share a live profile; give each render its
     // own throwaway --user-data-dir.
     let profile_dir = temp_dir.join("profile");
     if let Err(err) = fs::create_dir_all(&profile_dir) {
         tracing::error!(error = %err, path = %profile_dir.display(), "failed to create temp screenshot dir");
-        return None;
+        return Err(RenderError::Failed(format!(
+            "failed to create temp screenshot dir: {err}"
+        )));
     }
     let screenshot = temp_dir.join("shot.png");
     // A leftover file here (crashed previous run, reused directory) would
@@ -2274,7 +3009,7 @@ fn run_html_screenshot(
     profile_dir: &Path,
     screenshot: &Path,
     path: &Path,
-) -> Option<DynamicImage> {
+) -> Result<DynamicImage, RenderError> {
     // Scanned HTML lives in user-approved folders, but a saved page can still
     // carry live script and remote references, so all network traffic
     // (including localhost, via the <-loopback> bypass override) is routed
@@ -2296,9 +3031,16 @@ fn run_html_screenshot(
         .arg("--default-background-color=FFFFFFFF")
         .arg(format!("--user-data-dir={}", profile_dir.display()))
         .arg(format!("--screenshot={}", screenshot.display()));
-    for extra in &crate::config::runtime().html_renderer_args {
+    let runtime = crate::config::runtime();
+    for extra in &runtime.html_renderer_args {
         command.arg(extra);
     }
+    if runtime.render_memory_limit_mb > 0 {
+        crate::process_tree::limit_data_segment(
+            &mut command,
+            runtime.render_memory_limit_mb.saturating_mul(1024 * 1024),
+        );
+    }
     command
         .arg(url)
         .stdout(std::process::Stdio::null())
@@ -2307,7 +3049,9 @@ fn run_html_screenshot(
         Ok(child) => child,
         Err(err) => {
             tracing::error!(error = %err, browser = %browser.display(), "failed to launch headless browser");
-            return None;
+            return Err(RenderError::Failed(format!(
+                "failed to launch headless browser: {err}"
+            )));
         }
     };
     // Chromium is a process tree, and on Windows msedge.exe is a launcher
@@ -2318,43 +3062,50 @@ fn run_html_screenshot(
 
     // Poll instead of wait() so a wedged renderer cannot stall a scan worker
     // forever; these run inside spawn_blocking, so sleeping here is fine.
-    let deadline = Instant::now() + std::time::Duration::from_secs(30);
+    let deadline = render_deadline();
+    let timed_out = || deadline.is_some_and(|deadline| Instant::now() >= deadline);
     let status = loop {
         match child.try_wait() {
             Ok(Some(status)) => break status,
             Ok(None) => {
-                if Instant::now() >= deadline {
+                if timed_out() {
                     let _ = child.kill();
                     let _ = child.wait();
                     tracing::error!(path = %path.display(), "headless browser timed out rendering HTML");
-                    return None;
+                    return Err(RenderError::TimedOut);
                 }
                 std::thread::sleep(std::time::Duration::from_millis(100));
             }
             Err(err) => {
                 tracing::error!(error = %err, path = %path.display(), "failed to wait for headless browser");
-                return None;
+                return Err(RenderError::Failed(format!(
+                    "failed to wait for headless browser: {err}"
+                )));
             }
         }
     };
     if !status.success() {
         tracing::error!(status = %status, path = %path.display(), "headless browser exited with an error");
-        return None;
+        return Err(RenderError::Failed(format!(
+            "headless browser exited with {status}"
+        )));
     }
     // On Windows the spawned executable can be a launcher that exits at once
     // while a detached browser process writes the screenshot, so poll until
     // the file decodes (a partially written PNG fails to decode) or time is
-    // up.
+    // up. Without a render timeout the wait for the file is still bounded.
+    let deadline =
+        deadline.unwrap_or_else(|| Instant::now() + SCREENSHOT_FILE_WAIT);
     let mut last_err = None;
     while Instant::now() < deadline {
         match open_image(screenshot) {
-            Ok(image) => return Some(image),
+            Ok(image) => return Ok(image),
             Err(err) => last_err = Some(err),
         }
         std::thread::sleep(std::time::Duration::from_millis(100));
     }
     tracing::error!(error = ?last_err, path = %path.display(), "failed to read HTML screenshot");
-    None
+    Err(RenderError::Failed("failed to read HTML screenshot".to_string()))
 }
 
 static LABEL_FONT: OnceLock<Option<FontVec>> = OnceLock::new();
@@ -2569,16 +3320,9 @@ fn extract_video_frames(
     if duration <= 0.0 {
         return Ok(Vec::new());
     }
-
-    let interval = duration / num_frames as f64;
-    let temp_dir = temp_dir_path();
-    fs::create_dir_all(&temp_dir).map_err(|err| FileProcessError::Io(err.to_string()))?;
-
-    let result = extract_video_frames_into(path, num_frames, interval, &temp_dir);
-    if let Err(err) = fs::remove_dir_all(&temp_dir) {
-        tracing::debug!(error = %err, path = %temp_dir.display(), "failed to remove temp frame dir");
-    }
-    result
+    Ok(video_frames::extract_video_frames(
+        path, num_frames, duration,
+    )?)
 }
 
 /// Renders the last portion of a captured stderr for error messages, keeping
@@ -2593,56 +3337,6 @@ pub(crate) fn stderr_tail(stderr: &[u8]) -> String {
     }
 }
 
-fn extract_video_frames_into(
-    path: &Path,
-    num_frames: usize,
-    interval: f64,
-    temp_dir: &Path,
-) -> Result<Vec<DynamicImage>, FileProcessError> {
-    let output_pattern = temp_dir.join("frame_%04d.png");
-    // stdout is silenced, but stderr is captured so a failure can say why
-    // (corrupt file, missing codec, disk full) instead of just "ffmpeg
-    // failed"; it is only surfaced on a non-zero exit.
-    let output = Command::new(crate::media_tools::ffmpeg())
-        .arg("-i")
-        .arg(path)
-        .arg("-vf")
-        .arg(format!("fps=1/{}", interval))
-        .arg("-vsync")
-        .arg("vfr")
-        .arg(&output_pattern)
-        .stdout(std::process::Stdio::null())
-        .output()
-        .map_err(|err| FileProcessError::Unsupported(err.to_string()))?;
-
-    if !output.status.success() {
-        return Err(FileProcessError::Unsupported(format!(
-            "ffmpeg failed: {}",
-            stderr_tail(&output.stderr)
-        )));
-    }
-
-    let mut frames = Vec::new();
-    let mut entries =
-        fs::read_dir(temp_dir).map_err(|err| FileProcessError::Io(err.to_string()))?;
-    let mut paths = Vec::new();
-    while let Some(entry) = entries.next() {
-        let entry = entry.map_err(|err| FileProcessError::Io(err.to_string()))?;
-        if entry.path().extension().and_then(|ext| ext.to_str()) == Some("png") {
-            paths.push(entry.path());
-        }
-    }
-    paths.sort();
-
-    for frame_path in paths.into_iter().take(num_frames) {
-        if let Ok(image) = open_image(&frame_path) {
-            frames.push(image);
-        }
-    }
-
-    Ok(frames)
-}
-
 // Unread fields mirror the ffprobe JSON schema and are kept for Debug output.
 #[derive(Debug, Deserialize)]
 struct FfprobeStream {
@@ -2694,22 +3388,20 @@ struct MediaInfo {
 }
 
 fn extract_media_info(path: &Path) -> Result<MediaInfo, FileProcessError> {
-    let output = Command::new(crate::media_tools::ffprobe())
-        .arg("-v")
-        .arg("error")
-        .arg("-show_entries")
-        .arg("stream=index,codec_type,codec_name,duration,width,height,tags:format=duration")
-        .arg("-of")
-        .arg("json")
-        .arg(path)
-        .output()
-        .map_err(|err| FileProcessError::Unsupported(err.to_string()))?;
-
-    if !output.status.success() {
-        return Err(FileProcessError::Unsupported("ffprobe failed".to_string()));
-    }
-
-    let data: FfprobeOutput = serde_json::from_slice(&output.stdout)
+    let stdout = media_tools::run(
+        MediaTool::Ffprobe,
+        MediaTool::Ffprobe
+            .command()
+            .arg("-v")
+            .arg("error")
+            .arg("-show_entries")
+            .arg("stream=index,codec_type,codec_name,duration,width,height,tags:format=duration")
+            .arg("-of")
+            .arg("json")
+            .arg(path),
+    )?;
+
+    let data: FfprobeOutput = serde_json::from_slice(&stdout)
         .map_err(|err| FileProcessError::Unsupported(err.to_string()))?;
 
     let format_duration = data
@@ -2783,6 +3475,55 @@ fn calculate_hashes(path: &Path) -> Result<(String, String, i64), io::Error> {
     Ok((md5, sha256, total_size))
 }
 
+/// A file's size and full-precision mtime. `files.last_modified` is
+/// truncated to seconds, which would hide a write landing in the same second.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) struct FileFingerprint {
+    modified: std::time::SystemTime,
+    size: u64,
+}
+
+impl FileFingerprint {
+    pub(crate) fn read(path: &Path) -> Result<Self, FileProcessError> {
+        let metadata = fs::metadata(path).map_err(|err| FileProcessError::Io(err.to_string()))?;
+        let modified = metadata
+            .modified()
+            .map_err(|err| FileProcessError::Io(err.to_string()))?;
+        Ok(Self {
+            modified,
+            size: metadata.len(),
+        })
+    }
+
+    /// `Busy` if the file no longer matches this fingerprint.
+    pub(crate) fn ensure_unchanged(&self, path: &Path) -> Result<(), FileProcessError> {
+        if Self::read(path)? == *self {
+            Ok(())
+        } else {
+            Err(FileProcessError::Busy)
+        }
+    }
+}
+
+/// Blocks until `path` has gone `settle` without a change to its size or
+/// mtime, then returns its fingerprint; `Busy` if it changed meanwhile.
+/// Files last modified longer than `settle` ago return immediately, so only
+/// recently written files pay for the wait. A zero `settle` disables it.
+pub(crate) fn wait_until_settled(
+    path: &Path,
+    settle: Duration,
+) -> Result<FileFingerprint, FileProcessError> {
+    let before = FileFingerprint::read(path)?;
+    // An mtime in the future (clock skew) counts as just modified.
+    let age = before.modified.elapsed().unwrap_or(Duration::ZERO);
+    if age >= settle {
+        return Ok(before);
+    }
+    std::thread::sleep(settle - age);
+    before.ensure_unchanged(path)?;
+    Ok(before)
+}
+
 pub(crate) fn get_last_modified_time_and_size(path: &Path) -> Result<(String, i64), io::Error> {
     let metadata = fs::metadata(path)?;
     let size = metadata.len() as i64;
@@ -2961,6 +3702,98 @@ pub(crate) fn is_hidden_or_temp(path: &Path) -> bool {
     name.starts_with('.') || name.starts_with('~')
 }
 
+/// Directory names skipped by scans (`SystemConfig::ignored_dir_patterns`).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct IgnoredDirs {
+    /// Lowercased patterns; empty when the heuristic is disabled.
+    patterns: Vec<String>,
+}
+
+impl IgnoredDirs {
+    pub(crate) fn from_config(config: &SystemConfig) -> Self {
+        if !config.skip_ignored_dirs {
+            return Self::default();
+        }
+        Self {
+            patterns: config
+                .ignored_dir_patterns
+                .iter()
+                .filter(|pattern| !pattern.is_empty())
+                .map(|pattern| pattern.to_lowercase())
+                .collect(),
+        }
+    }
+
+    pub(crate) fn matches_name(&self, name: &OsStr) -> bool {
+        if self.patterns.is_empty() {
+            return false;
+        }
+        let name = name.to_string_lossy().to_lowercase();
+        self.patterns
+            .iter()
+            .any(|pattern| glob_matches(pattern, &name))
+    }
+
+    /// Whether any directory between `root` and the file at `path` is
+    /// ignored. The root itself, and paths outside it, never are.
+    pub(crate) fn contains_file(&self, root: &Path, path: &Path) -> bool {
+        let Some(dir) = path.parent() else {
+            return false;
+        };
+        let Ok(relative) = dir.strip_prefix(root) else {
+            return false;
+        };
+        relative
+            .components()
+            .any(|component| self.matches_name(component.as_os_str()))
+    }
+}
+
+/// Files below an ignored directory that a scan would otherwise have
+/// indexed, for the scan's `ignored_dir_files` count. Does not follow
+/// symlinks.
+fn count_scannable_files(dir: &Path, allowed_extensions: &HashSet<String>) -> i64 {
+    WalkDir::new(dir)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            entry.file_type().is_file()
+                && !is_hidden_or_temp(entry.path())
+                && has_allowed_extension(entry.path(), allowed_extensions)
+        })
+        .count() as i64
+}
+
+/// Glob match over the whole name: `*` is any run of characters, `?` any one.
+pub(crate) fn glob_matches(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+    let (mut p, mut n) = (0, 0);
+    // Last `*` seen and the name position it currently absorbs up to.
+    let mut backtrack = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, absorbed)) => {
+                    p = star + 1;
+                    n = absorbed + 1;
+                    backtrack = Some((star, absorbed + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
 pub(crate) fn is_excluded(path: &Path, excluded: &[PathBuf]) -> bool {
     excluded.iter().any(|prefix| path.starts_with(prefix))
 }
@@ -3000,6 +3833,55 @@ mod tests {
         assert!(check_folder_validity(&populated.to_string_lossy()));
     }
 
+    // Image dimensions are read from the header at stage 1, so a derived
+    // dimension filter drops icons before hashing; a video still passes.
+    #[test]
+    fn stage1_filter_checks_image_dimensions() {
+        let root = tempfile::TempDir::new().unwrap();
+        let icon = root.path().join("icon.png");
+        let photo = root.path().join("photo.png");
+        image::RgbImage::new(16, 16).save(&icon).unwrap();
+        image::RgbImage::new(512, 300).save(&photo).unwrap();
+        let video = root.path().join("clip.mp4");
+        fs::write(&video, b"not decoded at stage 1").unwrap();
+        let filter: Match = serde_json::from_value(serde_json::json!({
+            "match": { "gte": { "min_dimension": 256 } }
+        }))
+        .unwrap();
+
+        let passes = |path: &Path, mime: &str| {
+            passes_filescan_filter_stage1(Some(&filter), path, "2024-01-01T00:00:00", 10, mime)
+        };
+        assert!(!passes(&icon, "image/png"));
+        assert!(passes(&photo, "image/png"));
+        assert!(passes(&video, "video/mp4"));
+    }
+
+    // Old files skip the settle wait; a file growing during it is Busy.
+    #[test]
+    fn settle_wait_defers_files_still_being_written() {
+        let root = tempfile::TempDir::new().unwrap();
+        let path = root.path().join("copying.bin");
+        fs::write(&path, b"part").unwrap();
+
+        let file = fs::File::options().append(true).open(&path).unwrap();
+        file.set_modified(std::time::SystemTime::now() - Duration::from_secs(60))
+            .unwrap();
+        let started = Instant::now();
+        wait_until_settled(&path, Duration::from_secs(5)).unwrap();
+        assert!(started.elapsed() < Duration::from_secs(1));
+
+        fs::write(&path, b"part").unwrap();
+        let writer = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            let mut file = file;
+            io::Write::write_all(&mut file, b" more").unwrap();
+        });
+        let result = wait_until_settled(&path, Duration::from_millis(500));
+        writer.join().unwrap();
+        assert!(matches!(result, Err(FileProcessError::Busy)));
+    }
+
     // Ensures rescans persist items, files, and blurhash data.
     #[tokio::test]
     async fn rescan_creates_items_and_files() {
@@ -3192,6 +4074,771 @@ LIMIT 1
         assert_eq!(item_count.0, 1);
     }
 
+    // A file keeps the time its path was first indexed across rescans: a
+    // touched mtime updates last_modified only, and new content moves the
+    // path to another item without resetting it.
+    #[tokio::test]
+    async fn rescan_preserves_file_time_added() {
+        let test_env = test_data_dir();
+        let root = test_env.path();
+        let index_db = next_db_name();
+        let user_data_db = next_db_name();
+        migrate_databases_on_disk(Some(&index_db), Some(&user_data_db))
+            .await
+            .unwrap();
+
+        let media_dir = root.join("media");
+        fs::create_dir_all(&media_dir).unwrap();
+        let image_path = media_dir.join("sample.png");
+        image::RgbImage::new(8, 8).save(&image_path).unwrap();
+
+        let store = SystemConfigStore::new(root.to_path_buf());
+        let config = SystemConfig {
+            included_folders: vec![media_dir.to_string_lossy().to_string()],
+            ..Default::default()
+        };
+        store.save(&index_db, &config).unwrap();
+
+        let service = FileScanService::new(
+            index_db.clone(),
+            user_data_db.clone(),
+            root.to_path_buf(),
+            ScanOptions { worker_count: 2 },
+        );
+        service.rescan_folders().await.unwrap();
+
+        // Scans in the same second share a timestamp, so pin the stored
+        // value to something no rescan could write.
+        let first_seen = "2020-01-01T00:00:00";
+        let mut write_conn = crate::db::open_index_db_write_no_user_data(&index_db)
+            .await
+            .unwrap();
+        sqlx::query("UPDATE files SET time_added = ?1")
+            .bind(first_seen)
+            .execute(&mut write_conn)
+            .await
+            .unwrap();
+        drop(write_conn);
+        let mut conn = open_index_db_read(&index_db, &user_data_db).await.unwrap();
+        let (original_modified, original_sha): (String, String) =
+            sqlx::query_as("SELECT last_modified, sha256 FROM files")
+                .fetch_one(&mut conn)
+                .await
+                .unwrap();
+        drop(conn);
+
+        let mtime = fs::metadata(&image_path).unwrap().modified().unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&image_path)
+            .unwrap()
+            .set_modified(mtime + std::time::Duration::from_secs(10))
+            .unwrap();
+        service.rescan_folders().await.unwrap();
+        let mut conn = open_index_db_read(&index_db, &user_data_db).await.unwrap();
+        let (time_added, last_modified, sha256): (String, String, String) =
+            sqlx::query_as("SELECT time_added, last_modified, sha256 FROM files")
+                .fetch_one(&mut conn)
+                .await
+                .unwrap();
+        assert_eq!(time_added, first_seen);
+        assert_ne!(last_modified, original_modified);
+        assert_eq!(sha256, original_sha);
+        drop(conn);
+
+        image::RgbImage::new(16, 16).save(&image_path).unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&image_path)
+            .unwrap()
+            .set_modified(mtime + std::time::Duration::from_secs(20))
+            .unwrap();
+        service.rescan_folders().await.unwrap();
+        let mut conn = open_index_db_read(&index_db, &user_data_db).await.unwrap();
+        let (time_added, sha256, item_time_added): (String, String, String) = sqlx::query_as(
+            "SELECT files.time_added, files.sha256, items.time_added \
+             FROM files JOIN items ON items.id = files.item_id",
+        )
+        .fetch_one(&mut conn)
+        .await
+        .unwrap();
+        assert_eq!(time_added, first_seen);
+        assert_ne!(sha256, original_sha);
+        assert_ne!(item_time_added, first_seen);
+    }
+
+    #[test]
+    fn ignored_dirs_match_globs_below_the_root() {
+        let ignored = IgnoredDirs::from_config(&SystemConfig::default());
+        for name in [
+            ".git",
+            "node_modules",
+            "$recycle.bin",
+            ".Trash-1000",
+            ".trash",
+        ] {
+            assert!(ignored.matches_name(OsStr::new(name)), "{name}");
+        }
+        for name in ["git", ".github", "modules", "Trash"] {
+            assert!(!ignored.matches_name(OsStr::new(name)), "{name}");
+        }
+        assert!(glob_matches("a?c*", "abcdef"));
+        assert!(!glob_matches("a?c*x", "abcdef"));
+
+        let root = Path::new("/media/.git");
+        assert!(!ignored.contains_file(root, &root.join("a.png")));
+        assert!(ignored.contains_file(root, &root.join("x/.svn/a.png")));
+        assert!(!ignored.contains_file(root, Path::new("/other/.git/a.png")));
+
+        let disabled = IgnoredDirs::from_config(&SystemConfig {
+            skip_ignored_dirs: false,
+            ..Default::default()
+        });
+        assert!(!disabled.matches_name(OsStr::new(".git")));
+    }
+
+    // Ignored directories are pruned from the walk and counted once each in
+    // the scan stats, along with the media files they hold.
+    #[tokio::test]
+    async fn scan_skips_and_counts_ignored_dirs() {
+        let test_env = test_data_dir();
+        let root = test_env.path();
+        let index_db = next_db_name();
+        let user_data_db = next_db_name();
+        migrate_databases_on_disk(Some(&index_db), Some(&user_data_db))
+            .await
+            .unwrap();
+
+        let media_dir = root.join("ignore_media");
+        for dir in ["", ".git/objects", "node_modules/pkg", "keep"] {
+            let dir = media_dir.join(dir);
+            fs::create_dir_all(&dir).unwrap();
+            image::RgbImage::new(8, 8)
+                .save(dir.join("sample.png"))
+                .unwrap();
+        }
+        image::RgbImage::new(8, 8)
+            .save(media_dir.join("node_modules/other.png"))
+            .unwrap();
+        fs::write(media_dir.join("node_modules/README.md"), "not media").unwrap();
+
+        let store = SystemConfigStore::new(root.to_path_buf());
+        let config = SystemConfig {
+            included_folders: vec![media_dir.to_string_lossy().to_string()],
+            ..Default::default()
+        };
+        store.save(&index_db, &config).unwrap();
+
+        let service = FileScanService::new(
+            index_db.clone(),
+            user_data_db.clone(),
+            root.to_path_buf(),
+            ScanOptions { worker_count: 2 },
+        );
+        service.rescan_folders().await.unwrap();
+
+        let mut conn = open_index_db_read(&index_db, &user_data_db).await.unwrap();
+        let files: Vec<(String,)> = sqlx::query_as("SELECT path FROM files ORDER BY path")
+            .fetch_all(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!(files.len(), 2, "{files:?}");
+        assert!(
+            files
+                .iter()
+                .all(|(path,)| !path.contains(".git") && !path.contains("node_modules"))
+        );
+        let ignored: (i64, i64) = sqlx::query_as(
+            "SELECT ignored_dirs, ignored_dir_files FROM file_scans ORDER BY id DESC LIMIT 1",
+        )
+        .fetch_one(&mut conn)
+        .await
+        .unwrap();
+        assert_eq!(ignored, (2, 3));
+    }
+
+    // Ensures .panoptikonignore rules prune directories and skip files below
+    // the folder holding them, counting each once in the scan stats.
+    #[tokio::test]
+    async fn scan_honors_and_counts_ignore_files() {
+        let test_env = test_data_dir();
+        let root = test_env.path();
+        let index_db = next_db_name();
+        let user_data_db = next_db_name();
+        migrate_databases_on_disk(Some(&index_db), Some(&user_data_db))
+            .await
+            .unwrap();
+
+        let media_dir = root.join("ignore_file_media");
+        for dir in ["", "raw", "keep", "keep/nested"] {
+            let dir = media_dir.join(dir);
+            fs::create_dir_all(&dir).unwrap();
+            for name in ["sample.png", "draft.png"] {
+                image::RgbImage::new(8, 8).save(dir.join(name)).unwrap();
+            }
+        }
+        fs::write(
+            media_dir.join(crate::jobs::ignore_files::IGNORE_FILE_NAME),
+            "raw/\ndraft.png\n",
+        )
+        .unwrap();
+        fs::write(
+            media_dir
+                .join("keep/nested")
+                .join(crate::jobs::ignore_files::IGNORE_FILE_NAME),
+            "!draft.png\n",
+        )
+        .unwrap();
+
+        let store = SystemConfigStore::new(root.to_path_buf());
+        let config = SystemConfig {
+            included_folders: vec![media_dir.to_string_lossy().to_string()],
+            ..Default::default()
+        };
+        store.save(&index_db, &config).unwrap();
+
+        let service = FileScanService::new(
+            index_db.clone(),
+            user_data_db.clone(),
+            root.to_path_buf(),
+            ScanOptions { worker_count: 2 },
+        );
+        service.rescan_folders().await.unwrap();
+
+        let mut conn = open_index_db_read(&index_db, &user_data_db).await.unwrap();
+        let files: Vec<(String,)> = sqlx::query_as("SELECT path FROM files ORDER BY path")
+            .fetch_all(&mut conn)
+            .await
+            .unwrap();
+        let files = files
+            .into_iter()
+            .map(|(path,)| {
+                Path::new(&path)
+                    .strip_prefix(&media_dir)
+                    .unwrap()
+                    .to_string_lossy()
+                    .replace('\\', "/")
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            files,
+            vec![
+                "keep/nested/draft.png",
+                "keep/nested/sample.png",
+                "keep/sample.png",
+                "sample.png",
+            ]
+        );
+        // raw/ once, plus the top-level and keep/ drafts.
+        let ignored: (i64,) =
+            sqlx::query_as("SELECT ignored_files FROM file_scans ORDER BY id DESC LIMIT 1")
+                .fetch_one(&mut conn)
+                .await
+                .unwrap();
+        assert_eq!(ignored.0, 3);
+    }
+
+    // Ensures a scan survives a symlink cycle and indexes a file reachable
+    // through a link into the scanned folder only under its real path.
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn scan_indexes_symlinked_files_once() {
+        use std::os::unix::fs::symlink;
+
+        let test_env = test_data_dir();
+        let root = test_env.path();
+        let index_db = next_db_name();
+        let user_data_db = next_db_name();
+        migrate_databases_on_disk(Some(&index_db), Some(&user_data_db))
+            .await
+            .unwrap();
+
+        let media_dir = root.join("symlink_media");
+        fs::create_dir_all(media_dir.join("albums")).unwrap();
+        image::RgbImage::new(8, 8)
+            .save(media_dir.join("albums/sample.png"))
+            .unwrap();
+        symlink(&media_dir, media_dir.join("albums/loop")).unwrap();
+        symlink(media_dir.join("albums"), media_dir.join("favorites")).unwrap();
+
+        let store = SystemConfigStore::new(root.to_path_buf());
+        let config = SystemConfig {
+            included_folders: vec![media_dir.to_string_lossy().to_string()],
+            ..Default::default()
+        };
+        store.save(&index_db, &config).unwrap();
+
+        let service = FileScanService::new(
+            index_db.clone(),
+            user_data_db.clone(),
+            root.to_path_buf(),
+            ScanOptions { worker_count: 2 },
+        );
+        service.rescan_folders().await.unwrap();
+
+        let mut conn = open_index_db_read(&index_db, &user_data_db).await.unwrap();
+        let files: Vec<(String,)> = sqlx::query_as("SELECT path FROM files ORDER BY path")
+            .fetch_all(&mut conn)
+            .await
+            .unwrap();
+        let expected = media_dir.join("albums/sample.png");
+        assert_eq!(files, vec![(expected.to_string_lossy().to_string(),)]);
+        let errors: (i64,) =
+            sqlx::query_as("SELECT errors FROM file_scans ORDER BY id DESC LIMIT 1")
+                .fetch_one(&mut conn)
+                .await
+                .unwrap();
+        assert_eq!(errors.0, 0);
+    }
+
+    // Files whose names are not valid UTF-8 are indexed under distinct
+    // escaped paths, keep their raw bytes, stay unchanged on the next scan
+    // and are found on disk again through those bytes.
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn scan_keeps_non_utf8_file_names() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let test_env = test_data_dir();
+        let root = test_env.path();
+        let index_db = next_db_name();
+        let user_data_db = next_db_name();
+        migrate_databases_on_disk(Some(&index_db), Some(&user_data_db))
+            .await
+            .unwrap();
+
+        let media_dir = root.join("latin1_media");
+        fs::create_dir_all(&media_dir).unwrap();
+        let raw_paths = [b"caf\xe8.png", b"caf\xe9.png"]
+            .map(|name| media_dir.join(OsStr::from_bytes(name)));
+        for path in &raw_paths {
+            image::RgbImage::new(8, 8).save(path).unwrap();
+        }
+
+        let store = SystemConfigStore::new(root.to_path_buf());
+        let config = SystemConfig {
+            included_folders: vec![media_dir.to_string_lossy().to_string()],
+            ..Default::default()
+        };
+        store.save(&index_db, &config).unwrap();
+
+        let service = FileScanService::new(
+            index_db.clone(),
+            user_data_db.clone(),
+            root.to_path_buf(),
+            ScanOptions { worker_count: 2 },
+        );
+        service.rescan_folders().await.unwrap();
+
+        let mut conn = open_index_db_read(&index_db, &user_data_db).await.unwrap();
+        let totals: (i64, i64, i64) = sqlx::query_as(
+            "SELECT SUM(new_files), SUM(unchanged_files), SUM(errors) FROM file_scans",
+        )
+        .fetch_one(&mut conn)
+        .await
+        .unwrap();
+        assert_eq!(totals, (2, 2, 0));
+        let files: Vec<(String, Option<Vec<u8>>, String)> =
+            sqlx::query_as("SELECT path, path_bytes, sha256 FROM files ORDER BY path")
+                .fetch_all(&mut conn)
+                .await
+                .unwrap();
+        let dir = media_dir.to_string_lossy();
+        assert_eq!(
+            files
+                .iter()
+                .map(|(path, bytes, _)| (path.clone(), bytes.clone()))
+                .collect::<Vec<_>>(),
+            raw_paths
+                .iter()
+                .zip(["E8", "E9"])
+                .map(|(path, byte)| (
+                    format!("{dir}/caf\\x{byte}.png"),
+                    Some(path.as_os_str().as_bytes().to_vec())
+                ))
+                .collect::<Vec<_>>()
+        );
+
+        let existing = crate::db::items::get_existing_files_for_sha256(&mut conn, &files[0].2)
+            .await
+            .unwrap();
+        let mut disk_paths: Vec<_> = existing.iter().map(|file| file.disk_path()).collect();
+        disk_paths.sort();
+        assert_eq!(disk_paths, raw_paths.to_vec());
+    }
+
+    async fn latest_skipped_dirs(conn: &mut sqlx::SqliteConnection) -> i64 {
+        let row: (i64,) =
+            sqlx::query_as("SELECT skipped_dirs FROM file_scans ORDER BY id DESC LIMIT 1")
+                .fetch_one(conn)
+                .await
+                .unwrap();
+        row.0
+    }
+
+    // Replaces `remove` in `dir` by `add`, then puts the directory's time
+    // back, as a filesystem that does not update directory times would.
+    #[cfg(unix)]
+    fn swap_file_keeping_dir_time(dir: &Path, remove: &str, add: &str) {
+        let mtime = fs::metadata(dir).unwrap().modified().unwrap();
+        fs::remove_file(dir.join(remove)).unwrap();
+        image::RgbImage::new(8, 8).save(dir.join(add)).unwrap();
+        fs::File::open(dir).unwrap().set_modified(mtime).unwrap();
+    }
+
+    async fn indexed_filenames(index_db: &str, user_data_db: &str) -> Vec<String> {
+        let mut conn = open_index_db_read(index_db, user_data_db).await.unwrap();
+        let rows: Vec<(String,)> =
+            sqlx::query_as("SELECT filename FROM files WHERE available = TRUE ORDER BY filename")
+                .fetch_all(&mut conn)
+                .await
+                .unwrap();
+        rows.into_iter().map(|(name,)| name).collect()
+    }
+
+    // With skip_unchanged_dirs, rescans leave the files of unchanged
+    // directories alone yet still find a file added to one, since adding
+    // it moves the directory's time and entry count. A file swapped in
+    // without moving either is only found with the setting off or by a
+    // force_full scan.
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn differential_scan_skips_only_unchanged_dirs() {
+        let test_env = test_data_dir();
+        let root = test_env.path();
+        let index_db = next_db_name();
+        let user_data_db = next_db_name();
+        migrate_databases_on_disk(Some(&index_db), Some(&user_data_db))
+            .await
+            .unwrap();
+
+        let media_dir = root.join("differential_media");
+        let sub_dir = media_dir.join("sub");
+        fs::create_dir_all(&sub_dir).unwrap();
+        for path in [media_dir.join("top.png"), sub_dir.join("one.png")] {
+            image::RgbImage::new(8, 8).save(path).unwrap();
+        }
+        let store = SystemConfigStore::new(root.to_path_buf());
+        let mut config = SystemConfig {
+            included_folders: vec![media_dir.to_string_lossy().to_string()],
+            scan_settle_secs: 0,
+            skip_unchanged_dirs: true,
+            ..Default::default()
+        };
+        store.save(&index_db, &config).unwrap();
+        let options = ScanOptions { worker_count: 2 };
+        let service = FileScanService::new(&index_db, &user_data_db, root.to_path_buf(), options);
+
+        // The folder update's scan records the snapshots the full scan
+        // right after it matches.
+        service.rescan_folders().await.unwrap();
+        let mut conn = open_index_db_read(&index_db, &user_data_db).await.unwrap();
+        assert_eq!(latest_scan_record(&mut conn).await, (2, 0, 0, 0, 0));
+        assert_eq!(latest_skipped_dirs(&mut conn).await, 2);
+        drop(conn);
+
+        image::RgbImage::new(8, 8)
+            .save(sub_dir.join("two.png"))
+            .unwrap();
+        service.rescan_folders().await.unwrap();
+        let mut conn = open_index_db_read(&index_db, &user_data_db).await.unwrap();
+        assert_eq!(latest_scan_record(&mut conn).await, (2, 1, 0, 0, 0));
+        assert_eq!(latest_skipped_dirs(&mut conn).await, 1);
+        drop(conn);
+
+        swap_file_keeping_dir_time(&sub_dir, "two.png", "three.png");
+        service.rescan_folders().await.unwrap();
+        assert_eq!(
+            indexed_filenames(&index_db, &user_data_db).await,
+            ["one.png", "top.png", "two.png"]
+        );
+
+        config.skip_unchanged_dirs = false;
+        store.save(&index_db, &config).unwrap();
+        service.rescan_folders().await.unwrap();
+        let mut conn = open_index_db_read(&index_db, &user_data_db).await.unwrap();
+        assert_eq!(latest_scan_record(&mut conn).await, (2, 1, 0, 0, 1));
+        assert_eq!(latest_skipped_dirs(&mut conn).await, 0);
+        drop(conn);
+        assert_eq!(
+            indexed_filenames(&index_db, &user_data_db).await,
+            ["one.png", "three.png", "top.png"]
+        );
+
+        // Back on, the first scan only records; a forced one skips nothing.
+        config.skip_unchanged_dirs = true;
+        store.save(&index_db, &config).unwrap();
+        service.rescan_folders().await.unwrap();
+        swap_file_keeping_dir_time(&sub_dir, "three.png", "four.png");
+        let forced = FileScanService::new(&index_db, &user_data_db, root.to_path_buf(), options)
+            .with_force_full(true);
+        forced.rescan_folders().await.unwrap();
+        let mut conn = open_index_db_read(&index_db, &user_data_db).await.unwrap();
+        assert_eq!(latest_scan_record(&mut conn).await, (2, 1, 0, 0, 1));
+        assert_eq!(latest_skipped_dirs(&mut conn).await, 0);
+        drop(conn);
+        assert_eq!(
+            indexed_filenames(&index_db, &user_data_db).await,
+            ["four.png", "one.png", "top.png"]
+        );
+    }
+
+    // Ensures a single-folder scan only indexes that folder, and refuses a
+    // folder outside the included folders.
+    #[tokio::test]
+    async fn scan_folder_scans_only_the_given_subfolder() {
+        let test_env = test_data_dir();
+        let root = test_env.path();
+        let index_db = next_db_name();
+        let user_data_db = next_db_name();
+        migrate_databases_on_disk(Some(&index_db), Some(&user_data_db))
+            .await
+            .unwrap();
+
+        let media_dir = root.join("single_folder_media");
+        for sub in ["a", "b"] {
+            fs::create_dir_all(media_dir.join(sub)).unwrap();
+            image::RgbImage::new(8, 8)
+                .save(media_dir.join(sub).join("sample.png"))
+                .unwrap();
+        }
+        let store = SystemConfigStore::new(root.to_path_buf());
+        let config = SystemConfig {
+            included_folders: vec![media_dir.to_string_lossy().to_string()],
+            ..Default::default()
+        };
+        store.save(&index_db, &config).unwrap();
+
+        let service = FileScanService::new(
+            index_db.clone(),
+            user_data_db.clone(),
+            root.to_path_buf(),
+            ScanOptions { worker_count: 2 },
+        );
+        service.rescan_folders().await.unwrap();
+        for sub in ["a", "b"] {
+            image::RgbImage::new(4, 4)
+                .save(media_dir.join(sub).join("added.png"))
+                .unwrap();
+        }
+        let result = service
+            .scan_folder(&media_dir.join("a").to_string_lossy())
+            .await
+            .unwrap();
+        assert_eq!(result.scan_ids.len(), 1);
+
+        let mut conn = open_index_db_read(&index_db, &user_data_db).await.unwrap();
+        let files: Vec<(String,)> =
+            sqlx::query_as("SELECT path FROM files WHERE path LIKE '%added.png'")
+                .fetch_all(&mut conn)
+                .await
+                .unwrap();
+        let expected = media_dir.join("a/added.png");
+        assert_eq!(files, vec![(expected.to_string_lossy().to_string(),)]);
+
+        let outside = service
+            .scan_folder(&root.join("elsewhere").to_string_lossy())
+            .await;
+        assert!(outside.is_err());
+    }
+
+    // Ensures a fast scan indexes files without generating visuals and
+    // counts them as deferred, and a later full scan backfills them.
+    #[tokio::test]
+    async fn fast_scan_defers_visuals() {
+        let test_env = test_data_dir();
+        let root = test_env.path();
+        let index_db = next_db_name();
+        let user_data_db = next_db_name();
+        migrate_databases_on_disk(Some(&index_db), Some(&user_data_db))
+            .await
+            .unwrap();
+
+        let media_dir = root.join("fast_scan_media");
+        fs::create_dir_all(&media_dir).unwrap();
+        for (name, shade) in [("a.png", 10), ("b.png", 200)] {
+            image::RgbImage::from_pixel(8, 8, image::Rgb([shade, 0, 0]))
+                .save(media_dir.join(name))
+                .unwrap();
+        }
+
+        let store = SystemConfigStore::new(root.to_path_buf());
+        let mut config = SystemConfig {
+            included_folders: vec![media_dir.to_string_lossy().to_string()],
+            fast_scan: true,
+            ..Default::default()
+        };
+        store.save(&index_db, &config).unwrap();
+        let service = FileScanService::new(
+            index_db.clone(),
+            user_data_db.clone(),
+            root.to_path_buf(),
+            ScanOptions { worker_count: 2 },
+        );
+        service.rescan_folders().await.unwrap();
+
+        let mut conn = open_index_db_read(&index_db, &user_data_db).await.unwrap();
+        let blurhashes: (i64,) =
+            sqlx::query_as("SELECT COUNT(*) FROM items WHERE blurhash IS NOT NULL")
+                .fetch_one(&mut conn)
+                .await
+                .unwrap();
+        assert_eq!(blurhashes.0, 0);
+        // The folder's first scan indexes both files; the rescan after it
+        // finds them unchanged but still without visuals and defers again.
+        let deferred: Vec<(i64, i64)> =
+            sqlx::query_as("SELECT new_items, visuals_deferred FROM file_scans ORDER BY id")
+                .fetch_all(&mut conn)
+                .await
+                .unwrap();
+        assert_eq!(deferred, vec![(2, 2), (0, 2)]);
+
+        config.fast_scan = false;
+        store.save(&index_db, &config).unwrap();
+        service.rescan_folders().await.unwrap();
+        let blurhashes: (i64,) =
+            sqlx::query_as("SELECT COUNT(*) FROM items WHERE blurhash IS NOT NULL")
+                .fetch_one(&mut conn)
+                .await
+                .unwrap();
+        assert_eq!(blurhashes.0, 2);
+        let deferred: (i64,) =
+            sqlx::query_as("SELECT visuals_deferred FROM file_scans ORDER BY id DESC LIMIT 1")
+                .fetch_one(&mut conn)
+                .await
+                .unwrap();
+        assert_eq!(deferred.0, 0);
+    }
+
+    /// `count` square noise PNGs of `side` pixels under `dir`. Noise defeats
+    /// PNG compression, giving files that take measurable time to both hash
+    /// and decode.
+    fn noise_pngs(dir: &Path, count: usize, side: u32) -> Vec<PathBuf> {
+        fs::create_dir_all(dir).unwrap();
+        let mut seed = 0x2545_f491_u32;
+        (0..count)
+            .map(|idx| {
+                let image = image::RgbImage::from_fn(side, side, |_, _| {
+                    seed ^= seed << 13;
+                    seed ^= seed >> 17;
+                    seed ^= seed << 5;
+                    let [r, g, b, _] = seed.to_le_bytes();
+                    Rgb([r, g, b])
+                });
+                let path = dir.join(format!("noise_{idx}.png"));
+                image.save(&path).unwrap();
+                path
+            })
+            .collect()
+    }
+
+    // process_file hashes on a scoped thread while it probes and renders, so
+    // it must match running the stages one after another. A correct
+    // stored_visuals prediction skips rendering entirely, a wrong one still
+    // renders after hashing.
+    #[test]
+    fn process_file_overlaps_hashing_and_honors_visual_prediction() {
+        let test_env = test_data_dir();
+        let paths = noise_pngs(&test_env.path().join("overlap_media"), 4, 1024);
+
+        let timers = ScanTimers::default();
+        let sequential = paths
+            .iter()
+            .map(|path| {
+                let (md5, sha256, _) = calculate_hashes(path).unwrap();
+                let metadata = extract_item_metadata_inner(path, "image/png", md5, None).unwrap();
+                let visuals =
+                    new_item_visuals_or_empty(path, "image/png", &metadata, None, &timers);
+                (sha256, visuals.blurhash)
+            })
+            .collect::<Vec<_>>();
+
+        let overlapped = paths
+            .iter()
+            .map(|path| process_file(path.clone(), None, None, Duration::ZERO, &timers).unwrap())
+            .collect::<Vec<_>>();
+        for ((sha256, blurhash), prepared) in sequential.iter().zip(&overlapped) {
+            assert_eq!(&prepared.sha256, sha256);
+            assert_eq!(&prepared.blurhash, blurhash);
+            assert!(prepared.blurhash.is_some());
+            assert_eq!(prepared.metadata.md5.len(), 32);
+        }
+
+        let predicted = ScanTimers::default();
+        let skipped = process_file(
+            paths[0].clone(),
+            None,
+            Some(overlapped[0].sha256.as_str()),
+            Duration::ZERO,
+            &predicted,
+        )
+        .unwrap();
+        assert!(skipped.blurhash.is_none() && skipped.thumbnails.is_empty());
+        assert_eq!(predicted.thumbgen.work_secs(), 0.0);
+        assert!(predicted.hashing.work_secs() > 0.0);
+
+        let mispredicted = process_file(
+            paths[0].clone(),
+            None,
+            Some(overlapped[1].sha256.as_str()),
+            Duration::ZERO,
+            &ScanTimers::default(),
+        )
+        .unwrap();
+        assert_eq!(mispredicted.blurhash, overlapped[0].blurhash);
+    }
+
+    // Times process_file against hashing, probing and rendering one after
+    // another (best of PANOPTIKON_BENCH_RUNS, default 3) on
+    // PANOPTIKON_BENCH_FILES (default 8) noise PNGs of PANOPTIKON_BENCH_SIDE
+    // (default 2048) pixels. Run in release:
+    // `cargo test --release -p panoptikon process_file_overlap_benchmark -- --ignored --nocapture`.
+    #[test]
+    #[ignore = "benchmark; run in release with --ignored --nocapture"]
+    fn process_file_overlap_benchmark() {
+        let env_or = |name: &str, default: usize| {
+            std::env::var(name)
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(default)
+        };
+        let files = env_or("PANOPTIKON_BENCH_FILES", 8);
+        let side = env_or("PANOPTIKON_BENCH_SIDE", 2048) as u32;
+        let runs = env_or("PANOPTIKON_BENCH_RUNS", 3).max(1);
+        let test_env = test_data_dir();
+        let paths = noise_pngs(&test_env.path().join("overlap_bench"), files, side);
+        let bytes: u64 = paths
+            .iter()
+            .map(|path| fs::metadata(path).unwrap().len())
+            .sum();
+        let timers = ScanTimers::default();
+
+        let best = |run: &dyn Fn(&Path)| {
+            (0..runs)
+                .map(|_| {
+                    let start = Instant::now();
+                    paths.iter().for_each(|path| run(path));
+                    start.elapsed().as_secs_f64()
+                })
+                .fold(f64::INFINITY, f64::min)
+        };
+        let sequential = best(&|path| {
+            let (md5, _, _) = calculate_hashes(path).unwrap();
+            let metadata = extract_item_metadata_inner(path, "image/png", md5, None).unwrap();
+            new_item_visuals_or_empty(path, "image/png", &metadata, None, &timers);
+        });
+        let overlapped = best(&|path| {
+            process_file(path.to_path_buf(), None, None, Duration::ZERO, &timers).unwrap();
+        });
+        println!(
+            "{files} files, {:.1} MiB, {} cores: sequential {sequential:.3}s, overlapped {overlapped:.3}s ({:.2}x)",
+            bytes as f64 / (1024.0 * 1024.0),
+            std::thread::available_parallelism().map_or(1, |cores| cores.get()),
+            sequential / overlapped,
+        );
+    }
+
     #[test]
     fn served_directly_matches_the_thumbnail_decision() {
         // Small file: never thumbnailed, whatever the dimensions.
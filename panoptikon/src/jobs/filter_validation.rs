@@ -0,0 +1,356 @@
+//! Save-time validation of `job_filters` and `filescan_filter`.
+//!
+//! Both are only compiled when a job runs, so a typo would otherwise surface
+//! days later as a job that silently processes zero items. Each filter is
+//! preprocessed and dry-run through `build_query` for every entity it can be
+//! applied to; errors are pinned to the first failing subtree so the UI can
+//! point at the offending clause.
+//!
+//! Extraction jobs preprocess their query asynchronously, embedding any
+//! semantic search text through the inference server. Saving the config must
+//! not depend on that server, so vector-search leaves of extraction filters
+//! are skipped here and reported as warnings. The scan's deletion pass
+//! compiles `file_scan` filters synchronously, so those are checked in full.
+
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::db::system_config::SystemConfig;
+use crate::pql::build_query;
+use crate::pql::builder::filters::{Match, in_memory_match_error};
+use crate::pql::model::{
+    AndOperator, EntityType, JobFilter, NotOperator, OrOperator, PqlQuery, QueryElement,
+};
+
+/// Pseudo setter name that applies a job filter to the file scan.
+const FILE_SCAN_SETTER: &str = "file_scan";
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub(crate) struct FilterValidation {
+    /// Position in `job_filters`; absent for `filescan_filter`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index: Option<usize>,
+    pub setter_names: Vec<String>,
+    pub valid: bool,
+    /// Why the filter cannot be used; jobs would fail or match nothing.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Location of `error` in the config, e.g. `job_filters[0].pql_query.and_[1]`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// Problems that don't block saving, each prefixed with its path.
+    pub warnings: Vec<String>,
+}
+
+impl FilterValidation {
+    fn new(index: Option<usize>, setter_names: Vec<String>) -> Self {
+        Self {
+            index,
+            setter_names,
+            valid: true,
+            error: None,
+            path: None,
+            warnings: Vec::new(),
+        }
+    }
+
+    fn fail(mut self, path: impl Into<String>, error: impl Into<String>) -> Self {
+        self.valid = false;
+        self.path = Some(path.into());
+        self.error = Some(error.into());
+        self
+    }
+}
+
+/// Validates every job filter, then `filescan_filter` (reported last).
+pub(crate) fn validate_filters(config: &SystemConfig) -> Vec<FilterValidation> {
+    let mut report = config
+        .job_filters
+        .iter()
+        .enumerate()
+        .map(|(index, filter)| validate_job_filter(index, filter))
+        .collect::<Vec<_>>();
+    if let Some(filter) = &config.filescan_filter {
+        report.push(validate_filescan_filter(filter));
+    }
+    report
+}
+
+/// One `detail` line describing every invalid filter, or `None` if all pass.
+pub(crate) fn describe_invalid(report: &[FilterValidation]) -> Option<String> {
+    let errors = report
+        .iter()
+        .filter(|entry| !entry.valid)
+        .map(|entry| {
+            let label = match entry.index {
+                Some(index) => format!(
+                    "job_filters[{index}] (setters: {})",
+                    entry.setter_names.join(", ")
+                ),
+                None => "filescan_filter".to_string(),
+            };
+            format!(
+                "{label} at {}: {}",
+                entry.path.as_deref().unwrap_or_default(),
+                entry.error.as_deref().unwrap_or_default()
+            )
+        })
+        .collect::<Vec<_>>();
+    if errors.is_empty() {
+        return None;
+    }
+    Some(format!("Invalid PQL filters: {}", errors.join("; ")))
+}
+
+fn validate_job_filter(index: usize, filter: &JobFilter) -> FilterValidation {
+    let entry = FilterValidation::new(Some(index), filter.setter_names.clone());
+    let base = format!("job_filters[{index}].pql_query");
+    let scan_only = filter
+        .setter_names
+        .iter()
+        .all(|name| name == FILE_SCAN_SETTER);
+    let defer_vectors = !filter
+        .setter_names
+        .iter()
+        .any(|name| name == FILE_SCAN_SETTER);
+    // The scan deletion pass always queries files; extraction jobs query
+    // files or text depending on the model.
+    let entities: &[EntityType] = if scan_only {
+        &[EntityType::File]
+    } else {
+        &[EntityType::File, EntityType::Text]
+    };
+    check_query(entry, &filter.pql_query, &base, entities, defer_vectors)
+}
+
+fn validate_filescan_filter(filter: &Match) -> FilterValidation {
+    let entry = FilterValidation::new(None, Vec::new());
+    let element = QueryElement::Match(filter.clone());
+    let entry = check_query(
+        entry,
+        &element,
+        "filescan_filter",
+        &[EntityType::File],
+        false,
+    );
+    if !entry.valid {
+        return entry;
+    }
+    match in_memory_match_error(filter) {
+        Some((path, message)) => entry.fail(format!("filescan_filter.{path}"), message),
+        None => entry,
+    }
+}
+
+fn check_query(
+    mut entry: FilterValidation,
+    root: &QueryElement,
+    base: &str,
+    entities: &[EntityType],
+    defer_vectors: bool,
+) -> FilterValidation {
+    if entry.index.is_some() && entry.setter_names.is_empty() {
+        entry.warnings.push(format!(
+            "{base}: setter_names is empty, so no job uses this filter"
+        ));
+    }
+    let mut leaves = Vec::new();
+    let pruned = collect_leaves(root, base, defer_vectors, &mut leaves, &mut entry.warnings);
+    // Leaves first so the error points at the clause rather than the root.
+    for (path, leaf) in leaves {
+        if let Err(message) = dry_run(leaf, entities, &path, &mut Vec::new()) {
+            return entry.fail(path, message);
+        }
+    }
+    if let Some(root) = pruned
+        && let Err(message) = dry_run(root, entities, base, &mut entry.warnings)
+    {
+        return entry.fail(base, message);
+    }
+    entry
+}
+
+/// Collects the non-boolean leaves with their paths and returns the tree
+/// without deferred vector-search leaves (`None` if nothing is left).
+fn collect_leaves(
+    element: &QueryElement,
+    path: &str,
+    defer_vectors: bool,
+    leaves: &mut Vec<(String, QueryElement)>,
+    warnings: &mut Vec<String>,
+) -> Option<QueryElement> {
+    match element {
+        QueryElement::And(op) => {
+            let and_ = op
+                .and_
+                .iter()
+                .enumerate()
+                .filter_map(|(idx, child)| {
+                    let child_path = format!("{path}.and_[{idx}]");
+                    collect_leaves(child, &child_path, defer_vectors, leaves, warnings)
+                })
+                .collect::<Vec<_>>();
+            (!and_.is_empty()).then_some(QueryElement::And(AndOperator { and_ }))
+        }
+        QueryElement::Or(op) => {
+            let or_ = op
+                .or_
+                .iter()
+                .enumerate()
+                .filter_map(|(idx, child)| {
+                    let child_path = format!("{path}.or_[{idx}]");
+                    collect_leaves(child, &child_path, defer_vectors, leaves, warnings)
+                })
+                .collect::<Vec<_>>();
+            (!or_.is_empty()).then_some(QueryElement::Or(OrOperator { or_ }))
+        }
+        QueryElement::Not(op) => {
+            let child_path = format!("{path}.not_");
+            collect_leaves(&op.not_, &child_path, defer_vectors, leaves, warnings).map(|child| {
+                QueryElement::Not(NotOperator {
+                    not_: Box::new(child),
+                })
+            })
+        }
+        QueryElement::SemanticTextSearch(_)
+        | QueryElement::SemanticImageSearch(_)
+        | QueryElement::SimilarTo(_)
+            if defer_vectors =>
+        {
+            warnings.push(format!(
+                "{path}: vector search is only validated when the job runs"
+            ));
+            None
+        }
+        leaf => {
+            leaves.push((path.to_string(), leaf.clone()));
+            Some(leaf.clone())
+        }
+    }
+}
+
+/// Builds `element` for each entity. Fails only when no entity compiles;
+/// partial failures become warnings since the job's model decides which
+/// entity is queried.
+fn dry_run(
+    element: QueryElement,
+    entities: &[EntityType],
+    path: &str,
+    warnings: &mut Vec<String>,
+) -> Result<(), String> {
+    let mut failures = Vec::new();
+    for &entity in entities {
+        let query = PqlQuery {
+            query: Some(element.clone()),
+            entity,
+            page_size: 0,
+            check_path: false,
+            ..Default::default()
+        };
+        if let Err(err) = build_query(query, false) {
+            failures.push((entity, err.message));
+        }
+    }
+    if failures.len() == entities.len() {
+        let (_, message) = failures.remove(0);
+        return Err(message);
+    }
+    for (entity, message) in failures {
+        let entity = match entity {
+            EntityType::File => "file",
+            EntityType::Text => "text",
+        };
+        warnings.push(format!("{path}: fails for {entity}-entity jobs: {message}"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn config_with(value: serde_json::Value) -> SystemConfig {
+        serde_json::from_value(value).expect("system config")
+    }
+
+    // A well-formed filter passes, and vector leaves are deferred with a warning.
+    #[test]
+    fn valid_filters_pass_and_defer_vector_search() {
+        let config = config_with(json!({
+            "job_filters": [{
+                "setter_names": ["*"],
+                "pql_query": { "and_": [
+                    { "match": { "gt": { "size": 10 } } },
+                    { "text_embeddings": { "query": "cat", "model": "clip" } }
+                ] }
+            }]
+        }));
+        let report = validate_filters(&config);
+        assert_eq!(report.len(), 1);
+        assert!(report[0].valid, "{:?}", report[0]);
+        assert_eq!(report[0].warnings.len(), 1);
+        assert!(report[0].warnings[0].starts_with("job_filters[0].pql_query.and_[1]"));
+        assert!(describe_invalid(&report).is_none());
+    }
+
+    // An invalid clause is reported with its index, setters and path.
+    #[test]
+    fn invalid_clause_is_reported_with_its_path() {
+        let config = config_with(json!({
+            "job_filters": [
+                { "setter_names": ["*"], "pql_query": { "match": { "gt": { "size": 1 } } } },
+                {
+                    "setter_names": ["clip"],
+                    "pql_query": { "or_": [
+                        { "match": { "eq": { "type": "image/png" } } },
+                        { "not_": { "match": { "in_": { "size": 5 } } } }
+                    ] }
+                }
+            ]
+        }));
+        let report = validate_filters(&config);
+        assert!(report[0].valid);
+        let entry = &report[1];
+        assert!(!entry.valid);
+        assert_eq!(entry.index, Some(1));
+        assert_eq!(entry.setter_names, vec!["clip".to_string()]);
+        assert_eq!(
+            entry.path.as_deref(),
+            Some("job_filters[1].pql_query.or_[1].not_")
+        );
+        let detail = describe_invalid(&report).expect("detail");
+        assert!(detail.contains("job_filters[1] (setters: clip)"));
+    }
+
+    // Text-only columns warn for extraction filters but fail file_scan filters.
+    #[test]
+    fn text_columns_depend_on_the_entities_a_filter_reaches() {
+        let config = config_with(json!({
+            "job_filters": [
+                { "setter_names": ["*"], "pql_query": { "match": { "gt": { "text_length": 3 } } } },
+                { "setter_names": ["file_scan"], "pql_query": { "match": { "gt": { "text_length": 3 } } } }
+            ]
+        }));
+        let report = validate_filters(&config);
+        assert!(report[0].valid);
+        assert!(report[0].warnings.iter().any(|w| w.contains("file-entity")));
+        assert!(!report[1].valid);
+    }
+
+    // filescan_filter must also be evaluable in memory by the scanner.
+    #[test]
+    fn filescan_filter_rejects_in_memory_incompatible_operators() {
+        let config = config_with(json!({
+            "filescan_filter": { "match": { "eq": { "time_added": "2024-01-01" } } }
+        }));
+        let report = validate_filters(&config);
+        assert_eq!(report.len(), 1);
+        assert!(!report[0].valid);
+        assert_eq!(report[0].index, None);
+        assert_eq!(
+            report[0].path.as_deref(),
+            Some("filescan_filter.match.eq.time_added")
+        );
+    }
+}
@@ -0,0 +1,116 @@
+//! Stored frame retention. Video frames are kept mostly for the models
+//! that read them; once every setter in `frame_retention` has processed an
+//! item, each extraction job's end deletes its frames. The image frames
+//! input handler extracts them from the video again if a model needs them.
+
+use crate::api_error::ApiError;
+use crate::db::index_writer::{IndexDbWriterMessage, call_index_db_writer};
+use crate::db::system_config::FrameRetention;
+
+/// Items whose frames are deleted per writer transaction.
+const DROP_BATCH_ITEMS: i64 = 200;
+
+/// Deletes the frames of every item all of `retention`'s setters have
+/// processed, batch by batch, and returns how many frames were removed. A
+/// disabled retention removes nothing.
+pub(crate) async fn drop_processed_frames(
+    index_db: &str,
+    retention: &FrameRetention,
+) -> Result<u64, ApiError> {
+    if !retention.is_enabled() {
+        return Ok(0);
+    }
+    let mut total = 0;
+    loop {
+        let deleted = call_index_db_writer(index_db, |reply| {
+            IndexDbWriterMessage::DeleteProcessedFrames {
+                setters: retention.keep_frames_until_processed_by.clone(),
+                limit: DROP_BATCH_ITEMS,
+                reply,
+            }
+        })
+        .await?;
+        if deleted == 0 {
+            return Ok(total);
+        }
+        total += deleted;
+    }
+}
+
+/// The end-of-job pass: applies `retention` and only logs failures, since
+/// the job's own work is already written. Returns the frames deleted.
+pub(crate) async fn drop_frames_after_job(index_db: &str, retention: &FrameRetention) -> u64 {
+    match drop_processed_frames(index_db, retention).await {
+        Ok(0) => 0,
+        Ok(dropped) => {
+            tracing::info!(index_db, dropped, "dropped frames of fully processed items");
+            dropped
+        }
+        Err(err) => {
+            tracing::error!(index_db, error = ?err, "frame retention failed");
+            0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::migrations::migrate_databases_on_disk;
+    use crate::db::open_index_db_write_no_user_data;
+    use crate::db::storage::{get_frames_bytes, visuals_dir};
+    use crate::test_utils::test_data_dir;
+
+    /// Only items every listed setter has processed (a placeholder counts)
+    /// lose their frames; an empty list deletes nothing.
+    #[tokio::test]
+    async fn drops_frames_of_items_processed_by_every_listed_setter() {
+        let _test_env = test_data_dir();
+        let index_db = "frame_retention_index".to_string();
+        migrate_databases_on_disk(Some(&index_db), None)
+            .await
+            .unwrap();
+        let mut conn = open_index_db_write_no_user_data(&index_db).await.unwrap();
+        sqlx::query(
+            r#"
+INSERT INTO items (id, sha256, md5, type, time_added) VALUES
+    (1, 'aaaa11', 'md51', 'video/mp4', '2024-01-01T00:00:00'),
+    (2, 'bbbb22', 'md52', 'video/mp4', '2024-01-01T00:00:00');
+INSERT INTO setters (id, name) VALUES (1, 'clip'), (2, 'wd');
+INSERT INTO data_jobs (id, completed) VALUES (1, 1);
+INSERT INTO item_data (item_id, job_id, setter_id, data_type, idx, is_origin, is_placeholder)
+VALUES (1, 1, 1, 'clip', 0, 1, 0), (1, 1, 2, 'tags', 0, 1, 1), (2, 1, 1, 'clip', 0, 1, 0);
+INSERT INTO storage.frames (item_sha256, idx, item_mime_type, width, height, version, frame)
+VALUES
+    ('aaaa11', 0, 'video/mp4', 10, 10, 1, x'0a'),
+    ('aaaa11', 1, 'video/mp4', 10, 10, 1, x'0b'),
+    ('bbbb22', 0, 'video/mp4', 10, 10, 1, x'0c');
+            "#,
+        )
+        .execute(&mut conn)
+        .await
+        .unwrap();
+
+        let disabled = drop_processed_frames(&index_db, &FrameRetention::default())
+            .await
+            .unwrap();
+        assert_eq!(disabled, 0);
+
+        let retention = FrameRetention {
+            keep_frames_until_processed_by: vec!["clip".to_string(), "wd".to_string()],
+        };
+        let dropped = drop_processed_frames(&index_db, &retention).await.unwrap();
+        assert_eq!(dropped, 2);
+        let dir = visuals_dir(&index_db);
+        assert!(
+            get_frames_bytes(&mut conn, &dir, "aaaa11")
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(
+            get_frames_bytes(&mut conn, &dir, "bbbb22").await.unwrap(),
+            vec![vec![0x0c]]
+        );
+    }
+}
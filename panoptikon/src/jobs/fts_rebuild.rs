@@ -0,0 +1,145 @@
+//! FTS rebuild: repairs the full-text indexes when they drift from their
+//! content tables (path or text search missing rows that exist).
+//!
+//! Each index is checked and/or rebuilt by one index writer message, so the
+//! rebuild serializes with every other write while searches keep reading
+//! the previous index until it commits. The report is kept per index DB in
+//! process memory and exposed by the FTS status endpoint; it is updated
+//! after every table so a long run shows which indexes are done.
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::api_error::ApiError;
+use crate::db::extraction_write::current_iso_timestamp;
+use crate::db::fts::{FtsRepair, FtsTable};
+use crate::db::index_writer::{IndexDbWriterMessage, call_index_db_writer};
+use crate::jobs::registry::Registry;
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum FtsTarget {
+    /// The file path and filename index.
+    Paths,
+    /// The extracted text index.
+    Text,
+    #[default]
+    All,
+}
+
+impl FtsTarget {
+    fn tables(self) -> &'static [FtsTable] {
+        match self {
+            FtsTarget::Paths => &[FtsTable::FilesPath],
+            FtsTarget::Text => &[FtsTable::ExtractedText],
+            FtsTarget::All => &[FtsTable::FilesPath, FtsTable::ExtractedText],
+        }
+    }
+}
+
+/// Job options, stored as the queued job's metadata.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct FtsRebuildOptions {
+    pub target: FtsTarget,
+    pub check_first: bool,
+    pub force: bool,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub(crate) struct FtsRebuildReport {
+    pub started_at: String,
+    /// Null while the job is running, or if it failed or was cancelled.
+    pub finished_at: Option<String>,
+    pub target: FtsTarget,
+    pub check_first: bool,
+    pub force: bool,
+    /// One entry per index handled so far, in order.
+    pub tables: Vec<FtsRepair>,
+}
+
+static REPORTS: Registry<String, FtsRebuildReport> = Registry::new();
+
+/// Report of the running or most recent FTS rebuild job for `index_db` in
+/// this process.
+pub(crate) fn last_report(index_db: &str) -> Option<FtsRebuildReport> {
+    REPORTS.get(index_db)
+}
+
+fn publish_report(index_db: &str, report: &FtsRebuildReport) {
+    REPORTS.insert(index_db.to_string(), report.clone());
+}
+
+pub(crate) async fn run_fts_rebuild_job(
+    index_db: &str,
+    options: FtsRebuildOptions,
+) -> ApiResult<FtsRebuildReport> {
+    let mut report = FtsRebuildReport {
+        started_at: current_iso_timestamp(),
+        finished_at: None,
+        target: options.target,
+        check_first: options.check_first,
+        force: options.force,
+        tables: Vec::new(),
+    };
+    publish_report(index_db, &report);
+    for &table in options.target.tables() {
+        let repair = call_index_db_writer(index_db, |reply| IndexDbWriterMessage::RepairFts {
+            table,
+            check_first: options.check_first,
+            force: options.force,
+            reply,
+        })
+        .await?;
+        tracing::info!(
+            index_db,
+            table = table.name(),
+            integrity_ok = ?repair.integrity_ok,
+            rebuilt = repair.rebuilt,
+            duration = repair.duration,
+            rows = repair.rows,
+            "full-text index repair finished"
+        );
+        report.tables.push(repair);
+        publish_report(index_db, &report);
+    }
+    report.finished_at = Some(current_iso_timestamp());
+    publish_report(index_db, &report);
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::migrations::migrate_databases_on_disk;
+    use crate::test_utils::test_data_dir;
+
+    /// The job goes through the index writer for every targeted table and
+    /// publishes the finished report.
+    #[tokio::test]
+    async fn rebuild_job_reports_every_targeted_table() {
+        let _test_env = test_data_dir();
+        let index_db = "fts_rebuild_index".to_string();
+        migrate_databases_on_disk(Some(&index_db), None)
+            .await
+            .unwrap();
+
+        let options = FtsRebuildOptions {
+            check_first: true,
+            ..Default::default()
+        };
+        let report = run_fts_rebuild_job(&index_db, options).await.unwrap();
+        let tables: Vec<FtsTable> = report.tables.iter().map(|repair| repair.table).collect();
+        assert_eq!(tables, vec![FtsTable::FilesPath, FtsTable::ExtractedText]);
+        assert!(
+            report
+                .tables
+                .iter()
+                .all(|repair| repair.integrity_ok == Some(true) && !repair.rebuilt)
+        );
+        assert!(report.finished_at.is_some());
+        let published = last_report(&index_db).unwrap();
+        assert_eq!(published.finished_at, report.finished_at);
+    }
+}
@@ -0,0 +1,280 @@
+//! Per-directory `.panoptikonignore` files. Each one holds gitignore-style
+//! patterns that apply to the directory it sits in and everything below it;
+//! scans skip whatever they match. Parsed rules are cached per directory and
+//! dropped again when the watcher sees the ignore file change.
+
+use std::{
+    collections::HashMap,
+    fs,
+    io::ErrorKind,
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use crate::db::system_config::SystemConfig;
+use crate::jobs::files::glob_matches;
+
+pub(crate) const IGNORE_FILE_NAME: &str = ".panoptikonignore";
+
+/// One non-comment line of an ignore file.
+#[derive(Debug, Clone, PartialEq)]
+struct IgnoreRule {
+    /// Lowercased `/`-separated pattern segments; `**` spans any number of
+    /// directories.
+    segments: Vec<String>,
+    /// Matched against the whole path below the ignore file's directory
+    /// instead of just the last component (the pattern contained a `/`).
+    anchored: bool,
+    /// Trailing `/`: only matches directories.
+    dir_only: bool,
+    /// Leading `!`: re-includes what an earlier rule ignored.
+    negate: bool,
+}
+
+impl IgnoreRule {
+    fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (negate, pattern) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            // `\#` and `\!` match a literal leading `#` or `!`.
+            None if line.starts_with("\\#") || line.starts_with("\\!") => (false, &line[1..]),
+            None => (false, line),
+        };
+        let (dir_only, pattern) = match pattern.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, pattern),
+        };
+        let anchored = pattern.contains('/');
+        let segments = pattern
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .map(|segment| segment.to_lowercase())
+            .collect::<Vec<_>>();
+        if segments.is_empty() {
+            return None;
+        }
+        Some(Self {
+            segments,
+            anchored,
+            dir_only,
+            negate,
+        })
+    }
+
+    /// `relative` is the lowercased path below the ignore file's directory.
+    fn matches(&self, relative: &[String], is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        if self.anchored {
+            return segments_match(&self.segments, relative);
+        }
+        relative
+            .last()
+            .is_some_and(|name| glob_matches(&self.segments[0], name))
+    }
+}
+
+fn segments_match(pattern: &[String], path: &[String]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((first, rest)) if first == "**" => {
+            (0..=path.len()).any(|skip| segments_match(rest, &path[skip..]))
+        }
+        Some((first, rest)) => path
+            .split_first()
+            .is_some_and(|(name, tail)| glob_matches(first, name) && segments_match(rest, tail)),
+    }
+}
+
+fn parse_rules(contents: &str) -> Vec<IgnoreRule> {
+    contents.lines().filter_map(IgnoreRule::parse).collect()
+}
+
+/// `.panoptikonignore` lookups for one scan (`SystemConfig::respect_ignore_files`).
+#[derive(Debug, Default)]
+pub(crate) struct IgnoreFiles {
+    enabled: bool,
+    /// Rules per directory; None when the directory has no ignore file.
+    cache: Mutex<HashMap<PathBuf, Option<Arc<Vec<IgnoreRule>>>>>,
+}
+
+impl IgnoreFiles {
+    pub(crate) fn from_config(config: &SystemConfig) -> Self {
+        Self {
+            enabled: config.respect_ignore_files,
+            cache: Mutex::default(),
+        }
+    }
+
+    pub(crate) fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn rules_in(&self, dir: &Path) -> Option<Arc<Vec<IgnoreRule>>> {
+        let mut cache = self.cache.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some(rules) = cache.get(dir) {
+            return rules.clone();
+        }
+        let path = dir.join(IGNORE_FILE_NAME);
+        let rules = match fs::read_to_string(&path) {
+            Ok(contents) => Some(Arc::new(parse_rules(&contents))),
+            Err(err) if err.kind() == ErrorKind::NotFound => None,
+            Err(err) => {
+                tracing::warn!(path = %path.display(), error = %err, "failed to read ignore file");
+                None
+            }
+        };
+        cache.insert(dir.to_path_buf(), rules.clone());
+        rules
+    }
+
+    /// Whether `path` itself is ignored by the ignore files in `root` and the
+    /// directories between it and `path`. Deeper files take precedence, and
+    /// within a file the last matching rule wins. Ancestors of `root` are not
+    /// consulted; `root` itself and paths outside it are never ignored.
+    pub(crate) fn is_ignored(&self, root: &Path, path: &Path, is_dir: bool) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        let Ok(relative) = path.strip_prefix(root) else {
+            return false;
+        };
+        let components = relative
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(name) => Some(name),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let lowered = components
+            .iter()
+            .map(|name| name.to_string_lossy().to_lowercase())
+            .collect::<Vec<_>>();
+        let mut ignored = false;
+        let mut dir = root.to_path_buf();
+        for (depth, name) in components.iter().enumerate() {
+            if let Some(rules) = self.rules_in(&dir) {
+                let below = &lowered[depth..];
+                if let Some(rule) = rules.iter().rev().find(|rule| rule.matches(below, is_dir)) {
+                    ignored = !rule.negate;
+                }
+            }
+            dir.push(name);
+        }
+        ignored
+    }
+
+    /// Whether the file at `path`, or any directory between `root` and it, is
+    /// ignored. As with `.gitignore`, a file inside an ignored directory
+    /// cannot be re-included.
+    pub(crate) fn contains_file(&self, root: &Path, path: &Path) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        let Ok(relative) = path.strip_prefix(root) else {
+            return false;
+        };
+        let mut current = root.to_path_buf();
+        let mut components = relative.components().peekable();
+        while let Some(component) = components.next() {
+            current.push(component);
+            let is_dir = components.peek().is_some();
+            if self.is_ignored(root, &current, is_dir) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Drops cached rules for `dir` and everything below it, so the next
+    /// lookup re-reads their ignore files.
+    pub(crate) fn invalidate(&self, dir: &Path) {
+        let mut cache = self.cache.lock().unwrap_or_else(|err| err.into_inner());
+        cache.retain(|cached, _| !cached.starts_with(dir));
+    }
+
+    pub(crate) fn clear(&self) {
+        let mut cache = self.cache.lock().unwrap_or_else(|err| err.into_inner());
+        cache.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enabled() -> IgnoreFiles {
+        IgnoreFiles {
+            enabled: true,
+            ..Default::default()
+        }
+    }
+
+    // Ensures comments, escapes, negation, anchoring and directory-only
+    // markers are read the way .gitignore reads them.
+    #[test]
+    fn parses_gitignore_style_lines() {
+        let rules =
+            parse_rules("# comment\n\n*.TMP\n!keep.tmp\n/raw/\ndocs/**/draft?\n\\#literal\n");
+        assert_eq!(rules.len(), 5);
+        assert_eq!(rules[0].segments, vec!["*.tmp"]);
+        assert!(!rules[0].anchored);
+        assert!(rules[1].negate);
+        assert!(rules[2].anchored && rules[2].dir_only);
+        assert_eq!(rules[2].segments, vec!["raw"]);
+        assert_eq!(rules[3].segments, vec!["docs", "**", "draft?"]);
+        assert_eq!(rules[4].segments, vec!["#literal"]);
+    }
+
+    // Ensures unanchored patterns match at any depth, anchored ones only
+    // relative to their file, and `**` spans directories.
+    #[test]
+    fn matches_relative_to_the_ignore_file() {
+        let root = tempfile::TempDir::new().unwrap();
+        let base = root.path();
+        fs::create_dir_all(base.join("sub/raw")).unwrap();
+        fs::write(
+            base.join(IGNORE_FILE_NAME),
+            "*.psd\n/raw/\nexport/**/*.png\n",
+        )
+        .unwrap();
+        let ignore = enabled();
+
+        assert!(ignore.contains_file(base, &base.join("a/b/Cover.PSD")));
+        assert!(ignore.contains_file(base, &base.join("raw/a.png")));
+        assert!(!ignore.contains_file(base, &base.join("sub/raw/a.png")));
+        assert!(ignore.contains_file(base, &base.join("export/a.png")));
+        assert!(ignore.contains_file(base, &base.join("export/x/y/a.png")));
+        assert!(!ignore.contains_file(base, &base.join("export/a.jpg")));
+        assert!(!ignore.contains_file(base, &base.join("a.png")));
+        assert!(!ignore.contains_file(Path::new("/elsewhere"), &base.join("a.psd")));
+
+        let disabled = IgnoreFiles::default();
+        assert!(!disabled.contains_file(base, &base.join("a.psd")));
+    }
+
+    // Ensures a deeper ignore file overrides its parent, the last matching
+    // line wins, and invalidation picks up an edited file.
+    #[test]
+    fn nested_files_and_negation_take_precedence() {
+        let root = tempfile::TempDir::new().unwrap();
+        let base = root.path();
+        fs::create_dir_all(base.join("keep")).unwrap();
+        fs::write(base.join(IGNORE_FILE_NAME), "*.png\n!hero.png\n").unwrap();
+        fs::write(base.join("keep").join(IGNORE_FILE_NAME), "!*.png\n").unwrap();
+        let ignore = enabled();
+
+        assert!(ignore.contains_file(base, &base.join("a.png")));
+        assert!(!ignore.contains_file(base, &base.join("hero.png")));
+        assert!(!ignore.contains_file(base, &base.join("keep/a.png")));
+
+        fs::write(base.join("keep").join(IGNORE_FILE_NAME), "").unwrap();
+        assert!(!ignore.contains_file(base, &base.join("keep/a.png")));
+        ignore.invalidate(&base.join("keep"));
+        assert!(ignore.contains_file(base, &base.join("keep/a.png")));
+    }
+}
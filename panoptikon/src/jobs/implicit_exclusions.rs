@@ -0,0 +1,92 @@
+//! Directories Panoptikon writes to itself: the index and user-data DBs
+//! (with their WAL files and filesystem thumbnails) and the extraction
+//! scratch directory. Scans and the continuous watcher always skip them,
+//! whatever `excluded_folders` says, so pointing an included folder at a
+//! drive root cannot make the index watch its own writes.
+
+use std::path::{Path, PathBuf};
+
+use crate::jobs::files::normalize_path;
+
+/// The implicitly excluded roots for the data folder `data_dir`. DB files
+/// always live under the runtime data folder, so its directories are listed
+/// too when `data_dir` differs.
+pub(crate) fn implicit_excluded_roots(data_dir: &Path) -> Vec<PathBuf> {
+    let runtime = crate::config::runtime();
+    let mut candidates = vec![
+        data_dir.join("index"),
+        data_dir.join("user_data"),
+        runtime.data_folder.join("index"),
+        runtime.data_folder.join("user_data"),
+        runtime.temp_dir.clone(),
+    ];
+    // An included folder reached through a symlink is compared against the
+    // resolved location as well.
+    let resolved = candidates
+        .iter()
+        .filter_map(|path| path.canonicalize().ok())
+        .collect::<Vec<_>>();
+    candidates.extend(resolved);
+
+    let mut roots = Vec::new();
+    for candidate in candidates {
+        let root = normalize_path(&candidate.to_string_lossy(), true);
+        if !roots.contains(&root) {
+            roots.push(root);
+        }
+    }
+    roots
+}
+
+/// The implicit roots that lie inside one of `included_folders`, i.e. the
+/// ones a walk of those folders would have reached. Each is logged as a
+/// warning.
+pub(crate) fn applied_implicit_exclusions(
+    roots: &[PathBuf],
+    included_folders: &[String],
+) -> Vec<String> {
+    let mut applied = Vec::new();
+    for root in roots {
+        let Some(folder) = included_folders
+            .iter()
+            .find(|folder| root.starts_with(normalize_path(folder, true)))
+        else {
+            continue;
+        };
+        let root = root.to_string_lossy().to_string();
+        tracing::warn!(
+            folder,
+            excluded = %root,
+            "included folder contains Panoptikon's own data; excluding it from scans"
+        );
+        if !applied.contains(&root) {
+            applied.push(root);
+        }
+    }
+    applied
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Ensures only data directories under an included folder are reported.
+    #[test]
+    fn applied_exclusions_are_inside_included_folders() {
+        let data = tempfile::tempdir().unwrap();
+        let roots = implicit_excluded_roots(data.path());
+        let index_root = normalize_path(&data.path().join("index").to_string_lossy(), true);
+        assert!(roots.contains(&index_root));
+
+        let outside = tempfile::tempdir().unwrap();
+        let applied =
+            applied_implicit_exclusions(&roots, &[outside.path().to_string_lossy().to_string()]);
+        assert!(applied.is_empty());
+
+        let applied =
+            applied_implicit_exclusions(&roots, &[data.path().to_string_lossy().to_string()]);
+        let user_data_root = normalize_path(&data.path().join("user_data").to_string_lossy(), true);
+        assert!(applied.contains(&index_root.to_string_lossy().to_string()));
+        assert!(applied.contains(&user_data_root.to_string_lossy().to_string()));
+    }
+}
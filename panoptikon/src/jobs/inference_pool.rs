@@ -196,7 +196,7 @@ impl PoolState {
 pub(crate) struct JobInferenceContext {
     pub primary: InferenceApiClient,
     pub pool: InferencePool,
-    pub embedding_cache_size: usize,
+    pub embedding_cache_bytes: usize,
     /// Concurrent extraction input loaders (from the gateway's `[jobs]`
     /// config).
     pub loader_concurrency: usize,
@@ -0,0 +1,214 @@
+//! Scheduling windows for heavy jobs. The window lives in the system config
+//! (`job_window`); the job queue keeps data extraction and full folder
+//! rescans queued while their index DB's window is closed and wakes itself
+//! when it next opens. Times are local wall-clock times.
+
+use chrono::{Datelike, Duration as ChronoDuration, NaiveDate, NaiveDateTime, NaiveTime};
+
+use crate::db::system_config::{JobWindowConfig, SystemConfigStore};
+
+const DAY_NAMES: [(&str, &str); 7] = [
+    ("mon", "monday"),
+    ("tue", "tuesday"),
+    ("wed", "wednesday"),
+    ("thu", "thursday"),
+    ("fri", "friday"),
+    ("sat", "saturday"),
+    ("sun", "sunday"),
+];
+
+/// A parsed, enabled `job_window`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct JobWindow {
+    start: NaiveTime,
+    end: NaiveTime,
+    /// Opening days, indexed from Monday.
+    days: [bool; 7],
+}
+
+impl JobWindow {
+    /// Parses `config`; `None` when the window is disabled.
+    pub(crate) fn from_config(config: &JobWindowConfig) -> Result<Option<Self>, String> {
+        if !config.enabled {
+            return Ok(None);
+        }
+        let (start, end) = config
+            .allowed_hours
+            .split_once('-')
+            .ok_or_else(|| format!("expected HH:MM-HH:MM, got {:?}", config.allowed_hours))?;
+        Ok(Some(Self {
+            start: parse_time(start)?,
+            end: parse_time(end)?,
+            days: parse_days(&config.days)?,
+        }))
+    }
+
+    /// How long the window stays open once it opens; equal start and end
+    /// times keep it open all day.
+    fn length(&self) -> ChronoDuration {
+        let length = self.end - self.start;
+        if length > ChronoDuration::zero() {
+            length
+        } else {
+            length + ChronoDuration::days(1)
+        }
+    }
+
+    fn opens_on(&self, day: NaiveDate) -> bool {
+        self.days[day.weekday().num_days_from_monday() as usize]
+    }
+
+    pub(crate) fn is_open(&self, now: NaiveDateTime) -> bool {
+        // A window opened yesterday may still be open if it runs past
+        // midnight.
+        [now.date().pred_opt(), Some(now.date())]
+            .into_iter()
+            .flatten()
+            .filter(|day| self.opens_on(*day))
+            .any(|day| {
+                let opened = day.and_time(self.start);
+                opened <= now && now < opened + self.length()
+            })
+    }
+
+    /// The next opening strictly after `now`; `None` when no day opens it.
+    pub(crate) fn next_open(&self, now: NaiveDateTime) -> Option<NaiveDateTime> {
+        (0..=7)
+            .filter_map(|offset| now.date().checked_add_days(chrono::Days::new(offset)))
+            .filter(|day| self.opens_on(*day))
+            .map(|day| day.and_time(self.start))
+            .find(|opening| *opening > now)
+    }
+}
+
+fn parse_time(value: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(value.trim(), "%H:%M")
+        .map_err(|_| format!("expected a HH:MM time, got {:?}", value.trim()))
+}
+
+fn parse_day(value: &str) -> Result<usize, String> {
+    let name = value.trim().to_ascii_lowercase();
+    DAY_NAMES
+        .iter()
+        .position(|(short, long)| name == *short || name == *long)
+        .ok_or_else(|| format!("unknown day {:?}", value.trim()))
+}
+
+/// Parses `mon-fri,sun`-style day lists; a range may wrap past Sunday.
+fn parse_days(value: &str) -> Result<[bool; 7], String> {
+    let mut days = [false; 7];
+    for part in value.split(',').filter(|part| !part.trim().is_empty()) {
+        let (first, last) = match part.split_once('-') {
+            Some((first, last)) => (parse_day(first)?, parse_day(last)?),
+            None => {
+                let day = parse_day(part)?;
+                (day, day)
+            }
+        };
+        let mut day = first;
+        loop {
+            days[day] = true;
+            if day == last {
+                break;
+            }
+            day = (day + 1) % 7;
+        }
+    }
+    if !days.contains(&true) {
+        return Err("days must name at least one day".to_string());
+    }
+    Ok(days)
+}
+
+/// The enabled window of `index_db`, if any. An unreadable config or an
+/// invalid window (rejected at save time, so only a hand edit) imposes no
+/// window rather than holding jobs forever.
+pub(crate) fn configured_window(index_db: &str) -> Option<JobWindow> {
+    let config = SystemConfigStore::from_env().read(index_db).ok()?;
+    match JobWindow::from_config(&config.job_window) {
+        Ok(window) => window,
+        Err(err) => {
+            tracing::warn!(index_db, error = %err, "ignoring invalid job_window");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(hours: &str, days: &str) -> JobWindow {
+        JobWindow::from_config(&JobWindowConfig {
+            enabled: true,
+            allowed_hours: hours.to_string(),
+            days: days.to_string(),
+        })
+        .expect("valid window")
+        .expect("enabled window")
+    }
+
+    // 2026-10-16 is a Friday.
+    fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 10, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    // Ensures a window crossing midnight stays open into the next morning,
+    // including a day that does not itself open the window.
+    #[test]
+    fn overnight_window_belongs_to_its_opening_day() {
+        let fridays = window("22:00-07:00", "fri");
+        assert!(!fridays.is_open(at(16, 21, 59)));
+        assert!(fridays.is_open(at(16, 22, 0)));
+        assert!(fridays.is_open(at(17, 6, 59)));
+        assert!(!fridays.is_open(at(17, 7, 0)));
+        assert!(!fridays.is_open(at(17, 23, 0)));
+        assert_eq!(fridays.next_open(at(17, 7, 0)), Some(at(23, 22, 0)));
+        assert_eq!(fridays.next_open(at(16, 22, 0)), Some(at(23, 22, 0)));
+
+        let daily = window("22:00-07:00", "mon-sun");
+        assert!(daily.is_open(at(18, 3, 0)));
+        assert_eq!(daily.next_open(at(18, 12, 0)), Some(at(18, 22, 0)));
+    }
+
+    // Ensures same-day windows, all-day windows, and wrapping day ranges.
+    #[test]
+    fn daytime_and_all_day_windows() {
+        let weekdays = window("09:30-17:00", "mon-fri");
+        assert!(weekdays.is_open(at(16, 9, 30)));
+        assert!(!weekdays.is_open(at(16, 17, 0)));
+        assert!(!weekdays.is_open(at(17, 12, 0)));
+        assert_eq!(weekdays.next_open(at(16, 17, 0)), Some(at(19, 9, 30)));
+
+        let weekend = window("00:00-00:00", "sat-sun");
+        assert!(weekend.is_open(at(17, 0, 0)));
+        assert!(weekend.is_open(at(18, 23, 59)));
+        assert!(!weekend.is_open(at(19, 0, 0)));
+
+        let wrapping = window("10:00-11:00", "fri-mon");
+        assert!(wrapping.is_open(at(19, 10, 30)));
+        assert!(!wrapping.is_open(at(20, 10, 30)));
+    }
+
+    // Ensures invalid windows are rejected and disabled ones parse to none.
+    #[test]
+    fn invalid_windows_are_rejected() {
+        let config = |hours: &str, days: &str| JobWindowConfig {
+            enabled: true,
+            allowed_hours: hours.to_string(),
+            days: days.to_string(),
+        };
+        assert!(JobWindow::from_config(&config("22:00", "mon")).is_err());
+        assert!(JobWindow::from_config(&config("25:00-07:00", "mon")).is_err());
+        assert!(JobWindow::from_config(&config("22:00-07:00", "")).is_err());
+        assert!(JobWindow::from_config(&config("22:00-07:00", "mon-xyz")).is_err());
+        assert!(JobWindow::from_config(&config("22:00-07:00", "Monday, SUN")).is_ok());
+        assert_eq!(
+            JobWindow::from_config(&JobWindowConfig::default()),
+            Ok(None)
+        );
+    }
+}
@@ -0,0 +1,169 @@
+//! Lineage repair: deletes or detaches `item_data` rows whose `source_id`
+//! points at a deleted row (see `db::lineage`).
+//!
+//! Each batch is one index writer message, so scans and other writes keep
+//! interleaving with it. Progress is kept per index DB in process memory
+//! and exposed by the lineage endpoint; cancelling stops between batches,
+//! and the next run picks up whatever is left.
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::api_error::ApiError;
+use crate::db::extraction_write::current_iso_timestamp;
+use crate::db::index_writer::{IndexDbWriterMessage, call_index_db_writer};
+use crate::db::lineage::LineageRepairMode;
+use crate::jobs::registry::Registry;
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+/// Rows repaired per writer message when the job has no batch size.
+pub(crate) const DEFAULT_BATCH_SIZE: i64 = 500;
+
+/// Job options, stored as the queued job's metadata.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct LineageRepairOptions {
+    pub mode: LineageRepairMode,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub(crate) struct LineageRepairReport {
+    pub started_at: String,
+    /// Null while the job is running, or if it failed or was cancelled.
+    pub finished_at: Option<String>,
+    pub mode: LineageRepairMode,
+    /// Dangling rows examined so far.
+    pub scanned: u64,
+    /// Rows deleted or detached so far. Deleting a row also deletes what
+    /// was derived from it, which is not counted here.
+    pub repaired: u64,
+    /// Rows `null` mode left in place because the setter already has origin
+    /// data at the same index; `delete` mode can remove them.
+    pub skipped: u64,
+    /// Tags left without any item by deleted rows.
+    pub orphan_tags: u64,
+}
+
+static REPORTS: Registry<String, LineageRepairReport> = Registry::new();
+
+/// Report of the running or most recent lineage repair job for `index_db`
+/// in this process.
+pub(crate) fn last_report(index_db: &str) -> Option<LineageRepairReport> {
+    REPORTS.get(index_db)
+}
+
+fn publish_report(index_db: &str, report: &LineageRepairReport) {
+    REPORTS.insert(index_db.to_string(), report.clone());
+}
+
+pub(crate) async fn run_lineage_repair_job(
+    index_db: &str,
+    options: LineageRepairOptions,
+    batch_size: Option<i64>,
+) -> ApiResult<LineageRepairReport> {
+    let limit = batch_size
+        .filter(|size| *size > 0)
+        .unwrap_or(DEFAULT_BATCH_SIZE);
+    let mut report = LineageRepairReport {
+        started_at: current_iso_timestamp(),
+        finished_at: None,
+        mode: options.mode,
+        scanned: 0,
+        repaired: 0,
+        skipped: 0,
+        orphan_tags: 0,
+    };
+    publish_report(index_db, &report);
+    let mut after_id = 0;
+    loop {
+        let chunk =
+            call_index_db_writer(index_db, |reply| IndexDbWriterMessage::RepairLineageChunk {
+                mode: options.mode,
+                after_id,
+                limit,
+                reply,
+            })
+            .await?;
+        if chunk.scanned == 0 {
+            break;
+        }
+        after_id = chunk.cursor;
+        report.scanned += chunk.scanned;
+        report.repaired += chunk.repaired;
+        report.skipped += chunk.skipped;
+        publish_report(index_db, &report);
+    }
+    if options.mode == LineageRepairMode::Delete && report.repaired > 0 {
+        report.orphan_tags = call_index_db_writer(index_db, |reply| {
+            IndexDbWriterMessage::DeleteOrphanTags { reply }
+        })
+        .await?;
+    }
+    tracing::info!(
+        index_db,
+        mode = ?options.mode,
+        repaired = report.repaired,
+        skipped = report.skipped,
+        "data lineage repair finished"
+    );
+    report.finished_at = Some(current_iso_timestamp());
+    publish_report(index_db, &report);
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::lineage::get_dangling_lineage;
+    use crate::db::migrations::migrate_databases_on_disk;
+    use crate::db::open_index_db_write_no_user_data;
+    use crate::test_utils::test_data_dir;
+
+    /// Batches smaller than the backlog still repair every dangling row, and
+    /// the finished report is published.
+    #[tokio::test]
+    async fn repair_job_deletes_every_dangling_row() {
+        let _test_env = test_data_dir();
+        let index_db = "lineage_repair_index".to_string();
+        migrate_databases_on_disk(Some(&index_db), None)
+            .await
+            .unwrap();
+        {
+            let mut conn = open_index_db_write_no_user_data(&index_db).await.unwrap();
+            sqlx::query("PRAGMA foreign_keys = OFF")
+                .execute(&mut conn)
+                .await
+                .unwrap();
+            sqlx::query(
+                r#"
+INSERT INTO items (id, sha256, md5, type, time_added) VALUES
+    (1, 'sha1', 'md51', 'image/png', '2024-01-01T00:00:00');
+INSERT INTO setters (id, name) VALUES (1, 'embedder');
+INSERT INTO item_data (id, item_id, setter_id, data_type, idx, is_origin, source_id) VALUES
+    (1, 1, 1, 'text-embedding', 0, NULL, 90),
+    (2, 1, 1, 'text-embedding', 1, NULL, 91),
+    (3, 1, 1, 'text-embedding', 2, NULL, 92);
+                "#,
+            )
+            .execute(&mut conn)
+            .await
+            .unwrap();
+        }
+
+        let options = LineageRepairOptions {
+            mode: LineageRepairMode::Delete,
+        };
+        let report = run_lineage_repair_job(&index_db, options, Some(2))
+            .await
+            .unwrap();
+        assert_eq!((report.scanned, report.repaired, report.skipped), (3, 3, 0));
+        assert!(report.finished_at.is_some());
+        assert_eq!(
+            last_report(&index_db).unwrap().finished_at,
+            report.finished_at
+        );
+
+        let mut conn = open_index_db_write_no_user_data(&index_db).await.unwrap();
+        assert!(get_dangling_lineage(&mut conn).await.unwrap().is_empty());
+    }
+}
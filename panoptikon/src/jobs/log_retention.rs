@@ -0,0 +1,53 @@
+//! Extraction history pruning. The retention limits live in the system
+//! config (`extraction_log_retention`); the runner applies them after every
+//! job, and `POST /api/jobs/data/history/prune` applies them on demand.
+
+use crate::api_error::ApiError;
+use crate::db::index_writer::{IndexDbWriterMessage, call_index_db_writer};
+use crate::db::system_config::{ExtractionLogRetention, SystemConfigStore};
+
+/// Logs deleted per writer transaction, so a first prune of a long history
+/// never holds the writer for long.
+const PRUNE_BATCH_SIZE: i64 = 500;
+
+/// Deletes every log outside `retention`, batch by batch, and returns how
+/// many were removed. A disabled retention removes nothing.
+pub(crate) async fn prune_extraction_logs(
+    index_db: &str,
+    retention: &ExtractionLogRetention,
+) -> Result<u64, ApiError> {
+    if !retention.is_enabled() {
+        return Ok(0);
+    }
+    let mut total = 0;
+    loop {
+        let deleted = call_index_db_writer(index_db, |reply| IndexDbWriterMessage::PruneDataLogs {
+            keep_last_per_setter: retention.keep_last_per_setter,
+            max_age_days: retention.max_age_days,
+            batch_size: PRUNE_BATCH_SIZE,
+            reply,
+        })
+        .await?;
+        total += deleted;
+        if deleted < PRUNE_BATCH_SIZE as u64 {
+            return Ok(total);
+        }
+    }
+}
+
+/// The post-job pass: applies the configured retention and only logs
+/// failures, since the job itself has already finished.
+pub(crate) async fn prune_after_job(index_db: &str) {
+    let retention = match SystemConfigStore::from_env().read(index_db) {
+        Ok(config) => config.extraction_log_retention,
+        Err(err) => {
+            tracing::warn!(index_db, error = ?err, "skipping extraction log pruning");
+            return;
+        }
+    };
+    match prune_extraction_logs(index_db, &retention).await {
+        Ok(0) => {}
+        Ok(deleted) => tracing::info!(index_db, deleted, "pruned extraction logs"),
+        Err(err) => tracing::error!(index_db, error = ?err, "extraction log pruning failed"),
+    }
+}
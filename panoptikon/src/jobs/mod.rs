@@ -1,9 +1,33 @@
 pub(crate) mod continuous_scan;
 pub(crate) mod cron;
+pub(crate) mod data_coverage;
+pub(crate) mod db_maintenance;
 pub(crate) mod dir_poller;
+pub(crate) mod dir_snapshots;
 pub(crate) mod extraction;
+pub(crate) mod file_rescan;
+pub(crate) mod file_verification;
 pub(crate) mod files;
+pub(crate) mod fts_rebuild;
+pub(crate) mod filter_validation;
+pub(crate) mod frame_retention;
+pub(crate) mod ignore_files;
+pub(crate) mod implicit_exclusions;
 pub(crate) mod inference_pool;
+pub(crate) mod job_window;
+pub(crate) mod lineage_repair;
+pub(crate) mod log_retention;
+pub(crate) mod notifications;
+pub(crate) mod on_demand_visuals;
+pub(crate) mod pql_report;
 pub(crate) mod queue;
+pub(crate) mod registry;
+pub(crate) mod scan_io;
+pub(crate) mod search_export;
+pub(crate) mod symlinks;
+pub(crate) mod tag_import;
+pub(crate) mod tag_rethreshold;
 pub(crate) mod timing;
 pub(crate) mod vector_quants;
+pub(crate) mod visuals_regeneration;
+pub(crate) mod visuals_storage_migration;
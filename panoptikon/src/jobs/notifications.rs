@@ -0,0 +1,458 @@
+//! Webhook notifications for finished jobs and folder scans. The webhooks and
+//! their event filters live in `[notifications]` of the server config. Each
+//! event is POSTed as one small JSON object from a spawned task, so delivery
+//! never holds up the job or scan that triggered it.
+
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::task::JoinHandle;
+use utoipa::ToSchema;
+
+use crate::config::{NotificationEvent, NotificationsConfig};
+use crate::jobs::queue::JobType;
+
+/// Attempts per webhook and event, counting the first one.
+const DELIVERY_ATTEMPTS: u32 = 3;
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Failed paths sent along with a scan event.
+pub(crate) const MAX_ERROR_SAMPLES: usize = 5;
+
+/// The JSON body POSTed to a webhook. `text` and `content` carry the same
+/// one-line summary: Slack shows the former and Discord the latter, and both
+/// ignore the rest.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct NotificationPayload {
+    pub event: NotificationEvent,
+    pub text: String,
+    pub content: String,
+    pub index_db: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub job_type: Option<JobType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub queue_id: Option<i64>,
+    /// The scanned folder, for scan events.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    pub duration_secs: f64,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub counts: BTreeMap<&'static str, i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub error_samples: Vec<String>,
+}
+
+impl NotificationPayload {
+    fn new(event: NotificationEvent, index_db: &str, summary: String, elapsed: Duration) -> Self {
+        Self {
+            event,
+            text: summary.clone(),
+            content: summary,
+            index_db: index_db.to_string(),
+            job_type: None,
+            queue_id: None,
+            path: None,
+            duration_secs: elapsed.as_millis() as f64 / 1000.0,
+            counts: BTreeMap::new(),
+            error: None,
+            error_samples: Vec::new(),
+        }
+    }
+}
+
+/// Counters of one finished folder scan, as stored in its `file_scans` row.
+pub(crate) struct ScanCounts {
+    pub new_items: i64,
+    pub new_files: i64,
+    pub modified_files: i64,
+    pub unchanged_files: i64,
+    pub marked_unavailable: i64,
+    pub errors: i64,
+    pub timeouts: i64,
+    pub undecodable: i64,
+}
+
+/// Reports a job that left the runner. `error` is `None` for success;
+/// cancelled jobs are not reported at all.
+pub(crate) fn notify_job_outcome(
+    job_type: &JobType,
+    index_db: &str,
+    queue_id: i64,
+    elapsed: Duration,
+    error: Option<&str>,
+) {
+    let config = &crate::config::runtime().notifications;
+    if config.webhooks.is_empty() {
+        return;
+    }
+    let name = job_type_name(job_type);
+    let (event, summary) = match error {
+        None => (
+            NotificationEvent::JobFinished,
+            format!(
+                "Panoptikon: {name} job on {index_db} finished in {}",
+                format_duration(elapsed)
+            ),
+        ),
+        Some(error) => (
+            NotificationEvent::JobFailed,
+            format!(
+                "Panoptikon: {name} job on {index_db} failed after {}: {error}",
+                format_duration(elapsed)
+            ),
+        ),
+    };
+    let mut payload = NotificationPayload::new(event, index_db, summary, elapsed);
+    payload.job_type = Some(job_type.clone());
+    payload.queue_id = Some(queue_id);
+    payload.error = error.map(str::to_string);
+    dispatch(config, payload);
+}
+
+/// Reports one finished folder scan, plus `errors_above_threshold` when it
+/// had more errors than the configured threshold.
+pub(crate) fn notify_scan_finished(
+    index_db: &str,
+    folder: &str,
+    elapsed: Duration,
+    counts: ScanCounts,
+    error_paths: &[String],
+) {
+    let config = &crate::config::runtime().notifications;
+    if config.webhooks.is_empty() {
+        return;
+    }
+    for payload in scan_payloads(config, index_db, folder, elapsed, counts, error_paths) {
+        dispatch(config, payload);
+    }
+}
+
+fn scan_payloads(
+    config: &NotificationsConfig,
+    index_db: &str,
+    folder: &str,
+    elapsed: Duration,
+    counts: ScanCounts,
+    error_paths: &[String],
+) -> Vec<NotificationPayload> {
+    let summary = format!(
+        "Panoptikon: scan of {folder} on {index_db} finished in {}: {} new, {} modified, {} errors",
+        format_duration(elapsed),
+        counts.new_files,
+        counts.modified_files,
+        counts.errors
+    );
+    let mut finished =
+        NotificationPayload::new(NotificationEvent::ScanFinished, index_db, summary, elapsed);
+    finished.path = Some(folder.to_string());
+    finished.counts = BTreeMap::from([
+        ("new_items", counts.new_items),
+        ("new_files", counts.new_files),
+        ("modified_files", counts.modified_files),
+        ("unchanged_files", counts.unchanged_files),
+        ("marked_unavailable", counts.marked_unavailable),
+        ("errors", counts.errors),
+        ("timeouts", counts.timeouts),
+        ("undecodable", counts.undecodable),
+    ]);
+    finished.error_samples = error_paths
+        .iter()
+        .take(MAX_ERROR_SAMPLES)
+        .cloned()
+        .collect();
+
+    let mut payloads = vec![finished.clone()];
+    if counts.errors > config.error_threshold {
+        let summary = format!(
+            "Panoptikon: scan of {folder} on {index_db} had {} errors (threshold {})",
+            counts.errors, config.error_threshold
+        );
+        payloads.push(NotificationPayload {
+            event: NotificationEvent::ErrorsAboveThreshold,
+            text: summary.clone(),
+            content: summary,
+            ..finished
+        });
+    }
+    payloads
+}
+
+/// Sends `payload` to every webhook subscribed to its event, in the
+/// background. Failures are only logged.
+fn dispatch(config: &NotificationsConfig, payload: NotificationPayload) -> Option<JoinHandle<()>> {
+    let urls = config
+        .webhooks
+        .iter()
+        .filter(|webhook| webhook.wants(payload.event))
+        .map(|webhook| webhook.url.clone())
+        .collect::<Vec<_>>();
+    if urls.is_empty() {
+        return None;
+    }
+    Some(tokio::spawn(async move {
+        let deliveries = urls.iter().map(|url| deliver(url, &payload));
+        for outcome in futures_util::future::join_all(deliveries).await {
+            if let Some(error) = outcome.error {
+                tracing::warn!(
+                    webhook = %outcome.host,
+                    event = ?payload.event,
+                    error,
+                    "webhook notification failed"
+                );
+            }
+        }
+    }))
+}
+
+/// Result of delivering one event to one webhook.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub(crate) struct DeliveryOutcome {
+    /// Position of the webhook in `notifications.webhooks`.
+    pub webhook: usize,
+    /// Host of the webhook URL (the full URL usually embeds a secret).
+    pub host: String,
+    pub delivered: bool,
+    /// HTTP status of the last attempt, if the webhook answered.
+    pub status: Option<u16>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub(crate) struct NotificationTestResponse {
+    pub results: Vec<DeliveryOutcome>,
+}
+
+/// Sends a sample `job_finished` event to every configured webhook, whatever
+/// its event filter, and waits for the outcomes.
+pub(crate) async fn send_test_notification(
+    config: &NotificationsConfig,
+) -> NotificationTestResponse {
+    let payload = NotificationPayload::new(
+        NotificationEvent::JobFinished,
+        &crate::config::runtime().index_db,
+        "Panoptikon: test notification".to_string(),
+        Duration::ZERO,
+    );
+    let deliveries = config
+        .webhooks
+        .iter()
+        .map(|webhook| deliver(&webhook.url, &payload));
+    let results = futures_util::future::join_all(deliveries)
+        .await
+        .into_iter()
+        .enumerate()
+        .map(|(index, outcome)| DeliveryOutcome {
+            webhook: index,
+            ..outcome
+        })
+        .collect();
+    NotificationTestResponse { results }
+}
+
+/// POSTs `payload` to `url`, retrying network errors, 429 and 5xx answers.
+async fn deliver(url: &str, payload: &NotificationPayload) -> DeliveryOutcome {
+    let mut outcome = DeliveryOutcome {
+        webhook: 0,
+        host: webhook_host(url),
+        delivered: false,
+        status: None,
+        error: None,
+    };
+    for attempt in 1..=DELIVERY_ATTEMPTS {
+        if attempt > 1 {
+            tokio::time::sleep(RETRY_DELAY).await;
+        }
+        let retry = match client().post(url).json(payload).send().await {
+            Ok(response) => {
+                let status = response.status();
+                outcome.status = Some(status.as_u16());
+                if status.is_success() {
+                    outcome.delivered = true;
+                    outcome.error = None;
+                    return outcome;
+                }
+                outcome.error = Some(format!("webhook answered {status}"));
+                status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+            }
+            Err(err) => {
+                outcome.status = None;
+                // Webhook URLs often embed a token; keep it out of logs.
+                outcome.error = Some(err.without_url().to_string());
+                true
+            }
+        };
+        if !retry {
+            break;
+        }
+    }
+    outcome
+}
+
+fn client() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .unwrap_or_default()
+    })
+}
+
+fn webhook_host(url: &str) -> String {
+    url::Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+fn job_type_name(job_type: &JobType) -> String {
+    serde_json::to_value(job_type)
+        .ok()
+        .and_then(|value| value.as_str().map(str::to_string))
+        .unwrap_or_else(|| format!("{job_type:?}"))
+}
+
+fn format_duration(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    match secs {
+        0..60 => format!("{secs}s"),
+        60..3600 => format!("{}m {}s", secs / 60, secs % 60),
+        _ => format!("{}h {}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use axum::Json;
+    use axum::http::StatusCode;
+    use axum::routing::post;
+    use serde_json::Value;
+
+    use super::*;
+    use crate::config::WebhookConfig;
+
+    /// A local webhook receiver that records every body and answers with
+    /// `statuses` in turn (the last one repeating).
+    async fn mock_webhook(statuses: Vec<StatusCode>) -> (String, Arc<Mutex<Vec<Value>>>) {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let bodies = received.clone();
+        let router = axum::Router::new().route(
+            "/hook",
+            post(move |Json(body): Json<Value>| {
+                let bodies = bodies.clone();
+                let statuses = statuses.clone();
+                async move {
+                    let mut bodies = bodies.lock().unwrap();
+                    bodies.push(body);
+                    statuses[(bodies.len() - 1).min(statuses.len() - 1)]
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        (format!("http://{address}/hook"), received)
+    }
+
+    fn webhook(url: &str, events: Vec<NotificationEvent>) -> WebhookConfig {
+        WebhookConfig {
+            url: url.to_string(),
+            events,
+        }
+    }
+
+    // Ensures a scan over the error threshold sends both events with counts
+    // and error samples, and a filtered webhook only gets its own event.
+    #[tokio::test]
+    async fn scan_events_reach_subscribed_webhooks() {
+        let (all_url, all) = mock_webhook(vec![StatusCode::NO_CONTENT]).await;
+        let (errors_url, errors) = mock_webhook(vec![StatusCode::OK]).await;
+        let config = NotificationsConfig {
+            webhooks: vec![
+                webhook(&all_url, Vec::new()),
+                webhook(&errors_url, vec![NotificationEvent::ErrorsAboveThreshold]),
+            ],
+            error_threshold: 1,
+        };
+        let error_paths = (0..8)
+            .map(|n| format!("/data/bad{n}.png"))
+            .collect::<Vec<_>>();
+        let counts = ScanCounts {
+            new_items: 3,
+            new_files: 4,
+            modified_files: 1,
+            unchanged_files: 10,
+            marked_unavailable: 0,
+            errors: 2,
+            timeouts: 1,
+            undecodable: 1,
+        };
+        let payloads = scan_payloads(
+            &config,
+            "default",
+            "/data",
+            Duration::from_secs(75),
+            counts,
+            &error_paths,
+        );
+        for payload in payloads {
+            if let Some(handle) = dispatch(&config, payload) {
+                handle.await.unwrap();
+            }
+        }
+
+        let all = all.lock().unwrap();
+        let events = all
+            .iter()
+            .map(|body| body["event"].clone())
+            .collect::<Vec<_>>();
+        assert_eq!(events, ["scan_finished", "errors_above_threshold"]);
+        let finished = &all[0];
+        assert_eq!(finished["index_db"], "default");
+        assert_eq!(finished["path"], "/data");
+        assert_eq!(finished["duration_secs"], 75.0);
+        assert_eq!(finished["counts"]["new_files"], 4);
+        assert_eq!(finished["counts"]["errors"], 2);
+        assert_eq!(
+            finished["error_samples"].as_array().unwrap().len(),
+            MAX_ERROR_SAMPLES
+        );
+        assert_eq!(finished["text"], finished["content"]);
+        assert!(finished.get("job_type").is_none());
+        assert!(finished.get("error").is_none());
+
+        let errors = errors.lock().unwrap();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0]["event"], "errors_above_threshold");
+    }
+
+    // Ensures a failing webhook is retried and a rejected one is not.
+    #[tokio::test]
+    async fn delivery_retries_server_errors_only() {
+        let (flaky_url, flaky) =
+            mock_webhook(vec![StatusCode::BAD_GATEWAY, StatusCode::NO_CONTENT]).await;
+        let (rejecting_url, rejecting) = mock_webhook(vec![StatusCode::NOT_FOUND]).await;
+        let payload = NotificationPayload::new(
+            NotificationEvent::JobFailed,
+            "default",
+            "failed".to_string(),
+            Duration::from_secs(1),
+        );
+
+        let outcome = deliver(&flaky_url, &payload).await;
+        assert!(outcome.delivered, "{outcome:?}");
+        assert_eq!(outcome.status, Some(204));
+        assert_eq!(flaky.lock().unwrap().len(), 2);
+
+        let outcome = deliver(&rejecting_url, &payload).await;
+        assert!(!outcome.delivered);
+        assert_eq!(outcome.status, Some(404));
+        assert_eq!(rejecting.lock().unwrap().len(), 1);
+    }
+}
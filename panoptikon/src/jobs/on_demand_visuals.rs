@@ -0,0 +1,179 @@
+//! On-demand visuals: generates the thumbnails, frames and blurhash of an
+//! item the first time its thumbnail is requested without any stored,
+//! typically because a fast scan (see `SystemConfig::fast_scan`) skipped
+//! them.
+//!
+//! Generation runs in the background on a bounded set of workers and stores
+//! its results through the index DB writer like the visuals regeneration
+//! job. Requests for an item whose generation is already running share it
+//! rather than starting another. An item whose generation fails or yields
+//! nothing is not retried by this process; the regeneration job or the next
+//! full scan still gets to it.
+
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use tokio::sync::{Semaphore, watch};
+
+use crate::api_error::ApiError;
+use crate::db::open_index_db_read_no_user_data;
+use crate::db::storage::{OutdatedVisuals, get_frames_bytes, visuals_dir};
+use crate::jobs::registry::Registry;
+use crate::jobs::visuals_regeneration::{Outcome, regenerate_item, store_visuals};
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+/// Generation state per (index DB, sha256). The receiver turns true once
+/// generation finished; finished entries only remain for failed attempts.
+static ATTEMPTS: Registry<(String, String), watch::Receiver<bool>> = Registry::new();
+
+fn workers() -> &'static Semaphore {
+    static WORKERS: OnceLock<Semaphore> = OnceLock::new();
+    WORKERS.get_or_init(|| {
+        let workers = std::thread::available_parallelism()
+            .map(|count| count.get())
+            .unwrap_or(4);
+        Semaphore::new(workers)
+    })
+}
+
+/// Starts generating the visuals of `item` unless that is already running
+/// or has already failed in this process. The returned receiver turns true
+/// once the attempt is over, whatever its outcome.
+pub(crate) fn request_visuals(index_db: &str, item: OutdatedVisuals) -> watch::Receiver<bool> {
+    let key = (index_db.to_string(), item.sha256.clone());
+    let mut slots = ATTEMPTS.lock();
+    if let Some(pending) = slots.get(&key) {
+        return pending.clone();
+    }
+    let (done, pending) = watch::channel(false);
+    slots.insert(key.clone(), pending.clone());
+    drop(slots);
+
+    tokio::spawn(async move {
+        let stored = match generate(&key.0, &item).await {
+            Ok(stored) => stored,
+            Err(err) => {
+                tracing::error!(error = ?err, sha256 = item.sha256, "failed to generate visuals on demand");
+                false
+            }
+        };
+        if stored {
+            ATTEMPTS.remove(&key);
+        }
+        let _ = done.send(true);
+    });
+    pending
+}
+
+/// Waits up to `timeout` for a [`request_visuals`] attempt; false if it is
+/// still running.
+pub(crate) async fn wait_for_visuals(
+    mut pending: watch::Receiver<bool>,
+    timeout: Duration,
+) -> bool {
+    matches!(
+        tokio::time::timeout(timeout, pending.wait_for(|done| *done)).await,
+        Ok(Ok(_))
+    )
+}
+
+async fn generate(index_db: &str, item: &OutdatedVisuals) -> ApiResult<bool> {
+    let _permit = workers()
+        .acquire()
+        .await
+        .map_err(|_| ApiError::internal("Failed to schedule visuals generation"))?;
+    let existing_frames = if item.mime_type.starts_with("video") && !item.frames_outdated {
+        let mut conn = open_index_db_read_no_user_data(index_db).await?;
+        get_frames_bytes(&mut conn, &visuals_dir(index_db), &item.sha256).await?
+    } else {
+        Vec::new()
+    };
+    match regenerate_item(item, existing_frames).await {
+        Outcome::Regenerated(visuals) => {
+            store_visuals(index_db, item, visuals).await?;
+            tracing::debug!(
+                index_db,
+                sha256 = item.sha256,
+                "generated visuals on demand"
+            );
+            Ok(true)
+        }
+        Outcome::Skipped | Outcome::Failed => Ok(false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::migrations::migrate_databases_on_disk;
+    use crate::jobs::files::THUMBNAIL_PROCESS_VERSION;
+    use crate::test_utils::test_data_dir;
+
+    fn audio_item(sha256: &str, path: &std::path::Path) -> OutdatedVisuals {
+        OutdatedVisuals {
+            sha256: sha256.to_string(),
+            mime_type: "audio/mpeg".to_string(),
+            thumbnails_outdated: true,
+            frames_outdated: false,
+            path: Some(path.to_path_buf()),
+            duration: None,
+            video_tracks: None,
+        }
+    }
+
+    // Ensures concurrent requests share one generation, whose thumbnail and
+    // blurhash end up stored, and that a failed attempt is not retried.
+    #[tokio::test]
+    async fn requests_share_one_generation_and_store_it() {
+        let test_env = test_data_dir();
+        let index_db = "on_demand_visuals_index".to_string();
+        migrate_databases_on_disk(Some(&index_db), None)
+            .await
+            .unwrap();
+        // Unreadable audio still gets a rendered text thumbnail.
+        let audio_path = test_env.path().join("song.mp3");
+        std::fs::write(&audio_path, b"not really audio").unwrap();
+
+        let mut conn = crate::db::open_index_db_write_no_user_data(&index_db)
+            .await
+            .unwrap();
+        sqlx::query(
+            "INSERT INTO items (id, sha256, md5, type, time_added) VALUES (1, 'sha_audio', 'md5', 'audio/mpeg', '2024-01-01T00:00:00')",
+        )
+        .execute(&mut conn)
+        .await
+        .unwrap();
+        drop(conn);
+
+        let first = request_visuals(&index_db, audio_item("sha_audio", &audio_path));
+        let second = request_visuals(&index_db, audio_item("sha_audio", &audio_path));
+        assert!(first.same_channel(&second));
+        assert!(wait_for_visuals(first, Duration::from_secs(30)).await);
+
+        let mut conn = open_index_db_read_no_user_data(&index_db).await.unwrap();
+        let versions: Vec<i64> = sqlx::query_scalar(
+            "SELECT version FROM storage.thumbnails WHERE item_sha256 = 'sha_audio'",
+        )
+        .fetch_all(&mut conn)
+        .await
+        .unwrap();
+        assert_eq!(versions, vec![THUMBNAIL_PROCESS_VERSION]);
+        let blurhash: Option<String> =
+            sqlx::query_scalar("SELECT blurhash FROM items WHERE sha256 = 'sha_audio'")
+                .fetch_one(&mut conn)
+                .await
+                .unwrap();
+        assert!(blurhash.is_some());
+
+        // No available file: the attempt fails and later requests see it
+        // finished instead of starting another.
+        let mut missing = audio_item("sha_gone", &audio_path);
+        missing.path = None;
+        let first = request_visuals(&index_db, missing.clone());
+        assert!(wait_for_visuals(first.clone(), Duration::from_secs(30)).await);
+        let again = request_visuals(&index_db, missing);
+        assert!(again.same_channel(&first));
+        assert!(*again.borrow());
+    }
+}
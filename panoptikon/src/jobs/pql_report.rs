@@ -0,0 +1,512 @@
+//! `pql_report` jobs: run a saved or inline PQL query and deliver up to
+//! `max_results` of its results, with the total count, to a
+//! `[[reports.destinations]]` entry of the server config: POSTed to a
+//! webhook or written to a file. Enqueued through
+//! `POST /api/jobs/reports/pql` or by the cron scheduler for the index DB's
+//! `pql_reports` schedules. A report that can't be delivered after the
+//! retries fails the job.
+
+use std::path::Path;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::api::search::{
+    PqlCompiler, SearchResult, build_pql, run_pql_build, run_pql_build_count,
+};
+use crate::api_error::ApiError;
+use crate::config::{ReportDestinationConfig, ReportsConfig};
+use crate::db::open_index_db_read;
+use crate::db::saved_queries::get_saved_query;
+use crate::db::system_config::{
+    PqlReportSchedule, default_report_max_results, default_report_user,
+};
+use crate::jobs::files::current_iso_timestamp;
+use crate::jobs::inference_pool::job_inference_context;
+use crate::pql::model::{Column, PqlQuery};
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+/// Attempts per delivery, counting the first one.
+const DELIVERY_ATTEMPTS: u32 = 3;
+/// Delay before the second attempt; doubled for each later one.
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Options of one report, stored as the job's metadata.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub(crate) struct PqlReportOptions {
+    /// Names the report in its payload. Default: the saved query's name.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Saved query to run, looked up for `user` when the job runs.
+    #[serde(default)]
+    pub saved_query: Option<String>,
+    #[serde(default = "default_report_user")]
+    pub user: String,
+    /// Query to run instead of a saved one. The API resolves `saved_query`
+    /// into this field at enqueue time, so the job runs what was validated.
+    #[serde(default)]
+    #[schema(no_recursion)]
+    pub query: Option<PqlQuery>,
+    /// Columns to return instead of the query's `select`.
+    #[serde(default)]
+    pub select: Vec<Column>,
+    /// Most results to deliver, within `[reports] max_results`.
+    #[serde(default = "default_report_max_results")]
+    pub max_results: i64,
+    /// Name of a `[[reports.destinations]]` entry.
+    pub destination: String,
+}
+
+impl From<&PqlReportSchedule> for PqlReportOptions {
+    fn from(schedule: &PqlReportSchedule) -> Self {
+        Self {
+            name: Some(schedule.name.clone()),
+            saved_query: Some(schedule.saved_query.clone()),
+            user: schedule.user.clone(),
+            query: None,
+            select: schedule.select.clone(),
+            max_results: schedule.max_results,
+            destination: schedule.destination.clone(),
+        }
+    }
+}
+
+impl PqlReportOptions {
+    /// Checks what can be checked before the job runs.
+    pub(crate) fn validate(&self, reports: &ReportsConfig) -> ApiResult<()> {
+        if self.saved_query.is_none() && self.query.is_none() {
+            return Err(ApiError::bad_request(
+                "A report needs a saved_query or an inline query",
+            ));
+        }
+        if self.max_results < 1 {
+            return Err(ApiError::bad_request("max_results must be at least 1"));
+        }
+        if reports.destination(&self.destination).is_none() {
+            return Err(ApiError::bad_request(format!(
+                "Unknown report destination '{}'; destinations are configured in [reports]",
+                self.destination
+            )));
+        }
+        Ok(())
+    }
+
+    fn report_name(&self) -> String {
+        self.name
+            .clone()
+            .or_else(|| self.saved_query.clone())
+            .unwrap_or_else(|| "pql_report".to_string())
+    }
+}
+
+/// Checks an index DB's `pql_reports` before they are saved.
+pub(crate) fn validate_report_schedules(
+    schedules: &[PqlReportSchedule],
+    reports: &ReportsConfig,
+) -> ApiResult<()> {
+    let mut names = std::collections::HashSet::new();
+    for schedule in schedules {
+        if schedule.name.trim().is_empty() {
+            return Err(ApiError::bad_request("pql_reports entries need a name"));
+        }
+        if !names.insert(schedule.name.as_str()) {
+            return Err(ApiError::bad_request(format!(
+                "pql_reports name '{}' is duplicated",
+                schedule.name
+            )));
+        }
+        if let Err(err) = crate::jobs::cron::validate_cron_schedule(&schedule.schedule) {
+            return Err(ApiError::bad_request(format!(
+                "Invalid schedule {:?} of pql_reports '{}': {err}",
+                schedule.schedule, schedule.name
+            )));
+        }
+        PqlReportOptions::from(schedule).validate(reports)?;
+    }
+    Ok(())
+}
+
+/// The JSON document delivered for one report.
+#[derive(Serialize)]
+pub(crate) struct PqlReportPayload {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub saved_query: Option<String>,
+    pub index_db: String,
+    /// When the query started, local time.
+    pub executed_at: String,
+    /// Time spent compiling and running the query.
+    pub duration_ms: u64,
+    /// Every match, not just the delivered ones.
+    pub total_count: i64,
+    /// Whether `total_count` exceeds the delivered results.
+    pub truncated: bool,
+    pub results: Vec<SearchResult>,
+}
+
+pub(crate) async fn run_pql_report_job(
+    index_db: &str,
+    user_data_db: &str,
+    options: PqlReportOptions,
+) -> ApiResult<()> {
+    let reports = &crate::config::runtime().reports;
+    options.validate(reports)?;
+    let destination = reports
+        .destination(&options.destination)
+        .expect("validated above");
+    let context = job_inference_context();
+    let compiler = PqlCompiler {
+        disabled_filters: &crate::config::runtime().disabled_filters,
+        inference_client: &context.primary,
+        embedding_cache_bytes: context.embedding_cache_bytes,
+    };
+    let mut conn = open_index_db_read(index_db, user_data_db).await?;
+    let payload = build_report(
+        &mut conn,
+        &compiler,
+        index_db,
+        &options,
+        reports.max_results,
+    )
+    .await?;
+    tracing::info!(
+        index_db,
+        report = %payload.name,
+        total_count = payload.total_count,
+        results = payload.results.len(),
+        destination = %destination.name,
+        "delivering PQL report"
+    );
+    deliver_report(destination, &payload).await
+}
+
+/// Runs the report's query for its first `max_results` results (capped at
+/// `limit`) and the total count.
+pub(crate) async fn build_report(
+    conn: &mut sqlx::SqliteConnection,
+    compiler: &PqlCompiler<'_>,
+    index_db: &str,
+    options: &PqlReportOptions,
+    limit: i64,
+) -> ApiResult<PqlReportPayload> {
+    let mut query = match (&options.query, &options.saved_query) {
+        (Some(query), _) => query.clone(),
+        (None, Some(name)) => {
+            let record = get_saved_query(conn, &options.user, name)
+                .await?
+                .ok_or_else(|| {
+                    ApiError::not_found(format!(
+                        "Saved query '{name}' of user '{}' not found",
+                        options.user
+                    ))
+                })?;
+            serde_json::from_str(&record.query).map_err(|err| {
+                tracing::error!(error = %err, "failed to parse stored saved query");
+                ApiError::internal("Failed to parse stored saved query")
+            })?
+        }
+        (None, None) => {
+            return Err(ApiError::bad_request(
+                "A report needs a saved_query or an inline query",
+            ));
+        }
+    };
+    if !options.select.is_empty() {
+        query.select = options.select.clone();
+    }
+    query.page = 1;
+    query.page_size = options.max_results.clamp(1, limit.max(1));
+    query.count = true;
+    query.results = true;
+
+    let executed_at = current_iso_timestamp();
+    let started = Instant::now();
+    let payload = serde_json::to_value(&query).map_err(|err| {
+        tracing::error!(error = %err, "failed to encode report query");
+        ApiError::internal("Failed to encode the report query")
+    })?;
+    let build = build_pql(compiler, &payload, index_db).await?;
+    let results = run_pql_build(conn, &build).await?;
+    let total_count = run_pql_build_count(conn, &build)
+        .await?
+        .unwrap_or(results.len() as i64);
+    Ok(PqlReportPayload {
+        name: options.report_name(),
+        saved_query: options.saved_query.clone(),
+        index_db: index_db.to_string(),
+        executed_at,
+        duration_ms: started.elapsed().as_millis() as u64,
+        truncated: total_count > results.len() as i64,
+        total_count,
+        results,
+    })
+}
+
+/// Delivers `payload` to `destination`, retrying failed attempts. Webhooks
+/// are retried on network errors, 429 and 5xx answers; files on any error.
+pub(crate) async fn deliver_report(
+    destination: &ReportDestinationConfig,
+    payload: &PqlReportPayload,
+) -> ApiResult<()> {
+    let body = serde_json::to_vec(payload).map_err(|err| {
+        tracing::error!(error = %err, "failed to encode report");
+        ApiError::internal("Failed to encode the report")
+    })?;
+    let mut delay = RETRY_DELAY;
+    let mut error = String::new();
+    for attempt in 1..=DELIVERY_ATTEMPTS {
+        if attempt > 1 {
+            tokio::time::sleep(delay).await;
+            delay *= 2;
+        }
+        let outcome = match (&destination.url, &destination.path) {
+            (Some(url), _) => post_report(url, destination, body.clone()).await,
+            (None, Some(path)) => write_report(path, &body).await.map_err(|err| (err, true)),
+            (None, None) => Err(("destination has no url or path".to_string(), false)),
+        };
+        match outcome {
+            Ok(()) => return Ok(()),
+            Err((err, retry)) => {
+                tracing::warn!(
+                    destination = %destination.name,
+                    attempt,
+                    error = %err,
+                    "PQL report delivery failed"
+                );
+                error = err;
+                if !retry {
+                    break;
+                }
+            }
+        }
+    }
+    Err(ApiError::internal(format!(
+        "Failed to deliver the report to '{}': {error}",
+        destination.name
+    )))
+}
+
+/// One webhook attempt. The error says whether it is worth retrying.
+async fn post_report(
+    url: &str,
+    destination: &ReportDestinationConfig,
+    body: Vec<u8>,
+) -> Result<(), (String, bool)> {
+    let mut request = client()
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(body);
+    for (name, value) in &destination.headers {
+        request = request.header(name, value);
+    }
+    match request.send().await {
+        Ok(response) if response.status().is_success() => Ok(()),
+        Ok(response) => {
+            let status = response.status();
+            Err((
+                format!("webhook answered {status}"),
+                status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS,
+            ))
+        }
+        Err(err) => Err((err.without_url().to_string(), true)),
+    }
+}
+
+/// Replaces `path` with the report through a temp file in the same folder,
+/// so readers never see a partial report.
+async fn write_report(path: &Path, body: &[u8]) -> Result<(), String> {
+    let path = path.to_path_buf();
+    let body = body.to_vec();
+    tokio::task::spawn_blocking(move || {
+        let parent = path
+            .parent()
+            .ok_or_else(|| std::io::Error::other("report path has no parent folder"))?;
+        std::fs::create_dir_all(parent)?;
+        let mut temp = tempfile::NamedTempFile::new_in(parent)?;
+        std::io::Write::write_all(&mut temp, &body)?;
+        temp.persist(&path).map_err(|err| err.error)?;
+        Ok::<_, std::io::Error>(())
+    })
+    .await
+    .map_err(|err| err.to_string())?
+    .map_err(|err| err.to_string())
+}
+
+fn client() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .unwrap_or_default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::sync::{Arc, Mutex};
+
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::routing::post;
+    use serde_json::Value;
+
+    use super::*;
+    use crate::db::migrations::setup_test_databases;
+    use crate::inferio_client::InferenceApiClient;
+
+    async fn seed(conn: &mut sqlx::SqliteConnection) {
+        sqlx::query(
+            r#"
+            INSERT INTO file_scans (id, start_time, path)
+            VALUES (1, '2024-01-01T00:00:00', '/data');
+            INSERT INTO items (id, sha256, md5, type, size, time_added)
+            VALUES
+                (1, 'sha_1', 'md5_1', 'image/x-raw', 90000000, '2024-01-01T00:00:00'),
+                (2, 'sha_2', 'md5_2', 'image/x-raw', 70000000, '2024-01-01T00:00:00'),
+                (3, 'sha_3', 'md5_3', 'image/png', 1000, '2024-01-01T00:00:00');
+            INSERT INTO files (id, sha256, item_id, path, filename, last_modified, scan_id, available)
+            VALUES
+                (10, 'sha_1', 1, '/data/a.raw', 'a.raw', '2024-01-01T00:00:00', 1, 1),
+                (20, 'sha_2', 2, '/data/b.raw', 'b.raw', '2024-01-01T00:00:00', 1, 1),
+                (30, 'sha_3', 3, '/data/c.png', 'c.png', '2024-01-01T00:00:00', 1, 1);
+            INSERT INTO user_data.saved_queries (user, name, query, time_added, time_updated)
+            VALUES ('user', 'big raws',
+                '{"query": {"match": {"gt": {"size": 50000000}}}, "order_by": [{"order_by": "path"}]}',
+                '2024-01-01T00:00:00', '2024-01-01T00:00:00');
+            "#,
+        )
+        .execute(conn)
+        .await
+        .unwrap();
+    }
+
+    fn options(saved_query: &str, max_results: i64) -> PqlReportOptions {
+        PqlReportOptions {
+            name: None,
+            saved_query: Some(saved_query.to_string()),
+            user: "user".to_string(),
+            query: None,
+            select: vec![Column::Path, Column::Size],
+            max_results,
+            destination: "automation".to_string(),
+        }
+    }
+
+    // Ensures a saved query report returns the selected columns of its
+    // first max_results results with the count of every match.
+    #[tokio::test]
+    async fn saved_query_report_caps_results_and_counts_all() {
+        let mut dbs = setup_test_databases().await;
+        seed(&mut dbs.index_conn).await;
+        // Never called: the query has no vector filters.
+        let inference_client =
+            InferenceApiClient::new_with_metadata_cache("http://127.0.0.1:9".to_string(), false)
+                .unwrap();
+        let compiler = PqlCompiler {
+            disabled_filters: &[],
+            inference_client: &inference_client,
+            embedding_cache_bytes: 0,
+        };
+
+        let payload = build_report(
+            &mut dbs.index_conn,
+            &compiler,
+            "default",
+            &options("big raws", 5),
+            1,
+        )
+        .await
+        .unwrap();
+        assert_eq!(payload.name, "big raws");
+        assert_eq!(payload.total_count, 2);
+        assert!(payload.truncated);
+        let body = serde_json::to_value(&payload).unwrap();
+        assert_eq!(body["results"].as_array().unwrap().len(), 1);
+        assert_eq!(body["results"][0]["path"], "/data/a.raw");
+        assert_eq!(body["results"][0]["size"], 90000000);
+        assert!(body["results"][0].get("sha256").is_none());
+
+        let missing = build_report(
+            &mut dbs.index_conn,
+            &compiler,
+            "default",
+            &options("no such query", 5),
+            100,
+        )
+        .await;
+        assert!(missing.is_err());
+    }
+
+    // Ensures webhook delivery sends the configured headers and retries a
+    // server error, and a file destination is written whole.
+    #[tokio::test]
+    async fn report_reaches_webhook_and_file_destinations() {
+        let received = Arc::new(Mutex::new(Vec::<(Option<String>, Value)>::new()));
+        let bodies = received.clone();
+        let router = axum::Router::new().route(
+            "/hook",
+            post(
+                move |headers: HeaderMap, axum::Json(body): axum::Json<Value>| {
+                    let bodies = bodies.clone();
+                    async move {
+                        let mut bodies = bodies.lock().unwrap();
+                        let auth = headers
+                            .get("authorization")
+                            .and_then(|value| value.to_str().ok())
+                            .map(str::to_string);
+                        bodies.push((auth, body));
+                        if bodies.len() == 1 {
+                            StatusCode::BAD_GATEWAY
+                        } else {
+                            StatusCode::NO_CONTENT
+                        }
+                    }
+                },
+            ),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+        let payload = PqlReportPayload {
+            name: "nightly".to_string(),
+            saved_query: Some("big raws".to_string()),
+            index_db: "default".to_string(),
+            executed_at: "2026-10-17T03:00:00".to_string(),
+            duration_ms: 12,
+            total_count: 0,
+            truncated: false,
+            results: Vec::new(),
+        };
+        let webhook = ReportDestinationConfig {
+            name: "automation".to_string(),
+            url: Some(format!("http://{address}/hook")),
+            headers: BTreeMap::from([("Authorization".to_string(), "Bearer s3cret".to_string())]),
+            path: None,
+        };
+        deliver_report(&webhook, &payload).await.unwrap();
+        let received = received.lock().unwrap().clone();
+        assert_eq!(received.len(), 2);
+        assert_eq!(received[1].0.as_deref(), Some("Bearer s3cret"));
+        assert_eq!(received[1].1["name"], "nightly");
+        assert_eq!(received[1].1["total_count"], 0);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("reports").join("nightly.json");
+        let file = ReportDestinationConfig {
+            name: "archive".to_string(),
+            url: None,
+            headers: BTreeMap::new(),
+            path: Some(path.clone()),
+        };
+        deliver_report(&file, &payload).await.unwrap();
+        let written: Value = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(written["saved_query"], "big raws");
+        assert_eq!(written["executed_at"], "2026-10-17T03:00:00");
+    }
+}
@@ -1,17 +1,35 @@
 use std::collections::{HashMap, VecDeque};
 
-use ractor::{Actor, ActorProcessingErr, ActorRef};
+use chrono::{Local, NaiveDateTime, TimeZone};
+use ractor::concurrency::JoinHandle;
+use ractor::{Actor, ActorProcessingErr, ActorRef, MessagingErr};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use tokio::sync::{OnceCell, oneshot};
+use tracing::Instrument;
 use utoipa::ToSchema;
 
 use crate::api_error::ApiError;
+use crate::db::extraction_log::ExtractionFilterCounts;
 use crate::db::index_writer::IndexDbWriterMessage;
-use crate::db::index_writer::call_index_db_writer;
+use crate::db::index_writer::{IndexWriterStatus, call_index_db_writer, index_writer_status};
+use crate::db::system_config::{SystemConfig, SystemConfigStore};
 use crate::jobs::continuous_scan;
+use crate::jobs::db_maintenance;
 use crate::jobs::extraction;
-use crate::jobs::files::FileScanService;
+use crate::jobs::file_verification;
+use crate::jobs::files::{FileScanService, FolderRescanOptions};
+use crate::jobs::fts_rebuild;
+use crate::jobs::lineage_repair;
+use crate::jobs::job_window::{self, JobWindow};
+use crate::jobs::log_retention;
+use crate::jobs::notifications;
+use crate::jobs::pql_report;
+use crate::jobs::search_export;
 use crate::jobs::vector_quants;
+use crate::jobs::visuals_regeneration;
+use crate::jobs::visuals_storage_migration;
 
 type ApiResult<T> = std::result::Result<T, ApiError>;
 
@@ -24,6 +42,15 @@ pub(crate) enum JobType {
     FolderUpdate,
     JobDataDeletion,
     VectorQuantReconcile,
+    TextRenormalize,
+    FileVerification,
+    VisualsRegeneration,
+    VisualsStorageMigration,
+    FtsRebuild,
+    DbOptimize,
+    LineageRepair,
+    SearchExport,
+    PqlReport,
     #[cfg(test)]
     #[serde(rename = "test_sleep")]
     TestSleep,
@@ -32,6 +59,18 @@ pub(crate) enum JobType {
     TestPanic,
 }
 
+impl JobType {
+    /// Heavy jobs held back while their index DB's `job_window` is closed.
+    pub(crate) fn is_windowed(&self) -> bool {
+        match self {
+            JobType::DataExtraction | JobType::FolderRescan => true,
+            #[cfg(test)]
+            JobType::TestSleep => true,
+            _ => false,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub(crate) struct Job {
     pub queue_id: i64,
@@ -43,6 +82,26 @@ pub(crate) struct Job {
     pub threshold: Option<f64>,
     pub log_id: Option<i64>,
     pub tag: Option<String>,
+    pub dedup_key: Option<String>,
+    pub run_now: bool,
+    pub reprocess: bool,
+    pub chain: JobChain,
+}
+
+/// Longest chain of follow-up jobs: an enqueued extraction job plus at most
+/// this many generations of follow-ups it triggers.
+pub(crate) const MAX_FOLLOW_UP_DEPTH: usize = 3;
+
+/// Follow-up chaining of a data extraction job (see
+/// [`follow_up_requests`]). Empty for every other job.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct JobChain {
+    /// Inference IDs to enqueue once this job completes successfully.
+    pub follow_up: Vec<String>,
+    /// Queue ID of the job whose completion enqueued this one.
+    pub parent_queue_id: Option<i64>,
+    /// Inference IDs of the jobs that led to this one, the first first.
+    pub ancestors: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, ToSchema)]
@@ -56,6 +115,37 @@ pub(crate) struct JobModel {
     pub log_id: Option<i64>,
     pub running: bool,
     pub tag: Option<String>,
+    /// Identity of the job's work: enqueueing another job with the same key
+    /// while this one is still queued returns this job instead.
+    pub dedup_key: Option<String>,
+    /// True when an enqueue request returned this already-queued job
+    /// instead of creating a new one.
+    pub deduplicated: bool,
+    /// Enqueued with `run_now`: runs regardless of the `job_window`.
+    pub run_now: bool,
+    /// Extraction job that replaces each item's earlier data for the setter
+    /// instead of skipping already-processed items.
+    pub reprocess: bool,
+    /// Queued behind a closed `job_window`; later jobs may run first.
+    pub waiting_for_window: bool,
+    /// When the window next opens (local time, RFC 3339), while waiting.
+    pub window_opens_at: Option<String>,
+    /// Inference IDs enqueued as follow-up jobs once this one completes
+    /// successfully.
+    pub follow_up: Vec<String>,
+    /// The job whose successful completion enqueued this one.
+    pub parent_queue_id: Option<i64>,
+    /// Loaded input memory of a running extraction job against its budget;
+    /// large items lower the job's concurrency while the budget is full.
+    pub extraction_memory: Option<extraction::ExtractionMemory>,
+    /// Projected item count of a data extraction job and the items each of
+    /// its filters keeps out. Only in the enqueue response; null when the
+    /// projection failed.
+    pub filter_counts: Option<ExtractionFilterCounts>,
+    /// Time left of a data extraction job, from its measured throughput
+    /// while running or from the model's recent runs. Only in the queue
+    /// status.
+    pub eta: Option<extraction::ExtractionEta>,
 }
 
 #[derive(Debug, Clone, Serialize, ToSchema)]
@@ -63,6 +153,8 @@ pub(crate) struct QueueStatusModel {
     pub queue: Vec<JobModel>,
     /// Bounded, process-local outcomes for jobs that recently left the queue.
     pub outcomes: Vec<JobOutcomeModel>,
+    /// Backlog of each index DB writer started in this process.
+    pub index_writers: Vec<IndexWriterStatus>,
 }
 
 #[derive(Debug, Clone, Serialize, PartialEq, Eq, ToSchema)]
@@ -90,6 +182,17 @@ pub(crate) struct JobRequest {
     pub threshold: Option<f64>,
     pub log_id: Option<i64>,
     pub tag: Option<String>,
+    /// An `Enqueue` whose key matches a queued (not yet running) job returns
+    /// that job flagged `deduplicated` instead of adding another; `None`
+    /// never deduplicates.
+    pub dedup_key: Option<String>,
+    /// Bypass the index DB's `job_window` for this job.
+    pub run_now: bool,
+    /// Data extraction only: overwrite the setter's existing data for every
+    /// matching item instead of skipping processed items.
+    pub reprocess: bool,
+    /// Data extraction only: jobs to enqueue after this one succeeds.
+    pub chain: JobChain,
 }
 
 #[derive(Debug, Clone)]
@@ -118,8 +221,126 @@ impl JobModel {
             log_id: job.log_id,
             running,
             tag: job.tag.clone(),
+            dedup_key: job.dedup_key.clone(),
+            deduplicated: false,
+            run_now: job.run_now,
+            reprocess: job.reprocess,
+            waiting_for_window: false,
+            window_opens_at: None,
+            follow_up: job.chain.follow_up.clone(),
+            parent_queue_id: job.chain.parent_queue_id,
+            extraction_memory: running
+                .then(|| extraction::job_memory(job.queue_id))
+                .flatten(),
+            filter_counts: None,
+            eta: None,
+        }
+    }
+
+    fn waiting(mut self, wait: Option<Option<NaiveDateTime>>) -> Self {
+        if let Some(opens_at) = wait {
+            self.waiting_for_window = true;
+            self.window_opens_at = opens_at
+                .and_then(|opens_at| Local.from_local_datetime(&opens_at).earliest())
+                .map(|opens_at| opens_at.to_rfc3339());
+        }
+        self
+    }
+}
+
+/// Short digest of `value`'s JSON, for dedup keys over config sections.
+fn config_digest(value: &impl Serialize) -> String {
+    let json = serde_json::to_vec(value).unwrap_or_default();
+    format!("{:x}", Sha256::digest(json))[..16].to_string()
+}
+
+/// Dedup key of a data extraction job: the same model on the same index DB,
+/// with the same `job_filters` applying to it.
+pub(crate) fn extraction_dedup_key(
+    index_db: &str,
+    inference_id: &str,
+    config: &SystemConfig,
+) -> String {
+    let filters = config
+        .job_filters
+        .iter()
+        .filter(|filter| filter.applies_to(inference_id))
+        .collect::<Vec<_>>();
+    format!(
+        "data_extraction:{index_db}:{inference_id}:{}",
+        config_digest(&filters)
+    )
+}
+
+/// Requests for the follow-up jobs of a data extraction `job` that just
+/// completed, each carrying its own follow-ups from `job_settings`. A
+/// follow-up naming the job itself or one of its ancestors would loop and is
+/// dropped, as is every follow-up past [`MAX_FOLLOW_UP_DEPTH`].
+pub(crate) fn follow_up_requests(job: &Job, config: &SystemConfig) -> Vec<JobRequest> {
+    let Some(inference_id) = job.metadata.as_deref() else {
+        return Vec::new();
+    };
+    let mut ancestors = job.chain.ancestors.clone();
+    ancestors.push(inference_id.to_string());
+    let mut requests: Vec<JobRequest> = Vec::new();
+    for follow_up in &job.chain.follow_up {
+        if ancestors.contains(follow_up) {
+            tracing::warn!(
+                queue_id = job.queue_id,
+                follow_up,
+                "skipping follow-up job that would re-trigger an earlier job of its chain"
+            );
+            continue;
+        }
+        if ancestors.len() > MAX_FOLLOW_UP_DEPTH {
+            tracing::warn!(
+                queue_id = job.queue_id,
+                follow_up,
+                max_depth = MAX_FOLLOW_UP_DEPTH,
+                "skipping follow-up job past the maximum chain depth"
+            );
+            continue;
         }
+        if requests
+            .iter()
+            .any(|request| request.metadata.as_deref() == Some(follow_up))
+        {
+            continue;
+        }
+        requests.push(JobRequest {
+            job_type: JobType::DataExtraction,
+            index_db: job.index_db.clone(),
+            user_data_db: job.user_data_db.clone(),
+            metadata: Some(follow_up.clone()),
+            // Resolved from the model's metadata when the job runs.
+            batch_size: None,
+            threshold: None,
+            log_id: None,
+            tag: None,
+            dedup_key: Some(extraction_dedup_key(&job.index_db, follow_up, config)),
+            run_now: job.run_now,
+            reprocess: false,
+            chain: JobChain {
+                follow_up: extraction::resolve_follow_up(config, follow_up),
+                parent_queue_id: Some(job.queue_id),
+                ancestors: ancestors.clone(),
+            },
+        });
     }
+    requests
+}
+
+/// Dedup key of a folder rescan: the same index DB with the same included
+/// and excluded folder sets.
+pub(crate) fn folder_rescan_dedup_key(index_db: &str, config: &SystemConfig) -> String {
+    let mut included = config.included_folders.clone();
+    included.sort();
+    let mut excluded = config.excluded_folders.clone();
+    excluded.sort();
+    format!(
+        "folder_rescan:{index_db}:{}",
+        config_digest(&(included, excluded))
+    )
 }
 
 pub(crate) enum JobQueueMessage {
@@ -157,12 +378,18 @@ pub(crate) enum JobQueueMessage {
     Shutdown {
         reply: oneshot::Sender<Option<i64>>,
     },
+    /// Re-evaluates jobs waiting for their `job_window`: sent by the queue's
+    /// own timer when the earliest window opens, and after a config save.
+    RecheckWindows,
 }
 
 pub(crate) struct JobQueueActor;
 
 pub(crate) struct JobQueueArgs {
     pub runner_name: Option<String>,
+    /// The enabled `job_window` of an index DB; injected so tests need no
+    /// config files.
+    pub window_for: fn(&str) -> Option<JobWindow>,
 }
 
 pub(crate) struct JobQueueState {
@@ -173,11 +400,17 @@ pub(crate) struct JobQueueState {
     job_counter: i64,
     runner: ActorRef<JobRunnerMessage>,
     shutting_down: bool,
+    myself: ActorRef<JobQueueMessage>,
+    window_for: fn(&str) -> Option<JobWindow>,
+    /// Pending `RecheckWindows` timer and the opening it waits for.
+    window_wake: Option<(NaiveDateTime, WindowTimer)>,
 }
 
+type WindowTimer = JoinHandle<Result<(), MessagingErr<JobQueueMessage>>>;
+
 pub(crate) enum JobRunnerMessage {
     RunJob {
-        job: Job,
+        job: Box<Job>,
         reply: oneshot::Sender<ApiResult<()>>,
     },
     CancelRunning {
@@ -216,9 +449,8 @@ impl Actor for JobQueueActor {
         myself: ActorRef<Self::Msg>,
         args: Self::Arguments,
     ) -> Result<Self::State, ActorProcessingErr> {
-        let runner_name = args.runner_name;
         let (runner, _handle) = Actor::spawn(
-            runner_name,
+            args.runner_name,
             JobRunnerActor,
             JobRunnerArgs {
                 queue: myself.clone(),
@@ -237,6 +469,9 @@ impl Actor for JobQueueActor {
             job_counter: 0,
             runner,
             shutting_down: false,
+            myself,
+            window_for: args.window_for,
+            window_wake: None,
         })
     }
 
@@ -249,14 +484,38 @@ impl Actor for JobQueueActor {
         match message {
             JobQueueMessage::Enqueue { request, reply } => {
                 if state.shutting_down {
-                    let _ = reply.send(Err(ApiError::internal("Job queue is shutting down")));
+                    let _ = reply.send(Err(ApiError::job_conflict("Job queue is shutting down")));
                     return Ok(());
                 }
-                let model = push_job(state, request);
-                if state.running_job.is_none() {
-                    start_next_job(state).await;
+                let existing = request.dedup_key.as_deref().and_then(|key| {
+                    state
+                        .queue
+                        .iter()
+                        .find(|job| job.dedup_key.as_deref() == Some(key))
+                });
+                if let Some(existing) = existing {
+                    let mut model = JobModel::from_job(existing, false);
+                    model.deduplicated = true;
+                    let queue_id = model.queue_id;
+                    // A run_now duplicate promotes the queued job to run_now.
+                    if request.run_now {
+                        for job in state.queue.iter_mut() {
+                            if job.queue_id == queue_id {
+                                job.run_now = true;
+                            }
+                        }
+                        if let Some(job) = state.queued_jobs.get_mut(&queue_id) {
+                            job.run_now = true;
+                        }
+                        model.run_now = true;
+                        start_next_job(state).await;
+                    }
+                    let _ = reply.send(Ok(with_window_status(state, model)));
+                    return Ok(());
                 }
-                let _ = reply.send(Ok(model));
+                let model = push_job(state, request);
+                start_next_job(state).await;
+                let _ = reply.send(Ok(with_window_status(state, model)));
             }
             JobQueueMessage::EnqueueBatch {
                 requests,
@@ -264,7 +523,7 @@ impl Actor for JobQueueActor {
                 reply,
             } => {
                 if state.shutting_down {
-                    let _ = reply.send(Err(ApiError::internal("Job queue is shutting down")));
+                    let _ = reply.send(Err(ApiError::job_conflict("Job queue is shutting down")));
                     return Ok(());
                 }
                 let conflict = dedup.as_ref().is_some_and(|dedup| {
@@ -283,10 +542,12 @@ impl Actor for JobQueueActor {
                     let models = requests
                         .into_iter()
                         .map(|request| push_job(state, request))
+                        .collect::<Vec<_>>();
+                    start_next_job(state).await;
+                    let models = models
+                        .into_iter()
+                        .map(|model| with_window_status(state, model))
                         .collect();
-                    if state.running_job.is_none() {
-                        start_next_job(state).await;
-                    }
                     let _ = reply.send(Ok(Some(models)));
                 }
             }
@@ -295,12 +556,16 @@ impl Actor for JobQueueActor {
                 if let Some(running) = state.running_job.as_ref() {
                     queue.push(JobModel::from_job(running, true));
                 }
+                let now = Local::now().naive_local();
+                let mut windows = HashMap::new();
                 for job in state.queue.iter() {
-                    queue.push(JobModel::from_job(job, false));
+                    let wait = window_wait(state.window_for, &mut windows, job, now);
+                    queue.push(JobModel::from_job(job, false).waiting(wait));
                 }
                 let _ = reply.send(Ok(QueueStatusModel {
                     queue,
                     outcomes: state.outcomes.iter().cloned().collect(),
+                    index_writers: Vec::new(),
                 }));
             }
             JobQueueMessage::CancelQueued { queue_ids, reply } => {
@@ -354,8 +619,17 @@ impl Actor for JobQueueActor {
                     }
                 }
             }
+            JobQueueMessage::RecheckWindows => {
+                if let Some((_, timer)) = state.window_wake.take() {
+                    timer.abort();
+                }
+                start_next_job(state).await;
+            }
             JobQueueMessage::Shutdown { reply } => {
                 state.shutting_down = true;
+                if let Some((_, timer)) = state.window_wake.take() {
+                    timer.abort();
+                }
                 let dropped = state.queue.len();
                 state.queue.clear();
                 state.queued_jobs.clear();
@@ -382,6 +656,10 @@ fn push_job(state: &mut JobQueueState, request: JobRequest) -> JobModel {
         threshold: request.threshold,
         log_id: request.log_id,
         tag: request.tag,
+        dedup_key: request.dedup_key,
+        run_now: request.run_now,
+        reprocess: request.reprocess,
+        chain: request.chain,
     };
     let model = JobModel::from_job(&job, false);
     state.queue.push_back(job.clone());
@@ -406,20 +684,101 @@ fn record_outcome(
     }
 }
 
+/// Fills in the window status of an enqueue reply whose job is still queued.
+fn with_window_status(state: &JobQueueState, model: JobModel) -> JobModel {
+    let Some(job) = state.queued_jobs.get(&model.queue_id) else {
+        return model;
+    };
+    let now = Local::now().naive_local();
+    let wait = window_wait(state.window_for, &mut HashMap::new(), job, now);
+    JobModel {
+        run_now: job.run_now,
+        ..model
+    }
+    .waiting(wait)
+}
+
+/// `None` when `job` may start at `now`; otherwise it waits for its index
+/// DB's window, with the window's next opening if it has one. `windows`
+/// caches the config read per index DB.
+fn window_wait(
+    window_for: fn(&str) -> Option<JobWindow>,
+    windows: &mut HashMap<String, Option<JobWindow>>,
+    job: &Job,
+    now: NaiveDateTime,
+) -> Option<Option<NaiveDateTime>> {
+    if job.run_now || !job.job_type.is_windowed() {
+        return None;
+    }
+    let window = windows
+        .entry(job.index_db.clone())
+        .or_insert_with(|| window_for(&job.index_db))
+        .as_ref()?;
+    (!window.is_open(now)).then(|| window.next_open(now))
+}
+
+/// Arms the `RecheckWindows` timer for `opens_at` unless an earlier one is
+/// already pending. Long waits re-arm hourly so wall-clock changes (DST,
+/// clock corrections) are picked up.
+fn schedule_window_wake(state: &mut JobQueueState, opens_at: NaiveDateTime, now: NaiveDateTime) {
+    const MAX_WAKE_DELAY: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+    if let Some((pending, timer)) = state.window_wake.as_ref()
+        && *pending <= opens_at
+        && !timer.is_finished()
+    {
+        return;
+    }
+    if let Some((_, timer)) = state.window_wake.take() {
+        timer.abort();
+    }
+    // A second of slack so the timer never lands just before the opening.
+    let delay = (opens_at - now).to_std().unwrap_or_default() + std::time::Duration::from_secs(1);
+    let timer = state.myself.send_after(delay.min(MAX_WAKE_DELAY), || {
+        JobQueueMessage::RecheckWindows
+    });
+    state.window_wake = Some((opens_at, timer));
+}
+
+/// Starts the first queued job that may run now. Jobs waiting for a closed
+/// `job_window` keep their place while later jobs pass them; when every
+/// queued job waits, a timer wakes the queue at the earliest opening.
 async fn start_next_job(state: &mut JobQueueState) {
     if state.shutting_down || state.running_job.is_some() {
         return;
     }
-    let job = match state.queue.pop_front() {
-        Some(job) => job,
-        None => return,
+    let now = Local::now().naive_local();
+    let mut windows = HashMap::new();
+    let mut next_open: Option<NaiveDateTime> = None;
+    let mut runnable = None;
+    for (position, job) in state.queue.iter().enumerate() {
+        match window_wait(state.window_for, &mut windows, job, now) {
+            None => {
+                runnable = Some(position);
+                break;
+            }
+            Some(opens_at) => {
+                next_open = match (next_open, opens_at) {
+                    (Some(a), Some(b)) => Some(a.min(b)),
+                    (a, b) => a.or(b),
+                };
+            }
+        }
+    }
+    let Some(position) = runnable else {
+        if let Some(opens_at) = next_open {
+            schedule_window_wake(state, opens_at, now);
+        }
+        return;
+    };
+    let Some(job) = state.queue.remove(position) else {
+        return;
     };
     state.queued_jobs.remove(&job.queue_id);
     let (reply, rx) = oneshot::channel();
     if state
         .runner
         .send_message(JobRunnerMessage::RunJob {
-            job: job.clone(),
+            job: Box::new(job.clone()),
             reply,
         })
         .is_err()
@@ -508,11 +867,32 @@ impl Actor for JobRunnerActor {
         match message {
             JobRunnerMessage::RunJob { job, reply } => {
                 if state.running.is_some() {
-                    let _ = reply.send(Err(ApiError::internal("Job runner busy")));
+                    let _ = reply.send(Err(ApiError::job_conflict("Job runner busy")));
                     return Ok(());
                 }
                 let queue_id = job.queue_id;
-                let inner = tokio::spawn(execute_job(job));
+                let job_type = job.job_type.clone();
+                let index_db = job.index_db.clone();
+                let started = std::time::Instant::now();
+                // Everything the job logs, including index writer messages
+                // it sends, lands under this span.
+                let span = tracing::info_span!(
+                    "job",
+                    job_id = queue_id,
+                    job_type = ?job_type,
+                    index_db = %index_db
+                );
+                // Retention pruning runs inside the job's task, so the next
+                // job only starts once the history has been trimmed.
+                let inner = tokio::spawn(
+                    async move {
+                        let index_db = job.index_db.clone();
+                        let result = execute_job(*job).await;
+                        log_retention::prune_after_job(&index_db).await;
+                        result
+                    }
+                    .instrument(span),
+                );
                 let abort = inner.abort_handle();
                 // Watcher task: observes the job no matter how it ends
                 // (return, panic, or abort) and reports through the runner,
@@ -520,7 +900,9 @@ impl Actor for JobRunnerActor {
                 // cannot wedge the queue.
                 let runner = myself.clone();
                 tokio::spawn(async move {
-                    let result = match inner.await {
+                    let joined = inner.await;
+                    let cancelled = joined.as_ref().is_err_and(|err| err.is_cancelled());
+                    let result = match joined {
                         Ok(Ok(())) => JobRunResult {
                             success: true,
                             error: None,
@@ -538,6 +920,15 @@ impl Actor for JobRunnerActor {
                             error: Some(format!("Job panicked: {join_err}")),
                         },
                     };
+                    if !cancelled {
+                        notifications::notify_job_outcome(
+                            &job_type,
+                            &index_db,
+                            queue_id,
+                            started.elapsed(),
+                            result.error.as_deref(),
+                        );
+                    }
                     let _ =
                         runner.send_message(JobRunnerMessage::JobCompleted { queue_id, result });
                 });
@@ -575,13 +966,33 @@ impl Actor for JobRunnerActor {
     }
 }
 
+/// The job's options, stored as JSON in its metadata; the defaults when it
+/// has none.
+fn job_options<T: DeserializeOwned + Default>(job: &Job, kind: &str) -> Result<T, String> {
+    match job.metadata.as_deref() {
+        Some(_) => required_job_options(job, kind),
+        None => Ok(T::default()),
+    }
+}
+
+/// Like [`job_options`], for jobs that cannot run without options.
+fn required_job_options<T: DeserializeOwned>(job: &Job, kind: &str) -> Result<T, String> {
+    let metadata = job
+        .metadata
+        .as_deref()
+        .ok_or_else(|| format!("Missing {kind} options"))?;
+    serde_json::from_str(metadata).map_err(|err| format!("Invalid {kind} options: {err}"))
+}
+
 async fn execute_job(job: Job) -> Result<(), String> {
     match job.job_type {
         JobType::FolderRescan => {
+            let options: FolderRescanOptions = job_options(&job, "folder rescan")?;
             let guard = continuous_scan::pause_for_job_guarded(&job.index_db)
                 .await
                 .map_err(|err| format!("{err:?}"))?;
-            let service = FileScanService::from_env(job.index_db.clone(), job.user_data_db);
+            let service = FileScanService::from_env(job.index_db.clone(), job.user_data_db)
+                .with_force_full(options.force_full);
             let result = service.rescan_folders().await;
             guard.resume().await;
             result.map_err(|err| format!("{err:?}"))?;
@@ -604,6 +1015,7 @@ async fn execute_job(job: Job) -> Result<(), String> {
                 .await
                 .map_err(|err| format!("{err}"))?;
             vector_quants::finishing_phase(&job.index_db).await;
+            enqueue_follow_ups(&job).await;
             Ok(())
         }
         JobType::DataDeletion => {
@@ -648,6 +1060,82 @@ async fn execute_job(job: Job) -> Result<(), String> {
                 .map_err(|err| format!("{err:?}"))?;
             Ok(())
         }
+        JobType::TextRenormalize => {
+            // No continuous-scan pause: only extracted_text rows change, and
+            // continuous scans never write them.
+            extraction::run_text_renormalize_job(&job.index_db)
+                .await
+                .map_err(|err| format!("{err:?}"))
+        }
+        JobType::FileVerification => {
+            // No continuous-scan pause: files are only read, and the run
+            // writes nothing but its own verification tables.
+            let options = job_options(&job, "verification")?;
+            file_verification::run_verification_job(&job.index_db, options)
+                .await
+                .map(drop)
+                .map_err(|err| format!("{err:?}"))
+        }
+        JobType::VisualsRegeneration => {
+            // No continuous-scan pause: visuals are stored at the current
+            // version either way, and a scan racing this job on the same
+            // item just replaces the same rows.
+            visuals_regeneration::run_visuals_regeneration_job(&job.index_db, job.batch_size)
+                .await
+                .map(drop)
+                .map_err(|err| format!("{err:?}"))
+        }
+        JobType::VisualsStorageMigration => {
+            // No continuous-scan pause: only the storage of existing visuals
+            // moves, one writer transaction per batch.
+            visuals_storage_migration::run_visuals_storage_migration_job(
+                &job.index_db,
+                job.batch_size,
+            )
+            .await
+            .map(drop)
+            .map_err(|err| format!("{err:?}"))
+        }
+        JobType::FtsRebuild => {
+            // No continuous-scan pause: each rebuild is one writer
+            // transaction, so scan writes simply queue behind it.
+            let options = job_options(&job, "FTS rebuild")?;
+            fts_rebuild::run_fts_rebuild_job(&job.index_db, options)
+                .await
+                .map(drop)
+                .map_err(|err| format!("{err:?}"))
+        }
+        JobType::LineageRepair => {
+            // No continuous-scan pause: one writer transaction per batch.
+            let options = job_options(&job, "lineage repair")?;
+            lineage_repair::run_lineage_repair_job(&job.index_db, options, job.batch_size)
+                .await
+                .map(drop)
+                .map_err(|err| format!("{err:?}"))
+        }
+        JobType::SearchExport => {
+            // No continuous-scan pause: nothing is written to the index.
+            let options = required_job_options(&job, "search export")?;
+            search_export::run_search_export_job(&job.index_db, options)
+                .await
+                .map(drop)
+                .map_err(|err| format!("{err:?}"))
+        }
+        JobType::PqlReport => {
+            // No continuous-scan pause: the query only reads.
+            let options = required_job_options(&job, "PQL report")?;
+            pql_report::run_pql_report_job(&job.index_db, &job.user_data_db, options)
+                .await
+                .map_err(|err| format!("{err:?}"))
+        }
+        JobType::DbOptimize => {
+            // Pauses continuous scans itself, around the writer work only.
+            let options = job_options(&job, "optimize")?;
+            db_maintenance::run_optimize_job(&job.index_db, options)
+                .await
+                .map(drop)
+                .map_err(|err| format!("{err:?}"))
+        }
         #[cfg(test)]
         JobType::TestSleep => {
             let delay = job
@@ -663,6 +1151,36 @@ async fn execute_job(job: Job) -> Result<(), String> {
     }
 }
 
+/// Enqueues the follow-ups of a successful extraction job while it still
+/// counts as running, so they queue up behind whatever is already waiting.
+/// Failures are logged: the job itself did complete.
+async fn enqueue_follow_ups(job: &Job) {
+    if job.chain.follow_up.is_empty() {
+        return;
+    }
+    let config = match SystemConfigStore::from_env().load(&job.index_db) {
+        Ok(config) => config,
+        Err(err) => {
+            tracing::error!(error = ?err, "failed to load config for follow-up jobs");
+            return;
+        }
+    };
+    for request in follow_up_requests(job, &config) {
+        let follow_up = request.metadata.clone();
+        match enqueue_job(request).await {
+            Ok(model) => tracing::info!(
+                parent_queue_id = job.queue_id,
+                queue_id = model.queue_id,
+                follow_up,
+                "enqueued follow-up job"
+            ),
+            Err(err) => {
+                tracing::error!(error = ?err, follow_up, "failed to enqueue follow-up job")
+            }
+        }
+    }
+}
+
 static JOB_QUEUE: OnceCell<ActorRef<JobQueueMessage>> = OnceCell::const_new();
 
 pub(crate) async fn enqueue_job(request: JobRequest) -> ApiResult<JobModel> {
@@ -700,8 +1218,14 @@ pub(crate) async fn get_queue_status() -> ApiResult<QueueStatusModel> {
     queue
         .send_message(JobQueueMessage::GetQueueStatus { reply })
         .map_err(|_| ApiError::internal("Job queue unavailable"))?;
-    rx.await
-        .map_err(|_| ApiError::internal("Job queue dropped response"))?
+    let mut status = rx
+        .await
+        .map_err(|_| ApiError::internal("Job queue dropped response"))??;
+    // Asked of the writer supervisor here rather than in the queue actor, so
+    // the actor never waits on it.
+    status.index_writers = index_writer_status().await;
+    extraction::annotate_queue_eta(&mut status.queue).await;
+    Ok(status)
 }
 
 pub(crate) async fn cancel_queued_jobs(queue_ids: Vec<i64>) -> ApiResult<Vec<i64>> {
@@ -724,6 +1248,14 @@ pub(crate) async fn cancel_running_job() -> ApiResult<Option<i64>> {
         .map_err(|_| ApiError::internal("Job queue dropped response"))?
 }
 
+/// Re-evaluates waiting jobs, e.g. after a `job_window` change. A no-op when
+/// the queue was never started.
+pub(crate) fn wake_job_queue() {
+    if let Some(queue) = JOB_QUEUE.get() {
+        let _ = queue.send_message(JobQueueMessage::RecheckWindows);
+    }
+}
+
 /// Cancels the running job, drops all queued jobs, and makes the queue refuse
 /// new enqueues. Returns the cancelled running job's id, if there was one.
 /// Deliberately does not spawn the queue when it was never started.
@@ -744,6 +1276,7 @@ async fn ensure_job_queue() -> ApiResult<ActorRef<JobQueueMessage>> {
                 JobQueueActor,
                 JobQueueArgs {
                     runner_name: Some("job-runner".to_string()),
+                    window_for: job_window::configured_window,
                 },
             )
             .await
@@ -775,12 +1308,41 @@ mod tests {
             JobQueueActor,
             JobQueueArgs {
                 runner_name: Some(format!("job-runner-test-{unique}")),
+                window_for: test_window,
             },
         )
         .await
         .expect("failed to spawn test queue")
     }
 
+    static WINDOWED_DB_OPEN: std::sync::atomic::AtomicBool =
+        std::sync::atomic::AtomicBool::new(false);
+
+    /// "closed" always has a closed window, "windowed" until
+    /// `WINDOWED_DB_OPEN` is set; other index DBs have none.
+    fn test_window(index_db: &str) -> Option<JobWindow> {
+        let open = match index_db {
+            "closed" => false,
+            "windowed" => WINDOWED_DB_OPEN.load(std::sync::atomic::Ordering::SeqCst),
+            _ => true,
+        };
+        if open {
+            return None;
+        }
+        let now = Local::now();
+        let hours = format!(
+            "{}-{}",
+            (now + chrono::Duration::hours(2)).format("%H:%M"),
+            (now + chrono::Duration::hours(3)).format("%H:%M")
+        );
+        JobWindow::from_config(&crate::db::system_config::JobWindowConfig {
+            enabled: true,
+            allowed_hours: hours,
+            days: "mon-sun".to_string(),
+        })
+        .unwrap()
+    }
+
     async fn enqueue_on(queue: &ActorRef<JobQueueMessage>, request: JobRequest) -> JobModel {
         let (reply, rx) = oneshot::channel();
         queue
@@ -820,6 +1382,10 @@ mod tests {
             threshold: None,
             log_id: None,
             tag: Some("200".to_string()),
+            dedup_key: None,
+            run_now: false,
+            reprocess: false,
+            chain: JobChain::default(),
         };
         let job2 = JobRequest {
             tag: Some("50".to_string()),
@@ -854,6 +1420,10 @@ mod tests {
             threshold: None,
             log_id: None,
             tag: Some("200".to_string()),
+            dedup_key: None,
+            run_now: false,
+            reprocess: false,
+            chain: JobChain::default(),
         };
         let job2 = JobRequest {
             tag: Some("400".to_string()),
@@ -897,6 +1467,10 @@ mod tests {
             threshold: None,
             log_id: None,
             tag: Some("60000".to_string()),
+            dedup_key: None,
+            run_now: false,
+            reprocess: false,
+            chain: JobChain::default(),
         };
         let running = enqueue_on(&queue, job.clone()).await;
         let _queued = enqueue_on(&queue, job.clone()).await;
@@ -945,6 +1519,10 @@ mod tests {
             threshold: None,
             log_id: None,
             tag: None,
+            dedup_key: None,
+            run_now: false,
+            reprocess: false,
+            chain: JobChain::default(),
         };
         let sleep_job = JobRequest {
             job_type: JobType::TestSleep,
@@ -1003,6 +1581,10 @@ mod tests {
             threshold: None,
             log_id: None,
             tag: Some("cronjob".to_string()),
+            dedup_key: None,
+            run_now: false,
+            reprocess: false,
+            chain: JobChain::default(),
         };
         let dedup = || {
             Some(BatchDedup {
@@ -1045,6 +1627,269 @@ mod tests {
         handle.await.unwrap();
     }
 
+    // Ensures a matching dedup key returns the queued job instead of adding
+    // another, while a running job with that key or a keyless request does
+    // not deduplicate.
+    #[tokio::test]
+    async fn enqueue_returns_queued_job_with_same_dedup_key() {
+        let (queue, handle) = spawn_test_queue().await;
+        let job = JobRequest {
+            job_type: JobType::TestSleep,
+            index_db: "default".to_string(),
+            user_data_db: "default".to_string(),
+            metadata: None,
+            batch_size: None,
+            threshold: None,
+            log_id: None,
+            tag: Some("500".to_string()),
+            dedup_key: Some("running".to_string()),
+            run_now: false,
+            reprocess: false,
+            chain: JobChain::default(),
+        };
+        let running = enqueue_on(&queue, job.clone()).await;
+        let keyed = JobRequest {
+            dedup_key: Some("queued".to_string()),
+            ..job.clone()
+        };
+        let queued = enqueue_on(&queue, keyed.clone()).await;
+        assert!(!queued.deduplicated);
+
+        let duplicate = enqueue_on(&queue, keyed).await;
+        assert!(duplicate.deduplicated);
+        assert_eq!(duplicate.queue_id, queued.queue_id);
+        assert_eq!(duplicate.dedup_key.as_deref(), Some("queued"));
+
+        let behind_running = enqueue_on(&queue, job.clone()).await;
+        assert!(!behind_running.deduplicated);
+        assert_ne!(behind_running.queue_id, running.queue_id);
+        let unkeyed = JobRequest {
+            dedup_key: None,
+            ..job
+        };
+        assert!(!enqueue_on(&queue, unkeyed.clone()).await.deduplicated);
+        assert!(!enqueue_on(&queue, unkeyed).await.deduplicated);
+
+        let status = status_on(&queue).await;
+        assert_eq!(status.queue.len(), 5);
+
+        queue.stop(None);
+        handle.await.unwrap();
+    }
+
+    // Ensures a job outside its window waits while a later job runs, and
+    // starts once a recheck finds the window open.
+    #[tokio::test]
+    async fn windowed_job_waits_for_its_window() {
+        let (queue, handle) = spawn_test_queue().await;
+        let job = JobRequest {
+            job_type: JobType::TestSleep,
+            index_db: "windowed".to_string(),
+            user_data_db: "default".to_string(),
+            metadata: None,
+            batch_size: None,
+            threshold: None,
+            log_id: None,
+            tag: Some("200".to_string()),
+            dedup_key: None,
+            run_now: false,
+            reprocess: false,
+            chain: JobChain::default(),
+        };
+        let waiting = enqueue_on(&queue, job.clone()).await;
+        assert!(waiting.waiting_for_window);
+        assert!(waiting.window_opens_at.is_some());
+        let unwindowed = JobRequest {
+            index_db: "default".to_string(),
+            ..job
+        };
+        let passed = enqueue_on(&queue, unwindowed).await;
+        assert!(!passed.waiting_for_window);
+
+        let status = status_on(&queue).await;
+        assert_eq!(status.queue.len(), 2);
+        assert_eq!(status.queue[0].queue_id, passed.queue_id);
+        assert!(status.queue[0].running);
+        assert_eq!(status.queue[1].queue_id, waiting.queue_id);
+        assert!(status.queue[1].waiting_for_window);
+
+        tokio::time::sleep(std::time::Duration::from_millis(400)).await;
+        let status = status_on(&queue).await;
+        assert_eq!(status.queue.len(), 1);
+        assert!(!status.queue[0].running);
+
+        WINDOWED_DB_OPEN.store(true, std::sync::atomic::Ordering::SeqCst);
+        queue.send_message(JobQueueMessage::RecheckWindows).unwrap();
+        let status = status_on(&queue).await;
+        assert_eq!(status.queue[0].queue_id, waiting.queue_id);
+        assert!(status.queue[0].running);
+        assert!(!status.queue[0].waiting_for_window);
+
+        queue.stop(None);
+        handle.await.unwrap();
+    }
+
+    // Ensures run_now bypasses a closed window, including when it promotes
+    // an already-queued duplicate.
+    #[tokio::test]
+    async fn run_now_bypasses_the_window() {
+        let (queue, handle) = spawn_test_queue().await;
+        let job = JobRequest {
+            job_type: JobType::TestSleep,
+            index_db: "closed".to_string(),
+            user_data_db: "default".to_string(),
+            metadata: None,
+            batch_size: None,
+            threshold: None,
+            log_id: None,
+            tag: Some("200".to_string()),
+            dedup_key: Some("closed".to_string()),
+            run_now: false,
+            reprocess: false,
+            chain: JobChain::default(),
+        };
+        let waiting = enqueue_on(&queue, job.clone()).await;
+        assert!(waiting.waiting_for_window);
+
+        let promoted = enqueue_on(
+            &queue,
+            JobRequest {
+                run_now: true,
+                reprocess: false,
+                ..job.clone()
+            },
+        )
+        .await;
+        assert!(promoted.deduplicated);
+        assert!(promoted.run_now);
+        assert_eq!(promoted.queue_id, waiting.queue_id);
+        let status = status_on(&queue).await;
+        assert_eq!(status.queue[0].queue_id, waiting.queue_id);
+        assert!(status.queue[0].running);
+
+        let direct = enqueue_on(
+            &queue,
+            JobRequest {
+                dedup_key: None,
+                run_now: true,
+                reprocess: false,
+                ..job
+            },
+        )
+        .await;
+        assert!(!direct.waiting_for_window);
+
+        queue.stop(None);
+        handle.await.unwrap();
+    }
+
+    // Ensures extraction keys change with the job filters that apply to the
+    // model and rescan keys ignore folder order.
+    #[test]
+    fn dedup_keys_follow_the_relevant_config() {
+        let mut config = SystemConfig::default();
+        let base = extraction_dedup_key("default", "clip", &config);
+        assert!(base.starts_with("data_extraction:default:clip:"));
+        assert_ne!(base, extraction_dedup_key("other", "clip", &config));
+
+        config.job_filters.push(crate::pql::model::JobFilter {
+            setter_names: vec!["tagger".to_string()],
+            pql_query: crate::pql::model::QueryElement::And(crate::pql::model::AndOperator {
+                and_: Vec::new(),
+            }),
+        });
+        assert_eq!(extraction_dedup_key("default", "clip", &config), base);
+        config.job_filters[0].setter_names.push("*".to_string());
+        assert_ne!(extraction_dedup_key("default", "clip", &config), base);
+
+        config.included_folders = vec!["/a".to_string(), "/b".to_string()];
+        let rescan = folder_rescan_dedup_key("default", &config);
+        config.included_folders.reverse();
+        assert_eq!(folder_rescan_dedup_key("default", &config), rescan);
+        config.excluded_folders = vec!["/a/tmp".to_string()];
+        assert_ne!(folder_rescan_dedup_key("default", &config), rescan);
+    }
+
+    // Ensures follow-ups carry the chain forward with their own configured
+    // follow-ups (a model's entry over its group's), and that a follow-up
+    // re-triggering an ancestor or going past the maximum depth is dropped.
+    #[test]
+    fn follow_up_requests_extend_the_chain_without_loops() {
+        let setting = |group: &str, inference_id: Option<&str>, follow_up: &[&str]| {
+            crate::db::system_config::JobSettings {
+                group_name: group.to_string(),
+                inference_id: inference_id.map(str::to_string),
+                default_batch_size: None,
+                default_threshold: None,
+                follow_up: follow_up.iter().map(|id| id.to_string()).collect(),
+                per_frame_tags: None,
+            }
+        };
+        let config = SystemConfig {
+            job_settings: vec![
+                setting("embed", None, &["tags/wd"]),
+                setting("embed", Some("embed/minilm"), &["ocr/doctr"]),
+            ],
+            ..Default::default()
+        };
+        let ocr = Job {
+            queue_id: 12,
+            job_type: JobType::DataExtraction,
+            index_db: "default".to_string(),
+            user_data_db: "user_data".to_string(),
+            metadata: Some("ocr/doctr".to_string()),
+            batch_size: Some(8),
+            threshold: None,
+            log_id: None,
+            tag: None,
+            dedup_key: None,
+            run_now: true,
+            reprocess: true,
+            chain: JobChain {
+                follow_up: vec![
+                    "embed/minilm".to_string(),
+                    "embed/other".to_string(),
+                    "ocr/doctr".to_string(),
+                    "embed/minilm".to_string(),
+                ],
+                parent_queue_id: None,
+                ancestors: Vec::new(),
+            },
+        };
+
+        let requests = follow_up_requests(&ocr, &config);
+        let ids: Vec<_> = requests
+            .iter()
+            .map(|r| r.metadata.clone().unwrap())
+            .collect();
+        assert_eq!(ids, ["embed/minilm", "embed/other"]);
+        let minilm = &requests[0];
+        assert_eq!(minilm.chain.parent_queue_id, Some(12));
+        assert_eq!(minilm.chain.ancestors, ["ocr/doctr"]);
+        assert_eq!(minilm.chain.follow_up, ["ocr/doctr"]);
+        assert_eq!(requests[1].chain.follow_up, ["tags/wd"]);
+        assert!(minilm.run_now && !minilm.reprocess);
+        assert_eq!(minilm.batch_size, None);
+        assert_eq!(
+            minilm.dedup_key.as_deref(),
+            Some(extraction_dedup_key("default", "embed/minilm", &config).as_str())
+        );
+
+        // embed/minilm -> ocr/doctr would loop back to the chain's start.
+        let mut embed = ocr.clone();
+        embed.metadata = minilm.metadata.clone();
+        embed.chain = minilm.chain.clone();
+        assert!(follow_up_requests(&embed, &config).is_empty());
+
+        let mut deep = ocr.clone();
+        deep.chain.ancestors = (0..MAX_FOLLOW_UP_DEPTH)
+            .map(|depth| format!("m/{depth}"))
+            .collect();
+        assert!(follow_up_requests(&deep, &config).is_empty());
+        deep.chain.ancestors.pop();
+        assert_eq!(follow_up_requests(&deep, &config).len(), 2);
+    }
+
     #[tokio::test]
     async fn cancel_queued_job_removes_it() {
         let (queue, handle) = spawn_test_queue().await;
@@ -1057,6 +1902,10 @@ mod tests {
             threshold: None,
             log_id: None,
             tag: Some("200".to_string()),
+            dedup_key: None,
+            run_now: false,
+            reprocess: false,
+            chain: JobChain::default(),
         };
         let job2 = JobRequest {
             tag: Some("200".to_string()),
@@ -1092,6 +1941,10 @@ mod tests {
             threshold: None,
             log_id: None,
             tag: Some("500".to_string()),
+            dedup_key: None,
+            run_now: false,
+            reprocess: false,
+            chain: JobChain::default(),
         };
         let running = enqueue_on(&queue, job).await;
 
@@ -0,0 +1,50 @@
+//! Process-wide keyed state shared between jobs and the API: the last report
+//! of a maintenance job per index DB, handles of running extraction jobs,
+//! in-flight builds. A panic while the lock is held leaves the map usable.
+
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::{Mutex, MutexGuard, OnceLock};
+
+pub(crate) struct Registry<K, V> {
+    map: OnceLock<Mutex<HashMap<K, V>>>,
+}
+
+impl<K: Eq + Hash, V> Registry<K, V> {
+    pub(crate) const fn new() -> Self {
+        Self {
+            map: OnceLock::new(),
+        }
+    }
+
+    /// The map, for lookups that update an entry in place.
+    pub(crate) fn lock(&self) -> MutexGuard<'_, HashMap<K, V>> {
+        self.map
+            .get_or_init(|| Mutex::new(HashMap::new()))
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+    }
+
+    pub(crate) fn insert(&self, key: K, value: V) {
+        self.lock().insert(key, value);
+    }
+
+    pub(crate) fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.lock().remove(key)
+    }
+}
+
+impl<K: Eq + Hash, V: Clone> Registry<K, V> {
+    pub(crate) fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.lock().get(key).cloned()
+    }
+}
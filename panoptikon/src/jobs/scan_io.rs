@@ -0,0 +1,231 @@
+//! Per-folder file scan concurrency. A folder's `folder_scan_settings` entry
+//! either fixes the worker count or names the storage it lives on; `auto`
+//! measures the storage before each scan.
+
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use walkdir::WalkDir;
+
+use crate::db::system_config::{FolderScanSettings, IoProfile, SystemConfig};
+
+const HDD_WORKERS: usize = 2;
+const NETWORK_WORKERS: usize = 4;
+/// Files smaller than this are skipped by the probe: reads inside them stay
+/// within one readahead window and look sequential.
+const PROBE_MIN_FILE_SIZE: u64 = 1024 * 1024;
+const PROBE_MAX_FILES: usize = 8;
+/// Walk budget for finding probe files, so a folder of tiny files does not
+/// cost a full directory walk before the real one.
+const PROBE_MAX_ENTRIES: usize = 500;
+const PROBE_BLOCK_SIZE: usize = 4096;
+const PROBE_SEQUENTIAL_READS: u64 = 256;
+const PROBE_RANDOM_READS: u64 = 32;
+/// Average random read latency at or above which storage counts as seeking.
+/// SSDs answer uncached 4 KiB reads in well under a millisecond.
+const HDD_RANDOM_LATENCY: Duration = Duration::from_millis(2);
+/// Random reads must also be this many times slower than sequential ones,
+/// so a uniformly slow device is not mistaken for a seeking one.
+const HDD_RANDOM_TO_SEQUENTIAL_RATIO: u32 = 10;
+
+/// The settings entry with the longest path containing `folder`.
+pub(crate) fn folder_scan_settings<'a>(
+    config: &'a SystemConfig,
+    folder: &Path,
+) -> Option<&'a FolderScanSettings> {
+    config
+        .folder_scan_settings
+        .iter()
+        .filter(|settings| !settings.path.is_empty() && folder.starts_with(&settings.path))
+        .max_by_key(|settings| Path::new(&settings.path).components().count())
+}
+
+/// Number of concurrent file workers to scan `folder` with. `ssd_workers` is
+/// the CPU-sized default used for `ssd` storage and unconfigured folders.
+pub(crate) async fn folder_worker_count(
+    config: &SystemConfig,
+    folder: &Path,
+    ssd_workers: usize,
+) -> usize {
+    let Some(settings) = folder_scan_settings(config, folder) else {
+        return ssd_workers;
+    };
+    if let Some(worker_count) = settings.worker_count {
+        return worker_count.max(1);
+    }
+    let profile = match settings.io_profile {
+        IoProfile::Auto => {
+            let root = folder.to_path_buf();
+            let probed = tokio::task::spawn_blocking(move || probe_io_profile(&root))
+                .await
+                .ok()
+                .flatten();
+            tracing::info!(
+                folder = %folder.display(),
+                io_profile = ?probed,
+                "probed folder storage for scan concurrency"
+            );
+            probed.unwrap_or(IoProfile::Ssd)
+        }
+        profile => profile,
+    };
+    profile_worker_count(profile, ssd_workers)
+}
+
+fn profile_worker_count(profile: IoProfile, ssd_workers: usize) -> usize {
+    match profile {
+        IoProfile::Hdd => HDD_WORKERS,
+        IoProfile::Network => NETWORK_WORKERS,
+        IoProfile::Ssd | IoProfile::Auto => ssd_workers,
+    }
+}
+
+/// Times sequential 4 KiB reads from the start of one file against reads at
+/// scattered offsets across several, and classifies the storage as `hdd` or
+/// `ssd`. None when the folder has no file large enough to measure.
+fn probe_io_profile(folder: &Path) -> Option<IoProfile> {
+    let files = probe_files(folder);
+    let (first, _) = files.first()?;
+
+    let mut buf = vec![0_u8; PROBE_BLOCK_SIZE];
+    let mut file = File::open(first).ok()?;
+    let started = Instant::now();
+    for _ in 0..PROBE_SEQUENTIAL_READS {
+        file.read_exact(&mut buf).ok()?;
+    }
+    let sequential = started.elapsed() / PROBE_SEQUENTIAL_READS as u32;
+
+    let mut handles = Vec::with_capacity(files.len());
+    for (path, len) in &files {
+        handles.push((File::open(path).ok()?, *len));
+    }
+    let started = Instant::now();
+    for index in 0..PROBE_RANDOM_READS {
+        let (file, len) = &mut handles[index as usize % files.len()];
+        // Spread offsets with a multiplicative hash so consecutive reads in
+        // the same file land far apart.
+        let blocks = (*len / PROBE_BLOCK_SIZE as u64).saturating_sub(1).max(1);
+        let block = index.wrapping_mul(0x9E37_79B9_7F4A_7C15) % blocks;
+        file.seek(SeekFrom::Start(block * PROBE_BLOCK_SIZE as u64))
+            .ok()?;
+        file.read_exact(&mut buf).ok()?;
+    }
+    let random = started.elapsed() / PROBE_RANDOM_READS as u32;
+
+    Some(classify_latency(sequential, random))
+}
+
+fn probe_files(folder: &Path) -> Vec<(PathBuf, u64)> {
+    WalkDir::new(folder)
+        .follow_links(true)
+        .into_iter()
+        .filter_map(Result::ok)
+        .take(PROBE_MAX_ENTRIES)
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| {
+            let len = entry.metadata().ok()?.len();
+            (len >= PROBE_MIN_FILE_SIZE).then(|| (entry.into_path(), len))
+        })
+        .take(PROBE_MAX_FILES)
+        .collect()
+}
+
+fn classify_latency(sequential: Duration, random: Duration) -> IoProfile {
+    if random >= HDD_RANDOM_LATENCY && random >= sequential * HDD_RANDOM_TO_SEQUENTIAL_RATIO {
+        IoProfile::Hdd
+    } else {
+        IoProfile::Ssd
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(
+        path: &str,
+        io_profile: IoProfile,
+        worker_count: Option<usize>,
+    ) -> FolderScanSettings {
+        FolderScanSettings {
+            path: path.to_string(),
+            io_profile,
+            worker_count,
+        }
+    }
+
+    // Ensures the most specific entry wins and an explicit count beats the profile.
+    #[tokio::test]
+    async fn worker_count_uses_longest_matching_folder() {
+        let root = std::env::temp_dir().join("panoptikon-scan-io");
+        let config = SystemConfig {
+            folder_scan_settings: vec![
+                settings(&root.to_string_lossy(), IoProfile::Hdd, None),
+                settings(
+                    &root.join("fast").to_string_lossy(),
+                    IoProfile::Hdd,
+                    Some(6),
+                ),
+                settings(
+                    &root.join("share").to_string_lossy(),
+                    IoProfile::Network,
+                    None,
+                ),
+            ],
+            ..SystemConfig::default()
+        };
+
+        assert_eq!(
+            folder_worker_count(&config, &root.join("photos"), 16).await,
+            2
+        );
+        assert_eq!(
+            folder_worker_count(&config, &root.join("fast/a"), 16).await,
+            6
+        );
+        assert_eq!(
+            folder_worker_count(&config, &root.join("share"), 16).await,
+            4
+        );
+        let elsewhere = std::env::temp_dir().join("elsewhere");
+        assert_eq!(folder_worker_count(&config, &elsewhere, 16).await, 16);
+        // A sibling sharing the name prefix is not inside the folder.
+        assert_eq!(
+            folder_worker_count(&config, &root.join("faster"), 16).await,
+            2
+        );
+    }
+
+    // Ensures auto mode falls back to the ssd default when there is nothing to probe.
+    #[tokio::test]
+    async fn auto_profile_without_probe_files_uses_ssd_default() {
+        let temp = tempfile::tempdir().unwrap();
+        std::fs::write(temp.path().join("small.txt"), b"tiny").unwrap();
+        let config = SystemConfig {
+            folder_scan_settings: vec![settings(
+                &temp.path().to_string_lossy(),
+                IoProfile::Auto,
+                None,
+            )],
+            ..SystemConfig::default()
+        };
+
+        assert_eq!(folder_worker_count(&config, temp.path(), 12).await, 12);
+    }
+
+    // Ensures only slow, seek-bound random reads classify as a spinning disk.
+    #[test]
+    fn classify_latency_requires_slow_random_reads() {
+        let micros = Duration::from_micros;
+        assert_eq!(classify_latency(micros(20), micros(8_000)), IoProfile::Hdd);
+        assert_eq!(classify_latency(micros(20), micros(150)), IoProfile::Ssd);
+        assert_eq!(
+            classify_latency(micros(3_000), micros(8_000)),
+            IoProfile::Ssd
+        );
+    }
+}
@@ -0,0 +1,584 @@
+//! Search result export: copies the files matching a PQL query out of the
+//! index, as a zip archive streamed by the export endpoint or as a flat
+//! directory of hardlinks or copies written by a queued job.
+//!
+//! Both forms flatten the matched files into one directory level, so names
+//! are assigned here once for the whole result set: files are taken in path
+//! order, the first file with a given name (compared case-insensitively)
+//! keeps it, and later ones get the first eight hex digits of their sha256
+//! appended to the stem. Names are reduced to a single path component, so
+//! neither an archive entry nor a job target can point outside the export.
+//!
+//! Hardlink and copy destinations are checked before the job is queued:
+//! they must be absolute, free of `.`/`..` components, and outside every
+//! included folder unless `[search] export_allowed_destinations` covers
+//! them, so an export never feeds files back into a scanned tree. The file
+//! list is resolved when the export is requested and held in process memory
+//! until the job runs. Existing targets are never overwritten; they count
+//! as skipped. Progress and per-file failures are kept per index DB in
+//! process memory and exposed by the export status endpoint.
+
+use std::collections::HashSet;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::api_error::ApiError;
+use crate::db::extraction_write::current_iso_timestamp;
+use crate::db::raw_paths::disk_path;
+use crate::jobs::registry::Registry;
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+/// Hex digits of the sha256 appended to a colliding name.
+const SHA_SUFFIX_LEN: usize = 8;
+/// Per-file failures kept in a job's progress; later ones are only counted.
+pub(crate) const MAX_RECORDED_FAILURES: usize = 100;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ExportMode {
+    /// Stream a zip archive in the response.
+    #[default]
+    Zip,
+    /// Hardlink each file into the destination (same filesystem only).
+    Hardlink,
+    /// Copy each file into the destination.
+    Copy,
+}
+
+/// One matched file and the flat name it is exported under.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ExportFile {
+    pub path: String,
+    /// `raw_paths::path_bytes` of the path: set only when it is not UTF-8.
+    pub path_bytes: Option<Vec<u8>>,
+    pub sha256: String,
+    pub size: i64,
+    pub name: String,
+}
+
+/// Assigns export names to `(path, sha256, size)` rows and returns them in
+/// path order.
+pub(crate) fn name_export_files(mut rows: Vec<(String, String, i64)>) -> Vec<ExportFile> {
+    rows.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.cmp(&b.1)));
+    let mut taken = HashSet::new();
+    rows.into_iter()
+        .map(|(path, sha256, size)| {
+            let name = unique_name(&path, &sha256, &mut taken);
+            ExportFile {
+                path,
+                path_bytes: None,
+                sha256,
+                size,
+                name,
+            }
+        })
+        .collect()
+}
+
+impl ExportFile {
+    fn disk_path(&self) -> PathBuf {
+        disk_path(&self.path, self.path_bytes.as_deref())
+    }
+}
+
+fn unique_name(path: &str, sha256: &str, taken: &mut HashSet<String>) -> String {
+    let base = sanitize_name(path).unwrap_or_else(|| sha256.to_string());
+    let (stem, extension) = match base.rfind('.') {
+        Some(dot) if dot > 0 => base.split_at(dot),
+        _ => (base.as_str(), ""),
+    };
+    let short = &sha256[..sha256.len().min(SHA_SUFFIX_LEN)];
+    let mut candidates = vec![
+        base.clone(),
+        format!("{stem}_{short}{extension}"),
+        format!("{stem}_{sha256}{extension}"),
+    ]
+    .into_iter();
+    let mut counter = 1;
+    loop {
+        let candidate = candidates
+            .next()
+            .unwrap_or_else(|| format!("{stem}_{sha256}_{counter}{extension}"));
+        if taken.insert(candidate.to_lowercase()) {
+            return candidate;
+        }
+        counter += 1;
+    }
+}
+
+/// The last component of `path` (either separator style), with control
+/// characters and characters Windows forbids replaced by `_`. `None` when
+/// nothing usable is left.
+fn sanitize_name(path: &str) -> Option<String> {
+    let last = path.rsplit(['/', '\\']).next().unwrap_or(path);
+    let name: String = last
+        .chars()
+        .map(|ch| match ch {
+            '<' | '>' | ':' | '"' | '|' | '?' | '*' => '_',
+            ch if ch.is_control() => '_',
+            ch => ch,
+        })
+        .collect();
+    let name = name.trim_end_matches(['.', ' ']);
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+/// Checks a hardlink/copy destination and returns it with its existing
+/// ancestors resolved, so symlinks cannot smuggle it into a scanned tree.
+pub(crate) fn validate_destination(
+    destination: &str,
+    included_folders: &[String],
+    allowed_destinations: &[PathBuf],
+) -> ApiResult<PathBuf> {
+    let path = Path::new(destination);
+    if !path.is_absolute() {
+        return Err(ApiError::bad_request(
+            "destination must be an absolute path",
+        ));
+    }
+    if path
+        .components()
+        .any(|component| matches!(component, Component::ParentDir | Component::CurDir))
+    {
+        return Err(ApiError::bad_request(
+            "destination must not contain '.' or '..' components",
+        ));
+    }
+    let resolved = resolve_existing_prefix(path);
+    if resolved.exists() && !resolved.is_dir() {
+        return Err(ApiError::bad_request("destination is not a directory"));
+    }
+    if allowed_destinations
+        .iter()
+        .any(|allowed| resolved.starts_with(resolve_existing_prefix(allowed)))
+    {
+        return Ok(resolved);
+    }
+    if included_folders
+        .iter()
+        .any(|folder| resolved.starts_with(resolve_existing_prefix(Path::new(folder))))
+    {
+        return Err(ApiError::bad_request(
+            "destination is inside an included folder",
+        ));
+    }
+    Ok(resolved)
+}
+
+/// Canonicalizes the longest existing ancestor of `path` and re-appends the
+/// components below it.
+fn resolve_existing_prefix(path: &Path) -> PathBuf {
+    let mut existing = path;
+    let mut rest = Vec::new();
+    loop {
+        if let Ok(canonical) = existing.canonicalize() {
+            return rest
+                .iter()
+                .rev()
+                .fold(canonical, |acc: PathBuf, part| acc.join(part));
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                rest.push(name.to_os_string());
+                existing = parent;
+            }
+            _ => return path.to_path_buf(),
+        }
+    }
+}
+
+/// Entry listing the files a zip export could not include.
+const ZIP_ERRORS_ENTRY: &str = "export_errors.txt";
+
+/// Writes `files` into a zip archive on `out`, stored uncompressed (media
+/// rarely compresses), and returns the rewound file. Unreadable files are
+/// left out and listed in an `export_errors.txt` entry, as are files past
+/// `max_bytes` when they grew since they were indexed.
+pub(crate) fn write_zip_archive(
+    files: &[ExportFile],
+    out: std::fs::File,
+    max_bytes: u64,
+) -> io::Result<std::fs::File> {
+    use std::io::{Read, Seek, Write};
+    use zip::write::SimpleFileOptions;
+
+    let mut writer = zip::ZipWriter::new(out);
+    let mut failures = Vec::new();
+    let mut written = 0u64;
+    for file in files {
+        let mut reader = match std::fs::File::open(file.disk_path()) {
+            Ok(reader) => reader,
+            Err(err) => {
+                failures.push(format!("{}: {err}", file.path));
+                continue;
+            }
+        };
+        let len = reader.metadata().map(|meta| meta.len()).unwrap_or(0);
+        let options = SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Stored)
+            .large_file(len >= u32::MAX as u64);
+        writer.start_file(file.name.as_str(), options)?;
+        let remaining = max_bytes.saturating_sub(written);
+        match io::copy(&mut (&mut reader).take(remaining + 1), &mut writer) {
+            Ok(copied) if copied <= remaining => written += copied,
+            Ok(_) => {
+                writer.abort_file()?;
+                failures.push(format!("{}: export size limit reached", file.path));
+            }
+            Err(err) => {
+                writer.abort_file()?;
+                failures.push(format!("{}: {err}", file.path));
+            }
+        }
+    }
+    if !failures.is_empty() {
+        let mut name = ZIP_ERRORS_ENTRY.to_string();
+        while files
+            .iter()
+            .any(|file| file.name.eq_ignore_ascii_case(&name))
+        {
+            name.insert(0, '_');
+        }
+        writer.start_file(name, SimpleFileOptions::default())?;
+        for failure in &failures {
+            writeln!(writer, "{failure}")?;
+        }
+    }
+    let mut out = writer.finish().map_err(io::Error::other)?;
+    out.seek(io::SeekFrom::Start(0))?;
+    Ok(out)
+}
+
+/// Options of one export job, stored as the job's metadata. The file list
+/// itself stays in memory under `export_id`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct SearchExportOptions {
+    pub export_id: String,
+    pub mode: ExportMode,
+    pub destination: PathBuf,
+}
+
+static PENDING: Registry<String, Vec<ExportFile>> = Registry::new();
+
+/// Holds `files` for the job that will run the export and returns its id.
+pub(crate) fn stage_export(files: Vec<ExportFile>) -> String {
+    let export_id = uuid::Uuid::new_v4().to_string();
+    PENDING.insert(export_id.clone(), files);
+    export_id
+}
+
+/// Drops a staged file list whose job was never queued.
+pub(crate) fn discard_export(export_id: &str) {
+    PENDING.remove(export_id);
+}
+
+fn take_export(export_id: &str) -> Option<Vec<ExportFile>> {
+    PENDING.remove(export_id)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub(crate) struct ExportFailure {
+    pub path: String,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, ToSchema)]
+pub(crate) struct SearchExportProgress {
+    pub started_at: String,
+    /// Null while the job is running, or if it was cancelled.
+    pub finished_at: Option<String>,
+    pub mode: ExportMode,
+    pub destination: String,
+    /// Files matched when the export was requested.
+    pub total: i64,
+    /// Files looked at, whatever their outcome.
+    pub processed: i64,
+    pub exported: i64,
+    /// Files whose target name already existed in the destination; they
+    /// are left untouched.
+    pub skipped: i64,
+    pub failed: i64,
+    /// The first failures, by source path.
+    pub failures: Vec<ExportFailure>,
+}
+
+static PROGRESS: Registry<String, SearchExportProgress> = Registry::new();
+
+/// Progress of the running or most recent export job for `index_db` in
+/// this process.
+pub(crate) fn last_progress(index_db: &str) -> Option<SearchExportProgress> {
+    PROGRESS.get(index_db)
+}
+
+fn publish_progress(index_db: &str, progress: &SearchExportProgress) {
+    PROGRESS.insert(index_db.to_string(), progress.clone());
+}
+
+enum Outcome {
+    Exported,
+    Skipped,
+}
+
+fn export_file(source: &Path, target: &Path, mode: ExportMode) -> io::Result<Outcome> {
+    if target.symlink_metadata().is_ok() {
+        return Ok(Outcome::Skipped);
+    }
+    match mode {
+        ExportMode::Hardlink => std::fs::hard_link(source, target)?,
+        ExportMode::Copy => {
+            // create_new: a target appearing since the check above is not
+            // overwritten either.
+            let mut reader = std::fs::File::open(source)?;
+            let mut writer = std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(target)?;
+            if let Err(err) = io::copy(&mut reader, &mut writer) {
+                drop(writer);
+                let _ = std::fs::remove_file(target);
+                return Err(err);
+            }
+        }
+        ExportMode::Zip => return Err(io::Error::other("zip exports are not run as jobs")),
+    }
+    Ok(Outcome::Exported)
+}
+
+pub(crate) async fn run_search_export_job(
+    index_db: &str,
+    options: SearchExportOptions,
+) -> ApiResult<SearchExportProgress> {
+    if options.mode == ExportMode::Zip {
+        return Err(ApiError::bad_request("zip exports are not run as jobs"));
+    }
+    let files = take_export(&options.export_id).ok_or_else(|| {
+        ApiError::not_found("Export file list is no longer available; request the export again")
+    })?;
+    let mut progress = SearchExportProgress {
+        started_at: current_iso_timestamp(),
+        mode: options.mode,
+        destination: options.destination.to_string_lossy().into_owned(),
+        total: files.len() as i64,
+        ..Default::default()
+    };
+    publish_progress(index_db, &progress);
+    tracing::info!(
+        index_db,
+        files = files.len(),
+        mode = ?options.mode,
+        destination = %options.destination.display(),
+        "exporting search results"
+    );
+
+    let destination = options.destination.clone();
+    tokio::fs::create_dir_all(&destination).await.map_err(|err| {
+        tracing::error!(error = %err, destination = %destination.display(), "failed to create export destination");
+        ApiError::internal("Failed to create the export destination")
+    })?;
+
+    for file in files {
+        let source = file.disk_path();
+        let target = destination.join(&file.name);
+        let mode = options.mode;
+        let outcome = tokio::task::spawn_blocking(move || export_file(&source, &target, mode))
+            .await
+            .unwrap_or_else(|err| Err(io::Error::other(err.to_string())));
+        progress.processed += 1;
+        match outcome {
+            Ok(Outcome::Exported) => progress.exported += 1,
+            Ok(Outcome::Skipped) => progress.skipped += 1,
+            Err(err) => {
+                tracing::warn!(error = %err, path = %file.path, "failed to export file");
+                progress.failed += 1;
+                if progress.failures.len() < MAX_RECORDED_FAILURES {
+                    progress.failures.push(ExportFailure {
+                        path: file.path,
+                        error: err.to_string(),
+                    });
+                }
+            }
+        }
+        publish_progress(index_db, &progress);
+    }
+
+    progress.finished_at = Some(current_iso_timestamp());
+    publish_progress(index_db, &progress);
+    tracing::info!(
+        index_db,
+        exported = progress.exported,
+        skipped = progress.skipped,
+        failed = progress.failed,
+        "search export finished"
+    );
+    Ok(progress)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(path: &str, sha256: &str) -> (String, String, i64) {
+        (path.to_string(), sha256.to_string(), 1)
+    }
+
+    // Ensures colliding names get the sha prefix in path order, compared
+    // case-insensitively, and that traversal in stored paths is flattened.
+    #[test]
+    fn export_names_are_unique_and_deterministic() {
+        let rows = vec![
+            row("/b/photo.jpg", "bbbbbbbbbbbb"),
+            row("/a/photo.jpg", "aaaaaaaaaaaa"),
+            row("/c/PHOTO.jpg", "cccccccccccc"),
+            row("C:\\d\\..\\notes", "dddddddddddd"),
+            row("/e/..", "eeeeeeeeeeee"),
+        ];
+        let names: Vec<String> = name_export_files(rows.clone())
+            .into_iter()
+            .map(|file| file.name)
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "photo.jpg",
+                "photo_bbbbbbbb.jpg",
+                "PHOTO_cccccccc.jpg",
+                "eeeeeeeeeeee",
+                "notes",
+            ]
+        );
+        let mut reversed = rows;
+        reversed.reverse();
+        let again: Vec<String> = name_export_files(reversed)
+            .into_iter()
+            .map(|file| file.name)
+            .collect();
+        assert_eq!(again, names);
+    }
+
+    // Ensures a zip export holds every readable file under its export name
+    // and lists unreadable ones in the errors entry.
+    #[test]
+    fn zip_archive_lists_failures() {
+        use std::io::Read;
+
+        let root = tempfile::TempDir::new().unwrap();
+        std::fs::write(root.path().join("a.txt"), b"alpha").unwrap();
+        let path = |name: &str| root.path().join(name).to_string_lossy().into_owned();
+        let files = name_export_files(vec![
+            (path("a.txt"), "1111111111".to_string(), 5),
+            (path("gone.txt"), "2222222222".to_string(), 1),
+        ]);
+        let out = write_zip_archive(&files, tempfile::tempfile().unwrap(), 1024).unwrap();
+
+        let mut archive = zip::ZipArchive::new(out).unwrap();
+        assert_eq!(archive.len(), 2);
+        let mut text = String::new();
+        archive
+            .by_name("a.txt")
+            .unwrap()
+            .read_to_string(&mut text)
+            .unwrap();
+        assert_eq!(text, "alpha");
+        text.clear();
+        archive
+            .by_name(ZIP_ERRORS_ENTRY)
+            .unwrap()
+            .read_to_string(&mut text)
+            .unwrap();
+        assert!(text.contains("gone.txt"));
+    }
+
+    // Ensures destinations must be absolute, traversal-free and outside
+    // included folders unless explicitly allowed.
+    #[test]
+    fn destination_validation_rejects_scanned_trees() {
+        let root = tempfile::TempDir::new().unwrap();
+        let included = root.path().join("library");
+        std::fs::create_dir_all(&included).unwrap();
+        let folders = vec![included.to_string_lossy().into_owned()];
+        let outside = root.path().join("exports").join("new");
+
+        let ok = validate_destination(outside.to_str().unwrap(), &folders, &[]).unwrap();
+        assert!(ok.ends_with("exports/new"));
+        assert!(validate_destination("relative/dir", &folders, &[]).is_err());
+        let traversal = format!("{}/exports/../library", root.path().display());
+        assert!(validate_destination(&traversal, &folders, &[]).is_err());
+        let inside = included.join("export");
+        assert!(validate_destination(inside.to_str().unwrap(), &folders, &[]).is_err());
+        assert!(
+            validate_destination(
+                inside.to_str().unwrap(),
+                &folders,
+                &[included.join("export")]
+            )
+            .is_ok()
+        );
+
+        #[cfg(unix)]
+        {
+            let link = root.path().join("sneaky");
+            std::os::unix::fs::symlink(&included, &link).unwrap();
+            let through = link.join("export");
+            assert!(validate_destination(through.to_str().unwrap(), &folders, &[]).is_err());
+        }
+    }
+
+    // Ensures a copy job exports every file once, skips existing targets
+    // and records unreadable sources as failures.
+    #[tokio::test]
+    async fn copy_job_reports_per_file_outcomes() {
+        let root = tempfile::TempDir::new().unwrap();
+        let source = root.path().join("source");
+        std::fs::create_dir_all(source.join("x")).unwrap();
+        std::fs::write(source.join("a.txt"), b"first").unwrap();
+        std::fs::write(source.join("x").join("a.txt"), b"second").unwrap();
+        let destination = root.path().join("out");
+        std::fs::create_dir_all(&destination).unwrap();
+        std::fs::write(destination.join("kept.txt"), b"existing").unwrap();
+
+        let path = |name: &str| source.join(name).to_string_lossy().into_owned();
+        let files = name_export_files(vec![
+            (path("a.txt"), "1111111111".to_string(), 5),
+            (path("x/a.txt"), "2222222222".to_string(), 6),
+            (path("kept.txt"), "3333333333".to_string(), 1),
+            (path("missing.txt"), "4444444444".to_string(), 1),
+        ]);
+        let options = SearchExportOptions {
+            export_id: stage_export(files),
+            mode: ExportMode::Copy,
+            destination: destination.clone(),
+        };
+        let progress = run_search_export_job("export-test", options.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            (
+                progress.total,
+                progress.exported,
+                progress.skipped,
+                progress.failed
+            ),
+            (4, 2, 1, 1)
+        );
+        assert!(progress.finished_at.is_some());
+        assert_eq!(progress.failures.len(), 1);
+        assert!(progress.failures[0].path.ends_with("missing.txt"));
+        assert_eq!(std::fs::read(destination.join("a.txt")).unwrap(), b"first");
+        assert_eq!(
+            std::fs::read(destination.join("a_22222222.txt")).unwrap(),
+            b"second"
+        );
+        assert_eq!(
+            std::fs::read(destination.join("kept.txt")).unwrap(),
+            b"existing"
+        );
+        assert_eq!(last_progress("export-test"), Some(progress));
+        // The staged list is consumed by the run.
+        assert!(run_search_export_job("export-test", options).await.is_err());
+    }
+}
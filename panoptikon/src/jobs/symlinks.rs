@@ -0,0 +1,306 @@
+//! Symbolic links during folder scans and continuous scanning.
+//!
+//! With `follow_symlinks`, a walk enters linked directories and files, but
+//! never the same directory twice: directories are tracked by identity
+//! (device and inode on Unix, the canonical path elsewhere), which cuts
+//! symlink cycles and skips bind-mounted copies of a tree already walked.
+//! With `symlink_duplicates = "canonical"`, a path that reaches a file
+//! through a link is skipped when the real file lies in a scanned folder, so
+//! the file is only indexed under its real path.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use walkdir::DirEntry;
+
+use crate::db::system_config::{SymlinkDuplicates, SystemConfig};
+use crate::jobs::files::is_excluded;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum DirIdentity {
+    #[cfg(unix)]
+    Inode(u64, u64),
+    #[cfg(not(unix))]
+    Path(PathBuf),
+}
+
+fn dir_identity(path: &Path) -> Option<DirIdentity> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let metadata = std::fs::metadata(path).ok()?;
+        Some(DirIdentity::Inode(metadata.dev(), metadata.ino()))
+    }
+    #[cfg(not(unix))]
+    {
+        std::fs::canonicalize(path).ok().map(DirIdentity::Path)
+    }
+}
+
+/// Symlink settings plus the scanned folders, resolved once per scan or
+/// watcher restart.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct SymlinkPolicy {
+    follow: bool,
+    duplicates: SymlinkDuplicates,
+    /// Scanned folders as configured, with their canonical form.
+    roots: Vec<(PathBuf, PathBuf)>,
+    excluded: Vec<PathBuf>,
+}
+
+/// The default settings with no scanned folders.
+impl Default for SymlinkPolicy {
+    fn default() -> Self {
+        Self::new(&SystemConfig::default(), &[], &[])
+    }
+}
+
+impl SymlinkPolicy {
+    pub(crate) fn new(config: &SystemConfig, roots: &[PathBuf], excluded: &[PathBuf]) -> Self {
+        let roots = roots
+            .iter()
+            .map(|root| {
+                let canonical = std::fs::canonicalize(root).unwrap_or_else(|_| root.clone());
+                (root.clone(), canonical)
+            })
+            .collect();
+        let excluded = excluded
+            .iter()
+            .flat_map(|path| {
+                let canonical = std::fs::canonicalize(path).ok();
+                std::iter::once(path.clone()).chain(canonical)
+            })
+            .collect();
+        Self {
+            follow: config.follow_symlinks,
+            duplicates: config.symlink_duplicates,
+            roots,
+            excluded,
+        }
+    }
+
+    /// The real location of `path` when some component between its scanned
+    /// folder and itself is a link; `None` for direct paths and paths that
+    /// can't be resolved (e.g. already deleted).
+    pub(crate) fn link_target(&self, path: &Path) -> Option<PathBuf> {
+        let (root, canonical_root) = self
+            .roots
+            .iter()
+            .filter(|(root, _)| path.starts_with(root))
+            .max_by_key(|(root, _)| root.components().count())?;
+        let relative = path.strip_prefix(root).ok()?;
+        let canonical = std::fs::canonicalize(path).ok()?;
+        (canonical != canonical_root.join(relative)).then_some(canonical)
+    }
+
+    /// Whether `real` is scanned under its own path: inside a scanned folder
+    /// and not excluded.
+    fn covers(&self, real: &Path) -> bool {
+        self.roots.iter().any(|(root, canonical_root)| {
+            real.starts_with(canonical_root) || real.starts_with(root)
+        }) && !is_excluded(real, &self.excluded)
+    }
+
+    /// Whether to index or walk into the link at `link`, given where it
+    /// leads. Links to one of their own ancestors are never followed.
+    pub(crate) fn follows_link(&self, link: &Path) -> bool {
+        if !self.follow {
+            return false;
+        }
+        let Ok(real) = std::fs::canonicalize(link) else {
+            // Dangling: there is nothing to index behind it.
+            return false;
+        };
+        let cycle = link
+            .parent()
+            .and_then(|parent| std::fs::canonicalize(parent).ok())
+            .is_some_and(|parent| parent.starts_with(&real));
+        !cycle && (self.duplicates == SymlinkDuplicates::Keep || !self.covers(&real))
+    }
+
+    /// Watcher check for a changed path, applied before the other path
+    /// filters: paths through links follow the same rules as in a full scan.
+    pub(crate) fn admits(&self, path: &Path) -> bool {
+        match self.link_target(path) {
+            None => true,
+            Some(real) => {
+                self.follow && (self.duplicates == SymlinkDuplicates::Keep || !self.covers(&real))
+            }
+        }
+    }
+
+    /// Walk state for one full scan, shared by all its folders.
+    pub(crate) fn walk(&self) -> SymlinkWalk<'_> {
+        SymlinkWalk {
+            policy: self,
+            visited_dirs: Mutex::new(HashSet::new()),
+            skipped: AtomicU64::new(0),
+        }
+    }
+}
+
+/// Per-scan record of the directories already walked. Shared by reference
+/// between the walker's entry filter and the loop consuming its errors.
+pub(crate) struct SymlinkWalk<'a> {
+    policy: &'a SymlinkPolicy,
+    visited_dirs: Mutex<HashSet<DirIdentity>>,
+    skipped: AtomicU64,
+}
+
+impl SymlinkWalk<'_> {
+    /// Links and directories skipped as duplicates or cycles so far.
+    pub(crate) fn skipped(&self) -> u64 {
+        self.skipped.load(Ordering::Relaxed)
+    }
+
+    fn skip(&self) {
+        self.skipped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Whether the walk should yield `entry` (and enter it, for a
+    /// directory). Without `follow_symlinks` the walker reports links as
+    /// links, which are skipped here.
+    pub(crate) fn admits(&self, entry: &DirEntry) -> bool {
+        if entry.path_is_symlink() && !self.policy.follows_link(entry.path()) {
+            tracing::debug!(path = %entry.path().display(), "skipping symlink");
+            self.skip();
+            return false;
+        }
+        if !entry.file_type().is_dir() || !self.policy.follow {
+            return true;
+        }
+        let Some(identity) = dir_identity(entry.path()) else {
+            return true;
+        };
+        // A scanned folder is always walked, even when an earlier folder
+        // reached it through a link: skipping it would mark its files missing.
+        if self
+            .visited_dirs
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .insert(identity)
+            || entry.depth() == 0
+        {
+            return true;
+        }
+        tracing::debug!(
+            path = %entry.path().display(),
+            "skipping directory already walked through another path"
+        );
+        self.skip();
+        false
+    }
+
+    /// Whether a walk error is a link back to an ancestor directory, which
+    /// the walker reports before [`Self::admits`] sees the entry. Cycles are
+    /// expected with `follow_symlinks` and only logged at debug level.
+    pub(crate) fn is_cycle(&self, err: &walkdir::Error) -> bool {
+        let Some(ancestor) = err.loop_ancestor() else {
+            return false;
+        };
+        tracing::debug!(
+            path = ?err.path(),
+            ancestor = %ancestor.display(),
+            "skipping symlink cycle"
+        );
+        self.skip();
+        true
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::os::unix::fs::symlink;
+
+    use walkdir::WalkDir;
+
+    use super::*;
+
+    fn walk(root: &Path, config: &SystemConfig) -> (Vec<String>, u64) {
+        let policy = SymlinkPolicy::new(config, &[root.to_path_buf()], &[]);
+        let state = policy.walk();
+        let mut files = WalkDir::new(root)
+            .follow_links(config.follow_symlinks)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|entry| state.admits(entry))
+            .collect::<Vec<_>>()
+            .into_iter()
+            .filter_map(|entry| entry.map_err(|err| assert!(state.is_cycle(&err))).ok())
+            .filter(|entry| !entry.file_type().is_dir())
+            .map(|entry| {
+                entry
+                    .path()
+                    .strip_prefix(root)
+                    .unwrap()
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect::<Vec<_>>();
+        files.sort();
+        (files, state.skipped())
+    }
+
+    // Ensures a link back to an ancestor is walked once without errors, and
+    // a file reachable through a link into the scanned folder is only
+    // reported under its real path.
+    #[test]
+    fn cycles_end_and_duplicates_resolve_to_the_real_path() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let root = tmp.path().join("media");
+        std::fs::create_dir_all(root.join("albums/trip")).unwrap();
+        std::fs::write(root.join("albums/trip/a.jpg"), b"a").unwrap();
+        symlink(&root, root.join("albums/loop")).unwrap();
+        symlink(root.join("albums/trip"), root.join("favorites")).unwrap();
+        symlink(root.join("albums/trip/a.jpg"), root.join("cover.jpg")).unwrap();
+
+        let (files, skipped) = walk(&root, &SystemConfig::default());
+        assert_eq!(files, vec!["albums/trip/a.jpg"]);
+        assert_eq!(skipped, 3);
+
+        let keep = SystemConfig {
+            symlink_duplicates: SymlinkDuplicates::Keep,
+            ..Default::default()
+        };
+        let (files, _) = walk(&root, &keep);
+        assert_eq!(files, vec!["albums/trip/a.jpg", "cover.jpg"]);
+
+        let no_follow = SystemConfig {
+            follow_symlinks: false,
+            ..Default::default()
+        };
+        let (files, skipped) = walk(&root, &no_follow);
+        assert_eq!(files, vec!["albums/trip/a.jpg"]);
+        assert_eq!(skipped, 3);
+    }
+
+    // Ensures links to files outside the scanned folders are still indexed,
+    // and the watcher check agrees with the walk.
+    #[test]
+    fn links_out_of_scanned_folders_are_kept() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let root = tmp.path().join("media");
+        let elsewhere = tmp.path().join("elsewhere");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::create_dir_all(&elsewhere).unwrap();
+        std::fs::write(root.join("real.jpg"), b"r").unwrap();
+        std::fs::write(elsewhere.join("b.jpg"), b"b").unwrap();
+        symlink(&elsewhere, root.join("linked")).unwrap();
+        symlink(root.join("real.jpg"), root.join("alias.jpg")).unwrap();
+
+        let config = SystemConfig::default();
+        let (files, _) = walk(&root, &config);
+        assert_eq!(files, vec!["linked/b.jpg", "real.jpg"]);
+
+        let policy = SymlinkPolicy::new(&config, std::slice::from_ref(&root), &[]);
+        assert!(policy.admits(&root.join("real.jpg")));
+        assert!(policy.admits(&root.join("linked/b.jpg")));
+        assert!(!policy.admits(&root.join("alias.jpg")));
+        assert_eq!(
+            policy.link_target(&root.join("alias.jpg")),
+            Some(std::fs::canonicalize(root.join("real.jpg")).unwrap())
+        );
+    }
+}
@@ -0,0 +1,279 @@
+//! Tag import: reads NDJSON lines of `{sha256, tags}` (e.g. a Hydrus sidecar
+//! export) and writes them under a chosen setter as if a tagging model had
+//! run. The import gets its own data log entry, so it appears in extraction
+//! history and its data can be deleted from there like any job's.
+//!
+//! Lines are read from the request body as they arrive and written in
+//! batches of `batch_size`, one index writer transaction each, so memory
+//! stays bounded by the batch rather than the file. Hashes with no item and
+//! lines that fail to parse are reported instead of failing the import.
+
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+use utoipa::ToSchema;
+
+use crate::api_error::ApiError;
+use crate::db::extraction_write::{DataLogUpdate, TagEntry, current_iso_timestamp};
+use crate::db::index_writer::{IndexDbWriterMessage, call_index_db_writer};
+use crate::db::manual_tags::MANUAL_TAGS_SETTER;
+use crate::db::tag_import::{TagImportCounts, TagImportEntry};
+use crate::jobs::files::run_post_job_maintenance;
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+pub(crate) const DEFAULT_IMPORT_BATCH_SIZE: usize = 500;
+pub(crate) const MAX_IMPORT_BATCH_SIZE: usize = 10_000;
+
+#[derive(Debug, Deserialize)]
+struct ImportLine {
+    sha256: String,
+    tags: Vec<ImportTag>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ImportTag {
+    namespace: String,
+    name: String,
+    confidence: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub(crate) struct TagImportReport {
+    /// The import's data log job ID, as shown in extraction history.
+    pub job_id: i64,
+    pub setter_name: String,
+    #[serde(flatten)]
+    pub counts: TagImportCounts,
+    /// Non-empty lines read.
+    pub lines: u64,
+    /// Hashes that match no item, in input order.
+    pub unmatched: Vec<String>,
+    /// 1-based numbers of lines that are not valid import records.
+    pub invalid_lines: Vec<u64>,
+}
+
+fn validate_setter_name(setter_name: &str) -> ApiResult<()> {
+    if setter_name.trim().is_empty() {
+        return Err(ApiError::bad_request("setter_name must not be empty"));
+    }
+    if setter_name == MANUAL_TAGS_SETTER {
+        return Err(ApiError::bad_request(format!(
+            "{MANUAL_TAGS_SETTER} is reserved for manually added tags"
+        )));
+    }
+    Ok(())
+}
+
+fn parse_line(line: &str) -> Option<TagImportEntry> {
+    let parsed: ImportLine = serde_json::from_str(line).ok()?;
+    let sha256 = parsed.sha256.trim().to_ascii_lowercase();
+    if sha256.is_empty() {
+        return None;
+    }
+    let tags = parsed
+        .tags
+        .into_iter()
+        .map(|tag| TagEntry {
+            namespace: tag.namespace,
+            name: tag.name,
+            confidence: tag.confidence.unwrap_or(1.0),
+        })
+        .collect();
+    Some(TagImportEntry { sha256, tags })
+}
+
+async fn write_batch(
+    index_db: &str,
+    job_id: i64,
+    setter_name: &str,
+    entries: Vec<TagImportEntry>,
+    report: &mut TagImportReport,
+) -> ApiResult<()> {
+    let batch = call_index_db_writer(index_db, |reply| IndexDbWriterMessage::ImportTags {
+        job_id,
+        setter_name: setter_name.to_string(),
+        entries: entries.clone(),
+        reply,
+    })
+    .await?;
+    report.counts.add(&batch.counts);
+    report.unmatched.extend(batch.unmatched);
+    Ok(())
+}
+
+fn log_update(report: &TagImportReport, started: Instant, finished: bool) -> DataLogUpdate {
+    DataLogUpdate {
+        image_files: report.counts.image_files as i64,
+        video_files: report.counts.video_files as i64,
+        other_files: report.counts.other_files as i64,
+        total_segments: report.counts.items as i64,
+        errors: (report.unmatched.len() + report.invalid_lines.len()) as i64,
+        timeouts: 0,
+        undecodable: 0,
+        frames_dropped: 0,
+        total_remaining: 0,
+        data_load_time: started.elapsed().as_secs_f64(),
+        inference_time: 0.0,
+        finished,
+    }
+}
+
+async fn import_lines<R: AsyncBufRead + Unpin>(
+    index_db: &str,
+    setter_name: &str,
+    batch_size: usize,
+    reader: R,
+    report: &mut TagImportReport,
+) -> ApiResult<()> {
+    let mut lines = reader.lines();
+    let mut batch = Vec::with_capacity(batch_size);
+    let mut line_number = 0u64;
+    while let Some(line) = lines
+        .next_line()
+        .await
+        .map_err(|err| ApiError::bad_request(format!("Failed to read import body: {err}")))?
+    {
+        line_number += 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+        report.lines += 1;
+        match parse_line(&line) {
+            Some(entry) => batch.push(entry),
+            None => report.invalid_lines.push(line_number),
+        }
+        if batch.len() >= batch_size {
+            let entries = std::mem::replace(&mut batch, Vec::with_capacity(batch_size));
+            write_batch(index_db, report.job_id, setter_name, entries, report).await?;
+        }
+    }
+    if !batch.is_empty() {
+        write_batch(index_db, report.job_id, setter_name, batch, report).await?;
+    }
+    Ok(())
+}
+
+/// Imports every line of `reader` under `setter_name`. A failed batch stops
+/// the import and leaves its data log unfinished, like a failed extraction
+/// job; batches written before it are kept.
+pub(crate) async fn run_tag_import<R: AsyncBufRead + Unpin>(
+    index_db: &str,
+    setter_name: &str,
+    batch_size: Option<usize>,
+    reader: R,
+) -> ApiResult<TagImportReport> {
+    validate_setter_name(setter_name)?;
+    let batch_size = batch_size
+        .unwrap_or(DEFAULT_IMPORT_BATCH_SIZE)
+        .clamp(1, MAX_IMPORT_BATCH_SIZE);
+    let started = Instant::now();
+    let job_id = call_index_db_writer(index_db, |reply| IndexDbWriterMessage::AddDataLog {
+        scan_time: current_iso_timestamp(),
+        threshold: None,
+        types: vec!["tags".to_string()],
+        setter: setter_name.to_string(),
+        batch_size: batch_size as i64,
+        reprocess: false,
+        parameters: None,
+        filter_counts: None,
+        reply,
+    })
+    .await?;
+    let mut report = TagImportReport {
+        job_id,
+        setter_name: setter_name.to_string(),
+        counts: TagImportCounts::default(),
+        lines: 0,
+        unmatched: Vec::new(),
+        invalid_lines: Vec::new(),
+    };
+
+    let result = import_lines(index_db, setter_name, batch_size, reader, &mut report).await;
+    let update = log_update(&report, started, result.is_ok());
+    call_index_db_writer(index_db, |reply| IndexDbWriterMessage::UpdateDataLog {
+        job_id,
+        update: update.clone(),
+        reply,
+    })
+    .await?;
+    result?;
+
+    tracing::info!(
+        index_db,
+        setter_name,
+        job_id,
+        items = report.counts.items,
+        replaced = report.counts.replaced,
+        unmatched = report.unmatched.len(),
+        invalid_lines = report.invalid_lines.len(),
+        "tag import finished"
+    );
+    if report.counts.items > 0 {
+        run_post_job_maintenance(index_db, false).await;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::migrations::migrate_databases_on_disk;
+    use crate::db::open_index_db_write_no_user_data;
+    use crate::test_utils::test_data_dir;
+
+    /// Batches of two over five lines: the bad line and the unknown hash are
+    /// reported, and the data log entry is finished with the totals.
+    #[tokio::test]
+    async fn import_writes_batches_and_finishes_the_data_log() {
+        let _test_env = test_data_dir();
+        let index_db = "tag_import_index".to_string();
+        migrate_databases_on_disk(Some(&index_db), None)
+            .await
+            .unwrap();
+        let mut conn = open_index_db_write_no_user_data(&index_db).await.unwrap();
+        sqlx::query(
+            r#"
+INSERT INTO items (id, sha256, md5, type, time_added) VALUES
+    (1, 'aa', 'md51', 'image/png', '2024-01-01T00:00:00'),
+    (2, 'bb', 'md52', 'image/png', '2024-01-01T00:00:00'),
+    (3, 'cc', 'md53', 'text/plain', '2024-01-01T00:00:00');
+            "#,
+        )
+        .execute(&mut conn)
+        .await
+        .unwrap();
+
+        let body = concat!(
+            r#"{"sha256": "AA", "tags": [{"namespace": "general", "name": "cat"}]}"#,
+            "\n",
+            r#"{"sha256": "bb", "tags": [{"namespace": "general", "name": "dog", "confidence": 0.5}]}"#,
+            "\n\n",
+            "not json\n",
+            r#"{"sha256": "zz", "tags": []}"#,
+            "\n",
+            r#"{"sha256": "cc", "tags": [{"namespace": "series", "name": "x"}]}"#,
+        );
+        let report = run_tag_import(&index_db, "hydrus", Some(2), body.as_bytes())
+            .await
+            .unwrap();
+        assert_eq!(report.lines, 5);
+        assert_eq!(report.counts.items, 3);
+        assert_eq!(report.counts.other_files, 1);
+        assert_eq!(report.unmatched, vec!["zz"]);
+        assert_eq!(report.invalid_lines, vec![4]);
+
+        let (completed, segments, errors): (i64, i64, i64) = sqlx::query_as(
+            "SELECT completed, total_segments, errors FROM data_log WHERE job_id = ?1",
+        )
+        .bind(report.job_id)
+        .fetch_one(&mut conn)
+        .await
+        .unwrap();
+        assert_eq!((completed, segments, errors), (1, 3, 2));
+
+        let reserved = run_tag_import(&index_db, MANUAL_TAGS_SETTER, None, &b""[..]).await;
+        assert!(reserved.is_err());
+    }
+}
@@ -0,0 +1,250 @@
+//! Tag rethreshold: raises the confidence threshold of tags a setter already
+//! wrote without re-running inference. The run gets its own data log entry
+//! carrying the new threshold, so it appears in extraction history and later
+//! rethresholds measure against it.
+//!
+//! The setter's tags rows are walked in chunks of `RETHRESHOLD_CHUNK_ROWS`,
+//! one index writer transaction each.
+
+use std::time::Instant;
+
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::api_error::ApiError;
+use crate::db::extraction_write::{DataLogUpdate, current_iso_timestamp};
+use crate::db::index_writer::{IndexDbWriterMessage, call_index_db_writer};
+use crate::db::manual_tags::MANUAL_TAGS_SETTER;
+use crate::db::system_config::SystemConfigStore;
+use crate::db::tag_rethreshold::{TagRethresholdCounts, current_tags_threshold, setter_has_tags};
+use crate::jobs::files::run_post_job_maintenance;
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+const RETHRESHOLD_CHUNK_ROWS: i64 = 500;
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub(crate) struct TagRethresholdReport {
+    /// The rethreshold's data log job ID, as shown in extraction history.
+    pub job_id: i64,
+    pub setter_name: String,
+    pub threshold: f64,
+    /// The threshold the setter's tags were cut at before, when recorded.
+    pub previous_threshold: Option<f64>,
+    #[serde(flatten)]
+    pub counts: TagRethresholdCounts,
+}
+
+/// Checks the request against the setter's data before anything is logged.
+/// Returns the setter's current threshold.
+async fn validate(
+    conn: &mut sqlx::SqliteConnection,
+    setter_name: &str,
+    threshold: f64,
+) -> ApiResult<Option<f64>> {
+    if setter_name.trim().is_empty() {
+        return Err(ApiError::bad_request("setter_name must not be empty"));
+    }
+    if setter_name == MANUAL_TAGS_SETTER {
+        return Err(ApiError::bad_request(format!(
+            "{MANUAL_TAGS_SETTER} holds manually added tags, which have no threshold"
+        )));
+    }
+    if !threshold.is_finite() || threshold <= 0.0 {
+        return Err(ApiError::bad_request(
+            "threshold must be a finite number above 0",
+        ));
+    }
+    if !setter_has_tags(conn, setter_name).await? {
+        return Err(ApiError::bad_request(format!(
+            "Setter has no tags: {setter_name}"
+        )));
+    }
+    let current = current_tags_threshold(conn, setter_name).await?;
+    if let Some(current) = current
+        && threshold < current
+    {
+        return Err(ApiError::bad_request(format!(
+            "Cannot lower the threshold of {setter_name} from {current} to {threshold}: tags below \
+             {current} were discarded at inference time and are not stored. Delete the setter's \
+             data and re-run the model with threshold {threshold} instead."
+        )));
+    }
+    Ok(current)
+}
+
+fn log_update(counts: &TagRethresholdCounts, started: Instant, finished: bool) -> DataLogUpdate {
+    DataLogUpdate {
+        image_files: counts.image_files as i64,
+        video_files: counts.video_files as i64,
+        other_files: counts.other_files as i64,
+        total_segments: counts.items as i64,
+        errors: 0,
+        timeouts: 0,
+        undecodable: 0,
+        frames_dropped: 0,
+        total_remaining: 0,
+        data_load_time: started.elapsed().as_secs_f64(),
+        inference_time: 0.0,
+        finished,
+    }
+}
+
+async fn apply_chunks(
+    index_db: &str,
+    setter_name: &str,
+    threshold: f64,
+    counts: &mut TagRethresholdCounts,
+) -> ApiResult<()> {
+    let normalization = SystemConfigStore::from_env()
+        .load(index_db)?
+        .text_normalization;
+    let mut after_id = 0;
+    loop {
+        let chunk = call_index_db_writer(index_db, |reply| {
+            IndexDbWriterMessage::RethresholdTagsChunk {
+                setter_name: setter_name.to_string(),
+                threshold,
+                normalization: normalization.clone(),
+                after_id,
+                limit: RETHRESHOLD_CHUNK_ROWS,
+                reply,
+            }
+        })
+        .await?;
+        if chunk.scanned == 0 {
+            return Ok(());
+        }
+        counts.add(&chunk.counts);
+        after_id = chunk.cursor;
+    }
+}
+
+/// Removes the setter's tags below `threshold` and rewrites the tags text
+/// entries they appeared in. `conn` is a read connection to `index_db`, used
+/// to validate the request. A failed chunk stops the run and leaves its data
+/// log unfinished; chunks written before it are kept.
+pub(crate) async fn run_tag_rethreshold(
+    conn: &mut sqlx::SqliteConnection,
+    index_db: &str,
+    setter_name: &str,
+    threshold: f64,
+) -> ApiResult<TagRethresholdReport> {
+    let previous_threshold = validate(conn, setter_name, threshold).await?;
+    let started = Instant::now();
+    let job_id = call_index_db_writer(index_db, |reply| IndexDbWriterMessage::AddDataLog {
+        scan_time: current_iso_timestamp(),
+        threshold: Some(threshold),
+        types: vec!["tags".to_string()],
+        setter: setter_name.to_string(),
+        batch_size: RETHRESHOLD_CHUNK_ROWS,
+        reprocess: false,
+        parameters: None,
+        filter_counts: None,
+        reply,
+    })
+    .await?;
+    let mut report = TagRethresholdReport {
+        job_id,
+        setter_name: setter_name.to_string(),
+        threshold,
+        previous_threshold,
+        counts: TagRethresholdCounts::default(),
+    };
+
+    let result = apply_chunks(index_db, setter_name, threshold, &mut report.counts).await;
+    let update = log_update(&report.counts, started, result.is_ok());
+    call_index_db_writer(index_db, |reply| IndexDbWriterMessage::UpdateDataLog {
+        job_id,
+        update: update.clone(),
+        reply,
+    })
+    .await?;
+    result?;
+
+    tracing::info!(
+        index_db,
+        setter_name,
+        job_id,
+        threshold,
+        items = report.counts.items,
+        tags_removed = report.counts.tags_removed,
+        "tag rethreshold finished"
+    );
+    if report.counts.items > 0 {
+        run_post_job_maintenance(index_db, false).await;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+
+    use super::*;
+    use crate::db::migrations::migrate_databases_on_disk;
+    use crate::db::{open_index_db_read_no_user_data, open_index_db_write_no_user_data};
+    use crate::test_utils::test_data_dir;
+
+    /// A raise is logged with its threshold and becomes the floor for the
+    /// next request, which is refused when it tries to go back down.
+    #[tokio::test]
+    async fn rethreshold_logs_the_run_and_refuses_to_lower() {
+        let _test_env = test_data_dir();
+        let index_db = "tag_rethreshold_index".to_string();
+        migrate_databases_on_disk(Some(&index_db), None)
+            .await
+            .unwrap();
+        let mut write = open_index_db_write_no_user_data(&index_db).await.unwrap();
+        sqlx::query(
+            r#"
+INSERT INTO items (id, sha256, md5, type, time_added) VALUES
+    (1, 'aa', 'md51', 'video/mp4', '2024-01-01T00:00:00');
+INSERT INTO setters (id, name) VALUES (1, 'wd');
+INSERT INTO data_jobs (id, completed) VALUES (1, 1);
+INSERT INTO data_log (job_id, start_time, end_time, type, setter, threshold, batch_size)
+VALUES (1, '2024-01-01T00:00:00', '2024-01-01T00:00:00', 'tags', 'wd', 0.2, 64);
+INSERT INTO item_data
+    (id, item_id, job_id, setter_id, data_type, idx, is_origin, source_id, is_placeholder)
+VALUES (1, 1, 1, 1, 'tags', 0, 1, NULL, 0), (2, 1, 1, 1, 'tags', 1, NULL, 1, 0);
+INSERT INTO tags (id, namespace, name) VALUES (1, 'wd:general', 'cat'), (2, 'wd:general', 'sky');
+INSERT INTO tags_items (item_data_id, tag_id, confidence) VALUES
+    (1, 1, 0.9), (1, 2, 0.3), (2, 2, 0.3);
+            "#,
+        )
+        .execute(&mut write)
+        .await
+        .unwrap();
+        drop(write);
+        let mut read = open_index_db_read_no_user_data(&index_db).await.unwrap();
+
+        let report = run_tag_rethreshold(&mut read, &index_db, "wd", 0.5)
+            .await
+            .unwrap();
+        assert_eq!(report.previous_threshold, Some(0.2));
+        assert_eq!(report.counts.items, 1);
+        assert_eq!(report.counts.tags_removed, 2);
+        assert_eq!(report.counts.video_files, 1);
+        let frame_rows: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM item_data WHERE id = 2")
+            .fetch_one(&mut read)
+            .await
+            .unwrap();
+        assert_eq!(frame_rows, 0);
+        let (completed, threshold): (i64, f64) =
+            sqlx::query_as("SELECT completed, threshold FROM data_log WHERE job_id = ?1")
+                .bind(report.job_id)
+                .fetch_one(&mut read)
+                .await
+                .unwrap();
+        assert_eq!((completed, threshold), (1, 0.5));
+
+        let lowered = run_tag_rethreshold(&mut read, &index_db, "wd", 0.4)
+            .await
+            .unwrap_err();
+        assert!(lowered.detail().contains("re-run the model"), "{lowered:?}");
+        let unknown = run_tag_rethreshold(&mut read, &index_db, "missing", 0.9)
+            .await
+            .unwrap_err();
+        assert_eq!(unknown.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -14,7 +14,7 @@ use crate::db::vector_quants::{
     BACKFILL_CHUNK_ROWS, DELETE_CHUNK_ROWS, DesiredState, RECONCILE_JOB_TAG, ReconcileWork,
     SpaceBuild, analyze, compute_mean_artifact, load_desired_state, load_snapshot, plan_data,
 };
-use crate::jobs::queue::{BatchDedup, JobRequest, JobType, enqueue_jobs_unless_tagged};
+use crate::jobs::queue::{BatchDedup, JobChain, JobRequest, JobType, enqueue_jobs_unless_tagged};
 
 type ApiResult<T> = std::result::Result<T, ApiError>;
 
@@ -301,6 +301,10 @@ pub(crate) async fn check_and_schedule(index_db: &str, user_data_db: &str) {
                 threshold: None,
                 log_id: None,
                 tag: Some(RECONCILE_JOB_TAG.to_string()),
+                dedup_key: None,
+                run_now: false,
+                reprocess: false,
+                chain: JobChain::default(),
             };
             let dedup = BatchDedup {
                 tag: RECONCILE_JOB_TAG.to_string(),
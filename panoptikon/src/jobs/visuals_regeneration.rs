@@ -0,0 +1,471 @@
+//! Visuals regeneration: rebuilds thumbnails and video frames stored by an
+//! older [`THUMBNAIL_PROCESS_VERSION`] / [`FRAME_PROCESS_VERSION`].
+//!
+//! Scans only generate visuals for items that have none at the current
+//! version, so after a version bump this job is what reaches the items that
+//! are already indexed. It pages through the outdated items in sha256 order,
+//! regenerates each batch from an available file on a bounded set of
+//! blocking workers, and stores the results at the current version (which
+//! replaces the old rows). Items without an available file, and videos
+//! without a usable duration for a fresh frame extraction, are skipped and
+//! keep their old visuals; so do items whose regeneration fails.
+//!
+//! Items a fast scan indexed without visuals (see `SystemConfig::fast_scan`)
+//! are generated the same way in a second pass, so the job doubles as the
+//! bulk backfill for them.
+//!
+//! Progress is kept per index DB in process memory and exposed by the
+//! visuals status endpoint. Cancelling the job aborts it between items;
+//! everything stored up to then stays, and the next run continues with what
+//! is still outdated.
+
+use std::sync::Arc;
+
+use serde::Serialize;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use utoipa::ToSchema;
+
+use crate::api_error::ApiError;
+use crate::db::extraction_write::current_iso_timestamp;
+use crate::db::index_writer::{IndexDbWriterMessage, call_index_db_writer};
+use crate::db::open_index_db_read_no_user_data;
+use crate::db::storage::{
+    OutdatedVisuals, count_missing_visuals, count_outdated_visuals, get_frames_bytes,
+    get_missing_visuals, get_outdated_visuals, visuals_dir,
+};
+use crate::jobs::files::{
+    FRAME_PROCESS_VERSION, RegeneratedVisuals, THUMBNAIL_PROCESS_VERSION, regenerate_visuals,
+};
+use crate::jobs::registry::Registry;
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+/// Items per batch when the job has no batch size.
+pub(crate) const DEFAULT_BATCH_SIZE: i64 = 64;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, ToSchema)]
+pub(crate) struct VisualsRegenerationProgress {
+    pub started_at: String,
+    /// Null while the job is running, or if it was cancelled.
+    pub finished_at: Option<String>,
+    pub thumbnail_version: i64,
+    pub frame_version: i64,
+    /// Items with outdated thumbnails or frames, or left without visuals by
+    /// a fast scan, when the job started.
+    pub total: i64,
+    /// How many of `total` a fast scan left without visuals.
+    pub missing: i64,
+    /// Items looked at, whatever their outcome.
+    pub processed: i64,
+    pub regenerated: i64,
+    /// Items with no available file or no usable video track; their
+    /// outdated visuals are kept.
+    pub skipped: i64,
+    /// Items whose regeneration or storage failed; their outdated visuals
+    /// are kept.
+    pub failed: i64,
+}
+
+static PROGRESS: Registry<String, VisualsRegenerationProgress> = Registry::new();
+
+/// Progress of the running or most recent regeneration job for `index_db`
+/// in this process.
+pub(crate) fn last_progress(index_db: &str) -> Option<VisualsRegenerationProgress> {
+    PROGRESS.get(index_db)
+}
+
+fn publish_progress(index_db: &str, progress: &VisualsRegenerationProgress) {
+    PROGRESS.insert(index_db.to_string(), progress.clone());
+}
+
+pub(crate) enum Outcome {
+    Regenerated(RegeneratedVisuals),
+    Skipped,
+    Failed,
+}
+
+pub(crate) async fn run_visuals_regeneration_job(
+    index_db: &str,
+    batch_size: Option<i64>,
+) -> ApiResult<VisualsRegenerationProgress> {
+    let batch_size = batch_size
+        .filter(|size| *size > 0)
+        .unwrap_or(DEFAULT_BATCH_SIZE);
+    let mut conn = open_index_db_read_no_user_data(index_db).await?;
+    let outdated =
+        count_outdated_visuals(&mut conn, THUMBNAIL_PROCESS_VERSION, FRAME_PROCESS_VERSION).await?;
+    let missing = count_missing_visuals(&mut conn).await?;
+    let mut progress = VisualsRegenerationProgress {
+        started_at: current_iso_timestamp(),
+        thumbnail_version: THUMBNAIL_PROCESS_VERSION,
+        frame_version: FRAME_PROCESS_VERSION,
+        total: outdated.items + missing,
+        missing,
+        ..Default::default()
+    };
+    publish_progress(index_db, &progress);
+    tracing::info!(
+        index_db,
+        items = outdated.items,
+        thumbnails = outdated.thumbnails,
+        frames = outdated.frames,
+        missing,
+        "regenerating outdated visuals"
+    );
+
+    let workers = std::thread::available_parallelism()
+        .map(|count| count.get())
+        .unwrap_or(4);
+    let semaphore = Arc::new(Semaphore::new(workers));
+    // Outdated visuals first, then the items a fast scan left without any.
+    // Regenerated items drop out of both queries, so neither pass revisits
+    // what the other stored.
+    for missing_pass in [false, true] {
+        let mut after_sha256 = String::new();
+        loop {
+            let batch = if missing_pass {
+                get_missing_visuals(&mut conn, &after_sha256, batch_size).await?
+            } else {
+                get_outdated_visuals(
+                    &mut conn,
+                    THUMBNAIL_PROCESS_VERSION,
+                    FRAME_PROCESS_VERSION,
+                    &after_sha256,
+                    batch_size,
+                )
+                .await?
+            };
+            let Some(last) = batch.last() else {
+                break;
+            };
+            after_sha256 = last.sha256.clone();
+            process_batch(index_db, &mut conn, &semaphore, batch, &mut progress).await?;
+        }
+    }
+
+    progress.finished_at = Some(current_iso_timestamp());
+    publish_progress(index_db, &progress);
+    Ok(progress)
+}
+
+/// Regenerates one page of items on the blocking workers and stores the
+/// results, publishing progress once the page is done.
+async fn process_batch(
+    index_db: &str,
+    conn: &mut sqlx::SqliteConnection,
+    semaphore: &Arc<Semaphore>,
+    batch: Vec<OutdatedVisuals>,
+    progress: &mut VisualsRegenerationProgress,
+) -> ApiResult<()> {
+    let dir = visuals_dir(index_db);
+    let mut tasks = JoinSet::new();
+    for item in batch {
+        let existing_frames = if item.mime_type.starts_with("video") && !item.frames_outdated {
+            get_frames_bytes(conn, &dir, &item.sha256).await?
+        } else {
+            Vec::new()
+        };
+        let permit = semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|_| ApiError::internal("Failed to schedule regeneration work"))?;
+        tasks.spawn(async move {
+            let _permit = permit;
+            let outcome = regenerate_item(&item, existing_frames).await;
+            (item, outcome)
+        });
+    }
+    while let Some(joined) = tasks.join_next().await {
+        progress.processed += 1;
+        let (item, outcome) = match joined {
+            Ok(result) => result,
+            Err(err) => {
+                tracing::error!(error = %err, "visuals regeneration worker failed");
+                progress.failed += 1;
+                continue;
+            }
+        };
+        match outcome {
+            Outcome::Regenerated(visuals) => match store_visuals(index_db, &item, visuals).await {
+                Ok(()) => progress.regenerated += 1,
+                Err(err) => {
+                    tracing::error!(error = ?err, sha256 = item.sha256, "failed to store regenerated visuals");
+                    progress.failed += 1;
+                }
+            },
+            Outcome::Skipped => progress.skipped += 1,
+            Outcome::Failed => progress.failed += 1,
+        }
+    }
+    publish_progress(index_db, progress);
+    tracing::info!(
+        index_db,
+        processed = progress.processed,
+        total = progress.total,
+        regenerated = progress.regenerated,
+        skipped = progress.skipped,
+        failed = progress.failed,
+        "visuals regeneration progress"
+    );
+    Ok(())
+}
+
+pub(crate) async fn regenerate_item(
+    item: &OutdatedVisuals,
+    existing_frames: Vec<Vec<u8>>,
+) -> Outcome {
+    let Some(path) = item.path.clone() else {
+        return Outcome::Skipped;
+    };
+    let is_video = item.mime_type.starts_with("video");
+    // Only a fresh frame extraction needs the duration.
+    let mut video_duration = 0.0;
+    if is_video && existing_frames.is_empty() {
+        let duration = item.duration.unwrap_or(0.0);
+        if duration <= 0.0 || item.video_tracks.unwrap_or(0) <= 0 {
+            return Outcome::Skipped;
+        }
+        video_duration = duration;
+    }
+    let mime_type = item.mime_type.clone();
+    let joined = tokio::task::spawn_blocking(move || {
+        let result = regenerate_visuals(&path, &mime_type, &existing_frames, video_duration);
+        (path, result)
+    })
+    .await;
+    match joined {
+        // Images served from their original file legitimately get no
+        // thumbnail; for anything else an empty result means the renderer
+        // produced nothing, and the old thumbnails are better than none.
+        Ok((_, Ok(visuals)))
+            if visuals.thumbnails.is_empty() && !item.mime_type.starts_with("image") =>
+        {
+            Outcome::Failed
+        }
+        Ok((_, Ok(visuals))) if item.frames_outdated && visuals.frames.is_empty() => {
+            Outcome::Failed
+        }
+        Ok((_, Ok(visuals))) => Outcome::Regenerated(visuals),
+        Ok((path, Err(err))) => {
+            tracing::error!(error = ?err, path = %path.display(), "failed to regenerate visuals");
+            Outcome::Failed
+        }
+        Err(err) => {
+            tracing::error!(error = %err, sha256 = item.sha256, "visuals regeneration worker failed");
+            Outcome::Failed
+        }
+    }
+}
+
+/// Stores regenerated visuals at the current versions. Storing thumbnails
+/// replaces the old rows even when there are none now, e.g. for an image
+/// that the current logic serves from its original file.
+pub(crate) async fn store_visuals(
+    index_db: &str,
+    item: &OutdatedVisuals,
+    visuals: RegeneratedVisuals,
+) -> ApiResult<()> {
+    call_index_db_writer(index_db, |reply| IndexDbWriterMessage::StoreThumbnails {
+        sha256: item.sha256.clone(),
+        mime_type: item.mime_type.clone(),
+        process_version: THUMBNAIL_PROCESS_VERSION,
+        thumbnails: visuals.thumbnails.clone(),
+        reply,
+    })
+    .await?;
+    if !visuals.frames.is_empty() {
+        call_index_db_writer(index_db, |reply| IndexDbWriterMessage::StoreFrames {
+            sha256: item.sha256.clone(),
+            mime_type: item.mime_type.clone(),
+            process_version: FRAME_PROCESS_VERSION,
+            frames: visuals.frames.clone(),
+            reply,
+        })
+        .await?;
+    }
+    if let Some(blurhash) = &visuals.blurhash {
+        call_index_db_writer(index_db, |reply| IndexDbWriterMessage::SetBlurhash {
+            sha256: item.sha256.clone(),
+            blurhash: blurhash.clone(),
+            reply,
+        })
+        .await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::migrations::migrate_databases_on_disk;
+    use crate::test_utils::test_data_dir;
+
+    /// Three items: an outdated image with a file on disk, an outdated one
+    /// without any available file, and one already at the current version.
+    /// Only the first is regenerated; paging with one item per batch still
+    /// visits both outdated ones.
+    #[tokio::test]
+    async fn regeneration_replaces_outdated_visuals_only() {
+        let test_env = test_data_dir();
+        let index_db = "visuals_regeneration_index".to_string();
+        migrate_databases_on_disk(Some(&index_db), None)
+            .await
+            .unwrap();
+        let image_path = test_env.path().join("regenerate.png");
+        image::RgbImage::from_pixel(32, 32, image::Rgb([200, 40, 40]))
+            .save(&image_path)
+            .unwrap();
+
+        let mut conn = crate::db::open_index_db_write_no_user_data(&index_db)
+            .await
+            .unwrap();
+        sqlx::query(
+            r#"
+            INSERT INTO file_scans (id, start_time, path) VALUES (1, '2024-01-01T00:00:00', '/');
+            INSERT INTO items (id, sha256, md5, type, time_added) VALUES
+                (1, 'sha_a', 'md5_a', 'image/png', '2024-01-01T00:00:00'),
+                (2, 'sha_b', 'md5_b', 'image/png', '2024-01-01T00:00:00'),
+                (3, 'sha_c', 'md5_c', 'image/png', '2024-01-01T00:00:00');
+            INSERT INTO files (id, sha256, item_id, path, filename, last_modified, scan_id, available)
+            VALUES (2, 'sha_b', 2, '/gone.png', 'gone.png', '2024-01-01T00:00:00', 1, 0);
+            "#,
+        )
+        .execute(&mut conn)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO files (id, sha256, item_id, path, filename, last_modified, scan_id, available) VALUES (1, 'sha_a', 1, ?1, 'regenerate.png', '2024-01-01T00:00:00', 1, 1)",
+        )
+        .bind(image_path.to_string_lossy().as_ref())
+        .execute(&mut conn)
+        .await
+        .unwrap();
+        sqlx::query(
+            r#"
+            INSERT INTO storage.thumbnails (item_sha256, idx, item_mime_type, width, height, version, thumbnail)
+            VALUES
+                ('sha_a', 0, 'image/png', 10, 10, ?1 - 1, x'00'),
+                ('sha_b', 0, 'image/png', 10, 10, ?1 - 1, x'00'),
+                ('sha_c', 0, 'image/png', 10, 10, ?1, x'00')
+            "#,
+        )
+        .bind(THUMBNAIL_PROCESS_VERSION)
+        .execute(&mut conn)
+        .await
+        .unwrap();
+        drop(conn);
+
+        let progress = run_visuals_regeneration_job(&index_db, Some(1))
+            .await
+            .unwrap();
+        assert_eq!(
+            (
+                progress.total,
+                progress.processed,
+                progress.regenerated,
+                progress.skipped,
+                progress.failed
+            ),
+            (2, 2, 1, 1, 0)
+        );
+        assert!(progress.finished_at.is_some());
+        assert_eq!(last_progress(&index_db), Some(progress));
+
+        let mut conn = open_index_db_read_no_user_data(&index_db).await.unwrap();
+        // The small image is served from its file, so its outdated thumbnail
+        // is dropped rather than replaced; it gets a blurhash either way.
+        let thumbnails: Vec<(String, i64)> = sqlx::query_as(
+            "SELECT item_sha256, version FROM storage.thumbnails ORDER BY item_sha256",
+        )
+        .fetch_all(&mut conn)
+        .await
+        .unwrap();
+        assert_eq!(
+            thumbnails,
+            vec![
+                ("sha_b".to_string(), THUMBNAIL_PROCESS_VERSION - 1),
+                ("sha_c".to_string(), THUMBNAIL_PROCESS_VERSION),
+            ]
+        );
+        let blurhash: Option<String> =
+            sqlx::query_scalar("SELECT blurhash FROM items WHERE sha256 = 'sha_a'")
+                .fetch_one(&mut conn)
+                .await
+                .unwrap();
+        assert!(blurhash.is_some());
+        let outdated =
+            count_outdated_visuals(&mut conn, THUMBNAIL_PROCESS_VERSION, FRAME_PROCESS_VERSION)
+                .await
+                .unwrap();
+        assert_eq!(
+            outdated,
+            crate::db::storage::OutdatedVisualsCount {
+                items: 1,
+                thumbnails: 1,
+                frames: 0,
+            }
+        );
+    }
+
+    /// An image a fast scan left without visuals is picked up by the second
+    /// pass; one with no available file is left alone.
+    #[tokio::test]
+    async fn regeneration_backfills_items_missing_visuals() {
+        let test_env = test_data_dir();
+        let index_db = "visuals_regeneration_missing_index".to_string();
+        migrate_databases_on_disk(Some(&index_db), None)
+            .await
+            .unwrap();
+        let image_path = test_env.path().join("deferred.png");
+        image::RgbImage::from_pixel(32, 32, image::Rgb([40, 200, 40]))
+            .save(&image_path)
+            .unwrap();
+
+        let mut conn = crate::db::open_index_db_write_no_user_data(&index_db)
+            .await
+            .unwrap();
+        sqlx::query(
+            r#"
+            INSERT INTO file_scans (id, start_time, path) VALUES (1, '2024-01-01T00:00:00', '/');
+            INSERT INTO items (id, sha256, md5, type, time_added) VALUES
+                (1, 'sha_a', 'md5_a', 'image/png', '2024-01-01T00:00:00'),
+                (2, 'sha_b', 'md5_b', 'image/png', '2024-01-01T00:00:00');
+            INSERT INTO files (id, sha256, item_id, path, filename, last_modified, scan_id, available)
+            VALUES (2, 'sha_b', 2, '/gone.png', 'gone.png', '2024-01-01T00:00:00', 1, 0);
+            "#,
+        )
+        .execute(&mut conn)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO files (id, sha256, item_id, path, filename, last_modified, scan_id, available) VALUES (1, 'sha_a', 1, ?1, 'deferred.png', '2024-01-01T00:00:00', 1, 1)",
+        )
+        .bind(image_path.to_string_lossy().as_ref())
+        .execute(&mut conn)
+        .await
+        .unwrap();
+        drop(conn);
+
+        let progress = run_visuals_regeneration_job(&index_db, None).await.unwrap();
+        assert_eq!(
+            (
+                progress.total,
+                progress.missing,
+                progress.processed,
+                progress.regenerated
+            ),
+            (1, 1, 1, 1)
+        );
+
+        let mut conn = open_index_db_read_no_user_data(&index_db).await.unwrap();
+        let blurhash: Option<String> =
+            sqlx::query_scalar("SELECT blurhash FROM items WHERE sha256 = 'sha_a'")
+                .fetch_one(&mut conn)
+                .await
+                .unwrap();
+        assert!(blurhash.is_some());
+        assert_eq!(count_missing_visuals(&mut conn).await.unwrap(), 0);
+    }
+}
@@ -0,0 +1,153 @@
+//! Visuals storage migration: moves stored thumbnails and video frames to
+//! the configured `thumbnail_storage` backend.
+//!
+//! Switching the backend only changes where new visuals are written; reads
+//! look in both places, so nothing breaks in between. This job moves the
+//! rest, one batch per index writer message so scans and other writes keep
+//! interleaving with it. Moving to files empties the rows' blobs (the
+//! space comes back with the next VACUUM); moving back into SQLite deletes
+//! the files once their batch has committed. Cancelling stops between
+//! batches, and the next run picks up whatever is left.
+
+use crate::api_error::ApiError;
+use crate::config::ThumbnailStorage;
+use crate::db::index_writer::{IndexDbWriterMessage, call_index_db_writer};
+use crate::db::open_index_db_read_no_user_data;
+use crate::db::storage::{VisualTable, count_visuals_to_migrate};
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+/// Rows moved per writer message when the job has no batch size. Thumbnail
+/// blobs are tens of kilobytes, so a batch holds a few megabytes.
+pub(crate) const DEFAULT_BATCH_SIZE: i64 = 128;
+
+/// Moves every thumbnail and frame of `index_db` to the configured backend
+/// and returns how many rows were moved.
+pub(crate) async fn run_visuals_storage_migration_job(
+    index_db: &str,
+    batch_size: Option<i64>,
+) -> ApiResult<u64> {
+    let target = crate::config::runtime().thumbnail_storage;
+    migrate_visuals(index_db, target, batch_size).await
+}
+
+async fn migrate_visuals(
+    index_db: &str,
+    target: ThumbnailStorage,
+    batch_size: Option<i64>,
+) -> ApiResult<u64> {
+    let limit = batch_size
+        .filter(|size| *size > 0)
+        .unwrap_or(DEFAULT_BATCH_SIZE);
+    let mut moved_total = 0;
+    for table in [VisualTable::Thumbnails, VisualTable::Frames] {
+        let pending = {
+            let mut conn = open_index_db_read_no_user_data(index_db).await?;
+            count_visuals_to_migrate(&mut conn, table, target).await?
+        };
+        tracing::info!(
+            index_db,
+            table = table.name(),
+            ?target,
+            pending,
+            "migrating stored visuals"
+        );
+        let mut moved = 0;
+        loop {
+            let batch =
+                call_index_db_writer(index_db, |reply| IndexDbWriterMessage::MigrateVisuals {
+                    table,
+                    target,
+                    limit,
+                    reply,
+                })
+                .await?;
+            moved += batch;
+            if batch < limit as u64 {
+                break;
+            }
+            tracing::info!(
+                index_db,
+                table = table.name(),
+                moved,
+                pending,
+                "visuals storage migration progress"
+            );
+        }
+        moved_total += moved;
+    }
+    tracing::info!(
+        index_db,
+        moved = moved_total,
+        "visuals storage migration finished"
+    );
+    Ok(moved_total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::migrations::migrate_databases_on_disk;
+    use crate::db::storage::{get_frames_bytes, visuals_dir};
+    use crate::test_utils::test_data_dir;
+
+    async fn blob_lengths(conn: &mut sqlx::SqliteConnection, table: &str) -> Vec<i64> {
+        let sql = format!("SELECT length({table}) FROM storage.{table}s ORDER BY id");
+        sqlx::query_scalar(sqlx::AssertSqlSafe(sql))
+            .fetch_all(conn)
+            .await
+            .unwrap()
+    }
+
+    /// Batches smaller than the table still move every row, both tables are
+    /// covered, and moving back restores the blobs and removes the files.
+    #[tokio::test]
+    async fn migration_moves_every_row_in_both_directions() {
+        let _test_env = test_data_dir();
+        let index_db = "visuals_storage_migration_index".to_string();
+        migrate_databases_on_disk(Some(&index_db), None)
+            .await
+            .unwrap();
+        let mut conn = crate::db::open_index_db_write_no_user_data(&index_db)
+            .await
+            .unwrap();
+        sqlx::query(
+            r#"
+INSERT INTO storage.thumbnails (item_sha256, idx, item_mime_type, width, height, version, thumbnail)
+VALUES
+    ('aaaa11', 0, 'image/png', 10, 10, 1, x'01'),
+    ('bbbb22', 0, 'image/png', 10, 10, 1, x'02'),
+    ('cccc33', 0, 'image/png', 10, 10, 1, x'03');
+INSERT INTO storage.frames (item_sha256, idx, item_mime_type, width, height, version, frame)
+VALUES
+    ('cccc33', 0, 'video/mp4', 10, 10, 1, x'0a'),
+    ('cccc33', 1, 'video/mp4', 10, 10, 1, x'0b');
+            "#,
+        )
+        .execute(&mut conn)
+        .await
+        .unwrap();
+
+        let moved = migrate_visuals(&index_db, ThumbnailStorage::Filesystem, Some(2))
+            .await
+            .unwrap();
+        assert_eq!(moved, 5);
+        assert_eq!(blob_lengths(&mut conn, "thumbnail").await, vec![0, 0, 0]);
+        assert_eq!(blob_lengths(&mut conn, "frame").await, vec![0, 0]);
+        let dir = visuals_dir(&index_db);
+        let frame_file = VisualTable::Frames.file_path(&dir, "cccc33", 1);
+        assert_eq!(std::fs::read(&frame_file).unwrap(), vec![0x0b]);
+        assert_eq!(
+            get_frames_bytes(&mut conn, &dir, "cccc33").await.unwrap(),
+            vec![vec![0x0a], vec![0x0b]]
+        );
+
+        let moved = migrate_visuals(&index_db, ThumbnailStorage::Sqlite, Some(2))
+            .await
+            .unwrap();
+        assert_eq!(moved, 5);
+        assert_eq!(blob_lengths(&mut conn, "thumbnail").await, vec![1, 1, 1]);
+        assert_eq!(blob_lengths(&mut conn, "frame").await, vec![1, 1]);
+        assert!(!frame_file.exists());
+    }
+}
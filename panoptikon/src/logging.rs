@@ -4,17 +4,37 @@
 //! string disables file logging. The `RUST_LOG` env var takes precedence over
 //! `[logging].level` when set, so targeted per-module directives keep
 //! working — it is deliberately NOT absorbed into the config file.
+//! `[logging].format = "json"` switches both outputs to one JSON object per
+//! event.
+//!
+//! Every HTTP request runs inside a `request` span whose `request_id` is
+//! taken from an incoming `X-Request-Id` header or generated, and echoed in
+//! the response; job runs get a `job` span with `job_id`. Index writer
+//! messages carry the sender's span (see `db::index_writer`), so writes
+//! triggered by a request or job log under the same ids.
 
 use std::env;
 use std::fs;
 use std::path::PathBuf;
 
+use axum::extract::Request;
+use axum::http::{HeaderName, HeaderValue};
+use axum::middleware::Next;
+use axum::response::Response;
+use tracing::{Instrument, Subscriber};
 use tracing_appender::non_blocking::WorkerGuard;
-use tracing_subscriber::EnvFilter;
+use tracing_subscriber::fmt::MakeWriter;
 use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
 use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
 
-use crate::config::Settings;
+use crate::config::{LogFormat, Settings};
+
+pub(crate) const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Longest caller-supplied request id that is reused as-is.
+const MAX_REQUEST_ID_LEN: usize = 128;
 
 fn env_filter(configured_level: &str) -> EnvFilter {
     if env::var("RUST_LOG").is_ok_and(|value| !value.trim().is_empty()) {
@@ -65,6 +85,18 @@ fn open_logs_file(settings: &Settings) -> Option<(PathBuf, fs::File)> {
     }
 }
 
+fn fmt_layer<S, W>(format: LogFormat, writer: W, ansi: bool) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let layer = tracing_subscriber::fmt::layer().with_writer(writer);
+    match format {
+        LogFormat::Text => layer.with_ansi(ansi).boxed(),
+        LogFormat::Json => layer.json().with_span_list(true).boxed(),
+    }
+}
+
 /// Initializes tracing with a console layer and, unless disabled, an appending
 /// file layer. A failure to open the log file degrades to console-only rather
 /// than refusing to start. The returned guard must stay alive for the process
@@ -73,7 +105,8 @@ pub(crate) fn init(settings: &Settings) -> Option<WorkerGuard> {
     // Desktop captures stdout/stderr through a pipe for its diagnostics UI.
     // ANSI styling is useful in an interactive terminal but becomes raw
     // control characters in that transport.
-    let console_layer = tracing_subscriber::fmt::layer().with_ansi(!crate::desktop::is_managed());
+    let format = settings.logging.format;
+    let console_layer = fmt_layer(format, std::io::stdout, !crate::desktop::is_managed());
     let registry = tracing_subscriber::registry()
         .with(env_filter(&settings.logging.level))
         .with(console_layer);
@@ -81,10 +114,7 @@ pub(crate) fn init(settings: &Settings) -> Option<WorkerGuard> {
     match open_logs_file(settings) {
         Some((path, file)) => {
             let (writer, guard) = tracing_appender::non_blocking(file);
-            let file_layer = tracing_subscriber::fmt::layer()
-                .with_writer(writer)
-                .with_ansi(false);
-            registry.with(file_layer).init();
+            registry.with(fmt_layer(format, writer, false)).init();
             tracing::info!(path = %path.display(), "logging to file");
             Some(guard)
         }
@@ -95,6 +125,48 @@ pub(crate) fn init(settings: &Settings) -> Option<WorkerGuard> {
     }
 }
 
+/// Minimal tracing for the offline administration subcommands: console only
+/// and on stderr, so stdout carries nothing but their output (SQL, NDJSON
+/// rows, results) and can be piped.
+pub(crate) fn init_stderr(settings: &Settings) {
+    tracing_subscriber::registry()
+        .with(env_filter(&settings.logging.level))
+        .with(fmt_layer(settings.logging.format, std::io::stderr, true))
+        .init();
+}
+
+/// The caller's `X-Request-Id` when it is a short token of visible ASCII,
+/// otherwise a fresh UUID.
+fn resolve_request_id(incoming: Option<&HeaderValue>) -> HeaderValue {
+    incoming
+        .filter(|value| {
+            let bytes = value.as_bytes();
+            !bytes.is_empty()
+                && bytes.len() <= MAX_REQUEST_ID_LEN
+                && bytes.iter().all(|byte| byte.is_ascii_graphic())
+        })
+        .cloned()
+        .unwrap_or_else(|| {
+            HeaderValue::from_str(&uuid::Uuid::new_v4().to_string())
+                .expect("a UUID is a valid header value")
+        })
+}
+
+/// Outermost middleware: runs the request inside a `request` span carrying
+/// its id, forwards the id upstream (the proxy keeps end-to-end headers) and
+/// returns it in the response.
+pub(crate) async fn request_id(mut request: Request, next: Next) -> Response {
+    let id = resolve_request_id(request.headers().get(&REQUEST_ID_HEADER));
+    request.headers_mut().insert(REQUEST_ID_HEADER, id.clone());
+    let span = tracing::info_span!(
+        "request",
+        request_id = %String::from_utf8_lossy(id.as_bytes())
+    );
+    let mut response = next.run(request).instrument(span).await;
+    response.headers_mut().insert(REQUEST_ID_HEADER, id);
+    response
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -141,6 +213,7 @@ base_url = "http://127.0.0.1:6342"
             LoggingConfig {
                 file: None,
                 level: "INFO".into(),
+                ..Default::default()
             },
             "d:/pan",
         );
@@ -153,6 +226,7 @@ base_url = "http://127.0.0.1:6342"
             LoggingConfig {
                 file: Some("logs/custom.log".into()),
                 level: "INFO".into(),
+                ..Default::default()
             },
             "d:/pan",
         );
@@ -166,10 +240,33 @@ base_url = "http://127.0.0.1:6342"
                 LoggingConfig {
                     file: Some(disabled.into()),
                     level: "INFO".into(),
+                    ..Default::default()
                 },
                 "d:/pan",
             );
             assert_eq!(logs_file_path(&settings), None, "{disabled:?} disables");
         }
     }
+
+    /// A well-formed incoming `X-Request-Id` is kept so ids correlate across
+    /// hops; a missing, oversized or non-printable one is replaced by a UUID.
+    #[test]
+    fn request_id_reuses_valid_incoming_ids() {
+        let incoming = HeaderValue::from_static("req-42.a:b");
+        assert_eq!(resolve_request_id(Some(&incoming)), incoming);
+
+        let long = HeaderValue::from_str(&"x".repeat(MAX_REQUEST_ID_LEN + 1)).unwrap();
+        for rejected in [
+            None,
+            Some(HeaderValue::from_static("")),
+            Some(HeaderValue::from_static("two words")),
+            Some(long),
+        ] {
+            let id = resolve_request_id(rejected.as_ref());
+            assert!(
+                uuid::Uuid::parse_str(id.to_str().unwrap()).is_ok(),
+                "{rejected:?} -> {id:?}"
+            );
+        }
+    }
 }
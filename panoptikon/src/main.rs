@@ -5,15 +5,18 @@
 
 mod api;
 mod api_error;
+mod cli;
 mod config;
 mod db;
 mod desktop;
+mod diagnostics;
 mod env_template;
 mod inferio;
 mod inferio_client;
 mod jobs;
 mod logging;
 mod media_tools;
+mod msgpack;
 mod openapi;
 mod policy;
 mod policy_token;
@@ -27,12 +30,13 @@ mod shutdown;
 mod test_utils;
 mod ui;
 mod update;
+mod video_frames;
 
 use crate::jobs::inference_pool::{InferencePool, JobInferenceContext, set_job_inference_context};
 use anyhow::Context as _;
 use axum::{
     Router,
-    routing::{any, delete, get, post},
+    routing::{any, delete, get, post, put},
 };
 use clap::Parser;
 use std::{env, net::SocketAddr, path::PathBuf, sync::Arc};
@@ -71,6 +75,21 @@ struct Args {
 
 #[derive(clap::Subcommand, Debug)]
 enum Command {
+    /// Run the server (the default when no subcommand is given).
+    Serve,
+    /// Run database migrations without starting the server: every database
+    /// under the data folder, or the pair named with --index-db /
+    /// --user-data-db (created if missing). Prints one line per database.
+    Migrate(cli::DbArgs),
+    /// Scan a folder (default: every included folder) into an index DB and
+    /// exit, printing progress to stderr and a summary per folder.
+    Scan(cli::ScanArgs),
+    /// Compile a PQL query from a JSON file and print the SQL, or run it
+    /// with --execute and print the result rows as NDJSON.
+    Pql(cli::PqlArgs),
+    /// Re-hash indexed files against their stored sha256 (the file
+    /// verification job) and print the files that failed.
+    Verify(cli::VerifyArgs),
     /// Run ONLY the local inference service (`/api/inference/*` + `/health`):
     /// no proxy, API, jobs, cron, or migrations. For machines that just lend
     /// their GPU to other panoptikon instances (design doc §3).
@@ -89,6 +108,10 @@ enum Command {
         #[arg(long)]
         force: bool,
     },
+    /// Check the environment for common setup problems (SQLite, ffmpeg,
+    /// Python, renderers, data folder, inference server, database schema)
+    /// and print one line per check. Fails when any check fails.
+    Doctor,
     /// Download and install the latest release, replacing this executable.
     /// Checks GitHub every time (ignoring the startup-check throttle).
     Update {
@@ -98,6 +121,25 @@ enum Command {
     },
 }
 
+impl Command {
+    /// Whether the command takes the root lock: serving, and the offline
+    /// commands that write to the databases.
+    fn owns_root(&self) -> bool {
+        matches!(
+            self,
+            Self::Serve | Self::Migrate(_) | Self::Scan(_) | Self::Verify(_)
+        )
+    }
+
+    /// The offline administration commands, which log to stderr only.
+    fn is_offline(&self) -> bool {
+        matches!(
+            self,
+            Self::Migrate(_) | Self::Scan(_) | Self::Pql(_) | Self::Verify(_) | Self::Doctor
+        )
+    }
+}
+
 fn main() -> anyhow::Result<()> {
     // Build a custom tokio runtime with a larger worker thread stack size.
     // The default 2MB stack can be insufficient for deeply nested async code,
@@ -134,9 +176,10 @@ async fn async_main() -> anyhow::Result<()> {
         .or_else(|| env::var(config::CONFIG_PATH_ENV).ok().map(PathBuf::from));
     // A serving process owns its complete root. This prevents a foreground
     // Server and Desktop sidecar (or two foreground Servers) from opening the
-    // same SQLite databases. Setup/update/inferio retain their existing,
-    // narrower concurrency behavior.
-    let _root_lock = if args.command.is_none() {
+    // same SQLite databases; the offline DB commands take it for the same
+    // reason. Setup/update/inferio/pql retain their existing, narrower
+    // concurrency behavior.
+    let _root_lock = if args.command.as_ref().is_none_or(Command::owns_root) {
         Some(desktop::RootLock::acquire(std::env::current_dir()?)?)
     } else {
         None
@@ -154,11 +197,51 @@ async fn async_main() -> anyhow::Result<()> {
         resources::materialize_first_run(config_path.is_some() && !args.desktop_managed)?;
     // Config must load before logging init (logging is configured by
     // [logging] now); a config-load error is reported on stderr by main.
-    let settings = Arc::new(config::Settings::load(config_path)?);
+    let mut settings = config::Settings::load(config_path)?;
+    // Index DB instance locks (db/instance_lock.rs): the root lock above
+    // can't see a second gateway started from another root against the same
+    // data folder. A readonly process writes nothing and takes none.
+    let owns_root = args.command.as_ref().is_none_or(Command::owns_root);
+    let db_locks_held = if owns_root && !settings.readonly {
+        let held = db::instance_lock::acquire_startup_locks(
+            &settings.data_folder,
+            &settings.index_db,
+            std::time::Duration::from_secs(settings.server.db_lock_stale_secs.max(1)),
+        )?;
+        if !held.is_empty() {
+            match settings.server.db_lock_conflict {
+                config::DbLockConflict::Refuse => {
+                    let held = held.iter().map(ToString::to_string).collect::<Vec<_>>();
+                    anyhow::bail!(
+                        "{}; stop the other instance, or set [server] db_lock_conflict = \"readonly\" to start read-only",
+                        held.join("; ")
+                    );
+                }
+                config::DbLockConflict::Readonly => {
+                    // A readonly process holds no locks, not even on the
+                    // index DBs nobody else had.
+                    db::instance_lock::release_all();
+                    settings.readonly = true;
+                }
+            }
+        }
+        if !settings.readonly {
+            db::instance_lock::spawn_heartbeat();
+        }
+        held
+    } else {
+        Vec::new()
+    };
+    let settings = Arc::new(settings);
     config::install_runtime(&settings);
     // The guard must stay alive for the whole process: dropping it flushes
     // buffered file-log output.
-    let _log_guard = logging::init(&settings);
+    let _log_guard = if args.command.as_ref().is_some_and(Command::is_offline) {
+        logging::init_stderr(&settings);
+        None
+    } else {
+        logging::init(&settings)
+    };
     // First-run actions went to stderr when they happened (pre-logging);
     // repeat them through tracing so they land in the log file too.
     for message in &first_run_messages {
@@ -166,6 +249,16 @@ async fn async_main() -> anyhow::Result<()> {
     }
     env_template::warn_dotenv_diagnostics(&dotenv_diagnostics);
     settings.log_warnings();
+    for lock in db::instance_lock::lock_status().iter().filter(|lock| lock.owned) {
+        tracing::info!(
+            index_db = %lock.index_db,
+            path = %lock.path,
+            "holding index DB instance lock"
+        );
+    }
+    for held in &db_locks_held {
+        tracing::warn!("{held}; running read-only");
+    }
 
     // Policy-token HMAC key: random per boot unless [server]
     // policy_token_key pins it (policy_token.rs). Needed by the policy
@@ -190,24 +283,44 @@ async fn async_main() -> anyhow::Result<()> {
         Some(Command::Update { yes }) => {
             return update::run_update_command(crate::resources::VERSION, yes).await;
         }
-        None => {}
+        Some(Command::Migrate(db)) => return cli::migrate(db).await,
+        Some(Command::Scan(scan)) => return cli::scan(scan).await,
+        Some(Command::Pql(pql)) => return cli::pql(pql, &settings).await,
+        Some(Command::Verify(verify)) => return cli::verify(verify).await,
+        Some(Command::Doctor) => return cli::doctor(&settings).await,
+        Some(Command::Serve) | None => {}
     }
 
-    // Server path only (Setup/Inferio/Update returned above). Fire-and-forget a
+    // Server path only (every other command returned above). Fire-and-forget a
     // best-effort, throttled check for a newer release; it prints a banner if
     // one exists.
     if !args.disable_update_check && settings.server.check_for_updates {
         crate::update::spawn_startup_check(crate::resources::VERSION);
     }
 
-    let ui_upstream = proxy::Upstream::parse("ui", &settings.upstreams.ui.base_url)?;
-    let api_upstream = proxy::Upstream::parse("api", &settings.upstreams.api.base_url)?;
+    let ui_upstream = proxy::Upstream::parse(
+        "ui",
+        &settings.upstreams.ui.base_url,
+        settings.upstreams.ui.timeouts.clone(),
+    )?
+    .with_websocket(settings.upstreams.ui.websocket.clone());
+    let api_upstream = proxy::Upstream::parse(
+        "api",
+        &settings.upstreams.api.base_url,
+        settings.upstreams.api.timeouts.clone(),
+    )?
+    .with_websocket(settings.upstreams.api.websocket.clone());
     let inference_config = settings
         .upstreams
         .inference
         .first()
         .expect("inference upstream should be initialized");
-    let inference_upstream = proxy::Upstream::parse("inference", &inference_config.base_url)?;
+    let inference_upstream = proxy::Upstream::parse(
+        "inference",
+        &inference_config.base_url,
+        inference_config.timeouts.clone(),
+    )?
+    .with_websocket(inference_config.websocket.clone());
     let inference_client =
         inferio_client::InferenceApiClient::from_settings_with_metadata_cache(&settings, true)?;
     let job_endpoints = settings
@@ -221,7 +334,7 @@ async fn async_main() -> anyhow::Result<()> {
     set_job_inference_context(JobInferenceContext {
         primary: inference_client.clone(),
         pool: inference_pool,
-        embedding_cache_size: settings.search.embedding_cache_size,
+        embedding_cache_bytes: settings.search.embedding_cache_bytes(),
         loader_concurrency: settings.jobs.loader_concurrency,
         intermediate_budget_kib: u32::try_from(
             settings
@@ -243,13 +356,18 @@ async fn async_main() -> anyhow::Result<()> {
     // without persisting.
     api::search_cache::set_budget_limit_mb(settings.search.cache_size_max_mb);
     api::search_cache::set_budget_mb(settings.search.cache_size_mb);
+    api::search_slowlog::configure(
+        settings.search.slow_query_ms,
+        settings.search.slow_query_log_size,
+        settings.search.slow_query_max_bytes,
+    );
 
     let state = Arc::new(proxy::ProxyState::new(
         ui_upstream,
         api_upstream,
         inference_upstream,
         inference_client,
-        settings.search.embedding_cache_size,
+        settings.search.embedding_cache_bytes(),
         Arc::clone(&settings),
         Arc::clone(&token_key),
         shutdown_rx.clone(),
@@ -260,12 +378,21 @@ async fn async_main() -> anyhow::Result<()> {
     // When the gateway is the API server it owns the databases, so it runs
     // startup migrations like the Python server does (and, like Python,
     // skips them in readonly mode): the default databases are created if
-    // missing, then every other on-disk DB is brought up to date.
+    // missing, then every other on-disk DB is brought up to date (one that
+    // fails to migrate is logged and left as is; the others still migrate).
     // Python-created DBs are baselined, not re-migrated — see
     // db::migrations::ensure_baseline_if_needed.
     if local_api && !db::readonly_mode() {
         db::migrations::migrate_databases_on_disk(None, None).await?;
-        db::migrations::migrate_all_databases_on_disk().await?;
+        let failed = db::migrations::migrate_all_databases_on_disk()
+            .await?
+            .into_iter()
+            .filter(|result| result.error.is_some())
+            .map(|result| result.path.display().to_string())
+            .collect::<Vec<_>>();
+        if !failed.is_empty() {
+            tracing::warn!(?failed, "some databases could not be migrated and were left as is");
+        }
         // Vector-quant discrepancy check (crash/power-loss recovery and
         // first-post-upgrade convergence): metadata-only diffs are applied
         // synchronously, real data work enqueues a reconcile job. Runs in
@@ -344,7 +471,18 @@ async fn async_main() -> anyhow::Result<()> {
     let mut app = app
         .route("/docs", any(proxy::proxy_api))
         .route("/openapi.json", any(proxy::proxy_api))
+        .route(openapi::GATEWAY_SPEC_PATH, get(openapi::gateway_spec))
         .fallback(any(proxy::proxy_ui));
+    if !local_api {
+        // /docs is the Python API's own page here; the gateway's routes get
+        // a Swagger UI beside it that can switch between both specs.
+        app = app.merge(
+            SwaggerUi::new("/docs/gateway").config(utoipa_swagger_ui::Config::new([
+                utoipa_swagger_ui::Url::with_primary("Gateway", openapi::GATEWAY_SPEC_PATH, true),
+                utoipa_swagger_ui::Url::new("Python API", "/openapi.json"),
+            ])),
+        );
+    }
 
     if local_api {
         app = app
@@ -353,7 +491,8 @@ async fn async_main() -> anyhow::Result<()> {
             // Always allowed regardless of ruleset (the policy layer
             // exempts GET on this path): clients discover their policy's
             // capabilities and [policies.client] settings here.
-            .route("/api/client-config", get(api::client_config::client_config));
+            .route("/api/client-config", get(api::client_config::client_config))
+            .route("/api/diagnostics", get(api::diagnostics::diagnostics));
         app = app.route(
             "/api/relay/pairings/{relay_id}",
             get(api::relay::get_pairing).delete(api::relay::delete_pairing),
@@ -418,7 +557,9 @@ async fn async_main() -> anyhow::Result<()> {
                 )));
             app = app.merge(desktop_routes);
         }
-        let _ = jobs::continuous_scan::ensure_continuous_supervisor().await;
+        if !db::readonly_mode() {
+            let _ = jobs::continuous_scan::ensure_continuous_supervisor().await;
+        }
         app = app
             .route(
                 "/api/bookmarks/ns",
@@ -435,6 +576,10 @@ async fn async_main() -> anyhow::Result<()> {
                 "/api/bookmarks/item/{sha256}",
                 get(api::bookmarks::bookmarks_item),
             )
+            .route(
+                "/api/bookmarks/items/status",
+                post(api::bookmarks::bookmarks_items_status),
+            )
             .route(
                 "/api/bookmarks/ns/{namespace}/{sha256}",
                 get(api::bookmarks::get_bookmark)
@@ -466,10 +611,61 @@ async fn async_main() -> anyhow::Result<()> {
             )
             .route("/api/items/item/file", get(api::items::item_file))
             .route("/api/items/item/thumbnail", get(api::items::item_thumbnail))
-            .route("/api/items/item", get(api::items::item_meta))
+            .route(
+                "/api/items/item/placeholder",
+                get(api::items::item_placeholder),
+            )
+            .route("/api/items/item/frames", get(api::items::item_frame))
+            .route(
+                "/api/items/item/frames/meta",
+                get(api::items::item_frames_meta),
+            )
+            .route(
+                "/api/items/item",
+                get(api::items::item_meta).delete(api::items::purge_item),
+            )
+            .route("/api/items/stale", delete(api::items::purge_stale_items))
+            .route("/api/items/undecodable", get(api::items::undecodable_files))
             .route("/api/items/item/text", get(api::items::item_text))
-            .route("/api/items/item/tags", get(api::items::item_tags))
+            .route(
+                "/api/items/item/embeddings",
+                get(api::items::item_embeddings),
+            )
+            .route(
+                "/api/items/item/tags",
+                get(api::items::item_tags)
+                    .post(api::items::add_item_tags)
+                    .delete(api::items::remove_item_tags),
+            )
+            .route(
+                "/api/items/item/primary-file",
+                put(api::items::set_item_primary_file),
+            )
+            .route(
+                "/api/items/item/notes",
+                get(api::item_notes::get_item_note)
+                    .put(api::item_notes::set_item_note)
+                    .delete(api::item_notes::delete_item_note),
+            )
+            .route(
+                "/api/items/item/meta",
+                get(api::item_meta::get_item_meta_fields)
+                    .put(api::item_meta::set_item_meta_field)
+                    .delete(api::item_meta::delete_item_meta_field),
+            )
+            .route(
+                "/api/items/notes/export",
+                get(api::item_notes::export_item_notes),
+            )
+            .route(
+                "/api/items/notes/import",
+                post(api::item_notes::import_item_notes),
+            )
             .route("/api/items/text/any", get(api::items::texts_any))
+            .route(
+                "/api/items/item/open",
+                post(api::open::open_item_locally),
+            )
             .route(
                 "/api/open/file/{sha256}",
                 post(api::open::open_file_on_host),
@@ -484,21 +680,79 @@ async fn async_main() -> anyhow::Result<()> {
                 "/api/search/embeddings/cache",
                 get(api::search::get_search_cache).delete(api::search::clear_search_cache),
             )
+            .route(
+                "/api/search/embeddings/export",
+                post(api::search::export_embeddings),
+            )
+            .route(
+                "/api/search/export",
+                post(api::search_export::export_search_results),
+            )
+            .route(
+                "/api/search/export/status",
+                get(api::search_export::get_search_export_status),
+            )
             .route(
                 "/api/search/cache",
                 get(api::search_cache::get_result_cache)
                     .delete(api::search_cache::clear_result_cache)
                     .put(api::search_cache::resize_result_cache),
             )
+            .route(
+                "/api/search/slowlog",
+                get(api::search_slowlog::get_slowlog).delete(api::search_slowlog::clear_slowlog),
+            )
+            .route("/api/search/meta/keys", get(api::item_meta::get_meta_keys))
+            .route(
+                "/api/search/suggest",
+                get(api::search_suggest::get_search_suggestions),
+            )
             .route("/api/search/tags", get(api::search::get_tags))
             .route("/api/search/tags/top", get(api::search::get_top_tags))
+            .route(
+                "/api/search/tags/aliases",
+                get(api::search::get_tag_aliases)
+                    .put(api::search::set_tag_aliases)
+                    .delete(api::search::delete_tag_aliases),
+            )
             .route("/api/search/stats", get(api::search::get_stats))
+            .route(
+                "/api/search/stats/storage",
+                get(api::search::get_storage_stats).post(api::search::refresh_storage_stats),
+            )
+            .route(
+                "/api/search/saved",
+                get(api::saved_queries::list_saved_queries)
+                    .post(api::saved_queries::create_saved_query),
+            )
+            .route(
+                "/api/search/saved/export",
+                get(api::saved_queries::export_saved_queries),
+            )
+            .route(
+                "/api/search/saved/import",
+                post(api::saved_queries::import_saved_queries),
+            )
+            .route(
+                "/api/search/saved/{name}",
+                get(api::saved_queries::get_saved_query)
+                    .put(api::saved_queries::update_saved_query)
+                    .delete(api::saved_queries::delete_saved_query),
+            )
+            .route(
+                "/api/search/saved/{name}/run",
+                get(api::saved_queries::run_saved_query),
+            )
             .merge(SwaggerUi::new("/docs").url("/openapi.json", openapi::ApiDoc::openapi()))
             .merge(Redoc::with_url("/redoc", openapi::ApiDoc::openapi()));
         // Local API mode means the gateway owns jobs and cron. Do not run
         // the Python server's cron against the same databases — it would
         // double-schedule.
-        let _ = jobs::cron::ensure_cron_scheduler().await;
+        // A read-only gateway (configured, or another instance holds the
+        // index DB lock) leaves scheduling to the writer.
+        if !db::readonly_mode() {
+            let _ = jobs::cron::ensure_cron_scheduler().await;
+        }
         app = app
             .route(
                 "/api/jobs/queue",
@@ -513,11 +767,17 @@ async fn async_main() -> anyhow::Result<()> {
                 "/api/jobs/folders/rescan",
                 post(api::jobs::enqueue_folder_rescan),
             )
+            .route("/api/jobs/files/rescan", post(api::jobs::rescan_file))
+            .route("/api/jobs/data/debug", post(api::jobs::debug_data_extraction))
             .route(
                 "/api/jobs/folders",
                 get(api::jobs::get_folders).put(api::jobs::enqueue_update_folders),
             )
             .route("/api/jobs/cancel", post(api::jobs::cancel_current_job))
+            .route(
+                "/api/jobs/notifications/test",
+                get(api::jobs::test_notifications),
+            )
             .route(
                 "/api/jobs/folders/history",
                 get(api::jobs::get_scan_history),
@@ -526,14 +786,87 @@ async fn async_main() -> anyhow::Result<()> {
                 "/api/jobs/data/history",
                 get(api::jobs::get_extraction_history).delete(api::jobs::delete_scan_data),
             )
+            .route(
+                "/api/jobs/data/history/prune",
+                post(api::jobs::prune_extraction_history),
+            )
             .route(
                 "/api/jobs/config",
                 get(api::jobs::get_config).put(api::jobs::update_config),
             )
+            .route(
+                "/api/jobs/data/setters",
+                get(api::jobs::get_setter_models).delete(api::jobs::delete_orphaned_setters),
+            )
+            .route("/api/jobs/data/coverage", get(api::jobs::get_data_coverage))
+            // A static path, so it wins over the /api/inference nest/proxy
+            // wildcard: the cache it refreshes lives in this process.
+            .route(
+                "/api/inference/metadata/refresh",
+                post(api::jobs::refresh_inference_metadata_cache),
+            )
             .route(
                 "/api/jobs/data/setters/total",
                 get(api::jobs::get_setter_data_count),
             )
+            .route(
+                "/api/jobs/data/setters/embeddings",
+                get(api::jobs::get_setter_embeddings),
+            )
+            .route("/api/jobs/data/lineage", get(api::jobs::get_data_lineage))
+            .route(
+                "/api/jobs/data/lineage/repair",
+                post(api::jobs::enqueue_lineage_repair),
+            )
+            .route("/api/jobs/data/import/tags", post(api::jobs::import_tags))
+            .route(
+                "/api/jobs/data/tags/rethreshold",
+                post(api::jobs::rethreshold_tags),
+            )
+            .route(
+                "/api/jobs/data/text/renormalize",
+                post(api::jobs::enqueue_text_renormalize),
+            )
+            .route(
+                "/api/jobs/maintenance/verify",
+                post(api::jobs::enqueue_file_verification),
+            )
+            .route(
+                "/api/jobs/maintenance/verify/results",
+                get(api::jobs::get_file_verification_results),
+            )
+            .route(
+                "/api/jobs/maintenance/optimize",
+                post(api::jobs::enqueue_db_optimize),
+            )
+            .route(
+                "/api/jobs/reports/pql",
+                post(api::jobs::enqueue_pql_report),
+            )
+            .route(
+                "/api/jobs/maintenance/optimize/history",
+                get(api::jobs::get_db_optimize_history),
+            )
+            .route(
+                "/api/jobs/maintenance/visuals",
+                post(api::jobs::enqueue_visuals_regeneration),
+            )
+            .route(
+                "/api/jobs/maintenance/visuals/status",
+                get(api::jobs::get_visuals_status),
+            )
+            .route(
+                "/api/jobs/maintenance/visuals/storage",
+                post(api::jobs::enqueue_visuals_storage_migration),
+            )
+            .route(
+                "/api/jobs/maintenance/fts/rebuild",
+                post(api::jobs::enqueue_fts_rebuild),
+            )
+            .route(
+                "/api/jobs/maintenance/fts/status",
+                get(api::jobs::get_fts_status),
+            )
             .route("/api/jobs/quants", get(api::jobs::get_vector_quants))
             .route(
                 "/api/jobs/quants/reconcile",
@@ -557,13 +890,15 @@ async fn async_main() -> anyhow::Result<()> {
             );
     }
 
+    let warmup_state = Arc::clone(&state);
     let app = app
         .with_state(state)
         .layer(TraceLayer::new_for_http())
         .layer(policy::PolicyLayer::new(
             Arc::clone(&settings),
             Arc::clone(&token_key),
-        ));
+        ))
+        .layer(axum::middleware::from_fn(logging::request_id));
 
     // Bind every configured listener (primary + [[server.endpoints]]) before
     // serving any of them: a config that cannot fully bind fails startup as
@@ -577,6 +912,24 @@ async fn async_main() -> anyhow::Result<()> {
         listeners.push((name, listener));
     }
 
+    // Like the warmup below, the diagnostics wait for the listeners: with
+    // local inference their inference probe goes through this gateway.
+    if settings.server.startup_diagnostics {
+        let settings = Arc::clone(&settings);
+        tokio::spawn(async move {
+            diagnostics::log_startup_diagnostics(&settings).await;
+        });
+    }
+
+    // Search warmup waits for the listeners so it never delays serving; a
+    // failed step only lands in the report (see api::search_warmup).
+    if local_api {
+        let warmup = settings.search.warmup.clone();
+        tokio::spawn(async move {
+            api::search_warmup::run_search_warmup(&warmup_state, &warmup, None, None).await;
+        });
+    }
+
     // Cleanup task and HTTP drain both must finish before main returns;
     // shutdown.rs enforces the deadline.
     let inferio_manager = inferio_state
@@ -627,6 +980,74 @@ async fn api_not_found(uri: axum::http::Uri) -> api_error::ApiError {
 mod route_tests {
     use super::*;
 
+    /// `(path, methods)` of every `.route(...)` call in `source`. A built
+    /// Router cannot list its routes, so the coverage test reads them from
+    /// this file instead; catch-all proxy routes have no methods.
+    fn registered_routes(source: &str) -> Vec<(String, Vec<&'static str>)> {
+        let mut routes = Vec::new();
+        for call in source.split(".route(").skip(1) {
+            let Some(path) = call
+                .trim_start()
+                .strip_prefix('"')
+                .and_then(|rest| rest.split('"').next())
+            else {
+                continue;
+            };
+            if !path.starts_with('/') {
+                continue;
+            }
+            let mut depth = 1;
+            let end = call
+                .char_indices()
+                .find(|&(_, c)| {
+                    match c {
+                        '(' => depth += 1,
+                        ')' => depth -= 1,
+                        _ => {}
+                    }
+                    depth == 0
+                })
+                .map_or(call.len(), |(index, _)| index);
+            let args = &call[..end];
+            let methods = ["get", "post", "put", "patch", "delete"]
+                .into_iter()
+                .filter(|method| {
+                    args.match_indices(&format!("{method}(")).any(|(index, _)| {
+                        !args[..index].ends_with(|c: char| c.is_alphanumeric() || c == '_')
+                    })
+                })
+                .collect();
+            routes.push((path.to_string(), methods));
+        }
+        routes
+    }
+
+    /// Every locally served API route has a spec entry for each method it
+    /// registers, so the gateway spec cannot silently fall behind the router.
+    #[test]
+    fn local_api_routes_are_documented() {
+        let spec = serde_json::to_value(openapi::ApiDoc::openapi()).unwrap();
+        let routes = registered_routes(include_str!("main.rs"));
+        let mut checked = 0;
+        for (path, methods) in &routes {
+            if !path.starts_with("/api/") || path.contains("{*") {
+                continue;
+            }
+            for method in methods {
+                assert!(
+                    spec["paths"][path.as_str()][*method].is_object(),
+                    "{} {path} is routed but missing from the OpenAPI spec",
+                    method.to_uppercase()
+                );
+                checked += 1;
+            }
+        }
+        assert!(
+            checked > 100,
+            "only {checked} routes found; did the parser break?"
+        );
+    }
+
     #[test]
     fn relay_pairing_route_shapes_do_not_conflict() {
         let _: Router<Arc<proxy::ProxyState>> = Router::new()
@@ -670,6 +1091,7 @@ async fn inferio_main(
     let app = inferio::http::standalone_router(Arc::clone(&state))
         .layer(TraceLayer::new_for_http())
         .layer(policy::PolicyLayer::new(Arc::clone(&settings), token_key))
+        .layer(axum::middleware::from_fn(logging::request_id))
         .layer(axum::Extension(policy::ListenerEndpoint(Arc::from(
             config::PRIMARY_ENDPOINT,
         ))));
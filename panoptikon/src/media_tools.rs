@@ -11,10 +11,23 @@
 //! Resolution runs once per process, on first use, and is cached: the
 //! callers are blocking job helpers, so the python probe (and a possible
 //! first-use download) never blocks the async runtime.
+//!
+//! Every invocation goes through [`run`], which bounds it: a corrupt file
+//! can make either tool hang or stream without end, and one stuck child
+//! would otherwise hold a scan or extraction worker forever. Past
+//! `[jobs] ffmpeg_timeout_secs`/`ffprobe_timeout_secs` or
+//! `media_max_output_mb` of stdout the child is killed and reaped.
 
 use std::ffi::OsStr;
+use std::fmt;
+use std::io::Read;
 use std::path::{Path, PathBuf};
-use std::sync::OnceLock;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::api_error::ApiError;
 
 /// Python snippet printing the ffmpeg and ffprobe paths on two lines,
 /// downloading the binaries first if needed. Shared with the setup
@@ -126,6 +139,212 @@ fn venv_static_ffmpeg(python: &Path) -> Option<(PathBuf, PathBuf)> {
     Some((ffmpeg, ffprobe))
 }
 
+/// How often [`run`] checks a running child for exit, timeout or overflow.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+/// Bytes of stderr kept for error reports; older output is dropped.
+const STDERR_KEEP_BYTES: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MediaTool {
+    Ffmpeg,
+    Ffprobe,
+}
+
+impl MediaTool {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Self::Ffmpeg => "ffmpeg",
+            Self::Ffprobe => "ffprobe",
+        }
+    }
+
+    /// A command for the resolved executable; run it with [`run`].
+    pub(crate) fn command(self) -> Command {
+        Command::new(match self {
+            Self::Ffmpeg => ffmpeg(),
+            Self::Ffprobe => ffprobe(),
+        })
+    }
+
+    fn timeout(self) -> Option<Duration> {
+        let runtime = crate::config::runtime();
+        let secs = match self {
+            Self::Ffmpeg => runtime.ffmpeg_timeout_secs,
+            Self::Ffprobe => runtime.ffprobe_timeout_secs,
+        };
+        (secs > 0).then(|| Duration::from_secs(secs))
+    }
+}
+
+/// Why a media tool run produced no usable output.
+#[derive(Debug)]
+pub(crate) enum MediaToolError {
+    /// The executable could not be started (or waited on).
+    Spawn {
+        tool: &'static str,
+        error: std::io::Error,
+    },
+    /// Still running at the timeout; killed and reaped.
+    TimedOut {
+        tool: &'static str,
+        timeout: Duration,
+        stderr: String,
+    },
+    /// Wrote more than the stdout limit; killed and reaped.
+    OutputTooLarge { tool: &'static str, limit: u64 },
+    /// Exited unsuccessfully.
+    Failed { tool: &'static str, stderr: String },
+}
+
+impl MediaToolError {
+    pub(crate) fn is_timeout(&self) -> bool {
+        matches!(self, Self::TimedOut { .. })
+    }
+}
+
+impl fmt::Display for MediaToolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Spawn { tool, error } => write!(f, "{tool} could not be run: {error}"),
+            Self::TimedOut {
+                tool,
+                timeout,
+                stderr,
+            } => {
+                write!(f, "{tool} timed out after {}s", timeout.as_secs())?;
+                if !stderr.is_empty() {
+                    write!(f, ": {stderr}")?;
+                }
+                Ok(())
+            }
+            Self::OutputTooLarge { tool, limit } => {
+                write!(f, "{tool} output exceeded {limit} bytes")
+            }
+            Self::Failed { tool, stderr } => write!(f, "{tool} failed: {stderr}"),
+        }
+    }
+}
+
+impl From<MediaToolError> for ApiError {
+    fn from(err: MediaToolError) -> Self {
+        if err.is_timeout() {
+            ApiError::media_timeout(err.to_string())
+        } else {
+            ApiError::internal(err.to_string())
+        }
+    }
+}
+
+/// Runs `command` (built by [`MediaTool::command`]) to completion under the
+/// configured limits and returns its stdout. stdin is closed and stdout and
+/// stderr are captured, so callers must not set them.
+pub(crate) fn run(tool: MediaTool, command: &mut Command) -> Result<Vec<u8>, MediaToolError> {
+    let max_stdout = crate::config::runtime()
+        .media_max_output_mb
+        .saturating_mul(1024 * 1024);
+    run_limited(tool.name(), command, tool.timeout(), max_stdout)
+}
+
+/// [`run`] with explicit limits; `max_stdout` 0 means unlimited.
+fn run_limited(
+    tool: &'static str,
+    command: &mut Command,
+    timeout: Option<Duration>,
+    max_stdout: u64,
+) -> Result<Vec<u8>, MediaToolError> {
+    let spawn_error = |error| MediaToolError::Spawn { tool, error };
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(spawn_error)?;
+
+    // Both pipes are drained on their own threads: a child blocked writing
+    // to a full pipe would otherwise never exit.
+    let overflowed = Arc::new(AtomicBool::new(false));
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    let stdout_reader = {
+        let overflowed = overflowed.clone();
+        std::thread::spawn(move || {
+            let mut captured = Vec::new();
+            let mut buf = [0u8; 64 * 1024];
+            loop {
+                match stdout.read(&mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(read) => captured.extend_from_slice(&buf[..read]),
+                }
+                if max_stdout > 0 && captured.len() as u64 > max_stdout {
+                    // Stop reading; the poll loop kills the child.
+                    overflowed.store(true, Ordering::Relaxed);
+                    break;
+                }
+            }
+            captured
+        })
+    };
+    let mut stderr = child.stderr.take().expect("stderr is piped");
+    let stderr_reader = std::thread::spawn(move || {
+        let mut kept = Vec::new();
+        let mut buf = [0u8; 16 * 1024];
+        loop {
+            match stderr.read(&mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(read) => kept.extend_from_slice(&buf[..read]),
+            }
+            if kept.len() > STDERR_KEEP_BYTES {
+                kept.drain(..kept.len() - STDERR_KEEP_BYTES);
+            }
+        }
+        kept
+    });
+
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let status = loop {
+        if let Some(status) = child.try_wait().map_err(spawn_error)? {
+            break Some(status);
+        }
+        let timed_out = deadline.is_some_and(|deadline| Instant::now() >= deadline);
+        if timed_out || overflowed.load(Ordering::Relaxed) {
+            // kill fails only if the child already exited; wait reaps it
+            // either way so no zombie is left behind.
+            let _ = child.kill();
+            let _ = child.wait();
+            break None;
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    };
+
+    // The child is gone, so its pipes are closed and the readers finish.
+    let stdout = stdout_reader.join().unwrap_or_default();
+    let stderr = crate::jobs::files::stderr_tail(&stderr_reader.join().unwrap_or_default());
+    // The reader dropping its end of the pipe may end the child (SIGPIPE)
+    // before the poll loop sees the overflow.
+    if overflowed.load(Ordering::Relaxed) {
+        return Err(MediaToolError::OutputTooLarge {
+            tool,
+            limit: max_stdout,
+        });
+    }
+    match status {
+        Some(status) if status.success() => Ok(stdout),
+        Some(_) => Err(MediaToolError::Failed { tool, stderr }),
+        None => {
+            let timeout = timeout.unwrap_or_default();
+            tracing::warn!(
+                tool,
+                timeout_secs = timeout.as_secs(),
+                "media tool timed out"
+            );
+            Err(MediaToolError::TimedOut {
+                tool,
+                timeout,
+                stderr,
+            })
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -149,4 +368,37 @@ mod tests {
         assert_eq!(ffmpeg, PathBuf::from("ffmpeg"));
         assert_eq!(ffprobe, PathBuf::from("/opt/ffprobe"));
     }
+
+    #[cfg(unix)]
+    #[test]
+    fn hung_child_is_killed_at_the_timeout() {
+        let mut command = Command::new("sleep");
+        command.arg("30");
+        let started = Instant::now();
+        let err =
+            run_limited("sleep", &mut command, Some(Duration::from_millis(200)), 0).unwrap_err();
+        assert!(err.is_timeout(), "{err}");
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn stdout_beyond_the_limit_fails_and_stderr_is_reported() {
+        let mut command = Command::new("yes");
+        let err = run_limited("yes", &mut command, None, 1024).unwrap_err();
+        assert!(matches!(
+            err,
+            MediaToolError::OutputTooLarge { limit: 1024, .. }
+        ));
+
+        let mut command = Command::new("sh");
+        command.args(["-c", "echo partial; echo broken input >&2; exit 1"]);
+        let err = run_limited("sh", &mut command, None, 0).unwrap_err();
+        assert_eq!(err.to_string(), "sh failed: broken input");
+
+        let mut command = Command::new("sh");
+        command.args(["-c", "echo ok"]);
+        let stdout = run_limited("sh", &mut command, Some(Duration::from_secs(10)), 0).unwrap();
+        assert_eq!(stdout, b"ok\n");
+    }
 }
@@ -0,0 +1,161 @@
+//! MessagePack helpers shared by the inferio worker protocol and the
+//! msgpack-encoded search API. Values travel through `serde_json::Value` on
+//! both ends, so anything that serializes to JSON has exactly one msgpack
+//! form and the two encodings cannot disagree on structure.
+
+use anyhow::{Context, Result, bail};
+use rmpv::Value;
+use serde_json::Value as JsonValue;
+
+/// Media type for msgpack request and response bodies.
+pub(crate) const CONTENT_TYPE: &str = "application/msgpack";
+
+/// Whether a Content-Type or Accept entry names msgpack. The unregistered
+/// `application/x-msgpack` spelling is still common in client libraries.
+pub(crate) fn is_msgpack_media_type(media: &str) -> bool {
+    media.split(';').next().is_some_and(|kind| {
+        let kind = kind.trim();
+        kind.eq_ignore_ascii_case(CONTENT_TYPE)
+            || kind.eq_ignore_ascii_case("application/x-msgpack")
+    })
+}
+
+/// Encodes a JSON value as one msgpack value.
+pub(crate) fn encode_json(value: &JsonValue) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    rmpv::encode::write_value(&mut bytes, &json_to_rmpv(value))
+        .context("failed to encode msgpack value")?;
+    Ok(bytes)
+}
+
+/// Decodes a body holding exactly one msgpack value into JSON.
+pub(crate) fn decode_json(mut bytes: &[u8]) -> Result<JsonValue> {
+    let value = rmpv::decode::read_value(&mut bytes).context("malformed msgpack body")?;
+    if !bytes.is_empty() {
+        bail!("{} trailing bytes after the msgpack value", bytes.len());
+    }
+    rmpv_to_json(&value)
+}
+
+/// JSON → msgpack value. Straightforward except numbers: serde_json numbers
+/// are exactly one of i64/u64/f64, and each maps to the corresponding
+/// msgpack representation so ints stay ints end-to-end.
+pub(crate) fn json_to_rmpv(value: &JsonValue) -> Value {
+    match value {
+        JsonValue::Null => Value::Nil,
+        JsonValue::Bool(b) => Value::Boolean(*b),
+        JsonValue::Number(n) => {
+            if let Some(i) = n.as_i64() {
+                Value::from(i)
+            } else if let Some(u) = n.as_u64() {
+                Value::from(u)
+            } else {
+                // as_f64 is total for serde_json numbers that are not ints.
+                Value::F64(n.as_f64().unwrap_or(f64::NAN))
+            }
+        }
+        JsonValue::String(s) => Value::from(s.as_str()),
+        JsonValue::Array(items) => Value::Array(items.iter().map(json_to_rmpv).collect()),
+        JsonValue::Object(map) => Value::Map(
+            map.iter()
+                .map(|(key, value)| (Value::from(key.as_str()), json_to_rmpv(value)))
+                .collect(),
+        ),
+    }
+}
+
+/// msgpack → JSON value. Non-string map keys are coerced via their msgpack
+/// display form (Python dict keys from impls and client request maps are
+/// strings in practice). Binary/ext values have no JSON form and fail the
+/// conversion; callers that accept top-level bin (the inferio worker's
+/// `WorkerOutput::Bytes`) handle it before calling this.
+pub(crate) fn rmpv_to_json(value: &Value) -> Result<JsonValue> {
+    Ok(match value {
+        Value::Nil => JsonValue::Null,
+        Value::Boolean(b) => JsonValue::Bool(*b),
+        Value::Integer(i) => {
+            if let Some(v) = i.as_i64() {
+                JsonValue::from(v)
+            } else if let Some(v) = i.as_u64() {
+                JsonValue::from(v)
+            } else {
+                bail!("msgpack integer {i} fits neither i64 nor u64")
+            }
+        }
+        Value::F32(f) => serde_json::Number::from_f64(f64::from(*f))
+            .map(JsonValue::Number)
+            .with_context(|| format!("non-finite float {f} has no JSON form"))?,
+        Value::F64(f) => serde_json::Number::from_f64(*f)
+            .map(JsonValue::Number)
+            .with_context(|| format!("non-finite float {f} has no JSON form"))?,
+        Value::String(s) => {
+            JsonValue::String(s.as_str().context("non-UTF-8 msgpack string")?.to_owned())
+        }
+        Value::Binary(_) => bail!("binary data nested inside a JSON-like output has no JSON form"),
+        Value::Array(items) => {
+            JsonValue::Array(items.iter().map(rmpv_to_json).collect::<Result<_>>()?)
+        }
+        Value::Map(entries) => {
+            let mut map = serde_json::Map::with_capacity(entries.len());
+            for (key, value) in entries {
+                let key = match key.as_str() {
+                    Some(s) => s.to_owned(),
+                    None => key.to_string(),
+                };
+                map.insert(key, rmpv_to_json(value)?);
+            }
+            JsonValue::Object(map)
+        }
+        Value::Ext(tag, _) => bail!("msgpack ext type {tag} has no JSON form"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Non-finite floats and binary/ext nested inside a JSON-like output
+    /// have no JSON form: rmpv_to_json must report an error (never silently
+    /// coerce — the Python side would equally fail to JSON-encode them),
+    /// while ordinary finite floats convert cleanly.
+    #[test]
+    fn rmpv_to_json_rejects_nonfinite_and_nested_binary() {
+        assert!(rmpv_to_json(&Value::F64(f64::NAN)).is_err());
+        assert!(rmpv_to_json(&Value::F64(f64::INFINITY)).is_err());
+        assert!(rmpv_to_json(&Value::F32(f32::NEG_INFINITY)).is_err());
+        assert!(rmpv_to_json(&Value::Array(vec![Value::Binary(vec![1, 2])])).is_err());
+        assert!(rmpv_to_json(&Value::Ext(7, vec![0])).is_err());
+        assert_eq!(rmpv_to_json(&Value::F64(1.5)).unwrap(), json!(1.5));
+    }
+
+    // Ensures encode/decode round-trip JSON exactly, keeping ints as ints
+    // and map order, and that trailing garbage is rejected.
+    #[test]
+    fn encode_decode_round_trips_json() {
+        let value = json!({"b": 1, "a": [-2, 2.5, "ü", null, true], "big": u64::MAX});
+        let bytes = encode_json(&value).unwrap();
+        assert_eq!(decode_json(&bytes).unwrap(), value);
+        let keys: Vec<_> = decode_json(&bytes)
+            .unwrap()
+            .as_object()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect();
+        assert_eq!(keys, ["b", "a", "big"]);
+
+        let mut padded = bytes.clone();
+        padded.push(0xc0);
+        assert!(decode_json(&padded).is_err());
+        assert!(decode_json(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    // Ensures media-type matching ignores case and parameters.
+    #[test]
+    fn recognizes_msgpack_media_types() {
+        assert!(is_msgpack_media_type("application/msgpack"));
+        assert!(is_msgpack_media_type("Application/X-MsgPack; q=0.9"));
+        assert!(!is_msgpack_media_type("application/json"));
+    }
+}
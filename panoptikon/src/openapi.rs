@@ -1,3 +1,5 @@
+use axum::Json;
+use std::sync::OnceLock;
 use utoipa::Modify;
 use utoipa::OpenApi;
 use utoipa::openapi::schema::{ObjectBuilder, Schema, SchemaType};
@@ -26,36 +28,99 @@ impl Modify for JsonValueSchema {
         crate::api::search::search_pql_build,
         crate::api::search::get_search_cache,
         crate::api::search::clear_search_cache,
+        crate::api::search::export_embeddings,
+        crate::api::search_export::export_search_results,
+        crate::api::search_export::get_search_export_status,
         crate::api::search_cache::get_result_cache,
         crate::api::search_cache::clear_result_cache,
         crate::api::search_cache::resize_result_cache,
+        crate::api::search_slowlog::get_slowlog,
+        crate::api::search_slowlog::clear_slowlog,
+        crate::api::search_suggest::get_search_suggestions,
         crate::api::search::get_tags,
         crate::api::search::get_top_tags,
+        crate::api::search::get_tag_aliases,
+        crate::api::search::set_tag_aliases,
+        crate::api::search::delete_tag_aliases,
         crate::api::search::get_stats,
+        crate::api::search::get_storage_stats,
+        crate::api::search::refresh_storage_stats,
+        crate::api::saved_queries::list_saved_queries,
+        crate::api::saved_queries::create_saved_query,
+        crate::api::saved_queries::get_saved_query,
+        crate::api::saved_queries::update_saved_query,
+        crate::api::saved_queries::delete_saved_query,
+        crate::api::saved_queries::run_saved_query,
+        crate::api::saved_queries::export_saved_queries,
+        crate::api::saved_queries::import_saved_queries,
         crate::api::items::item_meta,
         crate::api::items::item_file,
         crate::api::items::item_thumbnail,
+        crate::api::items::item_placeholder,
+        crate::api::items::item_frame,
+        crate::api::items::item_frames_meta,
         crate::api::items::item_text,
+        crate::api::items::item_embeddings,
         crate::api::items::item_tags,
+        crate::api::items::add_item_tags,
+        crate::api::items::remove_item_tags,
+        crate::api::items::set_item_primary_file,
+        crate::api::items::purge_item,
+        crate::api::items::purge_stale_items,
+        crate::api::items::undecodable_files,
+        crate::api::item_meta::get_item_meta_fields,
+        crate::api::item_meta::set_item_meta_field,
+        crate::api::item_meta::delete_item_meta_field,
+        crate::api::item_meta::get_meta_keys,
+        crate::api::item_notes::get_item_note,
+        crate::api::item_notes::set_item_note,
+        crate::api::item_notes::delete_item_note,
+        crate::api::item_notes::export_item_notes,
+        crate::api::item_notes::import_item_notes,
         crate::api::items::texts_any,
         crate::api::open::open_file_on_host,
         crate::api::open::show_in_file_manager,
+        crate::api::open::open_item_locally,
         crate::api::jobs::queue_status,
         crate::api::jobs::enqueue_data_extraction,
         crate::api::jobs::enqueue_delete_extracted_data,
         crate::api::jobs::enqueue_folder_rescan,
+        crate::api::jobs::rescan_file,
+        crate::api::jobs::debug_data_extraction,
         crate::api::jobs::enqueue_update_folders,
         crate::api::jobs::cancel_queued,
         crate::api::jobs::cancel_current_job,
+        crate::api::jobs::test_notifications,
         crate::api::jobs::get_folders,
         crate::api::jobs::get_scan_history,
         crate::api::jobs::delete_scan_data,
         crate::api::jobs::get_extraction_history,
+        crate::api::jobs::prune_extraction_history,
         crate::api::jobs::update_config,
         crate::api::jobs::get_config,
         crate::api::jobs::get_setter_data_count,
+        crate::api::jobs::get_setter_models,
+        crate::api::jobs::get_setter_embeddings,
+        crate::api::jobs::get_data_lineage,
+        crate::api::jobs::enqueue_lineage_repair,
+        crate::api::jobs::get_data_coverage,
+        crate::api::jobs::delete_orphaned_setters,
+        crate::api::jobs::refresh_inference_metadata_cache,
         crate::api::jobs::get_vector_quants,
         crate::api::jobs::enqueue_vector_quant_reconcile,
+        crate::api::jobs::enqueue_text_renormalize,
+        crate::api::jobs::import_tags,
+        crate::api::jobs::rethreshold_tags,
+        crate::api::jobs::enqueue_file_verification,
+        crate::api::jobs::get_file_verification_results,
+        crate::api::jobs::enqueue_db_optimize,
+        crate::api::jobs::enqueue_pql_report,
+        crate::api::jobs::get_db_optimize_history,
+        crate::api::jobs::enqueue_visuals_regeneration,
+        crate::api::jobs::enqueue_visuals_storage_migration,
+        crate::api::jobs::get_visuals_status,
+        crate::api::jobs::enqueue_fts_rebuild,
+        crate::api::jobs::get_fts_status,
         crate::api::jobs::rebuild_vector_quant_pair,
         crate::api::jobs::manual_trigger_cronjob,
         crate::api::jobs::get_cronjob_schedule,
@@ -69,6 +134,7 @@ impl Modify for JsonValueSchema {
         crate::api::bookmarks::add_bookmark_by_sha256,
         crate::api::bookmarks::delete_bookmark_by_sha256,
         crate::api::bookmarks::bookmarks_item,
+        crate::api::bookmarks::bookmarks_items_status,
         crate::api::pinboards::list_pinboards,
         crate::api::pinboards::create_pinboard,
         crate::api::pinboards::get_pinboard,
@@ -81,6 +147,13 @@ impl Modify for JsonValueSchema {
         crate::api::db::db_info,
         crate::api::db::db_create,
         crate::api::client_config::client_config,
+        crate::api::diagnostics::diagnostics,
+        crate::api::relay::get_pairing,
+        crate::api::relay::delete_pairing,
+        crate::api::relay::get_pairing_operation,
+        crate::api::relay::begin_pairing_operation,
+        crate::api::relay::commit_pairing_operation,
+        crate::api::relay::cancel_pairing_operation,
         crate::api::desktop::setup_status,
         crate::api::desktop::validate_setup_folders,
         crate::api::desktop::validate_setup_continuous_folders,
@@ -102,47 +175,168 @@ impl Modify for JsonValueSchema {
             crate::api::search_cache::SearchCacheDbGroup,
             crate::api::search_cache::SearchCacheEntryInfo,
             crate::api::search_cache::SearchCacheResize,
+            crate::api::search_slowlog::SlowQueryLog,
+            crate::api::search_slowlog::SlowQueryEntry,
             crate::api::search::CompiledQuery,
             crate::api::search::PqlBuildResponse,
             crate::api::search::SearchResult,
             crate::api::search::FileSearchResponse,
+            crate::api::search::SkippedIndexDb,
             crate::api::search::TagSearchResults,
+            crate::api::search::TagSearchSource,
+            crate::api::search_suggest::SearchSuggestions,
+            crate::api::search_suggest::SearchSuggestion,
+            crate::api::search_suggest::SuggestionKind,
+            crate::api::search::TagAliasesResponse,
+            crate::api::search::TagAliasDeleteResponse,
+            crate::db::tag_aliases::TagAliasGroup,
             crate::api::search::TagFrequency,
             crate::api::search::TagStats,
             crate::api::search::FileStats,
             crate::api::search::ExtractedTextStats,
             crate::api::search::SearchStats,
+            crate::db::storage_stats::StorageStats,
+            crate::db::storage_stats::SetterStorage,
+            crate::db::storage_stats::DataSize,
+            crate::db::storage_stats::VisualStorage,
+            crate::db::storage_stats::DbFileSizes,
             crate::api::items::ItemMetadataResponse,
             crate::api::items::ItemRecordResponse,
             crate::api::items::FileRecordResponse,
             crate::api::items::TextResponse,
             crate::api::items::TagResponse,
+            crate::api::items::FrameTags,
+            crate::api::items::ManualTagsRequest,
+            crate::api::items::ManualTagsResponse,
+            crate::api::items::PrimaryFileRequest,
+            crate::api::items::ItemPurgeResponse,
+            crate::api::items::StaleItemPurgeResponse,
+            crate::api::items::UndecodableFilesResponse,
+            crate::db::items::UndecodableFile,
+            crate::api::item_meta::ItemMetaRequest,
+            crate::api::item_meta::ItemMetaField,
+            crate::api::item_meta::ItemMetaResponse,
+            crate::api::item_meta::ItemMetaDeleteResponse,
+            crate::api::item_meta::MetaKeyEntry,
+            crate::api::item_meta::MetaKeysResponse,
+            crate::db::item_meta::MetaValue,
+            crate::api::item_notes::ItemNoteRequest,
+            crate::api::item_notes::ItemNoteResponse,
+            crate::api::item_notes::ItemNoteDeleteResponse,
+            crate::api::item_notes::ItemNotesExport,
+            crate::api::item_notes::ItemNoteExportEntry,
+            crate::api::item_notes::ItemNotesImportResponse,
+            crate::db::item_purge::ItemPurgeCounts,
+            crate::db::manual_tags::ManualTag,
+            crate::api::items::FramesMetaResponse,
+            crate::api::items::EmbeddingFormat,
+            crate::api::items::EmbeddingVector,
+            crate::api::items::ItemEmbedding,
+            crate::api::items::ItemEmbeddingsResponse,
+            crate::api::search::EmbeddingExportRequest,
+            crate::api::search::EmbeddingExportRow,
+            crate::api::search_export::SearchExportRequest,
+            crate::api::search_export::SearchExportStatusResponse,
+            crate::jobs::search_export::ExportMode,
+            crate::jobs::search_export::ExportFailure,
+            crate::jobs::search_export::SearchExportProgress,
+            crate::db::storage::FrameInfo,
             crate::api::open::OpenResponse,
+            crate::api::open::OpenMode,
             crate::api::jobs::QueueCancelResponse,
             crate::api::jobs::CancelResponse,
             crate::api::jobs::FoldersResponse,
             crate::api::jobs::SetterDataStats,
+            crate::api::jobs::SetterModelInfo,
+            crate::api::jobs::SetterModelsResponse,
+            crate::api::jobs::InferenceMetadataRefreshResponse,
+            crate::api::jobs::SetterEmbeddingsResponse,
+            crate::api::jobs::LineageResponse,
+            crate::db::lineage::DanglingLineage,
+            crate::db::lineage::LineageRepairMode,
+            crate::jobs::lineage_repair::LineageRepairReport,
+            crate::jobs::extraction::ExtractionMemory,
+            crate::jobs::extraction::ExtractionEta,
+            crate::jobs::extraction::EtaConfidence,
+            crate::db::extraction_log::SetterEmbeddingStats,
+            crate::jobs::data_coverage::CoverageReport,
+            crate::jobs::data_coverage::ModelCoverage,
+            crate::api::jobs::DeletedSettersResponse,
+            crate::api::jobs::SystemConfigResponse,
+            crate::jobs::filter_validation::FilterValidation,
             crate::api::jobs::CronJobResponse,
             crate::api::jobs::CronScheduleResponse,
             crate::api::jobs::ContinuousScanMode,
             crate::api::jobs::ContinuousScanStatusResponse,
+            crate::jobs::notifications::NotificationTestResponse,
+            crate::jobs::notifications::DeliveryOutcome,
             crate::jobs::queue::JobModel,
             crate::jobs::queue::JobOutcomeModel,
             crate::jobs::queue::JobOutcomeStatus,
             crate::jobs::queue::QueueStatusModel,
+            crate::db::index_writer::IndexWriterStatus,
             crate::jobs::queue::JobType,
             crate::db::file_scans::FileScanRecord,
+            crate::api::jobs::FileRescanRequest,
+            crate::api::jobs::FileRescanResponse,
+            crate::api::jobs::FolderUpdateResponse,
+            crate::api::jobs::FolderUpdatePreviewResponse,
+            crate::db::folders::FolderRemovalPreview,
+            crate::db::folders::FolderRemovalImpact,
+            crate::db::folders::FolderRemovalReason,
+            crate::api::jobs::HistoryPruneResponse,
+            crate::jobs::file_rescan::FileRescanOutcome,
+            crate::jobs::file_rescan::FileRescanStatus,
+            crate::jobs::extraction::ExtractionDebugRequest,
+            crate::jobs::extraction::ExtractionDebugResponse,
+            crate::jobs::extraction::ExtractionDebugRow,
+            crate::jobs::extraction::ExtractionDebugInput,
+            crate::jobs::extraction::ExtractionDebugFile,
+            crate::jobs::extraction::ExtractionDebugOutput,
             crate::db::extraction_log::LogRecord,
+            crate::db::extraction_log::ExtractionParameters,
+            crate::db::extraction_log::ExtractionModelSnapshot,
+            crate::db::extraction_log::ExtractionFilterCounts,
+            crate::db::extraction_log::JobFilterExclusion,
             crate::db::system_config::SystemConfig,
             crate::db::system_config::CronJob,
             crate::db::system_config::JobSettings,
             crate::db::system_config::VectorQuantsConfig,
             crate::db::system_config::VectorQuantProfileConfig,
+            crate::db::system_config::TextNormalizationConfig,
+            crate::db::system_config::ExtractionLogRetention,
+            crate::db::system_config::FrameRetention,
+            crate::db::system_config::DbMaintenanceConfig,
+            crate::db::system_config::JobWindowConfig,
+            crate::db::system_config::PqlReportSchedule,
+            crate::jobs::pql_report::PqlReportOptions,
+            crate::db::system_config::VacuumMode,
+            crate::db::system_config::FolderScanSettings,
+            crate::db::system_config::IoProfile,
             crate::db::vector_quants::VectorQuantStatus,
             crate::db::vector_quants::VectorQuantProfileStatus,
             crate::db::vector_quants::VectorQuantSetterStatus,
             crate::api::jobs::VectorQuantActionResponse,
             crate::api::jobs::VectorQuantRebuildRequest,
+            crate::api::jobs::TextRenormalizeResponse,
+            crate::jobs::tag_import::TagImportReport,
+            crate::db::tag_import::TagImportCounts,
+            crate::api::jobs::TagRethresholdRequest,
+            crate::jobs::tag_rethreshold::TagRethresholdReport,
+            crate::db::tag_rethreshold::TagRethresholdCounts,
+            crate::api::jobs::VerifyResultsResponse,
+            crate::db::file_verification::VerificationRunRecord,
+            crate::db::file_verification::VerificationResult,
+            crate::db::db_maintenance::DbMaintenanceRun,
+            crate::api::jobs::VisualsStatusResponse,
+            crate::api::jobs::VisualsToMigrate,
+            crate::db::storage::OutdatedVisualsCount,
+            crate::jobs::visuals_regeneration::VisualsRegenerationProgress,
+            crate::api::jobs::FtsStatusResponse,
+            crate::jobs::fts_rebuild::FtsRebuildReport,
+            crate::jobs::fts_rebuild::FtsTarget,
+            crate::db::fts::FtsRepair,
+            crate::db::fts::FtsTable,
             crate::db::items::ExtractedTextRecord,
             crate::db::items::ItemIdentifierType,
             crate::api::bookmarks::BookmarkNamespaces,
@@ -151,6 +345,8 @@ impl Modify for JsonValueSchema {
             crate::api::bookmarks::FileSearchResult,
             crate::api::bookmarks::ExistingBookmarkMetadata,
             crate::api::bookmarks::ItemBookmarks,
+            crate::api::bookmarks::BookmarkStatusRequest,
+            crate::api::bookmarks::BookmarkStatus,
             crate::api::bookmarks::BookmarkMetadata,
             crate::api::bookmarks::MessageResult,
             crate::api::bookmarks::Items,
@@ -167,11 +363,27 @@ impl Modify for JsonValueSchema {
             crate::api::pinboards::PinboardDetailResponse,
             crate::api::pinboards::PinboardVersionsResponse,
             crate::api::pinboards::PinboardDeleteResponse,
+            crate::api::saved_queries::SavedQueryRequest,
+            crate::api::saved_queries::SavedQueryResponse,
+            crate::api::saved_queries::SavedQueryListResponse,
+            crate::api::saved_queries::SavedQueryDeleteResponse,
+            crate::api::saved_queries::SavedQueryExport,
+            crate::api::saved_queries::SavedQueryExportEntry,
+            crate::api::saved_queries::SavedQueryImportResponse,
             crate::policy::DbInfo,
             crate::policy::SingleDbInfo,
+            crate::policy::DbStatus,
+            crate::policy::DbStatusError,
+            crate::policy::DbErrorCode,
             crate::api::db::DbCreateResponse,
             crate::api::client_config::ClientConfigResponse,
+            crate::api::relay::CommitPairing,
+            crate::api::relay::PairingResponse,
+            crate::api::relay::PairingOperationResponse,
             crate::api::client_config::ClientCapabilities,
+            crate::diagnostics::DiagnosticsReport,
+            crate::diagnostics::DiagnosticCheck,
+            crate::diagnostics::CheckStatus,
             crate::api::desktop::DesktopSetupStatus,
             crate::api::desktop::DesktopFolderSelection,
             crate::api::desktop::DesktopContinuousScanSelection,
@@ -215,9 +427,16 @@ impl Modify for JsonValueSchema {
             crate::pql::model::TagsArgs,
             crate::pql::model::InBookmarks,
             crate::pql::model::InBookmarksArgs,
+            crate::pql::model::BookmarkRankBy,
+            crate::pql::model::MatchMeta,
+            crate::pql::model::MatchMetaArgs,
+            crate::pql::model::MatchNote,
+            crate::pql::model::MatchNoteArgs,
             crate::pql::model::ProcessedBy,
             crate::pql::model::HasUnprocessedData,
             crate::pql::model::DerivedDataArgs,
+            crate::pql::model::InFolder,
+            crate::pql::model::InFolderArgs,
             crate::pql::model::SemanticTextSearch,
             crate::pql::model::SemanticTextArgs,
             crate::pql::model::SemanticImageSearch,
@@ -227,7 +446,9 @@ impl Modify for JsonValueSchema {
             crate::pql::model::SourceArgs,
             crate::pql::model::DistanceAggregation,
             crate::pql::model::DistanceFunction,
-            crate::pql::model::EmbedArgs
+            crate::pql::model::EmbedArgs,
+            crate::api_error::ErrorBody,
+            crate::api_error::ErrorCode
         )
     ),
     nest(
@@ -240,8 +461,11 @@ impl Modify for JsonValueSchema {
         (name = "jobs"),
         (name = "bookmarks"),
         (name = "pinboards", description = "Saved pinboard arrangements with version history"),
+        (name = "saved_queries", description = "Named PQL queries stored server-side"),
         (name = "database"),
         (name = "client", description = "Per-policy client configuration and derived capabilities"),
+        (name = "diagnostics", description = "Setup checks for common environment problems"),
+        (name = "relay", description = "Per-policy Relay pairing registry"),
         (name = "inference", description = "Model inference service (served locally or proxied upstream — same contract either way)")
     ),
     modifiers(&JsonValueSchema)
@@ -249,6 +473,16 @@ impl Modify for JsonValueSchema {
 #[allow(dead_code)]
 pub struct ApiDoc;
 
+/// Where the gateway's own spec is always served. `/openapi.json` is the
+/// same document when `upstreams.api.local` is set, but is proxied to the
+/// Python API otherwise; this path describes the Rust routes either way.
+pub const GATEWAY_SPEC_PATH: &str = "/openapi-gateway.json";
+
+pub async fn gateway_spec() -> Json<&'static utoipa::openapi::OpenApi> {
+    static SPEC: OnceLock<utoipa::openapi::OpenApi> = OnceLock::new();
+    Json(SPEC.get_or_init(ApiDoc::openapi))
+}
+
 // The generated spec is a public contract: the UI's `lib/panoptikon.d.ts` is
 // generated from it, so any drift is a (potentially breaking) client change.
 // These tests pin the contract two ways: structural invariants with useful
@@ -17,6 +17,7 @@ use tower::{Layer, Service};
 use url::form_urlencoded;
 use utoipa::ToSchema;
 
+use crate::api_error::ApiError;
 use crate::config::{
     DbPolicy, MAX_DB_NAME_LEN, MAX_USERNAME_LEN, PolicyConfig, RuleConfig, Settings,
     is_safe_identifier,
@@ -24,6 +25,10 @@ use crate::config::{
 use crate::policy_token::{POLICY_TOKEN_HEADER, TokenKey};
 
 const USERNAME_HASH_LEN: usize = 32;
+/// The user anonymous requests to user-scoped endpoints act as.
+const DEFAULT_USER: &str = "user";
+/// The shared user whose bookmarks apply to everyone.
+const WILDCARD_USER: &str = "*";
 
 /// Name of the listener endpoint the connection arrived on ("default" for
 /// the primary `server.host`/`server.port` listener). Inserted as a request
@@ -186,6 +191,57 @@ pub(crate) struct PolicyContext {
     pub search_cache: bool,
 }
 
+/// Who a request acts as, resolved from the matched policy's
+/// `[policies.identity]` and inserted as a request extension. Only inserted
+/// when the policy configures API tokens: without them (no identity, or
+/// only a user header, which then just names the tenant) handlers behave as
+/// they always have (anonymous mode).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum RequestIdentity {
+    /// A verified API token, or the trusted user header. `user` is
+    /// normalized the same way as the tenant username.
+    User { user: String, admin: bool },
+    /// The policy configures API tokens but the request presented none and
+    /// carried no user header. User-scoped endpoints reject it.
+    Unauthenticated,
+}
+
+/// Whether a user-scoped request reads or changes the user's data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum UserAccess {
+    Read,
+    Write,
+}
+
+/// The user a user-scoped request (bookmarks, notes, saved queries) acts
+/// as. Without an identity (anonymous mode) the requested user is trusted,
+/// defaulting to `user`, exactly as before identities existed. With one,
+/// the request defaults to its own user and may read the shared `*` user;
+/// naming anyone else, or writing as `*`, requires an admin token.
+pub(crate) fn scoped_user(
+    identity: Option<&RequestIdentity>,
+    requested: Option<&str>,
+    access: UserAccess,
+) -> Result<String, ApiError> {
+    match identity {
+        None => Ok(requested.unwrap_or(DEFAULT_USER).to_string()),
+        Some(RequestIdentity::Unauthenticated) => Err(ApiError::new(
+            StatusCode::UNAUTHORIZED,
+            "This endpoint requires an API token under this policy",
+        )),
+        Some(RequestIdentity::User { user, admin }) => match requested {
+            None => Ok(user.clone()),
+            Some(requested) if normalize_username(requested) == *user => Ok(user.clone()),
+            Some(requested) if *admin => Ok(requested.to_string()),
+            Some(WILDCARD_USER) if access == UserAccess::Read => Ok(WILDCARD_USER.to_string()),
+            Some(_) => Err(ApiError::new(
+                StatusCode::FORBIDDEN,
+                "Data of other users requires an admin token",
+            )),
+        },
+    }
+}
+
 struct PolicyDecision {
     policy: PolicyConfig,
     username: Option<String>,
@@ -207,12 +263,50 @@ pub(crate) struct EnforcementError {
 pub(crate) struct DbInfo {
     pub(crate) index: SingleDbInfo,
     pub(crate) user_data: SingleDbInfo,
+    /// True when at least one listed database could not be inspected; its
+    /// entry in `databases` carries the error.
+    #[serde(default)]
+    pub(crate) partial: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub(crate) struct SingleDbInfo {
     pub(crate) current: String,
     pub(crate) all: Vec<String>,
+    /// Per-database health, one entry per name in `all`.
+    #[serde(default)]
+    pub(crate) databases: Vec<DbStatus>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub(crate) struct DbStatus {
+    pub(crate) name: String,
+    pub(crate) ok: bool,
+    /// Bytes on disk, WAL files included; set for readable databases.
+    pub(crate) size_bytes: Option<u64>,
+    /// Indexed items; set for readable index databases.
+    pub(crate) item_count: Option<i64>,
+    /// Why the database could not be read; set when `ok` is false.
+    pub(crate) error: Option<DbStatusError>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub(crate) struct DbStatusError {
+    pub(crate) code: DbErrorCode,
+    pub(crate) message: String,
+}
+
+/// | code | meaning |
+/// |---|---|
+/// | `corrupt` | The file is not a valid SQLite database |
+/// | `locked` | Another process held a lock for longer than the busy timeout |
+/// | `unreadable` | The file could not be opened, or lacks the expected tables |
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum DbErrorCode {
+    Corrupt,
+    Locked,
+    Unreadable,
 }
 
 fn apply_policy(
@@ -299,7 +393,28 @@ fn apply_policy(
         }
     }
 
-    let username = extract_username(&policy, req)?;
+    let identity = resolve_identity(&policy, req)?;
+    let has_tokens = policy
+        .identity
+        .as_ref()
+        .is_some_and(|config| !config.tokens.is_empty());
+    if has_tokens && matches!(identity, Some(RequestIdentity::User { .. })) {
+        // A verified token is this gateway's credential, never the
+        // upstream's.
+        req.headers_mut().remove(header::AUTHORIZATION);
+    }
+    // Tenant DBs and user-scoped endpoints name the user the same way.
+    let identity = identity.map(|identity| match identity {
+        RequestIdentity::User { user, admin } => RequestIdentity::User {
+            user: normalize_username(&user),
+            admin,
+        },
+        unauthenticated => unauthenticated,
+    });
+    let username = match &identity {
+        Some(RequestIdentity::User { user, .. }) => Some(user.clone()),
+        _ => None,
+    };
 
     if is_inference {
         strip_query_params(req, &["index_db", "user_data_db"])?;
@@ -333,6 +448,11 @@ fn apply_policy(
         db_action = db_action.combine(action);
     }
 
+    // A header-only identity names the tenant but leaves user-scoped
+    // endpoints trusting the client, as they did before tokens existed.
+    if has_tokens && let Some(identity) = identity {
+        req.extensions_mut().insert(identity);
+    }
     req.extensions_mut().insert(PolicyContext {
         policy_name: policy.name.clone(),
         db_action,
@@ -425,6 +545,7 @@ fn is_api_surface(path: &str) -> bool {
         || path == "/docs"
         || path == "/redoc"
         || path == "/openapi.json"
+        || path == crate::openapi::GATEWAY_SPEC_PATH
 }
 
 fn is_inference_path(path: &str) -> bool {
@@ -444,7 +565,11 @@ fn is_client_config_path(path: &str) -> bool {
 }
 
 fn needs_db_params(path: &str) -> bool {
-    if path == "/docs" || path == "/redoc" || path == "/openapi.json" {
+    if path == "/docs"
+        || path == "/redoc"
+        || path == "/openapi.json"
+        || path == crate::openapi::GATEWAY_SPEC_PATH
+    {
         return false;
     }
     if is_db_info_path(path) || is_db_create_path(path) || is_inference_path(path) {
@@ -591,6 +716,9 @@ fn enforce_db_params(
     let mut retained: Vec<(String, String)> = Vec::with_capacity(pairs.len());
     let mut index_db = None;
     let mut user_data_db = None;
+    // Multi-database searches name every index DB they span; each one goes
+    // through the same checks as `index_db`.
+    let mut index_dbs = Vec::new();
 
     for (key, value) in pairs.drain(..) {
         if key == "index_db" {
@@ -599,6 +727,10 @@ fn enforce_db_params(
             }
             continue;
         }
+        if key == "index_dbs" {
+            index_dbs.push(value);
+            continue;
+        }
         if key == "user_data_db" {
             if user_data_db.is_none() {
                 user_data_db = Some(value);
@@ -623,6 +755,19 @@ fn enforce_db_params(
         username,
     )?;
 
+    let mut action = index_resolution.action.combine(user_resolution.action);
+    for value in index_dbs {
+        let resolution = resolve_db_param(
+            "index_dbs",
+            Some(value),
+            &policy.index_db.default,
+            &policy.index_db,
+            username,
+        )?;
+        action = action.combine(resolution.action);
+        retained.push(("index_dbs".to_string(), resolution.value));
+    }
+
     retained.push(("index_db".to_string(), index_resolution.value));
     retained.push(("user_data_db".to_string(), user_resolution.value));
 
@@ -639,7 +784,7 @@ fn enforce_db_params(
         });
     }
 
-    Ok(index_resolution.action.combine(user_resolution.action))
+    Ok(action)
 }
 
 fn enforce_db_create_params(
@@ -774,6 +919,16 @@ fn filter_db_info_payload(
     info.user_data.current = user_current;
     info.index.all = filter_db_list(info.index.all, &policy.index_db, username);
     info.user_data.all = filter_db_list(info.user_data.all, &policy.user_data_db, username);
+    info.index.databases = filter_db_statuses(info.index.databases, &policy.index_db, username);
+    info.user_data.databases =
+        filter_db_statuses(info.user_data.databases, &policy.user_data_db, username);
+    // Broken databases the user cannot see must not flag the listing.
+    info.partial = info
+        .index
+        .databases
+        .iter()
+        .chain(&info.user_data.databases)
+        .any(|status| !status.ok);
     Ok(info)
 }
 
@@ -879,21 +1034,23 @@ fn resolve_default_db(
     }
     Ok(value)
 }
+fn db_listed(name: &str, policy: &DbPolicy, username: Option<&str>) -> bool {
+    if policy.allow.allows(name) {
+        return true;
+    }
+    let Some(username) = username else {
+        return false;
+    };
+    matches_prefix(policy.tenant_prefix_template.as_deref(), username, name)
+}
+
 fn filter_db_list(names: Vec<String>, policy: &DbPolicy, username: Option<&str>) -> Vec<String> {
     if policy.allow.is_all() {
         return names;
     }
     let mut filtered: Vec<String> = names
         .into_iter()
-        .filter(|name| {
-            if policy.allow.allows(name) {
-                return true;
-            }
-            let Some(username) = username else {
-                return false;
-            };
-            matches_prefix(policy.tenant_prefix_template.as_deref(), username, name)
-        })
+        .filter(|name| db_listed(name, policy, username))
         .collect();
 
     if let (Some(_username), Some(tenant_default)) = (username, policy.tenant_default.as_deref()) {
@@ -918,6 +1075,31 @@ fn filter_db_list(names: Vec<String>, policy: &DbPolicy, username: Option<&str>)
     deduped
 }
 
+/// Like [`filter_db_list`], without adding the tenant default (it has no
+/// status until it exists on disk).
+fn filter_db_statuses(
+    statuses: Vec<DbStatus>,
+    policy: &DbPolicy,
+    username: Option<&str>,
+) -> Vec<DbStatus> {
+    if policy.allow.is_all() {
+        return statuses;
+    }
+    let mut filtered: Vec<DbStatus> = Vec::with_capacity(statuses.len());
+    for mut status in statuses {
+        if !db_listed(&status.name, policy, username) {
+            continue;
+        }
+        if let Some(stripped) = strip_tenant_prefix(&status.name, policy, username) {
+            status.name = stripped;
+        }
+        if !filtered.iter().any(|entry| entry.name == status.name) {
+            filtered.push(status);
+        }
+    }
+    filtered
+}
+
 fn matches_prefix(template: Option<&str>, username: &str, candidate: &str) -> bool {
     let Some(prefix) = template else {
         return false;
@@ -935,18 +1117,47 @@ fn matches_prefix(template: Option<&str>, username: &str, candidate: &str) -> bo
     is_safe_identifier(rest, MAX_DB_NAME_LEN)
 }
 
-fn extract_username(
+/// Resolve the request's identity under `policy`. A bearer token matching
+/// one of `identity.tokens` wins; a bearer token matching none is rejected
+/// outright rather than silently downgraded. Otherwise `user_header` (when
+/// configured and present) names the user, never as admin.
+fn resolve_identity(
     policy: &PolicyConfig,
     req: &Request<Body>,
-) -> std::result::Result<Option<String>, EnforcementError> {
+) -> std::result::Result<Option<RequestIdentity>, EnforcementError> {
     let identity = match &policy.identity {
         Some(identity) => identity,
         None => return Ok(None),
     };
 
-    let header_value = match req.headers().get(&identity.user_header) {
-        Some(value) => value,
-        None => return Ok(None),
+    if !identity.tokens.is_empty()
+        && let Some(presented) = bearer_token(req)
+    {
+        // Compare digests so the comparison time says nothing about how
+        // much of a configured token the presented one shares.
+        let presented = Sha256::digest(presented.as_bytes());
+        let matched = identity
+            .tokens
+            .iter()
+            .find(|token| Sha256::digest(token.token.as_bytes()) == presented);
+        return match matched {
+            Some(token) => Ok(Some(RequestIdentity::User {
+                user: token.user.clone(),
+                admin: token.admin,
+            })),
+            None => Err(EnforcementError {
+                status: StatusCode::UNAUTHORIZED,
+                reason: "invalid_token",
+            }),
+        };
+    }
+
+    let header_value = identity
+        .user_header
+        .as_ref()
+        .and_then(|user_header| req.headers().get(user_header));
+    let Some(header_value) = header_value else {
+        return Ok((!identity.tokens.is_empty()).then_some(RequestIdentity::Unauthenticated));
     };
 
     let raw = header_value.to_str().map_err(|_| EnforcementError {
@@ -961,16 +1172,30 @@ fn extract_username(
         });
     }
 
+    Ok(Some(RequestIdentity::User {
+        user: value.to_string(),
+        admin: false,
+    }))
+}
+
+fn bearer_token(req: &Request<Body>) -> Option<&str> {
+    let value = header_to_str(req.headers().get(header::AUTHORIZATION))?;
+    let (scheme, token) = value.trim().split_once(' ')?;
+    let token = token.trim();
+    (scheme.eq_ignore_ascii_case("bearer") && !token.is_empty()).then_some(token)
+}
+
+/// The user id as used in tenant DB names: kept when it is a short safe
+/// identifier, hashed otherwise.
+fn normalize_username(value: &str) -> String {
     let hash_len = USERNAME_HASH_LEN;
     let needs_hash =
         value.len() > hash_len.saturating_sub(2) || !is_safe_identifier(value, MAX_USERNAME_LEN);
-    let normalized = if needs_hash {
+    if needs_hash {
         hash_username(value)
     } else {
         value.to_string()
-    };
-
-    Ok(Some(normalized))
+    }
 }
 
 fn build_query(pairs: Vec<(String, String)>) -> Option<String> {
@@ -1073,7 +1298,7 @@ fn header_to_str(value: Option<&header::HeaderValue>) -> Option<&str> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::{AllowList, IdentityConfig, PolicyMatch};
+    use crate::config::{AllowList, IdentityConfig, IdentityToken, PolicyMatch};
     use axum::http::Request;
     use std::collections::BTreeMap;
 
@@ -1107,6 +1332,19 @@ mod tests {
         }
     }
 
+    fn status(name: &str, ok: bool) -> DbStatus {
+        DbStatus {
+            name: name.to_string(),
+            ok,
+            size_bytes: ok.then_some(4096),
+            item_count: ok.then_some(0),
+            error: (!ok).then(|| DbStatusError {
+                code: DbErrorCode::Corrupt,
+                message: "file is not a database".to_string(),
+            }),
+        }
+    }
+
     /// Settings with one extra endpoint ("test") and three policies probing
     /// the match semantics: hosts+endpoints (AND), endpoints-only, and the
     /// usual hosts-only localhost policy, in that order.
@@ -1596,6 +1834,41 @@ allow = ["default"]
         assert_eq!(err.status, StatusCode::FORBIDDEN);
     }
 
+    #[test]
+    // Ensures every index_dbs entry is checked like index_db: allowed names pass, others are
+    // tenant-prefixed, and none is dropped.
+    fn index_dbs_entries_are_enforced() {
+        let policy = policy_with(
+            db_policy(
+                "default",
+                AllowList::List(vec!["default".to_string()]),
+                None,
+                Some("user_{username}_"),
+            ),
+            db_policy("default", AllowList::All, None, None),
+        );
+        let mut req = Request::builder()
+            .uri("http://localhost/api/search/pql?index_dbs=default&index_dbs=photos")
+            .body(Body::empty())
+            .unwrap();
+
+        let action = enforce_db_params(&policy, &mut req, Some("alice")).unwrap();
+        let query = parse_query(&req);
+
+        assert!(matches!(action, DbAction::Rewritten));
+        assert_eq!(
+            query.get("index_dbs").unwrap(),
+            &vec!["default".to_string(), "user_alice_photos".to_string()]
+        );
+
+        let mut req = Request::builder()
+            .uri("http://localhost/api/search/pql?index_dbs=photos")
+            .body(Body::empty())
+            .unwrap();
+        let err = enforce_db_params(&policy, &mut req, None).unwrap_err();
+        assert_eq!(err.status, StatusCode::FORBIDDEN);
+    }
+
     #[test]
     // Validates /api/db/create query enforcement: rewrite new_* params with tenant prefix,
     // drop any index_db/user_data_db params, and report a rewritten action.
@@ -1662,6 +1935,11 @@ allow = ["default"]
                     "user_alice_private".to_string(),
                     "user_bob_images".to_string(),
                 ],
+                databases: vec![
+                    status("default", true),
+                    status("user_alice_images", true),
+                    status("user_bob_images", false),
+                ],
             },
             user_data: SingleDbInfo {
                 current: "default".to_string(),
@@ -1670,7 +1948,9 @@ allow = ["default"]
                     "user_alice_bookmarks".to_string(),
                     "user_bob_bookmarks".to_string(),
                 ],
+                databases: Vec::new(),
             },
+            partial: true,
         };
 
         let filtered = filter_db_info_payload(info, &policy, Some("alice")).unwrap();
@@ -1695,6 +1975,16 @@ allow = ["default"]
             user_all,
             vec!["bookmarks".to_string(), "default".to_string()]
         );
+
+        // Bob's broken database is hidden, so Alice's listing is complete.
+        let statuses = filtered
+            .index
+            .databases
+            .iter()
+            .map(|status| status.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(statuses, vec!["default", "images"]);
+        assert!(!filtered.partial);
     }
 
     #[test]
@@ -1716,7 +2006,8 @@ allow = ["default"]
             ),
         );
         policy.identity = Some(IdentityConfig {
-            user_header: "X-Forwarded-User".to_string(),
+            user_header: Some("X-Forwarded-User".to_string()),
+            tokens: Vec::new(),
         });
         let unsafe_name = "alice@example.com";
         let hashed = hash_username(unsafe_name);
@@ -1726,7 +2017,12 @@ allow = ["default"]
             .body(Body::empty())
             .unwrap();
 
-        let username = extract_username(&policy, &req).unwrap().unwrap();
+        let identity = resolve_identity(&policy, &req).unwrap().unwrap();
+        let RequestIdentity::User { user, admin } = identity else {
+            panic!("header identity resolves to a user");
+        };
+        assert!(!admin);
+        let username = normalize_username(&user);
         assert_eq!(username, hashed);
         let action = enforce_db_params(&policy, &mut req, Some(&username)).unwrap();
         let query = parse_query(&req);
@@ -1737,4 +2033,152 @@ allow = ["default"]
             &vec![format!("user_{}_private", hashed)]
         );
     }
+
+    #[test]
+    // Verifies a configured bearer token resolves to its user and admin
+    // scope ahead of the user header, an unknown token is rejected, and a
+    // request with neither is unauthenticated once tokens are configured.
+    fn resolves_identity_from_tokens() {
+        let mut policy = policy_with(
+            db_policy("default", AllowList::All, None, None),
+            db_policy("default", AllowList::All, None, None),
+        );
+        policy.identity = Some(IdentityConfig {
+            user_header: Some("X-Forwarded-User".to_string()),
+            tokens: vec![IdentityToken {
+                token: "secret".to_string(),
+                user: "admin-bot".to_string(),
+                admin: true,
+            }],
+        });
+        let request = |authorization: Option<&str>, user: Option<&str>| {
+            let mut builder = Request::builder().uri("http://localhost/api/bookmarks/ns");
+            if let Some(value) = authorization {
+                builder = builder.header(header::AUTHORIZATION, value);
+            }
+            if let Some(value) = user {
+                builder = builder.header("X-Forwarded-User", value);
+            }
+            builder.body(Body::empty()).unwrap()
+        };
+
+        assert_eq!(
+            resolve_identity(&policy, &request(Some("Bearer secret"), Some("alice"))).unwrap(),
+            Some(RequestIdentity::User {
+                user: "admin-bot".to_string(),
+                admin: true,
+            })
+        );
+        let err = resolve_identity(&policy, &request(Some("Bearer wrong"), None)).unwrap_err();
+        assert_eq!(err.status, StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            resolve_identity(&policy, &request(None, Some("alice"))).unwrap(),
+            Some(RequestIdentity::User {
+                user: "alice".to_string(),
+                admin: false,
+            })
+        );
+        assert_eq!(
+            resolve_identity(&policy, &request(None, None)).unwrap(),
+            Some(RequestIdentity::Unauthenticated)
+        );
+    }
+
+    #[test]
+    // Verifies a header-only identity names the tenant without inserting a
+    // RequestIdentity, while under configured tokens the header user is
+    // inserted, normalized like the tenant username.
+    fn identity_extension_requires_tokens() {
+        let mut settings = endpoint_settings();
+        let key = TokenKey::random();
+        let request = || {
+            Request::builder()
+                .uri("http://localhost/api/items")
+                .header("host", "localhost")
+                .header("X-Forwarded-User", "alice@example.com")
+                .body(Body::empty())
+                .unwrap()
+        };
+        let set_identity = |settings: &mut Settings, tokens: Vec<IdentityToken>| {
+            for policy in settings.policies.iter_mut() {
+                policy.identity = Some(IdentityConfig {
+                    user_header: Some("X-Forwarded-User".to_string()),
+                    tokens: tokens.clone(),
+                });
+            }
+        };
+        let hashed = hash_username("alice@example.com");
+
+        set_identity(&mut settings, Vec::new());
+        let mut req = request();
+        let decision = apply_policy(&mut req, &settings, &key).unwrap();
+        assert_eq!(decision.username.as_deref(), Some(hashed.as_str()));
+        assert!(req.extensions().get::<RequestIdentity>().is_none());
+
+        set_identity(
+            &mut settings,
+            vec![IdentityToken {
+                token: "secret".to_string(),
+                user: "admin-bot".to_string(),
+                admin: true,
+            }],
+        );
+        let mut req = request();
+        apply_policy(&mut req, &settings, &key).unwrap();
+        assert_eq!(
+            req.extensions().get::<RequestIdentity>(),
+            Some(&RequestIdentity::User {
+                user: hashed,
+                admin: false,
+            })
+        );
+    }
+
+    #[test]
+    // Verifies anonymous requests keep the requested user, identified ones
+    // default to themselves (compared after normalization) and may read the
+    // shared `*` user, and only admins may name another user or write `*`.
+    fn scoped_user_enforces_identity() {
+        use UserAccess::{Read, Write};
+
+        assert_eq!(scoped_user(None, None, Write).unwrap(), "user");
+        assert_eq!(scoped_user(None, Some("bob"), Write).unwrap(), "bob");
+
+        let alice = RequestIdentity::User {
+            user: "alice".to_string(),
+            admin: false,
+        };
+        assert_eq!(scoped_user(Some(&alice), None, Write).unwrap(), "alice");
+        assert_eq!(
+            scoped_user(Some(&alice), Some("alice"), Write).unwrap(),
+            "alice"
+        );
+        assert_eq!(scoped_user(Some(&alice), Some("*"), Read).unwrap(), "*");
+        for (other, access) in [("bob", Read), ("bob", Write), ("*", Write)] {
+            let err = scoped_user(Some(&alice), Some(other), access).unwrap_err();
+            assert_eq!(err.status(), StatusCode::FORBIDDEN);
+        }
+
+        let hashed = RequestIdentity::User {
+            user: hash_username("alice@example.com"),
+            admin: false,
+        };
+        assert_eq!(
+            scoped_user(Some(&hashed), Some("alice@example.com"), Write).unwrap(),
+            hash_username("alice@example.com")
+        );
+
+        let admin = RequestIdentity::User {
+            user: "root".to_string(),
+            admin: true,
+        };
+        assert_eq!(
+            scoped_user(Some(&admin), Some("bob"), Write).unwrap(),
+            "bob"
+        );
+        assert_eq!(scoped_user(Some(&admin), Some("*"), Write).unwrap(), "*");
+
+        let err = scoped_user(Some(&RequestIdentity::Unauthenticated), None, Read).unwrap_err();
+        assert_eq!(err.status(), StatusCode::UNAUTHORIZED);
+    }
 }
@@ -30,10 +30,25 @@ pub(crate) struct PqlBuilderResult {
     pub(crate) query: SelectStatement,
     pub(crate) with_clause: Option<WithClause>,
     pub(crate) extra_columns: HashMap<String, String>,
+    /// SQL column label -> filter `name`, for `PqlQuery::attribute_filters`.
+    /// Each labeled column is true when that filter matched the row.
+    pub(crate) attribution_columns: HashMap<String, String>,
     /// `None` for count queries and for `page_size < 1` (no limit).
     pub(crate) pagination: Option<Pagination>,
     /// True when any filter joins the attached user_data database.
     pub(crate) uses_user_data: bool,
+    /// The selected ORDER BY values, in priority order, when the query asked
+    /// for them with `PqlQuery::select_order_keys`. Empty otherwise.
+    pub(crate) order_keys: Vec<OrderKey>,
+}
+
+/// A result column holding one ORDER BY term's value, so rows from separate
+/// executions can be merged in the query's own order. NULLs sort last in
+/// either direction, as in the SQL.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct OrderKey {
+    pub(crate) label: String,
+    pub(crate) descending: bool,
 }
 
 impl PqlBuilderResult {
@@ -86,6 +101,13 @@ struct ExtraColumn {
     alias: String,
 }
 
+/// A named sortable filter reported in each result's attribution.
+#[derive(Clone, Debug)]
+struct FilterAttribution {
+    name: String,
+    cte: CteRef,
+}
+
 #[derive(Clone, Debug)]
 struct OrderByFilter {
     cte: CteRef,
@@ -99,6 +121,7 @@ struct OrderByFilter {
 pub(crate) struct QueryState {
     order_list: Vec<OrderByFilter>,
     extra_columns: Vec<ExtraColumn>,
+    attributions: Vec<FilterAttribution>,
     selects: HashMap<String, FilterSelect>,
     ctes: Vec<CteDefinition>,
     cte_counter: i64,
@@ -106,6 +129,10 @@ pub(crate) struct QueryState {
     item_data_query: bool,
     entity: EntityType,
     uses_user_data: bool,
+    /// `PqlQuery::optimize`.
+    optimize: bool,
+    /// `PqlQuery::attribute_filters`.
+    attribute_filters: bool,
 }
 
 #[derive(Clone, Debug)]
@@ -122,7 +149,8 @@ enum OrderByColumn {
         order: Order,
     },
     Coalesce {
-        labels: Vec<String>,
+        /// Each rank's label with its own direction.
+        labels: Vec<(String, Order)>,
         order: Order,
         rrfs: Option<Vec<Rrf>>,
     },
@@ -204,6 +232,7 @@ fn build_query_with_root(
     let mut state = QueryState {
         order_list: Vec::new(),
         extra_columns: Vec::new(),
+        attributions: Vec::new(),
         selects: HashMap::new(),
         ctes: Vec::new(),
         cte_counter: 0,
@@ -211,6 +240,8 @@ fn build_query_with_root(
         item_data_query: matches!(input_query.entity, EntityType::Text),
         entity: input_query.entity,
         uses_user_data: false,
+        optimize: input_query.optimize,
+        attribute_filters: input_query.attribute_filters,
     };
 
     let mut root_cte_name: Option<String> = None;
@@ -326,8 +357,10 @@ fn build_query_with_root(
             query: count_query,
             with_clause,
             extra_columns,
+            attribution_columns: HashMap::new(),
             pagination: None,
             uses_user_data: state.uses_user_data,
+            order_keys: Vec::new(),
         });
     }
 
@@ -363,6 +396,15 @@ fn build_query_with_root(
         root_cte_name.as_deref(),
         &mut selected_columns,
     );
+    let (full_query, attribution_columns) = add_attribution_columns(
+        full_query,
+        &state,
+        root_cte_name.as_deref(),
+        last_cte_name.as_deref(),
+        &file_id_ref,
+        data_id_ref.as_ref(),
+        &mut selected_columns,
+    );
 
     // The API layer resolves the seed (minting one when the caller omitted
     // it), so this is Some for every request that orders randomly. The
@@ -378,22 +420,40 @@ fn build_query_with_root(
         seed,
     );
 
-    if let Some(partition_by) = input_query.partition_by.clone() {
+    let final_order = if let Some(partition_by) = input_query.partition_by.clone() {
         full_query = apply_partition_by(
             &partition_by,
             full_query,
+            &file_id_ref,
             &selected_columns.order,
             &order_columns,
             &mut state,
         );
+        order_columns
+            .iter()
+            .map(|order_col| order_spec_for_alias(order_col, "partition_cte"))
+            .collect()
     } else {
-        for order_spec in order_specs {
+        for order_spec in &order_specs {
             full_query.order_by_expr_with_nulls(
-                order_spec.expr,
-                order_spec.order,
+                order_spec.expr.clone(),
+                order_spec.order.clone(),
                 order_spec.nulls,
             );
         }
+        order_specs
+    };
+
+    let mut order_keys = Vec::new();
+    if input_query.select_order_keys {
+        for (index, order_spec) in final_order.into_iter().enumerate() {
+            let label = format!("order_key_{index}");
+            full_query.expr_as(order_spec.expr, Alias::new(label.as_str()));
+            order_keys.push(OrderKey {
+                label,
+                descending: matches!(order_spec.order, Order::Desc),
+            });
+        }
     }
 
     let page = std::cmp::Ord::max(input_query.page, 1);
@@ -408,12 +468,31 @@ fn build_query_with_root(
         query: full_query,
         with_clause,
         extra_columns,
+        attribution_columns,
         pagination,
         uses_user_data: state.uses_user_data,
+        order_keys,
     })
 }
 
 fn raise_if_invalid(input_query: &PqlQuery) -> Result<(), PqlError> {
+    if input_query.refine.is_some() {
+        return Err(PqlError::invalid(
+            "refine requires async preprocessing against an index database",
+        ));
+    }
+    let derived = input_query
+        .select
+        .iter()
+        .chain(input_query.partition_by.iter().flatten())
+        .copied()
+        .find(|column| is_derived_column(*column));
+    if let Some(column) = derived {
+        return Err(PqlError::invalid(format!(
+            "`{}` is derived from width and height and can only be used in match filters",
+            column_name(column)
+        )));
+    }
     if !matches!(input_query.entity, EntityType::Text) {
         if input_query.select.iter().copied().any(is_text_column) {
             return Err(PqlError::invalid(
@@ -453,17 +532,22 @@ fn process_query_element(
             }
             Ok(current)
         }
+        QueryElement::Or(op) if state.optimize => build_or_optimized(op.or_, context, state),
         QueryElement::Or(op) => {
             let mut iter = op.or_.into_iter();
             let first = iter
                 .next()
                 .ok_or_else(|| PqlError::invalid("OR operator has no operands"))?;
+            let order_start = state.order_list.len();
             let first_cte = process_query_element(first, context, state)?;
             let mut union_query = select_std_from_cte(&first_cte, state);
+            let mut branches = vec![first_cte];
             for sub_element in iter {
                 let sub_cte = process_query_element(sub_element, context, state)?;
                 union_query.union(UnionType::Distinct, select_std_from_cte(&sub_cte, state));
+                branches.push(sub_cte);
             }
+            rank_or_branches_together(state, order_start, &branches);
             let cte_name = format!("n{}_or", state.cte_counter);
             state.cte_counter += 1;
             let or_cte = create_cte(state, cte_name.clone(), union_query.to_owned());
@@ -517,8 +601,90 @@ fn process_query_element(
         QueryElement::SimilarTo(filter) => filter.build(context, state),
         QueryElement::MatchTags(filter) => filter.build(context, state),
         QueryElement::InBookmarks(filter) => filter.build(context, state),
+        QueryElement::MatchMeta(filter) => filter.build(context, state),
+        QueryElement::MatchNote(filter) => filter.build(context, state),
         QueryElement::ProcessedBy(filter) => filter.build(context, state),
         QueryElement::HasUnprocessedData(filter) => filter.build(context, state),
+        QueryElement::InFolder(filter) => filter.build(context, state),
+    }
+}
+
+/// `or_` under `PqlQuery::optimize`, returning the same rows as the `UNION`
+/// in `process_query_element`. All-`match` operands become a single filter.
+/// Otherwise the operands are concatenated with `UNION ALL` and grouped on
+/// the std key (file_id, plus data_id for text queries), which dedups on the
+/// key alone rather than sorting entire rows.
+fn build_or_optimized(
+    operands: Vec<QueryElement>,
+    context: &CteRef,
+    state: &mut QueryState,
+) -> Result<CteRef, PqlError> {
+    if operands.is_empty() {
+        return Err(PqlError::invalid("OR operator has no operands"));
+    }
+    if operands
+        .iter()
+        .all(|operand| matches!(operand, QueryElement::Match(_)))
+    {
+        let filters = operands
+            .into_iter()
+            .filter_map(|operand| match operand {
+                QueryElement::Match(filter) => Some(filter),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        return filters::build_match_any(&filters, context, state);
+    }
+
+    let order_start = state.order_list.len();
+    let mut branches = Vec::with_capacity(operands.len());
+    for operand in operands {
+        branches.push(process_query_element(operand, context, state)?);
+    }
+    rank_or_branches_together(state, order_start, &branches);
+    let mut union_query = select_std_from_cte(&branches[0], state);
+    for branch in &branches[1..] {
+        union_query.union(UnionType::All, select_std_from_cte(branch, state));
+    }
+    let cte_name = format!("n{}_or", state.cte_counter);
+    state.cte_counter += 1;
+    let all_cte = create_cte(state, format!("{cte_name}_all"), union_query);
+    let mut grouped = select_std_from_cte(&all_cte, state);
+    apply_group_by(&mut grouped, get_std_group_by(&all_cte, state));
+    let or_cte = create_cte(state, cte_name.clone(), grouped);
+    state.selects.insert(
+        cte_name,
+        FilterSelect {
+            select: select_std_from_cte(&or_cte, state),
+            context: or_cte.clone(),
+            joined_tables: JoinedTables::default(),
+        },
+    );
+    Ok(or_cte)
+}
+
+/// The OR CTE itself only carries the std key, so sortable branches are
+/// ranked through their own CTEs, which the final query left-joins. A branch
+/// rank is only set on the rows that branch matched: ordered one priority
+/// after another, every row of one branch would sort above all rows of the
+/// next. Moving the direct branches' ranks (those added since `order_start`)
+/// to one priority, the highest among them, makes `combine_order_lists`
+/// group them, so rows are ordered by their best rank across branches.
+fn rank_or_branches_together(state: &mut QueryState, order_start: usize, branches: &[CteRef]) {
+    let is_branch =
+        |filter: &OrderByFilter| branches.iter().any(|branch| branch.name == filter.cte.name);
+    let Some(priority) = state.order_list[order_start..]
+        .iter()
+        .filter(|filter| is_branch(filter))
+        .map(|filter| filter.priority)
+        .max()
+    else {
+        return;
+    };
+    for filter in state.order_list[order_start..].iter_mut() {
+        if is_branch(filter) {
+            filter.priority = priority;
+        }
     }
 }
 
@@ -642,6 +808,18 @@ fn apply_group_by(query: &mut SelectStatement, columns: Vec<ColumnRef>) {
     }
 }
 
+/// `(SELECT id FROM setters WHERE name = ?)`. Uncorrelated, so SQLite runs it
+/// once per statement and compares `item_data.setter_id` against a constant.
+/// NULL (matching nothing) when no setter has that name.
+fn setter_id_by_name(name: &str) -> Expr {
+    Query::select()
+        .column(Setters::Id)
+        .from(Setters::Table)
+        .and_where(Expr::col(Setters::Name).eq(name))
+        .to_owned()
+        .into()
+}
+
 fn add_select_columns(
     input_query: &mut PqlQuery,
     mut query: SelectStatement,
@@ -699,6 +877,57 @@ fn add_extra_columns(
     (query, column_aliases)
 }
 
+/// Records `cte` for attribution when the query asked for it and the filter
+/// is named. Called by every sortable filter with the CTE it returns.
+fn add_filter_attribution(state: &mut QueryState, cte: &CteRef, sort: &SortableOptions) {
+    if state.is_count_query || !state.attribute_filters {
+        return;
+    }
+    if let Some(name) = sort.name.as_ref().filter(|name| !name.is_empty()) {
+        state.attributions.push(FilterAttribution {
+            name: name.clone(),
+            cte: cte.clone(),
+        });
+    }
+}
+
+/// One boolean column per attributed filter. A filter CTE is probed with
+/// `EXISTS` rather than joined like extra columns, so filters that can match
+/// a row several times never duplicate it. Every final row comes from the
+/// root and last CTEs, so filters owning those are true outright.
+fn add_attribution_columns(
+    mut query: SelectStatement,
+    state: &QueryState,
+    root_cte_name: Option<&str>,
+    last_cte_name: Option<&str>,
+    file_id_ref: &ColumnRef,
+    data_id_ref: Option<&ColumnRef>,
+    selected_columns: &mut SelectedColumns,
+) -> (SelectStatement, HashMap<String, String>) {
+    let mut column_names = HashMap::new();
+    for (index, attribution) in state.attributions.iter().enumerate() {
+        let cte = &attribution.cte;
+        let matched = if [root_cte_name, last_cte_name].contains(&Some(cte.name.as_str())) {
+            Expr::val(true)
+        } else {
+            let mut probe = Query::select();
+            probe
+                .expr(Expr::val(1))
+                .from(Alias::new(cte.name.as_str()))
+                .and_where(Expr::col(cte.column_ref("file_id")).equals(file_id_ref.clone()));
+            if let Some(data_id_ref) = data_id_ref {
+                probe.and_where(Expr::col(cte.column_ref("data_id")).equals(data_id_ref.clone()));
+            }
+            Expr::exists(probe)
+        };
+        let label = format!("attr_{index}");
+        query.expr_as(matched, Alias::new(label.as_str()));
+        column_names.insert(label.clone(), attribution.name.clone());
+        selected_columns.push(&label);
+    }
+    (query, column_names)
+}
+
 fn add_joins(
     targets: &[CteRef],
     mut query: SelectStatement,
@@ -1038,22 +1267,23 @@ fn apply_coalesce_order_filters(
     let mut rrfs = Vec::new();
 
     for spec in args {
-        let expr = if Some(spec.cte.name.as_str()) == root_cte_name {
+        let rank = if Some(spec.cte.name.as_str()) == root_cte_name {
             Expr::col(Alias::new("order_rank"))
         } else {
             Expr::col((Alias::new(spec.cte.name.as_str()), Alias::new("order_rank")))
         };
-        columns.push(expr.clone());
+        let rank_order = direction_to_order(spec.direction);
+        columns.push(oriented_rank(rank.clone(), &rank_order, &order, enable_rrf));
         if enable_rrf {
             rrfs.push(spec.rrf.clone().unwrap_or_default());
         }
         if select_conds {
             if Some(spec.cte.name.as_str()) == root_cte_name {
-                select_labels.push("order_rank".to_string());
+                select_labels.push(("order_rank".to_string(), rank_order));
             } else {
                 let label = format!("o{index}_{}_rank", spec.cte.name);
-                query.expr_as(expr, Alias::new(label.as_str()));
-                select_labels.push(label);
+                query.expr_as(rank, Alias::new(label.as_str()));
+                select_labels.push((label, rank_order));
             }
         }
     }
@@ -1083,6 +1313,16 @@ fn apply_coalesce_order_filters(
     (query, order_spec, order_column)
 }
 
+/// A rank sorted the other way than its group joins it negated, so MIN/MAX
+/// still picks each row's best rank. (RRF sums positions instead.)
+fn oriented_rank(rank: Expr, rank_order: &Order, group_order: &Order, rrf: bool) -> Expr {
+    if !rrf && rank_order != group_order {
+        Expr::val(0).sub(rank)
+    } else {
+        rank
+    }
+}
+
 fn build_coalesced_expr(columns: &[Expr], order: Order, rrfs: Option<Vec<Rrf>>) -> Expr {
     if let Some(rrfs) = rrfs {
         let mut total: Option<Expr> = None;
@@ -1118,9 +1358,12 @@ fn build_coalesced_expr(columns: &[Expr], order: Order, rrfs: Option<Vec<Rrf>>)
     }
 }
 
+/// Keeps one row per partition: the first by the query's order, except that
+/// when partitioning by item the item's pinned primary file comes first.
 fn apply_partition_by(
     partition_by: &[Column],
     mut query: SelectStatement,
+    file_id_ref: &ColumnRef,
     selected_columns: &[String],
     order_columns: &[OrderByColumn],
     state: &mut QueryState,
@@ -1130,6 +1373,20 @@ fn apply_partition_by(
         let label = format!("part_{name}");
         query.expr_as(get_column_expr(*col), Alias::new(label.as_str()));
     }
+    let prefer_pinned = partition_by.contains(&Column::ItemId);
+    if prefer_pinned {
+        query
+            .join(
+                JoinType::LeftJoin,
+                ItemPrimaryFiles::Table,
+                Expr::col((ItemPrimaryFiles::Table, ItemPrimaryFiles::FileId))
+                    .equals(file_id_ref.clone()),
+            )
+            .expr_as(
+                Expr::col((ItemPrimaryFiles::Table, ItemPrimaryFiles::FileId)).is_not_null(),
+                Alias::new("part_pinned"),
+            );
+    }
 
     let select_cte = create_cte(state, "select_cte".to_string(), query.to_owned());
 
@@ -1141,6 +1398,15 @@ fn apply_partition_by(
             Alias::new(label.as_str()),
         ));
     }
+    if prefer_pinned {
+        window.order_by_expr(
+            Expr::col((
+                Alias::new(select_cte.name.as_str()),
+                Alias::new("part_pinned"),
+            )),
+            Order::Desc,
+        );
+    }
     for order_col in order_columns {
         let order_spec = order_spec_for_alias(order_col, select_cte.name.as_str());
         window.order_by_expr_with_nulls(order_spec.expr, order_spec.order, order_spec.nulls);
@@ -1190,7 +1456,10 @@ fn order_spec_for_alias(order: &OrderByColumn, alias: &str) -> OrderSpec {
         } => {
             let columns = labels
                 .iter()
-                .map(|label| Expr::col((Alias::new(alias), Alias::new(label.as_str()))))
+                .map(|(label, label_order)| {
+                    let rank = Expr::col((Alias::new(alias), Alias::new(label.as_str())));
+                    oriented_rank(rank, label_order, order, rrfs.is_some())
+                })
                 .collect::<Vec<_>>();
             OrderSpec {
                 expr: build_coalesced_expr(&columns, order.clone(), rrfs.clone()),
@@ -1250,6 +1519,8 @@ fn column_name(col: Column) -> &'static str {
         Column::Height => "height",
         Column::Duration => "duration",
         Column::TimeAdded => "time_added",
+        Column::FileTimeAdded => "file_time_added",
+        Column::LastSeen => "last_seen",
         Column::AudioTracks => "audio_tracks",
         Column::VideoTracks => "video_tracks",
         Column::SubtitleTracks => "subtitle_tracks",
@@ -1265,6 +1536,11 @@ fn column_name(col: Column) -> &'static str {
         Column::SetterName => "setter_name",
         Column::DataIndex => "data_index",
         Column::SourceId => "source_id",
+        Column::MinDimension => "min_dimension",
+        Column::MaxDimension => "max_dimension",
+        Column::Megapixels => "megapixels",
+        Column::Directory => "directory",
+        Column::TypePrefix => "type_prefix",
     }
 }
 
@@ -1283,6 +1559,8 @@ fn order_by_name(field: OrderByField) -> &'static str {
         OrderByField::Height => "height",
         OrderByField::Duration => "duration",
         OrderByField::TimeAdded => "time_added",
+        OrderByField::FileTimeAdded => "file_time_added",
+        OrderByField::LastSeen => "last_seen",
         OrderByField::AudioTracks => "audio_tracks",
         OrderByField::VideoTracks => "video_tracks",
         OrderByField::SubtitleTracks => "subtitle_tracks",
@@ -1317,6 +1595,8 @@ fn get_column_expr(column: Column) -> Expr {
         Column::Height => Expr::col((Items::Table, Items::Height)),
         Column::Duration => Expr::col((Items::Table, Items::Duration)),
         Column::TimeAdded => Expr::col((Items::Table, Items::TimeAdded)),
+        Column::FileTimeAdded => Expr::col((Files::Table, Files::TimeAdded)),
+        Column::LastSeen => Expr::col((Files::Table, Files::LastSeen)),
         Column::AudioTracks => Expr::col((Items::Table, Items::AudioTracks)),
         Column::VideoTracks => Expr::col((Items::Table, Items::VideoTracks)),
         Column::SubtitleTracks => Expr::col((Items::Table, Items::SubtitleTracks)),
@@ -1334,6 +1614,51 @@ fn get_column_expr(column: Column) -> Expr {
         Column::SetterName => Expr::col((Setters::Table, Setters::Name)),
         Column::DataIndex => Expr::col((ItemData::Table, ItemData::Idx)),
         Column::SourceId => Expr::col((ItemData::Table, ItemData::SourceId)),
+        // SQLite's multi-argument min()/max() return NULL if any argument is
+        // NULL, so items without dimensions never match.
+        Column::MinDimension => Func::cust("min")
+            .args([
+                Expr::col((Items::Table, Items::Width)),
+                Expr::col((Items::Table, Items::Height)),
+            ])
+            .into(),
+        Column::MaxDimension => Func::cust("max")
+            .args([
+                Expr::col((Items::Table, Items::Width)),
+                Expr::col((Items::Table, Items::Height)),
+            ])
+            .into(),
+        Column::Megapixels => Expr::col((Items::Table, Items::Width))
+            .mul(Expr::col((Items::Table, Items::Height)))
+            .div(Expr::cust("1000000.0")),
+        // Paths keep the platform's separators, so strip every trailing
+        // character that is not `/` or `\`: the set rtrim() removes is the
+        // path with its separators deleted.
+        Column::Directory => {
+            let path = Expr::col((Files::Table, Files::Path));
+            let separators_removed = Func::cust("replace").args([
+                Func::cust("replace")
+                    .args([path.clone(), Expr::val("/"), Expr::val("")])
+                    .into(),
+                Expr::val("\\"),
+                Expr::val(""),
+            ]);
+            Func::cust("rtrim")
+                .args([path, separators_removed.into()])
+                .into()
+        }
+        Column::TypePrefix => {
+            let item_type = Expr::col((Items::Table, Items::Type));
+            let slash: Expr = Func::cust("instr")
+                .args([item_type.clone(), Expr::val("/")])
+                .into();
+            Expr::case(
+                slash.clone().gt(0),
+                Func::cust("substr").args([item_type.clone(), Expr::val(1), slash.sub(1)]),
+            )
+            .finally(item_type)
+            .into()
+        }
     }
 }
 
@@ -1365,6 +1690,8 @@ fn get_order_by_expr(field: OrderByField, seed: i64) -> Expr {
         OrderByField::Height => Expr::col((Items::Table, Items::Height)),
         OrderByField::Duration => Expr::col((Items::Table, Items::Duration)),
         OrderByField::TimeAdded => Expr::col((Items::Table, Items::TimeAdded)),
+        OrderByField::FileTimeAdded => Expr::col((Files::Table, Files::TimeAdded)),
+        OrderByField::LastSeen => Expr::col((Files::Table, Files::LastSeen)),
         OrderByField::AudioTracks => Expr::col((Items::Table, Items::AudioTracks)),
         OrderByField::VideoTracks => Expr::col((Items::Table, Items::VideoTracks)),
         OrderByField::SubtitleTracks => Expr::col((Items::Table, Items::SubtitleTracks)),
@@ -1385,6 +1712,15 @@ fn get_order_by_expr(field: OrderByField, seed: i64) -> Expr {
     }
 }
 
+/// Pseudo-columns computed from width and height. They can be compared in
+/// match filters but not selected or partitioned by.
+pub(crate) fn is_derived_column(column: Column) -> bool {
+    matches!(
+        column,
+        Column::MinDimension | Column::MaxDimension | Column::Megapixels
+    )
+}
+
 fn is_text_column(column: Column) -> bool {
     matches!(
         column,
@@ -1426,6 +1762,12 @@ fn entity_to_data_type(entity: EntityType) -> &'static str {
     }
 }
 
+#[derive(sea_query::Iden)]
+enum ItemPrimaryFiles {
+    Table,
+    FileId,
+}
+
 #[derive(sea_query::Iden)]
 enum Files {
     Table,
@@ -1435,6 +1777,8 @@ enum Files {
     Path,
     Filename,
     LastModified,
+    TimeAdded,
+    LastSeen,
 }
 
 #[cfg(test)]
@@ -1536,6 +1880,136 @@ mod tests {
         );
     }
 
+    // Ensures file_time_added reads the file row, not the item's time_added.
+    #[test]
+    fn file_time_added_selects_and_orders_by_file_column() {
+        let query: PqlQuery = serde_json::from_value(serde_json::json!({
+            "select": ["path", "time_added", "file_time_added"],
+            "order_by": [{"order_by": "file_time_added", "order": "asc"}]
+        }))
+        .expect("deserialize PqlQuery");
+        let built = build_query(query, false).expect("build");
+        let sql = built.query.to_string(SqliteQueryBuilder);
+        assert!(
+            sql.contains(r#""files"."time_added" AS "file_time_added""#),
+            "{sql}"
+        );
+        assert!(sql.contains(r#""items"."time_added" AS "time_added""#), "{sql}");
+        assert!(sql.contains(r#"ORDER BY "files"."time_added" ASC"#), "{sql}");
+    }
+
+    // Ensures last_seen selects and orders by the file row's column.
+    #[test]
+    fn last_seen_selects_and_orders_by_file_column() {
+        let query: PqlQuery = serde_json::from_value(serde_json::json!({
+            "select": ["path", "last_seen"],
+            "order_by": [{"order_by": "last_seen", "order": "desc"}]
+        }))
+        .expect("deserialize PqlQuery");
+        let built = build_query(query, false).expect("build");
+        let sql = built.query.to_string(SqliteQueryBuilder);
+        assert!(sql.contains(r#""files"."last_seen" AS "last_seen""#), "{sql}");
+        assert!(sql.contains(r#"ORDER BY "files"."last_seen" DESC"#), "{sql}");
+    }
+
+    fn sorted_match_text(text: &str, priority: i32, direction: &str) -> serde_json::Value {
+        serde_json::json!({
+            "order_by": true,
+            "priority": priority,
+            "direction": direction,
+            "match_text": { "match": text }
+        })
+    }
+
+    fn built_sql(query: PqlQuery) -> String {
+        let built = build_query(query, false).expect("build");
+        built
+            .query
+            .with(built.with_clause.expect("with clause"))
+            .to_string(SqliteQueryBuilder)
+    }
+
+    // Sortable OR branches used to be ordered one priority after another, so
+    // every row of one branch sorted above all rows of the other, and a
+    // branch sorted the other way was ordered in the first branch's
+    // direction. Each row must now be ordered by its best branch rank.
+    #[test]
+    fn or_branches_order_by_their_best_rank() {
+        for optimize in [false, true] {
+            let mut query: PqlQuery = serde_json::from_value(serde_json::json!({
+                "query": { "or_": [
+                    sorted_match_text("hello", 0, "asc"),
+                    sorted_match_text("world", 5, "desc")
+                ] }
+            }))
+            .expect("query");
+            query.optimize = optimize;
+            let sql = built_sql(query);
+            let order_by = &sql[sql.rfind("ORDER BY").expect("order by")..];
+            // The group follows the first branch (asc); the desc branch's
+            // rank is negated so that MIN picks either branch's best.
+            assert!(
+                order_by.starts_with(
+                    "ORDER BY min(COALESCE(\"n0_MatchText\".\"order_rank\", \
+                     9223372036854775805), COALESCE(0 - \"n1_MatchText\".\"order_rank\", \
+                     9223372036854775805)) ASC NULLS LAST"
+                ),
+                "branch ranks must be combined, got: {order_by}"
+            );
+            assert_eq!(order_by.matches("order_rank").count(), 2, "{order_by}");
+        }
+
+        // Ranks outside the OR keep their own priority.
+        let query: PqlQuery = serde_json::from_value(serde_json::json!({
+            "query": { "and_": [
+                sorted_match_text("first", 9, "asc"),
+                { "or_": [
+                    sorted_match_text("hello", 0, "asc"),
+                    sorted_match_text("world", 5, "asc")
+                ] }
+            ] }
+        }))
+        .expect("query");
+        let sql = built_sql(query);
+        let order_by = &sql[sql.rfind("ORDER BY").expect("order by")..];
+        assert!(
+            order_by.starts_with(
+                "ORDER BY \"n0_MatchText\".\"order_rank\" ASC NULLS LAST, \
+                 min(COALESCE(\"n1_MatchText\".\"order_rank\""
+            ),
+            "{order_by}"
+        );
+    }
+
+    // Partitioned queries order the window and the outer query by each
+    // rank's selected label; every label is compared in its own direction,
+    // including the root CTE's rank, which is selected unaliased.
+    #[test]
+    fn mixed_direction_rank_groups_orient_each_partition_label() {
+        for (operator, asc_label, desc_label) in [
+            ("and_", "o0_n0_MatchText_rank", "order_rank"),
+            ("or_", "o0_n0_MatchText_rank", "o0_n1_MatchText_rank"),
+        ] {
+            let query: PqlQuery = serde_json::from_value(serde_json::json!({
+                "partition_by": ["item_id"],
+                "query": { operator: [
+                    sorted_match_text("hello", 0, "asc"),
+                    sorted_match_text("world", 0, "desc")
+                ] }
+            }))
+            .expect("query");
+            let sql = built_sql(query);
+            for alias in ["select_cte", "partition_cte"] {
+                let expected = format!(
+                    "min(COALESCE(\"{alias}\".\"{asc_label}\", 9223372036854775805), \
+                     COALESCE(0 - \"{alias}\".\"{desc_label}\", 9223372036854775805)) \
+                     ASC NULLS LAST"
+                );
+                assert!(sql.contains(&expected), "{operator}: {expected} in {sql}");
+            }
+        }
+    }
+
     #[test]
     fn empty_partition_by_count_query_matches_no_partitioning() {
         let with_empty =
@@ -1563,6 +2037,393 @@ mod tests {
                 .contains("partition_rownum")
         );
     }
+
+    async fn seed_or_db() -> crate::db::migrations::InMemoryDatabases {
+        crate::db::sql_functions::ensure_sqlite_extensions().expect("sqlite extensions");
+        let mut dbs = crate::db::migrations::setup_test_databases().await;
+        for statement in [
+            r#"INSERT INTO items (id, sha256, md5, type, size, time_added) VALUES
+                (1, 'sha_1', 'md5_1', 'image/png', 100, '2024-01-01T00:00:00'),
+                (2, 'sha_2', 'md5_2', 'image/jpeg', 2000, '2024-01-02T00:00:00'),
+                (3, 'sha_3', 'md5_3', 'video/mp4', 30000, '2024-01-03T00:00:00'),
+                (4, 'sha_4', 'md5_4', 'image/png', 400000, '2024-01-04T00:00:00')"#,
+            "INSERT INTO file_scans (id, start_time, path) VALUES (1, '2024-01-01T00:00:00', '/data')",
+            r#"INSERT INTO files (id, sha256, item_id, path, filename, last_modified, scan_id, available) VALUES
+                (10, 'sha_1', 1, '/data/a.png', 'a.png', '2024-01-01T00:00:00', 1, 1),
+                (11, 'sha_1', 1, '/data/copy/a.png', 'a.png', '2024-01-01T00:00:00', 1, 1),
+                (12, 'sha_2', 2, '/data/b.jpg', 'b.jpg', '2024-01-02T00:00:00', 1, 1),
+                (13, 'sha_3', 3, '/data/c.mp4', 'c.mp4', '2024-01-03T00:00:00', 1, 1),
+                (14, 'sha_4', 4, '/data/d.png', 'd.png', '2024-01-04T00:00:00', 1, 1)"#,
+            "INSERT INTO setters (id, name) VALUES (1, 'tagger'), (2, 'ocr')",
+            r#"INSERT INTO item_data (id, item_id, setter_id, data_type, idx, is_origin) VALUES
+                (20, 1, 1, 'tags', 0, 1),
+                (21, 2, 1, 'tags', 0, 1),
+                (22, 3, 1, 'tags', 0, 1),
+                (30, 1, 2, 'text', 0, 1),
+                (31, 1, 2, 'text', 1, 1),
+                (32, 3, 2, 'text', 0, 1),
+                (33, 4, 2, 'text', 0, 1)"#,
+            "INSERT INTO tags (id, namespace, name) VALUES (1, 'danbooru', 'cat'), (2, 'danbooru', 'dog'), (3, 'danbooru', 'bird')",
+            r#"INSERT INTO tags_items (item_data_id, tag_id, confidence) VALUES
+                (20, 1, 0.9), (20, 2, 0.8), (21, 2, 0.7), (22, 3, 0.6)"#,
+            r#"INSERT INTO extracted_text (id, language, language_confidence, confidence, text, text_length) VALUES
+                (30, 'en', 0.9, 0.5, 'hello', 5),
+                (31, 'fr', 0.9, 0.5, 'bonjour monde', 13),
+                (32, 'en', 0.9, 0.5, 'a longer caption', 16),
+                (33, 'de', 0.9, 0.5, 'hallo', 5)"#,
+        ] {
+            sqlx::query(statement)
+                .execute(&mut dbs.index_conn)
+                .await
+                .expect("seed");
+        }
+        dbs
+    }
+
+    async fn result_keys(
+        conn: &mut sqlx::SqliteConnection,
+        query: &serde_json::Value,
+        optimize: bool,
+    ) -> Vec<(i64, Option<i64>)> {
+        use sea_query_sqlx::SqlxBinder;
+        use sqlx::Row;
+
+        let mut query: PqlQuery = serde_json::from_value(query.clone()).expect("query");
+        query.page_size = 0;
+        query.optimize = optimize;
+        let text = matches!(query.entity, EntityType::Text);
+        let built = build_query(query, false).expect("build");
+        let statement = built.paginated_query();
+        let (sql, values) = match built.with_clause {
+            Some(with_clause) => statement.with(with_clause).build_sqlx(SqliteQueryBuilder),
+            None => statement.build_sqlx(SqliteQueryBuilder),
+        };
+        let rows = sqlx::query_with(sqlx::AssertSqlSafe(sql.as_str()), values)
+            .fetch_all(conn)
+            .await
+            .expect("execute");
+        let mut keys = rows
+            .iter()
+            .map(|row| {
+                let data_id = text.then(|| row.get::<i64, _>("data_id"));
+                (row.get::<i64, _>("file_id"), data_id)
+            })
+            .collect::<Vec<_>>();
+        keys.sort_unstable();
+        keys
+    }
+
+    // The optimized OR (merged matches, or UNION ALL + GROUP BY) must return
+    // exactly the rows of the UNION strategy, per file and per text-file pair.
+    #[tokio::test]
+    async fn optimized_or_matches_union_results() {
+        let mut dbs = seed_or_db().await;
+        let tags = |tag: &str| serde_json::json!({ "match_tags": { "tags": [tag] } });
+        let cases = [
+            serde_json::json!({
+                "query": { "or_": [tags("cat"), tags("dog"), {
+                    "match_tags": { "tags": ["bird"] }, "order_by": true
+                }] }
+            }),
+            serde_json::json!({
+                "query": { "or_": [
+                    { "match": { "eq": { "type": "image/png" } } },
+                    { "match": { "gt": { "size": 1000 } } }
+                ] }
+            }),
+            serde_json::json!({
+                "query": { "and_": [
+                    { "or_": [{ "match": { "eq": { "type": "video/mp4" } } }, tags("dog")] },
+                    { "not_": { "match": { "eq": { "path": "/data/b.jpg" } } } }
+                ] }
+            }),
+            serde_json::json!({
+                "entity": "text",
+                "query": { "or_": [
+                    { "match": { "eq": { "language": "en" } } },
+                    { "match": { "gt": { "text_length": 10 } } }
+                ] }
+            }),
+            serde_json::json!({
+                "entity": "text",
+                "query": { "or_": [{ "match": { "eq": { "language": "de" } } }, tags("cat")] }
+            }),
+            serde_json::json!({
+                "query": { "or_": [
+                    sorted_match_text("hello", 0, "asc"),
+                    sorted_match_text("caption", 5, "desc")
+                ] }
+            }),
+        ];
+        for case in &cases {
+            let union = result_keys(&mut dbs.index_conn, case, false).await;
+            let optimized = result_keys(&mut dbs.index_conn, case, true).await;
+            assert!(!union.is_empty(), "{case}");
+            assert_eq!(union, optimized, "{case}");
+        }
+
+        let optimized_sql = |case: &serde_json::Value| {
+            let mut query: PqlQuery = serde_json::from_value(case.clone()).expect("query");
+            query.optimize = true;
+            let built = build_query(query, false).expect("build");
+            built
+                .query
+                .with(built.with_clause.expect("with clause"))
+                .to_string(SqliteQueryBuilder)
+        };
+        assert!(optimized_sql(&cases[0]).contains("UNION ALL"));
+        assert!(!optimized_sql(&cases[1]).contains("UNION"));
+    }
+
+    // Ensures grouping files by item picks the pinned primary file over the
+    // one the order would pick, with and without filters, and falls back to
+    // the order once the pinned file is deleted.
+    #[tokio::test]
+    async fn partition_by_item_prefers_pinned_file() {
+        let mut dbs = seed_or_db().await;
+        let unfiltered = serde_json::json!({
+            "partition_by": ["item_id"],
+            "order_by": [{ "order_by": "path", "order": "asc" }]
+        });
+        let filtered = serde_json::json!({
+            "partition_by": ["item_id"],
+            "order_by": [{ "order_by": "path", "order": "asc" }],
+            "query": { "match": { "eq": { "type": "image/png" } } }
+        });
+        for query in [&unfiltered, &filtered] {
+            let keys = result_keys(&mut dbs.index_conn, query, false).await;
+            assert!(keys.contains(&(10, None)) && !keys.contains(&(11, None)), "{query}");
+        }
+
+        crate::db::files::set_primary_file(&mut dbs.index_conn, 1, Some(11))
+            .await
+            .expect("pin");
+        for query in [&unfiltered, &filtered] {
+            let keys = result_keys(&mut dbs.index_conn, query, false).await;
+            assert!(keys.contains(&(11, None)) && !keys.contains(&(10, None)), "{query}");
+        }
+
+        sqlx::query("DELETE FROM files WHERE id = 11")
+            .execute(&mut dbs.index_conn)
+            .await
+            .expect("delete");
+        let pins: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM item_primary_files")
+            .fetch_one(&mut dbs.index_conn)
+            .await
+            .expect("count");
+        assert_eq!(pins, 0);
+        let keys = result_keys(&mut dbs.index_conn, &unfiltered, false).await;
+        assert!(keys.contains(&(10, None)));
+    }
+
+    async fn attributions(
+        conn: &mut sqlx::SqliteConnection,
+        query: serde_json::Value,
+    ) -> Vec<(i64, Vec<(String, bool)>)> {
+        use sea_query_sqlx::SqlxBinder;
+        use sqlx::Row;
+
+        let mut query: PqlQuery = serde_json::from_value(query).expect("query");
+        query.page_size = 0;
+        let built = build_query(query, false).expect("build");
+        let statement = built.paginated_query();
+        let (sql, values) = match built.with_clause {
+            Some(with_clause) => statement.with(with_clause).build_sqlx(SqliteQueryBuilder),
+            None => statement.build_sqlx(SqliteQueryBuilder),
+        };
+        let rows = sqlx::query_with(sqlx::AssertSqlSafe(sql.as_str()), values)
+            .fetch_all(conn)
+            .await
+            .expect("execute");
+        let mut results = rows
+            .iter()
+            .map(|row| {
+                let mut matched = built
+                    .attribution_columns
+                    .iter()
+                    .map(|(label, name)| (name.clone(), row.get::<bool, _>(label.as_str())))
+                    .collect::<Vec<_>>();
+                matched.sort();
+                (row.get::<i64, _>("file_id"), matched)
+            })
+            .collect::<Vec<_>>();
+        results.sort();
+        results
+    }
+
+    // Ensures attribute_filters reports which named filter matched each row,
+    // including filters that neither order nor select, and leaves the count
+    // query untouched.
+    #[tokio::test]
+    async fn attribute_filters_reports_matching_filters() {
+        let mut dbs = seed_or_db().await;
+        let query = serde_json::json!({
+            "attribute_filters": true,
+            "query": { "or_": [
+                { "match_tags": { "tags": ["cat"] }, "name": "tags" },
+                { "match_text": { "match": "hallo" }, "name": "ocr", "order_by": true },
+                { "match_tags": { "tags": ["bird"] } }
+            ] }
+        });
+        let yes = |name: &str| (name.to_string(), true);
+        let no = |name: &str| (name.to_string(), false);
+        assert_eq!(
+            attributions(&mut dbs.index_conn, query.clone()).await,
+            vec![
+                (10, vec![no("ocr"), yes("tags")]),
+                (11, vec![no("ocr"), yes("tags")]),
+                (13, vec![no("ocr"), no("tags")]),
+                (14, vec![yes("ocr"), no("tags")]),
+            ]
+        );
+
+        // The filter at the root produced every row.
+        let root = serde_json::json!({
+            "attribute_filters": true,
+            "query": { "match_tags": { "tags": ["dog"] }, "name": "dog" }
+        });
+        assert_eq!(
+            attributions(&mut dbs.index_conn, root).await,
+            vec![
+                (10, vec![yes("dog")]),
+                (11, vec![yes("dog")]),
+                (12, vec![yes("dog")])
+            ]
+        );
+
+        let mut unflagged: PqlQuery = serde_json::from_value(query.clone()).expect("query");
+        unflagged.attribute_filters = false;
+        assert!(
+            build_query(unflagged, false)
+                .unwrap()
+                .attribution_columns
+                .is_empty()
+        );
+        let counted: PqlQuery = serde_json::from_value(query).expect("query");
+        let count = build_query(counted, true).unwrap();
+        assert!(count.attribution_columns.is_empty());
+        assert!(
+            !count
+                .query
+                .with(count.with_clause.expect("with clause"))
+                .to_string(SqliteQueryBuilder)
+                .contains("attr_")
+        );
+    }
+
+    async fn labelled_groups(
+        conn: &mut sqlx::SqliteConnection,
+        query: serde_json::Value,
+        label: &str,
+    ) -> (Vec<(String, i64)>, i64) {
+        use sea_query_sqlx::SqlxBinder;
+        use sqlx::Row;
+
+        let run = |built: PqlBuilderResult| {
+            let statement = built.paginated_query();
+            match built.with_clause {
+                Some(with_clause) => statement.with(with_clause).build_sqlx(SqliteQueryBuilder),
+                None => statement.build_sqlx(SqliteQueryBuilder),
+            }
+        };
+        let mut parsed: PqlQuery = serde_json::from_value(query.clone()).expect("query");
+        parsed.page_size = 0;
+        let (sql, values) = run(build_query(parsed, false).expect("build"));
+        let rows = sqlx::query_with(sqlx::AssertSqlSafe(sql.as_str()), values)
+            .fetch_all(&mut *conn)
+            .await
+            .expect("execute");
+        let mut groups = rows
+            .iter()
+            .map(|row| (row.get::<String, _>(label), row.get::<i64, _>("file_id")))
+            .collect::<Vec<_>>();
+        groups.sort();
+
+        let parsed: PqlQuery = serde_json::from_value(query).expect("query");
+        let (sql, values) = run(build_query(parsed, true).expect("count"));
+        let total = sqlx::query_with(sqlx::AssertSqlSafe(sql.as_str()), values)
+            .fetch_one(&mut *conn)
+            .await
+            .expect("count")
+            .get::<i64, _>("total");
+        (groups, total)
+    }
+
+    // Ensures partitioning by directory keeps one file per folder, labels it
+    // through the selected column, and treats bare filenames, trailing
+    // separators and Windows separators like the stored paths do.
+    #[tokio::test]
+    async fn partition_by_directory_keeps_one_file_per_folder() {
+        let mut dbs = seed_or_db().await;
+        sqlx::query(
+            r#"INSERT INTO files (id, sha256, item_id, path, filename, last_modified, scan_id, available) VALUES
+                (15, 'sha_2', 2, 'loose.jpg', 'loose.jpg', '2024-01-02T00:00:00', 1, 1),
+                (16, 'sha_3', 3, '/data/clips/', '', '2024-01-03T00:00:00', 1, 1),
+                (17, 'sha_4', 4, 'C:\pics\e.png', 'e.png', '2024-01-04T00:00:00', 1, 1),
+                (18, 'sha_4', 4, 'C:\pics\f.png', 'f.png', '2024-01-04T00:00:00', 1, 1)"#,
+        )
+        .execute(&mut dbs.index_conn)
+        .await
+        .expect("seed");
+
+        let (groups, total) = labelled_groups(
+            &mut dbs.index_conn,
+            serde_json::json!({
+                "partition_by": ["directory"],
+                "select": ["directory"],
+                "order_by": [{ "order_by": "path", "order": "asc" }]
+            }),
+            "directory",
+        )
+        .await;
+        assert_eq!(
+            groups,
+            vec![
+                (String::new(), 15),
+                ("/data/".to_string(), 10),
+                ("/data/clips/".to_string(), 16),
+                ("/data/copy/".to_string(), 11),
+                (r"C:\pics\".to_string(), 17),
+            ]
+        );
+        assert_eq!(total, 5);
+    }
+
+    // Ensures partitioning by type prefix groups MIME types by their major
+    // part and keeps a type without a slash whole.
+    #[tokio::test]
+    async fn partition_by_type_prefix_groups_mime_types() {
+        let mut dbs = seed_or_db().await;
+        for statement in [
+            "INSERT INTO items (id, sha256, md5, type, size, time_added) VALUES (5, 'sha_5', 'md5_5', 'unknown', 1, '2024-01-05T00:00:00')",
+            "INSERT INTO files (id, sha256, item_id, path, filename, last_modified, scan_id, available) VALUES (19, 'sha_5', 5, '/data/e', 'e', '2024-01-05T00:00:00', 1, 1)",
+        ] {
+            sqlx::query(statement)
+                .execute(&mut dbs.index_conn)
+                .await
+                .expect("seed");
+        }
+
+        let (groups, total) = labelled_groups(
+            &mut dbs.index_conn,
+            serde_json::json!({
+                "partition_by": ["type_prefix"],
+                "select": ["type_prefix"],
+                "order_by": [{ "order_by": "path", "order": "asc" }]
+            }),
+            "type_prefix",
+        )
+        .await;
+        assert_eq!(
+            groups,
+            vec![
+                ("image".to_string(), 10),
+                ("unknown".to_string(), 19),
+                ("video".to_string(), 13),
+            ]
+        );
+        assert_eq!(total, 3);
+    }
 }
 
 #[derive(sea_query::Iden)]
@@ -1666,3 +2527,25 @@ enum Bookmarks {
     Sha256,
     TimeAdded,
 }
+
+#[derive(sea_query::Iden)]
+enum ItemMeta {
+    Table,
+    Sha256,
+    Key,
+    Value,
+}
+
+#[derive(sea_query::Iden)]
+enum ItemNotes {
+    Table,
+    Id,
+    User,
+    Sha256,
+}
+
+#[derive(sea_query::Iden)]
+enum ItemNotesFts {
+    Table,
+    Rowid,
+}
@@ -1,6 +1,11 @@
+use sea_query::{Expr, ExprTrait, Func};
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
+use crate::pql::preprocess::PqlError;
+
+use super::super::{CteRef, Embeddings, ExtraColumn, QueryState};
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema)]
 pub(crate) enum DistanceAggregation {
     #[serde(rename = "MIN")]
@@ -41,6 +46,31 @@ impl Default for DistanceFunction {
     }
 }
 
+/// `distance_func` between the embeddings column and the query: the closest
+/// of the prompt template `variants` when the query kept them separate, else
+/// `embedding`.
+pub(crate) fn query_distance(
+    distance_func: &'static str,
+    embedding: &[u8],
+    variants: &[Vec<u8>],
+) -> Expr {
+    let distance_to = |query: &[u8]| -> Expr {
+        Func::cust(distance_func)
+            .args([
+                Expr::col((Embeddings::Table, Embeddings::Embedding)),
+                Expr::val(query.to_vec()),
+            ])
+            .into()
+    };
+    match variants {
+        [] | [_] => distance_to(embedding),
+        // SQLite's multi-argument MIN is the scalar minimum, not the aggregate.
+        _ => Func::cust("MIN")
+            .args(variants.iter().map(|variant| distance_to(variant)))
+            .into(),
+    }
+}
+
 /// Index mode for vector filters (docs/vector-index-design.md).
 ///
 /// `auto` resolves to the default quant profile where its coverage is ready
@@ -73,3 +103,119 @@ pub(crate) struct QuantResolved {
     pub profile_id: i64,
     pub query_quant: Option<Vec<u8>>,
 }
+
+/// Column alias for the `normalize_score` similarity.
+pub(crate) const SCORE_COLUMN: &str = "norm_score";
+
+pub(crate) fn default_l2_scale() -> f64 {
+    1.0
+}
+
+/// Registers an embedding filter's `select_as` rank and `normalize_score`
+/// similarity as `extra` columns of its CTE.
+pub(crate) fn push_score_columns(
+    state: &mut QueryState,
+    cte: &CteRef,
+    select_as: Option<&String>,
+    normalize_score: Option<&String>,
+) {
+    for (column, alias) in [("order_rank", select_as), (SCORE_COLUMN, normalize_score)] {
+        if let Some(alias) = alias {
+            state.extra_columns.push(ExtraColumn {
+                column: column.to_string(),
+                cte: cte.clone(),
+                alias: alias.clone(),
+            });
+        }
+    }
+}
+
+/// Maps an aggregated distance onto a 0-1 similarity, 1 being identical.
+/// Both mappings are monotone, so scores order exactly like distances:
+///
+/// - cosine: `MAX(0, MIN(1, 1 - distance / 2))`, since sqlite-vec's cosine
+///   distance spans 0..2 (the clamp only absorbs float error);
+/// - L2: `1 / (1 + distance / l2_scale)`, which scores a distance of
+///   `l2_scale` as 0.5.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ScoreNormalization {
+    function: DistanceFunction,
+    l2_scale: f64,
+}
+
+impl ScoreNormalization {
+    pub(crate) fn new(function: DistanceFunction, l2_scale: f64) -> Result<Self, PqlError> {
+        if function == DistanceFunction::L2 && !(l2_scale.is_finite() && l2_scale > 0.0) {
+            return Err(PqlError::invalid("l2_scale must be a positive number"));
+        }
+        Ok(Self { function, l2_scale })
+    }
+
+    /// The normalization a filter's `normalize_score` alias asks for; an
+    /// empty alias means "not requested", like the other `select_*_as`
+    /// options.
+    pub(crate) fn requested(
+        alias: Option<&str>,
+        function: DistanceFunction,
+        l2_scale: f64,
+    ) -> Result<Option<Self>, PqlError> {
+        match alias {
+            Some(alias) if !alias.is_empty() => Self::new(function, l2_scale).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    pub(crate) fn score_expr(&self, distance: Expr) -> Expr {
+        match self.function {
+            DistanceFunction::Cosine => {
+                let similarity = Expr::val(1.0).sub(distance.div(Expr::val(2.0)));
+                Func::cust("MAX")
+                    .args([
+                        Expr::val(0.0),
+                        Func::cust("MIN").args([Expr::val(1.0), similarity]).into(),
+                    ])
+                    .into()
+            }
+            DistanceFunction::L2 => {
+                Expr::val(1.0).div(Expr::val(1.0).add(distance.div(Expr::val(self.l2_scale))))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sea_query::{Query, SqliteQueryBuilder};
+    use sea_query_sqlx::SqlxBinder;
+
+    async fn scores(normalization: ScoreNormalization, distances: &[f64]) -> Vec<f64> {
+        let mut dbs = crate::db::migrations::setup_test_databases().await;
+        let mut results = Vec::new();
+        for distance in distances {
+            let (sql, values) = Query::select()
+                .expr(normalization.score_expr(Expr::val(*distance)))
+                .build_sqlx(SqliteQueryBuilder);
+            let score: f64 = sqlx::query_scalar_with(sqlx::AssertSqlSafe(sql.as_str()), values)
+                .fetch_one(&mut dbs.index_conn)
+                .await
+                .expect("score");
+            results.push(score);
+        }
+        results
+    }
+
+    // Pins the documented formulas: clients display these values as-is.
+    #[tokio::test]
+    async fn normalized_scores_follow_documented_formulas() {
+        let cosine = ScoreNormalization::new(DistanceFunction::Cosine, 0.0).unwrap();
+        assert_eq!(
+            scores(cosine, &[0.0, 0.5, 1.0, 2.0, 2.000001, -0.000001]).await,
+            [1.0, 0.75, 0.5, 0.0, 0.0, 1.0]
+        );
+        let l2 = ScoreNormalization::new(DistanceFunction::L2, 2.0).unwrap();
+        assert_eq!(scores(l2, &[0.0, 2.0, 6.0]).await, [1.0, 0.5, 0.25]);
+        assert!(ScoreNormalization::new(DistanceFunction::L2, 0.0).is_err());
+        assert!(ScoreNormalization::new(DistanceFunction::L2, f64::NAN).is_err());
+    }
+}
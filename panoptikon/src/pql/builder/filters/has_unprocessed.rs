@@ -1,12 +1,11 @@
-use sea_query::{Alias, Expr, ExprTrait, JoinType, Query};
+use sea_query::{Alias, Expr, ExprTrait, Query};
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
 use crate::pql::preprocess::PqlError;
 
 use super::super::{
-    CteRef, ItemData, JoinedTables, QueryState, Setters, apply_group_by, get_std_group_by,
-    select_std_from_cte, wrap_query,
+    CteRef, ItemData, JoinedTables, QueryState, select_std_from_cte, setter_id_by_name, wrap_query,
 };
 use super::FilterCompiler;
 
@@ -34,25 +33,13 @@ impl FilterCompiler for HasUnprocessedData {
         let mut not_exists_subquery = Query::select();
         not_exists_subquery.expr(Expr::val(1));
         not_exists_subquery.from_as(ItemData::Table, derived_alias.clone());
-        not_exists_subquery.join(
-            JoinType::InnerJoin,
-            Setters::Table,
-            Expr::col((Setters::Table, Setters::Id))
-                .equals((derived_alias.clone(), ItemData::SetterId)),
-        );
         not_exists_subquery.and_where(
             Expr::col((derived_alias.clone(), ItemData::SourceId))
                 .equals((src_alias.clone(), ItemData::Id)),
         );
-        not_exists_subquery
-            .and_where(Expr::col((Setters::Table, Setters::Name)).eq(args.setter_name.clone()));
-
-        let mut query = select_std_from_cte(context, state);
-        query.join_as(
-            JoinType::InnerJoin,
-            ItemData::Table,
-            src_alias.clone(),
-            Expr::col((src_alias.clone(), ItemData::ItemId)).equals(context.column_ref("item_id")),
+        not_exists_subquery.and_where(
+            Expr::col((derived_alias.clone(), ItemData::SetterId))
+                .eq(setter_id_by_name(&args.setter_name)),
         );
 
         let data_types = args
@@ -61,10 +48,21 @@ impl FilterCompiler for HasUnprocessedData {
             .cloned()
             .map(Expr::val)
             .collect::<Vec<_>>();
-        query.and_where(Expr::col((src_alias.clone(), ItemData::DataType)).is_in(data_types));
-        query.and_where(Expr::col((src_alias.clone(), ItemData::IsPlaceholder)).eq(0));
-        query.and_where(Expr::not_exists(not_exists_subquery.to_owned()));
-        apply_group_by(&mut query, get_std_group_by(context, state));
+        // Source data is matched by item_id for every entity, as before; the
+        // EXISTS keeps one row per context row without a GROUP BY.
+        let mut exists_subquery = Query::select();
+        exists_subquery.expr(Expr::val(1));
+        exists_subquery.from_as(ItemData::Table, src_alias.clone());
+        exists_subquery.and_where(
+            Expr::col((src_alias.clone(), ItemData::ItemId)).equals(context.column_ref("item_id")),
+        );
+        exists_subquery
+            .and_where(Expr::col((src_alias.clone(), ItemData::DataType)).is_in(data_types));
+        exists_subquery.and_where(Expr::col((src_alias.clone(), ItemData::IsPlaceholder)).eq(0));
+        exists_subquery.and_where(Expr::not_exists(not_exists_subquery.to_owned()));
+
+        let mut query = select_std_from_cte(context, state);
+        query.and_where(Expr::exists(exists_subquery.to_owned()));
 
         let joined_tables = JoinedTables::default();
         let cte = wrap_query(state, query, context, cte_name, &joined_tables);
@@ -79,9 +77,76 @@ mod tests {
     use crate::pql::model::{EntityType, QueryElement};
     use serde_json::json;
 
+    use super::super::super::{Setters, apply_group_by, get_std_group_by};
     use super::super::test_support::{
-        build_base_state, build_begin_cte, render_filter_sql, run_full_pql_query,
+        assert_constant_setter_lookup, build_base_state, build_begin_cte, explain_pql_query,
+        filter_result_ids, render_filter_sql, run_full_pql_query, seed_derived_data,
     };
+    use crate::db::migrations::setup_test_databases;
+    use crate::pql::model::PqlQuery;
+    use sea_query::JoinType;
+
+    /// The join + GROUP BY build this filter used before switching to EXISTS,
+    /// kept to check that both return the same rows.
+    struct LegacyHasUnprocessedData(HasUnprocessedData);
+
+    impl FilterCompiler for LegacyHasUnprocessedData {
+        fn build(&self, context: &CteRef, state: &mut QueryState) -> Result<CteRef, PqlError> {
+            let args = &self.0.has_data_unprocessed;
+            let cte_name = format!("n{}_LegacyHasUnprocessedData", state.cte_counter);
+            let src_alias = Alias::new("src_item_data");
+            let derived_alias = Alias::new("derived_data");
+
+            let mut not_exists_subquery = Query::select();
+            not_exists_subquery.expr(Expr::val(1));
+            not_exists_subquery.from_as(ItemData::Table, derived_alias.clone());
+            not_exists_subquery.join(
+                JoinType::InnerJoin,
+                Setters::Table,
+                Expr::col((Setters::Table, Setters::Id))
+                    .equals((derived_alias.clone(), ItemData::SetterId)),
+            );
+            not_exists_subquery.and_where(
+                Expr::col((derived_alias.clone(), ItemData::SourceId))
+                    .equals((src_alias.clone(), ItemData::Id)),
+            );
+            not_exists_subquery
+                .and_where(Expr::col((Setters::Table, Setters::Name)).eq(args.setter_name.clone()));
+
+            let mut query = select_std_from_cte(context, state);
+            query.join_as(
+                JoinType::InnerJoin,
+                ItemData::Table,
+                src_alias.clone(),
+                Expr::col((src_alias.clone(), ItemData::ItemId))
+                    .equals(context.column_ref("item_id")),
+            );
+            let data_types = args
+                .data_types
+                .iter()
+                .cloned()
+                .map(Expr::val)
+                .collect::<Vec<_>>();
+            query.and_where(Expr::col((src_alias.clone(), ItemData::DataType)).is_in(data_types));
+            query.and_where(Expr::col((src_alias.clone(), ItemData::IsPlaceholder)).eq(0));
+            query.and_where(Expr::not_exists(not_exists_subquery.to_owned()));
+            apply_group_by(&mut query, get_std_group_by(context, state));
+
+            let joined_tables = JoinedTables::default();
+            let cte = wrap_query(state, query, context, cte_name, &joined_tables);
+            state.cte_counter += 1;
+            Ok(cte)
+        }
+    }
+
+    fn unprocessed(setter: &str, data_types: &[&str]) -> HasUnprocessedData {
+        HasUnprocessedData {
+            has_data_unprocessed: DerivedDataArgs {
+                setter_name: setter.to_string(),
+                data_types: data_types.iter().map(|kind| kind.to_string()).collect(),
+            },
+        }
+    }
 
     #[test]
     fn has_unprocessed_builds_sql() {
@@ -97,6 +162,7 @@ mod tests {
         let sql = render_filter_sql(&filter, &mut state, &context);
         assert!(sql.contains("NOT EXISTS"));
         assert!(sql.contains("SELECT"));
+        assert!(!sql.contains("GROUP BY"));
     }
 
     #[tokio::test]
@@ -112,4 +178,59 @@ mod tests {
             .await
             .expect("has_unprocessed query");
     }
+
+    // Ensures the EXISTS build returns exactly the rows the join + GROUP BY
+    // build did, including placeholders, partly processed items and unknown
+    // setters.
+    #[tokio::test]
+    async fn has_unprocessed_matches_legacy_results() {
+        let mut dbs = setup_test_databases().await;
+        seed_derived_data(&mut dbs.index_conn).await;
+        let cases: [(&str, &[&str]); 5] = [
+            ("clip", &["text"]),
+            ("clip", &["text", "tags"]),
+            ("tagger", &["tags"]),
+            ("missing", &["text"]),
+            ("clip", &[]),
+        ];
+        for entity in [EntityType::File, EntityType::Text] {
+            for (setter, data_types) in cases {
+                let current = filter_result_ids(
+                    &unprocessed(setter, data_types),
+                    entity,
+                    &mut dbs.index_conn,
+                )
+                .await;
+                let legacy = filter_result_ids(
+                    &LegacyHasUnprocessedData(unprocessed(setter, data_types)),
+                    entity,
+                    &mut dbs.index_conn,
+                )
+                .await;
+                assert_eq!(current, legacy, "{entity:?} {setter} {data_types:?}");
+            }
+        }
+        let pending = filter_result_ids(
+            &unprocessed("clip", &["text"]),
+            EntityType::File,
+            &mut dbs.index_conn,
+        )
+        .await;
+        assert!(!pending.is_empty());
+    }
+
+    // Ensures the setter lookup inside NOT EXISTS is planned as a constant.
+    #[tokio::test]
+    async fn has_unprocessed_plan_uses_constant_setter_lookup() {
+        let plan = explain_pql_query(PqlQuery {
+            query: Some(QueryElement::HasUnprocessedData(unprocessed(
+                "clip",
+                &["text"],
+            ))),
+            entity: EntityType::File,
+            ..Default::default()
+        })
+        .await;
+        assert_constant_setter_lookup(&plan);
+    }
 }
@@ -6,13 +6,14 @@ use crate::pql::model::{OrderDirection, PartialSortableOptions, SortableOptions}
 use crate::pql::preprocess::PqlError;
 
 use super::super::{
-    BaseTable, CteRef, EmbeddingQuants, Embeddings, ExtraColumn, ExtractedText, ItemData, Items,
-    JoinedTables, OrderByFilter, QueryState, Setters, add_rank_column_expr, apply_group_by,
-    apply_sort_bounds, get_std_group_by, wrap_query,
+    BaseTable, CteRef, EmbeddingQuants, Embeddings, ExtractedText, ItemData, Items, JoinedTables,
+    OrderByFilter, QueryState, Setters, add_filter_attribution, add_rank_column_expr,
+    apply_group_by, apply_sort_bounds, get_std_group_by, wrap_query,
 };
 use super::FilterCompiler;
 use super::embedding_types::{
-    DistanceAggregation, DistanceFunction, IndexMode, QuantResolved, default_k,
+    DistanceAggregation, DistanceFunction, IndexMode, QuantResolved, SCORE_COLUMN,
+    ScoreNormalization, default_k, default_l2_scale, push_score_columns, query_distance,
 };
 use super::item_similarity::SourceArgs;
 use super::quant::{COARSE_DIST, COARSE_RANK, EXACT_DIST, assemble_two_stage};
@@ -28,6 +29,10 @@ pub(crate) struct SemanticImageArgs {
     pub query: String,
     #[serde(skip)]
     pub _embedding: Option<Vec<u8>>,
+    /// The normalized template variants, kept when `embed.separate_templates`
+    /// is set.
+    #[serde(skip)]
+    pub _template_embeddings: Vec<Vec<u8>>,
     #[serde(skip)]
     pub _distance_func_override: Option<DistanceFunction>,
     /// The image embedding model to use
@@ -38,6 +43,22 @@ pub(crate) struct SemanticImageArgs {
     /// The method to aggregate distances when an item has multiple embeddings. Default is MIN.
     #[serde(default)]
     pub distance_aggregation: DistanceAggregation,
+    /// Frame Indexes
+    ///
+    /// Only compare against the image embeddings at these indexes, e.g.
+    /// `[0]` for the first extracted frame of each video. Items that have
+    /// none of the listed indexes (such as single images when only later
+    /// frames are listed) are compared using all their embeddings. Text
+    /// embeddings searched through `clip_xmodal` are never restricted.
+    /// Must be non-empty and non-negative when given.
+    #[serde(default)]
+    pub frame_indexes: Option<Vec<i64>>,
+    /// Frame Aggregation
+    ///
+    /// Overrides `distance_aggregation` when `frame_indexes` is set;
+    /// ignored otherwise.
+    #[serde(default)]
+    pub frame_aggregation: Option<DistanceAggregation>,
     /// Embed The Query
     ///
     /// Embed the query using the model already specified in `model`.
@@ -80,6 +101,22 @@ pub(crate) struct SemanticImageArgs {
     /// pagination session.
     #[serde(default = "default_k")]
     pub k: i64,
+    /// Select Normalized Score As
+    ///
+    /// If set, a 0-1 similarity derived from the distance (1 = identical) is
+    /// included in the `extra` dict of each result under this key, alongside
+    /// the raw `order_rank` of `select_as`. Cosine distances map to
+    /// `max(0, min(1, 1 - distance / 2))`, L2 distances to
+    /// `1 / (1 + distance / l2_scale)`. Ordering is unaffected. Under a quant
+    /// profile only the `k` re-scored results get a score; the rest are null.
+    #[serde(default)]
+    pub normalize_score: Option<String>,
+    /// L2 Score Scale
+    ///
+    /// The L2 distance that `normalize_score` maps to 0.5. Must be positive;
+    /// ignored for cosine distances.
+    #[serde(default = "default_l2_scale")]
+    pub l2_scale: f64,
     #[serde(skip)]
     pub _quant: Option<QuantResolved>,
 }
@@ -128,6 +165,66 @@ fn default_sort_asc() -> SortableOptions {
     options
 }
 
+impl SemanticImageSearch {
+    /// Orders by distance to an embedding computed server-side (the refine
+    /// centroid) rather than one supplied in `query`.
+    pub(crate) fn from_embedding(
+        model: String,
+        embedding: Vec<u8>,
+        distance_aggregation: DistanceAggregation,
+        priority: i32,
+    ) -> Self {
+        let mut sort = default_sort_asc();
+        sort.priority = priority;
+        Self {
+            sort,
+            image_embeddings: SemanticImageArgs {
+                query: String::new(),
+                _embedding: Some(embedding),
+                _template_embeddings: Vec::new(),
+                _distance_func_override: None,
+                model,
+                distance_aggregation,
+                frame_indexes: None,
+                frame_aggregation: None,
+                embed: None,
+                clip_xmodal: false,
+                src_text: None,
+                index: IndexMode::default(),
+                variant: None,
+                k: default_k(),
+                normalize_score: None,
+                l2_scale: default_l2_scale(),
+                _quant: None,
+            },
+        }
+    }
+}
+
+/// Keeps image embeddings at one of `indexes`, plus every embedding of an
+/// item that has none of them under the same setter. Rows of other data
+/// types (xmodal text embeddings) always pass.
+fn frame_index_condition(indexes: &[i64]) -> Cond {
+    let frames = Alias::new("frames");
+    let mut listed = Query::select();
+    listed
+        .expr(Expr::val(1))
+        .from_as(ItemData::Table, frames.clone())
+        .and_where(
+            Expr::col((frames.clone(), ItemData::ItemId))
+                .equals((ItemData::Table, ItemData::ItemId)),
+        )
+        .and_where(
+            Expr::col((frames.clone(), ItemData::SetterId))
+                .equals((ItemData::Table, ItemData::SetterId)),
+        )
+        .and_where(Expr::col((frames.clone(), ItemData::Idx)).is_in(indexes.iter().copied()));
+    Cond::any()
+        .add(Expr::col((ItemData::Table, ItemData::DataType)).ne("clip"))
+        .add(Expr::col((ItemData::Table, ItemData::Idx)).is_in(indexes.iter().copied()))
+        .add(Expr::not_exists(listed))
+}
+
 /// Which vector payload the candidate skeleton joins.
 enum ImageVectorJoin {
     Embeddings,
@@ -287,6 +384,10 @@ impl SemanticImageSearch {
             query.and_where(cond.into());
         }
 
+        if let Some(indexes) = &args.frame_indexes {
+            query.and_where(frame_index_condition(indexes).into());
+        }
+
         query.join(
             JoinType::LeftJoin,
             Alias::new(context.name.as_str()),
@@ -303,20 +404,32 @@ impl SemanticImageSearch {
         (query, join_text)
     }
 
+    /// `frame_aggregation` when frames are restricted, else
+    /// `distance_aggregation`.
+    fn aggregation(&self) -> DistanceAggregation {
+        let args = &self.image_embeddings;
+        match (&args.frame_indexes, args.frame_aggregation) {
+            (Some(_), Some(aggregation)) => aggregation,
+            _ => args.distance_aggregation,
+        }
+    }
+
+    /// Cosine unless the model's metadata overrides it.
+    fn distance_function(&self) -> DistanceFunction {
+        self.image_embeddings
+            ._distance_func_override
+            .unwrap_or(DistanceFunction::Cosine)
+    }
+
     /// The full-precision rank aggregate, including confidence weighting.
     fn exact_rank_column(&self, embedding: &[u8]) -> Expr {
         let args = &self.image_embeddings;
-        let distance_func = match args._distance_func_override {
-            Some(DistanceFunction::L2) => "vec_distance_L2",
-            _ => "vec_distance_cosine",
+        let distance_func = match self.distance_function() {
+            DistanceFunction::L2 => "vec_distance_L2",
+            DistanceFunction::Cosine => "vec_distance_cosine",
         };
-        let vec_distance: Expr = Func::cust(distance_func)
-            .args([
-                Expr::col((Embeddings::Table, Embeddings::Embedding)),
-                Expr::val(embedding.to_vec()),
-            ])
-            .into();
-        let mut rank_column = match args.distance_aggregation {
+        let vec_distance = query_distance(distance_func, embedding, &args._template_embeddings);
+        let mut rank_column = match self.aggregation() {
             DistanceAggregation::Max => vec_distance.clone().max(),
             DistanceAggregation::Avg => vec_distance.clone().avg(),
             DistanceAggregation::Min => vec_distance.clone().min(),
@@ -384,21 +497,30 @@ impl SemanticImageSearch {
                     .into(),
             ])
             .into();
-        match self.image_embeddings.distance_aggregation {
+        match self.aggregation() {
             DistanceAggregation::Max => hamming.max(),
             DistanceAggregation::Avg => hamming.avg(),
             DistanceAggregation::Min => hamming.min(),
         }
     }
 
+    fn score_normalization(&self) -> Result<Option<ScoreNormalization>, PqlError> {
+        let args = &self.image_embeddings;
+        ScoreNormalization::requested(
+            args.normalize_score.as_deref(),
+            self.distance_function(),
+            args.l2_scale,
+        )
+    }
+
     fn register_outputs(&self, state: &mut QueryState, cte: &CteRef) {
-        if let Some(alias) = &self.sort.select_as {
-            state.extra_columns.push(ExtraColumn {
-                column: "order_rank".to_string(),
-                cte: cte.clone(),
-                alias: alias.clone(),
-            });
-        }
+        push_score_columns(
+            state,
+            cte,
+            self.sort.select_as.as_ref(),
+            self.image_embeddings.normalize_score.as_ref(),
+        );
+        add_filter_attribution(state, cte, &self.sort);
         if self.sort.order_by {
             state.order_list.push(OrderByFilter {
                 cte: cte.clone(),
@@ -439,6 +561,7 @@ impl FilterCompiler for SemanticImageSearch {
             return Ok(cte);
         }
 
+        let score = self.score_normalization()?;
         if let Some(quant) = &args._quant {
             let query_quant = quant
                 .query_quant
@@ -456,15 +579,21 @@ impl FilterCompiler for SemanticImageSearch {
             coarse.expr_as(self.coarse_rank_column(query_quant), Alias::new(COARSE_DIST));
 
             let k = args.k;
-            let (merge, merge_context) =
-                assemble_two_stage(state, &cte_name, coarse, &self.sort, |state, ranked| {
+            let (merge, merge_context) = assemble_two_stage(
+                state,
+                &cte_name,
+                coarse,
+                &self.sort,
+                score,
+                |state, ranked| {
                     let (mut head, _) =
                         self.candidate_skeleton(ranked, state, &ImageVectorJoin::Embeddings);
                     head.and_where(Expr::col(ranked.column_ref(COARSE_RANK)).lte(k));
                     apply_group_by(&mut head, get_std_group_by(ranked, state));
                     head.expr_as(self.exact_rank_column(embedding), Alias::new(EXACT_DIST));
                     head
-                });
+                },
+            );
 
             // The merge selects only from CTEs, so no base tables are
             // visible to the final query (same as the sort-bounds wrapper);
@@ -487,6 +616,12 @@ impl FilterCompiler for SemanticImageSearch {
             self.candidate_skeleton(context, state, &ImageVectorJoin::Embeddings);
         apply_group_by(&mut query, get_std_group_by(context, state));
         add_rank_column_expr(&mut query, &self.sort, self.exact_rank_column(embedding))?;
+        if let Some(score) = score {
+            query.expr_as(
+                score.score_expr(self.exact_rank_column(embedding)),
+                Alias::new(SCORE_COLUMN),
+            );
+        }
 
         let mut joined_tables = JoinedTables::default();
         joined_tables.mark(BaseTable::Items);
@@ -576,4 +711,68 @@ mod tests {
             .await
             .expect("semantic image query");
     }
+
+    // Ensures frame_indexes renders the per-item fallback subquery.
+    #[test]
+    fn semantic_image_frame_indexes_builds_sql() {
+        let mut filter: SemanticImageSearch = serde_json::from_value(json!({
+            "image_embeddings": {
+                "query": "hello",
+                "model": "clip/test",
+                "frame_indexes": [0, 2],
+                "frame_aggregation": "MAX"
+            }
+        }))
+        .expect("semantic image filter");
+        filter.image_embeddings._embedding = Some(vec![0, 0, 0, 0]);
+        filter.image_embeddings._distance_func_override = Some(DistanceFunction::Cosine);
+        let mut state = build_base_state(EntityType::File, false);
+        let context = build_begin_cte(&mut state);
+        let sql = render_filter_sql(&filter, &mut state, &context);
+        assert!(sql.contains("\"frames\""), "{sql}");
+        assert!(sql.contains("IN (0, 2)"), "{sql}");
+    }
+
+    // Ensures empty or negative frame_indexes are rejected before building.
+    #[test]
+    fn semantic_image_rejects_invalid_frame_indexes() {
+        use crate::pql::build_query;
+        use crate::pql::model::PqlQuery;
+
+        for indexes in [json!([]), json!([0, -1])] {
+            let mut filter: SemanticImageSearch = serde_json::from_value(json!({
+                "image_embeddings": {
+                    "query": "hello",
+                    "model": "clip/test",
+                    "frame_indexes": indexes
+                }
+            }))
+            .expect("semantic image filter");
+            filter.image_embeddings._embedding = Some(vec![0, 0, 0, 0]);
+            filter.image_embeddings._distance_func_override = Some(DistanceFunction::Cosine);
+            let query = PqlQuery {
+                query: Some(QueryElement::SemanticImageSearch(filter)),
+                ..Default::default()
+            };
+            assert!(build_query(query, false).is_err());
+        }
+    }
+
+    // Ensures the frame restriction's correlated subquery is valid SQLite.
+    #[tokio::test]
+    async fn semantic_image_frame_indexes_runs_full_query() {
+        let mut filter: SemanticImageSearch = serde_json::from_value(json!({
+            "image_embeddings": {
+                "query": "hello",
+                "model": "clip/test",
+                "frame_indexes": [0]
+            }
+        }))
+        .expect("semantic image filter");
+        filter.image_embeddings._embedding = Some(vec![0, 0, 0, 0]);
+        filter.image_embeddings._distance_func_override = Some(DistanceFunction::Cosine);
+        run_full_pql_query(QueryElement::SemanticImageSearch(filter), EntityType::File)
+            .await
+            .expect("semantic image query");
+    }
 }
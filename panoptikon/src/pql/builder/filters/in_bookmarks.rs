@@ -7,8 +7,8 @@ use crate::pql::preprocess::PqlError;
 
 use super::super::{
     BaseTable, Bookmarks, CteRef, ExtraColumn, Files, JoinedTables, OrderByFilter, QueryState,
-    add_rank_column_expr, apply_group_by, apply_sort_bounds, get_std_group_by, select_std_from_cte,
-    wrap_query,
+    add_filter_attribution, add_rank_column_expr, apply_group_by, apply_sort_bounds,
+    get_std_group_by, select_std_from_cte, wrap_query,
 };
 use super::FilterCompiler;
 
@@ -32,13 +32,33 @@ pub(crate) struct InBookmarksArgs {
     /// Include all sub-namespaces of the given namespaces (namespace.*).
     #[serde(default)]
     pub sub_ns: bool,
-    #[serde(default = "default_bookmarks_user")]
-    pub user: String,
+    /// Bookmarks User
+    ///
+    /// Defaults to `user`, or to the authenticated user when the search
+    /// request carries an identity.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
     /// Include Wildcard User
     ///
     /// Include bookmarks set to the wildcard user ('*').
     #[serde(default = "default_true")]
     pub include_wildcard: bool,
+    /// Rank By
+    ///
+    /// What `order_rank` holds: `time_added` of the item's most recent
+    /// matching bookmark, or `count`, the number of matching namespaces the
+    /// item is bookmarked in. A namespace bookmarked both by the user and
+    /// by the wildcard user counts once.
+    #[serde(default)]
+    pub rank_by: BookmarkRankBy,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum BookmarkRankBy {
+    #[default]
+    TimeAdded,
+    Count,
 }
 
 #[derive(Debug, Clone, Serialize, ToSchema)]
@@ -130,7 +150,9 @@ impl FilterCompiler for InBookmarks {
         }
 
         let mut users = Vec::new();
-        users.push(Expr::val(args.user.clone()));
+        users.push(Expr::val(
+            args.user.clone().unwrap_or_else(default_bookmarks_user),
+        ));
         if args.include_wildcard {
             users.push(Expr::val("*"));
         }
@@ -154,17 +176,25 @@ impl FilterCompiler for InBookmarks {
         }
 
         if !state.is_count_query {
-            let rank_expr = if args.filter {
+            let rank_expr = if !args.filter {
+                Expr::val(1)
+            } else {
                 // Aggregated: the query is grouped by the std columns, and an item
                 // can have several matching bookmarks (namespaces, wildcard user).
-                Func::max(Expr::col((
-                    user_data.clone(),
-                    Bookmarks::Table,
-                    Bookmarks::TimeAdded,
-                )))
-                .into()
-            } else {
-                Expr::val(1)
+                match args.rank_by {
+                    BookmarkRankBy::TimeAdded => Func::max(Expr::col((
+                        user_data.clone(),
+                        Bookmarks::Table,
+                        Bookmarks::TimeAdded,
+                    )))
+                    .into(),
+                    BookmarkRankBy::Count => Func::count_distinct(Expr::col((
+                        user_data.clone(),
+                        Bookmarks::Table,
+                        Bookmarks::Namespace,
+                    )))
+                    .into(),
+                }
             };
             add_rank_column_expr(&mut query, &self.sort, rank_expr)?;
         }
@@ -192,6 +222,7 @@ impl FilterCompiler for InBookmarks {
                     alias: alias.clone(),
                 });
             }
+            add_filter_attribution(state, &cte, &self.sort);
             if self.sort.order_by {
                 state.order_list.push(OrderByFilter {
                     cte: cte.clone(),
@@ -252,6 +283,98 @@ mod tests {
         assert!(sql.contains("MAX("));
     }
 
+    // Ensures count ranking counts each matching namespace once, across
+    // sub-namespaces and the wildcard user, and exposes it via select_as.
+    #[tokio::test]
+    async fn in_bookmarks_count_rank_orders_by_bookmark_count() {
+        use sea_query::SqliteQueryBuilder;
+        use sea_query_sqlx::SqlxBinder;
+        use sqlx::Row;
+
+        use crate::db::migrations::setup_test_databases;
+        use crate::pql::build_query;
+        use crate::pql::model::PqlQuery;
+
+        let mut dbs = setup_test_databases().await;
+        for statement in [
+            r#"INSERT INTO items (id, sha256, md5, type, time_added) VALUES
+                (1, 'sha_1', 'md5_1', 'image/png', '2024-01-01T00:00:00'),
+                (2, 'sha_2', 'md5_2', 'image/png', '2024-01-01T00:00:00'),
+                (3, 'sha_3', 'md5_3', 'image/png', '2024-01-01T00:00:00')"#,
+            "INSERT INTO file_scans (id, start_time, path) VALUES (1, '2024-01-01T00:00:00', '/data')",
+            r#"INSERT INTO files (id, sha256, item_id, path, filename, last_modified, scan_id, available) VALUES
+                (10, 'sha_1', 1, '/data/a.png', 'a.png', '2024-01-01T00:00:00', 1, 1),
+                (11, 'sha_2', 2, '/data/b.png', 'b.png', '2024-01-01T00:00:00', 1, 1),
+                (12, 'sha_3', 3, '/data/c.png', 'c.png', '2024-01-01T00:00:00', 1, 1)"#,
+            // sha_2: three people; sha_3: two, one of them through both the
+            // user and the wildcard user; sha_1: one, plus an unrelated
+            // namespace that must not count.
+            r#"INSERT INTO user_data.bookmarks (user, namespace, sha256, time_added) VALUES
+                ('user', 'likes.ann', 'sha_2', '2024-01-01T00:00:00'),
+                ('user', 'likes.bob', 'sha_2', '2024-01-01T00:00:00'),
+                ('*', 'likes.cat', 'sha_2', '2024-01-01T00:00:00'),
+                ('user', 'likes.ann', 'sha_3', '2024-01-05T00:00:00'),
+                ('*', 'likes.ann', 'sha_3', '2024-01-05T00:00:00'),
+                ('user', 'likes.bob', 'sha_3', '2024-01-05T00:00:00'),
+                ('user', 'likes.bob', 'sha_1', '2024-01-09T00:00:00'),
+                ('user', 'other', 'sha_1', '2024-01-09T00:00:00')"#,
+        ] {
+            sqlx::query(statement)
+                .execute(&mut dbs.index_conn)
+                .await
+                .expect("seed bookmarks");
+        }
+
+        let filter: InBookmarks = serde_json::from_value(json!({
+            "in_bookmarks": { "namespaces": ["likes"], "sub_ns": true, "rank_by": "count" },
+            "order_by": true,
+            "select_as": "bookmarks"
+        }))
+        .expect("in_bookmarks filter");
+        let built = build_query(
+            PqlQuery {
+                query: Some(QueryElement::InBookmarks(filter)),
+                page_size: 0,
+                ..Default::default()
+            },
+            false,
+        )
+        .expect("build");
+        let count_column = built
+            .extra_columns
+            .iter()
+            .find_map(|(label, alias)| (alias == "bookmarks").then(|| label.clone()))
+            .expect("bookmarks column");
+        let (sql, values) = built
+            .paginated_query()
+            .with(built.with_clause.expect("with clause"))
+            .build_sqlx(SqliteQueryBuilder);
+        let ranked = sqlx::query_with(sqlx::AssertSqlSafe(sql.as_str()), values)
+            .fetch_all(&mut dbs.index_conn)
+            .await
+            .expect("execute")
+            .iter()
+            .map(|row| {
+                (
+                    row.get::<i64, _>("item_id"),
+                    row.get::<i64, _>(count_column.as_str()),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(ranked, vec![(2, 3), (3, 2), (1, 1)]);
+
+        // Count queries carry no rank.
+        let count_filter: InBookmarks = serde_json::from_value(json!({
+            "in_bookmarks": { "rank_by": "count" },
+            "order_by": true
+        }))
+        .expect("in_bookmarks filter");
+        let mut state = build_base_state(EntityType::File, true);
+        let context = build_begin_cte(&mut state);
+        let sql = render_filter_sql(&count_filter, &mut state, &context);
+        assert!(!sql.contains("COUNT(DISTINCT"));
+    }
+
     #[tokio::test]
     async fn in_bookmarks_with_sort_bounds_runs_full_query() {
         // Regression: the gt/lt wrapper CTE hides the filter's joins, so the
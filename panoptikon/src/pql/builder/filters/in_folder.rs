@@ -0,0 +1,203 @@
+use sea_query::{Alias, Expr, ExprTrait, Func, Query};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::pql::preprocess::PqlError;
+
+use super::super::{CteRef, Files, JoinedTables, QueryState, select_std_from_cte, wrap_query};
+use super::FilterCompiler;
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub(crate) struct InFolderArgs {
+    /// Folder path
+    ///
+    /// Normalized like the included folders list (absolute, trailing separator),
+    /// then matched as a case-sensitive prefix of file paths
+    pub path: String,
+    /// Negate
+    ///
+    /// Match results that are not in the folder instead
+    #[serde(default)]
+    pub negate: bool,
+    /// Any File
+    ///
+    /// If true, the item matches when any of its files is under the folder.
+    /// If false, only the result's own file is checked.
+    #[serde(default = "default_true")]
+    pub any_file: bool,
+    /// Allow Any Prefix
+    ///
+    /// If false, the path must be one of the configured included folders
+    #[serde(default)]
+    pub any_prefix: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub(crate) struct InFolder {
+    /// In Folder
+    ///
+    /// Match items (or files) by whether they have a file under a folder
+    pub in_folder: InFolderArgs,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl FilterCompiler for InFolder {
+    fn build(&self, context: &CteRef, state: &mut QueryState) -> Result<CteRef, PqlError> {
+        let args = &self.in_folder;
+        let cte_name = format!("n{}_InFolder", state.cte_counter);
+        let files_alias = Alias::new("in_folder_files");
+
+        // substr() rather than LIKE: LIKE folds ASCII case and treats `_`
+        // and `%` in folder names as wildcards.
+        let prefix_len = args.path.chars().count() as i64;
+        let under_folder = Expr::expr(Func::cust("substr").args([
+            Expr::col((files_alias.clone(), Files::Path)),
+            Expr::val(1),
+            Expr::val(prefix_len),
+        ]))
+        .eq(args.path.clone());
+
+        let mut subquery = Query::select();
+        subquery.expr(Expr::val(1));
+        subquery.from_as(Files::Table, files_alias.clone());
+        if args.any_file {
+            subquery.and_where(
+                Expr::col((files_alias.clone(), Files::ItemId))
+                    .equals(context.column_ref("item_id")),
+            );
+        } else {
+            subquery.and_where(
+                Expr::col((files_alias.clone(), Files::Id)).equals(context.column_ref("file_id")),
+            );
+        }
+        subquery.and_where(under_folder);
+
+        let mut query = select_std_from_cte(context, state);
+        if args.negate {
+            query.and_where(Expr::not_exists(subquery.to_owned()));
+        } else {
+            query.and_where(Expr::exists(subquery.to_owned()));
+        }
+
+        let joined_tables = JoinedTables::default();
+        let cte = wrap_query(state, query, context, cte_name, &joined_tables);
+        state.cte_counter += 1;
+        Ok(cte)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::migrations::setup_test_databases;
+    use crate::pql::build_query;
+    use crate::pql::model::{AndOperator, EntityType, NotOperator, PqlQuery, QueryElement};
+    use sea_query::SqliteQueryBuilder;
+    use sea_query_sqlx::SqlxBinder;
+    use serde_json::json;
+
+    use super::super::test_support::{build_base_state, build_begin_cte, render_filter_sql};
+
+    fn in_folder(path: &str, negate: bool, any_file: bool) -> QueryElement {
+        serde_json::from_value(json!({
+            "in_folder": {
+                "path": path,
+                "negate": negate,
+                "any_file": any_file,
+                "any_prefix": true
+            }
+        }))
+        .expect("in_folder filter")
+    }
+
+    async fn run_paths(conn: &mut sqlx::SqliteConnection, query: PqlQuery) -> Vec<String> {
+        let built = build_query(query, false).expect("build_query");
+        let paginated = built.paginated_query();
+        let (sql, values) = match built.with_clause {
+            Some(with_clause) => paginated.with(with_clause).build_sqlx(SqliteQueryBuilder),
+            None => paginated.build_sqlx(SqliteQueryBuilder),
+        };
+        let mut paths: Vec<String> = sqlx::query_with(sqlx::AssertSqlSafe(sql.as_str()), values)
+            .fetch_all(conn)
+            .await
+            .expect("in_folder query")
+            .iter()
+            .map(|row| sqlx::Row::get(row, "path"))
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    fn file_query(filter: QueryElement) -> PqlQuery {
+        PqlQuery {
+            query: Some(filter),
+            entity: EntityType::File,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn in_folder_builds_sql() {
+        let filter: InFolder = serde_json::from_value(json!({
+            "in_folder": { "path": "/mnt/archive/", "negate": true }
+        }))
+        .expect("in_folder filter");
+        assert!(filter.in_folder.any_file);
+        let mut state = build_base_state(EntityType::Text, false);
+        let context = build_begin_cte(&mut state);
+        let sql = render_filter_sql(&filter, &mut state, &context);
+        assert!(sql.contains("NOT EXISTS"));
+        assert!(sql.contains("substr"));
+    }
+
+    // Item 1 has copies inside and outside the archive, item 2 only outside
+    // it, item 3 only under a differently-cased sibling folder. Negated
+    // any-file matching finds what still needs archiving; the per-file
+    // form and And/Not composition work on the same rows.
+    #[tokio::test]
+    async fn in_folder_matches_items_by_any_file_under_the_folder() {
+        let mut dbs = setup_test_databases().await;
+        let conn = &mut dbs.index_conn;
+        sqlx::query(
+            r#"
+INSERT INTO file_scans (id, start_time, path) VALUES (1, '2024-01-01T00:00:00', '/');
+INSERT INTO items (id, sha256, md5, type, time_added) VALUES
+    (1, 'sha1', 'md51', 'image/png', '2024-01-01T00:00:00'),
+    (2, 'sha2', 'md52', 'image/png', '2024-01-01T00:00:00'),
+    (3, 'sha3', 'md53', 'image/png', '2024-01-01T00:00:00');
+INSERT INTO files (id, sha256, item_id, path, filename, last_modified, scan_id, available) VALUES
+    (1, 'sha1', 1, '/mnt/archive/one.png', 'one.png', '2024-01-01T00:00:00', 1, 1),
+    (2, 'sha1', 1, '/home/me/one.png', 'one.png', '2024-01-01T00:00:00', 1, 1),
+    (3, 'sha2', 2, '/home/me/two.png', 'two.png', '2024-01-01T00:00:00', 1, 1),
+    (4, 'sha3', 3, '/mnt/Archive/three.png', 'three.png', '2024-01-01T00:00:00', 1, 1);
+            "#,
+        )
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+
+        let not_archived =
+            run_paths(conn, file_query(in_folder("/mnt/archive/", true, true))).await;
+        assert_eq!(
+            not_archived,
+            vec!["/home/me/two.png", "/mnt/Archive/three.png"]
+        );
+
+        let archived_files =
+            run_paths(conn, file_query(in_folder("/mnt/archive/", false, false))).await;
+        assert_eq!(archived_files, vec!["/mnt/archive/one.png"]);
+
+        let composed = file_query(QueryElement::And(AndOperator {
+            and_: vec![
+                in_folder("/home/me/", false, false),
+                QueryElement::Not(NotOperator {
+                    not_: Box::new(in_folder("/mnt/archive/", false, true)),
+                }),
+            ],
+        }));
+        assert_eq!(run_paths(conn, composed).await, vec!["/home/me/two.png"]);
+    }
+}
@@ -6,13 +6,14 @@ use crate::pql::model::{OrderDirection, PartialSortableOptions, SortableOptions}
 use crate::pql::preprocess::PqlError;
 
 use super::super::{
-    BaseTable, CteRef, EmbeddingQuants, Embeddings, ExtraColumn, ExtractedText, ItemData, Items,
-    JoinedTables, OrderByFilter, QueryState, Setters, add_rank_column_expr, apply_group_by,
-    apply_sort_bounds, create_cte, get_std_group_by, wrap_query,
+    BaseTable, CteRef, EmbeddingQuants, Embeddings, ExtractedText, ItemData, Items, JoinedTables,
+    OrderByFilter, QueryState, Setters, add_filter_attribution, add_rank_column_expr,
+    apply_group_by, apply_sort_bounds, create_cte, get_std_group_by, wrap_query,
 };
 use super::FilterCompiler;
 use super::embedding_types::{
-    DistanceAggregation, DistanceFunction, IndexMode, QuantResolved, default_k,
+    DistanceAggregation, DistanceFunction, IndexMode, QuantResolved, SCORE_COLUMN,
+    ScoreNormalization, default_k, default_l2_scale, push_score_columns,
 };
 use super::quant::{COARSE_DIST, COARSE_RANK, EXACT_DIST, assemble_two_stage};
 
@@ -139,6 +140,20 @@ pub(crate) struct SimilarityArgs {
     /// pagination session.
     #[serde(default = "default_k")]
     pub k: i64,
+    /// Select Normalized Score As
+    ///
+    /// If set, a 0-1 similarity (1 = identical) is included in the `extra`
+    /// dict of each result under this key, mapped from `distance_function`
+    /// as in the image embeddings filter. Ordering is unaffected; under a
+    /// quant profile results beyond `k` score null.
+    #[serde(default)]
+    pub normalize_score: Option<String>,
+    /// L2 Score Scale
+    ///
+    /// The L2 distance that `normalize_score` maps to 0.5. Must be positive;
+    /// ignored for cosine distances.
+    #[serde(default = "default_l2_scale")]
+    pub l2_scale: f64,
     #[serde(skip)]
     pub _quant: Option<QuantResolved>,
 }
@@ -595,14 +610,23 @@ impl SimilarTo {
         }
     }
 
+    fn score_normalization(&self) -> Result<Option<ScoreNormalization>, PqlError> {
+        let args = &self.similar_to;
+        ScoreNormalization::requested(
+            args.normalize_score.as_deref(),
+            args.distance_function,
+            args.l2_scale,
+        )
+    }
+
     fn register_outputs(&self, state: &mut QueryState, cte: &CteRef) {
-        if let Some(alias) = &self.sort.select_as {
-            state.extra_columns.push(ExtraColumn {
-                column: "order_rank".to_string(),
-                cte: cte.clone(),
-                alias: alias.clone(),
-            });
-        }
+        push_score_columns(
+            state,
+            cte,
+            self.sort.select_as.as_ref(),
+            self.similar_to.normalize_score.as_ref(),
+        );
+        add_filter_attribution(state, cte, &self.sort);
         if self.sort.order_by {
             state.order_list.push(OrderByFilter {
                 cte: cte.clone(),
@@ -650,6 +674,7 @@ impl FilterCompiler for SimilarTo {
             return Ok(cte);
         }
 
+        let score = self.score_normalization()?;
         if let Some(quant) = &args._quant {
             let coarse_collection = self.vector_collection(
                 context,
@@ -670,34 +695,37 @@ impl FilterCompiler for SimilarTo {
             );
 
             let k = args.k;
-            let (merge, merge_context) =
-                assemble_two_stage(state, &cte_name, coarse, &self.sort, |state, ranked| {
-                let exact_collection =
-                    self.vector_collection(context, state, &SimVectorJoin::Embeddings);
-                let unqemb_cte = create_cte(
-                    state,
-                    format!("unqemb_{cte_name}"),
-                    exact_collection,
-                );
-                let (mut head, _) = self.distance_select(
-                    state,
-                    &unqemb_cte,
-                    Some((self.exact_rank_column(), EXACT_DIST)),
-                );
-                let other_alias = Alias::new("other_embeddings");
-                let ranked_alias = Alias::new(ranked.name.as_str());
-                let mut join_cond = Expr::col((ranked_alias.clone(), Alias::new("file_id")))
-                    .equals((other_alias.clone(), Alias::new("file_id")));
-                if state.item_data_query {
-                    join_cond = join_cond.and(
-                        Expr::col((ranked_alias.clone(), Alias::new("data_id")))
-                            .equals((other_alias.clone(), Alias::new("data_id"))),
+            let (merge, merge_context) = assemble_two_stage(
+                state,
+                &cte_name,
+                coarse,
+                &self.sort,
+                score,
+                |state, ranked| {
+                    let exact_collection =
+                        self.vector_collection(context, state, &SimVectorJoin::Embeddings);
+                    let unqemb_cte =
+                        create_cte(state, format!("unqemb_{cte_name}"), exact_collection);
+                    let (mut head, _) = self.distance_select(
+                        state,
+                        &unqemb_cte,
+                        Some((self.exact_rank_column(), EXACT_DIST)),
                     );
-                }
-                head.join(JoinType::InnerJoin, ranked_alias.clone(), join_cond);
-                head.and_where(Expr::col(ranked.column_ref(COARSE_RANK)).lte(k));
-                head
-            });
+                    let other_alias = Alias::new("other_embeddings");
+                    let ranked_alias = Alias::new(ranked.name.as_str());
+                    let mut join_cond = Expr::col((ranked_alias.clone(), Alias::new("file_id")))
+                        .equals((other_alias.clone(), Alias::new("file_id")));
+                    if state.item_data_query {
+                        join_cond = join_cond.and(
+                            Expr::col((ranked_alias.clone(), Alias::new("data_id")))
+                                .equals((other_alias.clone(), Alias::new("data_id"))),
+                        );
+                    }
+                    head.join(JoinType::InnerJoin, ranked_alias.clone(), join_cond);
+                    head.and_where(Expr::col(ranked.column_ref(COARSE_RANK)).lte(k));
+                    head
+                },
+            );
 
             // The merge selects only from CTEs, so no base tables are
             // visible to the final query; its context is the ranked CTE in
@@ -724,6 +752,12 @@ impl FilterCompiler for SimilarTo {
         );
         let (mut distance_select, other_ctx) = self.distance_select(state, &unqemb_cte, None);
         add_rank_column_expr(&mut distance_select, &self.sort, self.exact_rank_column())?;
+        if let Some(score) = score {
+            distance_select.expr_as(
+                score.score_expr(self.exact_rank_column()),
+                Alias::new(SCORE_COLUMN),
+            );
+        }
 
         let (distance_select, context_for_wrap, joined_tables) = apply_sort_bounds(
             state,
@@ -9,7 +9,7 @@ use crate::pql::preprocess::PqlError;
 
 use super::super::{
     BaseTable, CteRef, ExtractedText, Files, ItemData, Items, JoinedTables, QueryState, Setters,
-    get_column_expr, is_text_column, select_std_from_cte, wrap_query,
+    column_name, get_column_expr, is_text_column, select_std_from_cte, wrap_query,
 };
 use super::FilterCompiler;
 
@@ -78,6 +78,12 @@ pub(crate) struct MatchValues {
     pub data_index: Option<OneOrMany<i64>>,
     #[serde(default)]
     pub source_id: Option<OneOrMany<i64>>,
+    #[serde(default)]
+    pub min_dimension: Option<OneOrMany<i64>>,
+    #[serde(default)]
+    pub max_dimension: Option<OneOrMany<i64>>,
+    #[serde(default)]
+    pub megapixels: Option<OneOrMany<f64>>,
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
@@ -138,6 +144,15 @@ pub(crate) struct MatchValue {
     pub data_index: Option<i64>,
     #[serde(default)]
     pub source_id: Option<i64>,
+    /// The smaller of width and height
+    #[serde(default)]
+    pub min_dimension: Option<i64>,
+    /// The larger of width and height
+    #[serde(default)]
+    pub max_dimension: Option<i64>,
+    /// width × height in millions of pixels
+    #[serde(default)]
+    pub megapixels: Option<f64>,
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
@@ -216,73 +231,264 @@ pub(crate) struct Match {
     pub match_: Matches,
 }
 
+/// Evaluates `filter` against one object the way the SQL builder's
+/// expression would evaluate against its row. A condition on a column `obj`
+/// doesn't carry is unknown (SQL's NULL) and the filter only rejects objects
+/// it is certainly false for: the file scanner evaluates before some columns
+/// are known, and leaves those conditions to a later stage.
 pub(crate) fn evaluate_match(filter: &Match, obj: &MatchValue) -> bool {
-    let obj_fields = collect_match_value_fields(obj)
+    evaluate_match_truth(filter, obj) != Some(false)
+}
+
+/// The three-valued result of `filter` on `obj`: `Some(true)` exactly when
+/// the SQL builder's expression would keep the row, `None` when it would be
+/// NULL because of a column `obj` doesn't carry.
+fn evaluate_match_truth(filter: &Match, obj: &MatchValue) -> Option<bool> {
+    let mut obj_fields = collect_match_value_fields(obj)
         .into_iter()
         .collect::<HashMap<_, _>>();
+    // Derived columns are computed here rather than by callers. Without both
+    // dimensions they stay absent, so their conditions are skipped.
+    if let (Some(width), Some(height)) = (obj.width, obj.height) {
+        obj_fields.insert(
+            Column::MinDimension,
+            FieldValue::Int(std::cmp::min(width, height)),
+        );
+        obj_fields.insert(
+            Column::MaxDimension,
+            FieldValue::Int(std::cmp::max(width, height)),
+        );
+        obj_fields.insert(
+            Column::Megapixels,
+            FieldValue::Float((width * height) as f64 / 1_000_000.0),
+        );
+    }
     evaluate_matches(&filter.match_, &obj_fields)
 }
 
+/// Every column `filter` places a condition on, in operator order.
+pub(crate) fn match_columns(filter: &Match) -> Vec<Column> {
+    let ops = match &filter.match_ {
+        Matches::Ops(ops) => vec![ops],
+        Matches::And(MatchAnd { and_ }) => and_.iter().collect(),
+        Matches::Or(MatchOr { or_ }) => or_.iter().collect(),
+        Matches::Not(MatchNot { not_ }) => vec![not_],
+    };
+    let mut columns = Vec::new();
+    for ops in ops {
+        for values in [&ops.eq, &ops.neq, &ops.gt, &ops.gte, &ops.lt, &ops.lte]
+            .into_iter()
+            .flatten()
+        {
+            columns.extend(
+                collect_match_value_fields(values)
+                    .into_iter()
+                    .map(|(c, _)| c),
+            );
+        }
+        for values in [
+            &ops.in_,
+            &ops.nin,
+            &ops.startswith,
+            &ops.not_startswith,
+            &ops.endswith,
+            &ops.not_endswith,
+            &ops.contains,
+            &ops.not_contains,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            columns.extend(
+                collect_match_values_fields(values)
+                    .into_iter()
+                    .map(|(c, _)| c),
+            );
+        }
+    }
+    columns
+}
+
+/// Columns the file scanner fills in before calling [`evaluate_match`];
+/// conditions on any other column are silently skipped in memory.
+const IN_MEMORY_COLUMNS: &[Column] = &[
+    Column::LastModified,
+    Column::Size,
+    Column::Path,
+    Column::Filename,
+    Column::Type,
+    Column::Md5,
+    Column::Sha256,
+    Column::Width,
+    Column::Height,
+    Column::Duration,
+    Column::AudioTracks,
+    Column::VideoTracks,
+    Column::SubtitleTracks,
+    Column::MinDimension,
+    Column::MaxDimension,
+    Column::Megapixels,
+];
+
+/// REAL columns: SQLite's text rendering of a float, which `LIKE` matches
+/// against, is not reproduced in memory.
+const IN_MEMORY_REAL_COLUMNS: &[Column] = &[Column::Duration, Column::Megapixels];
+
+/// Finds the first condition [`evaluate_match`] cannot evaluate against
+/// scanner metadata. Returns `(path, message)`, the path relative to the
+/// filter (e.g. `match.or_[1].startswith.size`).
+pub(crate) fn in_memory_match_error(filter: &Match) -> Option<(String, String)> {
+    match &filter.match_ {
+        Matches::Ops(ops) => in_memory_ops_error(ops, "match"),
+        Matches::And(MatchAnd { and_ }) => and_
+            .iter()
+            .enumerate()
+            .find_map(|(idx, ops)| in_memory_ops_error(ops, &format!("match.and_[{idx}]"))),
+        Matches::Or(MatchOr { or_ }) => or_
+            .iter()
+            .enumerate()
+            .find_map(|(idx, ops)| in_memory_ops_error(ops, &format!("match.or_[{idx}]"))),
+        Matches::Not(MatchNot { not_ }) => in_memory_ops_error(not_, "match.not_"),
+    }
+}
+
+fn in_memory_ops_error(ops: &MatchOps, path: &str) -> Option<(String, String)> {
+    let single = [
+        ("eq", &ops.eq),
+        ("neq", &ops.neq),
+        ("gt", &ops.gt),
+        ("gte", &ops.gte),
+        ("lt", &ops.lt),
+        ("lte", &ops.lte),
+    ];
+    for (op, values) in single {
+        let Some(values) = values else {
+            continue;
+        };
+        for (column, _) in collect_match_value_fields(values) {
+            if let Some(message) = in_memory_column_error(column, false) {
+                return Some((format!("{path}.{op}.{}", column_name(column)), message));
+            }
+        }
+    }
+    let lists = [
+        ("in_", &ops.in_, false),
+        ("nin", &ops.nin, false),
+        ("startswith", &ops.startswith, true),
+        ("not_startswith", &ops.not_startswith, true),
+        ("endswith", &ops.endswith, true),
+        ("not_endswith", &ops.not_endswith, true),
+        ("contains", &ops.contains, true),
+        ("not_contains", &ops.not_contains, true),
+    ];
+    for (op, values, string_op) in lists {
+        let Some(values) = values else {
+            continue;
+        };
+        for (column, _) in collect_match_values_fields(values) {
+            if let Some(message) = in_memory_column_error(column, string_op) {
+                return Some((format!("{path}.{op}.{}", column_name(column)), message));
+            }
+        }
+    }
+    None
+}
+
+fn in_memory_column_error(column: Column, string_op: bool) -> Option<String> {
+    let name = column_name(column);
+    if !IN_MEMORY_COLUMNS.contains(&column) {
+        return Some(format!("`{name}` is not available while scanning files"));
+    }
+    if string_op && IN_MEMORY_REAL_COLUMNS.contains(&column) {
+        return Some(format!(
+            "string operators on the real-valued `{name}` column cannot be evaluated while scanning files"
+        ));
+    }
+    None
+}
+
 impl FilterCompiler for Match {
     fn build(&self, context: &CteRef, state: &mut QueryState) -> Result<CteRef, PqlError> {
         let expression = build_matches_expression(&self.match_, state.item_data_query)?;
-        let mut query = select_std_from_cte(context, state);
+        build_match_cte(expression, context, state)
+    }
+}
+
+/// Builds several `match` filters as one whose condition ORs theirs, so an
+/// optimized `or_` of plain matches costs a single pass over its context.
+pub(crate) fn build_match_any(
+    filters: &[Match],
+    context: &CteRef,
+    state: &mut QueryState,
+) -> Result<CteRef, PqlError> {
+    let expressions = filters
+        .iter()
+        .map(|filter| build_matches_expression(&filter.match_, state.item_data_query))
+        .collect::<Result<Vec<_>, _>>()?;
+    build_match_cte(combine_or(expressions)?, context, state)
+}
+
+fn build_match_cte(
+    expression: Expr,
+    context: &CteRef,
+    state: &mut QueryState,
+) -> Result<CteRef, PqlError> {
+    let mut query = select_std_from_cte(context, state);
+    query.join(
+        JoinType::InnerJoin,
+        Items::Table,
+        Expr::col((Items::Table, Items::Id)).equals(context.column_ref("item_id")),
+    );
+    query.join(
+        JoinType::InnerJoin,
+        Files::Table,
+        Expr::col((Files::Table, Files::Id)).equals(context.column_ref("file_id")),
+    );
+    if state.item_data_query {
         query.join(
             JoinType::InnerJoin,
-            Items::Table,
-            Expr::col((Items::Table, Items::Id)).equals(context.column_ref("item_id")),
+            ExtractedText::Table,
+            Expr::col((ExtractedText::Table, ExtractedText::Id))
+                .equals(context.column_ref("data_id")),
         );
         query.join(
             JoinType::InnerJoin,
-            Files::Table,
-            Expr::col((Files::Table, Files::Id)).equals(context.column_ref("file_id")),
+            ItemData::Table,
+            Expr::col((ItemData::Table, ItemData::Id)).equals(context.column_ref("data_id")),
         );
-        if state.item_data_query {
-            query.join(
-                JoinType::InnerJoin,
-                ExtractedText::Table,
-                Expr::col((ExtractedText::Table, ExtractedText::Id))
-                    .equals(context.column_ref("data_id")),
-            );
-            query.join(
-                JoinType::InnerJoin,
-                ItemData::Table,
-                Expr::col((ItemData::Table, ItemData::Id)).equals(context.column_ref("data_id")),
-            );
-            query.join(
-                JoinType::InnerJoin,
-                Setters::Table,
-                Expr::col((Setters::Table, Setters::Id))
-                    .equals((ItemData::Table, ItemData::SetterId)),
-            );
-        }
-        query.and_where(expression);
-
-        let mut joined_tables = JoinedTables::default();
-        joined_tables.mark(BaseTable::Items);
-        joined_tables.mark(BaseTable::Files);
-        if state.item_data_query {
-            joined_tables.mark(BaseTable::ItemData);
-            joined_tables.mark(BaseTable::Setters);
-            joined_tables.mark(BaseTable::ExtractedText);
-        }
+        query.join(
+            JoinType::InnerJoin,
+            Setters::Table,
+            Expr::col((Setters::Table, Setters::Id)).equals((ItemData::Table, ItemData::SetterId)),
+        );
+    }
+    query.and_where(expression);
 
-        let cte_name = format!("n{}_Match", state.cte_counter);
-        let cte = wrap_query(state, query, context, cte_name, &joined_tables);
-        state.cte_counter += 1;
-        Ok(cte)
+    let mut joined_tables = JoinedTables::default();
+    joined_tables.mark(BaseTable::Items);
+    joined_tables.mark(BaseTable::Files);
+    if state.item_data_query {
+        joined_tables.mark(BaseTable::ItemData);
+        joined_tables.mark(BaseTable::Setters);
+        joined_tables.mark(BaseTable::ExtractedText);
     }
+
+    let cte_name = format!("n{}_Match", state.cte_counter);
+    let cte = wrap_query(state, query, context, cte_name, &joined_tables);
+    state.cte_counter += 1;
+    Ok(cte)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::pql::model::{EntityType, QueryElement};
+    use crate::pql::model::{EntityType, PqlQuery, QueryElement};
     use serde_json::json;
 
     use super::super::test_support::{
-        build_base_state, build_begin_cte, render_filter_sql, run_full_pql_query,
+        build_base_state, build_begin_cte, filter_result_ids, render_filter_sql, run_full_pql_query,
     };
+    use crate::db::migrations::setup_test_databases;
 
     #[test]
     fn matches_routes_boolean_operators() {
@@ -425,6 +631,250 @@ mod tests {
         };
         assert!(!evaluate_match(&filter, &blocked));
     }
+
+    // Flags columns the scanner never fills in and string operators on REAL columns.
+    #[test]
+    fn in_memory_match_error_reports_unsupported_conditions() {
+        let supported: Match = serde_json::from_value(json!({
+            "match": { "or_": [ { "startswith": { "path": "/media" } }, { "gt": { "size": 10 } } ] }
+        }))
+        .expect("supported filter");
+        assert!(in_memory_match_error(&supported).is_none());
+
+        let unknown_column: Match = serde_json::from_value(json!({
+            "match": { "and_": [ { "eq": { "type": "image/png" } }, { "eq": { "time_added": "2024" } } ] }
+        }))
+        .expect("time_added filter");
+        let (path, message) = in_memory_match_error(&unknown_column).expect("unsupported column");
+        assert_eq!(path, "match.and_[1].eq.time_added");
+        assert!(message.contains("time_added"));
+
+        let real_string_op: Match = serde_json::from_value(json!({
+            "match": { "not_": { "contains": { "duration": 10 } } }
+        }))
+        .expect("duration filter");
+        let (path, _) = in_memory_match_error(&real_string_op).expect("string op on duration");
+        assert_eq!(path, "match.not_.contains.duration");
+
+        let integer_string_op: Match = serde_json::from_value(json!({
+            "match": { "startswith": { "size": 10 } }
+        }))
+        .expect("size filter");
+        assert!(in_memory_match_error(&integer_string_op).is_none());
+    }
+
+    #[test]
+    fn derived_dimension_columns_build_sql() {
+        let filter: Match = serde_json::from_value(json!({
+            "match": { "gte": { "min_dimension": 256, "megapixels": 0.5 } }
+        }))
+        .expect("dimension filter");
+        let mut state = build_base_state(EntityType::File, false);
+        let context = build_begin_cte(&mut state);
+        let sql = render_filter_sql(&filter, &mut state, &context);
+        assert!(sql.contains("min(\"items\".\"width\", \"items\".\"height\")"));
+        assert!(sql.contains("1000000.0"));
+    }
+
+    // Icons are dropped by min_dimension, the megapixel threshold applies to
+    // the product, and files without dimensions are left to the SQL stage.
+    #[test]
+    fn evaluate_match_computes_derived_dimensions() {
+        let filter: Match = serde_json::from_value(json!({
+            "match": { "gte": { "min_dimension": 256 } }
+        }))
+        .expect("min_dimension filter");
+        assert!(in_memory_match_error(&filter).is_none());
+        let sized = |width, height| MatchValue {
+            width: Some(width),
+            height: Some(height),
+            ..Default::default()
+        };
+        assert!(!evaluate_match(&filter, &sized(16, 16)));
+        assert!(evaluate_match(&filter, &sized(512, 300)));
+        assert!(evaluate_match(&filter, &MatchValue::default()));
+
+        let megapixels: Match = serde_json::from_value(json!({
+            "match": { "lt": { "megapixels": 2.0 }, "gt": { "max_dimension": 1000 } }
+        }))
+        .expect("megapixels filter");
+        assert!(evaluate_match(&megapixels, &sized(1920, 1000)));
+        assert!(!evaluate_match(&megapixels, &sized(1920, 1080)));
+        assert!(!evaluate_match(&megapixels, &sized(800, 600)));
+
+        let string_op: Match = serde_json::from_value(json!({
+            "match": { "startswith": { "max_dimension": 5 } }
+        }))
+        .expect("string op filter");
+        assert!(in_memory_match_error(&string_op).is_none());
+        assert!(evaluate_match(&string_op, &sized(512, 300)));
+        assert!(!evaluate_match(&string_op, &sized(1920, 1080)));
+    }
+
+    // Ensures a negated condition on a column the scanner has not filled in
+    // yet leaves the file to the later stage instead of rejecting it.
+    #[test]
+    fn evaluate_match_treats_missing_columns_as_unknown() {
+        let filter: Match = serde_json::from_value(json!({
+            "match": { "not_": { "lt": { "width": 100 } } }
+        }))
+        .expect("not_ filter");
+        assert!(evaluate_match(&filter, &MatchValue::default()));
+
+        let filter: Match = serde_json::from_value(json!({
+            "match": { "or_": [ { "lt": { "width": 100 } }, { "eq": { "type": "image/png" } } ] }
+        }))
+        .expect("or_ filter");
+        let audio = MatchValue {
+            r#type: Some("audio/mpeg".to_string()),
+            ..Default::default()
+        };
+        assert!(evaluate_match(&filter, &audio));
+        assert_eq!(evaluate_match_truth(&filter, &audio), None);
+    }
+
+    // Ensures every operator and combinator evaluates in memory exactly as
+    // the SQL builder's expression does on the same rows, including LIKE
+    // wildcards and case sensitivity, mixed-case string ordering, integer
+    // LIKE and NULL columns.
+    #[tokio::test]
+    async fn evaluate_match_agrees_with_sql_on_seeded_rows() {
+        let mut dbs = setup_test_databases().await;
+        sqlx::query(
+            r#"
+            INSERT INTO file_scans (id, start_time, path)
+            VALUES (1, '2024-01-01T00:00:00', '/');
+            INSERT INTO items
+                (id, sha256, md5, type, size, width, height, duration,
+                 audio_tracks, video_tracks, subtitle_tracks, time_added)
+            VALUES
+                (1, 'sha_1', 'md5_1', 'image/png', 2048, 1920, 1080, NULL, NULL, NULL, NULL, '2024-01-01T00:00:00'),
+                (2, 'sha_2', 'md5_2', 'image/jpeg', 512, 16, 16, NULL, NULL, NULL, NULL, '2024-01-01T00:00:00'),
+                (3, 'sha_3', 'md5_3', 'video/mp4', 10000000, 1280, 720, 12.5, 1, 1, 0, '2024-01-01T00:00:00'),
+                (4, 'sha_4', 'md5_4', 'audio/mpeg', 300000, NULL, NULL, 180.0, 1, 0, 0, '2024-01-01T00:00:00'),
+                (5, 'sha_5', 'md5_5', 'text/plain', 0, NULL, NULL, NULL, NULL, NULL, NULL, '2024-01-01T00:00:00');
+            INSERT INTO files (id, sha256, item_id, path, filename, last_modified, scan_id, available)
+            VALUES
+                (10, 'sha_1', 1, '/Media/Photos/IMG_0001.PNG', 'IMG_0001.PNG', '2024-01-05T10:00:00', 1, 1),
+                (11, 'sha_2', 2, '/media/icons/fav%icon.jpg', 'fav%icon.jpg', '2024-02-01T00:00:00', 1, 1),
+                (12, 'sha_3', 3, '/media/clips/Été.mp4', 'Été.mp4', '2024-03-15T08:30:00', 1, 1),
+                (13, 'sha_4', 4, '/music/song_1.mp3', 'song_1.mp3', '2023-12-31T23:59:59', 1, 1),
+                (14, 'sha_5', 5, 'C:\docs\notes.txt', 'notes.txt', '2024-03-01T00:00:00', 1, 1);
+            "#,
+        )
+        .execute(&mut dbs.index_conn)
+        .await
+        .expect("seed");
+
+        let rows = sqlx::query(
+            "SELECT files.id, path, filename, last_modified, type, size, width, height, duration, \
+             items.md5, items.sha256, audio_tracks, video_tracks, subtitle_tracks \
+             FROM files JOIN items ON items.id = files.item_id ORDER BY files.id",
+        )
+        .fetch_all(&mut dbs.index_conn)
+        .await
+        .expect("rows");
+        let objects = rows
+            .iter()
+            .map(|row| {
+                use sqlx::Row;
+                let object = MatchValue {
+                    path: row.get("path"),
+                    filename: row.get("filename"),
+                    last_modified: row.get("last_modified"),
+                    r#type: row.get("type"),
+                    size: row.get("size"),
+                    width: row.get("width"),
+                    height: row.get("height"),
+                    duration: row.get("duration"),
+                    md5: row.get("md5"),
+                    sha256: row.get("sha256"),
+                    audio_tracks: row.get("audio_tracks"),
+                    video_tracks: row.get("video_tracks"),
+                    subtitle_tracks: row.get("subtitle_tracks"),
+                    ..Default::default()
+                };
+                (row.get::<i64, _>("id"), object)
+            })
+            .collect::<Vec<_>>();
+
+        let cases = [
+            json!({ "eq": { "type": "image/png" } }),
+            json!({ "neq": { "type": "image/png" } }),
+            json!({ "eq": { "duration": 12.5 } }),
+            json!({ "eq": { "audio_tracks": 1 } }),
+            json!({ "neq": { "subtitle_tracks": 0 } }),
+            json!({ "gt": { "size": 512 } }),
+            json!({ "gte": { "size": 512 } }),
+            json!({ "lt": { "width": 1280 } }),
+            json!({ "lte": { "height": 720 } }),
+            json!({ "lt": { "duration": 100 } }),
+            json!({ "gte": { "duration": 12.5 } }),
+            json!({ "gt": { "last_modified": "2024-02" } }),
+            json!({ "lt": { "path": "/media" } }),
+            json!({ "gte": { "filename": "fav" } }),
+            json!({ "in_": { "type": ["image/png", "audio/mpeg"] } }),
+            json!({ "nin": { "size": [0, 512] } }),
+            json!({ "in_": { "width": [16, 1920] } }),
+            json!({ "nin": { "md5": ["md5_1"] } }),
+            json!({ "startswith": { "path": "/media" } }),
+            json!({ "startswith": { "path": ["/music", "c:"] } }),
+            json!({ "not_startswith": { "path": ["/media", "/music"] } }),
+            json!({ "endswith": { "filename": ".png" } }),
+            json!({ "not_endswith": { "filename": [".jpg", ".MP3"] } }),
+            json!({ "contains": { "path": "%" } }),
+            json!({ "contains": { "filename": "g_" } }),
+            json!({ "contains": { "path": "été" } }),
+            json!({ "contains": { "path": "ÉTÉ" } }),
+            json!({ "not_contains": { "path": "photos" } }),
+            json!({ "startswith": { "size": 30 } }),
+            json!({ "contains": { "width": [92, 28] } }),
+            json!({ "not_endswith": { "height": 0 } }),
+            json!({ "gte": { "min_dimension": 256 } }),
+            json!({ "gt": { "megapixels": 1.0 } }),
+            json!({ "startswith": { "max_dimension": 1 } }),
+            json!({ "gt": { "size": 100 }, "startswith": { "type": "image/" } }),
+            json!({ "and_": [ { "gt": { "size": 100 } }, { "not_contains": { "path": "clips" } } ] }),
+            json!({ "and_": [ { "gt": { "width": 100 } }, { "eq": { "type": "audio/mpeg" } } ] }),
+            json!({ "or_": [ { "gt": { "width": 1000 } }, { "eq": { "type": "text/plain" } } ] }),
+            json!({ "or_": [ { "lt": { "width": 100 } }, { "eq": { "type": "video/mp4" } } ] }),
+            json!({ "not_": { "gt": { "width": 100 } } }),
+            json!({ "not_": { "contains": { "path": "media" } } }),
+            json!({ "not_": { "in_": { "audio_tracks": [1] } } }),
+        ];
+        for ops in cases {
+            let filter: Match = serde_json::from_value(json!({ "match": ops })).expect("filter");
+            let sql = filter_result_ids(&filter, EntityType::File, &mut dbs.index_conn)
+                .await
+                .into_iter()
+                .map(|(_, file_id, _)| file_id)
+                .collect::<Vec<_>>();
+            let memory = objects
+                .iter()
+                .filter(|(_, object)| evaluate_match_truth(&filter, object) == Some(true))
+                .map(|(file_id, _)| *file_id)
+                .collect::<Vec<_>>();
+            assert_eq!(memory, sql, "{ops}");
+            for (file_id, object) in &objects {
+                assert!(
+                    evaluate_match(&filter, object) || !sql.contains(file_id),
+                    "{ops} rejected file {file_id} that SQL keeps"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn derived_dimension_columns_cannot_be_selected() {
+        let query = PqlQuery {
+            select: vec![Column::Path, Column::Megapixels],
+            ..Default::default()
+        };
+        let Err(err) = crate::pql::build_query(query, false) else {
+            panic!("megapixels must not be selectable");
+        };
+        assert!(err.message.contains("megapixels"));
+    }
 }
 
 fn build_matches_expression(matches: &Matches, allow_text: bool) -> Result<Expr, PqlError> {
@@ -451,96 +901,77 @@ fn build_matches_expression(matches: &Matches, allow_text: bool) -> Result<Expr,
     }
 }
 
-fn evaluate_matches(matches: &Matches, obj_fields: &HashMap<Column, FieldValue>) -> bool {
+fn evaluate_matches(matches: &Matches, obj_fields: &HashMap<Column, FieldValue>) -> Option<bool> {
     match matches {
         Matches::Ops(ops) => evaluate_match_ops(ops, obj_fields),
         Matches::And(MatchAnd { and_ }) => {
-            and_.iter().all(|ops| evaluate_match_ops(ops, obj_fields))
+            all_of(and_.iter().map(|ops| evaluate_match_ops(ops, obj_fields)))
+        }
+        Matches::Or(MatchOr { or_ }) => {
+            any_of(or_.iter().map(|ops| evaluate_match_ops(ops, obj_fields)))
         }
-        Matches::Or(MatchOr { or_ }) => or_.iter().any(|ops| evaluate_match_ops(ops, obj_fields)),
-        Matches::Not(MatchNot { not_ }) => !evaluate_match_ops(not_, obj_fields),
+        Matches::Not(MatchNot { not_ }) => evaluate_match_ops(not_, obj_fields).map(|value| !value),
     }
 }
 
-fn evaluate_match_ops(ops: &MatchOps, obj_fields: &HashMap<Column, FieldValue>) -> bool {
-    let mut results = Vec::new();
-
-    if let Some(values) = &ops.eq {
-        results.extend(evaluate_match_values(values, obj_fields, MatchOp::Eq));
-    }
-    if let Some(values) = &ops.neq {
-        results.extend(evaluate_match_values(values, obj_fields, MatchOp::Neq));
-    }
-    if let Some(values) = &ops.in_ {
-        results.extend(evaluate_match_value_lists(values, obj_fields, MatchOp::In));
-    }
-    if let Some(values) = &ops.nin {
-        results.extend(evaluate_match_value_lists(
-            values,
-            obj_fields,
-            MatchOp::NotIn,
-        ));
-    }
-    if let Some(values) = &ops.gt {
-        results.extend(evaluate_match_values(values, obj_fields, MatchOp::Gt));
-    }
-    if let Some(values) = &ops.gte {
-        results.extend(evaluate_match_values(values, obj_fields, MatchOp::Gte));
-    }
-    if let Some(values) = &ops.lt {
-        results.extend(evaluate_match_values(values, obj_fields, MatchOp::Lt));
-    }
-    if let Some(values) = &ops.lte {
-        results.extend(evaluate_match_values(values, obj_fields, MatchOp::Lte));
-    }
-    if let Some(values) = &ops.startswith {
-        results.extend(evaluate_match_value_lists(
-            values,
-            obj_fields,
-            MatchOp::StartsWith,
-        ));
-    }
-    if let Some(values) = &ops.not_startswith {
-        results.extend(evaluate_match_value_lists(
-            values,
-            obj_fields,
-            MatchOp::NotStartsWith,
-        ));
-    }
-    if let Some(values) = &ops.endswith {
-        results.extend(evaluate_match_value_lists(
-            values,
-            obj_fields,
-            MatchOp::EndsWith,
-        ));
-    }
-    if let Some(values) = &ops.not_endswith {
-        results.extend(evaluate_match_value_lists(
-            values,
-            obj_fields,
-            MatchOp::NotEndsWith,
-        ));
-    }
-    if let Some(values) = &ops.contains {
-        results.extend(evaluate_match_value_lists(
-            values,
-            obj_fields,
-            MatchOp::Contains,
-        ));
-    }
-    if let Some(values) = &ops.not_contains {
-        results.extend(evaluate_match_value_lists(
-            values,
-            obj_fields,
-            MatchOp::NotContains,
-        ));
+/// SQL `AND`: false wins over unknown, unknown over true.
+fn all_of(results: impl IntoIterator<Item = Option<bool>>) -> Option<bool> {
+    let mut unknown = false;
+    for result in results {
+        match result {
+            Some(false) => return Some(false),
+            None => unknown = true,
+            Some(true) => {}
+        }
     }
+    (!unknown).then_some(true)
+}
 
-    if results.is_empty() {
-        return true;
+/// SQL `OR`: true wins over unknown, unknown over false.
+fn any_of(results: impl IntoIterator<Item = Option<bool>>) -> Option<bool> {
+    let mut unknown = false;
+    for result in results {
+        match result {
+            Some(true) => return Some(true),
+            None => unknown = true,
+            Some(false) => {}
+        }
     }
+    (!unknown).then_some(false)
+}
 
-    results.into_iter().all(|value| value)
+fn evaluate_match_ops(ops: &MatchOps, obj_fields: &HashMap<Column, FieldValue>) -> Option<bool> {
+    let single = [
+        (&ops.eq, MatchOp::Eq),
+        (&ops.neq, MatchOp::Neq),
+        (&ops.gt, MatchOp::Gt),
+        (&ops.gte, MatchOp::Gte),
+        (&ops.lt, MatchOp::Lt),
+        (&ops.lte, MatchOp::Lte),
+    ];
+    let lists = [
+        (&ops.in_, MatchOp::In),
+        (&ops.nin, MatchOp::NotIn),
+        (&ops.startswith, MatchOp::StartsWith),
+        (&ops.not_startswith, MatchOp::NotStartsWith),
+        (&ops.endswith, MatchOp::EndsWith),
+        (&ops.not_endswith, MatchOp::NotEndsWith),
+        (&ops.contains, MatchOp::Contains),
+        (&ops.not_contains, MatchOp::NotContains),
+    ];
+
+    let mut results = Vec::new();
+    for (values, op) in single {
+        if let Some(values) = values {
+            results.extend(evaluate_match_values(values, obj_fields, op));
+        }
+    }
+    for (values, op) in lists {
+        if let Some(values) = values {
+            results.extend(evaluate_match_value_lists(values, obj_fields, op));
+        }
+    }
+    all_of(results)
 }
 
 #[derive(Clone, Copy)]
@@ -565,129 +996,129 @@ fn evaluate_match_values(
     values: &MatchValue,
     obj_fields: &HashMap<Column, FieldValue>,
     op: MatchOp,
-) -> Vec<bool> {
-    let mut results = Vec::new();
-    for (column, value) in collect_match_value_fields(values) {
-        let Some(field_value) = obj_fields.get(&column) else {
-            continue;
-        };
-        results.push(compare_field(field_value, &value, op));
-    }
-    results
+) -> Vec<Option<bool>> {
+    collect_match_value_fields(values)
+        .into_iter()
+        .map(|(column, value)| {
+            let field_value = obj_fields.get(&column)?;
+            Some(compare_field(field_value, &value, op))
+        })
+        .collect()
 }
 
 fn evaluate_match_value_lists(
     values: &MatchValues,
     obj_fields: &HashMap<Column, FieldValue>,
     op: MatchOp,
-) -> Vec<bool> {
-    let mut results = Vec::new();
-    for (column, value) in collect_match_values_fields(values) {
-        let Some(field_value) = obj_fields.get(&column) else {
-            continue;
-        };
-        results.push(compare_field_list(field_value, &value, op));
-    }
-    results
+) -> Vec<Option<bool>> {
+    collect_match_values_fields(values)
+        .into_iter()
+        .map(|(column, value)| {
+            let field_value = obj_fields.get(&column)?;
+            compare_field_list(field_value, &value, op)
+        })
+        .collect()
 }
 
 fn compare_field(field_value: &FieldValue, value: &FieldValue, op: MatchOp) -> bool {
+    let ordering = sqlite_compare(field_value, value);
     match op {
-        MatchOp::Eq => field_value == value,
-        MatchOp::Neq => field_value != value,
-        MatchOp::Gt => compare_ordered(field_value, value, |ord| ord.is_gt()),
-        MatchOp::Gte => compare_ordered(field_value, value, |ord| ord.is_gt() || ord.is_eq()),
-        MatchOp::Lt => compare_ordered(field_value, value, |ord| ord.is_lt()),
-        MatchOp::Lte => compare_ordered(field_value, value, |ord| ord.is_lt() || ord.is_eq()),
+        MatchOp::Eq => ordering.is_eq(),
+        MatchOp::Neq => ordering.is_ne(),
+        MatchOp::Gt => ordering.is_gt(),
+        MatchOp::Gte => ordering.is_ge(),
+        MatchOp::Lt => ordering.is_lt(),
+        MatchOp::Lte => ordering.is_le(),
         _ => false,
     }
 }
 
-fn compare_field_list(field_value: &FieldValue, values: &FieldValues, op: MatchOp) -> bool {
-    match op {
-        MatchOp::In => {
-            let list = match values {
-                FieldValues::Many(values) => values,
-                FieldValues::Single(_) => return false,
-            };
-            list.iter().any(|value| field_value == value)
-        }
-        MatchOp::NotIn => {
-            let list = match values {
-                FieldValues::Many(values) => values,
-                FieldValues::Single(_) => return false,
-            };
-            list.iter().all(|value| field_value != value)
-        }
-        MatchOp::StartsWith | MatchOp::NotStartsWith => {
-            string_list_check(field_value, values, op, |value, pattern| {
-                value.starts_with(pattern)
-            })
+/// `None` when the result depends on SQLite's text rendering of a REAL,
+/// which `in_memory_match_error` rejects up front.
+fn compare_field_list(field_value: &FieldValue, values: &FieldValues, op: MatchOp) -> Option<bool> {
+    let list = match values {
+        FieldValues::Single(value) => std::slice::from_ref(value),
+        FieldValues::Many(values) => values.as_slice(),
+    };
+    let equals = |value: &FieldValue| sqlite_compare(field_value, value).is_eq();
+    let pattern = |value: &FieldValue| {
+        let raw = value.to_string_value();
+        match op {
+            MatchOp::StartsWith | MatchOp::NotStartsWith => format!("{raw}%"),
+            MatchOp::EndsWith | MatchOp::NotEndsWith => format!("%{raw}"),
+            _ => format!("%{raw}%"),
         }
-        MatchOp::EndsWith | MatchOp::NotEndsWith => {
-            string_list_check(field_value, values, op, |value, pattern| {
-                value.ends_with(pattern)
-            })
+    };
+    match op {
+        MatchOp::In => Some(list.iter().any(equals)),
+        MatchOp::NotIn => Some(!list.iter().any(equals)),
+        MatchOp::StartsWith | MatchOp::EndsWith | MatchOp::Contains => {
+            let text = like_text(field_value)?;
+            Some(list.iter().any(|value| sqlite_like(&text, &pattern(value))))
         }
-        MatchOp::Contains | MatchOp::NotContains => {
-            string_list_check(field_value, values, op, |value, pattern| {
-                value.contains(pattern)
-            })
+        MatchOp::NotStartsWith | MatchOp::NotEndsWith | MatchOp::NotContains => {
+            let text = like_text(field_value)?;
+            Some(!list.iter().any(|value| sqlite_like(&text, &pattern(value))))
         }
-        _ => false,
+        _ => Some(false),
     }
 }
 
-fn string_list_check(
-    field_value: &FieldValue,
-    values: &FieldValues,
-    op: MatchOp,
-    check: impl Fn(&str, &str) -> bool,
-) -> bool {
-    let FieldValue::String(field_str) = field_value else {
-        return false;
-    };
-    let list = match values {
-        FieldValues::Single(value) => vec![value.clone()],
-        FieldValues::Many(values) => values.clone(),
-    };
-
-    let negate = matches!(
-        op,
-        MatchOp::NotStartsWith | MatchOp::NotEndsWith | MatchOp::NotContains
-    );
-    let use_all = negate;
-
-    let mut results = list.iter().map(|value| match value {
-        FieldValue::String(pattern) => check(field_str, pattern),
-        _ => false,
-    });
+/// The text SQLite's `LIKE` sees for a value: integers render exactly,
+/// REALs are left unevaluated.
+fn like_text(value: &FieldValue) -> Option<String> {
+    match value {
+        FieldValue::Int(value) => Some(value.to_string()),
+        FieldValue::Float(_) => None,
+        FieldValue::String(value) => Some(value.clone()),
+    }
+}
 
-    if use_all {
-        results.all(|value| if negate { !value } else { value })
-    } else {
-        results.any(|value| if negate { !value } else { value })
+/// SQLite's `LIKE` as our connections configure it (`case_sensitive_like`):
+/// `%` matches any run of characters, `_` exactly one, everything else only
+/// itself, and there is no escape character.
+fn sqlite_like(text: &str, pattern: &str) -> bool {
+    let text = text.chars().collect::<Vec<_>>();
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let (mut t, mut p) = (0, 0);
+    // Position of the last `%` and the text index it currently absorbs up to.
+    let mut resume: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('%') => {
+                resume = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '_' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match resume {
+                Some((percent, absorbed)) => {
+                    p = percent + 1;
+                    t = absorbed + 1;
+                    resume = Some((percent, absorbed + 1));
+                }
+                None => return false,
+            },
+        }
     }
+    pattern[p..].iter().all(|&c| c == '%')
 }
 
-fn compare_ordered(
-    left: &FieldValue,
-    right: &FieldValue,
-    check: impl Fn(std::cmp::Ordering) -> bool,
-) -> bool {
+/// SQLite's comparison of two bound values: numbers compare numerically
+/// (integers against REALs too), text compares bytewise (BINARY collation),
+/// and any number sorts before any text.
+fn sqlite_compare(left: &FieldValue, right: &FieldValue) -> std::cmp::Ordering {
+    use std::cmp::Ordering;
     match (left, right) {
-        (FieldValue::Int(lhs), FieldValue::Int(rhs)) => check(lhs.cmp(rhs)),
-        (FieldValue::Float(lhs), FieldValue::Float(rhs)) => {
-            lhs.partial_cmp(rhs).map(check).unwrap_or(false)
-        }
-        (FieldValue::Int(lhs), FieldValue::Float(rhs)) => {
-            (*lhs as f64).partial_cmp(rhs).map(check).unwrap_or(false)
-        }
-        (FieldValue::Float(lhs), FieldValue::Int(rhs)) => {
-            lhs.partial_cmp(&(*rhs as f64)).map(check).unwrap_or(false)
-        }
-        (FieldValue::String(lhs), FieldValue::String(rhs)) => check(lhs.cmp(rhs)),
-        _ => false,
+        (FieldValue::Int(lhs), FieldValue::Int(rhs)) => lhs.cmp(rhs),
+        (FieldValue::String(lhs), FieldValue::String(rhs)) => lhs.as_bytes().cmp(rhs.as_bytes()),
+        (FieldValue::Float(lhs), FieldValue::Float(rhs)) => lhs.total_cmp(rhs),
+        (FieldValue::Int(lhs), FieldValue::Float(rhs)) => (*lhs as f64).total_cmp(rhs),
+        (FieldValue::Float(lhs), FieldValue::Int(rhs)) => lhs.total_cmp(&(*rhs as f64)),
+        (FieldValue::String(_), _) => Ordering::Greater,
+        (_, FieldValue::String(_)) => Ordering::Less,
     }
 }
 
@@ -1075,6 +1506,15 @@ fn collect_match_value_fields(values: &MatchValue) -> Vec<(Column, FieldValue)>
     if let Some(value) = values.source_id {
         fields.push((Column::SourceId, FieldValue::Int(value)));
     }
+    if let Some(value) = values.min_dimension {
+        fields.push((Column::MinDimension, FieldValue::Int(value)));
+    }
+    if let Some(value) = values.max_dimension {
+        fields.push((Column::MaxDimension, FieldValue::Int(value)));
+    }
+    if let Some(value) = values.megapixels {
+        fields.push((Column::Megapixels, FieldValue::Float(value)));
+    }
     fields
 }
 
@@ -1200,6 +1640,15 @@ fn collect_match_values_fields(values: &MatchValues) -> Vec<(Column, FieldValues
     if let Some(value) = values.source_id.as_ref() {
         fields.push((Column::SourceId, convert_one_or_many(value, map_int)));
     }
+    if let Some(value) = values.min_dimension.as_ref() {
+        fields.push((Column::MinDimension, convert_one_or_many(value, map_int)));
+    }
+    if let Some(value) = values.max_dimension.as_ref() {
+        fields.push((Column::MaxDimension, convert_one_or_many(value, map_int)));
+    }
+    if let Some(value) = values.megapixels.as_ref() {
+        fields.push((Column::Megapixels, convert_one_or_many(value, map_float)));
+    }
     fields
 }
 
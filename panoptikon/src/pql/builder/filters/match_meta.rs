@@ -0,0 +1,222 @@
+use sea_query::{Alias, Expr, ExprTrait, Func, JoinType};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::db::item_meta::MetaValue;
+use crate::pql::preprocess::PqlError;
+
+use super::super::{
+    BaseTable, CteRef, Files, ItemMeta, JoinedTables, QueryState, select_std_from_cte, wrap_query,
+};
+use super::FilterCompiler;
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub(crate) struct MatchMetaArgs {
+    /// The metadata field to test
+    pub key: String,
+    /// Value equals this. Numbers compare numerically, so `2` matches a stored `2.0`
+    #[serde(default)]
+    pub eq: Option<MetaValue>,
+    /// Value is greater than this number or string
+    #[serde(default)]
+    pub gt: Option<MetaValue>,
+    /// Value is greater than or equal to this number or string
+    #[serde(default)]
+    pub gte: Option<MetaValue>,
+    /// Value is less than this number or string
+    #[serde(default)]
+    pub lt: Option<MetaValue>,
+    /// Value is less than or equal to this number or string
+    #[serde(default)]
+    pub lte: Option<MetaValue>,
+    /// String value contains this text (case-insensitive)
+    #[serde(default)]
+    pub contains: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub(crate) struct MatchMeta {
+    /// Match Meta
+    ///
+    /// Only include items with this metadata field. Without any comparison,
+    /// every item that has the key matches. Comparisons only match values of
+    /// the same type: a number never equals the string "2".
+    pub match_meta: MatchMetaArgs,
+}
+
+#[derive(Clone, Copy)]
+enum Comparison {
+    Eq,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+}
+
+fn compare(lhs: Expr, op: Comparison, rhs: Expr) -> Expr {
+    match op {
+        Comparison::Eq => lhs.eq(rhs),
+        Comparison::Gt => lhs.gt(rhs),
+        Comparison::Gte => lhs.gte(rhs),
+        Comparison::Lt => lhs.lt(rhs),
+        Comparison::Lte => lhs.lte(rhs),
+    }
+}
+
+/// `value` holds the JSON encoding of the stored scalar; `json_type` tells
+/// the kinds apart and numbers are compared as REAL so integers and floats mix.
+fn value_condition(
+    value_col: &Expr,
+    op: Comparison,
+    operand: &MetaValue,
+) -> Result<Expr, PqlError> {
+    let json_type: Expr = Func::cust("json_type").arg(value_col.clone()).into();
+    let extracted: Expr = Func::cust("json_extract")
+        .args([value_col.clone(), Expr::val("$")])
+        .into();
+    Ok(match operand {
+        MetaValue::Bool(flag) => {
+            if !matches!(op, Comparison::Eq) {
+                return Err(PqlError::invalid("match_meta: booleans only support eq"));
+            }
+            json_type.eq(if *flag { "true" } else { "false" })
+        }
+        MetaValue::Int(number) => json_type.is_in(["integer", "real"]).and(compare(
+            Func::cast_as(extracted, Alias::new("REAL")).into(),
+            op,
+            Expr::val(*number as f64),
+        )),
+        MetaValue::Float(number) => json_type.is_in(["integer", "real"]).and(compare(
+            Func::cast_as(extracted, Alias::new("REAL")).into(),
+            op,
+            Expr::val(*number),
+        )),
+        MetaValue::String(text) => {
+            json_type
+                .eq("text")
+                .and(compare(extracted, op, Expr::val(text.clone())))
+        }
+    })
+}
+
+impl FilterCompiler for MatchMeta {
+    fn build(&self, context: &CteRef, state: &mut QueryState) -> Result<CteRef, PqlError> {
+        let args = &self.match_meta;
+        state.uses_user_data = true;
+        let user_data = Alias::new("user_data");
+
+        let mut query = select_std_from_cte(context, state);
+        query.join(
+            JoinType::InnerJoin,
+            Files::Table,
+            Expr::col((Files::Table, Files::Id)).equals(context.column_ref("file_id")),
+        );
+        // (sha256, key) is the primary key, so each row joins at most one field.
+        query.join(
+            JoinType::InnerJoin,
+            (user_data.clone(), ItemMeta::Table),
+            Expr::col((user_data.clone(), ItemMeta::Table, ItemMeta::Sha256))
+                .equals((Files::Table, Files::Sha256))
+                .and(
+                    Expr::col((user_data.clone(), ItemMeta::Table, ItemMeta::Key))
+                        .eq(args.key.clone()),
+                ),
+        );
+
+        let value_col = Expr::col((user_data.clone(), ItemMeta::Table, ItemMeta::Value));
+        let comparisons = [
+            (Comparison::Eq, &args.eq),
+            (Comparison::Gt, &args.gt),
+            (Comparison::Gte, &args.gte),
+            (Comparison::Lt, &args.lt),
+            (Comparison::Lte, &args.lte),
+        ];
+        for (op, operand) in comparisons {
+            if let Some(operand) = operand {
+                query.and_where(value_condition(&value_col, op, operand)?);
+            }
+        }
+        if let Some(text) = &args.contains {
+            let extracted: Expr = Func::cust("json_extract")
+                .args([value_col.clone(), Expr::val("$")])
+                .into();
+            let haystack: Expr = Func::lower(extracted).into();
+            let needle: Expr = Func::lower(Expr::val(text.clone())).into();
+            let json_type: Expr = Func::cust("json_type").arg(value_col.clone()).into();
+            query.and_where(
+                json_type
+                    .eq("text")
+                    .and(Expr::expr(Func::cust("instr").args([haystack, needle])).gt(0)),
+            );
+        }
+
+        let cte_name = format!("n{}_MatchMeta", state.cte_counter);
+        let mut joined_tables = JoinedTables::default();
+        joined_tables.mark(BaseTable::Files);
+        let cte = wrap_query(state, query, context, cte_name, &joined_tables);
+        state.cte_counter += 1;
+        Ok(cte)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pql::model::{EntityType, QueryElement};
+    use serde_json::json;
+
+    use super::super::test_support::{
+        build_base_state, build_begin_cte, render_filter_sql, run_full_pql_query,
+    };
+
+    fn parse(value: serde_json::Value) -> MatchMeta {
+        serde_json::from_value(value).expect("match_meta filter")
+    }
+
+    // Ensures the filter joins the attached table by sha256 and key, compares
+    // numbers as REAL and strings as text, and marks the user_data dependency.
+    #[test]
+    fn match_meta_builds_typed_comparisons() {
+        let filter = parse(json!({
+            "match_meta": { "key": "rating", "gte": 3, "lt": 4.5 }
+        }));
+        let mut state = build_base_state(EntityType::File, false);
+        let context = build_begin_cte(&mut state);
+        let sql = render_filter_sql(&filter, &mut state, &context);
+        assert!(sql.contains(r#""user_data"."item_meta""#));
+        assert!(sql.contains("'rating'"));
+        assert!(sql.contains("IN ('integer', 'real')"));
+        assert!(sql.contains("AS REAL"));
+        assert!(state.uses_user_data);
+
+        let filter = parse(json!({
+            "match_meta": { "key": "status", "eq": "reviewed", "contains": "REV" }
+        }));
+        let mut state = build_base_state(EntityType::File, false);
+        let context = build_begin_cte(&mut state);
+        let sql = render_filter_sql(&filter, &mut state, &context);
+        assert!(sql.contains("= 'text'"));
+        assert!(sql.contains("instr"));
+        assert!(!sql.contains("AS REAL"));
+    }
+
+    // Ensures ordering comparisons against a boolean are rejected.
+    #[test]
+    fn match_meta_rejects_boolean_ranges() {
+        let filter = parse(json!({ "match_meta": { "key": "done", "gt": true } }));
+        let mut state = build_base_state(EntityType::File, false);
+        let context = build_begin_cte(&mut state);
+        assert!(filter.build(&context, &mut state).is_err());
+    }
+
+    // Ensures the generated SQL runs against the migrated schemas.
+    #[tokio::test]
+    async fn match_meta_runs_full_query() {
+        let filter = parse(json!({
+            "match_meta": { "key": "done", "eq": true }
+        }));
+        run_full_pql_query(QueryElement::MatchMeta(filter), EntityType::File)
+            .await
+            .expect("match_meta query");
+    }
+}
@@ -0,0 +1,171 @@
+use sea_query::extension::sqlite::SqliteBinOper;
+use sea_query::{Alias, Expr, ExprTrait, JoinType};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::pql::model::SortableOptions;
+use crate::pql::preprocess::PqlError;
+
+use super::super::{
+    BaseTable, CteRef, ExtraColumn, Files, ItemNotes, ItemNotesFts, JoinedTables, OrderByFilter,
+    QueryState, add_filter_attribution, add_sortable_rank_column, apply_sort_bounds,
+    select_std_from_cte, wrap_query,
+};
+use super::FilterCompiler;
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub(crate) struct MatchNoteArgs {
+    /// Match
+    ///
+    /// The query to match against the user's item notes
+    pub r#match: String,
+    /// The user whose notes are searched
+    ///
+    /// Defaults to `user`, or to the authenticated user when the search
+    /// request carries an identity.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    /// Allow raw FTS5 MATCH Syntax
+    ///
+    /// If set to False, the query will be escaped before being passed to the FTS5 MATCH function
+    #[serde(default = "default_true")]
+    pub raw_fts5_match: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub(crate) struct MatchNote {
+    #[serde(flatten, default)]
+    pub sort: SortableOptions,
+    /// Match Note
+    ///
+    /// Match a query against the notes a user attached to items.
+    /// Only items with a matching note are included.
+    pub match_note: MatchNoteArgs,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_notes_user() -> String {
+    "user".to_string()
+}
+
+impl FilterCompiler for MatchNote {
+    fn build(&self, context: &CteRef, state: &mut QueryState) -> Result<CteRef, PqlError> {
+        let args = &self.match_note;
+        state.uses_user_data = true;
+        let user_data = Alias::new("user_data");
+
+        let mut query = select_std_from_cte(context, state);
+        query.join(
+            JoinType::InnerJoin,
+            Files::Table,
+            Expr::col((Files::Table, Files::Id)).equals(context.column_ref("file_id")),
+        );
+        // (user, sha256) is unique, so each row joins at most one note.
+        query.join(
+            JoinType::InnerJoin,
+            (user_data.clone(), ItemNotes::Table),
+            Expr::col((user_data.clone(), ItemNotes::Table, ItemNotes::Sha256))
+                .equals((Files::Table, Files::Sha256))
+                .and(
+                    Expr::col((user_data.clone(), ItemNotes::Table, ItemNotes::User))
+                        .eq(args.user.clone().unwrap_or_else(default_notes_user)),
+                ),
+        );
+        query.join(
+            JoinType::InnerJoin,
+            (user_data.clone(), ItemNotesFts::Table),
+            Expr::col((user_data.clone(), ItemNotesFts::Table, ItemNotesFts::Rowid)).equals((
+                user_data.clone(),
+                ItemNotes::Table,
+                ItemNotes::Id,
+            )),
+        );
+        query.and_where(
+            Expr::col(ItemNotesFts::Table)
+                .binary(SqliteBinOper::Match, Expr::val(args.r#match.clone())),
+        );
+
+        if !state.is_count_query {
+            add_sortable_rank_column(&mut query, &self.sort)?;
+        }
+
+        let cte_name = format!("n{}_MatchNote", state.cte_counter);
+        let mut joined_tables = JoinedTables::default();
+        joined_tables.mark(BaseTable::Files);
+        let (query, context_for_wrap, joined_tables) = apply_sort_bounds(
+            state,
+            query,
+            context.clone(),
+            &cte_name,
+            &self.sort,
+            joined_tables,
+        );
+
+        let cte = wrap_query(state, query, &context_for_wrap, cte_name, &joined_tables);
+        state.cte_counter += 1;
+        if !state.is_count_query {
+            if let Some(alias) = &self.sort.select_as {
+                state.extra_columns.push(ExtraColumn {
+                    column: "order_rank".to_string(),
+                    cte: cte.clone(),
+                    alias: alias.clone(),
+                });
+            }
+            add_filter_attribution(state, &cte, &self.sort);
+            if self.sort.order_by {
+                state.order_list.push(OrderByFilter {
+                    cte: cte.clone(),
+                    direction: self.sort.direction,
+                    priority: self.sort.priority,
+                    rrf: self.sort.rrf.clone(),
+                });
+            }
+        }
+        Ok(cte)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pql::model::{EntityType, QueryElement};
+    use serde_json::json;
+
+    use super::super::test_support::{
+        build_base_state, build_begin_cte, render_filter_sql, run_full_pql_query,
+    };
+
+    // Ensures the filter joins the attached notes and marks the user_data dependency.
+    #[test]
+    fn match_note_builds_sql() {
+        let filter: MatchNote = serde_json::from_value(json!({
+            "match_note": { "match": "approved", "user": "alice" },
+            "order_by": true
+        }))
+        .expect("match_note filter");
+        let mut state = build_base_state(EntityType::File, false);
+        let context = build_begin_cte(&mut state);
+        let sql = render_filter_sql(&filter, &mut state, &context);
+        assert!(sql.contains(r#""user_data"."item_notes""#));
+        assert!(sql.contains("item_notes_fts"));
+        assert!(sql.contains("MATCH"));
+        assert!(state.uses_user_data);
+    }
+
+    // Ensures the generated SQL runs against the migrated schemas.
+    #[tokio::test]
+    async fn match_note_runs_full_query() {
+        let filter: MatchNote = serde_json::from_value(json!({
+            "match_note": { "match": "approved" },
+            "order_by": true,
+            "gt": 0
+        }))
+        .expect("match_note filter");
+        run_full_pql_query(QueryElement::MatchNote(filter), EntityType::File)
+            .await
+            .expect("match_note query");
+    }
+}
@@ -8,7 +8,8 @@ use crate::pql::preprocess::PqlError;
 
 use super::super::{
     CteRef, ExtraColumn, FilesPathFts, JoinedTables, OrderByFilter, QueryState,
-    add_sortable_rank_column, apply_sort_bounds, select_std_from_cte, wrap_query,
+    add_filter_attribution, add_sortable_rank_column, apply_sort_bounds, select_std_from_cte,
+    wrap_query,
 };
 use super::FilterCompiler;
 
@@ -23,8 +24,11 @@ pub(crate) struct MatchPathArgs {
     pub filename_only: bool,
     /// Allow raw FTS5 MATCH Syntax
     ///
-    /// If set to False, the query will be escaped before being passed to the FTS5 MATCH function
-    #[serde(default = "default_true")]
+    /// If set to False (default), the query is escaped so arbitrary file names
+    /// match literally: each word is quoted, double quotes group a phrase and
+    /// a trailing * makes a prefix query. If set to True, the query is passed
+    /// to the FTS5 MATCH function as is. Also accepted as `raw_fts`.
+    #[serde(default, alias = "raw_fts")]
     pub raw_fts5_match: bool,
 }
 
@@ -38,10 +42,6 @@ pub(crate) struct MatchPath {
     pub match_path: MatchPathArgs,
 }
 
-fn default_true() -> bool {
-    true
-}
-
 impl FilterCompiler for MatchPath {
     fn build(&self, context: &CteRef, state: &mut QueryState) -> Result<CteRef, PqlError> {
         let mut query = select_std_from_cte(context, state);
@@ -88,6 +88,7 @@ impl FilterCompiler for MatchPath {
                     alias: alias.clone(),
                 });
             }
+            add_filter_attribution(state, &cte, &self.sort);
             if self.sort.order_by {
                 state.order_list.push(OrderByFilter {
                     cte: cte.clone(),
@@ -104,13 +105,100 @@ impl FilterCompiler for MatchPath {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::pql::model::{EntityType, QueryElement};
+    use crate::db::migrations::setup_test_databases;
+    use crate::pql::build_query;
+    use crate::pql::model::{EntityType, PqlQuery, QueryElement};
+    use sea_query::SqliteQueryBuilder;
+    use sea_query_sqlx::SqlxBinder;
     use serde_json::json;
 
     use super::super::test_support::{
         build_base_state, build_begin_cte, render_filter_sql, run_full_pql_query,
     };
 
+    async fn matching_paths(
+        conn: &mut sqlx::SqliteConnection,
+        match_path: serde_json::Value,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        let filter: QueryElement =
+            serde_json::from_value(json!({ "match_path": match_path })).expect("match_path");
+        let built = build_query(
+            PqlQuery {
+                query: Some(filter),
+                entity: EntityType::File,
+                ..Default::default()
+            },
+            false,
+        )
+        .expect("build_query");
+        let paginated = built.paginated_query();
+        let (sql, values) = match built.with_clause {
+            Some(with_clause) => paginated.with(with_clause).build_sqlx(SqliteQueryBuilder),
+            None => paginated.build_sqlx(SqliteQueryBuilder),
+        };
+        let mut paths: Vec<String> = sqlx::query_with(sqlx::AssertSqlSafe(sql.as_str()), values)
+            .fetch_all(conn)
+            .await?
+            .iter()
+            .map(|row| sqlx::Row::get(row, "path"))
+            .collect();
+        paths.sort();
+        Ok(paths)
+    }
+
+    // Ensures file names full of FTS5 syntax (colons, parentheses, quotes,
+    // unicode) match literally by default, a trailing * stays a prefix
+    // query, and raw_fts keeps passing the query through unescaped.
+    #[tokio::test]
+    async fn match_path_escapes_the_query_by_default() {
+        let mut dbs = setup_test_databases().await;
+        let conn = &mut dbs.index_conn;
+        sqlx::query(
+            r#"
+INSERT INTO file_scans (id, start_time, path) VALUES (1, '2024-01-01T00:00:00', '/');
+INSERT INTO items (id, sha256, md5, type, time_added) VALUES
+    (1, 'sha1', 'md51', 'audio/mpeg', '2024-01-01T00:00:00'),
+    (2, 'sha2', 'md52', 'image/png', '2024-01-01T00:00:00'),
+    (3, 'sha3', 'md53', 'image/png', '2024-01-01T00:00:00');
+INSERT INTO files (id, sha256, item_id, path, filename, last_modified, scan_id, available) VALUES
+    (1, 'sha1', 1, 'C:\Music\Song (v2).mp3', 'Song (v2).mp3', '2024-01-01T00:00:00', 1, 1),
+    (2, 'sha2', 2, '/photos/the "best" shot.png', 'the "best" shot.png', '2024-01-01T00:00:00', 1, 1),
+    (3, 'sha3', 3, '/photos/Grüße aus Köln.png', 'Grüße aus Köln.png', '2024-01-01T00:00:00', 1, 1);
+            "#,
+        )
+        .execute(&mut *conn)
+        .await
+        .expect("seed files");
+
+        let song = vec!["C:\\Music\\Song (v2).mp3".to_string()];
+        for query in ["C:\\Music", "(v2)", "Song (v2)", "\"Song (v2).mp3\""] {
+            let paths = matching_paths(conn, json!({ "match": query })).await;
+            assert_eq!(paths.expect(query), song, "{query}");
+        }
+        let quoted = matching_paths(conn, json!({ "match": "\\\"best\\\"" }))
+            .await
+            .unwrap();
+        assert_eq!(quoted, vec!["/photos/the \"best\" shot.png"]);
+        let unicode = matching_paths(
+            conn,
+            json!({ "match": "grüße köln", "filename_only": true }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(unicode, vec!["/photos/Grüße aus Köln.png"]);
+        let prefix = matching_paths(conn, json!({ "match": "/phot*" }))
+            .await
+            .unwrap();
+        assert_eq!(prefix.len(), 2);
+
+        let raw = matching_paths(conn, json!({ "match": "Song (v2)", "raw_fts": true })).await;
+        assert!(raw.is_err());
+        let raw = matching_paths(conn, json!({ "match": "song OR köln", "raw_fts": true }))
+            .await
+            .unwrap();
+        assert_eq!(raw.len(), 2);
+    }
+
     #[test]
     fn match_path_builds_sql() {
         let filter: MatchPath = serde_json::from_value(json!({
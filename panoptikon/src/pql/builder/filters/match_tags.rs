@@ -7,8 +7,8 @@ use crate::pql::preprocess::PqlError;
 
 use super::super::{
     CteRef, ExtraColumn, ItemData, JoinedTables, OrderByFilter, QueryState, Setters, Tags,
-    TagsItems, add_rank_column_expr, apply_group_by, apply_sort_bounds, create_cte,
-    get_std_group_by, select_std_from_cte, wrap_query,
+    TagsItems, add_filter_attribution, add_rank_column_expr, apply_group_by, apply_sort_bounds,
+    create_cte, get_std_group_by, select_std_from_cte, wrap_query,
 };
 use super::FilterCompiler;
 
@@ -43,6 +43,33 @@ pub(crate) struct TagsArgs {
     /// you can combine this with a separate filter for each tag in an OrOperator.
     #[serde(default)]
     pub all_setters_required: bool,
+    /// Expand tag aliases
+    ///
+    /// Match each tag's whole alias group (its canonical tag and all of the
+    /// canonical tag's aliases). A group counts as one tag when all tags are
+    /// required.
+    #[serde(default = "default_true")]
+    pub expand_aliases: bool,
+    /// The alias groups of `tags`, filled in by preprocessing. Tags sharing
+    /// a group share one entry.
+    #[serde(skip)]
+    pub alias_groups: Vec<Vec<String>>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl TagsArgs {
+    /// The tag groups to match: the resolved alias groups, or one group per
+    /// tag when none were resolved.
+    fn tag_groups(&self) -> Vec<Vec<String>> {
+        if self.alias_groups.is_empty() {
+            self.tags.iter().map(|tag| vec![tag.clone()]).collect()
+        } else {
+            self.alias_groups.clone()
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, ToSchema)]
@@ -85,7 +112,13 @@ impl FilterCompiler for MatchTags {
         let args = &self.match_tags;
         let cte_name = format!("n{}_MatchTags", state.cte_counter);
         let mut conditions = Vec::new();
-        let tag_values = args.tags.iter().cloned().map(Expr::val).collect::<Vec<_>>();
+        let tag_groups = args.tag_groups();
+        let tag_values = tag_groups
+            .iter()
+            .flatten()
+            .cloned()
+            .map(Expr::val)
+            .collect::<Vec<_>>();
         conditions.push(Expr::col((Tags::Table, Tags::Name)).is_in(tag_values));
         if args.min_confidence > 0.0 {
             conditions.push(
@@ -147,19 +180,34 @@ impl FilterCompiler for MatchTags {
         }
         apply_group_by(&mut matching_items_select, get_std_group_by(context, state));
 
+        // Aliased tags are counted by group, so any member satisfies it.
+        let matched_tag = if tag_groups.iter().all(|group| group.len() == 1) {
+            Expr::col((Tags::Table, Tags::Name))
+        } else {
+            let mut case = Expr::case(
+                Expr::col((Tags::Table, Tags::Name)).is_in(tag_groups[0].clone()),
+                0,
+            );
+            for (index, group) in tag_groups.iter().enumerate().skip(1) {
+                case = case.case(
+                    Expr::col((Tags::Table, Tags::Name)).is_in(group.clone()),
+                    index as i64,
+                );
+            }
+            case.into()
+        };
         let mut having_clauses = Vec::new();
         if args.all_setters_required {
             let setter_tag = Expr::col((ItemData::Table, ItemData::SetterId))
                 .binary(BinOper::Custom("||"), Expr::val("-"))
-                .binary(BinOper::Custom("||"), Expr::col((Tags::Table, Tags::Name)));
-            let expected = (args.tags.len() * args.setters.len()) as i64;
+                .binary(BinOper::Custom("||"), matched_tag);
+            let expected = (tag_groups.len() * args.setters.len()) as i64;
             having_clauses.push(Func::count_distinct(setter_tag).eq(expected));
         } else {
-            let expected = args.tags.len() as i64;
-            having_clauses
-                .push(Func::count_distinct(Expr::col((Tags::Table, Tags::Name))).eq(expected));
+            let expected = tag_groups.len() as i64;
+            having_clauses.push(Func::count_distinct(matched_tag).eq(expected));
         }
-        if args.match_any && args.tags.len() > 1 {
+        if args.match_any && tag_groups.len() > 1 {
             having_clauses.clear();
         }
         for clause in having_clauses {
@@ -167,8 +215,20 @@ impl FilterCompiler for MatchTags {
         }
 
         if !state.is_count_query {
-            let avg_confidence =
-                Func::avg(Expr::col((TagsItems::Table, TagsItems::Confidence))).into();
+            // Rank by the merged tag rows (idx 0) only: per-frame video rows
+            // repeat those tags and would weigh videos by their frame count.
+            // A tag found only on frames (a rating the merge didn't pick)
+            // still ranks by its frame confidences.
+            let confidence = Expr::col((TagsItems::Table, TagsItems::Confidence));
+            let merged_confidence = Expr::case(
+                Expr::col((ItemData::Table, ItemData::Idx)).eq(0),
+                confidence.clone(),
+            );
+            let avg_confidence = Func::coalesce([
+                Expr::from(Func::avg(merged_confidence)),
+                Expr::from(Func::avg(confidence)),
+            ])
+            .into();
             add_rank_column_expr(&mut matching_items_select, &self.sort, avg_confidence)?;
         }
 
@@ -215,6 +275,7 @@ impl FilterCompiler for MatchTags {
                     alias: alias.clone(),
                 });
             }
+            add_filter_attribution(state, &cte, &self.sort);
             if self.sort.order_by {
                 state.order_list.push(OrderByFilter {
                     cte: cte.clone(),
@@ -269,6 +330,48 @@ mod tests {
         assert!(sql.contains("SELECT"));
     }
 
+    // Ensures alias groups are matched as a whole and counted once each.
+    #[tokio::test]
+    async fn match_tags_counts_alias_groups_once() {
+        let mut filter: MatchTags = serde_json::from_value(json!({
+            "match_tags": { "tags": ["grayscale", "cat"] }
+        }))
+        .expect("match_tags filter");
+        filter.match_tags.alias_groups = vec![
+            vec!["monochrome".to_string(), "grayscale".to_string()],
+            vec!["cat".to_string()],
+        ];
+        let mut state = build_base_state(EntityType::File, false);
+        let context = build_begin_cte(&mut state);
+        let sql = render_filter_sql(&filter, &mut state, &context);
+        assert!(sql.contains("'monochrome'"));
+        assert!(sql.contains("CASE WHEN"));
+        assert!(sql.contains("= 2"));
+
+        run_full_pql_query(QueryElement::MatchTags(filter), EntityType::File)
+            .await
+            .expect("match_tags query");
+    }
+
+    // Ensures only merged tag rows feed the rank, so per-frame video tags
+    // don't count twice, falling back to frame rows when nothing else matched.
+    #[test]
+    fn match_tags_ranks_by_merged_rows() {
+        let filter: MatchTags = serde_json::from_value(json!({
+            "match_tags": { "tags": ["cat"] }
+        }))
+        .expect("match_tags filter");
+        let mut state = build_base_state(EntityType::File, false);
+        let context = build_begin_cte(&mut state);
+        let sql = render_filter_sql(&filter, &mut state, &context);
+        assert!(
+            sql.contains(
+                r#"COALESCE(AVG((CASE WHEN ("item_data"."idx" = 0) THEN "tags_items"."confidence" END)), AVG("tags_items"."confidence"))"#
+            ),
+            "{sql}"
+        );
+    }
+
     #[tokio::test]
     async fn match_tags_runs_full_query() {
         let filter: MatchTags = serde_json::from_value(json!({
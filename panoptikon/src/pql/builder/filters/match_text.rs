@@ -10,8 +10,9 @@ use crate::pql::preprocess::PqlError;
 
 use super::super::{
     BaseTable, CteRef, ExtraColumn, ExtractedText, ExtractedTextFts, ItemData, JoinedTables,
-    OrderByFilter, QueryState, Setters, add_rank_column_expr, apply_group_by, apply_sort_bounds,
-    create_cte, get_std_group_by, select_std_from_cte, wrap_query,
+    OrderByFilter, QueryState, Setters, add_filter_attribution, add_rank_column_expr,
+    apply_group_by, apply_sort_bounds, create_cte, get_std_group_by, select_std_from_cte,
+    wrap_query,
 };
 use super::FilterCompiler;
 
@@ -30,7 +31,8 @@ pub(crate) struct MatchTextArgs {
     /// Order by, select_as, and row_n will also be ignored.
     ///
     /// If set to False (default), and the match field is empty,
-    /// this filter will be skipped entirely.
+    /// this filter behaves as if it were True when any of the other
+    /// criteria is set, and is skipped entirely otherwise.
     #[serde(default)]
     pub filter_only: bool,
     /// Include text from these setters
@@ -136,23 +138,43 @@ fn default_snippet_end_tag() -> String {
     "</b>".to_string()
 }
 
+impl MatchTextArgs {
+    /// Whether any criterion other than the query itself is set.
+    pub(crate) fn has_criteria(&self) -> bool {
+        self.min_length.is_some_and(|length| length > 0)
+            || self.max_length.is_some_and(|length| length > 0)
+            || !self.setters.is_empty()
+            || !self.languages.is_empty()
+            || self
+                .min_language_confidence
+                .is_some_and(|value| value > 0.0)
+            || self.min_confidence.is_some_and(|value| value > 0.0)
+    }
+}
+
 impl FilterCompiler for MatchText {
     fn build(&self, context: &CteRef, state: &mut QueryState) -> Result<CteRef, PqlError> {
         let args = &self.match_text;
         let cte_name = format!("n{}_MatchText", state.cte_counter);
+        // Without a query there is nothing to MATCH (an empty one is a
+        // runtime error), so the FTS table is not joined at all and the
+        // remaining criteria read extracted_text alone.
+        let fts_match =
+            Some(args.r#match.as_str()).filter(|query| !args.filter_only && !query.is_empty());
         // An empty alias means "no snippet", matching Python's truthiness
         // gate — the UI sends select_snippet_as: "" on every text search.
+        // Snippets come from the FTS table, so they need a query too.
         let snippet_alias = args
             .select_snippet_as
             .as_deref()
-            .filter(|alias| !alias.is_empty());
+            .filter(|alias| !alias.is_empty() && fts_match.is_some());
         let want_snippet = snippet_alias.is_some() && !state.is_count_query;
 
         let mut criteria = Vec::new();
-        if !args.filter_only {
+        if let Some(query) = fts_match {
             criteria.push(
                 Expr::col((ExtractedTextFts::Table, ExtractedTextFts::Text))
-                    .binary(SqliteBinOper::Match, Expr::val(args.r#match.clone())),
+                    .binary(SqliteBinOper::Match, Expr::val(query)),
             );
         }
         if let Some(min_length) = args.min_length {
@@ -236,12 +258,14 @@ impl FilterCompiler for MatchText {
                 Expr::col((ExtractedText::Table, ExtractedText::Id))
                     .equals((ItemData::Table, ItemData::Id)),
             );
-            query.join(
-                JoinType::InnerJoin,
-                ExtractedTextFts::Table,
-                Expr::cust("extracted_text_fts.rowid")
-                    .equals((ExtractedText::Table, ExtractedText::Id)),
-            );
+            if fts_match.is_some() {
+                query.join(
+                    JoinType::InnerJoin,
+                    ExtractedTextFts::Table,
+                    Expr::cust("extracted_text_fts.rowid")
+                        .equals((ExtractedText::Table, ExtractedText::Id)),
+                );
+            }
             for condition in criteria {
                 query.and_where(condition);
             }
@@ -285,7 +309,7 @@ impl FilterCompiler for MatchText {
             } else {
                 apply_group_by(&mut final_query, get_std_group_by(context, state));
                 if !state.is_count_query {
-                    let rank_expr = if args.filter_only {
+                    let rank_expr = if fts_match.is_none() {
                         Expr::val(1)
                     } else {
                         Func::min(Expr::cust("rank")).into()
@@ -329,6 +353,7 @@ impl FilterCompiler for MatchText {
                         alias: alias.to_string(),
                     });
                 }
+                add_filter_attribution(state, &cte, &self.sort);
                 if self.sort.order_by {
                     state.order_list.push(OrderByFilter {
                         cte: cte.clone(),
@@ -358,12 +383,14 @@ impl FilterCompiler for MatchText {
             Setters::Table,
             Expr::col((Setters::Table, Setters::Id)).equals((ItemData::Table, ItemData::SetterId)),
         );
-        query.join(
-            JoinType::InnerJoin,
-            ExtractedTextFts::Table,
-            Expr::cust("extracted_text_fts.rowid")
-                .equals((ExtractedText::Table, ExtractedText::Id)),
-        );
+        if fts_match.is_some() {
+            query.join(
+                JoinType::InnerJoin,
+                ExtractedTextFts::Table,
+                Expr::cust("extracted_text_fts.rowid")
+                    .equals((ExtractedText::Table, ExtractedText::Id)),
+            );
+        }
         for condition in criteria {
             query.and_where(condition);
         }
@@ -392,7 +419,7 @@ impl FilterCompiler for MatchText {
         }
 
         if !state.is_count_query {
-            let rank_expr = if args.filter_only {
+            let rank_expr = if fts_match.is_none() {
                 Expr::val(1)
             } else {
                 Expr::cust("rank")
@@ -432,6 +459,7 @@ impl FilterCompiler for MatchText {
                     alias: alias.to_string(),
                 });
             }
+            add_filter_attribution(state, &cte, &self.sort);
             if self.sort.order_by {
                 state.order_list.push(OrderByFilter {
                     cte: cte.clone(),
@@ -468,6 +496,73 @@ mod tests {
         assert!(sql.contains("SELECT"));
     }
 
+    // A length-only filter reads extracted_text without the FTS join, on
+    // both the item and the text entity paths; adding a query brings the
+    // join and its MATCH back.
+    #[test]
+    fn match_text_without_query_skips_fts_join() {
+        for entity in [EntityType::File, EntityType::Text] {
+            let length_only: MatchText = serde_json::from_value(json!({
+                "match_text": { "match": "", "filter_only": true, "min_length": 200 }
+            }))
+            .expect("match_text filter");
+            let mut state = build_base_state(entity, false);
+            let context = build_begin_cte(&mut state);
+            let sql = render_filter_sql(&length_only, &mut state, &context);
+            assert!(!sql.contains("extracted_text_fts"), "{sql}");
+            assert!(!sql.contains("MATCH"), "{sql}");
+            assert!(
+                sql.contains(r#""extracted_text"."text_length" >= 200"#),
+                "{sql}"
+            );
+
+            let with_query: MatchText = serde_json::from_value(json!({
+                "match_text": { "match": "hello", "min_length": 200 }
+            }))
+            .expect("match_text filter");
+            let mut state = build_base_state(entity, false);
+            let context = build_begin_cte(&mut state);
+            let sql = render_filter_sql(&with_query, &mut state, &context);
+            assert!(
+                sql.contains(r#""extracted_text_fts"."text" MATCH 'hello'"#),
+                "{sql}"
+            );
+            assert!(
+                sql.contains(r#""extracted_text"."text_length" >= 200"#),
+                "{sql}"
+            );
+        }
+    }
+
+    // An empty query with other criteria becomes filter_only instead of
+    // compiling to MATCH '', and runs; on its own it is still dropped.
+    #[tokio::test]
+    async fn empty_match_with_criteria_is_filter_only() {
+        let filter: MatchText = serde_json::from_value(json!({
+            "match_text": { "match": "  ", "max_length": 50, "select_snippet_as": "snip" }
+        }))
+        .expect("match_text filter");
+        let Some(QueryElement::MatchText(validated)) =
+            crate::pql::preprocess::preprocess_query(QueryElement::MatchText(filter.clone()))
+                .expect("preprocess")
+        else {
+            panic!("filter with criteria must be kept");
+        };
+        assert!(validated.match_text.filter_only);
+        assert!(validated.match_text.select_snippet_as.is_none());
+        run_full_pql_query(QueryElement::MatchText(filter), EntityType::File)
+            .await
+            .expect("length-only match_text query");
+
+        let bare: MatchText = serde_json::from_value(json!({
+            "match_text": { "match": "" }
+        }))
+        .expect("match_text filter");
+        let dropped = crate::pql::preprocess::preprocess_query(QueryElement::MatchText(bare))
+            .expect("preprocess");
+        assert!(dropped.is_none());
+    }
+
     #[tokio::test]
     async fn match_text_runs_full_query() {
         let filter: MatchText = serde_json::from_value(json!({
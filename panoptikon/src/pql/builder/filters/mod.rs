@@ -2,8 +2,11 @@ mod embedding_types;
 mod has_unprocessed;
 mod image_embeddings;
 mod in_bookmarks;
+mod in_folder;
 mod item_similarity;
 mod match_filter;
+mod match_meta;
+mod match_note;
 mod match_path;
 mod match_tags;
 mod match_text;
@@ -17,12 +20,15 @@ use crate::pql::preprocess::PqlError;
 pub(crate) use embedding_types::{DistanceAggregation, DistanceFunction, IndexMode, QuantResolved};
 pub(crate) use has_unprocessed::{DerivedDataArgs, HasUnprocessedData};
 pub(crate) use image_embeddings::{SemanticImageArgs, SemanticImageSearch};
-pub(crate) use in_bookmarks::{InBookmarks, InBookmarksArgs};
+pub(crate) use in_bookmarks::{BookmarkRankBy, InBookmarks, InBookmarksArgs};
+pub(crate) use in_folder::{InFolder, InFolderArgs};
 pub(crate) use item_similarity::{SimilarTo, SimilarityArgs, SourceArgs};
 pub(crate) use match_filter::{
     Match, MatchAnd, MatchNot, MatchOps, MatchOr, MatchValue, MatchValues, Matches, OneOrMany,
-    evaluate_match,
+    build_match_any, evaluate_match, in_memory_match_error, match_columns,
 };
+pub(crate) use match_meta::{MatchMeta, MatchMetaArgs};
+pub(crate) use match_note::{MatchNote, MatchNoteArgs};
 pub(crate) use match_path::{MatchPath, MatchPathArgs};
 pub(crate) use match_tags::{MatchTags, TagsArgs};
 pub(crate) use match_text::{MatchText, MatchTextArgs};
@@ -39,6 +45,7 @@ pub(crate) mod test_support {
 
     use sea_query::{Alias, Cond, Expr, ExprTrait, Query, SqliteQueryBuilder};
     use sea_query_sqlx::SqlxBinder;
+    use sqlx::Row;
 
     use crate::db::migrations::setup_test_databases;
     use crate::db::sql_functions::ensure_sqlite_extensions;
@@ -58,6 +65,7 @@ pub(crate) mod test_support {
         QueryState {
             order_list: Vec::new(),
             extra_columns: Vec::new(),
+            attributions: Vec::new(),
             selects: HashMap::new(),
             ctes: Vec::new(),
             cte_counter: 0,
@@ -65,6 +73,8 @@ pub(crate) mod test_support {
             item_data_query: matches!(entity, EntityType::Text),
             entity,
             uses_user_data: false,
+            optimize: false,
+            attribute_filters: false,
         }
     }
 
@@ -109,6 +119,114 @@ pub(crate) mod test_support {
         select.with(with_clause).to_string(SqliteQueryBuilder)
     }
 
+    /// The (item_id, file_id, data_id) rows `filter` keeps from every file
+    /// (or every item_data row of the entity's type), sorted.
+    pub(crate) async fn filter_result_ids<F: FilterCompiler>(
+        filter: &F,
+        entity: EntityType,
+        conn: &mut sqlx::SqliteConnection,
+    ) -> Vec<(i64, i64, Option<i64>)> {
+        let mut state = build_base_state(entity, false);
+        let context = build_begin_cte(&mut state);
+        let sql = render_filter_sql(filter, &mut state, &context);
+        let rows = sqlx::query(sqlx::AssertSqlSafe(sql.as_str()))
+            .fetch_all(conn)
+            .await
+            .expect("filter query");
+        let mut ids = rows
+            .iter()
+            .map(|row| {
+                let data_id = state
+                    .item_data_query
+                    .then(|| row.get::<i64, _>("data_id"));
+                (row.get("item_id"), row.get("file_id"), data_id)
+            })
+            .collect::<Vec<_>>();
+        ids.sort_unstable();
+        ids
+    }
+
+    /// Items 1-4 with one file each (item 1 has two), `text` data from the
+    /// `ocr` setter, `clip` embeddings derived from some of it, a placeholder
+    /// and `tags` from `tagger`: enough to tell processed from unprocessed.
+    pub(crate) async fn seed_derived_data(conn: &mut sqlx::SqliteConnection) {
+        sqlx::query(
+            r#"
+            INSERT INTO file_scans (id, start_time, path)
+            VALUES (1, '2024-01-01T00:00:00', '/data');
+            INSERT INTO items (id, sha256, md5, type, time_added)
+            VALUES
+                (1, 'sha_1', 'md5_1', 'image/png', '2024-01-01T00:00:00'),
+                (2, 'sha_2', 'md5_2', 'image/png', '2024-01-01T00:00:00'),
+                (3, 'sha_3', 'md5_3', 'image/png', '2024-01-01T00:00:00'),
+                (4, 'sha_4', 'md5_4', 'image/png', '2024-01-01T00:00:00');
+            INSERT INTO files (id, sha256, item_id, path, filename, last_modified, scan_id, available)
+            VALUES
+                (10, 'sha_1', 1, '/data/one.png', 'one.png', '2024-01-01T00:00:00', 1, 1),
+                (11, 'sha_1', 1, '/data/copy.png', 'copy.png', '2024-01-01T00:00:00', 1, 1),
+                (12, 'sha_2', 2, '/data/two.png', 'two.png', '2024-01-01T00:00:00', 1, 1),
+                (13, 'sha_3', 3, '/data/three.png', 'three.png', '2024-01-01T00:00:00', 1, 1),
+                (14, 'sha_4', 4, '/data/four.png', 'four.png', '2024-01-01T00:00:00', 1, 1);
+            INSERT INTO setters (id, name) VALUES (1, 'ocr'), (2, 'clip'), (3, 'tagger');
+            INSERT INTO item_data
+                (id, item_id, setter_id, data_type, idx, is_origin, source_id, is_placeholder)
+            VALUES
+                (100, 1, 1, 'text', 0, 1, NULL, 0),
+                (101, 1, 2, 'text-embedding', 0, NULL, 100, 0),
+                (102, 2, 1, 'text', 0, 1, NULL, 0),
+                (103, 2, 1, 'text', 1, 1, NULL, 0),
+                (104, 2, 2, 'text-embedding', 0, NULL, 103, 0),
+                (105, 3, 1, 'text', 0, 1, NULL, 1),
+                (106, 4, 3, 'tags', 0, 1, NULL, 0);
+            "#,
+        )
+        .execute(conn)
+        .await
+        .expect("seed derived data");
+    }
+
+    /// The `detail` column of EXPLAIN QUERY PLAN for the full paginated query.
+    pub(crate) async fn explain_pql_query(query: PqlQuery) -> Vec<String> {
+        ensure_vec_extension_loaded();
+        let built = build_query(query, false).expect("build_query");
+        let mut dbs = setup_test_databases().await;
+
+        let paginated = built.paginated_query();
+        let (sql, values) = match built.with_clause {
+            Some(with_clause) => paginated.with(with_clause).build_sqlx(SqliteQueryBuilder),
+            None => paginated.build_sqlx(SqliteQueryBuilder),
+        };
+        let explain = format!("EXPLAIN QUERY PLAN {sql}");
+        sqlx::query_with(sqlx::AssertSqlSafe(explain.as_str()), values)
+            .fetch_all(&mut dbs.index_conn)
+            .await
+            .expect("explain query plan")
+            .iter()
+            .map(|row| row.get::<String, _>("detail"))
+            .collect()
+    }
+
+    /// Asserts `plan` reads `setters` exactly once, directly under an
+    /// uncorrelated (run once per statement) scalar subquery, and never
+    /// groups the context.
+    pub(crate) fn assert_constant_setter_lookup(plan: &[String]) {
+        let lookups = plan
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| line.contains(" setters "))
+            .collect::<Vec<_>>();
+        assert_eq!(lookups.len(), 1, "{plan:#?}");
+        let (index, _) = lookups[0];
+        assert!(
+            index > 0 && plan[index - 1].starts_with("SCALAR SUBQUERY"),
+            "{plan:#?}"
+        );
+        assert!(
+            !plan.iter().any(|line| line.contains("GROUP BY")),
+            "{plan:#?}"
+        );
+    }
+
     pub(crate) async fn run_pql_query(query: PqlQuery) -> Result<(), sqlx::Error> {
         ensure_vec_extension_loaded();
         let built = build_query(query, false).expect("build_query");
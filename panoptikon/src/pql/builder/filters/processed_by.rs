@@ -1,12 +1,11 @@
-use sea_query::{Expr, ExprTrait, JoinType};
+use sea_query::{Expr, ExprTrait, Query};
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
 use crate::pql::preprocess::PqlError;
 
 use super::super::{
-    BaseTable, CteRef, ItemData, JoinedTables, QueryState, Setters, apply_group_by,
-    get_std_group_by, select_std_from_cte, wrap_query,
+    CteRef, ItemData, JoinedTables, QueryState, select_std_from_cte, setter_id_by_name, wrap_query,
 };
 use super::FilterCompiler;
 
@@ -19,25 +18,27 @@ pub(crate) struct ProcessedBy {
 impl FilterCompiler for ProcessedBy {
     fn build(&self, context: &CteRef, state: &mut QueryState) -> Result<CteRef, PqlError> {
         let cte_name = format!("n{}_ProcessedBy", state.cte_counter);
-        let mut query = select_std_from_cte(context, state);
-        let join_cond = if state.item_data_query {
+        // A correlated EXISTS keeps one row per context row, so no GROUP BY
+        // over the whole context is needed to undo a join's fan-out.
+        let owner = if state.item_data_query {
             Expr::col((ItemData::Table, ItemData::SourceId)).equals(context.column_ref("data_id"))
         } else {
             Expr::col((ItemData::Table, ItemData::ItemId)).equals(context.column_ref("item_id"))
         };
-        query.join(JoinType::InnerJoin, ItemData::Table, join_cond);
-        query.join(
-            JoinType::InnerJoin,
-            Setters::Table,
-            Expr::col((Setters::Table, Setters::Id)).equals((ItemData::Table, ItemData::SetterId)),
-        );
-        query.and_where(Expr::col((Setters::Table, Setters::Name)).eq(self.processed_by.clone()));
-
-        apply_group_by(&mut query, get_std_group_by(context, state));
-
-        let mut joined_tables = JoinedTables::default();
-        joined_tables.mark(BaseTable::ItemData);
-        joined_tables.mark(BaseTable::Setters);
+        let mut derived = Query::select();
+        derived
+            .expr(Expr::val(1))
+            .from(ItemData::Table)
+            .and_where(owner)
+            .and_where(
+                Expr::col((ItemData::Table, ItemData::SetterId))
+                    .eq(setter_id_by_name(&self.processed_by)),
+            );
+
+        let mut query = select_std_from_cte(context, state);
+        query.and_where(Expr::exists(derived));
+
+        let joined_tables = JoinedTables::default();
         let cte = wrap_query(state, query, context, cte_name, &joined_tables);
         state.cte_counter += 1;
         Ok(cte)
@@ -47,12 +48,59 @@ impl FilterCompiler for ProcessedBy {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::pql::model::{EntityType, QueryElement};
+    use crate::pql::model::{EntityType, PqlQuery, QueryElement};
     use serde_json::json;
 
+    use super::super::super::{
+        BaseTable, Setters, apply_group_by, get_std_group_by, select_std_from_cte, wrap_query,
+    };
     use super::super::test_support::{
-        build_base_state, build_begin_cte, render_filter_sql, run_full_pql_query,
+        assert_constant_setter_lookup, build_base_state, build_begin_cte, explain_pql_query,
+        filter_result_ids, render_filter_sql, run_full_pql_query, seed_derived_data,
     };
+    use crate::db::migrations::setup_test_databases;
+    use sea_query::JoinType;
+
+    /// The join + GROUP BY build this filter used before switching to EXISTS,
+    /// kept to check that both return the same rows.
+    struct LegacyProcessedBy(ProcessedBy);
+
+    impl FilterCompiler for LegacyProcessedBy {
+        fn build(&self, context: &CteRef, state: &mut QueryState) -> Result<CteRef, PqlError> {
+            let cte_name = format!("n{}_LegacyProcessedBy", state.cte_counter);
+            let mut query = select_std_from_cte(context, state);
+            let join_cond = if state.item_data_query {
+                Expr::col((ItemData::Table, ItemData::SourceId))
+                    .equals(context.column_ref("data_id"))
+            } else {
+                Expr::col((ItemData::Table, ItemData::ItemId)).equals(context.column_ref("item_id"))
+            };
+            query.join(JoinType::InnerJoin, ItemData::Table, join_cond);
+            query.join(
+                JoinType::InnerJoin,
+                Setters::Table,
+                Expr::col((Setters::Table, Setters::Id))
+                    .equals((ItemData::Table, ItemData::SetterId)),
+            );
+            query.and_where(
+                Expr::col((Setters::Table, Setters::Name)).eq(self.0.processed_by.clone()),
+            );
+            apply_group_by(&mut query, get_std_group_by(context, state));
+
+            let mut joined_tables = JoinedTables::default();
+            joined_tables.mark(BaseTable::ItemData);
+            joined_tables.mark(BaseTable::Setters);
+            let cte = wrap_query(state, query, context, cte_name, &joined_tables);
+            state.cte_counter += 1;
+            Ok(cte)
+        }
+    }
+
+    fn processed_by(setter: &str) -> ProcessedBy {
+        ProcessedBy {
+            processed_by: setter.to_string(),
+        }
+    }
 
     #[test]
     fn processed_by_builds_sql() {
@@ -63,8 +111,9 @@ mod tests {
         let mut state = build_base_state(EntityType::File, false);
         let context = build_begin_cte(&mut state);
         let sql = render_filter_sql(&filter, &mut state, &context);
+        assert!(sql.contains("EXISTS"));
         assert!(sql.contains("setters"));
-        assert!(sql.contains("SELECT"));
+        assert!(!sql.contains("GROUP BY"));
     }
 
     #[tokio::test]
@@ -77,4 +126,41 @@ mod tests {
             .await
             .expect("processed_by query");
     }
+
+    // Ensures the EXISTS build returns exactly the rows the join + GROUP BY
+    // build did, for items and for text (matched through source_id).
+    #[tokio::test]
+    async fn processed_by_matches_legacy_results() {
+        let mut dbs = setup_test_databases().await;
+        seed_derived_data(&mut dbs.index_conn).await;
+        for entity in [EntityType::File, EntityType::Text] {
+            for setter in ["ocr", "clip", "tagger", "missing"] {
+                let current =
+                    filter_result_ids(&processed_by(setter), entity, &mut dbs.index_conn).await;
+                let legacy = filter_result_ids(
+                    &LegacyProcessedBy(processed_by(setter)),
+                    entity,
+                    &mut dbs.index_conn,
+                )
+                .await;
+                assert_eq!(current, legacy, "{entity:?} processed_by {setter}");
+            }
+        }
+        let clip_texts =
+            filter_result_ids(&processed_by("clip"), EntityType::Text, &mut dbs.index_conn).await;
+        assert!(!clip_texts.is_empty());
+    }
+
+    // Ensures SQLite resolves the setter name once, as an uncorrelated scalar
+    // subquery, instead of joining setters for every row.
+    #[tokio::test]
+    async fn processed_by_plan_uses_constant_setter_lookup() {
+        let plan = explain_pql_query(PqlQuery {
+            query: Some(QueryElement::ProcessedBy(processed_by("ocr"))),
+            entity: EntityType::File,
+            ..Default::default()
+        })
+        .await;
+        assert_constant_setter_lookup(&plan);
+    }
 }
@@ -26,6 +26,7 @@ use sea_query::{
 use crate::pql::model::SortableOptions;
 
 use super::super::{CteRef, QueryState, create_cte, direction_to_order};
+use super::embedding_types::{SCORE_COLUMN, ScoreNormalization};
 
 /// Column alias for the coarse Hamming aggregate in the coarse CTE.
 pub(super) const COARSE_DIST: &str = "cdist";
@@ -39,7 +40,10 @@ pub(super) const EXACT_DIST: &str = "edist";
 /// `build_head` receives the ranked CTE (standard columns + `cdist` +
 /// `crank`) to use as its candidate context and must produce a grouped
 /// select with the standard columns plus `edist`, restricted to
-/// `crank <= k`. Returns the merge select (standard columns + `order_rank`)
+/// `crank <= k`. With `score`, the merge also carries the normalized
+/// similarity of `edist` as `norm_score`; tail rows have no exact distance,
+/// so theirs is NULL. Returns the merge select (standard columns +
+/// `order_rank`)
 /// plus the ranked CTE ref, which is the merge's context (the final
 /// assembly joins base tables against the context's columns, so it must be
 /// a table in the merge's FROM scope) — pass it to `apply_sort_bounds` +
@@ -49,6 +53,7 @@ pub(super) fn assemble_two_stage<F>(
     cte_name: &str,
     coarse: SelectStatement,
     sort: &SortableOptions,
+    score: Option<ScoreNormalization>,
     build_head: F,
 ) -> (SelectStatement, CteRef)
 where
@@ -153,6 +158,12 @@ where
         );
     }
     merge.expr_window_as(Expr::cust("row_number()"), window, Alias::new("order_rank"));
+    if let Some(score) = score {
+        merge.expr_as(
+            score.score_expr(Expr::col((head_alias, Alias::new(EXACT_DIST)))),
+            Alias::new(SCORE_COLUMN),
+        );
+    }
 
     (merge, ranked_cte)
 }
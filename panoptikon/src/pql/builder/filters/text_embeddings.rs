@@ -6,12 +6,15 @@ use crate::pql::model::{EntityType, OrderDirection, PartialSortableOptions, Sort
 use crate::pql::preprocess::PqlError;
 
 use super::super::{
-    BaseTable, CteRef, EmbeddingQuants, Embeddings, ExtraColumn, ExtractedText, ItemData,
-    JoinedTables, OrderByFilter, QueryState, Setters, add_rank_column_expr, apply_group_by,
-    apply_sort_bounds, get_std_group_by, select_std_from_cte, wrap_query,
+    BaseTable, CteRef, EmbeddingQuants, Embeddings, ExtractedText, ItemData, JoinedTables,
+    OrderByFilter, QueryState, Setters, add_filter_attribution, add_rank_column_expr,
+    apply_group_by, apply_sort_bounds, get_std_group_by, select_std_from_cte, wrap_query,
 };
 use super::FilterCompiler;
-use super::embedding_types::{DistanceAggregation, IndexMode, QuantResolved, default_k};
+use super::embedding_types::{
+    DistanceAggregation, DistanceFunction, IndexMode, QuantResolved, SCORE_COLUMN,
+    ScoreNormalization, default_k, default_l2_scale, push_score_columns, query_distance,
+};
 use super::item_similarity::SourceArgs;
 use super::quant::{COARSE_DIST, COARSE_RANK, EXACT_DIST, assemble_two_stage};
 
@@ -32,6 +35,22 @@ pub(crate) struct EmbedArgs {
     /// The time-to-live in seconds for the inference *model* to be kept in memory
     #[serde(default = "default_ttl_seconds")]
     pub ttl_seconds: i64,
+    /// Prompt Templates
+    ///
+    /// Expands the query into one variant per template before embedding,
+    /// e.g. `a photo of {}`. Each template must contain a `{}` placeholder,
+    /// which is replaced with the query; at most 8 are allowed. The variants
+    /// are embedded (and cached) separately and their normalized vectors are
+    /// averaged into the search vector. Empty embeds the query as typed.
+    #[serde(default)]
+    pub prompt_templates: Vec<String>,
+    /// Separate Templates
+    ///
+    /// Rank each embedding by its distance to the closest template variant
+    /// instead of to their average. A quant profile's coarse pass still uses
+    /// the average.
+    #[serde(default)]
+    pub separate_templates: bool,
 }
 
 impl Default for EmbedArgs {
@@ -40,6 +59,8 @@ impl Default for EmbedArgs {
             cache_key: default_cache_key(),
             lru_size: default_lru_size(),
             ttl_seconds: default_ttl_seconds(),
+            prompt_templates: Vec::new(),
+            separate_templates: false,
         }
     }
 }
@@ -52,6 +73,10 @@ pub(crate) struct SemanticTextArgs {
     pub query: String,
     #[serde(skip)]
     pub _embedding: Option<Vec<u8>>,
+    /// The normalized template variants, kept when `embed.separate_templates`
+    /// is set.
+    #[serde(skip)]
+    pub _template_embeddings: Vec<Vec<u8>>,
     /// The text embedding model to use
     ///
     /// The text embedding model to use for the semantic search.
@@ -92,6 +117,19 @@ pub(crate) struct SemanticTextArgs {
     /// pagination session.
     #[serde(default = "default_k")]
     pub k: i64,
+    /// Select Normalized Score As
+    ///
+    /// If set, a 0-1 similarity (1 = identical) is included in the `extra`
+    /// dict of each result under this key. Text embeddings are always
+    /// searched by L2, so this is `1 / (1 + distance / l2_scale)`. Ordering
+    /// is unaffected; under a quant profile results beyond `k` score null.
+    #[serde(default)]
+    pub normalize_score: Option<String>,
+    /// L2 Score Scale
+    ///
+    /// The L2 distance that `normalize_score` maps to 0.5. Must be positive.
+    #[serde(default = "default_l2_scale")]
+    pub l2_scale: f64,
     #[serde(skip)]
     pub _quant: Option<QuantResolved>,
 }
@@ -385,12 +423,8 @@ impl SemanticTextSearch {
     /// The full-precision rank aggregate, including confidence weighting.
     fn exact_rank_column(&self, embedding: &[u8]) -> Expr {
         let args = &self.text_embeddings;
-        let vec_distance: Expr = Func::cust("vec_distance_L2")
-            .args([
-                Expr::col((Embeddings::Table, Embeddings::Embedding)),
-                Expr::val(embedding.to_vec()),
-            ])
-            .into();
+        let vec_distance =
+            query_distance("vec_distance_L2", embedding, &args._template_embeddings);
         let mut rank_column = match args.distance_aggregation {
             DistanceAggregation::Max => vec_distance.clone().max(),
             DistanceAggregation::Avg => vec_distance.clone().avg(),
@@ -466,14 +500,24 @@ impl SemanticTextSearch {
         }
     }
 
+    /// L2 is the only distance text embeddings are searched by.
+    fn score_normalization(&self) -> Result<Option<ScoreNormalization>, PqlError> {
+        let args = &self.text_embeddings;
+        ScoreNormalization::requested(
+            args.normalize_score.as_deref(),
+            DistanceFunction::L2,
+            args.l2_scale,
+        )
+    }
+
     fn register_outputs(&self, state: &mut QueryState, cte: &CteRef) {
-        if let Some(alias) = &self.sort.select_as {
-            state.extra_columns.push(ExtraColumn {
-                column: "order_rank".to_string(),
-                cte: cte.clone(),
-                alias: alias.clone(),
-            });
-        }
+        push_score_columns(
+            state,
+            cte,
+            self.sort.select_as.as_ref(),
+            self.text_embeddings.normalize_score.as_ref(),
+        );
+        add_filter_attribution(state, cte, &self.sort);
         if self.sort.order_by {
             state.order_list.push(OrderByFilter {
                 cte: cte.clone(),
@@ -517,6 +561,8 @@ impl FilterCompiler for SemanticTextSearch {
             }
         };
 
+        let score = self.score_normalization()?;
+
         if let Some(quant) = args._quant.as_ref().filter(|_| !state.is_count_query) {
             let query_quant = quant
                 .query_quant
@@ -534,14 +580,20 @@ impl FilterCompiler for SemanticTextSearch {
             coarse.expr_as(self.coarse_rank_column(query_quant), Alias::new(COARSE_DIST));
 
             let k = args.k;
-            let (merge, merge_context) =
-                assemble_two_stage(state, &cte_name, coarse, &self.sort, |state, ranked| {
+            let (merge, merge_context) = assemble_two_stage(
+                state,
+                &cte_name,
+                coarse,
+                &self.sort,
+                score,
+                |state, ranked| {
                     let mut head = skeleton(state, ranked, &TextVectorJoin::Embeddings);
                     head.and_where(Expr::col(ranked.column_ref(COARSE_RANK)).lte(k));
                     apply_group_by(&mut head, get_std_group_by(ranked, state));
                     head.expr_as(self.exact_rank_column(embedding), Alias::new(EXACT_DIST));
                     head
-                });
+                },
+            );
 
             // The merge selects only from CTEs, so no base tables are
             // visible to the final query; its context is the ranked CTE in
@@ -564,6 +616,12 @@ impl FilterCompiler for SemanticTextSearch {
         apply_group_by(&mut query, get_std_group_by(context, state));
         if !state.is_count_query {
             add_rank_column_expr(&mut query, &self.sort, self.exact_rank_column(embedding))?;
+            if let Some(score) = score {
+                query.expr_as(
+                    score.score_expr(self.exact_rank_column(embedding)),
+                    Alias::new(SCORE_COLUMN),
+                );
+            }
         }
 
         let (query, context_for_wrap, joined_tables) = apply_sort_bounds(
@@ -660,4 +718,201 @@ mod tests {
             .await
             .expect("semantic text entity query");
     }
+
+    fn f32_blob(values: &[f32]) -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect()
+    }
+
+    /// (file_id, distance) of a file-entity semantic text search, best first.
+    async fn chunk_ranking(
+        conn: &mut sqlx::SqliteConnection,
+        aggregation: &str,
+    ) -> Vec<(i64, f64)> {
+        use sea_query::SqliteQueryBuilder;
+        use sea_query_sqlx::SqlxBinder;
+        use sqlx::Row;
+
+        use crate::pql::build_query;
+        use crate::pql::model::PqlQuery;
+
+        let mut filter: SemanticTextSearch = serde_json::from_value(json!({
+            "text_embeddings": {
+                "query": "hello",
+                "model": "textembed/test",
+                "distance_aggregation": aggregation,
+            },
+            "select_as": "distance"
+        }))
+        .expect("semantic text filter");
+        filter.text_embeddings._embedding = Some(f32_blob(&[0.0, 0.0]));
+        let built = build_query(
+            PqlQuery {
+                query: Some(QueryElement::SemanticTextSearch(filter)),
+                page_size: 0,
+                ..Default::default()
+            },
+            false,
+        )
+        .expect("build");
+        let distance = built
+            .extra_columns
+            .iter()
+            .find_map(|(label, alias)| (alias == "distance").then(|| label.clone()))
+            .expect("distance column");
+        let (sql, values) = built
+            .paginated_query()
+            .with(built.with_clause.expect("with clause"))
+            .build_sqlx(SqliteQueryBuilder);
+        sqlx::query_with(sqlx::AssertSqlSafe(sql.as_str()), values)
+            .fetch_all(conn)
+            .await
+            .expect("execute")
+            .iter()
+            .map(|row| {
+                (
+                    row.get::<i64, _>("file_id"),
+                    row.get::<f64, _>(distance.as_str()),
+                )
+            })
+            .collect()
+    }
+
+    /// Two items: item 1's text has three chunk embeddings at distances 5,
+    /// 0.5 and 3 from the origin, item 2's a single one at distance 1.
+    async fn seed_chunked_items(conn: &mut sqlx::SqliteConnection) {
+        for statement in [
+            r#"INSERT INTO items (id, sha256, md5, type, time_added) VALUES
+                (1, 'sha_1', 'md5_1', 'image/png', '2024-01-01T00:00:00'),
+                (2, 'sha_2', 'md5_2', 'image/png', '2024-01-01T00:00:00')"#,
+            "INSERT INTO file_scans (id, start_time, path) VALUES (1, '2024-01-01T00:00:00', '/data')",
+            r#"INSERT INTO files (id, sha256, item_id, path, filename, last_modified, scan_id, available) VALUES
+                (10, 'sha_1', 1, '/data/a.png', 'a.png', '2024-01-01T00:00:00', 1, 1),
+                (11, 'sha_2', 2, '/data/b.png', 'b.png', '2024-01-01T00:00:00', 1, 1)"#,
+            "INSERT INTO setters (id, name) VALUES (1, 'ocr/model'), (2, 'textembed/test')",
+            r#"INSERT INTO item_data (id, item_id, setter_id, data_type, idx, is_origin, source_id, is_placeholder) VALUES
+                (30, 1, 1, 'text', 0, 1, NULL, 0),
+                (31, 2, 1, 'text', 0, 1, NULL, 0),
+                (40, 1, 2, 'text-embedding', 0, NULL, 30, 0),
+                (41, 1, 2, 'text-embedding', 1000, NULL, 30, 0),
+                (42, 1, 2, 'text-embedding', 2000, NULL, 30, 0),
+                (43, 2, 2, 'text-embedding', 0, NULL, 31, 0)"#,
+            r#"INSERT INTO extracted_text (id, language, language_confidence, confidence, text, text_length) VALUES
+                (30, 'en', 0.9, 0.5, 'a long page', 2500),
+                (31, 'en', 0.9, 0.5, 'a short page', 900)"#,
+        ] {
+            sqlx::query(statement)
+                .execute(&mut *conn)
+                .await
+                .expect("seed");
+        }
+        // The second chunk of item 1 is the closest vector overall; the
+        // other two are further than item 2's only embedding.
+        for (id, vector) in [
+            (40, [5.0, 0.0]),
+            (41, [0.5, 0.0]),
+            (42, [3.0, 0.0]),
+            (43, [1.0, 0.0]),
+        ] {
+            sqlx::query("INSERT INTO embeddings (id, embedding) VALUES (?, ?)")
+                .bind(id)
+                .bind(f32_blob(&vector))
+                .execute(&mut *conn)
+                .await
+                .expect("seed embedding");
+        }
+    }
+
+    // A chunked text row has one embedding per chunk (idx = chunk offset), so
+    // the distance aggregation decides which chunk stands for the row: with
+    // MIN the best chunk wins even when the others are far off.
+    #[tokio::test]
+    async fn semantic_text_best_chunk_distance_wins() {
+        crate::db::sql_functions::ensure_sqlite_extensions().expect("sqlite extensions");
+        let mut dbs = crate::db::migrations::setup_test_databases().await;
+        seed_chunked_items(&mut dbs.index_conn).await;
+
+        let best = chunk_ranking(&mut dbs.index_conn, "MIN").await;
+        assert_eq!(best.iter().map(|row| row.0).collect::<Vec<_>>(), [10, 11]);
+        assert!((best[0].1 - 0.5).abs() < 1e-6, "{best:?}");
+        let average = chunk_ranking(&mut dbs.index_conn, "AVG").await;
+        assert_eq!(
+            average.iter().map(|row| row.0).collect::<Vec<_>>(),
+            [11, 10]
+        );
+    }
+
+    // Ensures the normalized score sits next to the raw distance and follows
+    // the documented L2 formula, 1 / (1 + distance / l2_scale).
+    #[tokio::test]
+    async fn semantic_text_selects_normalized_score() {
+        use sea_query::SqliteQueryBuilder;
+        use sea_query_sqlx::SqlxBinder;
+        use sqlx::Row;
+
+        use crate::pql::build_query;
+        use crate::pql::model::PqlQuery;
+
+        crate::db::sql_functions::ensure_sqlite_extensions().expect("sqlite extensions");
+        let mut dbs = crate::db::migrations::setup_test_databases().await;
+        seed_chunked_items(&mut dbs.index_conn).await;
+
+        let mut filter: SemanticTextSearch = serde_json::from_value(json!({
+            "text_embeddings": {
+                "query": "hello",
+                "model": "textembed/test",
+                "normalize_score": "score",
+                "l2_scale": 0.5,
+            },
+            "select_as": "distance",
+            "row_n": true
+        }))
+        .expect("semantic text filter");
+        filter.text_embeddings._embedding = Some(f32_blob(&[0.0, 0.0]));
+        let built = build_query(
+            PqlQuery {
+                query: Some(QueryElement::SemanticTextSearch(filter)),
+                page_size: 0,
+                ..Default::default()
+            },
+            false,
+        )
+        .expect("build");
+        let label = |alias: &str| {
+            built
+                .extra_columns
+                .iter()
+                .find_map(|(label, name)| (name == alias).then(|| label.clone()))
+                .expect("extra column")
+        };
+        let (rank, score) = (label("distance"), label("score"));
+        let (sql, values) = built
+            .paginated_query()
+            .with(built.with_clause.clone().expect("with clause"))
+            .build_sqlx(SqliteQueryBuilder);
+        let rows: Vec<(i64, i64, f64)> =
+            sqlx::query_with(sqlx::AssertSqlSafe(sql.as_str()), values)
+                .fetch_all(&mut dbs.index_conn)
+                .await
+                .expect("execute")
+                .iter()
+                .map(|row| {
+                    (
+                        row.get("file_id"),
+                        row.get(rank.as_str()),
+                        row.get(score.as_str()),
+                    )
+                })
+                .collect();
+
+        // row_n turns order_rank into a rank; the score still comes from the
+        // MIN distances, 0.5 and 1.
+        assert_eq!(rows.len(), 2);
+        assert_eq!((rows[0].0, rows[0].1), (10, 1));
+        assert_eq!((rows[1].0, rows[1].1), (11, 2));
+        assert!((rows[0].2 - 0.5).abs() < 1e-6, "{rows:?}");
+        assert!((rows[1].2 - 1.0 / 3.0).abs() < 1e-6, "{rows:?}");
+    }
 }
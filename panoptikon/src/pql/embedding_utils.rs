@@ -20,6 +20,100 @@ pub(crate) fn serialize_f32(values: &[f32]) -> Vec<u8> {
     out
 }
 
+/// Inverse of [`serialize_f32`]: decodes a stored embedding blob.
+pub(crate) fn deserialize_f32(bytes: &[u8]) -> Result<Vec<f32>, String> {
+    if !bytes.len().is_multiple_of(4) {
+        return Err(format!(
+            "Stored embedding is {} bytes, not a whole number of f32 values",
+            bytes.len()
+        ));
+    }
+    Ok(bytes
+        .chunks_exact(4)
+        .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect())
+}
+
+/// Stored embeddings produced by `setter_name` for the items behind
+/// `file_ids`, as (file_id, embedding) pairs. Items with several embeddings
+/// (e.g. video frames) yield one pair per embedding; files whose item has
+/// none are absent.
+pub(crate) async fn fetch_file_embeddings(
+    conn: &mut sqlx::SqliteConnection,
+    file_ids: &[i64],
+    setter_name: &str,
+) -> Result<Vec<(i64, Vec<u8>)>, sqlx::Error> {
+    if file_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let placeholders = vec!["?"; file_ids.len()].join(", ");
+    let sql = format!(
+        r#"
+SELECT files.id, embeddings.embedding
+FROM files
+JOIN item_data ON item_data.item_id = files.item_id
+JOIN setters ON setters.id = item_data.setter_id
+JOIN embeddings ON embeddings.id = item_data.id
+WHERE setters.name = ?
+  AND files.id IN ({placeholders})
+ORDER BY files.id, item_data.idx
+        "#
+    );
+    let mut query = sqlx::query_as(sqlx::AssertSqlSafe(sql)).bind(setter_name);
+    for file_id in file_ids {
+        query = query.bind(file_id);
+    }
+    query.fetch_all(conn).await
+}
+
+/// Element-wise mean of f32 embeddings, serialized like stored embeddings.
+/// Every input must have the same dimension.
+pub(crate) fn average_embeddings<B: AsRef<[u8]>>(embeddings: &[B]) -> Result<Vec<u8>, String> {
+    let Some(first) = embeddings.first() else {
+        return Err("No embeddings to average".to_string());
+    };
+    let mut sum: Vec<f64> = deserialize_f32(first.as_ref())?
+        .into_iter()
+        .map(f64::from)
+        .collect();
+    for embedding in &embeddings[1..] {
+        let values = deserialize_f32(embedding.as_ref())?;
+        if values.len() != sum.len() {
+            return Err(format!(
+                "Embedding dimensions differ: {} and {}",
+                sum.len(),
+                values.len()
+            ));
+        }
+        for (total, value) in sum.iter_mut().zip(values) {
+            *total += f64::from(value);
+        }
+    }
+    let count = embeddings.len() as f64;
+    let mean: Vec<f32> = sum
+        .into_iter()
+        .map(|total| (total / count) as f32)
+        .collect();
+    Ok(serialize_f32(&mean))
+}
+
+/// Scales an embedding blob to unit length. A zero vector is returned
+/// unchanged.
+pub(crate) fn normalize_embedding(bytes: &[u8]) -> Result<Vec<u8>, String> {
+    let mut values = deserialize_f32(bytes)?;
+    let norm = values
+        .iter()
+        .map(|value| f64::from(*value) * f64::from(*value))
+        .sum::<f64>()
+        .sqrt();
+    if norm > 0.0 {
+        for value in &mut values {
+            *value = (f64::from(*value) / norm) as f32;
+        }
+    }
+    Ok(serialize_f32(&values))
+}
+
 #[derive(Clone, Copy, Debug)]
 enum NpyKind {
     Float,
@@ -351,7 +445,7 @@ fn f16_to_f32(bits: u16) -> f32 {
 
 #[cfg(test)]
 mod tests {
-    use super::{embedding_from_npy_bytes, parse_npy_f32};
+    use super::{average_embeddings, embedding_from_npy_bytes, parse_npy_f32, serialize_f32};
 
     const F32_1D: &[u8] = include_bytes!("../../tests/fixtures/npy/f32_1d.npy");
     const F16_2D_C: &[u8] = include_bytes!("../../tests/fixtures/npy/f16_2d_c.npy");
@@ -434,4 +528,18 @@ mod tests {
         }
         assert_eq!(bytes, expected);
     }
+
+    #[test]
+    fn averages_embeddings_and_checks_dimensions() {
+        let mean = average_embeddings(&[
+            serialize_f32(&[1.0, 0.0, 2.0]),
+            serialize_f32(&[0.0, 1.0, 4.0]),
+        ])
+        .expect("average");
+        assert_eq!(mean, serialize_f32(&[0.5, 0.5, 3.0]));
+
+        let mismatch = average_embeddings(&[serialize_f32(&[1.0, 0.0]), serialize_f32(&[1.0])]);
+        assert!(mismatch.unwrap_err().contains("dimensions differ"));
+        assert!(average_embeddings::<Vec<u8>>(&[]).is_err());
+    }
 }
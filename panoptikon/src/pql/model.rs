@@ -2,11 +2,12 @@ use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
 pub(crate) use crate::pql::builder::filters::{
-    DerivedDataArgs, DistanceAggregation, DistanceFunction, EmbedArgs, HasUnprocessedData,
-    InBookmarks, InBookmarksArgs, IndexMode, Match, MatchAnd, MatchNot, MatchOps, MatchOr,
-    MatchPath, MatchPathArgs, MatchTags, MatchText, MatchTextArgs, MatchValue, MatchValues,
-    Matches, ProcessedBy, QuantResolved, SemanticImageArgs, SemanticImageSearch, SemanticTextArgs,
-    SemanticTextSearch, SimilarTo, SimilarityArgs, SourceArgs, TagsArgs,
+    BookmarkRankBy, DerivedDataArgs, DistanceAggregation, DistanceFunction, EmbedArgs, HasUnprocessedData,
+    InBookmarks, InBookmarksArgs, InFolder, InFolderArgs, IndexMode, Match, MatchAnd, MatchMeta,
+    MatchMetaArgs, MatchNot, MatchNote, MatchNoteArgs, MatchOps, MatchOr, MatchPath, MatchPathArgs,
+    MatchTags, MatchText, MatchTextArgs, MatchValue, MatchValues, Matches, ProcessedBy,
+    QuantResolved, SemanticImageArgs, SemanticImageSearch, SemanticTextArgs, SemanticTextSearch,
+    SimilarTo, SimilarityArgs, SourceArgs, TagsArgs,
 };
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema)]
@@ -31,7 +32,13 @@ pub(crate) enum Column {
     Width,
     Height,
     Duration,
+    /// When the item (its content) was first indexed.
     TimeAdded,
+    /// When this file's path was first indexed. Kept across modification
+    /// rescans; differs from `time_added` for later copies of the content.
+    FileTimeAdded,
+    /// When a scan last found this file on disk.
+    LastSeen,
     AudioTracks,
     VideoTracks,
     SubtitleTracks,
@@ -47,6 +54,18 @@ pub(crate) enum Column {
     SetterName,
     DataIndex,
     SourceId,
+    /// The smaller of width and height. Match filters only.
+    MinDimension,
+    /// The larger of width and height. Match filters only.
+    MaxDimension,
+    /// width × height in millions of pixels. Match filters only.
+    Megapixels,
+    /// The file's directory: its path up to and including the last `/` or
+    /// `\`, or "" for a bare filename. Select and partition_by only.
+    Directory,
+    /// The item's MIME type before the `/` (e.g. "image"). Select and
+    /// partition_by only.
+    TypePrefix,
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema)]
@@ -64,7 +83,13 @@ pub(crate) enum OrderByField {
     Width,
     Height,
     Duration,
+    /// When the item (its content) was first indexed.
     TimeAdded,
+    /// When this file's path was first indexed. Kept across modification
+    /// rescans; differs from `time_added` for later copies of the content.
+    FileTimeAdded,
+    /// When a scan last found this file on disk.
+    LastSeen,
     AudioTracks,
     VideoTracks,
     SubtitleTracks,
@@ -212,6 +237,14 @@ pub(crate) struct SortableOptions {
     /// Moreover, the correct direction for RRF is "desc" (higher is better).
     #[serde(default)]
     pub rrf: Option<Rrf>,
+    /// Attribution Name
+    ///
+    /// If set and the query has `attribute_filters` = True, each result's
+    /// "attribution" object reports under this name whether this filter
+    /// matched it, whether or not the filter orders or selects anything.
+    /// Useful under `or_`, to tell which branch found a result.
+    #[serde(default)]
+    pub name: Option<String>,
 }
 
 impl Default for SortableOptions {
@@ -226,6 +259,7 @@ impl Default for SortableOptions {
             lt: None,
             select_as: None,
             rrf: None,
+            name: None,
         }
     }
 }
@@ -255,6 +289,8 @@ pub(crate) struct PartialSortableOptions {
     pub select_as: Option<String>,
     #[serde(default)]
     pub rrf: Option<Rrf>,
+    #[serde(default)]
+    pub name: Option<String>,
 }
 
 impl PartialSortableOptions {
@@ -269,6 +305,7 @@ impl PartialSortableOptions {
             lt: self.lt.or(defaults.lt),
             select_as: self.select_as.or(defaults.select_as),
             rrf: self.rrf.or(defaults.rrf),
+            name: self.name.or(defaults.name),
         }
     }
 }
@@ -298,6 +335,44 @@ impl Default for OrderArgs {
     }
 }
 
+/// Maximum number of file IDs a refine block may reference.
+pub(crate) const MAX_REFINE_FILES: usize = 1000;
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub(crate) struct RefineArgs {
+    /// File IDs
+    ///
+    /// The top results of a previous query, whose embeddings are averaged
+    /// into the centroid the results are ranked by. Files whose item has no
+    /// embedding from `model` are ignored; at least one must have one.
+    pub file_ids: Vec<i64>,
+    /// The image embedding model (setter name) to read embeddings from and
+    /// rank by.
+    pub model: String,
+    /// The method to aggregate distances when an item has multiple embeddings. Default is MIN.
+    #[serde(default)]
+    pub distance_aggregation: DistanceAggregation,
+    /// Priority of the centroid ordering. The default of 100 puts it ahead
+    /// of filter orderings and order_by terms left at their default priority.
+    #[serde(default = "default_refine_priority")]
+    pub priority: i32,
+}
+
+fn default_refine_priority() -> i32 {
+    100
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub(crate) struct GroupSimilarArgs {
+    /// The embedding model (setter name) whose embeddings are compared.
+    pub model: String,
+    /// Results closer than this distance to each other are stacked together.
+    pub threshold: f64,
+    /// The distance function to compare embeddings with. Default is L2.
+    #[serde(default)]
+    pub distance_function: DistanceFunction,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 #[serde(default)]
 pub(crate) struct PqlQuery {
@@ -314,6 +389,8 @@ pub(crate) struct PqlQuery {
     /// The columns to return in the query.
     /// The default columns are sha256, path, last_modified, and type.
     /// Columns belonging to text can only be selected if the entity is "text".
+    /// The derived min_dimension, max_dimension and megapixels columns can
+    /// only be used in match filters.
     pub select: Vec<Column>,
     /// Target Entity
     ///
@@ -337,6 +414,9 @@ pub(crate) struct PqlQuery {
     /// If you partition by "file_id", you'll get one result per unique file.
     /// Multiple columns yield one result for each unique combination of values for those columns.
     ///
+    /// "directory" and "type_prefix" partition by the file's folder and the
+    /// MIME type's major part; select them too to label each group.
+    ///
     /// You cannot partition by text columns if the entity is "file".
     pub partition_by: Option<Vec<Column>>,
     /// Random Order Seed
@@ -352,6 +432,12 @@ pub(crate) struct PqlQuery {
     /// behaviour (a new sample every time, and pages that may repeat or skip
     /// results) and bypasses the result cache. The seed actually used is
     /// always returned in the response.
+    ///
+    /// The seed belongs to the query, not to an order term: every "random"
+    /// term uses it. It composes with cursor pagination over a filter's
+    /// `gt`/`lt`: rows that tie on the filter's rank are shuffled the same way
+    /// on every request, so a cursor session stays consistent as long as the
+    /// seed does.
     pub seed: Option<i64>,
     pub page: i64,
     pub page_size: i64,
@@ -374,7 +460,10 @@ pub(crate) struct PqlQuery {
     /// This is because if another file exists, it will be included later in the results.
     ///
     /// In other cases, the system will try to find another file for the item and substitute it.
-    /// If no other working path is found, the result will be omitted.
+    /// If no other working path is found, the result is returned with `available: false`.
+    ///
+    /// Paths are checked concurrently within `[search] check_path_budget_ms`; rows not
+    /// checked in time are returned without `available` and counted in `unchecked_paths`.
     ///
     /// This is not reflected in the total count of results.
     pub check_path: bool,
@@ -394,6 +483,42 @@ pub(crate) struct PqlQuery {
     /// whatever page size asks for it. Clamped server-side; ignored when the
     /// cache is disabled or bypassed.
     pub prefetch_rows: u32,
+    /// Optimize Query
+    ///
+    /// Opt in to experimental rewrites of the generated SQL. Results are
+    /// unchanged; only the plan differs. Currently affects `or_`: operands
+    /// that are all `match` filters are merged into one filter with their
+    /// conditions ORed, and other operands are combined with `UNION ALL` and
+    /// deduplicated per result row instead of with `UNION`.
+    pub optimize: bool,
+    /// Refine ("more of the same")
+    ///
+    /// Re-ranks the query's results by distance to the centroid of the
+    /// image embeddings of the given files, typically the top results of the
+    /// same query. Results without an embedding from the model are
+    /// excluded, as with `image_embeddings`.
+    pub refine: Option<Box<RefineArgs>>,
+    /// Attribute Filters
+    ///
+    /// If true, each result gets an "attribution" object mapping the `name`
+    /// of every named sortable filter in the query to whether that filter
+    /// matched the result. Filters without a `name` are not reported.
+    /// Ignored by the count query.
+    pub attribute_filters: bool,
+    /// Group Similar Results
+    ///
+    /// Stacks near-identical results (e.g. burst photos) within the returned
+    /// page: results whose items' embeddings from `model` are closer than
+    /// `threshold`, directly or through other results, form one stack led by
+    /// the best-ranked of them. The response's `stacks` lists every stack by
+    /// position in `results`, which stays unchanged. Only the page is
+    /// grouped, not the whole result set. Ignored by the count query.
+    pub group_similar: Option<Box<GroupSimilarArgs>>,
+    /// Select each ORDER BY value as an `order_key_{n}` column (see
+    /// `builder::OrderKey`). Set internally by multi-database searches,
+    /// which merge the rows of several executions; never part of a request.
+    #[serde(skip)]
+    pub select_order_keys: bool,
 }
 
 impl Default for PqlQuery {
@@ -412,6 +537,11 @@ impl Default for PqlQuery {
             check_path: false,
             cache: true,
             prefetch_rows: 0,
+            optimize: false,
+            refine: None,
+            attribute_filters: false,
+            group_similar: None,
+            select_order_keys: false,
         }
     }
 }
@@ -510,8 +640,59 @@ pub(crate) enum QueryElement {
     SimilarTo(SimilarTo),
     MatchTags(MatchTags),
     InBookmarks(InBookmarks),
+    MatchMeta(MatchMeta),
+    MatchNote(MatchNote),
     ProcessedBy(ProcessedBy),
     HasUnprocessedData(HasUnprocessedData),
+    InFolder(InFolder),
+}
+
+/// Every filter's JSON key with its `QueryElement` variant name, in variant
+/// order. `[search] disabled_filters` accepts either spelling.
+pub(crate) const PQL_FILTERS: [(&str, &str); 13] = [
+    ("match", "Match"),
+    ("match_path", "MatchPath"),
+    ("match_text", "MatchText"),
+    ("text_embeddings", "SemanticTextSearch"),
+    ("image_embeddings", "SemanticImageSearch"),
+    ("similar_to", "SimilarTo"),
+    ("match_tags", "MatchTags"),
+    ("in_bookmarks", "InBookmarks"),
+    ("match_meta", "MatchMeta"),
+    ("match_note", "MatchNote"),
+    ("processed_by", "ProcessedBy"),
+    ("has_data_unprocessed", "HasUnprocessedData"),
+    ("in_folder", "InFolder"),
+];
+
+/// The JSON key of a filter given by key or variant name.
+pub(crate) fn filter_key(name: &str) -> Option<&'static str> {
+    PQL_FILTERS
+        .iter()
+        .find(|(key, variant)| *key == name || *variant == name)
+        .map(|(key, _)| *key)
+}
+
+impl QueryElement {
+    /// The filter's JSON key; `None` for the `and_`/`or_`/`not_` operators.
+    pub(crate) fn filter_key(&self) -> Option<&'static str> {
+        Some(match self {
+            QueryElement::And(_) | QueryElement::Or(_) | QueryElement::Not(_) => return None,
+            QueryElement::Match(_) => "match",
+            QueryElement::MatchPath(_) => "match_path",
+            QueryElement::MatchText(_) => "match_text",
+            QueryElement::SemanticTextSearch(_) => "text_embeddings",
+            QueryElement::SemanticImageSearch(_) => "image_embeddings",
+            QueryElement::SimilarTo(_) => "similar_to",
+            QueryElement::MatchTags(_) => "match_tags",
+            QueryElement::InBookmarks(_) => "in_bookmarks",
+            QueryElement::MatchMeta(_) => "match_meta",
+            QueryElement::MatchNote(_) => "match_note",
+            QueryElement::ProcessedBy(_) => "processed_by",
+            QueryElement::HasUnprocessedData(_) => "has_data_unprocessed",
+            QueryElement::InFolder(_) => "in_folder",
+        })
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
@@ -522,6 +703,15 @@ pub(crate) struct JobFilter {
     pub pql_query: QueryElement,
 }
 
+impl JobFilter {
+    /// Whether this filter applies to jobs of `setter_name` (`*` matches all).
+    pub(crate) fn applies_to(&self, setter_name: &str) -> bool {
+        self.setter_names
+            .iter()
+            .any(|name| name == "*" || name == setter_name)
+    }
+}
+
 fn default_direction() -> OrderDirection {
     OrderDirection::Asc
 }
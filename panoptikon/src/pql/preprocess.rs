@@ -1,11 +1,17 @@
+use crate::db::system_config::{SystemConfigStore, TextNormalizationConfig, normalize_folder_list};
+use crate::db::tag_aliases::resolve_tag_alias_groups;
 use crate::inferio_client::{InferenceApiClient, InferenceInput, PredictOutput};
-use crate::pql::embedding_utils::{embedding_from_npy_bytes, extract_embeddings, serialize_f32};
+use crate::pql::embedding_utils::{
+    average_embeddings, embedding_from_npy_bytes, extract_embeddings, fetch_file_embeddings,
+    normalize_embedding, serialize_f32,
+};
 use crate::pql::model::{
-    DistanceFunction, EmbedArgs, HasUnprocessedData, InBookmarks, IndexMode, Match, MatchAnd,
-    MatchOps, MatchOr, MatchPath, MatchTags, MatchText, MatchValue, MatchValues, Matches,
-    ProcessedBy, QuantResolved, QueryElement, SemanticImageSearch, SemanticTextSearch, SimilarTo,
+    AndOperator, DistanceFunction, EmbedArgs, HasUnprocessedData, InBookmarks, InFolder, IndexMode,
+    MAX_REFINE_FILES, Match, MatchAnd, MatchMeta, MatchNote, MatchOps, MatchOr, MatchPath,
+    MatchTags, MatchText, MatchValue, MatchValues, Matches, PqlQuery, ProcessedBy, QuantResolved,
+    QueryElement, SemanticImageSearch, SemanticTextSearch, SimilarTo,
 };
-use crate::pql::utils::parse_and_escape_query;
+use crate::pql::utils::{normalize_search_text, parse_and_escape_query};
 use base64::{Engine as _, engine::general_purpose};
 use hashlink::LruCache;
 use serde::Serialize;
@@ -13,19 +19,54 @@ use serde_json::Value;
 use std::future::Future;
 use std::pin::Pin;
 use std::sync::OnceLock;
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, MutexGuard};
 use tracing::warn;
 use utoipa::ToSchema;
 
 #[derive(Debug)]
 pub(crate) struct PqlError {
     pub message: String,
+    pub kind: PqlErrorKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PqlErrorKind {
+    Invalid,
+    /// The query may be fine: embedding its search text through the
+    /// inference server failed.
+    Upstream,
+    /// The query uses a filter listed in `[search] disabled_filters`.
+    Disabled,
+    /// The query may be fine: reading the index DB to resolve it failed.
+    Internal,
 }
 
 impl PqlError {
     pub(crate) fn invalid(message: impl Into<String>) -> Self {
         Self {
             message: message.into(),
+            kind: PqlErrorKind::Invalid,
+        }
+    }
+
+    pub(crate) fn upstream(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            kind: PqlErrorKind::Upstream,
+        }
+    }
+
+    pub(crate) fn internal(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            kind: PqlErrorKind::Internal,
+        }
+    }
+
+    fn disabled(filter: &str, path: &str) -> Self {
+        Self {
+            message: format!("The {filter} filter is disabled on this server (at {path})"),
+            kind: PqlErrorKind::Disabled,
         }
     }
 }
@@ -51,62 +92,106 @@ struct EmbeddingCacheKey {
     query: String,
 }
 
+/// Approximate fixed per-entry overhead (LRU node, key and value structs)
+/// on top of the embedding and key string bytes.
+const EMBEDDING_ENTRY_OVERHEAD_BYTES: usize = 128;
+
 #[derive(Debug, Clone)]
 struct CachedEmbedding {
     value: Vec<u8>,
+    /// What the entry is charged against the byte budget.
+    bytes: usize,
 }
 
-static EMBEDDING_CACHE: OnceLock<Mutex<LruCache<EmbeddingCacheKey, CachedEmbedding>>> =
-    OnceLock::new();
-
-fn embedding_cache() -> &'static Mutex<LruCache<EmbeddingCacheKey, CachedEmbedding>> {
-    EMBEDDING_CACHE.get_or_init(|| Mutex::new(LruCache::new(1)))
+/// Search-time embeddings in LRU order, evicted by a byte budget rather
+/// than an entry count: a 4096-dimension embedding costs eight times what a
+/// 512-dimension one does.
+struct EmbeddingCache {
+    lru: LruCache<EmbeddingCacheKey, CachedEmbedding>,
+    used_bytes: usize,
+    budget_bytes: usize,
 }
 
-fn ensure_cache_capacity(
-    cache: &mut LruCache<EmbeddingCacheKey, CachedEmbedding>,
-    capacity: usize,
-) {
-    let target = capacity.max(1);
-    if cache.capacity() != target {
-        cache.set_capacity(target);
+impl EmbeddingCache {
+    /// Applies the configured budget, evicting LRU entries if it shrank.
+    fn set_budget(&mut self, budget_bytes: usize) {
+        if self.budget_bytes != budget_bytes {
+            self.budget_bytes = budget_bytes;
+            self.evict_to_budget();
+        }
     }
-}
 
-async fn get_cached_embedding(key: &EmbeddingCacheKey, capacity: usize) -> Option<Vec<u8>> {
-    let cache = embedding_cache();
-    let mut guard = cache.lock().await;
-    ensure_cache_capacity(&mut guard, capacity);
-    if let Some(entry) = guard.get(key) {
-        return Some(entry.value.clone());
+    fn evict_to_budget(&mut self) {
+        while self.used_bytes > self.budget_bytes {
+            let Some((_, entry)) = self.lru.remove_lru() else {
+                self.used_bytes = 0;
+                break;
+            };
+            self.used_bytes = self.used_bytes.saturating_sub(entry.bytes);
+        }
+    }
+
+    fn get(&mut self, key: &EmbeddingCacheKey) -> Option<Vec<u8>> {
+        self.lru.get(key).map(|entry| entry.value.clone())
+    }
+
+    fn insert(&mut self, key: EmbeddingCacheKey, value: Vec<u8>) {
+        let bytes =
+            EMBEDDING_ENTRY_OVERHEAD_BYTES + key.model.len() + key.query.len() + value.len();
+        // An entry larger than the whole budget would only evict everything
+        // else and then itself.
+        if bytes > self.budget_bytes {
+            return;
+        }
+        if let Some(previous) = self.lru.insert(key, CachedEmbedding { value, bytes }) {
+            self.used_bytes = self.used_bytes.saturating_sub(previous.bytes);
+        }
+        self.used_bytes += bytes;
+        self.evict_to_budget();
+    }
+
+    fn clear(&mut self) {
+        self.lru.clear();
+        self.used_bytes = 0;
     }
-    guard.remove(key);
-    None
 }
 
-async fn put_cached_embedding(key: EmbeddingCacheKey, capacity: usize, value: Vec<u8>) {
-    let cache = embedding_cache();
+static EMBEDDING_CACHE: OnceLock<Mutex<EmbeddingCache>> = OnceLock::new();
+
+/// The process-wide cache with `budget_bytes` applied.
+async fn embedding_cache(budget_bytes: usize) -> MutexGuard<'static, EmbeddingCache> {
+    let cache = EMBEDDING_CACHE.get_or_init(|| {
+        Mutex::new(EmbeddingCache {
+            // Entry-count capacity is effectively unbounded; eviction is
+            // driven by the byte budget.
+            lru: LruCache::new(usize::MAX),
+            used_bytes: 0,
+            budget_bytes,
+        })
+    });
     let mut guard = cache.lock().await;
-    ensure_cache_capacity(&mut guard, capacity);
-    guard.insert(key, CachedEmbedding { value });
+    guard.set_budget(budget_bytes);
+    guard
 }
 
 async fn cached_embedding_or_fetch<F>(
     key: EmbeddingCacheKey,
-    cache_size: usize,
+    cache_bytes: usize,
     fetch: F,
 ) -> Result<Vec<u8>, PqlError>
 where
     F: Future<Output = Result<Vec<u8>, PqlError>>,
 {
-    if cache_size == 0 {
+    if cache_bytes == 0 {
         return fetch.await;
     }
-    if let Some(value) = get_cached_embedding(&key, cache_size).await {
+    if let Some(value) = embedding_cache(cache_bytes).await.get(&key) {
         return Ok(value);
     }
     let value = fetch.await?;
-    put_cached_embedding(key, cache_size, value.clone()).await;
+    embedding_cache(cache_bytes)
+        .await
+        .insert(key, value.clone());
     Ok(value)
 }
 
@@ -114,50 +199,54 @@ where
 pub(crate) struct EmbeddingCacheEntry {
     pub inference_id: String,
     pub kind: String,
+    /// Embedding size in bytes.
     pub size: usize,
+    /// Bytes the entry is charged against the budget: the embedding plus
+    /// its key and a fixed overhead.
+    pub bytes: usize,
 }
 
 #[derive(Debug, Serialize, ToSchema)]
 pub(crate) struct EmbeddingCacheStats {
+    /// Number of cached embeddings.
     pub used_slots: usize,
-    pub total_slots: usize,
+    pub used_bytes: usize,
+    /// From `search.embedding_cache_mb`. `0` means the cache is disabled.
+    pub budget_bytes: usize,
     pub page: usize,
     pub page_size: usize,
+    /// Most recently used first.
     pub entries: Vec<EmbeddingCacheEntry>,
 }
 
-pub(crate) async fn clear_embedding_cache(cache_size: usize) {
-    if cache_size == 0 {
+pub(crate) async fn clear_embedding_cache(cache_bytes: usize) {
+    if cache_bytes == 0 {
         return;
     }
-    let cache = embedding_cache();
-    let mut guard = cache.lock().await;
-    ensure_cache_capacity(&mut guard, cache_size);
-    guard.clear();
+    embedding_cache(cache_bytes).await.clear();
 }
 
 pub(crate) async fn embedding_cache_stats(
-    cache_size: usize,
+    cache_bytes: usize,
     page: usize,
     page_size: usize,
 ) -> EmbeddingCacheStats {
-    if cache_size == 0 {
+    if cache_bytes == 0 {
         return EmbeddingCacheStats {
             used_slots: 0,
-            total_slots: 0,
+            used_bytes: 0,
+            budget_bytes: 0,
             page,
             page_size,
             entries: Vec::new(),
         };
     }
 
-    let cache = embedding_cache();
-    let mut guard = cache.lock().await;
-    ensure_cache_capacity(&mut guard, cache_size);
-    let used_slots = guard.len();
-    let total_slots = guard.capacity();
+    let guard = embedding_cache(cache_bytes).await;
+    let used_slots = guard.lru.len();
 
     let mut entries: Vec<EmbeddingCacheEntry> = guard
+        .lru
         .iter()
         .map(|(key, value)| EmbeddingCacheEntry {
             inference_id: key.model.clone(),
@@ -166,6 +255,7 @@ pub(crate) async fn embedding_cache_stats(
                 EmbeddingKind::Image => "image".to_string(),
             },
             size: value.value.len(),
+            bytes: value.bytes,
         })
         .collect();
     entries.reverse();
@@ -177,13 +267,57 @@ pub(crate) async fn embedding_cache_stats(
 
     EmbeddingCacheStats {
         used_slots,
-        total_slots,
+        used_bytes: guard.used_bytes,
+        budget_bytes: guard.budget_bytes,
         page,
         page_size,
         entries,
     }
 }
 
+/// Fails on the first filter of `query` whose key is in `disabled`, naming
+/// it and its path (`query.and_[1].not_`), however deeply it is nested. A
+/// `refine` block counts as `image_embeddings`.
+pub(crate) fn reject_disabled_filters(
+    query: &PqlQuery,
+    disabled: &[String],
+) -> Result<(), PqlError> {
+    if disabled.is_empty() {
+        return Ok(());
+    }
+    if query.refine.is_some() && disabled.iter().any(|key| key == "image_embeddings") {
+        return Err(PqlError::disabled("image_embeddings", "refine"));
+    }
+    match &query.query {
+        Some(root) => reject_disabled_element(root, disabled, "query"),
+        None => Ok(()),
+    }
+}
+
+fn reject_disabled_element(
+    el: &QueryElement,
+    disabled: &[String],
+    path: &str,
+) -> Result<(), PqlError> {
+    match el {
+        QueryElement::And(op) => op.and_.iter().enumerate().try_for_each(|(idx, child)| {
+            reject_disabled_element(child, disabled, &format!("{path}.and_[{idx}]"))
+        }),
+        QueryElement::Or(op) => op.or_.iter().enumerate().try_for_each(|(idx, child)| {
+            reject_disabled_element(child, disabled, &format!("{path}.or_[{idx}]"))
+        }),
+        QueryElement::Not(op) => {
+            reject_disabled_element(&op.not_, disabled, &format!("{path}.not_"))
+        }
+        leaf => match leaf.filter_key() {
+            Some(key) if disabled.iter().any(|name| name == key) => {
+                Err(PqlError::disabled(key, path))
+            }
+            _ => Ok(()),
+        },
+    }
+}
+
 pub(crate) fn preprocess_query(el: QueryElement) -> Result<Option<QueryElement>, PqlError> {
     match el {
         QueryElement::And(mut op) => {
@@ -228,7 +362,7 @@ pub(crate) fn preprocess_query(el: QueryElement) -> Result<Option<QueryElement>,
         }
         QueryElement::Match(filter) => Ok(filter.validate().map(QueryElement::Match)),
         QueryElement::MatchPath(filter) => Ok(filter.validate().map(QueryElement::MatchPath)),
-        QueryElement::MatchText(filter) => Ok(filter.validate().map(QueryElement::MatchText)),
+        QueryElement::MatchText(filter) => Ok(filter.validate(None).map(QueryElement::MatchText)),
         QueryElement::SemanticTextSearch(filter) => filter
             .validate_sync()
             .map(|value| value.map(QueryElement::SemanticTextSearch)),
@@ -240,37 +374,108 @@ pub(crate) fn preprocess_query(el: QueryElement) -> Result<Option<QueryElement>,
             .map(|value| value.map(QueryElement::SimilarTo)),
         QueryElement::MatchTags(filter) => Ok(filter.validate().map(QueryElement::MatchTags)),
         QueryElement::InBookmarks(filter) => Ok(filter.validate().map(QueryElement::InBookmarks)),
+        QueryElement::MatchMeta(filter) => Ok(filter.validate().map(QueryElement::MatchMeta)),
+        QueryElement::MatchNote(filter) => Ok(filter.validate().map(QueryElement::MatchNote)),
         QueryElement::ProcessedBy(filter) => Ok(filter.validate().map(QueryElement::ProcessedBy)),
         QueryElement::HasUnprocessedData(filter) => {
             Ok(filter.validate().map(QueryElement::HasUnprocessedData))
         }
+        QueryElement::InFolder(filter) => filter
+            .validate(None)
+            .map(|value| value.map(QueryElement::InFolder)),
     }
 }
 
 pub(crate) async fn preprocess_query_async(
     el: QueryElement,
     inference: &InferenceApiClient,
-    embedding_cache_size: usize,
+    embedding_cache_bytes: usize,
     index_db: Option<&str>,
 ) -> Result<Option<QueryElement>, PqlError> {
     let mut state = AsyncPreprocessState {
         inference,
         metadata: None,
-        embedding_cache_size,
+        embedding_cache_bytes,
         index_db: index_db.map(str::to_string),
-        quant_conn: None,
+        index_conn: None,
+        text_normalization: None,
+        included_folders: None,
     };
     preprocess_query_async_inner(el, &mut state).await
 }
 
+/// Resolves the query's `refine` block: averages the stored embeddings of
+/// the referenced files (per file first, so a video's frames count once)
+/// and ANDs an `image_embeddings` ordering on that centroid into the query.
+/// Runs before [`preprocess_query_async`], which then applies the model's
+/// distance function and quant profile to it like any image search.
+pub(crate) async fn apply_refine(query: &mut PqlQuery, index_db: &str) -> Result<(), PqlError> {
+    let Some(refine) = query.refine.take() else {
+        return Ok(());
+    };
+    if refine.file_ids.is_empty() {
+        return Err(PqlError::invalid("refine requires at least one file_id"));
+    }
+    if refine.file_ids.len() > MAX_REFINE_FILES {
+        return Err(PqlError::invalid(format!(
+            "refine accepts at most {MAX_REFINE_FILES} file_ids"
+        )));
+    }
+    if refine.model.trim().is_empty() {
+        return Err(PqlError::invalid("refine requires a model"));
+    }
+
+    let mut conn = crate::db::open_index_db_read_no_user_data(index_db)
+        .await
+        .map_err(|err| {
+            tracing::error!(index_db, error = ?err, "failed to open index db for refine");
+            PqlError::internal("Failed to read refine embeddings")
+        })?;
+    let rows = fetch_file_embeddings(&mut conn, &refine.file_ids, &refine.model)
+        .await
+        .map_err(|err| {
+            tracing::error!(index_db, error = %err, "failed to fetch refine embeddings");
+            PqlError::internal("Failed to read refine embeddings")
+        })?;
+    if rows.is_empty() {
+        return Err(PqlError::invalid(format!(
+            "None of the refine file_ids have embeddings from {}",
+            refine.model
+        )));
+    }
+
+    let mut per_file = Vec::new();
+    for group in rows.chunk_by(|a, b| a.0 == b.0) {
+        let embeddings: Vec<&[u8]> = group.iter().map(|(_, bytes)| bytes.as_slice()).collect();
+        per_file.push(average_embeddings(&embeddings).map_err(PqlError::invalid)?);
+    }
+    let centroid = average_embeddings(&per_file).map_err(PqlError::invalid)?;
+
+    let ordering = QueryElement::SemanticImageSearch(SemanticImageSearch::from_embedding(
+        refine.model,
+        centroid,
+        refine.distance_aggregation,
+        refine.priority,
+    ));
+    query.query = Some(match query.query.take() {
+        Some(existing) => QueryElement::And(AndOperator {
+            and_: vec![existing, ordering],
+        }),
+        None => ordering,
+    });
+    Ok(())
+}
+
 struct AsyncPreprocessState<'a> {
     inference: &'a InferenceApiClient,
     metadata: Option<Value>,
-    embedding_cache_size: usize,
+    embedding_cache_bytes: usize,
     /// The index DB vector filters resolve quant profiles against; None
     /// (no DB context) makes `auto` resolve to exact.
     index_db: Option<String>,
-    quant_conn: Option<sqlx::SqliteConnection>,
+    index_conn: Option<sqlx::SqliteConnection>,
+    text_normalization: Option<TextNormalizationConfig>,
+    included_folders: Option<Vec<String>>,
 }
 
 impl<'a> AsyncPreprocessState<'a> {
@@ -286,24 +491,120 @@ impl<'a> AsyncPreprocessState<'a> {
         Ok(self.metadata.as_ref().expect("metadata cached"))
     }
 
-    /// Lazily opened read connection for quant-profile resolution.
-    async fn quant_conn(&mut self) -> Result<Option<&mut sqlx::SqliteConnection>, PqlError> {
-        if self.quant_conn.is_none() {
+    /// The index DB's `[text_normalization]`, read once per query so
+    /// `match_text` sees the same form the FTS index was built from. No DB
+    /// context leaves queries as typed; an unreadable config logs and falls
+    /// back to the defaults rather than failing the search.
+    fn text_normalization(&mut self) -> Option<&TextNormalizationConfig> {
+        if self.text_normalization.is_none() {
+            let index_db = self.index_db.as_deref()?;
+            let config = SystemConfigStore::from_env()
+                .read(index_db)
+                .map(|config| config.text_normalization)
+                .unwrap_or_else(|err| {
+                    warn!(index_db, error = ?err, "failed to read text normalization settings");
+                    TextNormalizationConfig::default()
+                });
+            self.text_normalization = Some(config);
+        }
+        self.text_normalization.as_ref()
+    }
+
+    /// The index DB's configured included folders, read once per query for
+    /// `in_folder` validation. No DB context skips the check.
+    fn included_folders(&mut self) -> Result<Option<&[String]>, PqlError> {
+        if self.included_folders.is_none() {
+            let Some(index_db) = self.index_db.as_deref() else {
+                return Ok(None);
+            };
+            let config = SystemConfigStore::from_env()
+                .read(index_db)
+                .map_err(|err| {
+                    warn!(index_db, error = ?err, "failed to read included folders");
+                    PqlError::invalid("Failed to read the included folders")
+                })?;
+            self.included_folders = Some(config.included_folders);
+        }
+        Ok(self.included_folders.as_deref())
+    }
+
+    /// Lazily opened read connection for quant-profile and tag-alias
+    /// resolution.
+    async fn index_conn(&mut self) -> Result<Option<&mut sqlx::SqliteConnection>, PqlError> {
+        if self.index_conn.is_none() {
             let Some(index_db) = self.index_db.clone() else {
                 return Ok(None);
             };
             let conn = crate::db::open_index_db_read_no_user_data(&index_db)
                 .await
                 .map_err(|err| {
-                    tracing::error!(index_db, error = ?err, "failed to open index db for quant resolution");
-                    PqlError::invalid("Failed to resolve vector quant profiles")
+                    tracing::error!(index_db, error = ?err, "failed to open index db for query preprocessing");
+                    PqlError::internal("Failed to read the index database")
                 })?;
-            self.quant_conn = Some(conn);
+            self.index_conn = Some(conn);
         }
-        Ok(self.quant_conn.as_mut())
+        Ok(self.index_conn.as_mut())
+    }
+
+    /// The alias group of each tag, merging tags that share a group. None
+    /// without DB context.
+    async fn tag_alias_groups(
+        &mut self,
+        tags: &[String],
+    ) -> Result<Option<Vec<Vec<String>>>, PqlError> {
+        let Some(conn) = self.index_conn().await? else {
+            return Ok(None);
+        };
+        let resolved = resolve_tag_alias_groups(conn, tags)
+            .await
+            .map_err(|err| PqlError::invalid(err.detail()))?;
+        let mut groups: Vec<Vec<String>> = Vec::with_capacity(resolved.len());
+        for group in resolved {
+            if !groups.contains(&group) {
+                groups.push(group);
+            }
+        }
+        Ok(Some(groups))
     }
 }
 
+/// Rejects a query embedding whose dimension differs from the one recorded
+/// for the model's stored embeddings (`setters.embedding_dim`), which would
+/// otherwise make every distance computation fail or compare nonsense.
+/// Nothing to check without DB context or before the model stored any.
+async fn check_query_embedding_dim(
+    state: &mut AsyncPreprocessState<'_>,
+    model: &str,
+    embedding: Option<&[u8]>,
+) -> Result<(), PqlError> {
+    let Some(embedding) = embedding else {
+        return Ok(());
+    };
+    let Some(conn) = state.index_conn().await? else {
+        return Ok(());
+    };
+    let recorded: Option<Option<i64>> =
+        sqlx::query_scalar("SELECT embedding_dim FROM setters WHERE name = ?")
+            .bind(model)
+            .fetch_optional(conn)
+            .await
+            .map_err(|err| {
+                tracing::error!(model, error = %err, "failed to read model embedding dimension");
+                PqlError::internal("Failed to read the model's embedding dimension")
+            })?;
+    let Some(expected) = recorded.flatten() else {
+        return Ok(());
+    };
+    let got = embedding.len() / 4;
+    if !embedding.len().is_multiple_of(4) || got as i64 != expected {
+        return Err(PqlError::invalid(format!(
+            "query embedding has {got} dimensions, but model '{model}' stores \
+             {expected}-dimensional embeddings"
+        )));
+    }
+    Ok(())
+}
+
 /// Resolves the quant profile for a vector filter (docs:
 /// "Filter arguments"). `auto` falls back to exact (None) when anything
 /// isn't ready; `quant` (or an explicit `variant`) turns the same
@@ -327,7 +628,7 @@ async fn resolve_vector_quant(
     // turn every vector query into a validation error.
     let variant = normalize_variant(variant);
     let strict = index == IndexMode::Quant || variant.is_some();
-    let Some(conn) = state.quant_conn().await? else {
+    let Some(conn) = state.index_conn().await? else {
         if strict {
             return Err(PqlError::invalid(
                 "vector quant profiles are unavailable in this context",
@@ -461,6 +762,25 @@ fn validate_quant_args_sync(
     Ok(())
 }
 
+/// `frame_indexes`, when given, must list at least one index and no
+/// negative ones.
+fn validate_frame_indexes(indexes: &Option<Vec<i64>>) -> Result<(), PqlError> {
+    let Some(indexes) = indexes else {
+        return Ok(());
+    };
+    if indexes.is_empty() {
+        return Err(PqlError::invalid(
+            "image_embeddings frame_indexes must not be empty",
+        ));
+    }
+    if let Some(index) = indexes.iter().find(|index| **index < 0) {
+        return Err(PqlError::invalid(format!(
+            "image_embeddings frame_indexes must be non-negative, got {index}"
+        )));
+    }
+    Ok(())
+}
+
 fn preprocess_query_async_inner<'a, 'b>(
     el: QueryElement,
     state: &'b mut AsyncPreprocessState<'a>,
@@ -511,7 +831,9 @@ fn preprocess_query_async_inner<'a, 'b>(
             }
             QueryElement::Match(filter) => Ok(filter.validate().map(QueryElement::Match)),
             QueryElement::MatchPath(filter) => Ok(filter.validate().map(QueryElement::MatchPath)),
-            QueryElement::MatchText(filter) => Ok(filter.validate().map(QueryElement::MatchText)),
+            QueryElement::MatchText(filter) => Ok(filter
+                .validate(state.text_normalization())
+                .map(QueryElement::MatchText)),
             QueryElement::SemanticTextSearch(filter) => filter
                 .validate_async(state)
                 .await
@@ -524,16 +846,27 @@ fn preprocess_query_async_inner<'a, 'b>(
                 .validate_async(state)
                 .await
                 .map(|value| value.map(QueryElement::SimilarTo)),
-            QueryElement::MatchTags(filter) => Ok(filter.validate().map(QueryElement::MatchTags)),
+            QueryElement::MatchTags(filter) => filter
+                .validate_async(state)
+                .await
+                .map(|value| value.map(QueryElement::MatchTags)),
             QueryElement::InBookmarks(filter) => {
                 Ok(filter.validate().map(QueryElement::InBookmarks))
             }
+            QueryElement::MatchMeta(filter) => Ok(filter.validate().map(QueryElement::MatchMeta)),
+            QueryElement::MatchNote(filter) => Ok(filter.validate().map(QueryElement::MatchNote)),
             QueryElement::ProcessedBy(filter) => {
                 Ok(filter.validate().map(QueryElement::ProcessedBy))
             }
             QueryElement::HasUnprocessedData(filter) => {
                 Ok(filter.validate().map(QueryElement::HasUnprocessedData))
             }
+            QueryElement::InFolder(filter) => {
+                let included_folders = state.included_folders()?;
+                filter
+                    .validate(included_folders)
+                    .map(|value| value.map(QueryElement::InFolder))
+            }
         }
     })
 }
@@ -561,9 +894,19 @@ impl MatchPath {
 }
 
 impl MatchText {
-    fn validate(mut self) -> Option<Self> {
+    /// `normalization` is the index DB's `[text_normalization]`, applied
+    /// before escaping so the query matches the normalized FTS index.
+    fn validate(mut self, normalization: Option<&TextNormalizationConfig>) -> Option<Self> {
+        if let Some(config) = normalization.filter(|config| config.is_enabled()) {
+            self.match_text.r#match = normalize_search_text(config, &self.match_text.r#match);
+        }
         if !self.match_text.filter_only && self.match_text.r#match.trim().is_empty() {
-            return None;
+            // An empty query still filters by the other criteria; with none
+            // set, the filter would match any text and is dropped instead.
+            if !self.match_text.has_criteria() {
+                return None;
+            }
+            self.match_text.filter_only = true;
         }
         if self.match_text.filter_only {
             self.match_text.select_snippet_as = None;
@@ -572,7 +915,7 @@ impl MatchText {
             self.sort.row_n = false;
             self.match_text.r#match.clear();
         }
-        if !self.match_text.raw_fts5_match {
+        if !self.match_text.raw_fts5_match && !self.match_text.filter_only {
             self.match_text.r#match = parse_and_escape_query(&self.match_text.r#match);
         }
         Some(self)
@@ -589,6 +932,22 @@ impl MatchTags {
         }
         Some(self)
     }
+
+    /// Also expands each tag into its alias group.
+    async fn validate_async(
+        self,
+        state: &mut AsyncPreprocessState<'_>,
+    ) -> Result<Option<Self>, PqlError> {
+        let Some(mut filter) = self.validate() else {
+            return Ok(None);
+        };
+        if filter.match_tags.expand_aliases
+            && let Some(groups) = state.tag_alias_groups(&filter.match_tags.tags).await?
+        {
+            filter.match_tags.alias_groups = groups;
+        }
+        Ok(Some(filter))
+    }
 }
 
 impl InBookmarks {
@@ -601,6 +960,29 @@ impl InBookmarks {
     }
 }
 
+impl MatchMeta {
+    fn validate(mut self) -> Option<Self> {
+        let key = self.match_meta.key.trim();
+        if key.is_empty() {
+            return None;
+        }
+        self.match_meta.key = key.to_string();
+        Some(self)
+    }
+}
+
+impl MatchNote {
+    fn validate(mut self) -> Option<Self> {
+        if self.match_note.r#match.trim().is_empty() {
+            return None;
+        }
+        if !self.match_note.raw_fts5_match {
+            self.match_note.r#match = parse_and_escape_query(&self.match_note.r#match);
+        }
+        Some(self)
+    }
+}
+
 impl ProcessedBy {
     fn validate(self) -> Option<Self> {
         if self.processed_by.trim().is_empty() {
@@ -611,6 +993,29 @@ impl ProcessedBy {
     }
 }
 
+impl InFolder {
+    /// Normalizes the folder the way saved included folders are, so a
+    /// missing trailing separator still matches only whole folder names.
+    /// Unless `any_prefix` is set it must then be one of
+    /// `included_folders`; `None` (no DB context) skips that check.
+    fn validate(mut self, included_folders: Option<&[String]>) -> Result<Option<Self>, PqlError> {
+        let Some(path) = normalize_folder_list(std::slice::from_ref(&self.in_folder.path)).pop()
+        else {
+            return Ok(None);
+        };
+        if !self.in_folder.any_prefix
+            && let Some(included) = included_folders
+            && !included.contains(&path)
+        {
+            return Err(PqlError::invalid(format!(
+                "in_folder path '{path}' is not an included folder; set any_prefix to match any path prefix"
+            )));
+        }
+        self.in_folder.path = path;
+        Ok(Some(self))
+    }
+}
+
 impl HasUnprocessedData {
     fn validate(self) -> Option<Self> {
         if self.has_data_unprocessed.setter_name.trim().is_empty() {
@@ -657,20 +1062,28 @@ impl SemanticTextSearch {
         }
         if self.text_embeddings._embedding.is_none() {
             if let Some(embed_args) = &self.text_embeddings.embed {
-                let embedding = embed_text_query(
+                let (embedding, variants) = embed_templated_query(
                     state,
+                    EmbeddingKind::Text,
                     &self.text_embeddings.query,
                     &self.text_embeddings.model,
                     embed_args,
                 )
                 .await?;
                 self.text_embeddings._embedding = Some(embedding);
+                self.text_embeddings._template_embeddings = variants;
             } else {
                 let embedding =
                     extract_embeddings(&self.text_embeddings.query).map_err(PqlError::invalid)?;
                 self.text_embeddings._embedding = Some(embedding);
             }
         }
+        check_query_embedding_dim(
+            state,
+            &self.text_embeddings.model,
+            self.text_embeddings._embedding.as_deref(),
+        )
+        .await?;
         self.text_embeddings._quant = resolve_vector_quant(
             state,
             self.text_embeddings.index,
@@ -687,7 +1100,9 @@ impl SemanticTextSearch {
 
 impl SemanticImageSearch {
     fn validate_sync(mut self) -> Result<Option<Self>, PqlError> {
-        if self.image_embeddings.query.trim().is_empty() {
+        if self.image_embeddings.query.trim().is_empty()
+            && self.image_embeddings._embedding.is_none()
+        {
             return Ok(None);
         }
         validate_quant_args_sync(
@@ -696,6 +1111,7 @@ impl SemanticImageSearch {
             self.image_embeddings.k,
             self.image_embeddings._quant.is_some(),
         )?;
+        validate_frame_indexes(&self.image_embeddings.frame_indexes)?;
         if self.image_embeddings._embedding.is_none() {
             if self.image_embeddings.embed.is_none() {
                 let embedding =
@@ -725,19 +1141,24 @@ impl SemanticImageSearch {
         mut self,
         state: &mut AsyncPreprocessState<'_>,
     ) -> Result<Option<Self>, PqlError> {
-        if self.image_embeddings.query.trim().is_empty() {
+        if self.image_embeddings.query.trim().is_empty()
+            && self.image_embeddings._embedding.is_none()
+        {
             return Ok(None);
         }
+        validate_frame_indexes(&self.image_embeddings.frame_indexes)?;
         if self.image_embeddings._embedding.is_none() {
             if let Some(embed_args) = &self.image_embeddings.embed {
-                let embedding = embed_image_query(
+                let (embedding, variants) = embed_templated_query(
                     state,
+                    EmbeddingKind::Image,
                     &self.image_embeddings.query,
                     &self.image_embeddings.model,
                     embed_args,
                 )
                 .await?;
                 self.image_embeddings._embedding = Some(embedding);
+                self.image_embeddings._template_embeddings = variants;
             } else {
                 let embedding =
                     extract_embeddings(&self.image_embeddings.query).map_err(PqlError::invalid)?;
@@ -751,6 +1172,12 @@ impl SemanticImageSearch {
         if !self.image_embeddings.clip_xmodal && self.image_embeddings.src_text.is_some() {
             self.image_embeddings.src_text = None;
         }
+        check_query_embedding_dim(
+            state,
+            &self.image_embeddings.model,
+            self.image_embeddings._embedding.as_deref(),
+        )
+        .await?;
         self.image_embeddings._quant = resolve_vector_quant(
             state,
             self.image_embeddings.index,
@@ -818,6 +1245,66 @@ impl SimilarTo {
     }
 }
 
+/// Upper bound on `embed.prompt_templates`: each template costs one
+/// inference call per uncached query.
+const MAX_PROMPT_TEMPLATES: usize = 8;
+
+fn validate_prompt_templates(embed: &EmbedArgs) -> Result<(), PqlError> {
+    if embed.prompt_templates.len() > MAX_PROMPT_TEMPLATES {
+        return Err(PqlError::invalid(format!(
+            "prompt_templates allows at most {MAX_PROMPT_TEMPLATES} templates, got {}",
+            embed.prompt_templates.len()
+        )));
+    }
+    if let Some(template) = embed
+        .prompt_templates
+        .iter()
+        .find(|template| !template.contains("{}"))
+    {
+        return Err(PqlError::invalid(format!(
+            "prompt template {template:?} has no {{}} placeholder for the query"
+        )));
+    }
+    Ok(())
+}
+
+/// Embeds the query, once per prompt template when `embed` has any. Each
+/// variant goes through the embedding cache on its own; the search vector
+/// is the re-normalized mean of the normalized variants. Also returns the
+/// variants when `separate_templates` asks to rank against each of them.
+async fn embed_templated_query(
+    state: &AsyncPreprocessState<'_>,
+    kind: EmbeddingKind,
+    query: &str,
+    model: &str,
+    embed: &EmbedArgs,
+) -> Result<(Vec<u8>, Vec<Vec<u8>>), PqlError> {
+    validate_prompt_templates(embed)?;
+    if embed.prompt_templates.is_empty() {
+        let embedding = match kind {
+            EmbeddingKind::Text => embed_text_query(state, query, model, embed).await?,
+            EmbeddingKind::Image => embed_image_query(state, query, model, embed).await?,
+        };
+        return Ok((embedding, Vec::new()));
+    }
+    let mut variants = Vec::with_capacity(embed.prompt_templates.len());
+    for template in &embed.prompt_templates {
+        let variant = template.replace("{}", query);
+        let embedding = match kind {
+            EmbeddingKind::Text => embed_text_query(state, &variant, model, embed).await?,
+            EmbeddingKind::Image => embed_image_query(state, &variant, model, embed).await?,
+        };
+        variants.push(normalize_embedding(&embedding).map_err(PqlError::invalid)?);
+    }
+    let embedding = average_embeddings(&variants)
+        .and_then(|mean| normalize_embedding(&mean))
+        .map_err(PqlError::invalid)?;
+    if !embed.separate_templates {
+        variants.clear();
+    }
+    Ok((embedding, variants))
+}
+
 async fn embed_text_query(
     state: &AsyncPreprocessState<'_>,
     query: &str,
@@ -829,7 +1316,7 @@ async fn embed_text_query(
         kind: EmbeddingKind::Text,
         query: query.to_string(),
     };
-    cached_embedding_or_fetch(key, state.embedding_cache_size, async {
+    cached_embedding_or_fetch(key, state.embedding_cache_bytes, async {
         let inputs = [InferenceInput::new(
             serde_json::json!({"text": query, "task": "s2s"}),
             None,
@@ -867,7 +1354,7 @@ async fn embed_image_query(
         kind: EmbeddingKind::Image,
         query: query.to_string(),
     };
-    cached_embedding_or_fetch(key, state.embedding_cache_size, async {
+    cached_embedding_or_fetch(key, state.embedding_cache_bytes, async {
         let inputs = [InferenceInput::new(
             serde_json::json!({"text": query}),
             None,
@@ -896,7 +1383,7 @@ async fn embed_image_query(
 
 fn inference_error<E: std::fmt::Display>(context: &'static str, err: E) -> PqlError {
     warn!(error = %err, "{context}");
-    PqlError::invalid(context)
+    PqlError::upstream(context)
 }
 
 fn embedding_from_predict(output: PredictOutput) -> Result<Vec<u8>, PqlError> {
@@ -1255,3 +1742,428 @@ mod quant_policy_tests {
         ));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn cache_key(query: &str) -> EmbeddingCacheKey {
+        EmbeddingCacheKey {
+            model: "m".to_string(),
+            kind: EmbeddingKind::Text,
+            query: query.to_string(),
+        }
+    }
+
+    // Entries are charged by size, so one large embedding evicts several
+    // small least-recently-used ones, and shrinking the budget evicts too.
+    #[test]
+    fn embedding_cache_evicts_by_bytes_in_lru_order() {
+        let entry_bytes =
+            |query: &str, len: usize| EMBEDDING_ENTRY_OVERHEAD_BYTES + 1 + query.len() + len;
+        let mut cache = EmbeddingCache {
+            lru: LruCache::new(usize::MAX),
+            used_bytes: 0,
+            budget_bytes: 3 * entry_bytes("a", 100),
+        };
+        for query in ["a", "b", "c"] {
+            cache.insert(cache_key(query), vec![0; 100]);
+        }
+        assert!(cache.get(&cache_key("a")).is_some());
+
+        cache.insert(cache_key("d"), vec![0; 200]);
+        assert!(cache.get(&cache_key("b")).is_none());
+        assert!(cache.get(&cache_key("c")).is_none());
+        assert!(cache.get(&cache_key("a")).is_some());
+        assert_eq!(
+            cache.used_bytes,
+            entry_bytes("a", 100) + entry_bytes("d", 200)
+        );
+
+        // Too large for the whole budget: not cached, nothing evicted.
+        cache.insert(cache_key("e"), vec![0; 1000]);
+        assert!(cache.get(&cache_key("e")).is_none());
+        assert_eq!(cache.lru.len(), 2);
+
+        cache.set_budget(entry_bytes("a", 100));
+        assert_eq!(cache.lru.len(), 1);
+        assert!(cache.get(&cache_key("a")).is_some());
+        assert_eq!(cache.used_bytes, entry_bytes("a", 100));
+    }
+
+    // match_text queries are normalized with the index DB's settings before
+    // escaping, so a fullwidth query reaches FTS in the form the normalized
+    // index holds; without DB settings the query is left as typed.
+    #[test]
+    fn match_text_query_is_normalized_before_escaping() {
+        let filter: MatchText = serde_json::from_value(json!({
+            "match_text": { "match": "ＰＡＮＯ\u{00AD}ＰＴＩＫＯＮ", "raw_fts5_match": false }
+        }))
+        .expect("match_text filter");
+        let config = TextNormalizationConfig {
+            nfkc: true,
+            strip_control: true,
+            ..TextNormalizationConfig::default()
+        };
+
+        let normalized = filter.clone().validate(Some(&config)).expect("kept");
+        assert_eq!(normalized.match_text.r#match, "\"PANOPTIKON\"");
+
+        let untouched = filter.validate(None).expect("kept");
+        assert_eq!(
+            untouched.match_text.r#match,
+            "\"ＰＡＮＯ\u{00AD}ＰＴＩＫＯＮ\""
+        );
+    }
+
+    // in_folder paths get the included-folder normalization: a missing
+    // trailing separator is added, so `/mnt/arch` never matches
+    // `/mnt/archive/`. The included-folder check compares exactly, so a
+    // differently-cased path is rejected unless any_prefix is set.
+    #[test]
+    fn in_folder_path_is_normalized_and_checked_against_included_folders() {
+        let filter = |path: &str, any_prefix: bool| -> InFolder {
+            serde_json::from_value(json!({
+                "in_folder": { "path": path, "any_prefix": any_prefix }
+            }))
+            .expect("in_folder filter")
+        };
+        let included = vec!["/mnt/archive/".to_string()];
+
+        let kept = filter("/mnt/archive", false)
+            .validate(Some(&included))
+            .expect("valid")
+            .expect("kept");
+        assert_eq!(kept.in_folder.path, "/mnt/archive/");
+        let kept = filter(" /mnt/archive/ ", false)
+            .validate(Some(&included))
+            .expect("valid")
+            .expect("kept");
+        assert_eq!(kept.in_folder.path, "/mnt/archive/");
+
+        let err = filter("/mnt/Archive", false)
+            .validate(Some(&included))
+            .expect_err("not included");
+        assert!(err.message.contains("/mnt/Archive/"));
+        let kept = filter("/mnt/Archive", true)
+            .validate(Some(&included))
+            .expect("valid")
+            .expect("kept");
+        assert_eq!(kept.in_folder.path, "/mnt/Archive/");
+        assert!(
+            filter("/mnt/elsewhere", false)
+                .validate(None)
+                .expect("no DB context")
+                .is_some()
+        );
+        assert!(filter("  ", false).validate(None).expect("valid").is_none());
+    }
+
+    // A disabled filter is rejected wherever it is nested, with its path;
+    // the same query passes once nothing it uses is disabled, and refine
+    // counts as an image_embeddings search.
+    #[test]
+    fn disabled_filters_are_rejected_with_their_path() {
+        let query: PqlQuery = serde_json::from_value(json!({
+            "query": { "and_": [
+                { "match_path": { "match": "cat" } },
+                { "or_": [
+                    { "match_tags": { "tags": ["cat"] } },
+                    { "not_": { "similar_to": { "target": "abc", "model": "clip" } } }
+                ] }
+            ] }
+        }))
+        .expect("query");
+
+        let disabled = vec!["similar_to".to_string()];
+        let err = reject_disabled_filters(&query, &disabled).unwrap_err();
+        assert_eq!(err.kind, PqlErrorKind::Disabled);
+        assert!(err.message.contains("similar_to"), "{}", err.message);
+        assert!(
+            err.message.contains("query.and_[1].or_[1].not_"),
+            "{}",
+            err.message
+        );
+        reject_disabled_filters(&query, &["image_embeddings".to_string()]).unwrap();
+
+        let refine: PqlQuery = serde_json::from_value(json!({
+            "refine": { "file_ids": [1], "model": "clip" }
+        }))
+        .expect("refine query");
+        let err = reject_disabled_filters(&refine, &["image_embeddings".to_string()]).unwrap_err();
+        assert!(err.message.contains("refine"), "{}", err.message);
+    }
+
+    // Files 1 and 2 (item 1 has two frames averaging to [1, 0]) form the
+    // centroid; an unknown file ID is ignored. Results rank by closeness to
+    // it and the item without an embedding drops out.
+    #[tokio::test]
+    async fn refine_ranks_results_by_the_centroid_of_the_given_files() {
+        use crate::db::migrations::migrate_databases_on_disk;
+        use crate::pql::model::RefineArgs;
+        use crate::test_utils::test_data_dir;
+        use sea_query::SqliteQueryBuilder;
+        use sea_query_sqlx::SqlxBinder;
+
+        let _test_env = test_data_dir();
+        let index_db = "refine_index".to_string();
+        migrate_databases_on_disk(Some(&index_db), None)
+            .await
+            .unwrap();
+        let mut conn = crate::db::open_index_db_write_no_user_data(&index_db)
+            .await
+            .unwrap();
+        let embedding = |values: &[f32]| serialize_f32(values);
+        sqlx::query(
+            r#"
+INSERT INTO file_scans (id, start_time, path) VALUES (1, '2024-01-01T00:00:00', '/');
+INSERT INTO items (id, sha256, md5, type, time_added) VALUES
+    (1, 'sha1', 'md51', 'video/mp4', '2024-01-01T00:00:00'),
+    (2, 'sha2', 'md52', 'image/png', '2024-01-01T00:00:00'),
+    (3, 'sha3', 'md53', 'image/png', '2024-01-01T00:00:00'),
+    (4, 'sha4', 'md54', 'image/png', '2024-01-01T00:00:00');
+INSERT INTO files (id, sha256, item_id, path, filename, last_modified, scan_id, available) VALUES
+    (1, 'sha1', 1, '/a.mp4', 'a.mp4', '2024-01-01T00:00:00', 1, 1),
+    (2, 'sha2', 2, '/b.png', 'b.png', '2024-01-01T00:00:00', 1, 1),
+    (3, 'sha3', 3, '/c.png', 'c.png', '2024-01-01T00:00:00', 1, 1),
+    (4, 'sha4', 4, '/d.png', 'd.png', '2024-01-01T00:00:00', 1, 1);
+INSERT INTO setters (id, name) VALUES (1, 'clip/test');
+INSERT INTO item_data (id, item_id, setter_id, data_type, idx, is_origin) VALUES
+    (1, 1, 1, 'clip', 0, 1),
+    (2, 1, 1, 'clip', 1, 1),
+    (3, 2, 1, 'clip', 0, 1),
+    (4, 3, 1, 'clip', 0, 1);
+            "#,
+        )
+        .execute(&mut conn)
+        .await
+        .unwrap();
+        for (id, values) in [
+            (1, [1.0, 0.2]),
+            (2, [1.0, -0.2]),
+            (3, [1.0, 0.0]),
+            (4, [0.0, 1.0]),
+        ] {
+            sqlx::query("INSERT INTO embeddings (id, embedding) VALUES (?1, ?2)")
+                .bind(id)
+                .bind(embedding(&values))
+                .execute(&mut conn)
+                .await
+                .unwrap();
+        }
+
+        let refine = |file_ids: Vec<i64>| PqlQuery {
+            refine: Some(Box::new(RefineArgs {
+                file_ids,
+                model: "clip/test".to_string(),
+                distance_aggregation: Default::default(),
+                priority: 100,
+            })),
+            ..Default::default()
+        };
+        let mut query = refine(vec![1, 2, 999]);
+        apply_refine(&mut query, &index_db).await.unwrap();
+        assert!(query.refine.is_none());
+        let built = crate::pql::build_query_preprocessed(query, false).unwrap();
+        let (sql, values) = built
+            .paginated_query()
+            .with(built.with_clause.clone().unwrap())
+            .build_sqlx(SqliteQueryBuilder);
+        let paths: Vec<String> = sqlx::query_with(sqlx::AssertSqlSafe(sql.as_str()), values)
+            .fetch_all(&mut conn)
+            .await
+            .unwrap()
+            .iter()
+            .map(|row| sqlx::Row::get(row, "path"))
+            .collect();
+        assert_eq!(paths.last().map(String::as_str), Some("/c.png"));
+        assert_eq!(paths.len(), 3);
+
+        let mut missing = refine(vec![4]);
+        let err = apply_refine(&mut missing, &index_db).await.unwrap_err();
+        assert!(err.message.contains("clip/test"));
+        assert_eq!(err.kind, PqlErrorKind::Invalid);
+
+        // A failing read is the server's fault, not the query's.
+        sqlx::query("DROP TABLE embeddings")
+            .execute(&mut conn)
+            .await
+            .unwrap();
+        let err = apply_refine(&mut refine(vec![1]), &index_db)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind, PqlErrorKind::Internal);
+    }
+
+    // Ensures a query embedding of another size than the model's recorded
+    // dimension is rejected naming both, while models without a recorded
+    // dimension pass.
+    #[tokio::test]
+    async fn query_embedding_dimension_must_match_the_model() {
+        use crate::db::migrations::migrate_databases_on_disk;
+        use crate::test_utils::test_data_dir;
+
+        let _test_env = test_data_dir();
+        let index_db = "embedding_dim_index".to_string();
+        migrate_databases_on_disk(Some(&index_db), None)
+            .await
+            .unwrap();
+        let mut conn = crate::db::open_index_db_write_no_user_data(&index_db)
+            .await
+            .unwrap();
+        sqlx::query(
+            "INSERT INTO setters (name, embedding_dim) VALUES ('clip/test', 2), ('fresh', NULL)",
+        )
+        .execute(&mut conn)
+        .await
+        .unwrap();
+        drop(conn);
+
+        let inference =
+            InferenceApiClient::new_with_metadata_cache("http://127.0.0.1:1", false).unwrap();
+        let mut state = AsyncPreprocessState {
+            inference: &inference,
+            metadata: None,
+            embedding_cache_bytes: 0,
+            index_db: Some(index_db.clone()),
+            index_conn: None,
+            text_normalization: None,
+            included_folders: None,
+        };
+        let three = serialize_f32(&[1.0, 0.0, 0.0]);
+        let err = check_query_embedding_dim(&mut state, "clip/test", Some(&three))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.message,
+            "query embedding has 3 dimensions, but model 'clip/test' stores 2-dimensional embeddings"
+        );
+        assert_eq!(err.kind, PqlErrorKind::Invalid);
+        let two = serialize_f32(&[1.0, 0.0]);
+        check_query_embedding_dim(&mut state, "clip/test", Some(&two))
+            .await
+            .unwrap();
+        check_query_embedding_dim(&mut state, "fresh", Some(&three))
+            .await
+            .unwrap();
+        check_query_embedding_dim(&mut state, "unknown", Some(&three))
+            .await
+            .unwrap();
+
+        // A failing read is the server's fault, not the query's.
+        let mut conn = crate::db::open_index_db_write_no_user_data(&index_db)
+            .await
+            .unwrap();
+        sqlx::query("ALTER TABLE setters RENAME COLUMN embedding_dim TO renamed_dim")
+            .execute(&mut conn)
+            .await
+            .unwrap();
+        let err = check_query_embedding_dim(&mut state, "clip/test", Some(&two))
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind, PqlErrorKind::Internal);
+    }
+
+    // Each prompt template variant is embedded and cached on its own, and
+    // the search vector is their normalized mean at the model's dimension.
+    // Templates without a placeholder, or too many of them, are rejected.
+    #[tokio::test]
+    async fn prompt_templates_cache_each_variant_and_average_them() {
+        use axum::routing::post;
+        use axum::{Json, Router};
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        use crate::pql::embedding_utils::deserialize_f32;
+
+        // Call n answers with the n-th unit axis, scaled so normalization
+        // is visible.
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&calls);
+        let app = Router::new().route(
+            "/api/inference/predict/{group}/{id}",
+            post(move || {
+                let counter = Arc::clone(&counter);
+                async move {
+                    let n = counter.fetch_add(1, Ordering::SeqCst);
+                    let mut embedding = vec![0.0_f32; 4];
+                    embedding[n % 4] = 3.0;
+                    Json(json!({ "outputs": [{ "embedding": embedding }] }))
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let cache_bytes = 1024 * 1024;
+        let inference =
+            InferenceApiClient::new_with_metadata_cache(format!("http://{addr}"), false).unwrap();
+        let state = AsyncPreprocessState {
+            inference: &inference,
+            metadata: None,
+            embedding_cache_bytes: cache_bytes,
+            index_db: None,
+            index_conn: None,
+            text_normalization: None,
+            included_folders: None,
+        };
+        let model = "templates/test";
+        let embed = EmbedArgs {
+            prompt_templates: vec![
+                "a photo of {}".to_string(),
+                "a drawing of {}".to_string(),
+                "{}".to_string(),
+            ],
+            ..EmbedArgs::default()
+        };
+
+        let (embedding, variants) =
+            embed_templated_query(&state, EmbeddingKind::Text, "cat", model, &embed)
+                .await
+                .unwrap();
+        let values = deserialize_f32(&embedding).unwrap();
+        let component = 1.0 / 3.0_f32.sqrt();
+        assert_eq!(values.len(), 4);
+        for (value, expected) in values.iter().zip([component, component, component, 0.0]) {
+            assert!((value - expected).abs() < 1e-6, "{values:?}");
+        }
+        assert!(variants.is_empty());
+
+        let stats = embedding_cache_stats(cache_bytes, 1, 100).await;
+        let cached: Vec<_> = stats
+            .entries
+            .iter()
+            .filter(|entry| entry.inference_id == model)
+            .collect();
+        assert_eq!(cached.len(), 3);
+        assert!(cached.iter().all(|entry| entry.size == 16));
+
+        // Cached variants are not re-embedded; separate mode keeps them.
+        let separate = EmbedArgs {
+            separate_templates: true,
+            ..embed.clone()
+        };
+        let (_, variants) =
+            embed_templated_query(&state, EmbeddingKind::Text, "cat", model, &separate)
+                .await
+                .unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        assert_eq!(variants.len(), 3);
+        assert_eq!(deserialize_f32(&variants[0]).unwrap(), [1.0, 0.0, 0.0, 0.0]);
+
+        let no_placeholder = EmbedArgs {
+            prompt_templates: vec!["a photo".to_string()],
+            ..EmbedArgs::default()
+        };
+        assert!(validate_prompt_templates(&no_placeholder).is_err());
+        let too_many = EmbedArgs {
+            prompt_templates: vec!["{}".to_string(); MAX_PROMPT_TEMPLATES + 1],
+            ..EmbedArgs::default()
+        };
+        assert!(validate_prompt_templates(&too_many).is_err());
+    }
+}
@@ -1,20 +1,195 @@
-use shell_words::split as shell_split;
+use unicode_normalization::UnicodeNormalization;
 
+use crate::db::system_config::TextNormalizationConfig;
+
+/// Escapes a user string into an FTS5 query that matches it literally. Each
+/// whitespace-separated token becomes a quoted string, so characters FTS5
+/// would parse as syntax (`:`, `(`, `-`, `^`, keywords) are plain text.
+/// Double-quoted runs stay together as one phrase (an unclosed quote runs to
+/// the end), `\"` is a literal quote, and an unquoted token ending in `*`
+/// becomes a prefix query.
 pub(crate) fn parse_and_escape_query(user_input: &str) -> String {
-    let mut working = user_input.replace("\\\"", "\"\"");
-    if working.matches('"').count() % 2 != 0 {
-        working.push('"');
-    }
-    working = working.replace('\'', "\\'");
-    working = working.replace("\"\"", "\\\"");
-
-    let tokens = shell_split(&working).unwrap_or_else(|_| {
-        working
-            .split_whitespace()
-            .map(|token| token.to_string())
-            .collect()
-    });
-    let escaped_tokens = tokens.into_iter().map(|token| token.replace('"', "\"\""));
-    let quoted_tokens = escaped_tokens.map(|token| format!("\"{}\"", token));
-    quoted_tokens.collect::<Vec<String>>().join(" ")
+    let mut tokens: Vec<(String, bool)> = Vec::new();
+    let mut token = String::new();
+    let mut quoted = false;
+    let mut in_phrase = false;
+    let mut chars = user_input.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'"') => {
+                token.push('"');
+                chars.next();
+            }
+            '"' => {
+                in_phrase = !in_phrase;
+                quoted = true;
+            }
+            c if c.is_whitespace() && !in_phrase => {
+                tokens.push((std::mem::take(&mut token), quoted));
+                quoted = false;
+            }
+            c => token.push(c),
+        }
+    }
+    tokens.push((token, quoted));
+
+    let escaped = tokens
+        .into_iter()
+        .filter(|(token, _)| !token.is_empty())
+        .map(|(token, quoted)| {
+            let stem = token.trim_end_matches('*');
+            let prefix = !quoted && !stem.is_empty() && stem.len() < token.len();
+            let literal = if prefix { stem } else { token.as_str() };
+            let literal = format!("\"{}\"", literal.replace('"', "\"\""));
+            if prefix {
+                format!("{literal} *")
+            } else {
+                literal
+            }
+        })
+        .collect::<Vec<_>>();
+    if escaped.is_empty() {
+        // An empty phrase matches nothing; an empty MATCH is a syntax error.
+        return "\"\"".to_string();
+    }
+    escaped.join(" ")
+}
+
+/// Applies the configured `[text_normalization]` steps. Extraction writes
+/// and `match_text` queries both go through here, so the FTS index and the
+/// query always see the same form.
+pub(crate) fn normalize_search_text(config: &TextNormalizationConfig, text: &str) -> String {
+    let mut normalized = if config.nfkc {
+        text.nfkc().collect::<String>()
+    } else {
+        text.to_string()
+    };
+    if config.ascii_punctuation {
+        normalized = normalized.chars().map(ascii_punctuation).collect();
+    }
+    if config.strip_control {
+        normalized.retain(|c| matches!(c, '\t' | '\n' | '\r') || !is_invisible(c));
+    }
+    if config.collapse_whitespace {
+        normalized = normalized.split_whitespace().collect::<Vec<_>>().join(" ");
+    }
+    normalized
+}
+
+/// The normalized form to store next to `text`, or None when normalization
+/// is off or leaves the text unchanged (the FTS index then covers `text`).
+pub(crate) fn normalized_for_index(config: &TextNormalizationConfig, text: &str) -> Option<String> {
+    if !config.is_enabled() {
+        return None;
+    }
+    let normalized = normalize_search_text(config, text);
+    (normalized != text).then_some(normalized)
+}
+
+fn ascii_punctuation(c: char) -> char {
+    match c {
+        '\u{2010}'..='\u{2015}' | '\u{2212}' | '\u{FE58}' | '\u{FE63}' | '\u{FF0D}' => '-',
+        '\u{2018}'..='\u{201B}' | '\u{2032}' | '\u{FF07}' => '\'',
+        '\u{201C}'..='\u{201F}' | '\u{2033}' | '\u{FF02}' => '"',
+        other => other,
+    }
+}
+
+/// Control characters plus the format characters OCR output carries that
+/// never render: soft hyphen, zero-width space/joiners, word joiner, BOM,
+/// and bidi marks/embeddings/isolates.
+fn is_invisible(c: char) -> bool {
+    c.is_control()
+        || matches!(
+            c,
+            '\u{00AD}'
+                | '\u{200B}'..='\u{200F}'
+                | '\u{202A}'..='\u{202E}'
+                | '\u{2060}'..='\u{2064}'
+                | '\u{2066}'..='\u{2069}'
+                | '\u{FEFF}'
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all_enabled() -> TextNormalizationConfig {
+        TextNormalizationConfig {
+            nfkc: true,
+            strip_control: true,
+            collapse_whitespace: true,
+            ascii_punctuation: true,
+        }
+    }
+
+    // OCR-style input with fullwidth letters, curly quotes, an en dash, a
+    // soft hyphen, and ragged whitespace normalizes to plain ASCII text.
+    #[test]
+    fn normalize_search_text_applies_every_step() {
+        let text =
+            "  \u{201C}ＰＡＮＯ\u{00AD}ＰＴＩＫＯＮ\u{201D}\u{2013}it\u{2019}s\u{200B}\n\tok  ";
+        assert_eq!(
+            normalize_search_text(&all_enabled(), text),
+            "\"PANOPTIKON\"-it's ok"
+        );
+    }
+
+    // Ensures FTS syntax characters, quotes, and unicode come out as quoted
+    // literals, quoted runs stay phrases, and a trailing `*` stays a prefix.
+    #[test]
+    fn parse_and_escape_query_quotes_every_token() {
+        assert_eq!(
+            parse_and_escape_query("C:\\Users (v2)"),
+            "\"C:\\Users\" \"(v2)\""
+        );
+        assert_eq!(
+            parse_and_escape_query("NOT a-b ^c"),
+            "\"NOT\" \"a-b\" \"^c\""
+        );
+        assert_eq!(
+            parse_and_escape_query("say \"hello world\" it's"),
+            "\"say\" \"hello world\" \"it's\""
+        );
+        assert_eq!(parse_and_escape_query("12\\\" x"), "\"12\"\"\" \"x\"");
+        assert_eq!(
+            parse_and_escape_query("\"unclosed phrase"),
+            "\"unclosed phrase\""
+        );
+        assert_eq!(
+            parse_and_escape_query("café* \"ä*\" *"),
+            "\"café\" * \"ä*\" \"*\""
+        );
+        assert_eq!(parse_and_escape_query("\"\" "), "\"\"");
+    }
+
+    // Each option is independent: with only NFKC on, quotes, soft hyphens,
+    // and whitespace are left alone.
+    #[test]
+    fn normalize_search_text_respects_disabled_steps() {
+        let config = TextNormalizationConfig {
+            nfkc: true,
+            ..TextNormalizationConfig::default()
+        };
+        assert_eq!(
+            normalize_search_text(&config, "\u{201C}ＡＢ\u{00AD}Ｃ\u{201D}  x"),
+            "\u{201C}AB\u{00AD}C\u{201D}  x"
+        );
+    }
+
+    // Nothing is stored next to the raw text when normalization is off or
+    // does not change it, so the FTS index keeps covering `text` itself.
+    #[test]
+    fn normalized_for_index_skips_unchanged_text() {
+        assert_eq!(
+            normalized_for_index(&TextNormalizationConfig::default(), "ＡＢＣ"),
+            None
+        );
+        assert_eq!(normalized_for_index(&all_enabled(), "plain text"), None);
+        assert_eq!(
+            normalized_for_index(&all_enabled(), "ＡＢＣ"),
+            Some("ABC".to_string())
+        );
+    }
 }
@@ -7,7 +7,8 @@
 //! process (msedge.exe); also used by the HTML-thumbnail browser path. On
 //! Unix the job-object role is split: `die_with_parent` ties the direct
 //! child to gateway death via the kernel, and `kill_process_group` reaps
-//! the child's descendants on the explicit kill paths.
+//! the child's descendants on the explicit kill paths. `limit_data_segment`
+//! caps the memory of the HTML-thumbnail browser.
 
 /// Keep console signals for the gateway alone: a Ctrl-C that reached the
 /// children directly would kill them before the supervisor is told to stop,
@@ -67,6 +68,37 @@ pub(crate) fn die_with_parent(command: &mut tokio::process::Command) {
     }
 }
 
+/// Cap the child's data segment (`RLIMIT_DATA`) at `bytes`, so a runaway
+/// allocation fails inside the child instead of pushing the host into swap
+/// or the OOM killer. `RLIMIT_AS` would also count Chromium's huge
+/// PROT_NONE address-space reservations and stop it from starting at all,
+/// and `RLIMIT_RSS` is not enforced by Linux. The limit is inherited by the
+/// child's descendants (the browser's renderer processes). Windows: no-op.
+pub(crate) fn limit_data_segment(command: &mut std::process::Command, bytes: u64) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        let limit = libc::rlimit {
+            rlim_cur: bytes as libc::rlim_t,
+            rlim_max: bytes as libc::rlim_t,
+        };
+        // SAFETY: runs between fork and exec in the child; setrlimit is
+        // async-signal-safe.
+        unsafe {
+            command.pre_exec(move || {
+                if libc::setrlimit(libc::RLIMIT_DATA, &limit) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (command, bytes);
+    }
+}
+
 /// SIGKILL the child's whole process group. The spawn made the child its
 /// own group leader (`detach_from_console`), so this reaps descendants
 /// (dataloader workers and the like) that a plain child kill would orphan —
@@ -198,3 +230,19 @@ mod windows_job {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    // Ensures the data-segment cap reaches the spawned child (`ulimit -d`
+    // reports it in KiB).
+    #[cfg(unix)]
+    #[test]
+    fn limit_data_segment_applies_to_child() {
+        let mut command = std::process::Command::new("sh");
+        command.arg("-c").arg("ulimit -d");
+        super::limit_data_segment(&mut command, 256 * 1024 * 1024);
+        let output = command.output().expect("sh should run");
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "262144");
+    }
+}
@@ -13,10 +13,13 @@ use hyper_util::{
     client::legacy::{Client, connect::HttpConnector},
     rt::{TokioExecutor, TokioIo},
 };
-use std::{net::SocketAddr, sync::Arc};
-use tokio::sync::watch;
+use serde::Serialize;
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore, watch};
 
-use crate::config::Settings;
+use crate::api_error::ErrorCode;
+use crate::config::{Settings, UpstreamTimeouts, UpstreamWebSocket};
 use crate::inferio_client::InferenceApiClient;
 use crate::policy::PolicyContext;
 use crate::policy_token::{POLICY_TOKEN_HEADER, TokenKey};
@@ -25,28 +28,51 @@ use crate::policy_token::{POLICY_TOKEN_HEADER, TokenKey};
 pub struct Upstream {
     name: String,
     base_uri: Uri,
+    timeouts: UpstreamTimeouts,
+    websocket: UpstreamWebSocket,
+    /// One permit per open upgraded connection when
+    /// `websocket.max_connections` is set; shared by every clone.
+    upgrade_slots: Option<Arc<Semaphore>>,
+    /// Per-upstream client: the connect deadline is a connector setting, so
+    /// upstreams with different `connect_secs` cannot share one.
+    client: Client<HttpConnector, Body>,
 }
 
 impl Upstream {
-    pub fn parse(name: &str, raw: &str) -> Result<Self> {
+    pub fn parse(name: &str, raw: &str, timeouts: UpstreamTimeouts) -> Result<Self> {
         let base_uri: Uri = raw.parse().context("invalid upstream URL")?;
         if base_uri.scheme().is_none() || base_uri.authority().is_none() {
             bail!("upstream URL must include scheme and authority");
         }
+        let mut connector = HttpConnector::new();
+        connector.set_connect_timeout(timeouts.connect_secs.map(Duration::from_secs));
+        let client = Client::builder(TokioExecutor::new()).build(connector);
         Ok(Self {
             name: name.to_string(),
             base_uri,
+            timeouts,
+            websocket: UpstreamWebSocket::default(),
+            upgrade_slots: None,
+            client,
         })
     }
+
+    /// Applies `[upstreams.<name>.websocket]` limits to upgraded connections.
+    pub fn with_websocket(mut self, websocket: UpstreamWebSocket) -> Self {
+        self.upgrade_slots = websocket
+            .max_connections
+            .map(|limit| Arc::new(Semaphore::new(limit)));
+        self.websocket = websocket;
+        self
+    }
 }
 
 pub struct ProxyState {
-    pub client: Client<HttpConnector, Body>,
     pub ui: Upstream,
     pub api: Upstream,
     pub inference: Upstream,
     pub inference_client: InferenceApiClient,
-    pub search_embedding_cache_size: usize,
+    pub search_embedding_cache_bytes: usize,
     /// Full gateway settings, for handlers that need policy/ruleset config
     /// (the /api/client-config capability derivation).
     pub settings: Arc<Settings>,
@@ -65,19 +91,17 @@ impl ProxyState {
         api: Upstream,
         inference: Upstream,
         inference_client: InferenceApiClient,
-        search_embedding_cache_size: usize,
+        search_embedding_cache_bytes: usize,
         settings: Arc<Settings>,
         token_key: Arc<TokenKey>,
         shutdown_rx: watch::Receiver<bool>,
     ) -> Self {
-        let client = Client::builder(TokioExecutor::new()).build_http();
         Self {
-            client,
             ui,
             api,
             inference,
             inference_client,
-            search_embedding_cache_size,
+            search_embedding_cache_bytes,
             settings,
             token_key,
             shutdown_rx,
@@ -202,10 +226,16 @@ fn spawn_upgrade_bridge(
     client: OnUpgrade,
     upstream_conn: OnUpgrade,
     mut shutdown_rx: watch::Receiver<bool>,
-    upstream_name: String,
+    upstream: &Upstream,
+    slot: Option<OwnedSemaphorePermit>,
     path: String,
 ) {
+    let upstream_name = upstream.name.clone();
+    let idle = upstream.websocket.idle_secs.map(Duration::from_secs);
     tokio::spawn(async move {
+        // Held until the bridge ends, so the connection counts against
+        // `websocket.max_connections` for as long as it is open.
+        let _slot = slot;
         let (client_io, upstream_io) = match tokio::try_join!(client, upstream_conn) {
             Ok(pair) => pair,
             Err(err) => {
@@ -221,8 +251,14 @@ fn spawn_upgrade_bridge(
         let mut client_io = TokioIo::new(client_io);
         let mut upstream_io = TokioIo::new(upstream_io);
         tracing::debug!(upstream = %upstream_name, path = %path, "upgrade bridge open");
+        let copy = async {
+            match idle {
+                Some(idle) => copy_bidirectional_idle(&mut client_io, &mut upstream_io, idle).await,
+                None => tokio::io::copy_bidirectional(&mut client_io, &mut upstream_io).await,
+            }
+        };
         tokio::select! {
-            result = tokio::io::copy_bidirectional(&mut client_io, &mut upstream_io) => {
+            result = copy => {
                 match result {
                     Ok((to_upstream, to_client)) => tracing::debug!(
                         upstream = %upstream_name,
@@ -259,6 +295,135 @@ fn spawn_upgrade_bridge(
     });
 }
 
+/// `tokio::io::copy_bidirectional` with an idle deadline: fails with
+/// `TimedOut` once neither side has sent anything for `idle` (or a write has
+/// been stuck that long). As there, one side's EOF is passed on as a write
+/// shutdown and the other direction keeps flowing. WebSocket frames are
+/// copied as bytes, so subprotocols, pings and close codes pass through
+/// untouched.
+async fn copy_bidirectional_idle<A, B>(
+    a: &mut A,
+    b: &mut B,
+    idle: Duration,
+) -> std::io::Result<(u64, u64)>
+where
+    A: AsyncRead + AsyncWrite + Unpin,
+    B: AsyncRead + AsyncWrite + Unpin,
+{
+    enum Read {
+        A(std::io::Result<usize>),
+        B(std::io::Result<usize>),
+    }
+    fn timed_out(_: tokio::time::error::Elapsed) -> std::io::Error {
+        std::io::Error::new(std::io::ErrorKind::TimedOut, "upgraded connection idle")
+    }
+
+    let mut a_buf = vec![0u8; 8 * 1024];
+    let mut b_buf = vec![0u8; 8 * 1024];
+    let (mut a_open, mut b_open) = (true, true);
+    let (mut a_to_b, mut b_to_a) = (0u64, 0u64);
+    while a_open || b_open {
+        let read = tokio::time::timeout(idle, async {
+            tokio::select! {
+                read = a.read(&mut a_buf), if a_open => Read::A(read),
+                read = b.read(&mut b_buf), if b_open => Read::B(read),
+            }
+        })
+        .await
+        .map_err(timed_out)?;
+        match read {
+            Read::A(read) => match read? {
+                0 => {
+                    a_open = false;
+                    b.shutdown().await?;
+                }
+                n => {
+                    tokio::time::timeout(idle, async {
+                        b.write_all(&a_buf[..n]).await?;
+                        b.flush().await
+                    })
+                    .await
+                    .map_err(timed_out)??;
+                    a_to_b += n as u64;
+                }
+            },
+            Read::B(read) => match read? {
+                0 => {
+                    b_open = false;
+                    a.shutdown().await?;
+                }
+                n => {
+                    tokio::time::timeout(idle, async {
+                        a.write_all(&b_buf[..n]).await?;
+                        a.flush().await
+                    })
+                    .await
+                    .map_err(timed_out)??;
+                    b_to_a += n as u64;
+                }
+            },
+        }
+    }
+    Ok((a_to_b, b_to_a))
+}
+
+/// Body of the 503/504 returned when an upstream is at its upgrade limit or
+/// misses a configured deadline.
+#[derive(Serialize)]
+struct UpstreamErrorBody {
+    detail: String,
+    code: ErrorCode,
+    upstream: String,
+}
+
+fn upstream_error_response(status: StatusCode, upstream: &Upstream, what: &str) -> Response<Body> {
+    let body = UpstreamErrorBody {
+        detail: format!("upstream '{}' {what}", upstream.name),
+        code: ErrorCode::UpstreamUnavailable,
+        upstream: upstream.name.clone(),
+    };
+    (status, axum::Json(body)).into_response()
+}
+
+fn upstream_timeout_response(upstream: &Upstream, what: &str) -> Response<Body> {
+    upstream_error_response(StatusCode::GATEWAY_TIMEOUT, upstream, what)
+}
+
+/// Server-sent event streams are long-lived by design; like upgrades they
+/// are exempt from the response-head deadline.
+fn accepts_event_stream(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|media| {
+            media
+                .split(';')
+                .next()
+                .is_some_and(|kind| kind.trim().eq_ignore_ascii_case("text/event-stream"))
+        })
+}
+
+/// Whether a client error is the connector's connect deadline firing
+/// (hyper-util surfaces it as an `io::ErrorKind::TimedOut` cause).
+fn is_connect_timeout(err: &hyper_util::client::legacy::Error) -> bool {
+    if !err.is_connect() {
+        return false;
+    }
+    let mut source = std::error::Error::source(err);
+    while let Some(cause) = source {
+        if cause
+            .downcast_ref::<std::io::Error>()
+            .is_some_and(|io| io.kind() == std::io::ErrorKind::TimedOut)
+        {
+            return true;
+        }
+        source = cause.source();
+    }
+    false
+}
+
 async fn proxy_request(
     client_addr: SocketAddr,
     state: Arc<ProxyState>,
@@ -311,6 +476,30 @@ async fn proxy_request(
         Some((protocol, on_upgrade))
     });
 
+    // An upgrade takes a connection slot before the upstream sees it; the
+    // permit goes to the bridge, or is released here if the upstream
+    // declines the switch.
+    let upgrade_slot = match (&client_upgrade, &upstream.upgrade_slots) {
+        (Some(_), Some(slots)) => match slots.clone().try_acquire_owned() {
+            Ok(permit) => Some(permit),
+            Err(_) => {
+                let limit = upstream.websocket.max_connections.unwrap_or_default();
+                tracing::warn!(
+                    upstream = %upstream.name,
+                    path = %path_and_query,
+                    limit,
+                    "upgrade refused: too many open connections"
+                );
+                return upstream_error_response(
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    &upstream,
+                    &format!("has {limit} open WebSocket connections, its limit"),
+                );
+            }
+        },
+        _ => None,
+    };
+
     // Hop-by-hop hygiene (RFC 9110 §7.6.1) runs BEFORE the gateway's own
     // header injections (hop count, policy token, x-forwarded-*), so a
     // client nominating one of those names in its Connection header cannot
@@ -366,8 +555,39 @@ async fn proxy_request(
         *req.version_mut() = Version::HTTP_11;
     }
 
-    let mut response = match state.client.request(req).await {
+    // The deadline bounds the wait for the response head only: a body that
+    // has started streaming is never cut off, and long-lived upgrades/SSE
+    // are exempt altogether.
+    let request_timeout = if client_upgrade.is_some() || accepts_event_stream(req.headers()) {
+        None
+    } else {
+        upstream.timeouts.request_timeout(req.uri().path())
+    };
+    let pending = upstream.client.request(req);
+    let result = match request_timeout {
+        Some(deadline) => match tokio::time::timeout(deadline, pending).await {
+            Ok(result) => result,
+            Err(_) => {
+                tracing::warn!(
+                    upstream = %upstream.name,
+                    path = %path_and_query,
+                    timeout_secs = deadline.as_secs(),
+                    "upstream request timed out"
+                );
+                return upstream_timeout_response(
+                    &upstream,
+                    &format!("did not respond within {}s", deadline.as_secs()),
+                );
+            }
+        },
+        None => pending.await,
+    };
+    let mut response = match result {
         Ok(response) => response,
+        Err(err) if is_connect_timeout(&err) => {
+            tracing::warn!(error = %err, upstream = %upstream.name, "upstream connect timed out");
+            return upstream_timeout_response(&upstream, "connect timed out");
+        }
         Err(err) => {
             tracing::error!(error = %err, upstream = %upstream.name, "upstream request failed");
             return StatusCode::BAD_GATEWAY.into_response();
@@ -420,7 +640,8 @@ async fn proxy_request(
             client_upgrade,
             upstream_upgrade,
             state.shutdown_rx.clone(),
-            upstream.name.clone(),
+            &upstream,
+            upgrade_slot,
             path_and_query,
         );
         return Response::from_parts(parts, Body::empty());
@@ -540,7 +761,8 @@ base_url = "http://127.0.0.1:6342"
     async fn self_referential_upstream_is_cut_off() {
         let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
         let addr = listener.local_addr().unwrap();
-        let upstream = Upstream::parse("api", &format!("http://{addr}")).unwrap();
+        let upstream =
+            Upstream::parse("api", &format!("http://{addr}"), Default::default()).unwrap();
         let state = test_state(upstream);
         let app = axum::Router::new()
             .route("/api/{*path}", any(proxy_api))
@@ -583,7 +805,8 @@ base_url = "http://127.0.0.1:6342"
             axum::serve(listener, echo).await.unwrap();
         });
 
-        let upstream = Upstream::parse("ui", &format!("http://{addr}")).unwrap();
+        let upstream =
+            Upstream::parse("ui", &format!("http://{addr}"), Default::default()).unwrap();
         let state = test_state(upstream);
         let key = Arc::clone(&state.token_key);
         let client_addr: SocketAddr = "127.0.0.1:5555".parse().unwrap();
@@ -734,9 +957,14 @@ base_url = "http://127.0.0.1:6342"
     /// Serve the test router (fallback → proxy_ui) on an ephemeral port,
     /// exactly like the production listeners (connect-info make service).
     async fn spawn_gateway(upstream_addr: SocketAddr) -> SocketAddr {
+        let upstream =
+            Upstream::parse("ui", &format!("http://{upstream_addr}"), Default::default()).unwrap();
+        spawn_gateway_for(upstream).await
+    }
+
+    async fn spawn_gateway_for(upstream: Upstream) -> SocketAddr {
         let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
         let addr = listener.local_addr().unwrap();
-        let upstream = Upstream::parse("ui", &format!("http://{upstream_addr}")).unwrap();
         let app = axum::Router::new()
             .fallback(any(proxy_ui))
             .with_state(test_state(upstream));
@@ -859,6 +1087,212 @@ base_url = "http://127.0.0.1:6342"
             .unwrap();
     }
 
+    /// A WebSocket frame with a payload under 126 bytes; clients must mask.
+    fn ws_frame(opcode: u8, payload: &[u8], masked: bool) -> Vec<u8> {
+        assert!(payload.len() < 126);
+        let mut frame = vec![0x80 | opcode];
+        if masked {
+            let mask = [0x12, 0x34, 0x56, 0x78];
+            frame.push(0x80 | payload.len() as u8);
+            frame.extend_from_slice(&mask);
+            frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+        } else {
+            frame.push(payload.len() as u8);
+            frame.extend_from_slice(payload);
+        }
+        frame
+    }
+
+    /// Read one short WebSocket frame, unmasking it: (opcode, payload).
+    async fn read_ws_frame(stream: &mut TcpStream) -> (u8, Vec<u8>) {
+        let mut head = [0u8; 2];
+        stream.read_exact(&mut head).await.unwrap();
+        let len = (head[1] & 0x7f) as usize;
+        assert!(len < 126);
+        let mut mask = [0u8; 4];
+        if head[1] & 0x80 != 0 {
+            stream.read_exact(&mut mask).await.unwrap();
+        }
+        let mut payload = vec![0u8; len];
+        stream.read_exact(&mut payload).await.unwrap();
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+        (head[0] & 0x0f, payload)
+    }
+
+    /// WebSocket echo upstream: answers each handshake with 101 and the
+    /// first offered subprotocol, echoes text frames and answers a close
+    /// frame with the same close code, then closes the connection.
+    async fn spawn_ws_echo_upstream() -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                tokio::spawn(async move {
+                    let head = read_http_head(&mut stream).await;
+                    let protocol = head
+                        .lines()
+                        .find_map(|line| {
+                            let (name, value) = line.split_once(':')?;
+                            name.eq_ignore_ascii_case("sec-websocket-protocol")
+                                .then(|| value.split(',').next().unwrap().trim().to_string())
+                        })
+                        .unwrap_or_default();
+                    let response = format!(
+                        "HTTP/1.1 101 Switching Protocols\r\n\
+                         Upgrade: websocket\r\n\
+                         Connection: Upgrade\r\n\
+                         Sec-WebSocket-Accept: dGVzdC1hY2NlcHQ=\r\n\
+                         Sec-WebSocket-Protocol: {protocol}\r\n\r\n"
+                    );
+                    stream.write_all(response.as_bytes()).await.unwrap();
+                    loop {
+                        let (opcode, payload) = read_ws_frame(&mut stream).await;
+                        stream
+                            .write_all(&ws_frame(opcode, &payload, false))
+                            .await
+                            .unwrap();
+                        if opcode == 0x8 {
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+        addr
+    }
+
+    /// Perform a WebSocket handshake through the gateway; returns the
+    /// connection and the response head.
+    async fn ws_connect(gateway_addr: SocketAddr) -> (TcpStream, String) {
+        let mut client = TcpStream::connect(gateway_addr).await.unwrap();
+        client
+            .write_all(
+                b"GET /_next/webpack-hmr HTTP/1.1\r\n\
+                  Host: localhost\r\n\
+                  Connection: Upgrade\r\n\
+                  Upgrade: websocket\r\n\
+                  Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+                  Sec-WebSocket-Protocol: hmr.v1, hmr.v0\r\n\
+                  Sec-WebSocket-Version: 13\r\n\r\n",
+            )
+            .await
+            .unwrap();
+        let head = read_http_head(&mut client).await;
+        (client, head)
+    }
+
+    /// Frames round-trip through the gateway to a WebSocket echo server:
+    /// the chosen subprotocol reaches the client, text frames come back
+    /// intact, and a close frame's code is echoed unchanged.
+    #[tokio::test]
+    async fn websocket_frames_round_trip_through_the_gateway() {
+        let gateway_addr = spawn_gateway(spawn_ws_echo_upstream().await).await;
+        let (mut client, head) = ws_connect(gateway_addr).await;
+        assert!(head.starts_with("HTTP/1.1 101"), "{head}");
+        assert!(
+            head.to_ascii_lowercase()
+                .contains("sec-websocket-protocol: hmr.v1"),
+            "{head}"
+        );
+
+        client
+            .write_all(&ws_frame(0x1, b"{\"action\":\"ping\"}", true))
+            .await
+            .unwrap();
+        assert_eq!(
+            read_ws_frame(&mut client).await,
+            (0x1, b"{\"action\":\"ping\"}".to_vec())
+        );
+
+        let mut close = 4001u16.to_be_bytes().to_vec();
+        close.extend_from_slice(b"bye");
+        client
+            .write_all(&ws_frame(0x8, &close, true))
+            .await
+            .unwrap();
+        assert_eq!(read_ws_frame(&mut client).await, (0x8, close));
+        let mut rest = Vec::new();
+        tokio::time::timeout(Duration::from_secs(5), client.read_to_end(&mut rest))
+            .await
+            .expect("the gateway closes after the upstream does")
+            .unwrap();
+        assert!(rest.is_empty());
+    }
+
+    /// With `websocket.max_connections` reached, another upgrade gets a 503
+    /// without reaching the upstream; closing a connection frees its slot.
+    #[tokio::test]
+    async fn upgrades_beyond_max_connections_get_503() {
+        let upstream_addr = spawn_ws_echo_upstream().await;
+        let upstream =
+            Upstream::parse("ui", &format!("http://{upstream_addr}"), Default::default())
+                .unwrap()
+                .with_websocket(UpstreamWebSocket {
+                    max_connections: Some(1),
+                    idle_secs: None,
+                });
+        let gateway_addr = spawn_gateway_for(upstream).await;
+
+        let (mut first, head) = ws_connect(gateway_addr).await;
+        assert!(head.starts_with("HTTP/1.1 101"), "{head}");
+        let (_, head) = ws_connect(gateway_addr).await;
+        assert!(head.starts_with("HTTP/1.1 503"), "{head}");
+
+        first
+            .write_all(&ws_frame(0x8, &1000u16.to_be_bytes(), true))
+            .await
+            .unwrap();
+        read_ws_frame(&mut first).await;
+        drop(first);
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+        loop {
+            let (_, head) = ws_connect(gateway_addr).await;
+            if head.starts_with("HTTP/1.1 101") {
+                break;
+            }
+            assert!(
+                tokio::time::Instant::now() < deadline,
+                "slot never freed: {head}"
+            );
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+
+    /// An upgraded connection with no traffic for `websocket.idle_secs` is
+    /// closed by the gateway; one with traffic stays open.
+    #[tokio::test]
+    async fn idle_upgrades_are_closed() {
+        let upstream_addr = spawn_ws_echo_upstream().await;
+        let upstream =
+            Upstream::parse("ui", &format!("http://{upstream_addr}"), Default::default())
+                .unwrap()
+                .with_websocket(UpstreamWebSocket {
+                    max_connections: None,
+                    idle_secs: Some(1),
+                });
+        let gateway_addr = spawn_gateway_for(upstream).await;
+        let (mut client, head) = ws_connect(gateway_addr).await;
+        assert!(head.starts_with("HTTP/1.1 101"), "{head}");
+
+        for _ in 0..3 {
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            client.write_all(&ws_frame(0x9, b"", true)).await.unwrap();
+            assert_eq!(read_ws_frame(&mut client).await.0, 0x9);
+        }
+
+        let mut rest = Vec::new();
+        let started = std::time::Instant::now();
+        tokio::time::timeout(Duration::from_secs(5), client.read_to_end(&mut rest))
+            .await
+            .expect("idle connection must be closed")
+            .unwrap();
+        assert!(rest.is_empty());
+        assert!(started.elapsed() >= Duration::from_millis(900));
+    }
+
     /// Plain (non-upgrade) responses get hop-by-hop headers stripped on the
     /// way back to the client — including Connection-nominated custom
     /// headers — while end-to-end headers and the body pass through.
@@ -927,6 +1361,127 @@ base_url = "http://127.0.0.1:6342"
         assert_eq!(response.status().as_u16(), 502);
     }
 
+    /// Upstream that answers every request after `delay` with a small
+    /// plain-text body.
+    async fn spawn_slow_upstream(delay: std::time::Duration) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let slow = axum::Router::new().fallback(any(move || async move {
+            tokio::time::sleep(delay).await;
+            "slow"
+        }));
+        tokio::spawn(async move {
+            axum::serve(listener, slow).await.unwrap();
+        });
+        addr
+    }
+
+    fn timeout_state(upstream_addr: SocketAddr, timeouts: UpstreamTimeouts) -> Arc<ProxyState> {
+        let upstream =
+            Upstream::parse("inference", &format!("http://{upstream_addr}"), timeouts).unwrap();
+        test_state(upstream)
+    }
+
+    /// A slow upstream that misses `request_secs` is answered with 504 and
+    /// a JSON body naming the upstream, instead of holding the client until
+    /// the upstream finally answers.
+    #[tokio::test]
+    async fn request_timeout_maps_to_504_naming_the_upstream() {
+        let upstream_addr = spawn_slow_upstream(std::time::Duration::from_secs(5)).await;
+        let state = timeout_state(
+            upstream_addr,
+            UpstreamTimeouts {
+                request_secs: Some(1),
+                ..Default::default()
+            },
+        );
+        let client_addr: SocketAddr = "127.0.0.1:5555".parse().unwrap();
+        let req = Request::builder()
+            .uri("http://gateway/api/inference/metadata")
+            .body(Body::empty())
+            .unwrap();
+        let started = std::time::Instant::now();
+        let response = proxy_request(client_addr, state, UpstreamKind::Inference, req).await;
+        assert!(started.elapsed() < std::time::Duration::from_secs(4));
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+        let body = axum::body::to_bytes(response.into_body(), 64 * 1024)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["upstream"], "inference");
+        assert_eq!(json["code"], "upstream_unavailable");
+        assert!(
+            json["detail"].as_str().unwrap().contains("'inference'"),
+            "body: {json}"
+        );
+    }
+
+    /// A `paths` override (longest matching prefix) lifts the deadline for
+    /// matching paths only: the slow predict succeeds while any other path
+    /// still times out under `request_secs`.
+    #[tokio::test]
+    async fn path_override_extends_the_deadline_for_matching_paths() {
+        let upstream_addr = spawn_slow_upstream(std::time::Duration::from_millis(1500)).await;
+        let state = timeout_state(
+            upstream_addr,
+            UpstreamTimeouts {
+                request_secs: Some(1),
+                paths: [
+                    ("/api/inference".to_string(), 1),
+                    ("/api/inference/predict".to_string(), 10),
+                ]
+                .into_iter()
+                .collect(),
+                ..Default::default()
+            },
+        );
+        let client_addr: SocketAddr = "127.0.0.1:5555".parse().unwrap();
+
+        let req = Request::builder()
+            .method("POST")
+            .uri("http://gateway/api/inference/predict/group/id")
+            .body(Body::empty())
+            .unwrap();
+        let response = proxy_request(
+            client_addr,
+            Arc::clone(&state),
+            UpstreamKind::Inference,
+            req,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let req = Request::builder()
+            .uri("http://gateway/api/inference/metadata")
+            .body(Body::empty())
+            .unwrap();
+        let response = proxy_request(client_addr, state, UpstreamKind::Inference, req).await;
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+    }
+
+    /// Requests accepting `text/event-stream` are exempt from the
+    /// response-head deadline, like upgrades: the slow upstream still
+    /// answers through the proxy.
+    #[tokio::test]
+    async fn event_stream_requests_are_exempt_from_request_timeout() {
+        let upstream_addr = spawn_slow_upstream(std::time::Duration::from_millis(1500)).await;
+        let state = timeout_state(
+            upstream_addr,
+            UpstreamTimeouts {
+                request_secs: Some(1),
+                ..Default::default()
+            },
+        );
+        let client_addr: SocketAddr = "127.0.0.1:5555".parse().unwrap();
+        let req = Request::builder()
+            .uri("http://gateway/events")
+            .header(header::ACCEPT, "text/event-stream")
+            .body(Body::empty())
+            .unwrap();
+        let response = proxy_request(client_addr, state, UpstreamKind::Inference, req).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
     /// Settings for the policy-layer tests: an open allow-all policy for
     /// localhost, and a "locked" policy for host denied.local whose ruleset
     /// admits nothing but GET /api/db.
@@ -991,7 +1546,8 @@ allow = "*"
         let addr = listener.local_addr().unwrap();
         let settings = policy_settings();
         let token_key = Arc::new(TokenKey::random());
-        let upstream = Upstream::parse("ui", &format!("http://{upstream_addr}")).unwrap();
+        let upstream =
+            Upstream::parse("ui", &format!("http://{upstream_addr}"), Default::default()).unwrap();
         let inference_client =
             InferenceApiClient::new_with_metadata_cache(format!("http://{upstream_addr}"), false)
                 .unwrap();
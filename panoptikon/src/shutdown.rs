@@ -153,6 +153,8 @@ pub(crate) async fn run_cleanup(
         if flushed > 0 {
             tracing::info!(writers = flushed, "index DB writers drained");
         }
+        // Writers are drained: another instance may take the DBs over.
+        crate::db::instance_lock::release_all();
         if let Some(manager) = inferio {
             manager.shutdown().await;
             tracing::info!("local inference workers stopped");
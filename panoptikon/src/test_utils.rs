@@ -54,3 +54,45 @@ pub(crate) fn test_data_dir() -> TestDataGuard {
     );
     TestDataGuard { _lock: lock, root }
 }
+
+/// A `ProxyState` whose upstreams and inference server all point at a
+/// closed port, for handler tests that never reach them (or that check how
+/// an unreachable inference server is reported).
+pub(crate) fn offline_proxy_state() -> crate::proxy::ProxyState {
+    use std::sync::Arc;
+
+    let settings = {
+        let _env = env_lock();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("gw.toml");
+        std::fs::write(
+            &path,
+            r#"
+[upstreams.ui]
+base_url = "http://127.0.0.1:6339"
+
+[upstreams.api]
+base_url = "http://127.0.0.1:6342"
+"#,
+        )
+        .unwrap();
+        crate::config::Settings::load(Some(path)).unwrap()
+    };
+    let upstream =
+        crate::proxy::Upstream::parse("api", "http://127.0.0.1:1", Default::default()).unwrap();
+    let client = crate::inferio_client::InferenceApiClient::new_with_metadata_cache(
+        "http://127.0.0.1:1".to_string(),
+        false,
+    )
+    .unwrap();
+    crate::proxy::ProxyState::new(
+        upstream.clone(),
+        upstream.clone(),
+        upstream,
+        client,
+        0,
+        Arc::new(settings),
+        Arc::new(crate::policy_token::TokenKey::random()),
+        tokio::sync::watch::channel(false).1,
+    )
+}
@@ -0,0 +1,381 @@
+//! Video frame extraction shared by file scans (thumbnails and stored
+//! frames) and extraction jobs (frames for image models when none are
+//! stored).
+//!
+//! Extraction walks a ladder of ffmpeg runs, each more permissive than the
+//! last, and stops at the first that yields a frame:
+//!
+//! 1. `fps`: frames spread evenly over the duration by the `fps` filter.
+//!    Decodes the whole stream, so it gives the best frames, but a corrupt
+//!    packet or a half-supported codec (old MPEG-1/VOB rips) fails it.
+//! 2. `seek`: one run per frame, seeking to the keyframe before each
+//!    timestamp (`-ss` before `-i`) and decoding only keyframes with
+//!    `-err_detect ignore_err`. Timestamps that still fail are skipped.
+//! 3. `first_frame`: a single frame from the start, error-resilient.
+//!
+//! A timeout or an ffmpeg that cannot be started ends the ladder at once:
+//! retrying a file that hangs ffmpeg only multiplies the time lost on it,
+//! and a missing ffmpeg says nothing about the file. Frames ffmpeg wrote
+//! that do not decode are skipped. When every step fails the file is
+//! [`FrameExtractionError::Undecodable`], which scans and extraction jobs
+//! count separately from other errors and mark on the item
+//! (`items.undecodable`, listed by GET /api/items/undecodable).
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use image::DynamicImage;
+
+use crate::media_tools::{self, MediaTool, MediaToolError};
+
+#[derive(Debug)]
+pub(crate) enum FrameExtractionError {
+    /// The temporary frame folder could not be created or read.
+    Io(String),
+    /// ffmpeg timed out or could not be started; later steps were not tried.
+    Tool(MediaToolError),
+    /// Every step failed or produced no frame; the reason the last one gave.
+    Undecodable(String),
+}
+
+impl std::fmt::Display for FrameExtractionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(reason) => write!(f, "failed to read extracted frames: {reason}"),
+            Self::Tool(err) => err.fmt(f),
+            Self::Undecodable(reason) => write!(f, "no frame could be decoded: {reason}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FrameStep {
+    Fps,
+    Seek,
+    FirstFrame,
+}
+
+impl FrameStep {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Fps => "fps",
+            Self::Seek => "seek",
+            Self::FirstFrame => "first_frame",
+        }
+    }
+}
+
+/// Up to `num_frames` frames of the video at `path`, spread over its
+/// `duration` (seconds). Without a positive duration only the first frame
+/// is tried.
+pub(crate) fn extract_video_frames(
+    path: &Path,
+    num_frames: usize,
+    duration: f64,
+) -> Result<Vec<DynamicImage>, FrameExtractionError> {
+    let temp_dir = temp_dir_path();
+    fs::create_dir_all(&temp_dir).map_err(|err| FrameExtractionError::Io(err.to_string()))?;
+    let result = walk_ladder(ladder(duration), |step| {
+        // Each step writes to a folder of its own, so frames a failed step
+        // left behind are never mixed into a later one's.
+        let step_dir = temp_dir.join(step.name());
+        fs::create_dir_all(&step_dir).map_err(|err| FrameExtractionError::Io(err.to_string()))?;
+        run_step(step, path, num_frames, duration, &step_dir)
+    });
+    if let Err(err) = fs::remove_dir_all(&temp_dir) {
+        tracing::debug!(error = %err, path = %temp_dir.display(), "failed to remove temp frame dir");
+    }
+    match &result {
+        Ok((step, frames)) if *step != FrameStep::Fps => tracing::warn!(
+            path = %path.display(),
+            step = step.name(),
+            frames = frames.len(),
+            "frame extraction needed a fallback"
+        ),
+        Err(FrameExtractionError::Undecodable(reason)) => tracing::warn!(
+            path = %path.display(),
+            reason,
+            "no frame could be decoded, even by the fallbacks"
+        ),
+        _ => {}
+    }
+    result.map(|(_, frames)| frames)
+}
+
+fn ladder(duration: f64) -> &'static [FrameStep] {
+    if duration > 0.0 {
+        &[FrameStep::Fps, FrameStep::Seek, FrameStep::FirstFrame]
+    } else {
+        &[FrameStep::FirstFrame]
+    }
+}
+
+/// Runs `steps` in order until one yields a frame. Undecodable steps fall
+/// through; any other error ends the ladder.
+fn walk_ladder(
+    steps: &[FrameStep],
+    mut run: impl FnMut(FrameStep) -> Result<Vec<DynamicImage>, FrameExtractionError>,
+) -> Result<(FrameStep, Vec<DynamicImage>), FrameExtractionError> {
+    let mut last_reason = String::from("no extraction step was run");
+    for &step in steps {
+        match run(step) {
+            Ok(frames) if !frames.is_empty() => return Ok((step, frames)),
+            Ok(_) => last_reason = format!("{}: ffmpeg wrote no frames", step.name()),
+            Err(FrameExtractionError::Undecodable(reason)) => {
+                tracing::debug!(step = step.name(), reason, "frame extraction step failed");
+                last_reason = format!("{}: {reason}", step.name());
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    Err(FrameExtractionError::Undecodable(last_reason))
+}
+
+fn run_step(
+    step: FrameStep,
+    path: &Path,
+    num_frames: usize,
+    duration: f64,
+    dir: &Path,
+) -> Result<Vec<DynamicImage>, FrameExtractionError> {
+    match step {
+        FrameStep::Fps => {
+            let interval = duration / num_frames as f64;
+            run_ffmpeg(MediaTool::Ffmpeg.command().args(fps_args(
+                path,
+                interval,
+                &dir.join("frame_%04d.png"),
+            )))?;
+        }
+        FrameStep::Seek => {
+            let interval = duration / num_frames as f64;
+            for index in 0..num_frames {
+                let output = dir.join(format!("frame_{index:04}.png"));
+                let args = seek_args(path, interval * index as f64, &output);
+                match run_ffmpeg(MediaTool::Ffmpeg.command().args(args)) {
+                    Ok(()) => {}
+                    Err(FrameExtractionError::Undecodable(reason)) => {
+                        tracing::debug!(index, reason, "seek frame failed, skipping it");
+                    }
+                    Err(err) => return Err(err),
+                }
+            }
+        }
+        FrameStep::FirstFrame => {
+            run_ffmpeg(
+                MediaTool::Ffmpeg
+                    .command()
+                    .args(first_frame_args(path, &dir.join("frame_0000.png"))),
+            )?;
+        }
+    }
+    read_frames(dir, num_frames)
+}
+
+fn run_ffmpeg(command: &mut std::process::Command) -> Result<(), FrameExtractionError> {
+    media_tools::run(MediaTool::Ffmpeg, command)
+        .map(|_| ())
+        .map_err(|err| match err {
+            MediaToolError::Failed { .. } | MediaToolError::OutputTooLarge { .. } => {
+                FrameExtractionError::Undecodable(err.to_string())
+            }
+            err => FrameExtractionError::Tool(err),
+        })
+}
+
+fn fps_args(path: &Path, interval: f64, output_pattern: &Path) -> Vec<std::ffi::OsString> {
+    let mut args = vec!["-i".into(), path.into()];
+    args.extend(["-vf".into(), format!("fps=1/{interval}").into()]);
+    args.extend(["-vsync".into(), "vfr".into(), output_pattern.into()]);
+    args
+}
+
+/// Input options (`-err_detect`, `-skip_frame`, `-ss`) go before `-i`, so
+/// ffmpeg seeks in the demuxer and never decodes the frames in between.
+fn seek_args(path: &Path, at_secs: f64, output: &Path) -> Vec<std::ffi::OsString> {
+    let mut args = resilient_input_args();
+    args.extend(["-skip_frame".into(), "nokey".into()]);
+    args.extend(["-ss".into(), format!("{at_secs:.3}").into()]);
+    args.extend(["-i".into(), path.into()]);
+    args.extend(single_frame_output_args(output));
+    args
+}
+
+fn first_frame_args(path: &Path, output: &Path) -> Vec<std::ffi::OsString> {
+    let mut args = resilient_input_args();
+    args.extend(["-i".into(), path.into()]);
+    args.extend(single_frame_output_args(output));
+    args
+}
+
+fn resilient_input_args() -> Vec<std::ffi::OsString> {
+    vec![
+        "-err_detect".into(),
+        "ignore_err".into(),
+        "-fflags".into(),
+        "+discardcorrupt+genpts".into(),
+    ]
+}
+
+fn single_frame_output_args(output: &Path) -> Vec<std::ffi::OsString> {
+    vec![
+        "-an".into(),
+        "-frames:v".into(),
+        "1".into(),
+        "-y".into(),
+        output.into(),
+    ]
+}
+
+fn read_frames(dir: &Path, num_frames: usize) -> Result<Vec<DynamicImage>, FrameExtractionError> {
+    let mut paths = fs::read_dir(dir)
+        .map_err(|err| FrameExtractionError::Io(err.to_string()))?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().and_then(|ext| ext.to_str()) == Some("png"))
+        .collect::<Vec<_>>();
+    paths.sort();
+    let mut frames = Vec::new();
+    for frame_path in paths.into_iter().take(num_frames) {
+        // A frame ffmpeg wrote but that does not decode (truncated output of
+        // a damaged stream) is left out; the others still make visuals.
+        match crate::jobs::files::open_image(&frame_path) {
+            Ok(image) => frames.push(image),
+            Err(err) => tracing::warn!(
+                error = %err,
+                frame = %frame_path.display(),
+                "skipping unreadable extracted frame"
+            ),
+        }
+    }
+    Ok(frames)
+}
+
+fn temp_dir_path() -> PathBuf {
+    // PID plus a process-local counter rules out collisions between
+    // concurrent extractions and between gateway instances; a collision
+    // means one call's cleanup deletes the other's frames mid-extraction.
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos())
+        .unwrap_or(0);
+    let unique = COUNTER.fetch_add(1, Ordering::Relaxed);
+    crate::config::runtime()
+        .temp_dir
+        .join(format!("frames-{}-{nanos:x}-{unique}", std::process::id()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame() -> DynamicImage {
+        DynamicImage::new_rgb8(2, 2)
+    }
+
+    fn failed(reason: &str) -> FrameExtractionError {
+        FrameExtractionError::Undecodable(reason.to_string())
+    }
+
+    // Ensures a failing or empty step falls through to the next one and
+    // the first step with frames wins.
+    #[test]
+    fn ladder_falls_through_to_the_first_step_with_frames() {
+        let mut tried = Vec::new();
+        let (step, frames) = walk_ladder(ladder(10.0), |step| {
+            tried.push(step);
+            match step {
+                FrameStep::Fps => Err(failed("Invalid data found when processing input")),
+                FrameStep::Seek => Ok(vec![frame(), frame()]),
+                FrameStep::FirstFrame => Ok(vec![frame()]),
+            }
+        })
+        .unwrap();
+        assert_eq!(step, FrameStep::Seek);
+        assert_eq!(frames.len(), 2);
+        assert_eq!(tried, vec![FrameStep::Fps, FrameStep::Seek]);
+
+        let (step, _) = walk_ladder(ladder(10.0), |step| match step {
+            FrameStep::FirstFrame => Ok(vec![frame()]),
+            _ => Ok(Vec::new()),
+        })
+        .unwrap();
+        assert_eq!(step, FrameStep::FirstFrame);
+    }
+
+    // Ensures a file no step can decode is undecodable with the last
+    // step's reason, and that without a duration only the first frame is
+    // tried.
+    #[test]
+    fn ladder_reports_undecodable_when_every_step_fails() {
+        let mut tried = Vec::new();
+        let err = walk_ladder(ladder(0.0), |step| {
+            tried.push(step);
+            Err(failed("codec not supported"))
+        })
+        .unwrap_err();
+        assert_eq!(tried, vec![FrameStep::FirstFrame]);
+        match err {
+            FrameExtractionError::Undecodable(reason) => {
+                assert_eq!(reason, "first_frame: codec not supported");
+            }
+            other => panic!("unexpected error: {other}"),
+        }
+    }
+
+    // Ensures a timeout ends the ladder instead of retrying a file that
+    // hangs ffmpeg.
+    #[test]
+    fn ladder_stops_at_a_timeout() {
+        let mut tried = Vec::new();
+        let err = walk_ladder(ladder(10.0), |step| {
+            tried.push(step);
+            Err(FrameExtractionError::Tool(MediaToolError::TimedOut {
+                tool: "ffmpeg",
+                timeout: std::time::Duration::from_secs(1),
+                stderr: String::new(),
+            }))
+        })
+        .unwrap_err();
+        assert_eq!(tried, vec![FrameStep::Fps]);
+        assert!(matches!(err, FrameExtractionError::Tool(err) if err.is_timeout()));
+    }
+
+    // Ensures the fallback steps put their seek and error-resilience
+    // options before the input, where ffmpeg applies them to demuxing and
+    // decoding.
+    #[test]
+    fn fallback_options_precede_the_input() {
+        let args = seek_args(Path::new("in.vob"), 12.5, Path::new("out.png"));
+        let args: Vec<_> = args.iter().map(|arg| arg.to_string_lossy()).collect();
+        let input = args.iter().position(|arg| arg == "-i").unwrap();
+        for option in ["-err_detect", "-skip_frame", "-ss"] {
+            let position = args.iter().position(|arg| arg == option).unwrap();
+            assert!(position < input, "{option} after -i in {args:?}");
+        }
+        assert_eq!(
+            args[args.iter().position(|arg| arg == "-ss").unwrap() + 1],
+            "12.500"
+        );
+        assert_eq!(args.last().unwrap(), "out.png");
+
+        let args = first_frame_args(Path::new("in.vob"), Path::new("out.png"));
+        assert!(!args.iter().any(|arg| arg == "-ss"));
+        assert!(args.iter().any(|arg| arg == "ignore_err"));
+    }
+
+    // Ensures a frame that does not decode is skipped rather than failing
+    // the extraction.
+    #[test]
+    fn unreadable_frames_are_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        frame().save(dir.path().join("frame_0000.png")).unwrap();
+        std::fs::write(dir.path().join("frame_0001.png"), b"not a png").unwrap();
+        frame().save(dir.path().join("frame_0002.png")).unwrap();
+
+        let frames = read_frames(dir.path(), 10).unwrap();
+        assert_eq!(frames.len(), 2);
+    }
+}